@@ -80,7 +80,7 @@ impl KvStore {
             let mut put_resp = kv_store.apply_put(&mut txn, request).await?;
             let resp = match txn.commit().await {
                 Ok(resp) => resp,
-                Err(AppError::CasFailed(_, _, _)) => continue,
+                Err(AppError::WriteBatch(_)) => continue,
                 Err(err) => return Err(err),
             };
             put_resp.header = Some(ResponseHeader::with_revision(resp.version as i64));
@@ -101,7 +101,7 @@ impl KvStore {
             let mut delete_resp = kv_store.apply_delete_range(&mut txn, req).await?;
             let resp = match txn.commit().await {
                 Ok(resp) => resp,
-                Err(AppError::CasFailed(_, _, _)) => continue,
+                Err(AppError::WriteBatch(_)) => continue,
                 Err(err) => return Err(err),
             };
             delete_resp.header = Some(ResponseHeader::with_revision(resp.version as i64));
@@ -116,7 +116,7 @@ impl KvStore {
             let mut txn_resp = kv_store.apply_txn(&mut inner_txn, req).await?;
             let resp = match inner_txn.commit().await {
                 Ok(resp) => resp,
-                Err(AppError::CasFailed(_, _, _)) => continue,
+                Err(AppError::WriteBatch(_)) => continue,
                 Err(err) => return Err(err),
             };
             txn_resp.header = Some(ResponseHeader::with_revision(resp.version as i64));
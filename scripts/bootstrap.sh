@@ -32,6 +32,9 @@ BASE_PORT=21805
 export RUST_LOG=info #,sekas_server=debug,sekas_client=debug
 export ENGULA_ENABLE_PROXY_SERVICE=true
 
+# The secret shared by the servers, it is required to join the cluster.
+export SEKAS_CLUSTER_SECRET=sekas-cluster-test
+
 ###### CONFIG ######
 
 function build_cluster_env() {
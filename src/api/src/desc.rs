@@ -14,7 +14,8 @@
 
 //! A mod to hold the helper functions of XxxDesc.
 
-use crate::server::v1::{GroupDesc, RangePartition, ShardDesc};
+use crate::server::v1::group_desc_delta::ReplicaChange;
+use crate::server::v1::{GroupDesc, GroupDescDelta, RangePartition, ShardDesc};
 
 impl ShardDesc {
     pub fn whole(shard_id: u64, table_id: u64) -> Self {
@@ -45,4 +46,67 @@ impl GroupDesc {
     pub fn drop_shard(&mut self, shard_id: u64) {
         self.shards.retain(|shard| shard.id != shard_id);
     }
+
+    /// Compute the changes from the `base` descriptor to this one.
+    pub fn delta_since(&self, base: &GroupDesc) -> GroupDescDelta {
+        let added_shards = self
+            .shards
+            .iter()
+            .filter(|shard| base.shard(shard.id) != Some(*shard))
+            .cloned()
+            .collect();
+        let removed_shard_ids = base
+            .shards
+            .iter()
+            .filter(|shard| self.shard(shard.id).is_none())
+            .map(|shard| shard.id)
+            .collect();
+        let mut replica_changes = self
+            .replicas
+            .iter()
+            .filter(|replica| !base.replicas.contains(replica))
+            .map(|replica| ReplicaChange { replica: Some(replica.clone()), removed: false })
+            .collect::<Vec<_>>();
+        replica_changes.extend(
+            base.replicas
+                .iter()
+                .filter(|replica| !self.replicas.iter().any(|r| r.id == replica.id))
+                .map(|replica| ReplicaChange { replica: Some(replica.clone()), removed: true }),
+        );
+        GroupDescDelta {
+            group_id: self.id,
+            base_epoch: base.epoch,
+            epoch: self.epoch,
+            added_shards,
+            removed_shard_ids,
+            replica_changes,
+        }
+    }
+
+    /// Apply the delta to this descriptor. Returns `false` and leaves the
+    /// descriptor untouched if the delta is not based on its epoch.
+    pub fn apply_delta(&mut self, delta: &GroupDescDelta) -> bool {
+        if self.id != delta.group_id || self.epoch != delta.base_epoch {
+            return false;
+        }
+        self.shards.retain(|shard| !delta.removed_shard_ids.contains(&shard.id));
+        for shard in &delta.added_shards {
+            match self.shard_mut(shard.id) {
+                Some(target) => *target = shard.clone(),
+                None => self.shards.push(shard.clone()),
+            }
+        }
+        for change in &delta.replica_changes {
+            let Some(replica) = &change.replica else { continue };
+            if change.removed {
+                self.replicas.retain(|r| r.id != replica.id);
+            } else if let Some(target) = self.replicas.iter_mut().find(|r| r.id == replica.id) {
+                *target = replica.clone();
+            } else {
+                self.replicas.push(replica.clone());
+            }
+        }
+        self.epoch = delta.epoch;
+        true
+    }
 }
@@ -49,7 +49,8 @@ impl ErrorDetailUnion {
                     | Value::NotLeader(_)
                     | Value::NotMatch(_)
                     | Value::NotRoot(_)
-                    | Value::ServerIsBusy(_),
+                    | Value::ServerIsBusy(_)
+                    | Value::ReplicaNotReady(_),
             )
         )
     }
@@ -93,6 +94,13 @@ impl ErrorDetail {
         Self::new(error_detail_union::Value::GroupNotFound(value))
     }
 
+    #[inline]
+    pub fn txn_shard_fenced(shard_id: u64) -> Self {
+        Self::with_detail_value(error_detail_union::Value::TxnShardFenced(TxnShardFenced {
+            shard_id,
+        }))
+    }
+
     #[inline]
     pub fn status(code: i32, msg: impl Into<String>) -> Self {
         Self::with_message(error_detail_union::Value::StatusCode(code), msg.into())
@@ -134,6 +142,15 @@ impl Error {
     pub fn group_not_found(group_id: u64) -> Self {
         Self::with_detail_value(error_detail_union::Value::GroupNotFound(GroupNotFound {
             group_id,
+            hint: None,
+        }))
+    }
+
+    #[inline]
+    pub fn group_relocated(group_id: u64, hint: GroupRelocatedHint) -> Self {
+        Self::with_detail_value(error_detail_union::Value::GroupNotFound(GroupNotFound {
+            group_id,
+            hint: Some(hint),
         }))
     }
 
@@ -151,6 +168,54 @@ impl Error {
         Self::with_detail_value(error_detail_union::Value::TxnConflict(TxnConflict {}))
     }
 
+    #[inline]
+    pub fn invalid_json(reason: impl Into<String>) -> Self {
+        Self::with_detail_value(error_detail_union::Value::InvalidJson(InvalidJson {
+            reason: reason.into(),
+        }))
+    }
+
+    #[inline]
+    pub fn version_too_old(version: u64, gc_watermark: u64) -> Self {
+        Self::with_detail_value(error_detail_union::Value::VersionTooOld(VersionTooOld {
+            version,
+            gc_watermark,
+        }))
+    }
+
+    #[inline]
+    pub fn value_type_mismatch(expected: impl Into<String>, actual_len: u64) -> Self {
+        Self::with_detail_value(error_detail_union::Value::ValueTypeMismatch(ValueTypeMismatch {
+            expected: expected.into(),
+            actual_len,
+        }))
+    }
+
+    #[inline]
+    pub fn replica_not_ready(group_id: u64, replica_id: u64, reason: impl Into<String>) -> Self {
+        Self::with_detail_value(error_detail_union::Value::ReplicaNotReady(ReplicaNotReady {
+            group_id,
+            replica_id,
+            reason: reason.into(),
+        }))
+    }
+
+    #[inline]
+    pub fn message_too_large(size: u64, limit: u64) -> Self {
+        Self::with_detail_value(error_detail_union::Value::MessageTooLarge(MessageTooLarge {
+            size,
+            limit,
+        }))
+    }
+
+    #[inline]
+    pub fn quota_exceeded(limit: impl Into<String>, value: u64) -> Self {
+        Self::with_detail_value(error_detail_union::Value::QuotaExceeded(QuotaExceeded {
+            limit: limit.into(),
+            value,
+        }))
+    }
+
     #[inline]
     pub fn status(code: i32, msg: impl Into<String>) -> Self {
         Error { details: vec![ErrorDetail::status(code, msg)] }
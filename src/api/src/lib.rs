@@ -17,6 +17,7 @@ mod desc;
 mod error;
 mod move_shard;
 mod request;
+mod scan;
 mod txn;
 mod value;
 mod write;
@@ -31,6 +32,8 @@ pub mod server {
     }
 }
 
+pub use crate::write::{append_entry_key, split_append_entry_key, APPEND_SEQUENCE_LEN};
+
 const SHARD_UPDATE_DELTA: u64 = 1 << 32;
 const CONFIG_CHANGE_DELTA: u64 = 1;
 
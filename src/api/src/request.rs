@@ -27,6 +27,7 @@ impl GroupRequest {
                     transferee,
                 })),
             }),
+            ..Default::default()
         }
     }
 
@@ -40,6 +41,7 @@ impl GroupRequest {
                     shard: Some(shard_desc),
                 })),
             }),
+            ..Default::default()
         }
     }
 
@@ -61,6 +63,7 @@ impl GroupRequest {
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::ChangeReplicas(change_replicas)),
             }),
+            ..Default::default()
         }
     }
 
@@ -82,6 +85,29 @@ impl GroupRequest {
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::ChangeReplicas(change_replicas)),
             }),
+            ..Default::default()
+        }
+    }
+
+    /// build add read replica request.
+    pub fn add_read_replica(group_id: u64, epoch: u64, replica_id: u64, node_id: u64) -> Self {
+        let change_replicas = ChangeReplicasRequest {
+            change_replicas: Some(ChangeReplicas {
+                changes: vec![ChangeReplica {
+                    change_type: ChangeReplicaType::AddReadReplica.into(),
+                    replica_id,
+                    node_id,
+                }],
+            }),
+        };
+
+        GroupRequest {
+            group_id,
+            epoch,
+            request: Some(GroupRequestUnion {
+                request: Some(group_request_union::Request::ChangeReplicas(change_replicas)),
+            }),
+            ..Default::default()
         }
     }
 
@@ -103,6 +129,7 @@ impl GroupRequest {
             request: Some(GroupRequestUnion {
                 request: Some(group_request_union::Request::ChangeReplicas(change_replicas)),
             }),
+            ..Default::default()
         }
     }
 
@@ -124,6 +151,7 @@ impl GroupRequest {
                     shard_desc: Some(shard_desc.to_owned()),
                 })),
             }),
+            ..Default::default()
         }
     }
 
@@ -145,6 +173,7 @@ impl GroupRequest {
                     split_key,
                 })),
             }),
+            ..Default::default()
         }
     }
 
@@ -159,6 +188,21 @@ impl GroupRequest {
                     right_shard_id,
                 })),
             }),
+            ..Default::default()
+        }
+    }
+
+    /// build remove shard request
+    pub fn remove_shard(group_id: u64, epoch: u64, shard_id: u64) -> Self {
+        GroupRequest {
+            group_id,
+            epoch,
+            request: Some(GroupRequestUnion {
+                request: Some(group_request_union::Request::RemoveShard(RemoveShardRequest {
+                    shard_id,
+                })),
+            }),
+            ..Default::default()
         }
     }
 }
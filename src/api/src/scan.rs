@@ -0,0 +1,82 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A mod to hold the helper functions of the scan requests.
+
+use crate::server::v1::{ShardScanRequest, ShardScanResponse, ValueSet};
+
+impl ValueSet {
+    /// The bytes of the user key and the values, it is the size accounted by
+    /// `ShardScanRequest::limit_bytes`.
+    pub fn num_bytes(&self) -> usize {
+        let value_bytes = self.values.iter().filter_map(|v| v.content.as_ref()).map(Vec::len);
+        self.user_key.len() + value_bytes.sum::<usize>()
+    }
+}
+
+impl ShardScanRequest {
+    /// Advance the request to continue after the value sets of a partial
+    /// response, the limits are reduced by the received value sets.
+    ///
+    /// Returns false if the limits are reached, nothing is left to scan.
+    pub fn advance(&mut self, resp: &ShardScanResponse) -> bool {
+        let Some(last) = resp.data.last() else { return true };
+        if self.limit != 0 {
+            let num_keys = resp.data.len() as u64;
+            if self.limit <= num_keys {
+                return false;
+            }
+            self.limit -= num_keys;
+        }
+        if self.limit_bytes != 0 {
+            let num_bytes = resp.data.iter().map(ValueSet::num_bytes).sum::<usize>() as u64;
+            if self.limit_bytes <= num_bytes {
+                return false;
+            }
+            self.limit_bytes -= num_bytes;
+        }
+
+        if let Some(prefix) = self.prefix.take() {
+            // The start and end keys are ignored by a prefix scan, so it is continued as a
+            // range scan bounded by the prefix.
+            if self.reverse {
+                self.start_key = Some(prefix);
+                self.exclude_start_key = false;
+            } else {
+                self.end_key = prefix_end(prefix);
+                self.exclude_end_key = true;
+            }
+        }
+        if self.reverse {
+            self.end_key = Some(last.user_key.clone());
+            self.exclude_end_key = true;
+        } else {
+            self.start_key = Some(last.user_key.clone());
+            self.exclude_start_key = true;
+        }
+        true
+    }
+}
+
+/// The exclusive end of the keys with the prefix, `None` if the keys are not
+/// bounded.
+fn prefix_end(mut prefix: Vec<u8>) -> Option<Vec<u8>> {
+    while let Some(last) = prefix.pop() {
+        if last != 0xFF {
+            prefix.push(last + 1);
+            return Some(prefix);
+        }
+    }
+    None
+}
@@ -18,10 +18,27 @@ use crate::server::v1::TxnIntent;
 
 impl TxnIntent {
     pub fn tombstone(start_version: u64) -> Self {
-        TxnIntent { start_version, is_delete: true, value: None }
+        TxnIntent { start_version, is_delete: true, value: None, append_sequence: None, ttl_ms: 0 }
     }
 
     pub fn with_put(start_version: u64, value: Option<Vec<u8>>) -> Self {
-        TxnIntent { start_version, is_delete: false, value }
+        TxnIntent { start_version, is_delete: false, value, append_sequence: None, ttl_ms: 0 }
+    }
+
+    /// The intent of the prefix of an append, whose value is the sequence.
+    pub fn with_append(start_version: u64, sequence: u64) -> Self {
+        TxnIntent {
+            start_version,
+            is_delete: false,
+            value: Some(sequence.to_be_bytes().to_vec()),
+            append_sequence: Some(sequence),
+            ttl_ms: 0,
+        }
+    }
+
+    /// The value expires after the TTL since it is committed, in millis.
+    pub fn with_ttl(mut self, ttl_ms: u64) -> Self {
+        self.ttl_ms = ttl_ms;
+        self
     }
 }
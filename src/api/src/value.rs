@@ -21,12 +21,12 @@ use crate::server::v1::{ShardKey, Value};
 impl Value {
     /// Construct a tombstone value.
     pub fn tombstone(version: u64) -> Self {
-        Value { content: None, version }
+        Value { content: None, version, expire_at: 0 }
     }
 
     /// Construct a put value.
     pub fn with_value(content: Vec<u8>, version: u64) -> Self {
-        Value { content: Some(content), version }
+        Value { content: Some(content), version, expire_at: 0 }
     }
 }
 
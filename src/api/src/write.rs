@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::server::v1::WriteRequest;
+use crate::server::v1::{Value, WriteRequest, WriteStatus};
 
 /// A set of helper functions to simplify `WriteRequest` interface.
 impl WriteRequest {
@@ -23,3 +23,36 @@ impl WriteRequest {
         }
     }
 }
+
+impl WriteStatus {
+    /// The status of an operation whose conditions are satisfied.
+    pub fn applied() -> Self {
+        WriteStatus::default()
+    }
+
+    /// The status of an operation whose condition `cond_index` is not
+    /// satisfied, with the current value of the target.
+    pub fn cas_failed(cond_index: u64, prev_value: Option<Value>) -> Self {
+        WriteStatus { cond_index: Some(cond_index), prev_value }
+    }
+}
+
+/// The length of the sequence suffix of the entries appended to a prefix.
+pub const APPEND_SEQUENCE_LEN: usize = core::mem::size_of::<u64>();
+
+/// The key of the entry appended to the prefix with the sequence, see
+/// [`crate::server::v1::PutType::AppendSequence`].
+pub fn append_entry_key(prefix: &[u8], sequence: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(prefix.len() + APPEND_SEQUENCE_LEN);
+    key.extend_from_slice(prefix);
+    key.extend_from_slice(&sequence.to_be_bytes());
+    key
+}
+
+/// Split the key of an appended entry into the prefix and the sequence, `None`
+/// is returned if the key is shorter than the sequence suffix.
+pub fn split_append_entry_key(key: &[u8]) -> Option<(&[u8], u64)> {
+    let prefix_len = key.len().checked_sub(APPEND_SEQUENCE_LEN)?;
+    let sequence = u64::from_be_bytes(key[prefix_len..].try_into().ok()?);
+    Some((&key[..prefix_len], sequence))
+}
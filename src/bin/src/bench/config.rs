@@ -20,9 +20,19 @@ use serde::{Deserialize, Serialize};
 pub struct AppConfig {
     pub num_threads: usize,
     pub report_interval: Duration,
+    /// The num of operations to perform, it is ignored if `duration` is set.
     pub operation: usize,
+    /// The duration to issue operations after warmup.
+    pub duration: Option<Duration>,
+    /// The duration to issue operations before measuring, the operations
+    /// during warmup are not recorded.
+    pub warmup: Duration,
 
     pub addrs: Vec<String>,
+    /// Start an in-process server to test, instead of connecting `addrs`.
+    pub standalone: bool,
+    /// The file to write the results as JSON.
+    pub output: Option<String>,
 
     pub database: String,
     pub table: String,
@@ -32,6 +42,7 @@ pub struct AppConfig {
 
     pub data: DataConfig,
     pub key: KeyConfig,
+    pub workload: WorkloadConfig,
     pub worker: WorkerConfig,
 }
 
@@ -41,13 +52,18 @@ impl Default for AppConfig {
             num_threads: num_cpus::get(),
             report_interval: Duration::from_secs(10),
             operation: 100000,
+            duration: None,
+            warmup: Duration::ZERO,
             addrs: vec!["0.0.0.0:21805".into()],
+            standalone: false,
+            output: None,
             database: "db".into(),
             table: "table".into(),
             create_if_missing: true,
             seed: None,
             data: DataConfig::default(),
             key: KeyConfig::default(),
+            workload: WorkloadConfig::default(),
             worker: WorkerConfig::default(),
         }
     }
@@ -58,26 +74,62 @@ pub struct DataConfig {
     pub inserted: u64,
     pub limited: u64,
 
-    pub read: f64,
-    pub write: f64,
     pub value: std::ops::Range<usize>,
 }
 
 impl Default for DataConfig {
     fn default() -> Self {
-        DataConfig { inserted: 10000, limited: 10000, read: 0.5, write: 0.5, value: 10..11 }
+        DataConfig { inserted: 10000, limited: 10000, value: 10..11 }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyDistribution {
+    Uniform,
+    /// The smaller indexes are hotter, skewed by `KeyConfig::zipf_theta`.
+    Zipfian,
+    /// Each worker issues the keys of its own part of the range in order.
+    Sequential,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeyConfig {
     pub prefix: String,
     pub leading: usize,
+    pub distribution: KeyDistribution,
+    /// The skew of the zipfian distribution, in (0, 1).
+    pub zipf_theta: f64,
 }
 
 impl Default for KeyConfig {
     fn default() -> Self {
-        KeyConfig { prefix: "user_".to_owned(), leading: 10 }
+        KeyConfig {
+            prefix: "user_".to_owned(),
+            leading: 10,
+            distribution: KeyDistribution::Uniform,
+            zipf_theta: 0.99,
+        }
+    }
+}
+
+/// The ratios of the operations to perform, they are normalized by the sum.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkloadConfig {
+    /// Get a key.
+    pub get: f64,
+    /// Put a key.
+    pub put: f64,
+    /// Read a key and write it back in a txn.
+    pub txn: f64,
+    /// Scan at most `scan_limit` keys from a key.
+    pub scan: f64,
+    pub scan_limit: usize,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        WorkloadConfig { get: 0.5, put: 0.5, txn: 0.0, scan: 0.0, scan_limit: 10 }
     }
 }
 
@@ -0,0 +1,153 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Range;
+
+use rand::prelude::*;
+
+use super::config::KeyDistribution;
+
+/// Generate the key indexes within the range by the distribution.
+pub enum KeyGenerator {
+    Uniform(Range<u64>),
+    Zipfian { start: u64, zipf: Zipfian },
+    Sequential { range: Range<u64>, next: u64 },
+}
+
+/// The zipfian distribution of the ranks in `[0, n)`, the rank 0 is the most
+/// frequent. See "Quickly Generating Billion-Record Synthetic Databases", Gray
+/// et al, SIGMOD 1994.
+pub struct Zipfian {
+    n: u64,
+    theta: f64,
+    alpha: f64,
+    zetan: f64,
+    eta: f64,
+}
+
+impl KeyGenerator {
+    pub fn new(distribution: KeyDistribution, range: Range<u64>, theta: f64) -> KeyGenerator {
+        assert!(!range.is_empty(), "the range of keys is empty");
+        match distribution {
+            KeyDistribution::Uniform => KeyGenerator::Uniform(range),
+            KeyDistribution::Zipfian => KeyGenerator::Zipfian {
+                start: range.start,
+                zipf: Zipfian::new(range.end - range.start, theta),
+            },
+            KeyDistribution::Sequential => KeyGenerator::Sequential { next: range.start, range },
+        }
+    }
+
+    pub fn next_index<R: Rng>(&mut self, rng: &mut R) -> u64 {
+        match self {
+            KeyGenerator::Uniform(range) => rng.gen_range(range.clone()),
+            KeyGenerator::Zipfian { start, zipf } => *start + zipf.next_rank(rng),
+            KeyGenerator::Sequential { range, next } => {
+                let index = *next;
+                *next = if index + 1 >= range.end { range.start } else { index + 1 };
+                index
+            }
+        }
+    }
+}
+
+impl Zipfian {
+    pub fn new(n: u64, theta: f64) -> Zipfian {
+        assert!(n > 0);
+        assert!(theta > 0.0 && theta < 1.0, "the zipfian theta {theta} is not in (0, 1)");
+        let zetan = zeta(n, theta);
+        let zeta2 = zeta(2.min(n), theta);
+        let alpha = 1.0 / (1.0 - theta);
+        let eta = (1.0 - (2.0 / n as f64).powf(1.0 - theta)) / (1.0 - zeta2 / zetan);
+        Zipfian { n, theta, alpha, zetan, eta }
+    }
+
+    pub fn next_rank<R: Rng>(&self, rng: &mut R) -> u64 {
+        let u: f64 = rng.gen();
+        let uz = u * self.zetan;
+        if uz < 1.0 {
+            return 0;
+        }
+        if uz < 1.0 + 0.5f64.powf(self.theta) {
+            return 1.min(self.n - 1);
+        }
+        let rank = (self.n as f64 * (self.eta * u - self.eta + 1.0).powf(self.alpha)) as u64;
+        rank.min(self.n - 1)
+    }
+}
+
+fn zeta(n: u64, theta: f64) -> f64 {
+    (1..=n).map(|i| 1.0 / (i as f64).powf(theta)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+
+    use super::*;
+
+    fn histogram(gen: &mut KeyGenerator, range: Range<u64>, samples: usize) -> Vec<usize> {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut counts = vec![0; (range.end - range.start) as usize];
+        for _ in 0..samples {
+            let index = gen.next_index(&mut rng);
+            assert!(range.contains(&index), "index {index} out of {range:?}");
+            counts[(index - range.start) as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn uniform_keys_spread_over_range() {
+        let mut gen = KeyGenerator::new(KeyDistribution::Uniform, 100..200, 0.99);
+        let counts = histogram(&mut gen, 100..200, 100_000);
+        // Each index is expected 1000 times.
+        assert!(counts.iter().all(|c| (800..1200).contains(c)), "{counts:?}");
+    }
+
+    #[test]
+    fn zipfian_keys_are_skewed() {
+        let mut gen = KeyGenerator::new(KeyDistribution::Zipfian, 100..1100, 0.99);
+        let counts = histogram(&mut gen, 100..1100, 100_000);
+        // The rank 0 takes about 1/zeta(1000) of the samples, which is ~13%.
+        assert!(counts[0] > 10_000 && counts[0] < 16_000, "{}", counts[0]);
+        assert!(counts[0] > counts[1] && counts[1] > counts[10] && counts[10] > counts[500]);
+        let top_10 = counts[..10].iter().sum::<usize>();
+        assert!(top_10 > 30_000, "{top_10}");
+
+        // The same seed generates the same keys.
+        let mut rng = SmallRng::seed_from_u64(7);
+        let first = (0..16).map(|_| gen.next_index(&mut rng)).collect::<Vec<_>>();
+        let mut rng = SmallRng::seed_from_u64(7);
+        let second = (0..16).map(|_| gen.next_index(&mut rng)).collect::<Vec<_>>();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn zipfian_small_range() {
+        let mut gen = KeyGenerator::new(KeyDistribution::Zipfian, 5..6, 0.5);
+        histogram(&mut gen, 5..6, 100);
+        let mut gen = KeyGenerator::new(KeyDistribution::Zipfian, 5..7, 0.5);
+        let counts = histogram(&mut gen, 5..7, 1000);
+        assert!(counts[0] > counts[1], "{counts:?}");
+    }
+
+    #[test]
+    fn sequential_keys_wrap_around() {
+        let mut gen = KeyGenerator::new(KeyDistribution::Sequential, 10..13, 0.99);
+        let mut rng = SmallRng::seed_from_u64(1);
+        let indexes = (0..7).map(|_| gen.next_index(&mut rng)).collect::<Vec<_>>();
+        assert_eq!(indexes, vec![10, 11, 12, 10, 11, 12, 10]);
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+/// The values below it are recorded exactly.
+const LINEAR_BUCKETS: u64 = 64;
+/// The sub-buckets of each power of two above `LINEAR_BUCKETS`, so the
+/// relative error of the recorded values is below 1/32.
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+
+/// A log-linear histogram of latencies in microseconds, the histograms of the
+/// workers are merged to aggregate the results.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        self.record_us(latency.as_micros().min(u64::MAX as u128) as u64);
+    }
+
+    pub fn record_us(&mut self, us: u64) {
+        let index = bucket_index(us);
+        if self.buckets.len() <= index {
+            self.buckets.resize(index + 1, 0);
+        }
+        self.buckets[index] += 1;
+        self.min = if self.count == 0 { us } else { self.min.min(us) };
+        self.max = self.max.max(us);
+        self.count += 1;
+        self.sum = self.sum.saturating_add(us);
+    }
+
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        if other.count == 0 {
+            return;
+        }
+        if self.buckets.len() < other.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        for (count, other) in self.buckets.iter_mut().zip(&other.buckets) {
+            *count += other;
+        }
+        self.min = if self.count == 0 { other.min } else { self.min.min(other.min) };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+    }
+
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    #[inline]
+    pub fn max_us(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean_us(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum as f64 / self.count as f64
+        }
+    }
+
+    /// The value at the quantile, eg. `0.99` for p99. It is the upper bound of
+    /// the bucket which contains the value, but not greater than the max.
+    pub fn percentile_us(&self, quantile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((quantile * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut cumulative = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            cumulative += count;
+            if cumulative >= rank {
+                return bucket_upper_bound(index).clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+}
+
+fn bucket_index(us: u64) -> usize {
+    if us < LINEAR_BUCKETS {
+        return us as usize;
+    }
+    let exp = 63 - us.leading_zeros();
+    let shift = exp - SUB_BUCKET_BITS;
+    let sub = (us >> shift) & (SUB_BUCKETS - 1);
+    let first_exp = LINEAR_BUCKETS.trailing_zeros();
+    (LINEAR_BUCKETS + (exp - first_exp) as u64 * SUB_BUCKETS + sub) as usize
+}
+
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index = index as u64;
+    if index < LINEAR_BUCKETS {
+        return index;
+    }
+    let first_exp = LINEAR_BUCKETS.trailing_zeros();
+    let exp = ((index - LINEAR_BUCKETS) / SUB_BUCKETS) as u32 + first_exp;
+    let sub = (index - LINEAR_BUCKETS) % SUB_BUCKETS;
+    let shift = exp - SUB_BUCKET_BITS;
+    let lower = (SUB_BUCKETS + sub) << shift;
+    lower + ((1u64 << shift) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_bounds() {
+        for us in [0, 1, 63, 64, 65, 127, 128, 1000, 123_456, 10_000_000, u64::MAX] {
+            let index = bucket_index(us);
+            let upper = bucket_upper_bound(index);
+            assert!(upper >= us, "{us} {index} {upper}");
+            assert!(upper - us <= us / SUB_BUCKETS, "{us} {index} {upper}");
+            if index > 0 {
+                assert!(bucket_upper_bound(index - 1) < us, "{us} {index}");
+            }
+        }
+    }
+
+    #[test]
+    fn percentiles() {
+        let mut h = LatencyHistogram::default();
+        assert_eq!(h.percentile_us(0.99), 0);
+        for us in 1..=1000 {
+            h.record_us(us);
+        }
+        assert_eq!(h.count(), 1000);
+        assert_eq!(h.mean_us(), 500.5);
+        assert_eq!(h.max_us(), 1000);
+        let within = |actual: u64, expect: u64| actual >= expect && actual <= expect * 33 / 32;
+        assert!(within(h.percentile_us(0.5), 500), "{}", h.percentile_us(0.5));
+        assert!(within(h.percentile_us(0.95), 950), "{}", h.percentile_us(0.95));
+        assert!(within(h.percentile_us(0.99), 990), "{}", h.percentile_us(0.99));
+        assert_eq!(h.percentile_us(0.999), 1000);
+        assert_eq!(h.percentile_us(0.0), 1);
+    }
+
+    #[test]
+    fn merge_histograms() {
+        let mut fast = LatencyHistogram::default();
+        let mut slow = LatencyHistogram::default();
+        for _ in 0..90 {
+            fast.record(Duration::from_micros(10));
+        }
+        for _ in 0..10 {
+            slow.record(Duration::from_millis(10));
+        }
+        let mut merged = LatencyHistogram::default();
+        merged.merge(&fast);
+        merged.merge(&slow);
+        merged.merge(&LatencyHistogram::default());
+        assert_eq!(merged.count(), 100);
+        assert_eq!(merged.percentile_us(0.5), 10);
+        assert_eq!(merged.percentile_us(0.9), 10);
+        assert_eq!(merged.percentile_us(0.95), 10_000);
+        assert_eq!(merged.max_us(), 10_000);
+        assert_eq!(merged.mean_us(), (90.0 * 10.0 + 10.0 * 10_000.0) / 100.0);
+
+        // The order of merging doesn't matter.
+        let mut other = slow.clone();
+        other.merge(&fast);
+        assert_eq!(other, merged);
+    }
+}
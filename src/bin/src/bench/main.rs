@@ -12,33 +12,28 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::sync::mpsc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use clap::Parser;
-use log::{debug, info};
+use log::{debug, info, warn};
 use rand::rngs::OsRng;
 use rand::RngCore;
-use sekas_client::{AppError, ClientOptions, Database, SekasClient, TableDesc};
-use sekas_runtime::sync::WaitGroup;
+use sekas_client::{AppError, ClientOptions, CreateTableOptions, Database, SekasClient, TableDesc};
 use sekas_runtime::{Shutdown, ShutdownNotifier};
-use tokio::runtime::Runtime;
 use tokio::select;
+use tokio::task::JoinHandle;
 use tokio::time::MissedTickBehavior;
 use tracing_subscriber::EnvFilter;
 
 use super::config::*;
-use super::report;
-use super::report::ReportContext;
+use super::report::{BenchResult, Recorder};
+use super::standalone::StandaloneServer;
 use super::worker::*;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-struct Context {
-    wait_group: WaitGroup,
-    shutdown: Shutdown,
-    runtime: Runtime,
-}
+const DEFAULT_TABLE: &str = "BENCH_TABLE";
 
 #[derive(Parser)]
 #[clap(about = "Start benchmark testing")]
@@ -46,6 +41,14 @@ pub struct Command {
     /// Sets a custom config file
     #[clap(long, value_name = "FILE")]
     conf: Option<String>,
+
+    /// Start an in-process server to test, instead of connecting the cluster
+    #[clap(long)]
+    standalone: bool,
+
+    /// Write the results as JSON to the file
+    #[clap(long, value_name = "FILE")]
+    output: Option<String>,
 }
 
 impl Command {
@@ -67,62 +70,71 @@ impl Command {
             .build()
             .unwrap();
 
-        const DEFAULT_TABLE: &str = "BENCH_TABLE";
-        let db = runtime.block_on(async { open_database(&cfg).await }).expect("open database");
-        let co =
-            runtime.block_on(async { open_table(&db, DEFAULT_TABLE).await }).expect("open table");
         let notifier = ShutdownNotifier::default();
-        let ctx = Context { wait_group: WaitGroup::new(), shutdown: notifier.subscribe(), runtime };
-
-        let (send, recv) = mpsc::channel();
-        let handle = ctx.runtime.spawn(async move {
+        let shutdown = notifier.subscribe();
+        let handle = runtime.spawn(async move {
             notifier.ctrl_c().await;
             info!("receive CTRL-C, exit");
-            send.send(()).unwrap_or_default();
         });
+        let result = runtime.block_on(run(cfg.clone(), shutdown)).expect("run benchmark");
+        handle.abort();
 
-        let base_seed = cfg.seed.unwrap_or_else(|| OsRng.next_u64());
+        println!("{}", result.summary());
+        if let Some(output) = cfg.output.as_ref() {
+            let json = serde_json::to_vec_pretty(&result).unwrap();
+            std::fs::write(output, json).expect("write results");
+            info!("the results are written to {output}");
+        }
+    }
+}
+
+/// Run the benchmark until the operations are finished or it is shutdown, the
+/// results of the measured duration are returned.
+pub(super) async fn run(cfg: AppConfig, shutdown: Shutdown) -> Result<BenchResult> {
+    let server = if cfg.standalone { Some(StandaloneServer::start(1)?) } else { None };
+    let db = match server.as_ref() {
+        Some(server) => wait_database(&cfg, vec![server.addr().to_owned()]).await?,
+        None => open_database(&cfg, cfg.addrs.clone()).await?,
+    };
+    let co = open_table(&db, DEFAULT_TABLE).await?;
 
-        info!("spawn {} workers with base seed {base_seed}", cfg.worker.num_worker);
-        let report_waiter = spawn_reporter(&ctx, cfg.clone());
+    let base_seed = cfg.seed.unwrap_or_else(|| OsRng.next_u64());
+    info!("spawn {} workers with base seed {base_seed}", cfg.worker.num_worker);
 
-        let num_op = cfg.operation / cfg.worker.num_worker;
-        for i in 0..cfg.worker.num_worker {
-            let seed = base_seed + i as u64;
-            spawn_worker(&ctx, cfg.clone(), i, seed, num_op, db.clone(), co.id);
-            if let Some(interval) = cfg.worker.start_intervals {
-                if recv.recv_timeout(interval).is_ok() {
-                    break;
-                }
+    let start = Instant::now();
+    let measure_start = start + cfg.warmup;
+    let num_op = cfg.operation / cfg.worker.num_worker;
+    let mut recorders = vec![];
+    let mut handles = vec![];
+    for i in 0..cfg.worker.num_worker {
+        let seed = base_seed + i as u64;
+        let recorder = Arc::<Mutex<Recorder>>::default();
+        let job =
+            Job::new(db.clone(), co.id, i, seed, num_op, start, recorder.clone(), cfg.clone());
+        handles.push(spawn_worker(i, seed, job, shutdown.clone()));
+        recorders.push(recorder);
+        if let Some(interval) = cfg.worker.start_intervals {
+            select! {
+                _ = shutdown.clone() => break,
+                _ = tokio::time::sleep(interval) => {},
             }
         }
+    }
 
-        info!("all workers are spawned, wait ...");
-
-        let wait_group = ctx.wait_group;
-        ctx.runtime.block_on(async move {
-            wait_group.wait().await;
-            handle.abort();
-            report_waiter.wait().await;
-        });
+    info!("all workers are spawned, wait ...");
+    let reporter = spawn_reporter(cfg.report_interval, measure_start, recorders.clone());
+    for handle in handles {
+        handle.await.unwrap_or_default();
     }
+    reporter.abort();
+    let elapsed = Instant::now().saturating_duration_since(measure_start);
+    Ok(aggregate(&recorders, elapsed))
 }
 
-fn spawn_worker(
-    ctx: &Context,
-    cfg: AppConfig,
-    i: usize,
-    seed: u64,
-    num_op: usize,
-    db: Database,
-    table_id: u64,
-) {
+fn spawn_worker(i: usize, seed: u64, job: Job, shutdown: Shutdown) -> JoinHandle<()> {
     debug!("spawn worker {i} with seed {seed}");
 
-    let job = Job::new(db, table_id, seed, num_op, cfg);
-    let shutdown = ctx.shutdown.clone();
-    let wait_group = ctx.wait_group.clone();
-    ctx.runtime.spawn(async move {
+    tokio::spawn(async move {
         select! {
             _ = shutdown => {
                 debug!("worker {i} receives shutdown signal");
@@ -131,8 +143,34 @@ fn spawn_worker(
                 debug!("worker {i} finish all operations");
             }
         }
-        drop(wait_group);
-    });
+    })
+}
+
+fn aggregate(recorders: &[Arc<Mutex<Recorder>>], elapsed: Duration) -> BenchResult {
+    let recorders = recorders.iter().map(|r| r.lock().unwrap().clone()).collect::<Vec<_>>();
+    BenchResult::aggregate(&recorders, elapsed)
+}
+
+/// Display the results since the warmup is finished periodically.
+fn spawn_reporter(
+    report_interval: Duration,
+    measure_start: Instant,
+    recorders: Vec<Arc<Mutex<Recorder>>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(report_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        // The first tick completes immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let now = Instant::now();
+            if now > measure_start {
+                println!("{}", aggregate(&recorders, now - measure_start).summary());
+            }
+        }
+    })
 }
 
 async fn create_or_open_database(client: &SekasClient, database: &str) -> Result<Database> {
@@ -144,19 +182,21 @@ async fn create_or_open_database(client: &SekasClient, database: &str) -> Result
 }
 
 async fn create_or_open_table(db: &Database, table: &str) -> Result<TableDesc> {
-    match db.create_table(table.to_owned()).await {
+    let opts = CreateTableOptions { wait_ready: true, ..CreateTableOptions::new(table) };
+    match db.create_table_with(opts).await {
         Ok(co) => Ok(co),
         Err(AppError::AlreadyExists(_)) => Ok(db.open_table(table.to_owned()).await?),
         Err(e) => Err(e.into()),
     }
 }
 
-async fn open_database(cfg: &AppConfig) -> Result<Database> {
+async fn open_database(cfg: &AppConfig, addrs: Vec<String>) -> Result<Database> {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(200)),
         timeout: Some(Duration::from_millis(500)),
+        ..Default::default()
     };
-    let client = SekasClient::new(opts, cfg.addrs.clone()).await?;
+    let client = SekasClient::new(opts, addrs).await?;
     let database = match client.open_database(cfg.database.clone()).await {
         Ok(db) => db,
         Err(AppError::NotFound(_)) if cfg.create_if_missing => {
@@ -169,6 +209,24 @@ async fn open_database(cfg: &AppConfig) -> Result<Database> {
     Ok(database)
 }
 
+/// Open the database once the server is ready, eg. the standalone server is
+/// bootstrapping.
+async fn wait_database(cfg: &AppConfig, addrs: Vec<String>) -> Result<Database> {
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        match open_database(cfg, addrs.clone()).await {
+            Ok(db) => return Ok(db),
+            Err(err) if Instant::now() < deadline => {
+                warn!("open database: {err}, retry later");
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
 async fn open_table(db: &Database, table: &str) -> Result<TableDesc> {
     let co = match db.open_table(table.to_owned()).await {
         Ok(co) => co,
@@ -180,34 +238,6 @@ async fn open_table(db: &Database, table: &str) -> Result<TableDesc> {
     Ok(co)
 }
 
-fn spawn_reporter(ctx: &Context, cfg: AppConfig) -> WaitGroup {
-    let shutdown = ctx.shutdown.clone();
-    let wait_group = WaitGroup::new();
-    let cloned_wait_group = wait_group.clone();
-    ctx.runtime.spawn(async move {
-        let mut ctx = ReportContext::default();
-        select! {
-            _ = shutdown => {},
-            _ = reporter_main(cfg, &mut ctx) => {},
-        }
-        report::display(&mut ctx);
-        drop(wait_group);
-    });
-    cloned_wait_group
-}
-
-async fn reporter_main(cfg: AppConfig, ctx: &mut ReportContext) {
-    let mut interval = tokio::time::interval(cfg.report_interval);
-    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
-
-    // The first tick completes immediately
-    interval.tick().await;
-    loop {
-        interval.tick().await;
-        report::display(ctx);
-    }
-}
-
 fn load_config(cmd: Command) -> Result<AppConfig> {
     use ::config::{Config, Environment, File};
 
@@ -223,5 +253,48 @@ fn load_config(cmd: Command) -> Result<AppConfig> {
         )
         .build()?;
 
-    Ok(cfg.try_deserialize()?)
+    let mut cfg: AppConfig = cfg.try_deserialize()?;
+    cfg.standalone |= cmd.standalone;
+    if cmd.output.is_some() {
+        cfg.output = cmd.output;
+    }
+    Ok(cfg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standalone_smoke() {
+        let cfg = AppConfig {
+            num_threads: 2,
+            report_interval: Duration::from_secs(60),
+            duration: Some(Duration::from_secs(2)),
+            warmup: Duration::from_millis(500),
+            standalone: true,
+            seed: Some(1),
+            key: KeyConfig { distribution: KeyDistribution::Zipfian, ..Default::default() },
+            workload: WorkloadConfig { get: 1.0, put: 1.0, txn: 1.0, scan: 1.0, scan_limit: 5 },
+            worker: WorkerConfig { num_worker: 4, start_intervals: None },
+            ..Default::default()
+        };
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .worker_threads(cfg.num_threads)
+            .build()
+            .unwrap();
+        let notifier = ShutdownNotifier::new();
+        let result = runtime.block_on(run(cfg, notifier.subscribe())).unwrap();
+
+        assert!(result.elapsed_secs >= 2.0, "{result:?}");
+        assert!(result.throughput > 0.0, "{result:?}");
+        for name in ["get", "put", "txn", "scan"] {
+            let op = &result.ops[name];
+            assert!(op.count > 0, "{name} {op:?}");
+            assert!(op.p50_us <= op.p99_us && op.p99_us <= op.max_us, "{name} {op:?}");
+        }
+        let json = serde_json::to_string(&result).unwrap();
+        assert_eq!(serde_json::from_str::<BenchResult>(&json).unwrap(), result);
+    }
 }
@@ -13,9 +13,11 @@
 // limitations under the License.
 
 mod config;
+mod distribution;
+mod histogram;
 mod main;
-mod metrics;
 mod report;
+mod standalone;
 mod worker;
 
 pub use main::Command as BenchCommand;
@@ -12,187 +12,199 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::{Duration, Instant};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
 
-use prometheus::proto::Metric;
+use serde::{Deserialize, Serialize};
 
-use super::metrics::*;
+use super::histogram::LatencyHistogram;
 
-pub(super) struct ReportContext {
-    instant: Instant,
-    get_success: Metric,
-    get_failure: Metric,
-    put_success: Metric,
-    put_failure: Metric,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OpKind {
+    Get,
+    Put,
+    Txn,
+    Scan,
 }
 
-struct Summary {
-    interval: Duration,
-    get_success: Histogram,
-    get_failure: Histogram,
-    put_success: Histogram,
-    put_failure: Histogram,
+/// The stats of the operations issued by a worker.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    ops: BTreeMap<OpKind, OpStats>,
 }
 
-struct Histogram {
-    count: usize,
-    avg: usize,
-    p99: usize,
-    p999: usize,
-    p9999: usize,
-    min: usize,
-    max: usize,
+#[derive(Debug, Clone, Default)]
+struct OpStats {
+    /// The latencies of the succeeded operations.
+    latency: LatencyHistogram,
+    errors: u64,
 }
 
-impl From<prometheus::proto::Metric> for Histogram {
-    fn from(m: prometheus::proto::Metric) -> Self {
-        let h = m.get_histogram();
-        let us = |v: f64| -> usize { (v * 1000. * 1000.) as usize };
-        let value = |percent: f64| -> usize {
-            let cnt = (percent * h.get_sample_count() as f64) + 0.5;
-            h.get_bucket()
-                .iter()
-                .find(|b| b.get_cumulative_count() as f64 >= cnt)
-                .map(|b| us(b.get_upper_bound()))
-                .unwrap_or_default()
-        };
-
-        let max = h
-            .get_bucket()
-            .iter()
-            .find(|v| v.get_cumulative_count() == h.get_sample_count())
-            .map(|v| us(v.get_upper_bound()))
-            .unwrap_or_default();
-
-        let min = h
-            .get_bucket()
-            .iter()
-            .take_while(|v| v.get_cumulative_count() == 0)
-            .map(|v| us(v.get_upper_bound()))
-            .last()
-            .unwrap_or_default();
-
-        let avg = if h.get_sample_count() == 0 {
-            0
-        } else {
-            us(h.get_sample_sum() / h.get_sample_count() as f64)
-        };
-
-        Histogram {
-            count: h.get_sample_count() as usize,
-            avg,
-            p99: value(0.99),
-            p999: value(0.999),
-            p9999: value(0.9999),
-            min,
-            max,
+/// The results of a benchmark, which is emitted as JSON.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BenchResult {
+    /// The measured seconds, excluding the warmup.
+    pub elapsed_secs: f64,
+    /// The succeeded operations.
+    pub total_ops: u64,
+    pub total_errors: u64,
+    /// The succeeded operations per second.
+    pub throughput: f64,
+    pub ops: BTreeMap<String, OpSummary>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpSummary {
+    pub count: u64,
+    pub errors: u64,
+    pub throughput: f64,
+    pub mean_us: f64,
+    pub p50_us: u64,
+    pub p95_us: u64,
+    pub p99_us: u64,
+    pub p999_us: u64,
+    pub max_us: u64,
+}
+
+impl OpKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpKind::Get => "get",
+            OpKind::Put => "put",
+            OpKind::Txn => "txn",
+            OpKind::Scan => "scan",
         }
     }
 }
 
-impl ReportContext {
-    pub fn default() -> Self {
-        use prometheus::core::Metric;
-        let get_success = GET_SUCCESS_REQUEST_DURATION_SECONDS.metric();
-        let get_failure = GET_FAILURE_REQUEST_DURATION_SECONDS.metric();
-        let put_success = PUT_SUCCESS_REQUEST_DURATION_SECONDS.metric();
-        let put_failure = PUT_FAILURE_REQUEST_DURATION_SECONDS.metric();
-        ReportContext {
-            instant: Instant::now(),
-            get_success,
-            get_failure,
-            put_success,
-            put_failure,
+impl Recorder {
+    pub fn record(&mut self, kind: OpKind, latency: Duration, succeeded: bool) {
+        let stats = self.ops.entry(kind).or_default();
+        if succeeded {
+            stats.latency.record(latency);
+        } else {
+            stats.errors += 1;
         }
     }
-}
 
-fn diff(current: &ReportContext, earlier: &ReportContext) -> Summary {
-    Summary {
-        interval: current.instant.saturating_duration_since(earlier.instant),
-        get_success: Histogram::from(histogram_diff(&current.get_success, &earlier.get_success)),
-        get_failure: Histogram::from(histogram_diff(&current.get_failure, &earlier.get_failure)),
-        put_success: Histogram::from(histogram_diff(&current.put_success, &earlier.put_success)),
-        put_failure: Histogram::from(histogram_diff(&current.put_failure, &earlier.put_failure)),
+    pub fn merge(&mut self, other: &Recorder) {
+        for (kind, other) in &other.ops {
+            let stats = self.ops.entry(*kind).or_default();
+            stats.latency.merge(&other.latency);
+            stats.errors += other.errors;
+        }
     }
 }
 
-fn histogram_diff(
-    current: &prometheus::proto::Metric,
-    earlier: &prometheus::proto::Metric,
-) -> prometheus::proto::Metric {
-    let mut m = prometheus::proto::Metric::default();
-    let mut h = prometheus::proto::Histogram::default();
-    let mut sum =
-        current.get_histogram().get_sample_sum() - earlier.get_histogram().get_sample_sum();
-    if sum < 0.0 {
-        sum = 0.0;
+impl BenchResult {
+    /// Aggregate the stats of the workers in the measured duration.
+    pub fn aggregate<'a, I>(recorders: I, elapsed: Duration) -> BenchResult
+    where
+        I: IntoIterator<Item = &'a Recorder>,
+    {
+        let mut merged = Recorder::default();
+        for recorder in recorders {
+            merged.merge(recorder);
+        }
+        let secs = elapsed.as_secs_f64();
+        let per_sec = |count: u64| if secs > 0.0 { count as f64 / secs } else { 0.0 };
+        let mut result = BenchResult { elapsed_secs: secs, ..Default::default() };
+        for (kind, stats) in &merged.ops {
+            let h = &stats.latency;
+            let summary = OpSummary {
+                count: h.count(),
+                errors: stats.errors,
+                throughput: per_sec(h.count()),
+                mean_us: h.mean_us(),
+                p50_us: h.percentile_us(0.5),
+                p95_us: h.percentile_us(0.95),
+                p99_us: h.percentile_us(0.99),
+                p999_us: h.percentile_us(0.999),
+                max_us: h.max_us(),
+            };
+            result.total_ops += summary.count;
+            result.total_errors += summary.errors;
+            result.ops.insert(kind.name().to_owned(), summary);
+        }
+        result.throughput = per_sec(result.total_ops);
+        result
     }
-    h.set_sample_sum(sum);
-    h.set_sample_count(
-        current
-            .get_histogram()
-            .get_sample_count()
-            .saturating_sub(earlier.get_histogram().get_sample_count()),
-    );
-
-    current
-        .get_histogram()
-        .get_bucket()
-        .iter()
-        .zip(earlier.get_histogram().get_bucket().iter())
-        .map(|(b1, b2)| {
-            let cumulative_count = b1.get_cumulative_count() - b2.get_cumulative_count();
-            let mut b = prometheus::proto::Bucket::default();
-            b.set_cumulative_count(cumulative_count);
-            b.set_upper_bound(b1.get_upper_bound());
-            b
-        })
-        .for_each(|v| h.mut_bucket().push(v));
-
-    m.set_histogram(h);
-    m
-}
 
-pub(super) fn display(earlier_ctx: &mut ReportContext) {
-    let mut current_ctx = ReportContext::default();
-    let summary = diff(&current_ctx, earlier_ctx);
-
-    display_histogram(
-        "GET",
-        &summary.get_success,
-        summary.interval,
-        current_ctx.get_success.get_histogram().get_sample_count(),
-    );
-    display_histogram(
-        "PUT",
-        &summary.put_success,
-        summary.interval,
-        current_ctx.put_success.get_histogram().get_sample_count(),
-    );
-    display_histogram(
-        "GET_ERROR",
-        &summary.get_failure,
-        summary.interval,
-        current_ctx.get_failure.get_histogram().get_sample_count(),
-    );
-    display_histogram(
-        "PUT_ERROR",
-        &summary.put_failure,
-        summary.interval,
-        current_ctx.put_failure.get_histogram().get_sample_count(),
-    );
-    std::mem::swap(earlier_ctx, &mut current_ctx);
+    /// The human readable summary.
+    pub fn summary(&self) -> String {
+        let mut s = String::new();
+        writeln!(
+            s,
+            "TOTAL - Takes(s): {:.1}, Count: {}, Errors: {}, OPS: {:.1}",
+            self.elapsed_secs, self.total_ops, self.total_errors, self.throughput
+        )
+        .unwrap();
+        for (name, op) in &self.ops {
+            writeln!(
+                s,
+                "{} - Count: {}, Errors: {}, OPS: {:.1}, Avg(us): {:.0}, 50th(us): {}, 95th(us): \
+                 {}, 99th(us): {}, 99.9th(us): {}, Max(us): {}",
+                name.to_uppercase(),
+                op.count,
+                op.errors,
+                op.throughput,
+                op.mean_us,
+                op.p50_us,
+                op.p95_us,
+                op.p99_us,
+                op.p999_us,
+                op.max_us
+            )
+            .unwrap();
+        }
+        s
+    }
 }
 
-fn display_histogram(name: &str, h: &Histogram, interval: Duration, count: u64) {
-    if h.count == 0 {
-        return;
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let seconds = interval.as_secs_f64();
-    let qps = (h.count as f64) / seconds;
+    #[test]
+    fn aggregate_recorders() {
+        let mut first = Recorder::default();
+        let mut second = Recorder::default();
+        for _ in 0..99 {
+            first.record(OpKind::Get, Duration::from_micros(50), true);
+        }
+        second.record(OpKind::Get, Duration::from_millis(5), true);
+        second.record(OpKind::Get, Duration::from_millis(1), false);
+        for _ in 0..20 {
+            second.record(OpKind::Put, Duration::from_micros(60), true);
+        }
+
+        let result = BenchResult::aggregate([&first, &second], Duration::from_secs(2));
+        assert_eq!(result.total_ops, 120);
+        assert_eq!(result.total_errors, 1);
+        assert_eq!(result.throughput, 60.0);
+        assert_eq!(result.ops.len(), 2);
+        let get = &result.ops["get"];
+        assert_eq!((get.count, get.errors), (100, 1));
+        assert_eq!(get.throughput, 50.0);
+        assert_eq!((get.p50_us, get.p99_us), (50, 50));
+        assert_eq!((get.p999_us, get.max_us), (5000, 5000));
+        assert_eq!(get.mean_us, (99.0 * 50.0 + 5000.0) / 100.0);
+        let put = &result.ops["put"];
+        assert_eq!((put.count, put.errors, put.p50_us), (20, 0, 60));
+
+        // The results are machine readable.
+        let json = serde_json::to_string(&result).unwrap();
+        let decoded: BenchResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, result);
+        let summary = result.summary();
+        assert!(summary.contains("TOTAL - Takes(s): 2.0, Count: 120, Errors: 1"), "{summary}");
+        assert!(summary.contains("GET - Count: 100, Errors: 1"), "{summary}");
+    }
 
-    println!("{name} - Takes(s): {:.1}, Count: {}, OPS: {:.1}, Avg(us): {}, Min(us): {}, Max(us): {}, 99th(us): {}, 99.9th(us): {}, 99.99th(us): {}",
-        seconds, count, qps, h.avg, h.min, h.max, h.p99, h.p999, h.p9999);
+    #[test]
+    fn aggregate_nothing() {
+        let result = BenchResult::aggregate(std::iter::empty(), Duration::ZERO);
+        assert_eq!(result, BenchResult::default());
+    }
 }
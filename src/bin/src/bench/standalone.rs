@@ -0,0 +1,72 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+
+use log::{error, info};
+use sekas_runtime::{ExecutorOwner, ShutdownNotifier};
+
+/// A single node cluster running in this process, so the benchmark could be
+/// run without external infra. The data is removed once it is dropped.
+pub struct StandaloneServer {
+    addr: String,
+    root_dir: PathBuf,
+    notifier: Option<ShutdownNotifier>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StandaloneServer {
+    pub fn start(cpu_nums: usize) -> std::io::Result<StandaloneServer> {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let root_dir = std::env::temp_dir().join(format!(
+            "sekas-bench-{}-{}",
+            std::process::id(),
+            addr.port()
+        ));
+        let addr = addr.to_string();
+        let config = sekas_server::Config {
+            root_dir: root_dir.clone(),
+            addr: addr.clone(),
+            cpu_nums: cpu_nums as u32,
+            init: true,
+            ..Default::default()
+        };
+        info!("start standalone server {addr} at {}", root_dir.display());
+        let notifier = ShutdownNotifier::new();
+        let shutdown = notifier.subscribe();
+        let handle = std::thread::spawn(move || {
+            let owner = ExecutorOwner::new(cpu_nums);
+            if let Err(err) = sekas_server::run(config, owner.executor(), shutdown) {
+                error!("standalone server: {err}");
+            }
+        });
+        Ok(StandaloneServer { addr, root_dir, notifier: Some(notifier), handle: Some(handle) })
+    }
+
+    #[inline]
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+}
+
+impl Drop for StandaloneServer {
+    fn drop(&mut self) {
+        drop(self.notifier.take());
+        if let Some(handle) = self.handle.take() {
+            handle.join().unwrap_or_default();
+        }
+        std::fs::remove_dir_all(&self.root_dir).unwrap_or_default();
+    }
+}
@@ -13,50 +13,85 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 use log::trace;
 use rand::prelude::*;
-use sekas_client::Database;
+use sekas_client::{Database, Range, RangeRequest, WriteBuilder};
 
-use super::metrics::*;
+use super::config::KeyDistribution;
+use super::distribution::KeyGenerator;
+use super::report::{OpKind, Recorder};
 use super::AppConfig;
 
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
 pub struct Job {
     db: Database,
     table_id: u64,
 
     consumed: usize,
     num_op: usize,
+    /// The operations are issued until the deadline, instead of `num_op`.
+    deadline: Option<Instant>,
+    /// The operations finished before it are not recorded.
+    measure_start: Instant,
+    recorder: Arc<Mutex<Recorder>>,
     gen: Generator,
 }
 
 pub struct Generator {
     cfg: AppConfig,
-    range: std::ops::Range<u64>,
+    keys: KeyGenerator,
     rng: SmallRng,
 }
 
 #[derive(Debug, Clone)]
 pub enum NextOp {
-    Put { key: Vec<u8>, value: Vec<u8> },
-    Get { key: Vec<u8> },
+    Put {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Get {
+        key: Vec<u8>,
+    },
+    /// Read the key and write it back in a txn.
+    Txn {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Scan {
+        start: Vec<u8>,
+        limit: usize,
+    },
 }
 
 impl Generator {
     pub fn new(seed: u64, cfg: AppConfig, range: std::ops::Range<u64>) -> Generator {
-        Generator { cfg, range, rng: SmallRng::seed_from_u64(seed) }
+        let keys = KeyGenerator::new(cfg.key.distribution, range, cfg.key.zipf_theta);
+        Generator { cfg, keys, rng: SmallRng::seed_from_u64(seed) }
     }
 
     pub fn next_op(&mut self) -> NextOp {
-        let v = self.rng.gen_range(0..100) as f64 / 100.0;
+        let workload = self.cfg.workload.clone();
+        let total = workload.get + workload.put + workload.txn + workload.scan;
+        let mut v = self.rng.gen::<f64>() * total;
         let key = self.next_key();
-        if v < self.cfg.data.write {
+        if v < workload.put {
             let value = self.next_bytes(self.cfg.data.value.clone());
-            NextOp::Put { key, value }
-        } else {
-            NextOp::Get { key }
+            return NextOp::Put { key, value };
+        }
+        v -= workload.put;
+        if v < workload.txn {
+            let value = self.next_bytes(self.cfg.data.value.clone());
+            return NextOp::Txn { key, value };
+        }
+        v -= workload.txn;
+        if v < workload.scan {
+            return NextOp::Scan { start: key, limit: workload.scan_limit };
         }
+        NextOp::Get { key }
     }
 
     fn next_bytes(&mut self, range: std::ops::Range<usize>) -> Vec<u8> {
@@ -69,24 +104,63 @@ impl Generator {
     }
 
     fn next_key(&mut self) -> Vec<u8> {
-        let index = self.rng.gen_range(self.range.clone());
+        let index = self.keys.next_index(&mut self.rng);
         format!("{}{index:0leading$}", self.cfg.key.prefix, leading = self.cfg.key.leading)
             .into_bytes()
     }
 }
 
 impl Job {
-    pub fn new(db: Database, table_id: u64, seed: u64, num_op: usize, cfg: AppConfig) -> Job {
-        let limited = cfg.data.limited;
-        Job { db, table_id, consumed: 0, num_op, gen: Generator::new(seed, cfg, 0..limited) }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        db: Database,
+        table_id: u64,
+        id: usize,
+        seed: u64,
+        num_op: usize,
+        start: Instant,
+        recorder: Arc<Mutex<Recorder>>,
+        cfg: AppConfig,
+    ) -> Job {
+        let measure_start = start + cfg.warmup;
+        let deadline = cfg.duration.map(|duration| measure_start + duration);
+        let range = worker_key_range(&cfg, id);
+        Job {
+            db,
+            table_id,
+            consumed: 0,
+            num_op,
+            deadline,
+            measure_start,
+            recorder,
+            gen: Generator::new(seed, cfg, range),
+        }
     }
 }
 
+/// The range of key indexes issued by the worker, the sequential keys of the
+/// workers are not overlapped.
+fn worker_key_range(cfg: &AppConfig, id: usize) -> std::ops::Range<u64> {
+    let limited = cfg.data.limited.max(1);
+    if cfg.key.distribution != KeyDistribution::Sequential {
+        return 0..limited;
+    }
+    let num_worker = (cfg.worker.num_worker as u64).clamp(1, limited);
+    let step = limited / num_worker;
+    let id = id as u64 % num_worker;
+    let end = if id + 1 == num_worker { limited } else { (id + 1) * step };
+    id * step..end
+}
+
 impl Iterator for Job {
     type Item = NextOp;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.consumed >= self.num_op {
+        let finished = match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => self.consumed >= self.num_op,
+        };
+        if finished {
             None
         } else {
             self.consumed += 1;
@@ -98,57 +172,92 @@ impl Iterator for Job {
 pub async fn worker_main(_id: usize, mut job: Job) {
     let db = job.db.clone();
     let table_id = job.table_id;
+    let measure_start = job.measure_start;
+    let recorder = job.recorder.clone();
     for next_op in &mut job {
-        execute(&db, table_id, next_op).await;
+        let start = Instant::now();
+        let (kind, result) = execute(&db, table_id, next_op).await;
+        if let Err(e) = &result {
+            trace!("{} request: {e:?}", kind.name());
+        }
+        if start >= measure_start {
+            recorder.lock().unwrap().record(kind, start.elapsed(), result.is_ok());
+        }
     }
 }
 
-async fn execute(db: &Database, co: u64, next_op: NextOp) {
+async fn execute(db: &Database, co: u64, next_op: NextOp) -> (OpKind, Result<()>) {
     match next_op {
-        NextOp::Get { key } => {
-            get(db, co, key).await;
-        }
+        NextOp::Get { key } => (OpKind::Get, db.get(co, key).await.map(|_| ()).map_err(Into::into)),
         NextOp::Put { key, value } => {
-            put(db, co, key, value).await;
+            (OpKind::Put, db.put(co, key, value).await.map_err(Into::into))
         }
+        NextOp::Txn { key, value } => (OpKind::Txn, read_modify_write(db, co, key, value).await),
+        NextOp::Scan { start, limit } => (OpKind::Scan, scan(db, co, start, limit).await),
     }
 }
 
-async fn get(db: &Database, co: u64, key: Vec<u8>) {
-    trace!("send get request");
-    let start = Instant::now();
-    match db.get(co, key).await {
-        Ok(_) => {
-            GET_SUCCESS_REQUEST_TOTAL.inc();
-            GET_SUCCESS_REQUEST_DURATION_SECONDS.observe(saturating_elapsed_seconds(start));
-        }
-        Err(e) => {
-            tracing::error!("get request {e:?}");
-            GET_FAILURE_REQUEST_TOTAL.inc();
-            GET_FAILURE_REQUEST_DURATION_SECONDS.observe(saturating_elapsed_seconds(start));
-        }
-    }
-    GET_REQUEST_TOTAL.inc();
+async fn read_modify_write(db: &Database, co: u64, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+    let mut txn = db.begin_txn();
+    txn.get(co, key.clone()).await?;
+    txn.put(co, WriteBuilder::new(key).ensure_put(value));
+    txn.commit().await?;
+    Ok(())
 }
 
-async fn put(db: &Database, co: u64, key: Vec<u8>, value: Vec<u8>) {
-    trace!("send put request");
-    let start = Instant::now();
-    match db.put(co, key, value).await {
-        Ok(_) => {
-            PUT_SUCCESS_REQUEST_TOTAL.inc();
-            PUT_SUCCESS_REQUEST_DURATION_SECONDS.observe(saturating_elapsed_seconds(start));
-        }
-        Err(_) => {
-            PUT_FAILURE_REQUEST_TOTAL.inc();
-            PUT_FAILURE_REQUEST_DURATION_SECONDS.observe(saturating_elapsed_seconds(start));
+async fn scan(db: &Database, co: u64, start: Vec<u8>, limit: usize) -> Result<()> {
+    let request = RangeRequest {
+        table_id: co,
+        range: Range::Range { begin: Some(start), end: None },
+        ..Default::default()
+    };
+    db.range(request).await?.try_collect_vec(limit).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::config::WorkloadConfig;
+
+    #[test]
+    fn generate_workload_mix() {
+        let cfg = AppConfig {
+            workload: WorkloadConfig { get: 1.0, put: 1.0, txn: 1.0, scan: 1.0, scan_limit: 5 },
+            ..Default::default()
+        };
+        let mut gen = Generator::new(1, cfg, 0..100);
+        let mut counts = [0; 4];
+        for _ in 0..10_000 {
+            let index = match gen.next_op() {
+                NextOp::Get { .. } => 0,
+                NextOp::Put { .. } => 1,
+                NextOp::Txn { .. } => 2,
+                NextOp::Scan { limit, .. } => {
+                    assert_eq!(limit, 5);
+                    3
+                }
+            };
+            counts[index] += 1;
         }
+        assert!(counts.iter().all(|c| (2_200..2_800).contains(c)), "{counts:?}");
+
+        let cfg = AppConfig {
+            workload: WorkloadConfig { get: 0.0, put: 1.0, txn: 0.0, scan: 0.0, scan_limit: 5 },
+            ..Default::default()
+        };
+        let mut gen = Generator::new(1, cfg, 0..100);
+        assert!((0..100).all(|_| matches!(gen.next_op(), NextOp::Put { .. })));
     }
-    PUT_REQUEST_TOTAL.inc();
-}
 
-#[inline]
-fn saturating_elapsed_seconds(instant: Instant) -> f64 {
-    let now = Instant::now();
-    now.saturating_duration_since(instant).as_secs_f64()
+    #[test]
+    fn sequential_worker_key_ranges() {
+        let mut cfg = AppConfig::default();
+        cfg.data.limited = 10;
+        cfg.worker.num_worker = 3;
+        assert_eq!(worker_key_range(&cfg, 0), 0..10);
+        cfg.key.distribution = KeyDistribution::Sequential;
+        let ranges = (0..3).map(|id| worker_key_range(&cfg, id)).collect::<Vec<_>>();
+        assert_eq!(ranges, vec![0..3, 3..6, 6..10]);
+    }
 }
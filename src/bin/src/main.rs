@@ -17,7 +17,7 @@ mod bench;
 mod shell;
 
 use clap::{Parser, Subcommand};
-use log::info;
+use log::{info, warn};
 use sekas_server::{Error, Result};
 use tracing_subscriber::EnvFilter;
 
@@ -48,8 +48,14 @@ struct StartCommand {
     #[clap(long, value_name = "ADDR")]
     join: Option<Vec<String>>,
 
-    /// Sets a custom config file
-    #[clap(long, value_name = "FILE")]
+    /// Sets the id of the cluster to bootstrap or join, the node is rejected
+    /// if the cluster it joins has another id
+    #[clap(long, value_name = "ID")]
+    cluster_id: Option<String>,
+
+    /// Sets a custom config file, it is read again to reload the
+    /// hot-reloadable settings once SIGHUP is received
+    #[clap(long, alias = "config", value_name = "FILE")]
     conf: Option<String>,
 
     /// Sets the address to listen, default is '127.0.0.1:2180'
@@ -65,38 +71,52 @@ struct StartCommand {
     #[clap(long, value_name = "LIMIT")]
     cpu_nums: Option<u32>,
 
+    /// Sets the log filter, in form of `RUST_LOG`, default is 'info'
+    #[clap(long, value_name = "FILTER")]
+    log_level: Option<String>,
+
     /// Dump config as toml file and exit
     #[clap(long, value_name = "FILE")]
     dump: Option<String>,
+
+    /// Mirror the catalog of the primary cluster which the node of the address
+    /// belongs to, the mutating requests are refused until it is promoted
+    #[clap(long, value_name = "ADDR")]
+    catalog_mirror_of: Option<String>,
+
+    /// Accept the unsafe admin requests which edit the descriptors in catalog
+    #[clap(long)]
+    enable_unsafe_admin: bool,
+
+    /// Sets the address to expose the metrics over HTTP, it is disabled by
+    /// default
+    #[clap(long, value_name = "ADDR")]
+    metrics_addr: Option<String>,
 }
 
 impl StartCommand {
     fn run(self) -> Result<()> {
         use sekas_runtime::{ExecutorOwner, ShutdownNotifier};
 
-        let filter_layer =
-            EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info")).unwrap();
-        tracing_subscriber::fmt()
-            .with_env_filter(filter_layer)
-            .with_ansi(atty::is(atty::Stream::Stderr))
-            .init();
-
-        let mut config = match load_config(&self) {
-            Ok(c) => c,
-            Err(e) => {
-                return Err(Error::InvalidArgument(format!("Config: {e}")));
-            }
-        };
-
-        if let Some(filename) = self.dump {
+        if let Some(filename) = self.dump.as_ref() {
+            let config =
+                build_config(&self).map_err(|e| Error::InvalidArgument(format!("Config: {e}")))?;
             let contents = toml::to_string(&config).expect("Config is serializable");
             std::fs::write(filename, contents)?;
             return Ok(());
         }
 
-        if config.cpu_nums == 0 {
-            config.cpu_nums = num_cpus::get() as u32;
-        }
+        let config = load_config(&self)?;
+
+        let filter_layer = log_filter(&config)?;
+        let default_filter = filter_layer.to_string();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(filter_layer)
+            .with_filter_reloading()
+            .with_ansi(atty::is(atty::Stream::Stderr));
+        let reload_handle = subscriber.reload_handle();
+        subscriber.init();
+        sekas_client::install_log_filter(&default_filter, reload_handle);
 
         info!("{config:#?}");
 
@@ -107,7 +127,30 @@ impl StartCommand {
         let _handle = executor.spawn(async move {
             notifier.ctrl_c().await;
         });
-        sekas_server::run(config, executor, shutdown)
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let _handle = executor.spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangup = signal(SignalKind::hangup()).expect("install SIGHUP handler");
+            while hangup.recv().await.is_some() {
+                info!("SIGHUP is received, reload config");
+                let (config, filter) =
+                    match load_config(&self).and_then(|c| log_filter(&c).map(|f| (c, f))) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            warn!("the reloaded config is ignored: {e}");
+                            continue;
+                        }
+                    };
+                if let Err(e) = sekas_client::set_default_log_filter(&filter.to_string()) {
+                    warn!("reload log filter: {e}");
+                }
+                if sender.send(config).is_err() {
+                    break;
+                }
+            }
+        });
+        sekas_server::run_with_reload(config, executor, shutdown, receiver)
     }
 }
 
@@ -116,7 +159,10 @@ fn main() -> Result<()> {
     std::panic::set_hook(Box::new(move |info| {
         tracing::error!("panic occurred: {}", info);
         default_panic(info);
-        std::process::abort();
+        // The panic raised in applying an entry only quarantines the replica.
+        if !sekas_server::raftgroup::is_apply_panic() {
+            std::process::abort();
+        }
     }));
 
     match Command::parse().subcmd {
@@ -132,7 +178,19 @@ fn main() -> Result<()> {
     }
 }
 
-fn load_config(cmd: &StartCommand) -> Result<sekas_server::Config, config::ConfigError> {
+/// Load the config from the file, the environment and the flags, and validate
+/// it.
+fn load_config(cmd: &StartCommand) -> Result<sekas_server::Config> {
+    let mut config =
+        build_config(cmd).map_err(|e| Error::InvalidArgument(format!("Config: {e}")))?;
+    if config.cpu_nums == 0 {
+        config.cpu_nums = num_cpus::get() as u32;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+fn build_config(cmd: &StartCommand) -> Result<sekas_server::Config, config::ConfigError> {
     use config::{Config, Environment, File};
 
     let mut builder = Config::builder()
@@ -152,9 +210,32 @@ fn load_config(cmd: &StartCommand) -> Result<sekas_server::Config, config::Confi
         .set_override_option("addr", cmd.addr.clone())?
         .set_override_option("root_dir", cmd.db.clone())?
         .set_override_option("join_list", cmd.join.clone())?
+        .set_override_option("cluster_id", cmd.cluster_id.clone())?
         .set_override_option("cpu_nums", cmd.cpu_nums)?
+        .set_override_option("log_level", cmd.log_level.clone())?
         .set_override_option("init", if cmd.init { Some(true) } else { None })?
         .build()?;
 
-    c.try_deserialize()
+    let mut config: sekas_server::Config = c.try_deserialize()?;
+    if let Some(primary) = cmd.catalog_mirror_of.as_ref() {
+        config.root.catalog_mirror_of = Some(primary.clone());
+    }
+    if cmd.enable_unsafe_admin {
+        config.root.enable_unsafe_admin = true;
+    }
+    if let Some(addr) = cmd.metrics_addr.as_ref() {
+        config.metrics.addr = Some(addr.clone());
+    }
+    Ok(config)
+}
+
+/// The log filter of the config, it falls back to `RUST_LOG` and `info`.
+fn log_filter(config: &sekas_server::Config) -> Result<EnvFilter> {
+    match config.log_level.as_ref() {
+        Some(level) => EnvFilter::try_new(level)
+            .map_err(|e| Error::InvalidArgument(format!("invalid config `log_level`: {e}"))),
+        None => {
+            Ok(EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("info")).unwrap())
+        }
+    }
 }
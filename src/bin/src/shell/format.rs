@@ -0,0 +1,241 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Render the [`ExecuteResult`] in the [`OutputFormat`] selected by the
+//! `FORMAT` statement.
+//!
+//! Binary keys and values are carried in [`Row`] as strings encoded by
+//! [`escape_bytes`], which turns tabs, newlines, quotes, backslashes and any
+//! non printable ASCII byte into escape sequences such as `\t`, `\n`, `\\` and
+//! `\xFF`. The encoded string is emitted as is by the table format, and escaped
+//! again by the JSON and TSV formats, so a binary field of them is decoded by
+//! [`sekas_rock::ascii::unescape_bytes`] back to the original bytes once the
+//! format is decoded.
+
+use log::error;
+use sekas_parser::{ColumnResult, ExecuteResult, OutputFormat, Row};
+use sekas_rock::ascii::escape_bytes;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+/// Encode the binary key or value into a [`Row`] value.
+#[inline]
+pub fn bytes_value(bytes: &[u8]) -> Value {
+    Value::String(escape_bytes(bytes))
+}
+
+/// Render the execute result, `None` is returned if there is nothing to show.
+pub fn render(result: ExecuteResult, format: OutputFormat) -> Option<String> {
+    match result {
+        ExecuteResult::Data(data) => {
+            check_row_len(&data);
+            Some(match format {
+                OutputFormat::Table => render_table(data),
+                OutputFormat::Json => render_json(data),
+                OutputFormat::Tsv => render_tsv(data),
+            })
+        }
+        ExecuteResult::Msg(msg) => Some(msg),
+        ExecuteResult::None => None,
+    }
+}
+
+fn check_row_len(data: &ColumnResult) {
+    let total_columns = data.columns.len();
+    for row in &data.rows {
+        if row.values.len() != total_columns {
+            error!(
+                "the result row len {} is not equals to columns len {}",
+                row.values.len(),
+                total_columns
+            );
+        }
+    }
+}
+
+fn render_table(data: ColumnResult) -> String {
+    use tabled::builder::Builder;
+    use tabled::settings::Style;
+
+    let mut builder = Builder::new();
+    builder.push_record(data.columns);
+    for row in data.rows {
+        builder.push_record(row.values.iter().map(|v| match v {
+            Value::String(str) => str.clone(),
+            _ => v.to_string(),
+        }));
+    }
+    builder.build().with(Style::ascii_rounded()).to_string()
+}
+
+/// A row serialized as a JSON object, the fields keep the order of columns.
+struct JsonObject<'a> {
+    columns: &'a [String],
+    row: &'a Row,
+}
+
+impl<'a> Serialize for JsonObject<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (column, value) in self.columns.iter().zip(&self.row.values) {
+            map.serialize_entry(column, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Render the rows as a JSON array of objects keyed by the column name.
+fn render_json(data: ColumnResult) -> String {
+    let objects =
+        data.rows.iter().map(|row| JsonObject { columns: &data.columns, row }).collect::<Vec<_>>();
+    serde_json::to_string_pretty(&objects).expect("serialize json values")
+}
+
+/// Render the rows as tab-separated values, the first line is the column
+/// names.
+///
+/// The backslashes, tabs, newlines and carriage returns of every string field
+/// are escaped as `\\`, `\t`, `\n` and `\r`, so a literal backslash is never
+/// confused with an escape sequence, and every field is decoded by
+/// [`sekas_rock::ascii::unescape_bytes`].
+fn render_tsv(data: ColumnResult) -> String {
+    let escape_field = |field: String| -> String {
+        let mut escaped = String::with_capacity(field.len());
+        for c in field.chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\t' => escaped.push_str("\\t"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                _ => escaped.push(c),
+            }
+        }
+        escaped
+    };
+
+    let mut lines = vec![data.columns.into_iter().map(escape_field).collect::<Vec<_>>().join("\t")];
+    for row in data.rows {
+        let fields = row.values.into_iter().map(|v| match v {
+            Value::String(str) => escape_field(str),
+            _ => v.to_string(),
+        });
+        lines.push(fields.collect::<Vec<_>>().join("\t"));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_rock::ascii::unescape_bytes;
+
+    use super::*;
+
+    const KEY_VALUES: &[(&[u8], Option<&[u8]>)] = &[
+        (b"key\twith\ttabs", Some(b"value\nwith\nnewlines")),
+        (b"\x00\x01non-utf8\xFE\xFF", Some(b"\x80\x81\r\n")),
+        (b"quote'\"backslash\\", None),
+    ];
+
+    fn column_result() -> ColumnResult {
+        let columns =
+            ["key", "value", "version", "note"].into_iter().map(ToOwned::to_owned).collect();
+        let rows = KEY_VALUES
+            .iter()
+            .enumerate()
+            .map(|(idx, (key, value))| Row {
+                values: vec![
+                    bytes_value(key),
+                    value.map(bytes_value).unwrap_or(Value::Null),
+                    (idx as u64).into(),
+                    format!("plain\\text\t{idx}").into(),
+                ],
+            })
+            .collect();
+        ColumnResult { columns, rows }
+    }
+
+    fn render_data(format: OutputFormat) -> String {
+        render(ExecuteResult::Data(column_result()), format).unwrap()
+    }
+
+    /// The golden files are ended with a newline.
+    fn golden(content: &str) -> &str {
+        content.strip_suffix('\n').unwrap_or(content)
+    }
+
+    #[test]
+    fn render_table_golden() {
+        assert_eq!(
+            render_data(OutputFormat::Table),
+            golden(include_str!("testdata/format.table.golden"))
+        );
+    }
+
+    #[test]
+    fn render_json_golden() {
+        assert_eq!(
+            render_data(OutputFormat::Json),
+            golden(include_str!("testdata/format.json.golden"))
+        );
+    }
+
+    #[test]
+    fn render_tsv_golden() {
+        assert_eq!(
+            render_data(OutputFormat::Tsv),
+            golden(include_str!("testdata/format.tsv.golden"))
+        );
+    }
+
+    #[test]
+    fn binary_fields_round_trip_through_json() {
+        let output = render_data(OutputFormat::Json);
+        let objects: Vec<serde_json::Map<String, Value>> = serde_json::from_str(&output).unwrap();
+        assert_eq!(objects.len(), KEY_VALUES.len());
+        for (object, &(key, value)) in objects.iter().zip(KEY_VALUES) {
+            let decode = |v: &Value| unescape_bytes(v.as_str().unwrap()).unwrap();
+            assert_eq!(decode(&object["key"]), key);
+            assert_eq!(
+                object["value"].as_str().map(|_| decode(&object["value"])).as_deref(),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn binary_fields_round_trip_through_tsv() {
+        let output = render_data(OutputFormat::Tsv);
+        let lines = output.split('\n').collect::<Vec<_>>();
+        assert_eq!(lines.len(), KEY_VALUES.len() + 1);
+        for (idx, (line, &(key, value))) in lines[1..].iter().zip(KEY_VALUES).enumerate() {
+            let fields = line
+                .split('\t')
+                .map(|field| String::from_utf8(unescape_bytes(field).unwrap()).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(unescape_bytes(&fields[0]).unwrap(), key);
+            if let Some(value) = value {
+                assert_eq!(unescape_bytes(&fields[1]).unwrap(), value);
+            }
+            assert_eq!(fields[3], format!("plain\\text\t{idx}"));
+        }
+    }
+
+    #[test]
+    fn render_message() {
+        let msg = ExecuteResult::Msg("OK".to_owned());
+        assert_eq!(render(msg, OutputFormat::Json).as_deref(), Some("OK"));
+        assert!(render(ExecuteResult::None, OutputFormat::Tsv).is_none());
+    }
+}
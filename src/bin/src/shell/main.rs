@@ -19,12 +19,14 @@ use std::time::Duration;
 
 use anyhow::{Context, Error};
 use clap::Parser;
-use log::error;
+use futures::StreamExt;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
-use sekas_client::{AppError, ClientOptions, Database, SekasClient};
+use sekas_client::{AppError, ClientOptions, Database, Range, RangeRequest, SekasClient};
 use sekas_parser::*;
 
+use super::format::{bytes_value, render};
+
 type Result<T, E = Error> = std::result::Result<T, E>;
 
 #[derive(Parser)]
@@ -74,6 +76,8 @@ struct Session {
 
     database_cache: HashMap<String, Database>,
     table_cache: HashMap<(String, String), u64>,
+
+    format: OutputFormat,
 }
 
 impl Session {
@@ -104,12 +108,27 @@ impl Session {
             Statement::Debug(debug) => debug.execute(),
             Statement::Help(help) => help.execute(),
             Statement::Echo(echo) => ExecuteResult::Msg(echo.message),
+            Statement::Format(format) => {
+                self.format = format.format;
+                ExecuteResult::Msg("OK".to_owned())
+            }
             Statement::CreateDb(create_db) => self.create_database(create_db).await?,
             Statement::CreateTable(create_table) => self.create_table(create_table).await?,
             Statement::Put(put) => self.put_key_value(put).await?,
             Statement::Delete(delete) => self.delete_key(delete).await?,
             Statement::Get(get) => self.get_key(get).await?,
-            Statement::Config(_) | Statement::Show(_) => return Ok(None),
+            Statement::Scan(scan) => self.scan_keys(scan).await?,
+            Statement::AlterDatabase(_)
+            | Statement::AlterTable(_)
+            | Statement::Approve(_)
+            | Statement::CompactTable(_)
+            | Statement::Config(_)
+            | Statement::DebugSchedule(_)
+            | Statement::DebugSearch(_)
+            | Statement::DebugVerify(_)
+            | Statement::KillTxn(_)
+            | Statement::Show(_)
+            | Statement::Split(_) => return Ok(None),
         };
         Ok(Some(result))
     }
@@ -141,6 +160,23 @@ impl Session {
 
     /// Execute create table statement.
     async fn create_table(&self, create_table_stmt: CreateTableStatement) -> Result<ExecuteResult> {
+        if let Some((src_db_name, src_table_name)) = create_table_stmt.clone_of {
+            let table = self
+                .sekas_client
+                .clone_table(
+                    src_db_name,
+                    src_table_name,
+                    create_table_stmt.db_name,
+                    create_table_stmt.table_name,
+                )
+                .await
+                .context("failed to clone table")?;
+            return Ok(ExecuteResult::Msg(format!(
+                "Ok, the table {} is read-only until the clone finishes",
+                table.id
+            )));
+        }
+
         let database = self
             .sekas_client
             .open_database(create_table_stmt.db_name.clone())
@@ -192,11 +228,7 @@ impl Session {
             let is_tombstone = value.content.is_none();
             vec![Row {
                 values: vec![
-                    if let Some(content) = value.content {
-                        content.into()
-                    } else {
-                        serde_json::Value::Null
-                    },
+                    value.content.as_deref().map(bytes_value).unwrap_or_default(),
                     value.version.into(),
                     is_tombstone.into(),
                 ],
@@ -207,6 +239,32 @@ impl Session {
         Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
     }
 
+    /// Scan keys from table.
+    async fn scan_keys(&mut self, stmt: ScanStatement) -> Result<ExecuteResult> {
+        let db = self.open_database(&stmt.db_name).await?;
+        let table_id = self.get_table(&stmt.db_name, &stmt.table_name).await?;
+        let range = match stmt.prefix {
+            Some(prefix) => Range::Prefix(prefix),
+            None => Range::all(),
+        };
+        let request = RangeRequest { table_id, range, reverse: stmt.reverse, ..Default::default() };
+        let mut stream = db.range(request).await?;
+
+        let columns =
+            ["key", "value", "version"].into_iter().map(ToOwned::to_owned).collect::<Vec<_>>();
+        let mut rows = vec![];
+        while let Some(value_sets) = stream.next().await {
+            for value_set in value_sets? {
+                let key = bytes_value(&value_set.user_key);
+                for value in value_set.values {
+                    let content = value.content.as_deref().map(bytes_value).unwrap_or_default();
+                    rows.push(Row { values: vec![key.clone(), content, value.version.into()] });
+                }
+            }
+        }
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
     /// Delete key from table.
     async fn delete_key(&mut self, stmt: DeleteStatement) -> Result<ExecuteResult> {
         let db = self.open_database(&stmt.db_name).await?;
@@ -239,37 +297,10 @@ impl Session {
         Ok(table_desc.id)
     }
 
-    /// Show the execute result.
+    /// Show the execute result in the selected output format.
     fn show_result(&self, result: ExecuteResult) {
-        use tabled::builder::Builder;
-        use tabled::settings::Style;
-
-        match result {
-            ExecuteResult::Data(data) => {
-                let total_columns = data.columns.len();
-                let mut builder = Builder::new();
-                builder.push_record(data.columns);
-                for row in data.rows {
-                    if row.values.len() != total_columns {
-                        error!(
-                            "the result row len {} is not equals to columns len {}",
-                            row.values.len(),
-                            total_columns
-                        );
-                    }
-                    builder.push_record(row.values.iter().map(|v| match v {
-                        serde_json::Value::String(str) => str.clone(),
-                        _ => v.to_string(),
-                    }));
-                }
-
-                let table = builder.build().with(Style::ascii_rounded()).to_string();
-                println!("{}", table);
-            }
-            ExecuteResult::Msg(msg) => {
-                println!("{}", msg);
-            }
-            ExecuteResult::None => (),
+        if let Some(output) = render(result, self.format) {
+            println!("{}", output);
         }
     }
 }
@@ -310,11 +341,16 @@ async fn editor_main(cmd: Command) {
 }
 
 async fn new_session(cmd: Command) -> Result<Session> {
-    let opts = ClientOptions { connect_timeout: cmd.connection_timeout, timeout: cmd.rpc_timeout };
+    let opts = ClientOptions {
+        connect_timeout: cmd.connection_timeout,
+        timeout: cmd.rpc_timeout,
+        ..Default::default()
+    };
     let sekas_client = SekasClient::new(opts, cmd.addrs).await?;
     Ok(Session {
         sekas_client,
         database_cache: HashMap::default(),
         table_cache: HashMap::default(),
+        format: OutputFormat::default(),
     })
 }
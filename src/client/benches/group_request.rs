@@ -0,0 +1,109 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The bytes allocated to issue a retried group request, by cloning the
+//! request for each attempt versus sharing the encoded payload.
+//!
+//! Run it with `cargo bench -p sekas-client --bench group_request`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use bytes::BytesMut;
+use prost::Message;
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::*;
+use sekas_client::EncodedGroupRequest;
+
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const VALUE_SIZE: usize = 1024 * 1024;
+/// The first attempt and three retries.
+const ATTEMPTS: u64 = 4;
+const ROUNDS: usize = 64;
+
+/// Issue a request `ROUNDS` times, returns the bytes allocated and the
+/// nanoseconds elapsed per request.
+fn measure(mut issue: impl FnMut(&mut BytesMut)) -> (usize, u128) {
+    // The wire buffer is reused by the transport.
+    let mut wire = BytesMut::with_capacity(2 * VALUE_SIZE);
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        issue(&mut wire);
+    }
+    let elapsed = start.elapsed().as_nanos() / ROUNDS as u128;
+    ((ALLOCATED.load(Ordering::Relaxed) - allocated) / ROUNDS, elapsed)
+}
+
+fn main() {
+    let put =
+        PutRequest { key: b"key".to_vec(), value: vec![b'v'; VALUE_SIZE], ..Default::default() };
+    let request =
+        Request::Write(ShardWriteRequest { shard_id: 1, puts: vec![put], ..Default::default() });
+    let request_id = "2f4e0a52-5a0c-4a8f-8d5c-4b6e6f3b0b9d";
+
+    let (cloned_bytes, cloned_nanos) = measure(|wire| {
+        for epoch in 1..=ATTEMPTS {
+            let req = GroupRequest {
+                group_id: 1,
+                epoch,
+                request: Some(GroupRequestUnion { request: Some(request.clone()) }),
+                request_id: request_id.to_owned(),
+                record_request_id: None,
+                allow_forward: false,
+            };
+            wire.clear();
+            req.encode(wire).unwrap();
+            black_box(&wire);
+        }
+    });
+
+    let (encoded_bytes, encoded_nanos) = measure(|wire| {
+        let encoded = EncodedGroupRequest::new(&request, request_id, None, false);
+        for epoch in 1..=ATTEMPTS {
+            let req = encoded.with_epoch(1, epoch);
+            wire.clear();
+            req.encode_raw(wire);
+            black_box(&wire);
+        }
+    });
+
+    println!("group request with a {VALUE_SIZE} bytes value, {ATTEMPTS} attempts per request");
+    println!("clone per attempt: {cloned_bytes:>10} bytes allocated, {cloned_nanos:>10} ns");
+    println!("encoded payload:   {encoded_bytes:>10} bytes allocated, {encoded_nanos:>10} ns");
+}
@@ -13,13 +13,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use sekas_api::server::v1::{DatabaseDesc, NodeCapabilities, TableDesc};
+
 use crate::discovery::StaticServiceDiscovery;
-use crate::rpc::{ConnManager, RootClient, Router};
+use crate::memory::{MemoryBudget, MemoryUsage};
+use crate::read_options::RecentVersion;
+use crate::rpc::{ChannelStats, ConnManager, RootClient, RootStatus, Router, ShardLeaseOptions};
+use crate::schema_cache::SchemaCache;
 use crate::{AppError, AppResult, Database};
 
+const DEFAULT_ROOT_UNAVAILABLE_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_SCHEMA_CACHE_TTL: Duration = Duration::from_secs(30);
+pub(crate) const DEFAULT_FORWARD_THRESHOLD_BYTES: usize = 64 * 1024;
+const DEFAULT_MAX_MESSAGE_BYTES: usize = 64 * 1024 * 1024;
+
 #[derive(Debug, Clone, Default)]
 pub struct ClientOptions {
     /// The duration of connection timeout, an error is issued if establish
@@ -28,6 +39,53 @@ pub struct ClientOptions {
 
     /// The duration of RPC over this client.
     pub timeout: Option<Duration>,
+
+    /// Whether to record the request ids into the raft log, the cluster
+    /// default is used if it is `None`.
+    pub record_request_id: Option<bool>,
+
+    /// The requests smaller than it allow the follower receiving them to
+    /// forward them to the leader, which saves a round trip if the client
+    /// guesses the leader wrong. 64KB by default, `Some(0)` disables the
+    /// forwarding.
+    pub forward_threshold_bytes: Option<usize>,
+
+    /// Whether to disable ordering the replicas by the health of nodes, it
+    /// makes the replicas accessed in a deterministic order.
+    pub disable_node_health: bool,
+
+    /// The duration of root being unreachable before the root-dependent
+    /// operations fail fast with `AppError::RootUnavailable`, 10s by default.
+    pub root_unavailable_timeout: Option<Duration>,
+
+    /// The duration of the cached table descs being served without reading
+    /// from root, 30s by default. The descs are also refreshed by the table
+    /// changed events delivered by the watch stream of root.
+    pub schema_cache_ttl: Option<Duration>,
+
+    /// The options of the shard leases acquired by `Router::lease_shard`, the
+    /// default options are used if it is `None`.
+    pub shard_lease: Option<ShardLeaseOptions>,
+
+    /// The max bytes of the group requests and responses, 64MB by default.
+    /// It is lowered to the limit advertised by the node capabilities once
+    /// they are fetched, the larger requests are rejected with
+    /// `AppError::MessageTooLarge` before they are sent. The nodes split the
+    /// scans into the responses within it. `Some(0)` means unlimited.
+    pub max_message_bytes: Option<usize>,
+
+    /// The max HTTP/2 channels to each node, 2 by default. The channels are
+    /// shared by all group clients, and the requests are balanced across
+    /// them.
+    pub max_channels_per_node: Option<usize>,
+
+    /// The max bytes of the writes staged by the txns and the scan pages
+    /// buffered by the range streams of this client, in total. Staging a write
+    /// or buffering a page beyond it fails fast with
+    /// `AppError::ClientMemoryExhausted`, the memory is released once the txn
+    /// is committed, aborted or dropped, and once the page is yielded. `None`
+    /// means unlimited, see [`SekasClient::memory_usage`].
+    pub memory_budget: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -41,6 +99,11 @@ struct ClientInner {
     root_client: RootClient,
     router: Router,
     conn_manager: ConnManager,
+    recent_version: RecentVersion,
+    schema_cache: SchemaCache,
+    /// The capabilities fetched from a node, see [`crate::TxnOptions`].
+    node_capabilities: Arc<Mutex<Option<NodeCapabilities>>>,
+    memory_budget: MemoryBudget,
 }
 
 impl SekasClient {
@@ -50,11 +113,36 @@ impl SekasClient {
         } else {
             ConnManager::new()
         };
+        conn_manager
+            .set_max_message_bytes(opts.max_message_bytes.unwrap_or(DEFAULT_MAX_MESSAGE_BYTES));
+        if let Some(max_channels) = opts.max_channels_per_node {
+            conn_manager.set_max_channels_per_node(max_channels);
+        }
 
         let discovery = Arc::new(StaticServiceDiscovery::new(addrs.clone()));
-        let root_client = RootClient::new(discovery, conn_manager.clone());
+        let unavailable_timeout =
+            opts.root_unavailable_timeout.unwrap_or(DEFAULT_ROOT_UNAVAILABLE_TIMEOUT);
+        let root_client =
+            RootClient::with_fail_fast(discovery, conn_manager.clone(), unavailable_timeout);
         let router = Router::new(root_client.clone()).await;
-        Ok(Self { inner: Arc::new(ClientInner { opts, root_client, router, conn_manager }) })
+        if let Some(shard_lease) = opts.shard_lease {
+            router.set_shard_lease_options(shard_lease);
+        }
+        let recent_version = RecentVersion::default();
+        let schema_cache =
+            SchemaCache::new(opts.schema_cache_ttl.unwrap_or(DEFAULT_SCHEMA_CACHE_TTL));
+        let memory_budget = MemoryBudget::new(opts.memory_budget.unwrap_or_default());
+        let inner = ClientInner {
+            opts,
+            root_client,
+            router,
+            conn_manager,
+            recent_version,
+            schema_cache,
+            node_capabilities: Arc::default(),
+            memory_budget,
+        };
+        Ok(Self { inner: Arc::new(inner) })
     }
 
     pub fn build(
@@ -63,12 +151,33 @@ impl SekasClient {
         root_client: RootClient,
         conn_manager: ConnManager,
     ) -> Self {
-        SekasClient { inner: Arc::new(ClientInner { opts, root_client, router, conn_manager }) }
+        if let Some(max_message_bytes) = opts.max_message_bytes {
+            conn_manager.set_max_message_bytes(max_message_bytes);
+        }
+        if let Some(max_channels) = opts.max_channels_per_node {
+            conn_manager.set_max_channels_per_node(max_channels);
+        }
+        let recent_version = RecentVersion::default();
+        let schema_cache =
+            SchemaCache::new(opts.schema_cache_ttl.unwrap_or(DEFAULT_SCHEMA_CACHE_TTL));
+        let memory_budget = MemoryBudget::new(opts.memory_budget.unwrap_or_default());
+        let inner = ClientInner {
+            opts,
+            root_client,
+            router,
+            conn_manager,
+            recent_version,
+            schema_cache,
+            node_capabilities: Arc::default(),
+            memory_budget,
+        };
+        SekasClient { inner: Arc::new(inner) }
     }
 
     /// Create a new database if it not exists.
     pub async fn create_database(&self, name: String) -> AppResult<Database> {
-        let db_desc = self.inner.root_client.create_database(name).await?;
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let db_desc = self.inner.root_client.create_database(name, request_id).await?;
         Ok(Database::new(self.clone(), db_desc))
     }
 
@@ -84,6 +193,14 @@ impl SekasClient {
         Ok(databases.into_iter().map(|desc| Database::new(self.clone(), desc)).collect::<Vec<_>>())
     }
 
+    /// List the descs of the databases in the ascending order of name.
+    ///
+    /// The read is linearizable, the databases created before this call are
+    /// returned. A large catalog is read page by page.
+    pub async fn list_databases(&self) -> AppResult<Vec<DatabaseDesc>> {
+        Ok(self.inner.root_client.list_database().await?)
+    }
+
     /// Open a database.
     pub async fn open_database(&self, name: String) -> AppResult<Database> {
         match self.inner.root_client.get_database(name.clone()).await? {
@@ -92,12 +209,65 @@ impl SekasClient {
         }
     }
 
+    /// Clone a table from a consistent snapshot of the source table, the dest
+    /// table could be in the same database with a new name. The dest table is
+    /// returned once it is created, and it is read-only until the data is
+    /// copied in the background.
+    pub async fn clone_table(
+        &self,
+        src_database: String,
+        src_table: String,
+        dest_database: String,
+        dest_table: String,
+    ) -> AppResult<TableDesc> {
+        let resp = self
+            .inner
+            .root_client
+            .clone_table(src_database, src_table, dest_database, dest_table)
+            .await?;
+        let desc = resp
+            .table
+            .ok_or_else(|| AppError::Internal("The table is not set".to_owned().into()))?;
+        self.schema_cache().insert(desc.clone());
+        Ok(desc)
+    }
+
     /// Issue a statement to root.
     #[inline]
     pub async fn handle_statement(&self, statement: &str) -> AppResult<Vec<u8>> {
         Ok(self.inner.root_client.handle_statement(statement).await?)
     }
 
+    /// The availability of root. The data operations are served by the cached
+    /// routing while root is unavailable, and the root-dependent operations
+    /// fail fast with `AppError::RootUnavailable`.
+    #[inline]
+    pub fn root_status(&self) -> RootStatus {
+        self.inner.root_client.root_status()
+    }
+
+    /// Override the log level of the target in this process for `ttl`, the
+    /// effective filter is returned. It requires the process to install the
+    /// reloadable filter, see [`crate::install_log_filter`].
+    ///
+    /// To change the log levels of the servers, issue `CONFIG log.<target>
+    /// <level>` via [`SekasClient::handle_statement`] instead.
+    pub fn set_log_filter(&self, target: &str, level: &str, ttl: Duration) -> AppResult<String> {
+        crate::log_filter::set_log_filter(target, level, ttl)
+    }
+
+    /// The statistics of the HTTP/2 channels to each node, keyed by the node
+    /// address.
+    pub fn channel_stats(&self) -> HashMap<String, ChannelStats> {
+        self.inner.conn_manager.channel_stats()
+    }
+
+    /// The memory reserved by the txns and the range streams of this client,
+    /// by category, see [`ClientOptions::memory_budget`].
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.inner.memory_budget.usage()
+    }
+
     /// Return the options.
     #[inline]
     pub fn options(&self) -> &ClientOptions {
@@ -109,8 +279,9 @@ impl SekasClient {
         &self.inner.root_client
     }
 
+    /// Return the router, eg. to subscribe the routing changes.
     #[inline]
-    pub(crate) fn router(&self) -> &Router {
+    pub fn router(&self) -> &Router {
         &self.inner.router
     }
 
@@ -118,4 +289,24 @@ impl SekasClient {
     pub(crate) fn conn_mgr(&self) -> &ConnManager {
         &self.inner.conn_manager
     }
+
+    #[inline]
+    pub(crate) fn memory_budget(&self) -> &MemoryBudget {
+        &self.inner.memory_budget
+    }
+
+    #[inline]
+    pub(crate) fn recent_version(&self) -> &RecentVersion {
+        &self.inner.recent_version
+    }
+
+    #[inline]
+    pub(crate) fn schema_cache(&self) -> &SchemaCache {
+        &self.inner.schema_cache
+    }
+
+    #[inline]
+    pub(crate) fn node_capabilities(&self) -> &Mutex<Option<NodeCapabilities>> {
+        &self.inner.node_capabilities
+    }
 }
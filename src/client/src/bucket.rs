@@ -0,0 +1,105 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sekas_rock::lexical::lexical_next_boundary;
+use sekas_schema::bucket::escape_bucket;
+
+use crate::{
+    AppResult, Database, DeletePrefixOptions, DeletePrefixProgress, Range, RangeRequest,
+    RangeStream,
+};
+
+/// A logical namespace within a table, see [`Database::bucket`].
+///
+/// The keys of the bucket are prefixed by the escaped bucket name, which is
+/// transparent to the users: the keys passed in and the keys scanned out are
+/// the keys within the bucket. See [`sekas_schema::bucket`] for the encoding.
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    db: Database,
+    table_id: u64,
+    /// The escaped bucket name followed by the separator.
+    prefix: Vec<u8>,
+}
+
+impl Database {
+    /// Open a bucket of the table. The bucket is not persisted, it exists once
+    /// a key is put into it.
+    pub fn bucket(&self, table_id: u64, bucket: &[u8]) -> Bucket {
+        Bucket { db: self.clone(), table_id, prefix: escape_bucket(bucket) }
+    }
+}
+
+impl Bucket {
+    /// The table of the bucket.
+    #[inline]
+    pub fn table_id(&self) -> u64 {
+        self.table_id
+    }
+
+    /// The prefix of the keys of the bucket in the table.
+    #[inline]
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// Get the value of a key of the bucket.
+    pub async fn get(&self, key: Vec<u8>) -> AppResult<Option<Vec<u8>>> {
+        self.db.get(self.table_id, self.key(&key)).await
+    }
+
+    /// Put a key value into the bucket.
+    pub async fn put(&self, key: Vec<u8>, value: Vec<u8>) -> AppResult<()> {
+        self.db.put(self.table_id, self.key(&key), value).await
+    }
+
+    /// Delete a key of the bucket.
+    pub async fn delete(&self, key: Vec<u8>) -> AppResult<()> {
+        self.db.delete(self.table_id, self.key(&key)).await
+    }
+
+    /// Scan a range of the bucket, the range is bounded to the bucket, and the
+    /// scanned user keys are the keys within the bucket.
+    ///
+    /// The `table_id` of the request is ignored.
+    pub async fn range(&self, mut request: RangeRequest) -> AppResult<RangeStream> {
+        request.table_id = self.table_id;
+        request.range = match request.range {
+            Range::Prefix(prefix) => Range::Prefix(self.key(&prefix)),
+            Range::Range { begin, end } => Range::Range {
+                begin: Some(self.key(begin.as_deref().unwrap_or_default())),
+                end: Some(match end {
+                    Some(end) => self.key(&end),
+                    None => lexical_next_boundary(&self.prefix),
+                }),
+            },
+        };
+        let stream = self.db.range(request).await?;
+        Ok(stream.strip_key_prefix(self.prefix.len()))
+    }
+
+    /// Delete all keys of the bucket, see [`Database::delete_prefix`].
+    pub async fn delete_bucket(
+        &self,
+        options: DeletePrefixOptions,
+    ) -> AppResult<DeletePrefixProgress> {
+        self.db.delete_prefix(self.table_id, self.prefix.clone(), options).await
+    }
+
+    fn key(&self, key: &[u8]) -> Vec<u8> {
+        let mut bucket_key = self.prefix.clone();
+        bucket_key.extend_from_slice(key);
+        bucket_key
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use sekas_runtime::time::Instant;
+
+/// The options of a single call, see [`crate::Txn::get_with`],
+/// [`crate::Txn::commit_with`] and [`crate::Database::with_call_options`].
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// The timeout of the call, the requests issued by the call are bounded by
+    /// it, instead of being abandoned in the middle. The default op timeout of
+    /// the txn is used if it is `None`, see
+    /// [`crate::TxnOptions::default_op_timeout`].
+    ///
+    /// Default: None
+    pub timeout: Option<Duration>,
+}
+
+impl CallOptions {
+    pub fn with_timeout(timeout: Duration) -> Self {
+        CallOptions { timeout: Some(timeout) }
+    }
+}
+
+/// The deadline of the call issued now, bounded by the `deadline`.
+pub(crate) fn call_deadline(
+    deadline: Option<Instant>,
+    timeout: Option<Duration>,
+) -> Option<Instant> {
+    let call_deadline = timeout.and_then(|v| Instant::now().checked_add(v));
+    match (deadline, call_deadline) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn call_deadline_is_bounded() {
+        assert_eq!(call_deadline(None, None), None);
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        assert_eq!(call_deadline(Some(deadline), None), Some(deadline));
+        assert_eq!(call_deadline(Some(deadline), Some(Duration::from_secs(10))), Some(deadline));
+
+        let bounded = call_deadline(Some(deadline), Some(Duration::from_millis(100))).unwrap();
+        assert!(bounded < deadline);
+        assert!(call_deadline(None, Some(Duration::from_millis(100))).is_some());
+    }
+}
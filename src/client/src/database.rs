@@ -12,11 +12,55 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
+use sekas_schema::system::txn::TXN_MAX_VERSION;
 
+use crate::call_options::call_deadline;
+use crate::error::TableNotReadyError;
 use crate::range::{RangeRequest, RangeStream};
 use crate::txn::WatchKeyStream;
-use crate::{AppError, AppResult, SekasClient, Txn, WriteBuilder};
+use crate::{
+    AppError, AppResult, CallOptions, GroupClient, ReadOptions, ReadResult, RetryState, RootClient,
+    RouterGroupState, SekasClient, Txn, WriteBuilder,
+};
+
+/// The options of creating a table.
+#[derive(Debug, Clone)]
+pub struct CreateTableOptions {
+    /// The name of the table.
+    pub name: String,
+    /// The properties of the table, such as `replication` and
+    /// `replicas_per_group`, which override the default properties.
+    pub properties: HashMap<String, String>,
+    /// Wait until every shard of the table is served by its group leader
+    /// before returning.
+    pub wait_ready: bool,
+    /// The timeout of waiting the table to be ready.
+    ///
+    /// Default: 30s
+    pub timeout: Duration,
+    /// The id of the request, the table created by the former request with the
+    /// same id is returned, instead of `AlreadyExists`. A random id is used if
+    /// it is not set.
+    pub request_id: Option<String>,
+}
+
+impl CreateTableOptions {
+    pub fn new(name: impl Into<String>) -> Self {
+        CreateTableOptions {
+            name: name.into(),
+            properties: HashMap::default(),
+            wait_ready: false,
+            timeout: Duration::from_secs(30),
+            request_id: None,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Database {
@@ -24,41 +68,270 @@ pub struct Database {
     pub(crate) desc: DatabaseDesc,
     /// Read value by ignore any versions.
     pub(crate) read_without_version: bool,
+    /// The timeout of the calls issued by this handle, see
+    /// [`Database::with_call_options`].
+    pub(crate) timeout: Option<Duration>,
 }
 
 impl Database {
     /// Create a new database instance.
     pub(crate) fn new(client: SekasClient, desc: DatabaseDesc) -> Self {
         let read_without_version = desc.id == sekas_schema::system::db::ID;
-        Database { client, desc, read_without_version }
+        Database { client, desc, read_without_version, timeout: None }
+    }
+
+    /// Return a handle of the database whose calls are bounded by the timeout
+    /// of the options, including the DDLs, the one-shot reads and writes, and
+    /// the ops of the txns began by it, see
+    /// [`crate::TxnOptions::default_op_timeout`].
+    pub fn with_call_options(&self, opts: CallOptions) -> Database {
+        Database { timeout: opts.timeout, ..self.clone() }
+    }
+
+    /// Fail fast if the database, or the table, is observed dropped by the
+    /// router.
+    pub(crate) fn check_dropped(&self, table_id: Option<u64>) -> AppResult<()> {
+        let router = self.client.router();
+        if router.is_database_dropped(self.desc.id) {
+            return Err(AppError::DatabaseDropped {
+                db_id: self.desc.id,
+                name: self.desc.name.clone(),
+            });
+        }
+        if let Some(table_id) = table_id.filter(|id| router.is_table_dropped(*id)) {
+            return Err(AppError::TableDropped { table_id });
+        }
+        Ok(())
+    }
+
+    /// Call the handler in background once the database is observed dropped,
+    /// it is called immediately if the database is already dropped. The
+    /// following calls of this handle return [`AppError::DatabaseDropped`].
+    pub fn on_dropped<F>(&self, handler: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.client.router().on_database_dropped(self.desc.id, Box::new(handler));
+    }
+
+    /// The root client whose admin requests are bounded by the timeout of this
+    /// handle.
+    fn root_client(&self) -> RootClient {
+        self.client.root_client().with_timeout(self.timeout)
     }
 
     /// Create a new table if not exists.
+    ///
+    /// It returns once the table is created, the shards of the table might
+    /// not be ready to serve yet, see [`Database::create_table_with`].
     pub async fn create_table(&self, name: String) -> AppResult<TableDesc> {
-        let desc = self.client.root_client().create_table(self.desc.clone(), name).await?;
+        self.create_table_with(CreateTableOptions::new(name)).await
+    }
+
+    /// Create a new table with options if not exists.
+    pub async fn create_table_with(&self, opts: CreateTableOptions) -> AppResult<TableDesc> {
+        self.check_dropped(None)?;
+        let request_id = opts.request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let desc = self
+            .root_client()
+            .create_table(self.desc.clone(), opts.name, opts.properties, request_id)
+            .await?;
+        self.client.schema_cache().insert(desc.clone());
+        if opts.wait_ready {
+            self.wait_table_ready(desc.id, opts.timeout).await?;
+        }
+        Ok(desc)
+    }
+
+    /// Create a table and write the seed rows in one call, for the
+    /// provisioning flows which should never observe a table without its
+    /// seed.
+    ///
+    /// It isn't an atomic transaction across the catalog and the data, but a
+    /// saga of the states below:
+    ///
+    /// 1. The table is created with the [`PROVISIONING`] mark, and waited to be
+    ///    ready. `SHOW tables` reports it as `provisioning`.
+    /// 2. The seed rows are written by a single txn, so either all or none of
+    ///    them are committed.
+    /// 3. The mark is cleared, the table is `ready`.
+    ///
+    /// If the call fails or the client dies before the mark is cleared, retry
+    /// the call with the same [`CreateTableOptions::request_id`] to complete
+    /// it: the table created by the former call is reused, the seed is written
+    /// again and the mark is cleared. The seed is not written if the table is
+    /// ready already. Otherwise the table still marked after
+    /// `root.provisioning_timeout_sec` is deleted by root, the retry after that
+    /// creates it again.
+    ///
+    /// [`PROVISIONING`]: sekas_schema::property::PROVISIONING
+    pub async fn create_table_with_seed(
+        &self,
+        mut opts: CreateTableOptions,
+        seed: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> AppResult<TableDesc> {
+        use sekas_schema::property::PROVISIONING;
+
+        let name = opts.name.clone();
+        opts.wait_ready = true;
+        opts.request_id.get_or_insert_with(|| uuid::Uuid::new_v4().to_string());
+        opts.properties.insert(PROVISIONING.to_owned(), String::default());
+        let desc = self.create_table_with(opts).await?;
+        if !desc.properties.contains_key(PROVISIONING) {
+            // Completed by the former call.
+            return Ok(desc);
+        }
+
+        if !seed.is_empty() {
+            let mut txn = self.begin_txn();
+            for (key, value) in seed {
+                txn.put(desc.id, WriteBuilder::new(key).ensure_put(value));
+            }
+            txn.commit().await?;
+        }
+        let properties = HashMap::from([(PROVISIONING.to_owned(), String::default())]);
+        self.alter_table(name, properties).await
+    }
+
+    /// Wait until every shard of the table is served by its group leader.
+    ///
+    /// A shard is ready once a raw key read of it is served by the leader,
+    /// which means the leader has applied the logs of its term, verified the
+    /// descriptor and owned the shard.
+    ///
+    /// [`AppError::TableNotReady`] is returned if the table is not ready in
+    /// `timeout`.
+    pub async fn wait_table_ready(&self, table_id: u64, timeout: Duration) -> AppResult<()> {
+        const INTERVAL: Duration = Duration::from_millis(10);
+
+        let deadline = sekas_runtime::time::Instant::now() + timeout;
+        let mut ready_shards = HashSet::new();
+        loop {
+            let shards = self.client.router().find_table_shards(table_id);
+            let mut pending_shards = Vec::new();
+            for (shard, group) in shards.iter() {
+                if ready_shards.contains(&shard.id) {
+                    continue;
+                }
+                let remaining =
+                    deadline.saturating_duration_since(sekas_runtime::time::Instant::now());
+                if let Some(group) = group.clone() {
+                    if self.probe_shard(shard, group, remaining.min(INTERVAL * 10)).await {
+                        ready_shards.insert(shard.id);
+                        continue;
+                    }
+                }
+                pending_shards.push(shard.id);
+            }
+            if !shards.is_empty() && pending_shards.is_empty() {
+                return Ok(());
+            }
+            if sekas_runtime::time::Instant::now() + INTERVAL > deadline {
+                return Err(AppError::TableNotReady(TableNotReadyError {
+                    table_id,
+                    pending_shards,
+                }));
+            }
+            sekas_runtime::time::sleep(INTERVAL).await;
+        }
+    }
+
+    /// Whether the shard is served by the leader of the group in `timeout`.
+    ///
+    /// The shard is probed with a normal read, the raw key state requests might
+    /// be rejected by the servers.
+    async fn probe_shard(
+        &self,
+        shard: &ShardDesc,
+        group: RouterGroupState,
+        timeout: Duration,
+    ) -> bool {
+        let req = Request::Get(ShardGetRequest {
+            shard_id: shard.id,
+            start_version: TXN_MAX_VERSION,
+            user_key: sekas_schema::shard::start_key(shard),
+            ..Default::default()
+        });
+        let mut group_client = GroupClient::new(group, self.client.clone());
+        group_client.set_timeout(timeout);
+        group_client.request(&req).await.is_ok()
+    }
+
+    /// Set the properties of a table, eg. `ttl`, the other properties are
+    /// kept. The updated desc is returned.
+    pub async fn alter_table(
+        &self,
+        name: String,
+        properties: HashMap<String, String>,
+    ) -> AppResult<TableDesc> {
+        self.check_dropped(None)?;
+        let desc = self.root_client().update_table(self.desc.clone(), name, properties).await?;
+        self.client.schema_cache().insert(desc.clone());
         Ok(desc)
     }
 
     /// Delete a specified table.
     pub async fn delete_table(&self, name: String) -> AppResult<()> {
-        self.client.root_client().delete_table(self.desc.clone(), name).await?;
+        self.check_dropped(None)?;
+        self.client.schema_cache().invalidate(self.desc.id, &name);
+        self.root_client().delete_table(self.desc.clone(), name).await?;
         Ok(())
     }
 
     /// List tables in the database.
     pub async fn list_table(&self) -> AppResult<Vec<TableDesc>> {
-        let tables = self.client.root_client().list_table(self.desc.clone()).await?;
+        self.check_dropped(None)?;
+        let tables = self.root_client().list_table(self.desc.clone()).await?;
         Ok(tables)
     }
 
+    /// List the descs of the tables in the database, in the ascending order of
+    /// name.
+    ///
+    /// The read is linearizable, the tables created before this call are
+    /// returned. A large catalog is read page by page.
+    pub async fn list_tables(&self) -> AppResult<Vec<TableDesc>> {
+        self.list_table().await
+    }
+
+    /// Get the desc of the table from root, bypassing the schema cache.
+    /// `None` is returned if the table does not exist.
+    pub async fn get_table(&self, name: &str) -> AppResult<Option<TableDesc>> {
+        self.check_dropped(None)?;
+        let table = self.root_client().get_table(self.desc.clone(), name.to_owned()).await?;
+        if let Some(desc) = &table {
+            self.client.schema_cache().insert(desc.clone());
+        }
+        Ok(table)
+    }
+
     /// Open a table.
+    ///
+    /// The desc is served by the schema cache, it is read from root once the
+    /// cached one is expired, see [`crate::ClientOptions::schema_cache_ttl`].
+    /// The newer desc delivered by the watch stream of root is taken
+    /// immediately.
     pub async fn open_table(&self, name: String) -> AppResult<TableDesc> {
-        match self.client.root_client().get_table(self.desc.clone(), name.clone()).await? {
+        self.check_dropped(None)?;
+        let watched = self.client.router().find_table(self.desc.id, &name);
+        if let Some(desc) = self.client.schema_cache().lookup(self.desc.id, &name, watched) {
+            return Ok(desc);
+        }
+        match self.root_client().get_table(self.desc.clone(), name.clone()).await? {
             None => Err(AppError::NotFound(format!("table {}", name))),
-            Some(co_desc) => Ok(co_desc),
+            Some(co_desc) => {
+                self.client.schema_cache().insert(co_desc.clone());
+                Ok(co_desc)
+            }
         }
     }
 
+    /// Drop the cached desc of the table, so the following
+    /// [`Database::open_table`] reads it from root.
+    pub fn invalidate_table(&self, name: &str) {
+        self.client.schema_cache().invalidate(self.desc.id, name);
+    }
+
     /// A helper function to delete a key.
     #[inline]
     pub async fn delete(&self, table_id: u64, key: Vec<u8>) -> AppResult<()> {
@@ -77,6 +350,19 @@ impl Database {
         Ok(())
     }
 
+    /// A helper function to put a key value, returns the value produced by
+    /// the put, eg. the sum of an add. See [`WriteBuilder::return_new_value`].
+    pub async fn put_returning(
+        &self,
+        table_id: u64,
+        put_req: PutRequest,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let mut txn = Txn::new(self.clone());
+        let index = txn.put_returning(table_id, put_req);
+        let resp = txn.commit().await?;
+        Ok(resp.new_value(index).map(ToOwned::to_owned))
+    }
+
     /// Begin a transcation at the database, which supports serializable
     /// snapshot isolation (WIP...)
     #[inline]
@@ -91,6 +377,44 @@ impl Database {
         txn.get(table_id, key).await
     }
 
+    /// Get the value of a key with the read options, the version it is read at
+    /// is returned along with the value.
+    pub async fn get_with(
+        &self,
+        table_id: u64,
+        key: Vec<u8>,
+        opts: &ReadOptions,
+    ) -> AppResult<ReadResult<Option<Vec<u8>>>> {
+        let txn = self.read_txn(opts).await?;
+        let value = txn.get(table_id, key).await?;
+        Ok(ReadResult { value, read_version: txn.read_version().await? })
+    }
+
+    /// A helper function to get the values of keys, the keys are read at the
+    /// same version.
+    pub async fn get_batch(
+        &self,
+        table_id: u64,
+        keys: Vec<Vec<u8>>,
+    ) -> AppResult<Vec<Option<Vec<u8>>>> {
+        let resp = self.get_batch_with(table_id, keys, &ReadOptions::default()).await?;
+        Ok(resp.value)
+    }
+
+    /// Get the values of keys with the read options, the keys are read at the
+    /// same version, which is returned along with the values.
+    pub async fn get_batch_with(
+        &self,
+        table_id: u64,
+        keys: Vec<Vec<u8>>,
+        opts: &ReadOptions,
+    ) -> AppResult<ReadResult<Vec<Option<Vec<u8>>>>> {
+        let txn = self.read_txn(opts).await?;
+        let gets = keys.into_iter().map(|key| txn.get(table_id, key));
+        let value = futures::future::try_join_all(gets).await?;
+        Ok(ReadResult { value, read_version: txn.read_version().await? })
+    }
+
     /// A helper function to get the raw value (version, tombstone ...) of a
     /// key.
     #[inline]
@@ -99,6 +423,47 @@ impl Database {
         txn.get_raw_value(table_id, key).await
     }
 
+    /// Inspect the state of a key as the server sees it, including the
+    /// tombstone and the unresolved intent, for debugging. It never blocks on
+    /// the intents of other txns.
+    ///
+    /// [`AppError::PermissionDenied`] is returned if it is disabled by the
+    /// servers.
+    pub async fn get_raw(&self, table_id: u64, key: Vec<u8>) -> AppResult<RawKeyState> {
+        self.check_dropped(Some(table_id))?;
+        let deadline = call_deadline(None, self.client.options().timeout);
+        let mut retry_state = RetryState::with_deadline_opt(call_deadline(deadline, self.timeout));
+        loop {
+            match self.get_raw_inner(table_id, &key, retry_state.timeout()).await {
+                Ok(state) => return Ok(state),
+                Err(err) => retry_state.retry(err).await?,
+            }
+        }
+    }
+
+    async fn get_raw_inner(
+        &self,
+        table_id: u64,
+        user_key: &[u8],
+        timeout: Option<Duration>,
+    ) -> crate::Result<RawKeyState> {
+        let (group, shard) = self.client.router().find_shard(table_id, user_key)?;
+        let req = Request::GetRawKey(GetRawKeyRequest {
+            shard_id: shard.id,
+            user_key: user_key.to_owned(),
+        });
+        let mut group_client = GroupClient::new(group, self.client.clone());
+        group_client.set_timeout_opt(timeout);
+        let resp = group_client
+            .request(&req)
+            .await
+            .map_err(|err| err.with_shard(Some(table_id), shard.id))?;
+        match resp {
+            Response::GetRawKey(GetRawKeyResponse { state }) => Ok(state.unwrap_or_default()),
+            _ => Err(crate::Error::Internal("invalid response type, GetRawKey is required".into())),
+        }
+    }
+
     /// A helper function to scan an range in a shard.
     #[inline]
     pub async fn scan(&self, request: ShardScanRequest) -> AppResult<ShardScanResponse> {
@@ -106,6 +471,24 @@ impl Database {
         txn.scan(request).await
     }
 
+    /// Scan an range in a shard with the read options, the version it is read
+    /// at is returned along with the response.
+    pub async fn scan_with(
+        &self,
+        request: ShardScanRequest,
+        opts: &ReadOptions,
+    ) -> AppResult<ReadResult<ShardScanResponse>> {
+        let txn = self.read_txn(opts).await?;
+        let value = txn.scan(request).await?;
+        Ok(ReadResult { value, read_version: txn.read_version().await? })
+    }
+
+    async fn read_txn(&self, opts: &ReadOptions) -> AppResult<Txn> {
+        let mut txn = Txn::new(self.clone());
+        txn.set_read_options(opts).await?;
+        Ok(txn)
+    }
+
     /// A helper function to scan an range.
     pub async fn range(&self, request: RangeRequest) -> AppResult<RangeStream> {
         let txn = Txn::new(self.clone());
@@ -0,0 +1,287 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use futures::stream::FusedStream;
+use log::trace;
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::group_response_union::Response;
+use sekas_api::server::v1::*;
+use sekas_rock::lexical::lexical_next_boundary;
+use sekas_runtime::time::Instant;
+use sekas_schema::shard;
+use tokio::sync::mpsc;
+
+use crate::{AppError, AppResult, Database, GroupClient, RetryState, SekasClient};
+
+/// The max num of keys visited by a single delete prefix request.
+const DELETE_PREFIX_BATCH_SIZE: u64 = 256;
+
+/// The options of [`Database::delete_prefix`].
+#[derive(Debug, Clone, Default)]
+pub struct DeletePrefixOptions {
+    /// The max num of keys deleted per second, `0` means unlimited.
+    ///
+    /// Default: 0
+    pub rate_limit: u64,
+    /// Count the keys to delete without deleting them.
+    pub dry_run: bool,
+}
+
+/// The keys under the prefix of a shard are deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardDeleted {
+    /// The shard serving the deletion. If a shard is split during the deletion,
+    /// the rest keys are deleted via the shard covering them.
+    pub shard_id: u64,
+    /// The num of keys deleted, or to be deleted in dry run. It is an estimate,
+    /// since the keys are counted batch by batch, and a batch could be retried.
+    pub num_deleted: u64,
+}
+
+/// The progress of [`Database::delete_prefix`], a [`ShardDeleted`] is yielded
+/// once the prefix in a shard is deleted.
+///
+/// The shards are deleted by a background task, which is aborted once the
+/// progress is dropped. The stream is terminated after an error is yielded.
+pub struct DeletePrefixProgress {
+    fence_version: u64,
+    delete_handle: Option<tokio::task::JoinHandle<()>>,
+
+    receiver: mpsc::Receiver<crate::Result<ShardDeleted>>,
+    terminated: bool,
+}
+
+struct PrefixDeleter {
+    client: SekasClient,
+    sender: mpsc::Sender<crate::Result<ShardDeleted>>,
+
+    table_id: u64,
+    prefix: Vec<u8>,
+    fence_version: u64,
+    options: DeletePrefixOptions,
+
+    /// The key to resume from, the keys before it are deleted.
+    cursor_key: Vec<u8>,
+    /// The exclusive end key of the prefix.
+    end_key: Vec<u8>,
+    /// The num of deleted keys of the current shard.
+    num_deleted: u64,
+
+    /// The start time and the total deleted keys, to limit the rate.
+    start_at: Instant,
+    total_deleted: u64,
+}
+
+impl Database {
+    /// Delete all keys under the prefix of the table, it is the one-shot way to
+    /// clear a large key range, since the keys are deleted outside of any txn,
+    /// shard by shard.
+    ///
+    /// A fence version is allocated before the deletion. The keys written
+    /// before the fence are deleted, and the keys written concurrently with a
+    /// version above the fence survive. The watchers receive a delete event for
+    /// each deleted key, the same as deleting the keys one by one.
+    pub async fn delete_prefix(
+        &self,
+        table_id: u64,
+        prefix: Vec<u8>,
+        options: DeletePrefixOptions,
+    ) -> AppResult<DeletePrefixProgress> {
+        if prefix.is_empty() {
+            return Err(AppError::InvalidArgument("the prefix to delete is empty".into()));
+        }
+        crate::txn::check_user_table(table_id)?;
+
+        let fence_version = self.client.root_client().alloc_txn_id(1, None).await?;
+        trace!("delete prefix {prefix:?} of table {table_id}, fence version {fence_version}");
+        let (sender, receiver) = mpsc::channel(1);
+        let deleter = PrefixDeleter {
+            client: self.client.clone(),
+            sender,
+            table_id,
+            end_key: lexical_next_boundary(&prefix),
+            cursor_key: prefix.clone(),
+            prefix,
+            fence_version,
+            options,
+            num_deleted: 0,
+            start_at: Instant::now(),
+            total_deleted: 0,
+        };
+        let handle = tokio::spawn(async move {
+            let mut deleter = deleter;
+            deleter.delete().await;
+        });
+        Ok(DeletePrefixProgress {
+            fence_version,
+            delete_handle: Some(handle),
+            receiver,
+            terminated: false,
+        })
+    }
+}
+
+impl DeletePrefixProgress {
+    /// The fence version of the deletion, the keys written at or above it are
+    /// not deleted.
+    pub fn fence_version(&self) -> u64 {
+        self.fence_version
+    }
+
+    /// Wait until all shards are deleted, returns the total num of deleted
+    /// keys.
+    pub async fn wait(mut self) -> crate::Result<u64> {
+        use futures::StreamExt;
+
+        let mut num_deleted = 0;
+        while let Some(shard_deleted) = self.next().await {
+            num_deleted += shard_deleted?.num_deleted;
+        }
+        Ok(num_deleted)
+    }
+
+    fn terminate(&mut self) {
+        self.terminated = true;
+        self.receiver.close();
+        self.abort_deleting();
+    }
+
+    fn abort_deleting(&mut self) {
+        if let Some(handle) = self.delete_handle.take() {
+            if !handle.is_finished() {
+                handle.abort();
+            }
+        }
+    }
+}
+
+impl futures::Stream for DeletePrefixProgress {
+    type Item = crate::Result<ShardDeleted>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.terminated {
+            return Poll::Ready(None);
+        }
+        let item = ready!(this.receiver.poll_recv(cx));
+        if !matches!(item, Some(Ok(_))) {
+            this.terminate();
+        }
+        Poll::Ready(item)
+    }
+}
+
+impl FusedStream for DeletePrefixProgress {
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+impl Drop for DeletePrefixProgress {
+    fn drop(&mut self) {
+        self.abort_deleting();
+    }
+}
+
+impl PrefixDeleter {
+    async fn delete(&mut self) {
+        if let Err(err) = self.delete_inner().await {
+            let _ = self.sender.send(Err(err)).await;
+        }
+    }
+
+    async fn delete_inner(&mut self) -> crate::Result<()> {
+        let mut retry_state = RetryState::default();
+        loop {
+            // The shard might be split or merged during the deletion, so it is
+            // always resolved by the cursor key.
+            let router = self.client.router();
+            let (group_state, shard_desc) = router.find_shard(self.table_id, &self.cursor_key)?;
+            let mut group_client = GroupClient::new(group_state, self.client.clone());
+            if let Err(err) = self.delete_shard(&mut group_client, &shard_desc).await {
+                retry_state.retry(err).await?;
+                continue;
+            }
+
+            retry_state.reset_wait_interval();
+            let shard_deleted = ShardDeleted {
+                shard_id: shard_desc.id,
+                num_deleted: std::mem::take(&mut self.num_deleted),
+            };
+            if self.sender.send(Ok(shard_deleted)).await.is_err() {
+                // The progress is dropped.
+                return Ok(());
+            }
+
+            let shard_end = shard::end_key(&shard_desc);
+            if shard_end.is_empty() || (!self.end_key.is_empty() && self.end_key <= shard_end) {
+                return Ok(());
+            }
+            self.cursor_key = shard_end;
+        }
+    }
+
+    async fn delete_shard(
+        &mut self,
+        group_client: &mut GroupClient,
+        shard_desc: &ShardDesc,
+    ) -> crate::Result<()> {
+        let limit = match self.options.rate_limit {
+            0 => DELETE_PREFIX_BATCH_SIZE,
+            rate_limit => std::cmp::min(rate_limit, DELETE_PREFIX_BATCH_SIZE),
+        };
+        loop {
+            let req = DeletePrefixRequest {
+                shard_id: shard_desc.id,
+                prefix: self.prefix.clone(),
+                fence_version: self.fence_version,
+                start_key: Some(self.cursor_key.clone()),
+                limit,
+                dry_run: self.options.dry_run,
+            };
+            let resp = match group_client.request(&Request::DeletePrefix(req)).await? {
+                Response::DeletePrefix(resp) => resp,
+                e => {
+                    return Err(crate::Error::Internal(
+                        format!("Response::DeletePrefix is required, but got {e:?}").into(),
+                    ));
+                }
+            };
+            self.num_deleted += resp.num_deleted;
+            self.throttle(resp.num_deleted).await;
+            match resp.resume_key {
+                Some(resume_key) => self.cursor_key = resume_key,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Wait until the rate of the deleted keys is under the limit.
+    async fn throttle(&mut self, num_deleted: u64) {
+        let rate_limit = self.options.rate_limit;
+        if rate_limit == 0 || self.options.dry_run {
+            return;
+        }
+        self.total_deleted += num_deleted;
+        let expected = Duration::from_secs_f64(self.total_deleted as f64 / rate_limit as f64);
+        let elapsed = self.start_at.elapsed();
+        if elapsed < expected {
+            sekas_runtime::time::sleep(expected - elapsed).await;
+        }
+    }
+}
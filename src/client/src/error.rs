@@ -14,8 +14,10 @@
 // limitations under the License.
 
 use std::error::Error as StdError;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, SystemTime};
 
-use sekas_api::server::v1::{GroupDesc, ReplicaDesc, RootDesc, Value};
+use sekas_api::server::v1::{GroupDesc, GroupRelocatedHint, ReplicaDesc, RootDesc, Value};
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub type AppResult<T> = std::result::Result<T, AppError>;
@@ -31,15 +33,126 @@ pub enum AppError {
     #[error("invalid argument {0}")]
     InvalidArgument(String),
 
-    #[error("deadline exceeded {0}")]
-    DeadlineExceeded(String),
+    /// The operation is not finished before the deadline, the context is
+    /// attached if the operation has been issued to the replicas of a group.
+    #[error("deadline exceeded {0}{}", display_context(.1))]
+    DeadlineExceeded(String, Option<Box<ErrorContext>>),
+
+    #[error("{0} is exhausted")]
+    ResourceExhausted(String),
+
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
 
     #[error("cas condition {1} not satisfied, operation index {0}")]
     CasFailed(u64, u64, Option<Value>),
 
+    #[error("write batch failed, operation index {}", .0.failed_index)]
+    WriteBatch(WriteBatchError),
+
+    #[error("table {} is not ready, pending shards {:?}", .0.table_id, .0.pending_shards)]
+    TableNotReady(TableNotReadyError),
+
     #[error("the txn is conflict with others")]
     TxnConflict,
 
+    #[error("insufficient balance {balance} to transfer {amount}, the floor is {floor}")]
+    InsufficientBalance { balance: i64, amount: i64, floor: i64 },
+
+    #[error("the prefix {prefix:?} is not empty, key {key:?} exists")]
+    PrefixNotEmpty { prefix: Vec<u8>, key: Vec<u8> },
+
+    #[error("invalid json {0}")]
+    InvalidJson(String),
+
+    /// The read version is beneath the GC watermark, the versions it requires
+    /// might have been dropped.
+    #[error("read version {version} is beneath the gc watermark {gc_watermark}")]
+    VersionTooOld { version: u64, gc_watermark: u64 },
+
+    /// The existing value is not encoded as the type required by the typed
+    /// atomic operation, eg. `WriteBuilder::add`. The txn is aborted without
+    /// writing anything.
+    #[error("the existing value of {actual_len} bytes is not a valid {expected}")]
+    ValueTypeMismatch { expected: String, actual_len: u64 },
+
+    #[error("data corrupted {0}")]
+    DataCorrupted(String),
+
+    /// The writes of the txn exceed the limits, the txn is rejected before
+    /// any of them is sent. See `TxnOptions`.
+    #[error(
+        "txn too large, {write_count} writes of {write_bytes} bytes, the limits are \
+         {max_write_count} writes and {max_write_bytes} bytes"
+    )]
+    TxnTooLarge { write_count: u64, write_bytes: u64, max_write_count: u64, max_write_bytes: u64 },
+
+    /// A chunk of the txn committed by `TxnOverflow::AutoChunk` is failed. The
+    /// first `committed_writes` writes, in the order of the deletes first and
+    /// then the puts, are committed by the former chunks, the rest are not.
+    #[error("txn chunk {failed_chunk} failed, {committed_writes} writes are committed: {source}")]
+    TxnChunkFailed {
+        failed_chunk: usize,
+        committed_writes: usize,
+        /// The version of the last committed chunk, 0 if none is committed.
+        committed_version: u64,
+        source: Box<AppError>,
+    },
+
+    /// The commit is not finished before the deadline. The txn is aborted if
+    /// the outcome is known, otherwise it might be committed, the outcome could
+    /// be read from the txn record of `txn_id`, see
+    /// `TxnStateTable::get_txn_record`. The `txn_id` is 0 if the txn is not
+    /// started yet.
+    #[error("commit txn {txn_id} timed out, outcome unknown: {outcome_unknown}")]
+    CommitTimedOut { txn_id: u64, outcome_unknown: bool },
+
+    /// Root is unreachable, the root-dependent operations, such as DDL, fail
+    /// fast until it is recovered. The data operations are served by the
+    /// cached routing.
+    #[error("root is unavailable since {since:?}")]
+    RootUnavailable { since: SystemTime },
+
+    /// The database of the handle is dropped, the handle fails fast once the
+    /// deletion is observed by the router. A database recreated under the
+    /// same name has a new id, it must be opened again.
+    #[error("database {name} ({db_id}) is dropped")]
+    DatabaseDropped { db_id: u64, name: String },
+
+    /// The table is dropped, or the database of it is dropped, see
+    /// [`AppError::DatabaseDropped`].
+    #[error("table {table_id} is dropped")]
+    TableDropped { table_id: u64 },
+
+    /// The encoded request or response exceeds the max message bytes
+    /// negotiated by the client and the nodes, see
+    /// `ClientOptions::max_message_bytes`. It fails on every replica, so it
+    /// is never retried. The scans are split automatically, so it is only
+    /// returned if a single value or write is too large.
+    #[error("message of {size} bytes exceeds the limit {limit} bytes")]
+    MessageTooLarge { size: u64, limit: u64 },
+
+    /// The DDL exceeds a quota of the database, eg. the max number of tables
+    /// of a database. `limit` is the name of the quota, the quota is freed
+    /// once the tables are deleted.
+    #[error("quota {limit} of {value} is exceeded")]
+    QuotaExceeded { limit: String, value: u64 },
+
+    /// The shard of the txn table can't be split, merged or moved by the
+    /// admin requests. It is never retried.
+    #[error("shard {shard_id} of the txn table can't be split, merged or moved")]
+    TxnShardFenced { shard_id: u64 },
+
+    /// The memory budget of the client is exhausted, the buffer of the
+    /// `category` requesting `requested` bytes is not allocated. It fails fast
+    /// instead of waiting for the memory to be released, see
+    /// `ClientOptions::memory_budget`.
+    #[error(
+        "client memory exhausted, {category} requests {requested} bytes, {used} of {budget} \
+         bytes are used"
+    )]
+    ClientMemoryExhausted { category: String, requested: u64, used: u64, budget: u64 },
+
     #[error("network: {0}")]
     Network(tonic::Status),
 
@@ -47,13 +160,105 @@ pub enum AppError {
     Internal(Box<dyn StdError + Send + Sync + 'static>),
 }
 
+/// The result of an operation of a write batch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpResult {
+    /// The operation is accepted, it takes effect only if the whole batch is
+    /// committed.
+    Applied,
+    /// The condition `cond_index` of the operation is not satisfied.
+    CasFailed {
+        cond_index: u64,
+        /// The current value of the target key.
+        prev_value: Option<Value>,
+    },
+    /// The operation is rejected, with the reason.
+    Rejected(String),
+}
+
+/// The error of a write batch, none of the operations of the batch is applied.
+#[derive(Debug, Clone)]
+pub struct WriteBatchError {
+    /// The index of the first failed operation.
+    pub failed_index: usize,
+    /// The results of operations reported by the servers, which are aligned
+    /// with the operations of the batch: the deletes first, then the puts.
+    pub per_op: Vec<OpResult>,
+}
+
+/// The error of a table which is not ready before the deadline.
+#[derive(Debug, Clone)]
+pub struct TableNotReadyError {
+    pub table_id: u64,
+    /// The shards not served by their group leaders yet, it is empty if the
+    /// shards of the table are not known yet.
+    pub pending_shards: Vec<u64>,
+}
+
+/// The context of a failed operation, it is collected only if the operation is
+/// failed, to help the application locating the failure.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub group_id: Option<u64>,
+    pub shard_id: Option<u64>,
+    pub table_id: Option<u64>,
+    /// The nodes attempted, in the order of the first attempt, with the
+    /// status of the last attempt to each node.
+    pub attempts: Vec<NodeAttempt>,
+    /// The number of retries before failing.
+    pub retries: u64,
+    pub elapsed: Duration,
+}
+
+/// The last status of issuing an operation to a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeAttempt {
+    pub node_id: u64,
+    pub status: String,
+}
+
+impl ErrorContext {
+    /// Record the status of the attempt to the node, the former status of the
+    /// same node is replaced.
+    pub(crate) fn record_attempt(&mut self, node_id: u64, status: String) {
+        match self.attempts.iter_mut().find(|attempt| attempt.node_id == node_id) {
+            Some(attempt) => attempt.status = status,
+            None => self.attempts.push(NodeAttempt { node_id, status }),
+        }
+    }
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(group_id) = self.group_id {
+            write!(f, "group {group_id} ")?;
+        }
+        if let Some(table_id) = self.table_id {
+            write!(f, "table {table_id} ")?;
+        }
+        if let Some(shard_id) = self.shard_id {
+            write!(f, "shard {shard_id} ")?;
+        }
+        write!(f, "nodes [")?;
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            write!(f, "{sep}{}: {}", attempt.node_id, attempt.status)?;
+        }
+        write!(f, "], {} retries in {:?}", self.retries, self.elapsed)
+    }
+}
+
+fn display_context(context: &Option<Box<ErrorContext>>) -> String {
+    context.as_ref().map(|ctx| format!(" ({ctx})")).unwrap_or_default()
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("invalid argument {0}")]
     InvalidArgument(String),
 
-    #[error("deadline exceeded {0}")]
-    DeadlineExceeded(String),
+    #[error("deadline exceeded {0}{}", display_context(.1))]
+    DeadlineExceeded(String, Option<Box<ErrorContext>>),
 
     #[error("{0} already exists")]
     AlreadyExists(String),
@@ -64,18 +269,54 @@ pub enum Error {
     #[error("{0} is exhausted")]
     ResourceExhausted(String),
 
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+
     #[error("cas condition {1} not satisfied, operation index {0}")]
     CasFailed(u64, u64, Option<Value>),
 
     #[error("the txn is conflict with others")]
     TxnConflict,
 
+    #[error("invalid json {0}")]
+    InvalidJson(String),
+
+    #[error("read version {0} is beneath the gc watermark {1}")]
+    VersionTooOld(u64, u64),
+
+    #[error("the existing value of {actual_len} bytes is not a valid {expected}")]
+    ValueTypeMismatch { expected: String, actual_len: u64 },
+
+    #[error("message of {size} bytes exceeds the limit {limit} bytes")]
+    MessageTooLarge { size: u64, limit: u64 },
+
+    #[error("quota {limit} of {value} is exceeded")]
+    QuotaExceeded { limit: String, value: u64 },
+
+    #[error("shard {0} of the txn table can't be split, merged or moved")]
+    TxnShardFenced(u64),
+
+    #[error(
+        "client memory exhausted, {category} requests {requested} bytes, {used} of {budget} \
+         bytes are used"
+    )]
+    ClientMemoryExhausted { category: String, requested: u64, used: u64, budget: u64 },
+
     #[error("group epoch not match")]
     EpochNotMatch(GroupDesc),
 
     #[error("group {0} not found")]
     GroupNotFound(u64),
 
+    /// The group is not found on the node, but the node known where it went.
+    #[error("group {0} is relocated")]
+    GroupRelocated(u64, GroupRelocatedHint),
+
+    /// The replica is unable to serve the read locally, eg. a stale read
+    /// replica, the read is retried on the other replicas.
+    #[error("replica {1} of group {0} is not ready: {2}")]
+    ReplicaNotReady(u64, u64, String),
+
     #[error("not root leader")]
     NotRootLeader(RootDesc, u64, Option<ReplicaDesc>),
 
@@ -90,8 +331,13 @@ pub enum Error {
 
     /// This indicates that the `GroupClient` has not been able to access the
     /// group leader after retries many times.
-    #[error("group {0} not accessable")]
-    GroupNotAccessable(u64),
+    #[error("group not accessable, {1}")]
+    GroupNotAccessable(u64, Box<ErrorContext>),
+
+    /// Root has been unreachable since the time, the root requests are
+    /// rejected by the client until it is recovered.
+    #[error("root is unavailable since {0:?}")]
+    RootUnavailable(SystemTime),
 
     #[error("transport {0}")]
     Transport(tonic::Status),
@@ -114,10 +360,11 @@ impl From<tonic::Status> for Error {
             Code::Ok => panic!("invalid argument"),
             Code::InvalidArgument => Error::InvalidArgument(status.message().into()),
             Code::Cancelled if status.message().contains("Timeout expired") => {
-                Error::DeadlineExceeded(status.message().into())
+                Error::DeadlineExceeded(status.message().into(), None)
             }
             Code::AlreadyExists => Error::AlreadyExists(status.message().into()),
-            Code::ResourceExhausted => Error::ResourceExhausted(status.message().into()),
+            Code::ResourceExhausted => from_resource_exhausted(status),
+            Code::PermissionDenied => from_permission_denied(status),
             Code::NotFound => Error::NotFound(status.message().into()),
             Code::Internal => Error::Internal(status.message().into()),
             Code::Unknown => from_source_or_details(status),
@@ -140,8 +387,14 @@ impl From<sekas_api::server::v1::Error> for Error {
         let detail = &err.details[0];
         let msg = detail.message.clone();
         match detail.detail.as_ref().and_then(|u| u.value.clone()) {
-            Some(Value::GroupNotFound(v)) => Error::GroupNotFound(v.group_id),
+            Some(Value::GroupNotFound(v)) => match v.hint {
+                Some(hint) => Error::GroupRelocated(v.group_id, hint),
+                None => Error::GroupNotFound(v.group_id),
+            },
             Some(Value::NotLeader(v)) => Error::NotLeader(v.group_id, v.term, v.leader),
+            Some(Value::ReplicaNotReady(v)) => {
+                Error::ReplicaNotReady(v.group_id, v.replica_id, v.reason)
+            }
             Some(Value::NotRoot(v)) => {
                 Error::NotRootLeader(v.root.unwrap_or_default(), v.term, v.leader)
             }
@@ -149,6 +402,18 @@ impl From<sekas_api::server::v1::Error> for Error {
             Some(Value::StatusCode(v)) => Status::new(v.into(), msg).into(),
             Some(Value::CasFailed(v)) => Error::CasFailed(v.index, v.cond_index, v.prev_value),
             Some(Value::TxnConflict(_)) => Error::TxnConflict,
+            Some(Value::InvalidJson(v)) => Error::InvalidJson(v.reason),
+            Some(Value::VersionTooOld(v)) => Error::VersionTooOld(v.version, v.gc_watermark),
+            Some(Value::ValueTypeMismatch(v)) => {
+                Error::ValueTypeMismatch { expected: v.expected, actual_len: v.actual_len }
+            }
+            Some(Value::MessageTooLarge(v)) => {
+                Error::MessageTooLarge { size: v.size, limit: v.limit }
+            }
+            Some(Value::QuotaExceeded(v)) => {
+                Error::QuotaExceeded { limit: v.limit, value: v.value }
+            }
+            Some(Value::TxnShardFenced(v)) => Error::TxnShardFenced(v.shard_id),
             _ => Status::internal(format!("unknown error detail, msg: {msg}")).into(),
         }
     }
@@ -160,17 +425,56 @@ impl From<tokio::task::JoinError> for Error {
     }
 }
 
+impl Error {
+    /// Attach the table and shard to the context of the error, if it has one.
+    pub(crate) fn with_shard(mut self, table_id: Option<u64>, shard_id: u64) -> Self {
+        if let Error::DeadlineExceeded(_, Some(ctx)) | Error::GroupNotAccessable(_, ctx) = &mut self
+        {
+            ctx.table_id = ctx.table_id.or(table_id);
+            ctx.shard_id = Some(shard_id);
+        }
+        self
+    }
+}
+
+impl AppError {
+    /// The context of the failed operation, for logging and diagnosing.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            AppError::DeadlineExceeded(_, ctx) => ctx.as_deref(),
+            AppError::TxnChunkFailed { source, .. } => source.context(),
+            _ => None,
+        }
+    }
+}
+
 impl From<Error> for AppError {
     fn from(err: Error) -> Self {
         match err {
             Error::InvalidArgument(v) => AppError::InvalidArgument(v),
-            Error::DeadlineExceeded(v) => AppError::DeadlineExceeded(v),
+            Error::DeadlineExceeded(v, ctx) => AppError::DeadlineExceeded(v, ctx),
             Error::NotFound(v) => AppError::NotFound(v),
             Error::AlreadyExists(v) => AppError::AlreadyExists(v),
+            Error::ResourceExhausted(v) => AppError::ResourceExhausted(v),
+            Error::PermissionDenied(v) => AppError::PermissionDenied(v),
             Error::CasFailed(index, cond_index, prev_value) => {
                 AppError::CasFailed(index, cond_index, prev_value)
             }
             Error::TxnConflict => AppError::TxnConflict,
+            Error::InvalidJson(v) => AppError::InvalidJson(v),
+            Error::VersionTooOld(version, gc_watermark) => {
+                AppError::VersionTooOld { version, gc_watermark }
+            }
+            Error::ValueTypeMismatch { expected, actual_len } => {
+                AppError::ValueTypeMismatch { expected, actual_len }
+            }
+            Error::MessageTooLarge { size, limit } => AppError::MessageTooLarge { size, limit },
+            Error::QuotaExceeded { limit, value } => AppError::QuotaExceeded { limit, value },
+            Error::TxnShardFenced(shard_id) => AppError::TxnShardFenced { shard_id },
+            Error::ClientMemoryExhausted { category, requested, used, budget } => {
+                AppError::ClientMemoryExhausted { category, requested, used, budget }
+            }
+            Error::RootUnavailable(since) => AppError::RootUnavailable { since },
             Error::Internal(v) => AppError::Internal(v),
 
             Error::Transport(status) => AppError::Network(status),
@@ -178,9 +482,10 @@ impl From<Error> for AppError {
             Error::Rpc(status) => panic!("unknown error: {status:?}"),
 
             Error::EpochNotMatch(_)
-            | Error::ResourceExhausted(_)
             | Error::GroupNotFound(_)
-            | Error::GroupNotAccessable(_)
+            | Error::GroupRelocated(..)
+            | Error::ReplicaNotReady(..)
+            | Error::GroupNotAccessable(..)
             | Error::NotRootLeader(..)
             | Error::NotLeader(..) => unreachable!("convert err {err:?} to `AppError`"),
         }
@@ -195,9 +500,29 @@ impl From<AppError> for tonic::Status {
             AppError::NotFound(msg) => Status::not_found(msg),
             AppError::AlreadyExists(msg) => Status::already_exists(msg),
             AppError::InvalidArgument(msg) => Status::invalid_argument(msg),
-            AppError::DeadlineExceeded(msg) => Status::deadline_exceeded(msg),
+            AppError::DeadlineExceeded(..) => Status::deadline_exceeded(err.to_string()),
+            AppError::ResourceExhausted(msg) => Status::resource_exhausted(msg),
+            AppError::PermissionDenied(msg) => Status::permission_denied(msg),
             AppError::CasFailed(_, _, _) => todo!("not supported"),
+            AppError::WriteBatch(_) => Status::aborted(err.to_string()),
+            AppError::TableNotReady(_) => Status::deadline_exceeded(err.to_string()),
             AppError::TxnConflict => todo!("not supported"),
+            AppError::InsufficientBalance { .. } => Status::failed_precondition(err.to_string()),
+            AppError::PrefixNotEmpty { .. } => Status::failed_precondition(err.to_string()),
+            AppError::InvalidJson(msg) => Status::invalid_argument(msg),
+            AppError::VersionTooOld { .. } => Status::out_of_range(err.to_string()),
+            AppError::ValueTypeMismatch { .. } => Status::failed_precondition(err.to_string()),
+            AppError::DataCorrupted(msg) => Status::data_loss(msg),
+            AppError::TxnTooLarge { .. } => Status::invalid_argument(err.to_string()),
+            AppError::TxnChunkFailed { .. } => Status::aborted(err.to_string()),
+            AppError::CommitTimedOut { .. } => Status::deadline_exceeded(err.to_string()),
+            AppError::RootUnavailable { .. } => Status::unavailable(err.to_string()),
+            AppError::DatabaseDropped { .. } => Status::not_found(err.to_string()),
+            AppError::TableDropped { .. } => Status::not_found(err.to_string()),
+            AppError::MessageTooLarge { .. } => Status::resource_exhausted(err.to_string()),
+            AppError::QuotaExceeded { .. } => Status::resource_exhausted(err.to_string()),
+            AppError::TxnShardFenced { .. } => Status::permission_denied(err.to_string()),
+            AppError::ClientMemoryExhausted { .. } => Status::resource_exhausted(err.to_string()),
             AppError::Network(status) => status, // as proxy
             AppError::Internal(err) => Status::internal(err.to_string()),
         }
@@ -298,6 +623,34 @@ pub fn from_source_or_details(status: tonic::Status) -> Error {
     from_source(status)
 }
 
+/// The message too large errors are reported with `ResourceExhausted`, the
+/// details carry the size and the limit of the message.
+fn from_resource_exhausted(status: tonic::Status) -> Error {
+    use prost::Message;
+    use sekas_api::server::v1;
+
+    if !status.details().is_empty() {
+        if let Ok(err) = v1::Error::decode(status.details()) {
+            return err.into();
+        }
+    }
+    Error::ResourceExhausted(status.message().into())
+}
+
+/// The txn shard fenced errors are reported with `PermissionDenied`, the
+/// details carry the fenced shard.
+fn from_permission_denied(status: tonic::Status) -> Error {
+    use prost::Message;
+    use sekas_api::server::v1;
+
+    if !status.details().is_empty() {
+        if let Ok(err) = v1::Error::decode(status.details()) {
+            return err.into();
+        }
+    }
+    Error::PermissionDenied(status.message().into())
+}
+
 pub fn from_source(status: tonic::Status) -> Error {
     if retryable_rpc_err(&status) {
         Error::Connect(status)
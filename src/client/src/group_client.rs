@@ -15,19 +15,21 @@
 
 use std::collections::HashMap;
 use std::future::Future;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use futures::StreamExt;
 use log::{debug, trace, warn};
 use sekas_api::server::v1::group_request_union::Request;
 use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
+use sekas_runtime::time::Instant;
 use sekas_schema::shard;
 use tonic::{Code, Status};
 
+use crate::app_client::DEFAULT_FORWARD_THRESHOLD_BYTES;
 use crate::metrics::*;
-use crate::rpc::{NodeClient, RouterGroupState, RpcTimeout};
-use crate::{record_latency_opt, Error, Result, SekasClient};
+use crate::rpc::{EncodedGroupRequest, GroupStreaming, NodeClient, RouterGroupState, RpcTimeout};
+use crate::{record_latency_opt, Error, ErrorContext, Result, SekasClient};
 
 #[derive(Clone, Debug, Default)]
 struct InvokeOpt<'a> {
@@ -44,6 +46,17 @@ struct InvokeOpt<'a> {
     ignore_transport_error: bool,
 }
 
+/// The preference of replicas to serve the read requests.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadPreference {
+    /// The reads are served by the leader.
+    #[default]
+    Leader,
+    /// The reads are served by a read replica if the group has one, and fall
+    /// back to the leader if the read replica is unable to serve them.
+    PreferReadReplica,
+}
+
 #[derive(Clone, Debug, Default)]
 struct InvokeContext {
     group_id: u64,
@@ -68,6 +81,7 @@ pub struct GroupClient {
     epoch: u64,
     leader_state: Option<(u64, u64)>,
     replicas: Vec<ReplicaDesc>,
+    read_preference: ReadPreference,
 
     // Cache the access node id to avoid polling again.
     access_node_id: Option<u64>,
@@ -75,6 +89,10 @@ pub struct GroupClient {
 
     /// Node id to node client.
     node_clients: HashMap<u64, NodeClient>,
+
+    /// The failed attempts of the current request, it is only filled when the
+    /// request is failed.
+    error_ctx: ErrorContext,
 }
 
 impl GroupClient {
@@ -89,7 +107,9 @@ impl GroupClient {
             leader_state: None,
             access_node_id: None,
             replicas: Vec::default(),
+            read_preference: ReadPreference::Leader,
             next_access_index: 0,
+            error_ctx: ErrorContext::default(),
         }
     }
 
@@ -114,6 +134,13 @@ impl GroupClient {
         self.timeout = timeout;
     }
 
+    /// Apply the read preference to the requests issued via this client, it
+    /// should only be used for the read requests.
+    pub fn set_read_preference(&mut self, read_preference: ReadPreference) {
+        self.read_preference = read_preference;
+        self.apply_read_preference();
+    }
+
     async fn invoke<F, O, V>(&mut self, op: F) -> Result<V>
     where
         F: Fn(InvokeContext, NodeClient) -> O,
@@ -132,26 +159,45 @@ impl GroupClient {
             self.initial_group_state()?;
         }
         self.next_access_index = 0;
+        self.apply_node_health();
 
-        let deadline = self.timeout.take().map(|duration| Instant::now() + duration);
+        let start = Instant::now();
+        let deadline = self.timeout.take().map(|duration| start + duration);
         let mut index = 0;
         let group_id = self.group_id;
+        self.error_ctx.attempts.clear();
         while let Some((node_id, client)) = self.recommend_client() {
             trace!("group {group_id} issue rpc request with index {index} to node {node_id}");
             index += 1;
-            let ctx = InvokeContext { group_id, epoch: self.epoch, timeout: self.timeout };
+            // The rpc is bounded by the rest of the timeout, so the server gives up the
+            // request once the caller does.
+            let timeout = deadline.map(|v| v.saturating_duration_since(Instant::now()));
+            let ctx = InvokeContext { group_id, epoch: self.epoch, timeout };
             match op(ctx, client).await {
                 Err(status) => self.apply_status(status, &opt)?,
-                Ok(s) => return Ok(s),
+                Ok(s) => {
+                    self.record_node_health(node_id, true);
+                    return Ok(s);
+                }
             };
             if deadline.map(|v| v.elapsed() > Duration::ZERO).unwrap_or_default() {
-                return Err(Error::DeadlineExceeded("issue rpc".to_owned()));
+                let ctx = self.take_error_context(index - 1, start);
+                return Err(Error::DeadlineExceeded("issue rpc".to_owned(), Some(ctx)));
             }
             GROUP_CLIENT_RETRY_TOTAL.inc();
         }
 
         trace!("group {group_id} issue rpc failed, group is not accessable");
-        Err(Error::GroupNotAccessable(group_id))
+        let ctx = self.take_error_context(index.saturating_sub(1), start);
+        Err(Error::GroupNotAccessable(group_id, ctx))
+    }
+
+    fn take_error_context(&mut self, retries: u64, start: Instant) -> Box<ErrorContext> {
+        let mut ctx = std::mem::take(&mut self.error_ctx);
+        ctx.group_id = Some(self.group_id);
+        ctx.retries = retries;
+        ctx.elapsed = start.elapsed();
+        Box::new(ctx)
     }
 
     fn recommend_client(&mut self) -> Option<(u64, NodeClient)> {
@@ -168,11 +214,11 @@ impl GroupClient {
     fn initial_group_state(&mut self) -> Result<()> {
         debug_assert_eq!(self.epoch, 0);
         debug_assert!(self.replicas.is_empty());
-        let group_state = self
-            .client
-            .router()
-            .find_group(self.group_id)
-            .map_err(|_| Error::GroupNotAccessable(self.group_id))?;
+        let group_id = self.group_id;
+        let group_state = self.client.router().find_group(group_id).map_err(|_| {
+            let ctx = ErrorContext { group_id: Some(group_id), ..Default::default() };
+            Error::GroupNotAccessable(group_id, Box::new(ctx))
+        })?;
         self.apply_group_state(group_state);
         Ok(())
     }
@@ -194,6 +240,39 @@ impl GroupClient {
             );
             move_node_to_first_element(&mut self.replicas, node_id);
         }
+        self.apply_read_preference();
+    }
+
+    /// Move a read replica to the first element, so it is accessed before the
+    /// leader.
+    fn apply_read_preference(&mut self) {
+        if self.read_preference != ReadPreference::PreferReadReplica {
+            return;
+        }
+        if let Some(idx) =
+            self.replicas.iter().position(|r| r.role == ReplicaRole::ReadReplica as i32)
+        {
+            self.replicas.swap(0, idx);
+        }
+    }
+
+    /// Order the replicas by the health of nodes, so that the degraded nodes
+    /// are accessed only if the others are not accessible.
+    fn apply_node_health(&mut self) {
+        if self.client.options().disable_node_health {
+            return;
+        }
+        let node_health = self.client.conn_mgr().node_health();
+        node_health.sort_replicas(&mut self.replicas);
+        if self.access_node_id.map(|id| node_health.is_degraded(id)).unwrap_or_default() {
+            self.access_node_id = None;
+        }
+    }
+
+    fn record_node_health(&self, node_id: u64, success: bool) {
+        if !self.client.options().disable_node_health {
+            self.client.conn_mgr().node_health().record(node_id, success);
+        }
     }
 
     /// Return the next node id, skip the leader node.
@@ -223,17 +302,28 @@ impl GroupClient {
                 }
                 Err(err) => {
                     warn!("connect to node {node_id} address {addr}: {err:?}");
+                    self.error_ctx.record_attempt(node_id, format!("connect {addr}: {err}"));
                 }
             }
         } else {
             warn!("not found the address of node {node_id}");
+            self.error_ctx.record_attempt(node_id, "address not found".to_owned());
         }
 
         None
     }
 
     fn apply_status(&mut self, status: tonic::Status, opt: &InvokeOpt<'_>) -> Result<()> {
-        match Error::from(status) {
+        let err = Error::from(status);
+        if let Some(node_id) = self.access_node_id {
+            let success = !matches!(
+                err,
+                Error::Connect(_) | Error::Transport(_) | Error::DeadlineExceeded(..)
+            );
+            self.record_node_health(node_id, success);
+            self.error_ctx.record_attempt(node_id, err.to_string());
+        }
+        match err {
             Error::GroupNotFound(_) => {
                 debug!(
                     "group {} issue rpc to {}: group not found",
@@ -243,6 +333,19 @@ impl GroupClient {
                 self.access_node_id = None;
                 Ok(())
             }
+            Error::GroupRelocated(_, hint) => {
+                self.apply_group_relocated_hint(hint);
+                Ok(())
+            }
+            Error::ReplicaNotReady(_, replica_id, reason) => {
+                debug!(
+                    "group {} issue rpc to {}: replica {replica_id} is not ready: {reason}",
+                    self.group_id,
+                    self.access_node_id.unwrap_or_default(),
+                );
+                self.access_node_id = None;
+                Ok(())
+            }
             Error::NotLeader(_, term, leader_desc) => {
                 trace!(
                     "group {} not leader, new leader {leader_desc:?}, term {term}",
@@ -275,10 +378,28 @@ impl GroupClient {
                 Ok(())
             }
             Error::EpochNotMatch(group_desc) => self.apply_epoch_not_match_status(group_desc, opt),
+            Error::MessageTooLarge { size, limit } => {
+                // The message exceeds the limit on every replica, so the replicas are not
+                // rotated.
+                debug!(
+                    "group {} issue rpc to {}: message of {size} bytes exceeds the limit {limit}",
+                    self.group_id,
+                    self.access_node_id.unwrap_or_default(),
+                );
+                Err(Error::MessageTooLarge { size, limit })
+            }
             e => {
                 if !matches!(
                     e,
-                    Error::CasFailed(_, _, _) | Error::InvalidArgument(_) | Error::TxnConflict
+                    Error::CasFailed(_, _, _)
+                        | Error::InvalidArgument(_)
+                        | Error::DeadlineExceeded(..)
+                        | Error::TxnConflict
+                        | Error::InvalidJson(_)
+                        | Error::PermissionDenied(_)
+                        | Error::TxnShardFenced(_)
+                        | Error::VersionTooOld(..)
+                        | Error::ValueTypeMismatch { .. }
                 ) {
                     warn!(
                         "group {} issue rpc to {}: epoch {} with unknown error {e:?}",
@@ -315,6 +436,30 @@ impl GroupClient {
         }
     }
 
+    /// Refresh the replicas from the hint, and retry them from the first one. A
+    /// staled hint is ignored, the next replica is accessed as usual.
+    fn apply_group_relocated_hint(&mut self, hint: GroupRelocatedHint) {
+        debug!(
+            "group {} issue rpc to {}: group relocated, epoch {} replicas {:?}, local epoch {}",
+            self.group_id,
+            self.access_node_id.unwrap_or_default(),
+            hint.epoch,
+            hint.replicas,
+            self.epoch,
+        );
+        self.access_node_id = None;
+        if hint.epoch <= self.epoch || hint.replicas.is_empty() {
+            return;
+        }
+
+        self.epoch = hint.epoch;
+        self.leader_state = None;
+        self.replicas = hint.replicas;
+        self.next_access_index = 0;
+        self.apply_read_preference();
+        self.apply_node_health();
+    }
+
     fn apply_epoch_not_match_status(
         &mut self,
         group_desc: GroupDesc,
@@ -340,7 +485,14 @@ impl GroupClient {
             group_desc.epoch,
         );
 
-        if opt.request.map(|r| !is_executable(&group_desc, r)).unwrap_or_default() {
+        let verdict = opt.request.map(|r| check_executable(&group_desc, r));
+        if let Some(verdict) = verdict {
+            debug!(
+                "group {} check request against the descriptor of epoch {}: {verdict:?}",
+                self.group_id, group_desc.epoch,
+            );
+        }
+        if !verdict.map(Executability::is_executable).unwrap_or(true) {
             // The target group would not execute the specified request.
             Err(Error::EpochNotMatch(group_desc))
         } else {
@@ -355,17 +507,39 @@ impl GroupClient {
 
 impl GroupClient {
     pub async fn request(&mut self, request: &Request) -> Result<Response> {
+        let request_id = uuid::Uuid::new_v4().to_string();
+        self.request_with_id(request, &request_id).await
+    }
+
+    /// Issue the request with the specified request id, the id is recorded
+    /// into the raft log of the group if it is required, and shared by the
+    /// retries of this request.
+    pub async fn request_with_id(
+        &mut self,
+        request: &Request,
+        request_id: &str,
+    ) -> Result<Response> {
+        let opts = self.client.options();
+        let record_request_id = opts.record_request_id;
+        let forward_threshold_bytes =
+            opts.forward_threshold_bytes.unwrap_or(DEFAULT_FORWARD_THRESHOLD_BYTES);
+        let allow_forward = request.encoded_len() < forward_threshold_bytes;
+        let is_scan = matches!(request, Request::Scan(_));
+        // The payload is encoded once, the attempts only differ in the epoch.
+        let encoded =
+            EncodedGroupRequest::new(request, request_id, record_request_id, allow_forward);
         let op = |ctx: InvokeContext, client: NodeClient| {
             let latency = take_group_request_metrics(request);
-            let req = GroupRequest {
-                group_id: ctx.group_id,
-                epoch: ctx.epoch,
-                request: Some(GroupRequestUnion { request: Some(request.clone()) }),
-            };
+            let req = encoded.with_epoch(ctx.group_id, ctx.epoch);
             async move {
                 record_latency_opt!(latency);
+                if is_scan {
+                    let frames =
+                        client.encoded_group_request(RpcTimeout::new(ctx.timeout, req)).await?;
+                    return Self::collect_scan_frames(frames).await;
+                }
                 client
-                    .unary_group_request(RpcTimeout::new(ctx.timeout, req))
+                    .unary_encoded_group_request(RpcTimeout::new(ctx.timeout, req))
                     .await
                     .and_then(Self::group_response)
             }
@@ -385,19 +559,18 @@ impl GroupClient {
         user_key: &[u8],
         version: u64,
     ) -> Result<impl futures::Stream<Item = Result<WatchKeyResponse, tonic::Status>>> {
+        let request = Request::WatchKey(WatchKeyRequest {
+            group_id: self.group_id,
+            shard_id,
+            key: user_key.to_vec(),
+            version,
+        });
         let op = |ctx: InvokeContext, client: NodeClient| {
-            let watch_key_req = WatchKeyRequest {
-                group_id: ctx.group_id,
-                shard_id,
-                key: user_key.to_vec(),
-                version,
-            };
             let req = GroupRequest {
                 group_id: ctx.group_id,
                 epoch: ctx.epoch,
-                request: Some(GroupRequestUnion {
-                    request: Some(Request::WatchKey(watch_key_req)),
-                }),
+                request: Some(GroupRequestUnion { request: Some(request.clone()) }),
+                ..Default::default()
             };
             async move {
                 Ok(client.group_request(RpcTimeout::new(ctx.timeout, req)).await?.map(|stream| {
@@ -409,10 +582,72 @@ impl GroupClient {
             }
         };
 
-        let opt = InvokeOpt { request: None, accurate_epoch: false, ignore_transport_error: false };
+        let opt = InvokeOpt {
+            request: Some(&request),
+            accurate_epoch: false,
+            ignore_transport_error: false,
+        };
         self.invoke_with_opt(op, opt).await
     }
 
+    /// Issue the scan request, the frames of the response are yielded once they
+    /// arrive. All frames except the last one set `has_more`.
+    ///
+    /// The first frame is received before returning, so that the errors of it
+    /// are retried like the other requests.
+    pub async fn scan_frames(
+        &mut self,
+        req: &ShardScanRequest,
+    ) -> Result<impl futures::Stream<Item = Result<ShardScanResponse, tonic::Status>>> {
+        let request = Request::Scan(req.clone());
+        let encoded = EncodedGroupRequest::new(&request, "", None, false);
+        let op = |ctx: InvokeContext, client: NodeClient| {
+            let latency = take_group_request_metrics(&request);
+            let req = encoded.with_epoch(ctx.group_id, ctx.epoch);
+            async move {
+                record_latency_opt!(latency);
+                let mut frames =
+                    client.encoded_group_request(RpcTimeout::new(ctx.timeout, req)).await?;
+                let first_frame = frames
+                    .message()
+                    .await?
+                    .ok_or_else(|| Status::internal("group response stream is empty"))
+                    .and_then(Self::scan_frame)?;
+                let following_frames = frames.map(|frame| frame.and_then(Self::scan_frame));
+                Ok(futures::stream::once(async move { Ok(first_frame) }).chain(following_frames))
+            }
+        };
+
+        let opt = InvokeOpt {
+            request: Some(&request),
+            accurate_epoch: false,
+            ignore_transport_error: false,
+        };
+        self.invoke_with_opt(op, opt).await
+    }
+
+    /// Collect the frames of a scan response into one response.
+    async fn collect_scan_frames(mut frames: GroupStreaming) -> Result<Response, Status> {
+        let mut scan_resp: Option<ShardScanResponse> = None;
+        while let Some(frame) = frames.message().await? {
+            let frame = Self::scan_frame(frame)?;
+            let resp = scan_resp.get_or_insert_with(ShardScanResponse::default);
+            resp.data.extend(frame.data);
+            resp.sealed.extend(frame.sealed);
+            resp.has_more = frame.has_more;
+        }
+        scan_resp
+            .map(Response::Scan)
+            .ok_or_else(|| Status::internal("group response stream is empty".to_owned()))
+    }
+
+    fn scan_frame(frame: GroupResponse) -> Result<ShardScanResponse, Status> {
+        match Self::group_response(frame)? {
+            Response::Scan(resp) => Ok(resp),
+            _ => Err(Status::internal("ShardScanResponse is required".to_owned())),
+        }
+    }
+
     fn group_response(resp: GroupResponse) -> Result<Response, Status> {
         use prost::Message;
 
@@ -430,11 +665,12 @@ impl GroupClient {
 // safely.
 impl GroupClient {
     pub async fn create_shard(&mut self, desc: &ShardDesc) -> Result<()> {
+        let encoded = EncodedGroupRequest::from(GroupRequest::create_shard(0, 0, desc.clone()));
         let op = |ctx: InvokeContext, client: NodeClient| {
-            let desc = desc.to_owned();
-            let req = GroupRequest::create_shard(ctx.group_id, ctx.epoch, desc);
+            let req = encoded.with_epoch(ctx.group_id, ctx.epoch);
             async move {
-                let resp = client.unary_group_request(req).await.and_then(Self::group_response)?;
+                let resp =
+                    client.unary_encoded_group_request(req).await.and_then(Self::group_response)?;
                 match resp {
                     Response::CreateShard(_) => Ok(()),
                     _ => Err(Status::internal("invalid response type, CreateShard is required")),
@@ -523,17 +759,34 @@ impl GroupClient {
         self.invoke(op).await
     }
 
+    pub async fn add_read_replica(&mut self, replica: u64, node: u64) -> Result<()> {
+        let op = |ctx: InvokeContext, client: NodeClient| {
+            let req = GroupRequest::add_read_replica(ctx.group_id, ctx.epoch, replica, node);
+            async move {
+                let resp = client.unary_group_request(req).await.and_then(Self::group_response)?;
+                match resp {
+                    Response::ChangeReplicas(_) => Ok(()),
+                    _ => Err(Status::internal("invalid response type, ChangeReplicas is required")),
+                }
+            }
+        };
+        self.invoke(op).await
+    }
+
     pub async fn accept_shard(
         &mut self,
         src_group: u64,
         src_epoch: u64,
         shard: &ShardDesc,
     ) -> Result<()> {
+        let encoded = EncodedGroupRequest::from(GroupRequest::accept_shard(
+            0, 0, src_group, src_epoch, shard,
+        ));
         let op = |ctx: InvokeContext, client: NodeClient| {
-            let req =
-                GroupRequest::accept_shard(ctx.group_id, ctx.epoch, src_group, src_epoch, shard);
+            let req = encoded.with_epoch(ctx.group_id, ctx.epoch);
             async move {
-                let resp = client.unary_group_request(req).await.and_then(Self::group_response)?;
+                let resp =
+                    client.unary_encoded_group_request(req).await.and_then(Self::group_response)?;
                 match resp {
                     Response::AcceptShard(_) => Ok(()),
                     _ => Err(Status::internal("invalid response type, AcceptShard is required")),
@@ -551,16 +804,18 @@ impl GroupClient {
         new_shard_id: u64,
         split_key: Option<Vec<u8>>,
     ) -> Result<()> {
+        let encoded = EncodedGroupRequest::from(GroupRequest::split_shard(
+            0,
+            0,
+            old_shard_id,
+            new_shard_id,
+            split_key,
+        ));
         let op = |ctx: InvokeContext, client: NodeClient| {
-            let req = GroupRequest::split_shard(
-                ctx.group_id,
-                ctx.epoch,
-                old_shard_id,
-                new_shard_id,
-                split_key.clone(),
-            );
+            let req = encoded.with_epoch(ctx.group_id, ctx.epoch);
             async move {
-                let resp = client.unary_group_request(req).await.and_then(Self::group_response)?;
+                let resp =
+                    client.unary_encoded_group_request(req).await.and_then(Self::group_response)?;
                 match resp {
                     Response::SplitShard(_) => Ok(()),
                     _ => Err(Status::internal("invalid response type, SplitShard is required")),
@@ -588,6 +843,22 @@ impl GroupClient {
             InvokeOpt { accurate_epoch: true, ignore_transport_error: true, ..Default::default() };
         self.invoke_with_opt(op, opt).await
     }
+
+    pub async fn remove_shard(&mut self, shard_id: u64) -> Result<()> {
+        let op = |ctx: InvokeContext, client: NodeClient| {
+            let req = GroupRequest::remove_shard(ctx.group_id, ctx.epoch, shard_id);
+            async move {
+                let resp = client.unary_group_request(req).await.and_then(Self::group_response)?;
+                match resp {
+                    Response::RemoveShard(_) => Ok(()),
+                    _ => Err(Status::internal("invalid response type, RemoveShard is required")),
+                }
+            }
+        };
+        let opt =
+            InvokeOpt { accurate_epoch: true, ignore_transport_error: true, ..Default::default() };
+        self.invoke_with_opt(op, opt).await
+    }
 }
 
 // Moving shard related functions, which will be retried at:
@@ -610,6 +881,22 @@ impl GroupClient {
         self.invoke_with_opt(op, opt).await
     }
 
+    pub async fn cancel_move(&mut self, desc: &MoveShardDesc) -> Result<()> {
+        let op = |_: InvokeContext, client: NodeClient| async move {
+            client.cancel_move(desc.clone()).await
+        };
+        let opt = InvokeOpt { ignore_transport_error: true, ..Default::default() };
+        self.invoke_with_opt(op, opt).await
+    }
+
+    pub async fn abort_move(&mut self, desc: &MoveShardDesc) -> Result<()> {
+        let op = |_: InvokeContext, client: NodeClient| async move {
+            client.abort_move(desc.clone()).await
+        };
+        let opt = InvokeOpt { ignore_transport_error: true, ..Default::default() };
+        self.invoke_with_opt(op, opt).await
+    }
+
     pub async fn forward(&mut self, req: &ForwardRequest) -> Result<ForwardResponse> {
         let op = |_: InvokeContext, client: NodeClient| {
             let cloned_req = req.clone();
@@ -618,62 +905,155 @@ impl GroupClient {
         let opt = InvokeOpt { accurate_epoch: true, ..Default::default() };
         self.invoke_with_opt(op, opt).await
     }
+
+    pub async fn ingest(&mut self, req: &IngestRequest) -> Result<()> {
+        let op = |_: InvokeContext, client: NodeClient| {
+            let cloned_req = req.clone();
+            async move { client.ingest(cloned_req).await }
+        };
+        let opt = InvokeOpt { ignore_transport_error: true, ..Default::default() };
+        self.invoke_with_opt(op, opt).await
+    }
 }
 
 #[inline]
 fn is_read_only_request(request: &Request) -> bool {
-    matches!(request, Request::Get(_) | Request::Scan(_))
+    matches!(request, Request::Get(_) | Request::GetRawKey(_) | Request::Scan(_))
+}
+
+/// Whether the group of a newer descriptor could execute the request, which is
+/// checked before retrying the request after `EpochNotMatch`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Executability {
+    Executable,
+    /// The target shard, or the part of it covering the keys of the request, is
+    /// not served by the group.
+    NotExecutable {
+        missing_shard: u64,
+    },
+    /// The request doesn't target any shard of the group, it is treated as
+    /// executable and left to the group to judge.
+    Unknown,
+}
+
+impl Executability {
+    #[inline]
+    fn is_executable(self) -> bool {
+        !matches!(self, Executability::NotExecutable { .. })
+    }
 }
 
-fn is_executable(descriptor: &GroupDesc, request: &Request) -> bool {
+fn check_executable(descriptor: &GroupDesc, request: &Request) -> Executability {
+    use Executability::*;
+
     match request {
-        Request::Get(req) => is_target_shard_exists(descriptor, req.shard_id, &req.user_key),
+        Request::Get(req) => check_target_shard(descriptor, req.shard_id, &req.user_key),
+        Request::GetRawKey(req) => check_target_shard(descriptor, req.shard_id, &req.user_key),
+        Request::Scan(req) => check_scan(descriptor, req),
         Request::Write(req) => {
-            is_all_target_shard_exists(descriptor, req.shard_id, &req.deletes, &req.puts)
+            check_all_target_shard(descriptor, req.shard_id, &req.deletes, &req.puts)
+        }
+        Request::DeletePrefix(req) => {
+            // The deleting resumes from the start key in the next shard.
+            let start = req.start_key.as_deref().unwrap_or(&req.prefix);
+            check_target_shard(descriptor, req.shard_id, start)
         }
+        Request::WatchKey(req) => check_target_shard(descriptor, req.shard_id, &req.key),
         Request::WriteIntent(WriteIntentRequest { write: Some(write), shard_id, .. }) => {
             match write {
                 write_intent_request::Write::Delete(delete) => {
-                    is_target_shard_exists(descriptor, *shard_id, &delete.key)
+                    check_target_shard(descriptor, *shard_id, &delete.key)
                 }
                 write_intent_request::Write::Put(put) => {
-                    is_target_shard_exists(descriptor, *shard_id, &put.key)
+                    check_target_shard(descriptor, *shard_id, &put.key)
                 }
             }
         }
-        Request::CommitIntent(req) => {
-            is_target_shard_exists(descriptor, req.shard_id, &req.user_key)
-        }
-        Request::ClearIntent(req) => {
-            is_target_shard_exists(descriptor, req.shard_id, &req.user_key)
-        }
-        _ => false,
+        Request::WriteIntent(WriteIntentRequest { write: None, .. }) => Unknown,
+        Request::CommitIntent(req) => check_target_shard(descriptor, req.shard_id, &req.user_key),
+        Request::ClearIntent(req) => check_target_shard(descriptor, req.shard_id, &req.user_key),
+        Request::CheckPrefixEmpty(req) => check_target_shard(descriptor, req.shard_id, &req.prefix),
+        Request::SplitShard(req) => check_shard_exists(descriptor, req.old_shard_id),
+        Request::MergeShard(req) => match check_shard_exists(descriptor, req.left_shard_id) {
+            Executable => check_shard_exists(descriptor, req.right_shard_id),
+            verdict => verdict,
+        },
+        Request::RemoveShard(req) => check_shard_exists(descriptor, req.shard_id),
+        Request::CreateShard(_)
+        | Request::ChangeReplicas(_)
+        | Request::AcceptShard(_)
+        | Request::Transfer(_)
+        | Request::MoveReplicas(_) => Unknown,
+    }
+}
+
+fn check_shard_exists(desc: &GroupDesc, shard_id: u64) -> Executability {
+    if desc.shards.iter().any(|s| s.id == shard_id) {
+        Executability::Executable
+    } else {
+        Executability::NotExecutable { missing_shard: shard_id }
     }
 }
 
-fn is_target_shard_exists(desc: &GroupDesc, shard_id: u64, key: &[u8]) -> bool {
+fn check_target_shard(desc: &GroupDesc, shard_id: u64, key: &[u8]) -> Executability {
     // TODO(walter) support migrate meta.
-    desc.shards
-        .iter()
-        .find(|s| s.id == shard_id)
-        .map(|s| shard::belong_to(s, key))
-        .unwrap_or_default()
+    let exists = desc.shards.iter().find(|s| s.id == shard_id).map(|s| shard::belong_to(s, key));
+    if exists.unwrap_or_default() {
+        Executability::Executable
+    } else {
+        Executability::NotExecutable { missing_shard: shard_id }
+    }
 }
 
-fn is_all_target_shard_exists(
+fn check_all_target_shard(
     descriptor: &GroupDesc,
     shard_id: u64,
     deletes: &[DeleteRequest],
     puts: &[PutRequest],
-) -> bool {
-    if !deletes.iter().all(|delete| is_target_shard_exists(descriptor, shard_id, &delete.key)) {
-        return false;
+) -> Executability {
+    let keys = deletes.iter().map(|d| &d.key).chain(puts.iter().map(|p| &p.key));
+    for key in keys {
+        let verdict = check_target_shard(descriptor, shard_id, key);
+        if !verdict.is_executable() {
+            return verdict;
+        }
     }
+    Executability::Executable
+}
 
-    if !puts.iter().all(|put| is_target_shard_exists(descriptor, shard_id, &put.key)) {
-        return false;
+/// The scanners resume from the cursor of the last page, and move to the next
+/// (or the previous) shard once the shard is scanned, so the scan is executable
+/// as long as the shard still covers the cursor.
+fn check_scan(desc: &GroupDesc, req: &ShardScanRequest) -> Executability {
+    if let Some(prefix) = &req.prefix {
+        return check_target_shard(desc, req.shard_id, prefix);
+    }
+    if !req.reverse {
+        let start = req.start_key.as_deref().unwrap_or_default();
+        return check_target_shard(desc, req.shard_id, start);
+    }
+
+    // The cursor of a reverse scan is the end key, the scan starts from the end
+    // of the last shard if it is not specified.
+    let covered =
+        desc.shards.iter().find(|s| s.id == req.shard_id).and_then(|s| s.range.as_ref()).map(
+            |range| match req.end_key.as_deref() {
+                None => range.end.is_empty(),
+                Some(end) if req.exclude_end_key => {
+                    range.start.as_slice() < end
+                        && (range.end.is_empty() || end <= range.end.as_slice())
+                }
+                Some(end) => {
+                    range.start.as_slice() <= end
+                        && (range.end.is_empty() || end < range.end.as_slice())
+                }
+            },
+        );
+    if covered.unwrap_or_default() {
+        Executability::Executable
+    } else {
+        Executability::NotExecutable { missing_shard: req.shard_id }
     }
-    true
 }
 
 fn move_node_to_first_element(replicas: &mut [ReplicaDesc], node_id: u64) {
@@ -695,3 +1075,244 @@ fn move_replica_to_first_element(replicas: &mut Vec<ReplicaDesc>, replica: Repli
         replicas.swap(0, idx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHARD_ID: u64 = 1;
+
+    fn descriptor(shards: Vec<ShardDesc>) -> GroupDesc {
+        GroupDesc { id: 1, epoch: 2, shards, ..Default::default() }
+    }
+
+    fn shard(start: &[u8], end: &[u8]) -> ShardDesc {
+        ShardDesc::with_range(SHARD_ID, 1, start.to_vec(), end.to_vec())
+    }
+
+    fn put(key: &[u8]) -> PutRequest {
+        PutRequest { key: key.to_vec(), ..Default::default() }
+    }
+
+    fn non_empty(key: &[u8]) -> Option<&[u8]> {
+        if key.is_empty() {
+            None
+        } else {
+            Some(key)
+        }
+    }
+
+    /// Scan the range `[start, end)`, the empty key means unbounded.
+    fn scan(start: &[u8], end: &[u8]) -> ShardScanRequest {
+        ShardScanRequest {
+            shard_id: SHARD_ID,
+            start_key: non_empty(start).map(ToOwned::to_owned),
+            end_key: non_empty(end).map(ToOwned::to_owned),
+            exclude_end_key: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn check_executable_by_request_kind() {
+        use Executability::*;
+
+        let missing = NotExecutable { missing_shard: SHARD_ID };
+        let with_shard = descriptor(vec![shard(b"b", b"m")]);
+        let without_shard = descriptor(vec![ShardDesc::with_range(2, 1, b"b".to_vec(), vec![])]);
+        let write_intent = |write| WriteIntentRequest {
+            shard_id: SHARD_ID,
+            write: Some(write),
+            ..Default::default()
+        };
+
+        // The request and the verdict of the descriptor with the target shard.
+        let cases = vec![
+            (
+                Request::Get(ShardGetRequest {
+                    shard_id: SHARD_ID,
+                    user_key: b"c".to_vec(),
+                    ..Default::default()
+                }),
+                Executable,
+            ),
+            (
+                Request::Get(ShardGetRequest {
+                    shard_id: SHARD_ID,
+                    user_key: b"x".to_vec(),
+                    ..Default::default()
+                }),
+                missing,
+            ),
+            (
+                Request::GetRawKey(GetRawKeyRequest {
+                    shard_id: SHARD_ID,
+                    user_key: b"c".to_vec(),
+                }),
+                Executable,
+            ),
+            (Request::Scan(scan(b"c", b"m")), Executable),
+            // The scan moves to the next shard once the shard is scanned.
+            (Request::Scan(scan(b"c", b"x")), Executable),
+            (Request::Scan(scan(b"c", b"")), Executable),
+            (Request::Scan(scan(b"", b"d")), missing),
+            (Request::Scan(scan(b"x", b"")), missing),
+            (
+                Request::Scan(ShardScanRequest { exclude_end_key: false, ..scan(b"c", b"m") }),
+                Executable,
+            ),
+            (Request::Scan(ShardScanRequest { reverse: true, ..scan(b"a", b"m") }), Executable),
+            (Request::Scan(ShardScanRequest { reverse: true, ..scan(b"", b"d") }), Executable),
+            (Request::Scan(ShardScanRequest { reverse: true, ..scan(b"", b"b") }), missing),
+            (Request::Scan(ShardScanRequest { reverse: true, ..scan(b"", b"x") }), missing),
+            (Request::Scan(ShardScanRequest { reverse: true, ..scan(b"c", b"") }), missing),
+            (
+                Request::Scan(ShardScanRequest {
+                    reverse: true,
+                    exclude_end_key: false,
+                    ..scan(b"", b"m")
+                }),
+                missing,
+            ),
+            (
+                Request::Scan(ShardScanRequest { prefix: Some(b"cc".to_vec()), ..scan(b"", b"") }),
+                Executable,
+            ),
+            (
+                Request::Write(ShardWriteRequest {
+                    shard_id: SHARD_ID,
+                    deletes: vec![DeleteRequest { key: b"c".to_vec(), ..Default::default() }],
+                    puts: vec![put(b"d")],
+                }),
+                Executable,
+            ),
+            (
+                Request::Write(ShardWriteRequest {
+                    shard_id: SHARD_ID,
+                    deletes: vec![],
+                    puts: vec![put(b"d"), put(b"x")],
+                }),
+                missing,
+            ),
+            (
+                Request::DeletePrefix(DeletePrefixRequest {
+                    shard_id: SHARD_ID,
+                    prefix: b"c".to_vec(),
+                    ..Default::default()
+                }),
+                Executable,
+            ),
+            (
+                Request::DeletePrefix(DeletePrefixRequest {
+                    shard_id: SHARD_ID,
+                    prefix: b"".to_vec(),
+                    ..Default::default()
+                }),
+                missing,
+            ),
+            (
+                Request::DeletePrefix(DeletePrefixRequest {
+                    shard_id: SHARD_ID,
+                    prefix: b"".to_vec(),
+                    start_key: Some(b"c".to_vec()),
+                    ..Default::default()
+                }),
+                Executable,
+            ),
+            (
+                Request::CheckPrefixEmpty(CheckPrefixEmptyRequest {
+                    shard_id: SHARD_ID,
+                    prefix: b"c".to_vec(),
+                    ..Default::default()
+                }),
+                Executable,
+            ),
+            (
+                Request::WatchKey(WatchKeyRequest {
+                    shard_id: SHARD_ID,
+                    key: b"c".to_vec(),
+                    ..Default::default()
+                }),
+                Executable,
+            ),
+            (
+                Request::WriteIntent(write_intent(write_intent_request::Write::Put(put(b"c")))),
+                Executable,
+            ),
+            (
+                Request::WriteIntent(write_intent(write_intent_request::Write::Delete(
+                    DeleteRequest { key: b"c".to_vec(), ..Default::default() },
+                ))),
+                Executable,
+            ),
+            (
+                Request::CommitIntent(CommitIntentRequest {
+                    shard_id: SHARD_ID,
+                    user_key: b"c".to_vec(),
+                    ..Default::default()
+                }),
+                Executable,
+            ),
+            (
+                Request::ClearIntent(ClearIntentRequest {
+                    shard_id: SHARD_ID,
+                    user_key: b"c".to_vec(),
+                    ..Default::default()
+                }),
+                Executable,
+            ),
+            (
+                Request::SplitShard(SplitShardRequest {
+                    old_shard_id: SHARD_ID,
+                    new_shard_id: 3,
+                    split_key: None,
+                }),
+                Executable,
+            ),
+            (
+                Request::MergeShard(MergeShardRequest {
+                    left_shard_id: SHARD_ID,
+                    right_shard_id: 3,
+                }),
+                NotExecutable { missing_shard: 3 },
+            ),
+            (Request::RemoveShard(RemoveShardRequest { shard_id: SHARD_ID }), Executable),
+        ];
+        for (request, expect) in cases {
+            let verdict = check_executable(&with_shard, &request);
+            assert_eq!(verdict, expect, "{request:?}");
+            // The descriptor without the target shard never executes it.
+            let verdict = check_executable(&without_shard, &request);
+            assert!(!verdict.is_executable(), "{request:?} {verdict:?}");
+        }
+
+        // The requests don't target any shard are left to the group.
+        let cases = vec![
+            Request::CreateShard(CreateShardRequest::default()),
+            Request::ChangeReplicas(ChangeReplicasRequest::default()),
+            Request::AcceptShard(AcceptShardRequest::default()),
+            Request::Transfer(TransferRequest::default()),
+            Request::MoveReplicas(MoveReplicasRequest::default()),
+            Request::WriteIntent(WriteIntentRequest::default()),
+        ];
+        for request in cases {
+            for desc in [&with_shard, &without_shard] {
+                assert_eq!(check_executable(desc, &request), Unknown, "{request:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn check_executable_by_unbounded_shard() {
+        let desc = descriptor(vec![shard(b"", b"")]);
+        let scan = Request::Scan(ShardScanRequest { shard_id: SHARD_ID, ..Default::default() });
+        assert_eq!(check_executable(&desc, &scan), Executability::Executable);
+        let delete_prefix = Request::DeletePrefix(DeletePrefixRequest {
+            shard_id: SHARD_ID,
+            prefix: vec![0xFF],
+            start_key: Some(vec![0xFF, 0x01]),
+            ..Default::default()
+        });
+        assert_eq!(check_executable(&desc, &delete_prefix), Executability::Executable);
+    }
+}
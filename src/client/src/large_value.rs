@@ -0,0 +1,354 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Store the values which are too large to be written as a single value.
+//!
+//! A large value is split into chunks, which are stored under the derived keys
+//! `key\0chunk\0{generation}{index}`. The manifest of the chunks is stored
+//! under the key itself, and it is the only thing the readers consult to
+//! locate the chunks.
+//!
+//! Each write allocates a new generation, so the chunks are never modified
+//! once they are written. A value fits in a transaction is written in one
+//! transaction. Otherwise, the chunks are staged by several transactions
+//! first, and the value becomes visible once the manifest is flipped to the new
+//! generation. The chunks of the previous generation are removed after the
+//! flip, and the staged chunks of a failed or cancelled write are left to
+//! [`Database::gc_large`].
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use log::warn;
+use sekas_runtime::time::timestamp_nanos;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{AppError, AppResult, Database, Range, RangeRequest, WriteBuilder};
+
+const MANIFEST_MAGIC: &[u8; 8] = b"SEKASLV1";
+const MANIFEST_LEN: usize = MANIFEST_MAGIC.len() + 8 + 4 + 8 + 4;
+const CHUNK_INFIX: &[u8] = b"\0chunk\0";
+
+/// The max number of chunks removed in a single transaction.
+const MAX_REMOVED_CHUNKS_PER_TXN: usize = 256;
+
+/// The options of writing large values.
+#[derive(Debug, Clone)]
+pub struct LargeValueOptions {
+    /// The size of each chunk.
+    ///
+    /// Default: 512KiB
+    pub chunk_size: usize,
+    /// The max bytes of chunks written by a single transaction. The value no
+    /// larger than it is written in one transaction, otherwise the chunks are
+    /// staged by multiple transactions.
+    ///
+    /// Default: 2MiB
+    pub max_txn_bytes: usize,
+    /// The chunks not referenced by the manifest are removed by
+    /// [`Database::gc_large`] once they have been staged for this long. It
+    /// should be larger than the duration of any write in progress.
+    ///
+    /// Default: 10min
+    pub stale_staging_timeout: Duration,
+}
+
+/// The manifest of a large value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Manifest {
+    generation: u64,
+    num_chunks: u32,
+    total_size: u64,
+    checksum: u32,
+}
+
+impl Default for LargeValueOptions {
+    fn default() -> Self {
+        LargeValueOptions {
+            chunk_size: 512 * 1024,
+            max_txn_bytes: 2 * 1024 * 1024,
+            stale_staging_timeout: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+impl Database {
+    /// Write a large value read from `reader`. The previous value of the key
+    /// is replaced atomically, the readers see either the previous or the new
+    /// value.
+    ///
+    /// The staged chunks are removed if the reader fails. If the write is
+    /// cancelled, the staged chunks are left to [`Database::gc_large`].
+    pub async fn put_large<R>(
+        &self,
+        table_id: u64,
+        key: Vec<u8>,
+        mut reader: R,
+        opts: &LargeValueOptions,
+    ) -> AppResult<()>
+    where
+        R: AsyncRead + Unpin,
+    {
+        if opts.chunk_size == 0 {
+            return Err(AppError::InvalidArgument("the chunk size is zero".to_owned()));
+        }
+
+        let generation = timestamp_nanos();
+        let mut hasher = crc32fast::Hasher::new();
+        let mut total_size = 0;
+        let mut num_chunks = 0;
+        let mut num_staged = 0;
+        let mut pending = Vec::new();
+        let mut pending_bytes = 0;
+        loop {
+            let chunk = match read_chunk(&mut reader, opts.chunk_size).await {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    self.remove_chunks(table_id, &key, generation, 0..num_staged).await;
+                    return Err(AppError::Internal(Box::new(err)));
+                }
+            };
+            if chunk.is_empty() {
+                break;
+            }
+
+            if !pending.is_empty() && pending_bytes + chunk.len() > opts.max_txn_bytes {
+                let mut txn = self.begin_txn();
+                for (index, chunk) in pending.drain(..) {
+                    let chunk_key = chunk_key(&key, generation, index);
+                    txn.put(table_id, WriteBuilder::new(chunk_key).ensure_put(chunk));
+                }
+                if let Err(err) = txn.commit().await {
+                    self.remove_chunks(table_id, &key, generation, 0..num_chunks).await;
+                    return Err(err);
+                }
+                num_staged = num_chunks;
+                pending_bytes = 0;
+            }
+
+            hasher.update(&chunk);
+            total_size += chunk.len() as u64;
+            pending_bytes += chunk.len();
+            pending.push((num_chunks, chunk));
+            num_chunks += 1;
+        }
+
+        // Flip the manifest to the new generation, along with the last chunks.
+        let manifest = Manifest { generation, num_chunks, total_size, checksum: hasher.finalize() };
+        let mut txn = self.begin_txn();
+        txn.put(
+            table_id,
+            WriteBuilder::new(key.clone()).take_prev_value().ensure_put(manifest.encode()),
+        );
+        for (index, chunk) in pending {
+            let chunk_key = chunk_key(&key, generation, index);
+            txn.put(table_id, WriteBuilder::new(chunk_key).ensure_put(chunk));
+        }
+        let resp = txn.commit().await?;
+        if let Some(prev) = resp.puts.into_iter().next().flatten().and_then(|v| v.content) {
+            if let Some(prev) = Manifest::decode(&prev) {
+                self.remove_chunks(table_id, &key, prev.generation, 0..prev.num_chunks).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the large value written by [`Database::put_large`]. The chunks are
+    /// verified against the manifest.
+    pub async fn get_large(&self, table_id: u64, key: Vec<u8>) -> AppResult<Option<Vec<u8>>> {
+        // The manifest and chunks are read at the same version, so the chunks
+        // of the read generation are visible even if it has been overwritten.
+        let txn = self.begin_txn();
+        let Some(value) = txn.get(table_id, key.clone()).await? else {
+            return Ok(None);
+        };
+        let manifest = Manifest::decode(&value).ok_or_else(|| {
+            AppError::InvalidArgument(format!("the value of key {key:?} is not a large value"))
+        })?;
+
+        let mut content = Vec::with_capacity(manifest.total_size as usize);
+        for index in 0..manifest.num_chunks {
+            let chunk_key = chunk_key(&key, manifest.generation, index);
+            let Some(chunk) = txn.get(table_id, chunk_key).await? else {
+                return Err(AppError::DataCorrupted(format!(
+                    "chunk {index} of large value {key:?} is missing"
+                )));
+            };
+            content.extend_from_slice(&chunk);
+        }
+        if content.len() as u64 != manifest.total_size {
+            return Err(AppError::DataCorrupted(format!(
+                "the size of large value {key:?} is {}, but {} is expected",
+                content.len(),
+                manifest.total_size
+            )));
+        }
+        if crc32fast::hash(&content) != manifest.checksum {
+            return Err(AppError::DataCorrupted(format!(
+                "the checksum of large value {key:?} is mismatched"
+            )));
+        }
+        Ok(Some(content))
+    }
+
+    /// Delete the large value, including all of its chunks.
+    pub async fn delete_large(&self, table_id: u64, key: Vec<u8>) -> AppResult<()> {
+        let mut txn = self.begin_txn();
+        txn.delete(table_id, WriteBuilder::new(key.clone()).take_prev_value().ensure_delete());
+        let resp = txn.commit().await?;
+        if let Some(prev) = resp.deletes.into_iter().next().flatten().and_then(|v| v.content) {
+            if let Some(prev) = Manifest::decode(&prev) {
+                self.remove_chunks(table_id, &key, prev.generation, 0..prev.num_chunks).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the chunks of the large value which are not referenced by the
+    /// manifest, such as the chunks staged by the failed or cancelled writes.
+    /// The chunks staged within [`LargeValueOptions::stale_staging_timeout`]
+    /// are kept since the write might be in progress.
+    ///
+    /// Return the number of removed chunks.
+    pub async fn gc_large(
+        &self,
+        table_id: u64,
+        key: Vec<u8>,
+        opts: &LargeValueOptions,
+    ) -> AppResult<usize> {
+        let current = match self.get(table_id, key.clone()).await? {
+            Some(value) => Manifest::decode(&value).map(|m| m.generation),
+            None => None,
+        };
+
+        let prefix = chunk_prefix(&key);
+        let stale_before =
+            timestamp_nanos().saturating_sub(opts.stale_staging_timeout.as_nanos() as u64);
+        let request =
+            RangeRequest { table_id, range: Range::Prefix(prefix.clone()), ..Default::default() };
+        let mut stream = self.range(request).await?;
+        let mut stale_chunks = Vec::new();
+        while let Some(value_sets) = stream.next().await {
+            for value_set in value_sets? {
+                if !value_set.values.iter().any(|v| v.content.is_some()) {
+                    continue;
+                }
+                let Some(generation) = parse_chunk_generation(&prefix, &value_set.user_key) else {
+                    continue;
+                };
+                if Some(generation) != current && generation < stale_before {
+                    stale_chunks.push(value_set.user_key);
+                }
+            }
+        }
+
+        let num_chunks = stale_chunks.len();
+        for keys in stale_chunks.chunks(MAX_REMOVED_CHUNKS_PER_TXN) {
+            let mut txn = self.begin_txn();
+            for key in keys {
+                txn.delete(table_id, WriteBuilder::new(key.clone()).ensure_delete());
+            }
+            txn.commit().await?;
+        }
+        Ok(num_chunks)
+    }
+
+    /// Remove the chunks of the generation. It is best-effort, the remaining
+    /// chunks are left to [`Database::gc_large`].
+    async fn remove_chunks(
+        &self,
+        table_id: u64,
+        key: &[u8],
+        generation: u64,
+        indexes: std::ops::Range<u32>,
+    ) {
+        let indexes = indexes.collect::<Vec<_>>();
+        for indexes in indexes.chunks(MAX_REMOVED_CHUNKS_PER_TXN) {
+            let mut txn = self.begin_txn();
+            for index in indexes {
+                let chunk_key = chunk_key(key, generation, *index);
+                txn.delete(table_id, WriteBuilder::new(chunk_key).ensure_delete());
+            }
+            if let Err(err) = txn.commit().await {
+                warn!(
+                    "table {table_id} remove chunks of large value {key:?} generation {generation}: {err:?}"
+                );
+                return;
+            }
+        }
+    }
+}
+
+impl Manifest {
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(MANIFEST_LEN);
+        buf.extend_from_slice(MANIFEST_MAGIC);
+        buf.extend_from_slice(&self.generation.to_be_bytes());
+        buf.extend_from_slice(&self.num_chunks.to_be_bytes());
+        buf.extend_from_slice(&self.total_size.to_be_bytes());
+        buf.extend_from_slice(&self.checksum.to_be_bytes());
+        buf
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Manifest> {
+        if bytes.len() != MANIFEST_LEN || !bytes.starts_with(MANIFEST_MAGIC) {
+            return None;
+        }
+        let bytes = &bytes[MANIFEST_MAGIC.len()..];
+        Some(Manifest {
+            generation: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            num_chunks: u32::from_be_bytes(bytes[8..12].try_into().unwrap()),
+            total_size: u64::from_be_bytes(bytes[12..20].try_into().unwrap()),
+            checksum: u32::from_be_bytes(bytes[20..24].try_into().unwrap()),
+        })
+    }
+}
+
+fn chunk_prefix(key: &[u8]) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(key.len() + CHUNK_INFIX.len() + 12);
+    prefix.extend_from_slice(key);
+    prefix.extend_from_slice(CHUNK_INFIX);
+    prefix
+}
+
+fn chunk_key(key: &[u8], generation: u64, index: u32) -> Vec<u8> {
+    let mut chunk_key = chunk_prefix(key);
+    chunk_key.extend_from_slice(&generation.to_be_bytes());
+    chunk_key.extend_from_slice(&index.to_be_bytes());
+    chunk_key
+}
+
+fn parse_chunk_generation(prefix: &[u8], chunk_key: &[u8]) -> Option<u64> {
+    let suffix = chunk_key.strip_prefix(prefix)?;
+    if suffix.len() != 12 {
+        return None;
+    }
+    Some(u64::from_be_bytes(suffix[..8].try_into().unwrap()))
+}
+
+/// Read a chunk from the reader, the chunk is shorter than `chunk_size` only if
+/// the reader reaches EOF.
+async fn read_chunk<R>(reader: &mut R, chunk_size: usize) -> std::io::Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut chunk = Vec::with_capacity(chunk_size);
+    while chunk.len() < chunk_size {
+        let limit = (chunk_size - chunk.len()) as u64;
+        if (&mut *reader).take(limit).read_to_end(&mut chunk).await? == 0 {
+            break;
+        }
+    }
+    Ok(chunk)
+}
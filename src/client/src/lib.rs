@@ -16,30 +16,69 @@
 pub mod error;
 
 mod app_client;
+mod bucket;
+mod call_options;
 mod database;
+mod delete_prefix;
 mod discovery;
 mod group_client;
+mod large_value;
+mod log_filter;
+mod memory;
 mod metrics;
 mod move_shard_client;
+mod parallel_scan;
 mod range;
+mod read_options;
 mod retry;
 mod rpc;
+mod scan_page;
+mod schema_cache;
 mod shard_client;
 mod txn;
+mod txn_limit;
+mod txn_retry;
 mod txn_table;
+mod txn_transfer;
 
 pub use sekas_api::server::v1::{DeleteRequest, PutRequest, TableDesc};
 use tonic::async_trait;
 
 pub use crate::app_client::{ClientOptions, SekasClient};
-pub use crate::database::Database;
+pub use crate::bucket::Bucket;
+pub use crate::call_options::CallOptions;
+pub use crate::database::{CreateTableOptions, Database};
+pub use crate::delete_prefix::{DeletePrefixOptions, DeletePrefixProgress, ShardDeleted};
 pub use crate::discovery::{ServiceDiscovery, StaticServiceDiscovery};
-pub use crate::error::{AppError, AppResult, Error, Result};
-pub use crate::group_client::GroupClient;
-pub use crate::move_shard_client::MoveShardClient;
-pub use crate::range::{Range, RangeRequest};
+pub use crate::error::{
+    AppError, AppResult, Error, ErrorContext, NodeAttempt, OpResult, Result, TableNotReadyError,
+    WriteBatchError,
+};
+pub use crate::group_client::{GroupClient, ReadPreference};
+pub use crate::large_value::LargeValueOptions;
+pub use crate::log_filter::{
+    effective_log_filter, install_log_filter, set_default_log_filter, set_log_filter,
+    DEFAULT_LOG_FILTER_TTL,
+};
+pub use crate::memory::{MemoryCategory, MemoryUsage};
+pub use crate::metrics::metrics_registry;
+pub use crate::move_shard_client::{ChunkOpener, MoveShardClient, ShardChunkStream};
+pub use crate::parallel_scan::{ParallelScanOptions, ScanVersion, ShardScanHandle};
+pub use crate::range::{KeyStream, Range, RangeRequest, RangeStream, ScanOptions};
+pub use crate::read_options::{Consistency, ReadOptions, ReadResult};
 pub use crate::retry::RetryState;
-pub use crate::rpc::{ConnManager, NodeClient, RootClient, Router, RouterGroupState};
+pub use crate::rpc::{
+    ChannelPool, ChannelStats, ConnManager, EncodedGroupRequest, NodeClient, NodeHealth,
+    RootClient, RootStatus, RouteEvent, RouteEventFilter, RouteEventKind, Router, RouterGroupState,
+    ShardLease, ShardLeaseNotice, ShardLeaseOptions, INTERNAL_ORIGIN_HEADER,
+    MAX_MESSAGE_BYTES_HEADER,
+};
+pub use crate::scan_page::{ScanPage, ScanToken};
 pub use crate::shard_client::ShardClient;
-pub use crate::txn::{Txn, WriteBatchResponse, WriteBuilder};
+pub use crate::txn::{
+    CommitPhase, CommitStats, Txn, WatchKeyStream, WriteBatchResponse, WriteBuilder,
+};
+pub use crate::txn_limit::{TxnOptions, TxnOverflow};
+pub use crate::txn_retry::TxnRetryOptions;
 pub use crate::txn_table::TxnStateTable;
+pub use crate::txn_transfer::TransferOptions;
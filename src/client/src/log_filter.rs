@@ -0,0 +1,172 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The log filter which could be changed at runtime.
+//!
+//! The process installs the reload handle of its tracing filter once, then the
+//! level of a target could be overridden without restarting, eg. by the
+//! `CONFIG log.<target> <level>` statement. Each override expires after its
+//! ttl, and the level reverts to the default filter of the process.
+//!
+//! The filter is shared by the whole process, so the servers and the clients
+//! embedded in the same process observe the same overrides.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use log::{info, warn};
+use sekas_runtime::JoinHandle;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{reload, EnvFilter};
+
+use crate::{AppError, AppResult};
+
+/// The ttl of an override if it is not specified.
+pub const DEFAULT_LOG_FILTER_TTL: Duration = Duration::from_secs(10 * 60);
+
+type ReloadFn = Box<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>;
+
+lazy_static! {
+    static ref LOG_FILTER: Mutex<Option<LogFilter>> = Mutex::default();
+}
+
+struct LogFilter {
+    reload: ReloadFn,
+    /// The directives of the configured filter.
+    default: String,
+    overrides: BTreeMap<String, Override>,
+}
+
+struct Override {
+    level: LevelFilter,
+    /// The timer to revert the override, it is aborted once the override is
+    /// replaced or removed.
+    _revert: Option<JoinHandle<()>>,
+}
+
+/// Install the reload handle of the tracing filter of the process, the
+/// `default` filter is used once the overrides are expired.
+pub fn install_log_filter<S: 'static>(default: &str, handle: reload::Handle<EnvFilter, S>) {
+    let reload: ReloadFn = Box::new(move |filter| handle.reload(filter));
+    *LOG_FILTER.lock().unwrap() =
+        Some(LogFilter { reload, default: default.to_owned(), overrides: BTreeMap::default() });
+}
+
+/// Replace the default filter of the process, eg. the config is reloaded. The
+/// overrides are kept until they are expired.
+pub fn set_default_log_filter(default: &str) -> AppResult<String> {
+    let mut guard = LOG_FILTER.lock().unwrap();
+    let log_filter = guard.as_mut().ok_or_else(not_installed)?;
+    log_filter.default = default.to_owned();
+    log_filter.apply()
+}
+
+/// Override the level of the target for `ttl`, the override of the target is
+/// removed if the level is empty. The effective filter is returned.
+///
+/// The [`DEFAULT_LOG_FILTER_TTL`] is used if the ttl is zero.
+pub fn set_log_filter(target: &str, level: &str, ttl: Duration) -> AppResult<String> {
+    if target.is_empty() || target.contains(|c| matches!(c, ',' | '=' | '[' | ']' | ' ')) {
+        return Err(AppError::InvalidArgument(format!("log target `{target}`")));
+    }
+    let level = if level.is_empty() {
+        None
+    } else {
+        let level = LevelFilter::from_str(level)
+            .map_err(|_| AppError::InvalidArgument(format!("log level `{level}`")))?;
+        Some(level)
+    };
+
+    let mut guard = LOG_FILTER.lock().unwrap();
+    let log_filter = guard.as_mut().ok_or_else(not_installed)?;
+    match level {
+        Some(level) => {
+            let ttl = if ttl.is_zero() { DEFAULT_LOG_FILTER_TTL } else { ttl };
+            let revert = revert_after(target.to_owned(), ttl);
+            log_filter.overrides.insert(target.to_owned(), Override { level, _revert: revert });
+            info!("log level of `{target}` is set to {level} for {ttl:?}");
+        }
+        None => {
+            log_filter.overrides.remove(target);
+            info!("log level of `{target}` is reverted");
+        }
+    }
+    log_filter.apply()
+}
+
+/// The effective filter of the process, `None` if the reloadable filter is not
+/// installed.
+pub fn effective_log_filter() -> Option<String> {
+    LOG_FILTER.lock().unwrap().as_ref().map(LogFilter::directives)
+}
+
+impl LogFilter {
+    fn directives(&self) -> String {
+        let mut directives = vec![self.default.clone()];
+        for (target, o) in &self.overrides {
+            directives.push(format!("{target}={}", o.level));
+        }
+        directives.retain(|d| !d.is_empty());
+        directives.join(",")
+    }
+
+    fn apply(&self) -> AppResult<String> {
+        let directives = self.directives();
+        let filter = EnvFilter::try_new(&directives)
+            .map_err(|e| AppError::InvalidArgument(format!("log filter `{directives}`: {e}")))?;
+        (self.reload)(filter)
+            .map_err(|e| AppError::Internal(format!("reload log filter: {e}").into()))?;
+        // The records of `log` are filtered by its max level before they are
+        // forwarded to tracing, so it follows the new max level of tracing.
+        log::set_max_level(as_log_level(LevelFilter::current()));
+        Ok(directives)
+    }
+}
+
+fn revert_after(target: String, ttl: Duration) -> Option<JoinHandle<()>> {
+    if tokio::runtime::Handle::try_current().is_err() {
+        warn!("the log level of `{target}` is not reverted, since there is no runtime");
+        return None;
+    }
+    Some(sekas_runtime::spawn(async move {
+        sekas_runtime::time::sleep(ttl).await;
+        let mut guard = LOG_FILTER.lock().unwrap();
+        let Some(log_filter) = guard.as_mut() else { return };
+        if log_filter.overrides.remove(&target).is_none() {
+            return;
+        }
+        match log_filter.apply() {
+            Ok(directives) => info!("log level of `{target}` is expired, filter `{directives}`"),
+            Err(err) => warn!("revert log level of `{target}`: {err}"),
+        }
+    }))
+}
+
+fn as_log_level(level: LevelFilter) -> log::LevelFilter {
+    match level {
+        LevelFilter::OFF => log::LevelFilter::Off,
+        LevelFilter::ERROR => log::LevelFilter::Error,
+        LevelFilter::WARN => log::LevelFilter::Warn,
+        LevelFilter::INFO => log::LevelFilter::Info,
+        LevelFilter::DEBUG => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    }
+}
+
+fn not_installed() -> AppError {
+    AppError::InvalidArgument("the reloadable log filter is not installed".to_owned())
+}
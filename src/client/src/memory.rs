@@ -0,0 +1,369 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The memory budget of a client, see [`crate::ClientOptions::memory_budget`].
+//!
+//! The writes staged by the txns and the scan pages buffered by the range
+//! streams reserve their capacities from the budget shared by all handles of a
+//! client. The reservations are counted by the shards picked by the threads, a
+//! shard takes the budget from the global counter in chunks, so the global
+//! counter is only touched once a chunk is used up or freed. The free chunks of
+//! all shards are reclaimed before a reservation fails.
+
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use sekas_api::server::v1::*;
+
+use crate::{Error, Result};
+
+const NUM_SHARDS: usize = 16;
+
+/// The max bytes taken from the global counter by a shard at once.
+const MAX_CHUNK_BYTES: u64 = 64 * 1024;
+
+/// The kinds of the client buffers accounted by the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryCategory {
+    /// The writes staged by the txns, including the flushed ones.
+    TxnWrites = 0,
+    /// The scan pages fetched but not yielded by the range streams.
+    ScanBuffers = 1,
+}
+
+const NUM_CATEGORIES: usize = 2;
+
+impl MemoryCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryCategory::TxnWrites => "txn_writes",
+            MemoryCategory::ScanBuffers => "scan_buffers",
+        }
+    }
+}
+
+/// The snapshot of the memory reserved from the budget of a client, see
+/// [`crate::SekasClient::memory_usage`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The bytes of the writes staged by the txns.
+    pub txn_writes: u64,
+    /// The bytes of the scan pages buffered by the range streams.
+    pub scan_buffers: u64,
+    /// The bytes taken from the budget, including the free chunks cached by
+    /// the shards, so it is slightly larger than the sum of the categories.
+    pub reserved: u64,
+    /// The budget of the client, `0` means unlimited.
+    pub budget: u64,
+}
+
+impl MemoryUsage {
+    /// The bytes used by all categories.
+    pub fn total(&self) -> u64 {
+        self.txn_writes + self.scan_buffers
+    }
+}
+
+/// The memory budget shared by all handles of a client.
+#[derive(Debug, Clone)]
+pub(crate) struct MemoryBudget {
+    inner: Arc<BudgetInner>,
+}
+
+#[derive(Debug)]
+struct BudgetInner {
+    /// The max bytes could be reserved, `0` means unlimited.
+    limit: u64,
+    chunk_bytes: u64,
+    /// The bytes taken by the shards.
+    taken: AtomicU64,
+    shards: [Shard; NUM_SHARDS],
+}
+
+#[derive(Debug, Default)]
+#[repr(align(64))]
+struct Shard {
+    /// The bytes taken from the global counter but not reserved yet.
+    free: AtomicU64,
+    /// The bytes reserved by each category.
+    used: [AtomicU64; NUM_CATEGORIES],
+}
+
+/// The bytes reserved from the budget, they are released once it is dropped.
+#[derive(Debug)]
+pub(crate) struct MemoryReservation {
+    budget: MemoryBudget,
+    category: MemoryCategory,
+    shard: usize,
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    /// A budget of `limit` bytes, `0` means unlimited, the usage is still
+    /// accounted.
+    pub(crate) fn new(limit: u64) -> Self {
+        let chunk_bytes = (limit / (NUM_SHARDS as u64 * 4)).clamp(1, MAX_CHUNK_BYTES);
+        let inner = BudgetInner {
+            limit,
+            chunk_bytes,
+            taken: AtomicU64::new(0),
+            shards: Default::default(),
+        };
+        MemoryBudget { inner: Arc::new(inner) }
+    }
+
+    /// An empty reservation of the category, it is grown by
+    /// [`MemoryReservation::grow`].
+    pub(crate) fn empty(&self, category: MemoryCategory) -> MemoryReservation {
+        MemoryReservation { budget: self.clone(), category, shard: shard_index(), bytes: 0 }
+    }
+
+    /// Reserve the bytes of the category, [`Error::ClientMemoryExhausted`] is
+    /// returned if the budget is exhausted.
+    pub(crate) fn reserve(
+        &self,
+        category: MemoryCategory,
+        bytes: u64,
+    ) -> Result<MemoryReservation> {
+        let mut reservation = self.empty(category);
+        reservation.grow(bytes)?;
+        Ok(reservation)
+    }
+
+    pub(crate) fn usage(&self) -> MemoryUsage {
+        let inner = &self.inner;
+        let used = |category: MemoryCategory| -> u64 {
+            inner.shards.iter().map(|s| s.used[category as usize].load(Ordering::Relaxed)).sum()
+        };
+        let txn_writes = used(MemoryCategory::TxnWrites);
+        let scan_buffers = used(MemoryCategory::ScanBuffers);
+        let reserved = if inner.limit == 0 {
+            txn_writes + scan_buffers
+        } else {
+            inner.taken.load(Ordering::Relaxed)
+        };
+        MemoryUsage { txn_writes, scan_buffers, reserved, budget: inner.limit }
+    }
+
+    fn acquire(&self, shard: usize, category: MemoryCategory, bytes: u64) -> Result<()> {
+        let inner = &self.inner;
+        let shard_ref = &inner.shards[shard];
+        if inner.limit != 0 && !take(&shard_ref.free, bytes) && !self.take_global(shard, bytes) {
+            let usage = self.usage();
+            return Err(Error::ClientMemoryExhausted {
+                category: category.as_str().to_owned(),
+                requested: bytes,
+                used: usage.total(),
+                budget: inner.limit,
+            });
+        }
+        shard_ref.used[category as usize].fetch_add(bytes, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn release(&self, shard: usize, category: MemoryCategory, bytes: u64) {
+        let inner = &self.inner;
+        let shard_ref = &inner.shards[shard];
+        shard_ref.used[category as usize].fetch_sub(bytes, Ordering::Relaxed);
+        if inner.limit == 0 {
+            return;
+        }
+        let free = shard_ref.free.fetch_add(bytes, Ordering::AcqRel) + bytes;
+        if free > 2 * inner.chunk_bytes {
+            // Keep a chunk for the following reservations, return the rest.
+            let chunk_bytes = inner.chunk_bytes;
+            if let Ok(prev) =
+                shard_ref.free.fetch_update(Ordering::AcqRel, Ordering::Acquire, |f| {
+                    (f > chunk_bytes).then_some(chunk_bytes)
+                })
+            {
+                inner.taken.fetch_sub(prev - chunk_bytes, Ordering::AcqRel);
+            }
+        }
+    }
+
+    /// Take the bytes from the global counter, a whole chunk is taken if
+    /// possible. The free chunks of all shards are reclaimed before giving up.
+    fn take_global(&self, shard: usize, bytes: u64) -> bool {
+        let inner = &self.inner;
+        let chunk = bytes.max(inner.chunk_bytes);
+        if self.take_global_exact(chunk) {
+            inner.shards[shard].free.fetch_add(chunk - bytes, Ordering::AcqRel);
+            return true;
+        }
+        if self.take_global_exact(bytes) {
+            return true;
+        }
+        for shard in &inner.shards {
+            let free = shard.free.swap(0, Ordering::AcqRel);
+            inner.taken.fetch_sub(free, Ordering::AcqRel);
+        }
+        self.take_global_exact(bytes)
+    }
+
+    fn take_global_exact(&self, bytes: u64) -> bool {
+        let limit = self.inner.limit;
+        self.inner
+            .taken
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |taken| {
+                (taken + bytes <= limit).then_some(taken + bytes)
+            })
+            .is_ok()
+    }
+}
+
+impl MemoryReservation {
+    /// Reserve more bytes, the reservation is unchanged if the budget is
+    /// exhausted.
+    pub(crate) fn grow(&mut self, bytes: u64) -> Result<()> {
+        if bytes == 0 {
+            return Ok(());
+        }
+        self.budget.acquire(self.shard, self.category, bytes)?;
+        self.bytes += bytes;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        if self.bytes != 0 {
+            self.budget.release(self.shard, self.category, self.bytes);
+        }
+    }
+}
+
+/// Take the bytes from the free bytes of a shard.
+fn take(free: &AtomicU64, bytes: u64) -> bool {
+    free.fetch_update(Ordering::AcqRel, Ordering::Acquire, |free| free.checked_sub(bytes)).is_ok()
+}
+
+/// The shard of the current thread, the threads are assigned to the shards in
+/// round robin.
+fn shard_index() -> usize {
+    static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % NUM_SHARDS;
+    }
+    SHARD.with(|shard| *shard)
+}
+
+/// The bytes held by a staged put, including the capacities of its buffers.
+pub(crate) fn put_capacity(put: &PutRequest) -> u64 {
+    (size_of::<(u64, PutRequest)>()
+        + put.key.capacity()
+        + put.value.capacity()
+        + conditions_capacity(&put.conditions)) as u64
+}
+
+/// The bytes held by a staged delete, including the capacities of its
+/// buffers.
+pub(crate) fn delete_capacity(delete: &DeleteRequest) -> u64 {
+    (size_of::<(u64, DeleteRequest)>()
+        + delete.key.capacity()
+        + conditions_capacity(&delete.conditions)) as u64
+}
+
+fn conditions_capacity(conditions: &Vec<WriteCondition>) -> usize {
+    conditions.capacity() * size_of::<WriteCondition>()
+        + conditions.iter().map(|c| c.value.capacity()).sum::<usize>()
+}
+
+/// The bytes held by a scan page, including the capacities of its buffers.
+pub(crate) fn value_sets_capacity(value_sets: &Vec<ValueSet>) -> u64 {
+    let values = value_sets.iter().map(|value_set| {
+        value_set.user_key.capacity()
+            + value_set.values.capacity() * size_of::<Value>()
+            + value_set
+                .values
+                .iter()
+                .map(|v| v.content.as_ref().map(Vec::capacity).unwrap_or_default())
+                .sum::<usize>()
+    });
+    (value_sets.capacity() * size_of::<ValueSet>() + values.sum::<usize>()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve_within_budget() {
+        let budget = MemoryBudget::new(1024);
+        let mut txn = budget.empty(MemoryCategory::TxnWrites);
+        txn.grow(600).unwrap();
+        let scan = budget.reserve(MemoryCategory::ScanBuffers, 400).unwrap();
+        match txn.grow(100) {
+            Err(Error::ClientMemoryExhausted { category, requested, used, budget }) => {
+                assert_eq!(category, "txn_writes");
+                assert_eq!((requested, used, budget), (100, 1000, 1024));
+            }
+            others => panic!("expect memory exhausted, but got {others:?}"),
+        }
+        assert_eq!(txn.bytes(), 600);
+        let usage = budget.usage();
+        assert_eq!((usage.txn_writes, usage.scan_buffers, usage.total()), (600, 400, 1000));
+
+        // The released bytes are reserved again.
+        drop(scan);
+        txn.grow(424).unwrap();
+        drop(txn);
+        let usage = budget.usage();
+        assert_eq!(usage.total(), 0);
+        assert!(usage.reserved <= 2 * budget.inner.chunk_bytes, "{usage:?}");
+    }
+
+    #[test]
+    fn reclaim_free_chunks_of_other_shards() {
+        let budget = MemoryBudget::new(64 * 1024);
+        let chunk_bytes = budget.inner.chunk_bytes;
+        // The free chunks cached by the shards are reclaimed.
+        for shard in 0..NUM_SHARDS {
+            budget.acquire(shard, MemoryCategory::TxnWrites, 1).unwrap();
+            budget.release(shard, MemoryCategory::TxnWrites, 1);
+        }
+        assert_eq!(budget.usage().reserved, NUM_SHARDS as u64 * chunk_bytes);
+        let reservation = budget.reserve(MemoryCategory::ScanBuffers, 60 * 1024).unwrap();
+        assert_eq!(budget.usage().scan_buffers, 60 * 1024);
+        drop(reservation);
+        assert_eq!(budget.usage().total(), 0);
+    }
+
+    #[test]
+    fn unlimited_budget_accounts_usage() {
+        let budget = MemoryBudget::new(0);
+        let reservation = budget.reserve(MemoryCategory::TxnWrites, u32::MAX as u64).unwrap();
+        assert_eq!(budget.usage().txn_writes, u32::MAX as u64);
+        drop(reservation);
+        assert_eq!(budget.usage(), MemoryUsage::default());
+    }
+
+    #[test]
+    fn capacity_counts_buffers() {
+        let put =
+            PutRequest { key: Vec::with_capacity(100), value: vec![0; 10], ..Default::default() };
+        assert!(put_capacity(&put) >= 110);
+        let value_sets = vec![ValueSet {
+            user_key: Vec::with_capacity(32),
+            values: vec![Value::with_value(vec![0; 64], 1)],
+        }];
+        assert!(value_sets_capacity(&value_sets) >= 96);
+    }
+}
@@ -21,16 +21,20 @@ make_static_metric! {
     pub struct GroupRequestTotal: IntCounter {
         "type" => {
             get,
+            get_raw_key,
             scan,
             write,
+            delete_prefix,
 
             prepare_intent,
             commit_intent,
             clear_intent,
+            check_prefix_empty,
 
             transfer,
             split_shard,
             merge_shard,
+            remove_shard,
             accept_shard,
             create_shard,
             move_replicas,
@@ -42,16 +46,20 @@ make_static_metric! {
     pub struct GroupRequestDuration: Histogram {
         "type" => {
             get,
+            get_raw_key,
             scan,
             write,
+            delete_prefix,
 
             prepare_intent,
             commit_intent,
             clear_intent,
+            check_prefix_empty,
 
             transfer,
             split_shard,
             merge_shard,
+            remove_shard,
             accept_shard,
             create_shard,
             move_replicas,
@@ -95,6 +103,10 @@ pub fn take_group_request_metrics(
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.get.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.get)
         }
+        Request::GetRawKey(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.get_raw_key.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.get_raw_key)
+        }
         Request::Scan(_) => {
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.scan.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.scan)
@@ -103,6 +115,10 @@ pub fn take_group_request_metrics(
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.write.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.write)
         }
+        Request::DeletePrefix(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.delete_prefix.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.delete_prefix)
+        }
         Request::WriteIntent(_) => {
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.prepare_intent.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.prepare_intent)
@@ -115,6 +131,10 @@ pub fn take_group_request_metrics(
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.clear_intent.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.clear_intent)
         }
+        Request::CheckPrefixEmpty(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.check_prefix_empty.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.check_prefix_empty)
+        }
         Request::AcceptShard(_) => {
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.accept_shard.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.accept_shard)
@@ -147,6 +167,10 @@ pub fn take_group_request_metrics(
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.merge_shard.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.merge_shard)
         }
+        Request::RemoveShard(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.remove_shard.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.remove_shard)
+        }
     }
 }
 
@@ -202,6 +226,125 @@ lazy_static! {
         DatabaseBytesTotal::from(&CLIENT_DATABASE_BYTES_TOTAL_VEC);
 }
 
+make_static_metric! {
+    pub struct TxnTableRequestTotal: IntCounter {
+        "type" => {
+            begin,
+            heartbeat,
+            commit,
+            abort,
+            get,
+            list,
+        }
+    }
+}
+
+// For the txn state table
+lazy_static! {
+    pub static ref CLIENT_TXN_TABLE_REQUEST_TOTAL_VEC: IntCounterVec = register_int_counter_vec!(
+        "client_txn_table_request_total",
+        "The total requests issued to the txn state table by client",
+        &["type"]
+    )
+    .unwrap();
+    pub static ref CLIENT_TXN_TABLE_REQUEST_TOTAL: TxnTableRequestTotal =
+        TxnTableRequestTotal::from(&CLIENT_TXN_TABLE_REQUEST_TOTAL_VEC);
+}
+
+// For the background tasks of streams
+lazy_static! {
+    pub static ref CLIENT_STREAM_TASKS_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "client_stream_tasks",
+        "The number of alive background tasks of client streams",
+        &["type"]
+    )
+    .unwrap();
+    pub static ref CLIENT_RANGE_STREAM_TASKS: IntGauge =
+        CLIENT_STREAM_TASKS_VEC.with_label_values(&["range"]);
+    pub static ref CLIENT_WATCH_STREAM_TASKS: IntGauge =
+        CLIENT_STREAM_TASKS_VEC.with_label_values(&["watch"]);
+}
+
+// For the routing
+lazy_static! {
+    pub static ref CLIENT_ROUTER_STALENESS_SECONDS: Gauge = register_gauge!(
+        "client_router_staleness_seconds",
+        "The seconds since the routing is last synced from root, zero if it is being watched"
+    )
+    .unwrap();
+    pub static ref CLIENT_SHARD_LEASE_NOTIFY_TIMEOUT_TOTAL: IntCounter = register_int_counter!(
+        "client_shard_lease_notify_timeout_total",
+        "The total routing updates applied before the shard lease notices are acknowledged"
+    )
+    .unwrap();
+}
+
+// For the connections
+lazy_static! {
+    pub static ref CLIENT_NODE_CHANNELS: IntGaugeVec = register_int_gauge_vec!(
+        "client_node_channels",
+        "The number of HTTP/2 channels dialed to each node",
+        &["node"]
+    )
+    .unwrap();
+    pub static ref CLIENT_NODE_ACTIVE_STREAMS: IntGaugeVec = register_int_gauge_vec!(
+        "client_node_active_streams",
+        "The number of requests in flight over the channels to each node",
+        &["node"]
+    )
+    .unwrap();
+}
+
+lazy_static! {
+    static ref CLIENT_REGISTRY: Registry = {
+        let registry = Registry::new();
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(GROUP_CLIENT_GROUP_REQUEST_TOTAL_VEC.clone()),
+            Box::new(GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS_VEC.clone()),
+            Box::new(GROUP_CLIENT_RETRY_TOTAL.clone()),
+            Box::new(CLIENT_DATABASE_REQUEST_TOTAL_VEC.clone()),
+            Box::new(CLIENT_DATABASE_REQUEST_DURATION_SECONDS_VEC.clone()),
+            Box::new(CLIENT_DATABASE_BYTES_TOTAL_VEC.clone()),
+            Box::new(CLIENT_TXN_TABLE_REQUEST_TOTAL_VEC.clone()),
+            Box::new(CLIENT_STREAM_TASKS_VEC.clone()),
+            Box::new(CLIENT_ROUTER_STALENESS_SECONDS.clone()),
+            Box::new(CLIENT_SHARD_LEASE_NOTIFY_TIMEOUT_TOTAL.clone()),
+            Box::new(CLIENT_NODE_CHANNELS.clone()),
+            Box::new(CLIENT_NODE_ACTIVE_STREAMS.clone()),
+        ];
+        for collector in collectors {
+            registry.register(collector).unwrap();
+        }
+        registry
+    };
+}
+
+/// The registry of the client metrics, the embedding applications gather it
+/// into their own exporters.
+///
+/// The metrics are also registered into the default registry of prometheus,
+/// so there is no need to gather both of them.
+pub fn metrics_registry() -> &'static Registry {
+    &CLIENT_REGISTRY
+}
+
+/// Count a background task as alive until it is dropped, either finished or
+/// aborted.
+pub(crate) struct AliveTaskGuard(&'static IntGauge);
+
+impl AliveTaskGuard {
+    pub(crate) fn new(gauge: &'static IntGauge) -> Self {
+        gauge.inc();
+        AliveTaskGuard(gauge)
+    }
+}
+
+impl Drop for AliveTaskGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
 #[macro_export]
 macro_rules! record_latency {
     ($metrics:expr) => {
@@ -13,12 +13,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use futures::stream::{BoxStream, FusedStream};
+use futures::StreamExt;
 use sekas_api::server::v1::*;
 
 use crate::group_client::GroupClient;
 use crate::retry::RetryState;
 use crate::shard_client::ShardClient;
-use crate::{Result, SekasClient};
+use crate::{Error, Result, SekasClient};
 
 /// FIXME(walter) refactor it!
 /// `MigrateClient` wraps `GroupClient` and provides retry for moving shard
@@ -26,11 +32,30 @@ use crate::{Result, SekasClient};
 pub struct MoveShardClient {
     group_id: u64,
     client: SekasClient,
+    chunk_opener: Option<ChunkOpener>,
+}
+
+/// Open the shard chunk sealed by the source node, see
+/// [`MoveShardClient::with_chunk_opener`].
+pub type ChunkOpener = Arc<dyn Fn(&SealedData) -> Result<Vec<ValueSet>> + Send + Sync>;
+
+/// The stream of shard chunks, see [`MoveShardClient::pull_shard`].
+pub struct ShardChunkStream {
+    inner: BoxStream<'static, Result<Vec<ValueSet>>>,
+    terminated: bool,
 }
 
 impl MoveShardClient {
     pub fn new(group_id: u64, client: SekasClient) -> Self {
-        MoveShardClient { group_id, client }
+        MoveShardClient { group_id, client, chunk_opener: None }
+    }
+
+    /// Request the source node to seal the pulled shard chunks, they are
+    /// opened by `opener`. The chunks are received in plain if the source node
+    /// doesn't enable the encryption.
+    pub fn with_chunk_opener(mut self, opener: ChunkOpener) -> Self {
+        self.chunk_opener = Some(opener);
+        self
     }
 
     pub async fn acquire_shard(&mut self, desc: &MoveShardDesc) -> Result<()> {
@@ -62,17 +87,78 @@ impl MoveShardClient {
         }
     }
 
+    /// Cancel the moving shard task, it is issued to the dest group.
+    pub async fn cancel_move(&mut self, desc: &MoveShardDesc) -> Result<()> {
+        let mut retry_state = RetryState::default();
+
+        loop {
+            let mut client = self.group_client();
+            match client.cancel_move(desc).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Abort the moving shard task and restore the moved data, it is issued to
+    /// the source group.
+    pub async fn abort_move(&mut self, desc: &MoveShardDesc) -> Result<()> {
+        let mut retry_state = RetryState::default();
+
+        loop {
+            let mut client = self.group_client();
+            match client.abort_move(desc).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
     pub async fn pull_shard_chunk(
         &self,
         shard_id: u64,
         last_key: Option<Vec<u8>>,
     ) -> Result<Vec<ValueSet>> {
+        let opener = self.chunk_opener.clone();
+        pull_shard_chunk(self.group_id, shard_id, self.client.clone(), last_key, opener).await
+    }
+
+    /// Pull the chunks of shard after `last_key` until the end of shard. The
+    /// chunks are pulled on demand, so nothing is left behind once the stream
+    /// is dropped.
+    pub fn pull_shard(&self, shard_id: u64, last_key: Option<Vec<u8>>) -> ShardChunkStream {
+        let group_id = self.group_id;
+        let client = self.client.clone();
+        let opener = self.chunk_opener.clone();
+        let inner = futures::stream::try_unfold(Some(last_key), move |last_key| {
+            let client = client.clone();
+            let opener = opener.clone();
+            async move {
+                // The shard is finished once an empty chunk is pulled.
+                let Some(last_key) = last_key else { return Ok(None) };
+                let chunk = pull_shard_chunk(group_id, shard_id, client, last_key, opener).await?;
+                let next_key = chunk.last().map(|value_set| Some(value_set.user_key.clone()));
+                if next_key.is_none() {
+                    return Ok(None);
+                }
+                Ok(Some((chunk, next_key)))
+            }
+        });
+        ShardChunkStream { inner: inner.boxed(), terminated: false }
+    }
+
+    pub async fn forward(&mut self, req: &ForwardRequest) -> Result<ForwardResponse> {
         let mut retry_state = RetryState::default();
 
         loop {
-            let client = ShardClient::new(self.group_id, shard_id, self.client.clone());
-            match client.pull(last_key.clone()).await {
+            let mut client = self.group_client();
+            match client.forward(req).await {
                 Ok(resp) => return Ok(resp),
+                e @ Err(crate::Error::EpochNotMatch(..)) => return e,
                 Err(err) => {
                     retry_state.retry(err).await?;
                 }
@@ -80,12 +166,35 @@ impl MoveShardClient {
         }
     }
 
-    pub async fn forward(&mut self, req: &ForwardRequest) -> Result<ForwardResponse> {
+    /// Ingest the value sets into a serving shard, see [`IngestRequest`].
+    pub async fn ingest(&mut self, req: &IngestRequest) -> Result<()> {
         let mut retry_state = RetryState::default();
 
         loop {
             let mut client = self.group_client();
-            match client.forward(req).await {
+            match client.ingest(req).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Pull a chunk of the shard from `start_key`, see
+    /// [`ShardClient::pull_snapshot`].
+    pub async fn pull_snapshot_chunk(
+        &self,
+        shard_id: u64,
+        read_version: u64,
+        start_key: &[u8],
+        end_key: Option<&[u8]>,
+    ) -> Result<(Vec<ValueSet>, bool)> {
+        let mut retry_state = RetryState::default();
+
+        loop {
+            let client = ShardClient::new(self.group_id, shard_id, self.client.clone());
+            match client.pull_snapshot(read_version, start_key, end_key).await {
                 Ok(resp) => return Ok(resp),
                 Err(err) => {
                     retry_state.retry(err).await?;
@@ -99,3 +208,68 @@ impl MoveShardClient {
         GroupClient::lazy(self.group_id, self.client.clone())
     }
 }
+
+impl futures::Stream for ShardChunkStream {
+    type Item = Result<Vec<ValueSet>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.terminated {
+            return Poll::Ready(None);
+        }
+        let item = ready!(this.inner.poll_next_unpin(cx));
+        if !matches!(item, Some(Ok(_))) {
+            this.terminated = true;
+        }
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.terminated {
+            (0, Some(0))
+        } else {
+            (0, None)
+        }
+    }
+}
+
+impl FusedStream for ShardChunkStream {
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+async fn pull_shard_chunk(
+    group_id: u64,
+    shard_id: u64,
+    client: SekasClient,
+    last_key: Option<Vec<u8>>,
+    opener: Option<ChunkOpener>,
+) -> Result<Vec<ValueSet>> {
+    let mut retry_state = RetryState::default();
+
+    loop {
+        let client = ShardClient::new(group_id, shard_id, client.clone());
+        match client.pull(last_key.clone(), opener.is_some()).await {
+            Ok(resp) => return open_shard_chunk(resp, opener.as_ref()),
+            Err(err) => {
+                retry_state.retry(err).await?;
+            }
+        }
+    }
+}
+
+/// The value sets of the pulled chunk, the sealed ones are opened by `opener`.
+fn open_shard_chunk(
+    resp: ShardScanResponse,
+    opener: Option<&ChunkOpener>,
+) -> Result<Vec<ValueSet>> {
+    let mut chunk = resp.data;
+    for sealed in &resp.sealed {
+        let Some(opener) = opener else {
+            return Err(Error::Internal("the shard chunk is sealed without request".into()));
+        };
+        chunk.extend(opener(sealed)?);
+    }
+    Ok(chunk)
+}
@@ -0,0 +1,200 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use prost::Message;
+use sekas_api::server::v1::*;
+use sekas_schema::shard;
+
+use crate::range::extract_request_range;
+use crate::{
+    AppError, AppResult, Database, Range, RangeRequest, RangeStream, ScanOptions, SekasClient, Txn,
+};
+
+/// The version all handles of a parallel scan are read at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanVersion(pub u64);
+
+/// The options of [`Database::parallel_scan`].
+#[derive(Debug, Clone)]
+pub struct ParallelScanOptions {
+    /// The version to scan, a new version is allocated if it is not specified.
+    pub version: Option<u64>,
+    /// The range to scan, all keys of the table by default.
+    pub range: Range,
+    /// The options of scan.
+    pub options: ScanOptions,
+}
+
+/// The opaque handle to scan a part of the table, which is one shard of the
+/// table when it is created, see [`Database::parallel_scan`].
+///
+/// It records the fixed range of user keys and the shared read version, so the
+/// keys returned by the handle are not affected by the splits, merges or moves
+/// of the shards after it is created. The routing is resolved again when the
+/// handle is scanned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardScanHandle {
+    desc: ParallelScanShard,
+}
+
+impl Default for ParallelScanOptions {
+    fn default() -> Self {
+        ParallelScanOptions { version: None, range: Range::all(), options: ScanOptions::default() }
+    }
+}
+
+impl ShardScanHandle {
+    /// Serialize the handle, so it could be sent to another process.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.desc.encode_to_vec()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> AppResult<Self> {
+        let desc = ParallelScanShard::decode(bytes)
+            .map_err(|err| AppError::InvalidArgument(format!("shard scan handle: {err}")))?;
+        Ok(ShardScanHandle { desc })
+    }
+
+    #[inline]
+    pub fn table_id(&self) -> u64 {
+        self.desc.table_id
+    }
+
+    #[inline]
+    pub fn read_version(&self) -> u64 {
+        self.desc.read_version
+    }
+
+    /// The range of user keys scanned by the handle, the end of the table if
+    /// the end key is `None`.
+    #[inline]
+    pub fn range(&self) -> (&[u8], Option<&[u8]>) {
+        (&self.desc.start_key, self.desc.end_key.as_deref())
+    }
+
+    /// The shard serving the range when the handle is created.
+    #[inline]
+    pub fn shard_id(&self) -> u64 {
+        self.desc.shard_id
+    }
+
+    /// The addresses of the nodes serving the range when the handle is
+    /// created, the leader goes first. The frameworks could schedule the task
+    /// of the handle close to them.
+    #[inline]
+    pub fn node_addrs(&self) -> &[String] {
+        &self.desc.node_addrs
+    }
+}
+
+impl Database {
+    /// Split the range of the table into the handles by the shards, so they
+    /// could be scanned in parallel, eg. by the tasks of a data-processing
+    /// framework. All handles are read at the same version, so the union of
+    /// them is a consistent snapshot of the range, and the ranges of them are
+    /// not overlapped.
+    ///
+    /// Each handle is scanned by [`SekasClient::resume_shard_scan`], maybe in
+    /// another process.
+    pub async fn parallel_scan(
+        &self,
+        table_id: u64,
+        opts: ParallelScanOptions,
+    ) -> AppResult<(ScanVersion, Vec<ShardScanHandle>)> {
+        let read_version = match opts.version {
+            Some(version) => version,
+            None => Txn::new(self.clone()).read_version().await?,
+        };
+        let (mut cursor_key, end_key) = extract_request_range(opts.range);
+        let router = self.client.router();
+        let mut handles = vec![];
+        loop {
+            let (group_state, shard_desc) = router.find_shard(table_id, &cursor_key)?;
+            let shard_end = shard::end_key(&shard_desc);
+            let is_last = shard_end.is_empty()
+                || end_key
+                    .as_ref()
+                    .map(|end| end.as_slice() <= shard_end.as_slice())
+                    .unwrap_or_default();
+            let mut replicas = group_state.replicas.values().collect::<Vec<_>>();
+            let leader_id = group_state.leader_state.map(|(id, _)| id);
+            replicas.sort_by_key(|r| (Some(r.id) != leader_id, r.id));
+            let node_addrs = replicas
+                .into_iter()
+                .filter_map(|r| router.find_node_addr(r.node_id).ok())
+                .collect::<Vec<_>>();
+            let desc = ParallelScanShard {
+                table_id,
+                read_version,
+                start_key: cursor_key,
+                end_key: if is_last { end_key.clone() } else { Some(shard_end.clone()) },
+                ignore_txn_intent: opts.options.ignore_txn_intent,
+                shard_id: shard_desc.id,
+                group_id: group_state.id,
+                node_addrs,
+            };
+            handles.push(ShardScanHandle { desc });
+            if is_last {
+                break;
+            }
+            cursor_key = shard_end;
+        }
+        Ok((ScanVersion(read_version), handles))
+    }
+}
+
+impl SekasClient {
+    /// Scan the range of the handle at the read version of it, see
+    /// [`Database::parallel_scan`]. The shards serving the range are resolved
+    /// again, so the range is scanned from the children if the shard is split.
+    ///
+    /// [`AppError::VersionTooOld`] is returned if the read version is beneath
+    /// the GC watermark.
+    pub async fn resume_shard_scan(&self, handle: &ShardScanHandle) -> AppResult<RangeStream> {
+        let desc = &handle.desc;
+        let request = RangeRequest {
+            table_id: desc.table_id,
+            version: Some(desc.read_version),
+            range: Range::Range { begin: Some(desc.start_key.clone()), end: desc.end_key.clone() },
+            options: ScanOptions { ignore_txn_intent: desc.ignore_txn_intent },
+            ..Default::default()
+        };
+        Ok(RangeStream::init(self.clone(), request, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shard_scan_handle_serialization() {
+        let desc = ParallelScanShard {
+            table_id: 1,
+            read_version: 100,
+            start_key: b"a".to_vec(),
+            end_key: Some(b"k".to_vec()),
+            shard_id: 2,
+            group_id: 3,
+            node_addrs: vec!["127.0.0.1:21805".to_owned()],
+            ..Default::default()
+        };
+        let handle = ShardScanHandle { desc };
+        let restored = ShardScanHandle::from_bytes(&handle.to_bytes()).unwrap();
+        assert_eq!(restored, handle);
+        assert_eq!((restored.table_id(), restored.read_version()), (1, 100));
+        assert_eq!(restored.range(), (b"a".as_slice(), Some(b"k".as_slice())));
+        assert!(ShardScanHandle::from_bytes(b"\xFF").is_err());
+    }
+}
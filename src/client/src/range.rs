@@ -11,17 +11,20 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::collections::VecDeque;
 use std::pin::Pin;
-use std::task::{Context, Poll};
-use std::time::Instant;
+use std::task::{ready, Context, Poll};
 
-use sekas_api::server::v1::group_request_union::Request;
-use sekas_api::server::v1::group_response_union::Response;
+use futures::stream::FusedStream;
+use futures::StreamExt;
 use sekas_api::server::v1::*;
-use sekas_rock::lexical::lexical_next_boundary;
+use sekas_rock::lexical::{lexical_next, lexical_next_boundary};
+use sekas_runtime::time::Instant;
 use sekas_schema::system::txn::TXN_MAX_VERSION;
 use tokio::sync::mpsc;
 
+use crate::memory::{value_sets_capacity, MemoryCategory, MemoryReservation};
+use crate::metrics::{AliveTaskGuard, CLIENT_RANGE_STREAM_TASKS};
 use crate::{GroupClient, RetryState, SekasClient};
 
 /// The range descriptor.
@@ -39,6 +42,17 @@ pub enum Range {
     },
 }
 
+/// The options of scan.
+///
+/// By default, a scan at read version V resolves or waits on the txn intents
+/// whose start version are not larger than V, and ignores the intents above V.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Read the committed values without waiting or resolving any txn intents,
+    /// the latest committed values are read if the version is not specified.
+    pub ignore_txn_intent: bool,
+}
+
 /// The range request.
 #[derive(Debug, Clone)]
 pub struct RangeRequest {
@@ -53,6 +67,12 @@ pub struct RangeRequest {
     pub limit: u64,
     /// The total bytes of key-value pairs to limit.
     pub limit_bytes: u64,
+    /// Scan keys in descending order. The shards are visited from the end of
+    /// range to the start of range, and the limits are applied to each shard
+    /// request as usual.
+    pub reverse: bool,
+    /// The options of scan.
+    pub options: ScanOptions,
     /// The max number of buffered requests. This is an internal option, do NOT
     /// change it if you don't known what it means.
     ///
@@ -60,10 +80,32 @@ pub struct RangeRequest {
     pub buffered_requests: usize,
 }
 
+/// The page buffered by the channel, with the memory reserved from the budget
+/// of the client until it is yielded.
+type BufferedPage = (crate::Result<Vec<ValueSet>>, Option<MemoryReservation>);
+
+/// The stream of scanned value sets, a batch of value sets is yielded for each
+/// page of the scan.
+///
+/// The pages are fetched by a background task, which is aborted once the
+/// stream is dropped. The stream is terminated after an error is yielded.
+///
+/// The buffered pages reserve their memory from the budget of the client, the
+/// scan fails with [`crate::Error::ClientMemoryExhausted`] if the budget is
+/// exhausted.
 pub struct RangeStream {
     fetch_handle: Option<tokio::task::JoinHandle<()>>,
 
-    receiver: mpsc::Receiver<crate::Result<Vec<ValueSet>>>,
+    receiver: mpsc::Receiver<BufferedPage>,
+    terminated: bool,
+    /// The len of the prefix stripped from the yielded user keys.
+    stripped_prefix_len: usize,
+}
+
+/// The stream of scanned keys, see [`RangeStream::into_key_stream`].
+pub struct KeyStream {
+    inner: RangeStream,
+    keys: VecDeque<Vec<u8>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -75,7 +117,7 @@ enum ScannerState {
 
 struct RangeScanner {
     client: SekasClient,
-    sender: mpsc::Sender<crate::Result<Vec<ValueSet>>>,
+    sender: mpsc::Sender<BufferedPage>,
 
     /// The state of this scanner.
     state: ScannerState,
@@ -88,10 +130,16 @@ struct RangeScanner {
     limit: u64,
     /// The num of bytes to limit.
     limit_bytes: u64,
+    /// Scan keys in descending order.
+    reverse: bool,
+    /// Ignore the txn intents rather than resolve them.
+    ignore_txn_intent: bool,
 
-    /// The current cursor to scan.
+    /// The current cursor to scan. It is the fixed start key of the range in
+    /// reverse mode.
     cursor_key: Vec<u8>,
-    /// The end key to scan.
+    /// The exclusive end key to scan. It is the cursor to scan in reverse mode,
+    /// the keys before it are not scanned yet.
     end_key: Option<Vec<u8>>,
     /// The num scanned batch.
     num_scanned: usize,
@@ -103,6 +151,14 @@ impl Range {
     }
 }
 
+impl ScanOptions {
+    /// The inconsistent mode for monitoring jobs, which reads the latest
+    /// committed values without any intent waiting.
+    pub fn inconsistent() -> Self {
+        ScanOptions { ignore_txn_intent: true }
+    }
+}
+
 impl Default for RangeRequest {
     fn default() -> Self {
         RangeRequest {
@@ -111,6 +167,8 @@ impl Default for RangeRequest {
             range: Range::all(),
             limit: 0,
             limit_bytes: 0,
+            reverse: false,
+            options: ScanOptions::default(),
             buffered_requests: 1,
         }
     }
@@ -120,18 +178,78 @@ impl futures::Stream for RangeStream {
     type Item = crate::Result<Vec<ValueSet>>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.get_mut().receiver.poll_recv(cx)
+        let this = self.get_mut();
+        if this.terminated {
+            return Poll::Ready(None);
+        }
+        // The memory of the page is released once it is yielded.
+        let mut item = ready!(this.receiver.poll_recv(cx)).map(|(page, _reservation)| page);
+        match &mut item {
+            Some(Ok(value_sets)) if this.stripped_prefix_len > 0 => {
+                for value_set in value_sets {
+                    value_set.user_key.drain(..this.stripped_prefix_len);
+                }
+            }
+            Some(Ok(_)) => {}
+            _ => this.terminate(),
+        }
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // The number of pages is unknown until the scan reaches the end of range.
+        if self.terminated {
+            (0, Some(0))
+        } else {
+            (0, None)
+        }
+    }
+}
+
+impl FusedStream for RangeStream {
+    fn is_terminated(&self) -> bool {
+        self.terminated
     }
 }
 
 impl Drop for RangeStream {
     fn drop(&mut self) {
-        if let Some(handle) = self.fetch_handle.take() {
-            if !handle.is_finished() {
-                handle.abort();
+        self.abort_fetching();
+    }
+}
+
+impl futures::Stream for KeyStream {
+    type Item = crate::Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(key) = this.keys.pop_front() {
+                return Poll::Ready(Some(Ok(key)));
+            }
+            match ready!(Pin::new(&mut this.inner).poll_next(cx)) {
+                Some(Ok(value_sets)) => {
+                    this.keys.extend(value_sets.into_iter().map(|value_set| value_set.user_key));
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => return Poll::Ready(None),
             }
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let num_keys = self.keys.len();
+        match self.inner.size_hint() {
+            (_, Some(0)) => (num_keys, Some(num_keys)),
+            _ => (num_keys, None),
+        }
+    }
+}
+
+impl FusedStream for KeyStream {
+    fn is_terminated(&self) -> bool {
+        self.keys.is_empty() && self.inner.is_terminated()
+    }
 }
 
 impl RangeStream {
@@ -150,24 +268,83 @@ impl RangeStream {
             version: request.version.unwrap_or(TXN_MAX_VERSION),
             limit: request.limit,
             limit_bytes: request.limit_bytes,
+            reverse: request.reverse,
+            ignore_txn_intent: request.options.ignore_txn_intent,
             cursor_key,
             end_key,
             num_scanned: 0,
         };
 
         // Spawn a task to fetch value set in background.
+        let guard = AliveTaskGuard::new(&CLIENT_RANGE_STREAM_TASKS);
         let handle = tokio::spawn(async move {
+            let _guard = guard;
             let mut scanner = scanner;
             scanner.scan(deadline).await;
         });
-        RangeStream { fetch_handle: Some(handle), receiver }
+        RangeStream {
+            fetch_handle: Some(handle),
+            receiver,
+            terminated: false,
+            stripped_prefix_len: 0,
+        }
+    }
+
+    /// Strip the prefix of the yielded user keys, all keys in the range must
+    /// have the prefix.
+    pub(crate) fn strip_key_prefix(mut self, prefix_len: usize) -> Self {
+        self.stripped_prefix_len = prefix_len;
+        self
+    }
+
+    /// Collect the value sets until the end of range or `limit` value sets are
+    /// collected, `0` means unlimited. The remaining pages are not fetched once
+    /// the limit is reached.
+    pub async fn try_collect_vec(mut self, limit: usize) -> crate::Result<Vec<ValueSet>> {
+        let mut value_sets = vec![];
+        while let Some(batch) = self.next().await {
+            value_sets.extend(batch?);
+            if limit != 0 && value_sets.len() >= limit {
+                value_sets.truncate(limit);
+                break;
+            }
+        }
+        Ok(value_sets)
+    }
+
+    /// Count the value sets until the end of range.
+    pub async fn count(mut self) -> crate::Result<usize> {
+        let mut num_value_sets = 0;
+        while let Some(batch) = self.next().await {
+            num_value_sets += batch?.len();
+        }
+        Ok(num_value_sets)
+    }
+
+    /// Convert into a stream which yields the user keys one by one.
+    pub fn into_key_stream(self) -> KeyStream {
+        KeyStream { inner: self, keys: VecDeque::default() }
+    }
+
+    fn terminate(&mut self) {
+        self.terminated = true;
+        self.receiver.close();
+        self.abort_fetching();
+    }
+
+    fn abort_fetching(&mut self) {
+        if let Some(handle) = self.fetch_handle.take() {
+            if !handle.is_finished() {
+                handle.abort();
+            }
+        }
     }
 }
 
 impl RangeScanner {
     async fn scan(&mut self, deadline: Option<Instant>) {
         if let Err(err) = self.scan_inner(deadline).await {
-            let _ = self.sender.send(Err(err)).await;
+            let _ = self.sender.send((Err(err), None)).await;
         }
     }
 
@@ -175,7 +352,11 @@ impl RangeScanner {
         let mut retry_state = RetryState::with_deadline_opt(deadline);
         while self.state == ScannerState::Normal {
             let router = self.client.router();
-            let (group_state, shard_desc) = router.find_shard(self.table_id, &self.cursor_key)?;
+            let (group_state, shard_desc) = if self.reverse {
+                router.find_shard_before(self.table_id, self.end_key.as_deref())?
+            } else {
+                router.find_shard(self.table_id, &self.cursor_key)?
+            };
             let mut group_client = GroupClient::new(group_state, self.client.clone());
             if let Err(err) = self.scan_shard(&mut group_client, &shard_desc).await {
                 retry_state.retry(err).await?;
@@ -188,7 +369,14 @@ impl RangeScanner {
                     format!("shard range is required, shard={shard_desc:?}").into(),
                 ));
             };
-            if is_entire_range_scanned(self.end_key.as_deref(), &shard_range.end) {
+            if self.reverse {
+                if is_entire_range_scanned_in_reverse(&self.cursor_key, &shard_range.start) {
+                    self.state = ScannerState::Finished;
+                } else {
+                    // This shard has been scanned, skip to previous shard.
+                    self.end_key = Some(shard_range.start);
+                }
+            } else if is_entire_range_scanned(self.end_key.as_deref(), &shard_range.end) {
                 self.state = ScannerState::Finished;
             } else {
                 // This shard has been scanned, skip to next shard.
@@ -213,26 +401,45 @@ impl RangeScanner {
                 start_key: Some(begin_key),
                 end_key: self.end_key.clone(),
                 exclude_end_key: true,
+                reverse: self.reverse,
+                ignore_txn_intent: self.ignore_txn_intent,
                 ..Default::default()
             };
-            let scan_resp = match group_client.request(&Request::Scan(req)).await? {
-                Response::Scan(resp) => resp,
-                e => {
-                    return Err(crate::Error::Internal(
-                        format!("Response::Scan is required, but got {e:?}").into(),
-                    ));
+            // The frames of a page are yielded once they arrive, instead of collecting the
+            // whole page.
+            let mut frames = Box::pin(group_client.scan_frames(&req).await?);
+            let mut has_more = false;
+            while let Some(frame) = frames.next().await {
+                let Ok(frame) = frame else {
+                    // The following frames are scanned again from the cursor, the errors of the
+                    // first frame are handled by the group client.
+                    has_more = true;
+                    break;
+                };
+                // The scan is failed rather than retried if the budget is exhausted.
+                let reservation = self
+                    .client
+                    .memory_budget()
+                    .reserve(MemoryCategory::ScanBuffers, value_sets_capacity(&frame.data))?;
+                if let Some(last_value) = frame.data.last() {
+                    if self.reverse {
+                        // The end key is excluded, so the last key is the cursor of next page.
+                        self.end_key = Some(last_value.user_key.clone());
+                    } else {
+                        // The immediate successor of the last key, the keys extending it (eg
+                        // `aa` after `a`) are skipped if resuming from the next boundary.
+                        self.cursor_key = lexical_next(&last_value.user_key);
+                    }
+                }
+                has_more = frame.has_more;
+                if self.sender.send((Ok(frame.data), Some(reservation))).await.is_err() {
+                    self.state = ScannerState::Cancelled;
+                    return Ok(());
                 }
-            };
-            if let Some(last_value) = scan_resp.data.last() {
-                self.cursor_key = lexical_next_boundary(&last_value.user_key);
-            }
-            if self.sender.send(Ok(scan_resp.data)).await.is_err() {
-                self.state = ScannerState::Cancelled;
-                return Ok(());
             }
 
             self.num_scanned += 1;
-            if !scan_resp.has_more {
+            if !has_more {
                 // This shard are scanned.
                 return Ok(());
             }
@@ -240,7 +447,7 @@ impl RangeScanner {
     }
 }
 
-fn extract_request_range(range: Range) -> (Vec<u8>, Option<Vec<u8>>) {
+pub(crate) fn extract_request_range(range: Range) -> (Vec<u8>, Option<Vec<u8>>) {
     match range {
         Range::Prefix(prefix) => {
             let end = lexical_next_boundary(&prefix);
@@ -258,6 +465,10 @@ fn is_entire_range_scanned(scan_end: Option<&[u8]>, shard_end: &[u8]) -> bool {
     }
 }
 
+fn is_entire_range_scanned_in_reverse(scan_start: &[u8], shard_start: &[u8]) -> bool {
+    shard_start <= scan_start
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -271,6 +482,16 @@ mod tests {
         assert!(!is_entire_range_scanned(Some(b"test"), b"tes"));
     }
 
+    #[test]
+    fn is_entire_range_scanned_in_reverse_basic() {
+        assert!(is_entire_range_scanned_in_reverse(b"test", b"test"));
+        assert!(is_entire_range_scanned_in_reverse(b"test", b"tes"));
+        assert!(is_entire_range_scanned_in_reverse(b"", b""));
+        assert!(is_entire_range_scanned_in_reverse(b"test", b""));
+        assert!(!is_entire_range_scanned_in_reverse(b"", b"test"));
+        assert!(!is_entire_range_scanned_in_reverse(b"tes", b"test"));
+    }
+
     #[test]
     fn extract_request_range_basic() {
         struct TestCase {
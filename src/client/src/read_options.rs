@@ -0,0 +1,111 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sekas_runtime::time::Instant;
+
+/// The consistency of the one-shot reads of [`crate::Database`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Consistency {
+    /// Read at a version allocated by root, served by the leader.
+    #[default]
+    Linearizable,
+    /// Read at a version allocated within the bound, the version allocated
+    /// recently by this client is reused to save a round trip to root. The
+    /// read is served by a read replica which has applied past the version
+    /// and heard from the leader within the bound, and falls back to the
+    /// leader otherwise.
+    BoundedStaleness(Duration),
+    /// Read at the exact version, served by the leader.
+    /// [`crate::AppError::VersionTooOld`] is returned if the version is beneath
+    /// the GC watermark.
+    Exact(u64),
+}
+
+/// The options of the one-shot reads of [`crate::Database`].
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    pub consistency: Consistency,
+}
+
+impl ReadOptions {
+    pub fn linearizable() -> Self {
+        ReadOptions { consistency: Consistency::Linearizable }
+    }
+
+    pub fn bounded_staleness(bound: Duration) -> Self {
+        ReadOptions { consistency: Consistency::BoundedStaleness(bound) }
+    }
+
+    pub fn exact(version: u64) -> Self {
+        ReadOptions { consistency: Consistency::Exact(version) }
+    }
+}
+
+/// The result of a read, with the version it is read at. The version could be
+/// used as the causal token of the following reads, see
+/// [`crate::Txn::set_causal_token`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadResult<T> {
+    pub value: T,
+    pub read_version: u64,
+}
+
+/// The latest version allocated by root for the client, and the time before it
+/// is requested.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RecentVersion {
+    inner: Arc<Mutex<Option<(u64, Instant)>>>,
+}
+
+impl RecentVersion {
+    /// Record a version, which is requested from root at `requested_at`.
+    pub(crate) fn observe(&self, version: u64, requested_at: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.map(|(v, _)| v < version).unwrap_or(true) {
+            *inner = Some((version, requested_at));
+        }
+    }
+
+    /// The recent version which is allocated within the bound.
+    pub(crate) fn within(&self, bound: Duration) -> Option<u64> {
+        let inner = self.inner.lock().unwrap();
+        inner.filter(|(_, requested_at)| requested_at.elapsed() <= bound).map(|(v, _)| v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_version_within_bound() {
+        let recent_version = RecentVersion::default();
+        assert_eq!(recent_version.within(Duration::from_secs(1)), None);
+
+        let now = Instant::now();
+        recent_version.observe(10, now);
+        recent_version.observe(5, now);
+        assert_eq!(recent_version.within(Duration::from_secs(1)), Some(10));
+
+        // The staled version is not reused.
+        let requested_at = now.checked_sub(Duration::from_secs(2)).unwrap();
+        let recent_version = RecentVersion::default();
+        recent_version.observe(10, requested_at);
+        assert_eq!(recent_version.within(Duration::from_secs(1)), None);
+        assert_eq!(recent_version.within(Duration::from_secs(3)), Some(10));
+    }
+}
@@ -13,9 +13,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use crate::{Error, Result};
+use sekas_runtime::time::Instant;
+
+use crate::{Error, ErrorContext, Result};
 
 const MIN_INTERVAL_MS: u64 = 8;
 const MAX_INTERVAL_MS: u64 = 3000;
@@ -23,24 +25,26 @@ const MAX_INTERVAL_MS: u64 = 3000;
 pub struct RetryState {
     interval_ms: u64,
     deadline: Option<Instant>,
+    start: Instant,
+    retries: u64,
+    /// The context of the last failure which has one, it is attached to the
+    /// error once the deadline is exceeded.
+    last_context: Option<Box<ErrorContext>>,
 }
 
 impl Default for RetryState {
     fn default() -> Self {
-        RetryState { interval_ms: MIN_INTERVAL_MS, deadline: None }
+        Self::with_deadline_opt(None)
     }
 }
 
 impl RetryState {
     pub fn new(timeout: Duration) -> Self {
-        RetryState { interval_ms: MIN_INTERVAL_MS, deadline: Instant::now().checked_add(timeout) }
+        Self::with_deadline_opt(Instant::now().checked_add(timeout))
     }
 
     pub fn with_timeout_opt(timeout: Option<Duration>) -> Self {
-        RetryState {
-            interval_ms: MIN_INTERVAL_MS,
-            deadline: timeout.and_then(|v| Instant::now().checked_add(v)),
-        }
+        Self::with_deadline_opt(timeout.and_then(|v| Instant::now().checked_add(v)))
     }
 
     pub fn with_deadline(deadline: Instant) -> Self {
@@ -48,7 +52,13 @@ impl RetryState {
     }
 
     pub fn with_deadline_opt(deadline: Option<Instant>) -> Self {
-        RetryState { interval_ms: MIN_INTERVAL_MS, deadline }
+        RetryState {
+            interval_ms: MIN_INTERVAL_MS,
+            deadline,
+            start: Instant::now(),
+            retries: 0,
+            last_context: None,
+        }
     }
 
     #[inline]
@@ -62,19 +72,30 @@ impl RetryState {
 
     pub fn is_retryable(&self, err: &Error) -> bool {
         match err {
-            Error::NotFound(_) | Error::EpochNotMatch(_) | Error::GroupNotAccessable(_) => true,
+            Error::NotFound(_) | Error::EpochNotMatch(_) | Error::GroupNotAccessable(..) => true,
             Error::NotLeader(..)
             | Error::GroupNotFound(_)
+            | Error::GroupRelocated(..)
+            | Error::ReplicaNotReady(..)
             | Error::NotRootLeader(..)
             | Error::Connect(_) => {
                 unreachable!()
             }
             Error::InvalidArgument(_)
-            | Error::DeadlineExceeded(_)
+            | Error::DeadlineExceeded(..)
             | Error::ResourceExhausted(_)
+            | Error::PermissionDenied(_)
+            | Error::VersionTooOld(..)
+            | Error::ValueTypeMismatch { .. }
+            | Error::MessageTooLarge { .. }
+            | Error::QuotaExceeded { .. }
+            | Error::TxnShardFenced(_)
+            | Error::ClientMemoryExhausted { .. }
             | Error::AlreadyExists(_)
             | Error::CasFailed(_, _, _)
             | Error::TxnConflict
+            | Error::InvalidJson(_)
+            | Error::RootUnavailable(_)
             | Error::Rpc(_)
             | Error::Transport(_)
             | Error::Internal(_) => false,
@@ -82,23 +103,54 @@ impl RetryState {
     }
 
     pub async fn retry(&mut self, err: Error) -> Result<()> {
+        let err = match err {
+            Error::GroupNotAccessable(group_id, ctx) => {
+                self.retries += ctx.retries;
+                self.last_context = Some(ctx.clone());
+                Error::GroupNotAccessable(group_id, ctx)
+            }
+            Error::DeadlineExceeded(msg, ctx) => {
+                return Err(Error::DeadlineExceeded(msg, self.fill_context(ctx)));
+            }
+            err => err,
+        };
         if !self.is_retryable(&err) {
             return Err(err);
         }
 
+        self.retries += 1;
         self.force_retry().await
     }
 
+    /// Accumulate the retries and the attempts of this state into the context,
+    /// the context of the last failure is used if the error has none.
+    fn fill_context(&mut self, ctx: Option<Box<ErrorContext>>) -> Option<Box<ErrorContext>> {
+        let mut ctx = match (ctx, self.last_context.take()) {
+            (Some(mut ctx), Some(last)) if ctx.group_id == last.group_id => {
+                let attempts = std::mem::replace(&mut ctx.attempts, last.attempts);
+                for attempt in attempts {
+                    ctx.record_attempt(attempt.node_id, attempt.status);
+                }
+                ctx
+            }
+            (ctx, last) => ctx.or(last)?,
+        };
+        ctx.retries += self.retries;
+        ctx.elapsed = self.start.elapsed();
+        Some(ctx)
+    }
+
     pub async fn force_retry(&mut self) -> Result<()> {
         let mut interval = Duration::from_millis(self.interval_ms);
         if let Some(deadline) = self.deadline {
             if let Some(duration) = deadline.checked_duration_since(Instant::now()) {
                 interval = std::cmp::min(interval, duration);
             } else {
-                return Err(Error::DeadlineExceeded("timeout".into()));
+                let ctx = self.fill_context(None);
+                return Err(Error::DeadlineExceeded("timeout".into(), ctx));
             }
         }
-        tokio::time::sleep(interval).await;
+        sekas_runtime::time::sleep(interval).await;
         self.interval_ms = std::cmp::min(self.interval_ms * 2, MAX_INTERVAL_MS);
         Ok(())
     }
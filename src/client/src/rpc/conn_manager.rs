@@ -13,32 +13,97 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+use prometheus::IntGauge;
 use sekas_api::server::v1::root_client::RootClient;
+use tonic::metadata::AsciiMetadataValue;
 use tonic::transport::{Channel, Endpoint};
 
-use super::NodeClient;
+use super::{NodeClient, NodeHealth};
+use crate::metrics::{CLIENT_NODE_ACTIVE_STREAMS, CLIENT_NODE_CHANNELS};
 use crate::{Error, Result};
 
+/// The default number of HTTP/2 channels to a node.
+pub const DEFAULT_MAX_CHANNELS_PER_NODE: usize = 2;
+
 #[derive(Clone, Debug)]
 pub struct ConnManager {
     connect_timeout: Option<Duration>,
     core: Arc<Mutex<Core>>,
+    node_health: NodeHealth,
+    /// The value of [`super::INTERNAL_ORIGIN_HEADER`] attached to the group
+    /// requests, it is only set on the handle used by the servers of the
+    /// cluster, see [`ConnManager::with_internal_origin`].
+    internal_origin: Option<AsciiMetadataValue>,
+    /// The max bytes of the group requests and responses of the node clients,
+    /// `0` means unlimited. See [`ConnManager::negotiate_max_message_bytes`].
+    max_message_bytes: Arc<AtomicUsize>,
+    /// The number of HTTP/2 channels of the pools created since now, see
+    /// [`ConnManager::set_max_channels_per_node`].
+    max_channels_per_node: Arc<AtomicUsize>,
 }
 
 #[derive(Debug)]
 struct Core {
-    channels: HashMap<String, ChannelInfo>,
+    pools: HashMap<String, PoolInfo>,
 }
 
 #[derive(Debug)]
-struct ChannelInfo {
-    channel: Channel,
+struct PoolInfo {
+    pool: ChannelPool,
     access: usize,
 }
 
+/// The HTTP/2 channels to a node, shared by all clients of a [`ConnManager`].
+///
+/// The streams are balanced across the channels by picking the one with the
+/// least active streams, and the channel with the lower index wins the tie,
+/// so a lazy channel is only dialed once the dialed ones are busy. The clones
+/// of a channel share its connection, a broken connection is redialed once no
+/// matter how many callers are waiting on it.
+#[derive(Clone, Debug)]
+pub struct ChannelPool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    channels: Vec<PooledChannel>,
+    metrics: Option<PoolMetrics>,
+}
+
+#[derive(Debug)]
+struct PooledChannel {
+    channel: Channel,
+    streams: AtomicUsize,
+    dialed: AtomicBool,
+}
+
+#[derive(Debug)]
+struct PoolMetrics {
+    channels: IntGauge,
+    streams: IntGauge,
+}
+
+/// Count an active stream of a channel in the pool until it is dropped.
+#[derive(Debug)]
+pub struct StreamGuard {
+    pool: Arc<PoolInner>,
+    index: usize,
+}
+
+/// The statistics of the channels to a node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// The number of the channels have been dialed.
+    pub channels: usize,
+    /// The number of the active streams over the channels.
+    pub streams: usize,
+}
+
 impl ConnManager {
     pub fn new() -> Self {
         ConnManager::default()
@@ -50,33 +115,110 @@ impl ConnManager {
         mgr
     }
 
-    // TODO(walter) add tags
+    /// The least loaded channel to the node.
     pub fn get(&self, addr: String) -> Result<Channel> {
+        Ok(self.get_pool(addr)?.pick().0)
+    }
+
+    /// The channels to the node. The pool is created under the lock, so the
+    /// concurrent callers share the same channels instead of dialing their
+    /// own.
+    pub fn get_pool(&self, addr: String) -> Result<ChannelPool> {
         let mut core = self.core.lock().unwrap();
-        if let Some(info) = core.channels.get_mut(&addr) {
+        if let Some(info) = core.pools.get_mut(&addr) {
             info.access += 1;
-            return Ok(info.channel.clone());
+            return Ok(info.pool.clone());
         }
 
-        let channel = match Endpoint::new(format!("http://{}", addr)) {
-            Ok(endpoint) => {
-                if let Some(connect_timeout) = self.connect_timeout {
-                    endpoint.connect_timeout(connect_timeout).connect_lazy()
-                } else {
-                    endpoint.connect_lazy()
-                }
-            }
+        let endpoint = match Endpoint::new(format!("http://{}", addr)) {
+            Ok(endpoint) => endpoint,
             Err(e) => return Err(Error::Internal(Box::new(e))),
         };
-        let info = ChannelInfo { channel: channel.clone(), access: 1 };
-        core.channels.insert(addr, info);
-        Ok(channel)
+        let endpoint = match self.connect_timeout {
+            Some(connect_timeout) => endpoint.connect_timeout(connect_timeout),
+            None => endpoint,
+        };
+        let num_channels = self.max_channels_per_node();
+        let channels = (0..num_channels).map(|_| endpoint.connect_lazy()).collect();
+        let pool = ChannelPool::with_metrics(channels, &addr);
+        core.pools.insert(addr, PoolInfo { pool: pool.clone(), access: 1 });
+        Ok(pool)
     }
 
     #[inline]
     pub fn get_node_client(&self, addr: String) -> Result<NodeClient> {
-        let channel = self.get(addr)?;
-        Ok(NodeClient::new(channel))
+        let pool = self.get_pool(addr)?;
+        Ok(NodeClient::with_pool(pool)
+            .with_internal_origin(self.internal_origin.clone())
+            .with_max_message_bytes(self.max_message_bytes()))
+    }
+
+    /// Limit the HTTP/2 channels to each node of the pools created since now.
+    /// The value is at least 1.
+    pub fn set_max_channels_per_node(&self, max_channels: usize) {
+        self.max_channels_per_node.store(max_channels.max(1), Ordering::Release);
+    }
+
+    /// The max HTTP/2 channels to each node.
+    #[inline]
+    pub fn max_channels_per_node(&self) -> usize {
+        self.max_channels_per_node.load(Ordering::Acquire)
+    }
+
+    /// The statistics of the channels to each node.
+    pub fn channel_stats(&self) -> HashMap<String, ChannelStats> {
+        let core = self.core.lock().unwrap();
+        core.pools.iter().map(|(addr, info)| (addr.clone(), info.pool.stats())).collect()
+    }
+
+    /// Limit the bytes of the group requests and responses of the node
+    /// clients created since now, `0` means unlimited.
+    pub fn set_max_message_bytes(&self, max_message_bytes: usize) {
+        self.max_message_bytes.store(max_message_bytes, Ordering::Release);
+    }
+
+    /// Lower the max message bytes to the limit advertised by the nodes, so
+    /// the requests exceeding it are rejected before they are sent. `0` means
+    /// the nodes are unlimited.
+    pub fn negotiate_max_message_bytes(&self, node_limit: usize) {
+        if node_limit == 0 {
+            return;
+        }
+        let _ = self.max_message_bytes.fetch_update(Ordering::AcqRel, Ordering::Acquire, |v| {
+            (v == 0 || v > node_limit).then_some(node_limit)
+        });
+    }
+
+    /// The max bytes of the group requests and responses, `0` means
+    /// unlimited.
+    #[inline]
+    pub fn max_message_bytes(&self) -> usize {
+        self.max_message_bytes.load(Ordering::Acquire)
+    }
+
+    /// A handle sharing the channels of this manager, whose node clients mark
+    /// the group requests as issued by the servers of the cluster. The servers
+    /// verify the origin before writing the system tables. The other handles
+    /// of the manager don't carry the origin, so they could be handed to the
+    /// proxies.
+    pub fn with_internal_origin(&self, origin: &[u8]) -> Result<ConnManager> {
+        let value = std::str::from_utf8(origin)
+            .ok()
+            .and_then(|origin| AsciiMetadataValue::try_from(origin).ok())
+            .ok_or_else(|| Error::InvalidArgument(format!("internal origin {origin:?}")))?;
+        Ok(ConnManager { internal_origin: Some(value), ..self.clone() })
+    }
+
+    /// The internal origin carried by this handle, see
+    /// [`ConnManager::with_internal_origin`].
+    pub fn internal_origin(&self) -> Option<AsciiMetadataValue> {
+        self.internal_origin.clone()
+    }
+
+    /// The health of nodes, shared by the clients of this manager.
+    #[inline]
+    pub fn node_health(&self) -> &NodeHealth {
+        &self.node_health
     }
 
     #[inline]
@@ -88,7 +230,7 @@ impl ConnManager {
 
 impl Default for ConnManager {
     fn default() -> Self {
-        let core = Arc::new(Mutex::new(Core { channels: HashMap::default() }));
+        let core = Arc::new(Mutex::new(Core { pools: HashMap::default() }));
         let cloned_core = core.clone();
 
         // FIXME
@@ -97,17 +239,108 @@ impl Default for ConnManager {
         tokio::spawn(async move {
             recycle_conn_main(cloned_core).await;
         });
-        ConnManager { core, connect_timeout: None }
+        ConnManager {
+            core,
+            connect_timeout: None,
+            node_health: NodeHealth::default(),
+            internal_origin: None,
+            max_message_bytes: Arc::default(),
+            max_channels_per_node: Arc::new(AtomicUsize::new(DEFAULT_MAX_CHANNELS_PER_NODE)),
+        }
+    }
+}
+
+impl ChannelPool {
+    /// A pool of the single channel, it isn't reported to the metrics.
+    pub fn single(channel: Channel) -> Self {
+        ChannelPool::new(vec![channel], None)
+    }
+
+    fn with_metrics(channels: Vec<Channel>, addr: &str) -> Self {
+        let metrics = PoolMetrics {
+            channels: CLIENT_NODE_CHANNELS.with_label_values(&[addr]),
+            streams: CLIENT_NODE_ACTIVE_STREAMS.with_label_values(&[addr]),
+        };
+        ChannelPool::new(channels, Some(metrics))
+    }
+
+    fn new(channels: Vec<Channel>, metrics: Option<PoolMetrics>) -> Self {
+        debug_assert!(!channels.is_empty());
+        let channels = channels
+            .into_iter()
+            .map(|channel| PooledChannel {
+                channel,
+                streams: AtomicUsize::new(0),
+                dialed: AtomicBool::new(false),
+            })
+            .collect();
+        ChannelPool { inner: Arc::new(PoolInner { channels, metrics }) }
+    }
+
+    /// Pick the channel with the least active streams, the stream is counted
+    /// until the returned guard is dropped.
+    pub fn pick(&self) -> (Channel, StreamGuard) {
+        let inner = &self.inner;
+        let (index, pooled) = inner
+            .channels
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, c)| c.streams.load(Ordering::Acquire))
+            .expect("the channel pool is not empty");
+        pooled.streams.fetch_add(1, Ordering::AcqRel);
+        let first_dial = !pooled.dialed.swap(true, Ordering::AcqRel);
+        if let Some(metrics) = inner.metrics.as_ref() {
+            if first_dial {
+                metrics.channels.inc();
+            }
+            metrics.streams.inc();
+        }
+        (pooled.channel.clone(), StreamGuard { pool: inner.clone(), index })
+    }
+
+    /// The statistics of the channels.
+    pub fn stats(&self) -> ChannelStats {
+        let channels = &self.inner.channels;
+        ChannelStats {
+            channels: channels.iter().filter(|c| c.dialed.load(Ordering::Acquire)).count(),
+            streams: channels.iter().map(|c| c.streams.load(Ordering::Acquire)).sum(),
+        }
+    }
+
+    /// Whether the pool is referenced by others, eg the node clients and the
+    /// active streams.
+    fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.inner) > 1
+    }
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.pool.channels[self.index].streams.fetch_sub(1, Ordering::AcqRel);
+        if let Some(metrics) = self.pool.metrics.as_ref() {
+            metrics.streams.dec();
+        }
+    }
+}
+
+impl Drop for PoolInner {
+    fn drop(&mut self) {
+        if let Some(metrics) = self.metrics.as_ref() {
+            let dialed = self.channels.iter().filter(|c| c.dialed.load(Ordering::Acquire)).count();
+            metrics.channels.sub(dialed as i64);
+        }
     }
 }
 
 async fn recycle_conn_main(core: Arc<Mutex<Core>>) {
-    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    let mut interval = sekas_runtime::time::interval(Duration::from_secs(60));
     loop {
         interval.tick().await;
         let mut core = core.lock().unwrap();
-        core.channels.retain(|_, v| {
-            if v.access == 0 {
+        // The pools still used by the node clients are kept, otherwise the next
+        // access would dial new channels to the same node.
+        core.pools.retain(|_, v| {
+            if v.access == 0 && !v.pool.is_shared() {
                 false
             } else {
                 v.access = 0;
@@ -116,3 +349,52 @@ async fn recycle_conn_main(core: Arc<Mutex<Core>>) {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn channel_pool_balance_streams() {
+        let mgr = ConnManager::new();
+        mgr.set_max_channels_per_node(2);
+        let addr = "127.0.0.1:1".to_owned();
+        let pool = mgr.get_pool(addr.clone()).unwrap();
+        assert_eq!(pool.stats(), ChannelStats::default());
+
+        // The second channel is only dialed once the first one is busy.
+        let (_, guard) = pool.pick();
+        drop(guard);
+        let (_, first) = pool.pick();
+        assert_eq!(pool.stats(), ChannelStats { channels: 1, streams: 1 });
+        let (_, second) = pool.pick();
+        assert_ne!(first.index, second.index);
+        let guards = (0..4).map(|_| pool.pick().1).collect::<Vec<_>>();
+        assert_eq!(pool.stats(), ChannelStats { channels: 2, streams: 6 });
+        assert_eq!(guards.iter().filter(|g| g.index == first.index).count(), 2);
+
+        // The node clients share the pool of the node.
+        let _client = mgr.get_node_client(addr.clone()).unwrap();
+        mgr.set_max_channels_per_node(0);
+        assert_eq!(mgr.max_channels_per_node(), 1);
+        drop((first, second, guards));
+        let stats = mgr.channel_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[&addr], ChannelStats { channels: 2, streams: 0 });
+    }
+
+    #[tokio::test]
+    async fn internal_origin_only_on_internal_handle() {
+        let mgr = ConnManager::new();
+        let internal = mgr.with_internal_origin(b"secret").unwrap();
+        assert_eq!(internal.internal_origin().unwrap(), "secret");
+        assert!(mgr.internal_origin().is_none());
+        assert!(mgr.clone().internal_origin().is_none());
+        assert!(mgr.with_internal_origin(b"the secret\n").is_err());
+
+        // The handles share the channels of the nodes.
+        let addr = "127.0.0.1:1".to_owned();
+        let _client = internal.get_node_client(addr.clone()).unwrap();
+        assert!(mgr.channel_stats().contains_key(&addr));
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The databases and tables observed dropped by the router.
+//!
+//! The ids of databases and tables are never reused, so a database or table
+//! recreated under the same name has a new id, the handles of the dropped one
+//! keep failing.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Formatter};
+use std::sync::{Arc, Mutex};
+
+/// The handler called once the database is dropped, see
+/// [`crate::Database::on_dropped`].
+pub(crate) type DropHandler = Box<dyn FnOnce() + Send + 'static>;
+
+#[derive(Clone, Default)]
+pub(crate) struct DroppedSchemas {
+    core: Arc<Mutex<DroppedCore>>,
+}
+
+#[derive(Default)]
+struct DroppedCore {
+    databases: HashSet<u64>,
+    tables: HashSet<u64>,
+    handlers: HashMap<u64 /* db */, Vec<DropHandler>>,
+}
+
+impl DroppedSchemas {
+    pub fn is_database_dropped(&self, db_id: u64) -> bool {
+        self.core.lock().unwrap().databases.contains(&db_id)
+    }
+
+    pub fn is_table_dropped(&self, table_id: u64) -> bool {
+        self.core.lock().unwrap().tables.contains(&table_id)
+    }
+
+    /// Mark the database and the tables of it dropped, the handlers of the
+    /// database are called in background.
+    pub fn drop_database(&self, db_id: u64, tables: impl IntoIterator<Item = u64>) {
+        let handlers = {
+            let mut core = self.core.lock().unwrap();
+            core.databases.insert(db_id);
+            core.tables.extend(tables);
+            core.handlers.remove(&db_id).unwrap_or_default()
+        };
+        for handler in handlers {
+            spawn_handler(handler);
+        }
+    }
+
+    pub fn drop_table(&self, table_id: u64) {
+        self.core.lock().unwrap().tables.insert(table_id);
+    }
+
+    /// Register the handler of the database, it is called immediately if the
+    /// database is already dropped.
+    pub fn on_database_dropped(&self, db_id: u64, handler: DropHandler) {
+        let mut core = self.core.lock().unwrap();
+        if core.databases.contains(&db_id) {
+            drop(core);
+            spawn_handler(handler);
+        } else {
+            core.handlers.entry(db_id).or_default().push(handler);
+        }
+    }
+}
+
+impl Debug for DroppedSchemas {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let core = self.core.lock().unwrap();
+        f.debug_struct("DroppedSchemas")
+            .field("databases", &core.databases)
+            .field("tables", &core.tables)
+            .field("handlers", &core.handlers.values().map(Vec::len).sum::<usize>())
+            .finish()
+    }
+}
+
+/// The handler is called outside of the router, so it could use the client.
+fn spawn_handler(handler: DropHandler) {
+    tokio::spawn(async move { handler() });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn drop_database_calls_handlers() {
+        let dropped = DroppedSchemas::default();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let counter = Arc::new(AtomicUsize::new(0));
+        let handler = |sender: tokio::sync::mpsc::UnboundedSender<u64>,
+                       counter: Arc<AtomicUsize>| {
+            Box::new(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                sender.send(1).unwrap();
+            }) as DropHandler
+        };
+        dropped.on_database_dropped(1, handler(sender.clone(), counter.clone()));
+        dropped.on_database_dropped(2, handler(sender.clone(), counter.clone()));
+        dropped.drop_database(1, [10, 11]);
+        receiver.recv().await.unwrap();
+        assert!(dropped.is_database_dropped(1));
+        assert!(!dropped.is_database_dropped(2));
+        assert!(dropped.is_table_dropped(10) && dropped.is_table_dropped(11));
+
+        // The handler of a dropped database is called immediately.
+        dropped.on_database_dropped(1, handler(sender, counter.clone()));
+        receiver.recv().await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+
+        dropped.drop_table(12);
+        assert!(dropped.is_table_dropped(12));
+    }
+}
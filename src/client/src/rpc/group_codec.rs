@@ -0,0 +1,260 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The group requests encoded once and shared by the retries.
+//!
+//! The fields of a protobuf message could be encoded in any order, so the
+//! payload of a [`GroupRequest`], which is everything except the group id and
+//! the epoch, is encoded once, and each attempt only encodes the group id and
+//! the epoch it is issued with in front of the shared payload.
+
+use bytes::{Buf, BufMut, Bytes};
+use prost::encoding::{self, WireType};
+use prost::Message;
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::{GroupRequest, GroupResponse};
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder, ProstCodec};
+use tonic::Status;
+
+/// The field tags of [`GroupRequest`].
+const GROUP_ID_TAG: u32 = 1;
+const EPOCH_TAG: u32 = 2;
+const REQUEST_TAG: u32 = 3;
+const REQUEST_ID_TAG: u32 = 4;
+const RECORD_REQUEST_ID_TAG: u32 = 5;
+const ALLOW_FORWARD_TAG: u32 = 6;
+
+/// A [`GroupRequest`] whose payload is encoded. Cloning it only increases the
+/// reference count of the payload.
+#[derive(Debug, Clone)]
+pub struct EncodedGroupRequest {
+    group_id: u64,
+    epoch: u64,
+    payload: Bytes,
+}
+
+impl EncodedGroupRequest {
+    /// Encode the payload of the group request of the request, without any
+    /// copies of the request.
+    pub fn new(
+        request: &Request,
+        request_id: &str,
+        record_request_id: Option<bool>,
+        allow_forward: bool,
+    ) -> Self {
+        let request_len = request.encoded_len();
+        let mut payload = Vec::with_capacity(request_len + request_id.len() + 32);
+        encoding::encode_key(REQUEST_TAG, WireType::LengthDelimited, &mut payload);
+        encoding::encode_varint(request_len as u64, &mut payload);
+        request.encode(&mut payload);
+        if !request_id.is_empty() {
+            encoding::encode_key(REQUEST_ID_TAG, WireType::LengthDelimited, &mut payload);
+            encoding::encode_varint(request_id.len() as u64, &mut payload);
+            payload.put_slice(request_id.as_bytes());
+        }
+        if let Some(record_request_id) = record_request_id {
+            encoding::bool::encode(RECORD_REQUEST_ID_TAG, &record_request_id, &mut payload);
+        }
+        if allow_forward {
+            encoding::bool::encode(ALLOW_FORWARD_TAG, &allow_forward, &mut payload);
+        }
+        EncodedGroupRequest { group_id: 0, epoch: 0, payload: payload.into() }
+    }
+
+    /// The request to issue to the group with the epoch, the payload is
+    /// shared.
+    pub fn with_epoch(&self, group_id: u64, epoch: u64) -> Self {
+        EncodedGroupRequest { group_id, epoch, payload: self.payload.clone() }
+    }
+
+    /// The size of the request on wire.
+    pub fn encoded_len(&self) -> usize {
+        let mut len = self.payload.len();
+        if self.group_id != 0 {
+            len += encoding::uint64::encoded_len(GROUP_ID_TAG, &self.group_id);
+        }
+        if self.epoch != 0 {
+            len += encoding::uint64::encoded_len(EPOCH_TAG, &self.epoch);
+        }
+        len
+    }
+
+    /// Encode the request, it has the same wire bytes as the [`GroupRequest`]
+    /// with the fields encoded in order.
+    pub fn encode_raw(&self, buf: &mut impl BufMut) {
+        if self.group_id != 0 {
+            encoding::uint64::encode(GROUP_ID_TAG, &self.group_id, buf);
+        }
+        if self.epoch != 0 {
+            encoding::uint64::encode(EPOCH_TAG, &self.epoch, buf);
+        }
+        buf.put_slice(&self.payload);
+    }
+}
+
+impl From<GroupRequest> for EncodedGroupRequest {
+    fn from(mut req: GroupRequest) -> Self {
+        let (group_id, epoch) = (req.group_id, req.epoch);
+        req.group_id = 0;
+        req.epoch = 0;
+        EncodedGroupRequest { group_id, epoch, payload: req.encode_to_vec().into() }
+    }
+}
+
+/// The codec of the group rpc, which sends [`EncodedGroupRequest`] and
+/// receives [`GroupResponse`].
+#[derive(Debug, Default, Clone)]
+pub(crate) struct GroupCodec {
+    /// The max bytes of the received responses, `0` means unlimited.
+    max_message_bytes: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct GroupEncoder;
+
+pub(crate) struct GroupDecoder {
+    inner: <ProstCodec<GroupRequest, GroupResponse> as Codec>::Decoder,
+    max_message_bytes: usize,
+}
+
+impl GroupCodec {
+    pub(crate) fn new(max_message_bytes: usize) -> Self {
+        GroupCodec { max_message_bytes }
+    }
+}
+
+impl Codec for GroupCodec {
+    type Encode = EncodedGroupRequest;
+    type Decode = GroupResponse;
+    type Encoder = GroupEncoder;
+    type Decoder = GroupDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        GroupEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        GroupDecoder {
+            inner: ProstCodec::<GroupRequest, GroupResponse>::default().decoder(),
+            max_message_bytes: self.max_message_bytes,
+        }
+    }
+}
+
+impl Encoder for GroupEncoder {
+    type Item = EncodedGroupRequest;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, buf: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        buf.reserve(item.encoded_len());
+        item.encode_raw(buf);
+        Ok(())
+    }
+}
+
+impl Decoder for GroupDecoder {
+    type Item = GroupResponse;
+    type Error = Status;
+
+    fn decode(&mut self, buf: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        let size = buf.remaining();
+        if self.max_message_bytes != 0 && size > self.max_message_bytes {
+            return Err(message_too_large(size, self.max_message_bytes));
+        }
+        self.inner.decode(buf)
+    }
+}
+
+/// The status of a message exceeds the max message bytes, it carries the same
+/// details as the one returned by the nodes.
+pub(crate) fn message_too_large(size: usize, limit: usize) -> Status {
+    use sekas_api::server::v1;
+
+    Status::with_details(
+        tonic::Code::ResourceExhausted,
+        format!("message length too large: found {size} bytes, the limit is: {limit} bytes"),
+        v1::Error::message_too_large(size as u64, limit as u64).encode_to_vec().into(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::server::v1::*;
+
+    use super::*;
+
+    fn decode(req: &EncodedGroupRequest) -> GroupRequest {
+        let mut buf = Vec::new();
+        req.encode_raw(&mut buf);
+        assert_eq!(buf.len(), req.encoded_len());
+        GroupRequest::decode(buf.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn encoded_group_request_wire_bytes() {
+        let put =
+            PutRequest { key: b"key".to_vec(), value: vec![b'v'; 4096], ..Default::default() };
+        let request = Request::Write(ShardWriteRequest {
+            shard_id: 1,
+            puts: vec![put],
+            ..Default::default()
+        });
+        let expect = |epoch, request_id: &str, record_request_id, allow_forward| GroupRequest {
+            group_id: 7,
+            epoch,
+            request: Some(GroupRequestUnion { request: Some(request.clone()) }),
+            request_id: request_id.to_owned(),
+            record_request_id,
+            allow_forward,
+        };
+
+        // The epoch is changed between the attempts.
+        let encoded = EncodedGroupRequest::new(&request, "id", Some(true), true);
+        for epoch in [1, 2, 300, u64::MAX] {
+            let req = encoded.with_epoch(7, epoch);
+            assert_eq!(decode(&req), expect(epoch, "id", Some(true), true));
+            assert_eq!(req.encoded_len(), expect(epoch, "id", Some(true), true).encoded_len());
+        }
+
+        // The default values are not encoded.
+        let encoded = EncodedGroupRequest::new(&request, "", None, false);
+        assert_eq!(decode(&encoded.with_epoch(7, 0)), expect(0, "", None, false));
+        assert_eq!(
+            encoded.with_epoch(7, 0).encoded_len(),
+            expect(0, "", None, false).encoded_len()
+        );
+
+        let req = GroupRequest::create_shard(3, 5, ShardDesc { id: 9, ..Default::default() });
+        let encoded = EncodedGroupRequest::from(req.clone());
+        assert_eq!(decode(&encoded), req);
+        let mut expect = req;
+        expect.epoch = 6;
+        assert_eq!(decode(&encoded.with_epoch(3, 6)), expect);
+    }
+
+    #[test]
+    fn message_too_large_status() {
+        use crate::Error;
+
+        let status = message_too_large(5 << 20, 4 << 20);
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(matches!(
+            Error::from(status),
+            Error::MessageTooLarge { size, limit } if size == 5 << 20 && limit == 4 << 20
+        ));
+
+        let status = Status::resource_exhausted("scan limit max_bytes (1024)");
+        assert!(matches!(Error::from(status), Error::ResourceExhausted(_)));
+    }
+}
@@ -13,11 +13,27 @@
 // limitations under the License.
 
 mod conn_manager;
+mod dropped_schema;
+mod group_codec;
 mod node_client;
+mod node_health;
+mod root_circuit;
 mod root_client;
+mod route_event;
 mod router;
+mod shard_lease;
 
-pub use self::conn_manager::ConnManager;
-pub use self::node_client::{Client as NodeClient, RpcTimeout};
+pub use self::conn_manager::{
+    ChannelPool, ChannelStats, ConnManager, StreamGuard, DEFAULT_MAX_CHANNELS_PER_NODE,
+};
+pub use self::group_codec::EncodedGroupRequest;
+pub use self::node_client::{
+    Client as NodeClient, GroupStreaming, RpcTimeout, INTERNAL_ORIGIN_HEADER,
+    MAX_MESSAGE_BYTES_HEADER,
+};
+pub use self::node_health::NodeHealth;
+pub use self::root_circuit::RootStatus;
 pub use self::root_client::Client as RootClient;
+pub use self::route_event::{RouteEvent, RouteEventFilter, RouteEventKind};
 pub use self::router::{Router, RouterGroupState};
+pub use self::shard_lease::{ShardLease, ShardLeaseNotice, ShardLeaseOptions};
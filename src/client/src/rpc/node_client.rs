@@ -13,31 +13,104 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use std::time::Duration;
 
+use futures::Stream;
 use prost::Message;
 use sekas_api::server::v1::*;
-use tonic::transport::Channel;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::AsciiMetadataValue;
+use tonic::transport::{Channel, Endpoint};
 use tonic::IntoRequest;
 
+use super::conn_manager::{ChannelPool, StreamGuard};
+use super::group_codec::{message_too_large, EncodedGroupRequest, GroupCodec};
+
+/// The header carries the origin of the group requests issued by the servers
+/// of the cluster, the writes to the system tables are rejected without it.
+pub const INTERNAL_ORIGIN_HEADER: &str = "sekas-internal-origin";
+
+/// The header carries the max bytes of the group responses accepted by the
+/// client, the nodes split the scans into the frames within it.
+pub const MAX_MESSAGE_BYTES_HEADER: &str = "sekas-max-message-bytes";
+
 #[derive(Debug, Clone)]
 pub struct Client {
-    client: node_client::NodeClient<Channel>,
+    channels: ChannelPool,
+    internal_origin: Option<AsciiMetadataValue>,
+    /// The max bytes of the group requests and responses, `0` means
+    /// unlimited.
+    max_message_bytes: usize,
+}
+
+/// The response stream of a group request, the stream is counted by the
+/// channel pool until it is dropped, see [`StreamGuard`].
+#[derive(Debug)]
+pub struct GroupStreaming {
+    inner: tonic::Streaming<GroupResponse>,
+    _stream: StreamGuard,
 }
 
 impl Client {
     pub fn new(channel: Channel) -> Self {
-        Client { client: node_client::NodeClient::new(channel) }
+        Client::with_pool(ChannelPool::single(channel))
+    }
+
+    /// Issue the requests over the channels of the pool, see [`ChannelPool`].
+    pub fn with_pool(channels: ChannelPool) -> Self {
+        Client { channels, internal_origin: None, max_message_bytes: 0 }
+    }
+
+    /// The client of the least loaded channel, the stream is counted until the
+    /// guard is dropped.
+    fn client(&self) -> (node_client::NodeClient<Channel>, StreamGuard) {
+        let (channel, guard) = self.channels.pick();
+        (node_client::NodeClient::new(channel), guard)
+    }
+
+    /// Attach the origin to the group requests, see [`INTERNAL_ORIGIN_HEADER`].
+    pub fn with_internal_origin(mut self, internal_origin: Option<AsciiMetadataValue>) -> Self {
+        self.internal_origin = internal_origin;
+        self
+    }
+
+    /// Limit the bytes of the group requests and responses, see
+    /// [`MAX_MESSAGE_BYTES_HEADER`]. `0` means unlimited.
+    pub fn with_max_message_bytes(mut self, max_message_bytes: usize) -> Self {
+        self.max_message_bytes = max_message_bytes;
+        self
+    }
+
+    fn attach_origin<T>(&self, req: impl IntoRequest<T>) -> tonic::Request<T> {
+        let mut req = req.into_request();
+        if let Some(origin) = self.internal_origin.as_ref() {
+            req.metadata_mut().insert(INTERNAL_ORIGIN_HEADER, origin.clone());
+        }
+        if self.max_message_bytes != 0 {
+            req.metadata_mut().insert(MAX_MESSAGE_BYTES_HEADER, self.max_message_bytes.into());
+        }
+        req
+    }
+
+    /// Reject the request exceeds the max message bytes before it is sent, it
+    /// would be rejected by every node.
+    fn check_message_size(&self, size: usize) -> Result<(), tonic::Status> {
+        if self.max_message_bytes != 0 && size > self.max_message_bytes {
+            return Err(message_too_large(size, self.max_message_bytes));
+        }
+        Ok(())
     }
 
     pub async fn connect(addr: String) -> Result<Self, tonic::transport::Error> {
         let addr = format!("http://{}", addr);
-        let client = node_client::NodeClient::connect(addr).await?;
-        Ok(Self { client })
+        let channel = Endpoint::new(addr)?.connect().await?;
+        Ok(Self::new(channel))
     }
 
     pub async fn get_root(&self) -> Result<RootDesc, tonic::Status> {
-        let mut client = self.client.clone();
+        let (mut client, _stream) = self.client();
         let resp = client
             .admin(NodeAdminRequest {
                 request: Some(node_admin_request::Request::GetRoot(GetRootRequest::default())),
@@ -57,7 +130,7 @@ impl Client {
         replica_id: u64,
         group_desc: GroupDesc,
     ) -> Result<(), tonic::Status> {
-        let mut client = self.client.clone();
+        let (mut client, _stream) = self.client();
         let req = CreateReplicaRequest { replica_id, group: Some(group_desc) };
         let resp = client
             .admin(NodeAdminRequest {
@@ -78,7 +151,7 @@ impl Client {
         replica_id: u64,
         group: GroupDesc,
     ) -> Result<(), tonic::Status> {
-        let mut client = self.client.clone();
+        let (mut client, _stream) = self.client();
         let req = RemoveReplicaRequest { replica_id, group: Some(group) };
         let resp = client
             .admin(NodeAdminRequest {
@@ -93,20 +166,173 @@ impl Client {
         }
     }
 
+    /// Search the recent raft entries of the replica, which write keys with
+    /// the prefix.
+    pub async fn search_raft_log(
+        &self,
+        req: SearchRaftLogRequest,
+    ) -> Result<Vec<RaftLogEntry>, tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::SearchRaftLog(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::SearchRaftLog(resp)) => Ok(resp.entries),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `SearchRaftLogResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    /// Compact the data of the replica, and returns the number of versions and
+    /// bytes dropped by the compaction.
+    pub async fn compact_group(
+        &self,
+        req: CompactGroupRequest,
+    ) -> Result<CompactGroupResponse, tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::CompactGroup(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::CompactGroup(resp)) => Ok(resp),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `CompactGroupResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    /// The limits of the node, see [`NodeCapabilities`].
+    pub async fn get_capabilities(&self) -> Result<NodeCapabilities, tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let req = GetCapabilitiesRequest::default();
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::GetCapabilities(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::GetCapabilities(resp)) => {
+                Ok(resp.capabilities.unwrap_or_default())
+            }
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `GetCapabilitiesResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    /// The build and runtime status of the node, see [`NodeRuntimeStatus`].
+    pub async fn get_node_status(&self) -> Result<NodeRuntimeStatus, tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let req = GetNodeStatusRequest::default();
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::GetNodeStatus(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::GetNodeStatus(resp)) => {
+                Ok(resp.status.unwrap_or_default())
+            }
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `GetNodeStatusResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    /// Compact the data of the replica served by the node, it returns once the
+    /// compaction is finished.
+    pub async fn compact_replica(
+        &self,
+        req: CompactReplicaRequest,
+    ) -> Result<CompactReplicaResponse, tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::CompactReplica(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::CompactReplica(resp)) => Ok(resp),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `CompactReplicaResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    /// Pin the GC watermark of the node at the version, see [`PinGcRequest`].
+    pub async fn pin_gc(&self, req: PinGcRequest) -> Result<PinGcResponse, tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let resp = client
+            .admin(NodeAdminRequest { request: Some(node_admin_request::Request::PinGc(req)) })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::PinGc(resp)) => Ok(resp),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `PinGcResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    /// Override the log level of a target on the node, and returns the
+    /// effective filter of the node.
+    pub async fn set_log_filter(&self, req: SetLogFilterRequest) -> Result<String, tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::SetLogFilter(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::SetLogFilter(resp)) => Ok(resp.filter),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `SetLogFilterResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    /// Resolve the quarantine of the replica, and returns the quarantine
+    /// remained after the action.
+    pub async fn resolve_quarantine(
+        &self,
+        req: ResolveQuarantineRequest,
+    ) -> Result<Option<ApplyQuarantine>, tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::ResolveQuarantine(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::ResolveQuarantine(resp)) => Ok(resp.quarantine),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `ResolveQuarantineResponse` is required".to_owned(),
+            )),
+        }
+    }
+
     pub async fn group_request(
         &self,
         req: impl IntoRequest<GroupRequest>,
-    ) -> Result<tonic::Streaming<GroupResponse>, tonic::Status> {
-        let mut client = self.client.clone();
+    ) -> Result<GroupStreaming, tonic::Status> {
+        let req = self.attach_origin(req);
+        self.check_message_size(req.get_ref().encoded_len())?;
+        let (mut client, stream) = self.client();
         let res = client.group(req).await?;
-        Ok(res.into_inner())
+        Ok(GroupStreaming { inner: res.into_inner(), _stream: stream })
     }
 
     pub async fn unary_group_request(
         &self,
         req: impl IntoRequest<GroupRequest>,
     ) -> Result<GroupResponse, tonic::Status> {
-        let mut client = self.client.clone();
+        let req = self.attach_origin(req);
+        self.check_message_size(req.get_ref().encoded_len())?;
+        let (mut client, _stream) = self.client();
         let res = client.group(req).await?;
         res.into_inner()
             .message()
@@ -114,11 +340,41 @@ impl Client {
             .ok_or_else(|| tonic::Status::internal("group response stream is empty"))
     }
 
+    /// Issue the group request whose payload is encoded, see
+    /// [`EncodedGroupRequest`].
+    pub async fn encoded_group_request(
+        &self,
+        req: impl IntoRequest<EncodedGroupRequest>,
+    ) -> Result<GroupStreaming, tonic::Status> {
+        let req = self.attach_origin(req);
+        self.check_message_size(req.get_ref().encoded_len())?;
+        let (channel, stream) = self.channels.pick();
+        let mut grpc = tonic::client::Grpc::new(channel);
+        grpc.ready()
+            .await
+            .map_err(|e| tonic::Status::unknown(format!("Service was not ready: {e}")))?;
+        let path = PathAndQuery::from_static("/sekas.server.v1.Node/Group");
+        let codec = GroupCodec::new(self.max_message_bytes);
+        let res = grpc.server_streaming(req, path, codec).await?;
+        Ok(GroupStreaming { inner: res.into_inner(), _stream: stream })
+    }
+
+    pub async fn unary_encoded_group_request(
+        &self,
+        req: impl IntoRequest<EncodedGroupRequest>,
+    ) -> Result<GroupResponse, tonic::Status> {
+        self.encoded_group_request(req)
+            .await?
+            .message()
+            .await?
+            .ok_or_else(|| tonic::Status::internal("group response stream is empty"))
+    }
+
     pub async fn root_heartbeat(
         &self,
         req: HeartbeatRequest,
     ) -> Result<HeartbeatResponse, tonic::Status> {
-        let mut client = self.client.clone();
+        let (mut client, _stream) = self.client();
         let resp = client
             .admin(NodeAdminRequest { request: Some(node_admin_request::Request::Heartbeat(req)) })
             .await?;
@@ -131,7 +387,7 @@ impl Client {
     }
 
     pub async fn forward(&self, req: ForwardRequest) -> Result<ForwardResponse, tonic::Status> {
-        let mut client = self.client.clone();
+        let (mut client, _stream) = self.client();
         let resp = client
             .move_shard(MoveShardRequest {
                 request: Some(move_shard_request::Request::Forward(req)),
@@ -146,7 +402,7 @@ impl Client {
     }
 
     pub async fn acquire_shard(&self, desc: MoveShardDesc) -> Result<(), tonic::Status> {
-        let mut client = self.client.clone();
+        let (mut client, _stream) = self.client();
         let resp = client
             .move_shard(MoveShardRequest {
                 request: Some(move_shard_request::Request::AcquireShard(AcquireShardRequest {
@@ -163,7 +419,7 @@ impl Client {
     }
 
     pub async fn move_out(&self, desc: MoveShardDesc) -> Result<(), tonic::Status> {
-        let mut client = self.client.clone();
+        let (mut client, _stream) = self.client();
         let resp = client
             .move_shard(MoveShardRequest {
                 request: Some(move_shard_request::Request::MoveOut(MoveOutRequest {
@@ -178,21 +434,85 @@ impl Client {
             )),
         }
     }
+
+    pub async fn cancel_move(&self, desc: MoveShardDesc) -> Result<(), tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let resp = client
+            .move_shard(MoveShardRequest {
+                request: Some(move_shard_request::Request::CancelMove(CancelMoveRequest {
+                    desc: Some(desc),
+                })),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(move_shard_response::Response::CancelMove(_)) => Ok(()),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `CancelMoveResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    pub async fn abort_move(&self, desc: MoveShardDesc) -> Result<(), tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let resp = client
+            .move_shard(MoveShardRequest {
+                request: Some(move_shard_request::Request::AbortMove(AbortMoveRequest {
+                    desc: Some(desc),
+                })),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(move_shard_response::Response::AbortMove(_)) => Ok(()),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `AbortMoveResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    pub async fn ingest(&self, req: IngestRequest) -> Result<(), tonic::Status> {
+        let (mut client, _stream) = self.client();
+        let resp = client
+            .move_shard(MoveShardRequest {
+                request: Some(move_shard_request::Request::Ingest(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(move_shard_response::Response::Ingest(_)) => Ok(()),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `IngestResponse` is required".to_owned(),
+            )),
+        }
+    }
+}
+
+impl GroupStreaming {
+    /// Fetch the next response of the stream.
+    pub async fn message(&mut self) -> Result<Option<GroupResponse>, tonic::Status> {
+        self.inner.message().await
+    }
+}
+
+impl Stream for GroupStreaming {
+    type Item = Result<GroupResponse, tonic::Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
 }
 
 #[derive(Default, Clone, Debug)]
-pub struct RpcTimeout<T: Message> {
+pub struct RpcTimeout<T> {
     timeout: Option<Duration>,
     msg: T,
 }
 
-impl<T: Message> RpcTimeout<T> {
+impl<T> RpcTimeout<T> {
     pub fn new(timeout: Option<Duration>, msg: T) -> Self {
         RpcTimeout { timeout, msg }
     }
 }
 
-impl<T: Message> IntoRequest<T> for RpcTimeout<T> {
+impl<T> IntoRequest<T> for RpcTimeout<T> {
     fn into_request(self) -> tonic::Request<T> {
         use tonic::Request;
 
@@ -462,7 +782,7 @@ mod transport_error_tests {
             }
         });
 
-        tokio::time::sleep(Duration::from_secs(1)).await;
+        sekas_runtime::time::sleep(Duration::from_secs(1)).await;
 
         drop(socket);
         handle.await.unwrap();
@@ -0,0 +1,252 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The health of nodes observed by the requests of a client.
+//!
+//! Each node has an exponentially decayed counter of the errors and successes
+//! of the requests issued to it, which is shared by all the group clients, so
+//! a flaky node is discovered once instead of by each request.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sekas_api::server::v1::ReplicaDesc;
+use sekas_runtime::time::Instant;
+
+/// The counters are halved after each half life.
+const HALF_LIFE: Duration = Duration::from_secs(10);
+
+/// The counters are scaled down once the sum exceeds it, so that even a node
+/// failing all the requests returns to the rotation after about
+/// `log2(MAX_SAMPLES)` half lives (60s).
+const MAX_SAMPLES: f64 = 64.0;
+
+/// Each node is assumed to have a few successes, so a node is not degraded by
+/// a single error, and the decayed errors of a recovered node are outweighed.
+const PRIOR_SUCCESSES: f64 = 4.0;
+
+/// A node is degraded if its recent error rate exceeds it.
+const DEGRADED_ERROR_RATE: f64 = 0.2;
+
+#[derive(Clone, Debug, Default)]
+pub struct NodeHealth {
+    scores: Arc<Mutex<HashMap<u64, NodeScore>>>,
+}
+
+#[derive(Clone, Debug)]
+struct NodeScore {
+    errors: f64,
+    successes: f64,
+    updated_at: Instant,
+}
+
+impl NodeHealth {
+    /// Record the result of a request issued to the node.
+    #[inline]
+    pub fn record(&self, node_id: u64, success: bool) {
+        self.record_at(node_id, success, Instant::now());
+    }
+
+    /// Returns the recent error rate of the node.
+    #[inline]
+    pub fn error_rate(&self, node_id: u64) -> f64 {
+        self.error_rate_at(node_id, Instant::now())
+    }
+
+    /// Returns whether the recent error rate of the node exceeds the threshold.
+    #[inline]
+    pub fn is_degraded(&self, node_id: u64) -> bool {
+        self.error_rate(node_id) > DEGRADED_ERROR_RATE
+    }
+
+    /// Sort the replicas by the health of their nodes: the first replica (the
+    /// leader in most cases) is kept first unless it is degraded, followed by
+    /// the healthy ones, and the degraded ones are the last resort.
+    #[inline]
+    pub fn sort_replicas(&self, replicas: &mut [ReplicaDesc]) {
+        self.sort_replicas_at(replicas, Instant::now());
+    }
+
+    fn record_at(&self, node_id: u64, success: bool, now: Instant) {
+        let mut scores = self.scores.lock().unwrap();
+        let score = scores.entry(node_id).or_insert_with(|| NodeScore::new(now));
+        score.decay(now);
+        if success {
+            score.successes += 1.0;
+        } else {
+            score.errors += 1.0;
+        }
+        let total = score.total();
+        if total > MAX_SAMPLES {
+            score.errors *= MAX_SAMPLES / total;
+            score.successes *= MAX_SAMPLES / total;
+        }
+    }
+
+    fn error_rate_at(&self, node_id: u64, now: Instant) -> f64 {
+        let scores = self.scores.lock().unwrap();
+        scores
+            .get(&node_id)
+            .map(|score| {
+                let mut score = score.clone();
+                score.decay(now);
+                score.error_rate()
+            })
+            .unwrap_or_default()
+    }
+
+    fn sort_replicas_at(&self, replicas: &mut [ReplicaDesc], now: Instant) {
+        if replicas.len() <= 1 {
+            return;
+        }
+
+        let mut ranks = replicas
+            .iter()
+            .enumerate()
+            .map(|(idx, replica)| {
+                let error_rate = self.error_rate_at(replica.node_id, now);
+                (error_rate > DEGRADED_ERROR_RATE, idx != 0, error_rate, replica.clone())
+            })
+            .collect::<Vec<_>>();
+        ranks.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)).then(a.2.total_cmp(&b.2)));
+        for (replica, (_, _, _, desc)) in replicas.iter_mut().zip(ranks) {
+            *replica = desc;
+        }
+    }
+}
+
+impl NodeScore {
+    fn new(now: Instant) -> Self {
+        NodeScore { errors: 0.0, successes: 0.0, updated_at: now }
+    }
+
+    fn decay(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at);
+        let factor = 0.5f64.powf(elapsed.as_secs_f64() / HALF_LIFE.as_secs_f64());
+        self.errors *= factor;
+        self.successes *= factor;
+        self.updated_at = now;
+    }
+
+    #[inline]
+    fn total(&self) -> f64 {
+        self.errors + self.successes
+    }
+
+    #[inline]
+    fn error_rate(&self) -> f64 {
+        self.errors / (self.total() + PRIOR_SUCCESSES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replicas() -> Vec<ReplicaDesc> {
+        (1..=3).map(|id| ReplicaDesc { id, node_id: id, ..Default::default() }).collect()
+    }
+
+    fn node_ids(replicas: &[ReplicaDesc]) -> Vec<u64> {
+        replicas.iter().map(|r| r.node_id).collect()
+    }
+
+    #[test]
+    fn sort_replicas_by_health() {
+        let health = NodeHealth::default();
+        let now = Instant::now();
+        let mut descs = replicas();
+        health.sort_replicas_at(&mut descs, now);
+        assert_eq!(node_ids(&descs), vec![1, 2, 3]);
+
+        // The node with a few errors is still healthy, but it is accessed later.
+        for _ in 0..9 {
+            health.record_at(2, true, now);
+        }
+        health.record_at(2, false, now);
+        health.sort_replicas_at(&mut descs, now);
+        assert_eq!(node_ids(&descs), vec![1, 3, 2]);
+
+        // The degraded leader is accessed at last.
+        for _ in 0..10 {
+            health.record_at(1, false, now);
+        }
+        health.sort_replicas_at(&mut descs, now);
+        assert_eq!(node_ids(&descs), vec![3, 2, 1]);
+    }
+
+    /// Issue requests from a round-robin preferred replica, the requests are
+    /// retried on the next replica in the order if the node fails. Returns the
+    /// number of requests served by each node.
+    fn simulate(
+        health: &NodeHealth,
+        now: Instant,
+        num_requests: usize,
+        fail: impl Fn(u64, usize) -> bool,
+    ) -> HashMap<u64, usize> {
+        let mut served = HashMap::new();
+        for i in 0..num_requests {
+            let mut descs = replicas();
+            let num_replicas = descs.len();
+            descs.rotate_left(i % num_replicas);
+            health.sort_replicas_at(&mut descs, now);
+            for desc in &descs {
+                let failed = fail(desc.node_id, i);
+                health.record_at(desc.node_id, !failed, now);
+                if !failed {
+                    *served.entry(desc.node_id).or_default() += 1;
+                    break;
+                }
+            }
+        }
+        served
+    }
+
+    #[test]
+    fn requests_shift_away_from_flaky_node_and_return() {
+        let health = NodeHealth::default();
+        let mut now = Instant::now();
+
+        let served = simulate(&health, now, 300, |_, _| false);
+        assert_eq!(served[&2], 100);
+
+        // Node 2 answers 50% of the requests with errors, it is skipped once the
+        // errors are observed.
+        let flaky = |node_id, i| node_id == 2 && i % 2 == 0;
+        let served = simulate(&health, now, 300, flaky);
+        assert!(served.get(&2).copied().unwrap_or_default() < 100, "{served:?}");
+        assert!(health.error_rate_at(2, now) > DEGRADED_ERROR_RATE);
+        let served = simulate(&health, now, 300, flaky);
+        assert!(!served.contains_key(&2), "{served:?}");
+
+        // Node 2 recovers, it returns to the rotation once the errors decay.
+        now += HALF_LIFE * 6;
+        assert!(health.error_rate_at(2, now) <= DEGRADED_ERROR_RATE);
+        let served = simulate(&health, now, 300, |_, _| false);
+        assert_eq!(served[&2], 100, "{served:?}");
+    }
+
+    #[test]
+    fn errors_decay() {
+        let health = NodeHealth::default();
+        let now = Instant::now();
+        for _ in 0..100 {
+            health.record_at(1, false, now);
+        }
+        assert!(health.error_rate_at(1, now) > DEGRADED_ERROR_RATE);
+        assert!(health.error_rate_at(1, now + HALF_LIFE * 4) > DEGRADED_ERROR_RATE);
+        assert!(health.error_rate_at(1, now + HALF_LIFE * 7) <= DEGRADED_ERROR_RATE);
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The circuit of the root group.
+//!
+//! Without a circuit, a root request is retried until root is reachable. With
+//! it, the request gives up once root has been unreachable for `open_after`,
+//! the circuit is opened and the following root requests fail immediately
+//! with `Error::RootUnavailable`. A single background task probes root with
+//! capped backoff, and closes the circuit once root is reachable again.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use sekas_runtime::time::{system_time, Instant};
+use tokio::sync::watch;
+
+/// The availability of root observed by the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootStatus {
+    Available,
+    /// Root is unreachable since the time, the root-dependent operations fail
+    /// fast until it is recovered.
+    Unavailable {
+        since: SystemTime,
+    },
+}
+
+#[derive(Debug)]
+pub(crate) struct RootCircuit {
+    open_after: Duration,
+    status: watch::Sender<RootStatus>,
+    probing: AtomicBool,
+}
+
+impl RootCircuit {
+    pub(crate) fn new(open_after: Duration) -> Self {
+        let (status, _) = watch::channel(RootStatus::Available);
+        RootCircuit { open_after, status, probing: AtomicBool::new(false) }
+    }
+
+    #[inline]
+    pub(crate) fn status(&self) -> RootStatus {
+        *self.status.borrow()
+    }
+
+    /// The duration of root being unreachable before the circuit is opened.
+    #[inline]
+    pub(crate) fn open_after(&self) -> Duration {
+        self.open_after
+    }
+
+    /// Open the circuit since root is unreachable from `failed_at`. Returns the
+    /// time since root is unavailable, and whether the caller should start the
+    /// probe task.
+    pub(crate) fn open(&self, failed_at: Instant) -> (SystemTime, bool) {
+        let since = system_time().checked_sub(failed_at.elapsed()).unwrap_or_else(system_time);
+        self.status.send_if_modified(|status| match status {
+            RootStatus::Available => {
+                *status = RootStatus::Unavailable { since };
+                true
+            }
+            RootStatus::Unavailable { .. } => false,
+        });
+        let since = match self.status() {
+            RootStatus::Unavailable { since } => since,
+            RootStatus::Available => since,
+        };
+        (since, !self.probing.swap(true, Ordering::AcqRel))
+    }
+
+    /// Close the circuit since root is reachable. The probe task exits once it
+    /// observes the closed circuit.
+    pub(crate) fn close(&self) {
+        self.probing.store(false, Ordering::Release);
+        self.status.send_if_modified(|status| {
+            let opened = matches!(status, RootStatus::Unavailable { .. });
+            *status = RootStatus::Available;
+            opened
+        });
+    }
+
+    /// Wait until the circuit is closed.
+    pub(crate) async fn wait_available(&self) {
+        let mut receiver = self.status.subscribe();
+        while !matches!(*receiver.borrow_and_update(), RootStatus::Available) {
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn open_and_close_circuit() {
+        let circuit = RootCircuit::new(Duration::from_secs(1));
+        assert_eq!(circuit.status(), RootStatus::Available);
+        circuit.wait_available().await;
+
+        // Only the first opener starts the probe task, and the time of the first
+        // failure is kept.
+        let failed_at = Instant::now();
+        let (since, start_probe) = circuit.open(failed_at);
+        assert!(start_probe);
+        let (reopened_since, start_probe) = circuit.open(Instant::now());
+        assert!(!start_probe);
+        assert_eq!(since, reopened_since);
+        assert_eq!(circuit.status(), RootStatus::Unavailable { since });
+
+        let waiter = circuit.wait_available();
+        circuit.close();
+        waiter.await;
+        assert_eq!(circuit.status(), RootStatus::Available);
+
+        // The probe task is started again by the next failure.
+        assert!(circuit.open(Instant::now()).1);
+    }
+}
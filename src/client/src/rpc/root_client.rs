@@ -15,23 +15,25 @@
 
 use std::collections::HashMap;
 use std::future::Future;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use derivative::Derivative;
-use log::trace;
+use log::{info, trace};
 use prost::Message;
 use sekas_api::server::v1::admin_request::Request;
 use sekas_api::server::v1::admin_response::Response;
 use sekas_api::server::v1::root_client::RootClient;
 use sekas_api::server::v1::*;
+use sekas_runtime::time::Instant;
 use tokio::sync::Mutex;
 use tonic::transport::Channel;
 use tonic::{Code, Status, Streaming};
 
 use crate::discovery::ServiceDiscovery;
 use crate::error::retryable_rpc_err;
-use crate::rpc::{ConnManager, NodeClient};
+use crate::rpc::root_circuit::RootCircuit;
+use crate::rpc::{ConnManager, NodeClient, RootStatus};
 use crate::{Error as ClientError, Result};
 
 macro_rules! extract_admin_response {
@@ -47,6 +49,13 @@ macro_rules! extract_admin_response {
     };
 }
 
+const MIN_PROBE_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The max number of descs listed per request, so that no response of a large
+/// catalog exceeds the message size limits.
+const LIST_PAGE_SIZE: u64 = 256;
+
 #[derive(thiserror::Error, Debug)]
 enum RootError {
     #[error("not root")]
@@ -62,6 +71,8 @@ struct AdminRequestBuilder;
 #[derive(Debug, Clone)]
 pub struct Client {
     shared: Arc<ClientShared>,
+    /// The timeout of the admin requests, see [`Client::with_timeout`].
+    timeout: Option<Duration>,
 }
 
 #[derive(Derivative)]
@@ -71,6 +82,7 @@ struct ClientShared {
     discovery: Arc<dyn ServiceDiscovery>,
     conn_manager: ConnManager,
     core: Mutex<ClientCore>,
+    circuit: Option<RootCircuit>,
 
     // Only one task is allowed to refresh root descriptor at a time.
     // The value is the latest epoch refreshed from nodes.
@@ -86,14 +98,62 @@ struct ClientCore {
 }
 
 impl Client {
+    /// Create a client which retries the root requests until root is
+    /// reachable.
     pub fn new(discovery: Arc<dyn ServiceDiscovery>, conn_manager: ConnManager) -> Self {
+        Client::new_with_circuit(discovery, conn_manager, None)
+    }
+
+    /// Create a client whose root requests fail fast with
+    /// `Error::RootUnavailable` once root has been unreachable for
+    /// `unavailable_timeout`, until root is recovered.
+    pub fn with_fail_fast(
+        discovery: Arc<dyn ServiceDiscovery>,
+        conn_manager: ConnManager,
+        unavailable_timeout: Duration,
+    ) -> Self {
+        let circuit = RootCircuit::new(unavailable_timeout);
+        Client::new_with_circuit(discovery, conn_manager, Some(circuit))
+    }
+
+    fn new_with_circuit(
+        discovery: Arc<dyn ServiceDiscovery>,
+        conn_manager: ConnManager,
+        circuit: Option<RootCircuit>,
+    ) -> Self {
         Client {
             shared: Arc::new(ClientShared {
                 discovery,
                 conn_manager,
                 core: Mutex::new(ClientCore { leader: None, term: 0, root: Arc::default() }),
+                circuit,
                 refresh_descriptor_lock: Mutex::new(0),
             }),
+            timeout: None,
+        }
+    }
+
+    /// Bound the admin requests issued by the returned client, such as the
+    /// DDLs, by the timeout. [`ClientError::DeadlineExceeded`] is returned
+    /// once it is exceeded.
+    pub fn with_timeout(&self, timeout: Option<Duration>) -> Self {
+        Client { shared: self.shared.clone(), timeout }
+    }
+
+    /// The availability of root, it is always available if the client
+    /// doesn't fail fast.
+    pub fn root_status(&self) -> RootStatus {
+        match &self.shared.circuit {
+            Some(circuit) => circuit.status(),
+            None => RootStatus::Available,
+        }
+    }
+
+    /// Wait until root is available, it is shared by all requests instead of
+    /// retrying them one by one.
+    pub async fn wait_available(&self) {
+        if let Some(circuit) = &self.shared.circuit {
+            circuit.wait_available().await;
         }
     }
 
@@ -108,17 +168,23 @@ impl Client {
     }
 
     pub async fn admin(&self, req: AdminRequest) -> Result<AdminResponse> {
+        let deadline = self.timeout.and_then(|v| Instant::now().checked_add(v));
         let res = self
-            .invoke(|mut client| {
-                let req = req.clone();
+            .invoke_with_timeout(self.timeout, |mut client| {
+                let mut req = tonic::Request::new(req.clone());
+                if let Some(deadline) = deadline {
+                    req.set_timeout(deadline.saturating_duration_since(Instant::now()));
+                }
                 async move { client.admin(req).await }
             })
             .await?;
         Ok(res.into_inner())
     }
 
-    pub async fn create_database(&self, name: String) -> Result<DatabaseDesc> {
-        let resp = self.admin(AdminRequestBuilder::create_database(name)).await?;
+    /// Create a database, the retried requests share the `request_id`, so the
+    /// database created by a request is returned to its retries.
+    pub async fn create_database(&self, name: String, request_id: String) -> Result<DatabaseDesc> {
+        let resp = self.admin(AdminRequestBuilder::create_database(name, request_id)).await?;
         let resp = extract_admin_response!(resp.response, Response::CreateDatabase);
         resp.database
             .ok_or_else(|| ClientError::Internal("The database is not set".to_owned().into()))
@@ -130,10 +196,20 @@ impl Client {
         Ok(())
     }
 
+    /// List the databases in the ascending order of name, they are read page
+    /// by page.
     pub async fn list_database(&self) -> Result<Vec<DatabaseDesc>> {
-        let resp = self.admin(AdminRequestBuilder::list_database()).await?;
-        let resp = extract_admin_response!(resp.response, Response::ListDatabases);
-        Ok(resp.databases)
+        let mut databases: Vec<DatabaseDesc> = Vec::default();
+        loop {
+            let start_after = databases.last().map(|db| db.name.clone()).unwrap_or_default();
+            let req = AdminRequestBuilder::list_database(start_after, LIST_PAGE_SIZE);
+            let resp = self.admin(req).await?;
+            let resp = extract_admin_response!(resp.response, Response::ListDatabases);
+            databases.extend(resp.databases);
+            if !resp.has_more {
+                return Ok(databases);
+            }
+        }
     }
 
     pub async fn get_database(&self, name: String) -> Result<Option<DatabaseDesc>> {
@@ -142,24 +218,276 @@ impl Client {
         Ok(resp.database)
     }
 
-    pub async fn create_table(&self, db_desc: DatabaseDesc, name: String) -> Result<TableDesc> {
-        let resp = self.admin(AdminRequestBuilder::create_table(db_desc, name)).await?;
+    /// Create a table, the retried requests share the `request_id`, so the
+    /// table created by a request is returned to its retries.
+    pub async fn create_table(
+        &self,
+        db_desc: DatabaseDesc,
+        name: String,
+        properties: HashMap<String, String>,
+        request_id: String,
+    ) -> Result<TableDesc> {
+        let req = AdminRequestBuilder::create_table(db_desc, name, properties, request_id);
+        let resp = self.admin(req).await?;
         let resp = extract_admin_response!(resp.response, Response::CreateTable);
         resp.table.ok_or_else(|| ClientError::Internal("The table is not set".to_owned().into()))
     }
 
+    /// Set the properties of the table, the updated desc is returned.
+    pub async fn update_table(
+        &self,
+        db_desc: DatabaseDesc,
+        name: String,
+        properties: HashMap<String, String>,
+    ) -> Result<TableDesc> {
+        let req = AdminRequestBuilder::update_table(db_desc, name, properties);
+        let resp = self.admin(req).await?;
+        let resp = extract_admin_response!(resp.response, Response::UpdateTable);
+        resp.table.ok_or_else(|| ClientError::Internal("The table is not set".to_owned().into()))
+    }
+
     pub async fn delete_table(&self, db_desc: DatabaseDesc, name: String) -> Result<()> {
         let resp = self.admin(AdminRequestBuilder::delete_table(db_desc.clone(), name)).await?;
         extract_admin_response!(resp.response, Response::DeleteTable);
         Ok(())
     }
 
+    /// Clone the table from a consistent snapshot of the source table, the
+    /// dest table is read-only until the clone finishes, see
+    /// [`RootClient::clone_status`].
+    pub async fn clone_table(
+        &self,
+        src_database: String,
+        src_table: String,
+        dest_database: String,
+        dest_table: String,
+    ) -> Result<CloneTableResponse> {
+        let req =
+            AdminRequestBuilder::clone_table(src_database, src_table, dest_database, dest_table);
+        let resp = self.admin(req).await?;
+        Ok(extract_admin_response!(resp.response, Response::CloneTable))
+    }
+
+    /// Export the tables at a single read version into the `dest` directory,
+    /// which is relative to the export directory of the root, the tables are
+    /// specified by `(database, table)`. The returned manifest binds the dumps
+    /// to the read version.
+    pub async fn export_tables(
+        &self,
+        specs: Vec<(String, String)>,
+        dest: String,
+    ) -> Result<ExportManifest> {
+        let resp = self.admin(AdminRequestBuilder::export_tables(specs, dest)).await?;
+        let resp = extract_admin_response!(resp.response, Response::ExportTables);
+        Ok(resp.manifest.unwrap_or_default())
+    }
+
+    /// Restore the tables exported into the `src` directory together, see
+    /// [`RootClient::export_tables`].
+    pub async fn import_tables(&self, src: String) -> Result<ImportTablesResponse> {
+        let resp = self.admin(AdminRequestBuilder::import_tables(src)).await?;
+        Ok(extract_admin_response!(resp.response, Response::ImportTables))
+    }
+
+    /// Get the clone status of the dest table, `None` if the table is not
+    /// cloned.
+    pub async fn clone_status(&self, table_id: u64) -> Result<Option<CloneStatus>> {
+        let resp = self.admin(AdminRequestBuilder::clone_status(table_id)).await?;
+        let resp = extract_admin_response!(resp.response, Response::CloneStatus);
+        Ok(resp.status)
+    }
+
+    /// List the tables of the database in the ascending order of name, they
+    /// are read page by page.
     pub async fn list_table(&self, db_desc: DatabaseDesc) -> Result<Vec<TableDesc>> {
-        let resp = self.admin(AdminRequestBuilder::list_table(db_desc)).await?;
-        let resp = extract_admin_response!(resp.response, Response::ListTables);
+        let mut tables: Vec<TableDesc> = Vec::default();
+        loop {
+            let start_after = tables.last().map(|table| table.name.clone()).unwrap_or_default();
+            let req = AdminRequestBuilder::list_table(db_desc.clone(), start_after, LIST_PAGE_SIZE);
+            let resp = self.admin(req).await?;
+            let resp = extract_admin_response!(resp.response, Response::ListTables);
+            tables.extend(resp.tables);
+            if !resp.has_more {
+                return Ok(tables);
+            }
+        }
+    }
+
+    /// Get the stats of the tables of the database.
+    pub async fn table_stats(&self, db_desc: DatabaseDesc) -> Result<Vec<TableStats>> {
+        let resp = self.admin(AdminRequestBuilder::table_stats(db_desc)).await?;
+        let resp = extract_admin_response!(resp.response, Response::TableStats);
         Ok(resp.tables)
     }
 
+    /// Split the shard at the key, or at the key estimated by the server if it
+    /// is not specified. Returns the two shards split from the shard.
+    pub async fn split_shard(
+        &self,
+        shard_id: u64,
+        split_key: Option<Vec<u8>>,
+    ) -> Result<Vec<ShardDesc>> {
+        let resp = self.admin(AdminRequestBuilder::split_shard(shard_id, split_key)).await?;
+        let resp = extract_admin_response!(resp.response, Response::SplitShard);
+        Ok(resp.shards)
+    }
+
+    /// Promote the catalog mirrored from a primary to an authoritative one.
+    /// Returns the new cluster epoch.
+    pub async fn promote_catalog(&self) -> Result<u64> {
+        let resp = self.admin(AdminRequestBuilder::promote_catalog()).await?;
+        let resp = extract_admin_response!(resp.response, Response::PromoteCatalog);
+        Ok(resp.cluster_epoch)
+    }
+
+    /// Get the group descriptor stored in catalog, and the bytes of it.
+    pub async fn get_raw_group_desc(&self, group_id: u64) -> Result<GetRawGroupDescResponse> {
+        let resp = self.admin(AdminRequestBuilder::get_raw_group_desc(group_id)).await?;
+        Ok(extract_admin_response!(resp.response, Response::GetRawGroupDesc))
+    }
+
+    /// Replace the group descriptor stored in catalog, it is refused unless
+    /// the root enables the unsafe admin requests. The `confirm_token` must be
+    /// the one returned by the latest [`Self::get_raw_group_desc`].
+    pub async fn put_raw_group_desc(
+        &self,
+        desc: GroupDesc,
+        expected_epoch: u64,
+        confirm_token: String,
+    ) -> Result<()> {
+        let req = AdminRequestBuilder::put_raw_group_desc(desc, expected_epoch, confirm_token);
+        let resp = self.admin(req).await?;
+        extract_admin_response!(resp.response, Response::PutRawGroupDesc);
+        Ok(())
+    }
+
+    /// Get the shard of the group descriptor stored in catalog.
+    pub async fn get_raw_shard_desc(
+        &self,
+        group_id: u64,
+        shard_id: u64,
+    ) -> Result<GetRawShardDescResponse> {
+        let resp = self.admin(AdminRequestBuilder::get_raw_shard_desc(group_id, shard_id)).await?;
+        Ok(extract_admin_response!(resp.response, Response::GetRawShardDesc))
+    }
+
+    /// Replace the shard of the group descriptor stored in catalog, it is
+    /// refused unless the root enables the unsafe admin requests. The
+    /// `confirm_token` must be the one returned by the latest
+    /// [`Self::get_raw_shard_desc`].
+    pub async fn put_raw_shard_desc(
+        &self,
+        group_id: u64,
+        desc: ShardDesc,
+        expected_epoch: u64,
+        confirm_token: String,
+    ) -> Result<()> {
+        let req =
+            AdminRequestBuilder::put_raw_shard_desc(group_id, desc, expected_epoch, confirm_token);
+        let resp = self.admin(req).await?;
+        extract_admin_response!(resp.response, Response::PutRawShardDesc);
+        Ok(())
+    }
+
+    /// Get the node descriptor stored in catalog, and the bytes of it.
+    pub async fn get_raw_node_desc(&self, node_id: u64) -> Result<GetRawNodeDescResponse> {
+        let resp = self.admin(AdminRequestBuilder::get_raw_node_desc(node_id)).await?;
+        Ok(extract_admin_response!(resp.response, Response::GetRawNodeDesc))
+    }
+
+    /// Replace the node descriptor stored in catalog, it is refused unless the
+    /// root enables the unsafe admin requests. The `confirm_token` must be
+    /// the one returned by the latest [`Self::get_raw_node_desc`].
+    pub async fn put_raw_node_desc(
+        &self,
+        desc: NodeDesc,
+        expected_raw: Vec<u8>,
+        confirm_token: String,
+    ) -> Result<()> {
+        let req = AdminRequestBuilder::put_raw_node_desc(desc, expected_raw, confirm_token);
+        let resp = self.admin(req).await?;
+        extract_admin_response!(resp.response, Response::PutRawNodeDesc);
+        Ok(())
+    }
+
+    /// List the topology events retained by root, in the order of id.
+    pub async fn list_topology_events(&self) -> Result<Vec<TopologyEvent>> {
+        let resp = self.admin(AdminRequestBuilder::list_topology_events()).await?;
+        let resp = extract_admin_response!(resp.response, Response::ListTopologyEvents);
+        Ok(resp.events)
+    }
+
+    /// List the running txns in descending order of age, at most `limit` txns
+    /// are returned if it is not zero. The txn shards failed to list are
+    /// returned as warnings.
+    pub async fn list_active_txns(&self, limit: u64) -> Result<ListActiveTxnsResponse> {
+        let resp = self.admin(AdminRequestBuilder::list_active_txns(limit)).await?;
+        Ok(extract_admin_response!(resp.response, Response::ListActiveTxns))
+    }
+
+    /// List a page of the groups matching the filter in the order of id, the
+    /// next page is read by the filter with the returned `next_page_token`.
+    pub async fn list_groups(&self, filter: GroupFilter) -> Result<ListGroupsResponse> {
+        let resp = self.admin(AdminRequestBuilder::list_groups(filter)).await?;
+        Ok(extract_admin_response!(resp.response, Response::ListGroups))
+    }
+
+    /// List a page of the nodes matching the filter in the order of id.
+    pub async fn list_nodes(&self, filter: NodeDescFilter) -> Result<ListNodesResponse> {
+        let resp = self.admin(AdminRequestBuilder::list_nodes(filter)).await?;
+        Ok(extract_admin_response!(resp.response, Response::ListNodes))
+    }
+
+    /// List a page of the replica states matching the filter in the order of
+    /// group id and replica id.
+    pub async fn list_replica_states(
+        &self,
+        filter: ReplicaStateFilter,
+    ) -> Result<ListReplicaStatesResponse> {
+        let resp = self.admin(AdminRequestBuilder::list_replica_states(filter)).await?;
+        Ok(extract_admin_response!(resp.response, Response::ListReplicaStates))
+    }
+
+    /// List the summaries of the recent reconciliation passes of the scheduler,
+    /// in descending order of seq.
+    pub async fn list_schedule_passes(&self) -> Result<Vec<SchedulePass>> {
+        let resp = self.admin(AdminRequestBuilder::list_schedule_passes()).await?;
+        let resp = extract_admin_response!(resp.response, Response::ListSchedulePasses);
+        Ok(resp.passes)
+    }
+
+    /// Run a reconciliation pass of the scheduler immediately, and returns its
+    /// summary once the pass is finished.
+    pub async fn trigger_schedule_pass(&self) -> Result<SchedulePass> {
+        let resp = self.admin(AdminRequestBuilder::trigger_schedule_pass()).await?;
+        let resp = extract_admin_response!(resp.response, Response::TriggerSchedulePass);
+        Ok(resp.pass.unwrap_or_default())
+    }
+
+    /// Override the log level of a target on the node, or all nodes if the
+    /// node is not specified, and returns the effective filters of the nodes.
+    pub async fn config_log_filter(
+        &self,
+        node_id: Option<u64>,
+        req: SetLogFilterRequest,
+    ) -> Result<Vec<NodeLogFilter>> {
+        let resp = self.admin(AdminRequestBuilder::config_log_filter(node_id, req)).await?;
+        let resp = extract_admin_response!(resp.response, Response::ConfigLogFilter);
+        Ok(resp.filters)
+    }
+
+    /// Compact the data of the table on all replicas hosting its shards, and
+    /// returns the results of the replicas once they are finished.
+    pub async fn compact_table(
+        &self,
+        database: String,
+        table: String,
+    ) -> Result<Vec<ReplicaCompaction>> {
+        let resp = self.admin(AdminRequestBuilder::compact_table(database, table)).await?;
+        let resp = extract_admin_response!(resp.response, Response::CompactTable);
+        Ok(resp.replicas)
+    }
+
     pub async fn handle_statement(&self, statement: &str) -> Result<Vec<u8>> {
         let resp = self
             .admin(AdminRequest {
@@ -182,6 +510,30 @@ impl Client {
         Ok(resp.table)
     }
 
+    /// Get the migration status of the shard, `None` if the shard is not in
+    /// migration.
+    pub async fn migration_status(&self, shard_id: u64) -> Result<Option<MigrationStatus>> {
+        let resp = self.admin(AdminRequestBuilder::migration_status(shard_id)).await?;
+        let resp = extract_admin_response!(resp.response, Response::MigrationStatus);
+        Ok(resp.status)
+    }
+
+    /// Cancel the migration of the shard, it is rejected once the migration is
+    /// committed.
+    pub async fn cancel_migration(&self, shard_id: u64) -> Result<()> {
+        let resp = self.admin(AdminRequestBuilder::cancel_migration(shard_id)).await?;
+        extract_admin_response!(resp.response, Response::CancelMigration);
+        Ok(())
+    }
+
+    /// Approve the pending recommendation of the scheduler, it is only
+    /// accepted in the manual-approve schedule mode.
+    pub async fn approve_action(&self, id: u64) -> Result<()> {
+        let resp = self.admin(AdminRequestBuilder::approve_action(id)).await?;
+        extract_admin_response!(resp.response, Response::ApproveAction);
+        Ok(())
+    }
+
     pub async fn join_node(&self, req: JoinNodeRequest) -> Result<JoinNodeResponse> {
         let res = self
             .invoke(|mut client| {
@@ -222,6 +574,23 @@ impl Client {
         Ok(res.into_inner())
     }
 
+    /// Replicate the catalog from the event after `from_seq` of the journal
+    /// incarnation, see `ReplicateCatalogRequest`.
+    pub async fn replicate_catalog(
+        &self,
+        incarnation: u64,
+        from_seq: u64,
+    ) -> Result<Streaming<ReplicateCatalogResponse>> {
+        let req = ReplicateCatalogRequest { incarnation, from_seq };
+        let res = self
+            .invoke(|mut client| {
+                let req = req.clone();
+                async move { client.replicate_catalog(req).await }
+            })
+            .await?;
+        Ok(res.into_inner())
+    }
+
     pub async fn alloc_replica(&self, req: AllocReplicaRequest) -> Result<AllocReplicaResponse> {
         let resp = self
             .invoke(|mut client| {
@@ -245,9 +614,37 @@ impl Client {
         F: Fn(root_client::RootClient<Channel>) -> O,
         O: Future<Output = Result<V, Status>>,
     {
+        self.invoke_inner(timeout, false, op).await
+    }
+
+    /// Invoke the op on the root leader. If the client fails fast, the op is
+    /// rejected while the circuit is opened, unless it is issued by the
+    /// probe, which gives up after a round of all root nodes.
+    async fn invoke_inner<F, O, V>(
+        &self,
+        timeout: Option<Duration>,
+        probe: bool,
+        op: F,
+    ) -> Result<V>
+    where
+        F: Fn(root_client::RootClient<Channel>) -> O,
+        O: Future<Output = Result<V, Status>>,
+    {
+        let fail_after = match &self.shared.circuit {
+            Some(_) if probe => Some(Duration::ZERO),
+            Some(circuit) => {
+                if let RootStatus::Unavailable { since } = circuit.status() {
+                    return Err(ClientError::RootUnavailable(since));
+                }
+                Some(circuit.open_after())
+            }
+            None => None,
+        };
+
         let mut interval = 1;
         let mut save_core = false;
         let mut core = self.core().await;
+        let mut first_failure = None;
 
         let deadline = timeout.map(|duration| Instant::now() + duration);
         'OUTER: loop {
@@ -257,12 +654,16 @@ impl Client {
                 let client = self.get_root_client(leader_node.addr.clone())?;
                 match invoke(client, &op).await {
                     Ok(res) => {
+                        self.close_circuit();
                         if save_core {
                             self.apply_core(core).await;
                         }
                         return Ok(res);
                     }
-                    Err(RootError::Rpc(status)) => return Err(status.into()),
+                    Err(RootError::Rpc(status)) => {
+                        self.close_circuit();
+                        return Err(status.into());
+                    }
                     Err(RootError::NotAvailable) => {
                         trace!("send rpc to root {}: remote is not available", leader_node.addr);
                     }
@@ -294,11 +695,15 @@ impl Client {
                 match invoke(client, &op).await {
                     Ok(res) => {
                         // Save new leader of root.
+                        self.close_circuit();
                         core.leader = Some(i);
                         self.apply_core(core).await;
                         return Ok(res);
                     }
-                    Err(RootError::Rpc(status)) => return Err(status.into()),
+                    Err(RootError::Rpc(status)) => {
+                        self.close_circuit();
+                        return Err(status.into());
+                    }
                     Err(RootError::NotAvailable) => {
                         // Connect timeout or refused, try next address.
                     }
@@ -323,14 +728,42 @@ impl Client {
             core = self.refresh_client_core(core).await?;
 
             if deadline.map(|v| v.elapsed() > Duration::ZERO).unwrap_or_default() {
-                return Err(crate::Error::DeadlineExceeded("issue rpc".to_owned()));
+                return Err(crate::Error::DeadlineExceeded("issue rpc".to_owned(), None));
+            }
+
+            if let Some(fail_after) = fail_after {
+                let failed_at = *first_failure.get_or_insert_with(Instant::now);
+                if failed_at.elapsed() >= fail_after {
+                    return Err(self.open_circuit(failed_at));
+                }
             }
 
-            tokio::time::sleep(Duration::from_millis(interval)).await;
+            sekas_runtime::time::sleep(Duration::from_millis(interval)).await;
             interval = std::cmp::min(interval * 2, 1000);
         }
     }
 
+    #[inline]
+    fn close_circuit(&self) {
+        if let Some(circuit) = &self.shared.circuit {
+            circuit.close();
+        }
+    }
+
+    /// Open the circuit, the probe task is started if it is not running.
+    fn open_circuit(&self, failed_at: Instant) -> ClientError {
+        let circuit = self.shared.circuit.as_ref().expect("the client fails fast");
+        let (since, start_probe) = circuit.open(failed_at);
+        if start_probe {
+            info!("root is unavailable since {since:?}, start probing root");
+            let shared = Arc::downgrade(&self.shared);
+            tokio::spawn(async move {
+                probe_root_main(shared).await;
+            });
+        }
+        ClientError::RootUnavailable(since)
+    }
+
     #[inline]
     async fn core(&self) -> ClientCore {
         self.shared.core.lock().await.clone()
@@ -389,6 +822,32 @@ impl Client {
     }
 }
 
+/// Probe root with capped backoff until it is reachable, or the client is
+/// dropped.
+async fn probe_root_main(shared: Weak<ClientShared>) {
+    let mut interval = MIN_PROBE_INTERVAL;
+    loop {
+        sekas_runtime::time::sleep(interval).await;
+        let Some(shared) = shared.upgrade() else { return };
+        let client = Client { shared, timeout: None };
+        if client.root_status() == RootStatus::Available {
+            // Closed by another request.
+            return;
+        }
+        let op = |mut client: RootClient<Channel>| async move {
+            client.admin(AdminRequestBuilder::list_database()).await
+        };
+        match client.invoke_inner(None, true, op).await {
+            Ok(_) => {
+                info!("root is available again");
+                return;
+            }
+            Err(err) => trace!("probe root: {err:?}"),
+        }
+        interval = std::cmp::min(interval * 2, MAX_PROBE_INTERVAL);
+    }
+}
+
 impl ClientCore {
     fn apply_leader(&mut self, leader: ReplicaDesc, term: u64) {
         for (idx, node) in self.root.root_nodes.iter().enumerate() {
@@ -402,27 +861,52 @@ impl ClientCore {
 }
 
 impl AdminRequestBuilder {
-    pub fn create_database(name: String) -> AdminRequest {
-        AdminRequest { request: Some(Request::CreateDatabase(CreateDatabaseRequest { name })) }
+    pub fn create_database(name: String, request_id: String) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::CreateDatabase(CreateDatabaseRequest { name, request_id })),
+        }
     }
 
     pub fn delete_database(name: String) -> AdminRequest {
         AdminRequest { request: Some(Request::DeleteDatabase(DeleteDatabaseRequest { name })) }
     }
 
-    pub fn list_database() -> AdminRequest {
-        AdminRequest { request: Some(Request::ListDatabases(ListDatabasesRequest {})) }
+    pub fn list_database(start_after: String, limit: u64) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::ListDatabases(ListDatabasesRequest { start_after, limit })),
+        }
     }
 
     pub fn get_database(name: String) -> AdminRequest {
         AdminRequest { request: Some(Request::GetDatabase(GetDatabaseRequest { name })) }
     }
 
-    pub fn create_table(database: DatabaseDesc, co_name: String) -> AdminRequest {
+    pub fn create_table(
+        database: DatabaseDesc,
+        co_name: String,
+        properties: HashMap<String, String>,
+        request_id: String,
+    ) -> AdminRequest {
         AdminRequest {
             request: Some(Request::CreateTable(CreateTableRequest {
                 name: co_name,
                 database: Some(database),
+                properties,
+                request_id,
+            })),
+        }
+    }
+
+    pub fn update_table(
+        database: DatabaseDesc,
+        co_name: String,
+        properties: HashMap<String, String>,
+    ) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::UpdateTable(UpdateTableRequest {
+                name: co_name,
+                database: Some(database),
+                properties,
             })),
         }
     }
@@ -436,12 +920,191 @@ impl AdminRequestBuilder {
         }
     }
 
-    pub fn list_table(database: DatabaseDesc) -> AdminRequest {
+    pub fn list_table(database: DatabaseDesc, start_after: String, limit: u64) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::ListTables(ListTablesRequest {
+                database: Some(database),
+                start_after,
+                limit,
+            })),
+        }
+    }
+
+    pub fn table_stats(database: DatabaseDesc) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::TableStats(TableStatsRequest { database: Some(database) })),
+        }
+    }
+
+    pub fn split_shard(shard_id: u64, split_key: Option<Vec<u8>>) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::SplitShard(ManualSplitShardRequest { shard_id, split_key })),
+        }
+    }
+
+    pub fn promote_catalog() -> AdminRequest {
+        AdminRequest { request: Some(Request::PromoteCatalog(PromoteCatalogRequest {})) }
+    }
+
+    pub fn get_raw_group_desc(group_id: u64) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::GetRawGroupDesc(GetRawGroupDescRequest { group_id })),
+        }
+    }
+
+    pub fn put_raw_group_desc(
+        desc: GroupDesc,
+        expected_epoch: u64,
+        confirm_token: String,
+    ) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::PutRawGroupDesc(PutRawGroupDescRequest {
+                desc: Some(desc),
+                expected_epoch,
+                confirm_token,
+            })),
+        }
+    }
+
+    pub fn get_raw_shard_desc(group_id: u64, shard_id: u64) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::GetRawShardDesc(GetRawShardDescRequest { group_id, shard_id })),
+        }
+    }
+
+    pub fn put_raw_shard_desc(
+        group_id: u64,
+        desc: ShardDesc,
+        expected_epoch: u64,
+        confirm_token: String,
+    ) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::PutRawShardDesc(PutRawShardDescRequest {
+                group_id,
+                desc: Some(desc),
+                expected_epoch,
+                confirm_token,
+            })),
+        }
+    }
+
+    pub fn get_raw_node_desc(node_id: u64) -> AdminRequest {
+        AdminRequest { request: Some(Request::GetRawNodeDesc(GetRawNodeDescRequest { node_id })) }
+    }
+
+    pub fn put_raw_node_desc(
+        desc: NodeDesc,
+        expected_raw: Vec<u8>,
+        confirm_token: String,
+    ) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::PutRawNodeDesc(PutRawNodeDescRequest {
+                desc: Some(desc),
+                expected_raw,
+                confirm_token,
+            })),
+        }
+    }
+
+    pub fn list_topology_events() -> AdminRequest {
+        AdminRequest { request: Some(Request::ListTopologyEvents(ListTopologyEventsRequest {})) }
+    }
+
+    pub fn list_active_txns(limit: u64) -> AdminRequest {
+        AdminRequest { request: Some(Request::ListActiveTxns(ListActiveTxnsRequest { limit })) }
+    }
+
+    pub fn list_groups(filter: GroupFilter) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::ListGroups(ListGroupsRequest { filter: Some(filter) })),
+        }
+    }
+
+    pub fn list_nodes(filter: NodeDescFilter) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::ListNodes(ListNodesRequest { filter: Some(filter) })),
+        }
+    }
+
+    pub fn list_replica_states(filter: ReplicaStateFilter) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::ListReplicaStates(ListReplicaStatesRequest {
+                filter: Some(filter),
+            })),
+        }
+    }
+
+    pub fn list_schedule_passes() -> AdminRequest {
+        AdminRequest { request: Some(Request::ListSchedulePasses(ListSchedulePassesRequest {})) }
+    }
+
+    pub fn trigger_schedule_pass() -> AdminRequest {
+        AdminRequest { request: Some(Request::TriggerSchedulePass(TriggerSchedulePassRequest {})) }
+    }
+
+    pub fn config_log_filter(node_id: Option<u64>, req: SetLogFilterRequest) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::ConfigLogFilter(ConfigLogFilterRequest {
+                node_id,
+                request: Some(req),
+            })),
+        }
+    }
+
+    pub fn compact_table(database: String, table: String) -> AdminRequest {
         AdminRequest {
-            request: Some(Request::ListTables(ListTablesRequest { database: Some(database) })),
+            request: Some(Request::CompactTable(CompactTableRequest { database, table })),
         }
     }
 
+    pub fn clone_table(
+        src_database: String,
+        src_table: String,
+        dest_database: String,
+        dest_table: String,
+    ) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::CloneTable(CloneTableRequest {
+                src_database,
+                src_table,
+                dest_database,
+                dest_table,
+            })),
+        }
+    }
+
+    pub fn export_tables(specs: Vec<(String, String)>, dest: String) -> AdminRequest {
+        let tables = specs
+            .into_iter()
+            .map(|(database, table)| ExportTableSpec { database, table })
+            .collect();
+        AdminRequest { request: Some(Request::ExportTables(ExportTablesRequest { tables, dest })) }
+    }
+
+    pub fn import_tables(src: String) -> AdminRequest {
+        AdminRequest { request: Some(Request::ImportTables(ImportTablesRequest { src })) }
+    }
+
+    pub fn clone_status(table_id: u64) -> AdminRequest {
+        AdminRequest { request: Some(Request::CloneStatus(CloneStatusRequest { table_id })) }
+    }
+
+    pub fn migration_status(shard_id: u64) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::MigrationStatus(MigrationStatusRequest { shard_id })),
+        }
+    }
+
+    pub fn cancel_migration(shard_id: u64) -> AdminRequest {
+        AdminRequest {
+            request: Some(Request::CancelMigration(CancelMigrationRequest { shard_id })),
+        }
+    }
+
+    pub fn approve_action(id: u64) -> AdminRequest {
+        AdminRequest { request: Some(Request::ApproveAction(ApproveActionRequest { id })) }
+    }
+
     pub fn get_table(database: DatabaseDesc, co_name: String) -> AdminRequest {
         AdminRequest {
             request: Some(Request::GetTable(GetTableRequest {
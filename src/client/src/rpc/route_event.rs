@@ -0,0 +1,246 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The events of the routing changes observed by the router.
+//!
+//! Each subscriber has its own queue, the router only appends events to the
+//! queues without waiting for the subscribers. If a subscriber lags, the
+//! pending events of the same group (or shard) are coalesced, the latest state
+//! wins.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex, Weak};
+
+use futures::Stream;
+use tokio::sync::Notify;
+
+/// A change of the routing, derived from the updates of the router.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteEvent {
+    /// The epoch of the group is changed, eg. by a config change, a split or a
+    /// shard moving.
+    GroupEpochChanged { group: u64, old: u64, new: u64 },
+    /// The shard is moved from a group to another group.
+    ShardMoved { shard: u64, from_group: u64, to_group: u64 },
+    /// The range of the parent shard is split, the children own the split out
+    /// ranges, and the parent keeps the rest.
+    ShardSplit { group: u64, parent: u64, children: Vec<u64> },
+    /// The leader of the group is changed to the replica on the node.
+    LeaderChanged { group: u64, node: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteEventKind {
+    GroupEpochChanged,
+    ShardMoved,
+    ShardSplit,
+    LeaderChanged,
+}
+
+/// Select the events to subscribe.
+#[derive(Debug, Clone, Default)]
+pub struct RouteEventFilter {
+    /// The events of these groups, all groups if it is empty. A shard moving
+    /// matches both the source and the dest group.
+    pub groups: HashSet<u64>,
+    /// The kinds of events, all kinds if it is empty.
+    pub kinds: HashSet<RouteEventKind>,
+}
+
+/// The subscribers of the route events.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RouteObservers {
+    subscribers: Arc<Mutex<Vec<Weak<Subscriber>>>>,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    filter: RouteEventFilter,
+    events: Mutex<VecDeque<RouteEvent>>,
+    notify: Notify,
+}
+
+impl RouteEvent {
+    pub fn kind(&self) -> RouteEventKind {
+        match self {
+            RouteEvent::GroupEpochChanged { .. } => RouteEventKind::GroupEpochChanged,
+            RouteEvent::ShardMoved { .. } => RouteEventKind::ShardMoved,
+            RouteEvent::ShardSplit { .. } => RouteEventKind::ShardSplit,
+            RouteEvent::LeaderChanged { .. } => RouteEventKind::LeaderChanged,
+        }
+    }
+
+    /// Merge the following event into this one if they describe the state of
+    /// the same target, returns whether it is merged.
+    fn coalesce(&mut self, event: &RouteEvent) -> bool {
+        match (self, event) {
+            (
+                RouteEvent::GroupEpochChanged { group, new, .. },
+                RouteEvent::GroupEpochChanged { group: other_group, new: other_new, .. },
+            ) if group == other_group => {
+                *new = *other_new;
+                true
+            }
+            (
+                RouteEvent::ShardMoved { shard, to_group, .. },
+                RouteEvent::ShardMoved { shard: other_shard, to_group: other_to_group, .. },
+            ) if shard == other_shard => {
+                *to_group = *other_to_group;
+                true
+            }
+            (
+                RouteEvent::LeaderChanged { group, node },
+                RouteEvent::LeaderChanged { group: other_group, node: other_node },
+            ) if group == other_group => {
+                *node = *other_node;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl RouteEventFilter {
+    fn matches(&self, event: &RouteEvent) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.contains(&event.kind()) {
+            return false;
+        }
+        if self.groups.is_empty() {
+            return true;
+        }
+        match event {
+            RouteEvent::GroupEpochChanged { group, .. }
+            | RouteEvent::ShardSplit { group, .. }
+            | RouteEvent::LeaderChanged { group, .. } => self.groups.contains(group),
+            RouteEvent::ShardMoved { from_group, to_group, .. } => {
+                self.groups.contains(from_group) || self.groups.contains(to_group)
+            }
+        }
+    }
+}
+
+impl RouteObservers {
+    pub fn subscribe(&self, filter: RouteEventFilter) -> impl Stream<Item = RouteEvent> {
+        let subscriber =
+            Arc::new(Subscriber { filter, events: Mutex::default(), notify: Notify::new() });
+        self.subscribers.lock().unwrap().push(Arc::downgrade(&subscriber));
+        futures::stream::unfold(subscriber, |subscriber| async move {
+            loop {
+                if let Some(event) = subscriber.pop() {
+                    return Some((event, subscriber));
+                }
+                subscriber.notify.notified().await;
+            }
+        })
+    }
+
+    /// Deliver the event to the subscribers, the dropped subscriptions are
+    /// removed.
+    pub fn publish(&self, event: RouteEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| {
+            let Some(subscriber) = subscriber.upgrade() else { return false };
+            if subscriber.filter.matches(&event) {
+                subscriber.push(event.clone());
+            }
+            true
+        });
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.lock().unwrap().is_empty()
+    }
+}
+
+impl Subscriber {
+    fn pop(&self) -> Option<RouteEvent> {
+        self.events.lock().unwrap().pop_front()
+    }
+
+    fn push(&self, event: RouteEvent) {
+        let mut events = self.events.lock().unwrap();
+        if !events.iter_mut().any(|pending| pending.coalesce(&event)) {
+            events.push_back(event);
+        }
+        drop(events);
+        self.notify.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{FutureExt, StreamExt};
+
+    use super::*;
+
+    fn drain(stream: &mut (impl Stream<Item = RouteEvent> + Unpin)) -> Vec<RouteEvent> {
+        let mut events = vec![];
+        while let Some(Some(event)) = stream.next().now_or_never() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn coalesce_events_of_lagged_subscriber() {
+        let observers = RouteObservers::default();
+        let mut stream = Box::pin(observers.subscribe(RouteEventFilter::default()));
+        observers.publish(RouteEvent::GroupEpochChanged { group: 1, old: 1, new: 2 });
+        observers.publish(RouteEvent::LeaderChanged { group: 1, node: 1 });
+        observers.publish(RouteEvent::GroupEpochChanged { group: 2, old: 1, new: 2 });
+        observers.publish(RouteEvent::GroupEpochChanged { group: 1, old: 2, new: 3 });
+        observers.publish(RouteEvent::LeaderChanged { group: 1, node: 2 });
+        assert_eq!(
+            drain(&mut stream),
+            vec![
+                RouteEvent::GroupEpochChanged { group: 1, old: 1, new: 3 },
+                RouteEvent::LeaderChanged { group: 1, node: 2 },
+                RouteEvent::GroupEpochChanged { group: 2, old: 1, new: 2 },
+            ]
+        );
+
+        // The events are not coalesced once they are consumed.
+        observers.publish(RouteEvent::GroupEpochChanged { group: 1, old: 3, new: 4 });
+        assert_eq!(
+            drain(&mut stream),
+            vec![RouteEvent::GroupEpochChanged { group: 1, old: 3, new: 4 }]
+        );
+    }
+
+    #[test]
+    fn filter_events() {
+        let observers = RouteObservers::default();
+        let filter = RouteEventFilter {
+            groups: HashSet::from([2]),
+            kinds: HashSet::from([RouteEventKind::ShardMoved, RouteEventKind::LeaderChanged]),
+        };
+        let mut stream = Box::pin(observers.subscribe(filter));
+        observers.publish(RouteEvent::GroupEpochChanged { group: 2, old: 1, new: 2 });
+        observers.publish(RouteEvent::LeaderChanged { group: 1, node: 1 });
+        observers.publish(RouteEvent::LeaderChanged { group: 2, node: 1 });
+        observers.publish(RouteEvent::ShardMoved { shard: 1, from_group: 2, to_group: 3 });
+        assert_eq!(
+            drain(&mut stream),
+            vec![
+                RouteEvent::LeaderChanged { group: 2, node: 1 },
+                RouteEvent::ShardMoved { shard: 1, from_group: 2, to_group: 3 },
+            ]
+        );
+
+        drop(stream);
+        observers.publish(RouteEvent::LeaderChanged { group: 2, node: 2 });
+        assert!(observers.is_empty());
+    }
+}
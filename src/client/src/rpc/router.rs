@@ -12,11 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
 use log::{info, trace, warn};
 use sekas_api::server::v1::watch_response::delete_event::Event as DeleteEvent;
 use sekas_api::server::v1::watch_response::update_event::Event as UpdateEvent;
@@ -25,7 +25,11 @@ use sekas_api::Epoch;
 use tokio::task::JoinHandle;
 use tonic::Streaming;
 
-use crate::rpc::RootClient;
+use crate::metrics::CLIENT_ROUTER_STALENESS_SECONDS;
+use crate::rpc::dropped_schema::{DropHandler, DroppedSchemas};
+use crate::rpc::route_event::RouteObservers;
+use crate::rpc::shard_lease::{PendingNotices, ShardLeases};
+use crate::rpc::{RootClient, RouteEvent, RouteEventFilter, ShardLease, ShardLeaseOptions};
 
 #[derive(Debug, Clone)]
 pub struct Router {
@@ -48,8 +52,14 @@ pub struct State {
     co_shards_lookup: HashMap<u64 /* co */, Vec<ShardDesc>>,
     shard_group_lookup: HashMap<u64 /* shard */, (u64, u64) /* (group, epoch) */>,
     group_id_lookup: HashMap<u64 /* group */, RouterGroupState>,
+    /// The group descriptors applied, the deltas are applied based on them.
+    group_desc_lookup: HashMap<u64 /* group */, GroupDesc>,
 
     cached_group_states: HashMap<u64, GroupState>,
+
+    observers: RouteObservers,
+    leases: ShardLeases,
+    dropped: DroppedSchemas,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -92,6 +102,29 @@ impl Router {
         Err(crate::Error::NotFound(format!("shard (key={:?})", user_key)))
     }
 
+    /// Find the shard which contains the keys just before the exclusive
+    /// `end_key`, `None` means the end of the table. It is used to traverse
+    /// shards in reverse order.
+    pub fn find_shard_before(
+        &self,
+        table_id: u64,
+        end_key: Option<&[u8]>,
+    ) -> Result<(RouterGroupState, ShardDesc), crate::Error> {
+        let state = self.core.state.lock().unwrap();
+        let shards = state
+            .co_shards_lookup
+            .get(&table_id)
+            .ok_or_else(|| crate::Error::NotFound(format!("shard (before key={:?})", end_key)))?;
+        for shard in shards {
+            if sekas_schema::shard::belong_to_before(shard, end_key) {
+                if let Some(group_state) = state.find_group_by_shard(shard.id) {
+                    return Ok((group_state, shard.clone()));
+                }
+            }
+        }
+        Err(crate::Error::NotFound(format!("shard (before key={:?})", end_key)))
+    }
+
     pub fn find_group_by_shard(&self, shard: u64) -> Result<RouterGroupState, crate::Error> {
         let state = self.core.state.lock().unwrap();
         state
@@ -99,6 +132,29 @@ impl Router {
             .ok_or_else(|| crate::Error::NotFound(format!("group (shard={shard:?})")))
     }
 
+    /// Find the shards of the table, and the states of the groups which the
+    /// shards belong to.
+    pub fn find_table_shards(&self, table_id: u64) -> Vec<(ShardDesc, Option<RouterGroupState>)> {
+        let state = self.core.state.lock().unwrap();
+        let Some(shards) = state.co_shards_lookup.get(&table_id) else {
+            return vec![];
+        };
+        shards.iter().map(|shard| (shard.clone(), state.find_group_by_shard(shard.id))).collect()
+    }
+
+    /// Find the table desc delivered by the watch stream of root.
+    pub fn find_table(&self, db: u64, name: &str) -> Option<TableDesc> {
+        let state = self.core.state.lock().unwrap();
+        let id = state.co_name_lookup.get(&(db, name.to_owned()))?;
+        state.co_id_lookup.get(id).cloned()
+    }
+
+    /// Find the table desc by id, delivered by the watch stream of root.
+    pub fn find_table_by_id(&self, table_id: u64) -> Option<TableDesc> {
+        let state = self.core.state.lock().unwrap();
+        state.co_id_lookup.get(&table_id).cloned()
+    }
+
     pub fn find_group(&self, id: u64) -> Result<RouterGroupState, crate::Error> {
         let state = self.core.state.lock().unwrap();
         let group = state.group_id_lookup.get(&id).cloned();
@@ -114,6 +170,58 @@ impl Router {
     pub fn total_nodes(&self) -> usize {
         self.core.state.lock().unwrap().node_id_lookup.len()
     }
+
+    /// Subscribe the routing changes selected by the filter.
+    ///
+    /// The events are derived from the updates applied by the router, in order
+    /// per group. The pending events of a lagged subscriber are coalesced, and
+    /// the routing updates never wait for the subscribers.
+    pub fn subscribe(&self, filter: RouteEventFilter) -> impl Stream<Item = RouteEvent> {
+        let observers = self.core.state.lock().unwrap().observers.clone();
+        observers.subscribe(filter)
+    }
+
+    /// Register the interest in the routing changes of the shard.
+    ///
+    /// Before applying any update which changes the epoch of the shard, eg. a
+    /// split or a shard moving, the router notifies the lease and waits until
+    /// the notice is acknowledged, at most `ShardLeaseOptions::window`. The
+    /// lease is expired if it is not renewed within `ShardLeaseOptions::ttl`.
+    pub fn lease_shard(&self, shard_id: u64) -> ShardLease {
+        let leases = self.core.state.lock().unwrap().leases.clone();
+        leases.lease(shard_id)
+    }
+
+    /// Set the options of the shard leases, the acquired leases keep their
+    /// ttl.
+    pub fn set_shard_lease_options(&self, opts: ShardLeaseOptions) {
+        self.core.state.lock().unwrap().leases.set_options(opts);
+    }
+
+    /// Whether the database is observed dropped by the watch stream of root.
+    pub fn is_database_dropped(&self, db_id: u64) -> bool {
+        self.core.state.lock().unwrap().dropped.is_database_dropped(db_id)
+    }
+
+    /// Whether the table, or the database of it, is observed dropped by the
+    /// watch stream of root.
+    pub fn is_table_dropped(&self, table_id: u64) -> bool {
+        self.core.state.lock().unwrap().dropped.is_table_dropped(table_id)
+    }
+
+    /// Call the handler in background once the database is observed dropped.
+    pub(crate) fn on_database_dropped(&self, db_id: u64, handler: DropHandler) {
+        let dropped = self.core.state.lock().unwrap().dropped.clone();
+        dropped.on_database_dropped(db_id, handler);
+    }
+}
+
+impl RouterGroupState {
+    /// The id of the node which the leader replica located.
+    fn leader_node(&self) -> Option<u64> {
+        let (replica_id, _) = self.leader_state?;
+        self.replicas.get(&replica_id).map(|r| r.node_id)
+    }
 }
 
 impl Drop for RouterCore {
@@ -149,11 +257,20 @@ impl State {
             UpdateEvent::Group(group_desc) => {
                 self.apply_group_descriptor(group_desc);
             }
+            UpdateEvent::GroupDelta(delta) => {
+                if let Some(group_desc) = self.resolve_group_delta(&delta) {
+                    self.apply_group_descriptor(group_desc);
+                }
+            }
             UpdateEvent::GroupState(group_state) => {
                 trace!("update event; group state {group_state:?}");
                 let id = group_state.group_id;
                 if let Some(group) = self.group_id_lookup.get_mut(&id) {
+                    let old_leader = group.leader_node();
                     group.leader_state = leader_state(&group_state);
+                    if let Some(node) = group.leader_node().filter(|n| Some(*n) != old_leader) {
+                        self.observers.publish(RouteEvent::LeaderChanged { group: id, node });
+                    }
                 } else {
                     self.cached_group_states.insert(id, group_state);
                 }
@@ -181,21 +298,65 @@ impl State {
         }
     }
 
+    /// Notify the leases of the shards whose epoch will be changed by the group
+    /// desc, either served by the group or moved into the group.
+    fn notify_shard_leases(&self, group_desc: &GroupDesc) -> PendingNotices {
+        let mut pending = PendingNotices::new();
+        let (id, epoch) = (group_desc.id, group_desc.epoch);
+        let old_epoch = self.group_id_lookup.get(&id).map(|state| state.epoch);
+        for shard_id in self.leases.shards() {
+            let Some((group_id, shard_epoch)) = self.shard_group_lookup.get(&shard_id) else {
+                continue;
+            };
+            let changed = if *group_id == id {
+                old_epoch.is_some_and(|old| old != epoch)
+            } else {
+                *shard_epoch < epoch && group_desc.shards.iter().any(|s| s.id == shard_id)
+            };
+            if changed {
+                self.leases.notify(shard_id, id, epoch, &mut pending);
+            }
+        }
+        pending
+    }
+
+    /// Resolve the group descriptor by applying the delta to the descriptor
+    /// applied before, `None` is returned if the delta is not based on it.
+    fn resolve_group_delta(&self, delta: &GroupDescDelta) -> Option<GroupDesc> {
+        let mut group_desc = self.group_desc_lookup.get(&delta.group_id)?.clone();
+        if !group_desc.apply_delta(delta) {
+            return None;
+        }
+        Some(group_desc)
+    }
+
     fn apply_group_descriptor(&mut self, group_desc: GroupDesc) {
         trace!("update event; group {group_desc:?}");
+        self.group_desc_lookup.insert(group_desc.id, group_desc.clone());
         let (id, epoch) = (group_desc.id, group_desc.epoch);
         let (shards, replicas) = (group_desc.shards, group_desc.replicas);
 
         let replicas =
             replicas.into_iter().map(|d| (d.id, d)).collect::<HashMap<u64, ReplicaDesc>>();
         let mut group_state = RouterGroupState { id, epoch, leader_state: None, replicas };
+        let (mut old_epoch, mut old_leader) = (None, None);
         if let Some(old_state) = self.group_id_lookup.get(&id) {
             group_state.leader_state = old_state.leader_state;
+            (old_epoch, old_leader) = (Some(old_state.epoch), old_state.leader_node());
         } else if let Some(cached_state) = self.cached_group_states.remove(&id) {
             group_state.leader_state = leader_state(&cached_state);
         }
+        let leader = group_state.leader_node().filter(|n| Some(*n) != old_leader);
         self.group_id_lookup.insert(id, group_state);
 
+        let mut events = vec![];
+        if let Some(old) = old_epoch.filter(|old| *old != epoch) {
+            events.push(RouteEvent::GroupEpochChanged { group: id, old, new: epoch });
+        }
+        if !self.observers.is_empty() {
+            events.extend(self.split_shard_events(id, &shards));
+        }
+
         for shard in shards {
             trace!(
                 "apply group desc, update shard {} to {:?}, group {}, epoch {}",
@@ -210,6 +371,13 @@ impl State {
                 }
                 Some((entry_id, entry_epoch)) => {
                     if *entry_epoch < epoch {
+                        if *entry_id != id {
+                            events.push(RouteEvent::ShardMoved {
+                                shard: shard.id,
+                                from_group: *entry_id,
+                                to_group: id,
+                            });
+                        }
                         *entry_id = id;
                         *entry_epoch = epoch;
                     }
@@ -227,6 +395,38 @@ impl State {
                 }
             }
         }
+
+        if let Some(node) = leader {
+            events.push(RouteEvent::LeaderChanged { group: id, node });
+        }
+        for event in events {
+            self.observers.publish(event);
+        }
+    }
+
+    /// Find the shards split out from the shards of the group, by the new
+    /// shards overlapped with the range of the shards before the update.
+    fn split_shard_events(&self, group_id: u64, shards: &[ShardDesc]) -> Vec<RouteEvent> {
+        let mut children: Vec<(u64, Vec<u64>)> = vec![];
+        for child in shards.iter().filter(|s| !self.shard_group_lookup.contains_key(&s.id)) {
+            let Some(table_shards) = self.co_shards_lookup.get(&child.table_id) else {
+                continue;
+            };
+            let parent = table_shards.iter().find(|old| {
+                self.shard_group_lookup.get(&old.id).map(|(group, _)| *group) == Some(group_id)
+                    && shards.iter().any(|s| s.id == old.id && s.range != old.range)
+                    && sekas_schema::shard::is_overlapped(old, child)
+            });
+            let Some(parent) = parent else { continue };
+            match children.iter_mut().find(|(id, _)| *id == parent.id) {
+                Some((_, ids)) => ids.push(child.id),
+                None => children.push((parent.id, vec![child.id])),
+            }
+        }
+        children
+            .into_iter()
+            .map(|(parent, children)| RouteEvent::ShardSplit { group: group_id, parent, children })
+            .collect()
     }
 
     fn apply_delete_event(&mut self, event: DeleteEvent) {
@@ -237,17 +437,59 @@ impl State {
             DeleteEvent::Group(_) => todo!(),
             DeleteEvent::GroupState(_) => todo!(),
             DeleteEvent::Database(db) => {
+                // The name might be taken by a recreated database.
                 if let Some(desc) = self.db_id_lookup.remove(&db) {
-                    self.db_name_lookup.remove(desc.name.as_str());
+                    if self.db_name_lookup.get(desc.name.as_str()) == Some(&db) {
+                        self.db_name_lookup.remove(desc.name.as_str());
+                    }
                 }
+                let tables = self.co_id_lookup.values().filter(|desc| desc.db == db);
+                self.dropped.drop_database(db, tables.map(|desc| desc.id).collect::<Vec<_>>());
             }
             DeleteEvent::Table(co) => {
                 if let Some(desc) = self.co_id_lookup.remove(&co) {
-                    self.co_name_lookup.remove(&(desc.db, desc.name));
+                    let name = (desc.db, desc.name);
+                    if self.co_name_lookup.get(&name) == Some(&co) {
+                        self.co_name_lookup.remove(&name);
+                    }
                 }
+                self.dropped.drop_table(co);
             }
         }
     }
+
+    /// The databases and tables missing from the snapshot are deleted while
+    /// the watch stream is broken, their delete events are applied.
+    fn apply_missing_deletes(&mut self, listed: &ListedSchemas) {
+        let (dbs, tables) = listed;
+        let missing_dbs = self.db_id_lookup.keys().filter(|id| !dbs.contains(id));
+        let missing_dbs = missing_dbs.cloned().collect::<Vec<_>>();
+        let missing_tables = self.co_id_lookup.keys().filter(|id| !tables.contains(id));
+        let missing_tables = missing_tables.cloned().collect::<Vec<_>>();
+        for db in missing_dbs {
+            info!("database {db} is deleted while the watch stream is broken");
+            self.apply_delete_event(DeleteEvent::Database(db));
+        }
+        for table in missing_tables {
+            self.apply_delete_event(DeleteEvent::Table(table));
+        }
+    }
+}
+
+/// The ids of the databases and tables listed by the snapshot of a watch
+/// stream.
+type ListedSchemas = (HashSet<u64>, HashSet<u64>);
+
+fn listed_schemas(updates: &[watch_response::UpdateEvent]) -> ListedSchemas {
+    let mut listed = ListedSchemas::default();
+    for update in updates {
+        match &update.event {
+            Some(UpdateEvent::Database(desc)) => listed.0.insert(desc.id),
+            Some(UpdateEvent::Table(desc)) => listed.1.insert(desc.id),
+            _ => false,
+        };
+    }
+    listed
 }
 
 async fn state_main(state: Arc<Mutex<State>>, root_client: RootClient) {
@@ -261,20 +503,44 @@ async fn state_main(state: Arc<Mutex<State>>, root_client: RootClient) {
         };
         let events = match root_client.watch(cur_group_epochs).await {
             Ok(events) => events,
+            Err(crate::Error::RootUnavailable(since)) => {
+                // The cached routing is served until root is recovered.
+                wait_root_available(&root_client, since).await;
+                continue;
+            }
             Err(e) => {
                 warn!("watch events: {e:?}");
-                tokio::time::sleep(Duration::from_millis(interval)).await;
+                sekas_runtime::time::sleep(Duration::from_millis(interval)).await;
                 interval = std::cmp::min(interval * 2, 1000);
                 continue;
             }
         };
 
         interval = 1;
+        CLIENT_ROUTER_STALENESS_SECONDS.set(0.0);
         watch_events(state.as_ref(), events).await;
     }
 }
 
+/// Wait until root is available, the staleness of the routing is recorded
+/// meanwhile.
+async fn wait_root_available(root_client: &RootClient, since: SystemTime) {
+    const RECORD_INTERVAL: Duration = Duration::from_secs(1);
+
+    loop {
+        let staleness = since.elapsed().unwrap_or_default();
+        CLIENT_ROUTER_STALENESS_SECONDS.set(staleness.as_secs_f64());
+        if sekas_runtime::time::timeout(RECORD_INTERVAL, root_client.wait_available()).await.is_ok()
+        {
+            return;
+        }
+    }
+}
+
 async fn watch_events(state: &Mutex<State>, mut events: Streaming<WatchResponse>) {
+    // The first response of the stream is the snapshot of all databases and
+    // tables.
+    let mut is_snapshot = true;
     while let Some(event) = events.next().await {
         let (updates, deletes) = match event {
             Ok(resp) => (resp.updates, resp.deletes),
@@ -283,8 +549,27 @@ async fn watch_events(state: &Mutex<State>, mut events: Streaming<WatchResponse>
                 continue;
             }
         };
+        let listed = std::mem::take(&mut is_snapshot).then(|| listed_schemas(&updates));
         for update in updates {
-            if let Some(event) = update.event {
+            if let Some(mut event) = update.event {
+                if let UpdateEvent::GroupDelta(delta) = &event {
+                    let Some(group_desc) = state.lock().unwrap().resolve_group_delta(delta) else {
+                        // There is a gap between the routing and the delta, watch again to
+                        // resync the full descriptor.
+                        warn!(
+                            "group {} delta is based on epoch {}, resync the descriptor",
+                            delta.group_id,
+                            Epoch(delta.base_epoch)
+                        );
+                        return;
+                    };
+                    event = UpdateEvent::Group(group_desc);
+                }
+                if let UpdateEvent::Group(group_desc) = &event {
+                    // The new routing is not served until the lease holders are notified.
+                    let pending = state.lock().unwrap().notify_shard_leases(group_desc);
+                    pending.wait().await;
+                }
                 let mut state = state.lock().unwrap();
                 state.apply_update_event(event);
             }
@@ -295,6 +580,9 @@ async fn watch_events(state: &Mutex<State>, mut events: Streaming<WatchResponse>
                 state.apply_delete_event(event);
             }
         }
+        if let Some(listed) = listed {
+            state.lock().unwrap().apply_missing_deletes(&listed);
+        }
     }
 }
 
@@ -333,6 +621,158 @@ mod tests {
         GroupDesc { id, epoch, shards: vec![], replicas: vec![] }
     }
 
+    fn range_shard(id: u64, start: &[u8], end: &[u8]) -> ShardDesc {
+        let range = Some(RangePartition { start: start.to_vec(), end: end.to_vec() });
+        ShardDesc { id, table_id: 1, range }
+    }
+
+    #[test]
+    fn route_events_of_group_descriptor() {
+        use futures::FutureExt;
+
+        let mut state = State::default();
+        let mut events = Box::pin(state.observers.subscribe(RouteEventFilter::default()));
+        let mut desc = descriptor(1, 1);
+        desc.shards.push(range_shard(1, b"", b""));
+        state.apply_group_descriptor(desc);
+        state.apply_group_descriptor(descriptor(2, 1));
+
+        // Shard 1 is split into shard 1 and 2.
+        let mut desc = descriptor(1, 2);
+        desc.shards.push(range_shard(1, b"", b"b"));
+        desc.shards.push(range_shard(2, b"b", b""));
+        state.apply_group_descriptor(desc);
+
+        // Shard 2 is moved to group 2.
+        let mut desc = descriptor(2, 1 + (1 << 32));
+        desc.shards.push(range_shard(2, b"b", b""));
+        state.apply_group_descriptor(desc);
+
+        let mut got = vec![];
+        while let Some(Some(event)) = events.next().now_or_never() {
+            got.push(event);
+        }
+        assert_eq!(
+            got,
+            vec![
+                RouteEvent::GroupEpochChanged { group: 1, old: 1, new: 2 },
+                RouteEvent::ShardSplit { group: 1, parent: 1, children: vec![2] },
+                RouteEvent::GroupEpochChanged { group: 2, old: 1, new: 1 + (1 << 32) },
+                RouteEvent::ShardMoved { shard: 2, from_group: 1, to_group: 2 },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn drop_schemas_missing_from_snapshot() {
+        let mut state = State::default();
+        let db = |id: u64| DatabaseDesc { id, name: format!("db-{id}"), ..Default::default() };
+        let table =
+            |id: u64, db: u64| TableDesc { id, db, name: format!("t-{id}"), ..Default::default() };
+        state.apply_update_event(UpdateEvent::Database(db(1)));
+        state.apply_update_event(UpdateEvent::Database(db(2)));
+        state.apply_update_event(UpdateEvent::Table(table(10, 1)));
+        state.apply_update_event(UpdateEvent::Table(table(20, 2)));
+        state.apply_update_event(UpdateEvent::Table(table(21, 2)));
+
+        // Database 1 and table 21 are deleted while the watch stream is broken, and
+        // table 21 is recreated under the same name.
+        let recreated = TableDesc { name: "t-21".to_owned(), ..table(22, 2) };
+        let snapshot = [
+            UpdateEvent::Database(db(2)),
+            UpdateEvent::Table(table(20, 2)),
+            UpdateEvent::Table(recreated),
+        ]
+        .into_iter()
+        .map(|event| watch_response::UpdateEvent { event: Some(event) })
+        .collect::<Vec<_>>();
+        let listed = listed_schemas(&snapshot);
+        for update in snapshot {
+            state.apply_update_event(update.event.unwrap());
+        }
+        state.apply_missing_deletes(&listed);
+        assert!(state.dropped.is_database_dropped(1));
+        assert!(!state.dropped.is_database_dropped(2));
+        assert!(state.dropped.is_table_dropped(10));
+        assert!(!state.dropped.is_table_dropped(20));
+        assert!(state.dropped.is_table_dropped(21));
+        assert!(!state.dropped.is_table_dropped(22));
+        assert!(!state.db_name_lookup.contains_key("db-1"));
+        assert_eq!(state.co_name_lookup.get(&(2, "t-21".to_owned())), Some(&22));
+    }
+
+    #[tokio::test]
+    async fn notify_shard_leases_of_group_descriptor() {
+        use futures::FutureExt;
+
+        let mut state = State::default();
+        let mut desc = descriptor(1, 1);
+        desc.shards.push(range_shard(1, b"", b""));
+        state.apply_group_descriptor(desc.clone());
+        state.apply_group_descriptor(descriptor(2, 1));
+        let mut lease = state.leases.lease(1);
+
+        // The epoch is not changed.
+        state.notify_shard_leases(&desc).wait().await;
+        assert!(lease.notified().now_or_never().is_none());
+
+        // Shard 1 is split, the old routing is served until the notice is acked.
+        let mut desc = descriptor(1, 2);
+        desc.shards.push(range_shard(1, b"", b"b"));
+        desc.shards.push(range_shard(2, b"b", b""));
+        let mut wait = Box::pin(state.notify_shard_leases(&desc).wait());
+        let notice = lease.notified().await.unwrap();
+        assert_eq!((notice.shard_id, notice.group_id, notice.epoch), (1, 1, 2));
+        assert!((&mut wait).now_or_never().is_none());
+        assert_eq!(state.find_group_by_shard(1).unwrap().epoch, 1);
+        notice.ack();
+        wait.await;
+        state.apply_group_descriptor(desc);
+
+        // Shard 1 is moved to group 2.
+        let mut desc = descriptor(2, 1 + (1 << 32));
+        desc.shards.push(range_shard(1, b"", b"b"));
+        state.notify_shard_leases(&desc).wait().await;
+        let notice = lease.notified().await.unwrap();
+        assert_eq!((notice.shard_id, notice.group_id, notice.epoch), (1, 2, 1 + (1 << 32)));
+
+        // The expired leases are not notified.
+        drop((notice, lease));
+        state.apply_group_descriptor(desc);
+        state.leases.set_options(ShardLeaseOptions { ttl: Duration::ZERO, ..Default::default() });
+        let mut lease = state.leases.lease(1);
+        state.notify_shard_leases(&descriptor(2, 2 + (1 << 32))).wait().await;
+        assert!(lease.notified().now_or_never().is_none());
+    }
+
+    #[test]
+    fn apply_group_descriptor_delta() {
+        let mut state = State::default();
+        let mut desc = descriptor(1, 1);
+        desc.shards.push(range_shard(1, b"", b""));
+        state.apply_group_descriptor(desc.clone());
+
+        // Shard 1 is split into shard 1 and 2.
+        let mut split_desc = descriptor(1, 2);
+        split_desc.shards.push(range_shard(1, b"", b"b"));
+        split_desc.shards.push(range_shard(2, b"b", b""));
+        let delta = split_desc.delta_since(&desc);
+        state.apply_update_event(UpdateEvent::GroupDelta(delta.clone()));
+        assert_eq!(state.find_group_by_shard(2).unwrap().epoch, 2);
+        assert_eq!(state.co_shards_lookup[&1].len(), 2);
+
+        // The delta is not applied twice.
+        assert!(state.resolve_group_delta(&delta).is_none());
+
+        // A gap between the routing and the delta requires a resync.
+        let mut moved_desc = descriptor(1, 4);
+        moved_desc.shards.push(range_shard(1, b"", b"b"));
+        let gap_delta = moved_desc.delta_since(&descriptor(1, 3));
+        assert!(state.resolve_group_delta(&gap_delta).is_none());
+        state.apply_update_event(UpdateEvent::GroupDelta(gap_delta));
+        assert_eq!(state.group_id_lookup[&1].epoch, 2);
+    }
+
     #[test]
     fn update_shard_by_group_descriptor() {
         // Shard 1 migrated from group 1 to group 2.
@@ -0,0 +1,320 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The leases of the shards, to be told before the routing of a shard changes.
+//!
+//! Before the router applies a group desc which changes the epoch of a leased
+//! shard, it sends a notice to the lease holder and waits until the notice is
+//! acknowledged (or dropped), so the holder could invalidate the values cached
+//! for the shard before any request is routed with the new epoch. The waiting
+//! is bounded by a window, so a dead holder can't stall the routing. A lease is
+//! expired if it is not renewed within the ttl, and the expired leases are not
+//! notified.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use futures::channel::oneshot;
+use sekas_runtime::time::Instant;
+use tokio::sync::mpsc;
+
+use crate::metrics::CLIENT_SHARD_LEASE_NOTIFY_TIMEOUT_TOTAL;
+
+/// The options of the shard leases.
+#[derive(Debug, Clone, Copy)]
+pub struct ShardLeaseOptions {
+    /// The max duration the router waits for the notices being acknowledged
+    /// before applying the new routing, 100ms by default.
+    pub window: Duration,
+    /// The duration of a lease being valid since it is acquired or renewed, 10s
+    /// by default.
+    pub ttl: Duration,
+}
+
+/// The interest in the routing changes of a shard. The lease is released once
+/// it is dropped.
+#[derive(Debug)]
+pub struct ShardLease {
+    shard_id: u64,
+    inner: Arc<LeaseInner>,
+    core: Weak<Mutex<LeasesCore>>,
+    receiver: mpsc::UnboundedReceiver<ShardLeaseNotice>,
+}
+
+/// The epoch of the leased shard is about to change. The router doesn't serve
+/// the new routing until the notice is acknowledged or dropped, or the window
+/// is elapsed.
+#[derive(Debug)]
+pub struct ShardLeaseNotice {
+    /// The id of the leased shard.
+    pub shard_id: u64,
+    /// The group which the shard belongs to after the change.
+    pub group_id: u64,
+    /// The epoch of the group after the change.
+    pub epoch: u64,
+    ack: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Debug)]
+struct LeaseInner {
+    ttl: Duration,
+    expire_at: Mutex<Instant>,
+    sender: mpsc::UnboundedSender<ShardLeaseNotice>,
+}
+
+/// The leases registered in the router.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ShardLeases {
+    core: Arc<Mutex<LeasesCore>>,
+}
+
+#[derive(Debug, Default)]
+struct LeasesCore {
+    opts: ShardLeaseOptions,
+    leases: HashMap<u64 /* shard */, Vec<Weak<LeaseInner>>>,
+}
+
+/// The notices sent to the lease holders, which are waited before applying the
+/// new routing.
+pub(crate) struct PendingNotices {
+    window: Duration,
+    acks: Vec<oneshot::Receiver<()>>,
+}
+
+impl Default for ShardLeaseOptions {
+    fn default() -> Self {
+        ShardLeaseOptions { window: Duration::from_millis(100), ttl: Duration::from_secs(10) }
+    }
+}
+
+impl ShardLease {
+    #[inline]
+    pub fn shard_id(&self) -> u64 {
+        self.shard_id
+    }
+
+    /// Wait for the next notice of the shard, `None` is returned if the router
+    /// is dropped.
+    pub async fn notified(&mut self) -> Option<ShardLeaseNotice> {
+        self.receiver.recv().await
+    }
+
+    /// Extend the lease by the ttl since now. The lease is registered again if
+    /// it has been removed from the router since it was expired.
+    pub fn renew(&self) {
+        *self.inner.expire_at.lock().unwrap() = Instant::now() + self.inner.ttl;
+        if let Some(core) = self.core.upgrade() {
+            core.lock().unwrap().register(self.shard_id, &self.inner);
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.inner.is_expired()
+    }
+}
+
+impl ShardLeaseNotice {
+    /// Tell the router that the cached values of the shard are invalidated. It
+    /// is the same as dropping the notice.
+    pub fn ack(mut self) {
+        if let Some(ack) = self.ack.take() {
+            let _ = ack.send(());
+        }
+    }
+}
+
+impl LeaseInner {
+    fn is_expired(&self) -> bool {
+        *self.expire_at.lock().unwrap() <= Instant::now()
+    }
+}
+
+impl ShardLeases {
+    pub(crate) fn set_options(&self, opts: ShardLeaseOptions) {
+        self.core.lock().unwrap().opts = opts;
+    }
+
+    pub(crate) fn lease(&self, shard_id: u64) -> ShardLease {
+        let mut core = self.core.lock().unwrap();
+        let ttl = core.opts.ttl;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let expire_at = Mutex::new(Instant::now() + ttl);
+        let inner = Arc::new(LeaseInner { ttl, expire_at, sender });
+        core.register(shard_id, &inner);
+        ShardLease { shard_id, inner, core: Arc::downgrade(&self.core), receiver }
+    }
+
+    /// The shards which have leases registered, the leases might be expired.
+    pub(crate) fn shards(&self) -> Vec<u64> {
+        self.core.lock().unwrap().leases.keys().cloned().collect()
+    }
+
+    /// Send the notices to the holders of the unexpired leases of the shard,
+    /// the released and expired leases are removed.
+    pub(crate) fn notify(
+        &self,
+        shard_id: u64,
+        group_id: u64,
+        epoch: u64,
+        pending: &mut PendingNotices,
+    ) {
+        let mut core = self.core.lock().unwrap();
+        pending.window = core.opts.window;
+        let Some(leases) = core.leases.get_mut(&shard_id) else { return };
+        leases.retain(|lease| {
+            let Some(lease) = lease.upgrade().filter(|lease| !lease.is_expired()) else {
+                return false;
+            };
+            let (sender, receiver) = oneshot::channel();
+            let notice = ShardLeaseNotice { shard_id, group_id, epoch, ack: Some(sender) };
+            if lease.sender.send(notice).is_ok() {
+                pending.acks.push(receiver);
+            }
+            true
+        });
+        if leases.is_empty() {
+            core.leases.remove(&shard_id);
+        }
+    }
+}
+
+impl LeasesCore {
+    /// Register the lease of the shard if it isn't registered yet, the
+    /// released leases are removed.
+    fn register(&mut self, shard_id: u64, inner: &Arc<LeaseInner>) {
+        let leases = self.leases.entry(shard_id).or_default();
+        leases.retain(|lease| lease.strong_count() > 0);
+        if !leases.iter().any(|lease| std::ptr::eq(lease.as_ptr(), Arc::as_ptr(inner))) {
+            leases.push(Arc::downgrade(inner));
+        }
+    }
+}
+
+impl PendingNotices {
+    pub(crate) fn new() -> Self {
+        PendingNotices { window: Duration::ZERO, acks: vec![] }
+    }
+
+    /// Wait until all notices are acknowledged or dropped, or the window is
+    /// elapsed.
+    pub(crate) async fn wait(self) {
+        if self.acks.is_empty() {
+            return;
+        }
+        let acks = futures::future::join_all(self.acks);
+        if sekas_runtime::time::timeout(self.window, acks).await.is_err() {
+            CLIENT_SHARD_LEASE_NOTIFY_TIMEOUT_TOTAL.inc();
+        }
+    }
+}
+
+impl Drop for ShardLeaseNotice {
+    fn drop(&mut self) {
+        if let Some(ack) = self.ack.take() {
+            let _ = ack.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+
+    fn leases(window: Duration, ttl: Duration) -> ShardLeases {
+        let leases = ShardLeases::default();
+        leases.set_options(ShardLeaseOptions { window, ttl });
+        leases
+    }
+
+    #[tokio::test]
+    async fn notify_shard_lease_holders() {
+        let leases = leases(Duration::from_secs(60), Duration::from_secs(60));
+        let mut lease = leases.lease(1);
+        let mut pending = PendingNotices::new();
+        leases.notify(1, 2, 3, &mut pending);
+        leases.notify(2, 2, 3, &mut pending);
+        let mut wait = Box::pin(pending.wait());
+        assert!((&mut wait).now_or_never().is_none());
+
+        let notice = lease.notified().await.unwrap();
+        assert_eq!((notice.shard_id, notice.group_id, notice.epoch), (1, 2, 3));
+        assert!((&mut wait).now_or_never().is_none());
+        notice.ack();
+        wait.await;
+
+        // The released leases are removed.
+        drop(lease);
+        let mut pending = PendingNotices::new();
+        leases.notify(1, 2, 4, &mut pending);
+        assert!(pending.acks.is_empty());
+        assert!(leases.shards().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_shard_lease_is_not_notified() {
+        let leases = leases(Duration::from_secs(60), Duration::ZERO);
+        let mut lease = leases.lease(1);
+        assert!(lease.is_expired());
+        let mut pending = PendingNotices::new();
+        leases.notify(1, 2, 3, &mut pending);
+        assert!(pending.acks.is_empty());
+        assert!(lease.notified().now_or_never().is_none());
+
+        leases.set_options(ShardLeaseOptions {
+            window: Duration::from_secs(60),
+            ttl: Duration::from_secs(60),
+        });
+        let lease = leases.lease(1);
+        assert!(!lease.is_expired());
+    }
+
+    #[tokio::test]
+    async fn renewed_shard_lease_is_notified_again() {
+        let ttl = Duration::from_millis(50);
+        let leases = leases(Duration::from_secs(60), ttl);
+        let mut lease = leases.lease(1);
+        sekas_runtime::time::sleep(ttl).await;
+        assert!(lease.is_expired());
+        let mut pending = PendingNotices::new();
+        leases.notify(1, 2, 3, &mut pending);
+        assert!(pending.acks.is_empty());
+        assert!(leases.shards().is_empty());
+
+        // The expired lease is removed from the router, it is registered again
+        // once renewed.
+        lease.renew();
+        lease.renew();
+        let mut pending = PendingNotices::new();
+        leases.notify(1, 2, 4, &mut pending);
+        assert_eq!(pending.acks.len(), 1);
+        let notice = lease.notified().await.unwrap();
+        assert_eq!((notice.shard_id, notice.group_id, notice.epoch), (1, 2, 4));
+    }
+
+    #[tokio::test]
+    async fn unresponsive_holder_is_bounded_by_window() {
+        let window = Duration::from_millis(50);
+        let leases = leases(window, Duration::from_secs(60));
+        let _lease = leases.lease(1);
+        let mut pending = PendingNotices::new();
+        leases.notify(1, 2, 3, &mut pending);
+        assert_eq!(pending.acks.len(), 1);
+        let start_at = Instant::now();
+        pending.wait().await;
+        assert!(start_at.elapsed() >= window);
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use futures::StreamExt;
+use prost::Message;
+use sekas_api::server::v1::*;
+use sekas_rock::lexical::lexical_next;
+
+use crate::range::extract_request_range;
+use crate::{AppError, AppResult, Database, Range, RangeRequest, ScanOptions, Txn};
+
+/// The opaque position to resume a paginated scan, see [`Database::scan_page`].
+///
+/// It records the next user key to scan and the read version of the scan,
+/// rather than any shard, so the scan resumed after splits, merges or moves of
+/// the shards lands at exactly the next key, at the same read version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanToken {
+    cursor: ScanCursor,
+}
+
+/// A page of a paginated scan.
+#[derive(Debug, Clone)]
+pub struct ScanPage {
+    /// The value sets of the page, in the order of the scan.
+    pub value_sets: Vec<ValueSet>,
+    /// The version all pages of the scan are read at.
+    pub read_version: u64,
+    /// The token to fetch the next page, `None` if the range is scanned.
+    pub next: Option<ScanToken>,
+}
+
+impl ScanToken {
+    /// Serialize the token, so it could be persisted across process restarts.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.cursor.encode_to_vec()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> AppResult<Self> {
+        let cursor = ScanCursor::decode(bytes)
+            .map_err(|err| AppError::InvalidArgument(format!("scan token: {err}")))?;
+        Ok(ScanToken { cursor })
+    }
+
+    #[inline]
+    pub fn table_id(&self) -> u64 {
+        self.cursor.table_id
+    }
+
+    #[inline]
+    pub fn read_version(&self) -> u64 {
+        self.cursor.read_version
+    }
+}
+
+impl Database {
+    /// Scan the first page of the range, which has at most `request.limit`
+    /// value sets, or about `request.limit_bytes` bytes of key-value pairs.
+    /// `0` means unlimited. The rest pages are fetched by
+    /// [`Database::resume_scan`] with the token of the page.
+    ///
+    /// All pages are read at the same version, which is allocated for the first
+    /// page if `request.version` is not specified. So the concatenated pages
+    /// are a consistent snapshot of the range.
+    pub async fn scan_page(&self, request: RangeRequest) -> AppResult<ScanPage> {
+        let read_version = match request.version {
+            Some(version) => version,
+            None => Txn::new(self.clone()).read_version().await?,
+        };
+        let (start_key, end_key) = extract_request_range(request.range);
+        let cursor = ScanCursor {
+            table_id: request.table_id,
+            read_version,
+            start_key,
+            end_key,
+            reverse: request.reverse,
+            ignore_txn_intent: request.options.ignore_txn_intent,
+        };
+        self.scan_cursor(cursor, request.limit, request.limit_bytes).await
+    }
+
+    /// Scan the next page from the token, see [`Database::scan_page`].
+    ///
+    /// [`AppError::VersionTooOld`] is returned if the read version of the scan
+    /// is beneath the GC watermark.
+    pub async fn resume_scan(
+        &self,
+        token: &ScanToken,
+        limit: u64,
+        limit_bytes: u64,
+    ) -> AppResult<ScanPage> {
+        self.scan_cursor(token.cursor.clone(), limit, limit_bytes).await
+    }
+
+    async fn scan_cursor(
+        &self,
+        cursor: ScanCursor,
+        limit: u64,
+        limit_bytes: u64,
+    ) -> AppResult<ScanPage> {
+        let request = RangeRequest {
+            table_id: cursor.table_id,
+            version: Some(cursor.read_version),
+            range: Range::Range {
+                begin: Some(cursor.start_key.clone()),
+                end: cursor.end_key.clone(),
+            },
+            limit,
+            limit_bytes,
+            reverse: cursor.reverse,
+            options: ScanOptions { ignore_txn_intent: cursor.ignore_txn_intent },
+            ..Default::default()
+        };
+        let mut stream = Txn::new(self.clone()).range(request).await?;
+        let mut value_sets = Vec::new();
+        let mut num_bytes = 0;
+        let mut has_more = false;
+        'fetch: while let Some(batch) = stream.next().await {
+            for value_set in batch? {
+                if is_page_full(value_sets.len() as u64, num_bytes, limit, limit_bytes) {
+                    has_more = true;
+                    break 'fetch;
+                }
+                num_bytes += value_set_size(&value_set);
+                value_sets.push(value_set);
+            }
+        }
+
+        let read_version = cursor.read_version;
+        let next = match value_sets.last() {
+            Some(last) if has_more => Some(ScanToken { cursor: advance(cursor, &last.user_key) }),
+            _ => None,
+        };
+        Ok(ScanPage { value_sets, read_version, next })
+    }
+}
+
+fn is_page_full(num_value_sets: u64, num_bytes: u64, limit: u64, limit_bytes: u64) -> bool {
+    (limit != 0 && num_value_sets >= limit) || (limit_bytes != 0 && num_bytes >= limit_bytes)
+}
+
+fn value_set_size(value_set: &ValueSet) -> u64 {
+    let values = value_set.values.iter().map(|v| v.content.as_ref().map_or(0, Vec::len));
+    (value_set.user_key.len() + values.sum::<usize>()) as u64
+}
+
+/// Move the cursor past the last scanned key.
+fn advance(mut cursor: ScanCursor, last_key: &[u8]) -> ScanCursor {
+    if cursor.reverse {
+        // The end key is excluded.
+        cursor.end_key = Some(last_key.to_owned());
+    } else {
+        cursor.start_key = lexical_next(last_key);
+    }
+    cursor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_token_advance_past_last_key() {
+        let cursor = ScanCursor {
+            table_id: 1,
+            read_version: 100,
+            start_key: b"a".to_vec(),
+            end_key: Some(b"z".to_vec()),
+            ..Default::default()
+        };
+        let next = advance(cursor.clone(), b"k");
+        assert_eq!(next.start_key, b"k\x00");
+        assert_eq!(next.end_key, Some(b"z".to_vec()));
+
+        let cursor = ScanCursor { reverse: true, ..cursor };
+        let next = advance(cursor, b"k");
+        assert_eq!(next.start_key, b"a");
+        assert_eq!(next.end_key, Some(b"k".to_vec()));
+
+        let token = ScanToken { cursor: next };
+        let restored = ScanToken::from_bytes(&token.to_bytes()).unwrap();
+        assert_eq!(restored, token);
+        assert_eq!((restored.table_id(), restored.read_version()), (1, 100));
+        assert!(ScanToken::from_bytes(b"\xFF").is_err());
+    }
+
+    #[test]
+    fn scan_page_full() {
+        assert!(!is_page_full(100, 100, 0, 0));
+        assert!(is_page_full(10, 0, 10, 0));
+        assert!(!is_page_full(9, 0, 10, 0));
+        assert!(is_page_full(1, 1024, 10, 1024));
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sekas_api::server::v1::TableDesc;
+use sekas_runtime::time::Instant;
+
+/// The table descs cached by the client, keyed by the database id and the
+/// table name.
+///
+/// A desc is refreshed from root once it is expired, or replaced by a newer
+/// desc delivered by the watch stream of root.
+#[derive(Debug, Clone)]
+pub(crate) struct SchemaCache {
+    ttl: Duration,
+    tables: Arc<Mutex<HashMap<(u64, String), CachedTable>>>,
+}
+
+#[derive(Debug)]
+struct CachedTable {
+    desc: TableDesc,
+    fetched_at: Instant,
+}
+
+impl SchemaCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        SchemaCache { ttl, tables: Arc::default() }
+    }
+
+    /// Lookup the cached desc, `None` is returned if it is not cached or
+    /// expired. The `watched` desc replaces the cached one if it is newer.
+    pub(crate) fn lookup(
+        &self,
+        db: u64,
+        name: &str,
+        watched: Option<TableDesc>,
+    ) -> Option<TableDesc> {
+        let mut tables = self.tables.lock().unwrap();
+        let key = (db, name.to_owned());
+        let cached = tables.get_mut(&key)?;
+        if let Some(desc) = watched.filter(|desc| is_newer(desc, &cached.desc)) {
+            cached.desc = desc;
+            cached.fetched_at = Instant::now();
+        } else if cached.fetched_at.elapsed() >= self.ttl {
+            tables.remove(&key);
+            return None;
+        }
+        tables.get(&key).map(|cached| cached.desc.clone())
+    }
+
+    /// Record the desc read from root.
+    pub(crate) fn insert(&self, desc: TableDesc) {
+        let mut tables = self.tables.lock().unwrap();
+        let key = (desc.db, desc.name.clone());
+        tables.insert(key, CachedTable { desc, fetched_at: Instant::now() });
+    }
+
+    pub(crate) fn invalidate(&self, db: u64, name: &str) {
+        self.tables.lock().unwrap().remove(&(db, name.to_owned()));
+    }
+}
+
+/// The table ids are increasing, a table re-created with the same name always
+/// has a larger id.
+fn is_newer(desc: &TableDesc, cached: &TableDesc) -> bool {
+    (desc.id, desc.version) > (cached.id, cached.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_desc(id: u64, version: u64) -> TableDesc {
+        TableDesc { id, db: 1, name: "table".to_owned(), version, ..Default::default() }
+    }
+
+    #[test]
+    fn schema_cache_expired_after_ttl() {
+        let cache = SchemaCache::new(Duration::from_secs(60));
+        assert_eq!(cache.lookup(1, "table", None), None);
+        cache.insert(table_desc(1024, 0));
+        assert_eq!(cache.lookup(1, "table", None), Some(table_desc(1024, 0)));
+        cache.invalidate(1, "table");
+        assert_eq!(cache.lookup(1, "table", None), None);
+
+        let cache = SchemaCache::new(Duration::ZERO);
+        cache.insert(table_desc(1024, 0));
+        assert_eq!(cache.lookup(1, "table", None), None);
+    }
+
+    #[test]
+    fn schema_cache_replaced_by_newer_watched_desc() {
+        let cache = SchemaCache::new(Duration::from_secs(60));
+        cache.insert(table_desc(1024, 1));
+        assert_eq!(cache.lookup(1, "table", Some(table_desc(1024, 0))), Some(table_desc(1024, 1)));
+        assert_eq!(cache.lookup(1, "table", Some(table_desc(1024, 2))), Some(table_desc(1024, 2)));
+        assert_eq!(cache.lookup(1, "table", None), Some(table_desc(1024, 2)));
+
+        // The table is re-created with the same name.
+        assert_eq!(cache.lookup(1, "table", Some(table_desc(1025, 0))), Some(table_desc(1025, 0)));
+    }
+}
@@ -64,7 +64,14 @@ impl ShardClient {
         }
     }
 
-    pub async fn pull(&self, last_key: Option<Vec<u8>>) -> Result<Vec<ValueSet>> {
+    /// Pull the value sets after `last_key`, including the tombstones and the
+    /// intents. The value sets are sealed by the serving node if `seal_data`
+    /// is set, see `ShardScanRequest::seal_data`.
+    pub async fn pull(
+        &self,
+        last_key: Option<Vec<u8>>,
+        seal_data: bool,
+    ) -> Result<ShardScanResponse> {
         let req = Request::Scan(ShardScanRequest {
             shard_id: self.shard_id,
             start_version: sekas_schema::system::txn::TXN_INTENT_VERSION,
@@ -78,10 +85,54 @@ impl ShardClient {
             include_raw_data: true,
             ignore_txn_intent: true,
             allow_scan_moving_shard: true,
+            reverse: false,
+            causal_token: 0,
+            txn_id: 0,
+            max_staleness_ms: 0,
+            seal_data,
         });
         let mut client = GroupClient::lazy(self.group_id, self.client.clone());
         match client.request(&req).await? {
-            Response::Scan(ShardScanResponse { data, .. }) => Ok(data),
+            Response::Scan(resp) => Ok(resp),
+            _ => Err(Error::Internal(
+                "invalid response type, `ShardScanResponse` is required".into(),
+            )),
+        }
+    }
+
+    /// Pull the value sets visible at `read_version` from `start_key` until
+    /// the end of shard or the exclusive `end_key`, whether there are more
+    /// value sets is returned too. Unlike [`ShardClient::pull`], only the
+    /// visible version of each key is pulled and the intents are resolved, so
+    /// the chunks of a shard form a consistent snapshot.
+    pub async fn pull_snapshot(
+        &self,
+        read_version: u64,
+        start_key: &[u8],
+        end_key: Option<&[u8]>,
+    ) -> Result<(Vec<ValueSet>, bool)> {
+        let req = Request::Scan(ShardScanRequest {
+            shard_id: self.shard_id,
+            start_version: read_version,
+            prefix: None,
+            limit: 0,
+            limit_bytes: 64 * 1024, // 64KB
+            exclude_start_key: false,
+            exclude_end_key: true,
+            start_key: Some(start_key.to_vec()),
+            end_key: end_key.map(ToOwned::to_owned),
+            include_raw_data: false,
+            ignore_txn_intent: false,
+            allow_scan_moving_shard: true,
+            reverse: false,
+            causal_token: 0,
+            txn_id: 0,
+            max_staleness_ms: 0,
+            seal_data: false,
+        });
+        let mut client = GroupClient::lazy(self.group_id, self.client.clone());
+        match client.request(&req).await? {
+            Response::Scan(ShardScanResponse { data, has_more, .. }) => Ok((data, has_more)),
             _ => Err(Error::Internal(
                 "invalid response type, `ShardScanResponse` is required".into(),
             )),
@@ -12,25 +12,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::{Duration, Instant};
+use std::collections::HashSet;
+use std::time::Duration;
 
 use futures::StreamExt;
 use log::{trace, warn};
 use sekas_api::server::v1::group_request_union::Request;
 use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
+use sekas_rock::lexical::lexical_next_boundary;
 use sekas_runtime::sync::OnceCell;
+use sekas_runtime::time::Instant;
+use sekas_schema::shard;
 use sekas_schema::system::txn::TXN_MAX_VERSION;
 use tokio::sync::mpsc;
 
-use crate::group_client::GroupClient;
+use crate::call_options::call_deadline;
+use crate::group_client::{GroupClient, ReadPreference};
+use crate::memory::{delete_capacity, put_capacity, MemoryCategory, MemoryReservation};
 use crate::metrics::*;
 use crate::range::RangeStream;
 use crate::retry::RetryState;
+use crate::txn_limit::{delete_size, put_size, TxnLimits};
 use crate::{
-    record_latency, AppResult, Database, Error, RangeRequest, Result, SekasClient, TxnStateTable,
+    record_latency, AppError, AppResult, CallOptions, Consistency, Database, Error, OpResult,
+    RangeRequest, ReadOptions, Result, SekasClient, TxnOptions, TxnOverflow, TxnStateTable,
+    WriteBatchError,
 };
 
+/// The timeout of aborting the txn whose commit is timed out, it is aborted in
+/// the background.
+const ABORT_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Default, Clone)]
 struct WriteBatchRequest {}
 
@@ -46,6 +59,41 @@ pub struct WriteBatchResponse {
     ///
     /// Only for the requests with `take_prev_value`.
     pub puts: Vec<Option<Value>>,
+    /// The sequences assigned to the puts, in the same order as `puts`.
+    ///
+    /// Only for the requests built by [`WriteBuilder::append_to_prefix`].
+    pub sequences: Vec<Option<u64>>,
+    /// The values produced by the puts, in the same order as `puts`.
+    ///
+    /// Only for the requests with [`WriteBuilder::return_new_value`].
+    pub new_values: Vec<Option<Vec<u8>>>,
+    /// The stats of committing.
+    pub stats: CommitStats,
+}
+
+impl WriteBatchResponse {
+    /// The value produced by the put issued by [`Txn::put_returning`].
+    pub fn new_value(&self, index: usize) -> Option<&[u8]> {
+        self.new_values.get(index).and_then(|value| value.as_deref())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CommitPhase {
+    /// The writes are committed with the txn record.
+    #[default]
+    Committed,
+    /// The txn never writes, it is committed locally without any txn record.
+    ReadOnly,
+    /// The writes are committed by a sequence of txns, see
+    /// [`TxnOverflow::AutoChunk`].
+    Chunked { num_chunks: usize },
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommitStats {
+    /// The phase the txn is committed at.
+    pub phase: CommitPhase,
 }
 
 /// A structure to build write request.
@@ -54,10 +102,12 @@ pub struct WriteBuilder {
     key: Vec<u8>,
     /// The cas conditions.
     conditions: Vec<WriteCondition>,
-    /// The TTL of key.
-    ttl: Option<u64>,
+    /// The time to live of the value since it is committed.
+    ttl: Option<Duration>,
     /// Whether to take prev values.
     take_prev_value: bool,
+    /// Whether to return the value produced by the put.
+    return_new_value: bool,
 }
 
 /// A structure to support ACID transaction.
@@ -66,8 +116,9 @@ pub struct Txn {
     db: Database,
     /// The deadline of this txn. The expired txn will be aborted.
     ///
-    /// The default value is inherited from database, and it can be overwrite by
-    /// [`Txn::set_timeout`].
+    /// The default value is inherited from [`crate::ClientOptions::timeout`],
+    /// each op is bounded by it and its own timeout, see
+    /// [`TxnOptions::default_op_timeout`].
     ///
     /// FIXME(walter) abort expired txn.
     deadline: Option<Instant>,
@@ -77,6 +128,37 @@ pub struct Txn {
     puts: Vec<(u64, PutRequest)>,
     /// The delete request to submit.
     deletes: Vec<(u64, DeleteRequest)>,
+    /// The prefixes to check emptiness at commit time, see
+    /// [`Txn::put_if_prefix_empty`].
+    prefix_checks: Vec<(u64, Vec<u8>)>,
+    /// The keys whose writes conflict with the older running txns rather than
+    /// waiting for them, see [`Txn::transfer`].
+    wait_die_keys: HashSet<(u64, Vec<u8>)>,
+    /// The preference of replicas to serve the gets and scans.
+    read_preference: ReadPreference,
+    /// The reads must observe the writes committed at versions not greater
+    /// than the causal token, 0 means no bound.
+    causal_token: u64,
+    /// The max duration the read replicas serving the reads haven't heard
+    /// from the leader, see [`Consistency::BoundedStaleness`].
+    max_staleness: Option<Duration>,
+    /// The writes whose intents are written before committing, see
+    /// [`Txn::flush`].
+    flushed: Option<WriteBatchContext>,
+    /// The task to keep the lease of the txn once the intents are flushed.
+    lease: Option<sekas_runtime::JoinHandle<()>>,
+    /// The limits of the writes, see [`Txn::set_options`].
+    options: TxnOptions,
+    /// The number of the buffered and flushed writes.
+    staged_write_count: u64,
+    /// The encoded bytes of the buffered and flushed writes.
+    staged_write_bytes: u64,
+    /// The memory of the buffered and flushed writes reserved from the budget
+    /// of the client, it is released once the txn is dropped.
+    memory: MemoryReservation,
+    /// The error of the write discarded since the memory budget is exhausted,
+    /// the txn could not be committed, see [`Txn::put`].
+    memory_exhausted: Option<AppError>,
 }
 
 /// A structure to hold the context about single write request.
@@ -91,6 +173,8 @@ struct WriteContext {
     index: usize,
     /// Is this request has been accepted.
     done: bool,
+    /// The result of the request, if it is failed.
+    failure: Option<OpResult>,
 }
 
 /// A structure to hold the context about a write batch request.
@@ -103,6 +187,11 @@ pub struct WriteBatchContext {
     /// The number of delete requests in this batch.
     num_deletes: usize,
 
+    /// The prefixes to check emptiness before committing.
+    prefix_checks: Vec<(u64, Vec<u8>)>,
+    /// The keys whose intents are written with wait-die.
+    wait_die_keys: HashSet<(u64, Vec<u8>)>,
+
     start_version: u64,
     commit_version: u64,
 
@@ -111,14 +200,28 @@ pub struct WriteBatchContext {
 
 impl WriteBuilder {
     pub fn new(key: Vec<u8>) -> Self {
-        WriteBuilder { key, conditions: vec![], ttl: None, take_prev_value: false }
+        WriteBuilder {
+            key,
+            conditions: vec![],
+            ttl: None,
+            take_prev_value: false,
+            return_new_value: false,
+        }
     }
 
-    /// With ttl, in seconds. (WIP)
+    /// The value expires after the TTL since it is committed, the expiry is
+    /// computed by the server when the txn is committed, rather than the clock
+    /// of the client. The expired value is read as absent, eg. an add on it
+    /// starts from zero. If the table has a TTL too, the shorter one is used.
     ///
-    /// Only works for put request.
-    pub fn with_ttl(mut self, ttl: Option<u64>) -> Self {
-        self.ttl = ttl;
+    /// The TTL belongs to the value written by this put only, a later put
+    /// without TTL on the same key clears it. The expiry is not observed by
+    /// the watchers, neither when the value expires nor when it is removed.
+    ///
+    /// Only works for put request, it is rounded up to millis, and a zero TTL
+    /// means the value never expires.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
         self
     }
 
@@ -129,9 +232,10 @@ impl WriteBuilder {
             put_type: PutType::None.into(),
             key: self.key,
             value,
-            ttl: self.ttl.unwrap_or_default(),
+            ttl_ms: self.ttl_ms(),
             take_prev_value: self.take_prev_value,
             conditions: self.conditions,
+            return_new_value: self.return_new_value,
         })
     }
 
@@ -162,9 +266,10 @@ impl WriteBuilder {
             put_type: PutType::Nop.into(),
             key: self.key,
             value: vec![],
-            ttl: 0,
+            ttl_ms: 0,
             conditions: self.conditions,
             take_prev_value: false,
+            return_new_value: false,
         })
     }
 
@@ -174,23 +279,101 @@ impl WriteBuilder {
     }
 
     /// Build an add request, the value will be interpreted as i64.
+    ///
+    /// The txn fails with [`AppError::ValueTypeMismatch`] if the exists value
+    /// is not a valid i64, see [`WriteBuilder::force_add`].
     #[allow(clippy::should_implement_trait)]
     pub fn add(self, val: i64) -> AppResult<PutRequest> {
+        self.build_add(PutType::AddI64, val)
+    }
+
+    /// Build an add request without any error, the value will be interpreted as
+    /// i64.
+    pub fn ensure_add(self, val: i64) -> PutRequest {
+        self.add(val).expect("Invalid add conditions")
+    }
+
+    /// Build an add request which discards the exists value if it is not a
+    /// valid i64, so the new value is `val` in that case.
+    pub fn force_add(self, val: i64) -> AppResult<PutRequest> {
+        self.build_add(PutType::ForceAddI64, val)
+    }
+
+    /// Build a force add request without any error, see
+    /// [`WriteBuilder::force_add`].
+    pub fn ensure_force_add(self, val: i64) -> PutRequest {
+        self.force_add(val).expect("Invalid add conditions")
+    }
+
+    fn build_add(self, put_type: PutType, val: i64) -> AppResult<PutRequest> {
         self.verify_conditions()?;
         Ok(PutRequest {
-            put_type: PutType::AddI64.into(),
+            put_type: put_type.into(),
             key: self.key,
             value: val.to_be_bytes().to_vec(),
-            ttl: self.ttl.unwrap_or_default(),
+            ttl_ms: self.ttl_ms(),
             conditions: self.conditions,
             take_prev_value: self.take_prev_value,
+            return_new_value: self.return_new_value,
         })
     }
 
-    /// Build an add request without any error, the value will be interpreted as
-    /// i64.
-    pub fn ensure_add(self, val: i64) -> PutRequest {
-        self.add(val).expect("Invalid add conditions")
+    /// Build an append request, the value is appended as an entry under the
+    /// key as the prefix, at the key of the next sequence of the prefix. The
+    /// sequence is assigned by the server and returned in
+    /// [`WriteBatchResponse::sequences`], the sequences of a prefix are dense
+    /// and start from 1.
+    ///
+    /// The prefix key itself holds the last assigned sequence (8 bytes big
+    /// endian), and the entries are located at
+    /// [`sekas_api::append_entry_key`], so that a reverse scan from the end of
+    /// the prefix reads the latest entries. The whole range of the entries
+    /// must belong to a single shard, the shards are never split within it.
+    pub fn append_to_prefix(self, value: Vec<u8>) -> AppResult<PutRequest> {
+        self.verify_conditions()?;
+        Ok(PutRequest {
+            put_type: PutType::AppendSequence.into(),
+            key: self.key,
+            value,
+            ttl_ms: self.ttl_ms(),
+            conditions: self.conditions,
+            take_prev_value: self.take_prev_value,
+            return_new_value: self.return_new_value,
+        })
+    }
+
+    /// Build an append request without any error, see
+    /// [`WriteBuilder::append_to_prefix`].
+    pub fn ensure_append_to_prefix(self, value: Vec<u8>) -> PutRequest {
+        self.append_to_prefix(value).expect("Invalid append conditions")
+    }
+
+    /// Build a merge json request, the patch is applied to the exists value as
+    /// a JSON merge patch (RFC 7386) by the server.
+    ///
+    /// If the key does not exist, the patch is applied to `null`, so the patch
+    /// becomes the document (the `null` members of an object patch are
+    /// removed). If the exists value is not a valid JSON document,
+    /// [`AppError::InvalidJson`] is returned.
+    pub fn merge_json(self, patch: serde_json::Value) -> AppResult<PutRequest> {
+        self.verify_conditions()?;
+        let value = serde_json::to_vec(&patch)
+            .map_err(|err| AppError::InvalidJson(format!("encode patch: {err}")))?;
+        Ok(PutRequest {
+            put_type: PutType::MergeJson.into(),
+            key: self.key,
+            value,
+            ttl_ms: self.ttl_ms(),
+            conditions: self.conditions,
+            take_prev_value: self.take_prev_value,
+            return_new_value: self.return_new_value,
+        })
+    }
+
+    /// Build a merge json request without any error, see
+    /// [`WriteBuilder::merge_json`].
+    pub fn ensure_merge_json(self, patch: serde_json::Value) -> PutRequest {
+        self.merge_json(patch).expect("Invalid merge json conditions")
     }
 
     /// Expect that the max version of the key is less than the input value.
@@ -332,67 +515,543 @@ impl WriteBuilder {
         self
     }
 
+    /// Return the value produced by the put, eg. the sum of an add, in
+    /// [`WriteBatchResponse::new_values`].
+    ///
+    /// The value is captured when the put is applied, so it is produced by
+    /// this put exactly rather than a later concurrent one. Default is
+    /// `false`.
+    pub fn return_new_value(mut self) -> Self {
+        self.return_new_value = true;
+        self
+    }
+
     fn verify_conditions(&self) -> AppResult<()> {
         // TODO(walter) check conditions
         Ok(())
     }
+
+    /// The TTL in millis, `0` means the value never expires.
+    fn ttl_ms(&self) -> u64 {
+        self.ttl.map(|ttl| ttl.as_nanos().div_ceil(1_000_000) as u64).unwrap_or_default()
+    }
 }
 
 impl Txn {
     pub(crate) fn new(db: Database) -> Self {
         let deadline = db.client.options().timeout.map(|v| Instant::now() + v);
+        let options = TxnOptions { default_op_timeout: db.timeout, ..Default::default() };
+        let memory = db.client.memory_budget().empty(MemoryCategory::TxnWrites);
         Txn {
             db,
             deadline,
             start_version: OnceCell::new(),
             puts: Vec::default(),
             deletes: Vec::default(),
+            prefix_checks: Vec::default(),
+            wait_die_keys: HashSet::default(),
+            read_preference: ReadPreference::Leader,
+            causal_token: 0,
+            max_staleness: None,
+            flushed: None,
+            lease: None,
+            options,
+            staged_write_count: 0,
+            staged_write_bytes: 0,
+            memory,
+            memory_exhausted: None,
         }
     }
 
+    /// Set the limits of the writes of this transaction, the behavior once
+    /// they are exceeded, and the default timeout of the ops.
+    pub fn set_options(&mut self, mut options: TxnOptions) {
+        if options.default_op_timeout.is_none() {
+            options.default_op_timeout = self.db.timeout;
+        }
+        self.options = options;
+    }
+
+    /// The deadline of an op issued now, bounded by the deadline of the txn.
+    fn op_deadline(&self, opts: &CallOptions) -> Option<Instant> {
+        call_deadline(self.deadline, opts.timeout.or(self.options.default_op_timeout))
+    }
+
+    /// The number of the writes staged by this transaction.
+    #[inline]
+    pub fn staged_write_count(&self) -> u64 {
+        self.staged_write_count
+    }
+
+    /// The encoded bytes of the writes staged by this transaction, it is close
+    /// to the bytes sent when committing.
+    #[inline]
+    pub fn staged_write_bytes(&self) -> u64 {
+        self.staged_write_bytes
+    }
+
+    /// Set the preference of replicas to serve the gets and scans of this
+    /// transaction.
+    ///
+    /// The read replicas might lag behind the leader, use
+    /// [`Txn::set_causal_token`] to bound the staleness.
+    pub fn set_read_preference(&mut self, read_preference: ReadPreference) {
+        self.read_preference = read_preference;
+    }
+
+    /// Require the reads to observe the writes committed at versions not
+    /// greater than the token, eg. the version of a [`WriteBatchResponse`].
+    pub fn set_causal_token(&mut self, causal_token: u64) {
+        self.causal_token = causal_token;
+    }
+
+    /// Apply the options of the one-shot reads, it should be called before
+    /// any read is issued.
+    pub(crate) async fn set_read_options(&mut self, opts: &ReadOptions) -> AppResult<()> {
+        match opts.consistency {
+            Consistency::Linearizable => {}
+            Consistency::BoundedStaleness(bound) => {
+                let version = match self.db.client.recent_version().within(bound) {
+                    Some(version) => version,
+                    None => self.get_start_version(self.op_timeout()).await?,
+                };
+                // The read replicas serve the read only if they have applied past the
+                // version.
+                self.start_version = OnceCell::new_with(Some(version));
+                self.causal_token = version;
+                self.max_staleness = Some(bound);
+                self.read_preference = ReadPreference::PreferReadReplica;
+            }
+            Consistency::Exact(version) => {
+                if version == 0 || version >= TXN_MAX_VERSION {
+                    return Err(AppError::InvalidArgument(format!("read version {version}")));
+                }
+                self.start_version = OnceCell::new_with(Some(version));
+            }
+        }
+        Ok(())
+    }
+
+    /// The staleness bound sent to the read replicas, 0 means the hard cap of
+    /// the servers. A zero bound is rounded up to 1ms, so it is not confused
+    /// with no bound.
+    fn max_staleness_ms(&self) -> u64 {
+        self.max_staleness.map(|bound| (bound.as_millis() as u64).max(1)).unwrap_or_default()
+    }
+
+    /// The version the reads of this transaction are issued at.
+    pub(crate) async fn read_version(&self) -> AppResult<u64> {
+        Ok(self.get_read_version(self.op_timeout()).await?)
+    }
+
+    /// The timeout of an op issued now with the default options.
+    fn op_timeout(&self) -> Option<Duration> {
+        let deadline = self.op_deadline(&CallOptions::default());
+        deadline.map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
     /// Issue a delete request to transaction.
+    ///
+    /// The delete is discarded if the memory budget of the client is
+    /// exhausted, and the commit fails with
+    /// [`AppError::ClientMemoryExhausted`], see [`Txn::try_delete`].
     #[inline]
     pub fn delete(&mut self, table_id: u64, delete_req: DeleteRequest) {
+        if let Err(err) = self.try_delete(table_id, delete_req) {
+            self.memory_exhausted.get_or_insert(err);
+        }
+    }
+
+    /// Issue a delete request to transaction,
+    /// [`AppError::ClientMemoryExhausted`] is returned if the memory budget
+    /// of the client is exhausted, the txn is unchanged in that case.
+    pub fn try_delete(&mut self, table_id: u64, delete_req: DeleteRequest) -> AppResult<()> {
+        self.memory.grow(delete_capacity(&delete_req))?;
+        self.staged_write_count += 1;
+        self.staged_write_bytes += delete_size(&delete_req);
         self.deletes.push((table_id, delete_req));
+        Ok(())
     }
 
     /// Issue a put request to transaction.
+    ///
+    /// The put is discarded if the memory budget of the client is exhausted,
+    /// and the commit fails with [`AppError::ClientMemoryExhausted`], see
+    /// [`Txn::try_put`].
     #[inline]
     pub fn put(&mut self, table_id: u64, put_req: PutRequest) {
+        if let Err(err) = self.try_put(table_id, put_req) {
+            self.memory_exhausted.get_or_insert(err);
+        }
+    }
+
+    /// Issue a put request to transaction, [`AppError::ClientMemoryExhausted`]
+    /// is returned if the memory budget of the client is exhausted, the txn is
+    /// unchanged in that case.
+    pub fn try_put(&mut self, table_id: u64, put_req: PutRequest) -> AppResult<()> {
+        self.memory.grow(put_capacity(&put_req))?;
+        self.staged_write_count += 1;
+        self.staged_write_bytes += put_size(&put_req);
         self.puts.push((table_id, put_req));
+        Ok(())
+    }
+
+    /// The txn discarded a write since the memory budget is exhausted.
+    fn check_memory_exhausted(&self) -> AppResult<()> {
+        match &self.memory_exhausted {
+            Some(AppError::ClientMemoryExhausted { category, requested, used, budget }) => {
+                Err(AppError::ClientMemoryExhausted {
+                    category: category.clone(),
+                    requested: *requested,
+                    used: *used,
+                    budget: *budget,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Issue a put request whose produced value is returned, eg. the sum of an
+    /// add. The value is resolved once the transaction is committed, by
+    /// [`WriteBatchResponse::new_value`] with the returned index.
+    pub fn put_returning(&mut self, table_id: u64, mut put_req: PutRequest) -> usize {
+        let num_flushed_puts =
+            self.flushed.as_ref().map(|ctx| ctx.writes.len() - ctx.num_deletes).unwrap_or_default();
+        let index = num_flushed_puts + self.puts.len();
+        put_req.return_new_value = true;
+        self.put(table_id, put_req);
+        index
+    }
+
+    /// Put the marker key if no key exists under the prefix, eg. create a
+    /// directory emulated by the key prefix.
+    ///
+    /// The emptiness is checked at commit time by all shards covering the
+    /// prefix, the intents written by this txn are ignored. The commit fails
+    /// with [`AppError::PrefixNotEmpty`] if a key under the prefix exists, or
+    /// [`AppError::TxnConflict`] if a key under the prefix is being written by
+    /// an older txn concurrently.
+    pub fn put_if_prefix_empty(
+        &mut self,
+        table_id: u64,
+        prefix: Vec<u8>,
+        marker_key: Vec<u8>,
+        value: Vec<u8>,
+    ) {
+        self.prefix_checks.push((table_id, prefix));
+        self.put(table_id, WriteBuilder::new(marker_key).ensure_put(value));
     }
 
     /// Commit this transaction.
+    ///
+    /// The puts and deletes are applied atomically. [`AppError::WriteBatch`]
+    /// is returned if the conditions of any operation are not satisfied, it
+    /// describes the result of each operation.
+    ///
+    /// A txn never writes is committed locally, the version of the response is
+    /// the read version of the txn, or 0 if it never reads.
+    ///
+    /// [`AppError::TxnTooLarge`] is returned before any write is sent if the
+    /// writes exceed the limits, unless [`TxnOverflow::AutoChunk`] is set.
+    /// [`AppError::TableDropped`] is returned if the table of any write is
+    /// observed dropped. [`AppError::ClientMemoryExhausted`] is returned if
+    /// any write is discarded, the txn is aborted.
     pub async fn commit(self) -> AppResult<WriteBatchResponse> {
-        let start_version = self.get_start_version().await?;
-        let ctx = WriteBatchContext::new(
+        self.commit_with(CallOptions::default()).await
+    }
+
+    /// Commit this transaction with the options, see [`Txn::commit`].
+    ///
+    /// [`AppError::CommitTimedOut`] is returned if the commit is not finished
+    /// before the deadline. The txn is aborted if the outcome is known,
+    /// otherwise it might have been committed.
+    pub async fn commit_with(mut self, opts: CallOptions) -> AppResult<WriteBatchResponse> {
+        if let Err(err) = self.check_memory_exhausted() {
+            self.abort().await;
+            return Err(err);
+        }
+        let deadline = self.op_deadline(&opts);
+        if self.is_read_only() {
+            trace!("commit read only txn");
+            let version = self.start_version.get().copied().unwrap_or_default();
+            let stats = CommitStats { phase: CommitPhase::ReadOnly };
+            return Ok(WriteBatchResponse { version, stats, ..Default::default() });
+        }
+        self.check_user_tables()?;
+        if let Some(limits) = self.exceeded_limits().await {
+            let chunkable = self.flushed.is_none()
+                && self.prefix_checks.is_empty()
+                && self.wait_die_keys.is_empty();
+            if self.options.on_overflow == TxnOverflow::AutoChunk && chunkable {
+                return self.commit_chunks(limits, deadline).await;
+            }
+            return Err(limits.too_large(self.staged_write_count, self.staged_write_bytes));
+        }
+        self.commit_batch(deadline).await
+    }
+
+    /// Commit the writes in a single txn.
+    async fn commit_batch(mut self, deadline: Option<Instant>) -> AppResult<WriteBatchResponse> {
+        self.check_flushed_keys()?;
+        let timeout = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        let start_version = match self.get_start_version(timeout).await {
+            Ok(start_version) => start_version,
+            Err(Error::DeadlineExceeded(..)) => {
+                // Nothing is written without the start version.
+                return Err(AppError::CommitTimedOut { txn_id: 0, outcome_unknown: false });
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let mut ctx = match self.flushed.take() {
+            Some(mut ctx) => {
+                ctx.retry_state = RetryState::with_deadline_opt(deadline);
+                ctx
+            }
+            None => self.new_write_batch(start_version, deadline),
+        };
+        ctx.extend(self.deletes, self.puts);
+        ctx.prefix_checks = self.prefix_checks;
+        ctx.wait_die_keys.extend(self.wait_die_keys);
+        // The lease task is stopped once the txn is committed or aborted.
+        ctx.commit().await
+    }
+
+    /// Write the intents of the buffered puts and deletes before committing,
+    /// eg. to bound the memory of a large txn.
+    ///
+    /// The flushed values are visible to the gets and scans of this txn, but
+    /// not to other txns until it is committed. The flushed keys can't be
+    /// written again in this txn. If the flush fails, eg. the conditions of
+    /// any operation are not satisfied, the txn is aborted. The intents of a
+    /// txn dropped without committing are resolved once its lease expires.
+    pub async fn flush(&mut self) -> AppResult<()> {
+        self.check_memory_exhausted()?;
+        if self.deletes.is_empty() && self.puts.is_empty() {
+            return Ok(());
+        }
+        self.check_user_tables()?;
+        if let Some(limits) = self.exceeded_limits().await {
+            return Err(limits.too_large(self.staged_write_count, self.staged_write_bytes));
+        }
+        self.check_flushed_keys()?;
+        let deadline = self.op_deadline(&CallOptions::default());
+        let start_version = self.get_start_version(self.op_timeout()).await?;
+        let mut ctx = match self.flushed.take() {
+            Some(ctx) => ctx,
+            None => {
+                let mut ctx = self.new_write_batch(start_version, deadline);
+                ctx.start_txn().await?;
+                let timeout = ctx.retry_state.timeout();
+                let txn_table = TxnStateTable::new(self.db.client.clone(), timeout);
+                self.lease = Some(sekas_runtime::spawn(async move {
+                    WriteBatchContext::lease_txn(txn_table, start_version).await;
+                }));
+                ctx
+            }
+        };
+        ctx.extend(std::mem::take(&mut self.deletes), std::mem::take(&mut self.puts));
+        ctx.wait_die_keys.extend(std::mem::take(&mut self.wait_die_keys));
+        let err = match ctx.prepare_intents().await {
+            Ok(None) => {
+                self.flushed = Some(ctx);
+                return Ok(());
+            }
+            Ok(Some(write_batch_err)) => AppError::WriteBatch(write_batch_err),
+            Err(err) => err.into(),
+        };
+        self.lease = None;
+        ctx.abort().await;
+        Err(err)
+    }
+
+    /// Abort this transaction, the flushed intents are cleared. A txn never
+    /// writes is aborted locally.
+    pub async fn abort(mut self) {
+        self.lease = None;
+        if let Some(ctx) = self.flushed.take() {
+            ctx.abort().await;
+        }
+    }
+
+    /// The limits if the staged writes exceed them.
+    async fn exceeded_limits(&self) -> Option<TxnLimits> {
+        let probe = match (self.deletes.first(), self.puts.first()) {
+            (Some((table_id, del)), _) => (*table_id, del.key.as_slice()),
+            (None, Some((table_id, put))) => (*table_id, put.key.as_slice()),
+            (None, None) => return None,
+        };
+        let limits = self.options.resolve(&self.db.client, probe).await;
+        limits.exceeded(self.staged_write_count, self.staged_write_bytes).then_some(limits)
+    }
+
+    /// Commit the writes as a sequence of txns within the limits, see
+    /// [`TxnOverflow::AutoChunk`].
+    async fn commit_chunks(
+        self,
+        limits: TxnLimits,
+        deadline: Option<Instant>,
+    ) -> AppResult<WriteBatchResponse> {
+        let sizes = self
+            .deletes
+            .iter()
+            .map(|(_, del)| delete_size(del))
+            .chain(self.puts.iter().map(|(_, put)| put_size(put)))
+            .collect::<Vec<_>>();
+        let chunks = limits.split_chunks(&sizes)?;
+        let num_chunks = chunks.len();
+        let num_deletes = self.deletes.len();
+        let mut deletes = self.deletes.into_iter();
+        let mut puts = self.puts.into_iter();
+        let mut resp = WriteBatchResponse::default();
+        for (failed_chunk, chunk) in chunks.into_iter().enumerate() {
+            let committed_writes = chunk.start;
+            let mut txn = Txn::new(self.db.clone());
+            for index in chunk {
+                if index < num_deletes {
+                    txn.deletes.extend(deletes.next());
+                } else {
+                    txn.puts.extend(puts.next());
+                }
+            }
+            trace!("commit txn chunk {failed_chunk}/{num_chunks}");
+            match txn.commit_batch(deadline).await {
+                Ok(chunk_resp) => {
+                    resp.version = chunk_resp.version;
+                    resp.deletes.extend(chunk_resp.deletes);
+                    resp.puts.extend(chunk_resp.puts);
+                    resp.sequences.extend(chunk_resp.sequences);
+                    resp.new_values.extend(chunk_resp.new_values);
+                }
+                Err(err) => {
+                    return Err(AppError::TxnChunkFailed {
+                        failed_chunk,
+                        committed_writes,
+                        committed_version: resp.version,
+                        source: Box::new(err),
+                    });
+                }
+            }
+        }
+        resp.stats = CommitStats { phase: CommitPhase::Chunked { num_chunks } };
+        Ok(resp)
+    }
+
+    /// The txn has no buffered or flushed writes, so no txn record is created.
+    fn is_read_only(&self) -> bool {
+        self.puts.is_empty()
+            && self.deletes.is_empty()
+            && self.prefix_checks.is_empty()
+            && self.flushed.is_none()
+    }
+
+    fn new_write_batch(&self, start_version: u64, deadline: Option<Instant>) -> WriteBatchContext {
+        WriteBatchContext::new(
             start_version,
-            self.deletes,
-            self.puts,
+            Vec::default(),
+            Vec::default(),
             self.db.client.clone(),
-            self.deadline,
-        );
-        Ok(ctx.commit().await?)
+            deadline,
+        )
+    }
+
+    /// The system tables are only written by the servers, reject the writes
+    /// before any intent is written.
+    fn check_user_tables(&self) -> AppResult<()> {
+        let table_ids = self.deletes.iter().map(|(id, _)| *id);
+        let table_ids = table_ids.chain(self.puts.iter().map(|(id, _)| *id));
+        table_ids
+            .map(|id| {
+                check_user_table(id)?;
+                self.db.check_dropped(Some(id))
+            })
+            .collect()
+    }
+
+    /// The intent of a key is written only once in a txn, so the buffered
+    /// writes must not overwrite the flushed keys.
+    fn check_flushed_keys(&self) -> AppResult<()> {
+        let Some(ctx) = self.flushed.as_ref() else { return Ok(()) };
+        let flushed = ctx.writes.iter().map(|w| (w.table_id, w.user_key())).collect::<HashSet<_>>();
+        let buffered = self
+            .deletes
+            .iter()
+            .map(|(table_id, del)| (*table_id, del.key.as_slice()))
+            .chain(self.puts.iter().map(|(table_id, put)| (*table_id, put.key.as_slice())));
+        for (table_id, key) in buffered {
+            if flushed.contains(&(table_id, key)) {
+                return Err(AppError::InvalidArgument(format!(
+                    "the key {key:?} of table {table_id} is already flushed"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The id of this txn if any intents are flushed, the reads of the txn
+    /// observe its own intents.
+    fn flushed_txn_id(&self) -> u64 {
+        self.flushed.as_ref().map(|ctx| ctx.start_version).unwrap_or_default()
     }
 
     /// Get key value with in an transaction.
     ///
     /// NOTE: This request will be sent to node servers, and the put/delete
-    /// requests already buffered in this TXN will be ignored.
+    /// requests already buffered in this TXN will be ignored, except the
+    /// flushed ones, see [`Txn::flush`].
     pub async fn get(&self, table_id: u64, key: Vec<u8>) -> AppResult<Option<Vec<u8>>> {
-        let value = self.get_raw_value(table_id, key).await?;
+        self.get_with(table_id, key, &CallOptions::default()).await
+    }
+
+    /// Get key value with in an transaction, with the options, see
+    /// [`Txn::get`].
+    pub async fn get_with(
+        &self,
+        table_id: u64,
+        key: Vec<u8>,
+        opts: &CallOptions,
+    ) -> AppResult<Option<Vec<u8>>> {
+        let value = self.read_raw_value(table_id, key, self.op_deadline(opts)).await?;
         Ok(value.and_then(|v| v.content))
     }
 
+    /// Get key value with in an transaction, the value is decoded as a JSON
+    /// document.
+    ///
+    /// [`AppError::InvalidJson`] is returned if the value is not a valid JSON
+    /// document.
+    ///
+    /// NOTE: This request will be sent to node servers, and the put/delete
+    /// requests already buffered in this TXN will be ignored.
+    pub async fn get_json(
+        &self,
+        table_id: u64,
+        key: Vec<u8>,
+    ) -> AppResult<Option<serde_json::Value>> {
+        let Some(content) = self.get(table_id, key).await? else { return Ok(None) };
+        let value = serde_json::from_slice(&content)
+            .map_err(|err| AppError::InvalidJson(format!("decode value: {err}")))?;
+        Ok(Some(value))
+    }
+
     /// Get a raw key value from this transaction.
     ///
     /// NOTE: This request will be sent to node servers, and the put/delete
     /// requests already buffered in this TXN will be ignored.
     pub async fn get_raw_value(&self, table_id: u64, key: Vec<u8>) -> AppResult<Option<Value>> {
+        self.read_raw_value(table_id, key, self.op_deadline(&CallOptions::default())).await
+    }
+
+    async fn read_raw_value(
+        &self,
+        table_id: u64,
+        key: Vec<u8>,
+        deadline: Option<Instant>,
+    ) -> AppResult<Option<Value>> {
+        self.db.check_dropped(Some(table_id))?;
         CLIENT_DATABASE_BYTES_TOTAL.rx.inc_by(key.len() as u64);
         CLIENT_DATABASE_REQUEST_TOTAL.get.inc();
         record_latency!(&CLIENT_DATABASE_REQUEST_DURATION_SECONDS.get);
-        let mut retry_state = RetryState::with_deadline_opt(self.deadline);
+        let mut retry_state = RetryState::with_deadline_opt(deadline);
 
         loop {
             match self.get_inner(table_id, &key, retry_state.timeout()).await {
@@ -418,13 +1077,16 @@ impl Txn {
         user_key: &[u8],
         timeout: Option<Duration>,
     ) -> crate::Result<Option<Value>> {
-        let start_version = self.get_read_version().await?;
+        let start_version = self.get_read_version(timeout).await?;
         let router = self.db.client.router();
         let (group, shard) = router.find_shard(table_id, user_key)?;
         let req = Request::Get(ShardGetRequest {
             shard_id: shard.id,
             start_version,
             user_key: user_key.to_owned(),
+            causal_token: self.causal_token,
+            txn_id: self.flushed_txn_id(),
+            max_staleness_ms: self.max_staleness_ms(),
         });
 
         trace!(
@@ -436,7 +1098,12 @@ impl Txn {
 
         let mut group_client = GroupClient::new(group, self.db.client.clone());
         group_client.set_timeout_opt(timeout);
-        match group_client.request(&req).await? {
+        group_client.set_read_preference(self.read_preference);
+        let resp = group_client
+            .request(&req)
+            .await
+            .map_err(|err| err.with_shard(Some(table_id), shard.id))?;
+        match resp {
             Response::Get(ShardGetResponse { value }) => Ok(value),
             _ => Err(crate::Error::Internal("invalid response type, Get is required".into())),
         }
@@ -470,8 +1137,11 @@ impl Txn {
         let group_state = router.find_group_by_shard(request.shard_id)?;
         let mut group_client = GroupClient::new(group_state, self.db.client.clone());
         group_client.set_timeout_opt(timeout);
+        let shard_id = request.shard_id;
         let request = Request::Write(request.clone());
-        match group_client.request(&request).await? {
+        let resp =
+            group_client.request(&request).await.map_err(|err| err.with_shard(None, shard_id))?;
+        match resp {
             Response::Write(resp) => Ok(resp),
             _ => Err(crate::Error::Internal("invalid response type, Write is required".into())),
         }
@@ -480,9 +1150,12 @@ impl Txn {
     /// To scan a shard.
     ///
     /// NOTE: This request will be sent to node servers, and the put/delete
-    /// requests already buffered in this TXN will be ignored.
+    /// requests already buffered in this TXN will be ignored, except the
+    /// flushed ones, see [`Txn::flush`].
     pub async fn scan(&self, mut request: ShardScanRequest) -> AppResult<ShardScanResponse> {
-        let mut retry_state = RetryState::with_deadline_opt(self.deadline);
+        self.db.check_dropped(None)?;
+        let mut retry_state =
+            RetryState::with_deadline_opt(self.op_deadline(&CallOptions::default()));
         loop {
             match self.scan_inner(&mut request, retry_state.timeout()).await {
                 Ok(value) => {
@@ -500,13 +1173,20 @@ impl Txn {
         request: &mut ShardScanRequest,
         timeout: Option<Duration>,
     ) -> crate::Result<ShardScanResponse> {
-        request.start_version = self.get_read_version().await?;
+        request.start_version = self.get_read_version(timeout).await?;
+        request.causal_token = self.causal_token;
+        request.max_staleness_ms = self.max_staleness_ms();
+        request.txn_id = self.flushed_txn_id();
         let router = self.db.client.router();
         let group_state = router.find_group_by_shard(request.shard_id)?;
+        let shard_id = request.shard_id;
         let request = Request::Scan(request.clone());
         let mut group_client = GroupClient::new(group_state, self.db.client.clone());
         group_client.set_timeout_opt(timeout);
-        match group_client.request(&request).await? {
+        group_client.set_read_preference(self.read_preference);
+        let resp =
+            group_client.request(&request).await.map_err(|err| err.with_shard(None, shard_id))?;
+        match resp {
             Response::Scan(resp) => Ok(resp),
             _ => Err(crate::Error::Internal("invalid response type, Scan is required".into())),
         }
@@ -517,8 +1197,11 @@ impl Txn {
     /// NOTE: This request will be sent to node servers, and the put/delete
     /// requests already buffered in this TXN will be ignored.
     pub async fn range(&self, mut request: RangeRequest) -> AppResult<RangeStream> {
-        if request.version.is_none() {
-            request.version = Some(self.get_read_version().await?);
+        self.db.check_dropped(Some(request.table_id))?;
+        if request.version.is_none() && request.options.ignore_txn_intent {
+            request.version = Some(TXN_MAX_VERSION);
+        } else if request.version.is_none() {
+            request.version = Some(self.get_read_version(self.op_timeout()).await?);
         }
         Ok(RangeStream::init(self.db.client.clone(), request, self.deadline))
     }
@@ -543,41 +1226,46 @@ impl Txn {
         key: &[u8],
         version: u64,
     ) -> AppResult<WatchKeyStream> {
+        self.db.check_dropped(Some(table_id))?;
         // TODO(walter) watch a key might have different deadline.
         let mut retry_state = RetryState::with_deadline_opt(self.deadline);
         let (sender, receiver) = mpsc::unbounded_channel();
         let db = self.db.clone();
         let user_key = key.to_vec();
+        let guard = AliveTaskGuard::new(&CLIENT_WATCH_STREAM_TASKS);
         let _handler = sekas_runtime::spawn(async move {
+            let _guard = guard;
             let mut ctx = WatchContext { table_id, version, user_key, sender };
             while let Err(err) = watch_key(&mut ctx, &db, retry_state.timeout()).await {
                 if let Err(err) = retry_state.retry(err).await {
-                    if ctx.sender.send(Err(err.into())).is_err() {
-                        break;
-                    }
+                    // The watch is terminated, eg. rejected by the resource limits of server.
+                    let _ = ctx.sender.send(Err(err.into()));
+                    break;
                 }
             }
         });
 
-        Ok(WatchKeyStream { _handler, receiver })
+        Ok(WatchKeyStream { _handler, receiver, terminated: false })
     }
 
-    async fn get_start_version(&self) -> crate::Result<u64> {
+    async fn get_start_version(&self, timeout: Option<Duration>) -> crate::Result<u64> {
         trace!("txn get start version");
-        let timeout = self.deadline.map(|d| d.saturating_duration_since(Instant::now()));
         self.start_version
             .get_or_try_init(|| async {
-                self.db.client.root_client().alloc_txn_id(1, timeout).await
+                let requested_at = Instant::now();
+                let version = self.db.client.root_client().alloc_txn_id(1, timeout).await?;
+                self.db.client.recent_version().observe(version, requested_at);
+                Ok(version)
             })
             .await
             .copied()
     }
 
-    async fn get_read_version(&self) -> crate::Result<u64> {
+    async fn get_read_version(&self, timeout: Option<Duration>) -> crate::Result<u64> {
         if self.db.read_without_version {
             Ok(TXN_MAX_VERSION)
         } else {
-            self.get_start_version().await
+            self.get_start_version(timeout).await
         }
     }
 }
@@ -590,6 +1278,7 @@ impl WriteContext {
             response: None,
             index,
             done: false,
+            failure: None,
         }
     }
 
@@ -600,6 +1289,7 @@ impl WriteContext {
             response: None,
             index,
             done: false,
+            failure: None,
         }
     }
 
@@ -619,29 +1309,49 @@ impl WriteBatchContext {
         client: SekasClient,
         deadline: Option<Instant>,
     ) -> Self {
-        let num_deletes = deletes.len();
-        let num_puts = puts.len();
-        let num_doing_writes = num_deletes + num_puts;
-        let mut writes = Vec::with_capacity(num_doing_writes);
-        writes.extend(deletes.into_iter().enumerate().map(WriteContext::with_delete));
-        writes.extend(puts.into_iter().enumerate().map(WriteContext::with_put));
-
-        WriteBatchContext {
+        let mut ctx = WriteBatchContext {
             client,
-            writes,
-            num_deletes,
-            num_doing_writes,
+            writes: Vec::with_capacity(deletes.len() + puts.len()),
+            num_deletes: 0,
+            num_doing_writes: 0,
+            prefix_checks: Vec::default(),
+            wait_die_keys: HashSet::default(),
             start_version,
             commit_version: 0,
             retry_state: RetryState::with_deadline_opt(deadline),
-        }
+        };
+        ctx.extend(deletes, puts);
+        ctx
     }
 
-    pub async fn commit(mut self) -> Result<WriteBatchResponse> {
+    /// Append the writes to this batch, the indexes of them follow the writes
+    /// of the same type in this batch.
+    fn extend(&mut self, deletes: Vec<(u64, DeleteRequest)>, puts: Vec<(u64, PutRequest)>) {
+        let num_deletes = self.num_deletes;
+        let num_puts = self.writes.len() - num_deletes;
+        self.num_deletes += deletes.len();
+        self.num_doing_writes += deletes.len() + puts.len();
+        self.writes.extend(
+            deletes
+                .into_iter()
+                .enumerate()
+                .map(|(index, delete)| WriteContext::with_delete((num_deletes + index, delete))),
+        );
+        self.writes.extend(
+            puts.into_iter()
+                .enumerate()
+                .map(|(index, put)| WriteContext::with_put((num_puts + index, put))),
+        );
+    }
+
+    pub async fn commit(mut self) -> AppResult<WriteBatchResponse> {
         // TODO: check parameters
 
         // TODO: handle errors to abort txn.
-        self.start_txn().await?;
+        if let Err(err) = self.start_txn().await {
+            // No intent is written, the txn is never committed.
+            return Err(commit_error(self.start_version, err.into(), false));
+        }
 
         let start_version = self.start_version;
         let txn_table = TxnStateTable::new(self.client.clone(), self.retry_state.timeout());
@@ -667,13 +1377,24 @@ impl WriteBatchContext {
             if let Err(err) = txn_table.heartbeat(start_version).await {
                 warn!("txn {start_version} lease heartbeat: {err}");
             }
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            sekas_runtime::time::sleep(Duration::from_millis(100)).await;
         }
     }
 
-    async fn commit_inner(mut self) -> Result<WriteBatchResponse> {
-        self.prepare_intents().await?;
-        self.commit_version = self.alloc_txn_version().await?;
+    async fn commit_inner(mut self) -> AppResult<WriteBatchResponse> {
+        let err = match self.prepare_intents().await {
+            Ok(None) => None,
+            Ok(Some(write_batch_err)) => Some(AppError::WriteBatch(write_batch_err)),
+            Err(err) => Some(err.into()),
+        };
+        if let Some(err) = err {
+            return Err(self.abort_with(err).await);
+        }
+
+        self.commit_version = match self.alloc_txn_version().await {
+            Ok(commit_version) => commit_version,
+            Err(err) => return Err(self.abort_with(err.into()).await),
+        };
 
         trace!(
             "commit txn, alloc txn version: {}, start version: {}",
@@ -681,31 +1402,54 @@ impl WriteBatchContext {
             self.start_version
         );
 
-        self.commit_txn().await?;
+        // The prefixes are checked after the commit version is allocated, the txns
+        // writing keys under the prefixes later must commit with larger versions.
+        if let Err(err) = self.check_prefixes().await {
+            return Err(self.abort_with(err).await);
+        }
+
+        if let Err(err) = self.commit_txn().await {
+            // The txn record might be committed by the timed out request.
+            return Err(commit_error(self.start_version, err.into(), true));
+        }
         let version = self.commit_version;
 
         let mut deletes = Vec::with_capacity(self.num_deletes);
         let mut puts = Vec::with_capacity(self.writes.len() - self.num_deletes);
+        let mut sequences = Vec::with_capacity(self.writes.len() - self.num_deletes);
+        let mut new_values = Vec::with_capacity(self.writes.len() - self.num_deletes);
         for write in &mut self.writes {
+            let response = write.response.take().unwrap_or_default();
             match &write.request {
                 WriteRequest::Delete(_) => {
-                    deletes.push(write.response.take().and_then(|v| v.prev_value));
+                    deletes.push(response.prev_value);
                 }
                 WriteRequest::Put(_) => {
-                    puts.push(write.response.take().and_then(|v| v.prev_value));
+                    puts.push(response.prev_value);
+                    sequences.push(response.sequence);
+                    new_values.push(response.new_value);
                 }
             }
         }
 
         self.commit_intents();
-        Ok(WriteBatchResponse { version, deletes, puts })
+        Ok(WriteBatchResponse {
+            version,
+            deletes,
+            puts,
+            sequences,
+            new_values,
+            stats: CommitStats::default(),
+        })
     }
 
     async fn alloc_txn_version(&mut self) -> Result<u64> {
         let root_client = self.client.root_client();
         loop {
+            let requested_at = Instant::now();
             match root_client.alloc_txn_id(1, self.retry_state.timeout()).await {
                 Ok(value) => {
+                    self.client.recent_version().observe(value, requested_at);
                     return Ok(value);
                 }
                 Err(err) => {
@@ -715,6 +1459,61 @@ impl WriteBatchContext {
         }
     }
 
+    async fn check_prefixes(&mut self) -> AppResult<()> {
+        for (table_id, prefix) in std::mem::take(&mut self.prefix_checks) {
+            if let Some(key) = self.check_prefix_empty(table_id, &prefix).await? {
+                trace!("txn {} check prefix {prefix:?}, key {key:?} exists", self.start_version);
+                return Err(AppError::PrefixNotEmpty { prefix, key });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the prefix in all shards covering it, returns the first key exists
+    /// under the prefix.
+    async fn check_prefix_empty(
+        &mut self,
+        table_id: u64,
+        prefix: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        let end_key = lexical_next_boundary(prefix);
+        let mut cursor = prefix.to_vec();
+        loop {
+            let router = self.client.router();
+            let (group_state, shard_desc) = router.find_shard(table_id, &cursor)?;
+            let mut client = GroupClient::new(group_state, self.client.clone());
+            if let Some(duration) = self.retry_state.timeout() {
+                client.set_timeout(duration);
+            }
+            let req = Request::CheckPrefixEmpty(CheckPrefixEmptyRequest {
+                shard_id: shard_desc.id,
+                start_version: self.start_version,
+                prefix: prefix.to_vec(),
+            });
+            match client.request(&req).await {
+                Ok(Response::CheckPrefixEmpty(CheckPrefixEmptyResponse { exists_key: None })) => {}
+                Ok(Response::CheckPrefixEmpty(CheckPrefixEmptyResponse { exists_key })) => {
+                    return Ok(exists_key);
+                }
+                Ok(_) => {
+                    return Err(Error::Internal(
+                        "invalid response type, CheckPrefixEmpty is required".into(),
+                    ));
+                }
+                Err(err) => {
+                    self.retry_state.retry(err).await?;
+                    continue;
+                }
+            }
+
+            let shard_end = shard::end_key(&shard_desc);
+            if shard_end.is_empty() || (!end_key.is_empty() && end_key <= shard_end) {
+                return Ok(None);
+            }
+            cursor = shard_end;
+        }
+    }
+
     async fn start_txn(&mut self) -> Result<()> {
         trace!("start txn, version={}", self.start_version);
         TxnStateTable::new(self.client.clone(), self.retry_state.timeout())
@@ -722,13 +1521,15 @@ impl WriteBatchContext {
             .await
     }
 
-    async fn prepare_intents(&mut self) -> Result<()> {
-        loop {
-            if !self.prepare_intents_inner().await? {
-                return Ok(());
-            }
+    /// Write intents of all requests, the [`WriteBatchError`] is returned if
+    /// the conditions of any request are not satisfied. All requests are
+    /// evaluated before reporting the error, so the result of each request is
+    /// reported by the servers.
+    async fn prepare_intents(&mut self) -> Result<Option<WriteBatchError>> {
+        while self.prepare_intents_inner().await? {
             self.retry_state.force_retry().await?;
         }
+        Ok(self.write_batch_error())
     }
 
     async fn prepare_intents_inner(&mut self) -> Result<bool> {
@@ -736,7 +1537,7 @@ impl WriteBatchContext {
         let router = self.client.router();
         let mut handles = Vec::with_capacity(self.writes.len());
         for (index, write) in self.writes.iter().enumerate() {
-            if write.done {
+            if write.done || write.failure.is_some() {
                 continue;
             }
             let (group_state, shard_desc) = router.find_shard(write.table_id, write.user_key())?;
@@ -748,44 +1549,64 @@ impl WriteBatchContext {
             );
 
             let mut client = GroupClient::new(group_state, self.client.clone());
+            let wait_die =
+                self.wait_die_keys.contains(&(write.table_id, write.user_key().to_vec()));
             let req = Request::WriteIntent(WriteIntentRequest {
                 start_version: self.start_version,
                 shard_id: shard_desc.id,
                 write: Some(write.request.clone()),
+                wait_die,
+                report_op_status: true,
             });
             if let Some(duration) = self.retry_state.timeout() {
                 client.set_timeout(duration);
             }
             let handle = tokio::spawn(async move {
-                match client.request(&req).await? {
-                    Response::WriteIntent(WriteIntentResponse { write: Some(resp) }) => {
-                        Ok((resp, index))
+                let resp = match client.request(&req).await {
+                    Ok(Response::WriteIntent(WriteIntentResponse { write: Some(resp) })) => {
+                        Ok(resp)
                     }
-                    _ => Err(Error::Internal(
-                        "invalid response type, Get is required".to_string().into(),
+                    Ok(_) => Err(Error::Internal(
+                        "invalid response type, WriteIntent is required".to_string().into(),
                     )),
-                }
+                    Err(err) => Err(err),
+                };
+                (index, resp)
             });
             handles.push(handle);
         }
 
+        let mut first_err = None;
         for handle in handles {
-            match handle.await? {
-                Ok((resp, index)) => {
-                    self.num_doing_writes =
-                        self.num_doing_writes.checked_sub(1).expect("out of range");
-                    let write = &mut self.writes[index];
+            let (index, resp) = handle.await?;
+            let write = &mut self.writes[index];
+            match resp {
+                Ok(WriteResponse {
+                    status: Some(WriteStatus { cond_index: Some(cond_index), prev_value }),
+                    ..
+                }) => {
+                    write.failure = Some(OpResult::CasFailed { cond_index, prev_value });
+                }
+                Ok(resp) => {
                     write.done = true;
                     write.response = Some(resp);
                 }
                 Err(err) => {
-                    // FIXME(walter) UPDATE THE CAS FAILED INDEX.
                     trace!("txn {} write intent: {err:?}", self.start_version);
-                    if !self.retry_state.is_retryable(&err) {
-                        return Err(err);
+                    if self.retry_state.is_retryable(&err) {
+                        continue;
                     }
+                    write.failure = Some(OpResult::Rejected(err.to_string()));
+                    first_err.get_or_insert(err);
                 }
             }
+            self.num_doing_writes = self.num_doing_writes.checked_sub(1).expect("out of range");
+        }
+        // The cas failure takes precedence, since it describes all operations.
+        if let Some(err) = first_err {
+            if !self.writes.iter().any(|w| matches!(w.failure, Some(OpResult::CasFailed { .. }))) {
+                return Err(err);
+            }
         }
         trace!("txn {} write intent left {} writes", self.start_version, self.num_doing_writes);
         Ok(self.num_doing_writes > 0)
@@ -802,13 +1623,64 @@ impl WriteBatchContext {
             .await
     }
 
-    #[allow(unused)]
     async fn abort_txn(&mut self) -> Result<()> {
         TxnStateTable::new(self.client.clone(), self.retry_state.timeout())
             .abort_txn(self.start_version)
             .await
     }
 
+    /// Build the [`WriteBatchError`] if the conditions of any request are not
+    /// satisfied.
+    fn write_batch_error(&self) -> Option<WriteBatchError> {
+        let failed_index = self
+            .writes
+            .iter()
+            .position(|w| matches!(w.failure, Some(OpResult::CasFailed { .. })))?;
+        let per_op =
+            self.writes.iter().map(|w| w.failure.clone().unwrap_or(OpResult::Applied)).collect();
+        Some(WriteBatchError { failed_index, per_op })
+    }
+
+    /// Abort the txn before it is committed, the error is returned. If the
+    /// deadline is exceeded, the txn is aborted in the background instead, so
+    /// [`AppError::CommitTimedOut`] is returned in time.
+    async fn abort_with(mut self, err: AppError) -> AppError {
+        if !matches!(err, AppError::DeadlineExceeded(..)) {
+            self.abort().await;
+            return err;
+        }
+        let txn_id = self.start_version;
+        self.retry_state = RetryState::new(ABORT_TIMEOUT);
+        tokio::spawn(async move {
+            self.abort().await;
+        });
+        AppError::CommitTimedOut { txn_id, outcome_unknown: false }
+    }
+
+    /// Abort the txn and clear the written intents, so that none of the
+    /// requests takes effect.
+    async fn abort(mut self) {
+        if let Err(err) = self.abort_txn().await {
+            // The intents will be resolved by the readers once the txn lease is expired.
+            warn!("txn {} abort: {}", self.start_version, err);
+            return;
+        }
+
+        tokio::spawn(async move {
+            trace!("clear txn intents, start version: {}", self.start_version);
+            for i in [1, 3, 5] {
+                match self.clear_intents().await {
+                    Ok(false) => break,
+                    Ok(true) => sekas_runtime::time::sleep(Duration::from_millis(i)).await,
+                    Err(err) => {
+                        warn!("txn {} clear intents: {}", self.start_version, err);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
     fn commit_intents(mut self) {
         tokio::spawn(async move {
             trace!(
@@ -824,7 +1696,7 @@ impl WriteBatchContext {
             for i in [1, 3, 5] {
                 match self.commit_intents_inner().await {
                     Ok(false) => break,
-                    Ok(true) => tokio::time::sleep(Duration::from_millis(i)).await,
+                    Ok(true) => sekas_runtime::time::sleep(Duration::from_millis(i)).await,
                     Err(err) => {
                         warn!("txn {} commit intents: {}", self.start_version, err);
                         break;
@@ -859,7 +1731,8 @@ impl WriteBatchContext {
                     Ok(other) => Err(Error::Internal(
                         format!("invalid response {other:?}, `CommitIntent` is required").into(),
                     )),
-                    Err(err) => Err(Error::Internal(format!("commit intent: {err:?}").into())),
+                    // Keep the error as it is, so the retryable ones are retried.
+                    Err(err) => Err(err),
                 }
             });
             handles.push(handle);
@@ -882,15 +1755,82 @@ impl WriteBatchContext {
         Ok(self.num_doing_writes > 0)
     }
 
-    #[allow(unused)]
-    async fn clear_intents(&mut self) -> Result<()> {
-        todo!()
+    /// Clear the written intents, returns whether there are intents left.
+    async fn clear_intents(&mut self) -> Result<bool> {
+        let router = self.client.router();
+
+        let mut handles = Vec::with_capacity(self.writes.len());
+        for (index, write) in self.writes.iter().enumerate() {
+            if !write.done {
+                continue;
+            }
+
+            let user_key = write.user_key();
+            let (group_state, shard_desc) = router.find_shard(write.table_id, user_key)?;
+            let req = ClearIntentRequest {
+                shard_id: shard_desc.id,
+                start_version: self.start_version,
+                user_key: user_key.to_vec(),
+            };
+            let mut client = GroupClient::new(group_state, self.client.clone());
+            let handle = tokio::spawn(async move {
+                match client.request(&Request::ClearIntent(req)).await {
+                    Ok(Response::ClearIntent(_)) => Ok(index),
+                    Ok(other) => Err(Error::Internal(
+                        format!("invalid response {other:?}, `ClearIntent` is required").into(),
+                    )),
+                    // Keep the error as it is, so the retryable ones are retried.
+                    Err(err) => Err(err),
+                }
+            });
+            handles.push(handle);
+        }
+        let mut num_left_intents = 0;
+        for handle in handles {
+            match handle.await? {
+                Ok(index) => {
+                    self.writes[index].done = false;
+                }
+                Err(err) => {
+                    if !self.retry_state.is_retryable(&err) {
+                        return Err(err);
+                    }
+                    num_left_intents += 1;
+                }
+            }
+        }
+        trace!("txn {} clear intent left {} writes", self.start_version, num_left_intents);
+        Ok(num_left_intents > 0)
+    }
+}
+
+/// Reject the writes to the system tables, which are only written by the
+/// servers of the cluster.
+pub(crate) fn check_user_table(table_id: u64) -> AppResult<()> {
+    if sekas_schema::system::table::is_reserved_table(table_id) {
+        return Err(AppError::PermissionDenied(format!(
+            "table {table_id} is a system table, which is only written by the servers"
+        )));
     }
+    Ok(())
 }
 
+/// Report the deadline exceeded of committing as [`AppError::CommitTimedOut`],
+/// along with whether the txn might be committed.
+fn commit_error(txn_id: u64, err: AppError, outcome_unknown: bool) -> AppError {
+    match err {
+        AppError::DeadlineExceeded(..) => AppError::CommitTimedOut { txn_id, outcome_unknown },
+        err => err,
+    }
+}
+
+/// The stream of the updated values of a key. The watching task is aborted
+/// once the stream is dropped, and the stream is terminated after an error is
+/// yielded.
 pub struct WatchKeyStream {
     _handler: sekas_runtime::JoinHandle<()>,
     receiver: mpsc::UnboundedReceiver<AppResult<Value>>,
+    terminated: bool,
 }
 
 impl futures::Stream for WatchKeyStream {
@@ -900,7 +1840,30 @@ impl futures::Stream for WatchKeyStream {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        self.get_mut().receiver.poll_recv(cx)
+        let this = self.get_mut();
+        if this.terminated {
+            return std::task::Poll::Ready(None);
+        }
+        let item = std::task::ready!(this.receiver.poll_recv(cx));
+        if !matches!(item, Some(Ok(_))) {
+            this.terminated = true;
+            this.receiver.close();
+        }
+        std::task::Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.terminated {
+            (0, Some(0))
+        } else {
+            (0, None)
+        }
+    }
+}
+
+impl futures::stream::FusedStream for WatchKeyStream {
+    fn is_terminated(&self) -> bool {
+        self.terminated
     }
 }
 
@@ -928,6 +1891,11 @@ async fn watch_key(ctx: &mut WatchContext, db: &Database, timeout: Option<Durati
                 Some(WatchResult::ShardMoved) => {
                     // The stream will be closed immediately.
                 }
+                Some(WatchResult::Lagging) => {
+                    // The stream will be closed immediately, re-establish it from the last
+                    // delivered version.
+                    trace!("watch key is lagging, resume from version {}", ctx.version);
+                }
                 Some(WatchResult::ValueUpdated) => {
                     let value = resp.value.ok_or_else(|| {
                         Error::Internal("The value field in WatchKeyResponse is required".into())
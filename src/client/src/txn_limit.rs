@@ -0,0 +1,258 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The limits of the writes staged by a txn.
+//!
+//! Each write is accounted by its encoded size, which is what it takes in the
+//! write intent request, so the limits are checked before any write is sent.
+//! The limits not specified by [`TxnOptions`] follow the capabilities
+//! advertised by the nodes.
+
+use std::ops::Range;
+use std::time::Duration;
+
+use log::warn;
+use prost::Message;
+use sekas_api::server::v1::*;
+
+use crate::{AppError, AppResult, SekasClient};
+
+/// The limits used if the capabilities of nodes are unknown, they are the
+/// defaults of the nodes.
+const DEFAULT_MAX_WRITE_BYTES: u64 = 64 * 1024 * 1024;
+const DEFAULT_MAX_WRITE_COUNT: u64 = 64 * 1024;
+
+/// The behavior of a txn whose writes exceed the limits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TxnOverflow {
+    /// Reject the txn with [`AppError::TxnTooLarge`] before any write is
+    /// sent.
+    #[default]
+    Error,
+    /// Commit the writes as a sequence of smaller txns within the limits, in
+    /// the order of the deletes first and then the puts.
+    ///
+    /// NOTE: it is weaker than a txn. The chunks are committed one by one, so
+    /// the committed writes are always a prefix of the writes, but the readers
+    /// might observe a partial prefix. The commit stops at the first failed
+    /// chunk with [`AppError::TxnChunkFailed`], which tells how far it got. The
+    /// txns with flushed writes or prefix checks are never chunked.
+    AutoChunk,
+}
+
+/// The options of the writes of a txn, see [`crate::Txn::set_options`].
+#[derive(Debug, Default, Clone)]
+pub struct TxnOptions {
+    /// The max bytes of the writes, `0` means unlimited. The limit advertised
+    /// by the nodes is used if it is `None`.
+    ///
+    /// Default: None
+    pub max_write_bytes: Option<u64>,
+    /// The max number of the writes, `0` means unlimited. The limit advertised
+    /// by the nodes is used if it is `None`.
+    ///
+    /// Default: None
+    pub max_write_count: Option<u64>,
+    /// Default: TxnOverflow::Error
+    pub on_overflow: TxnOverflow,
+    /// The timeout of each get, scan and commit of the txn, unless it is
+    /// overridden by [`crate::CallOptions::timeout`]. The timeout of the
+    /// database is used if it is `None`, see
+    /// [`crate::Database::with_call_options`].
+    ///
+    /// The ops are bounded by the deadline of the txn as well, see
+    /// [`crate::ClientOptions::timeout`].
+    ///
+    /// Default: None
+    pub default_op_timeout: Option<Duration>,
+}
+
+/// The limits resolved from [`TxnOptions`] and the node capabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TxnLimits {
+    pub max_write_bytes: u64,
+    pub max_write_count: u64,
+}
+
+impl TxnOptions {
+    /// Resolve the limits, the capabilities are fetched from a replica of the
+    /// shard of the key if any limit is not specified.
+    pub(crate) async fn resolve(&self, client: &SekasClient, probe: (u64, &[u8])) -> TxnLimits {
+        let (max_write_bytes, max_write_count) = match (self.max_write_bytes, self.max_write_count)
+        {
+            (Some(bytes), Some(count)) => (bytes, count),
+            (bytes, count) => {
+                let capabilities = node_capabilities(client, probe).await;
+                (
+                    bytes.unwrap_or(capabilities.max_txn_write_bytes),
+                    count.unwrap_or(capabilities.max_txn_write_count),
+                )
+            }
+        };
+        TxnLimits { max_write_bytes, max_write_count }
+    }
+}
+
+impl TxnLimits {
+    pub(crate) fn exceeded(&self, write_count: u64, write_bytes: u64) -> bool {
+        (self.max_write_count != 0 && write_count > self.max_write_count)
+            || (self.max_write_bytes != 0 && write_bytes > self.max_write_bytes)
+    }
+
+    pub(crate) fn too_large(&self, write_count: u64, write_bytes: u64) -> AppError {
+        AppError::TxnTooLarge {
+            write_count,
+            write_bytes,
+            max_write_count: self.max_write_count,
+            max_write_bytes: self.max_write_bytes,
+        }
+    }
+
+    /// Split the writes of the sizes into the consecutive chunks within the
+    /// limits. [`AppError::TxnTooLarge`] is returned if a single write exceeds
+    /// the limits.
+    pub(crate) fn split_chunks(&self, sizes: &[u64]) -> AppResult<Vec<Range<usize>>> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        let mut chunk_bytes = 0;
+        for (index, &size) in sizes.iter().enumerate() {
+            if self.exceeded(1, size) {
+                return Err(self.too_large(1, size));
+            }
+            if self.exceeded((index - start + 1) as u64, chunk_bytes + size) {
+                chunks.push(start..index);
+                start = index;
+                chunk_bytes = 0;
+            }
+            chunk_bytes += size;
+        }
+        if start < sizes.len() {
+            chunks.push(start..sizes.len());
+        }
+        Ok(chunks)
+    }
+}
+
+/// The staged size of a put, it is the encoded size of the request.
+#[inline]
+pub(crate) fn put_size(put: &PutRequest) -> u64 {
+    put.encoded_len() as u64
+}
+
+/// The staged size of a delete, it is the encoded size of the request.
+#[inline]
+pub(crate) fn delete_size(delete: &DeleteRequest) -> u64 {
+    delete.encoded_len() as u64
+}
+
+/// The capabilities of nodes, they are fetched once and cached by the client.
+/// The defaults are used if no replica of the probed shard responds. The max
+/// message bytes of the client is negotiated with the fetched capabilities.
+async fn node_capabilities(client: &SekasClient, probe: (u64, &[u8])) -> NodeCapabilities {
+    if let Some(capabilities) = client.node_capabilities().lock().unwrap().clone() {
+        return capabilities;
+    }
+
+    let (table_id, key) = probe;
+    let replicas = match client.router().find_shard(table_id, key) {
+        Ok((group_state, _)) => group_state.replicas.into_values().collect::<Vec<_>>(),
+        Err(err) => {
+            warn!("find shard of table {table_id} to fetch node capabilities: {err:?}");
+            Vec::default()
+        }
+    };
+    for replica in replicas {
+        let Ok(addr) = client.router().find_node_addr(replica.node_id) else {
+            continue;
+        };
+        let node_client = match client.conn_mgr().get_node_client(addr) {
+            Ok(node_client) => node_client,
+            Err(err) => {
+                warn!("connect node {} to fetch capabilities: {err:?}", replica.node_id);
+                continue;
+            }
+        };
+        match node_client.get_capabilities().await {
+            Ok(capabilities) => {
+                *client.node_capabilities().lock().unwrap() = Some(capabilities.clone());
+                client
+                    .conn_mgr()
+                    .negotiate_max_message_bytes(capabilities.max_message_bytes as usize);
+                return capabilities;
+            }
+            Err(err) => warn!("fetch capabilities of node {}: {err:?}", replica.node_id),
+        }
+    }
+    NodeCapabilities {
+        max_txn_write_bytes: DEFAULT_MAX_WRITE_BYTES,
+        max_txn_write_count: DEFAULT_MAX_WRITE_COUNT,
+        max_message_bytes: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WriteBuilder;
+
+    #[test]
+    fn txn_limits_split_chunks() {
+        let limits = TxnLimits { max_write_bytes: 100, max_write_count: 3 };
+        assert!(!limits.exceeded(3, 100));
+        assert!(limits.exceeded(4, 10));
+        assert!(limits.exceeded(1, 101));
+
+        let chunks = limits.split_chunks(&[10, 10, 10, 10, 60, 50, 50]).unwrap();
+        assert_eq!(chunks, vec![0..3, 3..5, 5..7]);
+        assert!(limits.split_chunks(&[]).unwrap().is_empty());
+        assert!(matches!(
+            limits.split_chunks(&[10, 101]),
+            Err(AppError::TxnTooLarge { write_count: 1, write_bytes: 101, .. })
+        ));
+
+        // `0` means unlimited.
+        let limits = TxnLimits { max_write_bytes: 0, max_write_count: 0 };
+        assert!(!limits.exceeded(u64::MAX, u64::MAX));
+        assert_eq!(limits.split_chunks(&[10, 10]).unwrap(), vec![0..2]);
+    }
+
+    #[test]
+    fn staged_size_matches_wire_size() {
+        // The shard id, the start version and the tags of the write.
+        const TOLERANCE: u64 = 32;
+
+        let put = WriteBuilder::new(vec![b'k'; 100])
+            .expect_not_exists()
+            .take_prev_value()
+            .ensure_put(vec![b'v'; 4096]);
+        let delete = WriteBuilder::new(vec![b'k'; 16]).ensure_delete();
+        let wire_size = |write: WriteRequest| {
+            let req = WriteIntentRequest {
+                shard_id: u64::MAX,
+                start_version: u64::MAX,
+                write: Some(write),
+                ..Default::default()
+            };
+            req.encoded_len() as u64
+        };
+
+        let staged = put_size(&put);
+        let wire = wire_size(WriteRequest::Put(put));
+        assert!(staged <= wire && wire <= staged + TOLERANCE, "staged {staged}, wire {wire}");
+
+        let staged = delete_size(&delete);
+        let wire = wire_size(WriteRequest::Delete(delete));
+        assert!(staged <= wire && wire <= staged + TOLERANCE, "staged {staged}, wire {wire}");
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use log::debug;
+use sekas_runtime::time::Instant;
+
+use crate::{AppError, AppResult, Database, Txn};
+
+/// The options of retrying a whole txn.
+#[derive(Debug, Clone)]
+pub struct TxnRetryOptions {
+    /// The max number of attempts, including the first one.
+    ///
+    /// Default: 10
+    pub max_attempts: usize,
+    /// The deadline of all attempts, it is checked before each retry, so an
+    /// in-flight attempt is not interrupted. `None` means no deadline.
+    ///
+    /// Default: None
+    pub timeout: Option<Duration>,
+    /// The backoff before the first retry, it is doubled after each retry.
+    ///
+    /// Default: 10ms
+    pub initial_backoff: Duration,
+    /// Default: 1s
+    pub max_backoff: Duration,
+}
+
+impl Default for TxnRetryOptions {
+    fn default() -> Self {
+        TxnRetryOptions {
+            max_attempts: 10,
+            timeout: None,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The error of an attempt, only the errors of commit might be retried.
+enum AttemptError {
+    Body(AppError),
+    Commit(AppError),
+}
+
+impl Database {
+    /// Run the body in a txn and commit it. If the txn conflicts with others,
+    /// it is retried from scratch with a fresh txn.
+    ///
+    /// The body might be invoked several times, so it is a `Fn` and should
+    /// only touch the data through the given txn, other side effects would be
+    /// repeated. The errors returned by the body are passed through without
+    /// retry.
+    pub async fn run_txn<F, T>(&self, options: TxnRetryOptions, body: F) -> AppResult<T>
+    where
+        F: for<'a> Fn(&'a mut Txn) -> BoxFuture<'a, AppResult<T>>,
+    {
+        let body = &body;
+        retry_txn(&options, || async move {
+            let mut txn = self.begin_txn();
+            let value = body(&mut txn).await.map_err(AttemptError::Body)?;
+            txn.commit().await.map_err(AttemptError::Commit)?;
+            Ok(value)
+        })
+        .await
+    }
+}
+
+async fn retry_txn<F, Fut, T>(options: &TxnRetryOptions, mut attempt: F) -> AppResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, AttemptError>>,
+{
+    let deadline = options.timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    let mut backoff = options.initial_backoff;
+    let mut num_attempts = 0;
+    loop {
+        num_attempts += 1;
+        let err = match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(AttemptError::Body(err)) => return Err(err),
+            Err(AttemptError::Commit(err)) => err,
+        };
+        if !is_retryable(&err) || num_attempts >= options.max_attempts {
+            return Err(err);
+        }
+
+        let interval = with_jitter(backoff);
+        if deadline.is_some_and(|deadline| Instant::now() + interval >= deadline) {
+            return Err(AppError::DeadlineExceeded(
+                format!("txn is still conflict after {num_attempts} attempts"),
+                None,
+            ));
+        }
+        debug!("txn is conflict, retry after {interval:?}, attempts {num_attempts}");
+        sekas_runtime::time::sleep(interval).await;
+        backoff = std::cmp::min(backoff * 2, options.max_backoff);
+    }
+}
+
+fn is_retryable(err: &AppError) -> bool {
+    matches!(err, AppError::TxnConflict)
+}
+
+/// Pick an interval in `[backoff/2, backoff]`, so the conflicting txns don't
+/// retry at the same time.
+fn with_jitter(backoff: Duration) -> Duration {
+    let half = backoff / 2;
+    half + half.mul_f64(sekas_runtime::sim::rng::random::<f64>())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn fast_options() -> TxnRetryOptions {
+        TxnRetryOptions {
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            ..Default::default()
+        }
+    }
+
+    async fn conflict_times(
+        options: &TxnRetryOptions,
+        num_conflicts: usize,
+    ) -> (AppResult<usize>, usize) {
+        let attempts = AtomicUsize::new(0);
+        let result = retry_txn(options, || async {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            if attempt < num_conflicts {
+                Err(AttemptError::Commit(AppError::TxnConflict))
+            } else {
+                Ok(attempt)
+            }
+        })
+        .await;
+        (result, attempts.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn retry_txn_on_conflict() {
+        let options = fast_options();
+        let (result, attempts) = conflict_times(&options, 0).await;
+        assert!(matches!(result, Ok(0)));
+        assert_eq!(attempts, 1);
+
+        let (result, attempts) = conflict_times(&options, 3).await;
+        assert!(matches!(result, Ok(3)));
+        assert_eq!(attempts, 4);
+
+        let (result, attempts) = conflict_times(&options, usize::MAX).await;
+        assert!(matches!(result, Err(AppError::TxnConflict)));
+        assert_eq!(attempts, options.max_attempts);
+    }
+
+    #[tokio::test]
+    async fn retry_txn_pass_through_errors() {
+        let options = fast_options();
+        let attempts = AtomicUsize::new(0);
+        let result: AppResult<()> = retry_txn(&options, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(AttemptError::Body(AppError::TxnConflict))
+        })
+        .await;
+        assert!(matches!(result, Err(AppError::TxnConflict)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        let attempts = AtomicUsize::new(0);
+        let result: AppResult<()> = retry_txn(&options, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(AttemptError::Commit(AppError::InvalidArgument("bad request".into())))
+        })
+        .await;
+        assert!(matches!(result, Err(AppError::InvalidArgument(_))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_txn_honor_deadline() {
+        let timeout = Duration::from_millis(100);
+        let options = TxnRetryOptions {
+            max_attempts: usize::MAX,
+            timeout: Some(timeout),
+            initial_backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(20),
+        };
+        let start = Instant::now();
+        let (result, attempts) = conflict_times(&options, usize::MAX).await;
+        assert!(matches!(result, Err(AppError::DeadlineExceeded(..))));
+        assert!(attempts > 1);
+        assert!(start.elapsed() < timeout, "elapsed {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn backoff_jitter() {
+        let backoff = Duration::from_millis(100);
+        for _ in 0..100 {
+            let interval = with_jitter(backoff);
+            assert!(interval >= backoff / 2 && interval <= backoff, "{interval:?}");
+        }
+    }
+}
@@ -18,10 +18,11 @@ use sekas_api::server::v1::group_request_union::Request;
 use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
 use sekas_rock::num::decode_u64;
-use sekas_rock::time::timestamp_millis;
+use sekas_runtime::time::timestamp_millis;
 use sekas_schema::system::keys::{self, txn_lower_key};
 use sekas_schema::system::{self, table};
 
+use crate::metrics::CLIENT_TXN_TABLE_REQUEST_TOTAL;
 use crate::{Error, GroupClient, Result, RetryState, SekasClient, WriteBuilder};
 
 const TXN_TIMEOUT: Duration = Duration::from_secs(5);
@@ -65,8 +66,9 @@ impl TxnStateTable {
     /// [`Error::InvalidArgument`] is returned if the specified txn has been
     /// committed or aborted.
     pub async fn begin_txn(&self, start_version: u64) -> Result<()> {
+        CLIENT_TXN_TABLE_REQUEST_TOTAL.begin.inc();
         let state_value = TxnState::Running.as_str_name().as_bytes().to_vec();
-        let heartbeat_value = timestamp_millis().to_le_bytes().to_vec();
+        let heartbeat_value = txn_u64_value(timestamp_millis());
         let hash_tag = system::txn::hash_tag(start_version);
         let request = TxnWriteRequest {
             hash_tag,
@@ -107,6 +109,7 @@ impl TxnStateTable {
 
     /// Update the txn heartbeat.
     pub async fn heartbeat(&self, start_version: u64) -> Result<()> {
+        CLIENT_TXN_TABLE_REQUEST_TOTAL.heartbeat.inc();
         let heartbeat_value = txn_u64_value(timestamp_millis());
         let hash_tag = system::txn::hash_tag(start_version);
         let request = TxnWriteRequest {
@@ -133,6 +136,7 @@ impl TxnStateTable {
     /// has been aborted.
     pub async fn commit_txn(&self, start_version: u64, commit_version: u64) -> Result<()> {
         debug_assert!(start_version < commit_version);
+        CLIENT_TXN_TABLE_REQUEST_TOTAL.commit.inc();
 
         let hash_tag = system::txn::hash_tag(start_version);
         let request = TxnWriteRequest {
@@ -185,6 +189,7 @@ impl TxnStateTable {
     /// Get the corresponding txn record.
     pub async fn get_txn_record(&self, start_version: u64) -> Result<Option<TxnRecord>> {
         trace!("get txn record, start version: {}", start_version);
+        CLIENT_TXN_TABLE_REQUEST_TOTAL.get.inc();
         let hash_tag = system::txn::hash_tag(start_version);
         let txn_prefix = keys::txn_prefix(hash_tag, start_version);
         let scan_resp = self.scan_txn_keys(&txn_prefix, start_version).await?;
@@ -196,6 +201,7 @@ impl TxnStateTable {
     /// [`Error::InvalidArgument`] is returned if the specified txn has been
     /// committed.
     pub async fn abort_txn(&self, start_version: u64) -> Result<()> {
+        CLIENT_TXN_TABLE_REQUEST_TOTAL.abort.inc();
         let expect_state_value = txn_state_value(TxnState::Running);
         let state_value = txn_state_value(TxnState::Aborted);
         let heartbeat_value = txn_u64_value(timestamp_millis());
@@ -240,6 +246,33 @@ impl TxnStateTable {
             TxnState::Aborted => Ok(()),
         }
     }
+
+    /// List the txn records stored in the txn shard, in the order of txn id.
+    ///
+    /// It is used to inspect the txns, the shard is located by the caller
+    /// since the txn shards might be split.
+    pub async fn list_txn_records(&self, group_id: u64, shard_id: u64) -> Result<Vec<TxnRecord>> {
+        CLIENT_TXN_TABLE_REQUEST_TOTAL.list.inc();
+        let mut retry_state = RetryState::with_timeout_opt(self.timeout);
+        let request = Request::Scan(ShardScanRequest {
+            shard_id,
+            start_version: system::txn::TXN_MAX_VERSION,
+            prefix: Some(keys::TXN_PREFIX.to_vec()),
+            ..Default::default()
+        });
+        let values = loop {
+            let mut group_client = GroupClient::lazy(group_id, self.client.clone());
+            group_client.set_timeout_opt(retry_state.timeout());
+            match group_client.request(&request).await {
+                Ok(Response::Scan(resp)) => break resp.data,
+                Ok(_) => {
+                    return Err(Error::Internal("invalid response type, Scan is required".into()))
+                }
+                Err(err) => retry_state.retry(err).await?,
+            }
+        };
+        parse_txn_records(values)
+    }
 }
 
 impl TxnStateTable {
@@ -358,6 +391,33 @@ fn parse_txn_record(
     Ok(Some(txn_record))
 }
 
+/// Parse the txn records from the scanned keys of a txn shard, the keys of a
+/// txn are adjacent since they share the txn prefix.
+fn parse_txn_records(values: Vec<ValueSet>) -> Result<Vec<TxnRecord>> {
+    const TXN_PREFIX_LEN: usize = keys::TXN_PREFIX.len() + 1 + std::mem::size_of::<u64>();
+
+    let mut records = Vec::new();
+    let mut it = values.into_iter().peekable();
+    while let Some(value_set) = it.peek() {
+        let Some(txn_prefix) = value_set.user_key.get(..TXN_PREFIX_LEN) else {
+            return Err(Error::Internal(
+                format!("invalid txn key {:?}", value_set.user_key).into(),
+            ));
+        };
+        let txn_prefix = txn_prefix.to_vec();
+        let hash_tag = txn_prefix[keys::TXN_PREFIX.len()];
+        let start_version = parse_u64(&txn_prefix[keys::TXN_PREFIX.len() + 1..])?;
+        let mut txn_values = Vec::with_capacity(3);
+        while let Some(value_set) = it.next_if(|v| v.user_key.starts_with(&txn_prefix)) {
+            txn_values.push(value_set);
+        }
+        if let Some(record) = parse_txn_record(hash_tag, start_version, txn_values)? {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
 fn parse_u64(bytes: &[u8]) -> Result<u64> {
     decode_u64(bytes).ok_or_else(|| {
         Error::Internal(
@@ -572,6 +632,41 @@ mod tests {
         assert!(matches!(result, Err(Error::Internal(_))));
     }
 
+    #[test]
+    fn parse_multiple_txn_records() {
+        let mut values = vec![];
+        for (hash_tag, txn_id, state) in
+            [(1, 123, TxnState::Running), (1, 124, TxnState::Aborted), (2, 7, TxnState::Running)]
+        {
+            values.push(ValueSet {
+                user_key: txn_heartbeat_key(hash_tag, txn_id),
+                values: vec![Value::with_value(txn_u64_value(txn_id * 10), 1)],
+            });
+            values.push(ValueSet {
+                user_key: txn_state_key(hash_tag, txn_id),
+                values: vec![Value::with_value(txn_state_value(state), 1)],
+            });
+        }
+
+        let records = parse_txn_records(values).unwrap();
+        let records =
+            records.iter().map(|r| (r.start_version, r.state, r.heartbeat)).collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            vec![
+                (123, TxnState::Running, 1230),
+                (124, TxnState::Aborted, 1240),
+                (7, TxnState::Running, 70)
+            ]
+        );
+
+        let values = vec![ValueSet {
+            user_key: b"txn_".to_vec(),
+            values: vec![Value::with_value(txn_state_value(TxnState::Running), 1)],
+        }];
+        assert!(matches!(parse_txn_records(values), Err(Error::Internal(_))));
+    }
+
     #[test]
     fn parse_txn_record_consume_all_keys() {
         let hash_tag = 1;
@@ -0,0 +1,122 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sekas_api::server::v1::Value;
+use sekas_rock::num::decode_i64;
+
+use crate::{AppError, AppResult, Txn, WriteBuilder};
+
+/// The options of [`Txn::transfer`].
+#[derive(Debug, Clone, Default)]
+pub struct TransferOptions {
+    /// The min balance of the source key after the transfer.
+    ///
+    /// Default: 0
+    pub floor: i64,
+}
+
+impl Txn {
+    /// Move `amount` from the balance of `from` to the balance of `to`, both
+    /// are `(table_id, key)`. The balances are interpreted as i64, and a
+    /// missing key is treated as 0. Returns the balances of `from` and `to`
+    /// after the transfer.
+    ///
+    /// The balances are read at the read version of this txn, and the adds are
+    /// conditioned on the versions read, so that the commit fails with
+    /// [`AppError::TxnConflict`] if either key is changed by others
+    /// concurrently, rather than breaking the floor of the source silently.
+    /// [`AppError::InsufficientBalance`] is returned if the source balance
+    /// would be less than [`TransferOptions::floor`].
+    pub async fn transfer(
+        &mut self,
+        from: (u64, Vec<u8>),
+        to: (u64, Vec<u8>),
+        amount: i64,
+        options: TransferOptions,
+    ) -> AppResult<(i64, i64)> {
+        if amount <= 0 {
+            return Err(AppError::InvalidArgument(format!(
+                "the transfer amount {amount} is not positive"
+            )));
+        }
+        if from == to {
+            return Err(AppError::InvalidArgument("transfer to the source key".into()));
+        }
+
+        let (from_table_id, from_key) = from;
+        let (to_table_id, to_key) = to;
+        let (from_value, to_value) = futures::try_join!(
+            self.get_raw_value(from_table_id, from_key.clone()),
+            self.get_raw_value(to_table_id, to_key.clone()),
+        )?;
+        let from_balance = decode_balance(from_value.as_ref())?;
+        let to_balance = decode_balance(to_value.as_ref())?;
+        let from_balance = match from_balance.checked_sub(amount) {
+            Some(balance) if balance >= options.floor => balance,
+            _ => {
+                return Err(AppError::InsufficientBalance {
+                    balance: from_balance,
+                    amount,
+                    floor: options.floor,
+                })
+            }
+        };
+        let to_balance = to_balance.checked_add(amount).ok_or_else(|| {
+            AppError::InvalidArgument(format!("the balance {to_balance} add {amount} overflows"))
+        })?;
+
+        // The transfers touching the same keys in different orders would wait for each
+        // other forever, so the younger txn fails instead of waiting (wait-die).
+        self.wait_die_keys.insert((from_table_id, from_key.clone()));
+        self.wait_die_keys.insert((to_table_id, to_key.clone()));
+        self.put(from_table_id, tracked_write(from_key, from_value.as_ref()).ensure_add(-amount));
+        self.put(to_table_id, tracked_write(to_key, to_value.as_ref()).ensure_add(amount));
+        Ok((from_balance, to_balance))
+    }
+}
+
+fn decode_balance(value: Option<&Value>) -> AppResult<i64> {
+    match value.and_then(|v| v.content.as_ref()) {
+        Some(content) => decode_i64(content)
+            .ok_or_else(|| AppError::InvalidArgument("the exists value is not a valid i64".into())),
+        None => Ok(0),
+    }
+}
+
+/// Build a write expecting the value read. An add with conditions is not an
+/// atomic operation, so it conflicts with the values committed after the
+/// start version of the txn.
+fn tracked_write(key: Vec<u8>, value: Option<&Value>) -> WriteBuilder {
+    let builder = WriteBuilder::new(key);
+    match value {
+        Some(value) if value.content.is_some() => builder.expect_version(value.version),
+        _ => builder.expect_not_exists(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_transfer_balance() {
+        assert_eq!(decode_balance(None).unwrap(), 0);
+        let tombstone = Value::tombstone(1);
+        assert_eq!(decode_balance(Some(&tombstone)).unwrap(), 0);
+        let value = Value::with_value((-5i64).to_be_bytes().to_vec(), 1);
+        assert_eq!(decode_balance(Some(&value)).unwrap(), -5);
+        let value = Value::with_value(b"abc".to_vec(), 1);
+        assert!(matches!(decode_balance(Some(&value)), Err(AppError::InvalidArgument(_))));
+    }
+}
@@ -12,20 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::ExecuteResult;
+use crate::{ExecuteResult, OutputFormat};
 
 #[derive(Debug)]
 pub enum Statement {
+    AlterDatabase(AlterDatabaseStatement),
+    AlterTable(AlterTableStatement),
+    Approve(ApproveStatement),
+    CompactTable(CompactTableStatement),
     CreateDb(CreateDbStatement),
     CreateTable(CreateTableStatement),
     Config(ConfigStatement),
     Debug(DebugStatement),
+    DebugSchedule(DebugScheduleStatement),
+    DebugSearch(DebugSearchStatement),
+    DebugVerify(DebugVerifyStatement),
     Echo(EchoStatement),
+    Format(FormatStatement),
     Help(HelpStatement),
+    KillTxn(KillTxnStatement),
     Show(ShowStatement),
+    Split(SplitStatement),
     Put(PutStatement),
     Delete(DeleteStatement),
     Get(GetStatement),
+    Scan(ScanStatement),
 }
 
 #[derive(Debug)]
@@ -33,6 +44,11 @@ pub struct EchoStatement {
     pub message: String,
 }
 
+#[derive(Debug)]
+pub struct FormatStatement {
+    pub format: OutputFormat,
+}
+
 #[derive(Debug)]
 pub struct CreateDbStatement {
     pub db_name: String,
@@ -44,12 +60,47 @@ pub struct CreateTableStatement {
     pub db_name: String,
     pub table_name: String,
     pub create_if_not_exists: bool,
+    /// The database and the name of the table to clone.
+    pub clone_of: Option<(String, String)>,
+}
+
+#[derive(Debug)]
+pub struct AlterDatabaseStatement {
+    pub db_name: String,
+    pub key: String,
+    /// The property is removed if the value is empty.
+    pub value: String,
+}
+
+#[derive(Debug)]
+pub struct AlterTableStatement {
+    pub db_name: String,
+    pub table_name: String,
+    pub key: String,
+    /// The property is removed if the value is empty.
+    pub value: String,
+}
+
+#[derive(Debug)]
+pub struct CompactTableStatement {
+    pub db_name: String,
+    pub table_name: String,
+}
+
+#[derive(Debug)]
+pub struct ApproveStatement {
+    pub id: String,
 }
 
 #[derive(Debug)]
 pub struct ConfigStatement {
     pub key: Box<[u8]>,
     pub value: Box<[u8]>,
+    /// The node to apply the config, only the node-level configs accept it.
+    pub node: Option<String>,
+    /// The seconds after which the config reverts, only the node-level configs
+    /// accept it.
+    pub ttl: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -57,6 +108,21 @@ pub struct DebugStatement {
     pub stmt: Box<Statement>,
 }
 
+/// Run a reconciliation pass of the scheduler immediately.
+#[derive(Debug)]
+pub struct DebugScheduleStatement {}
+
+#[derive(Debug)]
+pub struct DebugSearchStatement {
+    pub key_prefix: Vec<u8>,
+    pub group: String,
+}
+
+#[derive(Debug)]
+pub struct DebugVerifyStatement {
+    pub property: String,
+}
+
 #[derive(Debug)]
 pub struct HelpStatement {
     pub topic: Option<String>,
@@ -66,6 +132,21 @@ pub struct HelpStatement {
 pub struct ShowStatement {
     pub property: String,
     pub from: Option<String>,
+    /// Read the properties from the local state of root, which might be stale.
+    pub stale: bool,
+    /// The max number of rows to show.
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct KillTxnStatement {
+    pub txn_id: String,
+}
+
+#[derive(Debug)]
+pub struct SplitStatement {
+    pub shard: String,
+    pub key: Vec<u8>,
 }
 
 #[derive(Debug)]
@@ -90,6 +171,14 @@ pub struct GetStatement {
     pub table_name: String,
 }
 
+#[derive(Debug)]
+pub struct ScanStatement {
+    pub prefix: Option<Vec<u8>>,
+    pub db_name: String,
+    pub table_name: String,
+    pub reverse: bool,
+}
+
 impl DebugStatement {
     #[inline]
     pub fn execute(&self) -> ExecuteResult {
@@ -109,17 +198,99 @@ impl HelpStatement {
 
     fn display_topic(topic: &str) -> String {
         match topic {
+            "alter" | "ALTER" => Self::display_alter_topic(),
+            "approve" | "APPROVE" => Self::display_approve_topic(),
+            "compact" | "COMPACT" => Self::display_compact_topic(),
+            "config" | "CONFIG" => Self::display_config_topic(),
             "create" | "CREATE" => Self::display_create_topic(),
+            "kill" | "KILL" => Self::display_kill_topic(),
             "show" | "SHOW" => Self::display_show_topic(),
+            "split" | "SPLIT" => Self::display_split_topic(),
             "put" | "PUT" => Self::display_put_topic(),
             "delete" | "DELETE" => Self::display_delete_topic(),
             "get" | "GET" => Self::display_get_topic(),
+            "scan" | "SCAN" => Self::display_scan_topic(),
+            "format" | "FORMAT" => Self::display_format_topic(),
+            "debug" | "DEBUG" => Self::display_debug_topic(),
             _ => {
                 format!("unknown command `{}`. Try `help`?", topic)
             }
         }
     }
 
+    fn display_alter_topic() -> String {
+        r##"
+ALTER DATABASE <name:ident> SET <property:literal> <value:literal>
+    Change the property of a database, it is removed if the value is empty.
+    supported properties, they override the cluster defaults, see `CONFIG`:
+    - max_tables_per_database, max_shards_per_table and
+      max_shards_per_database, the quotas of the database, 0 means
+      unlimited. See `SHOW databases` for the effective quotas.
+
+ALTER TABLE <db:ident>.<name:ident> SET <property:literal> <value:literal>
+    Change the property of a table, it is removed if the value is empty.
+    supported properties:
+    - leader_preference, the zone label of the nodes preferred to place the
+      leaders of the groups serving the table. See `SHOW groups`.
+    - max_versions, read_replicas, replicas_per_group and replication
+
+Note:
+    The literal could be quoted by `"`.
+"##
+        .to_owned()
+    }
+
+    fn display_approve_topic() -> String {
+        r##"
+APPROVE <id:ident>
+    Approve a pending recommendation of the scheduler, it is only accepted
+    in the manual-approve schedule mode. See `SHOW recommendations`.
+"##
+        .to_owned()
+    }
+
+    fn display_compact_topic() -> String {
+        r##"
+COMPACT TABLE <db:ident>.<name:ident>
+    Compact the data of a table on all replicas hosting its shards, to
+    reclaim the disk space after large deletes. At most one manual
+    compaction is running on a node, the others are queued. It returns the
+    approximate size of each replica before and after the compaction once
+    all replicas are finished.
+"##
+        .to_owned()
+    }
+
+    fn display_config_topic() -> String {
+        r##"
+CONFIG <name:literal> <value:literal> [ON <node:ident>] [TTL <secs:ident>]
+    Change the config of cluster. supported configs:
+    - schedule_mode, one of auto, advise and manual-approve
+    - schedule_auto_cure, cure the groups lost replicas without approvals,
+      true or false
+    - snapshot_send_concurrency, the limit number of concurrent outgoing
+      snapshot transfers per node
+    - transfer_bytes_per_sec, the limit bytes per second shared by moving
+      shards and sending snapshots per node, 0 means unlimited
+    - log.<target>, the log level of the target on the node, or all nodes
+      if ON clause is omitted. It reverts to the configured default after
+      the TTL, 10 minutes by default. An empty level reverts it at once.
+      See `SHOW log_filters` for the effective filters.
+    - max_tables_per_database, max_shards_per_table and
+      max_shards_per_database, the default quotas of the databases, 0
+      means unlimited. See `ALTER DATABASE`.
+    - raft.pre_vote, the candidates check whether they could win before
+      incrementing the term, true or false
+    - raft.check_quorum, the leaders step down once they lose the quorum,
+      and the followers ignore the votes while they have heard from a live
+      leader, true or false
+
+Note:
+    The literal could be quoted by `"`.
+"##
+        .to_owned()
+    }
+
     fn display_create_topic() -> String {
         r##"
 CREATE DATABASE [IF NOT EXISTS] <name:ident>
@@ -128,6 +299,10 @@ CREATE DATABASE [IF NOT EXISTS] <name:ident>
 CREATE TABLE [IF NOT EXISTS] [<db:ident>.]<name:ident>
     Create a new table.
 
+CREATE TABLE <db:ident>.<name:ident> AS CLONE OF <db:ident>.<name:ident>
+    Create a new table from a consistent snapshot of another table, the new
+    table is read-only until the data is copied. See `SHOW clones`.
+
 Note:
     The ident accepts characters [a-zA-Z0-9_-].
 "##
@@ -136,21 +311,56 @@ Note:
 
     fn display_show_topic() -> String {
         r##"
-SHOW <property:ident> [FROM <name:ident>]
+SHOW <property:ident> [FROM <name:ident>] [STALE] [LIMIT <n:ident>]
     Show properties. supported properties:
-    - databases
+    - databases, with the effective quotas
     - tables FROM <database>
-    - groups
+    - groups, with the preferred zone of leaders and whether it is satisfied
     - replicas FROM <group-id>
     - shards FROM <group-id>
+    - intents FROM <group-id>, the oldest unresolved intents
+    - hotkeys FROM <group-id>, the hottest keys sampled by the leader
     - nodes
+    - migrations
+    - clones, the table clones in progress
+    - recommendations
+    - txns, the running txns in descending order of age
+    - log_filters [FROM <node-id>], the effective log filters of the nodes
+    - scheduler, the recent reconciliation passes of the scheduler, with
+      the decisions on the actions and the reasons
 
 Note:
+    The properties are read from the latest committed states of root, they
+    are read from the local states of root if STALE is specified, which is
+    cheaper but might miss the latest changes.
+    Only the txns and scheduler support LIMIT now, the LIMIT of scheduler
+    is the number of passes.
     The ident accepts characters [a-zA-Z0-9_-].
 "##
         .to_owned()
     }
 
+    fn display_kill_topic() -> String {
+        r##"
+KILL TXN <txn-id:ident>
+    Abort a running txn, its intents are resolved in the background. The
+    abortion is recorded into the topology event log. See `SHOW txns`.
+"##
+        .to_owned()
+    }
+
+    fn display_split_topic() -> String {
+        r##"
+SPLIT SHARD <shard-id:ident> AT <key:literal>
+    Split the shard at the key, the key becomes the start of the new shard.
+    The key must belong to the shard and not be the start of the shard.
+
+Note:
+    The literal could be quoted by `"`.
+"##
+        .to_owned()
+    }
+
     fn display_put_topic() -> String {
         r##"
 PUT <key:literal> <value:literal> INTO <db_name:ident>.<table_name:ident>
@@ -173,6 +383,58 @@ Note:
         .to_owned()
     }
 
+    fn display_scan_topic() -> String {
+        r##"
+SCAN [<prefix:literal>] FROM <db_name:ident>.<table_name:ident> [DESC]
+    Scan the keys of a table, in descending order if DESC is specified.
+
+Note:
+    The ident accepts characters [a-zA-Z0-9_-].
+"##
+        .to_owned()
+    }
+
+    fn display_format_topic() -> String {
+        r##"
+FORMAT <format:ident>
+    Set the output format of the following statements. supported formats:
+    - table, the default format
+    - json, an array of objects keyed by column name
+    - tsv, tab-separated values with a header line, the backslashes,
+      tabs and newlines of every field are escaped, eg `\\` and `\t`
+
+Note:
+    Binary keys and values are escaped, eg `\t`, `\n` and `\xFF`.
+"##
+        .to_owned()
+    }
+
+    fn display_debug_topic() -> String {
+        r##"
+DEBUG <statement>
+    Display the parsed statement.
+
+DEBUG SCHEDULE NOW
+    Run a reconciliation pass of the scheduler immediately, the decisions
+    on the actions considered by the pass are shown with the reasons. See
+    `SHOW scheduler` for the recent passes.
+
+DEBUG SEARCH <prefix:literal> FROM <group-id:ident>
+    Search the recent raft log of the group leader for the entries which
+    write keys with the prefix, the request ids of the entries are shown.
+
+DEBUG VERIFY <property:ident>
+    Verify the invariants of the cluster, the violations are shown and
+    raised as health alerts. supported properties:
+    - coverage, the shards of each table cover the whole key space without
+      gaps or overlaps, and each shard is listed by exactly one group.
+
+Note:
+    The literal could be quoted by `"`.
+"##
+        .to_owned()
+    }
+
     fn display_delete_topic() -> String {
         r##"
 DELETE <key:literal> FROM <db_name:ident>.<table_name:ident>
@@ -188,11 +450,20 @@ Note:
         r##"
 List of commands:
 
+alter       change the properties of a database or table
+approve     approve a recommendation of the scheduler
+compact     compact the data of a table to reclaim the disk space
+config      change the config of cluster
 create      create database, table ...
 show        show properties, such as databases, tables ...
+kill        abort a running txn
+split       split a shard at a key
 put         put value into a table
 delete      delete key from a table
 get         get the value of the key from a table
+scan        scan the keys of a table
+format      set the output format of results
+debug       display the statement, run the scheduler, search the raft log or verify the cluster
 help        get help about a topic or command
 
 For information on a specific command, type `help <command>'.
@@ -57,6 +57,15 @@ pub struct ColumnResult {
     pub rows: Vec<Row>,
 }
 
+/// The format used to display the [`ExecuteResult`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Tsv,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ExecuteResult {
     Data(ColumnResult),
@@ -14,7 +14,7 @@
 
 use crate::ast::*;
 use crate::token::{TokenRule, Tokenizer};
-use crate::{ParseError, ParseResult, Token};
+use crate::{OutputFormat, ParseError, ParseResult, Token};
 
 #[derive(Debug)]
 struct Parser<'a> {
@@ -39,6 +39,12 @@ impl<'a> Parser<'a> {
 
         let stmt = if self.peek::<Token![echo]>() {
             parse_echo_statement(self)?
+        } else if self.peek::<Token![alter]>() {
+            parse_alter_stmt(self)?
+        } else if self.peek::<Token![approve]>() {
+            parse_approve_stmt(self)?
+        } else if self.peek::<Token![compact]>() {
+            parse_compact_stmt(self)?
         } else if self.peek::<Token![config]>() {
             parse_config_stmt(self)?
         } else if self.peek::<Token![create]>() {
@@ -47,10 +53,18 @@ impl<'a> Parser<'a> {
             parse_get_stmt(self)?
         } else if self.peek::<Token![put]>() {
             parse_put_stmt(self)?
+        } else if self.peek::<Token![scan]>() {
+            parse_scan_stmt(self)?
         } else if self.peek::<Token![delete]>() {
             parse_delete_stmt(self)?
         } else if self.peek::<Token![show]>() {
             parse_show_stmt(self)?
+        } else if self.peek::<Token![kill]>() {
+            parse_kill_stmt(self)?
+        } else if self.peek::<Token![split]>() {
+            parse_split_stmt(self)?
+        } else if self.peek::<Token![format]>() {
+            parse_format_stmt(self)?
         } else if self.peek::<Token![help]>() {
             parse_help_stmt(self)?
         } else if self.peek::<Token![debug]>() {
@@ -78,21 +92,97 @@ fn parse_echo_statement(parser: &mut Parser) -> ParseResult<Statement> {
 }
 
 // Syntax:
-// CONFIG <name:literal> <value:literal>
+// ALTER DATABASE <db name:ident> SET <property:literal> <value:literal>
+// ALTER TABLE <db name:ident> . <table name:ident> SET <property:literal>
+// <value:literal>
+fn parse_alter_stmt(parser: &mut Parser) -> ParseResult<Statement> {
+    parser.next::<Token![alter]>()?;
+    if parser.peek::<Token![database]>() {
+        parser.next::<Token![database]>()?;
+        let db_name = parser.next::<Token![ident]>()?.value().to_owned();
+        parser.next::<Token![set]>()?;
+        let key = parser.next::<Token![literal]>()?;
+        let value = parser.next::<Token![literal]>()?;
+        parser.next::<Token![;]>()?;
+        return Ok(Statement::AlterDatabase(AlterDatabaseStatement {
+            db_name,
+            key: String::from_utf8_lossy(key.value()).into_owned(),
+            value: String::from_utf8_lossy(value.value()).into_owned(),
+        }));
+    }
+    parser.next::<Token![table]>()?;
+    let db_name = parser.next::<Token![ident]>()?.value().to_owned();
+    parser.next::<Token![.]>()?;
+    let table_name = parser.next::<Token![ident]>()?.value().to_owned();
+    parser.next::<Token![set]>()?;
+    let key = parser.next::<Token![literal]>()?;
+    let value = parser.next::<Token![literal]>()?;
+    parser.next::<Token![;]>()?;
+    Ok(Statement::AlterTable(AlterTableStatement {
+        db_name,
+        table_name,
+        key: String::from_utf8_lossy(key.value()).into_owned(),
+        value: String::from_utf8_lossy(value.value()).into_owned(),
+    }))
+}
+
+// Syntax:
+// COMPACT TABLE <db name:ident> . <table name:ident>
+fn parse_compact_stmt(parser: &mut Parser) -> ParseResult<Statement> {
+    parser.next::<Token![compact]>()?;
+    parser.next::<Token![table]>()?;
+    let db_name = parser.next::<Token![ident]>()?.value().to_owned();
+    parser.next::<Token![.]>()?;
+    let table_name = parser.next::<Token![ident]>()?.value().to_owned();
+    parser.next::<Token![;]>()?;
+    Ok(Statement::CompactTable(CompactTableStatement { db_name, table_name }))
+}
+
+// Syntax:
+// APPROVE <id:ident>
+fn parse_approve_stmt(parser: &mut Parser) -> ParseResult<Statement> {
+    parser.next::<Token![approve]>()?;
+    let id = parser.next::<Token![ident]>()?;
+    parser.next::<Token![;]>()?;
+    Ok(Statement::Approve(ApproveStatement { id: id.value().to_owned() }))
+}
+
+// Syntax:
+// CONFIG <name:literal> <value:literal> [ON <node:ident>] [TTL <secs:ident>]
 fn parse_config_stmt(parser: &mut Parser) -> ParseResult<Statement> {
     parser.next::<Token![config]>()?;
     let key = parser.next::<Token![literal]>()?;
     let value = parser.next::<Token![literal]>()?;
+    let node = if parser.peek::<Token![on]>() {
+        parser.next::<Token![on]>()?;
+        Some(parser.next::<Token![ident]>()?.value().to_owned())
+    } else {
+        None
+    };
+    let ttl = if parser.peek::<Token![ttl]>() {
+        parser.next::<Token![ttl]>()?;
+        let n = parser.next::<Token![ident]>()?;
+        let Ok(ttl) = n.value().parse::<u64>() else {
+            return Err(ParseError::Expect("u64 numeric".to_owned(), n.coord()));
+        };
+        Some(ttl)
+    } else {
+        None
+    };
     parser.next::<Token![;]>()?;
     Ok(Statement::Config(ConfigStatement {
         key: key.value().to_owned().into(),
         value: value.value().to_owned().into(),
+        node,
+        ttl,
     }))
 }
 
 // Syntax:
 // CREATE DATABASE [IF NOT EXISTS] <db name:ident>
 // CREATE TABLE [IF NOT EXISTS] <db name:ident> . <table name:ident>
+// CREATE TABLE <db name:ident> . <table name:ident> AS CLONE OF <db name:ident>
+// . <table name:ident>
 fn parse_create_stmt(parser: &mut Parser) -> ParseResult<Statement> {
     parser.next::<Token![create]>()?;
     if parser.peek::<Token![database]>() {
@@ -112,11 +202,23 @@ fn parse_create_stmt(parser: &mut Parser) -> ParseResult<Statement> {
         let db_name = parser.next::<Token![ident]>()?.value().to_owned();
         parser.next::<Token![.]>()?;
         let table_name = parser.next::<Token![ident]>()?.value().to_owned();
+        let clone_of = if !create_if_not_exists && parser.peek::<Token![as]>() {
+            parser.next::<Token![as]>()?;
+            parser.next::<Token![clone]>()?;
+            parser.next::<Token![of]>()?;
+            let src_db_name = parser.next::<Token![ident]>()?.value().to_owned();
+            parser.next::<Token![.]>()?;
+            let src_table_name = parser.next::<Token![ident]>()?.value().to_owned();
+            Some((src_db_name, src_table_name))
+        } else {
+            None
+        };
         parser.next::<Token![;]>()?;
         Ok(Statement::CreateTable(CreateTableStatement {
             db_name,
             table_name,
             create_if_not_exists,
+            clone_of,
         }))
     } else {
         Err(ParseError::UnexpectedToken("database or table".to_owned(), parser.tokenizer.coord()))
@@ -148,6 +250,29 @@ fn parse_put_stmt(parser: &mut Parser) -> ParseResult<Statement> {
     Ok(Statement::Put(PutStatement { key, value, db_name, table_name }))
 }
 
+// Syntax:
+// SCAN [<prefix:literal>] FROM <db_name:ident>.<table_name:ident> [DESC]
+fn parse_scan_stmt(parser: &mut Parser) -> ParseResult<Statement> {
+    parser.next::<Token![scan]>()?;
+    let prefix = if parser.peek::<Token![from]>() {
+        None
+    } else {
+        Some(parser.next::<Token![literal]>()?.value().to_owned())
+    };
+    parser.next::<Token![from]>()?;
+    let db_name = parser.next::<Token![ident]>()?.value().to_owned();
+    parser.next::<Token![.]>()?;
+    let table_name = parser.next::<Token![ident]>()?.value().to_owned();
+    let reverse = if parser.peek::<Token![desc]>() {
+        parser.next::<Token![desc]>()?;
+        true
+    } else {
+        false
+    };
+    parser.next::<Token![;]>()?;
+    Ok(Statement::Scan(ScanStatement { prefix, db_name, table_name, reverse }))
+}
+
 // Syntax:
 // DELETE <key:literal> FROM <db_name:ident>.<table_name:ident>
 fn parse_delete_stmt(parser: &mut Parser) -> ParseResult<Statement> {
@@ -161,7 +286,7 @@ fn parse_delete_stmt(parser: &mut Parser) -> ParseResult<Statement> {
 }
 
 // Syntax:
-// SHOW <property:ident> [FROM <name:ident>]
+// SHOW <property:ident> [FROM <name:ident>] [STALE] [LIMIT <n:ident>]
 fn parse_show_stmt(parser: &mut Parser) -> ParseResult<Statement> {
     parser.next::<Token![show]>()?;
     let ident = parser.next::<Token![ident]>()?;
@@ -172,8 +297,61 @@ fn parse_show_stmt(parser: &mut Parser) -> ParseResult<Statement> {
     } else {
         None
     };
+    let stale = parser.peek::<Token![stale]>();
+    if stale {
+        parser.next::<Token![stale]>()?;
+    }
+    let limit = if parser.peek::<Token![limit]>() {
+        parser.next::<Token![limit]>()?;
+        let n = parser.next::<Token![ident]>()?;
+        let Ok(limit) = n.value().parse::<u64>() else {
+            return Err(ParseError::Expect("u64 numeric".to_owned(), n.coord()));
+        };
+        Some(limit)
+    } else {
+        None
+    };
+    parser.next::<Token![;]>()?;
+    Ok(Statement::Show(ShowStatement { property: ident.value().to_owned(), from, stale, limit }))
+}
+
+// Syntax:
+// KILL TXN <txn-id:ident>
+fn parse_kill_stmt(parser: &mut Parser) -> ParseResult<Statement> {
+    parser.next::<Token![kill]>()?;
+    parser.next::<Token![txn]>()?;
+    let txn_id = parser.next::<Token![ident]>()?.value().to_owned();
     parser.next::<Token![;]>()?;
-    Ok(Statement::Show(ShowStatement { property: ident.value().to_owned(), from }))
+    Ok(Statement::KillTxn(KillTxnStatement { txn_id }))
+}
+
+// Syntax:
+// SPLIT SHARD <shard-id:ident> AT <key:literal>
+fn parse_split_stmt(parser: &mut Parser) -> ParseResult<Statement> {
+    parser.next::<Token![split]>()?;
+    parser.next::<Token![shard]>()?;
+    let shard = parser.next::<Token![ident]>()?.value().to_owned();
+    parser.next::<Token![at]>()?;
+    let key = parser.next::<Token![literal]>()?.value().to_owned();
+    parser.next::<Token![;]>()?;
+    Ok(Statement::Split(SplitStatement { shard, key }))
+}
+
+// Syntax:
+// FORMAT <format:ident>
+fn parse_format_stmt(parser: &mut Parser) -> ParseResult<Statement> {
+    parser.next::<Token![format]>()?;
+    let ident = parser.next::<Token![ident]>()?;
+    let format = match ident.value() {
+        "table" | "TABLE" => OutputFormat::Table,
+        "json" | "JSON" => OutputFormat::Json,
+        "tsv" | "TSV" => OutputFormat::Tsv,
+        others => {
+            return Err(ParseError::Unknown(format!("output format `{others}`"), ident.coord()))
+        }
+    };
+    parser.next::<Token![;]>()?;
+    Ok(Statement::Format(FormatStatement { format }))
 }
 
 // Syntax:
@@ -191,8 +369,31 @@ fn parse_help_stmt(parser: &mut Parser) -> ParseResult<Statement> {
 
 // Syntax:
 // DEBUG <statement>
+// DEBUG SCHEDULE NOW
+// DEBUG SEARCH <prefix:literal> FROM <group-id:ident>
+// DEBUG VERIFY <property:ident>
 fn parse_debug_stmt(parser: &mut Parser) -> ParseResult<Statement> {
     parser.next::<Token![debug]>()?;
+    if parser.peek::<Token![schedule]>() {
+        parser.next::<Token![schedule]>()?;
+        parser.next::<Token![now]>()?;
+        parser.next::<Token![;]>()?;
+        return Ok(Statement::DebugSchedule(DebugScheduleStatement {}));
+    }
+    if parser.peek::<Token![search]>() {
+        parser.next::<Token![search]>()?;
+        let key_prefix = parser.next::<Token![literal]>()?.value().to_owned();
+        parser.next::<Token![from]>()?;
+        let group = parser.next::<Token![ident]>()?.value().to_owned();
+        parser.next::<Token![;]>()?;
+        return Ok(Statement::DebugSearch(DebugSearchStatement { key_prefix, group }));
+    }
+    if parser.peek::<Token![verify]>() {
+        parser.next::<Token![verify]>()?;
+        let property = parser.next::<Token![ident]>()?.value().to_owned();
+        parser.next::<Token![;]>()?;
+        return Ok(Statement::DebugVerify(DebugVerifyStatement { property }));
+    }
 
     let Some(stmt) = parser.parse()? else {
         return Err(ParseError::UnexpectedEOS("statement".to_owned()));
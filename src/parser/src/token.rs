@@ -57,22 +57,45 @@ macro_rules! keyword {
     };
 }
 
+keyword!(alter);
+keyword!(approve);
+keyword!(as);
+keyword!(at);
+keyword!(clone);
+keyword!(compact);
 keyword!(config);
 keyword!(create);
 keyword!(database);
 keyword!(debug);
 keyword!(delete);
+keyword!(desc);
 keyword!(echo);
 keyword!(exists);
+keyword!(format);
 keyword!(from);
 keyword!(get);
 keyword!(help);
 keyword!(if);
 keyword!(into);
+keyword!(kill);
+keyword!(limit);
 keyword!(not);
+keyword!(now);
+keyword!(of);
+keyword!(on);
 keyword!(put);
+keyword!(scan);
+keyword!(schedule);
+keyword!(search);
+keyword!(set);
+keyword!(shard);
 keyword!(show);
+keyword!(split);
+keyword!(stale);
 keyword!(table);
+keyword!(ttl);
+keyword!(txn);
+keyword!(verify);
 
 macro_rules! symbol {
     ($name:ident, $value:literal) => {
@@ -250,22 +273,45 @@ impl<'a> TokenRule<'a> for Literal<'a> {
 #[macro_export]
 macro_rules! Token {
     // keywords
+    [alter] =>          { $crate::token::Alter };
+    [approve] =>        { $crate::token::Approve };
+    [as] =>             { $crate::token::As };
+    [at] =>             { $crate::token::At };
+    [clone] =>          { $crate::token::Clone };
+    [compact] =>        { $crate::token::Compact };
     [config] =>         { $crate::token::Config };
     [create] =>         { $crate::token::Create };
     [database] =>       { $crate::token::Database };
     [debug] =>          { $crate::token::Debug };
     [delete] =>         { $crate::token::Delete };
+    [desc] =>           { $crate::token::Desc };
     [echo] =>           { $crate::token::Echo };
     [exists] =>         { $crate::token::Exists };
+    [format] =>         { $crate::token::Format };
     [from] =>           { $crate::token::From };
     [get] =>            { $crate::token::Get };
     [help] =>           { $crate::token::Help };
     [if] =>             { $crate::token::If };
     [into] =>           { $crate::token::Into };
+    [kill] =>           { $crate::token::Kill };
+    [limit] =>          { $crate::token::Limit };
     [not] =>            { $crate::token::Not };
+    [now] =>            { $crate::token::Now };
+    [of] =>             { $crate::token::Of };
+    [on] =>             { $crate::token::On };
     [put] =>            { $crate::token::Put };
+    [scan] =>           { $crate::token::Scan };
+    [schedule] =>       { $crate::token::Schedule };
+    [search] =>         { $crate::token::Search };
+    [set] =>            { $crate::token::Set };
+    [shard] =>          { $crate::token::Shard };
+    [split] =>          { $crate::token::Split };
     [table] =>          { $crate::token::Table };
+    [ttl] =>            { $crate::token::Ttl };
+    [txn] =>            { $crate::token::Txn };
     [show] =>           { $crate::token::Show };
+    [stale] =>          { $crate::token::Stale };
+    [verify] =>         { $crate::token::Verify };
 
     // symbols
     [.] =>              { $crate::token::Dot };
@@ -20,3 +20,50 @@ pub fn escape_bytes(bytes: &[u8]) -> String {
     String::from_utf8(bytes.iter().flat_map(|&b| std::ascii::escape_default(b)).collect::<Vec<_>>())
         .expect("all bytes are escaped")
 }
+
+/// Reverts [`escape_bytes`], `None` is returned if the input contains a
+/// malformed escape sequence.
+pub fn unescape_bytes(escaped: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut iter = escaped.bytes();
+    while let Some(b) = iter.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        let unescaped = match iter.next()? {
+            b't' => b'\t',
+            b'r' => b'\r',
+            b'n' => b'\n',
+            b'x' => {
+                let high = (iter.next()? as char).to_digit(16)?;
+                let low = (iter.next()? as char).to_digit(16)?;
+                (high << 4 | low) as u8
+            }
+            c @ (b'\\' | b'\'' | b'"') => c,
+            _ => return None,
+        };
+        bytes.push(unescaped);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_and_unescape_bytes() {
+        let cases: &[&[u8]] =
+            &[b"", b"abc", b"a\tb\nc\r", b"'\"\\", b"\x00\x7F\x80\xFF", "中文".as_bytes()];
+        for &input in cases {
+            let escaped = escape_bytes(input);
+            assert!(escaped.bytes().all(|b| b.is_ascii_graphic()), "{escaped}");
+            assert_eq!(unescape_bytes(&escaped).as_deref(), Some(input), "{escaped}");
+        }
+
+        for malformed in ["\\", "\\x1", "\\xZZ", "\\a"] {
+            assert!(unescape_bytes(malformed).is_none(), "{malformed}");
+        }
+    }
+}
@@ -19,6 +19,7 @@ mod group;
 mod incoming;
 mod shutdown;
 
+pub mod sim;
 pub mod sync;
 pub mod time;
 
@@ -0,0 +1,462 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The virtual clock of simulation.
+//!
+//! The virtual time only moves forward by [`advance`] or
+//! [`advance_to_next_timer`], so the timers registered by [`sleep`],
+//! [`timeout`] and [`interval`] are fired in the order of their deadlines no
+//! matter how long the wall clock takes. If the simulation is not enabled,
+//! these timers are backed by tokio.
+//!
+//! The wall clock observed by [`unix_time`] is virtual too, it starts from a
+//! fixed point once the simulation is enabled, so the timestamps are
+//! reproducible.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+pub use tokio::time::MissedTickBehavior;
+
+/// The virtual wall clock starts from 2024-01-01T00:00:00Z.
+const VIRTUAL_UNIX_EPOCH: Duration = Duration::from_secs(1_704_067_200);
+
+lazy_static! {
+    static ref CLOCK: Clock = Clock::default();
+}
+
+#[derive(Default)]
+struct Clock {
+    enabled: AtomicBool,
+    state: Mutex<ClockState>,
+}
+
+#[derive(Default)]
+struct ClockState {
+    /// The instant of the wall clock when the simulation is enabled.
+    base: Option<std::time::Instant>,
+    /// The elapsed virtual time since the simulation is enabled.
+    elapsed: Duration,
+    next_timer_id: u64,
+    timers: BTreeMap<(std::time::Instant, u64), Waker>,
+}
+
+impl ClockState {
+    fn now(&self) -> std::time::Instant {
+        self.base.expect("the simulation is enabled") + self.elapsed
+    }
+
+    /// Fire all timers whose deadline is reached.
+    fn fire_timers(&mut self) {
+        let now = self.now();
+        while let Some(entry) = self.timers.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            entry.remove().wake();
+        }
+    }
+}
+
+/// Enable the virtual clock, the virtual time starts from the current wall
+/// clock.
+pub fn enable() {
+    let mut state = CLOCK.state.lock().unwrap();
+    state.base = Some(std::time::Instant::now());
+    state.elapsed = Duration::ZERO;
+    CLOCK.enabled.store(true, Ordering::Release);
+}
+
+/// Disable the virtual clock, all pending timers are fired.
+pub fn disable() {
+    let mut state = CLOCK.state.lock().unwrap();
+    CLOCK.enabled.store(false, Ordering::Release);
+    for (_, waker) in std::mem::take(&mut state.timers) {
+        waker.wake();
+    }
+}
+
+/// Whether the virtual clock is enabled.
+#[inline]
+pub fn is_enabled() -> bool {
+    CLOCK.enabled.load(Ordering::Acquire)
+}
+
+/// The elapsed virtual time since the simulation is enabled.
+pub fn elapsed() -> Duration {
+    CLOCK.state.lock().unwrap().elapsed
+}
+
+/// The duration since the unix epoch, it is virtual if the simulation is
+/// enabled.
+pub fn unix_time() -> Duration {
+    if is_enabled() {
+        return VIRTUAL_UNIX_EPOCH + elapsed();
+    }
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default()
+}
+
+/// Advance the virtual time and fire the timers whose deadline is reached.
+pub fn advance(duration: Duration) {
+    let mut state = CLOCK.state.lock().unwrap();
+    state.elapsed += duration;
+    state.fire_timers();
+}
+
+/// Advance the virtual time to the deadline of the next timer, but no more
+/// than `max_step`. Returns false if there is no any pending timer.
+pub fn advance_to_next_timer(max_step: Duration) -> bool {
+    let mut state = CLOCK.state.lock().unwrap();
+    let Some(&(deadline, _)) = state.timers.keys().next() else {
+        return false;
+    };
+    let step = deadline.saturating_duration_since(state.now()).min(max_step);
+    state.elapsed += step;
+    state.fire_timers();
+    true
+}
+
+/// A measurement of the virtual clock if the simulation is enabled, or the
+/// monotonically nondecreasing clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(std::time::Instant);
+
+impl Instant {
+    pub fn now() -> Instant {
+        if is_enabled() {
+            let state = CLOCK.state.lock().unwrap();
+            if state.base.is_some() {
+                return Instant(state.now());
+            }
+        }
+        Instant(std::time::Instant::now())
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().saturating_duration_since(*self)
+    }
+
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.0.duration_since(earlier.0)
+    }
+
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_duration_since(earlier.0)
+    }
+
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.0.saturating_duration_since(earlier.0)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(duration).map(Instant)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(duration).map(Instant)
+    }
+
+    pub fn from_std(instant: std::time::Instant) -> Instant {
+        Instant(instant)
+    }
+
+    pub fn into_std(self) -> std::time::Instant {
+        self.0
+    }
+}
+
+impl From<std::time::Instant> for Instant {
+    fn from(instant: std::time::Instant) -> Self {
+        Instant(instant)
+    }
+}
+
+impl std::ops::Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        Instant(self.0 + rhs)
+    }
+}
+
+impl std::ops::AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, rhs: Duration) {
+        self.0 += rhs;
+    }
+}
+
+impl std::ops::Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, rhs: Duration) -> Instant {
+        Instant(self.0 - rhs)
+    }
+}
+
+impl std::ops::Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.saturating_duration_since(rhs)
+    }
+}
+
+/// Future returned by [`sleep`] and [`sleep_until`].
+pub struct Sleep {
+    deadline: Instant,
+    kind: SleepKind,
+}
+
+enum SleepKind {
+    Real(Pin<Box<tokio::time::Sleep>>),
+    Virtual { timer_id: Option<u64> },
+}
+
+/// Waits until `duration` has elapsed.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Waits until `deadline` is reached.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    let kind = if is_enabled() {
+        SleepKind::Virtual { timer_id: None }
+    } else {
+        SleepKind::Real(Box::pin(tokio::time::sleep_until(deadline.0.into())))
+    };
+    Sleep { deadline, kind }
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let timer_id = match &mut this.kind {
+            SleepKind::Real(sleep) => return sleep.as_mut().poll(cx),
+            SleepKind::Virtual { timer_id } => timer_id,
+        };
+
+        let mut state = CLOCK.state.lock().unwrap();
+        if let Some(id) = timer_id.take() {
+            state.timers.remove(&(this.deadline.0, id));
+        }
+        if !is_enabled() || state.now() >= this.deadline.0 {
+            return Poll::Ready(());
+        }
+        let id = state.next_timer_id;
+        state.next_timer_id += 1;
+        state.timers.insert((this.deadline.0, id), cx.waker().clone());
+        *timer_id = Some(id);
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let SleepKind::Virtual { timer_id: Some(id) } = &self.kind {
+            let mut state = CLOCK.state.lock().unwrap();
+            state.timers.remove(&(self.deadline.0, *id));
+        }
+    }
+}
+
+/// Error returned by [`timeout`] and [`timeout_at`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("deadline has elapsed")]
+pub struct Elapsed(());
+
+/// Future returned by [`timeout`] and [`timeout_at`].
+#[pin_project::pin_project]
+pub struct Timeout<F> {
+    #[pin]
+    future: F,
+    delay: Sleep,
+}
+
+/// Requires a `Future` to complete before the specified duration has elapsed.
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout { future, delay: sleep(duration) }
+}
+
+/// Requires a `Future` to complete before the specified instant is reached.
+pub fn timeout_at<F: Future>(deadline: Instant, future: F) -> Timeout<F> {
+    Timeout { future, delay: sleep_until(deadline) }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(output) = this.future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(this.delay).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed(()))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Interval returned by [`interval`].
+pub struct Interval {
+    period: Duration,
+    delay: Sleep,
+    missed_tick_behavior: MissedTickBehavior,
+}
+
+/// Creates new [`Interval`] that yields with interval of `period`. The first
+/// tick completes immediately.
+pub fn interval(period: Duration) -> Interval {
+    assert!(period > Duration::ZERO, "`period` must be non-zero.");
+    Interval {
+        period,
+        delay: sleep_until(Instant::now()),
+        missed_tick_behavior: MissedTickBehavior::Burst,
+    }
+}
+
+impl Interval {
+    pub async fn tick(&mut self) -> Instant {
+        futures::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if Pin::new(&mut self.delay).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let timeout = self.delay.deadline();
+        let now = Instant::now();
+        let next = if now > timeout + self.period {
+            match self.missed_tick_behavior {
+                MissedTickBehavior::Burst => timeout + self.period,
+                MissedTickBehavior::Delay => now + self.period,
+                MissedTickBehavior::Skip => {
+                    let periods = (now - timeout).as_nanos() / self.period.as_nanos();
+                    timeout + self.period * (periods as u32 + 1)
+                }
+            }
+        } else {
+            timeout + self.period
+        };
+        self.delay = sleep_until(next);
+        Poll::Ready(timeout)
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    fn poll_once<F: Future + Unpin>(future: &mut F) -> Poll<F::Output> {
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        Pin::new(future).poll(&mut cx)
+    }
+
+    #[test]
+    fn virtual_sleep_fires_in_order() {
+        let _guard = crate::sim::tests::serial();
+        enable();
+        let start = Instant::now();
+        let mut short = sleep(Duration::from_secs(1));
+        let mut long = sleep(Duration::from_secs(60));
+        assert!(poll_once(&mut short).is_pending());
+        assert!(poll_once(&mut long).is_pending());
+
+        // The wall clock does not affect the virtual timers.
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(poll_once(&mut short).is_pending());
+
+        assert!(advance_to_next_timer(Duration::from_secs(3600)));
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert!(poll_once(&mut short).is_ready());
+        assert!(poll_once(&mut long).is_pending());
+
+        advance(Duration::from_secs(59));
+        assert!(poll_once(&mut long).is_ready());
+        assert!(!advance_to_next_timer(Duration::from_secs(1)));
+        assert_eq!(unix_time(), VIRTUAL_UNIX_EPOCH + Duration::from_secs(60));
+        disable();
+    }
+
+    #[test]
+    fn virtual_timeout_and_interval() {
+        let _guard = crate::sim::tests::serial();
+        enable();
+        let pending = futures::future::pending::<()>();
+        let mut future = Box::pin(timeout(Duration::from_millis(500), pending));
+        assert!(poll_once(&mut future).is_pending());
+        advance(Duration::from_millis(500));
+        assert!(matches!(poll_once(&mut future), Poll::Ready(Err(Elapsed(())))));
+
+        let mut ticker = interval(Duration::from_millis(100));
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(ticker.poll_tick(&mut cx).is_ready());
+        assert!(ticker.poll_tick(&mut cx).is_pending());
+        advance(Duration::from_millis(100));
+        assert!(ticker.poll_tick(&mut cx).is_ready());
+        assert!(ticker.poll_tick(&mut cx).is_pending());
+        disable();
+    }
+
+    #[test]
+    fn disable_fires_pending_timers() {
+        let _guard = crate::sim::tests::serial();
+        enable();
+        let fired = Arc::new(AtomicBool::new(false));
+        let fired_clone = fired.clone();
+        let handle = std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            rt.block_on(sleep(Duration::from_secs(3600)));
+            fired_clone.store(true, Ordering::Release);
+        });
+        while CLOCK.state.lock().unwrap().timers.is_empty() {
+            std::thread::yield_now();
+        }
+        assert!(!fired.load(Ordering::Acquire));
+        disable();
+        handle.join().unwrap();
+        assert!(fired.load(Ordering::Acquire));
+    }
+}
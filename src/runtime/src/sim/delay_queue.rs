@@ -0,0 +1,187 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A queue of delayed elements driven by the timers of [`super::clock`].
+//!
+//! It provides the subset of `tokio_util::time::DelayQueue` used by servers,
+//! so the elements expire by the virtual time once the simulation is enabled.
+
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use super::clock::{sleep_until, Instant, Sleep};
+
+/// Token to a value stored in a [`DelayQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key(u64);
+
+/// An entry in [`DelayQueue`] that has expired and been removed.
+#[derive(Debug)]
+pub struct Expired<T> {
+    data: T,
+    deadline: Instant,
+    key: Key,
+}
+
+impl<T> Expired<T> {
+    pub fn get_ref(&self) -> &T {
+        &self.data
+    }
+
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    pub fn key(&self) -> Key {
+        self.key
+    }
+}
+
+/// A queue of delayed elements.
+pub struct DelayQueue<T> {
+    next_key: u64,
+    entries: HashMap<Key, (Instant, T)>,
+    deadlines: BTreeSet<(Instant, Key)>,
+    delay: Option<Sleep>,
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        DelayQueue::new()
+    }
+}
+
+impl<T> DelayQueue<T> {
+    pub fn new() -> Self {
+        DelayQueue {
+            next_key: 0,
+            entries: HashMap::default(),
+            deadlines: BTreeSet::default(),
+            delay: None,
+        }
+    }
+
+    /// Insert `value` into the queue, it expires after `timeout`.
+    pub fn insert(&mut self, value: T, timeout: Duration) -> Key {
+        self.insert_at(value, Instant::now() + timeout)
+    }
+
+    /// Insert `value` into the queue, it expires at `when`.
+    pub fn insert_at(&mut self, value: T, when: Instant) -> Key {
+        let key = Key(self.next_key);
+        self.next_key += 1;
+        self.entries.insert(key, (when, value));
+        self.deadlines.insert((when, key));
+        key
+    }
+
+    /// Reset the deadline of the element.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `key` is not contained by the queue.
+    pub fn reset_at(&mut self, key: &Key, when: Instant) {
+        let (deadline, _) = self.entries.get_mut(key).expect("invalid key");
+        self.deadlines.remove(&(*deadline, *key));
+        *deadline = when;
+        self.deadlines.insert((when, *key));
+    }
+
+    /// Remove the element from the queue.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `key` is not contained by the queue.
+    pub fn remove(&mut self, key: &Key) -> Expired<T> {
+        let (deadline, data) = self.entries.remove(key).expect("invalid key");
+        self.deadlines.remove(&(deadline, *key));
+        Expired { data, deadline, key: *key }
+    }
+
+    /// Clear the queue.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.deadlines.clear();
+        self.delay = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Attempt to pull out the next expired element. It returns
+    /// `Poll::Ready(None)` if the queue is empty.
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<Option<Expired<T>>> {
+        loop {
+            let Some(&(deadline, key)) = self.deadlines.first() else {
+                self.delay = None;
+                return Poll::Ready(None);
+            };
+            if deadline <= Instant::now() {
+                self.delay = None;
+                return Poll::Ready(Some(self.remove(&key)));
+            }
+            if self.delay.as_ref().map(Sleep::deadline) != Some(deadline) {
+                self.delay = Some(sleep_until(deadline));
+            }
+            let delay = self.delay.as_mut().expect("the delay is armed");
+            if Pin::new(delay).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::clock;
+
+    #[test]
+    fn expire_by_virtual_time() {
+        let _guard = crate::sim::tests::serial();
+        clock::enable();
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut queue = DelayQueue::new();
+        let first = queue.insert(1, Duration::from_secs(10));
+        queue.insert(2, Duration::from_secs(5));
+        assert!(queue.poll_expired(&mut cx).is_pending());
+
+        clock::advance(Duration::from_secs(5));
+        let expired = match queue.poll_expired(&mut cx) {
+            Poll::Ready(Some(expired)) => expired.into_inner(),
+            _ => panic!("the element should be expired"),
+        };
+        assert_eq!(expired, 2);
+        assert!(queue.poll_expired(&mut cx).is_pending());
+
+        queue.reset_at(&first, Instant::now());
+        assert!(matches!(queue.poll_expired(&mut cx), Poll::Ready(Some(_))));
+        assert!(matches!(queue.poll_expired(&mut cx), Poll::Ready(None)));
+        clock::disable();
+    }
+}
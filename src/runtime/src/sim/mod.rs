@@ -0,0 +1,81 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The simulation supports for testing.
+//!
+//! The simulation is only available with the `simulation` feature. Once it is
+//! enabled by [`enable`], the timers, instants and timestamps of
+//! [`crate::time`] are driven by a virtual clock instead of the wall clock, the
+//! random decisions made by [`rng`] come from a generator seeded by the
+//! simulation, and the messages between nodes are routed by the faults
+//! injected in [`net`]. Without the feature, [`net::route`] always delivers
+//! messages immediately and [`rng`] is backed by the thread local generator.
+//!
+//! The simulation doesn't own the executor nor the transport: the tasks are
+//! still scheduled by tokio and the messages are still sent by gRPC over
+//! loopback. So a seed replays the virtual timestamps, the random decisions
+//! and the injected faults, but not the interleaving of tasks. A failure
+//! which depends on the interleaving is not reproduced by the seed. The tests
+//! are faster since the timers fire without waiting for the wall clock, but
+//! they are still bounded by the real I/O of the servers.
+
+#[cfg(feature = "simulation")]
+pub mod clock;
+#[cfg(feature = "simulation")]
+pub mod delay_queue;
+pub mod net;
+pub mod rng;
+
+/// Enable the simulation, the random decisions are made by random number
+/// generators seeded by `seed`.
+#[cfg(feature = "simulation")]
+pub fn enable(seed: u64) {
+    clock::enable();
+    rng::enable(seed);
+    net::enable(seed);
+}
+
+/// Disable the simulation, all pending virtual timers are fired.
+#[cfg(feature = "simulation")]
+pub fn disable() {
+    net::disable();
+    rng::disable();
+    clock::disable();
+}
+
+/// Whether the simulation is enabled.
+#[cfg(feature = "simulation")]
+#[inline]
+pub fn is_enabled() -> bool {
+    clock::is_enabled()
+}
+
+/// Whether the simulation is enabled.
+#[cfg(not(feature = "simulation"))]
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    false
+}
+
+#[cfg(all(test, feature = "simulation"))]
+pub(crate) mod tests {
+    use std::sync::{Mutex, MutexGuard};
+
+    /// The simulation is shared by the whole process, so the tests which
+    /// enable it are serialized.
+    pub(crate) fn serial() -> MutexGuard<'static, ()> {
+        static SERIAL: Mutex<()> = Mutex::new(());
+        SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The network fault injection of simulation.
+//!
+//! The faults are injected by the pair of node addresses. The transport layer
+//! asks [`route`] for the delivery of each message, the decisions of the
+//! random faults are made by a seeded random number generator, so the same
+//! seed makes the same decisions for the same sequence of messages.
+
+use std::time::Duration;
+
+/// The delivery of a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Deliver,
+    Drop,
+    Delay(Duration),
+}
+
+/// Decide the delivery of a message sent from `from` to `to`.
+#[cfg(not(feature = "simulation"))]
+#[inline(always)]
+pub fn route(_from: &str, _to: &str) -> Delivery {
+    Delivery::Deliver
+}
+
+#[cfg(feature = "simulation")]
+lazy_static::lazy_static! {
+    static ref NETWORK: std::sync::Mutex<Option<Network>> = std::sync::Mutex::new(None);
+}
+
+/// The fault injected to the messages between a pair of nodes.
+#[cfg(feature = "simulation")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// All messages are dropped.
+    Partition,
+    /// The messages are dropped with the probability.
+    Drop(f64),
+    /// The messages are delayed.
+    Delay(Duration),
+}
+
+#[cfg(feature = "simulation")]
+struct Network {
+    rng: rand::rngs::StdRng,
+    faults: std::collections::HashMap<(String, String), Fault>,
+}
+
+#[cfg(feature = "simulation")]
+pub(super) fn enable(seed: u64) {
+    use rand::SeedableRng;
+
+    let rng = rand::rngs::StdRng::seed_from_u64(seed);
+    *NETWORK.lock().unwrap() = Some(Network { rng, faults: Default::default() });
+}
+
+#[cfg(feature = "simulation")]
+pub(super) fn disable() {
+    *NETWORK.lock().unwrap() = None;
+}
+
+/// Decide the delivery of a message sent from `from` to `to`.
+#[cfg(feature = "simulation")]
+pub fn route(from: &str, to: &str) -> Delivery {
+    use rand::Rng;
+
+    let mut network = NETWORK.lock().unwrap();
+    let Some(network) = network.as_mut() else {
+        return Delivery::Deliver;
+    };
+    match network.faults.get(&(from.to_owned(), to.to_owned())).copied() {
+        None => Delivery::Deliver,
+        Some(Fault::Partition) => Delivery::Drop,
+        Some(Fault::Drop(probability)) if network.rng.gen_bool(probability) => Delivery::Drop,
+        Some(Fault::Drop(_)) => Delivery::Deliver,
+        Some(Fault::Delay(duration)) => Delivery::Delay(duration),
+    }
+}
+
+/// Inject the fault to the messages sent from `from` to `to`.
+#[cfg(feature = "simulation")]
+pub fn inject(from: &str, to: &str, fault: Fault) {
+    with_network(|network| {
+        network.faults.insert((from.to_owned(), to.to_owned()), fault);
+    });
+}
+
+/// Partition the two nodes in both directions.
+#[cfg(feature = "simulation")]
+pub fn partition(a: &str, b: &str) {
+    inject(a, b, Fault::Partition);
+    inject(b, a, Fault::Partition);
+}
+
+/// Remove the faults between the two nodes in both directions.
+#[cfg(feature = "simulation")]
+pub fn heal(a: &str, b: &str) {
+    with_network(|network| {
+        network.faults.remove(&(a.to_owned(), b.to_owned()));
+        network.faults.remove(&(b.to_owned(), a.to_owned()));
+    });
+}
+
+/// Remove all injected faults.
+#[cfg(feature = "simulation")]
+pub fn heal_all() {
+    with_network(|network| network.faults.clear());
+}
+
+#[cfg(feature = "simulation")]
+fn with_network<F: FnOnce(&mut Network)>(f: F) {
+    let mut network = NETWORK.lock().unwrap();
+    f(network.as_mut().expect("the simulation is not enabled"));
+}
+
+#[cfg(all(test, feature = "simulation"))]
+mod tests {
+    use super::*;
+
+    fn decisions(seed: u64) -> Vec<Delivery> {
+        enable(seed);
+        inject("a", "b", Fault::Drop(0.5));
+        let decisions = (0..64).map(|_| route("a", "b")).collect();
+        disable();
+        decisions
+    }
+
+    #[test]
+    fn network_faults() {
+        let _guard = crate::sim::tests::serial();
+
+        // The random faults are reproducible by seed.
+        let first = decisions(1);
+        assert!(first.contains(&Delivery::Drop));
+        assert!(first.contains(&Delivery::Deliver));
+        assert_eq!(first, decisions(1));
+
+        enable(2);
+        partition("a", "b");
+        assert_eq!(route("a", "b"), Delivery::Drop);
+        assert_eq!(route("b", "a"), Delivery::Drop);
+        assert_eq!(route("a", "c"), Delivery::Deliver);
+        inject("a", "c", Fault::Delay(Duration::from_millis(10)));
+        assert_eq!(route("a", "c"), Delivery::Delay(Duration::from_millis(10)));
+        heal("a", "b");
+        assert_eq!(route("a", "b"), Delivery::Deliver);
+        heal_all();
+        assert_eq!(route("a", "c"), Delivery::Deliver);
+        disable();
+        assert_eq!(route("a", "b"), Delivery::Deliver);
+    }
+}
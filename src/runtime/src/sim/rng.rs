@@ -0,0 +1,97 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The random number generator of simulation.
+//!
+//! The random decisions of servers and clients should be made by [`random`]
+//! and [`gen_range`], they are seeded by the simulation once it is enabled, and
+//! backed by the thread local generator otherwise.
+
+use std::ops::Range;
+
+use rand::distributions::uniform::SampleUniform;
+use rand::distributions::{Distribution, Standard};
+use rand::Rng;
+
+#[cfg(feature = "simulation")]
+lazy_static::lazy_static! {
+    static ref RNG: std::sync::Mutex<Option<rand::rngs::StdRng>> = std::sync::Mutex::new(None);
+}
+
+#[cfg(feature = "simulation")]
+pub(super) fn enable(seed: u64) {
+    use rand::SeedableRng;
+
+    *RNG.lock().unwrap() = Some(rand::rngs::StdRng::seed_from_u64(seed));
+}
+
+#[cfg(feature = "simulation")]
+pub(super) fn disable() {
+    *RNG.lock().unwrap() = None;
+}
+
+/// Generate a random value.
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    with_rng(|rng| rng.gen())
+}
+
+/// Generate a random value in the range `[low, high)`.
+pub fn gen_range<T>(range: Range<T>) -> T
+where
+    T: SampleUniform + PartialOrd,
+{
+    with_rng(|rng| rng.gen_range(range))
+}
+
+#[cfg(feature = "simulation")]
+fn with_rng<F, T>(f: F) -> T
+where
+    F: FnOnce(&mut dyn rand::RngCore) -> T,
+{
+    if let Some(rng) = RNG.lock().unwrap().as_mut() {
+        return f(rng);
+    }
+    f(&mut rand::thread_rng())
+}
+
+#[cfg(not(feature = "simulation"))]
+#[inline(always)]
+fn with_rng<F, T>(f: F) -> T
+where
+    F: FnOnce(&mut dyn rand::RngCore) -> T,
+{
+    f(&mut rand::thread_rng())
+}
+
+#[cfg(all(test, feature = "simulation"))]
+mod tests {
+    use super::*;
+
+    fn sequence(seed: u64) -> Vec<u64> {
+        enable(seed);
+        let values = (0..16).map(|_| gen_range(0..1000)).collect();
+        disable();
+        values
+    }
+
+    #[test]
+    fn random_is_reproducible_by_seed() {
+        let _guard = crate::sim::tests::serial();
+        assert_eq!(sequence(1), sequence(1));
+        assert_ne!(sequence(1), sequence(2));
+    }
+}
@@ -12,4 +12,61 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-pub use tokio::time::{sleep, timeout, timeout_at};
+
+//! The time utilities.
+//!
+//! With the `simulation` feature, the timers, instants and timestamps are
+//! driven by the virtual clock of `sim::clock` once the simulation is enabled.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(not(feature = "simulation"))]
+pub use tokio::time::{
+    error::Elapsed, interval, sleep, sleep_until, timeout, timeout_at, Instant, Interval,
+    MissedTickBehavior, Sleep, Timeout,
+};
+#[cfg(not(feature = "simulation"))]
+pub use tokio_util::time::delay_queue;
+
+#[cfg(feature = "simulation")]
+pub use crate::sim::clock::{
+    interval, sleep, sleep_until, timeout, timeout_at, Elapsed, Instant, Interval,
+    MissedTickBehavior, Sleep, Timeout,
+};
+#[cfg(feature = "simulation")]
+pub use crate::sim::delay_queue;
+
+/// The current time of the wall clock.
+#[inline]
+pub fn system_time() -> SystemTime {
+    UNIX_EPOCH + unix_time()
+}
+
+/// The duration since the unix epoch.
+#[cfg(not(feature = "simulation"))]
+#[inline]
+pub fn unix_time() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// The duration since the unix epoch.
+#[cfg(feature = "simulation")]
+#[inline]
+pub fn unix_time() -> Duration {
+    crate::sim::clock::unix_time()
+}
+
+/// See [`sekas_rock::time::timestamp_nanos`], it is virtual once the
+/// simulation is enabled.
+#[inline]
+pub fn timestamp_nanos() -> u64 {
+    if crate::sim::is_enabled() {
+        return unix_time().as_nanos() as u64;
+    }
+    sekas_rock::time::timestamp_nanos()
+}
+
+#[inline]
+pub fn timestamp_millis() -> u64 {
+    timestamp_nanos() / 1000 / 1000
+}
@@ -0,0 +1,133 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The encoding of the keys of buckets, which are the logical namespaces
+//! within a table.
+//!
+//! A bucketed key is the escaped bucket name, a separator and the user key. The
+//! `0x00` in the bucket name is escaped as `0x00 0xFF`, and the separator is
+//! `0x00 0x01`, so the separator never appears in an escaped name, and the
+//! prefix of a bucket is never a prefix of the prefix of another bucket.
+
+const ESCAPE: u8 = 0x00;
+const ESCAPED_ESCAPE: u8 = 0xFF;
+const SEPARATOR: [u8; 2] = [0x00, 0x01];
+
+/// Returns the escaped bucket name followed by the separator, which is the
+/// prefix of all keys of the bucket.
+pub fn escape_bucket(bucket: &[u8]) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(bucket.len() + SEPARATOR.len());
+    for &b in bucket {
+        prefix.push(b);
+        if b == ESCAPE {
+            prefix.push(ESCAPED_ESCAPE);
+        }
+    }
+    prefix.extend_from_slice(&SEPARATOR);
+    prefix
+}
+
+/// Returns the key of the user key in the bucket.
+pub fn bucket_key(bucket: &[u8], user_key: &[u8]) -> Vec<u8> {
+    let mut key = escape_bucket(bucket);
+    key.extend_from_slice(user_key);
+    key
+}
+
+/// Splits the bucketed key into the bucket name and the user key, `None` is
+/// returned if the key is not a bucketed key.
+pub fn unescape_bucket(key: &[u8]) -> Option<(Vec<u8>, &[u8])> {
+    let mut bucket = Vec::new();
+    let mut i = 0;
+    while i < key.len() {
+        let b = key[i];
+        if b != ESCAPE {
+            bucket.push(b);
+            i += 1;
+            continue;
+        }
+        match key.get(i + 1).cloned()? {
+            ESCAPED_ESCAPE => bucket.push(ESCAPE),
+            b if b == SEPARATOR[1] => return Some((bucket, &key[i + 2..])),
+            _ => return None,
+        }
+        i += 2;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_key_round_trip() {
+        let buckets: &[&[u8]] = &[
+            b"",
+            b"bucket",
+            b"\x00",
+            b"\x00\x00",
+            b"\x00\x01",
+            b"\x00\xFF",
+            b"a\x00\x01b",
+            b"\xFF\xFF",
+        ];
+        for &bucket in buckets {
+            for user_key in [b"".as_slice(), b"key", b"\x00\x01", b"\x00\xFF"] {
+                let key = bucket_key(bucket, user_key);
+                assert_eq!(unescape_bucket(&key), Some((bucket.to_vec(), user_key)), "{key:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn bucket_prefix_not_overlap() {
+        // The bucket names include the separator and the escape.
+        let buckets: &[&[u8]] = &[
+            b"",
+            b"a",
+            b"a\x00",
+            b"a\x00\x01",
+            b"a\x00\x01b",
+            b"a\x00\xFF",
+            b"a\x01",
+            b"ab",
+            b"\x00",
+            b"\x00\x01",
+        ];
+        for &left in buckets {
+            for &right in buckets {
+                if left == right {
+                    continue;
+                }
+                let (left_prefix, right_prefix) = (escape_bucket(left), escape_bucket(right));
+                assert!(!right_prefix.starts_with(&left_prefix), "{left:?} and {right:?}");
+
+                // The user keys of a bucket are never decoded as of another one.
+                for user_key in [b"".as_slice(), b"\x00\x01", b"b", b"\x00\xFFb"] {
+                    let key = bucket_key(left, user_key);
+                    assert!(!key.starts_with(&right_prefix), "{left:?} {user_key:?} {right:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unescape_invalid_key() {
+        let keys: &[&[u8]] = &[b"", b"bucket", b"bucket\x00", b"bucket\x00\x02key", b"\x00"];
+        for &key in keys {
+            assert_eq!(unescape_bucket(key), None, "{key:?}");
+        }
+    }
+}
@@ -23,3 +23,45 @@ pub const REPLICATION_ASYNC: &str = "async";
 pub const TABLE_TYPE: &str = "table_type";
 pub const TABLE_TYPE_SYSTEM: &str = "system";
 pub const TABLE_TYPE_USER: &str = "user";
+
+/// The number of read replicas of the groups serving the table.
+pub const READ_REPLICAS: &str = "read_replicas";
+
+/// The max number of committed versions retained per key, the older versions
+/// beneath the GC watermark are dropped by compaction, even if they are still
+/// visible at the watermark.
+pub const MAX_VERSIONS: &str = "max_versions";
+
+/// The time to live of the values written into the table, in seconds. The
+/// values committed since it is set expire after it, the shorter one is used if
+/// the write has its own TTL.
+pub const TTL: &str = "ttl";
+
+/// The id of the source table which the table is cloning from. The table is
+/// read-only until the clone finishes and the property is removed.
+pub const CLONE_SOURCE: &str = "clone_source";
+
+/// The table is being provisioned by `Database::create_table_with_seed`, the
+/// value is the time it is created at, in millis, filled by root. It is
+/// removed once the seed rows are committed, the table still marked after
+/// `root.provisioning_timeout_sec` is deleted by root.
+pub const PROVISIONING: &str = "provisioning";
+
+/// The zone preferred to place the leaders of the groups serving the table, it
+/// is a label of the nodes, eg. `us-east`. The groups serving the tables with
+/// different preferences follow the majority of their shards.
+pub const LEADER_PREFERENCE: &str = "leader_preference";
+
+/// The label of the nodes that host read replicas.
+pub const NODE_LABEL_ANALYTICS: &str = "analytics";
+
+/// The quotas of a database, they are the properties of the database which
+/// override the cluster defaults, see `CONFIG` and `ALTER DATABASE`. The value
+/// is a u64 numeric, `0` means unlimited.
+pub const MAX_TABLES_PER_DATABASE: &str = "max_tables_per_database";
+pub const MAX_SHARDS_PER_TABLE: &str = "max_shards_per_table";
+pub const MAX_SHARDS_PER_DATABASE: &str = "max_shards_per_database";
+
+/// All quotas of a database.
+pub const QUOTA_LIMITS: [&str; 3] =
+    [MAX_TABLES_PER_DATABASE, MAX_SHARDS_PER_TABLE, MAX_SHARDS_PER_DATABASE];
@@ -15,6 +15,117 @@
 
 use sekas_api::server::v1::*;
 
+/// The error of an invalid shard range, or an invalid partitioning of the
+/// shards of a table.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum PartitionError {
+    #[error("the range of shard {0} is missing")]
+    MissingRange(u64),
+    #[error("the range of shard {id} is empty, start {start:?}, end {end:?}")]
+    EmptyRange { id: u64, start: Vec<u8>, end: Vec<u8> },
+    #[error("the split key {key:?} is not inside the range of shard {id}")]
+    InvalidSplitKey { id: u64, key: Vec<u8> },
+    #[error("no shards to partition")]
+    NoShards,
+    #[error("shard {id} belongs to table {table_id}, expect table {expect}")]
+    TableMismatch { id: u64, table_id: u64, expect: u64 },
+    #[error("shard {0} and shard {1} are overlapped")]
+    Overlapped(u64, u64),
+    #[error("the key space next to shard {0} is not covered")]
+    Uncovered(u64),
+}
+
+/// The constructors of [`ShardDesc`].
+pub trait ShardDescBuilder: Sized {
+    /// Build a shard which covers the whole key space of the table.
+    fn whole_range(id: u64, table_id: u64) -> Self;
+
+    /// Build a shard of range `[start, end)`, an empty `end` means the end of
+    /// the key space. The range must not be empty.
+    fn range(id: u64, table_id: u64, start: Vec<u8>, end: Vec<u8>) -> Result<Self, PartitionError>;
+}
+
+impl ShardDescBuilder for ShardDesc {
+    fn whole_range(id: u64, table_id: u64) -> Self {
+        let range = RangePartition { start: SHARD_MIN.to_owned(), end: SHARD_MAX.to_owned() };
+        ShardDesc { id, table_id, range: Some(range) }
+    }
+
+    fn range(id: u64, table_id: u64, start: Vec<u8>, end: Vec<u8>) -> Result<Self, PartitionError> {
+        if !end.is_empty() && start >= end {
+            return Err(PartitionError::EmptyRange { id, start, end });
+        }
+        Ok(ShardDesc { id, table_id, range: Some(RangePartition { start, end }) })
+    }
+}
+
+/// Split the shard at `key`, returns the left shard `[start, key)` which keeps
+/// the id of the shard, and the right shard `[key, end)` with `new_shard_id`.
+///
+/// The `key` must be inside the range and not equal to the start, so neither
+/// shard is empty.
+pub fn split_range(
+    desc: &ShardDesc,
+    new_shard_id: u64,
+    key: Vec<u8>,
+) -> Result<(ShardDesc, ShardDesc), PartitionError> {
+    let range = desc.range.as_ref().ok_or(PartitionError::MissingRange(desc.id))?;
+    if !belong_to(desc, &key) || range.start == key {
+        return Err(PartitionError::InvalidSplitKey { id: desc.id, key });
+    }
+    let left = ShardDesc::range(desc.id, desc.table_id, range.start.clone(), key.clone())?;
+    let right = ShardDesc::range(new_shard_id, desc.table_id, key, range.end.clone())?;
+    Ok((left, right))
+}
+
+/// Validate that the shards of a table are not empty, not overlapped, and
+/// cover the whole key space contiguously. The shards could be in any order.
+pub fn validate_partitioning(shards: &[ShardDesc]) -> Result<(), PartitionError> {
+    let first = shards.first().ok_or(PartitionError::NoShards)?;
+    let mut ranges = Vec::with_capacity(shards.len());
+    for shard in shards {
+        if shard.table_id != first.table_id {
+            return Err(PartitionError::TableMismatch {
+                id: shard.id,
+                table_id: shard.table_id,
+                expect: first.table_id,
+            });
+        }
+        let range = shard.range.as_ref().ok_or(PartitionError::MissingRange(shard.id))?;
+        if !range.end.is_empty() && range.start >= range.end {
+            return Err(PartitionError::EmptyRange {
+                id: shard.id,
+                start: range.start.clone(),
+                end: range.end.clone(),
+            });
+        }
+        ranges.push((shard.id, range));
+    }
+    ranges.sort_by(|(_, lhs), (_, rhs)| lhs.start.cmp(&rhs.start));
+
+    // The id and the end of the previous shard.
+    let mut prev: Option<(u64, &[u8])> = None;
+    for (id, range) in ranges {
+        match prev {
+            None if range.start != *SHARD_MIN => return Err(PartitionError::Uncovered(id)),
+            Some((prev_id, prev_end))
+                if prev_end.is_empty() || range.start.as_slice() < prev_end =>
+            {
+                return Err(PartitionError::Overlapped(prev_id, id));
+            }
+            Some((_, prev_end)) if range.start.as_slice() > prev_end => {
+                return Err(PartitionError::Uncovered(id));
+            }
+            _ => {}
+        }
+        prev = Some((id, range.end.as_slice()));
+    }
+    match prev {
+        Some((id, end)) if end != SHARD_MAX.as_slice() => Err(PartitionError::Uncovered(id)),
+        _ => Ok(()),
+    }
+}
+
 lazy_static::lazy_static! {
     pub static ref SHARD_MIN: Vec<u8> = vec![];
     pub static ref SHARD_MAX: Vec<u8> = vec![];
@@ -24,6 +135,13 @@ pub fn in_range(start: &[u8], end: &[u8], use_key: &[u8]) -> bool {
     start <= use_key && (use_key < end || end.is_empty())
 }
 
+/// Return whether the shard belongs to the txn table. The txn records are
+/// looked up by a fixed placement, so the txn shards are fenced from being
+/// split, merged or moved.
+pub fn is_txn_shard(shard_id: u64) -> bool {
+    (crate::FIRST_TXN_SHARD_ID..crate::FIRST_USER_SHARD_ID).contains(&shard_id)
+}
+
 /// Return whether a user key belongs to the corresponding shard.
 pub fn belong_to(shard: &ShardDesc, user_key: &[u8]) -> bool {
     shard
@@ -33,6 +151,34 @@ pub fn belong_to(shard: &ShardDesc, user_key: &[u8]) -> bool {
         .unwrap_or_default()
 }
 
+/// Return whether the ranges of two shards are overlapped.
+pub fn is_overlapped(lhs: &ShardDesc, rhs: &ShardDesc) -> bool {
+    match (lhs.range.as_ref(), rhs.range.as_ref()) {
+        (Some(lhs), Some(rhs)) => {
+            (lhs.start < rhs.end || rhs.end.is_empty())
+                && (rhs.start < lhs.end || lhs.end.is_empty())
+        }
+        _ => false,
+    }
+}
+
+/// Return whether the keys just before the exclusive `end_key` belong to the
+/// corresponding shard, `None` means the end of key space. It is used to
+/// locate shards in reverse order.
+pub fn belong_to_before(shard: &ShardDesc, end_key: Option<&[u8]>) -> bool {
+    shard
+        .range
+        .as_ref()
+        .map(|range| match end_key {
+            Some(end_key) => {
+                range.start.as_slice() < end_key
+                    && (end_key <= range.end.as_slice() || range.end.is_empty())
+            }
+            None => range.end.is_empty(),
+        })
+        .unwrap_or_default()
+}
+
 /// Return the start key of the corresponding shard.
 #[inline]
 pub fn start_key(shard: &ShardDesc) -> Vec<u8> {
@@ -46,3 +192,119 @@ pub fn start_key(shard: &ShardDesc) -> Vec<u8> {
 pub fn end_key(shard: &ShardDesc) -> Vec<u8> {
     shard.range.as_ref().map(|range| range.end.clone()).unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    fn shard(id: u64, start: &[u8], end: &[u8]) -> ShardDesc {
+        ShardDesc::range(id, 1, start.to_vec(), end.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn shard_desc_range() {
+        assert!(ShardDesc::range(1, 1, b"a".to_vec(), b"b".to_vec()).is_ok());
+        assert!(ShardDesc::range(1, 1, b"a".to_vec(), vec![]).is_ok());
+        assert!(ShardDesc::range(1, 1, vec![], vec![]).is_ok());
+        assert!(matches!(
+            ShardDesc::range(1, 1, b"b".to_vec(), b"a".to_vec()),
+            Err(PartitionError::EmptyRange { .. })
+        ));
+        assert!(matches!(
+            ShardDesc::range(1, 1, b"a".to_vec(), b"a".to_vec()),
+            Err(PartitionError::EmptyRange { .. })
+        ));
+    }
+
+    #[test]
+    fn split_shard_range() {
+        let desc = ShardDesc::whole_range(1, 1);
+        let (left, right) = split_range(&desc, 2, b"m".to_vec()).unwrap();
+        assert_eq!(left, shard(1, b"", b"m"));
+        assert_eq!(right, shard(2, b"m", b""));
+
+        let desc = shard(1, b"b", b"d");
+        for key in [b"a".as_slice(), b"b", b"d", b"e"] {
+            assert!(matches!(
+                split_range(&desc, 2, key.to_vec()),
+                Err(PartitionError::InvalidSplitKey { .. })
+            ));
+        }
+        let missing = ShardDesc { id: 1, table_id: 1, range: None };
+        assert_eq!(split_range(&missing, 2, b"a".to_vec()), Err(PartitionError::MissingRange(1)));
+    }
+
+    #[test]
+    fn validate_shard_partitioning() {
+        assert_eq!(validate_partitioning(&[]), Err(PartitionError::NoShards));
+        assert!(validate_partitioning(&[ShardDesc::whole_range(1, 1)]).is_ok());
+        assert!(validate_partitioning(&[
+            shard(3, b"c", b""),
+            shard(1, b"", b"a"),
+            shard(2, b"a", b"c")
+        ])
+        .is_ok());
+
+        assert_eq!(
+            validate_partitioning(&[shard(1, b"a", b"")]),
+            Err(PartitionError::Uncovered(1))
+        );
+        assert_eq!(
+            validate_partitioning(&[shard(1, b"", b"a")]),
+            Err(PartitionError::Uncovered(1))
+        );
+        assert_eq!(
+            validate_partitioning(&[shard(1, b"", b"a"), shard(2, b"b", b"")]),
+            Err(PartitionError::Uncovered(2))
+        );
+        assert_eq!(
+            validate_partitioning(&[shard(1, b"", b"b"), shard(2, b"a", b"")]),
+            Err(PartitionError::Overlapped(1, 2))
+        );
+        assert_eq!(
+            validate_partitioning(&[shard(1, b"", b""), shard(2, b"a", b"")]),
+            Err(PartitionError::Overlapped(1, 2))
+        );
+        assert!(matches!(
+            validate_partitioning(&[
+                shard(1, b"", b"a"),
+                ShardDesc::range(2, 2, b"a".to_vec(), vec![]).unwrap()
+            ]),
+            Err(PartitionError::TableMismatch { id: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn random_split_sequences_keep_partitioning() {
+        for seed in 0..64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut shards = vec![ShardDesc::whole_range(1, 1)];
+            let mut next_shard_id = 2;
+            for _ in 0..128 {
+                let index = rng.gen_range(0..shards.len());
+                let key_len = rng.gen_range(0..4);
+                let key = (0..key_len).map(|_| rng.gen_range(b'a'..=b'e')).collect::<Vec<_>>();
+                match split_range(&shards[index], next_shard_id, key.clone()) {
+                    Ok((left, right)) => {
+                        assert!(belong_to(&right, &key));
+                        assert!(!belong_to(&left, &key));
+                        shards[index] = left;
+                        shards.push(right);
+                        next_shard_id += 1;
+                    }
+                    Err(PartitionError::InvalidSplitKey { .. }) => {
+                        let desc = &shards[index];
+                        assert!(!belong_to(desc, &key) || start_key(desc) == key);
+                    }
+                    Err(err) => panic!("split shard {:?} at {key:?}: {err}", shards[index]),
+                }
+                if let Err(err) = validate_partitioning(&shards) {
+                    panic!("seed {seed}, shards {shards:?}: {err}");
+                }
+            }
+        }
+    }
+}
@@ -29,6 +29,8 @@ pub fn unity_table_shards() -> Vec<ShardDesc> {
         table::replica_state_shard_desc(),
         table::job_shard_desc(),
         table::job_history_shard_desc(),
+        table::recommendation_shard_desc(),
+        table::topology_event_shard_desc(),
         table::txn_shard_desc(),
     ]
 }
@@ -44,6 +46,8 @@ pub fn tables() -> Vec<TableDesc> {
         table::replica_state_desc(),
         table::job_desc(),
         table::job_history_desc(),
+        table::recommendation_desc(),
+        table::topology_event_desc(),
         table::txn_desc(),
     ]
 }
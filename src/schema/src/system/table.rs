@@ -16,6 +16,7 @@ use std::collections::HashMap;
 use paste::paste;
 use sekas_api::server::v1::*;
 
+use crate::shard::ShardDescBuilder;
 use crate::LOCAL_TABLE_ID;
 
 macro_rules! decl_unity_range_table {
@@ -31,18 +32,12 @@ macro_rules! decl_unity_range_table {
                     name: stringify!($name).to_owned(),
                     db: crate::system::db::ID,
                     properties: default_system_properties(),
+                    ..Default::default()
                 }
             }
 
             pub fn [<$name:lower _shard_desc>]() -> ShardDesc {
-                ShardDesc {
-                    id: $table_id,
-                    table_id: $table_id,
-                    range: Some(RangePartition {
-                        start: crate::shard::SHARD_MIN.to_owned(),
-                        end: crate::shard::SHARD_MAX.to_owned(),
-                    }),
-                }
+                ShardDesc::whole_range($table_id, $table_id)
             }
         }
     };
@@ -58,6 +53,8 @@ decl_unity_range_table!(group, 5);
 decl_unity_range_table!(replica_state, 6);
 decl_unity_range_table!(job, 7);
 decl_unity_range_table!(job_history, 8);
+decl_unity_range_table!(recommendation, 9);
+decl_unity_range_table!(topology_event, 10);
 decl_unity_range_table!(end_unity_table, 100);
 
 decl_unity_range_table!(txn, crate::FIRST_TXN_SHARD_ID);
@@ -81,6 +78,13 @@ pub fn txn_table_id() -> u64 {
     TXN_ID
 }
 
+/// Whether the writes of the table are reserved to the servers. The txn table
+/// is excluded, since the clients write the txn records by the txn protocol.
+#[inline]
+pub fn is_reserved_table(table_id: u64) -> bool {
+    table_id < crate::FIRST_USER_TABLE_ID && table_id != TXN_ID
+}
+
 /// Get the default properties of system table.
 fn default_system_properties() -> HashMap<String, String> {
     use crate::property::*;
@@ -27,5 +27,16 @@ fn main() -> Result<(), Box<dyn Error>> {
         &["proto/v1/metadata.proto", "proto/v1/raft.proto"],
         &["proto", "proto/include", "../api/"],
     )?;
+
+    // The git hash is compiled in, it is reported by the node status.
+    let git_hash = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=SEKAS_GIT_HASH={git_hash}");
     Ok(())
 }
@@ -17,32 +17,61 @@ use std::sync::Arc;
 use std::time::Duration;
 use std::vec;
 
-use log::{debug, info, warn};
+use log::{debug, error, info, warn};
 use sekas_api::server::v1::node_server::NodeServer;
 use sekas_api::server::v1::root_server::RootServer;
 use sekas_api::server::v1::*;
 use sekas_client::RootClient;
 use sekas_runtime::{Executor, Shutdown};
+use tokio::sync::mpsc;
 
+use crate::compat::{describe_rejection, SERVER_VERSION, SUPPORTED_FEATURES};
 use crate::constants::*;
-use crate::engine::{Engines, StateEngine};
+use crate::engine::{prepare_data_dir, Engines, StateEngine};
 use crate::node::Node;
 use crate::root::Root;
 use crate::serverpb::v1::raft_server::RaftServer;
 use crate::serverpb::v1::NodeIdent;
+use crate::service::exporter::MetricsExporter;
 use crate::transport::TransportManager;
 use crate::{Config, Error, Result, Server};
 
 /// The main entrance of sekas server.
 pub fn run(config: Config, executor: Executor, shutdown: Shutdown) -> Result<()> {
-    executor.block_on(async { run_in_async(config, shutdown).await })
+    executor.block_on(async { run_in_async(config, shutdown, None).await })
 }
 
-async fn run_in_async(config: Config, shutdown: Shutdown) -> Result<()> {
+/// The main entrance of sekas server, the configs received from `reloads` are
+/// applied to the running server, see [`Config::reload`].
+pub fn run_with_reload(
+    config: Config,
+    executor: Executor,
+    shutdown: Shutdown,
+    reloads: mpsc::UnboundedReceiver<Config>,
+) -> Result<()> {
+    executor.block_on(async { run_in_async(config, shutdown, Some(reloads)).await })
+}
+
+async fn run_in_async(
+    config: Config,
+    shutdown: Shutdown,
+    reloads: Option<mpsc::UnboundedReceiver<Config>>,
+) -> Result<()> {
+    config.validate()?;
+    // Fail fast if the data dir is written by an incompatible binary, before any
+    // replica is recovered.
+    prepare_data_dir(&config.root_dir)?;
+    let exporter = MetricsExporter::bind(&config.metrics).await?;
     let engines = Engines::open(&config.root_dir, &config.db)?;
 
     let root_list = if config.init { vec![config.addr.clone()] } else { config.join_list.clone() };
-    let transport_manager = TransportManager::new(root_list, engines.state()).await;
+    let cluster_secret = match config.cluster_secret.as_ref() {
+        Some(secret) => secret.clone(),
+        None => uuid::Uuid::new_v4().simple().to_string(),
+    };
+    let transport_manager =
+        TransportManager::new(root_list, engines.state(), Some(&cluster_secret)).await?;
+    transport_manager.conn_manager().set_max_message_bytes(config.node.max_message_bytes);
     let address_resolver = transport_manager.address_resolver();
     let node = Node::new(config.clone(), engines, transport_manager.clone()).await?;
 
@@ -55,7 +84,28 @@ async fn run_in_async(config: Config, shutdown: Shutdown) -> Result<()> {
     info!("node {} starts serving requests", ident.node_id);
 
     let server = Server { node: Arc::new(node), root, address_resolver };
-    bootstrap_services(&config, server, &transport_manager, shutdown).await
+    if let Some(reloads) = reloads {
+        sekas_runtime::spawn(reload_config(config.clone(), server.node.clone(), reloads));
+    }
+    bootstrap_services(&config, server, &transport_manager, exporter, shutdown).await
+}
+
+/// Apply the hot-reloadable settings of the received configs, the invalid
+/// configs are ignored.
+async fn reload_config(
+    mut config: Config,
+    node: Arc<Node>,
+    mut reloads: mpsc::UnboundedReceiver<Config>,
+) {
+    while let Some(new_config) = reloads.recv().await {
+        if let Err(err) = new_config.validate() {
+            warn!("the reloaded config is ignored: {err}");
+            continue;
+        }
+        config = config.reload(&new_config);
+        node.reload_config(&config.node);
+        info!("config is reloaded");
+    }
 }
 
 /// Listen and serve incoming rpc requests.
@@ -63,6 +113,7 @@ async fn bootstrap_services(
     cfg: &Config,
     server: Server,
     _transport_manager: &TransportManager,
+    exporter: Option<MetricsExporter>,
     shutdown: Shutdown,
 ) -> Result<()> {
     use sekas_runtime::TcpIncoming;
@@ -70,20 +121,27 @@ async fn bootstrap_services(
     use tonic::transport::Server;
 
     use crate::service::admin::make_admin_service;
+    use crate::service::message_limit::MessageLimitService;
 
     let listener = TcpListener::bind(&cfg.addr).await?;
     let incoming = TcpIncoming::from_listener(listener, true);
+    if let Some(exporter) = exporter {
+        exporter.set_ready(server.clone());
+    }
 
     let builder = Server::builder()
         .accept_http1(true) // Support http1 for admin service.
-        .add_service(NodeServer::new(server.clone()))
+        .add_service(MessageLimitService::new(
+            NodeServer::new(server.clone()),
+            server.node.max_message_bytes(),
+        ))
         .add_service(RaftServer::new(server.clone()))
         .add_service(RootServer::new(server.clone()))
         .add_service(make_admin_service(server.clone()));
 
     #[cfg(feature = "layer_etcd")]
     let builder = {
-        let client = _transport_manager.build_client(sekas_client::ClientOptions::default());
+        let client = _transport_manager.build_proxy_client(sekas_client::ClientOptions::default());
         let kv_store = sekas_etcd_proxy::make_etcd_store(client);
         builder.add_service(sekas_etcd_proxy::make_etcd_kv_service(kv_store.clone()))
     };
@@ -111,35 +169,49 @@ async fn bootstrap_or_join_cluster(
     }
 
     Ok(if config.init {
-        bootstrap_cluster(node, &config.addr).await?
+        bootstrap_cluster(node, &config.addr, config.cluster_id.as_deref()).await?
     } else {
-        try_join_cluster(node, &config.addr, config.join_list.clone(), config.cpu_nums, root_client)
-            .await?
+        try_join_cluster(node, config, root_client).await?
     })
 }
 
 async fn try_join_cluster(
     node: &Node,
-    local_addr: &str,
-    join_list: Vec<String>,
-    cpu_nums: u32,
+    config: &Config,
     root_client: &RootClient,
 ) -> Result<NodeIdent> {
     info!("try join a bootstrapted cluster");
 
-    let join_list = join_list.into_iter().filter(|addr| *addr != local_addr).collect::<Vec<_>>();
+    let local_addr = config.addr.as_str();
+    let join_list =
+        config.join_list.iter().filter(|addr| *addr != local_addr).cloned().collect::<Vec<_>>();
     if join_list.is_empty() {
         return Err(Error::InvalidArgument("the filtered join list is empty".into()));
     }
 
-    let capacity = NodeCapacity { cpu_nums: cpu_nums as f64, ..Default::default() };
-
-    let req = JoinNodeRequest { addr: local_addr.to_owned(), capacity: Some(capacity) };
+    let capacity = NodeCapacity { cpu_nums: config.cpu_nums as f64, ..Default::default() };
+    let version = config.node.testing_knobs.fake_version.as_deref().unwrap_or(SERVER_VERSION);
+
+    // The node joins a cluster only if its data dir is not initialized, so
+    // there is no cluster id recorded, the configured one is sent if any.
+    let req = JoinNodeRequest {
+        addr: local_addr.to_owned(),
+        capacity: Some(capacity),
+        version: version.to_owned(),
+        features: SUPPORTED_FEATURES,
+        cluster_id: config.cluster_id.clone().unwrap_or_default().into_bytes(),
+        labels: config.node.labels.clone(),
+    };
 
     let mut backoff: u64 = 1;
     loop {
         info!("try send request to root server");
         match root_client.join_node(req.clone()).await {
+            Ok(JoinNodeResponse { rejection: Some(rejection), .. }) => {
+                let reason = describe_rejection(&rejection);
+                error!("node version {version} is rejected by the cluster: {reason}");
+                return Err(Error::JoinRejected(reason));
+            }
             Ok(res) => {
                 debug!("issue join request to root server success");
                 let node_ident =
@@ -156,14 +228,21 @@ async fn try_join_cluster(
     }
 }
 
-pub(crate) async fn bootstrap_cluster(node: &Node, addr: &str) -> Result<NodeIdent> {
+pub(crate) async fn bootstrap_cluster(
+    node: &Node,
+    addr: &str,
+    cluster_id: Option<&str>,
+) -> Result<NodeIdent> {
     info!("'--init' is specified, try bootstrap cluster");
 
     // TODO(walter) clean staled data in db.
     write_initial_cluster_data(node, addr).await?;
 
     let state_engine = node.state_engine();
-    let cluster_id = vec![];
+    let cluster_id = match cluster_id {
+        Some(cluster_id) => cluster_id.as_bytes().to_vec(),
+        None => uuid::Uuid::new_v4().to_string().into_bytes(),
+    };
 
     let ident = save_node_ident(state_engine, cluster_id.to_owned(), FIRST_NODE_ID).await?;
 
@@ -0,0 +1,403 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The encryption of data at rest and in transit.
+//!
+//! The engine doesn't support encrypting its own files, so only the data
+//! leaving the engine are encrypted, eg. the snapshot files and the shard
+//! chunks pulled by the dest group of moving shard. The data are sealed by
+//! ChaCha20-Poly1305, so any tampering is detected before they are applied.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+use chacha20poly1305::ChaCha20Poly1305;
+use prost::Message;
+use rand::rngs::OsRng;
+use rand::Rng;
+use sekas_api::server::v1::{SealedData, ShardScanResponse, ValueSet};
+
+use crate::{EncryptionConfig, Error, Result};
+
+/// The length of the key material.
+pub const KEY_LEN: usize = 32;
+
+/// The length of the nonce.
+pub const NONCE_LEN: usize = 12;
+
+/// The length of the authentication tag appended to the sealed data.
+pub const TAG_LEN: usize = 16;
+
+const FILE_BLOCK_SIZE: usize = 64 * 1024;
+
+pub type Nonce = [u8; NONCE_LEN];
+
+/// A cipher to seal and open the data with an AEAD.
+///
+/// The `(key_id, nonce)` must not be reused to seal different data.
+pub trait DataCipher: Send + Sync {
+    /// The id of key to encrypt the new data.
+    fn current_key_id(&self) -> String;
+
+    /// Whether the key is available to decrypt the data.
+    fn contains_key(&self, key_id: &str) -> bool;
+
+    /// Encrypt the data in place and append the tag, the `aad` is
+    /// authenticated but not encrypted. [`Error::KeyNotFound`] is returned if
+    /// the key is not available.
+    fn seal(&self, key_id: &str, nonce: &Nonce, aad: &[u8], data: &mut Vec<u8>) -> Result<()>;
+
+    /// Verify and decrypt the sealed data in place. [`Error::KeyNotFound`] is
+    /// returned if the key is not available, and [`Error::InvalidData`] if
+    /// the data or `aad` is tampered.
+    fn open(&self, key_id: &str, nonce: &Nonce, aad: &[u8], data: &mut Vec<u8>) -> Result<()>;
+}
+
+/// The provider of the key material, eg. a file or a KMS.
+pub trait KeyProvider: Send + Sync {
+    /// The id of key to encrypt the new data. The former keys are kept for
+    /// reading the data encrypted before rotating.
+    fn current_key_id(&self) -> String;
+
+    /// Get the key material, [`Error::KeyNotFound`] is returned if the key
+    /// doesn't exist.
+    fn key(&self, key_id: &str) -> Result<[u8; KEY_LEN]>;
+}
+
+/// A cipher based on ChaCha20-Poly1305, the keys are fetched from the provider.
+pub struct AeadCipher {
+    provider: Box<dyn KeyProvider>,
+}
+
+/// A provider which loads the keys from a file, each line of the file is a
+/// key in form of `<key-id>:<hex encoded key>`. The last one is the current
+/// key, so a key is rotated by appending a new line and restarting the node.
+pub struct FileKeyProvider {
+    current_key_id: String,
+    keys: HashMap<String, [u8; KEY_LEN]>,
+}
+
+impl AeadCipher {
+    pub fn new(provider: Box<dyn KeyProvider>) -> Self {
+        AeadCipher { provider }
+    }
+
+    fn aead(&self, key_id: &str) -> Result<ChaCha20Poly1305> {
+        let key = self.provider.key(key_id)?;
+        Ok(ChaCha20Poly1305::new(&key.into()))
+    }
+}
+
+impl DataCipher for AeadCipher {
+    fn current_key_id(&self) -> String {
+        self.provider.current_key_id()
+    }
+
+    fn contains_key(&self, key_id: &str) -> bool {
+        self.provider.key(key_id).is_ok()
+    }
+
+    fn seal(&self, key_id: &str, nonce: &Nonce, aad: &[u8], data: &mut Vec<u8>) -> Result<()> {
+        self.aead(key_id)?
+            .encrypt_in_place(nonce.into(), aad, data)
+            .map_err(|_| Error::InvalidArgument(format!("seal {} bytes", data.len())))
+    }
+
+    fn open(&self, key_id: &str, nonce: &Nonce, aad: &[u8], data: &mut Vec<u8>) -> Result<()> {
+        self.aead(key_id)?
+            .decrypt_in_place(nonce.into(), aad, data)
+            .map_err(|_| Error::InvalidData(format!("open the data sealed by key {key_id}")))
+    }
+}
+
+impl FileKeyProvider {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let mut current_key_id = None;
+        let mut keys = HashMap::default();
+        for line in content.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let invalid_key = || Error::InvalidData(format!("key file {}", path.display()));
+            let (key_id, hex) = line.split_once(':').ok_or_else(invalid_key)?;
+            let key = decode_hex_key(hex.trim()).ok_or_else(invalid_key)?;
+            keys.insert(key_id.trim().to_owned(), key);
+            current_key_id = Some(key_id.trim().to_owned());
+        }
+        let current_key_id = current_key_id.ok_or_else(|| {
+            Error::InvalidArgument(format!("no key exists in key file {}", path.display()))
+        })?;
+        Ok(FileKeyProvider { current_key_id, keys })
+    }
+}
+
+impl KeyProvider for FileKeyProvider {
+    fn current_key_id(&self) -> String {
+        self.current_key_id.clone()
+    }
+
+    fn key(&self, key_id: &str) -> Result<[u8; KEY_LEN]> {
+        self.keys.get(key_id).cloned().ok_or_else(|| Error::KeyNotFound(key_id.to_owned()))
+    }
+}
+
+/// Open the cipher of data at rest, `None` is returned if the encryption is
+/// disabled.
+pub fn open_data_cipher(cfg: &EncryptionConfig) -> Result<Option<Arc<dyn DataCipher>>> {
+    let Some(key_file) = cfg.key_file.as_ref() else { return Ok(None) };
+    let provider = FileKeyProvider::open(key_file)?;
+    Ok(Some(Arc::new(AeadCipher::new(Box::new(provider)))))
+}
+
+/// Generate a fresh nonce. The nonces never come from the seeded rng of the
+/// simulation, otherwise they repeat across the runs with the same key.
+pub(crate) fn random_nonce() -> Nonce {
+    OsRng.gen()
+}
+
+/// Seal the value sets of a shard chunk with the current key.
+pub(crate) fn seal_value_sets(cipher: &dyn DataCipher, data: Vec<ValueSet>) -> Result<SealedData> {
+    let key_id = cipher.current_key_id();
+    let nonce = random_nonce();
+    let mut payload = ShardScanResponse { data, ..Default::default() }.encode_to_vec();
+    cipher.seal(&key_id, &nonce, &[], &mut payload)?;
+    Ok(SealedData { key_id, nonce: nonce.to_vec(), payload })
+}
+
+/// Open the value sets sealed by [`seal_value_sets`].
+pub(crate) fn open_value_sets(
+    cipher: &dyn DataCipher,
+    sealed: &SealedData,
+) -> Result<Vec<ValueSet>> {
+    let nonce = Nonce::try_from(sealed.nonce.as_slice())
+        .map_err(|_| Error::InvalidData(format!("nonce of {} bytes", sealed.nonce.len())))?;
+    let mut payload = sealed.payload.clone();
+    cipher.open(&sealed.key_id, &nonce, &[], &mut payload)?;
+    Ok(ShardScanResponse::decode(payload.as_slice())?.data)
+}
+
+/// Seal the file `src` into `dst`, block by block.
+///
+/// The nonce of each block is composed of the `file_nonce` and the index of
+/// block, and the last block is marked by the aad, so the blocks couldn't be
+/// reordered or truncated.
+pub(crate) fn encrypt_file(
+    cipher: &dyn DataCipher,
+    key_id: &str,
+    file_nonce: u64,
+    src: &Path,
+    dst: &Path,
+) -> Result<()> {
+    let mut reader = std::fs::File::open(src)?;
+    let num_blocks = num_file_blocks(reader.metadata()?.len(), FILE_BLOCK_SIZE)?;
+    let mut writer = std::fs::File::create(dst)?;
+    let mut buf = Vec::with_capacity(FILE_BLOCK_SIZE + TAG_LEN);
+    for index in 0..num_blocks {
+        buf.resize(FILE_BLOCK_SIZE, 0);
+        let num_read = read_block(&mut reader, &mut buf)?;
+        buf.truncate(num_read);
+        let is_last = index + 1 == num_blocks;
+        cipher.seal(key_id, &block_nonce(file_nonce, index), &[is_last as u8], &mut buf)?;
+        writer.write_all(&buf)?;
+    }
+    writer.sync_all()?;
+    Ok(())
+}
+
+/// Open the file `src` sealed by [`encrypt_file`] into `dst`.
+pub(crate) fn decrypt_file(
+    cipher: &dyn DataCipher,
+    key_id: &str,
+    file_nonce: u64,
+    src: &Path,
+    dst: &Path,
+) -> Result<()> {
+    let mut reader = std::fs::File::open(src)?;
+    let num_blocks = num_file_blocks(reader.metadata()?.len(), FILE_BLOCK_SIZE + TAG_LEN)?;
+    let mut writer = std::fs::File::create(dst)?;
+    let mut buf = Vec::with_capacity(FILE_BLOCK_SIZE + TAG_LEN);
+    for index in 0..num_blocks {
+        buf.resize(FILE_BLOCK_SIZE + TAG_LEN, 0);
+        let num_read = read_block(&mut reader, &mut buf)?;
+        buf.truncate(num_read);
+        let is_last = index + 1 == num_blocks;
+        cipher.open(key_id, &block_nonce(file_nonce, index), &[is_last as u8], &mut buf)?;
+        writer.write_all(&buf)?;
+    }
+    writer.sync_all()?;
+    Ok(())
+}
+
+fn block_nonce(file_nonce: u64, index: u32) -> Nonce {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[..8].copy_from_slice(&file_nonce.to_le_bytes());
+    nonce[8..].copy_from_slice(&index.to_le_bytes());
+    nonce
+}
+
+/// The number of blocks of a file, an empty file has a single empty block.
+fn num_file_blocks(file_len: u64, block_size: usize) -> Result<u32> {
+    let num_blocks = file_len.div_ceil(block_size as u64).max(1);
+    u32::try_from(num_blocks)
+        .map_err(|_| Error::InvalidArgument(format!("file of {file_len} bytes is too large")))
+}
+
+/// Read until the buffer is filled or the end of file is reached.
+fn read_block(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut num_read = 0;
+    while num_read < buf.len() {
+        match reader.read(&mut buf[num_read..]) {
+            Ok(0) => break,
+            Ok(n) => num_read += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(num_read)
+}
+
+fn decode_hex_key(hex: &str) -> Option<[u8; KEY_LEN]> {
+    if hex.len() != KEY_LEN * 2 || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0; KEY_LEN];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    fn write_key_file(dir: &Path, keys: &[(&str, u8)]) -> std::path::PathBuf {
+        let path = dir.join("keys");
+        let content = keys
+            .iter()
+            .map(|(key_id, byte)| format!("{key_id}:{}\n", format!("{byte:02x}").repeat(KEY_LEN)))
+            .collect::<String>();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn open_cipher(dir: &Path) -> AeadCipher {
+        let key_file = write_key_file(dir, &[("k1", 1), ("k2", 2)]);
+        AeadCipher::new(Box::new(FileKeyProvider::open(key_file).unwrap()))
+    }
+
+    #[test]
+    fn aead_cipher_round_trip() {
+        let dir = TempDir::new("aead-cipher-round-trip").unwrap();
+        let cipher = open_cipher(dir.path());
+        assert_eq!(cipher.current_key_id(), "k2");
+
+        let plain = (0..255u8).collect::<Vec<_>>();
+        let nonce = random_nonce();
+        let mut sealed = plain.clone();
+        cipher.seal("k1", &nonce, b"aad", &mut sealed).unwrap();
+        assert_eq!(sealed.len(), plain.len() + TAG_LEN);
+        assert_ne!(&sealed[..plain.len()], plain.as_slice());
+
+        let mut opened = sealed.clone();
+        cipher.open("k1", &nonce, b"aad", &mut opened).unwrap();
+        assert_eq!(opened, plain);
+
+        // The tampered data, aad, nonce or key are rejected.
+        let mut tampered = sealed.clone();
+        tampered[3] ^= 1;
+        assert!(matches!(
+            cipher.open("k1", &nonce, b"aad", &mut tampered),
+            Err(Error::InvalidData(_))
+        ));
+        let mut opened = sealed.clone();
+        assert!(matches!(cipher.open("k1", &nonce, b"", &mut opened), Err(Error::InvalidData(_))));
+        let mut opened = sealed.clone();
+        let other_nonce = random_nonce();
+        assert!(matches!(
+            cipher.open("k1", &other_nonce, b"aad", &mut opened),
+            Err(Error::InvalidData(_))
+        ));
+        let mut opened = sealed.clone();
+        assert!(matches!(
+            cipher.open("k2", &nonce, b"aad", &mut opened),
+            Err(Error::InvalidData(_))
+        ));
+        let mut opened = sealed;
+        assert!(matches!(
+            cipher.open("k3", &nonce, b"aad", &mut opened),
+            Err(Error::KeyNotFound(key_id)) if key_id == "k3"
+        ));
+    }
+
+    #[test]
+    fn encrypt_file_round_trip() {
+        let dir = TempDir::new("encrypt-file-round-trip").unwrap();
+        let cipher = open_cipher(dir.path());
+        let (plain_path, sealed_path, opened_path) =
+            (dir.path().join("plain"), dir.path().join("sealed"), dir.path().join("opened"));
+        for len in [0, 1, FILE_BLOCK_SIZE, FILE_BLOCK_SIZE * 2 + 7] {
+            let plain = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+            std::fs::write(&plain_path, &plain).unwrap();
+            encrypt_file(&cipher, "k2", 7, &plain_path, &sealed_path).unwrap();
+            let sealed = std::fs::read(&sealed_path).unwrap();
+            let num_blocks = len.div_ceil(FILE_BLOCK_SIZE).max(1);
+            assert_eq!(sealed.len(), len + num_blocks * TAG_LEN);
+            decrypt_file(&cipher, "k2", 7, &sealed_path, &opened_path).unwrap();
+            assert_eq!(std::fs::read(&opened_path).unwrap(), plain);
+            assert!(decrypt_file(&cipher, "k2", 8, &sealed_path, &opened_path).is_err());
+        }
+
+        // The truncated file is rejected, even if it ends at a block boundary.
+        let sealed = std::fs::read(&sealed_path).unwrap();
+        std::fs::write(&sealed_path, &sealed[..FILE_BLOCK_SIZE + TAG_LEN]).unwrap();
+        assert!(matches!(
+            decrypt_file(&cipher, "k2", 7, &sealed_path, &opened_path),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn seal_value_sets_round_trip() {
+        let dir = TempDir::new("seal-value-sets-round-trip").unwrap();
+        let cipher = open_cipher(dir.path());
+        let value_sets = (0..3u8)
+            .map(|i| ValueSet { user_key: vec![i], ..Default::default() })
+            .collect::<Vec<_>>();
+        let first = seal_value_sets(&cipher, value_sets.clone()).unwrap();
+        let second = seal_value_sets(&cipher, value_sets.clone()).unwrap();
+        assert_eq!(first.key_id, "k2");
+        assert_ne!(first.nonce, second.nonce);
+        assert_ne!(first.payload, second.payload);
+        assert_eq!(open_value_sets(&cipher, &first).unwrap(), value_sets);
+
+        let mut tampered = first;
+        tampered.payload[0] ^= 1;
+        assert!(matches!(open_value_sets(&cipher, &tampered), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn file_key_provider_invalid_key() {
+        let dir = TempDir::new("file-key-provider-invalid-key").unwrap();
+        let path = dir.path().join("keys");
+        std::fs::write(&path, "k1:0102\n").unwrap();
+        assert!(matches!(FileKeyProvider::open(&path), Err(Error::InvalidData(_))));
+        std::fs::write(&path, "\n").unwrap();
+        assert!(matches!(FileKeyProvider::open(&path), Err(Error::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The compatibility between the nodes of a cluster, which is validated by the
+//! root when a node joins.
+
+use sekas_api::server::v1::join_rejection::{MissingFeature, Reason, VersionTooOld, WrongCluster};
+use sekas_api::server::v1::{JoinNodeRequest, JoinRejection};
+
+/// The build version of this node.
+pub const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The git commit hash this node is built from.
+pub const SERVER_GIT_HASH: &str = env!("SEKAS_GIT_HASH");
+
+/// The number of minor versions a joining node could fall behind the root.
+const MAX_MINOR_VERSION_SKEW: u64 = 1;
+
+/// The replicas are able to evaluate the merge json writes.
+pub const FEATURE_MERGE_JSON: u64 = 1 << 0;
+/// The nodes report their wall clock in the heartbeat responses.
+pub const FEATURE_CLOCK_SKEW_DETECTION: u64 = 1 << 1;
+
+const FEATURE_NAMES: [(u64, &str); 2] =
+    [(FEATURE_MERGE_JSON, "merge-json"), (FEATURE_CLOCK_SKEW_DETECTION, "clock-skew-detection")];
+
+/// The features supported by this node.
+pub const SUPPORTED_FEATURES: u64 = FEATURE_MERGE_JSON | FEATURE_CLOCK_SKEW_DETECTION;
+
+/// The features a node must support to join the cluster.
+const REQUIRED_FEATURES: u64 = FEATURE_MERGE_JSON | FEATURE_CLOCK_SKEW_DETECTION;
+
+/// Validate whether the joining node is compatible with the cluster, return
+/// the reason if it is rejected.
+pub fn check_join(cluster_id: &[u8], req: &JoinNodeRequest) -> Option<JoinRejection> {
+    let reason = if !req.cluster_id.is_empty() && req.cluster_id != cluster_id {
+        Reason::WrongCluster(WrongCluster { cluster_id: cluster_id.to_owned() })
+    } else if !is_compatible_version(&req.version) {
+        Reason::VersionTooOld(VersionTooOld { min: min_compatible_version() })
+    } else {
+        let name = missing_feature(req.features)?;
+        Reason::MissingFeature(MissingFeature { name: name.to_owned() })
+    };
+    Some(JoinRejection { reason: Some(reason) })
+}
+
+/// Describe the rejection for the operators.
+pub fn describe_rejection(rejection: &JoinRejection) -> String {
+    match &rejection.reason {
+        Some(Reason::WrongCluster(r)) => format!(
+            "the node belongs to another cluster, the cluster id is {}",
+            String::from_utf8_lossy(&r.cluster_id)
+        ),
+        Some(Reason::VersionTooOld(r)) => {
+            format!("the version is too old, the min compatible version is {}", r.min)
+        }
+        Some(Reason::MissingFeature(r)) => {
+            format!("the feature {} required by the cluster is not supported", r.name)
+        }
+        None => "unknown reason".to_owned(),
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|v| v.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+fn min_compatible_version() -> String {
+    let (major, minor, _) = parse_version(SERVER_VERSION).expect("invalid server version");
+    format!("{major}.{}.0", minor.saturating_sub(MAX_MINOR_VERSION_SKEW))
+}
+
+fn is_compatible_version(version: &str) -> bool {
+    let min = parse_version(&min_compatible_version()).unwrap();
+    parse_version(version).map(|v| v >= min).unwrap_or(false)
+}
+
+/// The names of the features carried by the bits.
+pub fn feature_names(features: u64) -> Vec<String> {
+    FEATURE_NAMES
+        .iter()
+        .filter(|(bit, _)| features & bit != 0)
+        .map(|(_, name)| (*name).to_owned())
+        .collect()
+}
+
+fn missing_feature(features: u64) -> Option<&'static str> {
+    FEATURE_NAMES
+        .iter()
+        .find(|(bit, _)| REQUIRED_FEATURES & bit != 0 && features & bit == 0)
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_compatibility() {
+        let req = JoinNodeRequest {
+            version: SERVER_VERSION.to_owned(),
+            features: SUPPORTED_FEATURES,
+            ..Default::default()
+        };
+        assert!(check_join(b"cluster", &req).is_none());
+        let req = JoinNodeRequest { cluster_id: b"cluster".to_vec(), ..req };
+        assert!(check_join(b"cluster", &req).is_none());
+
+        let rejection = check_join(b"another", &req).unwrap();
+        assert!(matches!(rejection.reason, Some(Reason::WrongCluster(_))));
+
+        for version in ["0.0.1", "", "invalid", "0.5"] {
+            let req = JoinNodeRequest { version: version.to_owned(), ..req.clone() };
+            let rejection = check_join(b"cluster", &req).unwrap();
+            assert!(
+                matches!(rejection.reason, Some(Reason::VersionTooOld(_))),
+                "version {version}"
+            );
+        }
+
+        let req = JoinNodeRequest { features: FEATURE_MERGE_JSON, ..req };
+        let rejection = check_join(b"cluster", &req).unwrap();
+        assert!(matches!(
+            rejection.reason,
+            Some(Reason::MissingFeature(MissingFeature { name })) if name == "clock-skew-detection"
+        ));
+    }
+
+    #[test]
+    fn supported_feature_names() {
+        assert_eq!(feature_names(SUPPORTED_FEATURES), ["merge-json", "clock-skew-detection"]);
+        assert!(feature_names(0).is_empty());
+    }
+}
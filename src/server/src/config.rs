@@ -14,13 +14,21 @@
 // limitations under the License.
 
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::time::Duration;
 
+use log::warn;
 use rocksdb::DBCompressionType;
 use sekas_runtime::ExecutorConfig;
 use serde::{Deserialize, Serialize};
 
 use crate::constants::REPLICA_PER_GROUP;
+use crate::engine::StorageFaults;
+use crate::node::move_shard::MoveShardFaults;
+use crate::node::observer::ReplicaObservers;
+use crate::replica::fsm::ApplyFaults;
+use crate::{Error, Result};
 
 #[derive(Default, Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
@@ -37,6 +45,32 @@ pub struct Config {
 
     pub join_list: Vec<String>,
 
+    /// The id of the cluster to bootstrap or join, the node is rejected if the
+    /// cluster it joins has another id. A random id is used to bootstrap a
+    /// cluster if it is not set.
+    ///
+    /// Default: None.
+    #[serde(default)]
+    pub cluster_id: Option<String>,
+
+    /// The secret shared by the servers of the cluster. It is attached to the
+    /// requests issued by the servers, the writes to the system tables without
+    /// it are rejected. The secret is never sent to the clients, so it should
+    /// be set to the same value on all servers of the cluster. It is required
+    /// if `join_list` is not empty. A random secret is used if it is not set,
+    /// then only the requests issued by this node are trusted.
+    ///
+    /// Default: None.
+    #[serde(default)]
+    pub cluster_secret: Option<String>,
+
+    /// The log filter of the server, in form of `RUST_LOG`, such as `info` or
+    /// `info,sekas_server=debug`. It is hot-reloadable.
+    ///
+    /// Default: `RUST_LOG` or `info`.
+    #[serde(default)]
+    pub log_level: Option<String>,
+
     #[serde(default)]
     pub node: NodeConfig,
 
@@ -51,6 +85,12 @@ pub struct Config {
 
     #[serde(default)]
     pub db: DbConfig,
+
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+
+    #[serde(default)]
+    pub metrics: MetricsConfig,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -65,11 +105,83 @@ pub struct NodeConfig {
     /// Default: 256.
     pub shard_gc_keys: usize,
 
+    /// The limit bytes per second shared by pulling shard chunks during moving
+    /// shard and sending snapshots, `0` means unlimited. It is hot-reloadable.
+    ///
+    /// Default: 0.
+    #[serde(default)]
+    pub shard_move_bytes_per_sec: u64,
+
+    /// The limit number of concurrent outgoing snapshot transfers, the others
+    /// are queued until a transfer is finished. It is hot-reloadable.
+    ///
+    /// Default: 2.
+    #[serde(default = "default_snapshot_send_concurrency")]
+    pub snapshot_send_concurrency: usize,
+
+    /// The max bytes of each group request and response, the larger ones are
+    /// rejected with `MessageTooLarge`, and the scans are split into the
+    /// frames within it. It is advertised to the clients by the node
+    /// capabilities. `0` means unlimited.
+    ///
+    /// Default: 64MB.
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+
+    /// The labels of this node, the read replicas are placed on the nodes
+    /// labeled `analytics`.
+    ///
+    /// Default: [].
+    #[serde(default)]
+    pub labels: Vec<String>,
+
     #[serde(default)]
     pub replica: ReplicaConfig,
 
     #[serde(default)]
     pub engine: EngineConfig,
+
+    /// The limits of watches, they are hot-reloadable.
+    #[serde(default)]
+    pub watch: WatchConfig,
+
+    /// The limits of scans, they are hot-reloadable.
+    #[serde(default)]
+    pub scan: ScanConfig,
+
+    #[serde(default)]
+    pub clock: ClockConfig,
+
+    /// The limits of txns, they are advertised to the clients by the node
+    /// capabilities.
+    #[serde(default)]
+    pub txn: TxnConfig,
+
+    /// The limits of forwarding requests to the leaders, they are
+    /// hot-reloadable.
+    #[serde(default)]
+    pub forward: ForwardConfig,
+
+    /// The observers of the replica lifecycle, they are registered by the
+    /// binaries embedding the server.
+    #[serde(skip)]
+    pub replica_observers: ReplicaObservers,
+
+    #[serde(skip)]
+    pub testing_knobs: NodeTestingKnobs,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NodeTestingKnobs {
+    /// Override the build version sent by the node when it joins a cluster.
+    pub fake_version: Option<String>,
+    /// The faults injected into the moving shard coordinator, it requires the
+    /// `testing` feature.
+    pub move_shard_faults: MoveShardFaults,
+    /// Stall the write intent requests until it is reset, as if the groups
+    /// can't make progress. It requires the `testing` feature.
+    #[cfg(any(test, feature = "testing"))]
+    pub stall_write_intents: Arc<AtomicBool>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -77,6 +189,9 @@ pub struct ReplicaTestingKnobs {
     pub disable_scheduler_orphan_replica_detecting_intervals: bool,
     pub disable_scheduler_durable_task: bool,
     pub disable_scheduler_remove_orphan_replica_task: bool,
+    /// The faults injected into the state machine of replicas, it requires the
+    /// `testing` feature.
+    pub apply_faults: ApplyFaults,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -86,16 +201,262 @@ pub struct ReplicaConfig {
     /// Default: 64MB.
     pub snap_file_size: u64,
 
+    /// Write an apply checkpoint once the number of entries applied since the
+    /// last checkpoint exceeds this limit, the restarting replica only replays
+    /// the entries after the checkpoint. `0` means unlimited.
+    ///
+    /// Default: 1024.
+    #[serde(default = "default_apply_checkpoint_entries")]
+    pub apply_checkpoint_entries: u64,
+
+    /// Write an apply checkpoint once the bytes of the write batches applied
+    /// since the last checkpoint exceeds this limit. `0` means unlimited.
+    ///
+    /// Default: 64MB.
+    #[serde(default = "default_apply_checkpoint_bytes")]
+    pub apply_checkpoint_bytes: u64,
+
+    /// The leader resolves the intents older than it in the background, the
+    /// intents of running txns are left untouched.
+    ///
+    /// Default: 10s.
+    #[serde(default = "default_resolve_intent_age_ms")]
+    pub resolve_intent_age_ms: u64,
+
+    /// The leader advances the GC watermark of the group to the versions
+    /// older than it in the background, the versions beneath the watermark are
+    /// dropped by compaction. `0` means the watermark is never advanced.
+    ///
+    /// Default: 10min.
+    #[serde(default = "default_gc_retention_ms")]
+    pub gc_retention_ms: u64,
+
+    /// Whether to record the id of the requests into the raft entries, the
+    /// clients could override it for each request.
+    ///
+    /// Default: true.
+    #[serde(default = "default_record_request_id")]
+    pub record_request_id: bool,
+
+    /// The max duration for a new leader to verify its descriptor epoch with
+    /// root, it falls back to a quorum read of the group once exceeded.
+    ///
+    /// Default: 3s.
+    #[serde(default = "default_verify_descriptor_timeout_ms")]
+    pub verify_descriptor_timeout_ms: u64,
+
+    /// Whether to serve the raw key state requests, which expose the versions
+    /// and intent of a key to the clients for debugging.
+    ///
+    /// Default: true.
+    #[serde(default = "default_enable_get_raw_key")]
+    pub enable_get_raw_key: bool,
+
+    #[serde(default)]
+    pub hot_key: HotKeyConfig,
+
+    #[serde(default)]
+    pub proposal_queue: ProposalQueueConfig,
+
     #[serde(skip)]
     pub testing_knobs: ReplicaTestingKnobs,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HotKeyConfig {
+    /// The fraction of the requests whose keys are sampled by the leader to
+    /// detect the hot keys of each shard, `0` disables the detection.
+    ///
+    /// Default: 0.01.
+    pub sample_rate: f64,
+
+    /// The max number of keys tracked for each shard.
+    ///
+    /// Default: 64.
+    pub capacity: usize,
+
+    /// The number of the hottest keys of each shard reported to root.
+    ///
+    /// Default: 8.
+    pub top_k: usize,
+
+    /// The window of the detection, the tracked keys are reset once a window
+    /// is finished, so that the keys which are no longer hot age out.
+    ///
+    /// Default: 10s.
+    pub window_ms: u64,
+}
+
+/// The fair queuing of the proposals of each group, across the tables whose
+/// shards share the group.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProposalQueueConfig {
+    /// The max number of in-flight write proposals of each group, the
+    /// following writes are queued and admitted by deficit round robin across
+    /// tables. `0` means unlimited.
+    ///
+    /// Default: 64.
+    pub max_inflight_proposals: usize,
+
+    /// The bytes of proposals admitted for a table of weight 1 in each round
+    /// of the deficit round robin.
+    ///
+    /// Default: 64KB.
+    pub quantum_bytes: u64,
+
+    /// The weights of the tables, the tables not listed have weight 1.
+    ///
+    /// Default: [].
+    #[serde(default)]
+    pub table_weights: Vec<TableWeight>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TableWeight {
+    pub table_id: u64,
+    pub weight: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WatchConfig {
+    /// The max number of active watches of each connection.
+    ///
+    /// Default: 4096.
+    pub max_watches_per_connection: usize,
+
+    /// The max number of active watches of the node.
+    ///
+    /// Default: 65536.
+    pub max_watches_per_node: usize,
+
+    /// The max bytes of undelivered events buffered by each watch, the watch
+    /// is cancelled as lagging once it is exceeded.
+    ///
+    /// Default: 1MB.
+    pub max_buffered_bytes_per_watch: usize,
+
+    /// The max bytes of undelivered events buffered by all watches of the
+    /// node.
+    ///
+    /// Default: 256MB.
+    pub max_buffered_bytes: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScanConfig {
+    /// The max bytes of the value sets of each scan response frame, a large
+    /// scan is streamed in frames.
+    ///
+    /// Default: 1MB.
+    pub frame_bytes: usize,
+
+    /// The max bytes of the value sets held by each in-flight scan, the scan
+    /// is rejected with `ResourceExhausted` once it is exceeded.
+    ///
+    /// Default: 64MB.
+    pub max_bytes_per_scan: usize,
+
+    /// The max bytes of the value sets held by all in-flight scans of the
+    /// node.
+    ///
+    /// Default: 1GB.
+    pub max_bytes: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ForwardConfig {
+    /// The max number of requests forwarded by the followers of the node to
+    /// the leaders concurrently, the others are returned `NotLeader`. `0`
+    /// disables the forwarding.
+    ///
+    /// Default: 256.
+    pub max_concurrent_forwards: usize,
+
+    /// The timeout of the forwarded requests which don't carry a deadline.
+    ///
+    /// Default: 3000ms.
+    pub timeout_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TxnConfig {
+    /// The max bytes of the writes of a txn, the larger txns are rejected by
+    /// the clients before committing. `0` means unlimited.
+    ///
+    /// Default: 64MB.
+    pub max_write_bytes: u64,
+
+    /// The max number of the writes of a txn. `0` means unlimited.
+    ///
+    /// Default: 65536.
+    pub max_write_count: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClockConfig {
+    /// The max tolerated clock skew between the node and the cluster. A skewed
+    /// node refuses to serve new commits until the skew falls back under the
+    /// limit.
+    ///
+    /// Default: 500ms.
+    pub max_clock_skew_ms: u64,
+
+    /// Wait out the max clock skew before acknowledging the writes whose
+    /// versions are fed by the local clock.
+    ///
+    /// Default: false.
+    pub commit_wait: bool,
+
+    /// The offset added to the wall clock of the node, it is used to inject
+    /// clock skew in tests.
+    #[serde(skip)]
+    pub testing_offset_ms: i64,
+}
+
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct EngineConfig {
     /// Log slow io requests if it exceeds the specified threshold.
     ///
     /// Default: disabled
     pub engine_slow_io_threshold_ms: Option<u64>,
+
+    #[serde(skip)]
+    pub testing_knobs: EngineTestingKnobs,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EngineTestingKnobs {
+    /// The faults injected into the group engines of replicas.
+    pub storage_faults: StorageFaults,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    /// The file of keys to encrypt the snapshot files and the chunks of moving
+    /// shards, each line is a key in form of `<key-id>:<hex encoded 32 bytes>`
+    /// and the last one is used to encrypt the new data. The former keys should
+    /// be kept until the snapshots encrypted by them are recycled.
+    ///
+    /// Default: disabled
+    pub key_file: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    /// The address of the HTTP listener which exposes the metrics in the
+    /// prometheus text format at `/metrics`, the liveness at `/healthz` and
+    /// the readiness at `/readyz`.
+    ///
+    /// Default: disabled
+    #[serde(default)]
+    pub addr: Option<String>,
+
+    /// The max number of series exposed for each metric, the others are
+    /// dropped to bound the label cardinality, such as the per-group metrics.
+    ///
+    /// Default: 1024.
+    #[serde(default = "default_max_series_per_metric")]
+    pub max_series_per_metric: usize,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -176,6 +537,13 @@ pub struct RaftConfig {
     /// Default: 64KB
     pub max_io_batch_size: u64,
 
+    /// Limit the number of requests consumed and entries applied by a group in
+    /// a scheduling round, the group exceeding it (or `max_io_batch_size`) is
+    /// requeued behind the other groups of the node.
+    ///
+    /// Default: 256
+    pub max_entries_per_round: usize,
+
     /// Limit the number of inflights messages which send to one peer.
     ///
     /// Default: 10K
@@ -191,6 +559,25 @@ pub struct RaftConfig {
     /// Default: false
     pub enable_log_recycle: bool,
 
+    /// Enable pre-vote, the candidates check whether they could win the
+    /// election before incrementing the term, so a node rejoining after a
+    /// transient partition doesn't disrupt the healthy leader. It could be
+    /// changed at runtime by `CONFIG "raft.pre_vote" "<true|false>"`.
+    ///
+    /// Default: true
+    #[serde(default = "default_pre_vote")]
+    pub pre_vote: bool,
+
+    /// Enable check quorum, the leaders step down once they haven't heard
+    /// from the quorum in an election timeout, and the followers ignore the
+    /// votes while they have heard from a live leader within an election
+    /// timeout. It could be changed at runtime by `CONFIG "raft.check_quorum"
+    /// "<true|false>"`.
+    ///
+    /// Default: true
+    #[serde(default = "default_check_quorum")]
+    pub check_quorum: bool,
+
     #[serde(skip)]
     pub testing_knobs: RaftTestingKnobs,
 }
@@ -235,6 +622,339 @@ pub struct RootConfig {
     /// Default: 3s.
     pub schedule_interval_sec: u64,
     pub max_create_group_retry_before_rollback: u64,
+    /// Set the policy to execute the reconcile tasks, it could be changed at
+    /// runtime by `CONFIG "schedule_mode" "<mode>"`.
+    ///
+    /// Default: auto
+    #[serde(default)]
+    pub schedule_mode: ScheduleMode,
+    /// Always cure the groups which lost replicas, even if the scheduler isn't
+    /// in auto mode. It could be changed at runtime by `CONFIG
+    /// "schedule_auto_cure" "<true|false>"`.
+    ///
+    /// Default: true
+    #[serde(default = "default_schedule_auto_cure")]
+    pub schedule_auto_cure: bool,
+    /// Set the intervals to verify the shard coverage of tables, in seconds.
+    /// `0` disables the periodic verification, it still could be triggered by
+    /// `DEBUG VERIFY COVERAGE`.
+    ///
+    /// Default: 60s
+    #[serde(default = "default_verify_coverage_interval_sec")]
+    pub verify_coverage_interval_sec: u64,
+    /// Mirror the catalog of the primary cluster which the node of the address
+    /// belongs to. The root refuses the mutating requests until the catalog is
+    /// promoted by the `PromoteCatalog` admin request.
+    ///
+    /// Default: None
+    #[serde(default)]
+    pub catalog_mirror_of: Option<String>,
+    /// Accept the unsafe admin requests which edit the descriptors in catalog
+    /// directly, see `PutRawGroupDescRequest`.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub enable_unsafe_admin: bool,
+    /// The nodes whose cpu utilization reported by the heartbeat is above the
+    /// threshold are avoided when placing new leaders and replicas. `1.0`
+    /// disables it.
+    ///
+    /// Default: 0.8
+    #[serde(default = "default_overloaded_cpu_util")]
+    pub overloaded_cpu_util: f64,
+    /// The tables still being provisioned after the duration are deleted, as
+    /// the clients creating them are considered dead, in seconds. `0`
+    /// disables the deletion.
+    ///
+    /// Default: 600s
+    #[serde(default = "default_provisioning_timeout_sec")]
+    pub provisioning_timeout_sec: u64,
+    /// The directory of the table exports, the paths of the export and import
+    /// requests are relative to it. It should be a storage shared by the root
+    /// nodes, so the exports could be imported after the root leader changes.
+    /// The exports and imports are rejected if it is not set.
+    ///
+    /// Default: None
+    #[serde(default)]
+    pub export_dir: Option<String>,
+
+    #[serde(skip)]
+    pub testing_knobs: RootTestingKnobs,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RootTestingKnobs {
+    /// Pause the create table jobs after the shards are created and before the
+    /// table desc is written, until it is reset.
+    pub pause_before_write_table_desc: Arc<AtomicBool>,
+    /// Pause the copying of the clone table jobs, until it is reset.
+    pub pause_clone_table: Arc<AtomicBool>,
+    /// Roll back the id counters to the initial values before repairing them
+    /// once the root steps leader, as if the catalog is restored from an old
+    /// backup. It is reset after the rolling back, and it requires the
+    /// `testing` feature.
+    #[cfg(any(test, feature = "testing"))]
+    pub rollback_id_counters: Arc<AtomicBool>,
+    /// The shards larger than it are split by the auto split, `0` means the
+    /// default threshold. It requires the `testing` feature.
+    #[cfg(any(test, feature = "testing"))]
+    pub split_threshold: u64,
+}
+
+/// The policy to execute the reconcile tasks of root scheduler.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScheduleMode {
+    /// Execute the reconcile tasks automatically.
+    #[default]
+    Auto,
+    /// Record the reconcile tasks as pending recommendations, without
+    /// executing them.
+    Advise,
+    /// Record the reconcile tasks as pending recommendations, and execute them
+    /// once they are approved by operators.
+    ManualApprove,
+}
+
+impl ScheduleMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleMode::Auto => "auto",
+            ScheduleMode::Advise => "advise",
+            ScheduleMode::ManualApprove => "manual-approve",
+        }
+    }
+}
+
+impl std::str::FromStr for ScheduleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ScheduleMode::Auto),
+            "advise" => Ok(ScheduleMode::Advise),
+            "manual-approve" => Ok(ScheduleMode::ManualApprove),
+            others => Err(format!("unknown schedule mode `{others}`")),
+        }
+    }
+}
+
+impl Config {
+    /// Check the settings and the orders between them, the error names the
+    /// offending field.
+    pub fn validate(&self) -> Result<()> {
+        if self.root_dir.as_os_str().is_empty() {
+            return Err(invalid_config("root_dir", "should not be empty"));
+        }
+        match self.addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+            _ => {
+                return Err(invalid_config(
+                    "addr",
+                    format!("`{}` is not in form of `<host>:<port>`", self.addr),
+                ))
+            }
+        }
+        if matches!(&self.log_level, Some(level) if level.trim().is_empty()) {
+            return Err(invalid_config("log_level", "should not be empty"));
+        }
+        if matches!(&self.cluster_id, Some(cluster_id) if cluster_id.is_empty()) {
+            return Err(invalid_config("cluster_id", "should not be empty"));
+        }
+        if matches!(&self.cluster_secret, Some(secret)
+            if secret.is_empty() || !secret.bytes().all(|b| b.is_ascii_graphic()))
+        {
+            return Err(invalid_config(
+                "cluster_secret",
+                "should be non-empty and only contain visible ASCII characters",
+            ));
+        }
+        if !self.join_list.is_empty() && self.cluster_secret.is_none() {
+            return Err(invalid_config("cluster_secret", "is required to join a cluster"));
+        }
+        if matches!(&self.metrics.addr, Some(addr) if addr == &self.addr) {
+            return Err(invalid_config("metrics.addr", "should be different from `addr`"));
+        }
+        if self.metrics.max_series_per_metric == 0 {
+            return Err(invalid_config("metrics.max_series_per_metric", "should be positive"));
+        }
+
+        let node = &self.node;
+        if node.shard_chunk_size == 0 {
+            return Err(invalid_config("node.shard_chunk_size", "should be positive"));
+        }
+        if node.snapshot_send_concurrency == 0 {
+            return Err(invalid_config("node.snapshot_send_concurrency", "should be positive"));
+        }
+        if node.replica.snap_file_size == 0 {
+            return Err(invalid_config("node.replica.snap_file_size", "should be positive"));
+        }
+        let hot_key = &node.replica.hot_key;
+        if !(0.0..=1.0).contains(&hot_key.sample_rate) {
+            return Err(invalid_config(
+                "node.replica.hot_key.sample_rate",
+                format!("{} is not in [0, 1]", hot_key.sample_rate),
+            ));
+        }
+        check_order(
+            ("node.replica.hot_key.top_k", hot_key.top_k as u64),
+            ("node.replica.hot_key.capacity", hot_key.capacity as u64),
+        )?;
+        let proposal_queue = &node.replica.proposal_queue;
+        if proposal_queue.quantum_bytes == 0 {
+            return Err(invalid_config(
+                "node.replica.proposal_queue.quantum_bytes",
+                "should be positive",
+            ));
+        }
+        if let Some(w) = proposal_queue.table_weights.iter().find(|w| w.weight == 0) {
+            return Err(invalid_config(
+                "node.replica.proposal_queue.table_weights",
+                format!("the weight of table {} should be positive", w.table_id),
+            ));
+        }
+        check_order(
+            ("node.watch.max_watches_per_connection", node.watch.max_watches_per_connection as u64),
+            ("node.watch.max_watches_per_node", node.watch.max_watches_per_node as u64),
+        )?;
+        check_order(
+            (
+                "node.watch.max_buffered_bytes_per_watch",
+                node.watch.max_buffered_bytes_per_watch as u64,
+            ),
+            ("node.watch.max_buffered_bytes", node.watch.max_buffered_bytes as u64),
+        )?;
+        if node.scan.frame_bytes == 0 {
+            return Err(invalid_config("node.scan.frame_bytes", "should be positive"));
+        }
+        check_order(
+            ("node.scan.frame_bytes", node.scan.frame_bytes as u64),
+            ("node.scan.max_bytes_per_scan", node.scan.max_bytes_per_scan as u64),
+        )?;
+        check_order(
+            ("node.scan.max_bytes_per_scan", node.scan.max_bytes_per_scan as u64),
+            ("node.scan.max_bytes", node.scan.max_bytes as u64),
+        )?;
+
+        if self.raft.tick_interval_ms == 0 {
+            return Err(invalid_config("raft.tick_interval_ms", "should be positive"));
+        }
+        // The heartbeat tick of raft is 1.
+        if self.raft.election_tick < 2 {
+            return Err(invalid_config("raft.election_tick", "should be greater than 1"));
+        }
+        if self.raft.max_entries_per_round == 0 {
+            return Err(invalid_config("raft.max_entries_per_round", "should be positive"));
+        }
+
+        if self.root.replicas_per_group == 0 {
+            return Err(invalid_config("root.replicas_per_group", "should be positive"));
+        }
+        if self.root.heartbeat_timeout_sec >= self.root.liveness_threshold_sec {
+            return Err(invalid_config(
+                "root.heartbeat_timeout_sec",
+                format!(
+                    "{} should be less than `root.liveness_threshold_sec` ({})",
+                    self.root.heartbeat_timeout_sec, self.root.liveness_threshold_sec
+                ),
+            ));
+        }
+        let overloaded_cpu_util = self.root.overloaded_cpu_util;
+        if !(overloaded_cpu_util > 0.0 && overloaded_cpu_util <= 1.0) {
+            return Err(invalid_config(
+                "root.overloaded_cpu_util",
+                format!("{overloaded_cpu_util} is not in (0, 1]"),
+            ));
+        }
+
+        let db = &self.db;
+        check_order(
+            ("db.min_write_buffer_number_to_merge", db.min_write_buffer_number_to_merge as u64),
+            ("db.max_write_buffer_number", db.max_write_buffer_number as u64),
+        )?;
+        check_order(
+            ("db.level0_file_num_compaction_trigger", db.level0_file_num_compaction_trigger as u64),
+            ("db.level0_slowdown_writes_trigger", db.level0_slowdown_writes_trigger as u64),
+        )?;
+        check_order(
+            ("db.level0_slowdown_writes_trigger", db.level0_slowdown_writes_trigger as u64),
+            ("db.level0_stop_write_trigger", db.level0_stop_write_trigger as u64),
+        )?;
+        check_order(
+            (
+                "db.soft_pending_compaction_bytes_limit",
+                db.soft_pending_compaction_bytes_limit as u64,
+            ),
+            (
+                "db.hard_pending_compaction_bytes_limit",
+                db.hard_pending_compaction_bytes_limit as u64,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Reload the config of a running server from `new`. The hot-reloadable
+    /// settings are taken from `new`, the changes of the others only take
+    /// effect after restarting, so they are rejected with a warning. Returns
+    /// the config to apply.
+    pub fn reload(&self, new: &Config) -> Config {
+        let mut applied = self.clone();
+        applied.log_level.clone_from(&new.log_level);
+        applied.node.shard_move_bytes_per_sec = new.node.shard_move_bytes_per_sec;
+        applied.node.snapshot_send_concurrency = new.node.snapshot_send_concurrency;
+        applied.node.watch = new.node.watch.clone();
+        applied.node.scan = new.node.scan.clone();
+        applied.node.forward = new.node.forward.clone();
+        for field in applied.changed_fields(new) {
+            warn!("config `{field}` isn't hot-reloadable, the change is rejected until restarting");
+        }
+        applied
+    }
+
+    /// The fields whose values are different from `other`.
+    fn changed_fields(&self, other: &Config) -> Vec<String> {
+        fn diff(
+            path: String,
+            lhs: &serde_json::Value,
+            rhs: &serde_json::Value,
+            out: &mut Vec<String>,
+        ) {
+            use serde_json::Value;
+
+            match (lhs, rhs) {
+                (Value::Object(lhs), Value::Object(rhs)) => {
+                    for (key, value) in lhs {
+                        let path =
+                            if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+                        diff(path, value, rhs.get(key).unwrap_or(&Value::Null), out);
+                    }
+                }
+                _ if lhs != rhs => out.push(path),
+                _ => {}
+            }
+        }
+
+        let lhs = serde_json::to_value(self).expect("Config is serializable");
+        let rhs = serde_json::to_value(other).expect("Config is serializable");
+        let mut fields = Vec::default();
+        diff(String::default(), &lhs, &rhs, &mut fields);
+        fields
+    }
+}
+
+fn invalid_config(field: &str, reason: impl std::fmt::Display) -> Error {
+    Error::InvalidArgument(format!("invalid config `{field}`: {reason}"))
+}
+
+/// Check the value of the former field doesn't exceed the latter one.
+fn check_order(lower: (&str, u64), upper: (&str, u64)) -> Result<()> {
+    if lower.1 > upper.1 {
+        return Err(invalid_config(
+            lower.0,
+            format!("{} should not exceed `{}` ({})", lower.1, upper.0, upper.1),
+        ));
+    }
+    Ok(())
 }
 
 impl Default for NodeConfig {
@@ -242,8 +962,19 @@ impl Default for NodeConfig {
         NodeConfig {
             shard_chunk_size: 64 * 1024 * 1024,
             shard_gc_keys: 256,
+            shard_move_bytes_per_sec: 0,
+            snapshot_send_concurrency: default_snapshot_send_concurrency(),
+            max_message_bytes: default_max_message_bytes(),
+            labels: Vec::default(),
             replica: ReplicaConfig::default(),
             engine: EngineConfig::default(),
+            watch: WatchConfig::default(),
+            scan: ScanConfig::default(),
+            clock: ClockConfig::default(),
+            txn: TxnConfig::default(),
+            forward: ForwardConfig::default(),
+            replica_observers: ReplicaObservers::default(),
+            testing_knobs: NodeTestingKnobs::default(),
         }
     }
 }
@@ -252,11 +983,95 @@ impl Default for ReplicaConfig {
     fn default() -> Self {
         ReplicaConfig {
             snap_file_size: 64 * 1024 * 1024 * 1024,
+            apply_checkpoint_entries: default_apply_checkpoint_entries(),
+            apply_checkpoint_bytes: default_apply_checkpoint_bytes(),
+            resolve_intent_age_ms: default_resolve_intent_age_ms(),
+            gc_retention_ms: default_gc_retention_ms(),
+            record_request_id: default_record_request_id(),
+            verify_descriptor_timeout_ms: default_verify_descriptor_timeout_ms(),
+            enable_get_raw_key: default_enable_get_raw_key(),
+            hot_key: HotKeyConfig::default(),
+            proposal_queue: ProposalQueueConfig::default(),
             testing_knobs: ReplicaTestingKnobs::default(),
         }
     }
 }
 
+impl Default for HotKeyConfig {
+    fn default() -> Self {
+        HotKeyConfig { sample_rate: 0.01, capacity: 64, top_k: 8, window_ms: 10 * 1000 }
+    }
+}
+
+impl Default for ProposalQueueConfig {
+    fn default() -> Self {
+        ProposalQueueConfig {
+            max_inflight_proposals: 64,
+            quantum_bytes: 64 * 1024,
+            table_weights: Vec::default(),
+        }
+    }
+}
+
+impl ProposalQueueConfig {
+    /// The weight of the table, 1 if it is not configured.
+    pub fn table_weight(&self, table_id: u64) -> u64 {
+        self.table_weights.iter().find(|w| w.table_id == table_id).map(|w| w.weight).unwrap_or(1)
+    }
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            max_watches_per_connection: 4096,
+            max_watches_per_node: 64 * 1024,
+            max_buffered_bytes_per_watch: 1024 * 1024,
+            max_buffered_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for TxnConfig {
+    fn default() -> Self {
+        TxnConfig { max_write_bytes: 64 * 1024 * 1024, max_write_count: 64 * 1024 }
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig { addr: None, max_series_per_metric: default_max_series_per_metric() }
+    }
+}
+
+impl Default for ForwardConfig {
+    fn default() -> Self {
+        ForwardConfig { max_concurrent_forwards: 256, timeout_ms: 3000 }
+    }
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig {
+            frame_bytes: 1024 * 1024,
+            max_bytes_per_scan: 64 * 1024 * 1024,
+            max_bytes: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        ClockConfig { max_clock_skew_ms: 500, commit_wait: false, testing_offset_ms: 0 }
+    }
+}
+
+impl ClockConfig {
+    #[inline]
+    pub fn max_clock_skew(&self) -> Duration {
+        Duration::from_millis(self.max_clock_skew_ms)
+    }
+}
+
 impl Default for DbConfig {
     fn default() -> Self {
         DbConfig {
@@ -312,9 +1127,9 @@ impl RaftConfig {
             election_tick: self.election_tick,
             heartbeat_tick: 1,
             applied,
-            pre_vote: true,
+            pre_vote: self.pre_vote,
             batch_append: true,
-            check_quorum: true,
+            check_quorum: self.check_quorum,
             max_size_per_msg: self.max_size_per_msg,
             max_inflight_msgs: self.max_inflight_msgs,
             max_committed_size_per_ready: self.max_io_batch_size,
@@ -332,9 +1147,12 @@ impl Default for RaftConfig {
             election_tick: 3,
             max_size_per_msg: 64 << 10,
             max_io_batch_size: 64 << 10,
+            max_entries_per_round: 256,
             max_inflight_msgs: 10 * 1000,
             engine_slow_io_threshold_ms: None,
             enable_log_recycle: false,
+            pre_vote: default_pre_vote(),
+            check_quorum: default_check_quorum(),
             testing_knobs: RaftTestingKnobs::default(),
         }
     }
@@ -360,10 +1178,83 @@ impl Default for RootConfig {
             heartbeat_timeout_sec: 4,
             schedule_interval_sec: 3,
             max_create_group_retry_before_rollback: 10,
+            schedule_mode: ScheduleMode::default(),
+            schedule_auto_cure: default_schedule_auto_cure(),
+            verify_coverage_interval_sec: default_verify_coverage_interval_sec(),
+            catalog_mirror_of: None,
+            enable_unsafe_admin: false,
+            overloaded_cpu_util: default_overloaded_cpu_util(),
+            provisioning_timeout_sec: default_provisioning_timeout_sec(),
+            export_dir: None,
+            testing_knobs: RootTestingKnobs::default(),
         }
     }
 }
 
+fn default_schedule_auto_cure() -> bool {
+    true
+}
+
+fn default_verify_coverage_interval_sec() -> u64 {
+    60
+}
+
+fn default_overloaded_cpu_util() -> f64 {
+    0.8
+}
+
+fn default_provisioning_timeout_sec() -> u64 {
+    600
+}
+
+fn default_snapshot_send_concurrency() -> usize {
+    2
+}
+
+fn default_max_message_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_max_series_per_metric() -> usize {
+    1024
+}
+
+fn default_apply_checkpoint_entries() -> u64 {
+    1024
+}
+
+fn default_apply_checkpoint_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_resolve_intent_age_ms() -> u64 {
+    10 * 1000
+}
+
+fn default_gc_retention_ms() -> u64 {
+    10 * 60 * 1000
+}
+
+fn default_record_request_id() -> bool {
+    true
+}
+
+fn default_verify_descriptor_timeout_ms() -> u64 {
+    3 * 1000
+}
+
+fn default_enable_get_raw_key() -> bool {
+    true
+}
+
+fn default_pre_vote() -> bool {
+    true
+}
+
+fn default_check_quorum() -> bool {
+    true
+}
+
 fn adaptive_block_cache_size() -> usize {
     if cfg!(test) {
         return 32 << 20;
@@ -380,3 +1271,105 @@ fn adaptive_max_background_jobs() -> i32 {
     #[allow(clippy::manual_clamp)]
     max(min(num_cpus::get() as i32, 8), 2)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            root_dir: PathBuf::from("/tmp/sekas"),
+            addr: "127.0.0.1:21805".to_owned(),
+            cpu_nums: 1,
+            ..Default::default()
+        }
+    }
+
+    fn assert_invalid(cfg: &Config, field: &str) {
+        let err = cfg.validate().unwrap_err();
+        assert!(
+            matches!(&err, Error::InvalidArgument(msg) if msg.contains(&format!("`{field}`"))),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn validate_config() {
+        config().validate().unwrap();
+
+        let mut cfg = config();
+        cfg.addr = "127.0.0.1".to_owned();
+        assert_invalid(&cfg, "addr");
+
+        let mut cfg = config();
+        cfg.cluster_secret = Some("the secret".to_owned());
+        assert_invalid(&cfg, "cluster_secret");
+
+        let mut cfg = config();
+        cfg.join_list = vec!["127.0.0.1:21805".to_owned()];
+        cfg.cluster_secret = None;
+        assert_invalid(&cfg, "cluster_secret");
+
+        let mut cfg = config();
+        cfg.root_dir = PathBuf::default();
+        assert_invalid(&cfg, "root_dir");
+
+        let mut cfg = config();
+        cfg.metrics.addr = Some(cfg.addr.clone());
+        assert_invalid(&cfg, "metrics.addr");
+
+        let mut cfg = config();
+        cfg.node.snapshot_send_concurrency = 0;
+        assert_invalid(&cfg, "node.snapshot_send_concurrency");
+
+        let mut cfg = config();
+        cfg.node.scan.frame_bytes = cfg.node.scan.max_bytes_per_scan + 1;
+        assert_invalid(&cfg, "node.scan.frame_bytes");
+
+        let mut cfg = config();
+        cfg.node.watch.max_watches_per_connection = cfg.node.watch.max_watches_per_node + 1;
+        assert_invalid(&cfg, "node.watch.max_watches_per_connection");
+
+        let mut cfg = config();
+        cfg.node.replica.hot_key.sample_rate = 1.5;
+        assert_invalid(&cfg, "node.replica.hot_key.sample_rate");
+
+        let mut cfg = config();
+        cfg.root.heartbeat_timeout_sec = cfg.root.liveness_threshold_sec;
+        assert_invalid(&cfg, "root.heartbeat_timeout_sec");
+
+        let mut cfg = config();
+        cfg.root.overloaded_cpu_util = 0.0;
+        assert_invalid(&cfg, "root.overloaded_cpu_util");
+
+        let mut cfg = config();
+        cfg.db.level0_slowdown_writes_trigger = cfg.db.level0_stop_write_trigger + 1;
+        assert_invalid(&cfg, "db.level0_slowdown_writes_trigger");
+    }
+
+    #[test]
+    fn reload_hot_settings_only() {
+        let cfg = config();
+        let mut new = cfg.clone();
+        new.log_level = Some("debug".to_owned());
+        new.node.shard_move_bytes_per_sec = 1024;
+        new.node.snapshot_send_concurrency = 1;
+        new.node.scan.max_bytes = 2 * cfg.node.scan.max_bytes;
+        new.node.forward.max_concurrent_forwards = 0;
+        new.addr = "127.0.0.1:21806".to_owned();
+        new.raft.election_tick = 10;
+
+        let applied = cfg.reload(&new);
+        assert_eq!(applied.log_level.as_deref(), Some("debug"));
+        assert_eq!(applied.node.shard_move_bytes_per_sec, 1024);
+        assert_eq!(applied.node.snapshot_send_concurrency, 1);
+        assert_eq!(applied.node.scan.max_bytes, new.node.scan.max_bytes);
+        assert_eq!(applied.node.forward.max_concurrent_forwards, 0);
+
+        // The immutable settings are kept.
+        assert_eq!(applied.addr, cfg.addr);
+        assert_eq!(applied.raft.election_tick, cfg.raft.election_tick);
+        assert_eq!(applied.changed_fields(&new), vec!["addr", "raft.election_tick"]);
+        assert!(applied.changed_fields(&applied.clone()).is_empty());
+    }
+}
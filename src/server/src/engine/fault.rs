@@ -0,0 +1,71 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The faults injected into the group engines, for testing only.
+//!
+//! A fault makes the group engine of a replica return a corruption error once
+//! it reads a user key in the range, until the fault is cleared, as if the
+//! blocks of the range are damaged.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use sekas_rock::ascii::escape_bytes;
+
+use crate::{Error, Result};
+
+/// The faults shared by the group engines of all replicas.
+#[derive(Clone, Debug, Default)]
+pub struct StorageFaults {
+    /// Whether any fault has ever been injected, to skip the lock in reading.
+    injected: Arc<AtomicBool>,
+    /// The corrupted user key ranges `[start, end)` of each replica, the empty
+    /// end means unbounded.
+    inner: Arc<Mutex<HashMap<u64, Vec<(Vec<u8>, Vec<u8>)>>>>,
+}
+
+impl StorageFaults {
+    /// Corrupt the user keys in range `[start, end)` of the replica.
+    pub fn inject_corruption(&self, replica_id: u64, start: &[u8], end: &[u8]) {
+        let mut faults = self.inner.lock().unwrap();
+        faults.entry(replica_id).or_default().push((start.to_owned(), end.to_owned()));
+        self.injected.store(true, Ordering::Release);
+    }
+
+    pub fn clear(&self, replica_id: u64) {
+        self.inner.lock().unwrap().remove(&replica_id);
+    }
+
+    /// Read the user key of the replica, returns an error if it is corrupted.
+    pub(crate) fn hit(&self, replica_id: u64, user_key: &[u8]) -> Result<()> {
+        if !self.injected.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let faults = self.inner.lock().unwrap();
+        let Some(ranges) = faults.get(&replica_id) else {
+            return Ok(());
+        };
+        let corrupted = ranges.iter().any(|(start, end)| {
+            start.as_slice() <= user_key && (end.is_empty() || user_key < end.as_slice())
+        });
+        if corrupted {
+            return Err(Error::StorageCorrupted(format!(
+                "block checksum mismatch is injected into key {} of replica {replica_id}",
+                escape_bytes(user_key)
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The format version of the data dir of a node.
+//!
+//! The version is recorded in a file of the base dir, it is written at the
+//! first start and checked at every start, before any engine is opened. A dir
+//! written by a newer binary is refused, and a dir written by an older binary
+//! is upgraded by the registered migrations in order. The version file is
+//! advanced once a migration is applied, so it is the high-water mark of the
+//! migrations: a migration interrupted by a crash is applied again at the next
+//! start, so every migration must be idempotent.
+
+use std::path::Path;
+
+use log::info;
+use prost::Message;
+use sekas_api::server::v1::{GroupDesc, RangePartition, ReplicaDesc, ShardDesc};
+
+use super::group::keys;
+use super::{open_raw_db, LAYOUT_DATA};
+use crate::{DbConfig, Error, Result};
+
+/// The file records the format version, in the base dir.
+pub(crate) const FORMAT_VERSION_FILE: &str = "FORMAT_VERSION";
+
+/// The format version written by this binary.
+pub(crate) const CURRENT_FORMAT_VERSION: u64 = 3;
+
+/// The version of a non-empty dir without the version file, which is written
+/// before the version file is introduced.
+const OLDEST_FORMAT_VERSION: u64 = 1;
+
+/// A migration upgrades the data dir from `version - 1` to `version`.
+pub(crate) struct Migration {
+    pub version: u64,
+    pub name: &'static str,
+    /// Apply the migration to the base dir, it must be idempotent.
+    pub apply: fn(&Path) -> Result<()>,
+}
+
+/// The migrations ordered by the version.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        name: "unify shard partitions into ranges",
+        apply: unify_shard_partitions,
+    },
+    Migration { version: 3, name: "values with expiry", apply: allow_expiring_values },
+];
+
+/// Check the format version of the base dir, and upgrade it to the current
+/// version if it is written by an older binary.
+pub(crate) fn prepare_data_dir(root_dir: &Path) -> Result<()> {
+    prepare_data_dir_with(root_dir, CURRENT_FORMAT_VERSION, MIGRATIONS)
+}
+
+fn prepare_data_dir_with(root_dir: &Path, current: u64, migrations: &[Migration]) -> Result<()> {
+    let Some(version) = read_format_version(root_dir)? else {
+        info!("initialize the format version of {} to {current}", root_dir.display());
+        return write_format_version(root_dir, current);
+    };
+    if version > current {
+        return Err(Error::IncompatibleDataDir(format!(
+            "the format version of {} is {version}, but this binary only supports up to \
+             {current}, please upgrade the binary",
+            root_dir.display(),
+        )));
+    }
+
+    for migration in migrations.iter().filter(|m| version < m.version && m.version <= current) {
+        info!(
+            "migrate the format of {} to version {}: {}",
+            root_dir.display(),
+            migration.version,
+            migration.name
+        );
+        (migration.apply)(root_dir).map_err(|err| {
+            Error::IncompatibleDataDir(format!(
+                "migrate the format of {} to version {} ({}): {err}",
+                root_dir.display(),
+                migration.version,
+                migration.name
+            ))
+        })?;
+        write_format_version(root_dir, migration.version)?;
+    }
+    if version < current {
+        write_format_version(root_dir, current)?;
+    }
+    Ok(())
+}
+
+/// Read the format version of the base dir, `None` if the dir is fresh.
+fn read_format_version(root_dir: &Path) -> Result<Option<u64>> {
+    let path = root_dir.join(FORMAT_VERSION_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(content) => {
+            let version = content.trim().parse::<u64>().map_err(|_| {
+                Error::IncompatibleDataDir(format!(
+                    "the format version file {} is corrupted: {content:?}",
+                    path.display()
+                ))
+            })?;
+            Ok(Some(version))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let is_empty = match std::fs::read_dir(root_dir) {
+                Ok(mut entries) => entries.next().is_none(),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => true,
+                Err(err) => return Err(err.into()),
+            };
+            Ok((!is_empty).then_some(OLDEST_FORMAT_VERSION))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Write the format version file atomically.
+fn write_format_version(root_dir: &Path, version: u64) -> Result<()> {
+    std::fs::create_dir_all(root_dir)?;
+    let path = root_dir.join(FORMAT_VERSION_FILE);
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, format!("{version}\n"))?;
+    std::fs::File::open(&tmp_path)?.sync_all()?;
+    std::fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// The shard descriptor before the partitions are unified into ranges, the
+/// field 3 was the hash partition and the field 4 was the range partition.
+#[derive(Clone, PartialEq, prost::Message)]
+struct LegacyShardDesc {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(uint64, tag = "2")]
+    table_id: u64,
+    #[prost(message, optional, tag = "3")]
+    hash: Option<LegacyHashPartition>,
+    #[prost(message, optional, tag = "4")]
+    range: Option<RangePartition>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct LegacyHashPartition {
+    #[prost(uint32, tag = "1")]
+    slot_id: u32,
+    #[prost(uint32, tag = "2")]
+    slots: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct LegacyGroupDesc {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(uint64, tag = "2")]
+    epoch: u64,
+    #[prost(message, repeated, tag = "3")]
+    shards: Vec<LegacyShardDesc>,
+    #[prost(message, repeated, tag = "4")]
+    replicas: Vec<ReplicaDesc>,
+}
+
+/// Rewrite the persisted group descriptors of the replicas, the range of a
+/// shard is moved from the legacy field to the unified one.
+fn unify_shard_partitions(root_dir: &Path) -> Result<()> {
+    let db_path = root_dir.join(LAYOUT_DATA);
+    if !db_path.exists() {
+        return Ok(());
+    }
+    let raw_db = open_raw_db(&DbConfig::default(), &db_path)?;
+    let names = rocksdb::DB::list_cf(&raw_db.options, &db_path)?;
+    for name in names.iter().filter(|name| *name != rocksdb::DEFAULT_COLUMN_FAMILY_NAME) {
+        let Some(cf_handle) = raw_db.cf_handle(name) else { continue };
+        let Some(value) = raw_db.db.get_cf(&cf_handle, keys::descriptor())? else { continue };
+        if let Some(desc) = unify_group_desc(&value)? {
+            info!("unify the shard partitions of group {} in column family {name}", desc.id);
+            raw_db.db.put_cf(&cf_handle, keys::descriptor(), desc.encode_to_vec())?;
+            raw_db.flush_cf(&cf_handle)?;
+        }
+    }
+    Ok(())
+}
+
+/// The values might carry an expiry since this version, which is unknown to the
+/// older binaries, so the dir is refused by them. The existing values are kept
+/// as they are.
+fn allow_expiring_values(_: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Convert the legacy group descriptor, `None` if it is already unified.
+fn unify_group_desc(value: &[u8]) -> Result<Option<GroupDesc>> {
+    if let Ok(desc) = GroupDesc::decode(value) {
+        if desc.shards.iter().all(|shard| shard.range.is_some()) {
+            return Ok(None);
+        }
+    }
+
+    let legacy = LegacyGroupDesc::decode(value)?;
+    let mut shards = Vec::with_capacity(legacy.shards.len());
+    for shard in legacy.shards {
+        let Some(range) = shard.range else {
+            return Err(Error::InvalidData(format!(
+                "shard {} of group {} is hash partitioned, which is no longer supported, \
+                 move the table out of it with the older binary first",
+                shard.id, legacy.id
+            )));
+        };
+        shards.push(ShardDesc { id: shard.id, table_id: shard.table_id, range: Some(range) });
+    }
+    Ok(Some(GroupDesc { id: legacy.id, epoch: legacy.epoch, shards, replicas: legacy.replicas }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use sekas_rock::fn_name;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    const LEGACY_CF: &str = "legacy-group";
+
+    fn legacy_group_desc(range: Option<RangePartition>) -> LegacyGroupDesc {
+        LegacyGroupDesc {
+            id: 1,
+            epoch: 3,
+            shards: vec![LegacyShardDesc {
+                id: 10,
+                table_id: 100,
+                hash: range.is_none().then_some(LegacyHashPartition { slot_id: 1, slots: 4 }),
+                range,
+            }],
+            replicas: vec![ReplicaDesc { id: 11, node_id: 1, ..Default::default() }],
+        }
+    }
+
+    /// Fabricate the data dir written before the version file is introduced.
+    fn fabricate_legacy_dir(root_dir: &Path, desc: &LegacyGroupDesc) {
+        let raw_db = open_raw_db(&DbConfig::default(), root_dir.join(LAYOUT_DATA)).unwrap();
+        raw_db.create_cf(LEGACY_CF).unwrap();
+        let cf_handle = raw_db.cf_handle(LEGACY_CF).unwrap();
+        raw_db.db.put_cf(&cf_handle, keys::descriptor(), desc.encode_to_vec()).unwrap();
+    }
+
+    fn read_group_desc(root_dir: &Path) -> GroupDesc {
+        let raw_db = open_raw_db(&DbConfig::default(), root_dir.join(LAYOUT_DATA)).unwrap();
+        let cf_handle = raw_db.cf_handle(LEGACY_CF).unwrap();
+        let value = raw_db.db.get_cf(&cf_handle, keys::descriptor()).unwrap().unwrap();
+        GroupDesc::decode(value.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn fresh_dir_is_initialized() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let root_dir = dir.path().join("node");
+        prepare_data_dir(&root_dir).unwrap();
+        assert_eq!(read_format_version(&root_dir).unwrap(), Some(CURRENT_FORMAT_VERSION));
+
+        // The current version is accepted.
+        prepare_data_dir(&root_dir).unwrap();
+        assert_eq!(read_format_version(&root_dir).unwrap(), Some(CURRENT_FORMAT_VERSION));
+    }
+
+    #[test]
+    fn newer_dir_is_refused() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        write_format_version(dir.path(), CURRENT_FORMAT_VERSION + 1).unwrap();
+        let err = prepare_data_dir(dir.path()).unwrap_err().to_string();
+        assert!(err.contains(&format!("is {}", CURRENT_FORMAT_VERSION + 1)), "{err}");
+        assert!(err.contains(&format!("up to {CURRENT_FORMAT_VERSION}")), "{err}");
+    }
+
+    #[test]
+    fn corrupted_version_file_is_refused() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        std::fs::write(dir.path().join(FORMAT_VERSION_FILE), "abc").unwrap();
+        assert!(matches!(prepare_data_dir(dir.path()), Err(Error::IncompatibleDataDir(_))));
+    }
+
+    #[test]
+    fn unversioned_dir_is_migrated() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let range = RangePartition { start: b"a".to_vec(), end: b"b".to_vec() };
+        fabricate_legacy_dir(dir.path(), &legacy_group_desc(Some(range.clone())));
+        assert_eq!(read_format_version(dir.path()).unwrap(), Some(OLDEST_FORMAT_VERSION));
+
+        prepare_data_dir(dir.path()).unwrap();
+        assert_eq!(read_format_version(dir.path()).unwrap(), Some(CURRENT_FORMAT_VERSION));
+        let desc = read_group_desc(dir.path());
+        assert_eq!(desc.epoch, 3);
+        assert_eq!(desc.replicas.len(), 1);
+        assert_eq!(desc.shards, vec![ShardDesc { id: 10, table_id: 100, range: Some(range) }]);
+
+        // The migration is idempotent.
+        unify_shard_partitions(dir.path()).unwrap();
+        assert_eq!(read_group_desc(dir.path()).shards[0].id, 10);
+    }
+
+    #[test]
+    fn hash_partitioned_dir_is_refused() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        fabricate_legacy_dir(dir.path(), &legacy_group_desc(None));
+        let err = prepare_data_dir(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("hash partitioned"), "{err}");
+        assert_eq!(read_format_version(dir.path()).unwrap(), Some(OLDEST_FORMAT_VERSION));
+    }
+
+    static NUM_APPLIED: AtomicUsize = AtomicUsize::new(0);
+    static NUM_CRASHES: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_applied(_: &Path) -> Result<()> {
+        NUM_APPLIED.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Crashes halfway at the first time, the marker written before crashing
+    /// is left.
+    fn crash_halfway_once(root_dir: &Path) -> Result<()> {
+        std::fs::write(root_dir.join("marker"), "halfway")?;
+        if NUM_CRASHES.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Err(Error::Io(std::io::Error::other("crashed")));
+        }
+        std::fs::write(root_dir.join("marker"), "done")?;
+        Ok(())
+    }
+
+    #[test]
+    fn interrupted_migration_is_resumed() {
+        let migrations = [
+            Migration { version: 2, name: "count", apply: count_applied },
+            Migration { version: 3, name: "crash", apply: crash_halfway_once },
+        ];
+        let dir = TempDir::new(fn_name!()).unwrap();
+        write_format_version(dir.path(), 1).unwrap();
+
+        assert!(prepare_data_dir_with(dir.path(), 3, &migrations).is_err());
+        // The high-water mark is advanced by the applied migrations.
+        assert_eq!(read_format_version(dir.path()).unwrap(), Some(2));
+        assert_eq!(NUM_APPLIED.load(Ordering::SeqCst), 1);
+
+        prepare_data_dir_with(dir.path(), 3, &migrations).unwrap();
+        assert_eq!(read_format_version(dir.path()).unwrap(), Some(3));
+        assert_eq!(NUM_APPLIED.load(Ordering::SeqCst), 1);
+        assert_eq!(std::fs::read_to_string(dir.path().join("marker")).unwrap(), "done");
+    }
+}
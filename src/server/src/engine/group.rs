@@ -16,25 +16,31 @@
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
-use std::sync::{Arc, RwLock};
-use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
 use log::{info, warn};
 use prost::Message;
 use sekas_api::server::v1::*;
 use sekas_rock::lexical;
+use sekas_runtime::time::Instant;
 use sekas_schema::shard;
 
-use super::RawDb;
+use super::fault::StorageFaults;
+use super::intent::{IntentInfo, IntentStats, ShardIntentStats};
+use super::{ttl, GcState, RawDb};
 use crate::constants::{INITIAL_EPOCH, LOCAL_TABLE_ID};
 use crate::serverpb::v1::*;
 use crate::{EngineConfig, Error, Result};
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct WriteStates {
     pub apply_state: Option<ApplyState>,
     pub descriptor: Option<GroupDesc>,
     pub move_shard_state: Option<MoveShardState>,
+    pub apply_checkpoint: Option<ApplyCheckpoint>,
+    pub purge_shard_states: Vec<PurgeShardState>,
+    pub gc_watermark: Option<u64>,
 }
 
 #[derive(Default)]
@@ -43,6 +49,29 @@ pub struct WriteBatch {
     inner: rocksdb::WriteBatch,
 }
 
+/// The kind of a user write in a `WriteBatch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteKind {
+    Put,
+    Tombstone,
+    Intent,
+    /// The mvcc record is removed, eg. a txn intent is cleared.
+    Clear,
+}
+
+/// The max number of mvcc entries visited to sample the version chains of a
+/// shard.
+const MAX_VERSION_CHAIN_SAMPLE_ENTRIES: usize = 4096;
+
+/// The percentiles of the number of committed versions per key, sampled from
+/// the leading keys of a shard.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VersionChainStats {
+    pub p50: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
 /// A structure supports grouped data, metadata saving and retriving.
 ///
 /// NOTE: Shard are managed by `GroupEngine` instead of a shard engine, because
@@ -55,8 +84,10 @@ where
 {
     cfg: EngineConfig,
     name: String,
+    replica_id: u64,
     raw_db: Arc<RawDb>,
     core: Arc<RwLock<GroupEngineCore>>,
+    intent_stats: Arc<Mutex<IntentStats>>,
 }
 
 #[derive(Default)]
@@ -86,6 +117,8 @@ enum SnapshotRange {
 pub(crate) struct Snapshot<'a> {
     table_id: u64,
     range: Option<SnapshotRange>,
+    /// The injected faults and the replica to read, for testing only.
+    faults: Option<(StorageFaults, u64)>,
 
     core: SnapshotCore<'a>,
 }
@@ -97,6 +130,14 @@ pub(crate) struct SnapshotCore<'a> {
     db_iter: rocksdb::DBIterator<'a>,
     current_key: Option<Vec<u8>>,
     cached_entry: Option<MvccEntry>,
+    /// Whether the user keys are traversed in descending order.
+    reverse: bool,
+    /// The versions of the current key, only used in reverse mode, since the
+    /// versions are visited in ascending order.
+    versions: Vec<MvccEntry>,
+    /// The version of the read, the values expired at it are read as
+    /// tombstones. The values are read as they are if it is not set.
+    read_version: Option<u64>,
 }
 
 /// Traverse multi-version of a single key.
@@ -112,16 +153,27 @@ pub(crate) struct MvccEntry {
     value: Box<[u8]>,
 }
 
+/// The mode to traverse a shard. The `End` and `ReversePrefix` modes traverse
+/// user keys in descending order, and the `end_key` of `End` mode is inclusive
+/// (the end of the shard is used if it is `None`). The `KeyAtVersion` mode
+/// seeks to the newest version of the key not greater than `version`, the
+/// intent and the newer versions are skipped without being visited.
 #[derive(Debug)]
 pub(crate) enum SnapshotMode<'a> {
     Start { start_key: Option<&'a [u8]> },
     Key { key: &'a [u8] },
+    KeyAtVersion { key: &'a [u8], version: u64 },
     Prefix { key: &'a [u8] },
+    End { end_key: Option<&'a [u8]> },
+    ReversePrefix { key: &'a [u8] },
 }
 
 struct ColumnFamilyDecorator<'a, 'b> {
     cf_handle: Arc<rocksdb::BoundColumnFamily<'b>>,
     wb: &'a mut rocksdb::WriteBatch,
+    /// The intents written (with the start version of txn) or resolved by the
+    /// write batches, keyed by the table id and user key.
+    intents: Vec<(u64, Vec<u8>, Option<u64>)>,
 }
 
 struct SlowIoGuard {
@@ -149,21 +201,24 @@ impl GroupEngine {
         let engine = GroupEngine {
             cfg: cfg.clone(),
             name,
+            replica_id,
             raw_db: raw_db.clone(),
             core: Arc::new(RwLock::new(GroupEngineCore {
                 group_desc: desc.clone(),
                 shard_descs: Default::default(),
                 move_shard_state: None,
             })),
+            intent_stats: Arc::default(),
         };
-
         // The group descriptor should be persisted into disk.
         let states = WriteStates {
             apply_state: Some(ApplyState { index: 0, term: 0 }),
             descriptor: Some(desc),
             ..Default::default()
         };
-        engine.commit(WriteBatch::default(), states, true)?;
+        let mut wb = WriteBatch::default();
+        wb.put(keys::intent_index_prefix(), b"");
+        engine.commit(wb, states, true)?;
 
         // Flush mem tables so that subsequent `ReadTier::Persisted` can be executed.
         raw_db.flush_cf(&cf_handle)?;
@@ -194,18 +249,24 @@ impl GroupEngine {
         }
         let core = GroupEngineCore { move_shard_state, group_desc, shard_descs };
 
-        Ok(Some(GroupEngine {
+        let engine = GroupEngine {
             cfg: cfg.clone(),
             name,
+            replica_id,
             raw_db: raw_db.clone(),
             core: Arc::new(RwLock::new(core)),
-        }))
+            intent_stats: Arc::default(),
+        };
+        engine.gc_state().set_watermark(internal::gc_watermark(&raw_db, &cf_handle)?);
+        engine.load_intent_stats()?;
+        Ok(Some(engine))
     }
 
     /// Destory a group engine.
     pub(crate) async fn destory(group_id: u64, replica_id: u64, raw_db: Arc<RawDb>) -> Result<()> {
         let name = Self::cf_name(group_id, replica_id);
         raw_db.drop_cf(&name)?;
+        raw_db.remove_gc_state(&name);
         info!("destory column family {}", name);
         Ok(())
     }
@@ -228,6 +289,51 @@ impl GroupEngine {
         internal::flushed_apply_state(&self.raw_db, &self.cf_handle())
     }
 
+    /// Return the persisted apply checkpoint and the group descriptor.
+    pub fn flushed_apply_checkpoint(&self) -> Result<Option<(ApplyCheckpoint, GroupDesc)>> {
+        let cf_handle = self.cf_handle();
+        let Some(checkpoint) = internal::flushed_apply_checkpoint(&self.raw_db, &cf_handle)? else {
+            return Ok(None);
+        };
+        let descriptor = internal::flushed_descriptor(&self.raw_db, &cf_handle)?;
+        Ok(Some((checkpoint, descriptor)))
+    }
+
+    /// Return the quarantine of the replica, see [`ApplyQuarantine`].
+    pub fn apply_quarantine(&self) -> Result<Option<ApplyQuarantine>> {
+        match self.raw_db.get_pinned_cf(&self.cf_handle(), keys::apply_quarantine())? {
+            Some(v) => Ok(Some(ApplyQuarantine::decode(v.as_ref())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Save or clear the quarantine of the replica. It is written outside of
+    /// raft and synced, since the entries after the poisoned one are never
+    /// applied, nor flushed.
+    pub fn save_apply_quarantine(&self, quarantine: Option<&ApplyQuarantine>) -> Result<()> {
+        let cf_handle = self.cf_handle();
+        let mut wb = rocksdb::WriteBatch::default();
+        match quarantine {
+            Some(quarantine) => {
+                wb.put_cf(&cf_handle, keys::apply_quarantine(), quarantine.encode_to_vec())
+            }
+            None => wb.delete_cf(&cf_handle, keys::apply_quarantine()),
+        }
+        let mut opts = rocksdb::WriteOptions::default();
+        opts.set_sync(true);
+        self.raw_db.write_opt(wb, &opts)?;
+        Ok(())
+    }
+
+    /// Flush the memtables of the group engine, the data written before are
+    /// persisted together once it finished.
+    pub fn flush(&self, wait: bool) -> Result<()> {
+        let mut opts = rocksdb::FlushOptions::default();
+        opts.set_wait(wait);
+        self.raw_db.flush_cf_opt(&self.cf_handle(), &opts)?;
+        Ok(())
+    }
+
     /// Get the latest key value from the corresponding shard.
     pub async fn get(&self, shard_id: u64, key: &[u8]) -> Result<Option<Value>> {
         let snapshot_mode = SnapshotMode::Key { key };
@@ -275,6 +381,48 @@ impl GroupEngine {
         Ok(())
     }
 
+    /// Put key value into the corresponding shard, it expires after the TTL
+    /// (in millis) since the version, or the TTL of the table if it is
+    /// shorter, `0` means no TTL. See [`super::ttl`].
+    pub fn put_with_ttl(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        key: &[u8],
+        value: &[u8],
+        version: u64,
+        ttl_ms: u64,
+    ) -> Result<()> {
+        let table_id = self.shard_desc(shard_id)?.table_id;
+        let table_ttl_ms = self.gc_state().table_ttl_ms(table_id);
+        let expire_at = ttl::expire_at(version, ttl_ms, table_ttl_ms);
+        self.put_with_expiry(wb, shard_id, key, value, version, expire_at)
+    }
+
+    /// Put key value which expires at `expire_at` (in millis since the unix
+    /// epoch) into the corresponding shard, `0` means never.
+    pub fn put_with_expiry(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        key: &[u8],
+        value: &[u8],
+        version: u64,
+        expire_at: u64,
+    ) -> Result<()> {
+        if expire_at == 0 {
+            return self.put(wb, shard_id, key, value, version);
+        }
+        let desc = self.shard_desc(shard_id)?;
+        let table_id = desc.table_id;
+        debug_assert_ne!(table_id, LOCAL_TABLE_ID);
+        debug_assert!(shard::belong_to(&desc, key));
+
+        wb.put(keys::mvcc_key(table_id, key, version), values::expiring_data(value, expire_at));
+
+        Ok(())
+    }
+
     /// Logically delete key from the corresponding shard.
     pub fn tombstone(
         &self,
@@ -325,11 +473,16 @@ impl GroupEngine {
 
         let cf_handle = self.cf_handle();
         let mut inner_wb = rocksdb::WriteBatch::default();
-        let mut decorator =
-            ColumnFamilyDecorator { cf_handle: cf_handle.clone(), wb: &mut inner_wb };
+        let mut decorator = ColumnFamilyDecorator {
+            cf_handle: cf_handle.clone(),
+            wb: &mut inner_wb,
+            intents: Vec::default(),
+        };
         for wb in wbs {
             wb.inner.iterate(&mut decorator);
         }
+        let intents = std::mem::take(&mut decorator.intents);
+        let intents = self.index_intents(intents, &mut inner_wb, &cf_handle);
         states.write(&mut inner_wb, &cf_handle);
 
         let mut opts = WriteOptions::default();
@@ -347,10 +500,27 @@ impl GroupEngine {
         if states.descriptor.is_some() || states.move_shard_state.is_some() {
             self.apply_core_states(states.descriptor, states.move_shard_state);
         }
+        if let Some(watermark) = states.gc_watermark {
+            self.gc_state().set_watermark(watermark);
+        }
+        if !intents.is_empty() {
+            self.apply_intents(intents);
+        }
 
         Ok(())
     }
 
+    /// Return the stats of the unresolved intents of the shard.
+    pub fn shard_intent_stats(&self, shard_id: u64) -> ShardIntentStats {
+        self.intent_stats.lock().unwrap().shard_stats(shard_id)
+    }
+
+    /// Return at most `limit` unresolved intents of the shard, in descending
+    /// order of age.
+    pub fn oldest_intents(&self, shard_id: u64, limit: usize) -> Vec<IntentInfo> {
+        self.intent_stats.lock().unwrap().oldest_intents(shard_id, limit)
+    }
+
     pub fn snapshot(&self, shard_id: u64, mode: SnapshotMode) -> Result<Snapshot> {
         use rocksdb::{Direction, IteratorMode, ReadOptions};
 
@@ -359,6 +529,8 @@ impl GroupEngine {
         debug_assert_ne!(table_id, LOCAL_TABLE_ID);
 
         let opts = ReadOptions::default();
+        let reverse = mode.is_reverse();
+        let range = SnapshotRange::new(&mode, &desc);
         let key = match &mode {
             SnapshotMode::Start { start_key: Some(start_key) } => {
                 debug_assert!(
@@ -375,14 +547,31 @@ impl GroupEngine {
                 debug_assert!(shard::belong_to(&desc, key), "shard desc {desc:?} key {key:?}");
                 keys::raw(table_id, key)
             }
+            SnapshotMode::KeyAtVersion { key, version } => {
+                debug_assert!(shard::belong_to(&desc, key), "shard desc {desc:?} key {key:?}");
+                keys::mvcc_key(table_id, key, *version)
+            }
             SnapshotMode::Prefix { key } => {
                 debug_assert!(shard::belong_to(&desc, key), "shard desc {desc:?} key {key:?}");
                 keys::raw(table_id, key)
             }
+            SnapshotMode::End { .. } | SnapshotMode::ReversePrefix { .. } => {
+                // The end key out of the shard is clamped by the range.
+                reverse_seek_key(table_id, &range)
+            }
+        };
+        let inner_mode = if key.is_empty() {
+            // The table id is the last one of the key space.
+            IteratorMode::End
+        } else if reverse {
+            IteratorMode::From(&key, Direction::Reverse)
+        } else {
+            IteratorMode::From(&key, Direction::Forward)
         };
-        let inner_mode = IteratorMode::From(&key, Direction::Forward);
         let iter = self.raw_db.iterator_cf_opt(&self.cf_handle(), opts, inner_mode);
-        Ok(Snapshot::new(table_id, iter, mode, &desc))
+        let mut snapshot = Snapshot::new(table_id, iter, range, reverse);
+        snapshot.faults = Some((self.cfg.testing_knobs.storage_faults.clone(), self.replica_id));
+        Ok(snapshot)
     }
 
     pub fn raw_iter(&self) -> Result<RawIterator> {
@@ -407,6 +596,8 @@ impl GroupEngine {
         let group_desc = internal::descriptor(&self.raw_db, &cf_handle)?;
         let move_shard_state = internal::move_shard_state(&self.raw_db, &cf_handle)?;
         self.apply_core_states(Some(group_desc), move_shard_state);
+        self.gc_state().set_watermark(internal::gc_watermark(&self.raw_db, &cf_handle)?);
+        self.load_intent_stats()?;
 
         Ok(())
     }
@@ -437,6 +628,7 @@ impl GroupEngine {
         {
             core.shard_descs.entry(shard_desc.id).or_insert(shard_desc);
         }
+        self.intent_stats.lock().unwrap().retain_shards(|id| core.shard_descs.contains_key(&id));
     }
 
     /// Get the approximates size of the target shard.
@@ -445,6 +637,47 @@ impl GroupEngine {
         self.raw_db.get_approximate_size(&self.cf_handle(), &start, &end)
     }
 
+    /// Estimate the number of the keys of the target shard, the versions of a
+    /// key are counted separately.
+    pub fn get_approximate_num_keys(&self, shard_id: u64) -> Result<u64> {
+        let (start, end) = self.shard_raw_boundary(shard_id)?;
+        self.raw_db.estimate_num_keys_in_range(&self.cf_handle(), &start, &end)
+    }
+
+    /// Sample the version chains of the leading keys of the shard, at most
+    /// [`MAX_VERSION_CHAIN_SAMPLE_ENTRIES`] entries are visited.
+    pub fn version_chain_stats(&self, shard_id: u64) -> Result<VersionChainStats> {
+        use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+        let mut chains = Vec::default();
+        let mut num_entries = 0;
+        let mut snapshot = self.snapshot(shard_id, SnapshotMode::Start { start_key: None })?;
+        while let Some(iter) = snapshot.next() {
+            let mut num_versions = 0;
+            for entry in iter? {
+                let entry = entry?;
+                num_entries += 1;
+                if entry.version() != TXN_INTENT_VERSION && !entry.is_truncated() {
+                    num_versions += 1;
+                }
+            }
+            chains.push(num_versions);
+            if num_entries >= MAX_VERSION_CHAIN_SAMPLE_ENTRIES {
+                break;
+            }
+        }
+        if chains.is_empty() {
+            return Ok(VersionChainStats::default());
+        }
+        chains.sort_unstable();
+        let percentile = |p: usize| chains[(chains.len() - 1) * p / 100];
+        Ok(VersionChainStats {
+            p50: percentile(50),
+            p99: percentile(99),
+            max: *chains.last().expect("chains is not empty"),
+        })
+    }
+
     /// Estimate the split keys (in user key) of the target shard.
     pub fn estimate_split_key(&self, shard_id: u64) -> Result<Option<Vec<u8>>> {
         let (start, end) = self.shard_raw_boundary(shard_id)?;
@@ -459,6 +692,140 @@ impl GroupEngine {
         Ok(keys::may_revert_mvcc_key(split_key))
     }
 
+    /// Return the states of purging the data of the removed shards.
+    /// Returns the GC state of the group, the versions beneath the GC watermark
+    /// are dropped when the data of the group is compacted.
+    #[inline]
+    pub fn gc_state(&self) -> Arc<GcState> {
+        self.raw_db.gc_state(&self.name)
+    }
+
+    /// Compact all the data of the group.
+    pub fn compact(&self) {
+        self.raw_db.compact_range_cf(&self.cf_handle(), None, None);
+    }
+
+    /// Compact the keys of the range, or all the data of the group if the range
+    /// is not specified. The memtable is flushed first, so the returned
+    /// approximate sizes of the data before and after the compaction cover the
+    /// recent writes.
+    pub fn compact_range(&self, range: Option<&CompactRange>) -> Result<(u64, u64)> {
+        let cf_handle = self.cf_handle();
+        let boundaries = match range {
+            Some(range) => {
+                let start = keys::raw(range.table_id, &range.start);
+                let end = if range.end.is_empty() {
+                    lexical::lexical_next_boundary(&keys::raw(range.table_id, &range.end))
+                } else {
+                    keys::raw(range.table_id, &range.end)
+                };
+                vec![(start, end)]
+            }
+            None => {
+                let core = self.core.read().expect("read lock");
+                core.shard_descs.values().map(internal::raw_boundary).collect::<Result<_>>()?
+            }
+        };
+        let approximate_size = || -> Result<u64> {
+            let mut size = 0;
+            for (start, end) in &boundaries {
+                size += self.raw_db.get_approximate_size(&cf_handle, start, end)?;
+            }
+            Ok(size)
+        };
+
+        self.raw_db.flush_cf(&cf_handle)?;
+        let size_before = approximate_size()?;
+        match range {
+            Some(_) => {
+                let (start, end) = &boundaries[0];
+                self.raw_db.compact_range_cf(&cf_handle, Some(start), Some(end));
+            }
+            None => self.raw_db.compact_range_cf(&cf_handle, None, None),
+        }
+        Ok((size_before, approximate_size()?))
+    }
+
+    pub fn purge_shard_states(&self) -> Result<Vec<PurgeShardState>> {
+        internal::purge_shard_states(&self.raw_db, &self.cf_handle())
+    }
+
+    /// Purge at most `limit` raw keys of the removed shard, starting from the
+    /// resume key of the state. The deletions and the advanced state are
+    /// written in the same batch, outside of raft, so the purging of each
+    /// replica is resumed from its own progress after restarting.
+    ///
+    /// The keys belonging to the shards still served by this group are never
+    /// purged. Once the range is exhausted, the state is marked finished, see
+    /// [`GroupEngine::finish_purge_shard`].
+    pub fn purge_shard_chunk(&self, state: &mut PurgeShardState, limit: usize) -> Result<()> {
+        use rocksdb::{Direction, IteratorMode, ReadOptions};
+        use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+        let shard_desc = state.shard.clone().ok_or_else(|| {
+            Error::InvalidData("the shard of purge shard state is not set".into())
+        })?;
+        let (start, end) = internal::raw_boundary(&shard_desc)?;
+        let served_shards = {
+            let core = self.core.read().unwrap();
+            core.shard_descs
+                .values()
+                .filter(|s| s.table_id == shard_desc.table_id)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let cf_handle = self.cf_handle();
+        let from = state.resume_key.clone().unwrap_or_else(|| start.clone());
+        let mut opts = ReadOptions::default();
+        opts.set_iterate_upper_bound(end.clone());
+        let mode = IteratorMode::From(&from, Direction::Forward);
+        let mut wb = rocksdb::WriteBatch::default();
+        let mut num_visited = 0;
+        let mut resume_key = None;
+        for item in self.raw_db.iterator_cf_opt(&cf_handle, opts, mode) {
+            let (key, value) = item?;
+            if num_visited >= limit {
+                resume_key = Some(key.to_vec());
+                break;
+            }
+            num_visited += 1;
+            let Some((table_id, user_key, version)) = keys::revert_data_key(&key) else {
+                continue;
+            };
+            if served_shards.iter().any(|s| shard::belong_to(s, &user_key)) {
+                continue;
+            }
+            state.purged_bytes += (key.len() + value.len()) as u64;
+            wb.delete_cf(&cf_handle, &key);
+            if version == TXN_INTENT_VERSION {
+                wb.delete_cf(&cf_handle, keys::intent_index(table_id, &user_key));
+            }
+        }
+
+        state.finished = resume_key.is_none();
+        state.resume_key = resume_key;
+        wb.put_cf(&cf_handle, keys::purge_shard_state(shard_desc.id), state.encode_to_vec());
+        self.raw_db.write_opt(wb, &rocksdb::WriteOptions::default())?;
+        Ok(())
+    }
+
+    /// Compact the range of the purged shard to reclaim the disk space, then
+    /// delete the purge state. The compaction is blocking, and it is done
+    /// again after restarting if the state is not deleted yet.
+    pub fn finish_purge_shard(&self, state: &PurgeShardState) -> Result<()> {
+        let shard_desc = state.shard.as_ref().ok_or_else(|| {
+            Error::InvalidData("the shard of purge shard state is not set".into())
+        })?;
+        let (start, end) = internal::raw_boundary(shard_desc)?;
+        let cf_handle = self.cf_handle();
+        self.raw_db.compact_range_cf(&cf_handle, Some(&start), Some(&end));
+        let mut wb = rocksdb::WriteBatch::default();
+        wb.delete_cf(&cf_handle, keys::purge_shard_state(shard_desc.id));
+        self.raw_db.write_opt(wb, &rocksdb::WriteOptions::default())?;
+        Ok(())
+    }
+
     /// return the desc of the specified shard.
     #[inline]
     pub fn shard_desc(&self, shard_id: u64) -> Result<ShardDesc> {
@@ -471,6 +838,116 @@ impl GroupEngine {
             .ok_or(Error::ShardNotFound(shard_id))
     }
 
+    /// Persist the applied time of the intents written by the write batch, and
+    /// remove those of the resolved intents, in the same write batch. The
+    /// intents are returned with the shard they belong to, the applied time is
+    /// `None` if the intent is resolved.
+    fn index_intents(
+        &self,
+        intents: Vec<(u64, Vec<u8>, Option<u64>)>,
+        wb: &mut rocksdb::WriteBatch,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+    ) -> Vec<(Option<u64>, Vec<u8>, Option<(u64, u64)>)> {
+        let core = self.core.read().unwrap();
+        let intent_stats = self.intent_stats.lock().unwrap();
+        intents
+            .into_iter()
+            .map(|(table_id, user_key, start_version)| {
+                let shard_id = core
+                    .shard_descs
+                    .values()
+                    .find(|s| s.table_id == table_id && shard::belong_to(s, &user_key))
+                    .map(|s| s.id);
+                let index_key = keys::intent_index(table_id, &user_key);
+                let Some(start_version) = start_version else {
+                    wb.delete_cf(cf_handle, index_key);
+                    return (shard_id, user_key, None);
+                };
+                let applied_at = match shard_id {
+                    Some(shard_id) => intent_stats.applied_at(shard_id, &user_key, start_version),
+                    None => sekas_runtime::time::timestamp_millis(),
+                };
+                wb.put_cf(cf_handle, index_key, values::intent_index(start_version, applied_at));
+                (shard_id, user_key, Some((start_version, applied_at)))
+            })
+            .collect()
+    }
+
+    /// Apply the intents written or resolved by a committed write batch to the
+    /// intent stats.
+    fn apply_intents(&self, intents: Vec<(Option<u64>, Vec<u8>, Option<(u64, u64)>)>) {
+        let mut intent_stats = self.intent_stats.lock().unwrap();
+        for (shard_id, user_key, written) in intents {
+            let Some(shard_id) = shard_id else { continue };
+            match written {
+                Some((start_version, applied_at)) => {
+                    intent_stats.on_intent_written(shard_id, user_key, start_version, applied_at)
+                }
+                None => intent_stats.on_intent_resolved(shard_id, &user_key),
+            }
+        }
+    }
+
+    /// Load the intent stats from the persisted intent index. It is only used
+    /// when the data of the group engine are replaced (opening or ingesting a
+    /// snapshot). The index is built by traversing the shards if the data are
+    /// written before the index is introduced.
+    fn load_intent_stats(&self) -> Result<()> {
+        let cf_handle = self.cf_handle();
+        if !internal::intent_index_built(&self.raw_db, &cf_handle)? {
+            self.build_intent_index()?;
+        }
+
+        let mut intent_stats = IntentStats::default();
+        let core = self.core.read().unwrap();
+        let intents = internal::intent_index(&self.raw_db, &cf_handle)?;
+        for (table_id, user_key, start_version, applied_at) in intents {
+            let Some(shard_id) = core
+                .shard_descs
+                .values()
+                .find(|s| s.table_id == table_id && shard::belong_to(s, &user_key))
+                .map(|s| s.id)
+            else {
+                continue;
+            };
+            intent_stats.on_intent_written(shard_id, user_key, start_version, applied_at);
+        }
+        *self.intent_stats.lock().unwrap() = intent_stats;
+        Ok(())
+    }
+
+    /// Build the intent index by traversing the shards, the ages of the
+    /// intents are counted from now on.
+    fn build_intent_index(&self) -> Result<()> {
+        use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+        let cf_handle = self.cf_handle();
+        let applied_at = sekas_runtime::time::timestamp_millis();
+        let mut wb = rocksdb::WriteBatch::default();
+        let shard_ids = self.core.read().unwrap().shard_descs.keys().cloned().collect::<Vec<_>>();
+        for shard_id in shard_ids {
+            let table_id = self.shard_desc(shard_id)?.table_id;
+            let mut snapshot = self.snapshot(shard_id, SnapshotMode::Start { start_key: None })?;
+            while let Some(mvcc_iter) = snapshot.next() {
+                let mut mvcc_iter = mvcc_iter?;
+                let Some(entry) = mvcc_iter.next().transpose()? else { continue };
+                if entry.version() != TXN_INTENT_VERSION {
+                    continue;
+                }
+                if let Some(txn_intent) = entry.value().and_then(|v| TxnIntent::decode(v).ok()) {
+                    wb.put_cf(
+                        &cf_handle,
+                        keys::intent_index(table_id, entry.user_key()),
+                        values::intent_index(txn_intent.start_version, applied_at),
+                    );
+                }
+            }
+        }
+        wb.put_cf(&cf_handle, keys::intent_index_prefix(), b"");
+        self.raw_db.write_opt(wb, &rocksdb::WriteOptions::default())?;
+        Ok(())
+    }
+
     #[inline]
     fn cf_handle(&self) -> Arc<rocksdb::BoundColumnFamily> {
         self.raw_db.cf_handle(&self.name).expect("column family handle")
@@ -485,17 +962,7 @@ impl GroupEngine {
 
     /// Get the raw db boundary of the target shard.
     fn shard_raw_boundary(&self, shard_id: u64) -> Result<(Vec<u8>, Vec<u8>)> {
-        let shard_desc = self.shard_desc(shard_id)?;
-        let RangePartition { start, end } = shard_desc.range.ok_or_else(|| {
-            Error::InvalidData(format!("the range field of shard {shard_id} is not set"))
-        })?;
-        let start = keys::raw(shard_desc.table_id, &start);
-        let end = if end.is_empty() {
-            lexical::lexical_next_boundary(&keys::raw(shard_desc.table_id, &end))
-        } else {
-            keys::raw(shard_desc.table_id, &end)
-        };
-        Ok((start, end))
+        internal::raw_boundary(&self.shard_desc(shard_id)?)
     }
 }
 
@@ -532,28 +999,34 @@ impl<'a> Iterator for RawIterator<'a> {
 }
 
 impl<'a> Snapshot<'a> {
-    fn new<'b>(
+    fn new(
         table_id: u64,
         db_iter: rocksdb::DBIterator<'a>,
-        snapshot_mode: SnapshotMode<'b>,
-        desc: &ShardDesc,
+        range: SnapshotRange,
+        reverse: bool,
     ) -> Self {
-        let range = match snapshot_mode {
-            SnapshotMode::Key { key } => Some(SnapshotRange::Target { target_key: key.to_owned() }),
-            SnapshotMode::Prefix { key } => Some(SnapshotRange::Prefix { prefix: key.to_owned() }),
-            SnapshotMode::Start { start_key } => Some(SnapshotRange::Range {
-                start: start_key.map(ToOwned::to_owned).unwrap_or_else(|| shard::start_key(desc)),
-                end: shard::end_key(desc),
-            }),
-        };
-
         Snapshot {
             table_id,
-            range,
-            core: SnapshotCore { db_iter, current_key: None, cached_entry: None },
+            range: Some(range),
+            faults: None,
+            core: SnapshotCore {
+                db_iter,
+                current_key: None,
+                cached_entry: None,
+                reverse,
+                versions: Vec::default(),
+                read_version: None,
+            },
         }
     }
 
+    /// Read the values expired at the read version as tombstones, so the
+    /// reads at the same version agree on every replica, see [`super::ttl`].
+    pub fn with_read_version(mut self, read_version: u64) -> Self {
+        self.core.read_version = Some(read_version);
+        self
+    }
+
     pub fn next(&mut self) -> Option<Result<MvccIterator<'a, '_>>> {
         self.next_mvcc_iterator()
     }
@@ -562,17 +1035,33 @@ impl<'a> Snapshot<'a> {
         let core = &mut self.core;
         loop {
             if let Some(entry) = core.cached_entry.as_ref() {
+                let mut is_valid_key = true;
                 if let Some(range) = self.range.as_ref() {
                     if !range.is_valid_key(entry.user_key()) {
-                        // The iterate target has been consumed.
-                        return None;
+                        if !core.reverse || range.is_before(entry.user_key()) {
+                            // The iterate target has been consumed.
+                            return None;
+                        }
+                        // The seek key of reverse iteration might be located at the intent of
+                        // the range end, skip it.
+                        is_valid_key = false;
                     }
                 }
 
                 // Skip iterated keys.
                 // TODO(walter) support seek to next user key to skip old versions.
-                if !core.is_current_key(entry.user_key()) {
+                if is_valid_key && !core.is_current_key(entry.user_key()) {
+                    if let Some((faults, replica_id)) = &self.faults {
+                        if let Err(err) = faults.hit(*replica_id, entry.user_key()) {
+                            return Some(Err(err));
+                        }
+                    }
                     core.current_key = Some(entry.user_key().to_owned());
+                    if core.reverse {
+                        if let Err(err) = core.load_versions(self.table_id) {
+                            return Some(Err(err));
+                        }
+                    }
                     return Some(Ok(MvccIterator { snapshot: self }));
                 }
             }
@@ -585,6 +1074,10 @@ impl<'a> Snapshot<'a> {
 
     fn next_mvcc_entry(&mut self) -> Option<Result<MvccEntry>> {
         let core = &mut self.core;
+        if core.reverse {
+            // All versions are loaded in ascending order.
+            return core.versions.pop().map(Ok);
+        }
         loop {
             if let Some(entry) = core.cached_entry.take() {
                 if core.is_current_key(entry.user_key()) {
@@ -614,10 +1107,30 @@ impl<'a> SnapshotCore<'a> {
             return None;
         }
 
+        // The expired value is read as absent.
+        let expired = self.read_version.is_some_and(|v| values::is_expired_at(&value, v));
+        let value = if expired { values::tombstone().into() } else { value };
         self.cached_entry = Some(MvccEntry::new(key, value));
         Some(Ok(()))
     }
 
+    /// Load all versions of the current key. It is used by the reverse
+    /// iteration, which visits versions in ascending order.
+    fn load_versions(&mut self, table_id: u64) -> Result<()> {
+        self.versions.clear();
+        while let Some(entry) = self.cached_entry.take() {
+            if !self.is_current_key(entry.user_key()) {
+                self.cached_entry = Some(entry);
+                break;
+            }
+            self.versions.push(entry);
+            if let Some(Err(err)) = self.next_entry(table_id) {
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
     #[inline]
     fn is_current_key(&self, target_key: &[u8]) -> bool {
         self.current_key.as_ref().map(|k| k == target_key).unwrap_or_default()
@@ -664,16 +1177,24 @@ impl MvccEntry {
     }
 
     /// Return value of this `MvccEntry`. `None` is returned if this entry is a
-    /// tombstone.
+    /// tombstone or a truncated marker.
     pub fn value(&self) -> Option<&[u8]> {
-        if self.value[0] == values::TOMBSTONE {
-            None
-        } else {
-            debug_assert_eq!(self.value[0], values::DATA);
-            Some(&self.value[1..])
+        match self.value[0] {
+            values::TOMBSTONE | values::TRUNCATED => None,
+            values::EXPIRING_DATA => Some(&self.value[values::EXPIRING_DATA_HEADER_LEN..]),
+            tag => {
+                debug_assert_eq!(tag, values::DATA);
+                Some(&self.value[1..])
+            }
         }
     }
 
+    /// The time this value expires at, in millis since the unix epoch, `None`
+    /// if it never expires.
+    pub fn expire_at(&self) -> Option<u64> {
+        values::expire_at(&self.value)
+    }
+
     #[allow(dead_code)]
     pub fn is_tombstone(&self) -> bool {
         self.value[0] == values::TOMBSTONE
@@ -681,17 +1202,73 @@ impl MvccEntry {
 
     #[allow(dead_code)]
     pub fn is_data(&self) -> bool {
-        self.value[0] == values::DATA
+        self.value[0] == values::DATA || self.value[0] == values::EXPIRING_DATA
+    }
+
+    /// Whether the older versions of this key are dropped by the max versions
+    /// of the table, so the reads requiring them should be rejected.
+    pub fn is_truncated(&self) -> bool {
+        self.value[0] == values::TRUNCATED
     }
 }
 
 impl From<MvccEntry> for Value {
     fn from(entry: MvccEntry) -> Self {
-        Value { content: entry.value().map(ToOwned::to_owned), version: entry.version() }
+        Value {
+            content: entry.value().map(ToOwned::to_owned),
+            version: entry.version(),
+            expire_at: entry.expire_at().unwrap_or_default(),
+        }
     }
 }
 
 impl SnapshotRange {
+    fn new(mode: &SnapshotMode<'_>, desc: &ShardDesc) -> Self {
+        match mode {
+            SnapshotMode::Key { key } | SnapshotMode::KeyAtVersion { key, .. } => {
+                SnapshotRange::Target { target_key: key.to_vec() }
+            }
+            SnapshotMode::Prefix { key } | SnapshotMode::ReversePrefix { key } => {
+                SnapshotRange::Prefix { prefix: key.to_vec() }
+            }
+            SnapshotMode::Start { start_key } => SnapshotRange::Range {
+                start: start_key.map(ToOwned::to_owned).unwrap_or_else(|| shard::start_key(desc)),
+                end: shard::end_key(desc),
+            },
+            SnapshotMode::End { end_key } => {
+                let shard_end = shard::end_key(desc);
+                let end = match end_key {
+                    Some(end_key) if shard_end.is_empty() || *end_key < shard_end.as_slice() => {
+                        // The end key is inclusive.
+                        lexical::lexical_next(end_key)
+                    }
+                    _ => shard_end,
+                };
+                SnapshotRange::Range { start: shard::start_key(desc), end }
+            }
+        }
+    }
+
+    /// Return the exclusive upper bound of this range, an empty value means
+    /// the upper bound is the end of the table.
+    fn upper_bound(&self) -> Vec<u8> {
+        match self {
+            SnapshotRange::Target { target_key } => lexical::lexical_next(target_key),
+            SnapshotRange::Prefix { prefix } => lexical::lexical_next_boundary(prefix),
+            SnapshotRange::Range { end, .. } => end.clone(),
+        }
+    }
+
+    /// Whether the key is less than all keys of this range.
+    #[inline]
+    fn is_before(&self, key: &[u8]) -> bool {
+        match self {
+            SnapshotRange::Target { target_key } => key < target_key.as_slice(),
+            SnapshotRange::Prefix { prefix } => key < prefix.as_slice(),
+            SnapshotRange::Range { start, .. } => key < start.as_slice(),
+        }
+    }
+
     #[inline]
     fn is_valid_key(&self, key: &[u8]) -> bool {
         match self {
@@ -703,16 +1280,42 @@ impl SnapshotRange {
     }
 }
 
+impl<'a> SnapshotMode<'a> {
+    /// Whether the user keys are traversed in descending order.
+    #[inline]
+    pub fn is_reverse(&self) -> bool {
+        matches!(self, SnapshotMode::End { .. } | SnapshotMode::ReversePrefix { .. })
+    }
+}
+
 impl<'a> Default for SnapshotMode<'a> {
     fn default() -> Self {
         SnapshotMode::Start { start_key: None }
     }
 }
 
-mod keys {
+/// Return the raw key to seek for the reverse iteration. An empty value is
+/// returned if the seek key is the end of the whole key space.
+fn reverse_seek_key(table_id: u64, range: &SnapshotRange) -> Vec<u8> {
+    let upper_bound = range.upper_bound();
+    if upper_bound.is_empty() {
+        lexical::lexical_next_boundary(&keys::raw(table_id, &upper_bound))
+    } else {
+        // The intent of the upper bound might be located at the seek key, it will be
+        // skipped during iteration.
+        keys::raw(table_id, &upper_bound)
+    }
+}
+
+pub(super) mod keys {
     const APPLY_STATE: &[u8] = b"APPLY_STATE";
     const DESCRIPTOR: &[u8] = b"DESCRIPTOR";
     const MIGRATE_STATE: &[u8] = b"MIGRATE_STATE";
+    const APPLY_CHECKPOINT: &[u8] = b"APPLY_CHECKPOINT";
+    const PURGE_SHARD_STATE: &[u8] = b"PURGE_SHARD_STATE";
+    const APPLY_QUARANTINE: &[u8] = b"APPLY_QUARANTINE";
+    const INTENT_INDEX: &[u8] = b"INTENT_INDEX";
+    const GC_WATERMARK: &[u8] = b"GC_WATERMARK";
 
     #[inline]
     pub fn raw(table_id: u64, key: &[u8]) -> Vec<u8> {
@@ -747,6 +1350,42 @@ mod keys {
         buf
     }
 
+    /// Extracts the table id, user key and version from the mvcc key of user
+    /// data, `None` is returned if it is not a mvcc key of user data.
+    pub fn revert_data_key(key: &[u8]) -> Option<(u64, Vec<u8>, u64)> {
+        const L: usize = core::mem::size_of::<u64>();
+        let (_, version) = split_data_key(key)?;
+        let table_id = u64::from_le_bytes(key[..L].try_into().unwrap());
+        Some((table_id, revert_mvcc_key(key), version))
+    }
+
+    /// Splits the mvcc key of user data into the prefix (the table id and the
+    /// encoded user key) and the version, without decoding the user key.
+    pub fn split_data_key(key: &[u8]) -> Option<(&[u8], u64)> {
+        const L: usize = core::mem::size_of::<u64>();
+        let len = key.len();
+        if len <= 2 * L || (len - 2 * L) % 9 != 0 {
+            return None;
+        }
+        let table_id = u64::from_le_bytes(key[..L].try_into().unwrap());
+        if table_id == super::LOCAL_TABLE_ID {
+            return None;
+        }
+        let version = !u64::from_be_bytes(key[(len - L)..].try_into().unwrap());
+        Some((&key[..(len - L)], version))
+    }
+
+    /// Extracts the table id and user key from the mvcc key of a txn intent,
+    /// `None` is returned if it is not an intent.
+    pub fn revert_intent_key(key: &[u8]) -> Option<(u64, Vec<u8>)> {
+        use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+        match revert_data_key(key) {
+            Some((table_id, user_key, TXN_INTENT_VERSION)) => Some((table_id, user_key)),
+            _ => None,
+        }
+    }
+
     /// Extracts user key from the mvcc key.
     pub fn may_revert_mvcc_key(key: &[u8]) -> Option<Vec<u8>> {
         const L: usize = core::mem::size_of::<u64>();
@@ -802,31 +1441,175 @@ mod keys {
         buf.extend_from_slice(MIGRATE_STATE);
         buf
     }
+
+    #[inline]
+    pub fn apply_checkpoint() -> Vec<u8> {
+        let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + APPLY_CHECKPOINT.len());
+        buf.extend_from_slice(super::LOCAL_TABLE_ID.to_le_bytes().as_slice());
+        buf.extend_from_slice(APPLY_CHECKPOINT);
+        buf
+    }
+
+    #[inline]
+    pub fn apply_quarantine() -> Vec<u8> {
+        let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + APPLY_QUARANTINE.len());
+        buf.extend_from_slice(super::LOCAL_TABLE_ID.to_le_bytes().as_slice());
+        buf.extend_from_slice(APPLY_QUARANTINE);
+        buf
+    }
+
+    #[inline]
+    pub fn gc_watermark() -> Vec<u8> {
+        let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + GC_WATERMARK.len());
+        buf.extend_from_slice(super::LOCAL_TABLE_ID.to_le_bytes().as_slice());
+        buf.extend_from_slice(GC_WATERMARK);
+        buf
+    }
+
+    #[inline]
+    pub fn purge_shard_state_prefix() -> Vec<u8> {
+        let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + PURGE_SHARD_STATE.len());
+        buf.extend_from_slice(super::LOCAL_TABLE_ID.to_le_bytes().as_slice());
+        buf.extend_from_slice(PURGE_SHARD_STATE);
+        buf
+    }
+
+    #[inline]
+    pub fn purge_shard_state(shard_id: u64) -> Vec<u8> {
+        let mut buf = purge_shard_state_prefix();
+        buf.extend_from_slice(shard_id.to_be_bytes().as_slice());
+        buf
+    }
+
+    /// The prefix of the intent index, the prefix itself marks the index is
+    /// built.
+    #[inline]
+    pub fn intent_index_prefix() -> Vec<u8> {
+        let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + INTENT_INDEX.len());
+        buf.extend_from_slice(super::LOCAL_TABLE_ID.to_le_bytes().as_slice());
+        buf.extend_from_slice(INTENT_INDEX);
+        buf
+    }
+
+    #[inline]
+    pub fn intent_index(table_id: u64, user_key: &[u8]) -> Vec<u8> {
+        let mut buf = intent_index_prefix();
+        buf.extend_from_slice(table_id.to_be_bytes().as_slice());
+        buf.extend_from_slice(user_key);
+        buf
+    }
+
+    /// Extracts the table id and user key from the key of the intent index.
+    pub fn revert_intent_index(key: &[u8]) -> Option<(u64, Vec<u8>)> {
+        const L: usize = core::mem::size_of::<u64>();
+        let suffix = key.strip_prefix(intent_index_prefix().as_slice())?;
+        if suffix.len() < L {
+            return None;
+        }
+        let table_id = u64::from_be_bytes(suffix[..L].try_into().unwrap());
+        Some((table_id, suffix[L..].to_owned()))
+    }
 }
 
-mod values {
+pub(super) mod values {
+    use super::ttl;
+
     pub(super) const DATA: u8 = 0;
     pub(super) const TOMBSTONE: u8 = 1;
+    /// The marker of the newest version dropped by the max versions.
+    pub(super) const TRUNCATED: u8 = 2;
+    /// The data with an expiry, the expiry (in millis since the unix epoch,
+    /// big endian) is followed by the content.
+    pub(super) const EXPIRING_DATA: u8 = 3;
+    pub(super) const EXPIRING_DATA_HEADER_LEN: usize = 1 + core::mem::size_of::<u64>();
 
     #[inline]
     pub fn tombstone() -> &'static [u8] {
         &[TOMBSTONE]
     }
 
+    #[inline]
+    pub fn is_tombstone(v: &[u8]) -> bool {
+        v.first() == Some(&TOMBSTONE)
+    }
+
+    #[inline]
+    pub fn truncated() -> &'static [u8] {
+        &[TRUNCATED]
+    }
+
+    #[inline]
+    pub fn is_truncated(v: &[u8]) -> bool {
+        v.first() == Some(&TRUNCATED)
+    }
+
     pub fn data(v: &[u8]) -> Vec<u8> {
         let mut buf = Vec::with_capacity(v.len() + 1);
         buf.push(DATA);
         buf.extend_from_slice(v);
         buf
     }
+
+    pub fn expiring_data(v: &[u8], expire_at: u64) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(v.len() + EXPIRING_DATA_HEADER_LEN);
+        buf.push(EXPIRING_DATA);
+        buf.extend_from_slice(&expire_at.to_be_bytes());
+        buf.extend_from_slice(v);
+        buf
+    }
+
+    /// The expiry of the value, `None` if it never expires.
+    #[inline]
+    pub fn expire_at(v: &[u8]) -> Option<u64> {
+        if v.first() != Some(&EXPIRING_DATA) || v.len() < EXPIRING_DATA_HEADER_LEN {
+            return None;
+        }
+        Some(u64::from_be_bytes(v[1..EXPIRING_DATA_HEADER_LEN].try_into().unwrap()))
+    }
+
+    /// Whether the value is expired at the version.
+    #[inline]
+    pub fn is_expired_at(v: &[u8], version: u64) -> bool {
+        expire_at(v).is_some_and(|expire_at| ttl::is_expired_at(expire_at, version))
+    }
+
+    /// The value of the intent index, the start version of the txn is followed
+    /// by the applied time (in millis since the unix epoch) of the intent.
+    pub fn intent_index(start_version: u64, applied_at: u64) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(2 * core::mem::size_of::<u64>());
+        buf.extend_from_slice(&start_version.to_be_bytes());
+        buf.extend_from_slice(&applied_at.to_be_bytes());
+        buf
+    }
+
+    /// Extracts the start version and the applied time from the value of the
+    /// intent index.
+    pub fn revert_intent_index(v: &[u8]) -> Option<(u64, u64)> {
+        const L: usize = core::mem::size_of::<u64>();
+        if v.len() != 2 * L {
+            return None;
+        }
+        let start_version = u64::from_be_bytes(v[..L].try_into().unwrap());
+        let applied_at = u64::from_be_bytes(v[L..].try_into().unwrap());
+        Some((start_version, applied_at))
+    }
 }
 
 impl<'a, 'b> rocksdb::WriteBatchIterator for ColumnFamilyDecorator<'a, 'b> {
     fn put(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
+        if let Some((table_id, user_key)) = keys::revert_intent_key(&key) {
+            if let Some(Ok(txn_intent)) = value.strip_prefix(&[values::DATA]).map(TxnIntent::decode)
+            {
+                self.intents.push((table_id, user_key, Some(txn_intent.start_version)));
+            }
+        }
         self.wb.put_cf(&self.cf_handle, key, value);
     }
 
     fn delete(&mut self, key: Box<[u8]>) {
+        if let Some((table_id, user_key)) = keys::revert_intent_key(&key) {
+            self.intents.push((table_id, user_key, None));
+        }
         self.wb.delete_cf(&self.cf_handle, key);
     }
 }
@@ -836,6 +1619,42 @@ impl WriteBatch {
     pub fn new(content: &[u8]) -> Self {
         WriteBatch { inner: rocksdb::WriteBatch::from_data(content) }
     }
+
+    /// Returns the table id, user key and kind of the user writes in this
+    /// batch, in the order they are written.
+    pub fn user_writes(&self) -> Vec<(u64, Vec<u8>, WriteKind)> {
+        let mut collector = UserWriteCollector::default();
+        self.inner.iterate(&mut collector);
+        collector.writes
+    }
+}
+
+#[derive(Default)]
+struct UserWriteCollector {
+    writes: Vec<(u64, Vec<u8>, WriteKind)>,
+}
+
+impl rocksdb::WriteBatchIterator for UserWriteCollector {
+    fn put(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
+        use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+        if let Some((table_id, user_key, version)) = keys::revert_data_key(&key) {
+            let kind = if version == TXN_INTENT_VERSION {
+                WriteKind::Intent
+            } else if value.first() == Some(&values::TOMBSTONE) {
+                WriteKind::Tombstone
+            } else {
+                WriteKind::Put
+            };
+            self.writes.push((table_id, user_key, kind));
+        }
+    }
+
+    fn delete(&mut self, key: Box<[u8]>) {
+        if let Some((table_id, user_key, _)) = keys::revert_data_key(&key) {
+            self.writes.push((table_id, user_key, WriteKind::Clear));
+        }
+    }
 }
 
 impl Deref for WriteBatch {
@@ -860,6 +1679,9 @@ impl WriteStates {
         if let Some(desc) = &self.descriptor {
             wb.put_cf(cf_handle, keys::descriptor(), desc.encode_to_vec());
         }
+        if let Some(checkpoint) = &self.apply_checkpoint {
+            wb.put_cf(cf_handle, keys::apply_checkpoint(), checkpoint.encode_to_vec());
+        }
         if let Some(move_shard_state) = &self.move_shard_state {
             // Moving shard in abort or finish steps are not persisted.
             if move_shard_state.step != MoveShardStep::Finished as i32
@@ -870,6 +1692,13 @@ impl WriteStates {
                 wb.delete_cf(cf_handle, keys::move_shard_state());
             }
         }
+        for state in &self.purge_shard_states {
+            let shard_id = state.shard.as_ref().map(|s| s.id).unwrap_or_default();
+            wb.put_cf(cf_handle, keys::purge_shard_state(shard_id), state.encode_to_vec());
+        }
+        if let Some(watermark) = self.gc_watermark {
+            wb.put_cf(cf_handle, keys::gc_watermark(), watermark.to_le_bytes());
+        }
     }
 }
 
@@ -909,6 +1738,70 @@ mod internal {
         Ok(GroupDesc::decode(value.as_ref())?)
     }
 
+    /// Get the raw db boundary of the shard.
+    pub(super) fn raw_boundary(shard_desc: &ShardDesc) -> Result<(Vec<u8>, Vec<u8>)> {
+        let RangePartition { start, end } = shard_desc.range.clone().ok_or_else(|| {
+            Error::InvalidData(format!("the range field of shard {} is not set", shard_desc.id))
+        })?;
+        let start = keys::raw(shard_desc.table_id, &start);
+        let end = if end.is_empty() {
+            lexical::lexical_next_boundary(&keys::raw(shard_desc.table_id, &end))
+        } else {
+            keys::raw(shard_desc.table_id, &end)
+        };
+        Ok((start, end))
+    }
+
+    pub(super) fn purge_shard_states(
+        db: &RawDb,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+    ) -> Result<Vec<PurgeShardState>> {
+        use rocksdb::{Direction, IteratorMode, ReadOptions};
+
+        let prefix = keys::purge_shard_state_prefix();
+        let mode = IteratorMode::From(&prefix, Direction::Forward);
+        let mut states = vec![];
+        for item in db.iterator_cf_opt(cf_handle, ReadOptions::default(), mode) {
+            let (key, value) = item?;
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            states.push(PurgeShardState::decode(value.as_ref())?);
+        }
+        Ok(states)
+    }
+
+    pub(super) fn intent_index_built(
+        db: &RawDb,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+    ) -> Result<bool> {
+        Ok(db.get_pinned_cf(cf_handle, keys::intent_index_prefix())?.is_some())
+    }
+
+    /// Returns the table id, user key, start version and applied time of the
+    /// indexed intents.
+    pub(super) fn intent_index(
+        db: &RawDb,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+    ) -> Result<Vec<(u64, Vec<u8>, u64, u64)>> {
+        use rocksdb::{Direction, IteratorMode, ReadOptions};
+
+        let prefix = keys::intent_index_prefix();
+        let mut opts = ReadOptions::default();
+        opts.set_iterate_upper_bound(lexical::lexical_next_boundary(&prefix));
+        let mode = IteratorMode::From(&prefix, Direction::Forward);
+        let mut intents = vec![];
+        for item in db.iterator_cf_opt(cf_handle, opts, mode) {
+            let (key, value) = item?;
+            let Some((table_id, user_key)) = keys::revert_intent_index(&key) else { continue };
+            let Some((start_version, applied_at)) = values::revert_intent_index(&value) else {
+                continue;
+            };
+            intents.push((table_id, user_key, start_version, applied_at));
+        }
+        Ok(intents)
+    }
+
     pub(super) fn move_shard_state(
         db: &RawDb,
         cf_handle: &impl rocksdb::AsColumnFamilyRef,
@@ -920,6 +1813,21 @@ mod internal {
         }
     }
 
+    /// Returns the persisted GC watermark, `0` if it is never advanced.
+    pub(super) fn gc_watermark(
+        db: &RawDb,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+    ) -> Result<u64> {
+        let Some(v) = db.get_pinned_cf(cf_handle, keys::gc_watermark())? else {
+            return Ok(0);
+        };
+        let bytes = v
+            .as_ref()
+            .try_into()
+            .map_err(|_| Error::InvalidData(format!("the gc watermark has {} bytes", v.len())))?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
     pub(super) fn flushed_apply_state(
         db: &RawDb,
         cf_handle: &impl rocksdb::AsColumnFamilyRef,
@@ -933,6 +1841,35 @@ mod internal {
         Ok(ApplyState::decode(value.as_ref())?)
     }
 
+    pub(super) fn flushed_apply_checkpoint(
+        db: &RawDb,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+    ) -> Result<Option<ApplyCheckpoint>> {
+        let opt = persisted_read_options();
+        if let Some(v) = db.get_pinned_cf_opt(cf_handle, keys::apply_checkpoint(), &opt)? {
+            Ok(Some(ApplyCheckpoint::decode(v.as_ref())?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub(super) fn flushed_descriptor(
+        db: &RawDb,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+    ) -> Result<GroupDesc> {
+        let opt = persisted_read_options();
+        let value = db
+            .get_pinned_cf_opt(cf_handle, keys::descriptor(), &opt)?
+            .expect("group descriptor will persisted when creating group");
+        Ok(GroupDesc::decode(value.as_ref())?)
+    }
+
+    fn persisted_read_options() -> rocksdb::ReadOptions {
+        let mut opt = rocksdb::ReadOptions::default();
+        opt.set_read_tier(rocksdb::ReadTier::Persisted);
+        opt
+    }
+
     #[inline]
     pub(super) fn shard_descs(group_desc: &GroupDesc) -> HashMap<u64, ShardDesc> {
         group_desc.shards.iter().map(|shard| (shard.id, shard.clone())).collect::<HashMap<_, _>>()
@@ -1377,6 +2314,78 @@ mod tests {
         assert!(snapshot.next().is_none());
     }
 
+    #[sekas_macro::test]
+    async fn iterate_in_reverse_order() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let group_engine = create_engine(1, 1, dir.path()).await;
+        let mut wb = WriteBatch::default();
+        group_engine.put(&mut wb, 1, b"a", b"", 1).unwrap();
+        group_engine.put(&mut wb, 1, b"a\x00", b"", 1).unwrap();
+        group_engine.put(&mut wb, 1, b"a\x00", b"", 2).unwrap();
+        group_engine.put(&mut wb, 1, b"a\xFF", b"", 1).unwrap();
+        group_engine.put(&mut wb, 1, b"b", b"", 1).unwrap();
+        group_engine.put(&mut wb, 1, b"b", b"", 2).unwrap();
+        group_engine.put(&mut wb, 1, b"b", b"", 3).unwrap();
+        group_engine.put(&mut wb, 1, b"c", b"", 1).unwrap();
+        group_engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        fn collect(snapshot: &mut Snapshot<'_>) -> Vec<(Vec<u8>, Vec<u64>)> {
+            let mut keys = vec![];
+            while let Some(mvcc_iter) = snapshot.next() {
+                let mvcc_iter = mvcc_iter.unwrap();
+                let user_key = mvcc_iter.user_key().to_owned();
+                let versions = mvcc_iter.map(|e| e.unwrap().version()).collect::<Vec<_>>();
+                keys.push((user_key, versions));
+            }
+            keys
+        }
+
+        // Iterate all keys in reverse order, the versions are still in descending
+        // order.
+        let mut snapshot = group_engine.snapshot(1, SnapshotMode::End { end_key: None }).unwrap();
+        let mut forward = collect(&mut group_engine.snapshot(1, SnapshotMode::default()).unwrap());
+        let reverse = collect(&mut snapshot);
+        assert_eq!(reverse[0], (b"c".to_vec(), vec![1]));
+        assert_eq!(reverse[1], (b"b".to_vec(), vec![3, 2, 1]));
+        forward.reverse();
+        assert_eq!(forward, reverse);
+
+        // The end key is inclusive.
+        let snapshot_mode = SnapshotMode::End { end_key: Some(b"a\x00") };
+        let mut snapshot = group_engine.snapshot(1, snapshot_mode).unwrap();
+        let keys = collect(&mut snapshot);
+        assert_eq!(keys, vec![(b"a\x00".to_vec(), vec![2, 1]), (b"a".to_vec(), vec![1])]);
+
+        // Iterate with prefix.
+        let snapshot_mode = SnapshotMode::ReversePrefix { key: b"a" };
+        let mut snapshot = group_engine.snapshot(1, snapshot_mode).unwrap();
+        let keys = collect(&mut snapshot).into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys, vec![b"a\xFF".to_vec(), b"a\x00".to_vec(), b"a".to_vec()]);
+
+        // Split the shard, the keys of the other shard are invisible.
+        let wb = WriteBatch::default();
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![
+                    ShardDesc::with_range(1, 1, vec![], vec![b'b']),
+                    ShardDesc::with_range(2, 1, vec![b'b'], vec![]),
+                ],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        group_engine.commit(wb, states, false).unwrap();
+
+        let mut snapshot = group_engine.snapshot(1, SnapshotMode::End { end_key: None }).unwrap();
+        let keys = collect(&mut snapshot).into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys, vec![b"a\xFF".to_vec(), b"a\x00".to_vec(), b"a".to_vec()]);
+
+        let mut snapshot = group_engine.snapshot(2, SnapshotMode::End { end_key: None }).unwrap();
+        let keys = collect(&mut snapshot).into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys, vec![b"c".to_vec(), b"b".to_vec()]);
+    }
+
     #[sekas_macro::test]
     async fn raw_iterate_all() {
         #[derive(Debug)]
@@ -1409,6 +2418,8 @@ mod tests {
         assert!(k[..] == keys::apply_state());
         let (k, _) = iter.next().unwrap().unwrap();
         assert!(k[..] == keys::descriptor());
+        let (k, _) = iter.next().unwrap().unwrap();
+        assert!(k[..] == keys::intent_index_prefix());
 
         // The the user payloads.
         for payload in &payloads {
@@ -1502,6 +2513,7 @@ mod tests {
                 }),
                 last_moved_key: None,
                 step: MoveShardStep::Prepare.into(),
+                ..Default::default()
             };
             let states = WriteStates {
                 move_shard_state: Some(move_shard_state.clone()),
@@ -1516,7 +2528,7 @@ mod tests {
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, 1, key, value, *version).unwrap();
             } else {
@@ -1532,19 +2544,13 @@ mod tests {
             // empty values.
             vec![],
             // a tombstone.
-            vec![Value { version: 1, content: None }],
+            vec![Value::tombstone(1)],
             // a write.
-            vec![Value { version: 1, content: Some(vec![b'1']) }],
+            vec![Value::with_value(vec![b'1'], 1)],
             // a write overwrite a tombstone.
-            vec![
-                Value { version: 2, content: Some(vec![b'1']) },
-                Value { version: 1, content: None },
-            ],
+            vec![Value::with_value(vec![b'1'], 2), Value::tombstone(1)],
             // a tombstone overwrite a write.
-            vec![
-                Value { version: 2, content: None },
-                Value { version: 1, content: Some(vec![b'1']) },
-            ],
+            vec![Value::tombstone(2), Value::with_value(vec![b'1'], 1)],
         ];
 
         let dir = TempDir::new(fn_name!()).unwrap();
@@ -1558,6 +2564,45 @@ mod tests {
         }
     }
 
+    #[sekas_macro::test]
+    async fn expired_values_read_as_tombstones() {
+        const SECOND: u64 = 1_000_000_000;
+
+        fn read_at(engine: &GroupEngine, key: &[u8], read_version: u64) -> Vec<Value> {
+            let snapshot = engine.snapshot(1, SnapshotMode::Key { key }).unwrap();
+            let mut snapshot = snapshot.with_read_version(read_version);
+            let iter = snapshot.next().unwrap().unwrap();
+            iter.map(|entry| entry.unwrap().into()).collect()
+        }
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path().join("1").as_path()).await;
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, 1, b"a", b"1", SECOND).unwrap();
+        engine.put_with_ttl(&mut wb, 1, b"a", b"2", 2 * SECOND, 1000).unwrap();
+        engine.put_with_ttl(&mut wb, 1, b"b", b"1", SECOND, 1000).unwrap();
+        engine.put(&mut wb, 1, b"b", b"2", 2 * SECOND).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        // The expiry is derived from the commit version.
+        let value = Value { content: Some(b"2".to_vec()), version: 2 * SECOND, expire_at: 3000 };
+        assert_eq!(read_at(&engine, b"a", 3 * SECOND - 1)[0], value);
+
+        // The older version is not exposed by the expired one, and the later write
+        // without TTL clears the expiry.
+        assert_eq!(
+            read_at(&engine, b"a", 3 * SECOND),
+            vec![Value::tombstone(2 * SECOND), Value::with_value(b"1".to_vec(), SECOND)]
+        );
+        assert_eq!(
+            read_at(&engine, b"b", 3 * SECOND)[0],
+            Value::with_value(b"2".to_vec(), 2 * SECOND)
+        );
+
+        // The raw values are read without the read version.
+        assert_eq!(engine.get(1, b"a").await.unwrap(), Some(value));
+    }
+
     #[sekas_macro::test]
     async fn estimate_split_key_of_all_range() {
         let dir = TempDir::new(fn_name!()).unwrap();
@@ -1622,4 +2667,303 @@ mod tests {
         let split_key = engine.estimate_split_key(shard_id).unwrap();
         assert!(split_key.is_some());
     }
+
+    #[sekas_macro::test]
+    async fn write_batch_user_writes() {
+        use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let (group_id, shard_id) = (1, 1);
+        let engine = create_engine(group_id, shard_id, dir.path()).await;
+        let table_id = engine.shard_desc(shard_id).unwrap().table_id;
+
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, shard_id, b"a", b"value", 1).unwrap();
+        engine.tombstone(&mut wb, shard_id, b"b", 2).unwrap();
+        engine.put(&mut wb, shard_id, b"c", b"intent", TXN_INTENT_VERSION).unwrap();
+        engine.delete(&mut wb, shard_id, b"123456789", TXN_INTENT_VERSION).unwrap();
+
+        let wb = WriteBatch::new(wb.data());
+        assert_eq!(
+            wb.user_writes(),
+            vec![
+                (table_id, b"a".to_vec(), WriteKind::Put),
+                (table_id, b"b".to_vec(), WriteKind::Tombstone),
+                (table_id, b"c".to_vec(), WriteKind::Intent),
+                (table_id, b"123456789".to_vec(), WriteKind::Clear),
+            ]
+        );
+    }
+
+    #[sekas_macro::test]
+    async fn purge_shard_chunk_keeps_served_shards() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let group_id = 1;
+        let engine = create_engine(group_id, 1, dir.path()).await;
+        let left = ShardDesc::with_range(1, 1, vec![], b"m".to_vec());
+        let right = ShardDesc::with_range(2, 1, b"m".to_vec(), vec![]);
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: group_id,
+                shards: vec![left.clone(), right.clone()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        let mut wb = WriteBatch::default();
+        for i in 0..100 {
+            engine.put(&mut wb, 1, format!("a-{i:03}").as_bytes(), b"value", 1).unwrap();
+            engine.put(&mut wb, 2, format!("m-{i:03}").as_bytes(), b"value", 1).unwrap();
+        }
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        // The purged range overlaps the served shard on purpose.
+        let purged_shard = ShardDesc::with_range(1, 1, vec![], vec![]);
+        let states = WriteStates {
+            descriptor: Some(GroupDesc { id: group_id, shards: vec![right], ..Default::default() }),
+            purge_shard_states: vec![PurgeShardState {
+                shard: Some(purged_shard),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        // Each chunk resumes from the persisted state.
+        let mut num_chunks = 0;
+        loop {
+            let mut states = engine.purge_shard_states().unwrap();
+            assert_eq!(states.len(), 1);
+            let mut state = states.pop().unwrap();
+            if state.finished {
+                break;
+            }
+            engine.purge_shard_chunk(&mut state, 30).unwrap();
+            num_chunks += 1;
+        }
+        assert_eq!(num_chunks, 7);
+
+        let state = engine.purge_shard_states().unwrap().pop().unwrap();
+        assert!(state.resume_key.is_none());
+        assert!(state.purged_bytes > 0);
+        engine.finish_purge_shard(&state).unwrap();
+        assert!(engine.purge_shard_states().unwrap().is_empty());
+
+        let left_start = keys::raw(1, b"a");
+        let left_end = keys::raw(1, b"m");
+        let iter = engine.raw_db.iterator_cf_opt(
+            &engine.cf_handle(),
+            rocksdb::ReadOptions::default(),
+            rocksdb::IteratorMode::From(&left_start, rocksdb::Direction::Forward),
+        );
+        let remains = iter.map(|item| item.unwrap().0).filter(|key| **key < *left_end).count();
+        assert_eq!(remains, 0);
+        for i in 0..100 {
+            let value = engine.get(2, format!("m-{i:03}").as_bytes()).await.unwrap();
+            assert!(value.is_some());
+        }
+    }
+
+    #[sekas_macro::test]
+    async fn compaction_drops_versions_beneath_gc_watermark() {
+        use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+        let shard_id = 1;
+        let mut wb = WriteBatch::default();
+        for i in 0..10 {
+            let key = format!("key-{i}");
+            for version in 1..=20 {
+                engine.put(&mut wb, shard_id, key.as_bytes(), b"value", version).unwrap();
+            }
+        }
+        engine.put(&mut wb, shard_id, b"key-0", b"intent", TXN_INTENT_VERSION).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        let num_versions = || {
+            let iter = engine.raw_iter().unwrap();
+            iter.filter(|item| {
+                let (key, _) = item.as_ref().unwrap();
+                keys::split_data_key(key).is_some()
+            })
+            .count()
+        };
+        assert_eq!(num_versions(), 201);
+
+        // Nothing is dropped without a watermark.
+        engine.compact();
+        assert_eq!(num_versions(), 201);
+
+        engine.gc_state().set_watermark(10);
+        engine.compact();
+        // The versions above the watermark, the newest version beneath the
+        // watermark and the intent are kept.
+        assert_eq!(num_versions(), 10 * 11 + 1);
+        assert_eq!(engine.gc_state().dropped().0, 90);
+        let value_set = engine.get_all_versions(shard_id, b"key-0").await.unwrap();
+        let versions = value_set.values.iter().map(|v| v.version).collect::<Vec<_>>();
+        let mut expected = vec![TXN_INTENT_VERSION];
+        expected.extend((10..=20).rev());
+        assert_eq!(versions, expected);
+    }
+
+    #[sekas_macro::test]
+    async fn save_and_clear_apply_quarantine() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_engine(1, 1, dir.path()).await;
+        assert_eq!(engine.apply_quarantine().unwrap(), None);
+
+        let quarantine = ApplyQuarantine {
+            index: 10,
+            term: 2,
+            message: "poisoned".to_owned(),
+            corrupted: false,
+        };
+        engine.save_apply_quarantine(Some(&quarantine)).unwrap();
+        assert_eq!(engine.apply_quarantine().unwrap(), Some(quarantine));
+
+        engine.save_apply_quarantine(None).unwrap();
+        assert_eq!(engine.apply_quarantine().unwrap(), None);
+    }
+
+    #[sekas_macro::test]
+    async fn purge_shard_resumes_after_restart() {
+        use crate::bootstrap::open_engine_with_default_config;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let (group_id, shard_id) = (1, 1);
+        let engine = create_engine(group_id, shard_id, dir.path()).await;
+        let shard_desc = engine.shard_desc(shard_id).unwrap();
+        let mut wb = WriteBatch::default();
+        for i in 0..100 {
+            engine.put(&mut wb, shard_id, format!("a-{i:03}").as_bytes(), b"value", 1).unwrap();
+        }
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+        let states = WriteStates {
+            descriptor: Some(GroupDesc { id: group_id, ..Default::default() }),
+            purge_shard_states: vec![PurgeShardState {
+                shard: Some(shard_desc),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        // Restart the engine as if the node is crashed, nothing but the
+        // persisted data is kept.
+        let db_dir = dir.path().join("db");
+        let restart = |engine: GroupEngine| {
+            let db_dir = db_dir.clone();
+            async move {
+                drop(engine);
+                let db = Arc::new(open_engine_with_default_config(db_dir).unwrap());
+                GroupEngine::open(&EngineConfig::default(), db, group_id, shard_id)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            }
+        };
+
+        let mut state = engine.purge_shard_states().unwrap().pop().unwrap();
+        engine.purge_shard_chunk(&mut state, 30).unwrap();
+        engine.purge_shard_chunk(&mut state, 30).unwrap();
+        let engine = restart(engine).await;
+
+        // The purging is resumed from the persisted progress.
+        let mut resumed = engine.purge_shard_states().unwrap().pop().unwrap();
+        assert_eq!(resumed, state);
+        while !resumed.finished {
+            engine.purge_shard_chunk(&mut resumed, 30).unwrap();
+        }
+        assert!(engine.purge_shard_states().unwrap().pop().unwrap().finished);
+
+        // The finished state is kept until the range is compacted.
+        let engine = restart(engine).await;
+        let state = engine.purge_shard_states().unwrap().pop().unwrap();
+        assert!(state.finished);
+        assert_eq!(state.purged_bytes, resumed.purged_bytes);
+        engine.finish_purge_shard(&state).unwrap();
+        let engine = restart(engine).await;
+        assert!(engine.purge_shard_states().unwrap().is_empty());
+        let start = keys::raw(1, b"a");
+        let iter = engine.raw_db.iterator_cf_opt(
+            &engine.cf_handle(),
+            rocksdb::ReadOptions::default(),
+            rocksdb::IteratorMode::From(&start, rocksdb::Direction::Forward),
+        );
+        let end = keys::raw(1, b"b");
+        assert_eq!(iter.map(|item| item.unwrap().0).filter(|key| **key < *end).count(), 0);
+    }
+
+    #[sekas_macro::test]
+    async fn gc_watermark_is_reloaded_after_restart() {
+        use crate::bootstrap::open_engine_with_default_config;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let (group_id, shard_id) = (1, 1);
+        let engine = create_engine(group_id, shard_id, dir.path()).await;
+        assert_eq!(engine.gc_state().unpinned_watermark(), 0);
+        let states = WriteStates { gc_watermark: Some(10), ..Default::default() };
+        engine.commit(WriteBatch::default(), states, true).unwrap();
+        assert_eq!(engine.gc_state().unpinned_watermark(), 10);
+
+        drop(engine);
+        let db = Arc::new(open_engine_with_default_config(dir.path().join("db")).unwrap());
+        let engine =
+            GroupEngine::open(&EngineConfig::default(), db, group_id, shard_id).await.unwrap();
+        assert_eq!(engine.unwrap().gc_state().unpinned_watermark(), 10);
+    }
+
+    #[sekas_macro::test]
+    async fn intent_stats_are_reloaded_from_index() {
+        use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let (group_id, shard_id) = (1, 1);
+        let engine = create_engine(group_id, shard_id, dir.path()).await;
+        let intent = TxnIntent { start_version: 10, ..Default::default() }.encode_to_vec();
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, shard_id, b"a", &intent, TXN_INTENT_VERSION).unwrap();
+        engine.put(&mut wb, shard_id, b"b", &intent, TXN_INTENT_VERSION).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+        let mut wb = WriteBatch::default();
+        engine.delete(&mut wb, shard_id, b"b", TXN_INTENT_VERSION).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+        let expected = engine.oldest_intents(shard_id, 10);
+        assert_eq!(expected.len(), 1);
+
+        let reopen = |engine: GroupEngine| async move {
+            let raw_db = engine.raw_db.clone();
+            drop(engine);
+            GroupEngine::open(&EngineConfig::default(), raw_db, group_id, shard_id)
+                .await
+                .unwrap()
+                .unwrap()
+        };
+
+        // The applied time of the intents are kept.
+        let engine = reopen(engine).await;
+        let intents = engine.oldest_intents(shard_id, 10);
+        assert_eq!(intents.len(), 1);
+        assert_eq!(intents[0].user_key, b"a".to_vec());
+        assert_eq!(intents[0].start_version, 10);
+        assert!(intents[0].age >= expected[0].age);
+
+        // The index is built by traversing the shards if it doesn't exist.
+        let table_id = engine.shard_desc(shard_id).unwrap().table_id;
+        let mut wb = rocksdb::WriteBatch::default();
+        wb.delete_cf(&engine.cf_handle(), keys::intent_index_prefix());
+        wb.delete_cf(&engine.cf_handle(), keys::intent_index(table_id, b"a"));
+        engine.raw_db.write_opt(wb, &rocksdb::WriteOptions::default()).unwrap();
+        let engine = reopen(engine).await;
+        assert_eq!(engine.shard_intent_stats(shard_id).num_intents, 1);
+        let cf_handle = engine.cf_handle();
+        assert!(internal::intent_index_built(&engine.raw_db, &cf_handle).unwrap());
+        let index = internal::intent_index(&engine.raw_db, &cf_handle).unwrap();
+        assert_eq!(index.len(), 1);
+        assert_eq!(index[0].1, b"a".to_vec());
+    }
 }
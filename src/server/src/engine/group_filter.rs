@@ -12,37 +12,493 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use rocksdb::{
-    compaction_filter::{CompactionFilter, Decision},
-    compaction_filter_factory::CompactionFilterFactory,
-};
+//! Drop the MVCC versions beneath the GC watermark during compaction.
+//!
+//! The reads below the GC watermark of a replica are rejected, so only the
+//! newest version not above the watermark is visible to the reads, the older
+//! versions of the same key are superseded. Dropping them inline during
+//! compaction avoids reading them and writing deletes by a separate job.
+//!
+//! The versions of a key beyond the `max_versions` of its table are dropped
+//! too, as long as they are beneath the watermark. The watermark is replicated
+//! by the group, so every replica drops the same versions regardless of its
+//! local reads. The newest dropped version is replaced by a truncated marker,
+//! so the reads requiring the dropped versions are rejected instead of missing
+//! the values.
+//!
+//! The values expired at the watermark are replaced by tombstones, or dropped
+//! as the tombstones are, since they are read as absent by every read accepted
+//! by the group, see [`super::ttl`].
+//!
+//! The versions pinned by the exports are shared by the column families of a
+//! node, the watermark never exceeds the oldest pinned version, so the reads at
+//! it are accepted until the pin is released or its lease expires.
 
-struct GroupCompactionFilter {
-    min_allowed_version: u64,
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use prometheus::*;
+use rocksdb::compaction_filter::{CompactionFilter, Decision};
+use rocksdb::compaction_filter_factory::{CompactionFilterContext, CompactionFilterFactory};
+use sekas_runtime::time::Instant;
+use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+use super::group::{keys, values};
+
+lazy_static! {
+    pub static ref ENGINE_GC_DROPPED_VERSIONS_TOTAL: IntCounter = register_int_counter!(
+        "engine_gc_dropped_versions_total",
+        "The total of versions beneath the GC watermark dropped by compaction",
+    )
+    .unwrap();
+    pub static ref ENGINE_GC_RECLAIMED_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "engine_gc_reclaimed_bytes_total",
+        "The total bytes of versions beneath the GC watermark dropped by compaction",
+    )
+    .unwrap();
+}
+
+/// The GC watermark of a group column family, and the decisions of the
+/// compaction filter on it.
+#[derive(Debug, Default)]
+pub(crate) struct GcState {
+    /// The versions not greater than it are beneath the watermark, `0` means
+    /// nothing is dropped.
+    watermark: AtomicU64,
+    dropped_versions: AtomicU64,
+    reclaimed_bytes: AtomicU64,
+    /// The max number of committed versions retained per key, keyed by the
+    /// table id.
+    max_versions: Mutex<HashMap<u64, u64>>,
+    /// The TTL of the tables in millis, keyed by the table id.
+    table_ttls: Mutex<HashMap<u64, u64>>,
+    pins: Arc<GcPins>,
+}
+
+/// The versions pinned by the exports, keyed by the pin id. Each pin holds a
+/// lease, it is ignored once the lease expires, so an exporter which died
+/// doesn't hold back the GC forever. The pins are persisted and restored by
+/// the node, see `Node::pin_gc`.
+#[derive(Debug, Default)]
+pub(crate) struct GcPins {
+    pins: Mutex<HashMap<String, (u64, Instant)>>,
+}
+
+/// The GC states of the column families, keyed by the column family name.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct GcStates {
+    states: Arc<Mutex<HashMap<String, Arc<GcState>>>>,
+    pins: Arc<GcPins>,
+}
+
+pub(crate) struct GcCompactionFilterFactory {
+    state: Arc<GcState>,
+}
+
+pub(crate) struct GcCompactionFilter {
+    state: Arc<GcState>,
+    watermark: u64,
+    /// Whether all the files of the column family are compacted, so the older
+    /// versions shadowed by a tombstone are compacted together with it.
+    is_full_compaction: bool,
+    /// The max versions of the tables, the versions above the watermark are
+    /// never dropped by them.
+    max_versions: HashMap<u64, u64>,
+    /// The mvcc key prefix (the table id and the encoded user key) of the last
+    /// visited version.
+    last_prefix: Vec<u8>,
+    /// Whether the last visited key has a version beneath the watermark, the
+    /// following older versions of it are superseded.
+    last_covered: bool,
+    /// The max versions of the last visited key, and the number of committed
+    /// versions visited.
+    last_max_versions: Option<u64>,
+    last_num_versions: u64,
+    /// Whether the last visited key is truncated, the following older versions
+    /// of it are dropped.
+    last_truncated: bool,
+}
+
+impl GcState {
+    /// The effective watermark, it never exceeds the oldest pinned version.
+    #[inline]
+    pub fn watermark(&self) -> u64 {
+        let watermark = self.watermark.load(Ordering::Acquire);
+        match self.pins.oldest() {
+            Some(pinned) => watermark.min(pinned),
+            None => watermark,
+        }
+    }
+
+    /// The watermark replicated by the group, regardless of the pins. The
+    /// versions beneath it might have been dropped already.
+    #[inline]
+    pub fn unpinned_watermark(&self) -> u64 {
+        self.watermark.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn set_watermark(&self, watermark: u64) {
+        self.watermark.store(watermark, Ordering::Release);
+    }
+
+    /// Returns the number of versions and bytes dropped by compaction.
+    #[inline]
+    pub fn dropped(&self) -> (u64, u64) {
+        (
+            self.dropped_versions.load(Ordering::Relaxed),
+            self.reclaimed_bytes.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Replace the max versions of the tables, it takes effect since the next
+    /// compaction.
+    pub fn set_max_versions(&self, max_versions: HashMap<u64, u64>) {
+        *self.max_versions.lock().unwrap() = max_versions;
+    }
+
+    /// Replace the TTL of the tables, in millis. It takes effect on the values
+    /// committed since now.
+    pub fn set_table_ttls(&self, table_ttls: HashMap<u64, u64>) {
+        *self.table_ttls.lock().unwrap() = table_ttls;
+    }
+
+    /// The TTL of the table in millis, `0` means no TTL.
+    pub fn table_ttl_ms(&self, table_id: u64) -> u64 {
+        self.table_ttls.lock().unwrap().get(&table_id).cloned().unwrap_or_default()
+    }
+
+    /// The oldest version pinned on this node, see [`GcPins`].
+    #[inline]
+    pub fn oldest_pinned(&self) -> Option<u64> {
+        self.pins.oldest()
+    }
+}
+
+impl GcPins {
+    /// Pin the version until the lease expires, the former pin with the same
+    /// id is replaced, so it is also used to renew the lease.
+    pub fn pin(&self, id: &str, version: u64, lease: Duration) {
+        let deadline = Instant::now() + lease;
+        self.pins.lock().unwrap().insert(id.to_owned(), (version, deadline));
+    }
+
+    /// Whether the version is pinned by the unexpired pin of the id.
+    pub fn holds(&self, id: &str, version: u64) -> bool {
+        let pins = self.pins.lock().unwrap();
+        pins.get(id).is_some_and(|(v, deadline)| *v == version && *deadline > Instant::now())
+    }
+
+    pub fn unpin(&self, id: &str) {
+        self.pins.lock().unwrap().remove(id);
+    }
+
+    /// The oldest version pinned by the unexpired pins, the expired pins are
+    /// removed.
+    pub fn oldest(&self) -> Option<u64> {
+        let mut pins = self.pins.lock().unwrap();
+        if pins.is_empty() {
+            return None;
+        }
+        let now = Instant::now();
+        pins.retain(|_, (_, deadline)| *deadline > now);
+        pins.values().map(|(version, _)| *version).min()
+    }
+}
+
+impl GcStates {
+    /// Returns the GC state of the column family, it is created if not exists.
+    pub fn state(&self, cf_name: &str) -> Arc<GcState> {
+        let mut states = self.states.lock().unwrap();
+        states
+            .entry(cf_name.to_owned())
+            .or_insert_with(|| Arc::new(GcState { pins: self.pins.clone(), ..Default::default() }))
+            .clone()
+    }
+
+    #[inline]
+    pub fn pins(&self) -> &GcPins {
+        &self.pins
+    }
+
+    pub fn remove(&self, cf_name: &str) {
+        self.states.lock().unwrap().remove(cf_name);
+    }
 }
 
-impl CompactionFilter for GroupCompactionFilter {
-    fn filter(&mut self, level: u32, key: &[u8], value: &[u8]) -> Decision {
-        todo!()
+impl GcCompactionFilterFactory {
+    pub fn new(state: Arc<GcState>) -> Self {
+        GcCompactionFilterFactory { state }
+    }
+}
+
+impl CompactionFilterFactory for GcCompactionFilterFactory {
+    type Filter = GcCompactionFilter;
+
+    fn create(&mut self, context: CompactionFilterContext) -> Self::Filter {
+        GcCompactionFilter {
+            state: self.state.clone(),
+            watermark: self.state.watermark(),
+            is_full_compaction: context.is_full_compaction,
+            max_versions: self.state.max_versions.lock().unwrap().clone(),
+            last_prefix: Vec::default(),
+            last_covered: false,
+            last_max_versions: None,
+            last_num_versions: 0,
+            last_truncated: false,
+        }
     }
 
-    /// Returns a name that identifies this compaction filter.
-    /// The name will be printed to LOG file on start up for diagnosis.
     fn name(&self) -> &CStr {
-        todo!()
+        CStr::from_bytes_with_nul(b"sekas-gc-compaction-filter-factory\0").expect("nul is provided")
     }
 }
 
-struct GroupCompactionFactory {}
+impl GcCompactionFilter {
+    fn decide(&mut self, key: &[u8], value: &[u8]) -> Decision {
+        if self.watermark == 0 && self.max_versions.is_empty() {
+            return Decision::Keep;
+        }
+        let Some((prefix, version)) = keys::split_data_key(key) else {
+            return Decision::Keep;
+        };
+        if version == TXN_INTENT_VERSION {
+            // The unresolved intents are never dropped.
+            return Decision::Keep;
+        }
+        if self.last_prefix != prefix {
+            const L: usize = core::mem::size_of::<u64>();
+            let table_id = u64::from_le_bytes(prefix[..L].try_into().unwrap());
+            self.last_prefix.clear();
+            self.last_prefix.extend_from_slice(prefix);
+            self.last_covered = false;
+            self.last_max_versions = self.max_versions.get(&table_id).cloned();
+            self.last_num_versions = 0;
+            self.last_truncated = false;
+        }
+        self.last_num_versions += 1;
+        if self.last_truncated {
+            return Decision::Remove;
+        }
+        if version <= self.watermark {
+            if self.last_covered {
+                return Decision::Remove;
+            }
+            self.last_covered = true;
+        }
+        if let Some(max_versions) = self.last_max_versions {
+            if self.last_num_versions > max_versions && version <= self.watermark {
+                // The newest dropped version is kept as the marker, the older ones are
+                // dropped as they are.
+                self.last_truncated = true;
+                return if values::is_truncated(value) {
+                    Decision::Keep
+                } else {
+                    Decision::Change(values::truncated())
+                };
+            }
+        }
+        if version > self.watermark {
+            return Decision::Keep;
+        }
+        // The value is expired at the watermark only if it is committed beneath
+        // the watermark.
+        let expired = values::is_expired_at(value, self.watermark);
 
-impl CompactionFilterFactory for GroupCompactionFactory {
-    type Filter: CompactionFilter;
+        // The newest version beneath the watermark is kept, except a tombstone
+        // (or an expired value) whose older versions are all dropped in the same
+        // compaction.
+        if self.is_full_compaction && (values::is_tombstone(value) || expired) {
+            Decision::Remove
+        } else if expired {
+            Decision::Change(values::tombstone())
+        } else {
+            Decision::Keep
+        }
+    }
+}
 
-    /// Returns a CompactionFilter for the compaction process
-    fn create(&mut self, context: CompactionFilterContext) -> Self::Filter;
+impl CompactionFilter for GcCompactionFilter {
+    fn filter(&mut self, _level: u32, key: &[u8], value: &[u8]) -> Decision {
+        let decision = self.decide(key, value);
+        if matches!(decision, Decision::Remove | Decision::Change(_)) {
+            let bytes = (key.len() + value.len()) as u64;
+            self.state.dropped_versions.fetch_add(1, Ordering::Relaxed);
+            self.state.reclaimed_bytes.fetch_add(bytes, Ordering::Relaxed);
+            ENGINE_GC_DROPPED_VERSIONS_TOTAL.inc();
+            ENGINE_GC_RECLAIMED_BYTES_TOTAL.inc_by(bytes);
+        }
+        decision
+    }
 
-    /// Returns a name that identifies this compaction filter factory.
     fn name(&self) -> &CStr {
-        &Cstr::new("group compaction filter")
+        CStr::from_bytes_with_nul(b"sekas-gc-compaction-filter\0").expect("nul is provided")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(watermark: u64, is_full_compaction: bool) -> GcCompactionFilter {
+        let state = Arc::new(GcState::default());
+        state.set_watermark(watermark);
+        let mut factory = GcCompactionFilterFactory::new(state);
+        factory.create(CompactionFilterContext { is_full_compaction, is_manual_compaction: true })
+    }
+
+    fn decide(filter: &mut GcCompactionFilter, key: &[u8], version: u64, value: &[u8]) -> bool {
+        let key = keys::mvcc_key(1, key, version);
+        matches!(filter.filter(0, &key, value), Decision::Remove)
+    }
+
+    fn is_truncated(filter: &mut GcCompactionFilter, key: &[u8], version: u64) -> bool {
+        let key = keys::mvcc_key(1, key, version);
+        let data = values::data(b"value");
+        matches!(filter.filter(0, &key, &data), Decision::Change(v) if values::is_truncated(v))
+    }
+
+    #[test]
+    fn drop_superseded_versions() {
+        let data = values::data(b"value");
+        let mut f = filter(10, false);
+        assert!(!decide(&mut f, b"a", TXN_INTENT_VERSION, &data));
+        assert!(!decide(&mut f, b"a", 12, &data));
+        assert!(!decide(&mut f, b"a", 10, &data));
+        assert!(decide(&mut f, b"a", 8, &data));
+        assert!(decide(&mut f, b"a", 2, values::tombstone()));
+        assert!(!decide(&mut f, b"b", 5, &data));
+        assert!(decide(&mut f, b"b", 4, &data));
+
+        // The tombstone is kept unless the compaction is full.
+        assert!(!decide(&mut f, b"c", 5, values::tombstone()));
+        assert!(decide(&mut f, b"c", 4, &data));
+        let mut f = filter(10, true);
+        assert!(decide(&mut f, b"c", 5, values::tombstone()));
+        assert!(decide(&mut f, b"c", 4, &data));
+
+        // Nothing is dropped without a watermark.
+        let mut f = filter(0, true);
+        assert!(!decide(&mut f, b"a", 10, &data));
+        assert!(!decide(&mut f, b"a", 8, &data));
+    }
+
+    #[test]
+    fn drop_expired_values() {
+        const SECOND: u64 = 1_000_000_000;
+        // The value committed at 10s expires at 11s.
+        let expiring = values::expiring_data(b"value", 11_000);
+        let data = values::data(b"value");
+        let state = Arc::new(GcState::default());
+        let mut factory = GcCompactionFilterFactory::new(state.clone());
+        let ctx = |is_full_compaction| CompactionFilterContext {
+            is_full_compaction,
+            is_manual_compaction: true,
+        };
+        let is_tombstone = |f: &mut GcCompactionFilter, key: &[u8], version: u64, value: &[u8]| {
+            let key = keys::mvcc_key(1, key, version);
+            matches!(f.filter(0, &key, value), Decision::Change(v) if values::is_tombstone(v))
+        };
+
+        // The values are retained until they are expired at the watermark.
+        state.set_watermark(9 * SECOND);
+        let mut f = factory.create(ctx(true));
+        assert!(!decide(&mut f, b"a", 10 * SECOND, &expiring));
+        assert!(!decide(&mut f, b"a", 8 * SECOND, &data));
+        state.set_watermark(10 * SECOND + SECOND / 2);
+        let mut f = factory.create(ctx(true));
+        assert!(!decide(&mut f, b"a", 10 * SECOND, &expiring));
+
+        // The expired value is replaced by a tombstone, the older versions are
+        // dropped as they are superseded.
+        state.set_watermark(12 * SECOND);
+        let mut f = factory.create(ctx(false));
+        assert!(is_tombstone(&mut f, b"a", 10 * SECOND, &expiring));
+        assert!(decide(&mut f, b"a", 8 * SECOND, &data));
+
+        // Or dropped together with the older versions by a full compaction.
+        let mut f = factory.create(ctx(true));
+        assert!(decide(&mut f, b"a", 10 * SECOND, &expiring));
+        assert!(decide(&mut f, b"a", 8 * SECOND, &data));
+        assert!(!decide(&mut f, b"b", 10 * SECOND, &values::expiring_data(b"value", 60_000)));
+    }
+
+    #[test]
+    fn drop_versions_beyond_max_versions() {
+        let data = values::data(b"value");
+        let state = Arc::new(GcState::default());
+        state.set_max_versions(HashMap::from([(1, 2)]));
+        state.set_watermark(8);
+        let mut factory = GcCompactionFilterFactory::new(state.clone());
+        let ctx = CompactionFilterContext { is_full_compaction: false, is_manual_compaction: true };
+
+        // The newest versions are retained, the newest dropped one is truncated.
+        let mut f = factory.create(ctx);
+        assert!(!decide(&mut f, b"a", TXN_INTENT_VERSION, &data));
+        assert!(!decide(&mut f, b"a", 12, &data));
+        assert!(!decide(&mut f, b"a", 10, values::tombstone()));
+        assert!(is_truncated(&mut f, b"a", 8));
+        assert!(decide(&mut f, b"a", 6, &data));
+
+        // The versions above the watermark are retained.
+        assert!(!decide(&mut f, b"b", 14, &data));
+        assert!(!decide(&mut f, b"b", 12, &data));
+        assert!(!decide(&mut f, b"b", 10, &data));
+        assert!(!decide(&mut f, b"b", 9, &data));
+        assert!(is_truncated(&mut f, b"b", 7));
+        assert!(decide(&mut f, b"b", 5, &data));
+
+        // The truncated marker is kept as it is.
+        let mut f = factory.create(ctx);
+        assert!(!decide(&mut f, b"a", 12, &data));
+        assert!(!decide(&mut f, b"a", 10, &data));
+        assert!(!decide(&mut f, b"a", 8, values::truncated()));
+
+        // Nothing is dropped without a watermark.
+        state.set_watermark(0);
+        let mut f = factory.create(ctx);
+        for version in (1..10).rev() {
+            assert!(!decide(&mut f, b"a", version, &data));
+        }
+
+        // The versions of other tables are untouched.
+        state.set_watermark(8);
+        let mut f = factory.create(ctx);
+        for version in (8..20).rev() {
+            let key = keys::mvcc_key(2, b"a", version);
+            assert!(matches!(f.filter(0, &key, &data), Decision::Keep));
+        }
+    }
+
+    #[test]
+    fn retain_pinned_versions() {
+        let data = values::data(b"value");
+        let states = GcStates::default();
+        let state = states.state("cf");
+        state.set_watermark(10);
+        states.pins().pin("export", 5, Duration::from_secs(3600));
+        states.pins().pin("expired", 3, Duration::ZERO);
+        assert_eq!(state.watermark(), 5);
+        assert_eq!(state.unpinned_watermark(), 10);
+
+        // The version visible at the pinned version is retained.
+        let mut factory = GcCompactionFilterFactory::new(state.clone());
+        let ctx = CompactionFilterContext { is_full_compaction: true, is_manual_compaction: true };
+        let mut f = factory.create(ctx);
+        assert!(!decide(&mut f, b"a", 8, &data));
+        assert!(!decide(&mut f, b"a", 4, &data));
+        assert!(decide(&mut f, b"a", 2, &data));
+
+        // The pins are shared by the column families.
+        assert_eq!(states.state("other").watermark(), 0);
+        states.state("other").set_watermark(20);
+        assert_eq!(states.state("other").watermark(), 5);
+
+        states.pins().unpin("export");
+        assert_eq!(state.watermark(), 10);
     }
 }
@@ -0,0 +1,205 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The statistics of the unresolved intents of a group.
+//!
+//! The stats are maintained incrementally when the write batches are committed
+//! into the group engine, so both the intents written by txns and the intents
+//! resolved by the latch manager are observed without scanning the shards. The
+//! applied time of each intent is persisted by the group engine in the same
+//! write batch, so the stats are reloaded without scanning the shards either.
+
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use sekas_runtime::time::{timestamp_millis, Instant};
+
+/// The window to estimate the resolve rate of intents.
+const RESOLVE_RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// An unresolved intent.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct IntentInfo {
+    pub user_key: Vec<u8>,
+    /// The start version of the txn, which is also the id of the txn.
+    pub start_version: u64,
+    /// The wall duration since the intent is applied by this replica.
+    pub age: Duration,
+}
+
+/// The summary of the unresolved intents of a shard.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ShardIntentStats {
+    pub num_intents: u64,
+    pub oldest_intent: Option<IntentInfo>,
+    /// The number of intents resolved per second.
+    pub resolve_rate: f64,
+}
+
+#[derive(Default)]
+pub(crate) struct IntentStats {
+    shards: HashMap<u64, ShardIntents>,
+}
+
+#[derive(Default)]
+struct ShardIntents {
+    /// The start version and the applied time (in millis since the unix epoch)
+    /// of the intents.
+    intents: HashMap<Vec<u8>, (u64, u64)>,
+    /// The intents ordered by the applied time.
+    by_age: BTreeSet<(u64, Vec<u8>)>,
+    resolve_rate: ResolveRate,
+}
+
+struct ResolveRate {
+    window_start: Instant,
+    num_resolved: u64,
+    rate: f64,
+}
+
+impl IntentStats {
+    /// Return the applied time of the intent written by txn `start_version`,
+    /// which is kept when the txn rewrites its intent.
+    pub fn applied_at(&self, shard_id: u64, user_key: &[u8], start_version: u64) -> u64 {
+        match self.shards.get(&shard_id).and_then(|shard| shard.intents.get(user_key)) {
+            Some(&(version, applied_at)) if version == start_version => applied_at,
+            _ => timestamp_millis(),
+        }
+    }
+
+    /// Record an intent written by txn `start_version` at `applied_at`, in
+    /// millis since the unix epoch.
+    pub fn on_intent_written(
+        &mut self,
+        shard_id: u64,
+        user_key: Vec<u8>,
+        start_version: u64,
+        applied_at: u64,
+    ) {
+        let shard = self.shards.entry(shard_id).or_default();
+        if let Some((_, prev_applied_at)) = shard.intents.get(&user_key) {
+            shard.by_age.remove(&(*prev_applied_at, user_key.clone()));
+        }
+        shard.by_age.insert((applied_at, user_key.clone()));
+        shard.intents.insert(user_key, (start_version, applied_at));
+    }
+
+    /// Record an intent is resolved, by either committing or aborting it.
+    pub fn on_intent_resolved(&mut self, shard_id: u64, user_key: &[u8]) {
+        let Some(shard) = self.shards.get_mut(&shard_id) else { return };
+        if let Some((_, applied_at)) = shard.intents.remove(user_key) {
+            shard.by_age.remove(&(applied_at, user_key.to_owned()));
+            shard.resolve_rate.num_resolved += 1;
+        }
+    }
+
+    /// Drop the stats of the shards which are no longer served by the group.
+    pub fn retain_shards<F: Fn(u64) -> bool>(&mut self, f: F) {
+        self.shards.retain(|shard_id, _| f(*shard_id));
+    }
+
+    pub fn shard_stats(&mut self, shard_id: u64) -> ShardIntentStats {
+        let Some(shard) = self.shards.get_mut(&shard_id) else {
+            return ShardIntentStats::default();
+        };
+        let resolve_rate = shard.resolve_rate.rate(Instant::now());
+        ShardIntentStats {
+            num_intents: shard.intents.len() as u64,
+            oldest_intent: shard.oldest_intents(timestamp_millis()).next(),
+            resolve_rate,
+        }
+    }
+
+    /// Return at most `limit` intents of the shard, in descending order of
+    /// age.
+    pub fn oldest_intents(&self, shard_id: u64, limit: usize) -> Vec<IntentInfo> {
+        let Some(shard) = self.shards.get(&shard_id) else {
+            return vec![];
+        };
+        shard.oldest_intents(timestamp_millis()).take(limit).collect()
+    }
+}
+
+impl ShardIntents {
+    fn oldest_intents(&self, now: u64) -> impl Iterator<Item = IntentInfo> + '_ {
+        self.by_age.iter().map(move |(applied_at, user_key)| IntentInfo {
+            user_key: user_key.clone(),
+            start_version: self.intents[user_key].0,
+            age: Duration::from_millis(now.saturating_sub(*applied_at)),
+        })
+    }
+}
+
+impl ResolveRate {
+    fn rate(&mut self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed >= RESOLVE_RATE_WINDOW {
+            self.rate = self.num_resolved as f64 / elapsed.as_secs_f64();
+            self.num_resolved = 0;
+            self.window_start = now;
+        }
+        self.rate
+    }
+}
+
+impl Default for ResolveRate {
+    fn default() -> Self {
+        ResolveRate { window_start: Instant::now(), num_resolved: 0, rate: 0.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intent_stats_track_oldest_intent() {
+        let mut stats = IntentStats::default();
+        let write = |stats: &mut IntentStats, user_key: &[u8], start_version: u64| {
+            let applied_at = stats.applied_at(1, user_key, start_version);
+            stats.on_intent_written(1, user_key.to_vec(), start_version, applied_at);
+        };
+        write(&mut stats, b"a", 10);
+        std::thread::sleep(Duration::from_millis(10));
+        write(&mut stats, b"b", 11);
+        // Rewrite by the same txn doesn't reset the age.
+        write(&mut stats, b"a", 10);
+
+        let shard_stats = stats.shard_stats(1);
+        assert_eq!(shard_stats.num_intents, 2);
+        let oldest = shard_stats.oldest_intent.unwrap();
+        assert_eq!(oldest.user_key, b"a".to_vec());
+        assert_eq!(oldest.start_version, 10);
+        assert!(oldest.age >= Duration::from_millis(10));
+
+        let intents = stats.oldest_intents(1, 10);
+        assert_eq!(intents.iter().map(|i| i.start_version).collect::<Vec<_>>(), vec![10, 11]);
+
+        stats.on_intent_resolved(1, b"a");
+        // Resolve an unknown intent is a no-op.
+        stats.on_intent_resolved(1, b"c");
+        stats.on_intent_resolved(2, b"a");
+        let shard_stats = stats.shard_stats(1);
+        assert_eq!(shard_stats.num_intents, 1);
+        assert_eq!(shard_stats.oldest_intent.unwrap().start_version, 11);
+
+        stats.on_intent_resolved(1, b"b");
+        let shard_stats = stats.shard_stats(1);
+        assert_eq!(shard_stats.num_intents, 0);
+        assert!(shard_stats.oldest_intent.is_none());
+
+        stats.retain_shards(|_| false);
+        assert!(stats.oldest_intents(1, 10).is_empty());
+    }
+}
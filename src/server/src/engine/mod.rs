@@ -12,10 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod fault;
+mod format;
 mod group;
+mod group_filter;
+mod intent;
 mod options;
 mod properties;
 mod state;
+mod ttl;
 
 use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
@@ -24,23 +29,28 @@ use std::sync::Arc;
 use log::info;
 use sekas_rock::fs::create_dir_all_if_not_exists;
 
+pub use self::fault::StorageFaults;
+pub(crate) use self::format::prepare_data_dir;
 pub(crate) use self::group::{
-    GroupEngine, MvccEntry, MvccIterator, RawIterator, Snapshot, SnapshotMode, WriteBatch,
-    WriteStates,
+    GroupEngine, MvccEntry, MvccIterator, RawIterator, Snapshot, SnapshotMode, VersionChainStats,
+    WriteBatch, WriteKind, WriteStates,
 };
+use self::group_filter::{GcCompactionFilterFactory, GcStates};
+pub(crate) use self::group_filter::{GcPins, GcState};
 pub(crate) use self::state::StateEngine;
 use crate::{DbConfig, Result};
 
 // The disk layouts.
-const LAYOUT_DATA: &str = "db";
-const LAYOUT_LOG: &str = "log";
-const LAYOUT_SNAP: &str = "snap";
+pub(crate) const LAYOUT_DATA: &str = "db";
+pub(crate) const LAYOUT_LOG: &str = "log";
+pub(crate) const LAYOUT_SNAP: &str = "snap";
 
 type DbResult<T, E = rocksdb::Error> = Result<T, E>;
 
 pub(crate) struct RawDb {
     pub options: rocksdb::Options,
     pub db: rocksdb::DB,
+    gc_states: GcStates,
 }
 
 impl RawDb {
@@ -51,7 +61,8 @@ impl RawDb {
 
     #[inline]
     pub fn create_cf<N: AsRef<str>>(&self, name: N) -> DbResult<()> {
-        self.db.create_cf(name, &self.options)
+        let options = cf_options(&self.options, &self.gc_states, name.as_ref());
+        self.db.create_cf(name, &options)
     }
 
     #[inline]
@@ -59,11 +70,38 @@ impl RawDb {
         self.db.drop_cf(name)
     }
 
+    /// Returns the GC state of the column family, which is consulted by the
+    /// compaction filter. It is kept if the column family is recreated.
+    #[inline]
+    pub fn gc_state(&self, name: &str) -> Arc<GcState> {
+        self.gc_states.state(name)
+    }
+
+    #[inline]
+    pub fn remove_gc_state(&self, name: &str) {
+        self.gc_states.remove(name)
+    }
+
+    /// Returns the GC pins, which are shared by the column families.
+    #[inline]
+    pub fn gc_pins(&self) -> &GcPins {
+        self.gc_states.pins()
+    }
+
     #[inline]
     pub fn flush_cf(&self, cf: &impl rocksdb::AsColumnFamilyRef) -> DbResult<()> {
         self.db.flush_cf(cf)
     }
 
+    #[inline]
+    pub fn flush_cf_opt(
+        &self,
+        cf: &impl rocksdb::AsColumnFamilyRef,
+        flushopts: &rocksdb::FlushOptions,
+    ) -> DbResult<()> {
+        self.db.flush_cf_opt(cf, flushopts)
+    }
+
     #[inline]
     pub fn write_opt(
         &self,
@@ -102,6 +140,16 @@ impl RawDb {
         self.db.iterator_cf_opt(cf_handle, readopts, mode)
     }
 
+    #[inline]
+    pub fn compact_range_cf(
+        &self,
+        cf: &impl rocksdb::AsColumnFamilyRef,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+    ) {
+        self.db.compact_range_cf(cf, start, end)
+    }
+
     #[inline]
     pub fn ingest_external_file_cf_opts<P: AsRef<Path>>(
         &self,
@@ -145,6 +193,35 @@ impl RawDb {
         Ok(split_keys.into_iter().collect::<Vec<_>>())
     }
 
+    /// Estimate the number of keys in the range, by the keys put in the sst
+    /// files overlapped with the range. Like [`Self::get_approximate_size`],
+    /// the keys in memtables are not counted.
+    pub fn estimate_num_keys_in_range(
+        &self,
+        cf: &impl rocksdb::AsColumnFamilyRef,
+        start: &[u8],
+        end: &[u8],
+    ) -> DbResult<u64, crate::Error> {
+        use properties::PROPERTY_NUM_PUT_KEYS;
+
+        let collection = if end.is_empty() {
+            self.db.get_properties_of_all_range(cf)?
+        } else {
+            self.db.get_properties_of_tables_in_range(cf, &[(start, end)])?
+        };
+        let mut num_keys = 0;
+        for table in collection.tables {
+            let properties = table.user_collected_properties();
+            if let Some(value) = properties.get(PROPERTY_NUM_PUT_KEYS) {
+                let bytes = <[u8; 8]>::try_from(&**value).map_err(|_| {
+                    crate::Error::InvalidData(format!("table property {PROPERTY_NUM_PUT_KEYS:?}"))
+                })?;
+                num_keys += u64::from_le_bytes(bytes);
+            }
+        }
+        Ok(num_keys)
+    }
+
     /// Get the approximate size of the target range.
     pub fn get_approximate_size(
         &self,
@@ -161,7 +238,7 @@ impl RawDb {
 #[derive(Clone)]
 pub(crate) struct Engines {
     log_path: PathBuf,
-    _db_path: PathBuf,
+    db_path: PathBuf,
     log: Arc<raft_engine::Engine>,
     db: Arc<RawDb>,
     state: StateEngine,
@@ -174,7 +251,7 @@ impl Engines {
         let db = Arc::new(open_raw_db(db_cfg, &db_path)?);
         let log = Arc::new(open_raft_engine(&log_path)?);
         let state = StateEngine::new(log.clone());
-        Ok(Engines { log_path, _db_path: db_path, log, db, state })
+        Ok(Engines { log_path, db_path, log, db, state })
     }
 
     #[inline]
@@ -192,6 +269,16 @@ impl Engines {
         self.state.clone()
     }
 
+    #[inline]
+    pub(crate) fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+
+    #[inline]
+    pub(crate) fn log_path(&self) -> &Path {
+        &self.log_path
+    }
+
     #[inline]
     pub(crate) fn snap_dir(&self) -> PathBuf {
         self.log_path.join(LAYOUT_SNAP)
@@ -203,23 +290,27 @@ pub(crate) fn open_raw_db<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<Raw
 
     std::fs::create_dir_all(&path)?;
     let options = options::to_rocksdb_options(cfg);
+    let gc_states = GcStates::default();
 
     // List column families and open database with column families.
     match DB::list_cf(&options, &path) {
         Ok(cfs) => {
             info!("open local db {} with {} column families", path.as_ref().display(), cfs.len());
-            let db = DB::open_cf_with_opts(
-                &options,
-                path,
-                cfs.into_iter().map(|name| (name, options.clone())),
-            )?;
-            Ok(RawDb { db, options })
+            let cfs = cfs
+                .into_iter()
+                .map(|name| {
+                    let cf_options = cf_options(&options, &gc_states, &name);
+                    (name, cf_options)
+                })
+                .collect::<Vec<_>>();
+            let db = DB::open_cf_with_opts(&options, path, cfs)?;
+            Ok(RawDb { db, options, gc_states })
         }
         Err(e) => {
             if e.as_ref().ends_with("CURRENT: No such file or directory") {
                 info!("create new local db: {}", path.as_ref().display());
                 let db = DB::open(&options, &path)?;
-                Ok(RawDb { db, options })
+                Ok(RawDb { db, options, gc_states })
             } else {
                 Err(e.into())
             }
@@ -227,6 +318,35 @@ pub(crate) fn open_raw_db<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<Raw
     }
 }
 
+/// Open the local db in read only mode. No lock is acquired, the db could be
+/// opened by the server after this instance is dropped.
+pub(crate) fn open_raw_db_for_read_only<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<RawDb> {
+    use rocksdb::DB;
+
+    let options = options::to_rocksdb_options(cfg);
+    let cfs = DB::list_cf(&options, &path)?;
+    info!(
+        "open local db {} for read only with {} column families",
+        path.as_ref().display(),
+        cfs.len()
+    );
+    let db = DB::open_cf_with_opts_for_read_only(
+        &options,
+        path,
+        cfs.into_iter().map(|name| (name, options.clone())),
+        false,
+    )?;
+    Ok(RawDb { db, options, gc_states: GcStates::default() })
+}
+
+/// The options of a column family, the versions beneath the GC watermark of the
+/// column family are dropped by compaction.
+fn cf_options(options: &rocksdb::Options, gc_states: &GcStates, name: &str) -> rocksdb::Options {
+    let mut cf_options = options.clone();
+    cf_options.set_compaction_filter_factory(GcCompactionFilterFactory::new(gc_states.state(name)));
+    cf_options
+}
+
 pub(crate) fn open_raft_engine(log_path: &Path) -> Result<raft_engine::Engine> {
     use raft_engine::{Config, Engine};
     let engine_dir = log_path.join("engine");
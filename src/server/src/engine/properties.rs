@@ -20,6 +20,7 @@ use rocksdb::table_properties::*;
 use serde::{Deserialize, Serialize};
 
 pub const PROPERTY_SPLIT_KEYS: &[u8] = b"sekas-split-keys";
+pub const PROPERTY_NUM_PUT_KEYS: &[u8] = b"sekas-num-put-keys";
 const ESTIMATE_KEYS_INTERVALS: usize = 1024;
 const ESTIMATE_SIZE_INTERVALS: usize = 16 << 20; // 16MB
 
@@ -44,6 +45,7 @@ impl TablePropertiesCollectorFactory for SplitKeyCollectorFactory {
 pub(crate) struct SplitKeyCollector {
     keys: Vec<Vec<u8>>,
     num_keys: usize,
+    num_put_keys: u64,
     total_size: usize,
     last_estimate_keys: usize,
     last_estimate_size: usize,
@@ -74,6 +76,9 @@ impl TablePropertiesCollector for SplitKeyCollector {
         }
 
         self.num_keys += 1;
+        if matches!(entry_type, EntryType::Put | EntryType::TimedPut) {
+            self.num_put_keys += 1;
+        }
         self.total_size += key.len() + value.len();
         if self.last_estimate_keys + ESTIMATE_KEYS_INTERVALS <= self.num_keys
             || self.last_estimate_size + ESTIMATE_SIZE_INTERVALS <= self.total_size
@@ -89,6 +94,7 @@ impl TablePropertiesCollector for SplitKeyCollector {
         let encoded_split_keys = split_keys.encode_to_vec();
         let mut map = BTreeMap::default();
         map.insert(PROPERTY_SPLIT_KEYS.into(), encoded_split_keys.into_boxed_slice());
+        map.insert(PROPERTY_NUM_PUT_KEYS.into(), self.num_put_keys.to_le_bytes().into());
         map
     }
 }
@@ -27,6 +27,7 @@ use crate::Result;
 /// - node ident
 /// - root node descriptors
 /// - replica states
+/// - GC pins
 ///
 /// NOTE: The group descriptors is stored in the corresponding GroupEngine,
 /// which is to ensure that both the changes of group descriptor and data are
@@ -118,6 +119,42 @@ impl StateEngine {
 
         Ok(replica_states)
     }
+
+    /// Save the GC pin, the former one with the same id is replaced.
+    pub async fn save_gc_pin(&self, pin: &GcPinMeta) -> Result<()> {
+        use raft_engine::LogBatch;
+
+        let mut lb = LogBatch::default();
+        lb.put_message(STATE_REPLICA_ID, keys::gc_pin(&pin.pin_id), pin)
+            .expect("GcPinMeta is Serializable");
+        self.raw.write(&mut lb, false)?;
+        Ok(())
+    }
+
+    pub async fn remove_gc_pin(&self, pin_id: &str) -> Result<()> {
+        use raft_engine::LogBatch;
+
+        let mut lb = LogBatch::default();
+        lb.delete(STATE_REPLICA_ID, keys::gc_pin(pin_id));
+        self.raw.write(&mut lb, false)?;
+        Ok(())
+    }
+
+    /// Fetch all GC pins, including the expired ones.
+    pub async fn gc_pins(&self) -> Result<Vec<GcPinMeta>> {
+        let mut pins = Vec::default();
+        self.raw.scan_messages(
+            STATE_REPLICA_ID,
+            Some(keys::gc_pin_prefix()),
+            Some(keys::gc_pin_end()),
+            false,
+            |_, pin: GcPinMeta| {
+                pins.push(pin);
+                true
+            },
+        )?;
+        Ok(pins)
+    }
 }
 
 mod keys {
@@ -125,6 +162,8 @@ mod keys {
     const ROOT_DESCRIPTOR_KEY: &[u8] = &[0x2];
     const REPLICA_STATE_PREFIX: &[u8] = &[0x3];
     const REPLICA_STATE_END: &[u8] = &[0x4];
+    const GC_PIN_PREFIX: &[u8] = &[0x5];
+    const GC_PIN_END: &[u8] = &[0x6];
 
     pub fn node_ident() -> &'static [u8] {
         IDENT_KEY
@@ -148,6 +187,20 @@ mod keys {
         buf[1..].copy_from_slice(&replica_id.to_le_bytes());
         buf
     }
+
+    pub fn gc_pin_prefix() -> &'static [u8] {
+        GC_PIN_PREFIX
+    }
+
+    pub fn gc_pin_end() -> &'static [u8] {
+        GC_PIN_END
+    }
+
+    pub fn gc_pin(pin_id: &str) -> Vec<u8> {
+        let mut buf = GC_PIN_PREFIX.to_vec();
+        buf.extend_from_slice(pin_id.as_bytes());
+        buf
+    }
 }
 
 #[cfg(test)]
@@ -193,6 +246,7 @@ mod tests {
                 addr: "localhost:10011".into(),
                 capacity: None,
                 status: NodeStatus::Active.into(),
+                labels: vec![],
             }],
         };
         engine.save_root_desc(&desc).await.unwrap();
@@ -218,4 +272,24 @@ mod tests {
         let read_states = engine.replica_states().await.unwrap();
         assert_eq!(expect_states, read_states);
     }
+
+    #[sekas_macro::test]
+    async fn save_and_remove_gc_pins() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = StateEngine::new(Arc::new(open_raft_engine(dir.path()).unwrap()));
+        engine.save_replica_state(1, 1, ReplicaLocalState::Normal).await.unwrap();
+        let pin = |pin_id: &str, version: u64| GcPinMeta {
+            pin_id: pin_id.to_owned(),
+            version,
+            deadline_ms: 1000,
+        };
+        engine.save_gc_pin(&pin("clone-1", 1)).await.unwrap();
+        engine.save_gc_pin(&pin("export-2", 2)).await.unwrap();
+        engine.save_gc_pin(&pin("export-2", 3)).await.unwrap();
+        assert_eq!(engine.gc_pins().await.unwrap(), vec![pin("clone-1", 1), pin("export-2", 3)]);
+
+        engine.remove_gc_pin("clone-1").await.unwrap();
+        assert_eq!(engine.gc_pins().await.unwrap(), vec![pin("export-2", 3)]);
+        assert_eq!(engine.replica_states().await.unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,83 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The expiry of values.
+//!
+//! A value written with a TTL, or into a table with the `ttl` property, carries
+//! an absolute expiry alongside its version. The expiry is derived from the
+//! timestamp of the commit version, which is allocated by root from its clock,
+//! so the replicas agree on it regardless of their own clocks. The shorter one
+//! is used if both the write and the table have a TTL. The expiry belongs to
+//! the version, so a later write without TTL clears it.
+//!
+//! A read treats the values expired at the timestamp of its read version as
+//! tombstones, so the conditions and the atomic operations treat them as
+//! absent, and the reads at the same version agree on every replica. The
+//! expired values are removed by compaction once they are expired at the GC
+//! watermark, which is replicated by the group and rejects the older reads. No
+//! watch event is emitted for the expiry, neither when a value expires nor
+//! when it is removed, the watchers only observe the committed writes.
+
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+/// The timestamp of the version, in millis since the unix epoch. The versions
+/// are allocated by root from its clock in nanos.
+#[inline]
+pub fn version_millis(version: u64) -> u64 {
+    version / NANOS_PER_MILLI
+}
+
+/// The expiry of a value committed at the version, `0` means never. The
+/// shorter one of the TTLs is used if both are set, `0` means no TTL.
+pub fn expire_at(version: u64, write_ttl_ms: u64, table_ttl_ms: u64) -> u64 {
+    let ttl_ms = match (write_ttl_ms, table_ttl_ms) {
+        (0, 0) => return 0,
+        (0, ttl) | (ttl, 0) => ttl,
+        (a, b) => a.min(b),
+    };
+    version_millis(version).saturating_add(ttl_ms)
+}
+
+/// Whether the value expiring at `expire_at` is expired at the version, `0`
+/// means never.
+#[inline]
+pub fn is_expired_at(expire_at: u64, version: u64) -> bool {
+    expire_at != 0 && expire_at <= version_millis(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn shorter_ttl_wins() {
+        let version = 3600 * SECOND + 1;
+        assert_eq!(expire_at(version, 0, 0), 0);
+        assert_eq!(expire_at(version, 1000, 0), 3_601_000);
+        assert_eq!(expire_at(version, 0, 1000), 3_601_000);
+        assert_eq!(expire_at(version, 60_000, 1000), 3_601_000);
+        assert_eq!(expire_at(version, 1000, 60_000), 3_601_000);
+    }
+
+    #[test]
+    fn expired_at_the_read_version() {
+        let expire_at = expire_at(3600 * SECOND, 1000, 0);
+        assert!(!is_expired_at(expire_at, 3600 * SECOND));
+        assert!(!is_expired_at(expire_at, 3601 * SECOND - 1));
+        assert!(is_expired_at(expire_at, 3601 * SECOND));
+        assert!(!is_expired_at(0, u64::MAX));
+    }
+}
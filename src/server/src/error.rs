@@ -12,7 +12,7 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use sekas_api::server::v1::{GroupDesc, ReplicaDesc, RootDesc, Value};
+use sekas_api::server::v1::{GroupDesc, GroupRelocatedHint, ReplicaDesc, RootDesc, Value};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -29,12 +29,21 @@ pub enum Error {
     #[error("database {0} not found")]
     DatabaseNotFound(String),
 
+    #[error("table {0} not found")]
+    TableNotFound(String),
+
     #[error("no available group")]
     NoAvaliableGroup,
 
     #[error("{0} is exhausted")]
     ResourceExhausted(String),
 
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("shard {0} of the txn table can't be split, merged or moved")]
+    TxnShardFenced(u64),
+
     #[error("condition {1} not satisfied, operation index {0}")]
     CasFailed(/* index */ u64, /* cond_index */ u64, Option<Value>),
 
@@ -45,12 +54,27 @@ pub enum Error {
     #[error("invalid {0} data")]
     InvalidData(String),
 
+    #[error("snapshot corrupted: {0}")]
+    SnapshotCorrupted(String),
+
+    #[error("storage corrupted: {0}")]
+    StorageCorrupted(String),
+
     #[error("request canceled")]
     Canceled,
 
+    #[error("key {0} not found")]
+    KeyNotFound(String),
+
     #[error("cluster not match")]
     ClusterNotMatch,
 
+    #[error("join rejected: {0}")]
+    JoinRejected(String),
+
+    #[error("incompatible data dir: {0}")]
+    IncompatibleDataDir(String),
+
     #[error("raft {0}")]
     Raft(#[from] raft::Error),
 
@@ -85,6 +109,14 @@ pub enum Error {
     #[error("group {0} not found")]
     GroupNotFound(u64),
 
+    #[error("group {0} is relocated")]
+    GroupRelocated(u64, GroupRelocatedHint),
+
+    /// The replica is unable to serve the read locally, the read is retried
+    /// on the other replicas.
+    #[error("replica {1} of group {0} is not ready: {2}")]
+    ReplicaNotReady(u64, u64, String),
+
     #[error("not root leader")]
     NotRootLeader(RootDesc, u64, Option<ReplicaDesc>),
 
@@ -102,10 +134,43 @@ pub enum Error {
 
     #[error("the txn is conflict with others")]
     TxnConflict,
+
+    #[error("invalid json {0}")]
+    InvalidJson(String),
+
+    #[error("read version {0} is beneath the gc watermark {1}")]
+    VersionTooOld(u64, u64),
+
+    #[error("the existing value of {actual_len} bytes is not a valid {expected}")]
+    ValueTypeMismatch { expected: String, actual_len: u64 },
+
+    /// The encoded group request or response exceeds the max message bytes,
+    /// it fails on every replica.
+    #[error("message of {size} bytes exceeds the limit {limit} bytes")]
+    MessageTooLarge { size: u64, limit: u64 },
+
+    /// The DDL exceeds a quota of the database, `limit` is the name of the
+    /// quota, see `sekas_schema::property::QUOTA_LIMITS`.
+    #[error("quota {limit} of {value} is exceeded")]
+    QuotaExceeded { limit: String, value: u64 },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+impl Error {
+    /// Whether the error indicates that the local storage is corrupted, eg. the
+    /// checksum of a block is mismatched, or a sst file is truncated. Retrying
+    /// on the same replica never succeeds.
+    pub fn is_storage_corrupted(&self) -> bool {
+        match self {
+            Error::StorageCorrupted(_) => true,
+            // See `Status::ToString` of rocksdb.
+            Error::RocksDb(err) => err.as_ref().starts_with("Corruption:"),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum BusyReason {
     Transfering,
@@ -114,6 +179,7 @@ pub enum BusyReason {
     PendingConfigChange,
     RequestChannelFulled,
     ProposalDropped,
+    Quarantined,
 }
 
 impl std::fmt::Display for BusyReason {
@@ -125,6 +191,7 @@ impl std::fmt::Display for BusyReason {
             BusyReason::Transfering => "leader transfering",
             BusyReason::RequestChannelFulled => "request channel fulled",
             BusyReason::ProposalDropped => "proposal dropped by raft",
+            BusyReason::Quarantined => "replica is quarantined",
         };
         f.write_str(reason)
     }
@@ -149,9 +216,17 @@ impl From<Error> for tonic::Status {
         match e {
             Error::InvalidArgument(msg) => Status::invalid_argument(msg),
             Error::DeadlineExceeded(msg) => Status::deadline_exceeded(msg),
-            err @ Error::DatabaseNotFound(_) => Status::not_found(err.to_string()),
+            err @ (Error::DatabaseNotFound(_) | Error::TableNotFound(_)) => {
+                Status::not_found(err.to_string())
+            }
             err @ Error::AlreadyExists(_) => Status::already_exists(err.to_string()),
             Error::ResourceExhausted(msg) => Status::resource_exhausted(msg),
+            Error::PermissionDenied(msg) => Status::permission_denied(msg),
+            Error::TxnShardFenced(shard_id) => Status::with_details(
+                Code::PermissionDenied,
+                e.to_string(),
+                v1::Error::txn_shard_fenced(shard_id).encode_to_vec().into(),
+            ),
             Error::CasFailed(index, cond_index, prev_value) => Status::with_details(
                 Code::Unknown,
                 "cas failed".to_string(),
@@ -163,6 +238,16 @@ impl From<Error> for tonic::Status {
                 e.to_string(),
                 v1::Error::group_not_found(group_id).encode_to_vec().into(),
             ),
+            Error::GroupRelocated(group_id, hint) => Status::with_details(
+                Code::Unknown,
+                format!("group {group_id} not found, it is relocated"),
+                v1::Error::group_relocated(group_id, hint).encode_to_vec().into(),
+            ),
+            Error::ReplicaNotReady(group_id, replica_id, reason) => Status::with_details(
+                Code::Unknown,
+                format!("replica {replica_id} of group {group_id} is not ready: {reason}"),
+                v1::Error::replica_not_ready(group_id, replica_id, reason).encode_to_vec().into(),
+            ),
             Error::NotLeader(group_id, term, leader) => Status::with_details(
                 Code::Unknown,
                 format!("not leader of group {}", group_id),
@@ -183,15 +268,45 @@ impl From<Error> for tonic::Status {
                 "the txn is conflict",
                 v1::Error::txn_conflict().encode_to_vec().into(),
             ),
+            Error::InvalidJson(reason) => Status::with_details(
+                Code::Unknown,
+                format!("invalid json {reason}"),
+                v1::Error::invalid_json(reason).encode_to_vec().into(),
+            ),
+            Error::VersionTooOld(version, gc_watermark) => Status::with_details(
+                Code::Unknown,
+                format!("read version {version} is beneath the gc watermark {gc_watermark}"),
+                v1::Error::version_too_old(version, gc_watermark).encode_to_vec().into(),
+            ),
+            Error::ValueTypeMismatch { expected, actual_len } => Status::with_details(
+                Code::Unknown,
+                format!("the existing value of {actual_len} bytes is not a valid {expected}"),
+                v1::Error::value_type_mismatch(expected, actual_len).encode_to_vec().into(),
+            ),
+            Error::MessageTooLarge { size, limit } => Status::with_details(
+                Code::ResourceExhausted,
+                format!("message of {size} bytes exceeds the limit {limit} bytes"),
+                v1::Error::message_too_large(size, limit).encode_to_vec().into(),
+            ),
+            Error::QuotaExceeded { limit, value } => Status::with_details(
+                Code::ResourceExhausted,
+                format!("quota {limit} of {value} is exceeded"),
+                v1::Error::quota_exceeded(limit, value).encode_to_vec().into(),
+            ),
 
             Error::Forward(_) => panic!("Forward only used inside node"),
             Error::ServiceIsBusy(_) => panic!("ServiceIsBusy only used inside node"),
             Error::GroupNotReady(_) => panic!("GroupNotReady only used inside node"),
 
             err @ (Error::Canceled
+            | Error::KeyNotFound(_)
             | Error::AbortScheduleTask(_)
             | Error::ClusterNotMatch
+            | Error::JoinRejected(_)
+            | Error::IncompatibleDataDir(_)
             | Error::InvalidData(_)
+            | Error::SnapshotCorrupted(_)
+            | Error::StorageCorrupted(_)
             | Error::Transport(_)
             | Error::Io(_)
             | Error::RocksDb(_)
@@ -229,6 +344,10 @@ impl From<Error> for sekas_api::server::v1::Error {
 
         match err {
             Error::GroupNotFound(group_id) => v1::Error::group_not_found(group_id),
+            Error::GroupRelocated(group_id, hint) => v1::Error::group_relocated(group_id, hint),
+            Error::ReplicaNotReady(group_id, replica_id, reason) => {
+                v1::Error::replica_not_ready(group_id, replica_id, reason)
+            }
             Error::NotLeader(group_id, term, leader) => {
                 v1::Error::not_leader(group_id, term, leader)
             }
@@ -243,25 +362,42 @@ impl From<Error> for sekas_api::server::v1::Error {
                 v1::Error::cas_failed(index, cond_index, prev_value)
             }
             Error::TxnConflict => v1::Error::txn_conflict(),
+            Error::InvalidJson(reason) => v1::Error::invalid_json(reason),
+            Error::VersionTooOld(version, gc_watermark) => {
+                v1::Error::version_too_old(version, gc_watermark)
+            }
+            Error::ValueTypeMismatch { expected, actual_len } => {
+                v1::Error::value_type_mismatch(expected, actual_len)
+            }
+            Error::MessageTooLarge { size, limit } => v1::Error::message_too_large(size, limit),
+            Error::QuotaExceeded { limit, value } => v1::Error::quota_exceeded(limit, value),
 
             Error::Forward(_) => panic!("Forward only used inside node"),
             Error::ServiceIsBusy(_) => panic!("ServiceIsBusy only used inside node"),
             Error::GroupNotReady(_) => panic!("GroupNotReady only used inside node"),
             Error::AbortScheduleTask(_) => panic!("AbortScheduleTask only used inside node"),
             Error::AlreadyExists(msg) => v1::Error::status(Code::AlreadyExists.into(), msg),
+            Error::ResourceExhausted(msg) => v1::Error::status(Code::ResourceExhausted.into(), msg),
+            Error::PermissionDenied(msg) => v1::Error::status(Code::PermissionDenied.into(), msg),
+            Error::TxnShardFenced(shard_id) => v1::Error::txn_shard_fenced(shard_id),
 
             err @ (Error::Transport(_)
-            | Error::ResourceExhausted(_)
             | Error::Raft(_)
             | Error::RaftEngine(_)
             | Error::RocksDb(_)
             | Error::Io(_)
             | Error::InvalidData(_)
+            | Error::SnapshotCorrupted(_)
+            | Error::StorageCorrupted(_)
             | Error::DatabaseNotFound(_)
+            | Error::TableNotFound(_)
             | Error::ShardNotFound(_)
             | Error::ClusterNotMatch
+            | Error::JoinRejected(_)
+            | Error::IncompatibleDataDir(_)
             | Error::NoAvaliableGroup
             | Error::Canceled
+            | Error::KeyNotFound(_)
             | Error::Rpc(_)) => v1::Error::status(Code::Internal.into(), err.to_string()),
         }
     }
@@ -277,18 +413,43 @@ impl From<sekas_client::Error> for Error {
     fn from(err: sekas_client::Error) -> Self {
         match err {
             sekas_client::Error::InvalidArgument(v) => Error::InvalidArgument(v),
-            sekas_client::Error::DeadlineExceeded(v) => Error::DeadlineExceeded(v),
+            sekas_client::Error::DeadlineExceeded(v, _) => Error::DeadlineExceeded(v),
             sekas_client::Error::AlreadyExists(v) => Error::AlreadyExists(v),
             sekas_client::Error::ResourceExhausted(v) => Error::ResourceExhausted(v),
+            sekas_client::Error::PermissionDenied(v) => Error::PermissionDenied(v),
             sekas_client::Error::CasFailed(index, cond_index, prev_value) => {
                 Error::CasFailed(index, cond_index, prev_value)
             }
             sekas_client::Error::TxnConflict => Error::TxnConflict,
+            sekas_client::Error::InvalidJson(v) => Error::InvalidJson(v),
+            sekas_client::Error::VersionTooOld(version, gc_watermark) => {
+                Error::VersionTooOld(version, gc_watermark)
+            }
+            sekas_client::Error::ValueTypeMismatch { expected, actual_len } => {
+                Error::ValueTypeMismatch { expected, actual_len }
+            }
+            sekas_client::Error::MessageTooLarge { size, limit } => {
+                Error::MessageTooLarge { size, limit }
+            }
+            sekas_client::Error::QuotaExceeded { limit, value } => {
+                Error::QuotaExceeded { limit, value }
+            }
+            sekas_client::Error::TxnShardFenced(shard_id) => Error::TxnShardFenced(shard_id),
+            err @ sekas_client::Error::ClientMemoryExhausted { .. } => {
+                Error::ResourceExhausted(err.to_string())
+            }
             sekas_client::Error::Rpc(err) => Error::Rpc(err),
             sekas_client::Error::Connect(err) => Error::Rpc(err),
             sekas_client::Error::Transport(err) => Error::Rpc(err),
+            err @ sekas_client::Error::RootUnavailable(_) => {
+                Error::Rpc(tonic::Status::unavailable(err.to_string()))
+            }
 
             sekas_client::Error::GroupNotFound(v) => Error::GroupNotFound(v),
+            sekas_client::Error::GroupRelocated(v, hint) => Error::GroupRelocated(v, hint),
+            sekas_client::Error::ReplicaNotReady(group, replica, reason) => {
+                Error::ReplicaNotReady(group, replica, reason)
+            }
             sekas_client::Error::NotRootLeader(desc, term, leader) => {
                 Error::NotRootLeader(desc, term, leader)
             }
@@ -300,7 +461,7 @@ impl From<sekas_client::Error> for Error {
             // NOTE: This is a fallback, for some scenarios where you don't need to deal with
             // `GroupNotAccessable` raised by `GroupClient`. (`GroupNotReady` only used inside
             // nodes)
-            sekas_client::Error::GroupNotAccessable(id) => Error::GroupNotReady(id),
+            sekas_client::Error::GroupNotAccessable(id, _) => Error::GroupNotReady(id),
 
             // FIXME(walter) handle unknown errors.
             sekas_client::Error::NotFound(v) => panic!("unknown not found: {v}"),
@@ -13,6 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#![cfg_attr(test, feature(test))]
 #![feature(const_type_name)]
 #![feature(cursor_remaining)]
 #![feature(exclusive_range_pattern)]
@@ -20,6 +21,7 @@
 #![feature(linked_list_cursors)]
 
 mod bootstrap;
+mod compat;
 mod config;
 mod constants;
 mod engine;
@@ -30,13 +32,15 @@ mod schedule;
 mod service;
 mod transport;
 
+pub mod cipher;
 pub mod node;
+pub mod offline;
 pub mod raftgroup;
 pub mod serverpb;
 
 pub(crate) use tonic::async_trait;
 
-pub use crate::bootstrap::run;
+pub use crate::bootstrap::{run, run_with_reload};
 pub use crate::config::*;
 pub use crate::error::{Error, Result};
 pub use crate::root::diagnosis;
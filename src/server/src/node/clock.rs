@@ -0,0 +1,162 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The clock skew detection of node.
+//!
+//! Root sends the estimated cluster clock in each heartbeat request, and the
+//! node answers with its own wall clock in the response. The node estimates its
+//! skew by the heartbeat requests, and root estimates the offset of each node
+//! by the heartbeat responses, see `crate::root::clock`.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use super::metrics::{NODE_CLOCK_SKEWED, NODE_CLOCK_SKEW_SECONDS};
+use crate::ClockConfig;
+
+/// The wall clock of node, in nanoseconds since the unix epoch.
+#[derive(Clone, Debug, Default)]
+pub struct WallClock {
+    offset_ms: i64,
+}
+
+impl WallClock {
+    pub fn new(cfg: &ClockConfig) -> Self {
+        WallClock { offset_ms: cfg.testing_offset_ms }
+    }
+
+    pub fn now_nanos(&self) -> u64 {
+        let now = sekas_runtime::time::unix_time().as_nanos() as i64;
+        now.saturating_add(self.offset_ms.saturating_mul(1_000_000)).max(0) as u64
+    }
+}
+
+/// Track the clock skew between the node and the cluster.
+#[derive(Clone)]
+pub struct ClockSkewMonitor {
+    inner: Arc<MonitorInner>,
+}
+
+struct MonitorInner {
+    clock: WallClock,
+    max_skew: Duration,
+    skew_nanos: AtomicU64,
+    skewed: AtomicBool,
+}
+
+impl ClockSkewMonitor {
+    pub fn new(cfg: &ClockConfig) -> Self {
+        ClockSkewMonitor {
+            inner: Arc::new(MonitorInner {
+                clock: WallClock::new(cfg),
+                max_skew: cfg.max_clock_skew(),
+                skew_nanos: AtomicU64::new(0),
+                skewed: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// The wall clock of the node.
+    #[inline]
+    pub fn clock(&self) -> &WallClock {
+        &self.inner.clock
+    }
+
+    /// Observe the cluster clock, which is carried by a heartbeat request. The
+    /// network delay is counted as skew, so the estimation is
+    /// conservative.
+    pub fn observe_cluster_clock(&self, cluster_nanos: u64) {
+        if cluster_nanos == 0 {
+            // The root doesn't carry the cluster clock.
+            return;
+        }
+
+        let skew = self.inner.clock.now_nanos().abs_diff(cluster_nanos);
+        self.inner.skew_nanos.store(skew, Ordering::Release);
+        NODE_CLOCK_SKEW_SECONDS.set(Duration::from_nanos(skew).as_secs_f64());
+
+        let skewed = Duration::from_nanos(skew) > self.inner.max_skew;
+        if self.inner.skewed.swap(skewed, Ordering::AcqRel) != skewed {
+            NODE_CLOCK_SKEWED.set(skewed as i64);
+            if skewed {
+                warn!(
+                    "the clock skew {:?} exceeds the limit {:?}, refuse to serve new commits",
+                    Duration::from_nanos(skew),
+                    self.inner.max_skew
+                );
+            } else {
+                info!(
+                    "the clock skew {:?} falls back under the limit {:?}",
+                    Duration::from_nanos(skew),
+                    self.inner.max_skew
+                );
+            }
+        }
+    }
+
+    /// The last estimated clock skew against the cluster clock.
+    #[inline]
+    pub fn skew(&self) -> Duration {
+        Duration::from_nanos(self.inner.skew_nanos.load(Ordering::Acquire))
+    }
+
+    /// Whether the clock skew exceeds the limit.
+    #[inline]
+    pub fn is_skewed(&self) -> bool {
+        self.inner.skewed.load(Ordering::Acquire)
+    }
+
+    /// The max tolerated clock skew.
+    #[inline]
+    pub fn max_skew(&self) -> Duration {
+        self.inner.max_skew
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_skew_exceeds_limit() {
+        let cfg = ClockConfig { max_clock_skew_ms: 100, ..Default::default() };
+        let monitor = ClockSkewMonitor::new(&cfg);
+        monitor.observe_cluster_clock(0);
+        assert!(!monitor.is_skewed());
+
+        let now = monitor.clock().now_nanos();
+        monitor.observe_cluster_clock(now);
+        assert!(!monitor.is_skewed());
+        assert!(monitor.skew() < Duration::from_millis(100));
+
+        monitor.observe_cluster_clock(now - Duration::from_secs(1).as_nanos() as u64);
+        assert!(monitor.is_skewed());
+        assert!(monitor.skew() >= Duration::from_secs(1));
+
+        monitor.observe_cluster_clock(monitor.clock().now_nanos());
+        assert!(!monitor.is_skewed());
+    }
+
+    #[test]
+    fn wall_clock_with_offset() {
+        let cfg = ClockConfig { testing_offset_ms: 10_000, ..Default::default() };
+        let skewed_clock = WallClock::new(&cfg);
+        let clock = WallClock::default();
+        let diff = skewed_clock.now_nanos() - clock.now_nanos();
+        assert!(Duration::from_nanos(diff) > Duration::from_secs(9));
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The manual compactions of the replicas, issued by the administrator to
+//! reclaim the disk space after large deletes, instead of waiting for the
+//! compactions picked by the engine.
+//!
+//! All replicas of a node share the same data dir, so at most one manual
+//! compaction is running on a node and the others are queued. A request of a
+//! replica is coalesced into the queued or running compaction of the same
+//! replica, if the range of that compaction covers the request.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use log::{debug, info, warn};
+use sekas_api::server::v1::*;
+
+use crate::engine::GroupEngine;
+use crate::{Error, Result};
+
+type CompactFuture = Shared<BoxFuture<'static, Result<CompactReplicaResponse, String>>>;
+
+/// The manual compactions of the replicas served by a node.
+#[derive(Clone, Default)]
+pub struct ManualCompactions {
+    inner: Arc<CompactionsInner>,
+}
+
+#[derive(Default)]
+struct CompactionsInner {
+    next_id: AtomicU64,
+    /// It is held by the running compaction.
+    running: futures::lock::Mutex<()>,
+    /// The queued and running compactions, keyed by the replica id.
+    pending: Mutex<HashMap<u64, Vec<PendingCompaction>>>,
+}
+
+struct PendingCompaction {
+    id: u64,
+    range: Option<CompactRange>,
+    future: CompactFuture,
+}
+
+impl ManualCompactions {
+    /// Compact the range of the replica, or all the data of the replica if the
+    /// range is not specified. It returns once the compaction is finished.
+    pub async fn compact(
+        &self,
+        replica_id: u64,
+        engine: GroupEngine,
+        range: Option<CompactRange>,
+    ) -> Result<CompactReplicaResponse> {
+        let (future, coalesced) = {
+            let mut pending = self.inner.pending.lock().unwrap();
+            let compactions = pending.entry(replica_id).or_default();
+            match compactions.iter().find(|c| covers(c.range.as_ref(), range.as_ref())) {
+                Some(compaction) => {
+                    debug!("the compaction of replica {replica_id} is coalesced");
+                    (compaction.future.clone(), true)
+                }
+                None => {
+                    let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+                    let future = self.clone().run(id, replica_id, engine, range.clone());
+                    // The compaction is finished even if all requests are canceled.
+                    let handle = sekas_runtime::spawn(future);
+                    let future = async move {
+                        handle.await.unwrap_or_else(|_| Err("the compaction is canceled".into()))
+                    };
+                    let future = future.boxed().shared();
+                    compactions.push(PendingCompaction { id, range, future: future.clone() });
+                    (future, false)
+                }
+            }
+        };
+        let mut resp = future.await.map_err(|msg| Error::Rpc(tonic::Status::internal(msg)))?;
+        resp.coalesced = coalesced;
+        Ok(resp)
+    }
+
+    async fn run(
+        self,
+        id: u64,
+        replica_id: u64,
+        engine: GroupEngine,
+        range: Option<CompactRange>,
+    ) -> Result<CompactReplicaResponse, String> {
+        let resp = {
+            let _running = self.inner.running.lock().await;
+            info!("replica {replica_id} begin manual compaction, range {range:?}");
+            let gc_state = engine.gc_state();
+            let (versions, _) = gc_state.dropped();
+            // Compact in the blocking threads, the foreground requests are not blocked.
+            let result =
+                sekas_runtime::spawn_blocking(move || engine.compact_range(range.as_ref())).await;
+            match result {
+                Ok(Ok((size_before, size_after))) => {
+                    let (dropped_versions, _) = gc_state.dropped();
+                    info!(
+                        "replica {replica_id} manual compaction is finished, size {size_before} => {size_after}"
+                    );
+                    Ok(CompactReplicaResponse {
+                        dropped_versions: dropped_versions - versions,
+                        size_before,
+                        size_after,
+                        coalesced: false,
+                    })
+                }
+                Ok(Err(err)) => {
+                    warn!("replica {replica_id} manual compaction: {err}");
+                    Err(format!("compact replica {replica_id}: {err}"))
+                }
+                Err(_) => Err("the compaction is canceled".into()),
+            }
+        };
+
+        let mut pending = self.inner.pending.lock().unwrap();
+        if let Some(compactions) = pending.get_mut(&replica_id) {
+            compactions.retain(|c| c.id != id);
+            if compactions.is_empty() {
+                pending.remove(&replica_id);
+            }
+        }
+        resp
+    }
+}
+
+/// Whether the range `a` covers the range `b`, `None` means all data of the
+/// replica.
+fn covers(a: Option<&CompactRange>, b: Option<&CompactRange>) -> bool {
+    match (a, b) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(a), Some(b)) => {
+            a.table_id == b.table_id
+                && a.start <= b.start
+                && (a.end.is_empty() || (!b.end.is_empty() && b.end <= a.end))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(table_id: u64, start: &[u8], end: &[u8]) -> CompactRange {
+        CompactRange { table_id, start: start.to_vec(), end: end.to_vec() }
+    }
+
+    #[test]
+    fn compact_range_covers() {
+        let table = range(1, b"", b"");
+        assert!(covers(None, None));
+        assert!(covers(None, Some(&table)));
+        assert!(!covers(Some(&table), None));
+        assert!(covers(Some(&table), Some(&table)));
+        assert!(covers(Some(&table), Some(&range(1, b"a", b"b"))));
+        assert!(!covers(Some(&table), Some(&range(2, b"", b""))));
+
+        let part = range(1, b"b", b"d");
+        assert!(covers(Some(&part), Some(&range(1, b"b", b"c"))));
+        assert!(!covers(Some(&part), Some(&range(1, b"a", b"c"))));
+        assert!(!covers(Some(&part), Some(&range(1, b"c", b"e"))));
+        assert!(!covers(Some(&part), Some(&range(1, b"c", b""))));
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Forwarding the requests received by followers to the leaders.
+//!
+//! A client which guesses the leader wrong pays a `NotLeader` round trip and a
+//! retry. If the request allows it, the follower which knows the leader proxies
+//! the request to the leader over the internode transport and relays the
+//! response instead. The forwards are bounded by the deadline of the request
+//! and the number of concurrent forwards of the node, `NotLeader` is returned
+//! once any of them is exceeded, so followers never become unbounded proxies.
+//!
+//! Once a write is sent to the leader it might be applied even if the forward
+//! fails or times out, so `DeadlineExceeded` is returned for it instead of
+//! `NotLeader`, the client must not retry it as if it is never executed.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use sekas_api::server::v1::group_request_union::Request;
+
+use crate::ForwardConfig;
+
+/// The registry of the requests forwarded by a node.
+#[derive(Clone)]
+pub struct ForwardRegistry {
+    inner: Arc<RegistryInner>,
+}
+
+struct RegistryInner {
+    cfg: RwLock<ForwardConfig>,
+    in_flight: AtomicUsize,
+}
+
+/// A slot of the concurrent forwards, it is released once dropped.
+pub struct ForwardPermit {
+    inner: Arc<RegistryInner>,
+}
+
+impl ForwardRegistry {
+    pub fn new(cfg: ForwardConfig) -> Self {
+        let inner = RegistryInner { cfg: RwLock::new(cfg), in_flight: AtomicUsize::new(0) };
+        ForwardRegistry { inner: Arc::new(inner) }
+    }
+
+    /// Apply the reloaded limits, the in-flight forwards are not affected.
+    pub fn update_config(&self, cfg: ForwardConfig) {
+        *self.inner.cfg.write().unwrap() = cfg;
+    }
+
+    /// The timeout of the forwarded requests which don't carry a deadline.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.inner.cfg.read().unwrap().timeout_ms)
+    }
+
+    /// The number of the in-flight forwards.
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Acquire a slot of the concurrent forwards, `None` is returned if the
+    /// limit is exceeded.
+    pub fn try_acquire(&self) -> Option<ForwardPermit> {
+        let limit = self.inner.cfg.read().unwrap().max_concurrent_forwards;
+        if self.inner.in_flight.fetch_add(1, Ordering::AcqRel) >= limit {
+            self.inner.in_flight.fetch_sub(1, Ordering::AcqRel);
+            return None;
+        }
+        Some(ForwardPermit { inner: self.inner.clone() })
+    }
+}
+
+impl Drop for ForwardPermit {
+    fn drop(&mut self) {
+        self.inner.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Whether the request could be forwarded to the leader. The scans and the
+/// watches are streamed, and the others are issued by the cluster itself, so
+/// they are always served by the replica receiving them.
+pub fn is_forwardable(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::Get(_)
+            | Request::Write(_)
+            | Request::WriteIntent(_)
+            | Request::CommitIntent(_)
+            | Request::ClearIntent(_)
+    )
+}
+
+/// Whether the forwarded request could be retried safely if the outcome of the
+/// forwarding is unknown.
+pub fn is_idempotent(request: &Request) -> bool {
+    matches!(request, Request::Get(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_forward_limit() {
+        let registry =
+            ForwardRegistry::new(ForwardConfig { max_concurrent_forwards: 2, timeout_ms: 100 });
+        assert_eq!(registry.timeout(), Duration::from_millis(100));
+        let first = registry.try_acquire().unwrap();
+        let _second = registry.try_acquire().unwrap();
+        assert!(registry.try_acquire().is_none());
+        assert_eq!(registry.in_flight(), 2);
+
+        drop(first);
+        assert_eq!(registry.in_flight(), 1);
+        let _third = registry.try_acquire().unwrap();
+
+        // The forwarding is disabled.
+        registry.update_config(ForwardConfig { max_concurrent_forwards: 0, timeout_ms: 100 });
+        assert!(registry.try_acquire().is_none());
+        assert_eq!(registry.in_flight(), 2);
+    }
+}
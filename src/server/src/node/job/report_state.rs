@@ -12,13 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::time::Duration;
 
 use futures::channel::mpsc;
 use futures::StreamExt;
 use log::warn;
 use sekas_api::server::v1::report_request::GroupUpdates;
-use sekas_api::server::v1::{GroupDesc, ReplicaState, ReportRequest, ScheduleState};
+use sekas_api::server::v1::{
+    GroupDesc, ReplicaState, ReportRequest, ReportResponse, ScheduleState,
+};
 use sekas_client::RootClient;
 use sekas_runtime::JoinHandle;
 
@@ -28,6 +31,7 @@ use crate::transport::TransportManager;
 
 pub struct StateChannel {
     sender: mpsc::UnboundedSender<GroupUpdates>,
+    root_client: Option<RootClient>,
     _worker_handle: Option<JoinHandle<()>>,
 }
 
@@ -35,22 +39,71 @@ pub(crate) fn setup(transport_manager: &TransportManager) -> StateChannel {
     let (sender, receiver) = mpsc::unbounded();
 
     let client = transport_manager.root_client().clone();
+    let root_client = client.clone();
     let task_handle = sekas_runtime::spawn(async move {
         report_state_worker(receiver, client).await;
     });
 
-    StateChannel::new(sender, task_handle)
+    StateChannel::new(sender, root_client, task_handle)
 }
 
 async fn report_state_worker(
     mut receiver: mpsc::UnboundedReceiver<GroupUpdates>,
     root_client: RootClient,
 ) {
-    while let Some(updates) = wait_state_updates(&mut receiver).await {
+    // The descriptors reported to root, the following descriptors of the same
+    // group are reported as deltas based on them.
+    let mut reported_descs = HashMap::new();
+    while let Some(mut updates) = wait_state_updates(&mut receiver).await {
+        let full_updates = encode_group_desc_deltas(&mut reported_descs, &mut updates);
         let req = ReportRequest { updates };
         record_latency!(take_report_metrics());
-        report_state_updates(&root_client, req).await;
+        let resp = report_state_updates(&root_client, &req).await;
+        if resp.resync_group_ids.is_empty() {
+            continue;
+        }
+
+        // The deltas are not applicable to the descriptors in catalog, report the
+        // full descriptors again.
+        warn!("resync the group descriptors {:?} to root", resp.resync_group_ids);
+        let updates = full_updates
+            .into_iter()
+            .filter(|u| resp.resync_group_ids.contains(&u.group_id))
+            .collect::<Vec<_>>();
+        for u in &updates {
+            reported_descs.remove(&u.group_id);
+        }
+        report_state_updates(&root_client, &ReportRequest { updates }).await;
+    }
+}
+
+/// Replace the reported group descriptors with the deltas based on the
+/// descriptors reported before, returns the last full descriptor of each group
+/// which is reported as delta.
+fn encode_group_desc_deltas(
+    reported_descs: &mut HashMap<u64, GroupDesc>,
+    updates: &mut [GroupUpdates],
+) -> Vec<GroupUpdates> {
+    let mut full_updates: HashMap<u64, GroupUpdates> = HashMap::new();
+    for u in updates {
+        let Some(desc) = u.group_desc.take() else { continue };
+        match reported_descs.get(&u.group_id) {
+            Some(base) if base.epoch < desc.epoch => {
+                u.group_desc_delta = Some(desc.delta_since(base));
+                full_updates.insert(
+                    u.group_id,
+                    GroupUpdates {
+                        group_id: u.group_id,
+                        group_desc: Some(desc.clone()),
+                        ..Default::default()
+                    },
+                );
+            }
+            _ => u.group_desc = Some(desc.clone()),
+        }
+        reported_descs.insert(u.group_id, desc);
     }
+    full_updates.into_values().collect()
 }
 
 /// Wait until at least a new request is received or the channel is closed.
@@ -87,23 +140,46 @@ async fn wait_state_updates(
 ///
 /// If one day you find that reporting has become a bottleneck, you can consider
 /// optimizing this code.
-async fn report_state_updates(root_client: &RootClient, request: ReportRequest) {
+async fn report_state_updates(root_client: &RootClient, request: &ReportRequest) -> ReportResponse {
     let mut interval = 1;
-    while let Err(e) = root_client.report(&request).await {
-        warn!("report state updates: {e}");
+    loop {
+        match root_client.report(request).await {
+            Ok(resp) => return resp,
+            Err(e) => warn!("report state updates: {e}"),
+        }
         sekas_runtime::time::sleep(Duration::from_millis(interval)).await;
         interval = std::cmp::min(interval * 2, 120);
     }
 }
 
 impl StateChannel {
-    pub fn new(sender: mpsc::UnboundedSender<GroupUpdates>, task_handle: JoinHandle<()>) -> Self {
-        StateChannel { sender, _worker_handle: Some(task_handle) }
+    pub fn new(
+        sender: mpsc::UnboundedSender<GroupUpdates>,
+        root_client: RootClient,
+        task_handle: JoinHandle<()>,
+    ) -> Self {
+        StateChannel { sender, root_client: Some(root_client), _worker_handle: Some(task_handle) }
     }
 
     #[cfg(test)]
     pub fn without_handle(sender: mpsc::UnboundedSender<GroupUpdates>) -> Self {
-        StateChannel { sender, _worker_handle: None }
+        StateChannel { sender, root_client: None, _worker_handle: None }
+    }
+
+    /// Report the group descriptor to root immediately, bypass the queued
+    /// updates. Returns the descriptor in catalog if it is newer than the
+    /// reported one, `None` is returned if there is no root to verify with.
+    pub async fn verify_group_descriptor(
+        &self,
+        group_id: u64,
+        group_desc: GroupDesc,
+    ) -> sekas_client::Result<Option<GroupDesc>> {
+        let Some(root_client) = self.root_client.as_ref() else {
+            return Ok(None);
+        };
+        let update = GroupUpdates { group_id, group_desc: Some(group_desc), ..Default::default() };
+        let resp = root_client.report(&ReportRequest { updates: vec![update] }).await?;
+        Ok(resp.newer_group_descs.into_iter().find(|desc| desc.id == group_id))
     }
 
     #[inline]
@@ -46,6 +46,122 @@ lazy_static! {
     pub static ref NODE_INGEST_CHUNK_TOTAL: IntCounter =
         register_int_counter!("node_ingest_chunk_total", "The total of ingest chunks of node")
             .unwrap();
+    pub static ref NODE_REPLICA_OBSERVER_DROPPED_EVENTS_TOTAL: IntCounter = register_int_counter!(
+        "node_replica_observer_dropped_events_total",
+        "The total of replica events dropped by the lagging observers of node"
+    )
+    .unwrap();
+    pub static ref NODE_ACTIVE_WATCHES: IntGauge =
+        register_int_gauge!("node_active_watches", "The number of active watches of node").unwrap();
+    pub static ref NODE_WATCH_BUFFERED_BYTES: IntGauge = register_int_gauge!(
+        "node_watch_buffered_bytes",
+        "The bytes of undelivered events buffered by watches of node"
+    )
+    .unwrap();
+    pub static ref NODE_WATCH_REJECTED_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "node_watch_rejected_total",
+        "The total of rejected watches of node",
+        &["limit"]
+    )
+    .unwrap();
+    pub static ref NODE_WATCH_LAGGING_TOTAL: IntCounter = register_int_counter!(
+        "node_watch_lagging_total",
+        "The total of watches cancelled as lagging of node"
+    )
+    .unwrap();
+    pub static ref NODE_SCAN_BUFFERED_BYTES: IntGauge = register_int_gauge!(
+        "node_scan_buffered_bytes",
+        "The bytes of value sets held by in-flight scans of node"
+    )
+    .unwrap();
+    pub static ref NODE_SCAN_PEAK_BUFFERED_BYTES: IntGauge = register_int_gauge!(
+        "node_scan_peak_buffered_bytes",
+        "The peak bytes of value sets held by in-flight scans of node"
+    )
+    .unwrap();
+    pub static ref NODE_SCAN_REJECTED_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "node_scan_rejected_total",
+        "The total of scans rejected by the memory limits of node",
+        &["limit"]
+    )
+    .unwrap();
+    pub static ref NODE_CLOCK_SKEW_SECONDS: Gauge = register_gauge!(
+        "node_clock_skew_seconds",
+        "The estimated clock skew between node and root"
+    )
+    .unwrap();
+    pub static ref NODE_CLOCK_SKEWED: IntGauge = register_int_gauge!(
+        "node_clock_skewed",
+        "Whether the clock skew of node exceeds the limit"
+    )
+    .unwrap();
+    pub static ref NODE_FORWARD_LEADER_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "node_forward_leader_total",
+        "The total of requests forwarded by the followers of node to the leaders",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref NODE_FORWARD_LEADER_DURATION_SECONDS: Histogram = register_histogram!(
+        "node_forward_leader_duration_seconds",
+        "The latency added by forwarding the requests to the leaders",
+        exponential_buckets(0.00005, 1.8, 26).unwrap(),
+    )
+    .unwrap();
+    pub static ref NODE_READ_REPLICA_REQUEST_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "node_read_replica_request_total",
+        "The total of requests served by the read replicas of node",
+        &["node", "type"]
+    )
+    .unwrap();
+    pub static ref NODE_READ_REPLICA_REJECTED_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "node_read_replica_rejected_total",
+        "The total of reads rejected by the stale or removed replicas of node",
+        &["node", "reason"]
+    )
+    .unwrap();
+    pub static ref NODE_TABLE_READ_QPS: GaugeVec = register_gauge_vec!(
+        "node_table_read_qps",
+        "The read requests per second of the tables led by node",
+        &["table"]
+    )
+    .unwrap();
+    pub static ref NODE_TABLE_WRITE_QPS: GaugeVec = register_gauge_vec!(
+        "node_table_write_qps",
+        "The write requests per second of the tables led by node",
+        &["table"]
+    )
+    .unwrap();
+    pub static ref NODE_TABLE_SIZE_BYTES: IntGaugeVec = register_int_gauge_vec!(
+        "node_table_size_bytes",
+        "The approximate bytes of the tables led by node",
+        &["table"]
+    )
+    .unwrap();
+    pub static ref NODE_TABLE_KEYS: IntGaugeVec = register_int_gauge_vec!(
+        "node_table_keys",
+        "The approximate number of the keys of the tables led by node",
+        &["table"]
+    )
+    .unwrap();
+}
+
+/// Roll the stats of the shards led by node up to the per-table metrics. The
+/// metrics of the tables no longer led by node are removed.
+pub fn report_table_stats(group_stats: &[sekas_api::server::v1::GroupStats]) {
+    NODE_TABLE_READ_QPS.reset();
+    NODE_TABLE_WRITE_QPS.reset();
+    NODE_TABLE_SIZE_BYTES.reset();
+    NODE_TABLE_KEYS.reset();
+    for shard in group_stats.iter().flat_map(|stats| stats.shard_stats.iter()) {
+        if shard.removed {
+            continue;
+        }
+        let table = shard.table_id.to_string();
+        NODE_TABLE_READ_QPS.with_label_values(&[&table]).add(shard.read_qps as f64);
+        NODE_TABLE_WRITE_QPS.with_label_values(&[&table]).add(shard.write_qps as f64);
+        NODE_TABLE_SIZE_BYTES.with_label_values(&[&table]).add(shard.shard_size as i64);
+        NODE_TABLE_KEYS.with_label_values(&[&table]).add(shard.num_keys as i64);
+    }
 }
 
 pub fn take_destory_replica_metrics() -> &'static Histogram {
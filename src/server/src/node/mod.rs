@@ -15,12 +15,21 @@
 
 pub mod metrics;
 
+pub mod clock;
+pub mod compact;
+pub mod forward;
 pub mod job;
 pub mod move_shard;
+pub mod observer;
 pub mod route_table;
+pub mod scan;
+pub mod status;
+pub mod tombstone;
+pub mod watch;
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::channel::mpsc;
 use futures::lock::Mutex;
@@ -29,25 +38,38 @@ use sekas_api::server::v1::group_request_union::Request;
 use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
 use sekas_client::ClientOptions;
+use sekas_runtime::time::Instant;
 use sekas_runtime::TaskGroup;
+use sekas_schema::property;
 
+use self::clock::ClockSkewMonitor;
+use self::compact::ManualCompactions;
+use self::forward::ForwardRegistry;
 use self::job::StateChannel;
 use self::move_shard::{ForwardCtx, MoveShardController};
+use self::observer::{ReplicaEvent, ReplicaObservers};
 pub use self::route_table::{RaftRouteTable, ReplicaRouteTable};
+use self::scan::ScanRegistry;
+use self::tombstone::GroupTombstones;
+use self::watch::WatchRegistry;
+use crate::cipher::DataCipher;
 use crate::constants::ROOT_GROUP_ID;
 use crate::engine::{Engines, GroupEngine, RawDb, StateEngine};
-use crate::raftgroup::snap::RecycleSnapMode;
-use crate::raftgroup::{ChannelManager, RaftGroup, RaftManager, SnapManager};
+use crate::error::BusyReason;
+use crate::raftgroup::snap::{RecycleSnapMode, SnapSendScheduler};
+use crate::raftgroup::{ChannelManager, RaftGroup, RaftManager, SnapManager, StateMachine};
 use crate::replica::fsm::{GroupStateMachine, WatchHub};
 pub use crate::replica::Replica;
 use crate::replica::{ExecCtx, LeaseState, LeaseStateObserver, ReplicaInfo};
 use crate::schedule::MoveReplicasProvider;
 use crate::serverpb::v1::*;
-use crate::transport::TransportManager;
+use crate::transport::{TokenBucket, TransportManager};
 use crate::{Config, EngineConfig, Error, NodeConfig, Result};
 
+/// The number of recent raft entries searched if the limit isn't specified.
+const DEFAULT_SEARCH_RAFT_LOG_LIMIT: usize = 1024;
+
 struct ReplicaContext {
-    #[allow(dead_code)]
     info: Arc<ReplicaInfo>,
     task_group: TaskGroup,
 }
@@ -86,6 +108,19 @@ where
     engines: Engines,
     state_engine: StateEngine,
     task_group: TaskGroup,
+    watch_registry: WatchRegistry,
+    scan_registry: ScanRegistry,
+    forward_registry: ForwardRegistry,
+    manual_compactions: ManualCompactions,
+    clock_skew: ClockSkewMonitor,
+    /// The final descriptors of the groups removed from this node recently.
+    group_tombstones: GroupTombstones,
+    /// The instant the node is started, the uptime is measured from it.
+    started_at: Instant,
+    health_sampler: status::HealthSampler,
+    /// The cipher to seal the shard chunks pulled by the moving shards, `None`
+    /// if the encryption is disabled.
+    cipher: Option<Arc<dyn DataCipher>>,
 
     /// Node related metadata, including serving replicas, root desc.
     node_state: Arc<Mutex<NodeState>>,
@@ -103,16 +138,31 @@ impl Node {
     ) -> Result<Self> {
         let raft_route_table = RaftRouteTable::new();
         let trans_mgr = Arc::new(ChannelManager::new(
+            &cfg.addr,
             transport_manager.address_resolver(),
             raft_route_table.clone(),
         ));
         let snap_dir = engines.snap_dir();
-        let snap_mgr = SnapManager::recovery(snap_dir).await?;
+        let cipher = crate::cipher::open_data_cipher(&cfg.encryption)?;
+        // The bandwidth is shared by moving shards and sending snapshots.
+        let bucket = TokenBucket::new(cfg.node.shard_move_bytes_per_sec);
+        let send_scheduler =
+            SnapSendScheduler::new(cfg.node.snapshot_send_concurrency, bucket.clone());
+        let snap_mgr = SnapManager::recovery(snap_dir, cipher.clone(), send_scheduler).await?;
         let raft_mgr = Arc::new(
             RaftManager::open(cfg.raft.clone(), engines.log(), snap_mgr, trans_mgr).await?,
         );
-        let migrate_ctrl = MoveShardController::new(cfg.node.clone(), transport_manager.clone());
+        let migrate_ctrl = MoveShardController::new(
+            cfg.node.clone(),
+            bucket,
+            transport_manager.clone(),
+            cipher.clone(),
+        );
         let state_engine = engines.state();
+        let watch_registry = WatchRegistry::new(cfg.node.watch.clone());
+        let scan_registry = ScanRegistry::new(cfg.node.scan.clone());
+        let forward_registry = ForwardRegistry::new(cfg.node.forward.clone());
+        let clock_skew = ClockSkewMonitor::new(&cfg.node.clock);
         Ok(Node {
             cfg: cfg.node,
             transport_manager,
@@ -123,6 +173,15 @@ impl Node {
             engines,
             state_engine,
             task_group: TaskGroup::default(),
+            watch_registry,
+            scan_registry,
+            forward_registry,
+            manual_compactions: ManualCompactions::default(),
+            clock_skew,
+            group_tombstones: GroupTombstones::default(),
+            started_at: Instant::now(),
+            health_sampler: status::HealthSampler::default(),
+            cipher,
             node_state: Arc::new(Mutex::new(NodeState::default())),
             replica_mutation: Arc::default(),
         })
@@ -141,6 +200,10 @@ impl Node {
         node_state.ident = Some(node_ident.to_owned());
         let state_channel = Arc::new(setup_report_state(&self.transport_manager));
 
+        // The pins are restored before the replicas are served, so the pinned
+        // versions are never dropped by their compactions.
+        self.restore_gc_pins().await?;
+
         let node_id = node_ident.node_id;
         for (group_id, replica_id, state) in self.state_engine.replica_states().await? {
             if state == ReplicaLocalState::Terminated {
@@ -182,6 +245,7 @@ impl Node {
         if self.check_replica_existence(group_id, replica_id).await? {
             return Ok(());
         }
+        self.group_tombstones.remove(group_id);
 
         // To ensure crash-recovery consistency, first create raft metadata, and then
         // save replica state. In this way, even if the node is restarted before
@@ -249,8 +313,10 @@ impl Node {
         };
 
         replica.shutdown(actual_desc).await?;
+        self.group_tombstones.insert(actual_desc);
         self.replica_route_table.remove(group_id);
         self.raft_route_table.delete(replica_id);
+        self.cfg.replica_observers.emit(ReplicaEvent::Destroyed { group_id, replica_id });
 
         let task_group = {
             let mut node_state = self.node_state.lock().await;
@@ -288,6 +354,7 @@ impl Node {
         local_state: ReplicaLocalState,
         channel: Arc<StateChannel>,
     ) -> Result<ReplicaContext> {
+        use crate::replica::{setup_descriptor_verifier, setup_shard_purger};
         use crate::schedule::setup_scheduler;
 
         let group_engine =
@@ -304,6 +371,11 @@ impl Node {
         )));
         let (watcher_sender, watcher_receiver) = std::sync::mpsc::channel();
         let watch_hub = WatchHub::new(watcher_receiver);
+        // Emit it before the raft group is started, so the leader events of this
+        // replica are observed after it.
+        self.cfg
+            .replica_observers
+            .emit(ReplicaEvent::Created { group_id, replica_id: info.replica_id });
         let raft_node = start_raft_group(
             &self.cfg,
             &self.raft_mgr,
@@ -318,8 +390,12 @@ impl Node {
 
         let replica_id = info.replica_id;
         let move_replicas_provider = Arc::new(MoveReplicasProvider::new());
-        let schedule_state_observer =
-            Arc::new(LeaseStateObserver::new(info.clone(), lease_state.clone(), channel));
+        let schedule_state_observer = Arc::new(LeaseStateObserver::new(
+            info.clone(),
+            lease_state.clone(),
+            channel.clone(),
+            self.cfg.replica_observers.clone(),
+        ));
 
         // TODO: config client options.
         let client = self.transport_manager.build_client(ClientOptions::default());
@@ -331,6 +407,8 @@ impl Node {
             client,
             move_replicas_provider.clone(),
             watcher_sender,
+            self.cfg.replica.hot_key.clone(),
+            self.cfg.replica.proposal_queue.clone(),
         );
         let replica = Arc::new(replica);
         self.replica_route_table.update(replica.clone());
@@ -340,6 +418,14 @@ impl Node {
         let migrate_handle = self.move_shard_ctrl.watch_state_changes(replica.clone(), receiver);
         task_group.add_task(migrate_handle);
 
+        let verify_timeout = Duration::from_millis(self.cfg.replica.verify_descriptor_timeout_ms);
+        let verifier_handle =
+            setup_descriptor_verifier(replica.clone(), channel.clone(), verify_timeout);
+        task_group.add_task(verifier_handle);
+
+        let purger_handle = setup_shard_purger(replica.clone());
+        task_group.add_task(purger_handle);
+
         let scheduler_handle = setup_scheduler(
             self.cfg.replica.clone(),
             replica.clone(),
@@ -363,6 +449,12 @@ impl Node {
         Ok(ReplicaContext { info, task_group })
     }
 
+    /// The observers of the lifecycle of the replicas served by this node.
+    #[inline]
+    pub fn replica_observers(&self) -> &ReplicaObservers {
+        &self.cfg.replica_observers
+    }
+
     /// Get root desc that known by node.
     pub async fn get_root(&self) -> RootDesc {
         self.node_state.lock().await.root.clone()
@@ -391,6 +483,18 @@ impl Node {
         Ok(())
     }
 
+    /// Stall the request until the testing knob is reset, the request is
+    /// canceled once its deadline is exceeded.
+    #[cfg(any(test, feature = "testing"))]
+    async fn stall_by_testing_knobs(&self) {
+        use std::sync::atomic::Ordering;
+
+        let knob = &self.cfg.testing_knobs.stall_write_intents;
+        while knob.load(Ordering::Acquire) {
+            sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
     pub async fn execute_request(
         &self,
         exec_ctx: &ExecCtx,
@@ -399,30 +503,219 @@ impl Node {
         use crate::replica::retry::execute;
 
         let Some(replica) = self.replica_route_table.find(request.group_id) else {
-            return Err(Error::GroupNotFound(request.group_id));
+            return Err(self.group_not_found(request.group_id).await);
+        };
+
+        let is_commit =
+            request.request.as_ref().and_then(|r| r.request.as_ref()).is_some_and(|r| {
+                matches!(
+                    r,
+                    Request::Write(_)
+                        | Request::DeletePrefix(_)
+                        | Request::WriteIntent(_)
+                        | Request::CommitIntent(_)
+                )
+            });
+        if is_commit && self.clock_skew.is_skewed() {
+            return Err(self.reject_skewed_commit(&replica));
+        }
+
+        let is_get_raw_key = request
+            .request
+            .as_ref()
+            .is_some_and(|r| matches!(r.request, Some(Request::GetRawKey(_))));
+        if is_get_raw_key && !self.cfg.replica.enable_get_raw_key {
+            return Err(Error::PermissionDenied("get raw key is disabled".into()));
+        }
+
+        if let Some(request) = request.request.as_ref().and_then(|r| r.request.as_ref()) {
+            self.check_table_writable(&replica, exec_ctx, request)?;
+            #[cfg(any(test, feature = "testing"))]
+            if matches!(request, Request::WriteIntent(_)) {
+                self.stall_by_testing_knobs().await;
+            }
+        }
+
+        let allow_forward = request.allow_forward
+            && request
+                .request
+                .as_ref()
+                .and_then(|r| r.request.as_ref())
+                .is_some_and(forward::is_forwardable);
+
+        let mut exec_ctx = exec_ctx.clone();
+        if !request.request_id.is_empty()
+            && request.record_request_id.unwrap_or(self.cfg.replica.record_request_id)
+        {
+            exec_ctx.request_id = Some(request.request_id.clone());
+        }
+
+        // The backoff of retrying the request refused by the dest group of a
+        // canceled moving shard.
+        const MAX_ABORTING_RETRIES: usize = 10;
+        const MAX_ABORTING_BACKOFF: Duration = Duration::from_millis(64);
+        let mut aborting_retries = 0;
+        let mut aborting_backoff = Duration::from_millis(1);
+        let resp = loop {
+            let forward_ctx = match execute(&replica, &exec_ctx, request).await {
+                Err(Error::Forward(forward_ctx)) => forward_ctx,
+                Ok(resp) => break resp,
+                Err(err @ Error::NotLeader(..)) if allow_forward => {
+                    return self.forward_to_leader(&exec_ctx, request, err).await;
+                }
+                Err(err) => return Err(err),
+            };
+            let request =
+                request.request.as_ref().and_then(|request| request.request.as_ref()).ok_or_else(
+                    || Error::InvalidArgument("GroupRequest::request is None".into()),
+                )?;
+            if let Request::Scan(scan_request) = request {
+                let scan_resp =
+                    self.forward_scan_request(replica, forward_ctx, scan_request).await?;
+                break GroupResponse::new(Response::Scan(scan_resp));
+            }
+            match self.move_shard_ctrl.forward(forward_ctx, request).await {
+                Ok(resp) => break GroupResponse::new(resp),
+                Err(Error::EpochNotMatch(_)) if aborting_retries < MAX_ABORTING_RETRIES => {
+                    // The dest group refuses the forwarded request since the moving shard is
+                    // canceled, execute it again once the moving is aborted in this group.
+                    aborting_retries += 1;
+                    sekas_runtime::time::sleep(aborting_backoff).await;
+                    aborting_backoff = (aborting_backoff * 2).min(MAX_ABORTING_BACKOFF);
+                }
+                Err(Error::EpochNotMatch(_)) => {
+                    warn!(
+                        "group {} the canceled moving shard isn't aborted after {} retries",
+                        replica.replica_info().group_id,
+                        aborting_retries
+                    );
+                    return Err(Error::ServiceIsBusy(BusyReason::Moving));
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        if is_commit && self.cfg.clock.commit_wait {
+            // Wait out the uncertainty of clocks, so that the commits are
+            // ordered in real time even if the clocks of nodes are skewed.
+            sekas_runtime::time::sleep(self.clock_skew.max_skew()).await;
+        }
+        Ok(resp)
+    }
+
+    /// Forward the request to the leader and relay the response, instead of
+    /// returning `NotLeader` to the client. The `NotLeader` is returned if the
+    /// leader is unknown, the limit of concurrent forwards is exceeded, or the
+    /// request isn't sent to the leader. A sent write which fails or times out
+    /// might be applied, its outcome is unknown, see [`forward`].
+    async fn forward_to_leader(
+        &self,
+        exec_ctx: &ExecCtx,
+        request: &GroupRequest,
+        err: Error,
+    ) -> Result<GroupResponse> {
+        use self::metrics::*;
+
+        let Error::NotLeader(group_id, _, Some(leader)) = &err else {
+            return Err(err);
+        };
+        let Some(_permit) = self.forward_registry.try_acquire() else {
+            NODE_FORWARD_LEADER_TOTAL.with_label_values(&["rejected"]).inc();
+            return Err(err);
         };
+        let timeout = match exec_ctx.deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => self.forward_registry.timeout(),
+        };
+        let client = match self.transport_manager.find_node_client(leader.node_id) {
+            Ok(client) => client,
+            Err(e) => {
+                debug!("group {group_id} forward request to node {}: {e}", leader.node_id);
+                NODE_FORWARD_LEADER_TOTAL.with_label_values(&["failed"]).inc();
+                return Err(err);
+            }
+        };
+
+        // The leader might be changed again, the forwarded request is never
+        // forwarded by the receiver.
+        let mut forwarded = request.clone();
+        forwarded.allow_forward = false;
+        let mut forwarded = tonic::Request::new(forwarded);
+        forwarded.set_timeout(timeout);
+        let start = Instant::now();
+        let forward = client.unary_group_request(forwarded);
+        match sekas_runtime::time::timeout(timeout, forward).await {
+            Ok(Ok(resp)) => {
+                NODE_FORWARD_LEADER_TOTAL.with_label_values(&["forwarded"]).inc();
+                NODE_FORWARD_LEADER_DURATION_SECONDS.observe(start.elapsed().as_secs_f64());
+                Ok(resp)
+            }
+            Ok(Err(status)) => {
+                debug!("group {group_id} forward request to node {}: {status}", leader.node_id);
+                NODE_FORWARD_LEADER_TOTAL.with_label_values(&["failed"]).inc();
+                if sekas_client::error::retryable_rpc_err(&status) {
+                    // The request is never sent to the leader.
+                    return Err(err);
+                }
+                Err(forward_outcome_unknown(request, err, status.to_string()))
+            }
+            Err(_) => {
+                debug!("group {group_id} forward request to node {} timeout", leader.node_id);
+                NODE_FORWARD_LEADER_TOTAL.with_label_values(&["failed"]).inc();
+                Err(forward_outcome_unknown(request, err, "timeout".to_owned()))
+            }
+        }
+    }
+
+    /// The group is not served by this node. Tell the client where the group
+    /// went if it is known, either from the final descriptor of the removed
+    /// replica, or from the routes watched by this node, whichever is newer.
+    async fn group_not_found(&self, group_id: u64) -> Error {
+        let routed =
+            self.transport_manager.find_group(group_id).ok().map(|state| GroupRelocatedHint {
+                replicas: state.replicas.into_values().collect(),
+                epoch: state.epoch,
+            });
+        let hint = match (self.group_tombstones.get(group_id), routed) {
+            (Some(a), Some(b)) => Some(if a.epoch >= b.epoch { a } else { b }),
+            (a, b) => a.or(b),
+        };
+        let node_id = self.node_state.lock().await.ident.as_ref().map(|ident| ident.node_id);
+        match hint {
+            // The hint pointing to this node is staled.
+            Some(hint)
+                if !hint.replicas.is_empty()
+                    && !hint.replicas.iter().any(|r| Some(r.node_id) == node_id) =>
+            {
+                Error::GroupRelocated(group_id, hint)
+            }
+            _ => Error::GroupNotFound(group_id),
+        }
+    }
 
-        match execute(&replica, exec_ctx, request).await {
-            Err(Error::Forward(forward_ctx)) => {
-                let request = request
-                    .request
-                    .as_ref()
-                    .and_then(|request| request.request.as_ref())
-                    .ok_or_else(|| {
-                        Error::InvalidArgument("GroupRequest::request is None".into())
-                    })?;
-                if let Request::Scan(scan_request) = request {
-                    let scan_resp =
-                        self.forward_scan_request(replica, forward_ctx, scan_request).await?;
-                    Ok(GroupResponse::new(Response::Scan(scan_resp)))
-                } else {
-                    let resp = self.move_shard_ctrl.forward(forward_ctx, request).await?;
-                    Ok(GroupResponse::new(resp))
+    /// Reject a commit request because the clock of this node is skewed. If the
+    /// replica is the leader, the leadership is transferred to a voter on
+    /// another node, so that the client could retry the request there.
+    fn reject_skewed_commit(&self, replica: &Replica) -> Error {
+        let group_id = replica.replica_info().group_id;
+        let replica_state = replica.replica_state();
+        if replica_state.role == RaftRole::Leader as i32 {
+            let node_id = replica_state.node_id;
+            let transferee = replica
+                .descriptor()
+                .replicas
+                .into_iter()
+                .find(|r| r.node_id != node_id && r.role == ReplicaRole::Voter as i32);
+            if let Some(transferee) = transferee {
+                info!(
+                    "clock is skewed, transfer leadership of group {group_id} to replica {}",
+                    transferee.id
+                );
+                if let Err(err) = replica.raft_node().transfer_leader(transferee.id) {
+                    warn!("group {group_id} transfer leadership to {}: {err}", transferee.id);
                 }
             }
-            Ok(resp) => Ok(resp),
-            Err(err) => Err(err),
         }
+        Error::NotLeader(group_id, replica_state.term, None)
     }
 
     pub async fn forward(&self, request: ForwardRequest) -> Result<ForwardResponse> {
@@ -458,8 +751,12 @@ impl Node {
         }
 
         debug_assert!(request.request.is_some());
-        let group_request =
-            GroupRequest { group_id: request.group_id, epoch: 0, request: request.request };
+        let group_request = GroupRequest {
+            group_id: request.group_id,
+            epoch: 0,
+            request: request.request,
+            ..Default::default()
+        };
 
         let exec_ctx = ExecCtx::forward(request.shard_id);
         let resp = match execute(&replica, &exec_ctx, &group_request).await {
@@ -471,12 +768,25 @@ impl Node {
         Ok(ForwardResponse { response: resp.response })
     }
 
+    /// Ingest the value sets into a serving shard, the keys already exist are
+    /// skipped. This request is issued by root to copy a cloning table.
+    pub async fn ingest(&self, request: IngestRequest) -> Result<()> {
+        let Some(replica) = self.replica_route_table.find(request.group_id) else {
+            return Err(Error::GroupNotFound(request.group_id));
+        };
+
+        replica.ingest_serving_value_sets(request.shard_id, &request.value_sets).await
+    }
+
     // This request is issued by dest group.
     pub async fn move_shard(&self, event: MoveShardEvent, desc: MoveShardDesc) -> Result<()> {
         use crate::replica::retry::move_shard_with_retry;
 
-        if desc.shard_desc.is_none() {
+        let Some(shard_desc) = desc.shard_desc.as_ref() else {
             return Err(Error::InvalidArgument("MoveShardDesc::shard_desc".to_owned()));
+        };
+        if sekas_schema::shard::is_txn_shard(shard_desc.id) {
+            return Err(Error::TxnShardFenced(shard_desc.id));
         }
 
         let group_id = desc.src_group_id;
@@ -491,6 +801,34 @@ impl Node {
         Ok(())
     }
 
+    /// Cancel a moving shard task before it is committed. This request is
+    /// issued to the dest group.
+    pub async fn cancel_move(&self, desc: MoveShardDesc) -> Result<()> {
+        if desc.shard_desc.is_none() {
+            return Err(Error::InvalidArgument("MoveShardDesc::shard_desc".to_owned()));
+        }
+
+        let group_id = desc.dest_group_id;
+        let Some(replica) = self.replica_route_table.find(group_id) else {
+            return Err(Error::GroupNotFound(group_id));
+        };
+        replica.cancel_shard_moving(&desc).await
+    }
+
+    /// Abort the canceled moving shard task and restore the moved data. This
+    /// request is issued by dest group.
+    pub async fn abort_move(&self, desc: MoveShardDesc) -> Result<()> {
+        if desc.shard_desc.is_none() {
+            return Err(Error::InvalidArgument("MoveShardDesc::shard_desc".to_owned()));
+        }
+
+        let group_id = desc.src_group_id;
+        let Some(replica) = self.replica_route_table.find(group_id) else {
+            return Err(Error::GroupNotFound(group_id));
+        };
+        self.move_shard_ctrl.abort_moving_shard(&replica, &desc).await
+    }
+
     #[inline]
     pub fn replica_table(&self) -> &ReplicaRouteTable {
         &self.replica_route_table
@@ -511,8 +849,64 @@ impl Node {
         &self.raft_mgr
     }
 
+    #[inline]
+    pub fn watch_registry(&self) -> &WatchRegistry {
+        &self.watch_registry
+    }
+
+    #[inline]
+    pub fn scan_registry(&self) -> &ScanRegistry {
+        &self.scan_registry
+    }
+
+    #[inline]
+    pub fn data_cipher(&self) -> Option<&Arc<dyn DataCipher>> {
+        self.cipher.as_ref()
+    }
+
+    #[inline]
+    pub fn clock_skew(&self) -> &ClockSkewMonitor {
+        &self.clock_skew
+    }
+
+    /// Apply the hot-reloadable settings of the node config to the running
+    /// subsystems, see [`Config::reload`].
+    pub fn reload_config(&self, cfg: &NodeConfig) {
+        self.watch_registry.update_config(cfg.watch.clone());
+        self.scan_registry.update_config(cfg.scan.clone());
+        self.forward_registry.update_config(cfg.forward.clone());
+        self.set_transfer_limits(cfg.snapshot_send_concurrency, cfg.shard_move_bytes_per_sec);
+    }
+
+    /// Change the limits of the bulk transfers, the bandwidth is shared by
+    /// moving shards and sending snapshots.
+    fn set_transfer_limits(&self, snapshot_send_concurrency: usize, bytes_per_sec: u64) {
+        let send_scheduler = self.raft_mgr.snapshot_manager().send_scheduler();
+        send_scheduler.set_max_concurrency(snapshot_send_concurrency);
+        send_scheduler.bucket().set_rate(bytes_per_sec);
+    }
+
+    /// Apply the limits of the bulk transfers changed by the `CONFIG`
+    /// statement, the absent ones are kept.
+    pub fn configure_transfer(&self, req: &ConfigureTransferRequest) -> ConfigureTransferResponse {
+        let send_scheduler = self.raft_mgr.snapshot_manager().send_scheduler();
+        if let Some(concurrency) = req.snapshot_send_concurrency {
+            send_scheduler.set_max_concurrency(concurrency as usize);
+        }
+        if let Some(bytes_per_sec) = req.bytes_per_sec {
+            send_scheduler.bucket().set_rate(bytes_per_sec);
+        }
+        ConfigureTransferResponse {}
+    }
+
+    /// Apply the election options of the raft groups changed by the `CONFIG`
+    /// statement, the absent ones are kept.
+    pub fn configure_raft(&self, req: &ConfigureRaftRequest) -> ConfigureRaftResponse {
+        self.raft_mgr.configure_election(req.pre_vote, req.check_quorum);
+        ConfigureRaftResponse {}
+    }
+
     pub async fn collect_stats(&self, _req: &CollectStatsRequest) -> CollectStatsResponse {
-        // TODO(walter) add read/write qps.
         let mut ns = NodeStats::default();
         let mut group_stats = vec![];
         let mut replica_stats = vec![];
@@ -532,19 +926,25 @@ impl Node {
                     ns.group_count += 1;
                 }
                 let replica_state = replica.replica_state();
-                if replica_state.role == RaftRole::Leader as i32 {
-                    ns.leader_count += 1;
-                    group_stats.push(replica.collect_group_stats());
-                }
-                let rs = ReplicaStats {
+                let mut rs = ReplicaStats {
                     replica_id: info.replica_id,
                     group_id: info.group_id,
                     read_qps: 0.,
                     write_qps: 0.,
                 };
+                if replica_state.role == RaftRole::Leader as i32 {
+                    ns.leader_count += 1;
+                    let stats = replica.collect_group_stats();
+                    rs.read_qps = stats.read_qps;
+                    rs.write_qps = stats.write_qps;
+                    ns.read_qps += stats.read_qps;
+                    ns.write_qps += stats.write_qps;
+                    group_stats.push(stats);
+                }
                 replica_stats.push(rs);
             }
         }
+        metrics::report_table_stats(&group_stats);
 
         CollectStatsResponse { node_stats: Some(ns), group_stats, replica_stats }
     }
@@ -583,7 +983,8 @@ impl Node {
     ) -> CollectMovingShardStateResponse {
         use collect_moving_shard_state_response::State;
 
-        let mut resp = CollectMovingShardStateResponse { state: State::None as i32, desc: None };
+        let mut resp =
+            CollectMovingShardStateResponse { state: State::None as i32, ..Default::default() };
 
         let group_id = req.group;
         if let Some(replica) = self.replica_route_table.find(group_id) {
@@ -593,6 +994,7 @@ impl Node {
                         Some(MoveShardStep::Prepare) => State::Prepare,
                         Some(MoveShardStep::Moved) => State::Moved,
                         Some(MoveShardStep::Moving) => State::Moving,
+                        Some(MoveShardStep::Aborting) => State::Aborting,
                         _ => State::None,
                     };
                     if ms.move_shard.is_none() {
@@ -600,6 +1002,8 @@ impl Node {
                     }
                     resp.state = state as i32;
                     resp.desc = ms.move_shard;
+                    resp.moved_keys = ms.moved_keys;
+                    resp.moved_bytes = ms.moved_bytes;
                 }
             }
         }
@@ -626,6 +1030,253 @@ impl Node {
         resp
     }
 
+    /// Search the recent raft entries of the replica served by this node, only
+    /// the entries which write keys with the prefix are returned.
+    pub fn search_raft_log(&self, req: &SearchRaftLogRequest) -> Result<SearchRaftLogResponse> {
+        use prost::Message;
+        use raft::prelude::EntryType;
+
+        let Some(replica) = self.replica_route_table.find(req.group_id) else {
+            return Err(Error::GroupNotFound(req.group_id));
+        };
+        let limit = if req.limit == 0 { DEFAULT_SEARCH_RAFT_LOG_LIMIT } else { req.limit as usize };
+        let raft_entries =
+            self.raft_mgr.recent_entries(replica.replica_info().replica_id, limit)?;
+        let mut entries = vec![];
+        for entry in raft_entries {
+            if entry.get_entry_type() != EntryType::EntryNormal || entry.data.is_empty() {
+                continue;
+            }
+            let eval_result = EvalResult::decode(&*entry.data)?;
+            let Some(summary) = summarize_eval_result(&eval_result, &req.key_prefix) else {
+                continue;
+            };
+            entries.push(RaftLogEntry {
+                index: entry.index,
+                term: entry.term,
+                request_id: eval_result.request_id,
+                summary,
+            });
+        }
+        Ok(SearchRaftLogResponse { entries })
+    }
+
+    /// Compact all the data of the replica, the versions beneath the GC
+    /// watermark are dropped by the compaction. The watermark is advanced
+    /// through raft first if it is specified, so it requires the leader.
+    pub async fn compact_group(&self, req: &CompactGroupRequest) -> Result<CompactGroupResponse> {
+        let Some(replica) = self.replica_route_table.find(req.group_id) else {
+            return Err(Error::GroupNotFound(req.group_id));
+        };
+        self.refresh_version_retention(&replica);
+        if let Some(watermark) = req.gc_watermark {
+            replica.update_gc_watermark(watermark).await?;
+        }
+        let group_engine = replica.group_engine();
+        let gc_state = group_engine.gc_state();
+        let (versions, bytes) = gc_state.dropped();
+        group_engine.compact();
+        let (dropped_versions, reclaimed_bytes) = gc_state.dropped();
+        Ok(CompactGroupResponse {
+            dropped_versions: dropped_versions - versions,
+            reclaimed_bytes: reclaimed_bytes - bytes,
+        })
+    }
+
+    /// Compact the data of the replica served by this node, see
+    /// [`ManualCompactions`].
+    pub async fn compact_replica(
+        &self,
+        req: CompactReplicaRequest,
+    ) -> Result<CompactReplicaResponse> {
+        let group_id = {
+            let node_state = self.node_state.lock().await;
+            node_state.serving_replicas.get(&req.replica_id).map(|ctx| ctx.info.group_id)
+        };
+        let replica = group_id.and_then(|group_id| self.replica_route_table.find(group_id));
+        let Some(replica) = replica.filter(|r| r.replica_info().replica_id == req.replica_id)
+        else {
+            return Err(Error::InvalidArgument(format!(
+                "replica {} is not served by this node",
+                req.replica_id
+            )));
+        };
+        self.refresh_version_retention(&replica);
+        self.manual_compactions.compact(req.replica_id, replica.group_engine(), req.range).await
+    }
+
+    /// Pin the GC watermark of this node at the version until the lease
+    /// expires, the pin is removed if the lease is zero. The pin is rejected
+    /// if the versions visible at it might have been dropped by the groups.
+    /// The groups are verified on every renewal too, unless the pin is still
+    /// held by this node, whose compactions have retained the versions since
+    /// it was verified. The pin is persisted, so it survives the restarts of
+    /// this node until the lease expires.
+    pub async fn pin_gc(&self, req: &PinGcRequest) -> Result<PinGcResponse> {
+        let db = self.engines.db();
+        let pins = db.gc_pins();
+        if req.lease_ms == 0 {
+            pins.unpin(&req.pin_id);
+            self.state_engine.remove_gc_pin(&req.pin_id).await?;
+            return Ok(PinGcResponse {});
+        }
+
+        // Pin before verifying, so the watermark used by the following compactions
+        // never exceeds the version once it is verified.
+        let held = pins.holds(&req.pin_id, req.version);
+        pins.pin(&req.pin_id, req.version, Duration::from_millis(req.lease_ms));
+        for group_id in req.group_ids.iter().filter(|_| !held) {
+            let Some(replica) = self.replica_route_table.find(*group_id) else {
+                continue;
+            };
+            let watermark = replica.group_engine().gc_state().unpinned_watermark();
+            if req.version < watermark {
+                pins.unpin(&req.pin_id);
+                self.state_engine.remove_gc_pin(&req.pin_id).await?;
+                return Err(Error::VersionTooOld(req.version, watermark));
+            }
+        }
+        let pin = GcPinMeta {
+            pin_id: req.pin_id.clone(),
+            version: req.version,
+            deadline_ms: sekas_runtime::time::timestamp_millis() + req.lease_ms,
+        };
+        self.state_engine.save_gc_pin(&pin).await?;
+        Ok(PinGcResponse {})
+    }
+
+    /// Restore the unexpired GC pins persisted before the restart, the expired
+    /// ones are removed.
+    async fn restore_gc_pins(&self) -> Result<()> {
+        let db = self.engines.db();
+        let now_ms = sekas_runtime::time::timestamp_millis();
+        for pin in self.state_engine.gc_pins().await? {
+            if pin.deadline_ms <= now_ms {
+                self.state_engine.remove_gc_pin(&pin.pin_id).await?;
+                continue;
+            }
+            info!("restore gc pin {} at version {}", pin.pin_id, pin.version);
+            let lease = Duration::from_millis(pin.deadline_ms - now_ms);
+            db.gc_pins().pin(&pin.pin_id, pin.version, lease);
+        }
+        Ok(())
+    }
+
+    /// Resolve the quarantine of the replica served by this node. Skipping the
+    /// poisoned entry requires its index and the confirmation token
+    /// `skip/<replica_id>/<index>`.
+    pub async fn resolve_quarantine(
+        &self,
+        req: &ResolveQuarantineRequest,
+    ) -> Result<ResolveQuarantineResponse> {
+        let Some(replica) = self.replica_route_table.find(req.group_id) else {
+            return Err(Error::GroupNotFound(req.group_id));
+        };
+        let Some(action) = QuarantineAction::from_i32(req.action) else {
+            return Err(Error::InvalidArgument(format!(
+                "unknown quarantine action {}",
+                req.action
+            )));
+        };
+        let replica_id = replica.replica_info().replica_id;
+        if action == QuarantineAction::Skip {
+            let token = format!("skip/{replica_id}/{}", req.expected_index);
+            if req.confirm_token != token {
+                return Err(Error::PermissionDenied(format!(
+                    "the confirmation token {token} is required to skip the entry"
+                )));
+            }
+        }
+
+        warn!(
+            "resolve the quarantine of group {} replica {replica_id} by {action:?}",
+            req.group_id
+        );
+        let quarantine = replica.raft_node().resolve_quarantine(action, req.expected_index).await?;
+        Ok(ResolveQuarantineResponse { quarantine })
+    }
+
+    /// Override the log level of the target in this process, the effective
+    /// filter is returned. It is shared by the servers of the process.
+    pub fn set_log_filter(&self, req: &SetLogFilterRequest) -> Result<SetLogFilterResponse> {
+        let filter = if req.target.is_empty() {
+            sekas_client::effective_log_filter().unwrap_or_default()
+        } else {
+            let ttl = Duration::from_millis(req.ttl_ms);
+            sekas_client::set_log_filter(&req.target, &req.level, ttl).map_err(|err| match err {
+                sekas_client::AppError::InvalidArgument(msg) => Error::InvalidArgument(msg),
+                err => Error::Rpc(err.into()),
+            })?
+        };
+        Ok(SetLogFilterResponse { filter })
+    }
+
+    /// The max bytes of each group request and response, `0` means unlimited.
+    #[inline]
+    pub fn max_message_bytes(&self) -> usize {
+        self.cfg.max_message_bytes
+    }
+
+    /// The limits of this node advertised to the clients.
+    pub fn get_capabilities(&self) -> GetCapabilitiesResponse {
+        let capabilities = NodeCapabilities {
+            max_txn_write_bytes: self.cfg.txn.max_write_bytes,
+            max_txn_write_count: self.cfg.txn.max_write_count,
+            max_message_bytes: self.cfg.max_message_bytes as u64,
+        };
+        GetCapabilitiesResponse { capabilities: Some(capabilities) }
+    }
+
+    /// The build and runtime status of this node.
+    pub async fn node_status(&self) -> NodeRuntimeStatus {
+        use crate::compat::{feature_names, SERVER_GIT_HASH, SERVER_VERSION, SUPPORTED_FEATURES};
+
+        let node_id = self.node_state.lock().await.ident.as_ref().map(|ident| ident.node_id);
+        let mut counts = ReplicaRoleCounts::default();
+        let mut open_proposals = 0;
+        for group_id in self.serving_group_id_list().await {
+            let Some(replica) = self.replica_route_table.find(group_id) else { continue };
+            let info = replica.replica_info();
+            if info.is_terminated() {
+                continue;
+            }
+            open_proposals += replica.open_proposals() as u64;
+            let descriptor = replica.descriptor();
+            let Some(desc) = descriptor.replicas.iter().find(|r| r.id == info.replica_id) else {
+                continue;
+            };
+            match ReplicaRole::from_i32(desc.role) {
+                Some(
+                    ReplicaRole::Voter | ReplicaRole::IncomingVoter | ReplicaRole::DemotingVoter,
+                ) => counts.voters += 1,
+                Some(ReplicaRole::Learner) => counts.learners += 1,
+                Some(ReplicaRole::ReadReplica) => counts.read_replicas += 1,
+                None => {}
+            }
+            if replica.replica_state().role == RaftRole::Leader as i32 {
+                counts.leaders += 1;
+            }
+        }
+
+        let (pre_vote, check_quorum) = self.raft_mgr.election_options();
+        NodeRuntimeStatus {
+            node_id: node_id.unwrap_or_default(),
+            version: SERVER_VERSION.to_owned(),
+            git_hash: SERVER_GIT_HASH.to_owned(),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            features: SUPPORTED_FEATURES,
+            feature_names: feature_names(SUPPORTED_FEATURES),
+            dirs: vec![
+                status::dir_usage("data", self.engines.db_path()),
+                status::dir_usage("wal", self.engines.log_path()),
+            ],
+            replica_counts: Some(counts),
+            health: Some(self.health_sampler.sample(open_proposals)),
+            pre_vote,
+            check_quorum,
+        }
+    }
+
     /// Forward scan request to dest group.
     ///
     /// Unlike other requests, scan request needs to scan both source and target
@@ -653,7 +1304,103 @@ impl Node {
             Response::Scan(scan) => scan,
             _ => return Err(Error::InvalidData("ShardScanResponse is required".into())),
         };
-        Ok(merge_scan_response(target_resp, source_resp))
+        Ok(merge_scan_response(target_resp, source_resp, scan_request.reverse))
+    }
+
+    /// Whether the request carries the secret of the servers of this cluster,
+    /// see [`sekas_client::INTERNAL_ORIGIN_HEADER`].
+    pub fn is_internal_origin(&self, origin: Option<&[u8]>) -> bool {
+        let expected = self.transport_manager.conn_manager().internal_origin();
+        matches!((origin, expected), (Some(origin), Some(expected)) if origin == expected.as_bytes())
+    }
+
+    /// Reject the writes to the system tables unless they are issued by the
+    /// servers, and the writes to the tables which are still cloning, the table
+    /// descs are delivered by the watch stream of root. The writes to a table
+    /// whose desc is not delivered yet are rejected too.
+    fn check_table_writable(
+        &self,
+        replica: &Replica,
+        exec_ctx: &ExecCtx,
+        request: &Request,
+    ) -> Result<()> {
+        let shard_id = match request {
+            Request::Write(req) => req.shard_id,
+            Request::WriteIntent(req) => req.shard_id,
+            Request::CommitIntent(req) => req.shard_id,
+            Request::ClearIntent(req) => req.shard_id,
+            Request::DeletePrefix(req) => req.shard_id,
+            _ => return Ok(()),
+        };
+        let descriptor = replica.descriptor();
+        let Some(shard) = descriptor.shards.iter().find(|s| s.id == shard_id) else {
+            return Ok(());
+        };
+        if sekas_schema::system::table::is_reserved_table(shard.table_id)
+            && !exec_ctx.internal_origin
+        {
+            return Err(Error::PermissionDenied(format!(
+                "shard {shard_id} of system table {} is only written by the servers",
+                shard.table_id
+            )));
+        }
+        if shard.table_id < sekas_schema::FIRST_USER_TABLE_ID
+            || matches!(request, Request::CommitIntent(_) | Request::ClearIntent(_))
+        {
+            // The system tables are never cloned, and the intents written before are
+            // always resolved.
+            return Ok(());
+        }
+        let router = self.transport_manager.router();
+        let Some(table) = router.find_table_by_id(shard.table_id) else {
+            // The table desc is not delivered yet, it might be still cloning. The
+            // client retries another replica.
+            let info = replica.replica_info();
+            return Err(Error::ReplicaNotReady(
+                info.group_id,
+                info.replica_id,
+                format!("table {} is unknown to the router", shard.table_id),
+            ));
+        };
+        if table.properties.contains_key(property::CLONE_SOURCE) {
+            return Err(Error::PermissionDenied(format!(
+                "table {} is read-only until the clone finishes",
+                table.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Refresh the max versions and the TTL of the tables served by the
+    /// replicas of this node, they are applied by the following compactions
+    /// and commits.
+    pub async fn refresh_all_version_retention(&self) {
+        for group_id in self.serving_group_id_list().await {
+            if let Some(replica) = self.replica_route_table.find(group_id) {
+                self.refresh_version_retention(&replica);
+            }
+        }
+    }
+
+    /// Refresh the max versions and the TTL of the tables served by the
+    /// replica, from the table descs delivered by the watch stream of root.
+    fn refresh_version_retention(&self, replica: &Replica) {
+        let router = self.transport_manager.router();
+        let mut max_versions = HashMap::default();
+        let mut table_ttls = HashMap::default();
+        for shard in &replica.descriptor().shards {
+            let Some(table) = router.find_table_by_id(shard.table_id) else { continue };
+            let parse = |name: &str| table.properties.get(name).and_then(|v| v.parse::<u64>().ok());
+            if let Some(num_versions) = parse(property::MAX_VERSIONS) {
+                max_versions.insert(shard.table_id, num_versions);
+            }
+            if let Some(ttl_sec) = parse(property::TTL) {
+                table_ttls.insert(shard.table_id, ttl_sec.saturating_mul(1000));
+            }
+        }
+        let gc_state = replica.group_engine().gc_state();
+        gc_state.set_max_versions(max_versions);
+        gc_state.set_table_ttls(table_ttls);
     }
 
     #[inline]
@@ -670,6 +1417,16 @@ impl NodeState {
     }
 }
 
+/// The forwarded request might be applied by the leader even if the forwarding
+/// fails, so only the idempotent request is redirected by `NotLeader`.
+fn forward_outcome_unknown(request: &GroupRequest, not_leader: Error, reason: String) -> Error {
+    let request = request.request.as_ref().and_then(|r| r.request.as_ref());
+    if request.is_some_and(forward::is_idempotent) {
+        return not_leader;
+    }
+    Error::DeadlineExceeded(format!("forward request to leader: {reason}, outcome unknown"))
+}
+
 async fn open_group_engine(
     cfg: &EngineConfig,
     raw_db: Arc<RawDb>,
@@ -700,21 +1457,110 @@ async fn start_raft_group(
     watch_hub: WatchHub,
 ) -> Result<RaftGroup> {
     let group_id = info.group_id;
-    let state_observer =
-        Box::new(LeaseStateObserver::new(info.clone(), lease_state.clone(), channel));
+    let state_observer = Box::new(LeaseStateObserver::new(
+        info.clone(),
+        lease_state.clone(),
+        channel,
+        cfg.replica_observers.clone(),
+    ));
 
-    let fsm = GroupStateMachine::new(
+    let mut fsm = GroupStateMachine::new(
         cfg.replica.clone(),
         info.clone(),
         group_engine.clone(),
         state_observer.clone(),
         watch_hub,
     );
+    if !fsm.verify_apply_checkpoint()? {
+        // Never serve the data of an inconsistent checkpoint, recover from the latest
+        // snapshot and replay the raft log after it.
+        let Some(snap_info) = raft_mgr.snapshot_manager().latest_snap(info.replica_id) else {
+            return Err(Error::InvalidData(format!(
+                "group {group_id} replica {} apply checkpoint is inconsistent and no snapshot is available",
+                info.replica_id
+            )));
+        };
+        let snap_index = snap_info.meta.apply_state.as_ref().map(|s| s.index).unwrap_or_default();
+        let truncated_index =
+            crate::raftgroup::read_raft_state(&raft_mgr.engine(), info.replica_id)?
+                .and_then(|(_, local_state)| local_state.last_truncated)
+                .map(|s| s.index)
+                .unwrap_or_default();
+        if snap_index < truncated_index {
+            return Err(Error::InvalidData(format!(
+                "group {group_id} replica {} apply checkpoint is inconsistent and the raft log after snapshot {snap_index} is truncated to {truncated_index}",
+                info.replica_id
+            )));
+        }
+        warn!(
+            "group {group_id} replica {} recover from snapshot {} since the apply checkpoint is inconsistent",
+            info.replica_id,
+            snap_info.base_dir.display()
+        );
+        fsm.apply_snapshot(&snap_info.base_dir)?;
+    }
     raft_mgr
         .start_raft_group(group_id, info.replica_id, info.node_id, fsm, state_observer, task_group)
         .await
 }
 
+/// Summarize the operations of the proposal, `None` is returned if it doesn't
+/// write any keys with the prefix.
+fn summarize_eval_result(eval_result: &EvalResult, key_prefix: &[u8]) -> Option<String> {
+    use crate::engine::{WriteBatch, WriteKind};
+
+    let mut ops = vec![];
+    if let Some(op) = &eval_result.op {
+        if !key_prefix.is_empty() {
+            return None;
+        }
+        let name = if op.add_shard.is_some() {
+            "add shard"
+        } else if op.purge_replica.is_some() {
+            "purge replica"
+        } else if op.move_shard.is_some() {
+            "move shard"
+        } else if op.split_shard.is_some() {
+            "split shard"
+        } else if op.merge_shard.is_some() {
+            "merge shard"
+        } else if op.sync_epoch.is_some() {
+            "sync epoch"
+        } else if op.remove_shard.is_some() {
+            "remove shard"
+        } else if op.update_gc_watermark.is_some() {
+            "update gc watermark"
+        } else {
+            "unknown"
+        };
+        ops.push(name.to_owned());
+    }
+    if let Some(batch) = &eval_result.batch {
+        let writes = WriteBatch::new(&batch.data)
+            .user_writes()
+            .into_iter()
+            .filter(|(_, user_key, _)| user_key.starts_with(key_prefix))
+            .map(|(_, _, kind)| kind)
+            .collect::<Vec<_>>();
+        if writes.is_empty() && eval_result.op.is_none() {
+            return None;
+        }
+        let kinds = [
+            (WriteKind::Put, "put"),
+            (WriteKind::Tombstone, "delete"),
+            (WriteKind::Intent, "write intent"),
+            (WriteKind::Clear, "clear"),
+        ];
+        for (kind, name) in kinds {
+            let num_writes = writes.iter().filter(|w| **w == kind).count();
+            if num_writes > 0 {
+                ops.push(format!("{name} {num_writes}"));
+            }
+        }
+    }
+    Some(ops.join(", "))
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::Path;
@@ -723,11 +1569,12 @@ mod tests {
     use sekas_api::server::v1::report_request::GroupUpdates;
     use sekas_rock::fn_name;
     use sekas_schema::system::txn::TXN_MAX_VERSION;
+    use sekas_schema::FIRST_TXN_SHARD_ID;
     use tempdir::TempDir;
 
     use super::*;
     use crate::constants::INITIAL_EPOCH;
-    use crate::RaftConfig;
+    use crate::{RaftConfig, ReplicaConfig};
 
     const TABLE_ID: u64 = 1;
     const NODE_ID: u64 = 2;
@@ -737,14 +1584,17 @@ mod tests {
 
     async fn create_node<P: AsRef<Path>>(root_dir: P) -> Node {
         let root_dir = root_dir.as_ref().to_owned();
+        // There is no root to verify the descriptors with.
+        let replica = ReplicaConfig { verify_descriptor_timeout_ms: 10, ..Default::default() };
         let config = Config {
             root_dir,
+            node: NodeConfig { replica, ..Default::default() },
             raft: RaftConfig { tick_interval_ms: 10, ..Default::default() },
             ..Default::default()
         };
 
         let engines = Engines::open(&config.root_dir, &config.db).unwrap();
-        let transport_manager = TransportManager::new(vec![], engines.state()).await;
+        let transport_manager = TransportManager::new(vec![], engines.state(), None).await.unwrap();
         Node::new(config, engines, transport_manager).await.unwrap()
     }
 
@@ -928,6 +1778,9 @@ mod tests {
             shard_id: SHARD_ID,
             start_version: version,
             user_key: key.to_vec(),
+            causal_token: 0,
+            txn_id: 0,
+            max_staleness_ms: 0,
         })
     }
 
@@ -1074,6 +1927,7 @@ mod tests {
                 take_prev_value: true,
                 ..Default::default()
             })),
+            ..Default::default()
         })
     }
 
@@ -1229,6 +2083,21 @@ mod tests {
                 split_shard: SplitShard { old_shard_id: 0, new_shard_id: 1, split_key: vec![b'b'] },
                 expect_shards: None,
             },
+            // The txn shards are fenced.
+            Test {
+                origin_shards: vec![ShardDesc::with_range(
+                    FIRST_TXN_SHARD_ID,
+                    table_id,
+                    vec![b'a'],
+                    vec![b'c'],
+                )],
+                split_shard: SplitShard {
+                    old_shard_id: FIRST_TXN_SHARD_ID,
+                    new_shard_id: 1,
+                    split_key: vec![b'b'],
+                },
+                expect_shards: None,
+            },
             // Split into two shards
             Test {
                 origin_shards: vec![ShardDesc::with_range(0, table_id, vec![b'a'], vec![b'c'])],
@@ -1323,6 +2192,15 @@ mod tests {
                 merge_shard: MergeShard { left_shard_id: 0, right_shard_id: 1 },
                 expect_shards: None,
             },
+            // The txn shards are fenced.
+            Test {
+                origin_shards: vec![
+                    ShardDesc::with_range(FIRST_TXN_SHARD_ID, table_id, vec![b'a'], vec![b'b']),
+                    ShardDesc::with_range(1, table_id, vec![b'b'], vec![b'c']),
+                ],
+                merge_shard: MergeShard { left_shard_id: FIRST_TXN_SHARD_ID, right_shard_id: 1 },
+                expect_shards: None,
+            },
             // Merge two shards.
             Test {
                 origin_shards: vec![
@@ -1374,4 +2252,25 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn forward_failure_of_non_idempotent_request_is_outcome_unknown() {
+        let wrap = |request| GroupRequest {
+            request: Some(GroupRequestUnion { request: Some(request) }),
+            ..Default::default()
+        };
+        let not_leader = || Error::NotLeader(1, 1, Some(ReplicaDesc::default()));
+
+        let get = wrap(Request::Get(ShardGetRequest::default()));
+        let err = forward_outcome_unknown(&get, not_leader(), "timeout".to_owned());
+        assert!(matches!(err, Error::NotLeader(..)), "{err:?}");
+
+        let write = wrap(Request::Write(ShardWriteRequest::default()));
+        let err = forward_outcome_unknown(&write, not_leader(), "timeout".to_owned());
+        assert!(matches!(err, Error::DeadlineExceeded(_)), "{err:?}");
+
+        let commit = wrap(Request::CommitIntent(CommitIntentRequest::default()));
+        let err = forward_outcome_unknown(&commit, not_leader(), "reset".to_owned());
+        assert!(matches!(err, Error::DeadlineExceeded(_)), "{err:?}");
+    }
 }
@@ -25,11 +25,13 @@ use sekas_api::Epoch;
 use sekas_client::MoveShardClient;
 use sekas_runtime::JoinHandle;
 
+use super::{MoveShardFaultPoint, MoveShardFaults};
+use crate::cipher::DataCipher;
 use crate::node::metrics::*;
 use crate::node::Replica;
 use crate::serverpb::v1::*;
-use crate::transport::TransportManager;
-use crate::{record_latency, NodeConfig, Result};
+use crate::transport::{TokenBucket, TransportManager};
+use crate::{record_latency, Error, NodeConfig, Result};
 
 #[derive(Debug)]
 pub struct ForwardCtx {
@@ -40,6 +42,9 @@ pub struct ForwardCtx {
 
 struct MoveShardCoordinator {
     cfg: NodeConfig,
+    /// The bandwidth of pulling shard chunks, it is shared with sending
+    /// snapshots.
+    bucket: TokenBucket,
 
     replica_id: u64,
     group_id: u64,
@@ -57,13 +62,22 @@ pub struct MoveShardController {
 
 struct MoveShardControllerShared {
     cfg: NodeConfig,
+    bucket: TokenBucket,
     transport_manager: TransportManager,
+    /// The cipher to open the sealed shard chunks, `None` if the encryption
+    /// is disabled.
+    cipher: Option<Arc<dyn DataCipher>>,
 }
 
 impl MoveShardController {
-    pub(crate) fn new(cfg: NodeConfig, transport_manager: TransportManager) -> Self {
+    pub(crate) fn new(
+        cfg: NodeConfig,
+        bucket: TokenBucket,
+        transport_manager: TransportManager,
+        cipher: Option<Arc<dyn DataCipher>>,
+    ) -> Self {
         MoveShardController {
-            shared: Arc::new(MoveShardControllerShared { cfg, transport_manager }),
+            shared: Arc::new(MoveShardControllerShared { cfg, bucket, transport_manager, cipher }),
         }
     }
 
@@ -92,10 +106,10 @@ impl MoveShardController {
                     } else {
                         desc.src_group_id
                     };
-                    let client =
-                        ctrl.shared.transport_manager.build_move_shard_client(target_group_id);
+                    let client = ctrl.shared.build_pull_client(target_group_id);
                     coord = Some(MoveShardCoordinator {
                         cfg: ctrl.shared.cfg.clone(),
+                        bucket: ctrl.shared.bucket.clone(),
                         replica_id,
                         group_id,
                         replica: replica.clone(),
@@ -129,6 +143,29 @@ impl MoveShardController {
         let resp = resp.response.and_then(|resp| resp.response);
         Ok(resp.unwrap())
     }
+
+    /// Abort the canceled moving shard in the source group. The data moved to
+    /// the dest group are restored before the moving state is cleared.
+    pub async fn abort_moving_shard(&self, replica: &Replica, desc: &MoveShardDesc) -> Result<()> {
+        if replica.move_shard_state().is_some_and(|state| state.get_move_shard_desc() == desc) {
+            let client = self.shared.build_pull_client(desc.dest_group_id);
+            restore_shard(&client, replica, desc).await?;
+        }
+        replica.abort_shard_moving(desc).await
+    }
+}
+
+impl MoveShardControllerShared {
+    /// Build the client to pull the shard chunks from the group, the chunks are
+    /// sealed in transit if the encryption is enabled.
+    fn build_pull_client(&self, group_id: u64) -> MoveShardClient {
+        let client = self.transport_manager.build_move_shard_client(group_id);
+        let Some(cipher) = self.cipher.clone() else { return client };
+        client.with_chunk_opener(Arc::new(move |sealed| {
+            crate::cipher::open_value_sets(cipher.as_ref(), sealed)
+                .map_err(|err| sekas_client::Error::Internal(Box::new(err)))
+        }))
+    }
 }
 
 impl MoveShardCoordinator {
@@ -146,6 +183,9 @@ impl MoveShardCoordinator {
                     // Send finish moving request to source group.
                     self.commit_source_group().await;
                 }
+                MoveShardStep::Aborting => {
+                    self.rollback_moving_shard().await;
+                }
                 MoveShardStep::Finished | MoveShardStep::Aborted => unreachable!(),
             }
         } else {
@@ -154,7 +194,9 @@ impl MoveShardCoordinator {
                     self.clean_orphan_shard().await;
                 }
                 MoveShardStep::Prepare | MoveShardStep::Moving => {}
-                MoveShardStep::Finished | MoveShardStep::Aborted => unreachable!(),
+                MoveShardStep::Finished | MoveShardStep::Aborted | MoveShardStep::Aborting => {
+                    unreachable!()
+                }
             }
         }
     }
@@ -171,6 +213,9 @@ impl MoveShardCoordinator {
                     "setup source group moving shard success. replica={}, group={}, desc={}",
                     self.replica_id, self.group_id, self.desc
                 );
+                if self.inject_fault(MoveShardFaultPoint::AfterAcquireShard).await {
+                    return;
+                }
                 self.enter_pulling_step().await;
             }
             Err(sekas_client::Error::EpochNotMatch(group_desc)) => {
@@ -190,6 +235,9 @@ impl MoveShardCoordinator {
     }
 
     async fn commit_source_group(&mut self) {
+        if self.inject_fault(MoveShardFaultPoint::BeforeCleanupSource).await {
+            return;
+        }
         if let Err(e) = self.client.move_out(&self.desc).await {
             error!(
                 "commit source group moving shard: {e:?}. replica={}, group={}, desc={}",
@@ -251,6 +299,37 @@ impl MoveShardCoordinator {
         );
     }
 
+    async fn rollback_moving_shard(&mut self) {
+        use super::gc::remove_shard;
+
+        if let Err(e) = self.client.abort_move(&self.desc).await {
+            error!(
+                "abort source group moving shard: {e:?}. replica={}, group={}, desc={}",
+                self.replica_id, self.group_id, self.desc
+            );
+            return;
+        }
+
+        info!(
+            "source group moving shard is aborted. replica={}, group={}, desc={}",
+            self.replica_id, self.group_id, self.desc
+        );
+
+        let group_engine = self.replica.group_engine();
+        if let Err(e) =
+            remove_shard(&self.cfg, self.replica.as_ref(), group_engine, self.desc.get_shard_id())
+                .await
+        {
+            error!(
+                "remove canceled shard from dest group: {e:?}. replica={}, group={}, desc={}",
+                self.replica_id, self.group_id, self.desc
+            );
+            return;
+        }
+
+        self.abort_moving_shard().await;
+    }
+
     async fn enter_pulling_step(&self) {
         if let Err(e) = self.replica.enter_pulling_step(&self.desc).await {
             error!(
@@ -279,8 +358,15 @@ impl MoveShardCoordinator {
     }
 
     async fn pull(&mut self, last_migrated_key: Option<Vec<u8>>) {
-        if let Err(e) =
-            pull_shard(&self.client, self.replica.as_ref(), &self.desc, last_migrated_key).await
+        if let Err(e) = pull_shard(
+            &self.client,
+            self.replica.as_ref(),
+            &self.desc,
+            last_migrated_key,
+            &self.bucket,
+            &self.cfg.testing_knobs.move_shard_faults,
+        )
+        .await
         {
             error!(
                 "pull shard from source group: {e:?}. replica={}, group={}, desc={}",
@@ -289,41 +375,90 @@ impl MoveShardCoordinator {
             return;
         }
 
+        if self.inject_fault(MoveShardFaultPoint::BeforeCommitMoving).await {
+            return;
+        }
         self.commit_dest_group().await;
     }
 
+    /// Returns whether the step is failed by an injected fault.
+    async fn inject_fault(&self, point: MoveShardFaultPoint) -> bool {
+        if !self.cfg.testing_knobs.move_shard_faults.hit(point).await {
+            return false;
+        }
+        warn!(
+            "moving shard is failed by injected fault at {point:?}. replica={}, group={}, desc={}",
+            self.replica_id, self.group_id, self.desc
+        );
+        true
+    }
+
     #[inline]
     fn is_dest_group(&self) -> bool {
         self.group_id == self.desc.dest_group_id
     }
 }
 
+/// Pull the shard chunks from the source group, the pulling bytes per second is
+/// limited by the `bucket`.
 pub async fn pull_shard(
     client: &MoveShardClient,
     replica: &Replica,
     desc: &MoveShardDesc,
     last_migrated_key: Option<Vec<u8>>,
+    bucket: &TokenBucket,
+    faults: &MoveShardFaults,
 ) -> Result<()> {
     record_latency!(take_pull_shard_metrics());
     let shard_id = desc.get_shard_id();
-    let mut finished = false;
-    let mut last_key = last_migrated_key;
-    while !finished {
-        trace!("pull shard {shard_id} chunk, last key {last_key:?}");
-        let shard_chunk = client.pull_shard_chunk(shard_id, last_key.clone()).await?;
+    trace!("pull shard {shard_id} chunks, last key {last_migrated_key:?}");
+    let mut shard_chunks = client.pull_shard(shard_id, last_migrated_key);
+    while let Some(shard_chunk) = shard_chunks.next().await {
+        let shard_chunk = shard_chunk?;
         trace!("pull shard {shard_id} chunk, receive {} value sets", shard_chunk.len());
-        if let Some(value_set) = shard_chunk.last() {
-            last_key = Some(value_set.user_key.clone());
-        } else {
-            finished = true;
-        }
         for value_set in &shard_chunk {
             replica.ingest_value_set(shard_id, value_set).await?;
         }
+        let chunk_bytes = shard_chunk.iter().map(value_set_bytes).sum::<u64>();
         if let Some(value_set) = shard_chunk.last() {
-            replica.save_ingest_progress(shard_id, &value_set.user_key).await?
+            let num_keys = shard_chunk.len() as u64;
+            replica
+                .save_ingest_progress(shard_id, &value_set.user_key, num_keys, chunk_bytes)
+                .await?
         }
         NODE_INGEST_CHUNK_TOTAL.inc();
+        if faults.hit(MoveShardFaultPoint::MidPull).await {
+            return Err(Error::Canceled);
+        }
+        if chunk_bytes > 0 {
+            bucket.consume(chunk_bytes).await;
+        }
     }
     Ok(())
 }
+
+/// Restore the data of the canceled moving shard from the dest group.
+async fn restore_shard(
+    client: &MoveShardClient,
+    replica: &Replica,
+    desc: &MoveShardDesc,
+) -> Result<()> {
+    let shard_id = desc.get_shard_id();
+    let mut shard_chunks = client.pull_shard(shard_id, None);
+    while let Some(shard_chunk) = shard_chunks.next().await {
+        let shard_chunk = shard_chunk?;
+        for value_set in &shard_chunk {
+            replica.restore_value_set(shard_id, value_set).await?;
+        }
+    }
+    Ok(())
+}
+
+fn value_set_bytes(value_set: &ValueSet) -> u64 {
+    let content_bytes = value_set
+        .values
+        .iter()
+        .map(|v| v.content.as_ref().map(Vec::len).unwrap_or_default())
+        .sum::<usize>();
+    (value_set.user_key.len() + content_bytes) as u64
+}
@@ -0,0 +1,149 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The faults injected into the moving shard coordinator, for testing only.
+//!
+//! A fault is injected at a named point of the coordinator of the dest group,
+//! it either pauses the coordinator until the fault is cleared, or fails the
+//! current step once, as if the leader crashed there. A failed step is not
+//! retried until the state is re-driven, eg by a leadership transfer.
+//!
+//! The faults can only be injected if the `testing` feature is enabled,
+//! otherwise reaching a point is a no-op.
+
+#[cfg(any(test, feature = "testing"))]
+use std::collections::HashMap;
+#[cfg(any(test, feature = "testing"))]
+use std::sync::{Arc, Mutex};
+#[cfg(any(test, feature = "testing"))]
+use std::time::Duration;
+
+/// The named points of the moving shard coordinator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MoveShardFaultPoint {
+    /// The shard is acquired by the source group, before entering the pulling
+    /// step.
+    AfterAcquireShard,
+    /// A chunk is ingested and its progress is saved, before pulling the next
+    /// chunk.
+    MidPull,
+    /// All chunks are pulled, before committing the moving in dest group.
+    BeforeCommitMoving,
+    /// The moving is committed in dest group, before the source group moves
+    /// out and cleans up the shard.
+    BeforeCleanupSource,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveShardFault {
+    /// Pause the coordinator until the fault is cleared.
+    Pause,
+    /// Fail the step once, the fault is cleared once it is hit.
+    Fail,
+}
+
+/// The faults shared by the coordinators of all nodes.
+#[derive(Clone, Debug, Default)]
+pub struct MoveShardFaults {
+    #[cfg(any(test, feature = "testing"))]
+    inner: Arc<Mutex<FaultsInner>>,
+}
+
+#[cfg(any(test, feature = "testing"))]
+#[derive(Debug, Default)]
+struct FaultsInner {
+    faults: HashMap<MoveShardFaultPoint, MoveShardFault>,
+    hits: HashMap<MoveShardFaultPoint, usize>,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl MoveShardFaults {
+    pub fn inject(&self, point: MoveShardFaultPoint, fault: MoveShardFault) {
+        self.inner.lock().unwrap().faults.insert(point, fault);
+    }
+
+    pub fn clear(&self, point: MoveShardFaultPoint) {
+        self.inner.lock().unwrap().faults.remove(&point);
+    }
+
+    /// The number of times the point is reached by the coordinators.
+    pub fn hits(&self, point: MoveShardFaultPoint) -> usize {
+        self.inner.lock().unwrap().hits.get(&point).cloned().unwrap_or_default()
+    }
+
+    /// Reach the point, returns whether the step should be failed.
+    pub(crate) async fn hit(&self, point: MoveShardFaultPoint) -> bool {
+        let mut paused = false;
+        loop {
+            {
+                let mut inner = self.inner.lock().unwrap();
+                if !paused {
+                    *inner.hits.entry(point).or_default() += 1;
+                }
+                match inner.faults.get(&point) {
+                    None => return false,
+                    Some(MoveShardFault::Fail) => {
+                        inner.faults.remove(&point);
+                        return true;
+                    }
+                    Some(MoveShardFault::Pause) => paused = true,
+                }
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+#[cfg(not(any(test, feature = "testing")))]
+impl MoveShardFaults {
+    /// Reach the point, no fault is injected without the `testing` feature.
+    #[inline(always)]
+    pub(crate) async fn hit(&self, _point: MoveShardFaultPoint) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sekas_macro::test]
+    async fn fail_fault_is_hit_once() {
+        let faults = MoveShardFaults::default();
+        assert!(!faults.hit(MoveShardFaultPoint::MidPull).await);
+        faults.inject(MoveShardFaultPoint::MidPull, MoveShardFault::Fail);
+        assert!(!faults.hit(MoveShardFaultPoint::AfterAcquireShard).await);
+        assert!(faults.hit(MoveShardFaultPoint::MidPull).await);
+        assert!(!faults.hit(MoveShardFaultPoint::MidPull).await);
+        assert_eq!(faults.hits(MoveShardFaultPoint::MidPull), 3);
+        assert_eq!(faults.hits(MoveShardFaultPoint::AfterAcquireShard), 1);
+    }
+
+    #[sekas_macro::test]
+    async fn pause_fault_until_cleared() {
+        let faults = MoveShardFaults::default();
+        faults.inject(MoveShardFaultPoint::BeforeCommitMoving, MoveShardFault::Pause);
+        let cloned = faults.clone();
+        let handle = sekas_runtime::spawn(async move {
+            cloned.hit(MoveShardFaultPoint::BeforeCommitMoving).await
+        });
+        while faults.hits(MoveShardFaultPoint::BeforeCommitMoving) == 0 {
+            sekas_runtime::time::sleep(Duration::from_millis(1)).await;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(faults.hits(MoveShardFaultPoint::BeforeCommitMoving), 1);
+        faults.clear(MoveShardFaultPoint::BeforeCommitMoving);
+        assert!(!handle.await.unwrap());
+    }
+}
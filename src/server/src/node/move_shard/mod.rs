@@ -14,6 +14,8 @@
 // limitations under the License.
 
 mod ctrl;
+mod fault;
 mod gc;
 
 pub(crate) use self::ctrl::{ForwardCtx, MoveShardController};
+pub use self::fault::{MoveShardFault, MoveShardFaultPoint, MoveShardFaults};
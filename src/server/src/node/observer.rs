@@ -0,0 +1,274 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The hooks of the replica lifecycle, for the binaries embedding the server.
+//!
+//! An observer is registered either by `NodeConfig::replica_observers` before
+//! the server is started, so that the replicas recovered by the bootstrap are
+//! observed too, or by `Server::register_replica_observer`, which only sees
+//! the events emitted after the registration.
+//!
+//! The events are delivered asynchronously by a dedicated thread of each
+//! observer, so the callbacks never block raft. Each observer has a bounded
+//! queue, the events are dropped and counted once the queue is full, see
+//! `ReplicaObservers::dropped_events`.
+//!
+//! Ordering guarantees:
+//! - The events of a replica are delivered in order: `on_replica_created`
+//!   first, then the alternating `on_leader_acquired` and `on_leader_lost`, and
+//!   `on_replica_destroyed` last, unless some of them are dropped.
+//! - The events of different replicas are not ordered, a replica of a group
+//!   might acquire the leadership before the old leader on the other node is
+//!   observed losing it.
+//! - A replica destroyed while it is the leader doesn't emit `on_leader_lost`,
+//!   and nothing is emitted when the node is shutdown.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+use log::{debug, info, warn};
+
+use super::metrics::NODE_REPLICA_OBSERVER_DROPPED_EVENTS_TOTAL;
+
+/// The number of undelivered events buffered for each observer.
+const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// Observe the lifecycle of the replicas served by this node. The callbacks are
+/// invoked by a dedicated thread, a slow callback only delays the following
+/// events of this observer.
+pub trait ReplicaObserver: Send + 'static {
+    /// The replica is opened and about to join its group, either created or
+    /// recovered by the bootstrap.
+    fn on_replica_created(&mut self, _group_id: u64, _replica_id: u64) {}
+
+    /// The replica becomes the leader of the group in `term`.
+    fn on_leader_acquired(&mut self, _group_id: u64, _term: u64) {}
+
+    /// The replica is no longer the leader of the group.
+    fn on_leader_lost(&mut self, _group_id: u64) {}
+
+    /// The replica is removed from this node.
+    fn on_replica_destroyed(&mut self, _group_id: u64, _replica_id: u64) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplicaEvent {
+    Created { group_id: u64, replica_id: u64 },
+    LeaderAcquired { group_id: u64, term: u64 },
+    LeaderLost { group_id: u64 },
+    Destroyed { group_id: u64, replica_id: u64 },
+}
+
+/// The observers registered to a node.
+#[derive(Clone, Default)]
+pub struct ReplicaObservers {
+    senders: Arc<Mutex<Vec<SyncSender<ReplicaEvent>>>>,
+    dropped: Arc<AtomicU64>,
+}
+
+/// An example observer, which logs the events.
+#[derive(Debug, Default)]
+pub struct LogReplicaObserver;
+
+impl ReplicaEvent {
+    fn dispatch(self, observer: &mut dyn ReplicaObserver) {
+        match self {
+            ReplicaEvent::Created { group_id, replica_id } => {
+                observer.on_replica_created(group_id, replica_id)
+            }
+            ReplicaEvent::LeaderAcquired { group_id, term } => {
+                observer.on_leader_acquired(group_id, term)
+            }
+            ReplicaEvent::LeaderLost { group_id } => observer.on_leader_lost(group_id),
+            ReplicaEvent::Destroyed { group_id, replica_id } => {
+                observer.on_replica_destroyed(group_id, replica_id)
+            }
+        }
+    }
+}
+
+impl ReplicaObservers {
+    /// Register an observer, it is notified of the events emitted after it.
+    pub fn register(&self, observer: Box<dyn ReplicaObserver>) {
+        self.register_with_capacity(observer, DEFAULT_QUEUE_CAPACITY);
+    }
+
+    /// Register an observer, at most `capacity` undelivered events are
+    /// buffered for it.
+    pub fn register_with_capacity(&self, mut observer: Box<dyn ReplicaObserver>, capacity: usize) {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        let spawn_result = std::thread::Builder::new()
+            .name("replica-observer".to_owned())
+            .spawn(move || deliver_events(observer.as_mut(), receiver));
+        if let Err(err) = spawn_result {
+            warn!("register replica observer: spawn thread: {err}");
+            return;
+        }
+        self.senders.lock().unwrap().push(sender);
+    }
+
+    /// The total number of events dropped since the queues of the observers
+    /// are full.
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Emit an event to all observers, it never blocks.
+    pub(crate) fn emit(&self, event: ReplicaEvent) {
+        let mut senders = self.senders.lock().unwrap();
+        senders.retain(|sender| match sender.try_send(event) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                debug!("replica observer is lagging, drop event {event:?}");
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                NODE_REPLICA_OBSERVER_DROPPED_EVENTS_TOTAL.inc();
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!("replica observer is exited, unregister it");
+                false
+            }
+        });
+    }
+}
+
+impl std::fmt::Debug for ReplicaObservers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplicaObservers")
+            .field("observers", &self.senders.lock().unwrap().len())
+            .field("dropped", &self.dropped_events())
+            .finish()
+    }
+}
+
+impl ReplicaObserver for LogReplicaObserver {
+    fn on_replica_created(&mut self, group_id: u64, replica_id: u64) {
+        info!("group {group_id} replica {replica_id} is created");
+    }
+
+    fn on_leader_acquired(&mut self, group_id: u64, term: u64) {
+        info!("group {group_id} leader is acquired at term {term}");
+    }
+
+    fn on_leader_lost(&mut self, group_id: u64) {
+        info!("group {group_id} leader is lost");
+    }
+
+    fn on_replica_destroyed(&mut self, group_id: u64, replica_id: u64) {
+        info!("group {group_id} replica {replica_id} is destroyed");
+    }
+}
+
+/// Deliver the events until all senders are dropped.
+fn deliver_events(observer: &mut dyn ReplicaObserver, receiver: Receiver<ReplicaEvent>) {
+    while let Ok(event) = receiver.recv() {
+        event.dispatch(observer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::Sender;
+    use std::time::Duration;
+
+    use super::*;
+
+    struct ChannelObserver {
+        sender: Sender<ReplicaEvent>,
+        /// Block the delivery until it is received.
+        gate: Option<Receiver<()>>,
+    }
+
+    impl ChannelObserver {
+        fn record(&mut self, event: ReplicaEvent) {
+            if let Some(gate) = self.gate.as_ref() {
+                gate.recv().unwrap_or_default();
+            }
+            self.sender.send(event).unwrap_or_default();
+        }
+    }
+
+    impl ReplicaObserver for ChannelObserver {
+        fn on_replica_created(&mut self, group_id: u64, replica_id: u64) {
+            self.record(ReplicaEvent::Created { group_id, replica_id });
+        }
+
+        fn on_leader_acquired(&mut self, group_id: u64, term: u64) {
+            self.record(ReplicaEvent::LeaderAcquired { group_id, term });
+        }
+
+        fn on_leader_lost(&mut self, group_id: u64) {
+            self.record(ReplicaEvent::LeaderLost { group_id });
+        }
+
+        fn on_replica_destroyed(&mut self, group_id: u64, replica_id: u64) {
+            self.record(ReplicaEvent::Destroyed { group_id, replica_id });
+        }
+    }
+
+    fn recv_events(receiver: &Receiver<ReplicaEvent>, n: usize) -> Vec<ReplicaEvent> {
+        (0..n).map(|_| receiver.recv_timeout(Duration::from_secs(5)).unwrap()).collect()
+    }
+
+    #[test]
+    fn events_are_delivered_in_order() {
+        let observers = ReplicaObservers::default();
+        let (sender, receiver) = mpsc::channel();
+        observers.register(Box::new(ChannelObserver { sender, gate: None }));
+        let events = vec![
+            ReplicaEvent::Created { group_id: 1, replica_id: 2 },
+            ReplicaEvent::LeaderAcquired { group_id: 1, term: 3 },
+            ReplicaEvent::LeaderLost { group_id: 1 },
+            ReplicaEvent::Destroyed { group_id: 1, replica_id: 2 },
+        ];
+        for event in &events {
+            observers.emit(*event);
+        }
+        assert_eq!(recv_events(&receiver, 4), events);
+        assert_eq!(observers.dropped_events(), 0);
+    }
+
+    #[test]
+    fn lagging_observer_drops_events() {
+        let observers = ReplicaObservers::default();
+        let (sender, receiver) = mpsc::channel();
+        let (gate_sender, gate) = mpsc::channel();
+        observers.register_with_capacity(Box::new(ChannelObserver { sender, gate: Some(gate) }), 2);
+        let (fast_sender, fast_receiver) = mpsc::channel();
+        observers.register(Box::new(ChannelObserver { sender: fast_sender, gate: None }));
+
+        // The first event is taken by the blocked observer, the next two are
+        // buffered and the others are dropped.
+        observers.emit(ReplicaEvent::Created { group_id: 1, replica_id: 1 });
+        std::thread::sleep(Duration::from_millis(100));
+        for group_id in 2..=5 {
+            observers.emit(ReplicaEvent::Created { group_id, replica_id: group_id });
+        }
+        assert_eq!(observers.dropped_events(), 2);
+        assert_eq!(recv_events(&fast_receiver, 5).len(), 5);
+
+        for _ in 0..3 {
+            gate_sender.send(()).unwrap();
+        }
+        let groups = recv_events(&receiver, 3)
+            .into_iter()
+            .map(|event| match event {
+                ReplicaEvent::Created { group_id, .. } => group_id,
+                others => panic!("unexpected event {others:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(groups, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The memory controls of the scans served by a node.
+//!
+//! A large scan is streamed in frames, each of them holds about `frame_bytes`
+//! of value sets. The bytes of the value sets are acquired from the
+//! [`ScanRegistry`] while the frame is built, and released once the frame is
+//! sent. The scan is rejected if the bytes held by it, or by all scans of the
+//! node, exceed the limits.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use super::metrics::*;
+use crate::{Error, Result, ScanConfig};
+
+const LIMIT_BYTES_PER_SCAN: &str = "max_bytes_per_scan";
+const LIMIT_BYTES_PER_NODE: &str = "max_bytes";
+
+/// The registry of the scans served by a node.
+#[derive(Clone)]
+pub struct ScanRegistry {
+    inner: Arc<RegistryInner>,
+}
+
+struct RegistryInner {
+    cfg: RwLock<ScanConfig>,
+    buffered_bytes: AtomicUsize,
+    peak_bytes: AtomicUsize,
+}
+
+/// The memory quota of an in-flight scan, the acquired bytes are released once
+/// all clones of it are dropped.
+#[derive(Clone)]
+pub struct ScanQuota {
+    shared: Arc<QuotaShared>,
+}
+
+struct QuotaShared {
+    registry: ScanRegistry,
+    bytes: AtomicUsize,
+}
+
+impl ScanRegistry {
+    pub fn new(cfg: ScanConfig) -> Self {
+        let inner = RegistryInner {
+            cfg: RwLock::new(cfg),
+            buffered_bytes: AtomicUsize::new(0),
+            peak_bytes: AtomicUsize::new(0),
+        };
+        ScanRegistry { inner: Arc::new(inner) }
+    }
+
+    /// Create the quota of a new scan.
+    pub fn quota(&self) -> ScanQuota {
+        let shared = QuotaShared { registry: self.clone(), bytes: AtomicUsize::new(0) };
+        ScanQuota { shared: Arc::new(shared) }
+    }
+
+    /// Apply the reloaded limits, the in-flight scans are checked against them
+    /// since the next acquisition.
+    pub fn update_config(&self, cfg: ScanConfig) {
+        *self.inner.cfg.write().unwrap() = cfg;
+    }
+
+    /// The max bytes of the value sets of each response frame.
+    #[inline]
+    pub fn frame_bytes(&self) -> usize {
+        self.inner.cfg().frame_bytes
+    }
+
+    /// The bytes of the value sets held by all in-flight scans.
+    pub fn buffered_bytes(&self) -> usize {
+        self.inner.buffered_bytes.load(Ordering::Acquire)
+    }
+
+    /// The peak of [`ScanRegistry::buffered_bytes`] since the node is started.
+    pub fn peak_buffered_bytes(&self) -> usize {
+        self.inner.peak_bytes.load(Ordering::Acquire)
+    }
+
+    /// Acquire the buffered bytes, returns false if the node limit is
+    /// exceeded.
+    fn acquire_bytes(&self, bytes: usize) -> bool {
+        let total = self.inner.buffered_bytes.fetch_add(bytes, Ordering::AcqRel) + bytes;
+        if total > self.inner.cfg().max_bytes {
+            self.inner.buffered_bytes.fetch_sub(bytes, Ordering::AcqRel);
+            return false;
+        }
+        let peak = self.inner.peak_bytes.fetch_max(total, Ordering::AcqRel).max(total);
+        NODE_SCAN_BUFFERED_BYTES.add(bytes as i64);
+        NODE_SCAN_PEAK_BUFFERED_BYTES.set(peak as i64);
+        true
+    }
+
+    fn release_bytes(&self, bytes: usize) {
+        self.inner.buffered_bytes.fetch_sub(bytes, Ordering::AcqRel);
+        NODE_SCAN_BUFFERED_BYTES.sub(bytes as i64);
+    }
+}
+
+impl RegistryInner {
+    #[inline]
+    fn cfg(&self) -> ScanConfig {
+        self.cfg.read().unwrap().clone()
+    }
+}
+
+impl ScanQuota {
+    /// Acquire the bytes of a value set to put into the frame.
+    ///
+    /// `Error::ResourceExhausted` with the name of the exceeded limit is
+    /// returned if the bytes held by the scan or the node exceed any limit.
+    pub fn acquire(&self, bytes: usize) -> Result<()> {
+        let registry = &self.shared.registry;
+        let cfg = registry.inner.cfg();
+        if self.held_bytes() + bytes > cfg.max_bytes_per_scan {
+            return Err(reject(LIMIT_BYTES_PER_SCAN, cfg.max_bytes_per_scan));
+        }
+        if !registry.acquire_bytes(bytes) {
+            return Err(reject(LIMIT_BYTES_PER_NODE, cfg.max_bytes));
+        }
+        self.shared.bytes.fetch_add(bytes, Ordering::AcqRel);
+        Ok(())
+    }
+
+    /// Release the bytes of the frames already sent.
+    pub fn release(&self) {
+        self.shared.release();
+    }
+
+    /// The bytes held by this scan.
+    pub fn held_bytes(&self) -> usize {
+        self.shared.bytes.load(Ordering::Acquire)
+    }
+}
+
+impl QuotaShared {
+    fn release(&self) {
+        let bytes = self.bytes.swap(0, Ordering::AcqRel);
+        self.registry.release_bytes(bytes);
+    }
+}
+
+impl Drop for QuotaShared {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+fn reject(limit: &'static str, value: usize) -> Error {
+    NODE_SCAN_REJECTED_TOTAL.with_label_values(&[limit]).inc();
+    Error::ResourceExhausted(format!("scan limit {limit} ({value})"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(max_bytes_per_scan: usize, max_bytes: usize) -> ScanRegistry {
+        ScanRegistry::new(ScanConfig { frame_bytes: 256, max_bytes_per_scan, max_bytes })
+    }
+
+    #[test]
+    fn acquire_bytes_beyond_scan_limit() {
+        let registry = registry(1024, 4096);
+        let quota = registry.quota();
+        quota.acquire(512).unwrap();
+        quota.acquire(512).unwrap();
+        let err = quota.acquire(1).err().unwrap();
+        assert!(
+            matches!(&err, Error::ResourceExhausted(msg) if msg.contains(LIMIT_BYTES_PER_SCAN)),
+            "{err:?}"
+        );
+        assert_eq!(registry.buffered_bytes(), 1024);
+
+        // The bytes of the sent frames are released.
+        quota.release();
+        assert_eq!(registry.buffered_bytes(), 0);
+        quota.acquire(1024).unwrap();
+        assert_eq!(registry.peak_buffered_bytes(), 1024);
+    }
+
+    #[test]
+    fn acquire_bytes_beyond_node_limit() {
+        let registry = registry(1024, 1536);
+        let quota_1 = registry.quota();
+        let quota_2 = registry.quota();
+        quota_1.acquire(1024).unwrap();
+        let err = quota_2.acquire(1024).err().unwrap();
+        assert!(
+            matches!(&err, Error::ResourceExhausted(msg) if msg.contains(LIMIT_BYTES_PER_NODE)),
+            "{err:?}"
+        );
+        assert_eq!(quota_2.held_bytes(), 0);
+        quota_2.acquire(512).unwrap();
+        assert_eq!(registry.buffered_bytes(), 1536);
+
+        // The bytes are released once the scan is finished or cancelled.
+        drop(quota_1);
+        assert_eq!(registry.buffered_bytes(), 512);
+        let cloned = quota_2.clone();
+        drop(quota_2);
+        assert_eq!(registry.buffered_bytes(), 512);
+        drop(cloned);
+        assert_eq!(registry.buffered_bytes(), 0);
+        assert_eq!(registry.peak_buffered_bytes(), 1536);
+    }
+
+    #[test]
+    fn reload_scan_limits() {
+        let registry = registry(1024, 4096);
+        let quota = registry.quota();
+        quota.acquire(1024).unwrap();
+        quota.acquire(1).unwrap_err();
+
+        // The in-flight scan observes the reloaded limits.
+        registry.update_config(ScanConfig {
+            frame_bytes: 512,
+            max_bytes_per_scan: 2048,
+            max_bytes: 4096,
+        });
+        assert_eq!(registry.frame_bytes(), 512);
+        quota.acquire(1024).unwrap();
+        let err = quota.acquire(1).err().unwrap();
+        assert!(matches!(&err, Error::ResourceExhausted(msg) if msg.contains("(2048)")), "{err:?}");
+    }
+}
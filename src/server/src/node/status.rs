@@ -0,0 +1,244 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The build and runtime status of a node, which is reported to root by the
+//! heartbeat and shown by the `SHOW nodes` statement. The health of the node
+//! is also consumed by the balancer of root.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::Mutex;
+
+use log::warn;
+use sekas_api::server::v1::{DirUsage, NodeHealth};
+use sekas_runtime::time::Instant;
+
+/// Samples the load of the node from procfs. The utilizations are computed
+/// from the counters since the last sampling, so the first sampling reports
+/// zero for them. All fields are zero if procfs is not available.
+#[derive(Default)]
+pub struct HealthSampler {
+    last: Mutex<Option<Counters>>,
+}
+
+struct Counters {
+    /// The busy and total cpu ticks.
+    cpu_ticks: (u64, u64),
+    /// The milliseconds spent doing I/O, by the disk name.
+    io_ticks: HashMap<String, u64>,
+    sampled_at: Instant,
+}
+
+/// The usage of the file system the directory located in. The space is unknown
+/// if the file system couldn't be queried.
+pub fn dir_usage(kind: &str, path: &Path) -> DirUsage {
+    let mut usage =
+        DirUsage { kind: kind.to_owned(), path: path.display().to_string(), ..Default::default() };
+    match statvfs(path) {
+        Ok((free_bytes, total_bytes)) => {
+            usage.free_bytes = free_bytes;
+            usage.total_bytes = total_bytes;
+        }
+        Err(err) => warn!("query the space of {kind} dir {}: {err}", path.display()),
+    }
+    usage
+}
+
+impl HealthSampler {
+    /// Sample the health, the `open_proposals` is counted by the caller.
+    pub fn sample(&self, open_proposals: u64) -> NodeHealth {
+        let mut health = NodeHealth { open_proposals, ..Default::default() };
+        if let Some(load1) = read_proc("loadavg").as_deref().and_then(parse_load1) {
+            health.load1 = load1;
+        }
+        if let Some((used, total)) = read_proc("meminfo").as_deref().and_then(parse_mem_usage) {
+            health.mem_used = used;
+            health.mem_total = total;
+        }
+
+        let current = Counters {
+            cpu_ticks: read_proc("stat").as_deref().and_then(parse_cpu_ticks).unwrap_or_default(),
+            io_ticks: read_proc("diskstats").as_deref().map(parse_io_ticks).unwrap_or_default(),
+            sampled_at: Instant::now(),
+        };
+        let mut last = self.last.lock().unwrap();
+        if let Some(last) = last.as_ref() {
+            health.cpu_util = cpu_util(last.cpu_ticks, current.cpu_ticks);
+            health.io_util = io_util(last, &current);
+        }
+        *last = Some(current);
+        health
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_proc(name: &str) -> Option<String> {
+    std::fs::read_to_string(Path::new("/proc").join(name)).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_proc(_name: &str) -> Option<String> {
+    None
+}
+
+/// Parses the first field of `/proc/loadavg`.
+fn parse_load1(content: &str) -> Option<f64> {
+    content.split_whitespace().next()?.parse().ok()
+}
+
+/// Parses the used and total bytes of memory from `/proc/meminfo`, the memory
+/// could be reclaimed is not counted as used.
+fn parse_mem_usage(content: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| -> Option<u64> {
+        let line = content.lines().find(|l| l.starts_with(name))?;
+        let kb = line[name.len()..].trim_start_matches(':').split_whitespace().next()?;
+        kb.parse::<u64>().ok().map(|kb| kb * 1024)
+    };
+    let total = field("MemTotal")?;
+    let available = field("MemAvailable").or_else(|| field("MemFree"))?;
+    Some((total.saturating_sub(available), total))
+}
+
+/// Parses the busy and total ticks of all cpus from `/proc/stat`, the idle and
+/// iowait ticks are not busy.
+fn parse_cpu_ticks(content: &str) -> Option<(u64, u64)> {
+    let line = content.lines().find(|l| l.starts_with("cpu "))?;
+    let ticks = line
+        .split_whitespace()
+        .skip(1)
+        .map(|v| v.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if ticks.len() < 4 {
+        return None;
+    }
+    // The guest ticks are counted in the user ticks already.
+    let total = ticks.iter().take(8).sum::<u64>();
+    let idle = ticks[3] + ticks.get(4).cloned().unwrap_or_default();
+    Some((total - idle, total))
+}
+
+/// Parses the milliseconds spent doing I/O of the disks from `/proc/diskstats`,
+/// the virtual devices are skipped.
+fn parse_io_ticks(content: &str) -> HashMap<String, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let name = *fields.get(2)?;
+            if name.starts_with("loop") || name.starts_with("ram") {
+                return None;
+            }
+            let io_ticks = fields.get(12)?.parse::<u64>().ok()?;
+            Some((name.to_owned(), io_ticks))
+        })
+        .collect()
+}
+
+fn cpu_util((last_busy, last_total): (u64, u64), (busy, total): (u64, u64)) -> f64 {
+    if total <= last_total {
+        return 0.0;
+    }
+    let util = busy.saturating_sub(last_busy) as f64 / (total - last_total) as f64;
+    util.clamp(0.0, 1.0)
+}
+
+/// The utilization of the busiest disk.
+fn io_util(last: &Counters, current: &Counters) -> f64 {
+    let elapsed_ms = current.sampled_at.duration_since(last.sampled_at).as_millis() as f64;
+    if elapsed_ms <= 0.0 {
+        return 0.0;
+    }
+    current
+        .io_ticks
+        .iter()
+        .filter_map(|(name, ticks)| Some(ticks.saturating_sub(*last.io_ticks.get(name)?)))
+        .map(|busy_ms| (busy_ms as f64 / elapsed_ms).clamp(0.0, 1.0))
+        .fold(0.0, f64::max)
+}
+
+/// Returns the available and total bytes of the file system.
+fn statvfs(path: &Path) -> std::io::Result<(u64, u64)> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: the path is a valid C string and the stat is written by the call.
+    let ret = unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: the stat is initialized since the call succeeds.
+    let stat = unsafe { stat.assume_init() };
+    let block_size = stat.f_frsize as u64;
+    Ok((stat.f_bavail as u64 * block_size, stat.f_blocks as u64 * block_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_of_dir() {
+        let dir = std::env::temp_dir();
+        let usage = dir_usage("data", &dir);
+        assert_eq!(usage.kind, "data");
+        assert!(usage.total_bytes > 0);
+        assert!(usage.free_bytes <= usage.total_bytes);
+
+        let usage = dir_usage("wal", &dir.join("not-exists-dir"));
+        assert_eq!(usage.total_bytes, 0);
+    }
+
+    #[test]
+    fn parse_procfs() {
+        assert_eq!(parse_load1("0.52 0.58 0.59 2/1234 5678\n"), Some(0.52));
+        assert_eq!(parse_load1(""), None);
+
+        let meminfo =
+            "MemTotal:       16384 kB\nMemFree:         1024 kB\nMemAvailable:    4096 kB\n";
+        assert_eq!(parse_mem_usage(meminfo), Some((12288 * 1024, 16384 * 1024)));
+        assert_eq!(
+            parse_mem_usage("MemTotal: 16 kB\nMemFree: 4 kB\n"),
+            Some((12 * 1024, 16 * 1024))
+        );
+        assert_eq!(parse_mem_usage("MemFree: 4 kB\n"), None);
+
+        let stat = "cpu  100 0 50 800 50 0 0 0 0 0\ncpu0 100 0 50 800 50 0 0 0 0 0\n";
+        assert_eq!(parse_cpu_ticks(stat), Some((150, 1000)));
+        assert_eq!(parse_cpu_ticks("intr 1 2 3\n"), None);
+        assert_eq!(cpu_util((150, 1000), (250, 1200)), 0.5);
+        assert_eq!(cpu_util((150, 1000), (150, 1000)), 0.0);
+
+        let diskstats = "   7       0 loop0 1 0 2 0 0 0 0 0 0 40 0\n   8       0 sda 10 0 20 5 10 0 20 5 0 300 10\n";
+        let io_ticks = parse_io_ticks(diskstats);
+        assert_eq!(io_ticks.len(), 1);
+        assert_eq!(io_ticks.get("sda"), Some(&300));
+    }
+
+    #[test]
+    fn sample_health() {
+        let sampler = HealthSampler::default();
+        let health = sampler.sample(3);
+        assert_eq!(health.open_proposals, 3);
+        assert_eq!(health.cpu_util, 0.0);
+
+        let health = sampler.sample(0);
+        assert!((0.0..=1.0).contains(&health.cpu_util));
+        assert!((0.0..=1.0).contains(&health.io_util));
+        assert!(health.mem_used <= health.mem_total);
+        if cfg!(target_os = "linux") {
+            assert!(health.mem_total > 0);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The final descriptors of the groups whose replicas are removed from this
+//! node. They are used to tell the clients with staled routes where the groups
+//! went, see `Node::group_not_found`.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sekas_api::server::v1::{GroupDesc, GroupRelocatedHint};
+use sekas_runtime::time::Instant;
+
+/// The max number of tombstones retained.
+const MAX_GROUP_TOMBSTONES: usize = 1024;
+
+/// The tombstones older than this are dropped, the clients should have
+/// refreshed their routes from root by then.
+const GROUP_TOMBSTONE_TTL: Duration = Duration::from_secs(600);
+
+#[derive(Clone, Default)]
+pub struct GroupTombstones {
+    inner: Arc<Mutex<TombstonesInner>>,
+}
+
+#[derive(Default)]
+struct TombstonesInner {
+    hints: HashMap<u64, (Instant, GroupRelocatedHint)>,
+    /// The group ids in the order of insertion, it might contain the groups
+    /// already replaced or dropped.
+    order: VecDeque<(Instant, u64)>,
+}
+
+impl GroupTombstones {
+    /// Record the final descriptor of a group whose replica is removed.
+    pub fn insert(&self, desc: &GroupDesc) {
+        self.insert_at(desc, Instant::now());
+    }
+
+    /// The hint of the group if it is removed recently.
+    pub fn get(&self, group_id: u64) -> Option<GroupRelocatedHint> {
+        self.get_at(group_id, Instant::now())
+    }
+
+    /// Drop the tombstone of the group, because a replica of it is served by
+    /// this node again.
+    pub fn remove(&self, group_id: u64) {
+        self.inner.lock().unwrap().hints.remove(&group_id);
+    }
+
+    fn insert_at(&self, desc: &GroupDesc, now: Instant) {
+        if desc.replicas.is_empty() {
+            return;
+        }
+        let hint = GroupRelocatedHint { replicas: desc.replicas.clone(), epoch: desc.epoch };
+        let mut inner = self.inner.lock().unwrap();
+        inner.expire(now);
+        inner.hints.insert(desc.id, (now, hint));
+        inner.order.push_back((now, desc.id));
+        while inner.hints.len() > MAX_GROUP_TOMBSTONES
+            || inner.order.len() > 2 * MAX_GROUP_TOMBSTONES
+        {
+            let Some((inserted_at, group_id)) = inner.order.pop_front() else { break };
+            inner.remove_if_inserted_at(group_id, inserted_at);
+        }
+    }
+
+    fn get_at(&self, group_id: u64, now: Instant) -> Option<GroupRelocatedHint> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.expire(now);
+        inner.hints.get(&group_id).map(|(_, hint)| hint.clone())
+    }
+}
+
+impl TombstonesInner {
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(inserted_at, group_id)) = self.order.front() {
+            if now.saturating_duration_since(inserted_at) < GROUP_TOMBSTONE_TTL {
+                break;
+            }
+            self.order.pop_front();
+            self.remove_if_inserted_at(group_id, inserted_at);
+        }
+    }
+
+    fn remove_if_inserted_at(&mut self, group_id: u64, inserted_at: Instant) {
+        if self.hints.get(&group_id).is_some_and(|(at, _)| *at == inserted_at) {
+            self.hints.remove(&group_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sekas_api::server::v1::ReplicaDesc;
+
+    use super::*;
+
+    fn group_desc(id: u64, epoch: u64) -> GroupDesc {
+        let replicas = vec![ReplicaDesc { id: id * 10, node_id: 2, ..Default::default() }];
+        GroupDesc { id, epoch, replicas, ..Default::default() }
+    }
+
+    #[test]
+    fn group_tombstones_aged_out() {
+        let tombstones = GroupTombstones::default();
+        let start = Instant::now();
+        tombstones.insert_at(&group_desc(1, 3), start);
+        tombstones.insert_at(&group_desc(2, 3), start + Duration::from_secs(300));
+        let hint = tombstones.get_at(1, start).unwrap();
+        assert_eq!(hint.epoch, 3);
+        assert_eq!(hint.replicas[0].node_id, 2);
+
+        // The group without any replicas is not recorded.
+        tombstones.insert_at(&GroupDesc { id: 3, ..Default::default() }, start);
+        assert!(tombstones.get_at(3, start).is_none());
+
+        let now = start + GROUP_TOMBSTONE_TTL;
+        assert!(tombstones.get_at(1, now).is_none());
+        assert!(tombstones.get_at(2, now).is_some());
+
+        tombstones.remove(2);
+        assert!(tombstones.get_at(2, now).is_none());
+    }
+
+    #[test]
+    fn group_tombstones_bounded() {
+        let tombstones = GroupTombstones::default();
+        let now = Instant::now();
+        tombstones.insert_at(&group_desc(0, 1), now);
+        // The tombstone is replaced by the newer one.
+        tombstones.insert_at(&group_desc(0, 2), now + Duration::from_secs(1));
+        for id in 1..MAX_GROUP_TOMBSTONES as u64 {
+            tombstones.insert_at(&group_desc(id, 1), now + Duration::from_secs(1));
+        }
+        assert_eq!(tombstones.get_at(0, now).unwrap().epoch, 2);
+
+        tombstones.insert_at(&group_desc(4096, 1), now + Duration::from_secs(2));
+        assert!(tombstones.get_at(0, now).is_none());
+        assert!(tombstones.get_at(1, now).is_some());
+        assert!(tombstones.get_at(4096, now).is_some());
+        assert_eq!(tombstones.inner.lock().unwrap().hints.len(), MAX_GROUP_TOMBSTONES);
+    }
+}
@@ -0,0 +1,403 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The resource controls of the key watches served by a node.
+//!
+//! Each watch must be registered in [`WatchRegistry`] before it is issued to
+//! the state machine, the registration is rejected once the number of watches
+//! of the connection or the node exceeds the limits.
+//!
+//! The events fired by the state machine are buffered in the channel until
+//! they are delivered to the client. A watch is cancelled as lagging if the
+//! buffered bytes of it, or of all watches of the node, exceed the limits. The
+//! client could re-establish a lagging watch from the last delivered version,
+//! since the values are read from the MVCC history in that case.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll, Waker};
+
+use super::metrics::*;
+use crate::replica::fsm::WatchEvent;
+use crate::{Error, Result, WatchConfig};
+
+const LIMIT_WATCHES_PER_CONNECTION: &str = "max_watches_per_connection";
+const LIMIT_WATCHES_PER_NODE: &str = "max_watches_per_node";
+
+/// The registry of the watches served by a node.
+#[derive(Clone)]
+pub struct WatchRegistry {
+    inner: Arc<RegistryInner>,
+}
+
+struct RegistryInner {
+    cfg: RwLock<WatchConfig>,
+    num_watches: Mutex<NumWatches>,
+    buffered_bytes: AtomicUsize,
+}
+
+#[derive(Default)]
+struct NumWatches {
+    total: usize,
+    connections: HashMap<SocketAddr, usize>,
+}
+
+/// The permit of a registered watch, the registration is released once it is
+/// dropped.
+pub struct WatchPermit {
+    registry: WatchRegistry,
+    conn: Option<SocketAddr>,
+}
+
+/// The sender side of the watch events, used by the state machine.
+///
+/// The channel is closed once all senders are dropped.
+pub struct WatchEventSender {
+    shared: Arc<Shared>,
+}
+
+/// The receiver side of the watch events.
+pub struct WatchEventReceiver {
+    shared: Arc<Shared>,
+    _permit: WatchPermit,
+}
+
+struct Shared {
+    registry: WatchRegistry,
+    buffer: Mutex<WatchBuffer>,
+}
+
+#[derive(Default)]
+struct WatchBuffer {
+    events: VecDeque<(WatchEvent, usize)>,
+    bytes: usize,
+    num_senders: usize,
+    receiver_dropped: bool,
+    lagging: bool,
+    waker: Option<Waker>,
+}
+
+impl WatchRegistry {
+    pub fn new(cfg: WatchConfig) -> Self {
+        let inner = RegistryInner {
+            cfg: RwLock::new(cfg),
+            num_watches: Mutex::default(),
+            buffered_bytes: AtomicUsize::new(0),
+        };
+        WatchRegistry { inner: Arc::new(inner) }
+    }
+
+    /// Register a watch issued from the connection `conn`.
+    ///
+    /// `Error::ResourceExhausted` with the name of the exceeded limit is
+    /// returned if the number of watches reaches any limit.
+    pub fn register(&self, conn: Option<SocketAddr>) -> Result<WatchPermit> {
+        let cfg = self.inner.cfg();
+        let mut num_watches = self.inner.num_watches.lock().unwrap();
+        if num_watches.total >= cfg.max_watches_per_node {
+            return Err(reject(LIMIT_WATCHES_PER_NODE, cfg.max_watches_per_node));
+        }
+        if let Some(addr) = conn {
+            let num_conn_watches = num_watches.connections.entry(addr).or_default();
+            if *num_conn_watches >= cfg.max_watches_per_connection {
+                return Err(reject(LIMIT_WATCHES_PER_CONNECTION, cfg.max_watches_per_connection));
+            }
+            *num_conn_watches += 1;
+        }
+        num_watches.total += 1;
+        NODE_ACTIVE_WATCHES.inc();
+        Ok(WatchPermit { registry: self.clone(), conn })
+    }
+
+    /// Create the channel to deliver the events of the registered watch.
+    pub fn channel(&self, permit: WatchPermit) -> (WatchEventSender, WatchEventReceiver) {
+        let buffer = WatchBuffer { num_senders: 1, ..Default::default() };
+        let shared = Arc::new(Shared { registry: self.clone(), buffer: Mutex::new(buffer) });
+        let sender = WatchEventSender { shared: shared.clone() };
+        (sender, WatchEventReceiver { shared, _permit: permit })
+    }
+
+    /// Apply the reloaded limits, the registered watches are kept even if they
+    /// exceed the new limits.
+    pub fn update_config(&self, cfg: WatchConfig) {
+        *self.inner.cfg.write().unwrap() = cfg;
+    }
+
+    /// The number of active watches.
+    pub fn num_watches(&self) -> usize {
+        self.inner.num_watches.lock().unwrap().total
+    }
+
+    /// The bytes of the undelivered events of all watches.
+    pub fn buffered_bytes(&self) -> usize {
+        self.inner.buffered_bytes.load(Ordering::Acquire)
+    }
+
+    fn unregister(&self, conn: Option<SocketAddr>) {
+        let mut num_watches = self.inner.num_watches.lock().unwrap();
+        if let Some(addr) = conn {
+            if let Some(num_conn_watches) = num_watches.connections.get_mut(&addr) {
+                *num_conn_watches -= 1;
+                if *num_conn_watches == 0 {
+                    num_watches.connections.remove(&addr);
+                }
+            }
+        }
+        num_watches.total -= 1;
+        NODE_ACTIVE_WATCHES.dec();
+    }
+
+    /// Acquire the buffered bytes, returns false if the node limit is
+    /// exceeded.
+    fn acquire_bytes(&self, bytes: usize) -> bool {
+        let total = self.inner.buffered_bytes.fetch_add(bytes, Ordering::AcqRel) + bytes;
+        if total > self.inner.cfg().max_buffered_bytes {
+            self.inner.buffered_bytes.fetch_sub(bytes, Ordering::AcqRel);
+            return false;
+        }
+        NODE_WATCH_BUFFERED_BYTES.add(bytes as i64);
+        true
+    }
+
+    fn release_bytes(&self, bytes: usize) {
+        self.inner.buffered_bytes.fetch_sub(bytes, Ordering::AcqRel);
+        NODE_WATCH_BUFFERED_BYTES.sub(bytes as i64);
+    }
+}
+
+impl RegistryInner {
+    #[inline]
+    fn cfg(&self) -> WatchConfig {
+        self.cfg.read().unwrap().clone()
+    }
+}
+
+impl Drop for WatchPermit {
+    fn drop(&mut self) {
+        self.registry.unregister(self.conn);
+    }
+}
+
+impl WatchEventSender {
+    /// Buffer the event until it is delivered.
+    ///
+    /// Returns false if the watch is closed or cancelled as lagging, the sender
+    /// should be released in this case.
+    pub fn send(&self, event: WatchEvent) -> bool {
+        let registry = &self.shared.registry;
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        if buffer.receiver_dropped || buffer.lagging {
+            return false;
+        }
+
+        let size = event_size(&event);
+        if buffer.bytes + size > registry.inner.cfg().max_buffered_bytes_per_watch
+            || !registry.acquire_bytes(size)
+        {
+            NODE_WATCH_LAGGING_TOTAL.inc();
+            buffer.lagging = true;
+            buffer.events.clear();
+            registry.release_bytes(std::mem::take(&mut buffer.bytes));
+            buffer.wake();
+            return false;
+        }
+
+        buffer.events.push_back((event, size));
+        buffer.bytes += size;
+        buffer.wake();
+        true
+    }
+}
+
+impl Clone for WatchEventSender {
+    fn clone(&self) -> Self {
+        self.shared.buffer.lock().unwrap().num_senders += 1;
+        WatchEventSender { shared: self.shared.clone() }
+    }
+}
+
+impl Drop for WatchEventSender {
+    fn drop(&mut self) {
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        buffer.num_senders -= 1;
+        if buffer.num_senders == 0 {
+            buffer.wake();
+        }
+    }
+}
+
+impl WatchEventReceiver {
+    /// Whether the watch is cancelled since it is lagging.
+    pub fn is_lagging(&self) -> bool {
+        self.shared.buffer.lock().unwrap().lagging
+    }
+}
+
+impl futures::Stream for WatchEventReceiver {
+    type Item = WatchEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        if let Some((event, size)) = buffer.events.pop_front() {
+            buffer.bytes -= size;
+            self.shared.registry.release_bytes(size);
+            return Poll::Ready(Some(event));
+        }
+        if buffer.lagging || buffer.num_senders == 0 {
+            return Poll::Ready(None);
+        }
+        buffer.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for WatchEventReceiver {
+    fn drop(&mut self) {
+        let mut buffer = self.shared.buffer.lock().unwrap();
+        buffer.receiver_dropped = true;
+        buffer.events.clear();
+        self.shared.registry.release_bytes(std::mem::take(&mut buffer.bytes));
+    }
+}
+
+impl WatchBuffer {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn reject(limit: &'static str, value: usize) -> Error {
+    NODE_WATCH_REJECTED_TOTAL.with_label_values(&[limit]).inc();
+    Error::ResourceExhausted(format!("watch limit {limit} ({value})"))
+}
+
+/// The estimated memory usage of a buffered event.
+fn event_size(event: &WatchEvent) -> usize {
+    std::mem::size_of::<WatchEvent>()
+        + event.key.len()
+        + event.value.as_ref().map_or(0, |v| v.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    fn event(version: u64, value_len: usize) -> WatchEvent {
+        WatchEvent {
+            version,
+            key: Box::from(b"key".as_slice()),
+            value: Some(vec![0; value_len].into()),
+        }
+    }
+
+    fn registry(max_buffered_bytes_per_watch: usize, max_buffered_bytes: usize) -> WatchRegistry {
+        WatchRegistry::new(WatchConfig {
+            max_watches_per_connection: 2,
+            max_watches_per_node: 3,
+            max_buffered_bytes_per_watch,
+            max_buffered_bytes,
+        })
+    }
+
+    #[test]
+    fn register_watches_beyond_limits() {
+        let registry = registry(1024, 4096);
+        let conn_1: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let conn_2: SocketAddr = "127.0.0.1:2".parse().unwrap();
+
+        let permit_1 = registry.register(Some(conn_1)).unwrap();
+        let _permit_2 = registry.register(Some(conn_1)).unwrap();
+        let err = registry.register(Some(conn_1)).err().unwrap();
+        assert!(
+            matches!(&err, Error::ResourceExhausted(msg) if msg.contains(LIMIT_WATCHES_PER_CONNECTION)),
+            "{err:?}"
+        );
+
+        let _permit_3 = registry.register(Some(conn_2)).unwrap();
+        let err = registry.register(Some(conn_2)).err().unwrap();
+        assert!(
+            matches!(&err, Error::ResourceExhausted(msg) if msg.contains(LIMIT_WATCHES_PER_NODE)),
+            "{err:?}"
+        );
+        assert_eq!(registry.num_watches(), 3);
+
+        // The registration is released once the permit is dropped.
+        drop(permit_1);
+        assert_eq!(registry.num_watches(), 2);
+        registry.register(Some(conn_1)).unwrap();
+    }
+
+    #[sekas_macro::test]
+    async fn slow_consumer_is_cancelled_as_lagging() {
+        let registry = registry(1024, 4096);
+        let (sender, mut receiver) = registry.channel(registry.register(None).unwrap());
+
+        // The buffered events are delivered in order.
+        assert!(sender.send(event(1, 256)));
+        assert!(sender.send(event(2, 256)));
+        assert!(registry.buffered_bytes() > 512);
+        assert_eq!(receiver.next().await.unwrap().version, 1);
+        assert_eq!(receiver.next().await.unwrap().version, 2);
+        assert_eq!(registry.buffered_bytes(), 0);
+
+        // Nobody consumes the events until the watch is lagging.
+        let mut version = 3;
+        while sender.send(event(version, 256)) {
+            version += 1;
+        }
+        assert_eq!(version, 6, "only 3 events could be buffered in 1KB");
+        assert!(receiver.is_lagging());
+        assert_eq!(registry.buffered_bytes(), 0);
+        assert!(receiver.next().await.is_none());
+        assert!(!sender.send(event(version, 0)));
+    }
+
+    #[sekas_macro::test]
+    async fn node_buffered_bytes_limit() {
+        let registry = registry(1024, 1024);
+        let (sender_1, mut receiver_1) = registry.channel(registry.register(None).unwrap());
+        let (sender_2, mut receiver_2) = registry.channel(registry.register(None).unwrap());
+
+        assert!(sender_1.send(event(1, 512)));
+        assert!(!sender_2.send(event(1, 512)));
+        assert!(receiver_2.is_lagging());
+        assert!(receiver_2.next().await.is_none());
+
+        // The first watch is not affected.
+        assert!(!receiver_1.is_lagging());
+        assert_eq!(receiver_1.next().await.unwrap().version, 1);
+        drop(sender_1);
+        assert!(receiver_1.next().await.is_none());
+        assert!(!receiver_1.is_lagging());
+    }
+
+    #[test]
+    fn release_buffered_bytes_once_receiver_is_dropped() {
+        let registry = registry(1024, 4096);
+        let (sender, receiver) = registry.channel(registry.register(None).unwrap());
+        assert!(sender.send(event(1, 128)));
+        assert!(registry.buffered_bytes() > 0);
+        drop(receiver);
+        assert_eq!(registry.buffered_bytes(), 0);
+        assert_eq!(registry.num_watches(), 0);
+        assert!(!sender.send(event(2, 128)));
+    }
+}
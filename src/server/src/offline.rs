@@ -0,0 +1,606 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Inspect the data directory of a stopped node.
+//!
+//! The local db is opened in read only mode, and the raft engine is opened from
+//! a copy of its files, so the inspection neither mutates the directory nor
+//! holds any lock which prevents the node from starting later.
+//!
+//! The raft logs of a group could be replayed by [`replay_group`], to find the
+//! entry where the state machines of two replicas diverge: the digests of the
+//! entries replayed from the directories of both replicas are compared by
+//! [`first_divergence`].
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use log::{info, warn};
+use prost::Message;
+use raft::prelude::{ConfChangeV2, Entry, EntryType};
+use raft_engine::RecoveryMode;
+use sekas_api::server::v1::{
+    ApplyQuarantine, GroupDesc, MoveShardState, ReplicaDesc, ShardDesc, ValueSet,
+};
+use sekas_rock::time::timestamp_nanos;
+use sekas_schema::system::txn::TXN_INTENT_VERSION;
+
+use crate::engine::{
+    open_raw_db, open_raw_db_for_read_only, GroupEngine, RawDb, SnapshotMode, StateEngine,
+    LAYOUT_DATA, LAYOUT_LOG, LAYOUT_SNAP,
+};
+use crate::raftgroup::snap::{list_numeric_path, SNAP_DATA, SNAP_META};
+use crate::raftgroup::{
+    decode_from_conf_change, read_raft_state, ApplyEntry, MessageExtTyped, StateMachine,
+};
+use crate::replica::fsm::{GroupStateMachine, StateMachineObserver, WatchHub};
+use crate::replica::ReplicaInfo;
+use crate::serverpb::v1::{EvalResult, ReplicaLocalState, SnapshotMeta};
+use crate::{DbConfig, EngineConfig, Error, ReplicaConfig, Result};
+
+/// The magic number at the beginning of a shard dump.
+const DUMP_MAGIC: &[u8] = b"SEKASDMP";
+
+/// The report of a data directory.
+#[derive(Clone, Debug, Default)]
+pub struct NodeInspection {
+    /// `None` if the node is not bootstrapped.
+    pub node_id: Option<u64>,
+    pub cluster_id: Option<Vec<u8>>,
+    pub replicas: Vec<ReplicaInspection>,
+    /// The corruptions not belong to any replica.
+    pub corruptions: Vec<Corruption>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplicaInspection {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub state: ReplicaLocalState,
+    pub applied_index: u64,
+    pub term: u64,
+    /// The index range of the raft logs, `None` if there is no any log.
+    pub log_range: Option<(u64, u64)>,
+    pub snapshots: Vec<SnapshotInspection>,
+    pub approximate_size: u64,
+    pub corruptions: Vec<Corruption>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SnapshotInspection {
+    /// The index of the snapshot dir, not the raft index.
+    pub index: u64,
+    pub dir: PathBuf,
+    /// The applied index of the snapshot, `None` if the meta is corrupted.
+    pub applied_index: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// The raft engine fails to recover, it could only be opened by dropping
+    /// the corrupted logs.
+    RaftLog {
+        reason: String,
+    },
+    /// The hard state or local state of the raft is not found.
+    MissingRaftState,
+    /// The column family of the replica is not found.
+    MissingGroupEngine,
+    /// Some logs are missing, the first index doesn't follow the truncated
+    /// index.
+    TruncatedLog {
+        truncated_index: u64,
+        first_index: u64,
+    },
+    /// The logs not applied yet have been truncated.
+    UnappliedLogTruncated {
+        applied_index: u64,
+        truncated_index: u64,
+    },
+    MissingSnapMeta {
+        dir: PathBuf,
+    },
+    InvalidSnapMeta {
+        dir: PathBuf,
+        reason: String,
+    },
+}
+
+/// The digest of the effects of a replayed entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntryDigest {
+    pub index: u64,
+    pub term: u64,
+    /// The crc32 of the write batch and the changed states of the entry.
+    pub digest: u32,
+    /// The bytes of the write batch of the entry.
+    pub write_bytes: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ReplayOptions {
+    /// The snapshot dir whose data is the state before the replayed entries,
+    /// it contains the `META` and `DATA` of the snapshot. The latest snapshot
+    /// of the replica is used if it is not specified and the logs since the
+    /// first index have been truncated.
+    pub base: Option<PathBuf>,
+    /// The config of the state machine, the testing knobs are applied to the
+    /// replay as well.
+    pub replica: ReplicaConfig,
+}
+
+impl NodeInspection {
+    /// Whether any corruption is detected.
+    pub fn is_corrupted(&self) -> bool {
+        !self.corruptions.is_empty() || self.replicas.iter().any(|r| !r.corruptions.is_empty())
+    }
+}
+
+/// Inspect the data directory of a stopped node.
+pub async fn inspect(dir: &Path) -> Result<NodeInspection> {
+    let mut inspection = NodeInspection::default();
+    let log_engine = LogEngineCopy::open(dir, &mut inspection.corruptions)?;
+    let raw_db = Arc::new(open_raw_db_for_read_only(&DbConfig::default(), dir.join(LAYOUT_DATA))?);
+    let state_engine = StateEngine::new(log_engine.engine.clone());
+    if let Some(ident) = state_engine.read_ident().await? {
+        inspection.node_id = Some(ident.node_id);
+        inspection.cluster_id = Some(ident.cluster_id);
+    }
+
+    let snap_dir = dir.join(LAYOUT_LOG).join(LAYOUT_SNAP);
+    for (group_id, replica_id, state) in state_engine.replica_states().await? {
+        let mut replica = ReplicaInspection {
+            group_id,
+            replica_id,
+            state,
+            applied_index: 0,
+            term: 0,
+            log_range: None,
+            snapshots: vec![],
+            approximate_size: 0,
+            corruptions: vec![],
+        };
+        if matches!(state, ReplicaLocalState::Tombstone | ReplicaLocalState::Terminated) {
+            // The data of these replicas is being or has been destroyed.
+            inspection.replicas.push(replica);
+            continue;
+        }
+
+        inspect_snapshots(&snap_dir.join(replica_id.to_string()), &mut replica)?;
+        match GroupEngine::open(&EngineConfig::default(), raw_db.clone(), group_id, replica_id)
+            .await?
+        {
+            Some(group_engine) => {
+                replica.applied_index = group_engine.raw_iter()?.apply_state().index;
+                for shard in group_engine.descriptor().shards {
+                    replica.approximate_size += group_engine.get_approximate_size(shard.id)?;
+                }
+            }
+            // The group engine of an initial replica is created when it is served.
+            None if state == ReplicaLocalState::Initial => {}
+            None => replica.corruptions.push(Corruption::MissingGroupEngine),
+        }
+        inspect_raft_logs(&log_engine.engine, &mut replica)?;
+        inspection.replicas.push(replica);
+    }
+    Ok(inspection)
+}
+
+/// Dump the committed versions of a shard's user data to the file `out`,
+/// return the number of keys dumped. The intents are skipped, since resolving
+/// them requires the txn records.
+///
+/// The dump starts with [`DUMP_MAGIC`] and the length-delimited
+/// [`ShardDesc`], followed by a length-delimited [`ValueSet`] per key.
+pub async fn dump_shard(dir: &Path, shard_id: u64, out: &Path) -> Result<usize> {
+    let log_engine = LogEngineCopy::open(dir, &mut vec![])?;
+    let raw_db = Arc::new(open_raw_db_for_read_only(&DbConfig::default(), dir.join(LAYOUT_DATA))?);
+    let state_engine = StateEngine::new(log_engine.engine.clone());
+    let group_engine = find_shard_group_engine(&state_engine, raw_db, shard_id).await?;
+    let shard_desc = group_engine.shard_desc(shard_id)?;
+
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(out)?);
+    writer.write_all(DUMP_MAGIC)?;
+    writer.write_all(&shard_desc.encode_length_delimited_to_vec())?;
+    let mut num_keys = 0;
+    let mut snapshot = group_engine.snapshot(shard_id, SnapshotMode::Start { start_key: None })?;
+    while let Some(mvcc_iter) = snapshot.next() {
+        let mut mvcc_iter = mvcc_iter?;
+        let mut value_set = ValueSet { user_key: mvcc_iter.user_key().to_owned(), values: vec![] };
+        for entry in &mut mvcc_iter {
+            let entry = entry?;
+            if entry.version() != TXN_INTENT_VERSION {
+                value_set.values.push(entry.into());
+            }
+        }
+        if !value_set.values.is_empty() {
+            writer.write_all(&value_set.encode_length_delimited_to_vec())?;
+            num_keys += 1;
+        }
+    }
+    writer.flush()?;
+    info!("dump {num_keys} keys of shard {shard_id} to {}", out.display());
+    Ok(num_keys)
+}
+
+/// Replay the raft logs of the group in the directory of a stopped node, and
+/// returns the digests of the entries in `[from_index, to_index]`.
+///
+/// See [`replay_group_with`] for the details.
+pub async fn replay_group(
+    dir: &Path,
+    group_id: u64,
+    from_index: u64,
+    to_index: u64,
+) -> Result<Vec<EntryDigest>> {
+    replay_group_with(dir, group_id, from_index, to_index, ReplayOptions::default()).await
+}
+
+/// Replay the raft logs of the group in the directory of a stopped node, and
+/// returns the digests of the entries in `[from_index, to_index]`.
+///
+/// The entries are applied by the state machine of replicas into a scratch
+/// engine, which starts from the empty state, or from the snapshot if the logs
+/// since the first index have been truncated. The replay is refused if the
+/// entries between the base state and `to_index` are not all retained.
+pub async fn replay_group_with(
+    dir: &Path,
+    group_id: u64,
+    from_index: u64,
+    to_index: u64,
+    opts: ReplayOptions,
+) -> Result<Vec<EntryDigest>> {
+    if from_index == 0 || from_index > to_index {
+        return Err(Error::InvalidArgument(format!(
+            "replay entries range [{from_index}, {to_index}]"
+        )));
+    }
+    let log_engine = LogEngineCopy::open(dir, &mut vec![])?;
+    let state_engine = StateEngine::new(log_engine.engine.clone());
+    let Some(replica_id) = find_group_replica(&state_engine, group_id).await? else {
+        return Err(Error::GroupNotFound(group_id));
+    };
+    let engine = &log_engine.engine;
+    let (Some(first_index), Some(last_index)) =
+        (engine.first_index(replica_id), engine.last_index(replica_id))
+    else {
+        return Err(Error::InvalidData(format!("group {group_id} has no raft logs")));
+    };
+    if to_index > last_index {
+        return Err(Error::InvalidArgument(format!(
+            "replay entries up to {to_index}, but the last index of group {group_id} is {last_index}"
+        )));
+    }
+
+    let snap_dir = dir.join(LAYOUT_LOG).join(LAYOUT_SNAP).join(replica_id.to_string());
+    let base = match opts.base.as_deref() {
+        Some(base_dir) => Some(read_replay_base(base_dir)?),
+        None if first_index > 1 => find_replay_base(&snap_dir, first_index, from_index)?,
+        None => None,
+    };
+    let base_index = base.as_ref().map(|(index, _)| *index).unwrap_or_default();
+    if base_index + 1 < first_index || base_index >= from_index {
+        return Err(Error::InvalidArgument(format!(
+            "replay entries since {from_index} from the base state at {base_index}, but the logs of group {group_id} before {first_index} have been truncated",
+        )));
+    }
+
+    let scratch = ScratchDir::create("replay")?;
+    let raw_db = Arc::new(open_raw_db(&DbConfig::default(), scratch.path())?);
+    let group_engine =
+        GroupEngine::create(&EngineConfig::default(), raw_db, group_id, replica_id).await?;
+    let replica_desc = ReplicaDesc { id: replica_id, ..Default::default() };
+    let info = Arc::new(ReplicaInfo::new(&replica_desc, group_id, ReplicaLocalState::Normal));
+    let (_, receiver) = std::sync::mpsc::channel();
+    let mut fsm = GroupStateMachine::new(
+        opts.replica,
+        info,
+        group_engine,
+        Box::new(ReplayObserver),
+        WatchHub::new(receiver),
+    );
+    if let Some((index, data_dir)) = base {
+        info!("group {group_id} replays entries from snapshot at {index}");
+        fsm.apply_snapshot(&data_dir)?;
+    }
+
+    const BATCH_SIZE: u64 = 1024;
+    let mut digests = Vec::with_capacity((to_index - from_index + 1) as usize);
+    let mut next_index = base_index + 1;
+    while next_index <= to_index {
+        let end_index = std::cmp::min(next_index + BATCH_SIZE, to_index + 1);
+        let mut entries = Vec::with_capacity((end_index - next_index) as usize);
+        engine.fetch_entries_to::<MessageExtTyped>(
+            replica_id,
+            next_index,
+            end_index,
+            None,
+            &mut entries,
+        )?;
+        for entry in entries {
+            let (index, term) = (entry.index, entry.term);
+            fsm.start_plug()?;
+            fsm.apply(index, term, decode_apply_entry(&entry)?)?;
+            let digest = fsm.plugged_digest();
+            let write_bytes = fsm.plugged_write_bytes();
+            fsm.finish_plug()?;
+            if index >= from_index {
+                digests.push(EntryDigest { index, term, digest, write_bytes });
+            }
+            next_index = index + 1;
+        }
+        sekas_runtime::yield_now().await;
+    }
+    Ok(digests)
+}
+
+/// Returns the index of the first entry whose digests are different, the
+/// entries not replayed by both are skipped.
+pub fn first_divergence(left: &[EntryDigest], right: &[EntryDigest]) -> Option<u64> {
+    let right = right.iter().map(|d| (d.index, d)).collect::<std::collections::HashMap<_, _>>();
+    left.iter()
+        .filter_map(|l| right.get(&l.index).map(|r| (l, *r)))
+        .find(|(l, r)| l.term != r.term || l.digest != r.digest)
+        .map(|(l, _)| l.index)
+}
+
+/// Load the shard dump written by [`dump_shard`].
+pub fn load_shard_dump(path: &Path) -> Result<(ShardDesc, Vec<ValueSet>)> {
+    let content = std::fs::read(path)?;
+    let Some(mut buf) = content.strip_prefix(DUMP_MAGIC) else {
+        return Err(Error::InvalidData(format!("shard dump {}", path.display())));
+    };
+    let shard_desc = ShardDesc::decode_length_delimited(&mut buf)?;
+    let mut value_sets = vec![];
+    while !buf.is_empty() {
+        value_sets.push(ValueSet::decode_length_delimited(&mut buf)?);
+    }
+    Ok((shard_desc, value_sets))
+}
+
+async fn find_shard_group_engine(
+    state_engine: &StateEngine,
+    raw_db: Arc<RawDb>,
+    shard_id: u64,
+) -> Result<GroupEngine> {
+    for (group_id, replica_id, state) in state_engine.replica_states().await? {
+        if matches!(state, ReplicaLocalState::Tombstone | ReplicaLocalState::Terminated) {
+            continue;
+        }
+        let Some(group_engine) =
+            GroupEngine::open(&EngineConfig::default(), raw_db.clone(), group_id, replica_id)
+                .await?
+        else {
+            continue;
+        };
+        if group_engine.descriptor().shards.iter().any(|s| s.id == shard_id) {
+            return Ok(group_engine);
+        }
+    }
+    Err(Error::ShardNotFound(shard_id))
+}
+
+async fn find_group_replica(state_engine: &StateEngine, group_id: u64) -> Result<Option<u64>> {
+    for (id, replica_id, state) in state_engine.replica_states().await? {
+        if id == group_id
+            && !matches!(state, ReplicaLocalState::Tombstone | ReplicaLocalState::Terminated)
+        {
+            return Ok(Some(replica_id));
+        }
+    }
+    Ok(None)
+}
+
+/// Read the applied index and the data dir of the snapshot.
+fn read_replay_base(snap_dir: &Path) -> Result<(u64, PathBuf)> {
+    let meta = SnapshotMeta::decode(&*std::fs::read(snap_dir.join(SNAP_META))?)?;
+    if !meta.key_id.is_empty() {
+        return Err(Error::InvalidArgument(format!(
+            "snapshot {} is encrypted, decrypt it as the base",
+            snap_dir.display()
+        )));
+    }
+    let applied_index = meta.apply_state.map(|s| s.index).unwrap_or_default();
+    Ok((applied_index, snap_dir.join(SNAP_DATA)))
+}
+
+/// Find the latest snapshot of the replica, which is followed by the retained
+/// logs and is taken before `from_index`.
+fn find_replay_base(
+    replica_snap_dir: &Path,
+    first_index: u64,
+    from_index: u64,
+) -> Result<Option<(u64, PathBuf)>> {
+    if !replica_snap_dir.is_dir() {
+        return Ok(None);
+    }
+    let mut base: Option<(u64, PathBuf)> = None;
+    for (_, dir) in list_numeric_path(replica_snap_dir)? {
+        let Ok((applied_index, data_dir)) = read_replay_base(&dir) else {
+            warn!("skip the invalid snapshot {} as the replay base", dir.display());
+            continue;
+        };
+        if applied_index + 1 >= first_index
+            && applied_index < from_index
+            && base.as_ref().map_or(true, |(index, _)| *index < applied_index)
+        {
+            base = Some((applied_index, data_dir));
+        }
+    }
+    Ok(base)
+}
+
+fn decode_apply_entry(entry: &Entry) -> Result<ApplyEntry> {
+    match entry.get_entry_type() {
+        EntryType::EntryNormal if entry.data.is_empty() => Ok(ApplyEntry::Empty),
+        EntryType::EntryNormal => {
+            Ok(ApplyEntry::Proposal { eval_result: EvalResult::decode(&*entry.data)? })
+        }
+        EntryType::EntryConfChangeV2 => {
+            let conf_change = if entry.data.is_empty() {
+                ConfChangeV2::default()
+            } else {
+                ConfChangeV2::decode(&*entry.data)?
+            };
+            Ok(ApplyEntry::ConfigChange { change_replicas: decode_from_conf_change(&conf_change) })
+        }
+        EntryType::EntryConfChange => {
+            Err(Error::InvalidData(format!("entry {} is ConfChangeV1", entry.index)))
+        }
+    }
+}
+
+/// The changes of the replayed state machine are not observed.
+struct ReplayObserver;
+
+impl StateMachineObserver for ReplayObserver {
+    fn on_descriptor_updated(&mut self, _: GroupDesc) {}
+    fn on_term_updated(&mut self, _: u64) {}
+    fn on_move_shard_state_updated(&mut self, _: Option<MoveShardState>) {}
+    fn on_move_shard_progress_updated(&mut self, _: Option<MoveShardState>) {}
+    fn on_quarantine_updated(&mut self, _: Option<ApplyQuarantine>) {}
+}
+
+fn inspect_snapshots(replica_snap_dir: &Path, replica: &mut ReplicaInspection) -> Result<()> {
+    if !replica_snap_dir.is_dir() {
+        return Ok(());
+    }
+    for (index, dir) in list_numeric_path(replica_snap_dir)? {
+        let meta_name = dir.join(SNAP_META);
+        let mut snapshot = SnapshotInspection { index, dir: dir.clone(), applied_index: None };
+        if !meta_name.exists() {
+            replica.corruptions.push(Corruption::MissingSnapMeta { dir });
+        } else {
+            match SnapshotMeta::decode(&*std::fs::read(&meta_name)?) {
+                Ok(meta) => snapshot.applied_index = meta.apply_state.map(|s| s.index),
+                Err(err) => replica
+                    .corruptions
+                    .push(Corruption::InvalidSnapMeta { dir, reason: err.to_string() }),
+            }
+        }
+        replica.snapshots.push(snapshot);
+    }
+    Ok(())
+}
+
+fn inspect_raft_logs(engine: &raft_engine::Engine, replica: &mut ReplicaInspection) -> Result<()> {
+    let replica_id = replica.replica_id;
+    let Some((hard_state, local_state)) = read_raft_state(engine, replica_id)? else {
+        if replica.state != ReplicaLocalState::Initial {
+            replica.corruptions.push(Corruption::MissingRaftState);
+        }
+        return Ok(());
+    };
+    replica.term = hard_state.term;
+    if let (Some(first_index), Some(last_index)) =
+        (engine.first_index(replica_id), engine.last_index(replica_id))
+    {
+        replica.log_range = Some((first_index, last_index));
+        let truncated_index = local_state.last_truncated.map(|t| t.index).unwrap_or_default();
+        if truncated_index + 1 != first_index {
+            replica.corruptions.push(Corruption::TruncatedLog { truncated_index, first_index });
+        }
+        if truncated_index > replica.applied_index {
+            replica.corruptions.push(Corruption::UnappliedLogTruncated {
+                applied_index: replica.applied_index,
+                truncated_index,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The raft engine opened from a copy of its files, since opening the raft
+/// engine acquires an exclusive lock and might purge or recycle log files.
+struct LogEngineCopy {
+    engine: Arc<raft_engine::Engine>,
+    dir: PathBuf,
+}
+
+impl LogEngineCopy {
+    fn open(root_dir: &Path, corruptions: &mut Vec<Corruption>) -> Result<Self> {
+        use raft_engine::{Config, Engine};
+
+        let engine_dir = root_dir.join(LAYOUT_LOG).join("engine");
+        let dir = scratch_path("inspect");
+        std::fs::create_dir_all(&dir)?;
+        let cleanup = |err: Error| {
+            std::fs::remove_dir_all(&dir).unwrap_or_default();
+            err
+        };
+        copy_log_files(&engine_dir, &dir).map_err(cleanup)?;
+
+        let mut cfg = Config {
+            dir: dir.to_str().unwrap().to_owned(),
+            recovery_mode: RecoveryMode::TolerateTailCorruption,
+            enable_log_recycle: false,
+            ..Default::default()
+        };
+        let engine = match Engine::open(cfg.clone()) {
+            Ok(engine) => engine,
+            Err(err) => {
+                warn!("raft engine {} is corrupted: {err}", engine_dir.display());
+                corruptions.push(Corruption::RaftLog { reason: err.to_string() });
+                cfg.recovery_mode = RecoveryMode::TolerateAnyCorruption;
+                Engine::open(cfg).map_err(|err| cleanup(err.into()))?
+            }
+        };
+        Ok(LogEngineCopy { engine: Arc::new(engine), dir })
+    }
+}
+
+impl Drop for LogEngineCopy {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).unwrap_or_default();
+    }
+}
+
+/// A temp dir removed once it is dropped.
+struct ScratchDir {
+    dir: PathBuf,
+}
+
+impl ScratchDir {
+    fn create(name: &str) -> Result<Self> {
+        let dir = scratch_path(name);
+        std::fs::create_dir_all(&dir)?;
+        Ok(ScratchDir { dir })
+    }
+
+    fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).unwrap_or_default();
+    }
+}
+
+fn scratch_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("sekas-{name}-{}-{}", std::process::id(), timestamp_nanos()))
+}
+
+fn copy_log_files(from: &Path, to: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let path = entry?.path();
+        // The lock file is held by a running node, and is not required.
+        if path.is_file() && path.file_name().map(|name| name != "LOCK").unwrap_or_default() {
+            std::fs::copy(&path, to.join(path.file_name().unwrap()))?;
+        }
+    }
+    Ok(())
+}
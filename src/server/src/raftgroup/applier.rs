@@ -12,23 +12,39 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::any::Any;
+use std::cell::Cell;
 use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
 
 use futures::channel::oneshot;
+use log::{error, info};
 use raft::prelude::{ConfChangeV2, Entry, EntryType};
 use raft::{RawNode, ReadState};
-use sekas_api::server::v1::ReplicaDesc;
+use sekas_api::server::v1::{ApplyQuarantine, ReplicaDesc};
 
 use super::fsm::StateMachine;
 use super::monitor::ApplierPerfContext;
 use super::storage::Storage;
 use super::ApplyEntry;
+use crate::error::BusyReason;
 use crate::raftgroup::metrics::*;
 use crate::raftgroup::monitor::record_perf_point;
 use crate::serverpb::v1::{EntryId, EvalResult};
 use crate::{record_latency, Error, Result};
 
+thread_local! {
+    /// Whether this thread is applying an entry.
+    static APPLYING: Cell<bool> = Cell::new(false);
+}
+
+/// Return whether the panic is raised in applying an entry. It is caught and
+/// the replica is quarantined, so the panic hook shouldn't abort the process.
+pub fn is_apply_panic() -> bool {
+    APPLYING.with(|applying| applying.get())
+}
+
 struct ProposalContext {
     index: u64,
     term: u64,
@@ -54,22 +70,51 @@ pub struct Applier<M: StateMachine> {
     read_states: Vec<ReadState>,
 
     last_applied_index: u64,
+    /// The index of the last committed entry delivered by raft. The entries
+    /// after the applied index are delivered only once, they are fetched from
+    /// the raft log once the quarantine is lifted.
+    last_delivered_index: u64,
+    /// The entry whose applying panics, the entries since it are not applied
+    /// until the quarantine is resolved.
+    quarantine: Option<ApplyQuarantine>,
+    /// The committed entries delivered but not applied yet, since the entries
+    /// applied in a round are bounded by `max_entries_per_round`.
+    pending_entries: VecDeque<Entry>,
+    max_entries_per_round: usize,
     state_machine: M,
 }
 
 impl<M: StateMachine> Applier<M> {
     pub fn new(group_id: u64, state_machine: M) -> Self {
+        let flushed_index = state_machine.flushed_index();
         Applier {
             group_id,
             proposal_queue: VecDeque::default(),
             next_read_state_index: 0,
             read_requests: HashMap::default(),
             read_states: Vec::default(),
-            last_applied_index: state_machine.flushed_index(),
+            last_applied_index: flushed_index,
+            last_delivered_index: flushed_index,
+            quarantine: state_machine.quarantined(),
+            pending_entries: VecDeque::default(),
+            max_entries_per_round: usize::MAX,
             state_machine,
         }
     }
 
+    /// Limit the number of entries applied in a round, the others are kept
+    /// pending until the next rounds.
+    #[inline]
+    pub fn set_max_entries_per_round(&mut self, max_entries: usize) {
+        self.max_entries_per_round = max_entries.max(1);
+    }
+
+    /// Whether there are committed entries pending to apply.
+    #[inline]
+    pub fn has_pending_entries(&self) -> bool {
+        !self.pending_entries.is_empty()
+    }
+
     #[inline]
     pub fn delegate_proposal_context(
         &mut self,
@@ -115,6 +160,9 @@ impl<M: StateMachine> Applier<M> {
         if !read_states.is_empty() {
             self.read_states.append(&mut read_states);
         }
+        if self.quarantine.is_some() {
+            self.abort_pending_requests();
+        }
     }
 
     #[inline]
@@ -126,9 +174,54 @@ impl<M: StateMachine> Applier<M> {
         let state_machine = self.mut_state_machine();
         state_machine.apply_snapshot(snap_dir)?;
         self.last_applied_index = state_machine.flushed_index();
+        self.last_delivered_index = self.last_delivered_index.max(self.last_applied_index);
+        let applied_index = self.last_applied_index;
+        self.pending_entries.retain(|entry| entry.index > applied_index);
+        if let Some(quarantine) = self.quarantine.take() {
+            // The replica is rebuilt, the poisoned entry is applied by the snapshot.
+            info!(
+                "group {} lifts the quarantine of entry {} since snapshot {} is applied",
+                self.group_id, quarantine.index, self.last_applied_index
+            );
+            self.state_machine.quarantine(None)?;
+        }
         Ok(())
     }
 
+    #[inline]
+    pub fn quarantine(&self) -> Option<&ApplyQuarantine> {
+        self.quarantine.as_ref()
+    }
+
+    /// Lift the quarantine and apply the entries since the poisoned one again,
+    /// see `last_delivered_index`. The replica is quarantined again if
+    /// applying any of them panics.
+    pub(super) fn reapply_quarantined_entries(
+        &mut self,
+        raw_node: &mut RawNode<Storage>,
+        replica_cache: &mut ReplicaCache,
+        entries: Vec<Entry>,
+    ) -> Result<u64> {
+        let Some(quarantine) = self.quarantine.take() else {
+            return Ok(self.last_applied_index);
+        };
+        let mut perf_ctx = ApplierPerfContext::default();
+        let applied = self.apply_entries(&mut perf_ctx, raw_node, replica_cache, entries);
+        if self.quarantine.is_none() {
+            info!(
+                "group {} lifts the quarantine of entry {}, applied index {applied}",
+                self.group_id, quarantine.index
+            );
+            self.state_machine.quarantine(None)?;
+        }
+        Ok(applied)
+    }
+
+    #[inline]
+    pub fn last_delivered_index(&self) -> u64 {
+        self.last_delivered_index
+    }
+
     #[inline]
     pub fn applied_index(&self) -> u64 {
         self.last_applied_index
@@ -139,7 +232,9 @@ impl<M: StateMachine> Applier<M> {
         self.state_machine.flushed_index()
     }
 
-    /// Apply entries and invoke proposal & read response.
+    /// Apply entries and invoke proposal & read response. At most
+    /// `max_entries_per_round` entries are applied, the others are kept pending
+    /// and applied by the next calls, before the entries passed by them.
     pub(super) fn apply_entries(
         &mut self,
         perf_ctx: &mut ApplierPerfContext,
@@ -148,36 +243,40 @@ impl<M: StateMachine> Applier<M> {
         committed_entries: Vec<Entry>,
     ) -> u64 {
         record_latency!(&RAFTGROUP_WORKER_APPLY_DURATION_SECONDS);
+        if let Some(entry) = committed_entries.last() {
+            self.last_delivered_index = self.last_delivered_index.max(entry.index);
+        }
+        self.pending_entries.extend(committed_entries);
+        let num_entries = self.pending_entries.len().min(self.max_entries_per_round);
+        let committed_entries = self.pending_entries.drain(..num_entries).collect::<Vec<_>>();
         RAFTGROUP_WORKER_APPLY_ENTRIES_SIZE.observe(committed_entries.len() as f64);
-
         perf_ctx.num_committed = committed_entries.len();
+
         record_perf_point(&mut perf_ctx.start_plug);
         self.state_machine.start_plug().expect("start_plug");
         let mut entry_ids = Vec::with_capacity(committed_entries.len());
         for entry in committed_entries {
+            if self.quarantine.as_ref().map(|q| q.index <= entry.index).unwrap_or_default() {
+                break;
+            }
             if entry.index != self.last_applied_index + 1 && self.last_applied_index != 0 {
                 panic!("group {} apply entries: log is not discontinuous, last applied index {}, entry index {}",
                     self.group_id, self.last_applied_index, entry.index);
             }
 
-            self.last_applied_index = entry.index;
-            entry_ids.push(EntryId::from(&entry));
-            match entry.get_entry_type() {
-                EntryType::EntryNormal if entry.data.is_empty() => {
-                    self.state_machine
-                        .apply(entry.index, entry.term, ApplyEntry::Empty)
-                        .expect("apply empty entry");
-                }
-                EntryType::EntryNormal => self.apply_normal_entry(entry),
-                EntryType::EntryConfChange => panic!("ConfChangeV1 not supported"),
-                EntryType::EntryConfChangeV2 => {
-                    self.apply_conf_change(raw_node, replica_cache, entry)
-                }
+            let entry_id = EntryId::from(&entry);
+            if let Err(quarantine) = self.apply_entry(raw_node, replica_cache, entry) {
+                self.quarantine_entry(quarantine);
+                break;
             }
+            self.last_applied_index = entry_id.index;
+            entry_ids.push(entry_id);
         }
 
-        record_perf_point(&mut perf_ctx.finish_plug);
-        self.state_machine.finish_plug().expect("finish_plug");
+        if !entry_ids.is_empty() {
+            record_perf_point(&mut perf_ctx.finish_plug);
+            self.state_machine.finish_plug().expect("finish_plug");
+        }
 
         record_perf_point(&mut perf_ctx.response_proposals);
         entry_ids
@@ -186,9 +285,112 @@ impl<M: StateMachine> Applier<M> {
 
         // Since the `last_applied_index` updated, try advance cached read states.
         self.response_cached_read_states();
+        if self.quarantine.is_some() {
+            // The entries since the poisoned one are fetched from the raft log again
+            // once the quarantine is lifted, see `last_delivered_index`.
+            self.pending_entries.clear();
+            self.abort_pending_requests();
+        }
         self.last_applied_index
     }
 
+    /// Apply the entry, returns the quarantine if applying panics, or the
+    /// local storage is found corrupted.
+    fn apply_entry(
+        &mut self,
+        raw_node: &mut RawNode<Storage>,
+        replica_cache: &mut ReplicaCache,
+        entry: Entry,
+    ) -> Result<(), ApplyQuarantine> {
+        let (index, term) = (entry.index, entry.term);
+        let result = APPLYING.with(|applying| {
+            applying.set(true);
+            let result = panic::catch_unwind(AssertUnwindSafe(|| match entry.get_entry_type() {
+                EntryType::EntryNormal if entry.data.is_empty() => {
+                    self.state_machine.apply(entry.index, entry.term, ApplyEntry::Empty)
+                }
+                EntryType::EntryNormal => self.apply_normal_entry(entry),
+                EntryType::EntryConfChange => panic!("ConfChangeV1 not supported"),
+                EntryType::EntryConfChangeV2 => {
+                    self.apply_conf_change(raw_node, replica_cache, entry);
+                    Ok(())
+                }
+            }));
+            applying.set(false);
+            result
+        });
+        let (message, corrupted) = match result {
+            Ok(Ok(())) => return Ok(()),
+            // Applying the entry again never succeeds, the replica must be rebuilt.
+            Ok(Err(err)) if err.is_storage_corrupted() => (err.to_string(), true),
+            Ok(Err(err)) => (format!("apply entry: {err:?}"), false),
+            Err(payload) => (panic_message(payload), false),
+        };
+        Err(ApplyQuarantine { index, term, message, corrupted })
+    }
+
+    /// Discard the changes of the poisoned entry and quarantine the replica,
+    /// the entries since it are not applied until the quarantine is resolved.
+    fn quarantine_entry(&mut self, quarantine: ApplyQuarantine) {
+        error!(
+            "group {} applying entry {} term {} {}, quarantine the replica: {}",
+            self.group_id,
+            quarantine.index,
+            quarantine.term,
+            if quarantine.corrupted { "finds the storage corrupted" } else { "panics" },
+            quarantine.message
+        );
+        RAFTGROUP_APPLY_QUARANTINE_TOTAL.inc();
+        self.state_machine.discard_entry();
+        self.state_machine.quarantine(Some(quarantine.clone())).expect("save apply quarantine");
+        self.quarantine = Some(quarantine);
+    }
+
+    /// Quarantine the replica since the local storage is found corrupted by
+    /// serving a read, the entries since the next one are not applied until
+    /// the replica is rebuilt.
+    pub(super) fn quarantine_corruption(&mut self, term: u64, message: String) {
+        if self.quarantine.is_some() {
+            return;
+        }
+        let index = self.last_applied_index + 1;
+        error!(
+            "group {} finds the storage corrupted, quarantine the replica at entry {index}: {message}",
+            self.group_id
+        );
+        RAFTGROUP_APPLY_QUARANTINE_TOTAL.inc();
+        let quarantine = ApplyQuarantine { index, term, message, corrupted: true };
+        self.state_machine.quarantine(Some(quarantine.clone())).expect("save apply quarantine");
+        self.quarantine = Some(quarantine);
+        self.abort_pending_requests();
+    }
+
+    /// The error of the requests served by the quarantined replica. The
+    /// corrupted replica is never recovered in place, so the requests are
+    /// redirected to the other replicas.
+    pub fn quarantined_error(&self) -> Error {
+        match &self.quarantine {
+            Some(quarantine) if quarantine.corrupted => {
+                Error::NotLeader(self.group_id, quarantine.term, None)
+            }
+            _ => Error::ServiceIsBusy(BusyReason::Quarantined),
+        }
+    }
+
+    /// The pending proposals and reads are never responded until the
+    /// quarantine is resolved, so fail them.
+    fn abort_pending_requests(&mut self) {
+        let proposals = std::mem::take(&mut self.proposal_queue);
+        let read_requests = std::mem::take(&mut self.read_requests);
+        for ctx in proposals {
+            ctx.sender.send(Err(self.quarantined_error())).unwrap_or_default();
+        }
+        for request in read_requests.into_values().flatten() {
+            request.send(Err(self.quarantined_error())).unwrap_or_default();
+        }
+        self.read_states.clear();
+    }
+
     fn apply_conf_change(
         &mut self,
         raw_node: &mut RawNode<Storage>,
@@ -214,15 +416,13 @@ impl<M: StateMachine> Applier<M> {
         raw_node.apply_conf_change(&conf_change).unwrap_or_default();
     }
 
-    fn apply_normal_entry(&mut self, entry: Entry) {
+    fn apply_normal_entry(&mut self, entry: Entry) -> Result<()> {
         use prost::Message;
 
         assert!(matches!(entry.get_entry_type(), EntryType::EntryNormal));
 
         let eval_result = EvalResult::decode(&*entry.data).expect("Entry::data is EvalResult");
-        self.state_machine
-            .apply(entry.index, entry.term, ApplyEntry::Proposal { eval_result })
-            .expect("apply normal entry");
+        self.state_machine.apply(entry.index, entry.term, ApplyEntry::Proposal { eval_result })
     }
 
     #[inline]
@@ -265,6 +465,16 @@ impl<M: StateMachine> Applier<M> {
     }
 }
 
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
+
 impl ReplicaCache {
     #[inline]
     pub fn batch_insert(&mut self, replicas: &[ReplicaDesc]) {
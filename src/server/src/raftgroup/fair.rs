@@ -0,0 +1,149 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The fair scheduling of the raft workers of a node.
+//!
+//! The raft workers of all groups share the executor threads of a node, so a
+//! group advancing a huge amount of entries in every round delays the rounds
+//! of the other groups. A round is bounded by the budget of entries and bytes,
+//! both the requests consumed and the entries applied, and the group exceeding
+//! the budget is requeued behind the others: it yields the executor, and waits
+//! until the rounds of the light groups in progress are finished, so the groups
+//! with small pending work are served first.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use sekas_runtime::time::Instant;
+use tokio::sync::Notify;
+
+/// The max duration a heavy group waits for the light groups in a requeuing,
+/// so that the heavy group itself is never starved.
+const MAX_REQUEUE_DELAY: Duration = Duration::from_millis(10);
+
+/// The work a group may consume in a scheduling round.
+#[derive(Clone, Copy, Debug)]
+pub struct RoundBudget {
+    /// The number of requests consumed and entries applied.
+    pub max_entries: usize,
+    /// The bytes of requests consumed.
+    pub max_bytes: usize,
+}
+
+impl RoundBudget {
+    /// Whether the work of a round exceeds the budget.
+    #[inline]
+    pub fn is_exceeded(&self, entries: usize, bytes: usize) -> bool {
+        entries >= self.max_entries || bytes >= self.max_bytes
+    }
+}
+
+/// The scheduler shared by the raft workers of a node.
+#[derive(Clone, Default)]
+pub struct RoundScheduler {
+    shared: Arc<SchedulerShared>,
+}
+
+#[derive(Default)]
+struct SchedulerShared {
+    /// The number of rounds of the light groups in progress.
+    num_light_rounds: AtomicUsize,
+    /// Notified once there is no round of the light groups in progress.
+    light_rounds_finished: Notify,
+}
+
+/// A round of a light group in progress, the heavy groups yield until it is
+/// dropped.
+pub struct LightRound {
+    scheduler: RoundScheduler,
+}
+
+impl RoundScheduler {
+    /// Begin a round of a light group, whose last round is within the budget.
+    pub fn begin_light_round(&self) -> LightRound {
+        self.shared.num_light_rounds.fetch_add(1, Ordering::AcqRel);
+        LightRound { scheduler: self.clone() }
+    }
+
+    /// The number of rounds of the light groups in progress.
+    #[inline]
+    pub fn num_light_rounds(&self) -> usize {
+        self.shared.num_light_rounds.load(Ordering::Acquire)
+    }
+
+    /// Requeue a heavy group behind the others, returns the duration waited
+    /// before it is scheduled again.
+    pub async fn requeue(&self) -> Duration {
+        let start = Instant::now();
+        sekas_runtime::yield_now().await;
+        let light_rounds_finished = async {
+            loop {
+                // The waiter is registered before checking the rounds, so the
+                // notification is never missed.
+                let notified = self.shared.light_rounds_finished.notified();
+                if self.num_light_rounds() == 0 {
+                    break;
+                }
+                notified.await;
+            }
+        };
+        let _ = sekas_runtime::time::timeout(MAX_REQUEUE_DELAY, light_rounds_finished).await;
+        start.elapsed()
+    }
+}
+
+impl Drop for LightRound {
+    fn drop(&mut self) {
+        let shared = &self.scheduler.shared;
+        if shared.num_light_rounds.fetch_sub(1, Ordering::AcqRel) == 1 {
+            shared.light_rounds_finished.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_budget_exceeded() {
+        let budget = RoundBudget { max_entries: 4, max_bytes: 1024 };
+        assert!(!budget.is_exceeded(3, 1023));
+        assert!(budget.is_exceeded(4, 0));
+        assert!(budget.is_exceeded(0, 1024));
+    }
+
+    #[sekas_macro::test]
+    async fn heavy_group_waits_for_light_rounds() {
+        let scheduler = RoundScheduler::default();
+        let light = scheduler.begin_light_round();
+        assert_eq!(scheduler.num_light_rounds(), 1);
+
+        // The heavy group is never starved by the light rounds.
+        let delay = scheduler.requeue().await;
+        assert!(delay >= MAX_REQUEUE_DELAY);
+
+        // The heavy group is scheduled once the light rounds are finished.
+        let handle = {
+            let scheduler = scheduler.clone();
+            sekas_runtime::spawn(async move { scheduler.requeue().await })
+        };
+        sekas_runtime::time::sleep(Duration::from_millis(1)).await;
+        drop(light);
+        assert_eq!(scheduler.num_light_rounds(), 0);
+        assert!(handle.await.unwrap() < MAX_REQUEUE_DELAY);
+        assert!(scheduler.requeue().await < MAX_REQUEUE_DELAY);
+    }
+}
@@ -14,7 +14,7 @@
 
 use std::path::Path;
 
-use sekas_api::server::v1::{ChangeReplicas, GroupDesc};
+use sekas_api::server::v1::{ApplyQuarantine, ChangeReplicas, GroupDesc};
 
 use crate::serverpb::v1::{ApplyState, EvalResult};
 use crate::Result;
@@ -31,8 +31,18 @@ pub enum ApplyEntry {
 pub trait StateMachine: Send {
     fn start_plug(&mut self) -> Result<()>;
     fn apply(&mut self, index: u64, term: u64, entry: ApplyEntry) -> Result<()>;
+    /// Discard the changes of the entry whose `apply` panics, the entries
+    /// applied before it in the same plug are kept.
+    fn discard_entry(&mut self);
     fn finish_plug(&mut self) -> Result<()>;
 
+    /// Save or clear the quarantine, the replica stops applying entries
+    /// since applying the quarantined one panics.
+    fn quarantine(&mut self, quarantine: Option<ApplyQuarantine>) -> Result<()>;
+
+    /// Return the saved quarantine.
+    fn quarantined(&self) -> Option<ApplyQuarantine>;
+
     fn apply_snapshot(&mut self, snap_dir: &Path) -> Result<()>;
 
     fn snapshot_builder(&self) -> Box<dyn SnapshotBuilder>;
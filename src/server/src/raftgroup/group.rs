@@ -12,10 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use futures::channel::{mpsc, oneshot};
-use sekas_api::server::v1::ChangeReplicas;
+use sekas_api::server::v1::{ApplyQuarantine, ChangeReplicas, QuarantineAction};
+use sekas_runtime::time::Instant;
 
 use super::metrics::*;
 use super::worker::{RaftGroupState, Request};
@@ -31,12 +33,29 @@ where
     Self: Send,
 {
     request_sender: mpsc::Sender<Request>,
+    contact: Arc<LeaderContact>,
+}
+
+/// The contact of a replica with the leader of its group, which bounds the
+/// staleness of the data served by the replica without the leader.
+#[derive(Default)]
+pub struct LeaderContact {
+    inner: Mutex<ContactState>,
+}
+
+#[derive(Default)]
+struct ContactState {
+    /// The last time the replica heard from the leader, by the appended
+    /// entries, heartbeats or snapshots of the current term.
+    last_heard: Option<Instant>,
+    /// The number of the failed raft messages since then.
+    num_unreachable: u64,
 }
 
 impl RaftGroup {
     /// Open the existed raft group.
-    pub fn open(sender: mpsc::Sender<Request>) -> Self {
-        RaftGroup { request_sender: sender }
+    pub fn open(sender: mpsc::Sender<Request>, contact: Arc<LeaderContact>) -> Self {
+        RaftGroup { request_sender: sender, contact }
     }
 
     /// Submit a data to replicate, and returns corresponding future value.
@@ -115,6 +134,28 @@ impl RaftGroup {
         Ok(receiver.await?)
     }
 
+    /// Resolve the quarantine of the replica, returns the quarantine remained.
+    pub async fn resolve_quarantine(
+        &self,
+        action: QuarantineAction,
+        expected_index: u64,
+    ) -> Result<Option<ApplyQuarantine>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(Request::ResolveQuarantine { action, expected_index, sender })?;
+        receiver.await?
+    }
+
+    /// Quarantine the replica since the local storage is found corrupted, the
+    /// requests are redirected to the other replicas.
+    pub fn quarantine_corruption(&self, message: String) {
+        self.send(Request::QuarantineCorruption { message }).unwrap_or_default();
+    }
+
+    #[inline]
+    pub fn leader_contact(&self) -> &LeaderContact {
+        &self.contact
+    }
+
     pub fn terminate(&self) {
         self.request_sender.clone().close_channel();
     }
@@ -130,3 +171,29 @@ impl RaftGroup {
         Ok(())
     }
 }
+
+impl LeaderContact {
+    /// Record that the replica heard from the leader just now.
+    pub fn heard(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.last_heard = Some(Instant::now());
+        inner.num_unreachable = 0;
+    }
+
+    /// Record a raft message which is failed to deliver.
+    pub fn report_unreachable(&self) {
+        self.inner.lock().unwrap().num_unreachable += 1;
+    }
+
+    /// The duration since the replica last heard from the leader, `None` if
+    /// it never heard from the leader since it is opened.
+    pub fn since_last_heard(&self) -> Option<Duration> {
+        self.inner.lock().unwrap().last_heard.map(|instant| instant.elapsed())
+    }
+
+    /// The number of the failed raft messages since the replica last heard
+    /// from the leader.
+    pub fn num_unreachable(&self) -> u64 {
+        self.inner.lock().unwrap().num_unreachable
+    }
+}
@@ -18,6 +18,7 @@ use futures::channel::mpsc;
 use futures::StreamExt;
 use log::{debug, warn};
 use sekas_api::server::v1::{NodeDesc, ReplicaDesc};
+use sekas_runtime::sim::net::{route, Delivery};
 use sekas_runtime::{JoinHandle, TaskGroup};
 
 use crate::node::route_table::RaftRouteTable;
@@ -34,6 +35,7 @@ struct StreamingRequest {
 }
 
 struct StreamingTask {
+    local_addr: Arc<str>,
     resolver: Arc<dyn AddressResolver>,
     raft_node: RaftGroup,
     request: StreamingRequest,
@@ -96,11 +98,17 @@ impl Channel {
 }
 
 impl ChannelManager {
-    pub fn new(resolver: Arc<dyn AddressResolver>, route_table: RaftRouteTable) -> Self {
+    /// Create a channel manager for the node listening on `local_addr`.
+    pub fn new(
+        local_addr: &str,
+        resolver: Arc<dyn AddressResolver>,
+        route_table: RaftRouteTable,
+    ) -> Self {
         let (sender, receiver) = mpsc::unbounded();
+        let local_addr = Arc::from(local_addr);
         let resolver_clone = resolver.clone();
         let handle = sekas_runtime::spawn(async move {
-            Self::run(resolver_clone, route_table, receiver).await;
+            Self::run(local_addr, resolver_clone, route_table, receiver).await;
         });
         ChannelManager { resolver, sender, _handle: handle }
     }
@@ -113,6 +121,7 @@ impl ChannelManager {
     }
 
     async fn run(
+        local_addr: Arc<str>,
         resolver: Arc<dyn AddressResolver>,
         route_table: RaftRouteTable,
         mut receiver: mpsc::UnboundedReceiver<StreamingRequest>,
@@ -130,7 +139,12 @@ impl ChannelManager {
                 }
             };
 
-            let task = StreamingTask { resolver: resolver.clone(), raft_node, request };
+            let task = StreamingTask {
+                local_addr: local_addr.clone(),
+                resolver: resolver.clone(),
+                raft_node,
+                request,
+            };
             let handle = sekas_runtime::spawn(async move {
                 task.run().await;
             });
@@ -155,7 +169,23 @@ impl StreamingTask {
         let node_desc = resolve_address(&*self.resolver, self.request.to.node_id).await?;
         let address = format!("http://{}", node_desc.addr);
         let mut client = RaftClient::connect(address).await?;
-        if let Err(e) = client.send_message(self.request.receiver).await {
+        // The messages are routed by the network faults injected by simulation, all
+        // messages are delivered immediately without the `simulation` feature.
+        let local_addr = self.local_addr;
+        let messages = self.request.receiver.filter_map(move |msg| {
+            let delivery = route(&local_addr, &node_desc.addr);
+            async move {
+                match delivery {
+                    Delivery::Deliver => Some(msg),
+                    Delivery::Drop => None,
+                    Delivery::Delay(duration) => {
+                        sekas_runtime::time::sleep(duration).await;
+                        Some(msg)
+                    }
+                }
+            }
+        });
+        if let Err(e) = client.send_message(messages).await {
             warn!("serve request to node {node_id} replica {target_id} from {from_id}: {e:?}");
         }
         Ok(())
@@ -12,11 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::Instant;
-
 use lazy_static::lazy_static;
 use prometheus::*;
 use prometheus_static_metric::make_static_metric;
+use sekas_runtime::time::Instant;
 
 use super::ReadPolicy;
 use crate::{Error, Result};
@@ -50,6 +49,24 @@ make_static_metric! {
             read_index,
         }
     }
+    struct ScheduleDelay: Histogram {
+        "type" => {
+            light,
+            heavy,
+        }
+    }
+    struct CreateSnapshotAvoidedTotal: IntCounter {
+        "type" => {
+            reuse,
+            in_progress,
+        }
+    }
+    struct ElectionPreventedTotal: IntCounter {
+        "type" => {
+            pre_vote_rejected,
+            in_lease,
+        }
+    }
 }
 
 lazy_static! {
@@ -113,6 +130,20 @@ lazy_static! {
         "The total of unreachable of raftgroup",
     )
     .unwrap();
+    pub static ref RAFTGROUP_APPLY_QUARANTINE_TOTAL: IntCounter = register_int_counter!(
+        "raftgroup_apply_quarantine_total",
+        "The total of replicas quarantined since applying an entry panics",
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_ELECTION_PREVENTED_TOTAL_VEC: IntCounterVec =
+        register_int_counter_vec!(
+            "raftgroup_election_prevented_total",
+            "The total of vote requests of raftgroup rejected by pre-vote or ignored in lease",
+            &["type"],
+        )
+        .unwrap();
+    pub static ref RAFTGROUP_ELECTION_PREVENTED_TOTAL: ElectionPreventedTotal =
+        ElectionPreventedTotal::from(&RAFTGROUP_ELECTION_PREVENTED_TOTAL_VEC);
 }
 
 lazy_static! {
@@ -126,6 +157,28 @@ lazy_static! {
         "The total bytes of send snapshot of raftgroup",
     )
     .unwrap();
+    pub static ref RAFTGROUP_SEND_SNAPSHOT_QUEUE_DEPTH: IntGauge = register_int_gauge!(
+        "raftgroup_send_snapshot_queue_depth",
+        "The number of snapshot transfers waiting for a slot",
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_SEND_SNAPSHOT_INFLIGHT: IntGauge = register_int_gauge!(
+        "raftgroup_send_snapshot_inflight",
+        "The number of snapshot transfers in sending",
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_SEND_SNAPSHOT_WAIT_DURATION_SECONDS: Histogram = register_histogram!(
+        "raftgroup_send_snapshot_wait_duration_seconds",
+        "The intervals of snapshot transfers waiting for a slot",
+        exponential_buckets(0.005, 1.8, 22).unwrap(),
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_SEND_SNAPSHOT_DURATION_SECONDS: Histogram = register_histogram!(
+        "raftgroup_send_snapshot_duration_seconds",
+        "The intervals of send snapshot of raftgroup",
+        exponential_buckets(0.005, 1.8, 22).unwrap(),
+    )
+    .unwrap();
 }
 
 lazy_static! {
@@ -173,6 +226,30 @@ lazy_static! {
         exponential_buckets(0.005, 1.8, 22).unwrap(),
     )
     .unwrap();
+    pub static ref RAFTGROUP_CREATE_SNAPSHOT_AVOIDED_TOTAL_VEC: IntCounterVec =
+        register_int_counter_vec!(
+            "raftgroup_create_snapshot_avoided_total",
+            "The total of create snapshot of raftgroup avoided by sharing a snapshot",
+            &["type"],
+        )
+        .unwrap();
+    pub static ref RAFTGROUP_CREATE_SNAPSHOT_AVOIDED_TOTAL: CreateSnapshotAvoidedTotal =
+        CreateSnapshotAvoidedTotal::from(&RAFTGROUP_CREATE_SNAPSHOT_AVOIDED_TOTAL_VEC);
+}
+
+lazy_static! {
+    pub static ref RAFTGROUP_RECOVERY_DURATION_SECONDS: Histogram = register_histogram!(
+        "raftgroup_recovery_duration_seconds",
+        "The intervals of replaying the committed entries of raftgroup after opening",
+        exponential_buckets(0.005, 1.8, 22).unwrap(),
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_RECOVERY_REPLAYED_ENTRIES: Histogram = register_histogram!(
+        "raftgroup_recovery_replayed_entries",
+        "The number of committed entries replayed by raftgroup after opening",
+        exponential_buckets(1.0, 2.0, 20).unwrap(),
+    )
+    .unwrap();
 }
 
 lazy_static! {
@@ -231,6 +308,34 @@ lazy_static! {
         exponential_buckets(1.0, 1.8, 22).unwrap(),
     )
     .unwrap();
+    pub static ref RAFTGROUP_WORKER_REQUEUE_TOTAL: IntCounter = register_int_counter!(
+        "raftgroup_worker_requeue_total",
+        "The total of rounds exceeding the budget, the groups are requeued behind others",
+    )
+    .unwrap();
+}
+
+lazy_static! {
+    pub static ref RAFTGROUP_WORKER_SCHEDULE_DELAY_SECONDS_VEC: HistogramVec =
+        register_histogram_vec!(
+            "raftgroup_worker_schedule_delay_seconds",
+            "The delay between the work of a group is pending and its round is scheduled",
+            &["type"],
+            exponential_buckets(0.00005, 1.8, 26).unwrap()
+        )
+        .unwrap();
+    pub static ref RAFTGROUP_WORKER_SCHEDULE_DELAY_SECONDS: ScheduleDelay =
+        ScheduleDelay::from(&RAFTGROUP_WORKER_SCHEDULE_DELAY_SECONDS_VEC);
+}
+
+/// The schedule delay of the groups, by whether the last round of the group
+/// exceeds the budget.
+pub fn take_schedule_delay_metrics(is_heavy: bool) -> &'static Histogram {
+    if is_heavy {
+        &RAFTGROUP_WORKER_SCHEDULE_DELAY_SECONDS.heavy
+    } else {
+        &RAFTGROUP_WORKER_SCHEDULE_DELAY_SECONDS.light
+    }
 }
 
 pub fn take_read_metrics(read_policy: ReadPolicy) -> &'static Histogram {
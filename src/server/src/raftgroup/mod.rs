@@ -13,6 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 mod applier;
+mod fair;
 mod fsm;
 mod group;
 mod io;
@@ -23,21 +24,25 @@ pub mod snap;
 mod storage;
 mod worker;
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use raft::prelude::{
-    ConfChangeSingle, ConfChangeTransition, ConfChangeType, ConfChangeV2, ConfState,
+    ConfChangeSingle, ConfChangeTransition, ConfChangeType, ConfChangeV2, ConfState, Entry,
 };
 use sekas_api::server::v1::*;
 use sekas_runtime::{JoinHandle, TaskGroup};
 
+pub use self::applier::is_apply_panic;
+use self::fair::RoundScheduler;
 pub use self::fsm::{ApplyEntry, SnapshotBuilder, StateMachine};
-pub use self::group::RaftGroup;
+pub use self::group::{LeaderContact, RaftGroup};
 use self::io::LogWriter;
 pub use self::io::{retrive_snapshot, AddressResolver, ChannelManager};
 pub use self::monitor::*;
 pub use self::snap::SnapManager;
-pub use self::storage::{destory as destory_storage, write_initial_state};
+pub(crate) use self::storage::MessageExtTyped;
+pub use self::storage::{destory as destory_storage, read_raft_state, write_initial_state};
 use self::worker::RaftWorker;
 pub use self::worker::{RaftGroupState, StateObserver};
 use crate::raftgroup::io::start_purging_expired_files;
@@ -55,12 +60,41 @@ pub enum ReadPolicy {
     ReadIndex,
 }
 
+/// The election options shared by the raft groups of a node, they could be
+/// changed at runtime and take effect on the next tick of each group.
+#[derive(Debug)]
+struct ElectionOptions {
+    pre_vote: AtomicBool,
+    check_quorum: AtomicBool,
+}
+
+impl ElectionOptions {
+    fn new(cfg: &RaftConfig) -> Self {
+        ElectionOptions {
+            pre_vote: AtomicBool::new(cfg.pre_vote),
+            check_quorum: AtomicBool::new(cfg.check_quorum),
+        }
+    }
+
+    #[inline]
+    fn pre_vote(&self) -> bool {
+        self.pre_vote.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn check_quorum(&self) -> bool {
+        self.check_quorum.load(Ordering::Relaxed)
+    }
+}
+
 pub struct RaftManager {
     pub cfg: RaftConfig,
+    election: Arc<ElectionOptions>,
     engine: Arc<raft_engine::Engine>,
     log_writer: LogWriter,
     transport_mgr: Arc<ChannelManager>,
     snap_mgr: SnapManager,
+    round_scheduler: RoundScheduler,
     _task_handle: Option<JoinHandle<()>>,
 }
 
@@ -73,12 +107,15 @@ impl RaftManager {
     ) -> Result<Self> {
         let task_handle = start_purging_expired_files(engine.clone());
         let log_writer = LogWriter::new(cfg.max_io_batch_size, engine.clone());
+        let election = Arc::new(ElectionOptions::new(&cfg));
         Ok(RaftManager {
             cfg,
+            election,
             engine,
             transport_mgr,
             snap_mgr,
             log_writer,
+            round_scheduler: RoundScheduler::default(),
             _task_handle: Some(task_handle),
         })
     }
@@ -93,11 +130,52 @@ impl RaftManager {
         &self.snap_mgr
     }
 
+    /// Change the election options of the raft groups, the absent ones are
+    /// kept.
+    pub fn configure_election(&self, pre_vote: Option<bool>, check_quorum: Option<bool>) {
+        if let Some(pre_vote) = pre_vote {
+            self.election.pre_vote.store(pre_vote, Ordering::Relaxed);
+        }
+        if let Some(check_quorum) = check_quorum {
+            self.election.check_quorum.store(check_quorum, Ordering::Relaxed);
+        }
+    }
+
+    /// The election options applied by the raft groups, returns `(pre_vote,
+    /// check_quorum)`.
+    #[inline]
+    pub fn election_options(&self) -> (bool, bool) {
+        (self.election.pre_vote(), self.election.check_quorum())
+    }
+
     #[inline]
     pub async fn list_groups(&self) -> Vec<u64> {
         self.engine.raft_groups()
     }
 
+    /// Read at most `limit` recent raft entries of the replica, in the
+    /// ascending order of index. The entries might be compacted concurrently,
+    /// so there is no guarantee that the last entries are returned.
+    pub fn recent_entries(&self, replica_id: u64, limit: usize) -> Result<Vec<Entry>> {
+        let (Some(first_index), Some(last_index)) =
+            (self.engine.first_index(replica_id), self.engine.last_index(replica_id))
+        else {
+            return Ok(vec![]);
+        };
+        let begin = std::cmp::max(first_index, (last_index + 1).saturating_sub(limit as u64));
+        let mut entries = vec![];
+        if begin <= last_index {
+            self.engine.fetch_entries_to::<MessageExtTyped>(
+                replica_id,
+                begin,
+                last_index + 1,
+                None,
+                &mut entries,
+            )?;
+        }
+        Ok(entries)
+    }
+
     pub async fn start_raft_group<M: 'static + StateMachine>(
         &self,
         group_id: u64,
@@ -109,7 +187,7 @@ impl RaftManager {
     ) -> Result<RaftGroup> {
         let worker =
             RaftWorker::open(group_id, replica_id, node_id, state_machine, self, observer).await?;
-        let raft_group = RaftGroup::open(worker.request_sender());
+        let raft_group = RaftGroup::open(worker.request_sender(), worker.leader_contact());
         let log_writer = self.log_writer.clone();
         let task_handle = sekas_runtime::spawn(async move {
             if let Err(err) = worker.run(log_writer).await {
@@ -130,7 +208,9 @@ fn encode_to_conf_change(change_replicas: ChangeReplicas) -> ConfChangeV2 {
         let change_type = match ChangeReplicaType::from_i32(c.change_type) {
             Some(ChangeReplicaType::Add) => ConfChangeType::AddNode,
             Some(ChangeReplicaType::Remove) => ConfChangeType::RemoveNode,
-            Some(ChangeReplicaType::AddLearner) => ConfChangeType::AddLearnerNode,
+            Some(ChangeReplicaType::AddLearner | ChangeReplicaType::AddReadReplica) => {
+                ConfChangeType::AddLearnerNode
+            }
             None => panic!("such change replica operation isn't supported"),
         };
         conf_changes
@@ -144,7 +224,7 @@ fn encode_to_conf_change(change_replicas: ChangeReplicas) -> ConfChangeV2 {
     }
 }
 
-fn decode_from_conf_change(conf_change: &ConfChangeV2) -> ChangeReplicas {
+pub(crate) fn decode_from_conf_change(conf_change: &ConfChangeV2) -> ChangeReplicas {
     use prost::Message;
 
     ChangeReplicas::decode(&*conf_change.context)
@@ -159,7 +239,7 @@ pub fn conf_state_from_group_descriptor(desc: &GroupDesc) -> ConfState {
             ReplicaRole::Voter => {
                 cs.voters.push(replica.id);
             }
-            ReplicaRole::Learner => {
+            ReplicaRole::Learner | ReplicaRole::ReadReplica => {
                 cs.learners.push(replica.id);
             }
             ReplicaRole::IncomingVoter => {
@@ -13,19 +13,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+
 use futures::channel::oneshot;
-use log::{info, trace};
+use log::{info, trace, warn};
 use raft::prelude::*;
-use raft::{ConfChangeI, StateRole, Storage as RaftStorage};
+use raft::{
+    ConfChangeI, GetEntriesContext, ProgressState, StateRole, Storage as RaftStorage,
+    CAMPAIGN_TRANSFER, INVALID_ID,
+};
 use raft_engine::LogBatch;
-use sekas_api::server::v1::RaftRole;
+use sekas_api::server::v1::{ApplyQuarantine, QuarantineAction, RaftRole};
+use sekas_runtime::time::Instant;
 
 use super::applier::{Applier, ReplicaCache};
 use super::fsm::StateMachine;
+use super::metrics::*;
 use super::monitor::{record_perf_point, AdvancePerfContext};
 use super::snap::apply::apply_snapshot;
 use super::storage::Storage;
-use super::{RaftManager, SnapManager};
+use super::{ElectionOptions, RaftManager, SnapManager};
 use crate::error::BusyReason;
 use crate::{Error, Result};
 
@@ -58,7 +65,11 @@ pub(super) trait AdvanceTemplate {
 
     fn mut_replica_cache(&mut self) -> &mut ReplicaCache;
 
-    fn apply_snapshot<M: StateMachine>(&mut self, applier: &mut Applier<M>, snapshot: &Snapshot);
+    fn apply_snapshot<M: StateMachine>(
+        &mut self,
+        applier: &mut Applier<M>,
+        snapshot: &Snapshot,
+    ) -> Result<()>;
 }
 
 pub struct RaftNode<M: StateMachine> {
@@ -70,6 +81,21 @@ pub struct RaftNode<M: StateMachine> {
 
     raw_node: RawNode<Storage>,
     applier: Applier<M>,
+    election: Arc<ElectionOptions>,
+
+    /// The progress of replaying the committed entries after the node is
+    /// opened, it is taken once all of them are applied.
+    recovery: Option<RecoveryProgress>,
+
+    /// The term and role when the election timeout is randomized, see
+    /// `RaftNode::randomize_election_timeout` for details.
+    election_epoch: (u64, StateRole),
+}
+
+struct RecoveryProgress {
+    start: Instant,
+    applied_index: u64,
+    committed_index: u64,
 }
 
 impl<M> RaftNode<M>
@@ -83,7 +109,8 @@ where
         state_machine: M,
     ) -> Result<Self> {
         let mut applier = Applier::new(group_id, state_machine);
-        try_apply_fresh_snapshot(replica_id, &mgr.snap_mgr, &mut applier).await;
+        applier.set_max_entries_per_round(mgr.cfg.max_entries_per_round);
+        try_apply_fresh_snapshot(replica_id, &mgr.snap_mgr, &mut applier).await?;
 
         let cfg = &mgr.cfg;
         let applied = applier.flushed_index();
@@ -101,14 +128,26 @@ where
         try_reset_storage_state(replica_id, &mgr.snap_mgr, &mgr.engine, &mut storage).await?;
 
         let config = cfg.to_raft_config(replica_id, applied);
-        Ok(RaftNode {
+        let raw_node = RawNode::with_default_logger(&config, storage)?;
+        let recovery = RecoveryProgress {
+            start: Instant::now(),
+            applied_index: applied,
+            committed_index: raw_node.raft.raft_log.committed,
+        };
+        let mut node = RaftNode {
             group_id,
             lease_read_requests: Vec::default(),
             read_index_requests: Vec::default(),
             read_states: Vec::default(),
-            raw_node: RawNode::with_default_logger(&config, storage)?,
+            raw_node,
             applier,
-        })
+            election: mgr.election.clone(),
+            recovery: Some(recovery),
+            election_epoch: (u64::MAX, StateRole::Follower),
+        };
+        node.randomize_election_timeout();
+        node.try_finish_recovery();
+        Ok(node)
     }
 
     pub fn propose(
@@ -169,6 +208,8 @@ where
         // See `raft-rs/src/raft.rs`:`step_leader` for details.
         if self.raw_node.raft.state != StateRole::Leader {
             Err(Error::NotLeader(self.group_id, self.raw_node.raft.term, None))
+        } else if self.applier.quarantine().is_some() {
+            Err(self.applier.quarantined_error())
         } else if self.raw_node.raft.lead_transferee.is_some() {
             Err(Error::ServiceIsBusy(BusyReason::Transfering))
         } else if check_config_change && self.has_pending_config_change() {
@@ -200,11 +241,20 @@ where
 
     #[inline]
     pub fn tick(&mut self) {
+        // Apply the election options changed at runtime.
+        let raft = &mut self.raw_node.raft;
+        raft.pre_vote = self.election.pre_vote();
+        raft.check_quorum = self.election.check_quorum();
         self.raw_node.tick();
+        self.randomize_election_timeout();
     }
 
     #[inline]
     pub fn step(&mut self, msg: Message) -> Result<(), raft::Error> {
+        if self.is_vote_in_lease(&msg) {
+            // It is ignored by raft, see `raft-rs/src/raft.rs`:`step` for details.
+            RAFTGROUP_ELECTION_PREVENTED_TOTAL.in_lease.inc();
+        }
         if msg.get_msg_type() == MessageType::MsgSnapStatus {
             self.raw_node.report_snapshot(
                 msg.from,
@@ -212,14 +262,57 @@ where
             );
             Ok(())
         } else {
-            match self.raw_node.step(msg) {
+            let result = match self.raw_node.step(msg) {
                 Ok(()) | Err(raft::Error::StepPeerNotFound) => Ok(()),
                 Err(e) => Err(e),
-            }
+            };
+            self.randomize_election_timeout();
+            result
         }
     }
 
+    /// Raft randomizes the election timeout by the thread local generator
+    /// whenever the term or role changes, it is replaced by the one of the
+    /// simulation, so that the election timeouts are replayed by the seed.
+    fn randomize_election_timeout(&mut self) {
+        if !sekas_runtime::sim::is_enabled() {
+            return;
+        }
+        let raft = &mut self.raw_node.raft;
+        let epoch = (raft.term, raft.state);
+        if self.election_epoch != epoch {
+            self.election_epoch = epoch;
+            // The range is `[election_tick, 2 * election_tick)`, see
+            // `RaftConfig::to_raft_config` for details.
+            let election_tick = raft.election_timeout();
+            raft.set_randomized_election_timeout(sekas_runtime::sim::rng::gen_range(
+                election_tick..2 * election_tick,
+            ));
+        }
+    }
+
+    /// Whether the message is a vote request ignored since the replica has
+    /// heard from a live leader within an election timeout.
+    fn is_vote_in_lease(&self, msg: &Message) -> bool {
+        let raft = &self.raw_node.raft;
+        matches!(msg.get_msg_type(), MessageType::MsgRequestVote | MessageType::MsgRequestPreVote)
+            && msg.term > raft.term
+            && msg.context != CAMPAIGN_TRANSFER
+            && raft.check_quorum
+            && raft.leader_id != INVALID_ID
+            && raft.election_elapsed < raft.election_timeout()
+    }
+
     fn advance_read_requests(&mut self) {
+        if self.applier.quarantine().is_some() {
+            let lease_read_requests = std::mem::take(&mut self.lease_read_requests);
+            let read_index_requests = std::mem::take(&mut self.read_index_requests);
+            for req in lease_read_requests.into_iter().chain(read_index_requests) {
+                req.send(Err(self.applier.quarantined_error())).unwrap_or_default();
+            }
+            return;
+        }
+
         if !self.lease_read_requests.is_empty() {
             let requests = std::mem::take(&mut self.lease_read_requests);
             if self.raw_node.raft.state != StateRole::Leader {
@@ -242,9 +335,11 @@ where
         }
     }
 
+    /// Whether the raft node is ready, or there are committed entries pending
+    /// to apply.
     #[inline]
     pub fn has_ready(&mut self) -> bool {
-        self.raw_node.has_ready()
+        self.raw_node.has_ready() || self.applier.has_pending_entries()
     }
 
     /// Advance raft node, persist, apply entries and send messages.
@@ -254,10 +349,17 @@ where
         template: &mut impl AdvanceTemplate,
     ) -> Option<WriteTask> {
         self.advance_read_requests();
+        self.step_down_if_quarantined();
         if !self.raw_node.has_ready() {
             if !self.read_states.is_empty() {
                 self.applier.apply_read_states(std::mem::take(&mut self.read_states));
             }
+            if self.applier.has_pending_entries() {
+                self.apply_committed_entries(perf_ctx, template, vec![]);
+                if self.recovery.is_some() {
+                    self.try_finish_recovery();
+                }
+            }
             return None;
         }
 
@@ -338,6 +440,24 @@ where
         self.raw_node.raft.raft_log.committed
     }
 
+    /// Whether this replica is the leader, but the voters replicating entries
+    /// don't form a quorum, eg the others are waiting for snapshots or
+    /// unreachable.
+    pub fn is_quorum_lacking(&self) -> bool {
+        let raft = &self.raw_node.raft;
+        if raft.state != StateRole::Leader {
+            return false;
+        }
+        let prs = raft.prs();
+        let voters = prs.conf().to_conf_state().voters;
+        let num_replicating = voters
+            .iter()
+            .filter_map(|id| prs.get(*id))
+            .filter(|pr| pr.state == ProgressState::Replicate)
+            .count();
+        num_replicating < raft::majority(voters.len())
+    }
+
     fn handle_apply(
         &mut self,
         perf_ctx: &mut AdvancePerfContext,
@@ -352,23 +472,161 @@ where
             self.applier.apply_read_states(ready.take_read_states());
         }
 
-        if !ready.committed_entries().is_empty() {
+        if !ready.committed_entries().is_empty() || self.applier.has_pending_entries() {
             trace!("{} apply committed entries {}", self.group_id, ready.committed_entries().len());
-            let replica_cache = template.mut_replica_cache();
-            let applied = self.applier.apply_entries(
-                &mut perf_ctx.applier,
-                &mut self.raw_node,
-                replica_cache,
-                ready.take_committed_entries(),
+            self.apply_committed_entries(perf_ctx, template, ready.take_committed_entries());
+        }
+
+        if !ready.snapshot().is_empty() {
+            if let Err(err) = template.apply_snapshot(&mut self.applier, ready.snapshot()) {
+                self.quarantine_corruption(format!("apply snapshot: {err}"));
+            }
+        }
+
+        if self.recovery.is_some() {
+            self.try_finish_recovery();
+        }
+    }
+
+    /// Apply the committed entries, after the pending ones of the former
+    /// rounds.
+    fn apply_committed_entries(
+        &mut self,
+        perf_ctx: &mut AdvancePerfContext,
+        template: &mut impl AdvanceTemplate,
+        committed_entries: Vec<Entry>,
+    ) {
+        let replica_cache = template.mut_replica_cache();
+        let applied = self.applier.apply_entries(
+            &mut perf_ctx.applier,
+            &mut self.raw_node,
+            replica_cache,
+            committed_entries,
+        );
+        self.raw_node.advance_apply_to(applied);
+
+        let last_applied_index = self.applier.applied_index();
+        self.raw_node.mut_store().post_apply(last_applied_index);
+    }
+
+    /// Resolve the quarantine of the applier, returns the quarantine remained.
+    ///
+    /// The entries delivered since the poisoned one are fetched from the raft
+    /// log and applied again, unless the replica is rebuilt from a snapshot.
+    pub(super) fn resolve_quarantine(
+        &mut self,
+        replica_cache: &mut ReplicaCache,
+        action: QuarantineAction,
+        expected_index: u64,
+    ) -> Result<Option<ApplyQuarantine>> {
+        let Some(quarantine) = self.applier.quarantine().cloned() else {
+            return Ok(None);
+        };
+        if action == QuarantineAction::Rebuild {
+            info!(
+                "group {} requests a snapshot to rebuild the replica quarantined at entry {}",
+                self.group_id, quarantine.index
             );
-            self.raw_node.advance_apply_to(applied);
+            self.raw_node.request_snapshot().map_err(|_| {
+                Error::InvalidArgument(format!(
+                    "the snapshot request of group {} is dropped, only a follower of a leader could request it",
+                    self.group_id
+                ))
+            })?;
+            return Ok(Some(quarantine));
+        }
 
-            let last_applied_index = self.applier.applied_index();
-            self.raw_node.mut_store().post_apply(last_applied_index);
+        let low = self.applier.applied_index() + 1;
+        let high = self.applier.last_delivered_index() + 1;
+        let mut entries =
+            self.raw_node.raft.raft_log.slice(low, high, None, GetEntriesContext::empty(false))?;
+        if action == QuarantineAction::Skip {
+            if quarantine.index != expected_index {
+                return Err(Error::InvalidArgument(format!(
+                    "the replica of group {} is quarantined at entry {}, but {expected_index} is expected",
+                    self.group_id, quarantine.index
+                )));
+            }
+            let Some(entry) = entries.iter_mut().find(|e| e.index == quarantine.index) else {
+                return Err(Error::InvalidData(format!(
+                    "the entry {} of group {} is not found",
+                    quarantine.index, self.group_id
+                )));
+            };
+            if entry.get_entry_type() != EntryType::EntryNormal {
+                return Err(Error::InvalidArgument(format!(
+                    "the entry {} of group {} changes the config, rebuild the replica instead",
+                    quarantine.index, self.group_id
+                )));
+            }
+            warn!(
+                "group {} skips the poisoned entry {} term {}, the replica might diverge",
+                self.group_id, quarantine.index, quarantine.term
+            );
+            entry.data = Default::default();
         }
 
-        if !ready.snapshot().is_empty() {
-            template.apply_snapshot(&mut self.applier, ready.snapshot());
+        let applied =
+            self.applier.reapply_quarantined_entries(&mut self.raw_node, replica_cache, entries)?;
+        self.raw_node.advance_apply_to(applied);
+        self.raw_node.mut_store().post_apply(applied);
+        Ok(self.applier.quarantine().cloned())
+    }
+
+    /// Quarantine the replica since the local storage is found corrupted, see
+    /// [`Applier::quarantine_corruption`].
+    pub(super) fn quarantine_corruption(&mut self, message: String) {
+        let term = self.raw_node.raft.term;
+        self.applier.quarantine_corruption(term, message);
+    }
+
+    /// The quarantined leader can't serve any requests, transfer the
+    /// leadership to the voter which matches the most entries.
+    fn step_down_if_quarantined(&mut self) {
+        let raft = &self.raw_node.raft;
+        if self.applier.quarantine().is_none()
+            || raft.state != StateRole::Leader
+            || raft.lead_transferee.is_some()
+        {
+            return;
+        }
+
+        let prs = raft.prs();
+        let transferee = prs
+            .conf()
+            .to_conf_state()
+            .voters
+            .into_iter()
+            .filter(|id| *id != raft.id)
+            .filter_map(|id| prs.get(id).map(|pr| (pr.matched, id)))
+            .max();
+        if let Some((_, transferee)) = transferee {
+            info!(
+                "group {} transfers the leadership to {transferee} since the leader is quarantined",
+                self.group_id
+            );
+            self.raw_node.transfer_leader(transferee);
+        }
+    }
+
+    /// Record the recovery metrics once the entries committed before opening
+    /// are applied.
+    fn try_finish_recovery(&mut self) {
+        let applied_index = self.applier.applied_index();
+        match &self.recovery {
+            Some(recovery) if recovery.committed_index <= applied_index => {}
+            _ => return,
+        }
+        let recovery = self.recovery.take().unwrap();
+        let replayed_entries = recovery.committed_index.saturating_sub(recovery.applied_index);
+        let elapsed = recovery.start.elapsed();
+        RAFTGROUP_RECOVERY_REPLAYED_ENTRIES.observe(replayed_entries as f64);
+        RAFTGROUP_RECOVERY_DURATION_SECONDS.observe(elapsed.as_secs_f64());
+        if replayed_entries > 0 {
+            info!(
+                "group {} replays {replayed_entries} entries in {elapsed:?} after opening",
+                self.group_id
+            );
         }
     }
 
@@ -445,7 +703,8 @@ async fn try_apply_fresh_snapshot<M>(
     replica_id: u64,
     snap_mgr: &SnapManager,
     applier: &mut Applier<M>,
-) where
+) -> Result<()>
+where
     M: StateMachine,
 {
     if let Some(info) = snap_mgr.latest_snap(replica_id) {
@@ -456,9 +715,10 @@ async fn try_apply_fresh_snapshot<M>(
                 apply_state.index, apply_state.term,
                 applier.flushed_index()
             );
-            apply_snapshot(replica_id, snap_mgr, applier, &info.to_raft_snapshot());
+            apply_snapshot(replica_id, snap_mgr, applier, &info.to_raft_snapshot())?;
         }
     }
+    Ok(())
 }
 
 async fn try_reset_storage_state(
@@ -498,7 +758,7 @@ mod tests {
     use std::sync::Arc;
 
     use raft_engine::*;
-    use sekas_api::server::v1::{GroupDesc, NodeDesc, ReplicaDesc, ReplicaRole};
+    use sekas_api::server::v1::{ApplyQuarantine, GroupDesc, NodeDesc, ReplicaDesc, ReplicaRole};
     use sekas_runtime::ExecutorOwner;
 
     use super::*;
@@ -527,10 +787,20 @@ mod tests {
             Ok(())
         }
 
+        fn discard_entry(&mut self) {}
+
         fn finish_plug(&mut self) -> crate::Result<()> {
             Ok(())
         }
 
+        fn quarantine(&mut self, _: Option<ApplyQuarantine>) -> crate::Result<()> {
+            Ok(())
+        }
+
+        fn quarantined(&self) -> Option<ApplyQuarantine> {
+            None
+        }
+
         fn apply_snapshot(&mut self, snap_dir: &std::path::Path) -> crate::Result<()> {
             self.current_snapshot = Some(snap_dir.to_owned());
             Ok(())
@@ -559,11 +829,16 @@ mod tests {
     where
         M: StateMachine,
     {
-        try_apply_fresh_snapshot(replica_id, snap_mgr, applier).await;
+        try_apply_fresh_snapshot(replica_id, snap_mgr, applier).await?;
         try_reset_storage_state(replica_id, snap_mgr, engine, storage).await
     }
 
-    fn create_snapshot(snap_mgr: &SnapManager, replica_id: u64, index: u64, term: u64) -> PathBuf {
+    async fn create_snapshot(
+        snap_mgr: &SnapManager,
+        replica_id: u64,
+        index: u64,
+        term: u64,
+    ) -> PathBuf {
         use prost::Message;
 
         let snap_dir = snap_mgr.create(replica_id);
@@ -575,7 +850,7 @@ mod tests {
             ..Default::default()
         };
         std::fs::write(data, meta.encode_to_vec()).unwrap();
-        snap_mgr.install(replica_id, &snap_dir, &meta);
+        snap_mgr.install(replica_id, &snap_dir, &meta).await.unwrap();
         snap_dir
     }
 
@@ -616,7 +891,7 @@ mod tests {
             assert!(applier.mut_state_machine().current_snapshot.is_none());
 
             // 2. recovery snapshot
-            let snap = create_snapshot(&snap_mgr, 1, 123, 1);
+            let snap = create_snapshot(&snap_mgr, 1, 123, 1).await;
 
             try_recover_snapshot(1, &snap_mgr, &engine, &mut storage, &mut applier).await.unwrap();
             assert!(
@@ -665,7 +940,7 @@ mod tests {
             storage.compact_to(51);
 
             // create staled snapshot.
-            create_snapshot(&snap_mgr, 1, 10, 1);
+            create_snapshot(&snap_mgr, 1, 10, 1).await;
 
             try_recover_snapshot(1, &snap_mgr, &engine, &mut storage, &mut applier).await.unwrap();
             assert!(applier.mut_state_machine().current_snapshot.is_none());
@@ -723,7 +998,7 @@ mod tests {
             let state_machine = SimpleStateMachine { flushed_index: 123, current_snapshot: None };
             let mut applier = Applier::new(1, state_machine);
 
-            create_snapshot(&snap_mgr, 1, 123, 123);
+            create_snapshot(&snap_mgr, 1, 123, 123).await;
 
             // case 1: snapshot exceeds log storage range.
             try_recover_snapshot(1, &snap_mgr, &engine, &mut storage, &mut applier).await.unwrap();
@@ -734,7 +1009,7 @@ mod tests {
             insert_entries(engine.clone(), &mut storage, vec![(124, 123), (125, 123), (126, 123)])
                 .await;
             applier.mut_state_machine().flushed_index = 125;
-            create_snapshot(&snap_mgr, 1, 125, 124);
+            create_snapshot(&snap_mgr, 1, 125, 124).await;
 
             try_recover_snapshot(1, &snap_mgr, &engine, &mut storage, &mut applier).await.unwrap();
             assert!(applier.mut_state_machine().current_snapshot.is_none());
@@ -777,10 +1052,20 @@ mod tests {
                 Ok(())
             }
 
+            fn discard_entry(&mut self) {}
+
             fn finish_plug(&mut self) -> crate::Result<()> {
                 Ok(())
             }
 
+            fn quarantine(&mut self, _: Option<ApplyQuarantine>) -> crate::Result<()> {
+                Ok(())
+            }
+
+            fn quarantined(&self) -> Option<ApplyQuarantine> {
+                None
+            }
+
             fn apply_snapshot(&mut self, data: &std::path::Path) -> crate::Result<()> {
                 use prost::Message;
 
@@ -826,15 +1111,18 @@ mod tests {
             let snap_dir = dir.path().join("snap");
             let snap_mgr = SnapManager::new(snap_dir.clone());
             let resolver = Arc::new(MockedAddressResolver {});
-            let transport_mgr = Arc::new(ChannelManager::new(resolver, RaftRouteTable::new()));
+            let transport_mgr = Arc::new(ChannelManager::new("", resolver, RaftRouteTable::new()));
             let log_writer = LogWriter::new(64 << 10, engine.clone());
+            let raft_cfg = RaftConfig { max_entries_per_round: 8, ..Default::default() };
             let raft_mgr = RaftManager {
-                cfg: RaftConfig::default(),
+                cfg: raft_cfg.clone(),
+                election: Arc::new(ElectionOptions::new(&raft_cfg)),
                 engine: engine.clone(),
                 transport_mgr,
                 snap_mgr: snap_mgr.clone(),
                 log_writer,
                 _task_handle: None,
+                round_scheduler: Default::default(),
             };
 
             // 1. initial storage with log entries in [0, 100), all entries are committed.
@@ -850,7 +1138,7 @@ mod tests {
 
             // 2. create snapshot with index 50 term 1.
             // See storage::write_initial_state for details about term 1.
-            create_snapshot(&snap_mgr, 1, 50, 1);
+            create_snapshot(&snap_mgr, 1, 50, 1).await;
 
             // 3. recover node from snapshot. and apply all entries.
             let state_machine = CheckIndexStateMachine { flushed_index: 0 };
@@ -877,9 +1165,9 @@ mod tests {
                     &mut self,
                     applier: &mut Applier<M>,
                     snapshot: &Snapshot,
-                ) {
+                ) -> Result<()> {
                     use crate::raftgroup::snap::apply::apply_snapshot;
-                    apply_snapshot(1, &self.snap_mgr, applier, snapshot);
+                    apply_snapshot(1, &self.snap_mgr, applier, snapshot)
                 }
             }
 
@@ -887,12 +1175,16 @@ mod tests {
                 snap_mgr: snap_mgr.clone(),
                 replica_cache: ReplicaCache::default(),
             };
-            let mut perf_ctx = AdvancePerfContext::default();
-            while let Some(task) = node.advance(&mut perf_ctx, &mut template) {
-                let mut batch = LogBatch::default();
-                node.mut_store().write(&mut batch, &task).expect("write log batch");
-                engine.write(&mut batch, false).unwrap();
-                node.post_advance(&mut perf_ctx, task.post_ready(), &mut template)
+            // The entries applied in a round are bounded.
+            while node.has_ready() {
+                let mut perf_ctx = AdvancePerfContext::default();
+                if let Some(task) = node.advance(&mut perf_ctx, &mut template) {
+                    let mut batch = LogBatch::default();
+                    node.mut_store().write(&mut batch, &task).expect("write log batch");
+                    engine.write(&mut batch, false).unwrap();
+                    node.post_advance(&mut perf_ctx, task.post_ready(), &mut template)
+                }
+                assert!(perf_ctx.applier.num_committed <= 8);
             }
             assert!(node.mut_state_machine().flushed_index() >= 100);
         });
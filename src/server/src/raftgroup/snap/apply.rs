@@ -11,28 +11,73 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::path::{Path, PathBuf};
+
 use raft::prelude::Snapshot;
 
-use super::{SnapManager, SNAP_DATA};
+use super::{SnapManager, SnapshotInfo, SNAP_DATA, SNAP_PLAIN};
+use crate::cipher::DataCipher;
 use crate::raftgroup::applier::Applier;
 use crate::raftgroup::metrics::*;
 use crate::raftgroup::StateMachine;
-use crate::record_latency;
+use crate::{record_latency, Error, Result};
 
+/// Apply the snapshot to the state machine. The error is returned instead of
+/// panicking if the snapshot couldn't be opened, eg. it is tampered.
 pub fn apply_snapshot<M: StateMachine>(
     replica_id: u64,
     snap_mgr: &SnapManager,
     applier: &mut Applier<M>,
     snapshot: &Snapshot,
-) {
+) -> Result<()> {
     record_latency!(take_apply_snapshot_metrics());
     let snap_id = &snapshot.data;
     let snap_info = snap_mgr
         .lock_snap(replica_id, snap_id)
         .expect("The snapshot should does not be gc before apply");
-    // TODO(walter) check snapshot data integrity.
-    let snap_dir = snap_info.base_dir.join(SNAP_DATA);
-    applier
-        .apply_snapshot(&snap_dir)
-        .expect("apply snapshot must success, because the data integrity might broken");
+    if snap_info.meta.key_id.is_empty() {
+        let snap_dir = snap_info.base_dir.join(SNAP_DATA);
+        return applier.apply_snapshot(&snap_dir);
+    }
+
+    let cipher = snap_mgr.cipher().ok_or_else(|| {
+        Error::KeyNotFound(format!("{}, the encryption is disabled", snap_info.meta.key_id))
+    })?;
+    let snap_dir = decrypt_snapshot(cipher.as_ref(), &snap_info)?;
+    let result = applier.apply_snapshot(&snap_dir);
+    remove_path(&snap_dir).unwrap_or_default();
+    result
+}
+
+/// Decrypt the snapshot files into a scratch dir, and returns the path which
+/// mirrors the `DATA` of the snapshot. The snapshot files are left untouched,
+/// since the snapshot might be sent to other replicas.
+pub(super) fn decrypt_snapshot(cipher: &dyn DataCipher, info: &SnapshotInfo) -> Result<PathBuf> {
+    let plain_dir = info.base_dir.join(SNAP_PLAIN);
+    remove_path(&plain_dir)?;
+    for file in &info.meta.files {
+        let Ok(relative_path) = Path::new(&file.name).strip_prefix(SNAP_DATA) else {
+            return Err(Error::InvalidData(format!("snapshot file {}", file.name)));
+        };
+        let target = if relative_path.as_os_str().is_empty() {
+            plain_dir.clone()
+        } else {
+            plain_dir.join(relative_path)
+        };
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let source = info.base_dir.join(&file.name);
+        crate::cipher::decrypt_file(cipher, &info.meta.key_id, file.nonce, &source, &target)?;
+    }
+    Ok(plain_dir)
+}
+
+fn remove_path(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    } else if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
 }
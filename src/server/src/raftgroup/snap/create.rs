@@ -18,9 +18,11 @@ use futures::channel::mpsc;
 use futures::SinkExt;
 use log::{error, info};
 use prost::Message;
+use rand::rngs::OsRng;
+use rand::Rng;
 use sekas_runtime::JoinHandle;
 
-use super::{SnapManager, SNAP_DATA};
+use super::{CreatingState, SnapManager, SNAP_DATA};
 use crate::raftgroup::fsm::SnapshotBuilder;
 use crate::raftgroup::metrics::*;
 use crate::raftgroup::snap::{SNAP_META, SNAP_TEMP};
@@ -37,7 +39,7 @@ pub fn dispatch_creating_snap_task(
 ) -> JoinHandle<()> {
     let builder = state_machine.snapshot_builder();
     sekas_runtime::spawn(async move {
-        match create_snapshot(replica_id, &snap_mgr, builder).await {
+        match create_or_wait_snapshot(replica_id, &snap_mgr, builder).await {
             Ok(_) => {
                 info!("replica {replica_id} create snapshot success");
             }
@@ -50,6 +52,30 @@ pub fn dispatch_creating_snap_task(
     })
 }
 
+/// Create new snapshot and returns snapshot id. If the snapshot of the replica
+/// is being created by another task, waits for it instead of creating a new
+/// one.
+pub(super) async fn create_or_wait_snapshot(
+    replica_id: u64,
+    snap_mgr: &SnapManager,
+    builder: Box<dyn SnapshotBuilder>,
+) -> Result<Vec<u8>> {
+    let guard = match snap_mgr.begin_creating(replica_id) {
+        CreatingState::Owned(guard) => guard,
+        CreatingState::Waiting(receiver) => {
+            info!("replica {replica_id} wait for the snapshot being created");
+            RAFTGROUP_CREATE_SNAPSHOT_AVOIDED_TOTAL.in_progress.inc();
+            return match receiver.await {
+                Ok(Some(snapshot_id)) => Ok(snapshot_id),
+                _ => Err(Error::Canceled),
+            };
+        }
+    };
+    let snapshot_id = create_snapshot(replica_id, snap_mgr, builder).await?;
+    guard.finish(snapshot_id.clone());
+    Ok(snapshot_id)
+}
+
 /// Create new snapshot and returns snapshot id.
 pub(super) async fn create_snapshot(
     replica_id: u64,
@@ -66,7 +92,7 @@ pub(super) async fn create_snapshot(
         panic!("Checkpoint did not generate any data.");
     }
 
-    let mut files = vec![];
+    let mut paths = vec![];
     if data.is_dir() {
         for entry in std::fs::read_dir(data)? {
             let entry = entry?;
@@ -74,20 +100,42 @@ pub(super) async fn create_snapshot(
             if path.is_dir() {
                 panic!("Snapshot with hierarchical directories is not supported yet");
             }
-            files.push(read_file_meta(&path).await?);
+            paths.push(path);
         }
     } else {
-        files.push(read_file_meta(&data).await?);
+        paths.push(data);
     }
 
-    let snap_meta =
-        SnapshotMeta { apply_state: Some(apply_state), group_desc: Some(descriptor), files };
+    let key_id = snap_mgr.cipher().map(|cipher| cipher.current_key_id()).unwrap_or_default();
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let mut nonce = 0;
+        if let Some(cipher) = snap_mgr.cipher() {
+            // The checksum is computed over the encrypted content, so it could be
+            // verified without the key. The nonce never comes from the seeded rng
+            // of the simulation.
+            nonce = OsRng.gen();
+            let sealed = path.with_extension("sealed");
+            crate::cipher::encrypt_file(cipher.as_ref(), &key_id, nonce, &path, &sealed)?;
+            std::fs::rename(&sealed, &path)?;
+        }
+        let mut file = read_file_meta(&path).await?;
+        file.nonce = nonce;
+        files.push(file);
+    }
+
+    let snap_meta = SnapshotMeta {
+        apply_state: Some(apply_state),
+        group_desc: Some(descriptor),
+        files,
+        key_id,
+    };
 
     stable_snapshot_meta(&snap_dir, &snap_meta).await?;
 
     info!("replica {replica_id} create snapshot {} success", snap_dir.display());
 
-    Ok(snap_mgr.install(replica_id, &snap_dir, &snap_meta))
+    snap_mgr.install(replica_id, &snap_dir, &snap_meta).await
 }
 
 pub(super) async fn stable_snapshot_meta(base_dir: &Path, snap_meta: &SnapshotMeta) -> Result<()> {
@@ -111,33 +159,9 @@ pub(super) async fn stable_snapshot_meta(base_dir: &Path, snap_meta: &SnapshotMe
 }
 
 async fn read_file_meta(filename: &Path) -> Result<SnapshotFile> {
-    use std::fs::OpenOptions;
-    use std::io::{ErrorKind, Read};
-
-    let mut buf = vec![0; 4096];
-    let mut file = OpenOptions::new().read(true).open(filename)?;
-    let mut hasher = crc32fast::Hasher::new();
-
-    let mut size: u64 = 0;
-    let mut count = 0;
-    loop {
-        let n = match file.read(&mut buf) {
-            Ok(n) => n,
-            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-            Err(e) => return Err(e.into()),
-        };
-        if n == 0 {
-            break;
-        }
+    use std::io::ErrorKind;
 
-        size += n as u64;
-        count += 1;
-        hasher.update(&buf[..n]);
-        if count % 10 == 0 {
-            sekas_runtime::yield_now().await;
-        }
-    }
-    let crc32 = hasher.finalize();
+    let (crc32, size) = super::verify::checksum_file(filename).await?;
 
     let name = if filename.file_name().unwrap() == SNAP_DATA {
         Path::new(SNAP_DATA).to_path_buf()
@@ -151,5 +175,5 @@ async fn read_file_meta(filename: &Path) -> Result<SnapshotFile> {
             format!("{} is not a valid UTF-8 encoding, the name of snapshot data requires UTF-8 encoding", name.display()),
         )));
     };
-    Ok(SnapshotFile { name: name.to_owned(), crc32, size })
+    Ok(SnapshotFile { name: name.to_owned(), crc32, size, nonce: 0 })
 }
@@ -14,12 +14,13 @@
 
 use std::ffi::OsString;
 use std::fs::File;
+use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use futures::channel::mpsc;
 use futures::{SinkExt, StreamExt};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use raft::eraftpb::Message;
 use sekas_api::server::v1::ReplicaDesc;
 use sekas_runtime::JoinHandle;
@@ -31,6 +32,9 @@ use crate::raftgroup::{retrive_snapshot, ChannelManager};
 use crate::serverpb::v1::{snapshot_chunk, SnapshotChunk, SnapshotFile, SnapshotMeta};
 use crate::{record_latency, Error, Result};
 
+/// The max times to fetch a snapshot if the received one is corrupted.
+const MAX_SNAPSHOT_FETCHES: usize = 3;
+
 struct PartialFile {
     meta: SnapshotFile,
     file: File,
@@ -70,6 +74,7 @@ impl SnapshotBuilder {
             Some(snapshot_chunk::Value::Meta(meta)) => {
                 self.meta.apply_state = meta.apply_state;
                 self.meta.group_desc = meta.group_desc;
+                self.meta.key_id = meta.key_id;
                 Ok(())
             }
             None => Ok(()),
@@ -137,17 +142,17 @@ impl PartialFile {
         self.file.sync_all()?;
 
         if self.size as u64 != self.meta.size {
-            return Err(Error::InvalidData(format!(
-                "invalid size of file, expect {}, but got {}",
-                self.meta.size, self.size
+            return Err(Error::SnapshotCorrupted(format!(
+                "invalid size of file {}, expect {}, but got {}",
+                self.meta.name, self.meta.size, self.size
             )));
         }
 
         let crc32 = self.crc32.finalize();
         if crc32 != self.meta.crc32 {
-            return Err(Error::InvalidData(format!(
-                "checksum is not equals, expect {}, but got {}",
-                self.meta.size, self.size
+            return Err(Error::SnapshotCorrupted(format!(
+                "checksum of file {} is not equals, expect {}, but got {}",
+                self.meta.name, self.meta.crc32, crc32
             )));
         }
 
@@ -191,15 +196,56 @@ async fn download_snap(
     assert!(msg.has_snapshot() && !msg.get_snapshot().is_empty());
     let snapshot = msg.get_snapshot();
     let snapshot_id = snapshot.data.clone();
-    let chunk_stream = retrive_snapshot(&tran_mgr, from_replica, snapshot_id).await?;
-    save_snapshot(&snap_mgr, replica_id, chunk_stream).await
+    fetch_and_save_snapshot(&snap_mgr, replica_id, || {
+        retrive_snapshot(&tran_mgr, from_replica.clone(), snapshot_id.clone())
+    })
+    .await
+}
+
+/// Fetch the snapshot and save it, the snapshot is fetched again if the
+/// received one is corrupted.
+pub(super) async fn fetch_and_save_snapshot<F, Fut, S>(
+    snap_mgr: &SnapManager,
+    replica_id: u64,
+    fetch: F,
+) -> Result<Vec<u8>>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<S>>,
+    S: futures::Stream<Item = Result<SnapshotChunk, tonic::Status>> + Unpin,
+{
+    let mut num_fetched = 0;
+    loop {
+        let chunk_stream = fetch().await?;
+        num_fetched += 1;
+        match save_snapshot(snap_mgr, replica_id, chunk_stream).await {
+            Err(Error::SnapshotCorrupted(msg)) if num_fetched < MAX_SNAPSHOT_FETCHES => {
+                warn!("replica {replica_id} fetch snapshot again since it is corrupted: {msg}");
+            }
+            result => return result,
+        }
+    }
 }
 
 pub(super) async fn save_snapshot<S>(
     snap_mgr: &SnapManager,
     replica_id: u64,
-    mut chunk_stream: S,
+    chunk_stream: S,
 ) -> Result<Vec<u8>>
+where
+    S: futures::Stream<Item = Result<SnapshotChunk, tonic::Status>> + Unpin,
+{
+    let (base_dir, snap_meta) = receive_snapshot(snap_mgr, replica_id, chunk_stream).await?;
+    snap_mgr.install(replica_id, &base_dir, &snap_meta).await
+}
+
+/// Receive the snapshot into a new snapshot dir, returns the dir and the meta
+/// of the received snapshot.
+pub(super) async fn receive_snapshot<S>(
+    snap_mgr: &SnapManager,
+    replica_id: u64,
+    mut chunk_stream: S,
+) -> Result<(PathBuf, SnapshotMeta)>
 where
     S: futures::Stream<Item = Result<SnapshotChunk, tonic::Status>> + Unpin,
 {
@@ -213,6 +259,9 @@ where
         snap_builder.append(chunk).await?;
     }
 
+    // Reject the snapshot which could not be decrypted, before it is installed.
+    snap_mgr.check_snapshot_key(&snap_builder.meta)?;
+
     let snap_meta = snap_builder.finish().await?;
-    Ok(snap_mgr.install(replica_id, &base_dir, &snap_meta))
+    Ok((base_dir, snap_meta))
 }
@@ -16,28 +16,39 @@
 pub mod apply;
 pub mod create;
 pub mod download;
+pub mod schedule;
 pub mod send;
+mod verify;
 
 use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use futures::channel::mpsc;
+use futures::channel::{mpsc, oneshot};
 use futures::StreamExt;
 use log::{error, info, warn};
 use raft::prelude::{Snapshot, SnapshotMetadata};
+use sekas_runtime::time::Instant;
 use sekas_runtime::JoinHandle;
 
 pub use self::create::dispatch_creating_snap_task;
 pub use self::download::dispatch_downloading_snap_task;
+pub use self::schedule::{SnapSendRecord, SnapSendScheduler};
+use crate::cipher::DataCipher;
 use crate::serverpb::v1::SnapshotMeta;
-use crate::Result;
+use crate::{Error, Result};
 
-const SNAP_DATA: &str = "DATA";
+pub(crate) const SNAP_DATA: &str = "DATA";
+const SNAP_PLAIN: &str = "PLAIN";
 const SNAP_TEMP: &str = "TEMP";
-const SNAP_META: &str = "META";
+pub(crate) const SNAP_META: &str = "META";
+const SNAP_VERIFIED: &str = "SNAP_VERIFIED";
+
+/// A snapshot younger than this is reused to serve the followers instead of
+/// creating a new one, as long as it covers the requested index.
+const SNAP_FRESH_INTERVALS: Duration = Duration::from_secs(60);
 
 #[derive(Debug)]
 pub enum RecycleSnapMode {
@@ -82,13 +93,39 @@ where
 struct SnapManagerShared {
     root_dir: PathBuf,
     min_keep_intervals: Duration,
+    fresh_intervals: Duration,
     _recycler_handle: Option<JoinHandle<()>>,
+    /// The cipher to encrypt the snapshot files, `None` if the encryption is
+    /// disabled.
+    cipher: Option<Arc<dyn DataCipher>>,
+    /// The scheduler of sending snapshots to the followers.
+    send_scheduler: SnapSendScheduler,
     inner: Mutex<SnapManagerInner>,
 }
 
 struct SnapManagerInner {
     sender: mpsc::UnboundedSender<(u64, PathBuf)>,
     replicas: HashMap<u64, ReplicaSnapManager>,
+    /// The replicas which are creating snapshot, with the waiters of the
+    /// created snapshot id.
+    creating: HashMap<u64, Vec<oneshot::Sender<Option<Vec<u8>>>>>,
+}
+
+/// The state of creating snapshot of a replica, see
+/// [`SnapManager::begin_creating`].
+pub enum CreatingState {
+    /// The caller should create the snapshot, the waiters are notified once
+    /// the guard is finished or dropped.
+    Owned(CreatingGuard),
+    /// The snapshot is being created by another task, the receiver yields the
+    /// id of the created snapshot, or `None` if the creating is failed.
+    Waiting(oneshot::Receiver<Option<Vec<u8>>>),
+}
+
+pub struct CreatingGuard {
+    replica_id: u64,
+    snapshot_id: Option<Vec<u8>>,
+    manager: SnapManager,
 }
 
 impl SnapManager {
@@ -99,13 +136,24 @@ impl SnapManager {
             shared: Arc::new(SnapManagerShared {
                 root_dir: dir,
                 min_keep_intervals: Duration::from_secs(0),
+                fresh_intervals: SNAP_FRESH_INTERVALS,
                 _recycler_handle: None,
-                inner: Mutex::new(SnapManagerInner { sender, replicas: HashMap::default() }),
+                cipher: None,
+                send_scheduler: SnapSendScheduler::default(),
+                inner: Mutex::new(SnapManagerInner {
+                    sender,
+                    replicas: HashMap::default(),
+                    creating: HashMap::default(),
+                }),
             }),
         }
     }
 
-    pub async fn recovery<P: AsRef<Path>>(root_dir: P) -> Result<SnapManager> {
+    pub async fn recovery<P: AsRef<Path>>(
+        root_dir: P,
+        cipher: Option<Arc<dyn DataCipher>>,
+        send_scheduler: SnapSendScheduler,
+    ) -> Result<SnapManager> {
         use prost::Message;
 
         let (mut sender, receiver) = mpsc::unbounded();
@@ -135,21 +183,58 @@ impl SnapManager {
                     }
                 };
 
+                if !contains_key(cipher.as_deref(), &snapshot_meta.key_id) {
+                    warn!(
+                        "replica {replica_id} recycles snap {index} since key {} is not found",
+                        snapshot_meta.key_id
+                    );
+                    sender.start_send((replica_id, snap_dir)).unwrap_or_default();
+                    continue;
+                }
+
+                // The snapshot verified before is not hashed again, unless the `META` is
+                // changed.
+                if !verify::is_verified(&snap_dir, &bytes)? {
+                    let result = verify::verify_snapshot(&snap_dir, &snapshot_meta)
+                        .await
+                        .and_then(|_| verify::stable_verified_marker(&snap_dir, &bytes));
+                    if let Err(err) = result {
+                        warn!("replica {replica_id} recycles snap {index} since verify: {err}");
+                        sender.start_send((replica_id, snap_dir)).unwrap_or_default();
+                        continue;
+                    }
+                }
+
+                let replica_mgr = replicas
+                    .entry(replica_id)
+                    .or_insert_with(|| ReplicaSnapManager::new(replica_id, replica_dir.clone()));
+                replica_mgr.next_snapshot_index =
+                    std::cmp::max(replica_mgr.next_snapshot_index, index as usize + 1);
+
+                let snapshot_id = snapshot_id(replica_id, &snapshot_meta);
+                if replica_mgr.contains(&snapshot_id) {
+                    warn!("replica {replica_id} recycles snap {index} since it is duplicated");
+                    sender.start_send((replica_id, snap_dir)).unwrap_or_default();
+                    continue;
+                }
+
                 info!("replica {replica_id} recovers snap {index}, dir {}", snap_dir.display());
 
-                let snapshot_id = format!("{}", index).as_bytes().to_owned();
+                // The age of the snapshot is kept across restarting, so that a stale
+                // snapshot is not considered fresh.
+                let created_at = std::fs::metadata(&meta_name)?
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.elapsed().ok())
+                    .and_then(|elapsed| Instant::now().checked_sub(elapsed))
+                    .unwrap_or_else(Instant::now);
                 let info = SnapshotInfo {
                     snapshot_id,
                     base_dir: snap_dir,
                     meta: snapshot_meta,
                     ref_count: 0,
-                    created_at: Instant::now(),
+                    created_at,
                 };
-                let replica_mgr = replicas
-                    .entry(replica_id)
-                    .or_insert_with(|| ReplicaSnapManager::new(replica_id, replica_dir.clone()));
-                replica_mgr.next_snapshot_index =
-                    std::cmp::max(replica_mgr.next_snapshot_index, index as usize);
                 replica_mgr.push(info);
                 num_snaps += 1;
             }
@@ -161,12 +246,38 @@ impl SnapManager {
             shared: Arc::new(SnapManagerShared {
                 root_dir: root_dir.to_owned(),
                 min_keep_intervals: Duration::from_secs(180),
+                fresh_intervals: SNAP_FRESH_INTERVALS,
                 _recycler_handle: Some(recycler_handle),
-                inner: Mutex::new(SnapManagerInner { sender, replicas }),
+                cipher,
+                send_scheduler,
+                inner: Mutex::new(SnapManagerInner {
+                    sender,
+                    replicas,
+                    creating: HashMap::default(),
+                }),
             }),
         })
     }
 
+    #[inline]
+    pub fn cipher(&self) -> Option<&Arc<dyn DataCipher>> {
+        self.shared.cipher.as_ref()
+    }
+
+    #[inline]
+    pub fn send_scheduler(&self) -> &SnapSendScheduler {
+        &self.shared.send_scheduler
+    }
+
+    /// Ensure the key which encrypts the snapshot is available, so that the
+    /// snapshot could be applied.
+    pub fn check_snapshot_key(&self, meta: &SnapshotMeta) -> Result<()> {
+        if !contains_key(self.shared.cipher.as_deref(), &meta.key_id) {
+            return Err(Error::KeyNotFound(meta.key_id.clone()));
+        }
+        Ok(())
+    }
+
     /// Mark group as creating, and return a dir to save snapshot.
     pub fn create(&self, replica_id: u64) -> PathBuf {
         let mut inner = self.shared.inner.lock().unwrap();
@@ -177,10 +288,51 @@ impl SnapManager {
             .next_snapshot_dir()
     }
 
+    /// Register the creating of a snapshot of the replica. Only the first
+    /// caller creates the snapshot, the others wait for the result of it.
+    pub fn begin_creating(&self, replica_id: u64) -> CreatingState {
+        let mut inner = self.shared.inner.lock().unwrap();
+        if let Some(waiters) = inner.creating.get_mut(&replica_id) {
+            let (sender, receiver) = oneshot::channel();
+            waiters.push(sender);
+            return CreatingState::Waiting(receiver);
+        }
+        inner.creating.insert(replica_id, vec![]);
+        CreatingState::Owned(CreatingGuard { replica_id, snapshot_id: None, manager: self.clone() })
+    }
+
     /// Install a snapshot and returns snapshot id.
-    pub fn install(&self, replica_id: u64, dir_name: &Path, meta: &SnapshotMeta) -> Vec<u8> {
-        // TODO(walter) check snapshot data integrity.
+    ///
+    /// The files of the snapshot are verified against the meta before it is
+    /// installed, `Error::SnapshotCorrupted` is returned and the snapshot is
+    /// recycled if any of them is mismatched.
+    ///
+    /// The snapshot id is derived from the replica id and the apply index, if a
+    /// snapshot with the same id is installed, the new one is recycled and the
+    /// id of the installed one is returned.
+    pub async fn install(
+        &self,
+        replica_id: u64,
+        dir_name: &Path,
+        meta: &SnapshotMeta,
+    ) -> Result<Vec<u8>> {
+        use prost::Message;
+
+        let result = verify::verify_snapshot(dir_name, meta)
+            .await
+            .and_then(|_| verify::stable_verified_marker(dir_name, &meta.encode_to_vec()));
+        if let Err(err) = result {
+            warn!(
+                "replica {replica_id} refuses to install snap, recycle dir {}: {err}",
+                dir_name.display()
+            );
+            let mut sender = self.shared.inner.lock().unwrap().sender.clone();
+            sender.start_send((replica_id, dir_name.to_owned())).unwrap_or_default();
+            return Err(err);
+        }
+
         let mut inner = self.shared.inner.lock().unwrap();
+        let mut sender = inner.sender.clone();
         let replica = inner
             .replicas
             .get_mut(&replica_id)
@@ -190,9 +342,18 @@ impl SnapManager {
             Some(parent) if parent == replica.base_dir => {
                 let name = dir_name.file_name().unwrap().to_string_lossy().into_owned();
                 let snapshot_index = name.parse::<usize>().expect("install invalid snapshot dir");
-                let snapshot_id = format!("{}", snapshot_index).as_bytes().to_owned();
+                let snapshot_id = snapshot_id(replica_id, meta);
                 debug_assert!(snapshot_index < replica.next_snapshot_index);
 
+                if replica.contains(&snapshot_id) {
+                    info!(
+                        "replica {replica_id} snap {snapshot_index} is duplicated, recycle dir {}",
+                        dir_name.display()
+                    );
+                    sender.start_send((replica_id, dir_name.to_owned())).unwrap_or_default();
+                    return Ok(snapshot_id);
+                }
+
                 info!(
                     "replica {replica_id} install snap {snapshot_index}, dir {}",
                     dir_name.display()
@@ -205,7 +366,7 @@ impl SnapManager {
                     ref_count: 0,
                     created_at: Instant::now(),
                 });
-                snapshot_id
+                Ok(snapshot_id)
             }
             _ => panic!("install invalid snapshot dir: {}", dir_name.display()),
         }
@@ -216,6 +377,15 @@ impl SnapManager {
         inner.replicas.get(&replica_id).and_then(|rep| rep.snapshots.last()).cloned()
     }
 
+    /// Returns the latest snapshot which covers the `required_index` and is
+    /// still fresh enough to be sent to the followers.
+    pub fn fresh_snap(&self, replica_id: u64, required_index: u64) -> Option<SnapshotInfo> {
+        self.latest_snap(replica_id).filter(|info| {
+            info.apply_index() >= required_index
+                && info.created_at.elapsed() < self.shared.fresh_intervals
+        })
+    }
+
     pub fn lock_snap(&self, replica_id: u64, snapshot_id: &[u8]) -> Option<SnapshotGuard> {
         let mut inner = self.shared.inner.lock().unwrap();
         inner
@@ -280,10 +450,14 @@ impl ReplicaSnapManager {
     }
 
     fn push(&mut self, info: SnapshotInfo) {
-        let index = self.snapshots.partition_point(|i| i.snapshot_id < info.snapshot_id);
+        let index = self.snapshots.partition_point(|i| i.apply_index() < info.apply_index());
         self.snapshots.insert(index, info);
     }
 
+    fn contains(&self, snapshot_id: &[u8]) -> bool {
+        self.snapshots.iter().any(|info| info.snapshot_id == snapshot_id)
+    }
+
     fn next_snapshot_dir(&mut self) -> PathBuf {
         let snapshot_index = self.next_snapshot_index;
         self.next_snapshot_index += 1;
@@ -311,6 +485,11 @@ impl ReplicaSnapManager {
 }
 
 impl SnapshotInfo {
+    #[inline]
+    pub fn apply_index(&self) -> u64 {
+        self.meta.apply_state.as_ref().map(|s| s.index).unwrap_or_default()
+    }
+
     pub fn to_raft_snapshot(&self) -> Snapshot {
         let snap_meta = &self.meta;
         let apply_state = snap_meta.apply_state.clone().unwrap();
@@ -325,6 +504,25 @@ impl SnapshotInfo {
     }
 }
 
+impl CreatingGuard {
+    /// Finish the creating with the id of the created snapshot.
+    pub fn finish(mut self, snapshot_id: Vec<u8>) {
+        self.snapshot_id = Some(snapshot_id);
+    }
+}
+
+impl Drop for CreatingGuard {
+    fn drop(&mut self) {
+        let waiters = {
+            let mut inner = self.manager.shared.inner.lock().unwrap();
+            inner.creating.remove(&self.replica_id).unwrap_or_default()
+        };
+        for waiter in waiters {
+            waiter.send(self.snapshot_id.clone()).unwrap_or_default();
+        }
+    }
+}
+
 impl Drop for SnapshotGuard {
     fn drop(&mut self) {
         let mut inner = self.manager.shared.inner.lock().unwrap();
@@ -342,7 +540,14 @@ impl std::ops::Deref for SnapshotGuard {
     }
 }
 
-fn list_numeric_path(root: &Path) -> Result<Vec<(u64, PathBuf)>> {
+/// The snapshot id is derived from the replica id and the apply index, so the
+/// snapshots of the same state share the same id.
+fn snapshot_id(replica_id: u64, meta: &SnapshotMeta) -> Vec<u8> {
+    let apply_index = meta.apply_state.as_ref().map(|s| s.index).unwrap_or_default();
+    format!("{replica_id}-{apply_index}").into_bytes()
+}
+
+pub(crate) fn list_numeric_path(root: &Path) -> Result<Vec<(u64, PathBuf)>> {
     let mut values = vec![];
     for entry in std::fs::read_dir(root)? {
         let entry = entry?;
@@ -366,6 +571,10 @@ fn list_numeric_path(root: &Path) -> Result<Vec<(u64, PathBuf)>> {
     Ok(values)
 }
 
+fn contains_key(cipher: Option<&dyn DataCipher>, key_id: &str) -> bool {
+    key_id.is_empty() || cipher.map(|cipher| cipher.contains_key(key_id)).unwrap_or_default()
+}
+
 async fn recycle_snapshot(mut receiver: mpsc::UnboundedReceiver<(u64, PathBuf)>) {
     while let Some((replica_id, snapshot_dir)) = receiver.next().await {
         if let Err(err) = std::fs::remove_dir_all(&snapshot_dir) {
@@ -390,7 +599,7 @@ mod tests {
 
     use super::*;
     use crate::raftgroup::SnapshotBuilder;
-    use crate::serverpb::v1::ApplyState;
+    use crate::serverpb::v1::{snapshot_chunk, ApplyState, SnapshotChunk};
 
     struct SimpleSnapshotBuilder {
         index: u64,
@@ -432,6 +641,39 @@ mod tests {
         }
     }
 
+    /// A snapshot builder which counts the checkpoints, and takes a while to
+    /// build, so that the concurrent requests overlap.
+    struct CountingSnapshotBuilder {
+        index: u64,
+        content: Vec<u8>,
+        num_checkpoints: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[crate::async_trait]
+    impl SnapshotBuilder for CountingSnapshotBuilder {
+        async fn checkpoint(&self, base_dir: &Path) -> Result<(ApplyState, GroupDesc)> {
+            self.num_checkpoints.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            sleep(Duration::from_millis(100)).await;
+            if let Some(parent) = base_dir.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(base_dir, &self.content)?;
+            let state = ApplyState { index: self.index, term: 0 };
+            Ok((state, GroupDesc::default()))
+        }
+    }
+
+    fn open_cipher(dir: &Path, keys: &[(&str, u8)]) -> Arc<dyn DataCipher> {
+        let key_file = dir.join("keys");
+        let content = keys
+            .iter()
+            .map(|(key_id, byte)| format!("{key_id}:{}\n", format!("{byte:02x}").repeat(32)))
+            .collect::<String>();
+        std::fs::write(&key_file, content).unwrap();
+        let cfg = crate::EncryptionConfig { key_file: Some(key_file) };
+        crate::cipher::open_data_cipher(&cfg).unwrap().unwrap()
+    }
+
     async fn build_snapshot(
         manager: &SnapManager,
         replica_id: u64,
@@ -451,7 +693,8 @@ mod tests {
 
             let replica_id_1: u64 = 1;
             let replica_id_2: u64 = 2;
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
 
             let snap_id_1 = build_snapshot(&snap_manager, replica_id_1, 1, vec![1]).await;
             let snap_id_2 = build_snapshot(&snap_manager, replica_id_1, 2, vec![2]).await;
@@ -460,7 +703,8 @@ mod tests {
 
             drop(snap_manager);
 
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
             for snap_id in &replica_snaps_1 {
                 assert!(
                     snap_manager.lock_snap(replica_id_1, snap_id.as_slice()).is_some(),
@@ -485,7 +729,8 @@ mod tests {
             std::fs::create_dir_all(&root_dir).unwrap();
 
             let replica_id: u64 = 1;
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
 
             // Prepare snapshot
             let content = vec![1, 2, 3, 4, 5, 6, 7];
@@ -521,7 +766,8 @@ mod tests {
             std::fs::create_dir_all(&root_dir).unwrap();
 
             let replica_id: u64 = 1;
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
 
             // Prepare snapshot
             let content_1 = vec![1, 2, 3, 4, 5, 6, 7, 1];
@@ -561,6 +807,266 @@ mod tests {
         });
     }
 
+    #[test]
+    fn send_and_save_encrypted_snapshot() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("download-encrypted-snapshot").unwrap();
+            let key_dir = TempDir::new("download-encrypted-snapshot-keys").unwrap();
+            let cipher = open_cipher(key_dir.path(), &[("k1", 1)]);
+
+            let replica_id: u64 = 1;
+            let snap_manager =
+                SnapManager::recovery(&root_dir, Some(cipher), SnapSendScheduler::default())
+                    .await
+                    .unwrap();
+
+            let content_1 = vec![1, 2, 3, 4, 5, 6, 7, 1];
+            let content_2 = vec![1, 2, 3, 4, 5, 6, 7, 2];
+            let builder: Box<dyn SnapshotBuilder> = Box::new(MultiFilesSnapshotBuilder {
+                index: 1,
+                content_1: content_1.clone(),
+                content_2: content_2.clone(),
+            });
+            let snap_id =
+                create::create_snapshot(replica_id, &snap_manager, builder).await.unwrap();
+
+            // The snapshot files are encrypted at rest.
+            let snap = snap_manager.lock_snap(replica_id, &snap_id).unwrap();
+            assert_eq!(snap.meta.key_id, "k1");
+            let data = snap.base_dir.join(SNAP_DATA);
+            assert_ne!(std::fs::read(data.join("1")).unwrap(), content_1);
+            assert_ne!(std::fs::read(data.join("2")).unwrap(), content_2);
+            drop(snap);
+
+            let snapshot_chunk_stream =
+                send::send_snapshot(&snap_manager, replica_id, snap_id).await.unwrap();
+            let new_snap_id =
+                download::save_snapshot(&snap_manager, replica_id + 1, snapshot_chunk_stream)
+                    .await
+                    .unwrap();
+
+            // The received snapshot could be decrypted by the key.
+            let snap = snap_manager.lock_snap(replica_id + 1, &new_snap_id).unwrap();
+            assert_eq!(snap.meta.key_id, "k1");
+            let cipher = snap_manager.cipher().unwrap();
+            let plain_dir = apply::decrypt_snapshot(cipher.as_ref(), &snap).unwrap();
+            assert_eq!(std::fs::read(plain_dir.join("1")).unwrap(), content_1);
+            assert_eq!(std::fs::read(plain_dir.join("2")).unwrap(), content_2);
+
+            // The tampered snapshot is rejected rather than applied.
+            let path = snap.base_dir.join(SNAP_DATA).join("2");
+            let mut sealed = std::fs::read(&path).unwrap();
+            sealed[0] ^= 1;
+            std::fs::write(&path, sealed).unwrap();
+            let result = apply::decrypt_snapshot(cipher.as_ref(), &snap);
+            assert!(matches!(result, Err(Error::InvalidData(_))), "{result:?}");
+        });
+    }
+
+    #[test]
+    fn save_encrypted_snapshot_without_key() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("save-encrypted-snapshot-without-key").unwrap();
+            let key_dir = TempDir::new("save-encrypted-snapshot-without-key-keys").unwrap();
+            let cipher = open_cipher(key_dir.path(), &[("k1", 1)]);
+            let other_cipher = open_cipher(key_dir.path(), &[("k2", 2)]);
+
+            let leader_dir = root_dir.path().join("leader");
+            let follower_dir = root_dir.path().join("follower");
+            std::fs::create_dir_all(&leader_dir).unwrap();
+            std::fs::create_dir_all(&follower_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager =
+                SnapManager::recovery(&leader_dir, Some(cipher), SnapSendScheduler::default())
+                    .await
+                    .unwrap();
+            let snap_id = build_snapshot(&snap_manager, replica_id, 1, vec![1, 2, 3]).await;
+
+            for cipher in [None, Some(other_cipher)] {
+                let follower_manager =
+                    SnapManager::recovery(&follower_dir, cipher, SnapSendScheduler::default())
+                        .await
+                        .unwrap();
+                let snapshot_chunk_stream =
+                    send::send_snapshot(&snap_manager, replica_id, snap_id.clone()).await.unwrap();
+                let result = download::save_snapshot(
+                    &follower_manager,
+                    replica_id + 1,
+                    snapshot_chunk_stream,
+                )
+                .await;
+                assert!(matches!(result, Err(Error::KeyNotFound(key_id)) if key_id == "k1"));
+                assert!(follower_manager.latest_snap(replica_id + 1).is_none());
+            }
+        });
+    }
+
+    #[test]
+    fn rotate_snapshot_key() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("rotate-snapshot-key").unwrap();
+            let key_dir = TempDir::new("rotate-snapshot-key-keys").unwrap();
+
+            let replica_id: u64 = 1;
+            let cipher = open_cipher(key_dir.path(), &[("k1", 1)]);
+            let snap_manager =
+                SnapManager::recovery(&root_dir, Some(cipher), SnapSendScheduler::default())
+                    .await
+                    .unwrap();
+            let snap_id_1 = build_snapshot(&snap_manager, replica_id, 1, vec![1]).await;
+            drop(snap_manager);
+
+            // The new snapshots are encrypted by the new key, and the former
+            // snapshots are still readable.
+            let cipher = open_cipher(key_dir.path(), &[("k1", 1), ("k2", 2)]);
+            let snap_manager =
+                SnapManager::recovery(&root_dir, Some(cipher), SnapSendScheduler::default())
+                    .await
+                    .unwrap();
+            let snap_id_2 = build_snapshot(&snap_manager, replica_id + 1, 2, vec![2]).await;
+            let cipher = snap_manager.cipher().unwrap().clone();
+            let snaps = [(replica_id, &snap_id_1, "k1", 1), (replica_id + 1, &snap_id_2, "k2", 2)];
+            for (replica_id, snap_id, key_id, content) in snaps {
+                let snap = snap_manager.lock_snap(replica_id, snap_id).unwrap();
+                assert_eq!(snap.meta.key_id, key_id);
+                let plain = apply::decrypt_snapshot(cipher.as_ref(), &snap).unwrap();
+                assert_eq!(std::fs::read(plain).unwrap(), vec![content]);
+            }
+            drop(snap_manager);
+
+            // The snapshots whose key is removed are recycled.
+            let cipher = open_cipher(key_dir.path(), &[("k2", 2)]);
+            let snap_manager =
+                SnapManager::recovery(&root_dir, Some(cipher), SnapSendScheduler::default())
+                    .await
+                    .unwrap();
+            assert!(snap_manager.lock_snap(replica_id, &snap_id_1).is_none());
+            assert!(snap_manager.lock_snap(replica_id + 1, &snap_id_2).is_some());
+        });
+    }
+
+    /// Flip the first byte of the first data chunk of the stream.
+    fn corrupt_chunk_stream(
+        stream: send::SnapshotChunkStream,
+    ) -> impl futures::Stream<Item = Result<SnapshotChunk, tonic::Status>> + Unpin {
+        let mut corrupted = false;
+        stream.map(move |chunk| {
+            chunk.map(|mut chunk| {
+                if let Some(snapshot_chunk::Value::ChunkData(data)) = chunk.value.as_mut() {
+                    if !corrupted && !data.is_empty() {
+                        data[0] ^= 0xFF;
+                        corrupted = true;
+                    }
+                }
+                chunk
+            })
+        })
+    }
+
+    #[test]
+    fn refuse_installing_corrupted_snapshot() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("install-corrupted-snapshot").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
+            let snap_id = build_snapshot(&snap_manager, replica_id, 1, vec![1, 2, 3]).await;
+            let snap = snap_manager.lock_snap(replica_id, &snap_id).unwrap();
+            assert!(snap.base_dir.join(SNAP_VERIFIED).exists());
+            drop(snap);
+
+            // Corrupt one byte of the received file before it is installed.
+            let snapshot_chunk_stream =
+                send::send_snapshot(&snap_manager, replica_id, snap_id).await.unwrap();
+            let (base_dir, meta) =
+                download::receive_snapshot(&snap_manager, replica_id + 1, snapshot_chunk_stream)
+                    .await
+                    .unwrap();
+            let data = base_dir.join(SNAP_DATA);
+            let mut content = std::fs::read(&data).unwrap();
+            content[1] ^= 0xFF;
+            std::fs::write(&data, content).unwrap();
+
+            let result = snap_manager.install(replica_id + 1, &base_dir, &meta).await;
+            assert!(matches!(result, Err(Error::SnapshotCorrupted(_))), "{result:?}");
+            assert!(snap_manager.latest_snap(replica_id + 1).is_none());
+            assert!(!base_dir.join(SNAP_VERIFIED).exists());
+        });
+    }
+
+    #[test]
+    fn refetch_corrupted_snapshot() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("refetch-corrupted-snapshot").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
+            let content = vec![1, 2, 3, 4, 5, 6, 7];
+            let snap_id = build_snapshot(&snap_manager, replica_id, 1, content.clone()).await;
+
+            // The first fetched snapshot is corrupted.
+            let num_fetched = std::sync::atomic::AtomicUsize::new(0);
+            let fetch = || async {
+                let stream =
+                    send::send_snapshot(&snap_manager, replica_id, snap_id.clone()).await.unwrap();
+                if num_fetched.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                    Ok(futures::future::Either::Left(corrupt_chunk_stream(stream)))
+                } else {
+                    Ok(futures::future::Either::Right(stream))
+                }
+            };
+            let new_snap_id =
+                download::fetch_and_save_snapshot(&snap_manager, replica_id + 1, fetch)
+                    .await
+                    .unwrap();
+            assert_eq!(num_fetched.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+            let snap = snap_manager.lock_snap(replica_id + 1, &new_snap_id).unwrap();
+            let received_content = std::fs::read(snap.base_dir.join(SNAP_DATA)).unwrap();
+            assert_eq!(received_content, content);
+        });
+    }
+
+    #[test]
+    fn recovery_verifies_unmarked_snapshot() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("snap-recovery-verify").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
+            let snap_id = build_snapshot(&snap_manager, replica_id, 1, vec![1, 2, 3]).await;
+            let base_dir = snap_manager.lock_snap(replica_id, &snap_id).unwrap().base_dir.clone();
+            drop(snap_manager);
+
+            // The verified snapshot is not hashed again.
+            let data = base_dir.join(SNAP_DATA);
+            std::fs::write(&data, [3, 2, 1]).unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
+            assert!(snap_manager.lock_snap(replica_id, &snap_id).is_some());
+            drop(snap_manager);
+
+            // The snapshot without the marker is verified and recycled.
+            std::fs::remove_file(base_dir.join(SNAP_VERIFIED)).unwrap();
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
+            assert!(snap_manager.lock_snap(replica_id, &snap_id).is_none());
+        });
+    }
+
     #[test]
     fn recycle() {
         let owner = ExecutorOwner::new(1);
@@ -594,6 +1100,52 @@ mod tests {
         });
     }
 
+    #[test]
+    fn concurrent_snapshot_requests_share_checkpoint() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("snap-concurrent-requests").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager =
+                SnapManager::recovery(&root_dir, None, SnapSendScheduler::default()).await.unwrap();
+
+            let content = vec![1, 2, 3];
+            let num_checkpoints = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let create = || {
+                let builder: Box<dyn SnapshotBuilder> = Box::new(CountingSnapshotBuilder {
+                    index: 10,
+                    content: content.clone(),
+                    num_checkpoints: num_checkpoints.clone(),
+                });
+                create::create_or_wait_snapshot(replica_id, &snap_manager, builder)
+            };
+            let (id_1, id_2, id_3) = futures::join!(create(), create(), create());
+            let snap_ids = vec![id_1.unwrap(), id_2.unwrap(), id_3.unwrap()];
+            assert_eq!(num_checkpoints.load(std::sync::atomic::Ordering::SeqCst), 1);
+            assert!(snap_ids.iter().all(|id| *id == snap_ids[0]));
+
+            for (i, snap_id) in snap_ids.into_iter().enumerate() {
+                let target_id = replica_id + 1 + i as u64;
+                let snapshot_chunk_stream =
+                    send::send_snapshot(&snap_manager, replica_id, snap_id).await.unwrap();
+                let new_snap_id =
+                    download::save_snapshot(&snap_manager, target_id, snapshot_chunk_stream)
+                        .await
+                        .unwrap();
+                let snap = snap_manager.lock_snap(target_id, &new_snap_id).unwrap();
+                let received_content = std::fs::read(snap.base_dir.join(SNAP_DATA)).unwrap();
+                assert_eq!(received_content, content);
+            }
+
+            // The snapshot is reused by the later requests.
+            let snap = snap_manager.fresh_snap(replica_id, 10).unwrap();
+            assert_eq!(snap.apply_index(), 10);
+            assert!(snap_manager.fresh_snap(replica_id, 11).is_none());
+        });
+    }
+
     #[test]
     fn ordered_install() {
         let owner = ExecutorOwner::new(1);
@@ -606,15 +1158,17 @@ mod tests {
 
             let snap_dir_1 = snap_mgr.create(replica_id);
             let snap_dir_2 = snap_mgr.create(replica_id);
+            std::fs::create_dir_all(&snap_dir_1).unwrap();
+            std::fs::create_dir_all(&snap_dir_2).unwrap();
             let snap_meta = SnapshotMeta {
                 apply_state: Some(ApplyState::default()),
                 group_desc: Some(GroupDesc::default()),
-                files: vec![],
+                ..Default::default()
             };
 
             // Install snap in reversed orders.
-            let expected_id = snap_mgr.install(replica_id, &snap_dir_2, &snap_meta);
-            snap_mgr.install(replica_id, &snap_dir_1, &snap_meta);
+            let expected_id = snap_mgr.install(replica_id, &snap_dir_2, &snap_meta).await.unwrap();
+            snap_mgr.install(replica_id, &snap_dir_1, &snap_meta).await.unwrap();
 
             assert!(matches!(snap_mgr.latest_snap(replica_id),
                 Some(info) if info.snapshot_id == expected_id));
@@ -632,13 +1186,14 @@ mod tests {
             let snap_mgr = SnapManager::new(root_dir.path().to_owned());
 
             let snap_dir_1 = snap_mgr.create(replica_id);
+            std::fs::create_dir_all(&snap_dir_1).unwrap();
             let snap_meta = SnapshotMeta {
                 apply_state: Some(ApplyState::default()),
                 group_desc: Some(GroupDesc::default()),
-                files: vec![],
+                ..Default::default()
             };
             snap_mgr.recycle_snapshots(replica_id, RecycleSnapMode::RequiredIndex(123123));
-            snap_mgr.install(replica_id, &snap_dir_1, &snap_meta);
+            snap_mgr.install(replica_id, &snap_dir_1, &snap_meta).await.unwrap();
         });
     }
 }
@@ -0,0 +1,317 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The scheduling of the outgoing snapshot transfers of a node.
+//!
+//! A node sends at most `max_concurrency` snapshots at the same time, the
+//! others are queued and the receivers keep waiting, so the followers stay in
+//! the snapshot pending state of the leaders instead of timing out. Once a
+//! slot is freed, the snapshot of a group lacking quorum is sent first, since
+//! the group could not serve any writes until the snapshot is applied.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::channel::oneshot;
+use log::debug;
+use sekas_runtime::time::Instant;
+use serde::Serialize;
+
+use crate::raftgroup::metrics::*;
+use crate::transport::TokenBucket;
+use crate::{Error, Result};
+
+/// The default limit of concurrent outgoing snapshot transfers.
+pub const DEFAULT_SNAPSHOT_SEND_CONCURRENCY: usize = 2;
+
+/// The max number of finished transfers retained for diagnosing.
+const MAX_SEND_RECORDS: usize = 64;
+
+/// The record of an outgoing snapshot transfer.
+#[derive(Clone, Debug, Serialize)]
+pub struct SnapSendRecord {
+    /// The id of the replica which sends the snapshot.
+    pub replica_id: u64,
+    /// Whether the group lacks quorum when the transfer is started.
+    pub quorum_lacking: bool,
+    /// The millis since the scheduler is created.
+    pub started_at_ms: u64,
+    pub finished_at_ms: u64,
+    /// The millis waiting in the queue.
+    pub waited_ms: u64,
+}
+
+/// The permit of sending a snapshot, the slot is freed once it is dropped.
+pub struct SendPermit {
+    scheduler: Option<SnapSendScheduler>,
+    replica_id: u64,
+    quorum_lacking: bool,
+    queued_at: Instant,
+    started_at: Instant,
+}
+
+#[derive(Clone)]
+pub struct SnapSendScheduler {
+    shared: Arc<SchedulerShared>,
+}
+
+struct SchedulerShared {
+    bucket: TokenBucket,
+    created_at: Instant,
+    inner: Mutex<SchedulerInner>,
+}
+
+struct SchedulerInner {
+    max_concurrency: usize,
+    num_sending: usize,
+    next_seq: u64,
+    /// The replicas whose groups lack quorum, reported by the leaders.
+    quorum_lacking: HashSet<u64>,
+    waiters: Vec<SendWaiter>,
+    records: VecDeque<SnapSendRecord>,
+}
+
+struct SendWaiter {
+    seq: u64,
+    replica_id: u64,
+    queued_at: Instant,
+    sender: oneshot::Sender<SendPermit>,
+}
+
+impl SnapSendScheduler {
+    pub fn new(max_concurrency: usize, bucket: TokenBucket) -> Self {
+        let inner = SchedulerInner {
+            max_concurrency,
+            num_sending: 0,
+            next_seq: 0,
+            quorum_lacking: HashSet::default(),
+            waiters: Vec::default(),
+            records: VecDeque::default(),
+        };
+        SnapSendScheduler {
+            shared: Arc::new(SchedulerShared {
+                bucket,
+                created_at: Instant::now(),
+                inner: Mutex::new(inner),
+            }),
+        }
+    }
+
+    /// The bandwidth of sending snapshots, it is shared with moving shards.
+    #[inline]
+    pub fn bucket(&self) -> &TokenBucket {
+        &self.shared.bucket
+    }
+
+    /// Change the limit of concurrent transfers, the queued transfers are
+    /// started if the limit is raised.
+    pub fn set_max_concurrency(&self, max_concurrency: usize) {
+        let mut inner = self.shared.inner.lock().unwrap();
+        inner.max_concurrency = max_concurrency;
+        self.dispatch(&mut inner);
+    }
+
+    /// Mark whether the group of the leader replica lacks quorum, the
+    /// snapshots of such groups jump the queue.
+    pub fn set_quorum_lacking(&self, replica_id: u64, quorum_lacking: bool) {
+        let mut inner = self.shared.inner.lock().unwrap();
+        if quorum_lacking {
+            inner.quorum_lacking.insert(replica_id);
+        } else {
+            inner.quorum_lacking.remove(&replica_id);
+        }
+    }
+
+    /// Acquire a slot to send a snapshot of the replica, it waits until a slot
+    /// is freed.
+    pub async fn acquire(&self, replica_id: u64) -> Result<SendPermit> {
+        let receiver = {
+            let mut inner = self.shared.inner.lock().unwrap();
+            let (sender, receiver) = oneshot::channel();
+            let seq = inner.next_seq;
+            inner.next_seq += 1;
+            inner.waiters.push(SendWaiter { seq, replica_id, queued_at: Instant::now(), sender });
+            self.dispatch(&mut inner);
+            receiver
+        };
+        receiver.await.map_err(|_| Error::Canceled)
+    }
+
+    /// The number of queued transfers.
+    pub fn num_queued(&self) -> usize {
+        self.shared.inner.lock().unwrap().waiters.len()
+    }
+
+    /// The recently finished transfers, in the order of finishing.
+    pub fn records(&self) -> Vec<SnapSendRecord> {
+        self.shared.inner.lock().unwrap().records.iter().cloned().collect()
+    }
+
+    /// Start the queued transfers until the slots are exhausted, the groups
+    /// lacking quorum first and then in the order of queuing.
+    fn dispatch(&self, inner: &mut SchedulerInner) {
+        while inner.num_sending < inner.max_concurrency.max(1) && !inner.waiters.is_empty() {
+            let (index, _) = inner
+                .waiters
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, w)| (!inner.quorum_lacking.contains(&w.replica_id), w.seq))
+                .expect("waiters is not empty");
+            let waiter = inner.waiters.swap_remove(index);
+            let quorum_lacking = inner.quorum_lacking.contains(&waiter.replica_id);
+            let permit = SendPermit {
+                scheduler: Some(self.clone()),
+                replica_id: waiter.replica_id,
+                quorum_lacking,
+                queued_at: waiter.queued_at,
+                started_at: Instant::now(),
+            };
+            inner.num_sending += 1;
+            if let Err(mut permit) = waiter.sender.send(permit) {
+                // The receiver is gone, the slot is reused at once.
+                permit.scheduler = None;
+                inner.num_sending -= 1;
+                continue;
+            }
+            debug!(
+                "start sending snapshot of replica {}, quorum lacking {quorum_lacking}",
+                waiter.replica_id
+            );
+        }
+        RAFTGROUP_SEND_SNAPSHOT_QUEUE_DEPTH.set(inner.waiters.len() as i64);
+        RAFTGROUP_SEND_SNAPSHOT_INFLIGHT.set(inner.num_sending as i64);
+    }
+
+    fn release(&self, permit: &SendPermit) {
+        let now = Instant::now();
+        let waited = permit.started_at.saturating_duration_since(permit.queued_at);
+        let sent = now.saturating_duration_since(permit.started_at);
+        RAFTGROUP_SEND_SNAPSHOT_WAIT_DURATION_SECONDS.observe(waited.as_secs_f64());
+        RAFTGROUP_SEND_SNAPSHOT_DURATION_SECONDS.observe(sent.as_secs_f64());
+
+        let since_created = |instant: Instant| -> u64 {
+            as_millis(instant.saturating_duration_since(self.shared.created_at))
+        };
+        let record = SnapSendRecord {
+            replica_id: permit.replica_id,
+            quorum_lacking: permit.quorum_lacking,
+            started_at_ms: since_created(permit.started_at),
+            finished_at_ms: since_created(now),
+            waited_ms: as_millis(waited),
+        };
+
+        let mut inner = self.shared.inner.lock().unwrap();
+        if inner.records.len() >= MAX_SEND_RECORDS {
+            inner.records.pop_front();
+        }
+        inner.records.push_back(record);
+        inner.num_sending -= 1;
+        self.dispatch(&mut inner);
+    }
+}
+
+impl Default for SnapSendScheduler {
+    fn default() -> Self {
+        SnapSendScheduler::new(DEFAULT_SNAPSHOT_SEND_CONCURRENCY, TokenBucket::new(0))
+    }
+}
+
+impl Drop for SendPermit {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.release(self);
+        }
+    }
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sekas_macro::test]
+    async fn snapshot_send_serialized() {
+        let scheduler = SnapSendScheduler::new(1, TokenBucket::new(0));
+        let first = scheduler.acquire(1).await.unwrap();
+
+        let mut handles = vec![];
+        for replica_id in 2..=10 {
+            let scheduler = scheduler.clone();
+            handles.push(sekas_runtime::spawn(async move {
+                let permit = scheduler.acquire(replica_id).await.unwrap();
+                sekas_runtime::time::sleep(Duration::from_millis(1)).await;
+                drop(permit);
+            }));
+            // Keep the order of queuing.
+            while scheduler.num_queued() < (replica_id - 1) as usize {
+                sekas_runtime::yield_now().await;
+            }
+        }
+
+        // The groups lacking quorum jump the queue.
+        scheduler.set_quorum_lacking(7, true);
+        scheduler.set_quorum_lacking(9, true);
+        drop(first);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let records = scheduler.records();
+        let order = records.iter().map(|r| r.replica_id).collect::<Vec<_>>();
+        assert_eq!(order, vec![1, 7, 9, 2, 3, 4, 5, 6, 8, 10]);
+        for window in records.windows(2) {
+            assert!(window[0].finished_at_ms <= window[1].started_at_ms, "{window:?}");
+        }
+        assert!(records[1].quorum_lacking && records[2].quorum_lacking);
+    }
+
+    #[sekas_macro::test]
+    async fn snapshot_send_canceled_waiter() {
+        let scheduler = SnapSendScheduler::new(1, TokenBucket::new(0));
+        let first = scheduler.acquire(1).await.unwrap();
+
+        let canceled = {
+            let scheduler = scheduler.clone();
+            sekas_runtime::spawn(async move { scheduler.acquire(2).await.map(|_| ()) })
+        };
+        while scheduler.num_queued() < 1 {
+            sekas_runtime::yield_now().await;
+        }
+        drop(canceled);
+
+        // The slot of the canceled waiter is reused.
+        drop(first);
+        let _permit = scheduler.acquire(3).await.unwrap();
+        assert_eq!(scheduler.num_queued(), 0);
+    }
+
+    #[sekas_macro::test]
+    async fn snapshot_send_raise_concurrency() {
+        let scheduler = SnapSendScheduler::new(1, TokenBucket::new(0));
+        let _first = scheduler.acquire(1).await.unwrap();
+        let handle = {
+            let scheduler = scheduler.clone();
+            sekas_runtime::spawn(async move { scheduler.acquire(2).await.map(|_| ()) })
+        };
+        while scheduler.num_queued() < 1 {
+            sekas_runtime::yield_now().await;
+        }
+        scheduler.set_max_concurrency(2);
+        handle.await.unwrap().unwrap();
+    }
+}
@@ -15,15 +15,19 @@
 
 use std::ffi::OsString;
 use std::fs::File;
+use std::future::Future;
 use std::io::Read;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use log::debug;
+use sekas_runtime::time::Sleep;
 
+use super::schedule::SendPermit;
 use super::{SnapManager, SnapshotGuard};
 use crate::raftgroup::metrics::*;
 use crate::serverpb::v1::{snapshot_chunk, SnapshotChunk};
+use crate::transport::TokenBucket;
 use crate::{Error, Result};
 
 type SnapResult = Result<SnapshotChunk, tonic::Status>;
@@ -32,6 +36,12 @@ pub struct SnapshotChunkStream {
     info: SnapshotGuard,
     file: Option<File>,
     file_index: usize,
+    bucket: TokenBucket,
+    /// The delay before sending the next chunk, to pay back the borrowed
+    /// bandwidth.
+    delay: Option<Pin<Box<Sleep>>>,
+    /// The slot of sending snapshots, it is freed once the stream is dropped.
+    _permit: SendPermit,
 }
 
 pub async fn send_snapshot(
@@ -46,13 +56,26 @@ pub async fn send_snapshot(
         }
     };
 
+    // The snapshot is locked during queuing, so it won't be recycled before it
+    // is sent. The receiver keeps waiting, so the follower stays in snapshot
+    // pending state of the leader.
+    let scheduler = snap_mgr.send_scheduler();
+    let permit = scheduler.acquire(replica_id).await?;
+
     RAFTGROUP_SEND_SNAPSHOT_TOTAL.inc();
-    Ok(SnapshotChunkStream::new(snapshot_info))
+    Ok(SnapshotChunkStream::new(snapshot_info, scheduler.bucket().clone(), permit))
 }
 
 impl SnapshotChunkStream {
-    fn new(info: SnapshotGuard) -> Self {
-        SnapshotChunkStream { info, file: None, file_index: 0 }
+    fn new(info: SnapshotGuard, bucket: TokenBucket, permit: SendPermit) -> Self {
+        SnapshotChunkStream {
+            info,
+            file: None,
+            file_index: 0,
+            bucket,
+            delay: None,
+            _permit: permit,
+        }
     }
 
     fn next_chunk(&mut self) -> Option<SnapResult> {
@@ -82,6 +105,10 @@ impl SnapshotChunkStream {
                 }
                 chunk_data.truncate(num_read);
                 RAFTGROUP_SEND_SNAPSHOT_BYTES_TOTAL.inc_by(num_read as u64);
+                let delay = self.bucket.acquire(num_read as u64);
+                if !delay.is_zero() {
+                    self.delay = Some(Box::pin(sekas_runtime::time::sleep(delay)));
+                }
                 let value = snapshot_chunk::Value::ChunkData(chunk_data);
                 Some(Ok(SnapshotChunk { value: Some(value) }))
             }
@@ -117,7 +144,14 @@ impl SnapshotChunkStream {
 impl futures::Stream for SnapshotChunkStream {
     type Item = SnapResult;
 
-    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        Poll::Ready(self.get_mut().next_chunk())
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(delay) = this.delay.as_mut() {
+            if delay.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.delay = None;
+        }
+        Poll::Ready(this.next_chunk())
     }
 }
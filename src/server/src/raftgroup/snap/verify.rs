@@ -0,0 +1,135 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The integrity of the snapshot files.
+//!
+//! A snapshot is verified before it is installed: the size and crc32 of each
+//! file are recomputed and compared with `SnapshotMeta`, and the directories of
+//! the files are synced. Once verified, a `SNAP_VERIFIED` marker which holds
+//! the checksum of `META` is written, so the unchanged snapshots are not hashed
+//! again during recovery.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use super::SNAP_VERIFIED;
+use crate::serverpb::v1::SnapshotMeta;
+use crate::{Error, Result};
+
+/// Compute the crc32 and size of the file.
+pub(super) async fn checksum_file(path: &Path) -> Result<(u32, u64)> {
+    use std::fs::OpenOptions;
+    use std::io::{ErrorKind, Read};
+
+    let mut buf = vec![0; 4096];
+    let mut file = OpenOptions::new().read(true).open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+
+    let mut size: u64 = 0;
+    let mut count = 0;
+    loop {
+        let n = match file.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if n == 0 {
+            break;
+        }
+
+        size += n as u64;
+        count += 1;
+        hasher.update(&buf[..n]);
+        if count % 10 == 0 {
+            sekas_runtime::yield_now().await;
+        }
+    }
+    Ok((hasher.finalize(), size))
+}
+
+/// Verify the files of the snapshot against the meta, and sync the dirs of
+/// them. `Error::SnapshotCorrupted` is returned if any file is missing or
+/// mismatched.
+pub(super) async fn verify_snapshot(base_dir: &Path, meta: &SnapshotMeta) -> Result<()> {
+    for file in &meta.files {
+        let path = base_dir.join(&file.name);
+        let (crc32, size) = match checksum_file(&path).await {
+            Ok(v) => v,
+            Err(Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::SnapshotCorrupted(format!("file {} is missing", file.name)));
+            }
+            Err(err) => return Err(err),
+        };
+        if size != file.size {
+            return Err(Error::SnapshotCorrupted(format!(
+                "the size of file {} is {size}, but {} is expected",
+                file.name, file.size
+            )));
+        }
+        if crc32 != file.crc32 {
+            return Err(Error::SnapshotCorrupted(format!(
+                "the crc32 of file {} is {crc32}, but {} is expected",
+                file.name, file.crc32
+            )));
+        }
+    }
+
+    sync_snapshot_dirs(base_dir, meta)
+}
+
+/// Returns whether the snapshot has been verified since the `META` is written.
+pub(super) fn is_verified(base_dir: &Path, meta_content: &[u8]) -> Result<bool> {
+    let marker = base_dir.join(SNAP_VERIFIED);
+    if !std::fs::try_exists(&marker)? {
+        return Ok(false);
+    }
+    let content = std::fs::read(marker)?;
+    Ok(content == crc32fast::hash(meta_content).to_le_bytes())
+}
+
+/// Write the `SNAP_VERIFIED` marker of the snapshot. The marker is bound to the
+/// content of `META`, a torn marker is mismatched and the snapshot is verified
+/// again.
+pub(super) fn stable_verified_marker(base_dir: &Path, meta_content: &[u8]) -> Result<()> {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    let marker = base_dir.join(SNAP_VERIFIED);
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).open(marker)?;
+    file.write_all(&crc32fast::hash(meta_content).to_le_bytes())?;
+    file.sync_all()?;
+    drop(file);
+
+    std::fs::File::open(base_dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Sync the dirs of the snapshot files, and the parent of the snapshot dir, so
+/// that the entries of the renamed or created files are durable.
+fn sync_snapshot_dirs(base_dir: &Path, meta: &SnapshotMeta) -> Result<()> {
+    let mut dirs = BTreeSet::<PathBuf>::new();
+    for file in &meta.files {
+        if let Some(parent) = base_dir.join(&file.name).parent() {
+            dirs.insert(parent.to_owned());
+        }
+    }
+    dirs.insert(base_dir.to_owned());
+    if let Some(parent) = base_dir.parent() {
+        dirs.insert(parent.to_owned());
+    }
+    for dir in dirs {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
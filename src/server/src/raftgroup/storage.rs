@@ -24,6 +24,7 @@ use raft::GetEntriesContext;
 use raft_engine::{Command, Engine, LogBatch, MessageExt};
 use sekas_api::server::v1::*;
 
+use super::metrics::RAFTGROUP_CREATE_SNAPSHOT_AVOIDED_TOTAL;
 use super::node::WriteTask;
 use super::snap::SnapManager;
 use super::RaftConfig;
@@ -63,6 +64,9 @@ pub struct Storage {
 
     pub create_snapshot: Cell<bool>,
     pub is_creating_snapshot: Cell<bool>,
+    /// Whether a snapshot is created for the pending snapshot request, the
+    /// snapshots served without it are reused.
+    snapshot_requested: Cell<bool>,
     snap_mgr: SnapManager,
     engine: Arc<Engine>,
 }
@@ -144,6 +148,7 @@ impl Storage {
             snap_mgr,
             create_snapshot: Cell::new(false),
             is_creating_snapshot: Cell::new(false),
+            snapshot_requested: Cell::new(false),
             engine,
         })
     }
@@ -347,17 +352,17 @@ impl raft::Storage for Storage {
 
     fn snapshot(&self, request_index: u64, _to: u64) -> raft::Result<Snapshot> {
         if !self.is_creating_snapshot.get() {
-            if let Some(snap_info) = self.snap_mgr.latest_snap(self.replica_id) {
-                let snap_meta = &snap_info.meta;
-                let apply_state = snap_meta.apply_state.clone().unwrap();
-                if apply_state.index >= request_index {
-                    return Ok(snap_info.to_raft_snapshot());
+            if let Some(snap_info) = self.snap_mgr.fresh_snap(self.replica_id, request_index) {
+                if !self.snapshot_requested.replace(false) {
+                    RAFTGROUP_CREATE_SNAPSHOT_AVOIDED_TOTAL.reuse.inc();
                 }
+                return Ok(snap_info.to_raft_snapshot());
             }
 
             assert!(!self.create_snapshot.get());
             self.create_snapshot.set(true);
             self.is_creating_snapshot.set(true);
+            self.snapshot_requested.set(true);
         }
 
         Err(raft::Error::Store(raft::StorageError::SnapshotTemporarilyUnavailable))
@@ -487,10 +492,10 @@ pub async fn write_initial_state(
             last_index += 1;
             let change_replicas = ChangeReplicas {
                 changes: vec![ChangeReplica {
-                    change_type: if replica.role == ReplicaRole::Learner as i32 {
-                        ChangeReplicaType::AddLearner.into()
-                    } else {
-                        ChangeReplicaType::Add.into()
+                    change_type: match ReplicaRole::from_i32(replica.role) {
+                        Some(ReplicaRole::Learner) => ChangeReplicaType::AddLearner.into(),
+                        Some(ReplicaRole::ReadReplica) => ChangeReplicaType::AddReadReplica.into(),
+                        _ => ChangeReplicaType::Add.into(),
                     },
                     replica_id,
                     node_id,
@@ -556,6 +561,17 @@ pub async fn destory(engine: &Engine, replica_id: u64) -> Result<()> {
     Ok(())
 }
 
+/// Read the hard state and local state of the replica, without opening the raft
+/// storage. `None` is returned if the states are not initialized.
+pub fn read_raft_state(
+    engine: &Engine,
+    replica_id: u64,
+) -> Result<Option<(HardState, RaftLocalState)>> {
+    let hard_state = engine.get_message::<HardState>(replica_id, keys::HARD_STATE_KEY)?;
+    let local_state = engine.get_message::<RaftLocalState>(replica_id, keys::LOCAL_STATE_KEY)?;
+    Ok(hard_state.zip(local_state))
+}
+
 pub mod keys {
     pub const HARD_STATE_KEY: &[u8] = b"hard_state";
     pub const LOCAL_STATE_KEY: &[u8] = b"local_state";
@@ -17,7 +17,7 @@ use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::task::Context;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use futures::channel::{mpsc, oneshot};
 use futures::stream::FusedStream;
@@ -26,12 +26,16 @@ use log::{debug, info, warn};
 use raft::prelude::*;
 use raft::{SoftState, StateRole};
 use raft_engine::{Engine, LogBatch};
-use sekas_api::server::v1::{ChangeReplicas, RaftRole, ReplicaDesc};
+use sekas_api::server::v1::{
+    ApplyQuarantine, ChangeReplicas, QuarantineAction, RaftRole, ReplicaDesc,
+};
+use sekas_runtime::time::{interval, Instant, Interval, MissedTickBehavior};
 use sekas_runtime::TaskGroup;
-use tokio::time::{interval, Interval, MissedTickBehavior};
 
 use super::applier::{Applier, ReplicaCache};
+use super::fair::{RoundBudget, RoundScheduler};
 use super::fsm::StateMachine;
+use super::group::LeaderContact;
 use super::io::{Channel, ChannelManager, LogWriter};
 use super::metrics::*;
 use super::monitor::WorkerPerfContext;
@@ -44,17 +48,43 @@ use crate::serverpb::v1::{EvalResult, RaftMessage};
 use crate::{record_latency, RaftConfig, Result};
 
 pub enum Request {
-    Read { policy: ReadPolicy, sender: oneshot::Sender<Result<()>> },
-    Propose { eval_result: EvalResult, start: Instant, sender: oneshot::Sender<Result<()>> },
+    Read {
+        policy: ReadPolicy,
+        sender: oneshot::Sender<Result<()>>,
+    },
+    Propose {
+        eval_result: EvalResult,
+        start: Instant,
+        sender: oneshot::Sender<Result<()>>,
+    },
     CreateSnapshotFinished,
-    InstallSnapshot { msg: Message },
-    RejectSnapshot { msg: Message },
-    ChangeConfig { change: ChangeReplicas, sender: oneshot::Sender<Result<()>> },
-    Transfer { transferee: u64 },
+    InstallSnapshot {
+        msg: Message,
+    },
+    RejectSnapshot {
+        msg: Message,
+    },
+    ChangeConfig {
+        change: ChangeReplicas,
+        sender: oneshot::Sender<Result<()>>,
+    },
+    Transfer {
+        transferee: u64,
+    },
     Message(RaftMessage),
-    Unreachable { target_id: u64 },
+    Unreachable {
+        target_id: u64,
+    },
     State(oneshot::Sender<RaftGroupState>),
     Monitor(oneshot::Sender<Box<WorkerPerfContext>>),
+    ResolveQuarantine {
+        action: QuarantineAction,
+        expected_index: u64,
+        sender: oneshot::Sender<Result<Option<ApplyQuarantine>>>,
+    },
+    QuarantineCorruption {
+        message: String,
+    },
     Start,
 }
 
@@ -113,6 +143,9 @@ impl<'a> super::node::AdvanceTemplate for AdvanceImpl<'a> {
     fn send_messages(&mut self, msgs: Vec<Message>) {
         let mut seperated_msgs: HashMap<u64, Vec<Message>> = HashMap::default();
         for msg in msgs {
+            if msg.get_msg_type() == MessageType::MsgRequestPreVoteResponse && msg.reject {
+                RAFTGROUP_ELECTION_PREVENTED_TOTAL.pre_vote_rejected.inc();
+            }
             seperated_msgs.entry(msg.to).or_default().push(msg);
         }
         for (target_id, msgs) in seperated_msgs {
@@ -147,8 +180,12 @@ impl<'a> super::node::AdvanceTemplate for AdvanceImpl<'a> {
     }
 
     #[inline]
-    fn apply_snapshot<M: StateMachine>(&mut self, applier: &mut Applier<M>, snapshot: &Snapshot) {
-        apply_snapshot(self.replica_id, self.snap_mgr, applier, snapshot);
+    fn apply_snapshot<M: StateMachine>(
+        &mut self,
+        applier: &mut Applier<M>,
+        snapshot: &Snapshot,
+    ) -> Result<()> {
+        apply_snapshot(self.replica_id, self.snap_mgr, applier, snapshot)
     }
 }
 
@@ -171,6 +208,15 @@ where
     engine: Arc<Engine>,
     observer: Box<dyn StateObserver>,
     replica_cache: ReplicaCache,
+    contact: Arc<LeaderContact>,
+    /// Whether the group lacks quorum, it is reported to the snapshot send
+    /// scheduler.
+    quorum_lacking: bool,
+
+    round_scheduler: RoundScheduler,
+    round_budget: RoundBudget,
+    /// Whether the last round exceeds the budget.
+    is_heavy: bool,
 
     task_group: TaskGroup,
     marker: PhantomData<M>,
@@ -181,6 +227,8 @@ struct WorkerContext {
     accumulated_bytes: usize,
     perf_ctx: WorkerPerfContext,
     monitors: Vec<oneshot::Sender<Box<WorkerPerfContext>>>,
+    /// The in queue duration of the first proposal of this round.
+    schedule_delay: Option<Duration>,
 }
 
 impl<M> RaftWorker<M>
@@ -204,6 +252,10 @@ where
         let (mut request_sender, request_receiver) =
             mpsc::channel(raft_mgr.cfg.max_inflight_requests);
         request_sender.send(Request::Start).await.unwrap();
+        let round_budget = RoundBudget {
+            max_entries: raft_mgr.cfg.max_entries_per_round,
+            max_bytes: raft_mgr.cfg.max_io_batch_size as usize,
+        };
 
         observer.on_state_updated(
             raft_node.raft().leader_id,
@@ -225,6 +277,11 @@ where
             engine: raft_mgr.engine.clone(),
             observer,
             replica_cache,
+            contact: Arc::default(),
+            quorum_lacking: false,
+            round_scheduler: raft_mgr.round_scheduler.clone(),
+            round_budget,
+            is_heavy: false,
             task_group: TaskGroup::default(),
             marker: PhantomData,
         })
@@ -235,6 +292,11 @@ where
         self.request_sender.clone()
     }
 
+    #[inline]
+    pub fn leader_contact(&self) -> Arc<LeaderContact> {
+        self.contact.clone()
+    }
+
     /// Poll requests and messages, forward both to `RaftNode`, and advance
     /// `RaftNode`.
     pub async fn run(mut self, log_writer: LogWriter) -> Result<()> {
@@ -247,9 +309,21 @@ where
         while !self.request_receiver.is_terminated() {
             let mut ctx = WorkerContext::default();
             self.maintenance(&mut ctx, &mut interval).await?;
+            // The heavy groups yield until the rounds of the light groups are finished.
+            let light_round = (!self.is_heavy).then(|| self.round_scheduler.begin_light_round());
             self.consume_requests(&mut ctx)?;
             self.dispatch(&mut ctx, &mut log_writer).await?;
             self.finish_round(ctx);
+            drop(light_round);
+            if self.is_heavy {
+                // Requeue behind the other groups of this node.
+                RAFTGROUP_WORKER_REQUEUE_TOTAL.inc();
+                let delay = self.round_scheduler.requeue().await;
+                take_schedule_delay_metrics(true).observe(delay.as_secs_f64());
+            }
+        }
+        if self.quorum_lacking {
+            self.snap_mgr.send_scheduler().set_quorum_lacking(self.desc.id, false);
         }
 
         debug!("group {} replica {} raft worker is quit", self.group_id, self.desc.id);
@@ -289,8 +363,22 @@ where
     }
 
     fn on_tick_fire(&mut self, ctx: &mut WorkerContext) {
+        if self.raft_node.raft().state == StateRole::Leader {
+            self.contact.heard();
+        }
         self.raft_node.tick();
         self.compact_log(ctx);
+        self.report_quorum_lacking();
+    }
+
+    /// The snapshots of the groups lacking quorum are sent first, since they
+    /// can't serve any writes until the snapshots are applied.
+    fn report_quorum_lacking(&mut self) {
+        let quorum_lacking = self.raft_node.is_quorum_lacking();
+        if quorum_lacking != self.quorum_lacking {
+            self.quorum_lacking = quorum_lacking;
+            self.snap_mgr.send_scheduler().set_quorum_lacking(self.desc.id, quorum_lacking);
+        }
     }
 
     fn consume_requests(&mut self, ctx: &mut WorkerContext) -> Result<()> {
@@ -298,7 +386,7 @@ where
         record_perf_point(&mut ctx.perf_ctx.consume_requests);
         while let Ok(Some(request)) = self.request_receiver.try_next() {
             self.handle_request(ctx, request)?;
-            if ctx.accumulated_bytes >= self.cfg.max_io_batch_size as usize {
+            if self.round_budget.is_exceeded(ctx.perf_ctx.num_requests, ctx.accumulated_bytes) {
                 break;
             }
         }
@@ -349,9 +437,14 @@ where
         Ok(())
     }
 
-    fn finish_round(&self, mut ctx: WorkerContext) {
+    fn finish_round(&mut self, mut ctx: WorkerContext) {
         record_perf_point(&mut ctx.perf_ctx.finish);
         ctx.perf_ctx.accumulated_bytes = ctx.accumulated_bytes;
+        if let Some(delay) = ctx.schedule_delay {
+            take_schedule_delay_metrics(self.is_heavy).observe(delay.as_secs_f64());
+        }
+        let num_entries = ctx.perf_ctx.num_requests + ctx.perf_ctx.advance.applier.num_committed;
+        self.is_heavy = self.round_budget.is_exceeded(num_entries, ctx.accumulated_bytes);
         for sender in ctx.monitors {
             sender.send(Box::new(ctx.perf_ctx.clone())).unwrap_or_default();
         }
@@ -375,6 +468,9 @@ where
                 self.handle_msg(ctx, msg)?;
             }
             Request::Unreachable { target_id } => {
+                if self.raft_node.raft().state != StateRole::Leader {
+                    self.contact.report_unreachable();
+                }
                 self.raft_node.report_unreachable(target_id);
             }
             Request::RejectSnapshot { msg: input } => {
@@ -408,6 +504,17 @@ where
             Request::Monitor(sender) => {
                 ctx.monitors.push(sender);
             }
+            Request::ResolveQuarantine { action, expected_index, sender } => {
+                let result = self.raft_node.resolve_quarantine(
+                    &mut self.replica_cache,
+                    action,
+                    expected_index,
+                );
+                sender.send(result).unwrap_or_default();
+            }
+            Request::QuarantineCorruption { message } => {
+                self.raft_node.quarantine_corruption(message);
+            }
             Request::Start => {}
         }
         Ok(())
@@ -417,6 +524,9 @@ where
         let from_replica = raft_msg.from_replica.unwrap();
         self.replica_cache.insert(from_replica.clone());
         for msg in raft_msg.messages {
+            if is_from_leader(&msg, self.raft_node.raft().term) {
+                self.contact.heard();
+            }
             if msg.get_msg_type() == MessageType::MsgSnapshot {
                 // TODO(walter) In order to avoid useless downloads, should check whether this
                 // snapshot will be accept.
@@ -452,6 +562,7 @@ where
         ctx.perf_ctx.num_proposal += 1;
         self.raft_node.propose(data, vec![], sender);
         RAFTGROUP_WORKER_REQUEST_IN_QUEUE_DURATION_SECONDS.observe(elapsed_seconds(start));
+        ctx.schedule_delay.get_or_insert_with(|| start.elapsed());
     }
 
     fn handle_conf_change(&mut self, change: ChangeReplicas, sender: oneshot::Sender<Result<()>>) {
@@ -525,6 +636,18 @@ where
     }
 }
 
+/// Whether the message is sent by the leader of the term, which is not less
+/// than the local term.
+fn is_from_leader(msg: &Message, local_term: u64) -> bool {
+    matches!(
+        msg.get_msg_type(),
+        MessageType::MsgAppend
+            | MessageType::MsgHeartbeat
+            | MessageType::MsgSnapshot
+            | MessageType::MsgReadIndexResp
+    ) && msg.term >= local_term
+}
+
 impl SlowIoGuard {
     fn new(threshold: u64) -> Self {
         SlowIoGuard { threshold, start: Instant::now() }
@@ -41,70 +41,185 @@ pub(crate) async fn get<T: LatchManager>(
         }
     }
 
+    super::check_read_version(engine, req.start_version)?;
     trace!(
         "read key {:?} at shard {} with version {}",
         req.user_key,
         req.shard_id,
         req.start_version
     );
-    read_key(engine, latch_mgr, req.shard_id, &req.user_key, req.start_version).await
+    read_key(engine, latch_mgr, req.shard_id, &req.user_key, req.start_version, req.txn_id).await
 }
 
+/// Inspect the versions and intent of the key. The intent is reported as is,
+/// it is never resolved nor waited.
+pub(crate) fn get_raw_key(engine: &GroupEngine, req: &GetRawKeyRequest) -> Result<RawKeyState> {
+    let key = &req.user_key;
+    let mut state = RawKeyState::default();
+    let mut snapshot = engine.snapshot(req.shard_id, SnapshotMode::Key { key })?;
+    if let Some(iter) = snapshot.next() {
+        for entry in iter? {
+            let entry = entry?;
+            if entry.version() == TXN_INTENT_VERSION {
+                let Some(value) = entry.value() else {
+                    return Err(Error::InvalidData(format!(
+                        "the intent value of key: {key:?} not exists?"
+                    )));
+                };
+                let intent = TxnIntent::decode(value)?;
+                state.intent = Some(IntentInfo {
+                    txn_id: intent.start_version,
+                    start_version: intent.start_version,
+                    is_delete: intent.is_delete,
+                });
+                continue;
+            }
+            if entry.is_truncated() {
+                // The older versions are dropped by the max versions.
+                break;
+            }
+
+            state.versions_retained += 1;
+            if state.latest.is_some() {
+                continue;
+            }
+            match entry.value() {
+                Some(value) => {
+                    state.latest =
+                        Some(ValueMeta { version: entry.version(), size: value.len() as u64 });
+                }
+                None if state.tombstone.is_none() => state.tombstone = Some(entry.version()),
+                None => {}
+            }
+        }
+    }
+    Ok(state)
+}
+
+/// Read the first visible version of the key. The intent written by the txn
+/// `txn_id` is visible as a provisional value, the intents of other txns are
+/// resolved.
 async fn read_key<T: LatchManager>(
     engine: &GroupEngine,
     latch_mgr: &T,
     shard_id: u64,
     key: &[u8],
     start_version: u64,
+    txn_id: u64,
 ) -> Result<Option<Value>> {
+    // The intent is always located at the first of the versions.
     let snapshot_mode = SnapshotMode::Key { key };
-    let mut snapshot = engine.snapshot(shard_id, snapshot_mode)?;
+    let mut snapshot = engine.snapshot(shard_id, snapshot_mode)?.with_read_version(start_version);
+    let Some(iter) = snapshot.next() else { return Ok(None) };
+    let Some(entry) = iter?.next().transpose()? else { return Ok(None) };
+    trace!("read key entry with version: {}", entry.version());
+    if entry.version() == TXN_INTENT_VERSION {
+        // maybe we need to wait intent.
+        let Some(value) = entry.value() else {
+            return Err(Error::InvalidData(format!(
+                "the intent value of key: {key:?} not exists?"
+            )));
+        };
+        let intent = TxnIntent::decode(value)?;
+        if txn_id != 0 && intent.start_version == txn_id {
+            if intent.value.is_some() || intent.is_delete {
+                trace!("get return the intent of the owner txn, shard_id {shard_id}, txn {txn_id}");
+                return Ok(Some(Value {
+                    content: intent.value,
+                    version: intent.start_version,
+                    ..Default::default()
+                }));
+            }
+            // The nop intent doesn't change the value.
+        } else if intent.start_version <= start_version {
+            if let Some(value) =
+                latch_mgr.resolve_txn(shard_id, key, start_version, intent.start_version).await?
+            {
+                if value.version <= start_version {
+                    trace!("get return resolve txn intent, shard_id {}, value version: {}, start version: {}",
+                            shard_id, value.version, start_version);
+                    return Ok(Some(value));
+                }
+            }
+        }
+    } else if entry.is_truncated() {
+        // The versions visible to this read are dropped by the max versions.
+        return Err(Error::VersionTooOld(start_version, entry.version() + 1));
+    } else if entry.version() <= start_version {
+        // The latest version is visible, which is the common case.
+        trace!(
+            "get return entry, shard_id {}, value version: {}, start version: {}",
+            shard_id,
+            entry.version(),
+            start_version
+        );
+        // ATTN: [`read_key`] should return the first entry, include tombstone entry.
+        return Ok(Some(entry.into()));
+    }
+    drop(snapshot);
+    read_key_at_version(engine, shard_id, key, start_version)
+}
+
+/// Read the first committed version of the key not greater than the
+/// `start_version`, by seeking to it directly instead of walking through the
+/// newer versions.
+fn read_key_at_version(
+    engine: &GroupEngine,
+    shard_id: u64,
+    key: &[u8],
+    start_version: u64,
+) -> Result<Option<Value>> {
+    let version = start_version.min(TXN_INTENT_VERSION - 1);
+    let snapshot_mode = SnapshotMode::KeyAtVersion { key, version };
+    let mut snapshot = engine.snapshot(shard_id, snapshot_mode)?.with_read_version(start_version);
+    let Some(iter) = snapshot.next() else { return Ok(None) };
+    let Some(entry) = iter?.next().transpose()? else { return Ok(None) };
+    if entry.is_truncated() {
+        // The versions visible to this read are dropped by the max versions.
+        drop(snapshot);
+        let oldest_retained = oldest_retained_version(engine, shard_id, key)?;
+        return Err(Error::VersionTooOld(start_version, oldest_retained));
+    }
+    trace!(
+        "get return entry, shard_id {}, value version: {}, start version: {}",
+        shard_id,
+        entry.version(),
+        start_version
+    );
+    debug_assert!(entry.version() <= start_version);
+    // ATTN: [`read_key`] should return the first entry, include tombstone entry.
+    Ok(Some(entry.into()))
+}
+
+/// Return the oldest version of the key retained by the max versions. It walks
+/// through all versions, but it is only used to report the rejected reads.
+fn oldest_retained_version(engine: &GroupEngine, shard_id: u64, key: &[u8]) -> Result<u64> {
+    let mut snapshot = engine.snapshot(shard_id, SnapshotMode::Key { key })?;
+    let mut oldest_retained = None;
     if let Some(iter) = snapshot.next() {
         for entry in iter? {
             let entry = entry?;
-            trace!("read key entry with version: {}", entry.version());
             if entry.version() == TXN_INTENT_VERSION {
-                // maybe we need to wait intent.
-                let Some(value) = entry.value() else {
-                    return Err(Error::InvalidData(format!(
-                        "the intent value of key: {key:?} not exists?"
-                    )));
-                };
-                let intent = TxnIntent::decode(value)?;
-                if intent.start_version <= start_version {
-                    if let Some(value) = latch_mgr
-                        .resolve_txn(shard_id, key, start_version, intent.start_version)
-                        .await?
-                    {
-                        if value.version <= start_version {
-                            trace!("get return resolve txn intent, shard_id {}, value version: {}, start version: {}",
-                                    shard_id, value.version, start_version);
-                            return Ok(Some(value));
-                        }
-                    }
-                }
-            } else if entry.version() <= start_version {
-                trace!(
-                    "get return entry, shard_id {}, value version: {}, start version: {}",
-                    shard_id,
-                    entry.version(),
-                    start_version
-                );
-                // This entry is safe for reading.
-                // ATTN: [`read_key`] should return the first entry, include tombstone entry.
-                return Ok(Some(entry.into()));
+                continue;
+            }
+            if entry.is_truncated() {
+                return Ok(oldest_retained.unwrap_or(entry.version() + 1));
             }
+            oldest_retained = Some(entry.version());
         }
     }
-    Ok(None)
+    Ok(oldest_retained.unwrap_or_default())
 }
 
 #[cfg(test)]
 mod tests {
+    extern crate test;
+
     use std::collections::VecDeque;
     use std::sync::Mutex;
 
     use sekas_rock::fn_name;
+    use sekas_runtime::ExecutorOwner;
     use tempdir::TempDir;
 
     use super::*;
@@ -147,7 +262,7 @@ mod tests {
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, 1, key, value, *version).unwrap();
             } else {
@@ -193,7 +308,7 @@ mod tests {
             let key = idx.to_string();
             commit_values(&engine, key.as_bytes(), &values);
 
-            let got = read_key(&engine, &latch_mgr, 1, key.as_bytes(), 3).await.unwrap();
+            let got = read_key(&engine, &latch_mgr, 1, key.as_bytes(), 3, 0).await.unwrap();
             assert_eq!(got, expect, "idx = {idx}");
         }
     }
@@ -240,11 +355,53 @@ mod tests {
             let key = idx.to_string();
             commit_values(&engine, key.as_bytes(), &values);
 
-            let got = read_key(&engine, &latch_mgr, 1, key.as_bytes(), txn_version).await.unwrap();
+            let got =
+                read_key(&engine, &latch_mgr, 1, key.as_bytes(), txn_version, 0).await.unwrap();
             assert_eq!(got, expect, "idx = {idx}");
         }
     }
 
+    #[sekas_macro::test]
+    async fn get_raw_key_without_resolving_intent() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let get_raw_key = |key: &[u8]| {
+            let req = GetRawKeyRequest { shard_id: 1, user_key: key.to_vec() };
+            get_raw_key(&engine, &req).unwrap()
+        };
+
+        assert_eq!(get_raw_key(b"0"), RawKeyState::default());
+
+        // The tombstone newer than the latest value.
+        let values = vec![
+            Value::with_value(b"123".to_vec(), 1),
+            Value::tombstone(2),
+            Value::with_value(b"12".to_vec(), 3),
+            Value::tombstone(4),
+        ];
+        commit_values(&engine, b"1", &values);
+        let expect = RawKeyState {
+            latest: Some(ValueMeta { version: 3, size: 2 }),
+            tombstone: Some(4),
+            intent: None,
+            versions_retained: 4,
+        };
+        assert_eq!(get_raw_key(b"1"), expect);
+
+        // The intent is reported as is.
+        let mut values = vec![Value::with_value(b"123".to_vec(), 1)];
+        let intent = TxnIntent::tombstone(5);
+        values.push(Value::with_value(intent.encode_to_vec(), TXN_INTENT_VERSION));
+        commit_values(&engine, b"2", &values);
+        let expect = RawKeyState {
+            latest: Some(ValueMeta { version: 1, size: 3 }),
+            tombstone: None,
+            intent: Some(IntentInfo { txn_id: 5, start_version: 5, is_delete: true }),
+            versions_retained: 1,
+        };
+        assert_eq!(get_raw_key(b"2"), expect);
+    }
+
     struct MockLatchManager {
         values: Mutex<VecDeque<Option<Value>>>,
     }
@@ -331,8 +488,203 @@ mod tests {
             commit_values(&engine, key.as_bytes(), &values);
 
             let latch_mgr = MockLatchManager::with_value(resolve);
-            let got = read_key(&engine, &latch_mgr, 1, key.as_bytes(), txn_version).await.unwrap();
+            let got =
+                read_key(&engine, &latch_mgr, 1, key.as_bytes(), txn_version, 0).await.unwrap();
             assert_eq!(got, expect, "idx = {idx}");
         }
     }
+
+    #[sekas_macro::test]
+    async fn read_key_with_own_intent() {
+        struct TestCase {
+            intent: TxnIntent,
+            txn_id: u64,
+            expect: Option<Value>,
+        }
+
+        let txn_version = 123;
+        let values = vec![Value::with_value(b"123".to_vec(), 122)];
+        let cases = vec![
+            // case 1. the provisional value of the owner txn.
+            TestCase {
+                intent: TxnIntent::with_put(txn_version, Some(b"124".to_vec())),
+                txn_id: txn_version,
+                expect: Some(Value::with_value(b"124".to_vec(), txn_version)),
+            },
+            // case 2. the provisional tombstone of the owner txn.
+            TestCase {
+                intent: TxnIntent::tombstone(txn_version),
+                txn_id: txn_version,
+                expect: Some(Value::tombstone(txn_version)),
+            },
+            // case 3. the nop intent of the owner txn.
+            TestCase {
+                intent: TxnIntent::with_put(txn_version, None),
+                txn_id: txn_version,
+                expect: Some(Value::with_value(b"123".to_vec(), 122)),
+            },
+            // case 4. the intent of a younger txn is invisible to others.
+            TestCase {
+                intent: TxnIntent::with_put(txn_version + 1, Some(b"124".to_vec())),
+                txn_id: txn_version,
+                expect: Some(Value::with_value(b"123".to_vec(), 122)),
+            },
+        ];
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        // The intents above are never resolved.
+        let latch_mgr = NopLatchManager::default();
+        for (idx, TestCase { intent, txn_id, expect }) in cases.into_iter().enumerate() {
+            let key = idx.to_string();
+            let mut values = values.clone();
+            values.push(Value::with_value(intent.encode_to_vec(), TXN_INTENT_VERSION));
+            commit_values(&engine, key.as_bytes(), &values);
+
+            let got = read_key(&engine, &latch_mgr, 1, key.as_bytes(), txn_version, txn_id)
+                .await
+                .unwrap();
+            assert_eq!(got, expect, "idx = {idx}");
+        }
+
+        // The provisional value is not visible to other txns, the intent is resolved.
+        let key = b"0";
+        let latch_mgr = MockLatchManager::with_value(None);
+        let got =
+            read_key(&engine, &latch_mgr, 1, key, txn_version + 1, txn_version - 1).await.unwrap();
+        assert_eq!(got, Some(Value::with_value(b"123".to_vec(), 122)));
+    }
+
+    #[sekas_macro::test]
+    async fn read_key_at_old_versions() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let latch_mgr = NopLatchManager::default();
+
+        // Every 5th version is a tombstone.
+        let values = (1..=100)
+            .map(|v| if v % 5 == 0 { Value::tombstone(v * 10) } else { value_at(v * 10) })
+            .collect::<Vec<_>>();
+        commit_values(&engine, b"key", &values);
+
+        for start_version in 0..=1100 {
+            let expect = values.iter().rev().find(|v| v.version <= start_version).cloned();
+            let got = read_key(&engine, &latch_mgr, 1, b"key", start_version, 0).await.unwrap();
+            assert_eq!(got, expect, "start version = {start_version}");
+        }
+
+        // The neighbor keys are not visible.
+        commit_values(&engine, b"kex", &[value_at(1)]);
+        commit_values(&engine, b"key1", &[value_at(1)]);
+        let got = read_key(&engine, &latch_mgr, 1, b"key", 9, 0).await.unwrap();
+        assert_eq!(got, None);
+        let got = read_key(&engine, &latch_mgr, 1, b"key", u64::MAX, 0).await.unwrap();
+        assert_eq!(got, Some(Value::tombstone(1000)));
+    }
+
+    #[sekas_macro::test]
+    async fn read_key_at_old_versions_behind_intent() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let latch_mgr = NopLatchManager::default();
+
+        let intent = TxnIntent::with_put(1000, Some(b"provisional".to_vec()));
+        let values = vec![
+            value_at(10),
+            Value::tombstone(20),
+            value_at(30),
+            Value::with_value(intent.encode_to_vec(), TXN_INTENT_VERSION),
+        ];
+        commit_values(&engine, b"key", &values);
+
+        // The intent is not visible, the versions behind it are read.
+        let cases = [(5, None), (15, Some(value_at(10))), (25, Some(Value::tombstone(20)))];
+        for (start_version, expect) in cases {
+            let got = read_key(&engine, &latch_mgr, 1, b"key", start_version, 0).await.unwrap();
+            assert_eq!(got, expect, "start version = {start_version}");
+        }
+        let got = read_key(&engine, &latch_mgr, 1, b"key", 999, 0).await.unwrap();
+        assert_eq!(got, Some(value_at(30)));
+
+        // The nop intent of the owner txn doesn't hide the older versions.
+        let intent = TxnIntent::with_put(1000, None);
+        let values =
+            vec![value_at(10), Value::with_value(intent.encode_to_vec(), TXN_INTENT_VERSION)];
+        commit_values(&engine, b"nop", &values);
+        let got = read_key(&engine, &latch_mgr, 1, b"nop", 1000, 1000).await.unwrap();
+        assert_eq!(got, Some(value_at(10)));
+    }
+
+    fn value_at(version: u64) -> Value {
+        Value::with_value(version.to_string().into_bytes(), version)
+    }
+
+    const NUM_BENCH_VERSIONS: u64 = 10000;
+
+    /// Create a key with [`NUM_BENCH_VERSIONS`] versions, the executor owner
+    /// and the dir must outlive the engine.
+    fn setup_bench_engine(dir: &TempDir) -> (ExecutorOwner, GroupEngine) {
+        let owner = ExecutorOwner::new(1);
+        let engine = owner.executor().block_on(async {
+            let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+            let values = (1..=NUM_BENCH_VERSIONS).map(value_at).collect::<Vec<_>>();
+            commit_values(&engine, b"key", &values);
+            engine
+        });
+        (owner, engine)
+    }
+
+    /// The read path before seeking to the read version directly, which walks
+    /// through the newer versions.
+    fn walk_to_version(engine: &GroupEngine, key: &[u8], start_version: u64) -> Option<Value> {
+        let mut snapshot = engine.snapshot(1, SnapshotMode::Key { key }).unwrap();
+        let iter = snapshot.next()?.unwrap();
+        for entry in iter {
+            let entry = entry.unwrap();
+            if entry.version() <= start_version {
+                return Some(entry.into());
+            }
+        }
+        None
+    }
+
+    fn bench_read_key(b: &mut test::Bencher, start_version: u64) {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let (owner, engine) = setup_bench_engine(&dir);
+        let latch_mgr = NopLatchManager::default();
+        b.iter(|| {
+            owner.executor().block_on(async {
+                let value = read_key(&engine, &latch_mgr, 1, b"key", start_version, 0).await;
+                test::black_box(value.unwrap().unwrap());
+            })
+        });
+    }
+
+    fn bench_walk_to_version(b: &mut test::Bencher, start_version: u64) {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let (_owner, engine) = setup_bench_engine(&dir);
+        b.iter(|| test::black_box(walk_to_version(&engine, b"key", start_version).unwrap()));
+    }
+
+    #[bench]
+    fn bench_read_key_latest_version(b: &mut test::Bencher) {
+        bench_read_key(b, NUM_BENCH_VERSIONS);
+    }
+
+    #[bench]
+    fn bench_read_key_oldest_version(b: &mut test::Bencher) {
+        bench_read_key(b, 1);
+    }
+
+    /// The baseline of reading the oldest version, by walking the version
+    /// chain.
+    #[bench]
+    fn bench_walk_to_oldest_version(b: &mut test::Bencher) {
+        bench_walk_to_version(b, 1);
+    }
+
+    #[bench]
+    fn bench_walk_to_latest_version(b: &mut test::Bencher) {
+        bench_walk_to_version(b, NUM_BENCH_VERSIONS);
+    }
 }
@@ -11,7 +11,8 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use sekas_api::server::v1::ValueSet;
+use sekas_api::server::v1::{Value, ValueSet};
+use sekas_schema::system::txn::TXN_INTENT_VERSION;
 
 use crate::engine::{GroupEngine, WriteBatch};
 use crate::serverpb::v1::{EvalResult, WriteBatchRep};
@@ -32,13 +33,105 @@ pub async fn ingest_value_set(
     };
 
     let mut wb = WriteBatch::default();
+    put_value_set(engine, &mut wb, shard_id, value_set)?;
+
+    let eval_result = EvalResult {
+        batch: Some(WriteBatchRep { data: wb.data().to_vec() }),
+        ..Default::default()
+    };
+    Ok(Some(eval_result))
+}
+
+/// Ingest the value sets of keys which are not exists before in a single
+/// write batch.
+pub async fn ingest_value_sets(
+    engine: &GroupEngine,
+    shard_id: u64,
+    value_sets: &[ValueSet],
+) -> Result<Option<EvalResult>> {
+    let mut wb = WriteBatch::default();
+    for value_set in value_sets {
+        if value_set.values.is_empty() || engine.get(shard_id, &value_set.user_key).await?.is_some()
+        {
+            continue;
+        }
+        put_value_set(engine, &mut wb, shard_id, value_set)?;
+    }
+    if wb.is_empty() {
+        return Ok(None);
+    }
+
+    let eval_result = EvalResult {
+        batch: Some(WriteBatchRep { data: wb.data().to_vec() }),
+        ..Default::default()
+    };
+    Ok(Some(eval_result))
+}
+
+fn put_value_set(
+    engine: &GroupEngine,
+    wb: &mut WriteBatch,
+    shard_id: u64,
+    value_set: &ValueSet,
+) -> Result<()> {
+    for value in &value_set.values {
+        if let Some(content) = value.content.as_ref() {
+            let (key, version) = (&value_set.user_key, value.version);
+            engine.put_with_expiry(wb, shard_id, key, content, version, value.expire_at)?;
+        } else {
+            engine.tombstone(wb, shard_id, &value_set.user_key, value.version)?;
+        }
+    }
+    Ok(())
+}
+
+/// Restore the versions of a key which are newer than the local versions, it is
+/// used to roll back the data written to the dest group of a canceled moving.
+///
+/// The intent of the key follows the dest group: it is restored if the dest
+/// group has a different one, and removed if the dest group has resolved it.
+pub async fn restore_value_set(
+    engine: &GroupEngine,
+    shard_id: u64,
+    value_set: &ValueSet,
+) -> Result<Option<EvalResult>> {
+    let local_value_set = engine.get_all_versions(shard_id, &value_set.user_key).await?;
+    let latest_version = local_value_set
+        .values
+        .iter()
+        .map(|v| v.version)
+        .filter(|v| *v != TXN_INTENT_VERSION)
+        .max()
+        .unwrap_or_default();
+
+    let mut wb = WriteBatch::default();
+    let find_intent = |values: &[Value]| {
+        values.iter().find(|v| v.version == TXN_INTENT_VERSION).and_then(|v| v.content.clone())
+    };
+    let local_intent = find_intent(&local_value_set.values);
+    match find_intent(&value_set.values) {
+        Some(intent) if local_intent.as_ref() != Some(&intent) => {
+            engine.put(&mut wb, shard_id, &value_set.user_key, &intent, TXN_INTENT_VERSION)?;
+        }
+        None if local_intent.is_some() => {
+            engine.delete(&mut wb, shard_id, &value_set.user_key, TXN_INTENT_VERSION)?;
+        }
+        _ => {}
+    }
     for value in &value_set.values {
+        if value.version <= latest_version || value.version == TXN_INTENT_VERSION {
+            continue;
+        }
         if let Some(content) = value.content.as_ref() {
-            engine.put(&mut wb, shard_id, &value_set.user_key, content, value.version)?;
+            let (key, version) = (&value_set.user_key, value.version);
+            engine.put_with_expiry(&mut wb, shard_id, key, content, version, value.expire_at)?;
         } else {
             engine.tombstone(&mut wb, shard_id, &value_set.user_key, value.version)?;
         }
     }
+    if wb.is_empty() {
+        return Ok(None);
+    }
 
     let eval_result = EvalResult {
         batch: Some(WriteBatchRep { data: wb.data().to_vec() }),
@@ -49,7 +142,6 @@ pub async fn ingest_value_set(
 
 #[cfg(test)]
 mod tests {
-    use sekas_api::server::v1::Value;
     use sekas_rock::fn_name;
     use tempdir::TempDir;
 
@@ -75,4 +167,75 @@ mod tests {
         let result = ingest_value_set(&engine, SHARD_ID, &value_set).await.unwrap();
         assert!(result.is_none());
     }
+
+    #[sekas_macro::test]
+    async fn cmd_restore_value_set_only_writes_newer_versions() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+
+        let value_set = ValueSet {
+            user_key: vec![1, 2, 3, 4],
+            values: vec![Value::with_value(vec![1], 1), Value::tombstone(2)],
+        };
+        let eval_result = ingest_value_set(&engine, SHARD_ID, &value_set).await.unwrap().unwrap();
+        let wb = WriteBatch::new(&eval_result.batch.unwrap().data);
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        // All versions already exist.
+        let result = restore_value_set(&engine, SHARD_ID, &value_set).await.unwrap();
+        assert!(result.is_none());
+
+        let value_set = ValueSet {
+            user_key: vec![1, 2, 3, 4],
+            values: vec![
+                Value::with_value(vec![3], 3),
+                Value::tombstone(2),
+                Value::with_value(vec![1], 1),
+            ],
+        };
+        let eval_result = restore_value_set(&engine, SHARD_ID, &value_set).await.unwrap().unwrap();
+        let wb = WriteBatch::new(&eval_result.batch.unwrap().data);
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        let value_set = engine.get_all_versions(SHARD_ID, &[1, 2, 3, 4]).await.unwrap();
+        let versions = value_set.values.iter().map(|v| v.version).collect::<Vec<_>>();
+        assert_eq!(versions, vec![3, 2, 1]);
+    }
+
+    async fn local_intent(engine: &GroupEngine, key: &[u8]) -> Option<Value> {
+        let value_set = engine.get_all_versions(SHARD_ID, key).await.unwrap();
+        value_set.values.into_iter().find(|v| v.version == TXN_INTENT_VERSION)
+    }
+
+    #[sekas_macro::test]
+    async fn cmd_restore_value_set_follows_dest_intent() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let key = vec![1, 2, 3, 4];
+        let commit = |eval_result: EvalResult| {
+            let wb = WriteBatch::new(&eval_result.batch.unwrap().data);
+            engine.commit(wb, WriteStates::default(), false).unwrap();
+        };
+
+        // The intent written to the dest group is restored.
+        let intent = Value::with_value(vec![9], TXN_INTENT_VERSION);
+        let value_set = ValueSet {
+            user_key: key.clone(),
+            values: vec![intent.clone(), Value::with_value(vec![1], 1)],
+        };
+        commit(restore_value_set(&engine, SHARD_ID, &value_set).await.unwrap().unwrap());
+        assert_eq!(local_intent(&engine, &key).await.and_then(|v| v.content), Some(vec![9]));
+        assert!(restore_value_set(&engine, SHARD_ID, &value_set).await.unwrap().is_none());
+
+        // The intent resolved by the dest group is removed.
+        let value_set = ValueSet {
+            user_key: key.clone(),
+            values: vec![Value::with_value(vec![9], 2), Value::with_value(vec![1], 1)],
+        };
+        commit(restore_value_set(&engine, SHARD_ID, &value_set).await.unwrap().unwrap());
+        assert!(local_intent(&engine, &key).await.is_none());
+        let value_set = engine.get_all_versions(SHARD_ID, &key).await.unwrap();
+        let versions = value_set.values.iter().map(|v| v.version).collect::<Vec<_>>();
+        assert_eq!(versions, vec![2, 1]);
+    }
 }
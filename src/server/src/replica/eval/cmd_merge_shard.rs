@@ -25,6 +25,13 @@ pub(crate) fn merge_shard(engine: &GroupEngine, req: &MergeShardRequest) -> Resu
 
     debug!("execute merge shard {right_shard_id} into {left_shard_id}",);
 
+    if let Some(shard_id) = [left_shard_id, right_shard_id]
+        .into_iter()
+        .find(|id| sekas_schema::shard::is_txn_shard(*id))
+    {
+        return Err(Error::TxnShardFenced(shard_id));
+    }
+
     let left_shard = engine.shard_desc(left_shard_id)?;
     let right_shard = engine.shard_desc(right_shard_id)?;
     let Some(RangePartition { start: _, end: left_end }) = &left_shard.range else {
@@ -45,5 +52,5 @@ pub(crate) fn merge_shard(engine: &GroupEngine, req: &MergeShardRequest) -> Resu
 
     let merge_shard = MergeShard { left_shard_id, right_shard_id };
     let sync_op = Box::new(SyncOp { merge_shard: Some(merge_shard), ..Default::default() });
-    Ok(EvalResult { batch: None, op: Some(sync_op) })
+    Ok(EvalResult { op: Some(sync_op), ..Default::default() })
 }
@@ -0,0 +1,42 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use log::debug;
+use sekas_api::server::v1::*;
+
+use crate::error::BusyReason;
+use crate::replica::{EvalResult, ExecCtx, GroupEngine, RemoveShard, SyncOp};
+use crate::{Error, Result};
+
+/// Eval remove shard request. It is idempotent, `None` is returned if the
+/// shard is already removed.
+pub(crate) fn remove_shard(
+    exec_ctx: &ExecCtx,
+    engine: &GroupEngine,
+    req: &RemoveShardRequest,
+) -> Result<Option<EvalResult>> {
+    let shard_id = req.shard_id;
+    if exec_ctx.move_shard_desc.as_ref().is_some_and(|desc| desc.get_shard_id() == shard_id) {
+        return Err(Error::ServiceIsBusy(BusyReason::Moving));
+    }
+    if engine.descriptor().shard(shard_id).is_none() {
+        debug!("execute remove shard {shard_id}, but the shard is not exists");
+        return Ok(None);
+    }
+
+    debug!("execute remove shard {shard_id}");
+    let remove_shard = RemoveShard { shard_id };
+    let sync_op = Box::new(SyncOp { remove_shard: Some(remove_shard), ..Default::default() });
+    Ok(Some(EvalResult { op: Some(sync_op), ..Default::default() }))
+}
@@ -23,10 +23,12 @@ use crate::node::move_shard::ForwardCtx;
 use crate::replica::ExecCtx;
 use crate::{Error, Result};
 
-/// Merge two scan response of an moving shard.
+/// Merge two scan response of an moving shard. The value sets are in
+/// descending order if `reverse` is set.
 pub(crate) fn merge_scan_response(
     target: ShardScanResponse,
     source: ShardScanResponse,
+    reverse: bool,
 ) -> ShardScanResponse {
     let mut target_iter = target.data.into_iter();
     let mut source_iter = source.data.into_iter();
@@ -36,7 +38,7 @@ pub(crate) fn merge_scan_response(
     let mut source_next = source_iter.next();
     loop {
         match (target_next, source_next) {
-            (Some(x), Some(y)) => match x.user_key.cmp(&y.user_key) {
+            (Some(x), Some(y)) => match compare_user_key(&x.user_key, &y.user_key, reverse) {
                 std::cmp::Ordering::Less => {
                     value_sets.push(x);
                     target_next = target_iter.next();
@@ -70,10 +72,34 @@ pub(crate) fn merge_scan_response(
     }
 
     let has_more = target.has_more || source.has_more;
-    ShardScanResponse { data: value_sets, has_more }
+    ShardScanResponse { data: value_sets, has_more, ..Default::default() }
+}
+
+#[inline]
+fn compare_user_key(x: &[u8], y: &[u8], reverse: bool) -> std::cmp::Ordering {
+    if reverse {
+        y.cmp(x)
+    } else {
+        x.cmp(y)
+    }
 }
 
 /// Scan the specified range.
+///
+/// The visibility of txn intents and tombstones:
+/// - an intent written by the txn `txn_id` itself is visible to the txn as a
+///   provisional value, the nop intent is skipped.
+/// - an intent whose start version is larger than the read version is ignored
+///   entirely, the scan never waits on it.
+/// - an intent whose start version is not larger than the read version is
+///   resolved, the scan waits until the txn is committed or aborted. The wait
+///   of each intent is bounded, the txn record is checked again after the bound
+///   is exceeded, so that the intent of an expired txn is aborted and cleared.
+/// - if `ignore_txn_intent` is set, all intents are ignored and the latest
+///   committed version not larger than the read version is returned. It is the
+///   inconsistent mode for monitoring jobs.
+/// - tombstones are only returned if `include_raw_data` is set, otherwise a key
+///   whose visible version is a tombstone is skipped.
 pub(crate) async fn scan<T>(
     exec_ctx: &ExecCtx,
     engine: &GroupEngine,
@@ -100,6 +126,7 @@ where
         }
     }
 
+    super::check_read_version(engine, req.start_version)?;
     trace!("scan shard {}, version: {}", req.shard_id, req.start_version);
 
     let mut req = req.clone();
@@ -107,15 +134,32 @@ where
         Some(prefix) => {
             req.exclude_end_key = false;
             req.exclude_start_key = false;
-            SnapshotMode::Prefix { key: prefix }
+            if req.reverse {
+                SnapshotMode::ReversePrefix { key: prefix }
+            } else {
+                SnapshotMode::Prefix { key: prefix }
+            }
+        }
+        None if req.reverse => {
+            SnapshotMode::End { end_key: req.end_key.as_ref().map(|v| v.as_ref()) }
         }
         None => SnapshotMode::Start { start_key: req.start_key.as_ref().map(|v| v.as_ref()) },
     };
-    let snapshot = engine.snapshot(req.shard_id, snapshot_mode)?;
-    scan_inner(latch_mgr, snapshot, &req).await
+    let mut snapshot = engine.snapshot(req.shard_id, snapshot_mode)?;
+    if !req.include_raw_data {
+        // The raw data is copied with the expiry, eg. by the moving shards.
+        snapshot = snapshot.with_read_version(req.start_version);
+    }
+    let result = scan_inner(exec_ctx, latch_mgr, snapshot, &req).await;
+    if let (Err(_), Some(quota)) = (&result, exec_ctx.scan_quota.as_ref()) {
+        // The scanned value sets are dropped, release the bytes acquired for them.
+        quota.release();
+    }
+    result
 }
 
 async fn scan_inner<T>(
+    exec_ctx: &ExecCtx,
     latch_mgr: &T,
     mut snapshot: Snapshot<'_>,
     req: &ShardScanRequest,
@@ -128,12 +172,19 @@ where
     let mut has_more = false;
     while let Some(mvcc_iter) = snapshot.next() {
         let mvcc_iter = mvcc_iter?;
-        if is_exceeds(&req.end_key, mvcc_iter.user_key()) {
+        if req.reverse {
+            if is_precedes(&req.start_key, mvcc_iter.user_key()) {
+                break;
+            }
+        } else if is_exceeds(&req.end_key, mvcc_iter.user_key()) {
             break;
         }
 
         let value_set_opt = scan_value_set(mvcc_iter, latch_mgr, req).await?;
         let Some((value_set, value_bytes)) = value_set_opt else { continue };
+        if let Some(quota) = exec_ctx.scan_quota.as_ref() {
+            quota.acquire(value_bytes)?;
+        }
 
         data.push(value_set);
         total_bytes += value_bytes;
@@ -146,7 +197,7 @@ where
             break;
         }
     }
-    Ok(ShardScanResponse { data, has_more })
+    Ok(ShardScanResponse { data, has_more, ..Default::default() })
 }
 
 async fn scan_value_set<T: LatchManager>(
@@ -156,27 +207,35 @@ async fn scan_value_set<T: LatchManager>(
 ) -> Result<Option<(ValueSet, usize)>> {
     let mut values = Vec::default();
     let mut total_bytes = 0;
+    let mut oldest_retained = None;
     for entry in &mut mvcc_iter {
         let entry = entry?;
         let (user_key, mut version) = (entry.user_key(), entry.version());
+        let expire_at = entry.expire_at().unwrap_or_default();
         if is_exclude_boundary(req, user_key) {
             // skip exclude keys.
             return Ok(None);
         }
 
         let value;
-        if version == TXN_INTENT_VERSION && !req.ignore_txn_intent {
+        if entry.is_truncated() {
+            if req.include_raw_data {
+                // The older versions are dropped by the max versions.
+                break;
+            }
+            let oldest_retained = oldest_retained.unwrap_or(version + 1);
+            return Err(Error::VersionTooOld(req.start_version, oldest_retained));
+        } else if version == TXN_INTENT_VERSION && !req.ignore_txn_intent {
             let intent_value = entry.value().ok_or_else(|| {
                 Error::InvalidData(format!("the value of intent key {user_key:?} is not exists",))
             })?;
-            match resolve_txn(latch_mgr, req.shard_id, req.start_version, user_key, intent_value)
-                .await?
-            {
+            match resolve_txn(latch_mgr, req, user_key, intent_value).await? {
                 Some(v) => (value, version) = v,
                 None => continue,
             }
         } else if req.start_version < version {
             // skip invisible versions.
+            oldest_retained = Some(version);
             continue;
         } else {
             value = entry.value().map(ToOwned::to_owned);
@@ -184,9 +243,9 @@ async fn scan_value_set<T: LatchManager>(
 
         if let Some(value) = value {
             total_bytes += value.len();
-            values.push(Value { content: Some(value), version });
+            values.push(Value { content: Some(value), version, expire_at });
         } else if req.include_raw_data {
-            values.push(Value { content: None, version });
+            values.push(Value::tombstone(version));
         }
 
         if !req.include_raw_data {
@@ -214,6 +273,11 @@ fn is_exceeds(target: &Option<Vec<u8>>, user_key: &[u8]) -> bool {
     target.as_ref().map(|target_key| target_key.as_slice() < user_key).unwrap_or_default()
 }
 
+#[inline]
+fn is_precedes(target: &Option<Vec<u8>>, user_key: &[u8]) -> bool {
+    target.as_ref().map(|target_key| user_key < target_key.as_slice()).unwrap_or_default()
+}
+
 #[inline]
 fn is_exclude_boundary(req: &ShardScanRequest, user_key: &[u8]) -> bool {
     if req.exclude_start_key && is_equals(&req.start_key, user_key) {
@@ -229,12 +293,19 @@ fn is_exclude_boundary(req: &ShardScanRequest, user_key: &[u8]) -> bool {
 
 async fn resolve_txn<T: LatchManager>(
     latch_mgr: &T,
-    shard_id: u64,
-    start_version: u64,
+    req: &ShardScanRequest,
     user_key: &[u8],
     encoded_intent_value: &[u8],
 ) -> Result<Option<(Option<Vec<u8>>, u64)>> {
+    let (shard_id, start_version) = (req.shard_id, req.start_version);
     let intent = TxnIntent::decode(encoded_intent_value)?;
+    if req.txn_id != 0 && intent.start_version == req.txn_id {
+        if intent.value.is_none() && !intent.is_delete {
+            // skip nop intent, the committed versions are visible.
+            return Ok(None);
+        }
+        return Ok(Some((intent.value, intent.start_version)));
+    }
     if intent.start_version > start_version {
         // skip invisible versions.
         return Ok(None);
@@ -255,7 +326,10 @@ async fn resolve_txn<T: LatchManager>(
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use sekas_rock::fn_name;
+    use sekas_schema::system::txn::TXN_MAX_VERSION;
     use tempdir::TempDir;
 
     use super::*;
@@ -266,7 +340,7 @@ mod tests {
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, SHARD_ID, key, value, *version).unwrap();
             } else {
@@ -276,6 +350,115 @@ mod tests {
         engine.commit(wb, WriteStates::default(), false).unwrap();
     }
 
+    /// A latch manager returns the preset txn state and counts the resolving.
+    #[derive(Default)]
+    struct PresetLatchManager {
+        committed_version: Option<u64>,
+        num_resolved: AtomicUsize,
+    }
+
+    impl LatchManager for PresetLatchManager {
+        type Guard = <LocalLatchManager as LatchManager>::Guard;
+
+        async fn resolve_txn(
+            &self,
+            _shard_id: u64,
+            _user_key: &[u8],
+            _start_version: u64,
+            _intent_version: u64,
+        ) -> Result<Option<Value>> {
+            self.num_resolved.fetch_add(1, Ordering::SeqCst);
+            Ok(self.committed_version.map(|v| Value::with_value(b"intent".to_vec(), v)))
+        }
+
+        async fn acquire(&self, _shard_id: u64, _user_key: &[u8]) -> Result<Self::Guard> {
+            unreachable!()
+        }
+    }
+
+    fn park_txn_intent(engine: &GroupEngine, key: &[u8], start_version: u64) {
+        let mut wb = WriteBatch::default();
+        let intent = TxnIntent::with_put(start_version, Some(b"intent".to_vec())).encode_to_vec();
+        engine.put(&mut wb, SHARD_ID, key, &intent, TXN_INTENT_VERSION).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+    }
+
+    fn scan_values(resp: &ShardScanResponse) -> Vec<(Vec<u8>, u64)> {
+        resp.data
+            .iter()
+            .map(|v| (v.values[0].content.clone().unwrap(), v.values[0].version))
+            .collect()
+    }
+
+    #[sekas_macro::test]
+    async fn scan_with_parked_txn_intent() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        for i in 1..4u8 {
+            commit_values(&engine, &[i], &[Value::with_value(vec![i], 10)]);
+        }
+        park_txn_intent(&engine, &[2u8], 50);
+
+        let expect_committed = vec![(vec![1u8], 10), (vec![2u8], 10), (vec![3u8], 10)];
+
+        // case 1: the intent above the read version is ignored without waiting.
+        let latch_mgr = PresetLatchManager::default();
+        let scan_req =
+            ShardScanRequest { shard_id: SHARD_ID, start_version: 40, ..Default::default() };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(scan_values(&resp), expect_committed);
+        assert_eq!(latch_mgr.num_resolved.load(Ordering::SeqCst), 0);
+
+        // case 2: the inconsistent mode reads the latest committed values without
+        // waiting.
+        let scan_req = ShardScanRequest {
+            shard_id: SHARD_ID,
+            start_version: TXN_MAX_VERSION,
+            ignore_txn_intent: true,
+            ..Default::default()
+        };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(scan_values(&resp), expect_committed);
+        assert_eq!(latch_mgr.num_resolved.load(Ordering::SeqCst), 0);
+
+        // case 3: the intent below the read version is resolved, and it is committed.
+        let latch_mgr = PresetLatchManager { committed_version: Some(60), ..Default::default() };
+        let scan_req =
+            ShardScanRequest { shard_id: SHARD_ID, start_version: 100, ..Default::default() };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(
+            scan_values(&resp),
+            vec![(vec![1u8], 10), (b"intent".to_vec(), 60), (vec![3u8], 10)]
+        );
+        assert_eq!(latch_mgr.num_resolved.load(Ordering::SeqCst), 1);
+
+        // case 4: the intent below the read version is resolved, and it is aborted.
+        let latch_mgr = PresetLatchManager::default();
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(scan_values(&resp), expect_committed);
+        assert_eq!(latch_mgr.num_resolved.load(Ordering::SeqCst), 1);
+
+        // case 5: the txn is committed after the read version.
+        let latch_mgr = PresetLatchManager { committed_version: Some(120), ..Default::default() };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(scan_values(&resp), expect_committed);
+
+        // case 6: the intent is visible to the owner txn without resolving.
+        let latch_mgr = PresetLatchManager::default();
+        let scan_req = ShardScanRequest {
+            shard_id: SHARD_ID,
+            start_version: 50,
+            txn_id: 50,
+            ..Default::default()
+        };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(
+            scan_values(&resp),
+            vec![(vec![1u8], 10), (b"intent".to_vec(), 50), (vec![3u8], 10)]
+        );
+        assert_eq!(latch_mgr.num_resolved.load(Ordering::SeqCst), 0);
+    }
+
     #[sekas_macro::test]
     async fn scan_with_txn_intent() {
         // 1. write intent with version 90
@@ -399,6 +582,67 @@ mod tests {
         assert_eq!(resp.data[1].user_key, vec![4u8]);
     }
 
+    #[sekas_macro::test]
+    async fn scan_in_reverse_order() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let latch_mgr = LocalLatchManager::default();
+
+        for i in 1..100u8 {
+            let (key, value) = (vec![i], vec![i]);
+            commit_values(&engine, &key, &[Value::with_value(value.clone(), 99)]);
+            commit_values(&engine, &key, &[Value::with_value(value, 100)]);
+        }
+
+        // case 1: scan with limit returns the largest keys and all versions.
+        let scan_req = ShardScanRequest {
+            shard_id: SHARD_ID,
+            start_version: 1000,
+            limit: 2,
+            include_raw_data: true,
+            reverse: true,
+            ..Default::default()
+        };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert!(resp.has_more);
+        assert_eq!(resp.data.len(), 2);
+        assert_eq!(resp.data[0].user_key, vec![99u8]);
+        assert_eq!(resp.data[1].user_key, vec![98u8]);
+        let versions = resp.data[0].values.iter().map(|v| v.version).collect::<Vec<_>>();
+        assert_eq!(versions, vec![100, 99]);
+
+        // case 2: fetch the next page with the last key.
+        let scan_req = ShardScanRequest {
+            shard_id: SHARD_ID,
+            start_version: 1000,
+            end_key: Some(vec![98u8]),
+            exclude_end_key: true,
+            limit: 2,
+            reverse: true,
+            ..Default::default()
+        };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(resp.data.len(), 2);
+        assert_eq!(resp.data[0].user_key, vec![97u8]);
+        assert_eq!(resp.data[1].user_key, vec![96u8]);
+
+        // case 3: scan in range, excludes start key.
+        let scan_req = ShardScanRequest {
+            shard_id: SHARD_ID,
+            start_version: 1000,
+            start_key: Some(vec![3u8]),
+            end_key: Some(vec![5u8]),
+            exclude_start_key: true,
+            reverse: true,
+            ..Default::default()
+        };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert!(!resp.has_more);
+        assert_eq!(resp.data.len(), 2);
+        assert_eq!(resp.data[0].user_key, vec![5u8]);
+        assert_eq!(resp.data[1].user_key, vec![4u8]);
+    }
+
     #[sekas_macro::test]
     async fn scan_with_prefix() {
         let dir = TempDir::new(fn_name!()).unwrap();
@@ -590,4 +834,23 @@ mod tests {
         assert_eq!(resp.data[0].values[0].version, TXN_INTENT_VERSION);
         assert_eq!(resp.data[0].values[1].version, 100);
     }
+
+    #[sekas_macro::test]
+    async fn scan_beneath_gc_watermark() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let latch_mgr = LocalLatchManager::default();
+        commit_values(&engine, b"a", &[Value::with_value(b"a".to_vec(), 100)]);
+        engine.gc_state().set_watermark(50);
+
+        let scan_req =
+            ShardScanRequest { shard_id: SHARD_ID, start_version: 40, ..Default::default() };
+        let r = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await;
+        assert!(matches!(r, Err(Error::VersionTooOld(40, 50))), "{r:?}");
+
+        let scan_req =
+            ShardScanRequest { shard_id: SHARD_ID, start_version: 100, ..Default::default() };
+        let resp = scan(&ExecCtx::default(), &engine, &latch_mgr, &scan_req).await.unwrap();
+        assert_eq!(scan_values(&resp), vec![(b"a".to_vec(), 100)]);
+    }
 }
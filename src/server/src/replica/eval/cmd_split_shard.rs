@@ -13,13 +13,18 @@
 // limitations under the License.
 
 use log::debug;
+use prost::Message;
 use sekas_api::server::v1::*;
+use sekas_schema::system::txn::TXN_INTENT_VERSION;
 
 use crate::replica::{EvalResult, GroupEngine, SplitShard, SyncOp};
 use crate::{Error, Result};
 
 /// Eval split shard request.
-pub(crate) fn split_shard(engine: &GroupEngine, req: &SplitShardRequest) -> Result<EvalResult> {
+pub(crate) async fn split_shard(
+    engine: &GroupEngine,
+    req: &SplitShardRequest,
+) -> Result<EvalResult> {
     let old_shard_id = req.old_shard_id;
     let new_shard_id = req.new_shard_id;
 
@@ -30,6 +35,10 @@ pub(crate) fn split_shard(engine: &GroupEngine, req: &SplitShardRequest) -> Resu
         req.split_key.is_some()
     );
 
+    if sekas_schema::shard::is_txn_shard(old_shard_id) {
+        return Err(Error::TxnShardFenced(old_shard_id));
+    }
+
     let shard_desc = engine.shard_desc(old_shard_id)?;
     let split_key = match req.split_key.as_ref().cloned() {
         Some(split_key) => {
@@ -38,6 +47,12 @@ pub(crate) fn split_shard(engine: &GroupEngine, req: &SplitShardRequest) -> Resu
                     "the user provided split key is not belong to the shard {old_shard_id}"
                 )));
             }
+            if shard_desc.range.as_ref().is_some_and(|range| range.start == split_key) {
+                // The left shard would be empty.
+                return Err(Error::InvalidArgument(format!(
+                    "the user provided split key is the start of the shard {old_shard_id}"
+                )));
+            }
             split_key
         }
         None => engine.estimate_split_key(old_shard_id)?.ok_or_else(|| {
@@ -49,13 +64,44 @@ pub(crate) fn split_shard(engine: &GroupEngine, req: &SplitShardRequest) -> Resu
         })?,
     };
 
+    let split_key = align_append_split_key(engine, old_shard_id, split_key).await?;
     debug!("execute split shard {}, split key {:?}", old_shard_id, split_key);
-    debug_assert!(
-        sekas_schema::shard::belong_to(&shard_desc, &split_key),
-        "estimated split key {split_key:?} is not belongs to shard {shard_desc:?}"
-    );
+    // The estimated split key might be the start of the shard, reject it before
+    // proposing, since the applying uses the same range math.
+    if let Err(err) = sekas_schema::shard::split_range(&shard_desc, new_shard_id, split_key.clone())
+    {
+        return Err(Error::InvalidArgument(format!("split shard {old_shard_id}: {err}")));
+    }
 
     let split_shard = SplitShard { old_shard_id, new_shard_id, split_key };
     let sync_op = Box::new(SyncOp { split_shard: Some(split_shard), ..Default::default() });
-    Ok(EvalResult { batch: None, op: Some(sync_op) })
+    Ok(EvalResult { op: Some(sync_op), ..Default::default() })
+}
+
+/// Move the split key located in the entries appended to a prefix to the prefix
+/// itself, so that the prefix holding the sequence and its entries are never
+/// split apart. See `PutType::AppendSequence`.
+async fn align_append_split_key(
+    engine: &GroupEngine,
+    shard_id: u64,
+    split_key: Vec<u8>,
+) -> Result<Vec<u8>> {
+    let Some((prefix, _)) = sekas_api::split_append_entry_key(&split_key) else {
+        return Ok(split_key);
+    };
+    let is_append_prefix = match engine.get(shard_id, prefix).await? {
+        Some(value) if value.version == TXN_INTENT_VERSION => match value.content {
+            Some(content) => TxnIntent::decode(content.as_slice())?.append_sequence.is_some(),
+            None => false,
+        },
+        Some(value) => {
+            value.content.is_some_and(|content| content.len() == sekas_api::APPEND_SEQUENCE_LEN)
+        }
+        None => false,
+    };
+    if is_append_prefix {
+        debug!("align the split key of shard {shard_id} to the append prefix {prefix:?}");
+        return Ok(prefix.to_vec());
+    }
+    Ok(split_key)
 }
@@ -15,18 +15,23 @@
 use log::{debug, trace};
 use prost::Message;
 use sekas_api::server::v1::*;
-use sekas_rock::num::decode_i64;
-use sekas_schema::system::txn::TXN_INTENT_VERSION;
+use sekas_rock::num::{decode_i64, decode_u64};
+use sekas_schema::shard;
+use sekas_schema::system::txn::{TXN_INTENT_VERSION, TXN_MAX_VERSION};
 
 use super::cas::eval_conditions;
 use super::latch::DeferSignalLatchGuard;
-use super::LatchGuard;
+use super::{LatchGuard, LatchManager};
 use crate::engine::{GroupEngine, SnapshotMode, WriteBatch};
+use crate::error::BusyReason;
 use crate::node::move_shard::ForwardCtx;
 use crate::replica::ExecCtx;
 use crate::serverpb::v1::EvalResult;
 use crate::{Error, Result};
 
+/// The max size of the JSON value after applying a merge patch.
+const MAX_JSON_VALUE_SIZE: usize = 1024 * 1024;
+
 pub(crate) async fn write_intent<T: LatchGuard>(
     exec_ctx: &ExecCtx,
     group_engine: &GroupEngine,
@@ -57,6 +62,7 @@ pub(crate) async fn write_intent<T: LatchGuard>(
         req.start_version,
         req.shard_id,
         user_key,
+        req.wait_die,
     )
     .await?;
 
@@ -68,11 +74,13 @@ pub(crate) async fn write_intent<T: LatchGuard>(
     }
 
     let mut wb = WriteBatch::default();
+    let mut sequence = None;
+    let mut new_value = None;
     let prev_value = match write {
         WriteRequest::Delete(del) => {
             if !skip_write {
                 if let Some(cond_idx) = eval_conditions(prev_value.as_ref(), &del.conditions)? {
-                    return Err(Error::CasFailed(0, cond_idx as u64, prev_value));
+                    return cas_failed(req, cond_idx, prev_value);
                 }
                 let txn_intent = TxnIntent::tombstone(req.start_version).encode_to_vec();
                 group_engine.put(
@@ -89,16 +97,49 @@ pub(crate) async fn write_intent<T: LatchGuard>(
                 None
             }
         }
+        WriteRequest::Put(put) if put.put_type() == PutType::AppendSequence => {
+            sequence = Some(if skip_write {
+                // Support idempotent, the sequence assigned before is returned.
+                read_target_intent(group_engine, req.start_version, req.shard_id, &put.key)
+                    .await?
+                    .and_then(|intent| intent.append_sequence)
+                    .ok_or_else(|| {
+                        Error::InvalidData(format!(
+                            "the append intent of key {:?} has no sequence",
+                            put.key
+                        ))
+                    })?
+            } else {
+                if let Some(cond_idx) = eval_conditions(prev_value.as_ref(), &put.conditions)? {
+                    return cas_failed(req, cond_idx, prev_value);
+                }
+                write_append_intents(group_engine, &mut wb, req, put, prev_value.as_ref())?
+            });
+            if put.return_new_value {
+                new_value = sequence.map(|sequence| sequence.to_be_bytes().to_vec());
+            }
+            if put.take_prev_value {
+                prev_value
+            } else {
+                None
+            }
+        }
         WriteRequest::Put(put) => {
             if !skip_write {
                 log::debug!("eval conditions {:?}, prev value {:?}", put.conditions, prev_value);
                 if let Some(cond_idx) = eval_conditions(prev_value.as_ref(), &put.conditions)? {
-                    return Err(Error::CasFailed(0, cond_idx as u64, prev_value));
+                    return cas_failed(req, cond_idx, prev_value);
                 }
                 let apply_value =
                     apply_put_op(put.put_type(), prev_value.as_ref(), put.value.clone())?;
-                let txn_intent =
-                    TxnIntent::with_put(req.start_version, apply_value).encode_to_vec();
+                // The value is captured under the latch, so it is produced by this put
+                // exactly, rather than a later concurrent one.
+                if put.return_new_value {
+                    new_value = apply_value.clone();
+                }
+                let txn_intent = TxnIntent::with_put(req.start_version, apply_value)
+                    .with_ttl(put.ttl_ms)
+                    .encode_to_vec();
                 group_engine.put(
                     &mut wb,
                     req.shard_id,
@@ -106,6 +147,12 @@ pub(crate) async fn write_intent<T: LatchGuard>(
                     &txn_intent,
                     TXN_INTENT_VERSION,
                 )?;
+            } else if put.return_new_value {
+                // Support idempotent, the value applied before is returned.
+                new_value =
+                    read_target_intent(group_engine, req.start_version, req.shard_id, &put.key)
+                        .await?
+                        .and_then(|intent| intent.value);
             }
             if put.take_prev_value {
                 prev_value
@@ -115,12 +162,29 @@ pub(crate) async fn write_intent<T: LatchGuard>(
         }
     };
 
-    let resp = WriteResponse { prev_value };
+    let status = req.report_op_status.then(WriteStatus::applied);
+    let resp = WriteResponse { prev_value, sequence, new_value, status };
     let eval_result =
         if !wb.is_empty() { Some(EvalResult::with_batch(wb.data().to_owned())) } else { None };
     Ok((eval_result, WriteIntentResponse { write: Some(resp) }))
 }
 
+/// Report the unsatisfied condition in the status of the response if it is
+/// required by the request, otherwise fail with `CasFailed`. Nothing is
+/// written.
+fn cas_failed(
+    req: &WriteIntentRequest,
+    cond_idx: usize,
+    prev_value: Option<Value>,
+) -> Result<(Option<EvalResult>, WriteIntentResponse)> {
+    if !req.report_op_status {
+        return Err(Error::CasFailed(0, cond_idx as u64, prev_value));
+    }
+    let status = WriteStatus::cas_failed(cond_idx as u64, prev_value);
+    let resp = WriteResponse { status: Some(status), ..Default::default() };
+    Ok((None, WriteIntentResponse { write: Some(resp) }))
+}
+
 pub(crate) async fn commit_intent<T: LatchGuard>(
     exec_ctx: &ExecCtx,
     group_engine: &GroupEngine,
@@ -137,9 +201,15 @@ pub(crate) async fn commit_intent<T: LatchGuard>(
     if let Some(desc) = exec_ctx.move_shard_desc.as_ref() {
         let shard_id = desc.shard_desc.as_ref().unwrap().id;
         if shard_id == req.shard_id {
-            let payload = group_engine.get_all_versions(req.shard_id, &req.user_key).await?;
-            let forward_ctx =
-                ForwardCtx { shard_id, dest_group_id: desc.dest_group_id, payloads: vec![payload] };
+            let mut payloads = vec![group_engine.get_all_versions(shard_id, &req.user_key).await?];
+            // The appended entry is resolved together with the prefix.
+            if let Some(entry_key) =
+                append_entry_key_of(group_engine, req.start_version, shard_id, &req.user_key)
+                    .await?
+            {
+                payloads.push(group_engine.get_all_versions(shard_id, &entry_key).await?);
+            }
+            let forward_ctx = ForwardCtx { shard_id, dest_group_id: desc.dest_group_id, payloads };
             return Err(Error::Forward(forward_ctx));
         }
     }
@@ -173,7 +243,40 @@ pub(crate) async fn commit_intent<T: LatchGuard>(
             sekas_rock::ascii::escape_bytes(&req.user_key),
             sekas_rock::ascii::escape_bytes(&value),
         );
-        group_engine.put(&mut wb, req.shard_id, &req.user_key, &value, req.commit_version)?;
+        if intent.append_sequence.is_some() {
+            // The last sequence of the prefix never expires, otherwise the sequences would
+            // be assigned again.
+            group_engine.put(&mut wb, req.shard_id, &req.user_key, &value, req.commit_version)?;
+        } else {
+            // The expiry is computed at commit, so the value lives for the whole TTL
+            // since it becomes visible.
+            group_engine.put_with_ttl(
+                &mut wb,
+                req.shard_id,
+                &req.user_key,
+                &value,
+                req.commit_version,
+                intent.ttl_ms,
+            )?;
+        }
+    }
+    if let Some(sequence) = intent.append_sequence {
+        let entry_key = sekas_api::append_entry_key(&req.user_key, sequence);
+        let entry_intent =
+            read_target_intent(group_engine, req.start_version, req.shard_id, &entry_key).await?;
+        if let Some(entry_intent) = entry_intent {
+            if let Some(value) = entry_intent.value {
+                group_engine.delete(&mut wb, req.shard_id, &entry_key, TXN_INTENT_VERSION)?;
+                group_engine.put_with_ttl(
+                    &mut wb,
+                    req.shard_id,
+                    &entry_key,
+                    &value,
+                    req.commit_version,
+                    entry_intent.ttl_ms,
+                )?;
+            }
+        }
     }
 
     trace!(
@@ -204,58 +307,268 @@ pub(crate) async fn clear_intent<T: LatchGuard>(
     if let Some(desc) = exec_ctx.move_shard_desc.as_ref() {
         let shard_id = desc.shard_desc.as_ref().unwrap().id;
         if shard_id == req.shard_id {
-            let payload = group_engine.get_all_versions(req.shard_id, &req.user_key).await?;
-            let forward_ctx =
-                ForwardCtx { shard_id, dest_group_id: desc.dest_group_id, payloads: vec![payload] };
+            let mut payloads = vec![group_engine.get_all_versions(shard_id, &req.user_key).await?];
+            // The appended entry is resolved together with the prefix.
+            if let Some(entry_key) =
+                append_entry_key_of(group_engine, req.start_version, shard_id, &req.user_key)
+                    .await?
+            {
+                payloads.push(group_engine.get_all_versions(shard_id, &entry_key).await?);
+            }
+            let forward_ctx = ForwardCtx { shard_id, dest_group_id: desc.dest_group_id, payloads };
             return Err(Error::Forward(forward_ctx));
         }
     }
 
-    if read_target_intent(group_engine, req.start_version, req.shard_id, &req.user_key)
-        .await?
-        .is_none()
-    {
+    let Some(intent) =
+        read_target_intent(group_engine, req.start_version, req.shard_id, &req.user_key).await?
+    else {
         return Ok(None);
-    }
+    };
 
     let mut wb = WriteBatch::default();
     group_engine.delete(&mut wb, req.shard_id, &req.user_key, TXN_INTENT_VERSION)?;
+    if let Some(sequence) = intent.append_sequence {
+        let entry_key = sekas_api::append_entry_key(&req.user_key, sequence);
+        if read_target_intent(group_engine, req.start_version, req.shard_id, &entry_key)
+            .await?
+            .is_some()
+        {
+            group_engine.delete(&mut wb, req.shard_id, &entry_key, TXN_INTENT_VERSION)?;
+        }
+    }
 
     latch_guard.signal_all(TxnState::Aborted, None);
 
     Ok(if wb.is_empty() { None } else { Some(EvalResult::with_batch(wb.data().to_owned())) })
 }
 
+/// Check that no key exists under the prefix in the shard, it is the
+/// commit-time condition of `Txn::put_if_prefix_empty`. The intents written by
+/// the txn itself are ignored.
+///
+/// The intents of the younger txns are waited, but the running older txns are
+/// treated as conflicts, so that two txns checking the intents of each other
+/// never wait for each other.
+pub(crate) async fn check_prefix_empty<T: LatchManager>(
+    exec_ctx: &ExecCtx,
+    group_engine: &GroupEngine,
+    latch_mgr: &T,
+    req: &CheckPrefixEmptyRequest,
+) -> Result<CheckPrefixEmptyResponse> {
+    if exec_ctx.move_shard_desc.as_ref().is_some_and(|desc| desc.get_shard_id() == req.shard_id) {
+        return Err(Error::ServiceIsBusy(BusyReason::Moving));
+    }
+
+    let desc = group_engine.shard_desc(req.shard_id)?;
+    let start_key = std::cmp::max(req.prefix.clone(), shard::start_key(&desc));
+    if !shard::belong_to(&desc, &start_key) {
+        return Ok(CheckPrefixEmptyResponse::default());
+    }
+
+    let snapshot_mode = SnapshotMode::Start { start_key: Some(&start_key) };
+    let snapshot = group_engine.snapshot(req.shard_id, snapshot_mode)?;
+    let mut snapshot = snapshot.with_read_version(req.start_version);
+    while let Some(mvcc_iter) = snapshot.next() {
+        let mut mvcc_iter = mvcc_iter?;
+        let user_key = mvcc_iter.user_key().to_owned();
+        if !user_key.starts_with(&req.prefix) {
+            break;
+        }
+        for entry in &mut mvcc_iter {
+            let entry = entry?;
+            if entry.version() != TXN_INTENT_VERSION {
+                // The latest committed version decides the existence of the key.
+                if entry.value().is_some() {
+                    return Ok(CheckPrefixEmptyResponse { exists_key: Some(user_key) });
+                }
+                break;
+            }
+
+            let content = entry.value().ok_or_else(|| {
+                Error::InvalidData(format!("the intent value of key: {user_key:?} not exists"))
+            })?;
+            let intent = TxnIntent::decode(content)?;
+            let value = match intent.start_version.cmp(&req.start_version) {
+                std::cmp::Ordering::Equal => continue,
+                std::cmp::Ordering::Less => {
+                    latch_mgr
+                        .resolve_txn_or_conflict(
+                            req.shard_id,
+                            &user_key,
+                            TXN_MAX_VERSION,
+                            intent.start_version,
+                        )
+                        .await?
+                }
+                std::cmp::Ordering::Greater => {
+                    latch_mgr
+                        .resolve_txn(req.shard_id, &user_key, TXN_MAX_VERSION, intent.start_version)
+                        .await?
+                }
+            };
+            if let Some(value) = value {
+                if value.content.is_some() {
+                    return Ok(CheckPrefixEmptyResponse { exists_key: Some(user_key) });
+                }
+                break;
+            }
+            // The txn is aborted, read the committed versions.
+        }
+    }
+    Ok(CheckPrefixEmptyResponse::default())
+}
+
+/// Assign the next sequence of the prefix, and write the intents of both the
+/// prefix and the entry appended. The whole range of the entries must belong to
+/// the shard, so that the prefix and its entries are never split apart.
+fn write_append_intents(
+    group_engine: &GroupEngine,
+    wb: &mut WriteBatch,
+    req: &WriteIntentRequest,
+    put: &PutRequest,
+    prev_value: Option<&Value>,
+) -> Result<u64> {
+    let desc = group_engine.shard_desc(req.shard_id)?;
+    let last_entry_key = sekas_api::append_entry_key(&put.key, u64::MAX);
+    if !shard::belong_to(&desc, &put.key) || !shard::belong_to(&desc, &last_entry_key) {
+        return Err(Error::InvalidArgument(format!(
+            "the entries of the append prefix {:?} straddle the boundary of shard {}",
+            put.key, req.shard_id
+        )));
+    }
+
+    let sequence = next_append_sequence(prev_value)?;
+    let entry_key = sekas_api::append_entry_key(&put.key, sequence);
+    let (entry_intent, _) =
+        read_intent_and_next_key(group_engine, req.start_version, req.shard_id, &entry_key)?;
+    if entry_intent.is_some_and(|intent| intent.start_version != req.start_version) {
+        trace!("txn {} append entry {entry_key:?} is written by another txn", req.start_version);
+        return Err(Error::TxnConflict);
+    }
+
+    let prefix_intent = TxnIntent::with_append(req.start_version, sequence).encode_to_vec();
+    group_engine.put(wb, req.shard_id, &put.key, &prefix_intent, TXN_INTENT_VERSION)?;
+    let entry_intent =
+        TxnIntent::with_put(req.start_version, Some(put.value.clone())).with_ttl(put.ttl_ms);
+    group_engine.put(
+        wb,
+        req.shard_id,
+        &entry_key,
+        &entry_intent.encode_to_vec(),
+        TXN_INTENT_VERSION,
+    )?;
+    trace!("txn {} append entry {entry_key:?} with sequence {sequence}", req.start_version);
+    Ok(sequence)
+}
+
+/// The next sequence of the prefix, the sequences start from 1.
+fn next_append_sequence(prev_value: Option<&Value>) -> Result<u64> {
+    let Some(content) = prev_value.and_then(|v| v.content.as_ref()) else {
+        return Ok(1);
+    };
+    let sequence = decode_u64(content).ok_or_else(|| {
+        Error::InvalidArgument("the exists value of the append prefix is not a valid u64".into())
+    })?;
+    sequence.checked_add(1).ok_or_else(|| {
+        Error::InvalidArgument("the sequence of the append prefix is exhausted".into())
+    })
+}
+
 fn apply_put_op(
     r#type: PutType,
     prev_value: Option<&Value>,
     value: Vec<u8>,
 ) -> Result<Option<Vec<u8>>> {
     match r#type {
-        PutType::AddI64 => {
+        PutType::AddI64 | PutType::ForceAddI64 => {
             let delta = decode_i64(&value)
                 .ok_or_else(|| Error::InvalidArgument("input value is not a valid i64".into()))?;
 
+            // The exists value must be an i64 encoded by `sekas_rock::num`, otherwise
+            // the add would produce a wrong result silently.
             let former_value = match prev_value.and_then(|v| v.content.as_ref()) {
-                Some(content) => decode_i64(content).ok_or_else(|| {
-                    Error::InvalidArgument("the exists value is not a valid i64".into())
-                })?,
+                Some(content) => match decode_i64(content) {
+                    Some(former_value) => former_value,
+                    None if r#type == PutType::ForceAddI64 => {
+                        debug!("reset the exists value of {} bytes by force add", content.len());
+                        0
+                    }
+                    None => {
+                        return Err(Error::ValueTypeMismatch {
+                            expected: "i64".to_owned(),
+                            actual_len: content.len() as u64,
+                        })
+                    }
+                },
                 None => 0,
             };
             trace!("add i64 former value {} delta value {}", former_value, delta);
             Ok(Some(former_value.wrapping_add(delta).to_be_bytes().to_vec()))
         }
+        PutType::MergeJson => {
+            let patch: serde_json::Value = serde_json::from_slice(&value)
+                .map_err(|err| Error::InvalidJson(format!("the merge patch: {err}")))?;
+
+            // The missing value is treated as `null`, so that the patch becomes the
+            // document.
+            let mut document = match prev_value.and_then(|v| v.content.as_ref()) {
+                Some(content) => serde_json::from_slice(content)
+                    .map_err(|err| Error::InvalidJson(format!("the exists value: {err}")))?,
+                None => serde_json::Value::Null,
+            };
+            merge_json_patch(&mut document, patch);
+            let merged = serde_json::to_vec(&document)
+                .map_err(|err| Error::InvalidJson(format!("the merged value: {err}")))?;
+            if merged.len() > MAX_JSON_VALUE_SIZE {
+                return Err(Error::InvalidArgument(format!(
+                    "the merged json value size {} exceeds the limit {MAX_JSON_VALUE_SIZE}",
+                    merged.len()
+                )));
+            }
+            trace!("merge json patch, the merged value size {}", merged.len());
+            Ok(Some(merged))
+        }
+        PutType::AppendSequence => {
+            Ok(Some(next_append_sequence(prev_value)?.to_be_bytes().to_vec()))
+        }
         PutType::None => Ok(Some(value)),
         PutType::Nop => Ok(None),
     }
 }
 
+/// Apply the JSON merge patch to the target, see RFC 7386.
+fn merge_json_patch(target: &mut serde_json::Value, patch: serde_json::Value) {
+    use serde_json::Value as JsonValue;
+
+    let JsonValue::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = JsonValue::Object(Default::default());
+    }
+    let object = target.as_object_mut().expect("target is an object");
+    for (name, value) in patch {
+        if value.is_null() {
+            object.remove(&name);
+        } else {
+            merge_json_patch(object.entry(name).or_insert(JsonValue::Null), value);
+        }
+    }
+}
+
+/// Read the first value which is not an intent. If `die_if_younger` is set, the
+/// txn returns [`Error::TxnConflict`] rather than waiting for a running txn
+/// which starts before it (wait-die), so that the txns writing the same keys
+/// in different orders will not wait for each other forever.
 async fn read_first_non_intent_key<T: LatchGuard>(
     latch_guard: &mut DeferSignalLatchGuard<T>,
     engine: &GroupEngine,
     start_version: u64,
     shard_id: u64,
     key: &[u8],
+    die_if_younger: bool,
 ) -> Result<(bool, Option<Value>)> {
     loop {
         let (txn_intent, prev_value) =
@@ -268,7 +581,11 @@ async fn read_first_non_intent_key<T: LatchGuard>(
         }
 
         trace!("another txn {} intent exists", txn_intent.start_version);
-        latch_guard.resolve_txn(shard_id, key, txn_intent).await?;
+        if die_if_younger && txn_intent.start_version < start_version {
+            latch_guard.resolve_txn_or_conflict(shard_id, key, txn_intent).await?;
+        } else {
+            latch_guard.resolve_txn(shard_id, key, txn_intent).await?;
+        }
     }
 }
 
@@ -278,7 +595,8 @@ fn read_intent_and_next_key(
     shard_id: u64,
     key: &[u8],
 ) -> Result<(Option<TxnIntent>, Option<Value>)> {
-    let mut snapshot = engine.snapshot(shard_id, SnapshotMode::Key { key })?;
+    let snapshot = engine.snapshot(shard_id, SnapshotMode::Key { key })?;
+    let mut snapshot = snapshot.with_read_version(start_version);
     if let Some(mvcc_iter) = snapshot.next() {
         let mut mvcc_iter = mvcc_iter?;
         if let Some(entry) = mvcc_iter.next() {
@@ -301,7 +619,7 @@ fn read_intent_and_next_key(
     Ok((None, None))
 }
 
-async fn read_target_intent(
+pub(super) async fn read_target_intent(
     engine: &GroupEngine,
     start_version: u64,
     shard_id: u64,
@@ -326,11 +644,28 @@ async fn read_target_intent(
     Ok(Some(intent))
 }
 
+/// The key of the entry appended by the intent of the prefix, `None` is
+/// returned if the intent is not an append.
+async fn append_entry_key_of(
+    engine: &GroupEngine,
+    start_version: u64,
+    shard_id: u64,
+    prefix: &[u8],
+) -> Result<Option<Vec<u8>>> {
+    let intent = read_target_intent(engine, start_version, shard_id, prefix).await?;
+    let sequence = intent.and_then(|intent| intent.append_sequence);
+    Ok(sequence.map(|sequence| sekas_api::append_entry_key(prefix, sequence)))
+}
+
 // An atomic operation will not conflict with previous values.
 fn is_atomic_operation(write: &WriteRequest) -> bool {
     match write {
         WriteRequest::Put(put)
-            if put.conditions.is_empty() && put.put_type == PutType::AddI64 as i32 =>
+            if put.conditions.is_empty()
+                && (put.put_type == PutType::AddI64 as i32
+                    || put.put_type == PutType::ForceAddI64 as i32
+                    || put.put_type == PutType::MergeJson as i32
+                    || put.put_type == PutType::AppendSequence as i32) =>
         {
             true
         }
@@ -340,6 +675,7 @@ fn is_atomic_operation(write: &WriteRequest) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::sync::atomic::AtomicU64;
     use std::sync::{Arc, Mutex};
     use std::time::Duration;
@@ -397,7 +733,7 @@ mod tests {
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, 1, key, value, *version).unwrap();
             } else {
@@ -485,6 +821,7 @@ mod tests {
                 take_prev_value: true,
                 ..Default::default()
             })),
+            ..Default::default()
         }
     }
 
@@ -581,6 +918,109 @@ mod tests {
         assert!(write.prev_value.is_none());
     }
 
+    #[sekas_macro::test]
+    async fn write_intent_return_new_value() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let mut latch_guard = DeferSignalLatchGuard::<NotifyLatchGuard>::empty();
+
+        let key = b"counter".to_vec();
+        let start_version = 9394;
+        commit_values(
+            &engine,
+            &key,
+            &[Value::with_value(10i64.to_be_bytes().to_vec(), start_version - 100)],
+        );
+
+        let req = WriteIntentRequest {
+            start_version,
+            shard_id: 1,
+            write: Some(WriteRequest::Put(
+                WriteBuilder::new(key.clone()).return_new_value().ensure_add(5),
+            )),
+            ..Default::default()
+        };
+        let (eval_result, resp) =
+            write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await.unwrap();
+        assert_eq!(resp.write.unwrap().new_value, Some(15i64.to_be_bytes().to_vec()));
+        let wb = WriteBatch::new(&eval_result.unwrap().batch.unwrap().data);
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        // The value applied before is returned by the retried write.
+        let (eval_result, resp) =
+            write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await.unwrap();
+        assert!(eval_result.is_none());
+        assert_eq!(resp.write.unwrap().new_value, Some(15i64.to_be_bytes().to_vec()));
+    }
+
+    /// Write and commit the put in a txn, returns the new value of the put.
+    async fn put_and_commit(
+        engine: &GroupEngine,
+        start_version: u64,
+        put: PutRequest,
+    ) -> Option<Vec<u8>> {
+        let mut latch_guard = DeferSignalLatchGuard::<NotifyLatchGuard>::empty();
+        let user_key = put.key.clone();
+        let req = WriteIntentRequest {
+            start_version,
+            shard_id: 1,
+            write: Some(WriteRequest::Put(put)),
+            ..Default::default()
+        };
+        let (eval_result, resp) =
+            write_intent(&ExecCtx::default(), engine, &mut latch_guard, &req).await.unwrap();
+        commit_eval_result(engine, eval_result);
+        let commit_version = start_version + 1;
+        let req = CommitIntentRequest { shard_id: 1, start_version, commit_version, user_key };
+        let eval_result =
+            commit_intent(&ExecCtx::default(), engine, &mut latch_guard, &req).await.unwrap();
+        commit_eval_result(engine, eval_result);
+        resp.write.unwrap().new_value
+    }
+
+    #[sekas_macro::test]
+    async fn commit_intent_with_ttl() {
+        const SECOND: u64 = 1_000_000_000;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let key = b"counter".to_vec();
+        let ttl = Duration::from_secs(60);
+
+        // The expiry is derived from the commit version.
+        let put = WriteBuilder::new(key.clone()).with_ttl(ttl).ensure_add(5);
+        put_and_commit(&engine, 100 * SECOND, put).await;
+        let value = engine.get(1, &key).await.unwrap().unwrap();
+        assert_eq!(value.expire_at, 160_000, "{value:?}");
+
+        // The add on the key expired at the start version starts from zero, and
+        // the expiry is cleared by the write without TTL.
+        let new_value = put_and_commit(
+            &engine,
+            160 * SECOND,
+            WriteBuilder::new(key.clone()).return_new_value().ensure_add(7),
+        )
+        .await;
+        assert_eq!(new_value, Some(7i64.to_be_bytes().to_vec()));
+        let value = engine.get(1, &key).await.unwrap().unwrap();
+        assert_eq!(value, Value::with_value(7i64.to_be_bytes().to_vec(), 160 * SECOND + 1));
+
+        // The shorter TTL of the table wins.
+        engine.gc_state().set_table_ttls(HashMap::from([(1, 1000)]));
+        let put = WriteBuilder::new(key.clone()).with_ttl(ttl).ensure_put(b"1".to_vec());
+        put_and_commit(&engine, 300 * SECOND, put).await;
+        let value = engine.get(1, &key).await.unwrap().unwrap();
+        assert_eq!(value.expire_at, 301_000, "{value:?}");
+        let put = WriteBuilder::new(key.clone()).ensure_put(b"2".to_vec());
+        put_and_commit(&engine, 400 * SECOND, put).await;
+        let value = engine.get(1, &key).await.unwrap().unwrap();
+        assert_eq!(value.expire_at, 401_000, "{value:?}");
+        let put = WriteBuilder::new(key.clone()).with_ttl(Duration::from_millis(10));
+        put_and_commit(&engine, 500 * SECOND, put.ensure_put(b"3".to_vec())).await;
+        let value = engine.get(1, &key).await.unwrap().unwrap();
+        assert_eq!(value.expire_at, 500_010, "{value:?}");
+    }
+
     #[sekas_macro::test]
     async fn write_intent_with_condition() {
         let dir = TempDir::new(fn_name!()).unwrap();
@@ -597,6 +1037,7 @@ mod tests {
             write: Some(WriteRequest::Put(
                 WriteBuilder::new(key.clone()).expect_exists().ensure_put(b"value".to_vec()),
             )),
+            ..Default::default()
         };
         let r = write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await;
         assert!(matches!(r, Err(Error::CasFailed(0, 0, _))), "{r:?}");
@@ -608,13 +1049,29 @@ mod tests {
             write: Some(WriteRequest::Delete(
                 WriteBuilder::new(key.clone()).expect_exists().ensure_delete(),
             )),
+            ..Default::default()
         };
         let r = write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await;
         assert!(matches!(r, Err(Error::CasFailed(0, 0, _))), "{r:?}");
 
+        // 3. the unsatisfied condition is reported in the status, nothing is written.
+        let req = WriteIntentRequest {
+            start_version,
+            shard_id: 1,
+            write: Some(WriteRequest::Put(
+                WriteBuilder::new(key.clone()).expect_exists().ensure_put(b"value".to_vec()),
+            )),
+            report_op_status: true,
+            ..Default::default()
+        };
+        let (eval_result, resp) =
+            write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await.unwrap();
+        assert!(eval_result.is_none());
+        assert_eq!(resp.write.unwrap().status, Some(WriteStatus::cas_failed(0, None)));
+
         commit_values(&engine, &key, &[Value::with_value(b"value".to_vec(), start_version - 100)]);
 
-        // 3. put exists success
+        // 4. put exists success
         let req = WriteIntentRequest {
             start_version,
             shard_id: 1,
@@ -624,9 +1081,12 @@ mod tests {
                     .take_prev_value()
                     .ensure_put(b"value".to_vec()),
             )),
+            report_op_status: true,
+            ..Default::default()
         };
-        let r = write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await;
-        assert!(r.is_ok());
+        let (_, resp) =
+            write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await.unwrap();
+        assert_eq!(resp.write.unwrap().status, Some(WriteStatus::applied()));
     }
 
     #[test]
@@ -672,6 +1132,109 @@ mod tests {
         let value = Value::with_value(vec![2u8], 1);
         assert!(matches!(
             apply_put_op(PutType::AddI64, Some(&value), 1i64.to_be_bytes().to_vec()),
+            Err(Error::ValueTypeMismatch { actual_len: 1, .. })
+        ));
+
+        // The force add discards the exists value.
+        let r = apply_put_op(PutType::ForceAddI64, Some(&value), 5i64.to_be_bytes().to_vec());
+        assert_eq!(decode_i64(&r.unwrap().unwrap()), Some(5));
+        let value = Value::with_value(2i64.to_be_bytes().to_vec(), 1);
+        let r = apply_put_op(PutType::ForceAddI64, Some(&value), 5i64.to_be_bytes().to_vec());
+        assert_eq!(decode_i64(&r.unwrap().unwrap()), Some(7));
+    }
+
+    #[test]
+    fn apply_put_op_merge_json() {
+        use serde_json::json;
+
+        struct TestCase {
+            prev_value: Option<serde_json::Value>,
+            patch: serde_json::Value,
+            expect: serde_json::Value,
+        }
+
+        let cases = vec![
+            // prev value not exists
+            TestCase { prev_value: None, patch: json!({"a": 1}), expect: json!({"a": 1}) },
+            TestCase { prev_value: None, patch: json!({"a": null}), expect: json!({}) },
+            TestCase { prev_value: None, patch: json!([1, 2]), expect: json!([1, 2]) },
+            // normal case, see the examples of RFC 7386.
+            TestCase {
+                prev_value: Some(json!({"a": "b"})),
+                patch: json!({"a": "c"}),
+                expect: json!({"a": "c"}),
+            },
+            TestCase {
+                prev_value: Some(json!({"a": "b"})),
+                patch: json!({"b": "c"}),
+                expect: json!({"a": "b", "b": "c"}),
+            },
+            TestCase {
+                prev_value: Some(json!({"a": "b", "b": "c"})),
+                patch: json!({"a": null}),
+                expect: json!({"b": "c"}),
+            },
+            TestCase {
+                prev_value: Some(json!({"a": {"b": "c"}})),
+                patch: json!({"a": {"b": "d", "c": null}}),
+                expect: json!({"a": {"b": "d"}}),
+            },
+            TestCase {
+                prev_value: Some(json!({"a": [{"b": "c"}]})),
+                patch: json!({"a": [1]}),
+                expect: json!({"a": [1]}),
+            },
+            TestCase {
+                prev_value: Some(json!(["a", "b"])),
+                patch: json!({"a": "c"}),
+                expect: json!({"a": "c"}),
+            },
+            TestCase {
+                prev_value: Some(json!({"a": "foo"})),
+                patch: json!("bar"),
+                expect: json!("bar"),
+            },
+            TestCase {
+                prev_value: Some(json!({"e": null})),
+                patch: json!({"a": 1}),
+                expect: json!({"e": null, "a": 1}),
+            },
+        ];
+        for TestCase { prev_value, patch, expect } in cases {
+            let value = prev_value.map(|v| Value::with_value(serde_json::to_vec(&v).unwrap(), 1));
+            let r = apply_put_op(
+                PutType::MergeJson,
+                value.as_ref(),
+                serde_json::to_vec(&patch).unwrap(),
+            )
+            .unwrap()
+            .unwrap();
+            let r: serde_json::Value = serde_json::from_slice(&r).unwrap();
+            assert_eq!(r, expect);
+        }
+    }
+
+    #[test]
+    fn apply_put_op_merge_json_invalid() {
+        assert!(matches!(
+            apply_put_op(PutType::MergeJson, None, b"{".to_vec()),
+            Err(Error::InvalidJson(_))
+        ));
+
+        // The tombstone is treated as missing value.
+        let value = Value::tombstone(1);
+        let r = apply_put_op(PutType::MergeJson, Some(&value), b"{}".to_vec()).unwrap();
+        assert!(matches!(r, Some(v) if v == b"{}".to_vec()));
+
+        let value = Value::with_value(b"not json".to_vec(), 1);
+        assert!(matches!(
+            apply_put_op(PutType::MergeJson, Some(&value), b"{}".to_vec()),
+            Err(Error::InvalidJson(_))
+        ));
+
+        let patch = serde_json::json!({ "a": "x".repeat(MAX_JSON_VALUE_SIZE) });
+        assert!(matches!(
+            apply_put_op(PutType::MergeJson, None, serde_json::to_vec(&patch).unwrap()),
             Err(Error::InvalidArgument(_))
         ));
     }
@@ -695,6 +1258,67 @@ mod tests {
         assert!(matches!(r, Some(v) if v == vec![1u8]));
     }
 
+    #[test]
+    fn apply_put_op_append_sequence() {
+        let r = apply_put_op(PutType::AppendSequence, None, b"entry".to_vec()).unwrap();
+        assert_eq!(r, Some(1u64.to_be_bytes().to_vec()));
+
+        let value = Value::with_value(7u64.to_be_bytes().to_vec(), 1);
+        let r = apply_put_op(PutType::AppendSequence, Some(&value), b"entry".to_vec()).unwrap();
+        assert_eq!(r, Some(8u64.to_be_bytes().to_vec()));
+
+        let value = Value::with_value(b"not a sequence".to_vec(), 1);
+        assert!(matches!(
+            apply_put_op(PutType::AppendSequence, Some(&value), b"entry".to_vec()),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[sekas_macro::test]
+    async fn write_intent_merge_json_without_conflict() {
+        use serde_json::json;
+
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        let latch_mgr = LocalLatchManager::default();
+        let shard_id = 1;
+        let key = b"document".to_vec();
+
+        // Both txns start before any of them commits.
+        for (start_version, commit_version, patch) in
+            [(10, 12, json!({"a": 1})), (11, 13, json!({"b": 2}))]
+        {
+            let mut latch_guard = DeferSignalLatchGuard::with_single(
+                &ShardKey { shard_id, user_key: key.clone() },
+                latch_mgr.acquire(shard_id, &key).await.unwrap(),
+            );
+            let req = WriteIntentRequest {
+                shard_id,
+                start_version,
+                write: Some(WriteRequest::Put(
+                    WriteBuilder::new(key.clone()).ensure_merge_json(patch),
+                )),
+                ..Default::default()
+            };
+            let (eval_result, _) =
+                write_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await.unwrap();
+            commit_eval_result(&engine, eval_result);
+            let req = CommitIntentRequest {
+                shard_id,
+                start_version,
+                commit_version,
+                user_key: key.clone(),
+            };
+            let eval_result =
+                commit_intent(&ExecCtx::default(), &engine, &mut latch_guard, &req).await.unwrap();
+            commit_eval_result(&engine, eval_result);
+        }
+
+        let value = engine.get(shard_id, &key).await.unwrap().unwrap();
+        let document: serde_json::Value = serde_json::from_slice(&value.content.unwrap()).unwrap();
+        assert_eq!(document, json!({"a": 1, "b": 2}));
+    }
+
     #[sekas_macro::test]
     async fn write_intent_resolve_orphan_txn_read_latest_write() {
         // A case:
@@ -729,6 +1353,7 @@ mod tests {
                     write: Some(WriteRequest::Put(
                         WriteBuilder::new(key_clone.clone()).ensure_add(1),
                     )),
+                    ..Default::default()
                 };
                 let mut latch_guard = DeferSignalLatchGuard::with_single(
                     &ShardKey { shard_id, user_key: key_clone.to_vec() },
@@ -14,11 +14,17 @@
 // limitations under the License.
 
 use log::trace;
-use sekas_api::server::v1::{PutType, ShardWriteRequest, ShardWriteResponse, WriteResponse};
-use sekas_rock::time::timestamp_nanos;
+use sekas_api::server::v1::{
+    DeletePrefixRequest, DeletePrefixResponse, PutType, ShardWriteRequest, ShardWriteResponse,
+    WriteResponse,
+};
+use sekas_runtime::time::timestamp_nanos;
+use sekas_schema::shard;
+use sekas_schema::system::txn::TXN_INTENT_VERSION;
 
 use super::cas::eval_conditions;
-use crate::engine::{GroupEngine, WriteBatch};
+use crate::engine::{GroupEngine, SnapshotMode, WriteBatch};
+use crate::error::BusyReason;
 use crate::node::move_shard::ForwardCtx;
 use crate::replica::ExecCtx;
 use crate::serverpb::v1::EvalResult;
@@ -50,6 +56,32 @@ pub(crate) async fn batch_write(
     }
 
     let mut wb = WriteBatch::default();
+    let resp = write_shard(group_engine, req, &mut wb).await?;
+    Ok((Some(EvalResult::with_batch(wb.data().to_owned())), resp))
+}
+
+/// Write the shards of the group in a single batch, so the writes are applied
+/// atomically. It is only used by root to write the system tables, whose
+/// shards are never moved.
+pub(crate) async fn batch_write_shards(
+    group_engine: &GroupEngine,
+    reqs: &[ShardWriteRequest],
+) -> Result<Option<EvalResult>> {
+    let mut wb = WriteBatch::default();
+    for req in reqs {
+        write_shard(group_engine, req, &mut wb).await?;
+    }
+    if wb.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(EvalResult::with_batch(wb.data().to_owned())))
+}
+
+async fn write_shard(
+    group_engine: &GroupEngine,
+    req: &ShardWriteRequest,
+    wb: &mut WriteBatch,
+) -> Result<ShardWriteResponse> {
     let mut resp = ShardWriteResponse::default();
     let num_deletes = req.deletes.len();
     for (idx, del) in req.deletes.iter().enumerate() {
@@ -60,9 +92,11 @@ pub(crate) async fn batch_write(
         let prev_version = prev_value.as_ref().map(|v| v.version).unwrap_or_default();
         resp.deletes.push(WriteResponse {
             prev_value: if del.take_prev_value { prev_value } else { None },
+            sequence: None,
+            new_value: None,
         });
         let version = std::cmp::max(prev_version + 1, next_version());
-        group_engine.tombstone(&mut wb, req.shard_id, &del.key, version)?;
+        group_engine.tombstone(wb, req.shard_id, &del.key, version)?;
     }
     for (idx, put) in req.puts.iter().enumerate() {
         if put.put_type != PutType::None as i32 {
@@ -77,6 +111,8 @@ pub(crate) async fn batch_write(
         let prev_version = prev_value.as_ref().map(|v| v.version).unwrap_or_default();
         resp.puts.push(WriteResponse {
             prev_value: if put.take_prev_value { prev_value } else { None },
+            sequence: None,
+            new_value: put.return_new_value.then(|| put.value.clone()),
         });
         let version = std::cmp::max(prev_version + 1, next_version());
         trace!(
@@ -86,7 +122,77 @@ pub(crate) async fn batch_write(
             sekas_rock::ascii::escape_bytes(&put.key),
             sekas_rock::ascii::escape_bytes(&put.value),
         );
-        group_engine.put(&mut wb, req.shard_id, &put.key, &put.value, version)?;
+        group_engine.put_with_ttl(wb, req.shard_id, &put.key, &put.value, version, put.ttl_ms)?;
+    }
+    Ok(resp)
+}
+
+/// Delete the keys under the prefix by writing a tombstone at the fence
+/// version, the keys whose latest committed version is not less than the fence
+/// are kept. At most `limit` keys are visited, and the resume key is returned
+/// if there are more keys left in the shard.
+///
+/// The keys with an intent are deleted too, since the txn of the intent might
+/// commit below the fence, eg. a txn acknowledged before resolving its intents.
+/// The tombstone shadows the value committed below the fence, and the value
+/// committed above the fence is kept.
+pub(crate) async fn delete_prefix(
+    exec_ctx: &ExecCtx,
+    group_engine: &GroupEngine,
+    req: &DeletePrefixRequest,
+) -> Result<(Option<EvalResult>, DeletePrefixResponse)> {
+    if exec_ctx.move_shard_desc.as_ref().is_some_and(|desc| desc.get_shard_id() == req.shard_id) {
+        return Err(Error::ServiceIsBusy(BusyReason::Moving));
+    }
+
+    let desc = group_engine.shard_desc(req.shard_id)?;
+    let start_key = req.start_key.as_ref().unwrap_or(&req.prefix);
+    let start_key = std::cmp::max(start_key.clone(), shard::start_key(&desc));
+    if !start_key.starts_with(&req.prefix) || !shard::belong_to(&desc, &start_key) {
+        return Ok((None, DeletePrefixResponse::default()));
+    }
+
+    let mut wb = WriteBatch::default();
+    let mut resp = DeletePrefixResponse::default();
+    let mut num_visited = 0;
+    let snapshot_mode = SnapshotMode::Start { start_key: Some(&start_key) };
+    let snapshot = group_engine.snapshot(req.shard_id, snapshot_mode)?;
+    let mut snapshot = snapshot.with_read_version(req.fence_version);
+    while let Some(mvcc_iter) = snapshot.next() {
+        let mut mvcc_iter = mvcc_iter?;
+        let user_key = mvcc_iter.user_key().to_owned();
+        if !user_key.starts_with(&req.prefix) {
+            break;
+        }
+        if req.limit != 0 && num_visited >= req.limit {
+            resp.resume_key = Some(user_key);
+            break;
+        }
+        num_visited += 1;
+
+        let is_alive = match mvcc_iter.next().transpose()? {
+            Some(entry) if entry.version() == TXN_INTENT_VERSION => true,
+            Some(entry) => entry.version() < req.fence_version && entry.value().is_some(),
+            None => false,
+        };
+        if !is_alive {
+            continue;
+        }
+        resp.num_deleted += 1;
+        if !req.dry_run {
+            group_engine.tombstone(&mut wb, req.shard_id, &user_key, req.fence_version)?;
+        }
+    }
+    trace!(
+        "delete prefix {}, shard id {}, fence version {}, dry run {}, deleted {} keys",
+        sekas_rock::ascii::escape_bytes(&req.prefix),
+        req.shard_id,
+        req.fence_version,
+        req.dry_run,
+        resp.num_deleted,
+    );
+    if wb.is_empty() {
+        return Ok((None, resp));
     }
     Ok((Some(EvalResult::with_batch(wb.data().to_owned())), resp))
 }
@@ -98,7 +204,8 @@ fn next_version() -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use sekas_api::server::v1::Value;
+    use prost::Message;
+    use sekas_api::server::v1::{TxnIntent, Value};
     use sekas_client::WriteBuilder;
     use sekas_rock::fn_name;
     use tempdir::TempDir;
@@ -110,7 +217,7 @@ mod tests {
 
     fn commit_values(engine: &GroupEngine, key: &[u8], values: &[Value]) {
         let mut wb = WriteBatch::default();
-        for Value { version, content } in values {
+        for Value { version, content, .. } in values {
             if let Some(value) = content {
                 engine.put(&mut wb, SHARD_ID, key, value, *version).unwrap();
             } else {
@@ -169,4 +276,58 @@ mod tests {
         let r = batch_write(&exec_ctx, &engine, &req).await;
         assert!(r.is_ok());
     }
+
+    #[sekas_macro::test]
+    async fn delete_prefix_below_fence_version() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let engine = create_group_engine(dir.path(), 1, 1, 1).await;
+        commit_values(&engine, b"b-1", &[Value::with_value(b"value".to_vec(), 10)]);
+        commit_values(&engine, b"b-2", &[Value::with_value(b"value".to_vec(), 30)]);
+        commit_values(&engine, b"b-3", &[Value::tombstone(5)]);
+        commit_values(&engine, b"b-4", &[Value::with_value(b"value".to_vec(), 10)]);
+        commit_values(&engine, b"c-1", &[Value::with_value(b"value".to_vec(), 10)]);
+
+        // The intent is not resolved yet.
+        let intent = TxnIntent::with_put(15, Some(b"value".to_vec()));
+        let mut wb = WriteBatch::default();
+        engine.put(&mut wb, SHARD_ID, b"b-5", &intent.encode_to_vec(), TXN_INTENT_VERSION).unwrap();
+        engine.commit(wb, WriteStates::default(), false).unwrap();
+
+        let exec_ctx = ExecCtx::default();
+        let mut req = DeletePrefixRequest {
+            shard_id: SHARD_ID,
+            prefix: b"b-".to_vec(),
+            fence_version: 20,
+            limit: 2,
+            dry_run: true,
+            ..Default::default()
+        };
+        let (eval_result, resp) = delete_prefix(&exec_ctx, &engine, &req).await.unwrap();
+        assert!(eval_result.is_none());
+        assert_eq!(resp.num_deleted, 1);
+        assert_eq!(resp.resume_key, Some(b"b-3".to_vec()));
+
+        req.dry_run = false;
+        req.start_key = resp.resume_key;
+        let (eval_result, resp) = delete_prefix(&exec_ctx, &engine, &req).await.unwrap();
+        assert_eq!(resp.num_deleted, 1);
+        assert_eq!(resp.resume_key, Some(b"b-5".to_vec()));
+        let batch = eval_result.unwrap().batch.unwrap();
+        engine.commit(WriteBatch::new(&batch.data), WriteStates::default(), false).unwrap();
+
+        req.start_key = resp.resume_key;
+        let (eval_result, resp) = delete_prefix(&exec_ctx, &engine, &req).await.unwrap();
+        assert_eq!(resp.num_deleted, 1);
+        assert_eq!(resp.resume_key, None);
+        let batch = eval_result.unwrap().batch.unwrap();
+        engine.commit(WriteBatch::new(&batch.data), WriteStates::default(), false).unwrap();
+
+        // The tombstone is written at the fence version.
+        let value = engine.get(SHARD_ID, b"b-4").await.unwrap().unwrap();
+        assert_eq!(value, Value::tombstone(20));
+        let value_set = engine.get_all_versions(SHARD_ID, b"b-5").await.unwrap();
+        assert!(value_set.values.iter().any(|v| *v == Value::tombstone(20)));
+        let value = engine.get(SHARD_ID, b"c-1").await.unwrap().unwrap();
+        assert_eq!(value.content, Some(b"value".to_vec()));
+    }
 }
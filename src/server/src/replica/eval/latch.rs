@@ -24,9 +24,17 @@ use crate::{Error, Result};
 pub trait LatchGuard {
     /// Resolve the state of the specified txn record and release the lock
     /// guard. Return the value if the txn is committed, otherwise [`None`] is
-    /// returned.
+    /// returned. The running txn is waited for a bounded duration, then
+    /// [`Error::TxnConflict`] is returned.
     async fn resolve_txn(&mut self, txn_intent: TxnIntent) -> Result<Option<Value>>;
 
+    /// Like [`LatchGuard::resolve_txn`], but [`Error::TxnConflict`] is returned
+    /// instead of waiting, if the txn is still running. The implementations
+    /// which wait for the running txns should override it.
+    async fn resolve_txn_or_conflict(&mut self, txn_intent: TxnIntent) -> Result<Option<Value>> {
+        self.resolve_txn(txn_intent).await
+    }
+
     /// Signal all intent waiters.
     fn signal_all(&self, txn_state: TxnState, commit_version: Option<u64>);
 }
@@ -47,6 +55,18 @@ pub trait LatchManager {
         intent_version: u64,
     ) -> Result<Option<Value>>;
 
+    /// Like [`LatchManager::resolve_txn`], but [`Error::TxnConflict`] is
+    /// returned instead of waiting, if the txn is still running.
+    async fn resolve_txn_or_conflict(
+        &self,
+        shard_id: u64,
+        user_key: &[u8],
+        start_version: u64,
+        intent_version: u64,
+    ) -> Result<Option<Value>> {
+        self.resolve_txn(shard_id, user_key, start_version, intent_version).await
+    }
+
     /// Acquire row latch for the specified user key.
     async fn acquire(&self, shard_id: u64, user_key: &[u8]) -> Result<Self::Guard>;
 }
@@ -75,15 +95,33 @@ impl<L: LatchGuard> DeferSignalLatchGuard<L> {
         user_key: &[u8],
         txn_intent: TxnIntent,
     ) -> Result<Option<Value>> {
+        self.latch_mut(shard_id, user_key, &txn_intent)?.resolve_txn(txn_intent).await
+        // TODO(walter) release the other latches!
+    }
+
+    /// See [`LatchGuard::resolve_txn_or_conflict`].
+    pub async fn resolve_txn_or_conflict(
+        &mut self,
+        shard_id: u64,
+        user_key: &[u8],
+        txn_intent: TxnIntent,
+    ) -> Result<Option<Value>> {
+        self.latch_mut(shard_id, user_key, &txn_intent)?.resolve_txn_or_conflict(txn_intent).await
+    }
+
+    fn latch_mut(
+        &mut self,
+        shard_id: u64,
+        user_key: &[u8],
+        txn_intent: &TxnIntent,
+    ) -> Result<&mut L> {
         let shard_key = ShardKey { shard_id, user_key: user_key.to_vec() };
-        let latch = self.latches.get_mut(&shard_key).ok_or_else(|| {
+        self.latches.get_mut(&shard_key).ok_or_else(|| {
             Error::InvalidData(format!(
                 "resolve txn but not hold the latch, start version {}",
                 txn_intent.start_version
             ))
-        })?;
-        latch.resolve_txn(txn_intent).await
-        // TODO(walter) release the other latches!
+        })
     }
 
     #[inline]
@@ -124,6 +162,9 @@ where
         Request::ClearIntent(req) => (req.shard_id, vec![req.user_key.clone()]),
         Request::Scan(_)
         | Request::Get(_)
+        | Request::GetRawKey(_)
+        | Request::CheckPrefixEmpty(_)
+        | Request::DeletePrefix(_)
         | Request::CreateShard(_)
         | Request::ChangeReplicas(_)
         | Request::AcceptShard(_)
@@ -131,7 +172,8 @@ where
         | Request::MoveReplicas(_)
         | Request::WatchKey(_)
         | Request::SplitShard(_)
-        | Request::MergeShard(_) => return Ok(None),
+        | Request::MergeShard(_)
+        | Request::RemoveShard(_) => return Ok(None),
     };
 
     if keys.is_empty() {
@@ -171,16 +213,27 @@ pub mod remote {
     use prost::Message;
     use sekas_api::server::v1::{ShardKey, TxnIntent, TxnState, Value};
     use sekas_client::TxnStateTable;
-    use sekas_rock::time::timestamp_millis;
+    use sekas_runtime::time::{timestamp_millis, Instant};
     use sekas_schema::system::txn::TXN_INTENT_VERSION;
 
-    use super::LatchGuard;
     use crate::engine::{GroupEngine, SnapshotMode, WriteBatch};
     use crate::raftgroup::RaftGroup;
+    use crate::replica::eval::cmd_txn::read_target_intent;
     use crate::replica::eval::LatchManager;
     use crate::serverpb::v1::EvalResult;
     use crate::{Error, Result};
 
+    /// The interval to read the txn record again while waiting for an intent to
+    /// be committed or aborted, so an intent left by a crashed txn will not
+    /// block readers forever.
+    const INTENT_RECHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+    /// The max duration to wait for the intent of a running txn. Once it is
+    /// exceeded, [`Error::TxnConflict`] is returned and the reader retries
+    /// later, since a txn keeping its lease by heartbeats might hold the intent
+    /// for long.
+    const MAX_INTENT_WAIT_DURATION: Duration = Duration::from_secs(1);
+
     #[derive(Default)]
     struct LatchBlock {
         hold: bool,
@@ -342,10 +395,19 @@ pub mod remote {
                     commit_version,
                 )?;
             }
+            if let Some((entry_key, entry_intent)) =
+                self.read_append_entry_intent(shard_key, txn_intent).await?
+            {
+                let engine = &self.core.group_engine;
+                engine.delete(&mut wb, shard_key.shard_id, &entry_key, TXN_INTENT_VERSION)?;
+                if let Some(value) = entry_intent.value.as_ref() {
+                    engine.put(&mut wb, shard_key.shard_id, &entry_key, value, commit_version)?;
+                }
+            }
             self.core.raft_group.propose(EvalResult::with_batch(wb.data().to_vec())).await
         }
 
-        async fn clear_intent(&self, shard_key: &ShardKey) -> Result<()> {
+        async fn clear_intent(&self, shard_key: &ShardKey, txn_intent: &TxnIntent) -> Result<()> {
             let mut wb = WriteBatch::default();
             self.core.group_engine.delete(
                 &mut wb,
@@ -353,8 +415,37 @@ pub mod remote {
                 &shard_key.user_key,
                 TXN_INTENT_VERSION,
             )?;
+            if let Some((entry_key, _)) =
+                self.read_append_entry_intent(shard_key, txn_intent).await?
+            {
+                self.core.group_engine.delete(
+                    &mut wb,
+                    shard_key.shard_id,
+                    &entry_key,
+                    TXN_INTENT_VERSION,
+                )?;
+            }
             self.core.raft_group.propose(EvalResult::with_batch(wb.data().to_owned())).await
         }
+
+        /// Read the intent of the entry appended by the intent of the prefix,
+        /// it is resolved together with the prefix.
+        async fn read_append_entry_intent(
+            &self,
+            shard_key: &ShardKey,
+            txn_intent: &TxnIntent,
+        ) -> Result<Option<(Vec<u8>, TxnIntent)>> {
+            let Some(sequence) = txn_intent.append_sequence else { return Ok(None) };
+            let entry_key = sekas_api::append_entry_key(&shard_key.user_key, sequence);
+            let entry_intent = read_target_intent(
+                &self.core.group_engine,
+                txn_intent.start_version,
+                shard_key.shard_id,
+                &entry_key,
+            )
+            .await?;
+            Ok(entry_intent.map(|intent| (entry_key, intent)))
+        }
     }
 
     impl super::LatchManager for RemoteLatchManager {
@@ -368,12 +459,43 @@ pub mod remote {
             user_key: &[u8],
             start_version: u64,
             intent_version: u64,
+        ) -> Result<Option<Value>> {
+            self.resolve_txn_inner(shard_id, user_key, start_version, intent_version, true).await
+        }
+
+        async fn resolve_txn_or_conflict(
+            &self,
+            shard_id: u64,
+            user_key: &[u8],
+            start_version: u64,
+            intent_version: u64,
+        ) -> Result<Option<Value>> {
+            self.resolve_txn_inner(shard_id, user_key, start_version, intent_version, false).await
+        }
+
+        async fn acquire(&self, shard_id: u64, key: &[u8]) -> Result<RemoteLatchGuard> {
+            match self.acquire_internal(shard_id, key) {
+                Ok(latch) => Ok(latch),
+                Err(rx) => Ok(rx.await.expect("Will not be dropped without send()")),
+            }
+        }
+    }
+
+    impl RemoteLatchManager {
+        async fn resolve_txn_inner(
+            &self,
+            shard_id: u64,
+            user_key: &[u8],
+            start_version: u64,
+            intent_version: u64,
+            wait: bool,
         ) -> Result<Option<Value>> {
             trace!("txn {start_version} try resolve txn {intent_version}, shard {shard_id} user key {user_key:?}");
             let mut latch_guard = self.acquire(shard_id, user_key).await?;
             // read the txn intent again with latch guard.
             let snapshot_mode = SnapshotMode::Key { key: user_key };
-            let mut snapshot = self.core.group_engine.snapshot(shard_id, snapshot_mode)?;
+            let snapshot = self.core.group_engine.snapshot(shard_id, snapshot_mode)?;
+            let mut snapshot = snapshot.with_read_version(start_version);
             let Some(mvcc_iter) = snapshot.next() else { return Ok(None) };
             for entry in mvcc_iter? {
                 let entry = entry?;
@@ -385,7 +507,7 @@ pub mod remote {
                     })?;
                     let txn_intent = TxnIntent::decode(content)?;
                     if txn_intent.start_version == intent_version {
-                        return latch_guard.resolve_txn(txn_intent).await;
+                        return latch_guard.resolve_txn_inner(txn_intent, wait).await;
                     }
                     // no such intent exists, just read the recent value.
                 } else if entry.version() <= start_version {
@@ -394,19 +516,54 @@ pub mod remote {
             }
             Ok(None)
         }
+    }
 
-        async fn acquire(&self, shard_id: u64, key: &[u8]) -> Result<RemoteLatchGuard> {
-            match self.acquire_internal(shard_id, key) {
-                Ok(latch) => Ok(latch),
-                Err(rx) => Ok(rx.await.expect("Will not be dropped without send()")),
+    impl super::LatchGuard for RemoteLatchGuard {
+        async fn resolve_txn(&mut self, txn_intent: TxnIntent) -> Result<Option<Value>> {
+            self.resolve_txn_inner(txn_intent, true).await
+        }
+
+        async fn resolve_txn_or_conflict(
+            &mut self,
+            txn_intent: TxnIntent,
+        ) -> Result<Option<Value>> {
+            self.resolve_txn_inner(txn_intent, false).await
+        }
+
+        fn signal_all(&self, txn_state: TxnState, commit_version: Option<u64>) {
+            // FIXME(walter) what happen if the signal intent is not equals to wait intent.
+            let commit_version = commit_version.unwrap_or_default();
+            if let Some(mut latch_block) =
+                self.latch_mgr.core.latches.get_mut(&self.shard_key.clone())
+            {
+                for sender in std::mem::take(&mut latch_block.intent_waiters) {
+                    let _ = sender.send((txn_state, commit_version));
+                }
             }
         }
     }
 
-    impl super::LatchGuard for RemoteLatchGuard {
-        async fn resolve_txn(&mut self, txn_intent: TxnIntent) -> Result<Option<Value>> {
+    impl RemoteLatchGuard {
+        /// Whether the intent of the latched key is written by the txn.
+        async fn hold_intent_of(&self, start_version: u64) -> Result<bool> {
+            let intent = read_target_intent(
+                &self.latch_mgr.core.group_engine,
+                start_version,
+                self.shard_key.shard_id,
+                &self.shard_key.user_key,
+            )
+            .await?;
+            Ok(intent.is_some())
+        }
+
+        async fn resolve_txn_inner(
+            &mut self,
+            txn_intent: TxnIntent,
+            wait: bool,
+        ) -> Result<Option<Value>> {
             let start_version = txn_intent.start_version;
             trace!("try resolve txn {start_version}, shard key {:?}", self.shard_key);
+            let deadline = Instant::now() + MAX_INTENT_WAIT_DURATION;
             loop {
                 let txn_record =
                     self.latch_mgr.core.txn_table.get_txn_record(start_version).await?.ok_or_else(
@@ -439,7 +596,18 @@ pub mod remote {
                             }
                             Err(err) => return Err(err.into()),
                         }
+                    } else if !wait {
+                        debug!("txn {} is running, the intent is conflict", start_version);
+                        return Err(Error::TxnConflict);
                     } else {
+                        let now = Instant::now();
+                        if deadline <= now {
+                            debug!(
+                                "wait txn {} intent exceeds {:?}, the intent is conflict",
+                                start_version, MAX_INTENT_WAIT_DURATION
+                            );
+                            return Err(Error::TxnConflict);
+                        }
                         debug!("wait txn {} intent to commit or abort", start_version);
                         let (sender, receiver) = oneshot::channel();
                         {
@@ -453,18 +621,44 @@ pub mod remote {
                         }
                         debug_assert!(self.hold, "resolve txn should hold the lock");
                         self.hold = false;
-                        let (txn_state, commit_version) = receiver.await.expect("Do not cancel");
+                        let wait_duration = INTENT_RECHECK_INTERVAL.min(deadline - now);
+                        let wait_result =
+                            sekas_runtime::time::timeout(wait_duration, receiver).await;
                         *self = self
                             .latch_mgr
                             .acquire(self.shard_key.shard_id, &self.shard_key.user_key)
                             .await?;
-                        (txn_state, commit_version)
+                        match wait_result {
+                            Ok(result) => result.expect("Do not cancel"),
+                            Err(_) => {
+                                debug!(
+                                    "wait txn {} intent timeout, try resolve it again",
+                                    start_version
+                                );
+                                // The receiver is dropped, remove the waiter from the latch.
+                                self.latch_mgr
+                                    .core
+                                    .get_latch_mut(
+                                        self.shard_key.shard_id,
+                                        &self.shard_key.user_key,
+                                    )
+                                    .intent_waiters
+                                    .retain(|sender| !sender.is_canceled());
+                                continue;
+                            }
+                        }
                     }
                 } else {
                     delete_intent = true;
                     (txn_record.state, txn_record.commit_version.unwrap_or_default())
                 };
 
+                // The intent might be resolved by others during waiting, then the key might be
+                // written by another txn, whose intent must be kept.
+                if delete_intent && !self.hold_intent_of(start_version).await? {
+                    delete_intent = false;
+                }
+
                 debug!("txn {} intent state {}, commit version {commit_version} delete intent {delete_intent}", start_version,
                     actual_txn_state.as_str_name());
                 match actual_txn_state {
@@ -480,12 +674,13 @@ pub mod remote {
                             return Ok(Some(Value {
                                 content: txn_intent.value,
                                 version: commit_version,
+                                ..Default::default()
                             }));
                         }
                     }
                     TxnState::Aborted => {
                         if delete_intent {
-                            self.latch_mgr.clear_intent(&self.shard_key).await?;
+                            self.latch_mgr.clear_intent(&self.shard_key, &txn_intent).await?;
                         }
                         return Ok(None);
                     }
@@ -495,18 +690,6 @@ pub mod remote {
                 }
             }
         }
-
-        fn signal_all(&self, txn_state: TxnState, commit_version: Option<u64>) {
-            // FIXME(walter) what happen if the signal intent is not equals to wait intent.
-            let commit_version = commit_version.unwrap_or_default();
-            if let Some(mut latch_block) =
-                self.latch_mgr.core.latches.get_mut(&self.shard_key.clone())
-            {
-                for sender in std::mem::take(&mut latch_block.intent_waiters) {
-                    let _ = sender.send((txn_state, commit_version));
-                }
-            }
-        }
     }
 
     impl Drop for RemoteLatchGuard {
@@ -552,7 +735,7 @@ pub mod remote {
                     .unwrap();
             let engine = create_group_engine(dir.path(), 1, 1, 1).await;
             let (sender, _receiver) = mpsc::channel(1024);
-            let raft_group = RaftGroup::open(sender);
+            let raft_group = RaftGroup::open(sender, std::sync::Arc::default());
             let latch_mgr = RemoteLatchManager::new(client, engine, raft_group);
 
             let shard_id = 1;
@@ -705,7 +888,11 @@ pub mod local {
                     if txn_intent.is_delete {
                         Ok(Some(Value::tombstone(commit_version)))
                     } else {
-                        Ok(Some(Value { content: txn_intent.value, version: commit_version }))
+                        Ok(Some(Value {
+                            content: txn_intent.value,
+                            version: commit_version,
+                            ..Default::default()
+                        }))
                     }
                 }
                 _ => unreachable!(),
@@ -19,6 +19,7 @@ mod cmd_get;
 mod cmd_ingest;
 mod cmd_merge_shard;
 mod cmd_move_replicas;
+mod cmd_remove_shard;
 mod cmd_scan;
 mod cmd_split_shard;
 mod cmd_txn;
@@ -28,19 +29,32 @@ mod latch;
 use sekas_api::server::v1::ShardDesc;
 
 pub(crate) use self::cmd_accept_shard::accept_shard;
-pub(crate) use self::cmd_get::get;
-pub(crate) use self::cmd_ingest::ingest_value_set;
+pub(crate) use self::cmd_get::{get, get_raw_key};
+pub(crate) use self::cmd_ingest::{ingest_value_set, ingest_value_sets, restore_value_set};
 pub(crate) use self::cmd_merge_shard::merge_shard;
 pub(crate) use self::cmd_move_replicas::move_replicas;
+pub(crate) use self::cmd_remove_shard::remove_shard;
 pub(crate) use self::cmd_scan::{merge_scan_response, scan};
 pub(crate) use self::cmd_split_shard::split_shard;
-pub(crate) use self::cmd_txn::{clear_intent, commit_intent, write_intent};
-pub(crate) use self::cmd_write::batch_write;
+pub(crate) use self::cmd_txn::{check_prefix_empty, clear_intent, commit_intent, write_intent};
+pub(crate) use self::cmd_write::{batch_write, batch_write_shards, delete_prefix};
 pub(crate) use self::latch::{acquire_row_latches, remote, LatchGuard, LatchManager};
+use crate::engine::GroupEngine;
 use crate::serverpb::v1::EvalResult;
+use crate::{Error, Result};
 
 pub fn add_shard(shard: ShardDesc) -> EvalResult {
     use crate::serverpb::v1::SyncOp;
 
     EvalResult { op: Some(SyncOp::add_shard(shard)), ..Default::default() }
 }
+
+/// Reject the reads beneath the GC watermark of the group, the versions they
+/// require might have been dropped by the compaction.
+fn check_read_version(engine: &GroupEngine, read_version: u64) -> Result<()> {
+    let gc_watermark = engine.gc_state().watermark();
+    if read_version < gc_watermark {
+        return Err(Error::VersionTooOld(read_version, gc_watermark));
+    }
+    Ok(())
+}
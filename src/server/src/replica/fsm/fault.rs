@@ -0,0 +1,98 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The faults injected into the state machine of replicas, for testing only.
+//!
+//! A fault panics the state machine once it applies a write of the user key,
+//! until the fault is cleared, as if the entry is poisoned. A divergence
+//! drops the writes of the entry at the index, as if the state machine
+//! diverges from the other replicas.
+//!
+//! The faults can only be injected if the `testing` feature is enabled,
+//! otherwise applying an entry never checks them.
+
+#[cfg(any(test, feature = "testing"))]
+use std::collections::HashSet;
+#[cfg(any(test, feature = "testing"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(test, feature = "testing"))]
+use std::sync::{Arc, Mutex};
+
+#[cfg(any(test, feature = "testing"))]
+use crate::engine::WriteBatch;
+
+/// The faults shared by the state machines of all replicas.
+#[derive(Clone, Debug, Default)]
+pub struct ApplyFaults {
+    #[cfg(any(test, feature = "testing"))]
+    inner: Arc<Mutex<HashSet<Vec<u8>>>>,
+    /// The index of the diverged entry, `0` if there is no divergence.
+    #[cfg(any(test, feature = "testing"))]
+    diverged_index: Arc<AtomicU64>,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl ApplyFaults {
+    /// Panic the state machines which apply a write of the user key.
+    pub fn inject_panic(&self, user_key: &[u8]) {
+        self.inner.lock().unwrap().insert(user_key.to_owned());
+    }
+
+    pub fn clear(&self, user_key: &[u8]) {
+        self.inner.lock().unwrap().remove(user_key);
+    }
+
+    /// Drop the writes of the entry at the index once it is applied, `0`
+    /// clears the divergence.
+    pub fn inject_divergence(&self, index: u64) {
+        self.diverged_index.store(index, Ordering::Relaxed);
+    }
+
+    /// Whether the writes of the entry at the index should be dropped.
+    pub(crate) fn diverges(&self, index: u64) -> bool {
+        index != 0 && self.diverged_index.load(Ordering::Relaxed) == index
+    }
+
+    /// Apply the write batch, panics if it writes any injected user key.
+    pub(crate) fn hit(&self, data: &[u8]) {
+        let poisoned_key = {
+            let faults = self.inner.lock().unwrap();
+            if faults.is_empty() {
+                return;
+            }
+            let writes = WriteBatch::new(data).user_writes();
+            writes.into_iter().map(|(_, user_key, _)| user_key).find(|k| faults.contains(k))
+        };
+        // The lock is released before panicking, to not poison it.
+        if let Some(user_key) = poisoned_key {
+            panic!(
+                "apply fault is injected into key {}",
+                sekas_rock::ascii::escape_bytes(&user_key)
+            );
+        }
+    }
+}
+
+#[cfg(not(any(test, feature = "testing")))]
+impl ApplyFaults {
+    /// No entry diverges without the `testing` feature.
+    #[inline(always)]
+    pub(crate) fn diverges(&self, _index: u64) -> bool {
+        false
+    }
+
+    /// No fault is injected without the `testing` feature.
+    #[inline(always)]
+    pub(crate) fn hit(&self, _data: &[u8]) {}
+}
@@ -14,17 +14,21 @@
 // limitations under the License.
 
 mod checkpoint;
+mod fault;
 
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
 use std::sync::{mpsc, Arc};
 
-use log::{info, trace, warn};
+use log::{debug, info, trace, warn};
+use prost::Message;
 use sekas_api::server::v1::*;
 use sekas_api::{apply_config_delta, apply_shard_delta, Epoch};
 
+pub use self::fault::ApplyFaults;
 use super::ReplicaInfo;
 use crate::engine::{GroupEngine, MvccEntry, WriteBatch, WriteStates};
+use crate::node::watch::WatchEventSender;
 use crate::raftgroup::{ApplyEntry, SnapshotBuilder, StateMachine};
 use crate::serverpb::v1::*;
 use crate::{Error, ReplicaConfig, Result};
@@ -46,6 +50,14 @@ pub trait StateMachineObserver: Send + Sync {
 
     /// This function will be called once the move shard state changes.
     fn on_move_shard_state_updated(&mut self, state: Option<MoveShardState>);
+
+    /// This function will be called once the progress of the moving shard
+    /// advances, but the step of the move shard state is unchanged.
+    fn on_move_shard_progress_updated(&mut self, state: Option<MoveShardState>);
+
+    /// This function will be called once the replica is quarantined, or the
+    /// quarantine is lifted.
+    fn on_quarantine_updated(&mut self, quarantine: Option<ApplyQuarantine>);
 }
 
 #[derive(Debug)]
@@ -58,7 +70,7 @@ pub struct WatchEvent {
     pub key: Box<[u8]>,
 }
 
-type WatchTrigger = futures::channel::mpsc::UnboundedSender<WatchEvent>;
+type WatchTrigger = WatchEventSender;
 type WatchTarget = (u64, Box<[u8]>);
 
 pub struct WatchHub {
@@ -100,16 +112,16 @@ impl rocksdb::WriteBatchIterator for GroupStateMachine {
                 sekas_rock::ascii::escape_bytes(user_key),
                 version
             );
-            senders.retain_mut(|sender| {
+            senders.retain(|sender| {
                 let event = WatchEvent {
                     version,
                     key: user_key.into(),
                     value: entry.value().map(Into::into),
                 };
-                sender.start_send(event).is_ok()
+                sender.send(event)
             });
             if senders.is_empty() {
-                // All watchers are closed.
+                // All watchers are closed or lagging.
                 self.watch_hub.watchers.remove(user_key);
             }
         }
@@ -137,7 +149,30 @@ where
     /// Whether `GroupDesc` changes during apply.
     desc_updated: bool,
     move_shard_state_updated: bool,
+    move_shard_progress_updated: bool,
     last_applied_term: u64,
+
+    /// The entries and bytes applied since the last apply checkpoint.
+    unchecked_entries: u64,
+    unchecked_bytes: u64,
+
+    /// The plugged states before applying the current entry.
+    savepoint: Option<ApplySavepoint>,
+    quarantine: Option<ApplyQuarantine>,
+}
+
+/// The plugged states before applying an entry, they are restored if applying
+/// the entry panics, see `StateMachine::discard_entry`.
+struct ApplySavepoint {
+    num_write_batches: usize,
+    /// The write states and the moved out shards, they are only saved if the
+    /// entry might change them, see `GroupStateMachine::take_savepoint`.
+    states: Option<(WriteStates, HashMap<u64, ShardDesc>)>,
+    desc_updated: bool,
+    move_shard_state_updated: bool,
+    move_shard_progress_updated: bool,
+    unchecked_entries: u64,
+    unchecked_bytes: u64,
 }
 
 impl GroupStateMachine {
@@ -145,10 +180,14 @@ impl GroupStateMachine {
         cfg: ReplicaConfig,
         info: Arc<ReplicaInfo>,
         group_engine: GroupEngine,
-        observer: Box<dyn StateMachineObserver>,
+        mut observer: Box<dyn StateMachineObserver>,
         watch_hub: WatchHub,
     ) -> Self {
         let apply_state = group_engine.flushed_apply_state().expect("access flushed index");
+        let quarantine = group_engine.apply_quarantine().expect("access apply quarantine");
+        if quarantine.is_some() {
+            observer.on_quarantine_updated(quarantine.clone());
+        }
         GroupStateMachine {
             cfg,
             info,
@@ -160,9 +199,39 @@ impl GroupStateMachine {
             move_out_shards: HashMap::new(),
             desc_updated: false,
             move_shard_state_updated: false,
+            move_shard_progress_updated: false,
             last_applied_term: apply_state.term,
+            unchecked_entries: 0,
+            unchecked_bytes: 0,
+            savepoint: None,
+            quarantine,
         }
     }
+
+    /// Verify the flushed apply checkpoint with the flushed states, return
+    /// `false` if they are inconsistent and the replica should be recovered
+    /// from a snapshot.
+    pub(crate) fn verify_apply_checkpoint(&self) -> Result<bool> {
+        let Some((checkpoint, desc)) = self.group_engine.flushed_apply_checkpoint()? else {
+            return Ok(true);
+        };
+        let apply_state = self.flushed_apply_state();
+        let checkpoint_state = checkpoint.apply_state.clone().unwrap_or_default();
+        if checkpoint_state.index < apply_state.index {
+            // The states after the checkpoint are flushed by rocksdb.
+            return Ok(true);
+        }
+        if checkpoint_state != apply_state
+            || checkpoint.digest != apply_checkpoint_digest(&apply_state, &desc)
+        {
+            warn!(
+                "group {} replica {} apply checkpoint {:?} is inconsistent with the flushed apply state {:?}",
+                self.info.group_id, self.info.replica_id, checkpoint, apply_state
+            );
+            return Ok(false);
+        }
+        Ok(true)
+    }
 }
 
 impl GroupStateMachine {
@@ -217,6 +286,15 @@ impl GroupStateMachine {
             if let Some(merge_shard) = op.merge_shard {
                 self.apply_merge_shard(merge_shard, &mut desc)?;
             }
+            if let Some(sync_epoch) = op.sync_epoch {
+                self.apply_sync_epoch(sync_epoch, &mut desc);
+            }
+            if let Some(remove_shard) = op.remove_shard {
+                self.apply_remove_shard(remove_shard, &mut desc);
+            }
+            if let Some(update_gc_watermark) = op.update_gc_watermark {
+                self.apply_update_gc_watermark(update_gc_watermark);
+            }
 
             // Any sync_op will update group desc.
             self.plugged_write_states.descriptor = Some(desc);
@@ -246,8 +324,8 @@ impl GroupStateMachine {
 
                 let state = MoveShardState {
                     move_shard: move_shard.desc,
-                    last_moved_key: None,
                     step: MoveShardStep::Prepare as i32,
+                    ..Default::default()
                 };
                 debug_assert!(state.move_shard.is_some());
                 self.plugged_write_states.move_shard_state = Some(state);
@@ -265,6 +343,9 @@ impl GroupStateMachine {
 
                 debug_assert!(state.step == MoveShardStep::Moving as i32);
                 state.last_moved_key = Some(move_shard.last_ingested_key);
+                state.moved_keys += move_shard.ingested_keys;
+                state.moved_bytes += move_shard.ingested_bytes;
+                self.move_shard_progress_updated = true;
 
                 self.plugged_write_states.move_shard_state = Some(state);
             }
@@ -289,9 +370,22 @@ impl GroupStateMachine {
                 self.plugged_write_states.move_shard_state = Some(state);
                 self.move_shard_state_updated = true;
             }
+            MoveShardEvent::Cancel => {
+                let mut state = self.must_move_shard_state();
+                debug_assert!(
+                    state.step == MoveShardStep::Moving as i32
+                        || state.step == MoveShardStep::Prepare as i32
+                );
+                state.step = MoveShardStep::Aborting as i32;
+                self.plugged_write_states.move_shard_state = Some(state);
+                self.move_shard_state_updated = true;
+            }
             MoveShardEvent::Abort => {
                 let mut state = self.must_move_shard_state();
-                debug_assert!(state.step == MoveShardStep::Prepare as i32);
+                debug_assert!(
+                    state.step == MoveShardStep::Prepare as i32
+                        || state.step == MoveShardStep::Aborting as i32
+                );
 
                 state.step = MoveShardStep::Aborted as i32;
                 self.plugged_write_states.move_shard_state = Some(state);
@@ -367,6 +461,80 @@ impl GroupStateMachine {
         Ok(())
     }
 
+    fn apply_sync_epoch(&mut self, sync_epoch: SyncEpoch, group_desc: &mut GroupDesc) {
+        let former_epoch = group_desc.epoch;
+        if let Some(catalog_desc) = sync_epoch.catalog_desc {
+            if catalog_desc.epoch > former_epoch {
+                // The shards removed by the catalog are purged as `RemoveShard`, except the
+                // shard being moved.
+                let moving_shard_id =
+                    self.group_engine.move_shard_state().map(|m| m.get_shard_desc().id);
+                for shard_desc in &group_desc.shards {
+                    if Some(shard_desc.id) != moving_shard_id
+                        && catalog_desc.shards.iter().all(|s| s.id != shard_desc.id)
+                    {
+                        self.plugged_write_states.purge_shard_states.push(PurgeShardState {
+                            shard: Some(shard_desc.clone()),
+                            ..Default::default()
+                        });
+                    }
+                }
+                group_desc.shards = catalog_desc.shards;
+            }
+        }
+        group_desc.epoch = apply_shard_delta(std::cmp::max(former_epoch, sync_epoch.epoch));
+        self.desc_updated = true;
+
+        info!(
+            "apply sync epoch {}, group={}, replica={}, former epoch={}, epoch={}",
+            Epoch(sync_epoch.epoch),
+            self.info.group_id,
+            self.info.replica_id,
+            Epoch(former_epoch),
+            Epoch(group_desc.epoch)
+        );
+    }
+
+    fn apply_remove_shard(&mut self, remove_shard: RemoveShard, group_desc: &mut GroupDesc) {
+        let shard_id = remove_shard.shard_id;
+        let Some(index) = group_desc.shards.iter().position(|s| s.id == shard_id) else {
+            warn!(
+                "apply remove shard {shard_id}, but it is not exists, group={}, replica={}",
+                self.info.group_id, self.info.replica_id
+            );
+            return;
+        };
+        let shard_desc = group_desc.shards.remove(index);
+        group_desc.epoch = apply_shard_delta(group_desc.epoch);
+        self.desc_updated = true;
+        self.plugged_write_states
+            .purge_shard_states
+            .push(PurgeShardState { shard: Some(shard_desc), ..Default::default() });
+
+        info!(
+            "apply remove shard {shard_id}, group={}, replica={}, epoch={}",
+            self.info.group_id,
+            self.info.replica_id,
+            Epoch(group_desc.epoch)
+        );
+    }
+
+    fn apply_update_gc_watermark(&mut self, update_gc_watermark: UpdateGcWatermark) {
+        let watermark = update_gc_watermark.watermark;
+        let current = self
+            .plugged_write_states
+            .gc_watermark
+            .unwrap_or_else(|| self.group_engine.gc_state().unpinned_watermark());
+        if watermark <= current {
+            debug!(
+                "apply update gc watermark {watermark}, but it is not above {current}, group={}, replica={}",
+                self.info.group_id, self.info.replica_id
+            );
+            return;
+        }
+        self.plugged_write_states.gc_watermark = Some(watermark);
+    }
+
     fn flush_updated_events(&mut self, term: u64) {
         if self.desc_updated {
             self.desc_updated = false;
@@ -380,8 +548,70 @@ impl GroupStateMachine {
 
         if self.move_shard_state_updated {
             self.move_shard_state_updated = false;
+            self.move_shard_progress_updated = false;
             self.observer.on_move_shard_state_updated(self.group_engine.move_shard_state());
+        } else if self.move_shard_progress_updated {
+            self.move_shard_progress_updated = false;
+            self.observer.on_move_shard_progress_updated(self.group_engine.move_shard_state());
+        }
+    }
+
+    fn should_checkpoint(&self) -> bool {
+        let entries = self.cfg.apply_checkpoint_entries;
+        let bytes = self.cfg.apply_checkpoint_bytes;
+        (entries != 0 && self.unchecked_entries >= entries)
+            || (bytes != 0 && self.unchecked_bytes >= bytes)
+    }
+
+    /// Save the plugged states before applying the entry. The write states are
+    /// only changed by the config changes and the sync ops, so they are not
+    /// cloned for the entries of the plain writes.
+    fn take_savepoint(&mut self, entry: &ApplyEntry) {
+        let changes_states = match entry {
+            ApplyEntry::Empty => false,
+            ApplyEntry::ConfigChange { .. } => true,
+            ApplyEntry::Proposal { eval_result } => eval_result.op.is_some(),
+        };
+        let states = changes_states
+            .then(|| (self.plugged_write_states.clone(), self.move_out_shards.clone()));
+        self.savepoint = Some(ApplySavepoint {
+            num_write_batches: self.plugged_write_batches.len(),
+            states,
+            desc_updated: self.desc_updated,
+            move_shard_state_updated: self.move_shard_state_updated,
+            move_shard_progress_updated: self.move_shard_progress_updated,
+            unchecked_entries: self.unchecked_entries,
+            unchecked_bytes: self.unchecked_bytes,
+        });
+    }
+
+    /// The digest of the effects plugged by the entries applied since the plug
+    /// is started, they are the write batches and the changed states except
+    /// the apply state.
+    pub(crate) fn plugged_digest(&self) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        for batch in &self.plugged_write_batches {
+            hasher.update(batch.data());
+        }
+        let states = &self.plugged_write_states;
+        if let Some(desc) = &states.descriptor {
+            hasher.update(&desc.encode_to_vec());
+        }
+        if let Some(state) = &states.move_shard_state {
+            hasher.update(&state.encode_to_vec());
+        }
+        for state in &states.purge_shard_states {
+            hasher.update(&state.encode_to_vec());
+        }
+        if let Some(watermark) = states.gc_watermark {
+            hasher.update(&watermark.to_le_bytes());
         }
+        hasher.finalize()
+    }
+
+    /// The bytes of the write batches plugged since the plug is started.
+    pub(crate) fn plugged_write_bytes(&self) -> usize {
+        self.plugged_write_batches.iter().map(|batch| batch.data().len()).sum()
     }
 
     #[inline]
@@ -433,29 +663,74 @@ impl StateMachine for GroupStateMachine {
     fn apply(&mut self, index: u64, term: u64, entry: ApplyEntry) -> Result<()> {
         let group_id = self.info.group_id;
         trace!("group {group_id} apply entry index {index} term {term}",);
+        self.take_savepoint(&entry);
         match entry {
             ApplyEntry::Empty => {}
             ApplyEntry::ConfigChange { change_replicas } => {
                 self.apply_change_replicas(change_replicas)?;
             }
-            ApplyEntry::Proposal { eval_result } => {
+            ApplyEntry::Proposal { mut eval_result } => {
+                if self.cfg.testing_knobs.apply_faults.diverges(index) {
+                    warn!("group {group_id} drops the writes of entry {index}, the apply diverges");
+                    eval_result.batch = None;
+                }
+                if !eval_result.request_id.is_empty() {
+                    debug!(
+                        "group {group_id} apply entry index {index} term {term} of request {}",
+                        eval_result.request_id
+                    );
+                }
+                if let Some(wb) = &eval_result.batch {
+                    self.cfg.testing_knobs.apply_faults.hit(&wb.data);
+                    self.unchecked_bytes += wb.data.len() as u64;
+                }
                 self.apply_proposal(eval_result)?;
             }
         }
+        self.unchecked_entries += 1;
         self.plugged_write_states.apply_state = Some(ApplyState { index, term });
 
         Ok(())
     }
 
+    fn discard_entry(&mut self) {
+        let Some(savepoint) = self.savepoint.take() else { return };
+        self.plugged_write_batches.truncate(savepoint.num_write_batches);
+        if let Some((write_states, move_out_shards)) = savepoint.states {
+            self.plugged_write_states = write_states;
+            self.move_out_shards = move_out_shards;
+        }
+        self.desc_updated = savepoint.desc_updated;
+        self.move_shard_state_updated = savepoint.move_shard_state_updated;
+        self.move_shard_progress_updated = savepoint.move_shard_progress_updated;
+        self.unchecked_entries = savepoint.unchecked_entries;
+        self.unchecked_bytes = savepoint.unchecked_bytes;
+    }
+
     fn finish_plug(&mut self) -> Result<()> {
-        let Some(ApplyState { term, .. }) = self.plugged_write_states.apply_state else {
+        self.savepoint = None;
+        let Some(apply_state) = self.plugged_write_states.apply_state.clone() else {
             panic!("invoke GroupStateMachine::finish_plug but WriteStates::apply_states is None");
         };
+        let term = apply_state.term;
+        let checkpoint = self.should_checkpoint();
+        if checkpoint {
+            let digest = apply_checkpoint_digest(&apply_state, &self.descriptor());
+            self.plugged_write_states.apply_checkpoint =
+                Some(ApplyCheckpoint { apply_state: Some(apply_state), digest });
+        }
         self.group_engine.group_commit(
             self.plugged_write_batches.as_slice(),
             std::mem::take(&mut self.plugged_write_states),
             false,
         )?;
+        if checkpoint {
+            // The checkpoint is written in the same batch with the applied data, so they
+            // are persisted by the same flush.
+            self.group_engine.flush(false)?;
+            self.unchecked_entries = 0;
+            self.unchecked_bytes = 0;
+        }
         self.trigger_updation_watchers();
         self.flush_updated_events(term);
         Ok(())
@@ -469,6 +744,21 @@ impl StateMachine for GroupStateMachine {
         Ok(())
     }
 
+    fn quarantine(&mut self, quarantine: Option<ApplyQuarantine>) -> Result<()> {
+        if self.quarantine == quarantine {
+            return Ok(());
+        }
+        self.group_engine.save_apply_quarantine(quarantine.as_ref())?;
+        self.quarantine = quarantine.clone();
+        self.observer.on_quarantine_updated(quarantine);
+        Ok(())
+    }
+
+    #[inline]
+    fn quarantined(&self) -> Option<ApplyQuarantine> {
+        self.quarantine.clone()
+    }
+
     fn snapshot_builder(&self) -> Box<dyn SnapshotBuilder> {
         Box::new(checkpoint::GroupSnapshotBuilder::new(self.cfg.clone(), self.group_engine.clone()))
     }
@@ -488,6 +778,14 @@ impl StateMachine for GroupStateMachine {
     }
 }
 
+fn apply_checkpoint_digest(apply_state: &ApplyState, desc: &GroupDesc) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&apply_state.index.to_le_bytes());
+    hasher.update(&apply_state.term.to_le_bytes());
+    hasher.update(&desc.encode_to_vec());
+    hasher.finalize()
+}
+
 impl ChangeReplicaKind {
     fn new(cc: &ChangeReplicas) -> Self {
         match cc.changes.len() {
@@ -529,6 +827,18 @@ fn apply_simple_change(local_id: u64, desc: &mut GroupDesc, change: &ChangeRepli
                 });
             }
         }
+        Some(ChangeReplicaType::AddReadReplica) => {
+            info!("group {group_id} replica {local_id} add read replica {replica_id}");
+            if let Some(replica) = exist {
+                replica.role = ReplicaRole::ReadReplica.into();
+            } else {
+                desc.replicas.push(ReplicaDesc {
+                    id: replica_id,
+                    node_id,
+                    role: ReplicaRole::ReadReplica.into(),
+                });
+            }
+        }
         Some(ChangeReplicaType::Remove) => {
             info!("group {group_id} replica {local_id} remove voter {replica_id}");
             desc.replicas.retain(|rep| rep.id != replica_id);
@@ -576,11 +886,19 @@ fn apply_enter_joint(local_id: u64, desc: &mut GroupDesc, changes: &[ChangeRepli
                     role: ReplicaRole::Learner as i32,
                 });
             }
-            (Some(ReplicaRole::Learner), ChangeReplicaType::Remove) => {
+            (None, ChangeReplicaType::AddReadReplica) => {
+                desc.replicas.push(ReplicaDesc {
+                    id: replica_id,
+                    node_id,
+                    role: ReplicaRole::ReadReplica as i32,
+                });
+            }
+            (Some(ReplicaRole::Learner | ReplicaRole::ReadReplica), ChangeReplicaType::Remove) => {
                 outgoing_learners.insert(replica_id);
             }
             (Some(ReplicaRole::Voter), ChangeReplicaType::Add)
             | (Some(ReplicaRole::Learner), ChangeReplicaType::AddLearner)
+            | (Some(ReplicaRole::ReadReplica), ChangeReplicaType::AddReadReplica)
             | (None, ChangeReplicaType::Remove) => {}
             _ => unreachable!(),
         }
@@ -609,31 +927,38 @@ fn apply_leave_joint(local_id: u64, desc: &mut GroupDesc) {
 fn group_role_digest(desc: &GroupDesc) -> String {
     let mut voters = vec![];
     let mut learners = vec![];
+    let mut read_replicas = vec![];
     for r in &desc.replicas {
         match ReplicaRole::from_i32(r.role) {
             Some(ReplicaRole::Voter | ReplicaRole::IncomingVoter | ReplicaRole::DemotingVoter) => {
                 voters.push(r.id)
             }
             Some(ReplicaRole::Learner) => learners.push(r.id),
+            Some(ReplicaRole::ReadReplica) => read_replicas.push(r.id),
             _ => continue,
         }
     }
-    format!("voters {voters:?} learners {learners:?}")
+    format!("voters {voters:?} learners {learners:?} read replicas {read_replicas:?}")
 }
 
 fn change_replicas_digest(changes: &[ChangeReplica]) -> String {
     let mut add_voters = vec![];
     let mut remove_replicas = vec![];
     let mut add_learners = vec![];
+    let mut add_read_replicas = vec![];
     for cc in changes {
         match ChangeReplicaType::from_i32(cc.change_type) {
             Some(ChangeReplicaType::Add) => add_voters.push(cc.replica_id),
             Some(ChangeReplicaType::AddLearner) => add_learners.push(cc.replica_id),
+            Some(ChangeReplicaType::AddReadReplica) => add_read_replicas.push(cc.replica_id),
             Some(ChangeReplicaType::Remove) => remove_replicas.push(cc.replica_id),
             _ => continue,
         }
     }
-    format!("add voters {add_voters:?} learners {add_learners:?} remove {remove_replicas:?}")
+    format!(
+        "add voters {add_voters:?} learners {add_learners:?} read replicas {add_read_replicas:?} \
+         remove {remove_replicas:?}"
+    )
 }
 
 fn find_replica_mut(desc: &mut GroupDesc, replica_id: u64) -> Option<&mut ReplicaDesc> {
@@ -659,29 +984,15 @@ fn apply_split_shard(group_desc: &mut GroupDesc, split_shard: SplitShard) -> Res
             split_shard.old_shard_id
         ))
     })?;
-    let Some(RangePartition { start, end }) = &old_shard.range else {
-        return Err(Error::InvalidData(format!(
-            "shard desc {} range fields is missing",
-            split_shard.old_shard_id
-        )));
-    };
-    if !sekas_schema::shard::belong_to(old_shard, &split_shard.split_key) {
-        return Err(Error::InvalidData(format!(
-            "the split shard key is not belong to the old shard {} range",
-            split_shard.old_shard_id
-        )));
-    }
-
-    let new_shard = ShardDesc::with_range(
+    let (left_shard, right_shard) = sekas_schema::shard::split_range(
+        old_shard,
         split_shard.new_shard_id,
-        old_shard.table_id,
-        split_shard.split_key.clone(),
-        end.clone(),
-    );
-    let old_range = RangePartition { start: start.clone(), end: split_shard.split_key };
-    old_shard.range = Some(old_range);
+        split_shard.split_key,
+    )
+    .map_err(|err| Error::InvalidData(format!("apply split shard: {err}")))?;
+    *old_shard = left_shard;
 
-    group_desc.shards.push(new_shard);
+    group_desc.shards.push(right_shard);
     group_desc.epoch = apply_shard_delta(group_desc.epoch);
     Ok(())
 }
@@ -739,7 +1050,33 @@ fn apply_merge_shard(group_desc: &mut GroupDesc, merge_shard: MergeShard) -> Res
 
 #[cfg(test)]
 mod tests {
+    use sekas_rock::fn_name;
+    use tempdir::TempDir;
+
     use super::*;
+    use crate::EngineConfig;
+
+    struct NoopObserver;
+
+    impl StateMachineObserver for NoopObserver {
+        fn on_descriptor_updated(&mut self, _: GroupDesc) {}
+        fn on_term_updated(&mut self, _: u64) {}
+        fn on_move_shard_state_updated(&mut self, _: Option<MoveShardState>) {}
+        fn on_move_shard_progress_updated(&mut self, _: Option<MoveShardState>) {}
+        fn on_quarantine_updated(&mut self, _: Option<ApplyQuarantine>) {}
+    }
+
+    async fn create_state_machine(dir: &Path, cfg: ReplicaConfig) -> GroupStateMachine {
+        use crate::bootstrap::open_engine_with_default_config;
+
+        let db = Arc::new(open_engine_with_default_config(dir).unwrap());
+        let group_engine = GroupEngine::create(&EngineConfig::default(), db, 1, 1).await.unwrap();
+        let replica_desc = ReplicaDesc { id: 1, node_id: 1, ..Default::default() };
+        let info = Arc::new(ReplicaInfo::new(&replica_desc, 1, ReplicaLocalState::Normal));
+        let (_, receiver) = mpsc::channel();
+        let watch_hub = WatchHub::new(receiver);
+        GroupStateMachine::new(cfg, info, group_engine, Box::new(NoopObserver), watch_hub)
+    }
 
     fn group_replicas(desc: &GroupDesc) -> Vec<(u64, ReplicaRole)> {
         let mut result: Vec<(u64, ReplicaRole)> =
@@ -820,6 +1157,16 @@ mod tests {
                 replica_id: 2,
                 expects: vec![(1, ReplicaRole::Learner)],
             },
+            Test {
+                tips: "9. add not exists read replica",
+                change_type: ChangeReplicaType::AddReadReplica,
+                replica_id: 3,
+                expects: vec![
+                    (1, ReplicaRole::Learner),
+                    (2, ReplicaRole::Voter),
+                    (3, ReplicaRole::ReadReplica),
+                ],
+            },
         ];
 
         let base_group_desc = GroupDesc {
@@ -1087,4 +1434,130 @@ mod tests {
             }
         }
     }
+
+    #[sekas_macro::test]
+    async fn verify_apply_checkpoint() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let cfg = ReplicaConfig { apply_checkpoint_entries: 2, ..Default::default() };
+        let mut fsm = create_state_machine(dir.path(), cfg).await;
+        for index in 1..=5 {
+            fsm.start_plug().unwrap();
+            fsm.apply(index, 1, ApplyEntry::Empty).unwrap();
+            fsm.finish_plug().unwrap();
+        }
+        let engine = fsm.group_engine.clone();
+        engine.flush(true).unwrap();
+        let (checkpoint, _) = engine.flushed_apply_checkpoint().unwrap().unwrap();
+        assert_eq!(checkpoint.apply_state, Some(ApplyState { index: 4, term: 1 }));
+        assert!(fsm.verify_apply_checkpoint().unwrap());
+
+        // The checkpoint doesn't match the flushed states.
+        let apply_state = ApplyState { index: 6, term: 1 };
+        let states = WriteStates {
+            apply_state: Some(apply_state.clone()),
+            apply_checkpoint: Some(ApplyCheckpoint { apply_state: Some(apply_state), digest: 1 }),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+        engine.flush(true).unwrap();
+        assert!(!fsm.verify_apply_checkpoint().unwrap());
+
+        // The states after the checkpoint are flushed.
+        let states = WriteStates {
+            apply_state: Some(ApplyState { index: 7, term: 1 }),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+        engine.flush(true).unwrap();
+        assert!(fsm.verify_apply_checkpoint().unwrap());
+    }
+
+    #[sekas_macro::test]
+    async fn discard_panicked_entry() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let cfg = ReplicaConfig::default();
+        let faults = cfg.testing_knobs.apply_faults.clone();
+        let mut fsm = create_state_machine(dir.path(), cfg).await;
+        let engine = fsm.group_engine.clone();
+        let shard = ShardDesc::with_range(1, 1, vec![], vec![]);
+        let states = WriteStates {
+            descriptor: Some(GroupDesc { id: 1, shards: vec![shard], ..Default::default() }),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+
+        let eval_result = |key: &[u8]| {
+            let mut wb = WriteBatch::default();
+            engine.put(&mut wb, 1, key, b"value", 1).unwrap();
+            let batch = Some(WriteBatchRep { data: wb.data().to_owned() });
+            ApplyEntry::Proposal { eval_result: EvalResult { batch, ..Default::default() } }
+        };
+
+        faults.inject_panic(b"poison");
+        fsm.start_plug().unwrap();
+        fsm.apply(1, 1, eval_result(b"key")).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            fsm.apply(2, 1, eval_result(b"poison")).unwrap();
+        }));
+        assert!(result.is_err());
+        fsm.discard_entry();
+        fsm.finish_plug().unwrap();
+        engine.flush(true).unwrap();
+        assert_eq!(engine.flushed_apply_state().unwrap(), ApplyState { index: 1, term: 1 });
+        assert!(engine.get(1, b"key").await.unwrap().is_some());
+        assert!(engine.get(1, b"poison").await.unwrap().is_none());
+
+        // The quarantine is persisted.
+        let quarantine =
+            ApplyQuarantine { index: 2, term: 1, message: "poisoned".to_owned(), corrupted: false };
+        fsm.quarantine(Some(quarantine.clone())).unwrap();
+        assert_eq!(fsm.quarantined(), Some(quarantine.clone()));
+        assert_eq!(engine.apply_quarantine().unwrap(), Some(quarantine));
+        fsm.quarantine(None).unwrap();
+        assert_eq!(engine.apply_quarantine().unwrap(), None);
+
+        // The states changed by the former entries are kept.
+        let update = ApplyEntry::Proposal {
+            eval_result: EvalResult {
+                op: Some(SyncOp::update_gc_watermark(20)),
+                ..Default::default()
+            },
+        };
+        fsm.start_plug().unwrap();
+        fsm.apply(2, 1, update).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            fsm.apply(3, 1, eval_result(b"poison")).unwrap();
+        }));
+        assert!(result.is_err());
+        fsm.discard_entry();
+        fsm.finish_plug().unwrap();
+        assert_eq!(engine.gc_state().unpinned_watermark(), 20);
+        engine.flush(true).unwrap();
+        assert_eq!(engine.flushed_apply_state().unwrap(), ApplyState { index: 2, term: 1 });
+    }
+
+    #[sekas_macro::test]
+    async fn apply_update_gc_watermark() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let mut fsm = create_state_machine(dir.path(), ReplicaConfig::default()).await;
+        let engine = fsm.group_engine.clone();
+        let update = |watermark| ApplyEntry::Proposal {
+            eval_result: EvalResult {
+                op: Some(SyncOp::update_gc_watermark(watermark)),
+                ..Default::default()
+            },
+        };
+
+        fsm.start_plug().unwrap();
+        fsm.apply(1, 1, update(20)).unwrap();
+        fsm.apply(2, 1, update(10)).unwrap();
+        fsm.finish_plug().unwrap();
+        assert_eq!(engine.gc_state().unpinned_watermark(), 20);
+
+        // A smaller watermark is ignored.
+        fsm.start_plug().unwrap();
+        fsm.apply(3, 1, update(15)).unwrap();
+        fsm.finish_plug().unwrap();
+        assert_eq!(engine.gc_state().unpinned_watermark(), 20);
+    }
 }
@@ -0,0 +1,337 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The hot key detection of the shards served by a leader replica.
+//!
+//! The keys of the requests are sampled at `HotKeyConfig::sample_rate`, and
+//! counted by a space-bounded heavy hitters sketch (SpaceSaving) of each shard.
+//! The sketch is reset once a window is finished, the hot keys of the last
+//! finished window are reported, so a key ages out once it is not hot in a
+//! whole window.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::{write_intent_request, HotKeyStats};
+use sekas_runtime::time::Instant;
+
+use crate::HotKeyConfig;
+
+/// The max length of the key prefix of the reported hot keys.
+const HOT_KEY_PREFIX_LEN: usize = 64;
+
+/// The hot keys of a shard.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ShardHotKeys {
+    /// The requests per second of the shard.
+    pub requests_per_sec: f64,
+    /// The hottest keys, in descending order of request rate.
+    pub hot_keys: Vec<HotKeyStats>,
+}
+
+pub(crate) struct HotKeyTracker {
+    cfg: HotKeyConfig,
+    shards: Mutex<HashMap<u64, ShardWindow>>,
+}
+
+struct ShardWindow {
+    sketch: SpaceSaving,
+    num_sampled: u64,
+    start_at: Instant,
+    /// The hot keys of the last finished window.
+    last_window: Option<ShardHotKeys>,
+}
+
+/// The SpaceSaving sketch, it tracks at most `capacity` keys. Once it is full,
+/// the key with the min count is replaced by the new key, which inherits the
+/// min count, so the count of a key is overestimated by at most the min count.
+struct SpaceSaving {
+    capacity: usize,
+    counters: HashMap<Vec<u8>, u64>,
+}
+
+impl HotKeyTracker {
+    pub(crate) fn new(cfg: HotKeyConfig) -> Self {
+        HotKeyTracker { cfg, shards: Mutex::default() }
+    }
+
+    /// Sample the keys of the request.
+    pub(crate) fn record_request(&self, request: &Request) {
+        if self.cfg.sample_rate <= 0.0 {
+            return;
+        }
+        match request {
+            Request::Get(req) => self.sample(req.shard_id, &req.user_key),
+            Request::Write(req) => {
+                for put in &req.puts {
+                    self.sample(req.shard_id, &put.key);
+                }
+                for delete in &req.deletes {
+                    self.sample(req.shard_id, &delete.key);
+                }
+            }
+            Request::WriteIntent(req) => match &req.write {
+                Some(write_intent_request::Write::Put(put)) => self.sample(req.shard_id, &put.key),
+                Some(write_intent_request::Write::Delete(delete)) => {
+                    self.sample(req.shard_id, &delete.key)
+                }
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    #[inline]
+    fn sample(&self, shard_id: u64, key: &[u8]) {
+        if self.cfg.sample_rate < 1.0
+            && sekas_runtime::sim::rng::random::<f64>() >= self.cfg.sample_rate
+        {
+            return;
+        }
+        self.record(shard_id, key, Instant::now());
+    }
+
+    fn record(&self, shard_id: u64, key: &[u8], now: Instant) {
+        let mut shards = self.shards.lock().unwrap();
+        let window =
+            shards.entry(shard_id).or_insert_with(|| ShardWindow::new(self.cfg.capacity, now));
+        window.advance(&self.cfg, now);
+        window.sketch.insert(key);
+        window.num_sampled += 1;
+    }
+
+    /// The hot keys of the shard, `None` if no key of the shard is sampled.
+    pub(crate) fn shard_hot_keys(&self, shard_id: u64) -> Option<ShardHotKeys> {
+        self.shard_hot_keys_at(shard_id, Instant::now())
+    }
+
+    fn shard_hot_keys_at(&self, shard_id: u64, now: Instant) -> Option<ShardHotKeys> {
+        let mut shards = self.shards.lock().unwrap();
+        let window = shards.get_mut(&shard_id)?;
+        window.advance(&self.cfg, now);
+        match &window.last_window {
+            Some(last_window) => Some(last_window.clone()),
+            None if window.num_sampled > 0 => Some(window.summary(&self.cfg, now)),
+            None => None,
+        }
+    }
+
+    /// Stop tracking the shards not served by the replica.
+    pub(crate) fn retain_shards(&self, shard_ids: &[u64]) {
+        let mut shards = self.shards.lock().unwrap();
+        shards.retain(|shard_id, _| shard_ids.contains(shard_id));
+    }
+}
+
+impl ShardWindow {
+    fn new(capacity: usize, now: Instant) -> Self {
+        ShardWindow {
+            sketch: SpaceSaving::new(capacity),
+            num_sampled: 0,
+            start_at: now,
+            last_window: None,
+        }
+    }
+
+    /// Finish the window if it is expired.
+    fn advance(&mut self, cfg: &HotKeyConfig, now: Instant) {
+        let window = Duration::from_millis(cfg.window_ms);
+        let elapsed = now.saturating_duration_since(self.start_at);
+        if elapsed < window {
+            return;
+        }
+        // The samples of a window finished long ago are stale.
+        self.last_window = if elapsed < 2 * window && self.num_sampled > 0 {
+            Some(self.summary(cfg, now))
+        } else {
+            None
+        };
+        self.sketch.clear();
+        self.num_sampled = 0;
+        self.start_at = now;
+    }
+
+    fn summary(&self, cfg: &HotKeyConfig, now: Instant) -> ShardHotKeys {
+        // The rates of the samples in a short duration are not reliable.
+        let secs = now.saturating_duration_since(self.start_at).as_secs_f64().max(1.0);
+        let scale = 1.0 / (cfg.sample_rate.min(1.0) * secs);
+        let hot_keys = self
+            .sketch
+            .top_k(cfg.top_k)
+            .into_iter()
+            .map(|(key, count)| HotKeyStats {
+                key_prefix: key.iter().take(HOT_KEY_PREFIX_LEN).cloned().collect(),
+                requests_per_sec: (count as f64 * scale) as f32,
+            })
+            .collect();
+        ShardHotKeys { requests_per_sec: self.num_sampled as f64 * scale, hot_keys }
+    }
+}
+
+impl SpaceSaving {
+    fn new(capacity: usize) -> Self {
+        SpaceSaving { capacity, counters: HashMap::with_capacity(capacity) }
+    }
+
+    fn insert(&mut self, key: &[u8]) {
+        if let Some(count) = self.counters.get_mut(key) {
+            *count += 1;
+            return;
+        }
+        if self.counters.len() < self.capacity {
+            self.counters.insert(key.to_owned(), 1);
+            return;
+        }
+        let Some((min_key, min_count)) = self
+            .counters
+            .iter()
+            .min_by_key(|(_, count)| **count)
+            .map(|(key, count)| (key.clone(), *count))
+        else {
+            return;
+        };
+        self.counters.remove(&min_key);
+        self.counters.insert(key.to_owned(), min_count + 1);
+    }
+
+    /// The `k` keys with the max counts, in descending order of count.
+    fn top_k(&self, k: usize) -> Vec<(&[u8], u64)> {
+        let mut counters =
+            self.counters.iter().map(|(key, count)| (key.as_slice(), *count)).collect::<Vec<_>>();
+        counters.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        counters.truncate(k);
+        counters
+    }
+
+    fn clear(&mut self) {
+        self.counters.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use sekas_api::server::v1::{PutRequest, ShardGetRequest, ShardWriteRequest};
+
+    use super::*;
+
+    fn config(sample_rate: f64) -> HotKeyConfig {
+        HotKeyConfig { sample_rate, capacity: 4, top_k: 2, window_ms: 10 * 1000 }
+    }
+
+    #[test]
+    fn space_saving_finds_heavy_hitters() {
+        let mut sketch = SpaceSaving::new(4);
+        for i in 0..1000u32 {
+            sketch.insert(b"hot");
+            if i % 2 == 0 {
+                sketch.insert(b"warm");
+            }
+            // The cold keys are more than the capacity of the sketch.
+            sketch.insert(format!("cold-{}", i % 16).as_bytes());
+        }
+        let top_k = sketch.top_k(2);
+        assert_eq!(top_k[0].0, b"hot");
+        assert!(top_k[0].1 >= 1000);
+        assert_eq!(top_k[1].0, b"warm");
+        assert!(top_k[1].1 >= 500);
+        assert_eq!(sketch.counters.len(), 4);
+    }
+
+    #[test]
+    fn hot_keys_age_out() {
+        let tracker = HotKeyTracker::new(config(1.0));
+        let start = Instant::now();
+        assert!(tracker.shard_hot_keys_at(1, start).is_none());
+        for i in 0..100 {
+            tracker.record(1, b"key-1", start);
+            if i % 4 == 0 {
+                tracker.record(1, b"key-2", start);
+            }
+        }
+
+        // The hot keys of the last finished window are reported.
+        let finished_at = start + Duration::from_secs(10);
+        let hot_keys = tracker.shard_hot_keys_at(1, finished_at).unwrap();
+        assert_eq!(hot_keys.requests_per_sec, 12.5);
+        assert_eq!(hot_keys.hot_keys.len(), 2);
+        assert_eq!(hot_keys.hot_keys[0].key_prefix, b"key-1");
+        assert_eq!(hot_keys.hot_keys[0].requests_per_sec, 10.0);
+        assert_eq!(hot_keys.hot_keys[1].key_prefix, b"key-2");
+        tracker.record(1, b"key-3", finished_at + Duration::from_secs(1));
+        let hot_keys = tracker.shard_hot_keys_at(1, finished_at + Duration::from_secs(5)).unwrap();
+        assert_eq!(hot_keys.hot_keys[0].key_prefix, b"key-1");
+
+        // The key not hot in the next window ages out.
+        let hot_keys = tracker.shard_hot_keys_at(1, finished_at + Duration::from_secs(10)).unwrap();
+        assert_eq!(hot_keys.hot_keys.len(), 1);
+        assert_eq!(hot_keys.hot_keys[0].key_prefix, b"key-3");
+        assert!(tracker.shard_hot_keys_at(1, finished_at + Duration::from_secs(40)).is_none());
+
+        tracker.record(2, b"key", start);
+        tracker.retain_shards(&[1]);
+        assert!(tracker.shard_hot_keys_at(2, start).is_none());
+    }
+
+    #[test]
+    fn long_keys_are_truncated() {
+        let tracker = HotKeyTracker::new(config(1.0));
+        let key = vec![b'k'; HOT_KEY_PREFIX_LEN * 2];
+        let req = ShardWriteRequest {
+            shard_id: 1,
+            puts: vec![PutRequest { key: key.clone(), ..Default::default() }],
+            ..Default::default()
+        };
+        tracker.record_request(&Request::Write(req));
+        let hot_keys = tracker.shard_hot_keys(1).unwrap();
+        assert_eq!(hot_keys.hot_keys[0].key_prefix, &key[..HOT_KEY_PREFIX_LEN]);
+    }
+
+    fn bench_record_request(b: &mut test::Bencher, sample_rate: f64) {
+        let tracker = HotKeyTracker::new(HotKeyConfig { sample_rate, ..Default::default() });
+        let requests = (0..1024)
+            .map(|i| {
+                Request::Get(ShardGetRequest {
+                    shard_id: 1,
+                    user_key: format!("user-key-{i:08}").into_bytes(),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut i = 0;
+        b.iter(|| {
+            tracker.record_request(test::black_box(&requests[i % requests.len()]));
+            i += 1;
+        });
+    }
+
+    /// The baseline of the overhead, the detection is disabled.
+    #[bench]
+    fn bench_record_request_disabled(b: &mut test::Bencher) {
+        bench_record_request(b, 0.0);
+    }
+
+    #[bench]
+    fn bench_record_request_at_default_sample_rate(b: &mut test::Bencher) {
+        bench_record_request(b, HotKeyConfig::default().sample_rate);
+    }
+
+    #[bench]
+    fn bench_record_request_at_full_sample_rate(b: &mut test::Bencher) {
+        bench_record_request(b, 1.0);
+    }
+}
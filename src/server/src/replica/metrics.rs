@@ -0,0 +1,57 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use lazy_static::lazy_static;
+use prometheus::*;
+
+lazy_static! {
+    pub static ref REPLICA_SHARD_INTENTS: IntGaugeVec = register_int_gauge_vec!(
+        "replica_shard_intents",
+        "The number of unresolved intents of the shards served by leader replicas",
+        &["group", "shard"]
+    )
+    .unwrap();
+    pub static ref REPLICA_SHARD_OLDEST_INTENT_AGE_SECONDS: GaugeVec = register_gauge_vec!(
+        "replica_shard_oldest_intent_age_seconds",
+        "The age of the oldest unresolved intent of the shards served by leader replicas",
+        &["group", "shard"]
+    )
+    .unwrap();
+    pub static ref REPLICA_SHARD_RESOLVED_INTENTS_PER_SEC: GaugeVec = register_gauge_vec!(
+        "replica_shard_resolved_intents_per_sec",
+        "The number of intents resolved per second of the shards served by leader replicas",
+        &["group", "shard"]
+    )
+    .unwrap();
+    pub static ref REPLICA_TABLE_PROPOSAL_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "replica_table_proposal_total",
+        "The total of write proposals admitted by the leader replicas, by table",
+        &["group", "table"]
+    )
+    .unwrap();
+    pub static ref REPLICA_TABLE_PROPOSAL_QUEUE_DELAY_SECONDS: HistogramVec =
+        register_histogram_vec!(
+            "replica_table_proposal_queue_delay_seconds",
+            "The duration of write proposals queued before admitted, by table",
+            &["group", "table"],
+            exponential_buckets(0.00005, 1.8, 26).unwrap(),
+        )
+        .unwrap();
+    pub static ref REPLICA_RESOLVE_INTENT_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "replica_resolve_intent_total",
+        "The total of intents resolved by the background intent resolver",
+        &["type"]
+    )
+    .unwrap();
+}
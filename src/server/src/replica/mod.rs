@@ -15,35 +15,50 @@
 
 mod eval;
 pub mod fsm;
+mod hot_key;
+pub mod metrics;
 mod move_shard;
+mod proposal_queue;
+mod purge;
 pub mod retry;
+mod shard_load;
 mod state;
+mod verify;
 
-use std::sync::atomic::AtomicI32;
+use std::sync::atomic::{AtomicI32, AtomicU64};
 use std::sync::{Arc, Mutex};
 use std::task::Poll;
+use std::time::Duration;
 
-use futures::channel::mpsc;
-use log::{info, trace, warn};
+use log::{error, info, trace, warn};
+use prost::Message;
 use sekas_api::server::v1::group_request_union::Request;
 use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
 use sekas_api::Epoch;
+use sekas_runtime::time::Instant;
 use serde::Serialize;
 
 use self::eval::acquire_row_latches;
 pub(crate) use self::eval::merge_scan_response;
 use self::eval::remote::RemoteLatchManager;
-use self::fsm::WatchEvent;
+use self::hot_key::HotKeyTracker;
+use self::proposal_queue::ProposalQueue;
+pub(crate) use self::purge::setup_shard_purger;
+use self::shard_load::ShardLoadTracker;
 pub use self::state::{LeaseState, LeaseStateObserver};
-use crate::engine::GroupEngine;
+pub(crate) use self::verify::setup_descriptor_verifier;
+use crate::engine::{GroupEngine, VersionChainStats};
 use crate::error::BusyReason;
+use crate::node::metrics::{NODE_READ_REPLICA_REJECTED_TOTAL, NODE_READ_REPLICA_REQUEST_TOTAL};
+use crate::node::scan::ScanQuota;
+use crate::node::watch::WatchEventSender;
 use crate::raftgroup::{
     perf_point_micros, write_initial_state, RaftGroup, ReadPolicy, WorkerPerfContext,
 };
 use crate::schedule::MoveReplicasProvider;
 use crate::serverpb::v1::*;
-use crate::{Error, RaftConfig, Result};
+use crate::{Error, HotKeyConfig, ProposalQueueConfig, RaftConfig, Result};
 
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct ReplicaPerfContext {
@@ -78,11 +93,44 @@ pub struct ExecCtx {
 
     pub watch_event_sender: Option<WatchEventSender>,
 
+    /// The memory quota of the scan, the bytes of the scanned value sets are
+    /// acquired from it.
+    pub scan_quota: Option<ScanQuota>,
+
+    /// The request id recorded into the proposed raft entries.
+    pub request_id: Option<String>,
+
+    /// The deadline of the request, the request forwarded to the leader is
+    /// bounded by it.
+    pub deadline: Option<Instant>,
+
+    /// Whether the request is issued by the servers of the cluster, only they
+    /// are allowed to write the system tables.
+    pub internal_origin: bool,
+
     /// The move shard desc, filled by `check_request_early`.
     move_shard_desc: Option<MoveShardDesc>,
 }
 
-type WatchEventSender = mpsc::UnboundedSender<WatchEvent>;
+/// The max duration a read replica waits for catching up the causal token,
+/// the request is redirected to the leader once it is exceeded.
+const READ_REPLICA_WAIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The hard cap of the duration since a read replica last heard from the
+/// leader, the reads are rejected beyond it whatever the requested bound is.
+const READ_REPLICA_MAX_STALENESS: Duration = Duration::from_secs(10);
+
+/// The number of the failed raft messages since the replica last heard from
+/// the leader, beyond which the replica considers itself removed from the
+/// group.
+const REMOVED_UNREACHABLE_THRESHOLD: u64 = 16;
+
+/// The number of the oldest intents of each shard reported to the root.
+const REPORT_OLDEST_INTENTS: usize = 10;
+
+/// The max length of the key prefix of the reported intents.
+const INTENT_KEY_PREFIX_LEN: usize = 32;
+
 type WatcherSender = std::sync::mpsc::Sender<((u64, Box<[u8]>), WatchEventSender)>;
 
 pub struct Replica
@@ -97,6 +145,12 @@ where
     move_replicas_provider: Arc<MoveReplicasProvider>,
     meta_acl: Arc<tokio::sync::RwLock<()>>,
     latch_mgr: RemoteLatchManager,
+    /// The writes committed at versions not greater than it have been applied
+    /// by this replica, only used by the read replicas.
+    read_safe_version: AtomicU64,
+    hot_keys: HotKeyTracker,
+    shard_loads: ShardLoadTracker,
+    proposal_queue: ProposalQueue,
 }
 
 impl Replica {
@@ -121,6 +175,7 @@ impl Replica {
     }
 
     /// Open the existed replica of raft group.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         info: Arc<ReplicaInfo>,
         lease_state: Arc<Mutex<LeaseState>>,
@@ -129,9 +184,12 @@ impl Replica {
         sekas_client: sekas_client::SekasClient,
         move_replicas_provider: Arc<MoveReplicasProvider>,
         watcher_sender: WatcherSender,
+        hot_key_cfg: HotKeyConfig,
+        proposal_queue_cfg: ProposalQueueConfig,
     ) -> Self {
         let latch_mgr =
             RemoteLatchManager::new(sekas_client, group_engine.clone(), raft_group.clone());
+        let proposal_queue = ProposalQueue::new(info.group_id, proposal_queue_cfg);
         Replica {
             info,
             group_engine,
@@ -142,6 +200,10 @@ impl Replica {
             meta_acl: Arc::default(),
             // FIXME(walter) create latch manager if epoch changed.
             latch_mgr,
+            read_safe_version: AtomicU64::new(0),
+            hot_keys: HotKeyTracker::new(hot_key_cfg),
+            shard_loads: ShardLoadTracker::new(),
+            proposal_queue,
         }
     }
 
@@ -172,8 +234,66 @@ impl Replica {
         }
 
         let _acl_guard = self.take_acl_guard(request).await;
+        if matches!(request, Request::Get(_) | Request::Scan(_)) {
+            self.check_not_removed()?;
+            if self.is_read_replica() {
+                return self.execute_on_read_replica(exec_ctx, request).await;
+            }
+        }
         self.check_request_early(exec_ctx, request)?;
-        self.evaluate_command(exec_ctx, request).await
+        self.hot_keys.record_request(request);
+        self.shard_loads.record_request(request);
+        self.evaluate_command(exec_ctx, request).await.map_err(|err| self.on_evaluate_error(err))
+    }
+
+    /// Serve a get or scan request on a read replica. The request waits until
+    /// the writes required by its causal token are applied, and is redirected
+    /// to the leader if it couldn't be served here.
+    async fn execute_on_read_replica(
+        &self,
+        exec_ctx: &mut ExecCtx,
+        request: &Request,
+    ) -> Result<Response> {
+        use std::sync::atomic::Ordering;
+
+        let (causal_token, max_staleness_ms, request_type) = match request {
+            Request::Get(req) => (req.causal_token, req.max_staleness_ms, "get"),
+            Request::Scan(req) => (req.causal_token, req.max_staleness_ms, "scan"),
+            _ => unreachable!("only get and scan are served by read replicas"),
+        };
+        self.check_read_replica_staleness(max_staleness_ms)?;
+        if causal_token > self.read_safe_version.load(Ordering::Acquire) {
+            // The intents of a write are replicated before its commit version is allocated,
+            // so a read index issued after the token is received covers them.
+            let read = self.raft_group.read(ReadPolicy::ReadIndex);
+            match sekas_runtime::time::timeout(READ_REPLICA_WAIT_TIMEOUT, read).await {
+                Ok(Ok(())) => {
+                    self.read_safe_version.fetch_max(causal_token, Ordering::AcqRel);
+                }
+                Ok(Err(err)) => {
+                    trace!("group {} read replica wait read index: {err}", self.info.group_id);
+                    return Err(self.redirect_to_leader());
+                }
+                Err(_) => return Err(self.redirect_to_leader()),
+            }
+        }
+
+        self.check_read_replica_request_early(exec_ctx)?;
+        match self
+            .evaluate_command(exec_ctx, request)
+            .await
+            .map_err(|err| self.on_evaluate_error(err))
+        {
+            Ok(resp) => {
+                NODE_READ_REPLICA_REQUEST_TOTAL
+                    .with_label_values(&[&self.info.node_id.to_string(), request_type])
+                    .inc();
+                Ok(resp)
+            }
+            // The intents could only be resolved by the leader.
+            Err(Error::NotLeader(..)) => Err(self.redirect_to_leader()),
+            Err(err) => Err(err),
+        }
     }
 
     /// Execute group request. instead of be blocked, it will returns
@@ -190,7 +310,7 @@ impl Replica {
         let _acl_guard =
             self.try_take_acl_guard(request).ok_or(Error::ServiceIsBusy(BusyReason::AclGuard))?;
         self.check_request_early(&mut exec_ctx, request)?;
-        self.evaluate_command(&exec_ctx, request).await
+        self.evaluate_command(&exec_ctx, request).await.map_err(|err| self.on_evaluate_error(err))
     }
 
     pub async fn on_leader(&self, source: &'static str, immediate: bool) -> Result<Option<u64>> {
@@ -223,6 +343,58 @@ impl Replica {
         Ok(())
     }
 
+    /// Resolve the intent of txn `start_version` by the txn record, the intent
+    /// is committed or cleared if the txn is finished or expired. It waits
+    /// until then if the txn is still running.
+    pub(crate) async fn resolve_intent(
+        &self,
+        shard_id: u64,
+        user_key: &[u8],
+        start_version: u64,
+    ) -> Result<()> {
+        use self::eval::LatchManager;
+
+        let _acl_guard = self.take_read_acl_guard().await;
+        self.check_leader_early()?;
+        self.latch_mgr.resolve_txn(shard_id, user_key, start_version, start_version).await?;
+        Ok(())
+    }
+
+    /// Write the shards of the group in a single proposal, so the writes are
+    /// applied atomically. It is used by root to write the system tables.
+    pub(crate) async fn batch_write_shards(&self, writes: &[ShardWriteRequest]) -> Result<()> {
+        if self.info.is_terminated() {
+            return Err(Error::GroupNotFound(self.info.group_id));
+        }
+
+        let _acl_guard = self.take_read_acl_guard().await;
+        self.check_leader_early()?;
+        // Acquire the latches in the order of shard, to avoid deadlock.
+        let mut writes = writes.to_vec();
+        writes.sort_unstable_by_key(|write| write.shard_id);
+        let mut latches = Vec::with_capacity(writes.len());
+        for write in &writes {
+            let request = Request::Write(write.clone());
+            latches.push(acquire_row_latches(&self.latch_mgr, &request).await?);
+        }
+        if let Some(eval_result) = eval::batch_write_shards(&self.group_engine, &writes).await? {
+            self.raft_group.propose(eval_result).await?;
+        }
+        Ok(())
+    }
+
+    /// Advance the GC watermark of the group through raft, so all replicas
+    /// drop the versions beneath it by compaction, and it survives restarts.
+    /// A watermark not above the current one is ignored.
+    pub(crate) async fn update_gc_watermark(&self, watermark: u64) -> Result<()> {
+        let _acl_guard = self.take_read_acl_guard().await;
+        self.check_leader_early()?;
+        let eval_result =
+            EvalResult { op: Some(SyncOp::update_gc_watermark(watermark)), ..Default::default() };
+        self.raft_group.propose(eval_result).await?;
+        Ok(())
+    }
+
     #[inline]
     pub fn replica_info(&self) -> Arc<ReplicaInfo> {
         self.info.clone()
@@ -263,6 +435,12 @@ impl Replica {
         self.lease_state.lock().unwrap().schedule_state.clone()
     }
 
+    /// The number of the proposals admitted but not finished yet.
+    #[inline]
+    pub fn open_proposals(&self) -> usize {
+        self.proposal_queue.inflights()
+    }
+
     pub async fn monitor(&self) -> Result<ReplicaPerfContext> {
         let take_acl_guard = perf_point_micros();
         let _acl_guard = self.take_read_acl_guard().await;
@@ -275,7 +453,12 @@ impl Replica {
     pub fn collect_group_stats(&self) -> GroupStats {
         let descriptor = self.descriptor();
         let shard_count = descriptor.shards.len();
+        let descriptor_epoch = descriptor.epoch;
         let group_id = self.info.group_id;
+        let shard_ids = descriptor.shards.iter().map(|shard| shard.id).collect::<Vec<_>>();
+        self.hot_keys.retain_shards(&shard_ids);
+        self.shard_loads.collect(&shard_ids);
+        let (mut read_qps, mut write_qps) = (0.0, 0.0);
         let mut shard_stats = Vec::with_capacity(shard_count);
         for shard in descriptor.shards {
             let shard_id = shard.id;
@@ -290,14 +473,81 @@ impl Replica {
                     continue;
                 }
             };
-            shard_stats.push(ShardStats { shard_id, table_id, shard_size });
+            let num_keys = match self.group_engine.get_approximate_num_keys(shard_id) {
+                Ok(num_keys) => num_keys,
+                Err(err) => {
+                    warn!(
+                        "get approximate num keys of shard {shard_id}: {err}, group_id={group_id}"
+                    );
+                    0
+                }
+            };
+            let version_chain = match self.group_engine.version_chain_stats(shard_id) {
+                Ok(stats) => stats,
+                Err(err) => {
+                    warn!("sample version chains of shard {shard_id}: {err}, group_id={group_id}");
+                    VersionChainStats::default()
+                }
+            };
+            let load = self.shard_loads.shard_load(shard_id);
+            read_qps += load.read_qps;
+            write_qps += load.write_qps;
+            let intent_stats = self.group_engine.shard_intent_stats(shard_id);
+            let oldest_intent = intent_stats.oldest_intent.unwrap_or_default();
+            let oldest_intents = self
+                .group_engine
+                .oldest_intents(shard_id, REPORT_OLDEST_INTENTS)
+                .into_iter()
+                .map(|intent| IntentStats {
+                    key_prefix: intent.user_key.into_iter().take(INTENT_KEY_PREFIX_LEN).collect(),
+                    start_version: intent.start_version,
+                    age_ms: intent.age.as_millis() as u64,
+                })
+                .collect();
+            let hot_keys = self.hot_keys.shard_hot_keys(shard_id).unwrap_or_default();
+            shard_stats.push(ShardStats {
+                shard_id,
+                table_id,
+                shard_size,
+                num_intents: intent_stats.num_intents,
+                oldest_intent_version: oldest_intent.start_version,
+                oldest_intent_age_ms: oldest_intent.age.as_millis() as u64,
+                resolved_intents_per_sec: intent_stats.resolve_rate as f32,
+                oldest_intents,
+                requests_per_sec: hot_keys.requests_per_sec as f32,
+                hot_keys: hot_keys.hot_keys,
+                num_keys,
+                read_qps: load.read_qps as f32,
+                write_qps: load.write_qps as f32,
+                version_chain_p50: version_chain.p50,
+                version_chain_p99: version_chain.p99,
+                version_chain_max: version_chain.max,
+                ..Default::default()
+            });
+        }
+        match self.group_engine.purge_shard_states() {
+            Ok(states) => {
+                for state in states {
+                    let Some(shard) = state.shard else { continue };
+                    shard_stats.push(ShardStats {
+                        shard_id: shard.id,
+                        table_id: shard.table_id,
+                        removed: true,
+                        purged_bytes: state.purged_bytes,
+                        purge_finished: state.finished,
+                        ..Default::default()
+                    });
+                }
+            }
+            Err(err) => warn!("read purge shard states: {err}, group_id={group_id}"),
         }
         GroupStats {
             group_id,
             shard_count: shard_count as u64,
-            read_qps: 0.,
-            write_qps: 0.,
+            read_qps: read_qps as f32,
+            write_qps: write_qps as f32,
             shard_stats,
+            epoch: descriptor_epoch,
         }
     }
 }
@@ -346,11 +596,21 @@ impl Replica {
                 let resp = ShardGetResponse { value };
                 (None, Response::Get(resp))
             }
+            Request::GetRawKey(req) => {
+                let state = eval::get_raw_key(&self.group_engine, req)?;
+                let resp = GetRawKeyResponse { state: Some(state) };
+                (None, Response::GetRawKey(resp))
+            }
             Request::Write(req) => {
                 let (eval_result, resp) =
                     eval::batch_write(exec_ctx, &self.group_engine, req).await?;
                 (eval_result, Response::Write(resp))
             }
+            Request::DeletePrefix(req) => {
+                let (eval_result, resp) =
+                    eval::delete_prefix(exec_ctx, &self.group_engine, req).await?;
+                (eval_result, Response::DeletePrefix(resp))
+            }
             Request::WriteIntent(req) => {
                 let (eval_result, resp) = eval::write_intent(
                     exec_ctx,
@@ -386,6 +646,12 @@ impl Replica {
                     eval::scan(exec_ctx, &self.group_engine, &self.latch_mgr, req).await?;
                 (None, Response::Scan(eval_result))
             }
+            Request::CheckPrefixEmpty(req) => {
+                let resp =
+                    eval::check_prefix_empty(exec_ctx, &self.group_engine, &self.latch_mgr, req)
+                        .await?;
+                (None, Response::CheckPrefixEmpty(resp))
+            }
             Request::CreateShard(req) => {
                 // TODO(walter) check the existing of shard.
                 let shard = req
@@ -418,6 +684,11 @@ impl Replica {
                 (None, Response::MoveReplicas(resp))
             }
             Request::AcceptShard(req) => {
+                if let Some(shard) = req.shard_desc.as_ref() {
+                    if sekas_schema::shard::is_txn_shard(shard.id) {
+                        return Err(Error::TxnShardFenced(shard.id));
+                    }
+                }
                 let eval_result = eval::accept_shard(self.info.group_id, exec_ctx.epoch, req).await;
                 let resp = AcceptShardResponse {};
                 (Some(eval_result), Response::AcceptShard(resp))
@@ -443,27 +714,56 @@ impl Replica {
                 return Ok(Response::WatchKey(WatchKeyResponse::default()));
             }
             Request::SplitShard(req) => {
-                let eval_result = eval::split_shard(&self.group_engine, req)?;
+                let eval_result = eval::split_shard(&self.group_engine, req).await?;
                 (Some(eval_result), Response::SplitShard(SplitShardResponse {}))
             }
             Request::MergeShard(req) => {
                 let eval_result = eval::merge_shard(&self.group_engine, req)?;
                 (Some(eval_result), Response::MergeShard(MergeShardResponse {}))
             }
+            Request::RemoveShard(req) => {
+                let eval_result = eval::remove_shard(exec_ctx, &self.group_engine, req)?;
+                (eval_result, Response::RemoveShard(RemoveShardResponse {}))
+            }
         };
 
-        if let Some(eval_result) = eval_result_opt {
+        if let Some(mut eval_result) = eval_result_opt {
+            if let Some(request_id) = &exec_ctx.request_id {
+                eval_result.request_id = request_id.clone();
+            }
+            let _permit = match self.proposal_table_id(request) {
+                Some(table_id) => {
+                    let cost = eval_result.encoded_len() as u64;
+                    Some(self.proposal_queue.admit(table_id, cost).await)
+                }
+                None => None,
+            };
             self.raft_group.propose(eval_result).await?;
         }
 
         Ok(resp)
     }
 
+    /// The table of the shard written by the request, the writes are queued
+    /// fairly across tables before proposing. The other requests are not
+    /// queued.
+    fn proposal_table_id(&self, request: &Request) -> Option<u64> {
+        let shard_id = match request {
+            Request::Write(req) => req.shard_id,
+            Request::WriteIntent(req) => req.shard_id,
+            Request::CommitIntent(req) => req.shard_id,
+            Request::ClearIntent(req) => req.shard_id,
+            Request::DeletePrefix(req) => req.shard_id,
+            _ => return None,
+        };
+        self.group_engine.shard_desc(shard_id).ok().map(|shard| shard.table_id)
+    }
+
     fn check_request_early(&self, exec_ctx: &mut ExecCtx, req: &Request) -> Result<()> {
         let group_id = self.info.group_id;
         exec_ctx.group_id = group_id;
         exec_ctx.replica_id = self.info.replica_id;
-        let lease_state = self.lease_state.lock().unwrap();
+        let mut lease_state = self.lease_state.lock().unwrap();
         if !lease_state.is_raft_leader() {
             Err(Error::NotLeader(
                 group_id,
@@ -475,8 +775,17 @@ impl Replica {
             // unapplied WALs, so the freshness of metadata cannot be
             // guaranteed.
             Err(Error::GroupNotReady(group_id))
-        } else if exec_ctx.forward_shard_id.is_some() {
-            Ok(())
+        } else if !lease_state.is_descriptor_verified() {
+            // The descriptor might be behind the catalog, see `verify`.
+            Err(Error::GroupNotReady(group_id))
+        } else if let Some(shard_id) = exec_ctx.forward_shard_id {
+            if lease_state.is_shard_moving_aborting(shard_id) {
+                // The moving is canceled, the forwarded requests should be served by the source
+                // group once the moving is aborted.
+                Err(Error::ShardNotFound(shard_id))
+            } else {
+                Ok(())
+            }
         } else if exec_ctx.epoch < lease_state.descriptor.epoch {
             trace!(
                 "request epoch {} less than local epoch {}, group: {}, replica: {}",
@@ -486,6 +795,18 @@ impl Replica {
                 self.info.replica_id
             );
             Err(Error::EpochNotMatch(lease_state.descriptor.clone()))
+        } else if exec_ctx.epoch > lease_state.descriptor.epoch {
+            // The epoch comes from the catalog, so the local descriptor is behind the
+            // catalog even though it was verified.
+            warn!(
+                "request epoch {} is larger than local epoch {}, verify descriptor again, group: {}, replica: {}",
+                Epoch(exec_ctx.epoch),
+                Epoch(lease_state.descriptor.epoch),
+                self.info.group_id,
+                self.info.replica_id
+            );
+            lease_state.invalidate_descriptor_verified();
+            Err(Error::GroupNotReady(group_id))
         } else if lease_state.has_shard_moving() && matches!(req, Request::AcceptShard(_)) {
             trace!(
                 "the request shard is in moving, group: {}, replica: {}",
@@ -512,6 +833,115 @@ impl Replica {
         }
     }
 
+    fn check_read_replica_request_early(&self, exec_ctx: &mut ExecCtx) -> Result<()> {
+        exec_ctx.group_id = self.info.group_id;
+        exec_ctx.replica_id = self.info.replica_id;
+        let lease_state = self.lease_state.lock().unwrap();
+        let local_epoch = lease_state.descriptor.epoch;
+        if exec_ctx.forward_shard_id.is_none() && exec_ctx.epoch < local_epoch {
+            Err(Error::EpochNotMatch(lease_state.descriptor.clone()))
+        } else if exec_ctx.forward_shard_id.is_some()
+            || exec_ctx.epoch > local_epoch
+            || lease_state.has_shard_moving()
+        {
+            // The read replica lags behind, or the request needs to be forwarded, leave it
+            // to the leader.
+            Err(Error::NotLeader(
+                self.info.group_id,
+                lease_state.applied_term,
+                lease_state.leader_descriptor(),
+            ))
+        } else {
+            exec_ctx.move_shard_desc = None;
+            Ok(())
+        }
+    }
+
+    /// The corrupted replica is quarantined until it is rebuilt on another
+    /// node, the request is redirected to the other replicas.
+    fn on_evaluate_error(&self, err: Error) -> Error {
+        if !err.is_storage_corrupted() {
+            return err;
+        }
+        error!(
+            "group {} replica {} finds the storage corrupted: {err}",
+            self.info.group_id, self.info.replica_id
+        );
+        self.raft_group.quarantine_corruption(err.to_string());
+        let applied_term = self.lease_state.lock().unwrap().applied_term;
+        Error::NotLeader(self.info.group_id, applied_term, None)
+    }
+
+    /// Reject the read if the read replica hasn't heard from the leader within
+    /// the requested bound, or the hard cap if the bound is not specified.
+    /// Otherwise the data served might be older than the bound, eg. the
+    /// replica is partitioned from the leader.
+    fn check_read_replica_staleness(&self, max_staleness_ms: u64) -> Result<()> {
+        let bound = match max_staleness_ms {
+            0 => READ_REPLICA_MAX_STALENESS,
+            ms => Duration::from_millis(ms).min(READ_REPLICA_MAX_STALENESS),
+        };
+        match self.raft_group.leader_contact().since_last_heard() {
+            Some(elapsed) if elapsed <= bound => Ok(()),
+            elapsed => {
+                NODE_READ_REPLICA_REJECTED_TOTAL
+                    .with_label_values(&[&self.info.node_id.to_string(), "stale"])
+                    .inc();
+                let reason = match elapsed {
+                    Some(elapsed) => {
+                        format!("last heard from the leader {elapsed:?} ago, beyond {bound:?}")
+                    }
+                    None => "never heard from the leader".to_owned(),
+                };
+                Err(Error::ReplicaNotReady(self.info.group_id, self.info.replica_id, reason))
+            }
+        }
+    }
+
+    /// Stop serving any read once the replica learns that it was removed from
+    /// the group, by the local descriptor or by the raft messages failed over
+    /// the threshold, since its data is no longer replicated by the leader.
+    fn check_not_removed(&self) -> Result<()> {
+        let removed = {
+            let lease_state = self.lease_state.lock().unwrap();
+            let replicas = &lease_state.descriptor.replicas;
+            !lease_state.is_raft_leader()
+                && ((!replicas.is_empty()
+                    && !replicas.iter().any(|r| r.id == self.info.replica_id))
+                    || self.raft_group.leader_contact().num_unreachable()
+                        >= REMOVED_UNREACHABLE_THRESHOLD)
+        };
+        if removed {
+            NODE_READ_REPLICA_REJECTED_TOTAL
+                .with_label_values(&[&self.info.node_id.to_string(), "removed"])
+                .inc();
+            return Err(Error::ReplicaNotReady(
+                self.info.group_id,
+                self.info.replica_id,
+                "removed from the group".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn redirect_to_leader(&self) -> Error {
+        let lease_state = self.lease_state.lock().unwrap();
+        Error::NotLeader(
+            self.info.group_id,
+            lease_state.applied_term,
+            lease_state.leader_descriptor(),
+        )
+    }
+
+    fn is_read_replica(&self) -> bool {
+        let lease_state = self.lease_state.lock().unwrap();
+        lease_state
+            .descriptor
+            .replicas
+            .iter()
+            .any(|r| r.id == self.info.replica_id && r.role == ReplicaRole::ReadReplica as i32)
+    }
+
     fn check_leader_early(&self) -> Result<()> {
         let lease_state = self.lease_state.lock().unwrap();
         if !lease_state.is_ready_for_serving() {
@@ -599,13 +1029,17 @@ fn is_change_meta_request(request: &Request) -> bool {
         | Request::MoveReplicas(_)
         | Request::Transfer(_)
         | Request::SplitShard(_)
-        | Request::MergeShard(_) => true,
+        | Request::MergeShard(_)
+        | Request::RemoveShard(_) => true,
         Request::Get(_)
+        | Request::GetRawKey(_)
         | Request::Write(_)
+        | Request::DeletePrefix(_)
         | Request::Scan(_)
         | Request::WriteIntent(_)
         | Request::CommitIntent(_)
         | Request::ClearIntent(_)
+        | Request::CheckPrefixEmpty(_)
         | Request::WatchKey(_) => false,
     }
 }
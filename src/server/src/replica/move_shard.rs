@@ -16,7 +16,7 @@
 use log::{debug, info};
 use sekas_api::server::v1::*;
 
-use super::eval::{ingest_value_set, LatchManager};
+use super::eval::{ingest_value_set, ingest_value_sets, restore_value_set, LatchManager};
 use super::{LeaseState, Replica, ReplicaInfo};
 use crate::engine::WriteBatch;
 use crate::serverpb::v1::*;
@@ -26,7 +26,7 @@ impl Replica {
     /// Ingest value set of a key if it not exists before.
     pub async fn ingest_value_set(&self, shard_id: u64, value_set: &ValueSet) -> Result<()> {
         let _acl_guard = self.take_read_acl_guard().await;
-        self.check_moving_shard_request_early(shard_id)?;
+        self.check_moving_shard_ingest_early(shard_id)?;
 
         let _latch_guard = self.latch_mgr.acquire(shard_id, &value_set.user_key).await?;
         let eval_result = match ingest_value_set(&self.group_engine, shard_id, value_set).await? {
@@ -38,12 +38,66 @@ impl Replica {
         Ok(())
     }
 
-    /// Save the ingestion progress to support fast recovery.
-    pub async fn save_ingest_progress(&self, shard_id: u64, user_key: &[u8]) -> Result<()> {
+    /// Ingest the value sets of keys into a serving shard if they not exist
+    /// before, in a single proposal. It is used to copy the data of a cloning
+    /// table.
+    pub async fn ingest_serving_value_sets(
+        &self,
+        shard_id: u64,
+        value_sets: &[ValueSet],
+    ) -> Result<()> {
+        let _acl_guard = self.take_read_acl_guard().await;
+        self.check_leader_early()?;
+        if !self.lease_state.lock().unwrap().descriptor.shards.iter().any(|s| s.id == shard_id) {
+            return Err(Error::ShardNotFound(shard_id));
+        }
+
+        // ATTN: Sort keys before acquiring any latch, to avoid deadlock.
+        let mut keys = value_sets.iter().map(|v| v.user_key.as_slice()).collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys.dedup();
+        let mut latches = Vec::with_capacity(keys.len());
+        for key in keys {
+            latches.push(self.latch_mgr.acquire(shard_id, key).await?);
+        }
+        let eval_result = match ingest_value_sets(&self.group_engine, shard_id, value_sets).await? {
+            Some(eval_result) => eval_result,
+            None => return Ok(()),
+        };
+        self.raft_group.propose(eval_result).await?;
+
+        Ok(())
+    }
+
+    /// Restore the versions of a key which are not exists in local, it is used
+    /// to roll back the moved data from the dest group.
+    pub async fn restore_value_set(&self, shard_id: u64, value_set: &ValueSet) -> Result<()> {
         let _acl_guard = self.take_read_acl_guard().await;
         self.check_moving_shard_request_early(shard_id)?;
-        let eval_result =
-            EvalResult { op: Some(SyncOp::ingest(user_key.to_vec())), ..Default::default() };
+
+        let _latch_guard = self.latch_mgr.acquire(shard_id, &value_set.user_key).await?;
+        let eval_result = match restore_value_set(&self.group_engine, shard_id, value_set).await? {
+            Some(eval_result) => eval_result,
+            None => return Ok(()),
+        };
+        self.raft_group.propose(eval_result).await?;
+
+        Ok(())
+    }
+
+    /// Save the ingestion progress to support fast recovery, the number of keys
+    /// and bytes ingested since the last saving are accumulated.
+    pub async fn save_ingest_progress(
+        &self,
+        shard_id: u64,
+        user_key: &[u8],
+        ingested_keys: u64,
+        ingested_bytes: u64,
+    ) -> Result<()> {
+        let _acl_guard = self.take_read_acl_guard().await;
+        self.check_moving_shard_ingest_early(shard_id)?;
+        let op = SyncOp::ingest(user_key.to_vec(), ingested_keys, ingested_bytes);
+        let eval_result = EvalResult { op: Some(op), ..Default::default() };
         self.raft_group.propose(eval_result).await?;
         Ok(())
     }
@@ -61,8 +115,10 @@ impl Replica {
             self.group_engine.delete(&mut wb, shard_id, key, *version)?;
         }
 
-        let eval_result =
-            EvalResult { batch: Some(WriteBatchRep { data: wb.data().to_owned() }), op: None };
+        let eval_result = EvalResult {
+            batch: Some(WriteBatchRep { data: wb.data().to_owned() }),
+            ..Default::default()
+        };
         self.raft_group.propose(eval_result).await?;
 
         Ok(())
@@ -80,6 +136,10 @@ impl Replica {
         self.update_move_shard_state(desc, MoveShardEvent::Commit).await
     }
 
+    pub async fn cancel_shard_moving(&self, desc: &MoveShardDesc) -> Result<()> {
+        self.update_move_shard_state(desc, MoveShardEvent::Cancel).await
+    }
+
     pub async fn abort_shard_moving(&self, desc: &MoveShardDesc) -> Result<()> {
         self.update_move_shard_state(desc, MoveShardEvent::Abort).await
     }
@@ -127,6 +187,16 @@ impl Replica {
         }
     }
 
+    /// Like `check_moving_shard_request_early`, but the canceled moving shard
+    /// is not allowed to ingest data.
+    fn check_moving_shard_ingest_early(&self, shard_id: u64) -> Result<()> {
+        self.check_moving_shard_request_early(shard_id)?;
+        if self.lease_state.lock().unwrap().is_shard_moving_aborting(shard_id) {
+            return Err(Error::ShardNotFound(shard_id));
+        }
+        Ok(())
+    }
+
     fn check_move_shard_state_update_early(
         &self,
         desc: &MoveShardDesc,
@@ -145,10 +215,22 @@ impl Replica {
             Self::check_moving_shard_setup(self.info.as_ref(), &lease_state, desc)
         } else if matches!(event, MoveShardEvent::Commit) {
             Self::check_moving_shard_commit(self.info.as_ref(), &lease_state, desc)
+        } else if matches!(event, MoveShardEvent::Cancel) {
+            Self::check_moving_shard_cancel(self.info.as_ref(), &lease_state, desc)
+        } else if matches!(event, MoveShardEvent::Abort) && lease_state.move_shard_state.is_none() {
+            info!(
+                "the moving shard has been aborted, skip abort request. replica={}, group={}, desc={}",
+                self.info.replica_id, group_id, desc
+            );
+            Ok(false)
         } else if lease_state.move_shard_state.is_none() {
             Err(Error::InvalidArgument("no such moving shard exists".to_owned()))
         } else if !lease_state.is_same_shard_moving(desc) {
             Err(Error::InvalidArgument("exists another moving shard task".to_owned()))
+        } else if matches!(event, MoveShardEvent::Ingest)
+            && lease_state.is_shard_moving_aborting(desc.get_shard_id())
+        {
+            Err(Error::InvalidArgument("the moving shard has been canceled".to_owned()))
         } else {
             Ok(true)
         }
@@ -202,10 +284,42 @@ impl Replica {
                 "this moving shard has been committed, skip commit request. replica={}, group={}, desc={}",
                 info.replica_id, info.group_id, desc);
             Ok(false)
+        } else if lease_state.is_shard_moving_aborting(desc.get_shard_id()) {
+            Err(Error::InvalidArgument("the moving shard has been canceled".to_owned()))
         } else {
             Ok(true)
         }
     }
+
+    fn check_moving_shard_cancel(
+        info: &ReplicaInfo,
+        lease_state: &LeaseState,
+        desc: &MoveShardDesc,
+    ) -> Result<bool> {
+        if is_moving_shard_finished(info, desc, &lease_state.descriptor) {
+            return Err(Error::InvalidArgument("the moving shard has been committed".to_owned()));
+        }
+        let Some(state) = lease_state.move_shard_state.as_ref() else {
+            return Err(Error::InvalidArgument("no such moving shard task exists".to_owned()));
+        };
+        if !lease_state.is_same_shard_moving(desc) {
+            Err(Error::InvalidArgument("no such moving shard task exists".to_owned()))
+        } else if desc.dest_group_id != info.group_id {
+            Err(Error::InvalidArgument("the moving shard is canceled by dest group".to_owned()))
+        } else if state.step == MoveShardStep::Aborting as i32 {
+            info!(
+                "this moving shard has been canceled, skip cancel request. replica={}, group={}, desc={}",
+                info.replica_id, info.group_id, desc
+            );
+            Ok(false)
+        } else if state.step == MoveShardStep::Prepare as i32
+            || state.step == MoveShardStep::Moving as i32
+        {
+            Ok(true)
+        } else {
+            Err(Error::InvalidArgument("the moving shard has been committed".to_owned()))
+        }
+    }
 }
 
 fn is_moving_shard_finished(
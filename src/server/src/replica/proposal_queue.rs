@@ -0,0 +1,260 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The fair queuing of the write proposals of a group.
+//!
+//! The writes are classified by the table of the shard. They are admitted
+//! immediately until the in-flight proposals of the group reach
+//! `ProposalQueueConfig::max_inflight_proposals`, then they are queued and
+//! admitted by deficit round robin across the tables, so a flood of writes on
+//! one table can't starve the writes of the other tables sharing the group.
+//! The writes of a table are admitted in FIFO order, and the queue degrades to
+//! FIFO if only one table is waiting.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use futures::channel::oneshot;
+use sekas_runtime::time::Instant;
+
+use super::metrics::{REPLICA_TABLE_PROPOSAL_QUEUE_DELAY_SECONDS, REPLICA_TABLE_PROPOSAL_TOTAL};
+use crate::ProposalQueueConfig;
+
+pub(crate) struct ProposalQueue {
+    group_id: u64,
+    cfg: ProposalQueueConfig,
+    inner: Mutex<QueueInner>,
+}
+
+/// Releases the in-flight slot once the proposal is finished.
+pub(crate) struct ProposalPermit<'a> {
+    queue: &'a ProposalQueue,
+}
+
+#[derive(Default)]
+struct QueueInner {
+    inflights: usize,
+    /// The tables which have waiting proposals, in round robin order.
+    active_tables: VecDeque<u64>,
+    tables: HashMap<u64, TableQueue>,
+}
+
+#[derive(Default)]
+struct TableQueue {
+    /// The bytes could be admitted in the current round.
+    deficit: u64,
+    /// Whether the quantum of the current round is granted.
+    in_round: bool,
+    waiters: VecDeque<Waiter>,
+}
+
+struct Waiter {
+    cost: u64,
+    sender: oneshot::Sender<()>,
+}
+
+impl ProposalQueue {
+    pub(crate) fn new(group_id: u64, cfg: ProposalQueueConfig) -> Self {
+        ProposalQueue { group_id, cfg, inner: Mutex::default() }
+    }
+
+    /// Wait until the proposal of `cost` bytes of the table is admitted.
+    pub(crate) async fn admit(&self, table_id: u64, cost: u64) -> ProposalPermit<'_> {
+        let receiver = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.active_tables.is_empty() && !inner.is_full(&self.cfg) {
+                inner.inflights += 1;
+                None
+            } else {
+                let (sender, receiver) = oneshot::channel();
+                let queue = inner.tables.entry(table_id).or_default();
+                queue.waiters.push_back(Waiter { cost, sender });
+                if queue.waiters.len() == 1 {
+                    inner.active_tables.push_back(table_id);
+                }
+                Some(receiver)
+            }
+        };
+
+        let labels = [self.group_id.to_string(), table_id.to_string()];
+        if let Some(receiver) = receiver {
+            let start_at = Instant::now();
+            let mut waiting = Waiting { queue: self, receiver };
+            // The sender is only dropped along with the queue.
+            let _ = (&mut waiting.receiver).await;
+            REPLICA_TABLE_PROPOSAL_QUEUE_DELAY_SECONDS
+                .with_label_values(&[&labels[0], &labels[1]])
+                .observe(start_at.elapsed().as_secs_f64());
+        }
+        REPLICA_TABLE_PROPOSAL_TOTAL.with_label_values(&[&labels[0], &labels[1]]).inc();
+        ProposalPermit { queue: self }
+    }
+
+    /// The number of the admitted proposals which are not finished yet.
+    pub(crate) fn inflights(&self) -> usize {
+        self.inner.lock().unwrap().inflights
+    }
+
+    fn release(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.inflights -= 1;
+        inner.dispatch(&self.cfg);
+    }
+}
+
+impl QueueInner {
+    fn is_full(&self, cfg: &ProposalQueueConfig) -> bool {
+        cfg.max_inflight_proposals != 0 && self.inflights >= cfg.max_inflight_proposals
+    }
+
+    /// Admit the waiting proposals until the in-flight slots are used up.
+    fn dispatch(&mut self, cfg: &ProposalQueueConfig) {
+        while !self.is_full(cfg) {
+            let Some(table_id) = self.active_tables.front().cloned() else {
+                return;
+            };
+            let single_table = self.active_tables.len() == 1;
+            let queue = self.tables.get_mut(&table_id).expect("the active table must exist");
+            if !queue.in_round {
+                queue.in_round = true;
+                queue.deficit += cfg.quantum_bytes * cfg.table_weight(table_id);
+            }
+            let waiter = queue.waiters.front().expect("the active table must have waiters");
+            if waiter.cost > queue.deficit && !single_table {
+                // Finish the round of this table, the deficit is kept for the next round.
+                queue.in_round = false;
+                self.active_tables.rotate_left(1);
+                continue;
+            }
+
+            let waiter = queue.waiters.pop_front().unwrap();
+            queue.deficit = queue.deficit.saturating_sub(waiter.cost);
+            if queue.waiters.is_empty() {
+                self.tables.remove(&table_id);
+                self.active_tables.pop_front();
+            }
+            // The waiter is canceled if the receiver is dropped, the slot is not taken.
+            if waiter.sender.send(()).is_ok() {
+                self.inflights += 1;
+            }
+        }
+    }
+}
+
+/// Gives back the slot if the proposal is admitted but the waiting future is
+/// dropped before it observes that.
+struct Waiting<'a> {
+    queue: &'a ProposalQueue,
+    receiver: oneshot::Receiver<()>,
+}
+
+impl<'a> Drop for Waiting<'a> {
+    fn drop(&mut self) {
+        // The proposals are admitted with the lock held, so the proposal is either
+        // admitted already or never admitted once the receiver is closed.
+        let mut inner = self.queue.inner.lock().unwrap();
+        self.receiver.close();
+        if let Ok(Some(())) = self.receiver.try_recv() {
+            inner.inflights -= 1;
+            inner.dispatch(&self.queue.cfg);
+        }
+    }
+}
+
+impl<'a> Drop for ProposalPermit<'a> {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::TableWeight;
+
+    fn config(max_inflight_proposals: usize) -> ProposalQueueConfig {
+        ProposalQueueConfig { max_inflight_proposals, quantum_bytes: 100, table_weights: vec![] }
+    }
+
+    /// Queue the proposals once the in-flight slots are used up, returns the
+    /// tables in the admitted order.
+    async fn admitted_order(cfg: ProposalQueueConfig, proposals: &[(u64, u64)]) -> Vec<u64> {
+        let queue = Arc::new(ProposalQueue::new(1, cfg));
+        let permit = queue.admit(0, 1).await;
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for (table_id, cost) in proposals.iter().cloned() {
+            let (queue, order) = (queue.clone(), order.clone());
+            let mut fut = Box::pin(async move {
+                let _permit = queue.admit(table_id, cost).await;
+                order.lock().unwrap().push(table_id);
+            });
+            // Enqueue in the order of the proposals.
+            assert!((&mut fut).now_or_never().is_none());
+            handles.push(sekas_runtime::spawn(fut));
+        }
+        drop(permit);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let order = order.lock().unwrap().clone();
+        order
+    }
+
+    #[sekas_macro::test]
+    async fn admit_immediately_below_limit() {
+        let queue = ProposalQueue::new(1, config(2));
+        let _p1 = queue.admit(1, 1000).await;
+        let _p2 = queue.admit(2, 1000).await;
+        assert!(queue.admit(1, 1).now_or_never().is_none());
+
+        let queue = ProposalQueue::new(1, config(0));
+        let _permits = futures::future::join_all((0..100).map(|_| queue.admit(1, 1000))).await;
+    }
+
+    #[sekas_macro::test]
+    async fn round_robin_across_tables() {
+        // The bulk writes of table 1 are queued before the writes of table 2.
+        let mut proposals = vec![(1, 100); 6];
+        proposals.extend([(2, 10), (2, 10)]);
+        let order = admitted_order(config(1), &proposals).await;
+        assert_eq!(order, vec![1, 2, 2, 1, 1, 1, 1, 1]);
+    }
+
+    #[sekas_macro::test]
+    async fn large_proposal_accumulates_deficit() {
+        let proposals = [(1, 250), (2, 100), (2, 100), (2, 100), (2, 100)];
+        let order = admitted_order(config(1), &proposals).await;
+        assert_eq!(order, vec![2, 2, 1, 2, 2]);
+
+        // A single table is admitted in FIFO order regardless of the deficit.
+        let proposals = [(1, 1000), (1, 10), (1, 1000)];
+        let order = admitted_order(config(1), &proposals).await;
+        assert_eq!(order, vec![1, 1, 1]);
+    }
+
+    #[sekas_macro::test]
+    async fn weighted_tables() {
+        let mut cfg = config(1);
+        cfg.table_weights.push(TableWeight { table_id: 1, weight: 3 });
+        let mut proposals = vec![(1, 100); 6];
+        proposals.extend([(2, 100); 2]);
+        let order = admitted_order(cfg, &proposals).await;
+        assert_eq!(order, vec![1, 1, 1, 2, 1, 1, 1, 2]);
+    }
+}
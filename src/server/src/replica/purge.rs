@@ -0,0 +1,80 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Purge the data of the removed shards.
+//!
+//! Removing a shard only drops it from the group descriptor, and records a
+//! purge state in the same batch. Each replica purges the range of the shard
+//! chunk by chunk in the background, so a large shard never blocks the apply
+//! loop, and the purging is resumed from the persisted progress after
+//! restarting. The range is compacted in the blocking threads once it is
+//! purged, and then the purge state is deleted.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{info, warn};
+use sekas_runtime::JoinHandle;
+
+use super::Replica;
+
+/// The max num of raw keys visited by a purge chunk.
+const PURGE_CHUNK_KEYS: usize = 1024;
+
+/// The pause between purge chunks, to yield the disk to the foreground writes.
+const PURGE_CHUNK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The interval to check whether there are shards to purge.
+const PURGE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(crate) fn setup_shard_purger(replica: Arc<Replica>) -> JoinHandle<()> {
+    sekas_runtime::spawn(async move {
+        while !replica.info.is_terminated() {
+            if let Err(err) = replica.purge_removed_shards().await {
+                warn!(
+                    "group {} replica {} purge removed shards: {err}",
+                    replica.info.group_id, replica.info.replica_id
+                );
+            }
+            sekas_runtime::time::sleep(PURGE_CHECK_INTERVAL).await;
+        }
+    })
+}
+
+impl Replica {
+    async fn purge_removed_shards(&self) -> crate::Result<()> {
+        let states = self.group_engine.purge_shard_states()?;
+        for mut state in states {
+            while !state.finished {
+                if self.info.is_terminated() {
+                    return Ok(());
+                }
+                self.group_engine.purge_shard_chunk(&mut state, PURGE_CHUNK_KEYS)?;
+                sekas_runtime::time::sleep(PURGE_CHUNK_INTERVAL).await;
+            }
+            let engine = self.group_engine.clone();
+            let finished_state = state.clone();
+            sekas_runtime::spawn_blocking(move || engine.finish_purge_shard(&finished_state))
+                .await??;
+            info!(
+                "group {} replica {} purged shard {}, {} bytes",
+                self.info.group_id,
+                self.info.replica_id,
+                state.shard.as_ref().map(|s| s.id).unwrap_or_default(),
+                state.purged_bytes
+            );
+        }
+        Ok(())
+    }
+}
@@ -132,6 +132,9 @@ fn is_executable(descriptor: &GroupDesc, request: &Request) -> bool {
     if !super::is_change_meta_request(request) {
         return match request {
             Request::Get(req) => is_target_shard_exists(descriptor, req.shard_id, &req.user_key),
+            Request::GetRawKey(req) => {
+                is_target_shard_exists(descriptor, req.shard_id, &req.user_key)
+            }
             Request::Scan(req) => is_scan_retryable(descriptor, req),
             Request::Write(req) => {
                 for delete in &req.deletes {
@@ -162,13 +165,16 @@ fn is_executable(descriptor: &GroupDesc, request: &Request) -> bool {
                 is_target_shard_exists(descriptor, req.shard_id, &req.user_key)
             }
             Request::WatchKey(req) => is_target_shard_exists(descriptor, req.shard_id, &req.key),
+            // The shard might not cover the whole prefix after the descriptor is changed.
+            Request::CheckPrefixEmpty(_) | Request::DeletePrefix(_) => false,
             Request::AcceptShard(_)
             | Request::CreateShard(_)
             | Request::ChangeReplicas(_)
             | Request::Transfer(_)
             | Request::MoveReplicas(_)
             | Request::SplitShard(_)
-            | Request::MergeShard(_) => unreachable!(),
+            | Request::MergeShard(_)
+            | Request::RemoveShard(_) => unreachable!(),
         };
     }
 
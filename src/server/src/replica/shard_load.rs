@@ -0,0 +1,144 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The read and write rates of the shards served by a leader replica.
+//!
+//! Every request is counted, the rates are computed over the duration since
+//! the last collection, which is the interval of the heartbeats of root.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_runtime::time::Instant;
+
+/// The read and write requests per second of a shard.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct ShardLoad {
+    pub read_qps: f64,
+    pub write_qps: f64,
+}
+
+pub(crate) struct ShardLoadTracker {
+    inner: Mutex<TrackerInner>,
+}
+
+struct TrackerInner {
+    last_collect_at: Instant,
+    shards: HashMap<u64, RequestCount>,
+    /// The loads computed by the last collection.
+    loads: HashMap<u64, ShardLoad>,
+}
+
+#[derive(Default)]
+struct RequestCount {
+    reads: u64,
+    writes: u64,
+}
+
+impl ShardLoadTracker {
+    pub(crate) fn new() -> Self {
+        let inner = TrackerInner {
+            last_collect_at: Instant::now(),
+            shards: HashMap::default(),
+            loads: HashMap::default(),
+        };
+        ShardLoadTracker { inner: Mutex::new(inner) }
+    }
+
+    pub(crate) fn record_request(&self, request: &Request) {
+        let (shard_id, is_write) = match request {
+            Request::Get(req) => (req.shard_id, false),
+            Request::Scan(req) => (req.shard_id, false),
+            Request::Write(req) => (req.shard_id, true),
+            Request::WriteIntent(req) => (req.shard_id, true),
+            Request::DeletePrefix(req) => (req.shard_id, true),
+            _ => return,
+        };
+        let mut inner = self.inner.lock().unwrap();
+        let count = inner.shards.entry(shard_id).or_default();
+        if is_write {
+            count.writes += 1;
+        } else {
+            count.reads += 1;
+        }
+    }
+
+    /// Compute the loads of the shards since the last collection, the shards
+    /// not served by the replica are dropped.
+    pub(crate) fn collect(&self, shard_ids: &[u64]) {
+        self.collect_at(shard_ids, Instant::now());
+    }
+
+    fn collect_at(&self, shard_ids: &[u64], now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        // The rates of a short duration are not reliable.
+        let secs = now.saturating_duration_since(inner.last_collect_at).as_secs_f64().max(1.0);
+        let shards = std::mem::take(&mut inner.shards);
+        inner.loads = shards
+            .into_iter()
+            .filter(|(shard_id, _)| shard_ids.contains(shard_id))
+            .map(|(shard_id, count)| {
+                let load = ShardLoad {
+                    read_qps: count.reads as f64 / secs,
+                    write_qps: count.writes as f64 / secs,
+                };
+                (shard_id, load)
+            })
+            .collect();
+        inner.last_collect_at = now;
+    }
+
+    /// The load of the shard computed by the last collection.
+    pub(crate) fn shard_load(&self, shard_id: u64) -> ShardLoad {
+        self.inner.lock().unwrap().loads.get(&shard_id).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sekas_api::server::v1::{ShardGetRequest, ShardWriteRequest};
+
+    use super::*;
+
+    fn get(shard_id: u64) -> Request {
+        Request::Get(ShardGetRequest { shard_id, ..Default::default() })
+    }
+
+    fn write(shard_id: u64) -> Request {
+        Request::Write(ShardWriteRequest { shard_id, ..Default::default() })
+    }
+
+    #[test]
+    fn shard_load_since_last_collection() {
+        let tracker = ShardLoadTracker::new();
+        let start = tracker.inner.lock().unwrap().last_collect_at;
+        for _ in 0..40 {
+            tracker.record_request(&get(1));
+            tracker.record_request(&write(1));
+            tracker.record_request(&write(2));
+        }
+        tracker.record_request(&write(3));
+        tracker.collect_at(&[1, 2], start + Duration::from_secs(10));
+        assert_eq!(tracker.shard_load(1), ShardLoad { read_qps: 4.0, write_qps: 4.0 });
+        assert_eq!(tracker.shard_load(2), ShardLoad { read_qps: 0.0, write_qps: 4.0 });
+        assert_eq!(tracker.shard_load(3), ShardLoad::default());
+
+        // The counts are reset by the collection.
+        tracker.collect_at(&[1, 2], start + Duration::from_secs(20));
+        assert_eq!(tracker.shard_load(1), ShardLoad::default());
+    }
+}
@@ -25,14 +25,18 @@ use sekas_api::Epoch;
 use super::fsm::StateMachineObserver;
 use super::ReplicaInfo;
 use crate::node::job::StateChannel;
+use crate::node::observer::{ReplicaEvent, ReplicaObservers};
 use crate::raftgroup::StateObserver;
 use crate::schedule::ScheduleStateObserver;
-use crate::serverpb::v1::MoveShardState;
+use crate::serverpb::v1::{MoveShardState, MoveShardStep};
 
 pub struct LeaseState {
     pub leader_id: u64,
     /// the largest term which state machine already known.
     pub applied_term: u64,
+    /// The term in which the descriptor is verified against the catalog of
+    /// root, see `Replica::verify_descriptor`.
+    pub verified_term: u64,
     pub replica_state: ReplicaState,
     pub descriptor: GroupDesc,
     pub move_shard_state: Option<MoveShardState>,
@@ -48,6 +52,7 @@ pub struct LeaseStateObserver {
     info: Arc<ReplicaInfo>,
     lease_state: Arc<Mutex<LeaseState>>,
     state_channel: Arc<StateChannel>,
+    replica_observers: ReplicaObservers,
 }
 
 impl LeaseState {
@@ -62,6 +67,7 @@ impl LeaseState {
             move_shard_state_subscriber,
             leader_id: 0,
             applied_term: 0,
+            verified_term: 0,
             schedule_state: ScheduleState::default(),
             replica_state: ReplicaState::default(),
             leader_subscribers: HashMap::default(),
@@ -79,9 +85,43 @@ impl LeaseState {
         self.applied_term == self.replica_state.term
     }
 
+    /// Whether the descriptor has been verified in the current term?
+    #[inline]
+    pub fn is_descriptor_verified(&self) -> bool {
+        self.verified_term == self.replica_state.term
+    }
+
     #[inline]
     pub fn is_ready_for_serving(&self) -> bool {
-        self.is_raft_leader() && self.is_log_term_matched()
+        self.is_raft_leader() && self.is_log_term_matched() && self.is_descriptor_verified()
+    }
+
+    /// Mark the descriptor verified in `term`, the waiters are woken if the
+    /// replica becomes ready for serving.
+    pub fn mark_descriptor_verified(&mut self, term: u64) -> bool {
+        if self.replica_state.term != term || !self.is_raft_leader() {
+            return false;
+        }
+        self.verified_term = term;
+        if !self.is_ready_for_serving() {
+            return false;
+        }
+        self.wake_all_waiters();
+        if let Some(move_shard_state) = self.move_shard_state.as_ref() {
+            self.move_shard_state_subscriber
+                .unbounded_send(move_shard_state.to_owned())
+                .unwrap_or_default();
+        }
+        true
+    }
+
+    /// Verify the descriptor again, since a request carries an epoch newer
+    /// than the local one.
+    pub fn invalidate_descriptor_verified(&mut self) {
+        if self.is_descriptor_verified() {
+            self.verified_term = 0;
+            self.wake_all_waiters();
+        }
     }
 
     #[inline]
@@ -94,6 +134,14 @@ impl LeaseState {
         self.move_shard_state.as_ref().map(|s| s.get_shard_id() == shard_id).unwrap_or_default()
     }
 
+    /// Whether the moving of the shard is canceled and being aborted.
+    #[inline]
+    pub fn is_shard_moving_aborting(&self, shard_id: u64) -> bool {
+        self.move_shard_state.as_ref().is_some_and(|s| {
+            s.get_shard_id() == shard_id && s.step == MoveShardStep::Aborting as i32
+        })
+    }
+
     #[inline]
     pub fn is_same_shard_moving(&self, desc: &MoveShardDesc) -> bool {
         self.move_shard_state.as_ref().unwrap().get_move_shard_desc() == desc
@@ -123,8 +171,9 @@ impl LeaseStateObserver {
         info: Arc<ReplicaInfo>,
         lease_state: Arc<Mutex<LeaseState>>,
         state_channel: Arc<StateChannel>,
+        replica_observers: ReplicaObservers,
     ) -> Self {
-        LeaseStateObserver { info, lease_state, state_channel }
+        LeaseStateObserver { info, lease_state, state_channel, replica_observers }
     }
 
     fn update_replica_state(
@@ -134,6 +183,7 @@ impl LeaseStateObserver {
         term: u64,
         role: RaftRole,
     ) -> (ReplicaState, Option<GroupDesc>) {
+        let mut lease_state = self.lease_state.lock().unwrap();
         let replica_state = ReplicaState {
             replica_id: self.info.replica_id,
             group_id: self.info.group_id,
@@ -141,8 +191,8 @@ impl LeaseStateObserver {
             voted_for,
             role: role.into(),
             node_id: self.info.node_id,
+            quarantine: lease_state.replica_state.quarantine.clone(),
         };
-        let mut lease_state = self.lease_state.lock().unwrap();
         let prev_role = lease_state.replica_state.role;
         let epoch = lease_state.descriptor.epoch;
         lease_state.leader_id = leader_id;
@@ -155,6 +205,10 @@ impl LeaseStateObserver {
                 self.info.group_id,
                 Epoch(epoch)
             );
+            if prev_role != RaftRole::Leader as i32 {
+                let group_id = self.info.group_id;
+                self.replica_observers.emit(ReplicaEvent::LeaderAcquired { group_id, term });
+            }
             Some(lease_state.descriptor.clone())
         } else {
             if prev_role == RaftRole::Leader as i32 {
@@ -165,6 +219,8 @@ impl LeaseStateObserver {
                     self.info.group_id,
                     Epoch(epoch)
                 );
+                self.replica_observers
+                    .emit(ReplicaEvent::LeaderLost { group_id: self.info.group_id });
             }
             None
         };
@@ -198,18 +254,10 @@ impl StateMachineObserver for LeaseStateObserver {
     fn on_term_updated(&mut self, term: u64) {
         let mut lease_state = self.lease_state.lock().unwrap();
         lease_state.applied_term = term;
-        if lease_state.is_ready_for_serving() {
-            info!(
-                "replica {} node {} is ready for serving requests of group {} at term {term}",
-                self.info.replica_id, self.info.node_id, self.info.group_id
-            );
+        if lease_state.is_raft_leader() && lease_state.is_log_term_matched() {
+            // Wake the verifier, the replica is ready for serving once the descriptor is
+            // verified.
             lease_state.wake_all_waiters();
-            if let Some(move_shard_state) = lease_state.move_shard_state.as_ref() {
-                lease_state
-                    .move_shard_state_subscriber
-                    .unbounded_send(move_shard_state.to_owned())
-                    .unwrap_or_default();
-            }
         }
     }
 
@@ -225,6 +273,23 @@ impl StateMachineObserver for LeaseStateObserver {
             }
         }
     }
+
+    fn on_move_shard_progress_updated(&mut self, move_shard_state: Option<MoveShardState>) {
+        // The move shard controller only cares about the step changes.
+        let mut lease_state = self.lease_state.lock().unwrap();
+        lease_state.move_shard_state = move_shard_state;
+    }
+
+    fn on_quarantine_updated(&mut self, quarantine: Option<ApplyQuarantine>) {
+        let mut lease_state = self.lease_state.lock().unwrap();
+        lease_state.replica_state.quarantine = quarantine;
+        // The replica state is reported once the raft state is observed, the
+        // quarantine loaded before that is reported together.
+        if lease_state.replica_state.replica_id == self.info.replica_id {
+            let state = lease_state.replica_state.clone();
+            self.state_channel.broadcast_replica_state(self.info.group_id, state);
+        }
+    }
 }
 
 impl ScheduleStateObserver for LeaseStateObserver {
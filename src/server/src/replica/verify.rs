@@ -0,0 +1,147 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verify the descriptor of a new leader before serving.
+//!
+//! A replica restored from an old disk image might be elected with a
+//! descriptor several epochs behind the catalog of root. So a new leader
+//! reports its descriptor to root first, and lifts its epoch above the catalog
+//! if it is behind, the requests carrying the stale epoch are rejected with
+//! `EpochNotMatch` since then. The leader falls back to a quorum read if root
+//! isn't reachable in time.
+
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use log::{info, warn};
+use sekas_api::server::v1::GroupDesc;
+use sekas_api::Epoch;
+use sekas_runtime::JoinHandle;
+
+use super::Replica;
+use crate::constants::ROOT_GROUP_ID;
+use crate::node::job::StateChannel;
+use crate::raftgroup::ReadPolicy;
+use crate::serverpb::v1::{EvalResult, SyncOp};
+use crate::Result;
+
+/// The interval to retry once the verifying is failed.
+const VERIFY_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+pub(crate) fn setup_descriptor_verifier(
+    replica: Arc<Replica>,
+    channel: Arc<StateChannel>,
+    timeout: Duration,
+) -> JoinHandle<()> {
+    sekas_runtime::spawn(async move {
+        while let Some(term) = replica.on_unverified_leader().await {
+            if let Err(err) = replica.verify_descriptor(term, &channel, timeout).await {
+                warn!(
+                    "group {} replica {} verify descriptor at term {term}: {err}",
+                    replica.info.group_id, replica.info.replica_id
+                );
+                sekas_runtime::time::sleep(VERIFY_RETRY_INTERVAL).await;
+            }
+        }
+    })
+}
+
+impl Replica {
+    /// Wait until the replica is the leader and all logs of the previous
+    /// terms are applied, but the descriptor isn't verified in this term.
+    /// Returns `None` if the replica is terminated.
+    async fn on_unverified_leader(&self) -> Option<u64> {
+        use futures::future::poll_fn;
+
+        poll_fn(|ctx| {
+            let mut lease_state = self.lease_state.lock().unwrap();
+            if self.info.is_terminated() {
+                Poll::Ready(None)
+            } else if lease_state.is_raft_leader()
+                && lease_state.is_log_term_matched()
+                && !lease_state.is_descriptor_verified()
+            {
+                Poll::Ready(Some(lease_state.replica_state.term))
+            } else {
+                lease_state.leader_subscribers.insert("verifier", ctx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    async fn verify_descriptor(
+        &self,
+        term: u64,
+        channel: &StateChannel,
+        timeout: Duration,
+    ) -> Result<()> {
+        let group_id = self.info.group_id;
+        let replica_id = self.info.replica_id;
+        // The catalog itself is stored in the root group.
+        if group_id != ROOT_GROUP_ID {
+            loop {
+                let desc = self.descriptor();
+                let epoch = desc.epoch;
+                let verify = channel.verify_group_descriptor(group_id, desc);
+                match sekas_runtime::time::timeout(timeout, verify).await {
+                    Ok(Ok(None)) => break,
+                    Ok(Ok(Some(catalog_desc))) => {
+                        warn!(
+                            "group {group_id} replica {replica_id} epoch {} is behind the catalog epoch {}, sync it before serving",
+                            Epoch(epoch),
+                            Epoch(catalog_desc.epoch)
+                        );
+                        self.sync_epoch(catalog_desc).await?;
+                    }
+                    Ok(Err(err)) => {
+                        warn!(
+                            "group {group_id} replica {replica_id} verify epoch {} with root: {err}, fallback to quorum read",
+                            Epoch(epoch)
+                        );
+                        self.raft_group.read(ReadPolicy::ReadIndex).await?;
+                        break;
+                    }
+                    Err(_) => {
+                        warn!(
+                            "group {group_id} replica {replica_id} verify epoch {} with root timeout, fallback to quorum read",
+                            Epoch(epoch)
+                        );
+                        self.raft_group.read(ReadPolicy::ReadIndex).await?;
+                        break;
+                    }
+                }
+            }
+        }
+
+        if self.lease_state.lock().unwrap().mark_descriptor_verified(term) {
+            info!(
+                "replica {replica_id} node {} is ready for serving requests of group {group_id} at term {term}",
+                self.info.node_id
+            );
+        }
+        Ok(())
+    }
+
+    /// Lift the epoch of descriptor above the catalog, and take the shards of
+    /// the catalog.
+    async fn sync_epoch(&self, catalog_desc: GroupDesc) -> Result<()> {
+        let _guard = self.take_write_acl_guard().await;
+        let eval_result =
+            EvalResult { op: Some(SyncOp::sync_epoch(catalog_desc)), ..Default::default() };
+        self.raft_group.propose(eval_result).await?;
+        Ok(())
+    }
+}
@@ -12,11 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use sekas_api::server::v1::{GroupDesc, NodeDesc};
 
 use self::policy_leader_cnt::LeaderCountPolicy;
+use self::policy_read_replica::ReadReplicaPolicy;
 use self::policy_replica_cnt::ReplicaCountPolicy;
 use self::policy_shard_cnt::ShardCountPolicy;
 use self::source::NodeFilter;
@@ -27,6 +29,7 @@ use crate::{Result, RootConfig};
 mod sim_test;
 
 mod policy_leader_cnt;
+mod policy_read_replica;
 mod policy_replica_cnt;
 mod policy_shard_cnt;
 mod source;
@@ -57,6 +60,12 @@ pub enum ShardAction {
     Migrate(ReallocateShard),
 }
 
+#[derive(Clone, Debug)]
+pub enum ReadReplicaAction {
+    Add { group: u64, target_node: u64 },
+    Remove { group: u64, replica: u64, node: u64 },
+}
+
 #[derive(Clone, Debug)]
 pub enum LeaderAction {
     Noop,
@@ -159,9 +168,7 @@ impl<T: AllocSource> Allocator<T> {
         // TODO: try qps rebalance.
 
         // try replica-count rebalance.
-        let actions =
-            ReplicaCountPolicy::with(self.alloc_source.to_owned(), self.cluster_stats.to_owned())
-                .compute_balance()?;
+        let actions = self.replica_count_policy().compute_balance()?;
         if !actions.is_empty() {
             return Ok(actions);
         }
@@ -177,8 +184,14 @@ impl<T: AllocSource> Allocator<T> {
         // always follow comput_replica_role_action() so no need refresh
         // self.alloc_source.refresh_all().await?;
 
+        let policy = ShardCountPolicy::with(self.alloc_source.to_owned());
+        if let Some(action) = policy.compute_root_evacuation() {
+            metrics::RECONCILE_ALREADY_BALANCED_INFO.group_shard_count.set(0);
+            return Ok(vec![action]);
+        }
+
         if self.alloc_source.nodes(NodeFilter::All).len() >= self.config.replicas_per_group {
-            let actions = ShardCountPolicy::with(self.alloc_source.to_owned()).compute_balance()?;
+            let actions = policy.compute_balance()?;
             if !actions.is_empty() {
                 metrics::RECONCILE_ALREADY_BALANCED_INFO.group_shard_count.set(0);
                 return Ok(actions);
@@ -188,6 +201,18 @@ impl<T: AllocSource> Allocator<T> {
         Ok(Vec::new())
     }
 
+    /// Compute read replica change actions, the read replicas are required by
+    /// table, so the number of read replicas of each table is given.
+    pub async fn compute_read_replica_action(
+        &self,
+        table_read_replicas: &HashMap<u64, usize>,
+    ) -> Result<Vec<ReadReplicaAction>> {
+        self.alloc_source.refresh_all().await?;
+
+        Ok(ReadReplicaPolicy::with(self.alloc_source.to_owned())
+            .compute_actions(table_read_replicas))
+    }
+
     /// Allocate new replica in one group.
     pub async fn allocate_group_replica(
         &self,
@@ -196,23 +221,48 @@ impl<T: AllocSource> Allocator<T> {
     ) -> Result<Vec<NodeDesc>> {
         self.alloc_source.refresh_all().await?;
 
-        ReplicaCountPolicy::with(self.alloc_source.to_owned(), self.cluster_stats.to_owned())
-            .allocate_group_replica(existing_replica_nodes, wanted_count)
+        self.replica_count_policy().allocate_group_replica(existing_replica_nodes, wanted_count)
+    }
+
+    /// Whether there are enough nodes to allocate the replicas of a new group.
+    pub async fn can_allocate_group(&self) -> Result<bool> {
+        self.alloc_source.refresh_all().await?;
+
+        let nodes = self.alloc_source.nodes(NodeFilter::NotDecommissioned);
+        Ok(nodes.len() >= self.config.replicas_per_group)
+    }
+
+    /// The number of the nodes and groups known by the allocator, as of the
+    /// latest refresh.
+    pub fn num_nodes_and_groups(&self) -> (usize, usize) {
+        let nodes = self.alloc_source.nodes(NodeFilter::NotDecommissioned).len();
+        (nodes, self.alloc_source.groups().len())
     }
 
-    /// Find a group to place shard.
+    /// Find a group to place shard, the root group is never chosen.
     pub async fn place_group_for_shard(&self, n: usize) -> Result<Vec<GroupDesc>> {
         self.alloc_source.refresh_all().await?;
 
         ShardCountPolicy::with(self.alloc_source.to_owned()).allocate_shard(n)
     }
 
-    pub async fn compute_leader_action(&self) -> Result<Vec<LeaderAction>> {
+    /// Compute leader transfer actions, the leaders of the groups are moved to
+    /// their preferred zones first, and kept there while balancing the leader
+    /// counts.
+    pub async fn compute_leader_action(
+        &self,
+        leader_zones: &HashMap<u64, String>,
+    ) -> Result<Vec<LeaderAction>> {
         if !self.config.enable_leader_balance {
             return Ok(vec![]);
         }
         // self.alloc_source.refresh_all().await?;
-        match LeaderCountPolicy::with(self.alloc_source.to_owned()).compute_balance()? {
+        let policy = LeaderCountPolicy::with(
+            self.alloc_source.to_owned(),
+            self.config.overloaded_cpu_util,
+            leader_zones.clone(),
+        );
+        match policy.compute_balance()? {
             LeaderAction::Noop => {}
             e @ LeaderAction::Shed { .. } => return Ok(vec![e]),
         }
@@ -221,6 +271,14 @@ impl<T: AllocSource> Allocator<T> {
 }
 
 impl<T: AllocSource> Allocator<T> {
+    fn replica_count_policy(&self) -> ReplicaCountPolicy<T> {
+        ReplicaCountPolicy::with(
+            self.alloc_source.to_owned(),
+            self.cluster_stats.to_owned(),
+            self.config.overloaded_cpu_util,
+        )
+    }
+
     fn preferred_remove_groups(&self, want_remove: usize) -> Vec<u64> {
         // TODO:
         // 1 remove groups from unreachable nodes that indicated by NodeLiveness(they
@@ -253,6 +311,13 @@ impl<T: AllocSource> Allocator<T> {
     }
 }
 
+/// Whether the cpu utilization reported by the node is above the threshold, the
+/// new leaders and replicas are not placed on it. It is a soft signal, the node
+/// which hasn't reported its health is not overloaded.
+fn is_cpu_overloaded<T: AllocSource>(alloc_source: &T, node_id: u64, threshold: f64) -> bool {
+    alloc_source.node_health(&node_id).map(|h| h.cpu_util > threshold).unwrap_or_default()
+}
+
 // Allocate Group's replica between nodes.
 impl<T: AllocSource> Allocator<T> {}
 
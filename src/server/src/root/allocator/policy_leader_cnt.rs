@@ -21,12 +21,15 @@ use log::debug;
 use sekas_api::server::v1::{NodeDesc, RaftRole, ReplicaDesc, ReplicaRole};
 
 use super::source::NodeFilter;
-use super::{AllocSource, BalanceStatus, LeaderAction, TransferLeader};
+use super::{is_cpu_overloaded, AllocSource, BalanceStatus, LeaderAction, TransferLeader};
 use crate::constants::ROOT_GROUP_ID;
 use crate::Result;
 
 pub struct LeaderCountPolicy<T: AllocSource> {
     alloc_source: Arc<T>,
+    overloaded_cpu_util: f64,
+    /// The zone preferred to place the leader of each group.
+    leader_zones: HashMap<u64, String>,
 }
 
 enum TransferDescision {
@@ -41,11 +44,19 @@ enum TransferDescision {
 }
 
 impl<T: AllocSource> LeaderCountPolicy<T> {
-    pub fn with(alloc_source: Arc<T>) -> Self {
-        Self { alloc_source }
+    pub fn with(
+        alloc_source: Arc<T>,
+        overloaded_cpu_util: f64,
+        leader_zones: HashMap<u64, String>,
+    ) -> Self {
+        Self { alloc_source, overloaded_cpu_util, leader_zones }
     }
 
     pub fn compute_balance(&self) -> Result<LeaderAction> {
+        if let Some(action) = self.compute_preferred_leader() {
+            return Ok(LeaderAction::Shed(action));
+        }
+
         let mean = self.mean_leader_count(NodeFilter::Schedulable);
         let candidate_nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
         let ranked_nodes = Self::rank_nodes_for_leader(candidate_nodes, mean);
@@ -132,6 +143,14 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
                 if Self::leader_balance_state(sim_count, mean) == BalanceStatus::Overfull {
                     continue;
                 }
+                if is_cpu_overloaded(&*self.alloc_source, target_node.id, self.overloaded_cpu_util)
+                {
+                    debug!("skip transferring leader to the overloaded node {}", target_node.id);
+                    continue;
+                }
+                if self.leaves_preferred_zone(*group_id, n, target_node) {
+                    continue;
+                }
                 let target_replica = exist_replica_in_nodes.get(&target_node.id);
                 if target_replica.is_none() {
                     continue;
@@ -149,6 +168,62 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
         Ok(None)
     }
 
+    /// Move the leader of a group to the preferred zone, if the leader is out
+    /// of the zone and a voter in the zone is able to take over.
+    fn compute_preferred_leader(&self) -> Option<TransferLeader> {
+        if self.leader_zones.is_empty() {
+            return None;
+        }
+        let nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
+        let nodes = nodes.into_iter().map(|n| (n.id, n)).collect::<HashMap<_, _>>();
+        let groups = self.alloc_source.groups();
+        let mut leader_zones = self.leader_zones.iter().collect::<Vec<_>>();
+        leader_zones.sort_unstable();
+        for (group_id, zone) in leader_zones {
+            let Some(group) = groups.get(group_id) else { continue };
+            let leader = group.replicas.iter().find(|r| {
+                self.alloc_source
+                    .replica_state(&r.id)
+                    .map(|s| s.role == RaftRole::Leader as i32)
+                    .unwrap_or_default()
+            });
+            let Some(leader) = leader else { continue };
+            if nodes.get(&leader.node_id).map(|n| n.labels.contains(zone)).unwrap_or_default() {
+                continue;
+            }
+            let target = group
+                .replicas
+                .iter()
+                .filter(|r| r.id != leader.id && r.role == ReplicaRole::Voter as i32)
+                .filter_map(|r| nodes.get(&r.node_id).map(|n| (r, n)))
+                .filter(|(_, n)| n.labels.contains(zone))
+                .filter(|(_, n)| {
+                    !is_cpu_overloaded(&*self.alloc_source, n.id, self.overloaded_cpu_util)
+                })
+                .min_by_key(|(_, n)| n.capacity.as_ref().unwrap().leader_count);
+            let Some((target_replica, _)) = target else {
+                debug!("no voter of group {group_id} in the preferred zone {zone}");
+                continue;
+            };
+            return Some(TransferLeader {
+                group: *group_id,
+                src_node: leader.node_id,
+                src_replica: leader.id,
+                target_node: target_replica.node_id,
+                target_replica: target_replica.id,
+            });
+        }
+        None
+    }
+
+    /// Whether transferring the leader moves it out of the preferred zone of
+    /// the group. Balancing the leader counts never does it, only the health
+    /// of the nodes does, eg. draining a node.
+    fn leaves_preferred_zone(&self, group_id: u64, src: &NodeDesc, target: &NodeDesc) -> bool {
+        let Some(zone) = self.leader_zones.get(&group_id) else { return false };
+        src.labels.contains(zone) && !target.labels.contains(zone)
+    }
+
     fn rank_nodes_for_leader(ns: Vec<NodeDesc>, mean_cnt: f64) -> Vec<(NodeDesc, BalanceStatus)> {
         let mut with_status = ns
             .into_iter()
@@ -0,0 +1,112 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use sekas_api::server::v1::{GroupDesc, NodeDesc, ReplicaRole};
+use sekas_schema::property::NODE_LABEL_ANALYTICS;
+
+use super::source::NodeFilter;
+use super::{AllocSource, ReadReplicaAction};
+
+pub struct ReadReplicaPolicy<T: AllocSource> {
+    alloc_source: Arc<T>,
+}
+
+impl<T: AllocSource> ReadReplicaPolicy<T> {
+    pub fn with(alloc_source: Arc<T>) -> Self {
+        Self { alloc_source }
+    }
+
+    /// Compute the read replicas to add or remove, so that each group has the
+    /// max read replicas required by the tables it serves.
+    pub fn compute_actions(
+        &self,
+        table_read_replicas: &HashMap<u64, usize>,
+    ) -> Vec<ReadReplicaAction> {
+        let candidate_nodes = self
+            .alloc_source
+            .nodes(NodeFilter::Schedulable)
+            .into_iter()
+            .filter(is_analytics_node)
+            .collect::<Vec<_>>();
+        let mut node_replicas = candidate_nodes
+            .iter()
+            .map(|n| (n.id, n.capacity.as_ref().map(|c| c.replica_count).unwrap_or_default()))
+            .collect::<HashMap<_, _>>();
+
+        let mut groups = self.alloc_source.groups().into_values().collect::<Vec<_>>();
+        groups.sort_unstable_by_key(|g| g.id);
+        let mut group_nodes = groups
+            .iter()
+            .map(|g| (g.id, g.replicas.iter().map(|r| r.node_id).collect::<HashSet<_>>()))
+            .collect::<HashMap<_, _>>();
+        // The replicas being created are not in the descriptors yet.
+        for replica_state in self.alloc_source.replica_states() {
+            if let Some(nodes) = group_nodes.get_mut(&replica_state.group_id) {
+                nodes.insert(replica_state.node_id);
+            }
+        }
+
+        let mut actions = vec![];
+        for group in &groups {
+            let wanted = desired_read_replicas(group, table_read_replicas);
+            let read_replicas = group
+                .replicas
+                .iter()
+                .filter(|r| r.role == ReplicaRole::ReadReplica as i32)
+                .collect::<Vec<_>>();
+            if read_replicas.len() > wanted {
+                for replica in read_replicas.into_iter().skip(wanted) {
+                    actions.push(ReadReplicaAction::Remove {
+                        group: group.id,
+                        replica: replica.id,
+                        node: replica.node_id,
+                    });
+                }
+                continue;
+            }
+
+            let exist_nodes = group_nodes.entry(group.id).or_default();
+            for _ in read_replicas.len()..wanted {
+                let Some(target) = candidate_nodes
+                    .iter()
+                    .filter(|n| !exist_nodes.contains(&n.id))
+                    .min_by_key(|n| (node_replicas[&n.id], n.id))
+                else {
+                    break;
+                };
+                exist_nodes.insert(target.id);
+                *node_replicas.get_mut(&target.id).unwrap() += 1;
+                actions.push(ReadReplicaAction::Add { group: group.id, target_node: target.id });
+            }
+        }
+        actions
+    }
+}
+
+pub fn is_analytics_node(node: &NodeDesc) -> bool {
+    node.labels.iter().any(|label| label == NODE_LABEL_ANALYTICS)
+}
+
+fn desired_read_replicas(group: &GroupDesc, table_read_replicas: &HashMap<u64, usize>) -> usize {
+    group
+        .shards
+        .iter()
+        .filter_map(|shard| table_read_replicas.get(&shard.table_id))
+        .max()
+        .cloned()
+        .unwrap_or_default()
+}
@@ -16,10 +16,11 @@ use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use sekas_api::server::v1::{NodeDesc, ReplicaDesc};
+use sekas_api::server::v1::{NodeDesc, ReplicaDesc, ReplicaRole};
 
+use super::policy_read_replica::is_analytics_node;
 use super::source::NodeFilter;
-use super::{AllocSource, ReallocateReplica, ReplicaAction};
+use super::{is_cpu_overloaded, AllocSource, ReallocateReplica, ReplicaAction};
 use crate::constants::{REPLICA_PER_GROUP, ROOT_GROUP_ID};
 use crate::root::allocator::BalanceStatus;
 use crate::root::ClusterStats;
@@ -28,11 +29,16 @@ use crate::Result;
 pub struct ReplicaCountPolicy<T: AllocSource> {
     alloc_source: Arc<T>,
     cluster_stats: Arc<ClusterStats>,
+    overloaded_cpu_util: f64,
 }
 
 impl<T: AllocSource> ReplicaCountPolicy<T> {
-    pub fn with(alloc_source: Arc<T>, cluster_stats: Arc<ClusterStats>) -> Self {
-        Self { alloc_source, cluster_stats }
+    pub fn with(
+        alloc_source: Arc<T>,
+        cluster_stats: Arc<ClusterStats>,
+        overloaded_cpu_util: f64,
+    ) -> Self {
+        Self { alloc_source, cluster_stats, overloaded_cpu_util }
     }
 
     pub fn allocate_group_replica(
@@ -45,9 +51,16 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         // skip the nodes already have group replicas.
         candidate_nodes.retain(|n| !existing_replica_nodes.iter().any(|rn| *rn == n.id));
 
-        // sort by alloc score
+        // sort by alloc score, the analytics nodes are reserved for read replicas, so
+        // they are chosen at last. The overloaded nodes are chosen only if there are
+        // not enough other nodes.
         candidate_nodes.sort_by(|n1, n2| {
-            self.node_alloc_score(n2).partial_cmp(&self.node_alloc_score(n1)).unwrap()
+            is_analytics_node(n1)
+                .cmp(&is_analytics_node(n2))
+                .then_with(|| self.is_overloaded(n1).cmp(&self.is_overloaded(n2)))
+                .then_with(|| {
+                    self.node_alloc_score(n2).partial_cmp(&self.node_alloc_score(n1)).unwrap()
+                })
         });
 
         Ok(candidate_nodes.into_iter().take(wanted_count).collect())
@@ -105,6 +118,9 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
             if Self::node_balance_state(sim_count, mean) == BalanceStatus::Overfull {
                 continue;
             }
+            if self.is_overloaded(target) {
+                continue;
+            }
             let (source_replica, group) = self.preferred_remove_replica(src, target, &groups)?;
             return Some(ReplicaAction::Migrate(ReallocateReplica {
                 group,
@@ -123,8 +139,8 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         group_nodes: &HashMap<u64, HashSet<u64>>,
     ) -> Option<(ReplicaDesc, u64)> {
         // TODO: sort & rank replica
-        self.alloc_source.node_replicas(&src.id).into_iter().find(|(_, g)| {
-            if *g == ROOT_GROUP_ID {
+        self.alloc_source.node_replicas(&src.id).into_iter().find(|(r, g)| {
+            if *g == ROOT_GROUP_ID || r.role == ReplicaRole::ReadReplica as i32 {
                 return false;
             }
             if let Some(exist_nodes) = group_nodes.get(g) {
@@ -185,6 +201,10 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         BalanceStatus::Balanced
     }
 
+    fn is_overloaded(&self, n: &NodeDesc) -> bool {
+        is_cpu_overloaded(&*self.alloc_source, n.id, self.overloaded_cpu_util)
+    }
+
     fn node_alloc_score(&self, n: &NodeDesc) -> f64 {
         // TODO: add more rule to calculate score.
         -(self.node_replica_count(n) as f64)
@@ -18,6 +18,7 @@ use std::sync::Arc;
 
 use log::debug;
 use sekas_api::server::v1::{GroupDesc, ShardDesc};
+use sekas_schema::FIRST_USER_TABLE_ID;
 
 use super::{AllocSource, ReallocateShard, ShardAction};
 use crate::constants::ROOT_GROUP_ID;
@@ -66,6 +67,20 @@ impl<T: AllocSource> ShardCountPolicy<T> {
         Ok(vec![])
     }
 
+    /// Migrate the user shards out of the root group one by one, so that the
+    /// user traffic doesn't share the raft group with the catalog.
+    pub fn compute_root_evacuation(&self) -> Option<ShardAction> {
+        let groups = self.alloc_source.groups();
+        let root_group = groups.get(&ROOT_GROUP_ID)?;
+        let shard = root_group.shards.iter().find(|s| s.table_id >= FIRST_USER_TABLE_ID)?;
+        let target = self.current_user_groups().into_iter().min_by_key(|g| g.shards.len())?;
+        Some(ShardAction::Migrate(ReallocateShard {
+            shard: shard.id,
+            source_group: ROOT_GROUP_ID,
+            target_group: target.id,
+        }))
+    }
+
     fn mean_shard_count(&self) -> f64 {
         let groups = self.current_user_groups();
         let total_shards = groups.iter().map(|n| n.shards.len() as u64).sum::<u64>() as f64;
@@ -137,9 +152,8 @@ impl<T: AllocSource> ShardCountPolicy<T> {
         src_group: &GroupDesc,
         _target_group: &GroupDesc,
     ) -> Option<ShardDesc> {
-        let replicas = src_group.shards.to_owned();
         // TODO: ranking shards and choose the preferred one
-        replicas.first().map(ToOwned::to_owned)
+        src_group.shards.iter().find(|s| !sekas_schema::shard::is_txn_shard(s.id)).cloned()
     }
 
     fn current_user_groups(&self) -> Vec<GroupDesc> {
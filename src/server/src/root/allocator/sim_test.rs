@@ -22,7 +22,7 @@ use sekas_api::server::v1::*;
 use sekas_runtime::ExecutorOwner;
 
 use super::*;
-use crate::constants::REPLICA_PER_GROUP;
+use crate::constants::{FIRST_GROUP_ID, REPLICA_PER_GROUP, ROOT_GROUP_ID};
 
 #[test]
 fn sim_boostrap_join_node_balance() {
@@ -45,6 +45,7 @@ fn sim_boostrap_join_node_balance() {
             addr: "".into(),
             capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 1, leader_count: 1 }),
             status: NodeStatus::Active as i32,
+            labels: vec![],
         }]);
         p.set_replica_states(vec![ReplicaState {
             replica_id: 1,
@@ -53,6 +54,7 @@ fn sim_boostrap_join_node_balance() {
             voted_for: 0,
             role: RaftRole::Leader.into(),
             node_id: 1,
+            quarantine: None,
         }]);
 
         let act = a.compute_group_action().await.unwrap();
@@ -67,12 +69,14 @@ fn sim_boostrap_join_node_balance() {
                 addr: "".into(),
                 capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 0, leader_count: 0 }),
                 status: NodeStatus::Active as i32,
+                labels: vec![],
             },
             NodeDesc {
                 id: 3,
                 addr: "".into(),
                 capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 0, leader_count: 0 }),
                 status: NodeStatus::Active as i32,
+                labels: vec![],
             },
         ]);
         p.set_nodes(nodes);
@@ -97,6 +101,7 @@ fn sim_boostrap_join_node_balance() {
                 voted_for: 0,
                 role: RaftRole::Leader.into(),
                 node_id: 1,
+                quarantine: None,
             },
             ReplicaState {
                 replica_id: 2,
@@ -105,6 +110,7 @@ fn sim_boostrap_join_node_balance() {
                 voted_for: 0,
                 role: RaftRole::Follower.into(),
                 node_id: 2,
+                quarantine: None,
             },
             ReplicaState {
                 replica_id: 3,
@@ -113,6 +119,7 @@ fn sim_boostrap_join_node_balance() {
                 voted_for: 0,
                 role: RaftRole::Follower.into(),
                 node_id: 3,
+                quarantine: None,
             },
         ]);
         p.display();
@@ -153,6 +160,7 @@ fn sim_boostrap_join_node_balance() {
                             voted_for: 0,
                             role,
                             node_id: n.id,
+                            quarantine: None,
                         });
                         replica_id_gen += 1;
                     }
@@ -189,6 +197,7 @@ fn sim_boostrap_join_node_balance() {
             addr: "".into(),
             capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 0, leader_count: 0 }),
             status: NodeStatus::Active as i32,
+            labels: vec![],
         }]);
         p.set_nodes(nodes);
         p.display();
@@ -227,6 +236,7 @@ fn sim_boostrap_join_node_balance() {
                             voted_for: 0,
                             role,
                             node_id: n.id,
+                            quarantine: None,
                         });
                         replica_id_gen += 1;
                     }
@@ -309,7 +319,7 @@ fn sim_boostrap_join_node_balance() {
 
         println!("10. try balance leader between nodes");
         loop {
-            let lact = a.compute_leader_action().await.unwrap();
+            let lact = a.compute_leader_action(&HashMap::default()).await.unwrap();
             if lact.is_empty() {
                 break;
             }
@@ -332,10 +342,246 @@ fn sim_boostrap_join_node_balance() {
     });
 }
 
+#[test]
+fn sim_evacuate_user_shards_from_root_group() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(ClusterStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        p.set_nodes(vec![NodeDesc {
+            id: 1,
+            addr: "".into(),
+            capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 2, leader_count: 2 }),
+            status: NodeStatus::Active as i32,
+            labels: vec![],
+        }]);
+        let system_shard = ShardDesc { id: 1, table_id: 1, ..Default::default() };
+        let user_shard = ShardDesc {
+            id: sekas_schema::FIRST_USER_SHARD_ID,
+            table_id: sekas_schema::FIRST_USER_TABLE_ID,
+            ..Default::default()
+        };
+        p.set_groups(vec![
+            GroupDesc {
+                id: ROOT_GROUP_ID,
+                epoch: 0,
+                shards: vec![system_shard, user_shard.clone()],
+                replicas: vec![ReplicaDesc { id: 1, node_id: 1, role: ReplicaRole::Voter.into() }],
+            },
+            GroupDesc {
+                id: FIRST_GROUP_ID,
+                epoch: 0,
+                shards: vec![],
+                replicas: vec![ReplicaDesc { id: 2, node_id: 1, role: ReplicaRole::Voter.into() }],
+            },
+        ]);
+
+        // The root group is never chosen to place the user shards.
+        let groups = a.place_group_for_shard(1).await.unwrap();
+        assert_eq!(groups.iter().map(|g| g.id).collect::<Vec<_>>(), vec![FIRST_GROUP_ID]);
+
+        let sact = a.compute_shard_action().await.unwrap();
+        assert_eq!(sact.len(), 1);
+        let ShardAction::Migrate(ReallocateShard { shard, source_group, target_group }) = &sact[0];
+        assert_eq!(
+            (*shard, *source_group, *target_group),
+            (user_shard.id, ROOT_GROUP_ID, FIRST_GROUP_ID)
+        );
+        p.move_shards(*source_group, *target_group, *shard);
+
+        // The system shards are left in the root group.
+        let sact = a.compute_shard_action().await.unwrap();
+        assert!(sact.is_empty());
+    });
+}
+
+#[test]
+fn sim_avoid_overloaded_node() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(ClusterStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        let nodes = (1..=4)
+            .map(|id| NodeDesc {
+                id,
+                addr: "".into(),
+                capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 0, leader_count: 0 }),
+                status: NodeStatus::Active as i32,
+                labels: vec![],
+            })
+            .collect::<Vec<_>>();
+        p.set_nodes(nodes);
+
+        // All leaders are located in node 1, node 2 reports a saturated cpu.
+        let mut groups = Vec::new();
+        let mut replica_states = Vec::new();
+        for i in 0..3 {
+            let group_id = FIRST_GROUP_ID + i;
+            let replicas = (1..=3)
+                .map(|node_id| ReplicaDesc {
+                    id: group_id * 10 + node_id,
+                    node_id,
+                    role: ReplicaRole::Voter.into(),
+                })
+                .collect::<Vec<_>>();
+            for r in &replicas {
+                let role = if r.node_id == 1 { RaftRole::Leader } else { RaftRole::Follower };
+                replica_states.push(ReplicaState {
+                    replica_id: r.id,
+                    group_id,
+                    term: 1,
+                    voted_for: 0,
+                    role: role.into(),
+                    node_id: r.node_id,
+                    quarantine: None,
+                });
+            }
+            groups.push(GroupDesc { id: group_id, epoch: 0, shards: vec![], replicas });
+        }
+        p.set_groups(groups);
+        p.set_replica_states(replica_states);
+        p.set_node_health(1, NodeHealth { cpu_util: 0.5, ..Default::default() });
+        p.set_node_health(2, NodeHealth { cpu_util: 0.95, load1: 8.0, ..Default::default() });
+
+        // The new leaders are directed away from the overloaded node.
+        loop {
+            let lact = a.compute_leader_action(&HashMap::default()).await.unwrap();
+            if lact.is_empty() {
+                break;
+            }
+            for act in &lact {
+                let LeaderAction::Shed(action) = act else { unreachable!() };
+                assert_ne!(action.target_node, 2, "{action:?}");
+                p.transfer_leader(action.src_replica, action.target_replica);
+            }
+        }
+        let leader_counts = p
+            .nodes(NodeFilter::All)
+            .iter()
+            .map(|n| (n.id, n.capacity.as_ref().unwrap().leader_count))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(leader_counts[&1], 2);
+        assert_eq!(leader_counts[&2], 0);
+        assert_eq!(leader_counts[&3], 1);
+
+        // The new replicas are placed on the overloaded node at last.
+        let nodes = a.allocate_group_replica(vec![], 4).await.unwrap();
+        let node_ids = nodes.iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(node_ids.len(), 4);
+        assert_eq!(node_ids[0], 4);
+        assert_eq!(node_ids[3], 2);
+
+        // Once the cpu of node 2 cools down, it is chosen again.
+        p.set_node_health(2, NodeHealth { cpu_util: 0.1, ..Default::default() });
+        let lact = a.compute_leader_action(&HashMap::default()).await.unwrap();
+        let [LeaderAction::Shed(action)] = lact.as_slice() else { panic!("{lact:?}") };
+        assert_eq!(action.target_node, 2);
+    });
+}
+
+#[test]
+fn sim_leader_preference() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(ClusterStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        // Node 1 and 2 are in the east zone, node 3 and 4 are in the west zone.
+        let nodes = (1..=4)
+            .map(|id| NodeDesc {
+                id,
+                addr: "".into(),
+                capacity: Some(NodeCapacity { cpu_nums: 2.0, replica_count: 0, leader_count: 0 }),
+                status: NodeStatus::Active as i32,
+                labels: vec![if id <= 2 { "east" } else { "west" }.to_owned()],
+            })
+            .collect::<Vec<_>>();
+        p.set_nodes(nodes);
+
+        // All leaders are located in the west zone.
+        let mut groups = Vec::new();
+        let mut replica_states = Vec::new();
+        for i in 0..4 {
+            let group_id = FIRST_GROUP_ID + i;
+            let replicas = (0..3)
+                .map(|j| (i + j) % 4 + 1)
+                .map(|node_id| ReplicaDesc {
+                    id: group_id * 10 + node_id,
+                    node_id,
+                    role: ReplicaRole::Voter.into(),
+                })
+                .collect::<Vec<_>>();
+            let leader = replicas.iter().rev().find(|r| r.node_id > 2).unwrap().id;
+            for r in &replicas {
+                let role = if r.id == leader { RaftRole::Leader } else { RaftRole::Follower };
+                replica_states.push(ReplicaState {
+                    replica_id: r.id,
+                    group_id,
+                    term: 1,
+                    voted_for: 0,
+                    role: role.into(),
+                    node_id: r.node_id,
+                    quarantine: None,
+                });
+            }
+            groups.push(GroupDesc { id: group_id, epoch: 0, shards: vec![], replicas });
+        }
+        p.set_groups(groups);
+        p.set_replica_states(replica_states);
+
+        // The last group has no preference.
+        let leader_zones =
+            (0..3).map(|i| (FIRST_GROUP_ID + i, "east".to_owned())).collect::<HashMap<_, _>>();
+        for _ in 0..16 {
+            let lact = a.compute_leader_action(&leader_zones).await.unwrap();
+            if lact.is_empty() {
+                break;
+            }
+            for act in &lact {
+                let LeaderAction::Shed(action) = act else { unreachable!() };
+                println!(
+                    "transfer group {} leader from {} to {}",
+                    action.group, action.src_node, action.target_node,
+                );
+                p.transfer_leader(action.src_replica, action.target_replica);
+            }
+        }
+        assert!(a.compute_leader_action(&leader_zones).await.unwrap().is_empty());
+        p.display();
+
+        let groups = p.groups();
+        for group_id in leader_zones.keys() {
+            let leader = groups[group_id]
+                .replicas
+                .iter()
+                .find(|r| p.replica_state(&r.id).unwrap().role == RaftRole::Leader as i32)
+                .unwrap();
+            assert!(leader.node_id <= 2, "the leader of group {group_id} is {leader:?}");
+        }
+
+        // The replicas are not moved.
+        let replica_counts = p
+            .nodes(NodeFilter::All)
+            .iter()
+            .map(|n| n.capacity.as_ref().unwrap().replica_count)
+            .collect::<Vec<_>>();
+        assert_eq!(replica_counts, vec![3, 3, 3, 3]);
+    });
+}
+
 pub struct MockInfoProvider {
     nodes: Arc<Mutex<Vec<NodeDesc>>>,
     groups: Arc<Mutex<GroupInfo>>,
     replicas: Arc<Mutex<HashMap<u64, ReplicaState>>>,
+    healths: Arc<Mutex<HashMap<u64, NodeHealth>>>,
     shard_id_gen: AtomicU64,
 }
 
@@ -351,6 +597,7 @@ impl MockInfoProvider {
             nodes: Default::default(),
             groups: Default::default(),
             replicas: Default::default(),
+            healths: Default::default(),
             shard_id_gen: AtomicU64::new(1),
         }
     }
@@ -386,6 +633,10 @@ impl AllocSource for MockInfoProvider {
         let replica_info = self.replicas.lock().unwrap();
         replica_info.iter().map(|e| e.1.to_owned()).collect()
     }
+
+    fn node_health(&self, node_id: &u64) -> Option<NodeHealth> {
+        self.healths.lock().unwrap().get(node_id).cloned()
+    }
 }
 
 impl MockInfoProvider {
@@ -414,7 +665,7 @@ impl MockInfoProvider {
         let mut nodes = self.nodes(NodeFilter::All);
         for n in nodes.iter_mut() {
             let mut cap = n.capacity.take().unwrap();
-            cap.replica_count = node_replicas.get(&n.id).unwrap().len() as u64;
+            cap.replica_count = node_replicas.get(&n.id).map(Vec::len).unwrap_or_default() as u64;
             n.capacity = Some(cap)
         }
         self.set_nodes(nodes);
@@ -458,6 +709,10 @@ impl MockInfoProvider {
         let _ = std::mem::replace(&mut *replicas, id_to_state);
     }
 
+    fn set_node_health(&self, node_id: u64, health: NodeHealth) {
+        self.healths.lock().unwrap().insert(node_id, health);
+    }
+
     pub fn move_replica(&self, replica_id: u64, node: u64) {
         let mut groups = self.groups();
         for group in groups.values_mut() {
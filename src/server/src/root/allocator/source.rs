@@ -20,6 +20,7 @@ use sekas_api::server::v1::*;
 
 use super::RootShared;
 use crate::root::liveness::Liveness;
+use crate::root::node_status::NodeStatusCache;
 use crate::Result;
 
 pub enum NodeFilter {
@@ -43,12 +44,16 @@ pub trait AllocSource {
     fn replica_state(&self, replica_id: &u64) -> Option<ReplicaState>;
 
     fn replica_states(&self) -> Vec<ReplicaState>;
+
+    /// The health last reported by the node, `None` if it is not reported yet.
+    fn node_health(&self, node_id: &u64) -> Option<NodeHealth>;
 }
 
 #[derive(Clone)]
 pub struct SysAllocSource {
     root: Arc<RootShared>,
     liveness: Arc<Liveness>,
+    node_statuses: Arc<NodeStatusCache>,
 
     nodes: Arc<Mutex<Vec<NodeDesc>>>,
     groups: Arc<Mutex<GroupInfo>>,
@@ -67,10 +72,15 @@ struct ReplicaInfo {
 }
 
 impl SysAllocSource {
-    pub fn new(root: Arc<RootShared>, liveness: Arc<Liveness>) -> Self {
+    pub fn new(
+        root: Arc<RootShared>,
+        liveness: Arc<Liveness>,
+        node_statuses: Arc<NodeStatusCache>,
+    ) -> Self {
         Self {
             root,
             liveness,
+            node_statuses,
             nodes: Default::default(),
             groups: Default::default(),
             replicas: Default::default(),
@@ -130,6 +140,10 @@ impl AllocSource for SysAllocSource {
         let replica_info = self.replicas.lock().unwrap();
         replica_info.replicas.iter().map(|e| e.1.to_owned()).collect()
     }
+
+    fn node_health(&self, node_id: &u64) -> Option<NodeHealth> {
+        self.node_statuses.health(*node_id)
+    }
 }
 
 impl SysAllocSource {
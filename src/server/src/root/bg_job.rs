@@ -21,20 +21,24 @@ use std::time::Duration;
 use futures::future::poll_fn;
 use log::{error, info, warn};
 use prometheus::HistogramTimer;
+use sekas_api::server::v1::watch_response::*;
 use sekas_api::server::v1::*;
 use sekas_client::RetryState;
-use tokio::time::Instant;
+use sekas_runtime::time::Instant;
 
 use super::allocator::*;
+use super::clone::{clone_pin_id, unpin_clone, CLONE_PIN_LEASE};
+use super::export::{pin_gc, pinned_groups};
 use super::schedule::background_job::Job;
 use super::schedule::*;
 use super::{HeartbeatQueue, HeartbeatTask, RootShared, Schema};
 use crate::constants::INITIAL_EPOCH;
 use crate::root::metrics;
-use crate::Result;
+use crate::{Result, RootTestingKnobs};
 
 pub struct Jobs {
     core: JobCore,
+    knobs: RootTestingKnobs,
 }
 
 impl Jobs {
@@ -42,8 +46,10 @@ impl Jobs {
         root_shared: Arc<RootShared>,
         alloc: Arc<Allocator<SysAllocSource>>,
         heartbeat_queue: Arc<HeartbeatQueue>,
+        knobs: RootTestingKnobs,
     ) -> Self {
         Self {
+            knobs,
             core: JobCore {
                 root_shared,
                 alloc,
@@ -102,6 +108,9 @@ impl Jobs {
             background_job::Job::PurgeDatabase(purge_database) => {
                 self.handle_purge_database(job, purge_database).await
             }
+            background_job::Job::CloneTable(clone_table) => {
+                self.handle_clone_table(job, clone_table).await
+            }
         };
         info!("background job: {job:?}, handle result: {r:?}");
         r
@@ -150,6 +159,37 @@ impl Jobs {
         .await
     }
 
+    /// Wait the running create table job submitted by the request, returns
+    /// `false` if there is no such job.
+    pub async fn wait_create_table_job(
+        &self,
+        database: u64,
+        table_name: &str,
+        request_id: &str,
+    ) -> Result<bool> {
+        // The jobs are recovered before the leader is enabled.
+        self.core.check_root_leader()?;
+        if request_id.is_empty() {
+            return Ok(false);
+        }
+        let job_id = self.core.need_handle_jobs().into_iter().find_map(|job| match job.job {
+            Some(Job::CreateTable(create_table))
+                if create_table.database == database
+                    && create_table.table_name == table_name
+                    && create_table.desc.as_ref().is_some_and(|d| d.request_id == request_id) =>
+            {
+                Some(job.id)
+            }
+            _ => None,
+        });
+        let Some(job_id) = job_id else {
+            return Ok(false);
+        };
+        info!("wait the running create table job {job_id} of request {request_id}");
+        self.core.wait_and_check_result(&job_id).await?;
+        Ok(true)
+    }
+
     /// Submit pruge table job.
     pub async fn submit_purge_table_job(&self, db: &DatabaseDesc, table: &TableDesc) -> Result<()> {
         let table_id = table.id;
@@ -171,6 +211,41 @@ impl Jobs {
         .await
     }
 
+    /// Submit clone table job, the data of the source table visible at the
+    /// `read_version` is copied to the shards of the dest table.
+    pub async fn submit_clone_table_job(
+        &self,
+        src_table: u64,
+        table: TableDesc,
+        read_version: u64,
+        shards: Vec<ShardDesc>,
+    ) -> Result<()> {
+        let shards = shards
+            .into_iter()
+            .map(|shard| CloneShardProgress {
+                next_key: sekas_schema::shard::start_key(&shard),
+                shard: Some(shard),
+                ..Default::default()
+            })
+            .collect();
+        self.submit(
+            BackgroundJob {
+                job: Some(Job::CloneTable(CloneTableJob {
+                    src_table,
+                    desc: Some(table),
+                    read_version,
+                    shards,
+                    status: CloneTableJobStatus::Copying as i32,
+                    created_time: format!("{:?}", Instant::now()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+            false,
+        )
+        .await
+    }
+
     /// Submit create group job.
     pub async fn submit_create_group_job(&self) -> Result<()> {
         let status = CreateOneGroupStatus::Init as i32;
@@ -225,17 +300,34 @@ impl Jobs {
         job_id: u64,
         create_table: &mut CreateTableJob,
     ) -> Result<()> {
-        while let Some(shard) = create_table.wait_create.pop() {
-            let groups = self.core.alloc.place_group_for_shard(1).await?;
-            if groups.is_empty() {
-                return Err(crate::Error::ResourceExhausted("no enough groups".into()));
-            }
-            let group = groups.first().unwrap();
-            info!("try create shard at group {}, shards: {}", group.id, group.shards.len());
-            if let Err(err) = self.try_create_shard(group.id, &shard).await {
+        while let Some(shard) = create_table.wait_create.last().cloned() {
+            // The group is recorded before creating the shard, so the shard is created at
+            // the same group if the job is resumed by a new root leader.
+            let group_id = match create_table.shard_groups.get(&shard.id) {
+                Some(group_id) => *group_id,
+                None => {
+                    let groups = self.core.alloc.place_group_for_shard(1).await?;
+                    let Some(group) = groups.first() else {
+                        return Err(crate::Error::ResourceExhausted(
+                            "cluster not ready for user tables, no user group".into(),
+                        ));
+                    };
+                    info!(
+                        "place shard {} at group {}, shards: {}",
+                        shard.id,
+                        group.id,
+                        group.shards.len()
+                    );
+                    create_table.shard_groups.insert(shard.id, group.id);
+                    self.save_create_table(job_id, create_table).await?;
+                    group.id
+                }
+            };
+            create_table.wait_create.pop();
+            if let Err(err) = self.try_create_shard(group_id, &shard).await {
                 error!(
-                    "create table shard error and try to rollback: {err:?}. group={}, shard={}",
-                    group.id, shard.id
+                    "create table shard error and try to rollback: {err:?}. group={group_id}, shard={}",
+                    shard.id
                 );
                 create_table.remark = format!("{err:?}");
                 create_table.wait_cleanup.push(shard);
@@ -256,10 +348,26 @@ impl Jobs {
         job_id: u64,
         create_table: &mut CreateTableJob,
     ) -> Result<()> {
-        // FIXME(walter) what happen if create_table success but save_create_table
-        // failed?
         let schema = self.core.root_shared.schema()?;
-        schema.create_table(create_table.desc.as_ref().unwrap().to_owned()).await?;
+        while self.knobs.pause_before_write_table_desc.load(atomic::Ordering::Acquire) {
+            sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+        }
+        let desc = create_table.desc.as_ref().unwrap();
+        match schema.get_table(desc.db, &desc.name).await? {
+            None => {
+                schema.create_table(desc.to_owned()).await?;
+            }
+            Some(table) if table.id == desc.id => {
+                // The desc is written before the root leader fails over.
+            }
+            Some(table) => {
+                warn!("create table but it already exists. table={}, id={}", table.name, table.id);
+                create_table.remark = format!("table {} already exists", table.name);
+                create_table.status = CreateTableJobStatus::Rollbacking as i32;
+                self.save_create_table(job_id, create_table).await?;
+                return Ok(());
+            }
+        }
         create_table.status = CreateTableJobStatus::Finish as i32;
         self.save_create_table(job_id, create_table).await?;
         Ok(())
@@ -270,12 +378,11 @@ impl Jobs {
         job_id: u64,
         create_table: &mut CreateTableJob,
     ) -> Result<()> {
-        loop {
-            let shard = create_table.wait_cleanup.pop();
-            if shard.is_none() {
-                break;
+        while let Some(shard) = create_table.wait_cleanup.last() {
+            if let Some(group_id) = create_table.shard_groups.get(&shard.id) {
+                self.try_remove_shard(*group_id, shard.id).await?;
             }
-            let _ = shard; // TODO: delete shard.
+            create_table.wait_cleanup.pop();
             self.save_create_table(job_id, create_table).await?;
         }
         create_table.status = CreateTableJobStatus::Abort as i32;
@@ -322,6 +429,242 @@ impl Jobs {
     }
 }
 
+impl Jobs {
+    // handle clone_table.
+    async fn handle_clone_table(
+        &self,
+        job: &BackgroundJob,
+        clone_table: &CloneTableJob,
+    ) -> Result<()> {
+        let mut clone_table = clone_table.to_owned();
+        loop {
+            match CloneTableJobStatus::from_i32(clone_table.status).unwrap() {
+                CloneTableJobStatus::Copying => {
+                    if !self.handle_copy_table(job.id, &mut clone_table).await? {
+                        // The copying is resumed in the next round, so the other jobs are not
+                        // blocked by a large table.
+                        return Ok(());
+                    }
+                }
+                CloneTableJobStatus::Rollbacking => {
+                    self.handle_cleanup_clone(job.id, &mut clone_table).await?;
+                }
+                CloneTableJobStatus::Finish | CloneTableJobStatus::Abort => {
+                    let schema = self.core.root_shared.schema()?;
+                    let transport_manager = &self.core.root_shared.transport_manager;
+                    unpin_clone(transport_manager, &schema, clone_table.read_version).await;
+                    let mut job = job.to_owned();
+                    job.job = Some(background_job::Job::CloneTable(clone_table));
+                    return self.core.finish(job).await;
+                }
+            }
+        }
+    }
+
+    /// Copy a bounded number of chunks to the dest table, returns `false` if
+    /// the copying is not finished yet.
+    async fn handle_copy_table(
+        &self,
+        job_id: u64,
+        clone_table: &mut CloneTableJob,
+    ) -> Result<bool> {
+        const CHUNKS_PER_ROUND: usize = 16;
+
+        if self.knobs.pause_clone_table.load(atomic::Ordering::Acquire) {
+            sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+            return Ok(false);
+        }
+
+        let schema = self.core.root_shared.schema()?;
+        let desc = clone_table.desc.clone().unwrap();
+        let read_version = clone_table.read_version;
+        let tables = schema.list_table().await?;
+        if !tables.iter().any(|t| t.id == desc.id) {
+            // The shards of the deleted dest table are removed by the purge table job.
+            warn!("the dest table {} of the clone is deleted, abort the clone", desc.id);
+            clone_table.remark = format!("dest table {} is deleted", desc.name);
+            clone_table.status = CloneTableJobStatus::Abort as i32;
+            self.save_clone_table(job_id, clone_table).await?;
+            return Ok(true);
+        }
+        if !tables.iter().any(|t| t.id == clone_table.src_table) {
+            warn!("the source table {} of the clone is deleted, rollback", clone_table.src_table);
+            clone_table.remark = format!("source table {} is deleted", clone_table.src_table);
+            clone_table.status = CloneTableJobStatus::Rollbacking as i32;
+            self.save_clone_table(job_id, clone_table).await?;
+            return Ok(true);
+        }
+
+        // Renew the GC pin of the read version, the groups serving the source
+        // table are verified again since the shards might be moved. The copying
+        // fails with `VersionTooOld` if the pin is expired and the versions are
+        // collected.
+        let src_table = clone_table.src_table;
+        let pin_id = clone_pin_id(read_version);
+        let transport_manager = &self.core.root_shared.transport_manager;
+        let group_ids = pinned_groups(&schema, &[src_table]).await?;
+        match pin_gc(transport_manager, &schema, &pin_id, read_version, CLONE_PIN_LEASE, group_ids)
+            .await
+        {
+            Ok(()) => {}
+            Err(err @ crate::Error::VersionTooOld(..)) => {
+                error!(
+                    "renew gc pin {pin_id} of clone table {} and try to rollback: {err:?}",
+                    desc.id
+                );
+                clone_table.remark = format!("{err:?}");
+                clone_table.status = CloneTableJobStatus::Rollbacking as i32;
+                self.save_clone_table(job_id, clone_table).await?;
+                return Ok(true);
+            }
+            Err(err) => warn!("renew gc pin {pin_id}: {err}"),
+        }
+
+        let mut num_chunks = 0;
+        for idx in 0..clone_table.shards.len() {
+            while !clone_table.shards[idx].finished {
+                if num_chunks == CHUNKS_PER_ROUND {
+                    return Ok(false);
+                }
+                num_chunks += 1;
+                let progress = &mut clone_table.shards[idx];
+                match self.copy_clone_chunk(src_table, read_version, progress).await {
+                    Ok(()) => {}
+                    Err(err @ crate::Error::VersionTooOld(..)) => {
+                        error!("clone table {} and try to rollback: {err:?}", desc.id);
+                        clone_table.remark = format!("{err:?}");
+                        clone_table.status = CloneTableJobStatus::Rollbacking as i32;
+                        self.save_clone_table(job_id, clone_table).await?;
+                        return Ok(true);
+                    }
+                    Err(err) => return Err(err),
+                }
+                self.save_clone_table(job_id, clone_table).await?;
+            }
+        }
+
+        // All data are copied, make the dest table writable.
+        if let Some(mut table) = schema.get_table(desc.db, &desc.name).await? {
+            if table.id == desc.id
+                && table.properties.remove(sekas_schema::property::CLONE_SOURCE).is_some()
+            {
+                table.version += 1;
+                schema.update_table(table.clone()).await?;
+                self.core
+                    .root_shared
+                    .watcher_hub
+                    .notify_updates(vec![UpdateEvent {
+                        event: Some(update_event::Event::Table(table)),
+                    }])
+                    .await;
+            }
+        }
+        info!(
+            "clone table {} from table {src_table} at version {read_version} is finished",
+            desc.id
+        );
+        clone_table.status = CloneTableJobStatus::Finish as i32;
+        self.save_clone_table(job_id, clone_table).await?;
+        Ok(true)
+    }
+
+    /// Copy a chunk of the source table to the dest shard. The keys visible at
+    /// the read version are pulled from the source shard covering the next key,
+    /// so the copying is not affected by the splitting and migrating of the
+    /// source shards.
+    async fn copy_clone_chunk(
+        &self,
+        src_table: u64,
+        read_version: u64,
+        progress: &mut CloneShardProgress,
+    ) -> Result<()> {
+        let transport_manager = &self.core.root_shared.transport_manager;
+        let router = transport_manager.router();
+        let shard = progress.shard.clone().unwrap();
+        let end_key = Some(sekas_schema::shard::end_key(&shard)).filter(|key| !key.is_empty());
+        if end_key.as_ref().is_some_and(|end_key| progress.next_key >= *end_key) {
+            progress.finished = true;
+            return Ok(());
+        }
+
+        let route_err = |err: sekas_client::Error| {
+            crate::Error::Rpc(tonic::Status::unavailable(format!("route clone table: {err}")))
+        };
+        let (src_group, src_shard) =
+            router.find_shard(src_table, &progress.next_key).map_err(route_err)?;
+        let client = transport_manager.build_move_shard_client(src_group.id);
+        let (value_sets, has_more) = client
+            .pull_snapshot_chunk(src_shard.id, read_version, &progress.next_key, end_key.as_deref())
+            .await?;
+        if let Some(last_key) = value_sets.last().map(|v| v.user_key.clone()) {
+            let num_keys = value_sets.len() as u64;
+            let num_bytes = value_sets
+                .iter()
+                .flat_map(|v| v.values.iter().map(|value| (v.user_key.len(), value)))
+                .map(|(key_len, value)| key_len + value.content.as_ref().map_or(0, Vec::len))
+                .sum::<usize>() as u64;
+            let dest_group = router.find_group_by_shard(shard.id).map_err(route_err)?;
+            let mut client = transport_manager.build_move_shard_client(dest_group.id);
+            let req = IngestRequest { group_id: dest_group.id, shard_id: shard.id, value_sets };
+            client.ingest(&req).await?;
+            // The next key is the immediate successor of the last copied key.
+            progress.next_key = last_key;
+            progress.next_key.push(0);
+            progress.copied_keys += num_keys;
+            progress.copied_bytes += num_bytes;
+        }
+        if !has_more {
+            // The keys of the source shard within the range are copied.
+            let src_end_key = sekas_schema::shard::end_key(&src_shard);
+            if src_end_key.is_empty() || end_key.is_some_and(|end_key| src_end_key >= end_key) {
+                progress.finished = true;
+            } else {
+                progress.next_key = src_end_key;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the partial dest table of the failed clone.
+    async fn handle_cleanup_clone(
+        &self,
+        job_id: u64,
+        clone_table: &mut CloneTableJob,
+    ) -> Result<()> {
+        let schema = self.core.root_shared.schema()?;
+        let desc = clone_table.desc.clone().unwrap();
+        if let Some(table) = schema.get_table(desc.db, &desc.name).await? {
+            if table.id == desc.id {
+                schema.delete_table(table).await?;
+                self.core
+                    .root_shared
+                    .watcher_hub
+                    .notify_deletes(vec![DeleteEvent {
+                        event: Some(delete_event::Event::Table(desc.id)),
+                    }])
+                    .await;
+            }
+        }
+        for (group_id, shard) in schema.get_table_shards(desc.id).await? {
+            self.try_remove_shard(group_id, shard.id).await?;
+        }
+        info!("the partial dest table {} of the failed clone is removed", desc.id);
+        clone_table.status = CloneTableJobStatus::Abort as i32;
+        self.save_clone_table(job_id, clone_table).await?;
+        Ok(())
+    }
+
+    async fn save_clone_table(&self, job_id: u64, clone_table: &CloneTableJob) -> Result<()> {
+        self.core
+            .update(BackgroundJob {
+                id: job_id,
+                job: Some(background_job::Job::CloneTable(clone_table.to_owned())),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
 impl Jobs {
     // handle create_one_group
     async fn handle_create_one_group(
@@ -635,9 +978,20 @@ impl Jobs {
         Ok(())
     }
 
-    async fn try_remove_shard(&self, _group: u64, _shard: u64) -> Result<()> {
-        // TODO: impl remove shard.
-        Ok(())
+    async fn try_remove_shard(&self, group_id: u64, shard_id: u64) -> Result<()> {
+        let mut group_client = self.core.root_shared.transport_manager.lazy_group_client(group_id);
+        let mut retry_state = RetryState::new(Duration::from_secs(10));
+        loop {
+            match group_client.remove_shard(shard_id).await {
+                Ok(()) => {
+                    info!("group {group_id} shard {shard_id} is removed, the data will be purged");
+                    return Ok(());
+                }
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
     }
 }
 
@@ -848,6 +1202,8 @@ fn res_key(job: &BackgroundJob) -> Option<Vec<u8>> {
             key.extend_from_slice(job.table_name.as_bytes());
             Some(key)
         }
-        background_job::Job::CreateOneGroup(_) | background_job::Job::PurgeDatabase(_) => None,
+        background_job::Job::CreateOneGroup(_)
+        | background_job::Job::PurgeDatabase(_)
+        | background_job::Job::CloneTable(_) => None,
     }
 }
@@ -0,0 +1,163 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::warn;
+
+use crate::node::clock::WallClock;
+use crate::ClockConfig;
+
+/// Estimate the clock offsets of nodes by the timestamps exchanged in
+/// heartbeats.
+///
+/// The offset of a node is measured against the wall clock of root, and the
+/// median of all offsets is taken as the cluster clock. So a node is skewed if
+/// its clock is far away from the cluster clock, including the root itself.
+pub(crate) struct ClockSkewTracker {
+    clock: WallClock,
+    max_skew: Duration,
+    /// The offsets (in nanoseconds) between the clock of nodes and root.
+    offsets: Mutex<HashMap<u64, i64>>,
+}
+
+impl ClockSkewTracker {
+    pub(crate) fn new(cfg: &ClockConfig) -> Self {
+        ClockSkewTracker {
+            clock: WallClock::new(cfg),
+            max_skew: cfg.max_clock_skew(),
+            offsets: Mutex::default(),
+        }
+    }
+
+    /// The wall clock of root.
+    #[inline]
+    pub(crate) fn clock(&self) -> &WallClock {
+        &self.clock
+    }
+
+    /// The estimated cluster clock, which is carried by the heartbeat requests.
+    pub(crate) fn cluster_now_nanos(&self) -> u64 {
+        let now = self.clock.now_nanos() as i64;
+        now.saturating_add(self.median_offset()).max(0) as u64
+    }
+
+    /// Record the timestamp of a node, which is responded by heartbeat. The
+    /// `send_nanos` and `recv_nanos` are the root clock when the heartbeat is
+    /// sent and the response is received.
+    pub(crate) fn observe(&self, node_id: u64, send_nanos: u64, node_nanos: u64, recv_nanos: u64) {
+        if node_nanos == 0 {
+            // The node doesn't carry its clock.
+            return;
+        }
+
+        // Assume that the delay of request and response are the same.
+        let middle = send_nanos / 2 + recv_nanos / 2;
+        let offset = node_nanos as i64 - middle as i64;
+        let mut offsets = self.offsets.lock().unwrap();
+        let was_skewed = Self::is_offset_skewed(&offsets, node_id, self.max_skew);
+        offsets.insert(node_id, offset);
+        if !was_skewed && Self::is_offset_skewed(&offsets, node_id, self.max_skew) {
+            warn!(
+                "the clock of node {node_id} is skewed, offset against root {offset}ns, limit {:?}",
+                self.max_skew,
+            );
+        }
+    }
+
+    /// Forget the clock of an unreachable node.
+    pub(crate) fn forget(&self, node_id: u64) {
+        self.offsets.lock().unwrap().remove(&node_id);
+    }
+
+    /// The clock skew between the node and the cluster clock.
+    pub(crate) fn node_skew(&self, node_id: u64) -> Option<Duration> {
+        let offsets = self.offsets.lock().unwrap();
+        let median = Self::median(&offsets);
+        offsets.get(&node_id).map(|offset| Duration::from_nanos(offset.abs_diff(median)))
+    }
+
+    /// Whether the clock of node is skewed from the cluster clock.
+    pub(crate) fn is_node_skewed(&self, node_id: u64) -> bool {
+        self.node_skew(node_id).is_some_and(|skew| skew > self.max_skew)
+    }
+
+    /// Whether the clock of root is skewed from the cluster clock.
+    pub(crate) fn is_skewed(&self) -> bool {
+        Duration::from_nanos(self.median_offset().unsigned_abs()) > self.max_skew
+    }
+
+    fn median_offset(&self) -> i64 {
+        Self::median(&self.offsets.lock().unwrap())
+    }
+
+    fn median(offsets: &HashMap<u64, i64>) -> i64 {
+        let mut values = offsets.values().cloned().collect::<Vec<_>>();
+        if values.is_empty() {
+            return 0;
+        }
+        values.sort_unstable();
+        values[values.len() / 2]
+    }
+
+    fn is_offset_skewed(offsets: &HashMap<u64, i64>, node_id: u64, max_skew: Duration) -> bool {
+        let median = Self::median(offsets);
+        offsets
+            .get(&node_id)
+            .is_some_and(|offset| Duration::from_nanos(offset.abs_diff(median)) > max_skew)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    #[test]
+    fn skewed_node_is_detected() {
+        let cfg = ClockConfig { max_clock_skew_ms: 500, ..Default::default() };
+        let tracker = ClockSkewTracker::new(&cfg);
+        tracker.observe(1, 100 * SECOND, 100 * SECOND, 100 * SECOND);
+        tracker.observe(2, 100 * SECOND, 100 * SECOND + 1000, 100 * SECOND + 2000);
+        tracker.observe(3, 100 * SECOND, 110 * SECOND, 100 * SECOND);
+        assert!(!tracker.is_node_skewed(1));
+        assert!(!tracker.is_node_skewed(2));
+        assert!(tracker.is_node_skewed(3));
+        assert!(!tracker.is_node_skewed(4));
+        assert!(!tracker.is_skewed());
+
+        tracker.forget(3);
+        assert!(tracker.node_skew(3).is_none());
+    }
+
+    #[test]
+    fn skewed_root_is_detected() {
+        let cfg = ClockConfig { max_clock_skew_ms: 500, ..Default::default() };
+        let tracker = ClockSkewTracker::new(&cfg);
+        // The root is located in node 1.
+        tracker.observe(1, 100 * SECOND, 100 * SECOND, 100 * SECOND);
+        tracker.observe(2, 100 * SECOND, 110 * SECOND, 100 * SECOND);
+        tracker.observe(3, 100 * SECOND, 110 * SECOND, 100 * SECOND);
+        assert!(tracker.is_skewed());
+        assert!(tracker.is_node_skewed(1));
+        assert!(!tracker.is_node_skewed(2));
+
+        // The cluster clock follows the majority.
+        let skew = tracker.cluster_now_nanos().abs_diff(tracker.clock().now_nanos());
+        assert!(Duration::from_nanos(skew) >= Duration::from_secs(9));
+    }
+}
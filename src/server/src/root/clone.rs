@@ -0,0 +1,209 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use log::{info, warn};
+use sekas_api::server::v1::clone_status::Phase;
+use sekas_api::server::v1::watch_response::*;
+use sekas_api::server::v1::*;
+use sekas_schema::property::CLONE_SOURCE;
+
+use super::export::{pin_gc, pinned_groups};
+use super::schedule::{background_job, BackgroundJob, CloneTableJob, CloneTableJobStatus};
+use super::{Root, Schema};
+use crate::transport::TransportManager;
+use crate::{Error, Result};
+
+/// The lease of the GC pin of a clone, it is renewed by every round of the
+/// clone table job.
+pub(super) const CLONE_PIN_LEASE: Duration = Duration::from_secs(30);
+
+impl Root {
+    /// Clone a table from a consistent snapshot of the source table.
+    ///
+    /// The dest table is created with the properties and the shard boundaries
+    /// of the source table, then the data visible at the allocated read version
+    /// is copied by the clone table job. The dest table is read-only until the
+    /// job finishes, and it is removed if the job fails. The GC watermark of
+    /// the nodes is pinned at the read version until the job finishes.
+    pub async fn clone_table(
+        &self,
+        src_database: &str,
+        src_table: &str,
+        dest_database: &str,
+        dest_table: &str,
+    ) -> Result<CloneTableResponse> {
+        self.check_catalog_writable()?;
+        let schema = self.schema()?;
+        let src_db = schema
+            .get_database(src_database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(src_database.to_owned()))?;
+        let dest_db = schema
+            .get_database(dest_database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(dest_database.to_owned()))?;
+        let src = schema
+            .get_table(src_db.id, src_table)
+            .await?
+            .ok_or_else(|| Error::TableNotFound(src_table.to_owned()))?;
+        if src.id < sekas_schema::FIRST_USER_TABLE_ID {
+            return Err(Error::InvalidArgument("unsupported clone system table".into()));
+        }
+        if src.properties.contains_key(CLONE_SOURCE) {
+            return Err(Error::InvalidArgument(format!("table {src_table} is cloning")));
+        }
+        if schema.get_table(dest_db.id, dest_table).await?.is_some() {
+            return Err(Error::AlreadyExists(format!("table {dest_table}")));
+        }
+
+        self.ensure_user_group().await?;
+        // The txns committed before are visible at the read version, and the
+        // versions visible at it are retained until the clone finishes.
+        let read_version = self.alloc_txn_id(1).await?;
+        let src_shards = schema.get_table_shards(src.id).await?;
+        let group_ids = pinned_groups(&schema, &[src.id]).await?;
+        let pin_id = clone_pin_id(read_version);
+        let transport_manager = &self.shared.transport_manager;
+        if let Err(err) =
+            pin_gc(transport_manager, &schema, &pin_id, read_version, CLONE_PIN_LEASE, group_ids)
+                .await
+        {
+            unpin_clone(transport_manager, &schema, read_version).await;
+            return Err(err);
+        }
+        match self
+            .submit_clone_table(&schema, &src, dest_db.id, dest_table, read_version, src_shards)
+            .await
+        {
+            Ok(table) => Ok(CloneTableResponse { table: Some(table), read_version }),
+            Err(err) => {
+                unpin_clone(transport_manager, &schema, read_version).await;
+                Err(err)
+            }
+        }
+    }
+
+    /// Create the dest table with the shard boundaries of the source table,
+    /// and submit the clone table job copying the data into it.
+    async fn submit_clone_table(
+        &self,
+        schema: &Schema,
+        src: &TableDesc,
+        dest_db: u64,
+        dest_table: &str,
+        read_version: u64,
+        src_shards: Vec<(u64, ShardDesc)>,
+    ) -> Result<TableDesc> {
+        let mut properties = src.properties.clone();
+        properties.insert(CLONE_SOURCE.to_owned(), src.id.to_string());
+        let table = schema
+            .prepare_create_table(TableDesc {
+                name: dest_table.to_owned(),
+                db: dest_db,
+                properties,
+                ..Default::default()
+            })
+            .await?;
+        let mut shards = Vec::new();
+        for (_, shard) in src_shards {
+            let id = schema.next_shard_id().await?;
+            shards.push(ShardDesc { id, table_id: table.id, range: shard.range });
+        }
+        shards.sort_unstable_by_key(sekas_schema::shard::start_key);
+        info!(
+            "prepare clone table. source={}, table={}, table_id={}, shards={}, read_version={read_version}",
+            src.id,
+            table.name,
+            table.id,
+            shards.len()
+        );
+
+        self.jobs.submit_create_table_job(table.clone(), shards.clone()).await?;
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Table(table.clone())),
+            }])
+            .await;
+        self.jobs.submit_clone_table_job(src.id, table.clone(), read_version, shards).await?;
+        Ok(table)
+    }
+
+    /// List the clones in progress.
+    pub async fn list_clones(&self) -> Result<Vec<CloneStatus>> {
+        let schema = self.schema()?;
+        Ok(schema.list_job().await?.iter().filter_map(to_clone_status).collect())
+    }
+
+    /// Get the clone status of the dest table, `None` if the table is not
+    /// cloned. The finished clones are read from the job history.
+    pub async fn clone_status(&self, table_id: u64) -> Result<Option<CloneStatus>> {
+        let schema = self.schema()?;
+        let mut jobs = schema.list_job().await?;
+        jobs.extend(schema.list_history_job().await?);
+        Ok(jobs
+            .iter()
+            .filter_map(to_clone_status)
+            .filter(|status| status.dest_table == table_id)
+            .max_by_key(|status| status.read_version))
+    }
+}
+
+/// The id of the GC pin of the clone at the read version.
+pub(super) fn clone_pin_id(read_version: u64) -> String {
+    format!("clone-{read_version}")
+}
+
+/// Remove the GC pin of the clone, it expires by itself if it is failed to
+/// remove.
+pub(super) async fn unpin_clone(
+    transport_manager: &TransportManager,
+    schema: &Schema,
+    read_version: u64,
+) {
+    let pin_id = clone_pin_id(read_version);
+    if let Err(err) =
+        pin_gc(transport_manager, schema, &pin_id, read_version, Duration::ZERO, vec![]).await
+    {
+        warn!("unpin gc {pin_id}: {err}");
+    }
+}
+
+fn to_clone_status(job: &BackgroundJob) -> Option<CloneStatus> {
+    let Some(background_job::Job::CloneTable(clone_table)) = job.job.as_ref() else {
+        return None;
+    };
+    Some(clone_table_status(clone_table))
+}
+
+fn clone_table_status(clone_table: &CloneTableJob) -> CloneStatus {
+    let phase = match CloneTableJobStatus::from_i32(clone_table.status) {
+        Some(CloneTableJobStatus::Copying) | None => Phase::Copying,
+        Some(CloneTableJobStatus::Rollbacking) => Phase::Cleaning,
+        Some(CloneTableJobStatus::Finish) => Phase::Finished,
+        Some(CloneTableJobStatus::Abort) => Phase::Aborted,
+    };
+    CloneStatus {
+        src_table: clone_table.src_table,
+        dest_table: clone_table.desc.as_ref().map(|d| d.id).unwrap_or_default(),
+        read_version: clone_table.read_version,
+        phase: phase as i32,
+        copied_shards: clone_table.shards.iter().filter(|s| s.finished).count() as u64,
+        total_shards: clone_table.shards.len() as u64,
+        copied_keys: clone_table.shards.iter().map(|s| s.copied_keys).sum(),
+        copied_bytes: clone_table.shards.iter().map(|s| s.copied_bytes).sum(),
+        remark: clone_table.remark.clone(),
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The admin requests to compact the data of a table manually.
+//!
+//! Root only fans out the requests to the replicas hosting the shards of the
+//! table, the nodes limit the concurrency of the manual compactions by
+//! themselves.
+
+use log::{info, warn};
+use sekas_api::server::v1::*;
+
+use super::Root;
+use crate::{Error, Result};
+
+impl Root {
+    /// Compact the data of the table on all replicas hosting its shards, both
+    /// the leaders and the followers. It returns once all replicas are
+    /// finished.
+    ///
+    /// The replicas failed to compact are returned with the reason, instead
+    /// of failing the whole request.
+    pub async fn compact_table(&self, database: &str, table: &str) -> Result<CompactTableResponse> {
+        let schema = self.linearizable_schema().await?;
+        let db = schema
+            .get_database(database)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.to_owned()))?;
+        let table = schema
+            .get_table(db.id, table)
+            .await?
+            .ok_or_else(|| Error::TableNotFound(table.to_owned()))?;
+
+        let mut replicas = vec![];
+        let filter = GroupFilter { table_id: Some(table.id), ..Default::default() };
+        for group in schema.list_group_with(&filter).await? {
+            for replica in &group.replicas {
+                replicas.push((group.id, replica.clone()));
+            }
+        }
+        info!("compact table {} on {} replicas", table.id, replicas.len());
+
+        let range = CompactRange { table_id: table.id, ..Default::default() };
+        let results = futures::future::join_all(replicas.iter().map(|(_, replica)| {
+            let req = CompactReplicaRequest { replica_id: replica.id, range: Some(range.clone()) };
+            let schema = schema.clone();
+            async move {
+                let node = schema
+                    .get_node(replica.node_id)
+                    .await?
+                    .ok_or_else(|| Error::InvalidData(format!("node {}", replica.node_id)))?;
+                let client = self.shared.transport_manager.get_node_client(node.addr)?;
+                Ok::<_, Error>(client.compact_replica(req).await?)
+            }
+        }))
+        .await;
+        let mut resp = CompactTableResponse::default();
+        for ((group_id, replica), result) in replicas.into_iter().zip(results) {
+            let mut compaction = ReplicaCompaction {
+                group_id,
+                replica_id: replica.id,
+                node_id: replica.node_id,
+                ..Default::default()
+            };
+            match result {
+                Ok(result) => compaction.result = Some(result),
+                Err(err) => {
+                    warn!("compact replica {} of table {}: {err}", replica.id, table.id);
+                    compaction.error = err.to_string();
+                }
+            }
+            resp.replicas.push(compaction);
+        }
+        Ok(resp)
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The verification of the shard coverage of tables.
+//!
+//! The shards of a table must cover the key space from `SHARD_MIN` to
+//! `SHARD_MAX` without gaps or overlaps, each shard must be listed by exactly
+//! one group, except the shards being moved, and the groups the shards routed
+//! to must list them. The violations are raised as health alerts once they are
+//! found by two successive verifications, so that the transient states during
+//! splits, merges and moves are not reported.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+use log::warn;
+use sekas_api::server::v1::*;
+use sekas_rock::ascii::escape_bytes;
+
+use super::health::HealthAlert;
+use super::Root;
+use crate::Result;
+
+impl Root {
+    /// Verify the shard coverage of all tables in catalog, and the shards
+    /// routed by root. Returns the violations found.
+    pub async fn verify_coverage(&self) -> Result<Vec<HealthAlert>> {
+        let schema = self.schema()?;
+        // The groups are read page by page, only the shards and the epochs are kept.
+        let mut table_shards: HashMap<u64, Vec<(u64, ShardDesc)>> = HashMap::new();
+        let mut group_epochs = HashMap::new();
+        let mut filter = GroupFilter::default();
+        loop {
+            let page = schema.list_group_page(&filter).await?;
+            for group in page.items {
+                group_epochs.insert(group.id, group.epoch);
+                for shard in group.shards {
+                    table_shards.entry(shard.table_id).or_default().push((group.id, shard));
+                }
+            }
+            if !page.has_more() {
+                break;
+            }
+            filter.page_token = page.next_page_token;
+        }
+
+        let router = self.shared.transport_manager.router();
+        let mut violations = Vec::new();
+        for table in schema.list_table().await? {
+            let shards = table_shards.remove(&table.id).unwrap_or_default();
+            let routes = router
+                .find_table_shards(table.id)
+                .into_iter()
+                .filter_map(|(shard, state)| state.map(|state| (shard, state.id, state.epoch)))
+                .collect::<Vec<_>>();
+            let routed_violations = check_routed_shards(table.id, &routes, &shards, &group_epochs);
+            violations.extend(check_table_coverage(table.id, shards));
+            violations.extend(routed_violations);
+        }
+
+        // The moving shard is listed by both the source and dest groups.
+        if violations.iter().any(|v| matches!(v, HealthAlert::ShardInMultipleGroups { .. })) {
+            let moving_shards = self
+                .list_migrations()
+                .await?
+                .into_iter()
+                .filter_map(|m| m.desc.and_then(|desc| desc.shard_desc).map(|shard| shard.id))
+                .collect::<HashSet<_>>();
+            violations.retain(|v| {
+                !matches!(v, HealthAlert::ShardInMultipleGroups { shard_id, .. }
+                    if moving_shards.contains(shard_id))
+            });
+        }
+        Ok(violations)
+    }
+
+    /// Verify the shard coverage periodically, until the root leader is
+    /// dropped.
+    pub(super) async fn run_coverage_verifier(&self) {
+        let interval = Duration::from_secs(self.cfg.verify_coverage_interval_sec);
+        if interval.is_zero() {
+            return;
+        }
+        let mut last_violations = Vec::new();
+        loop {
+            sekas_runtime::time::sleep(interval).await;
+            let violations = match self.verify_coverage().await {
+                Ok(violations) => violations,
+                Err(err) => {
+                    warn!("verify shard coverage: {err:?}");
+                    continue;
+                }
+            };
+            let confirmed =
+                violations.iter().filter(|v| last_violations.contains(*v)).cloned().collect();
+            self.health.refresh_coverage(confirmed);
+            last_violations = violations;
+        }
+    }
+}
+
+/// Check the shards of a table listed by the groups in catalog, for the gaps,
+/// the overlaps and the shards listed by multiple groups.
+pub(crate) fn check_table_coverage(
+    table_id: u64,
+    shards: Vec<(/* group_id */ u64, ShardDesc)>,
+) -> Vec<HealthAlert> {
+    let mut violations = Vec::new();
+    let mut owners: BTreeMap<u64, (ShardDesc, Vec<u64>)> = BTreeMap::new();
+    for (group_id, shard) in shards {
+        owners.entry(shard.id).or_insert_with(|| (shard, vec![])).1.push(group_id);
+    }
+
+    let mut ranges = Vec::with_capacity(owners.len());
+    for (shard_id, (shard, mut group_ids)) in owners {
+        group_ids.sort_unstable();
+        group_ids.dedup();
+        let group_id = group_ids[0];
+        if group_ids.len() > 1 {
+            violations.push(HealthAlert::ShardInMultipleGroups { table_id, shard_id, group_ids });
+        }
+        let range = shard.range.unwrap_or_default();
+        ranges.push((range.start, range.end, shard_id, group_id));
+    }
+    if ranges.is_empty() {
+        // The shards of table are not created yet.
+        return violations;
+    }
+    ranges.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| cmp_end(&a.1, &b.1)));
+
+    // The end of the range covered so far, `None` means the end of key space, and
+    // the shard claims it.
+    let mut covered = Some(Vec::new());
+    let mut last: Option<(u64, u64)> = None;
+    for (start, end, shard_id, group_id) in ranges {
+        match covered.as_ref() {
+            Some(covered_end) if start > *covered_end => {
+                violations.push(HealthAlert::ShardGap {
+                    table_id,
+                    start: escape_bytes(covered_end),
+                    end: escape_bytes(&start),
+                    prev_shard_id: last.map(|(id, _)| id),
+                    next_shard_id: Some(shard_id),
+                });
+            }
+            Some(covered_end) if start == *covered_end => {}
+            _ => {
+                let overlap_end = match covered.as_ref() {
+                    Some(covered_end) if cmp_end(covered_end, &end).is_lt() => covered_end,
+                    _ => &end,
+                };
+                let (overlap_shard_id, overlap_group_id) =
+                    last.expect("the covered range is empty");
+                violations.push(HealthAlert::ShardOverlap {
+                    table_id,
+                    start: escape_bytes(&start),
+                    end: escape_bytes(overlap_end),
+                    shard_id,
+                    group_id,
+                    overlap_shard_id,
+                    overlap_group_id,
+                });
+            }
+        }
+        if let Some(covered_end) = covered.as_ref() {
+            if cmp_end(covered_end, &end).is_lt() {
+                covered = if end.is_empty() { None } else { Some(end) };
+                last = Some((shard_id, group_id));
+            }
+        }
+    }
+    if let Some(covered_end) = covered {
+        violations.push(HealthAlert::ShardGap {
+            table_id,
+            start: escape_bytes(&covered_end),
+            end: String::default(),
+            prev_shard_id: last.map(|(id, _)| id),
+            next_shard_id: None,
+        });
+    }
+    violations
+}
+
+/// Check that the groups the shards routed to list them. The routes of a group
+/// whose epoch doesn't match the catalog are skipped, they are not refreshed
+/// yet.
+pub(crate) fn check_routed_shards(
+    table_id: u64,
+    routes: &[(ShardDesc, /* group_id */ u64, /* epoch */ u64)],
+    shards: &[(/* group_id */ u64, ShardDesc)],
+    group_epochs: &HashMap<u64, u64>,
+) -> Vec<HealthAlert> {
+    let mut violations = Vec::new();
+    for (shard, group_id, epoch) in routes {
+        if group_epochs.get(group_id) != Some(epoch) {
+            continue;
+        }
+        if !shards.iter().any(|(id, s)| id == group_id && s.id == shard.id) {
+            violations.push(HealthAlert::ShardNotInGroup {
+                table_id,
+                shard_id: shard.id,
+                group_id: *group_id,
+            });
+        }
+    }
+    violations
+}
+
+/// Compare the end keys, the empty end key means the end of key space.
+fn cmp_end(lhs: &[u8], rhs: &[u8]) -> std::cmp::Ordering {
+    match (lhs.is_empty(), rhs.is_empty()) {
+        (true, true) => std::cmp::Ordering::Equal,
+        (true, false) => std::cmp::Ordering::Greater,
+        (false, true) => std::cmp::Ordering::Less,
+        (false, false) => lhs.cmp(rhs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shard(id: u64, start: &[u8], end: &[u8]) -> ShardDesc {
+        let range = RangePartition { start: start.to_vec(), end: end.to_vec() };
+        ShardDesc { id, table_id: 1, range: Some(range) }
+    }
+
+    #[test]
+    fn contiguous_shards_are_covered() {
+        let shards = vec![(1, shard(11, b"m", b"")), (2, shard(10, b"", b"m"))];
+        assert!(check_table_coverage(1, shards).is_empty());
+
+        // The shard being moved is listed by both groups.
+        let shards = vec![(1, shard(10, b"", b"")), (2, shard(10, b"", b""))];
+        let violations = check_table_coverage(1, shards);
+        assert_eq!(
+            violations,
+            vec![HealthAlert::ShardInMultipleGroups {
+                table_id: 1,
+                shard_id: 10,
+                group_ids: vec![1, 2]
+            }]
+        );
+    }
+
+    #[test]
+    fn detect_shard_gaps() {
+        let shards = vec![(1, shard(10, b"b", b"d")), (1, shard(11, b"f", b"x"))];
+        let gap = |start: &str, end: &str, prev_shard_id, next_shard_id| HealthAlert::ShardGap {
+            table_id: 1,
+            start: start.to_owned(),
+            end: end.to_owned(),
+            prev_shard_id,
+            next_shard_id,
+        };
+        assert_eq!(
+            check_table_coverage(1, shards),
+            vec![
+                gap("", "b", None, Some(10)),
+                gap("d", "f", Some(10), Some(11)),
+                gap("x", "", Some(11), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_shard_overlaps() {
+        let shards =
+            vec![(1, shard(10, b"", b"m")), (2, shard(11, b"k", b"")), (3, shard(12, b"p", b"q"))];
+        let overlap =
+            |start: &str, end: &str, shard_id, group_id, overlap_shard_id, overlap_group_id| {
+                HealthAlert::ShardOverlap {
+                    table_id: 1,
+                    start: start.to_owned(),
+                    end: end.to_owned(),
+                    shard_id,
+                    group_id,
+                    overlap_shard_id,
+                    overlap_group_id,
+                }
+            };
+        assert_eq!(
+            check_table_coverage(1, shards),
+            vec![overlap("k", "m", 11, 2, 10, 1), overlap("p", "q", 12, 3, 11, 2)]
+        );
+    }
+
+    #[test]
+    fn detect_shard_not_in_group() {
+        let shards = vec![(1, shard(10, b"", b""))];
+        let group_epochs = HashMap::from([(1, 5)]);
+        let routes = vec![(shard(10, b"", b"m"), 1, 5), (shard(11, b"m", b""), 1, 5)];
+        assert_eq!(
+            check_routed_shards(1, &routes, &shards, &group_epochs),
+            vec![HealthAlert::ShardNotInGroup { table_id: 1, shard_id: 11, group_id: 1 }]
+        );
+
+        // The staled routes are skipped.
+        let routes = vec![(shard(11, b"m", b""), 1, 4), (shard(12, b"", b""), 2, 1)];
+        assert!(check_routed_shards(1, &routes, &shards, &group_epochs).is_empty());
+    }
+}
@@ -0,0 +1,451 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export a set of tables at a single read version, and import them together.
+//!
+//! The read version is allocated from the timestamp oracle, so the txns
+//! committed before the export are visible to all dumps, and the txns committed
+//! after are visible to none. The GC watermark of the nodes is pinned at the
+//! read version during the export, the pin is a lease renewed by the root
+//! leader, so it expires by itself if the exporter dies. The export fails with
+//! `VersionTooOld` if any involved group has collected the versions before the
+//! pin, it is verified again on every renewal since the shards might be moved
+//! to the other groups.
+//!
+//! The dumps are written into the directory `root.export_dir`, which should be
+//! shared by the root nodes. The paths of the requests are relative to it.
+//!
+//! The imported tables are marked by [`PROVISIONING`] until all dumps are
+//! ingested, so they are deleted by the provisioning janitor if the root leader
+//! dies in the middle, and by the import itself if it fails.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use log::{info, warn};
+use prost::Message;
+use sekas_api::server::v1::*;
+use sekas_runtime::time::Instant;
+use sekas_schema::property::{CLONE_SOURCE, PROVISIONING, TABLE_TYPE};
+
+use super::{Root, Schema};
+use crate::transport::TransportManager;
+use crate::{Error, Result};
+
+/// The file name of the manifest in the export directory.
+const MANIFEST_FILE: &str = "MANIFEST";
+
+/// The lease of the GC pins, it is renewed once a third of it has elapsed.
+const EXPORT_PIN_LEASE: Duration = Duration::from_secs(30);
+
+/// The max number of keys ingested by a request.
+const INGEST_BATCH_KEYS: usize = 256;
+
+/// The GC pin of a running export.
+struct ExportPin {
+    id: String,
+    version: u64,
+    /// The exported tables, the groups serving them are verified by the pin.
+    table_ids: Vec<u64>,
+    renewed_at: Instant,
+}
+
+impl Root {
+    /// Export the tables at a single read version into the `dest` directory
+    /// relative to the export directory, and returns the manifest binding the
+    /// dumps to it.
+    pub async fn export_tables(
+        &self,
+        specs: &[ExportTableSpec],
+        dest: &str,
+    ) -> Result<ExportManifest> {
+        if specs.is_empty() {
+            return Err(Error::InvalidArgument("no table to export".into()));
+        }
+        let schema = self.linearizable_schema().await?;
+        let mut tables = Vec::with_capacity(specs.len());
+        for spec in specs {
+            let db = schema
+                .get_database(&spec.database)
+                .await?
+                .ok_or_else(|| Error::DatabaseNotFound(spec.database.clone()))?;
+            let table = schema
+                .get_table(db.id, &spec.table)
+                .await?
+                .ok_or_else(|| Error::TableNotFound(spec.table.clone()))?;
+            if table.id < sekas_schema::FIRST_USER_TABLE_ID {
+                return Err(Error::InvalidArgument("unsupported export system table".into()));
+            }
+            if tables.iter().any(|(_, t): &(DatabaseDesc, TableDesc)| t.id == table.id) {
+                return Err(Error::InvalidArgument(format!("table {} is duplicated", spec.table)));
+            }
+            tables.push((db, table));
+        }
+        let dest = self.export_path(dest)?;
+        let dest = dest.as_path();
+        std::fs::create_dir_all(dest)?;
+        if dest.join(MANIFEST_FILE).exists() {
+            return Err(Error::AlreadyExists(format!("export {}", dest.display())));
+        }
+
+        // The txns committed before are visible at the read version.
+        let read_version = self.alloc_txn_id(1).await?;
+        let mut pin = ExportPin {
+            id: format!("export-{read_version}"),
+            version: read_version,
+            table_ids: tables.iter().map(|(_, table)| table.id).collect(),
+            renewed_at: Instant::now(),
+        };
+        let group_ids = pinned_groups(&schema, &pin.table_ids).await?;
+        let mut manifest = ExportManifest { read_version, ..Default::default() };
+        let result = async {
+            self.pin_gc(&schema, &pin, EXPORT_PIN_LEASE, group_ids).await?;
+            info!(
+                "export {} tables at version {read_version} into {}",
+                tables.len(),
+                dest.display()
+            );
+            for (db, table) in tables {
+                let exported = self.dump_table(&schema, &mut pin, &table, dest).await?;
+                manifest.tables.push(ExportedTable {
+                    database: db.name,
+                    table: Some(table),
+                    ..exported
+                });
+            }
+            Ok::<_, Error>(())
+        }
+        .await;
+        // The pin expires by itself if it is failed to remove.
+        if let Err(err) = self.pin_gc(&schema, &pin, Duration::ZERO, vec![]).await {
+            warn!("unpin gc {}: {err}", pin.id);
+        }
+        result?;
+
+        let mut file = File::create(dest.join(MANIFEST_FILE))?;
+        file.write_all(&manifest.encode_to_vec())?;
+        file.sync_all()?;
+        info!("export at version {read_version} into {} is finished", dest.display());
+        Ok(manifest)
+    }
+
+    /// Restore the tables exported into the `src` directory relative to the
+    /// export directory together, either all of them are restored or none is.
+    pub async fn import_tables(&self, src: &str) -> Result<ImportTablesResponse> {
+        self.check_catalog_writable()?;
+        let src = self.export_path(src)?;
+        let src = src.as_path();
+        let manifest = ExportManifest::decode(std::fs::read(src.join(MANIFEST_FILE))?.as_slice())?;
+        let schema = self.schema()?;
+        let mut tables = Vec::with_capacity(manifest.tables.len());
+        for exported in &manifest.tables {
+            let desc = exported
+                .table
+                .as_ref()
+                .ok_or_else(|| Error::InvalidData(format!("export table {}", exported.file)))?;
+            let db = schema
+                .get_database(&exported.database)
+                .await?
+                .ok_or_else(|| Error::DatabaseNotFound(exported.database.clone()))?;
+            if schema.get_table(db.id, &desc.name).await?.is_some() {
+                return Err(Error::AlreadyExists(format!("table {}", desc.name)));
+            }
+            tables.push((db, desc, exported));
+        }
+
+        let mut created = Vec::with_capacity(tables.len());
+        let result = async {
+            for (db, desc, exported) in tables {
+                let table = self.create_imported_table(&db, desc).await?;
+                created.push((db, table.clone()));
+                self.ingest_dump(&schema, &table, &src.join(&exported.file)).await?;
+            }
+            Ok::<_, Error>(())
+        }
+        .await;
+        if let Err(err) = result {
+            warn!("import from {} is failed, remove the imported tables: {err}", src.display());
+            for (db, table) in &created {
+                if let Err(err) = self.delete_table(&table.name, db).await {
+                    warn!("remove the imported table {}: {err}", table.name);
+                }
+            }
+            return Err(err);
+        }
+
+        let mut resp =
+            ImportTablesResponse { read_version: manifest.read_version, ..Default::default() };
+        for (db, table) in created {
+            let properties = HashMap::from([(PROVISIONING.to_owned(), String::default())]);
+            resp.tables.push(self.update_table(&table.name, &db, properties).await?);
+        }
+        info!(
+            "import {} tables exported at version {} is finished",
+            resp.tables.len(),
+            manifest.read_version
+        );
+        Ok(resp)
+    }
+
+    /// Dump the keys of the table visible at the read version into a file of
+    /// the directory. The keys are pulled from the shard covering the next key,
+    /// so the dumping is not affected by the splitting and migrating of the
+    /// shards.
+    async fn dump_table(
+        &self,
+        schema: &Schema,
+        pin: &mut ExportPin,
+        table: &TableDesc,
+        dest: &Path,
+    ) -> Result<ExportedTable> {
+        let transport_manager = &self.shared.transport_manager;
+        let router = transport_manager.router();
+        let route_err = |err: sekas_client::Error| {
+            Error::Rpc(tonic::Status::unavailable(format!("route export table: {err}")))
+        };
+
+        let file = format!("{}-{}.dump", table.id, pin.version);
+        let mut writer = BufWriter::new(File::create(dest.join(&file))?);
+        let mut exported = ExportedTable { file, ..Default::default() };
+        let mut next_key = vec![];
+        loop {
+            if pin.renewed_at.elapsed() >= EXPORT_PIN_LEASE / 3 {
+                pin.renewed_at = Instant::now();
+                let group_ids = pinned_groups(schema, &pin.table_ids).await?;
+                match self.pin_gc(schema, pin, EXPORT_PIN_LEASE, group_ids).await {
+                    Ok(()) => {}
+                    Err(err @ Error::VersionTooOld(..)) => return Err(err),
+                    Err(err) => warn!("renew gc pin {}: {err}", pin.id),
+                }
+            }
+            let (group, shard) = router.find_shard(table.id, &next_key).map_err(route_err)?;
+            let client = transport_manager.build_move_shard_client(group.id);
+            let (value_sets, has_more) =
+                client.pull_snapshot_chunk(shard.id, pin.version, &next_key, None).await?;
+            if let Some(last_key) = value_sets.last().map(|v| v.user_key.clone()) {
+                for value_set in &value_sets {
+                    exported.num_keys += 1;
+                    exported.num_bytes += value_set
+                        .values
+                        .iter()
+                        .map(|v| value_set.user_key.len() + v.content.as_ref().map_or(0, Vec::len))
+                        .sum::<usize>() as u64;
+                    writer.write_all(&value_set.encode_length_delimited_to_vec())?;
+                }
+                // The next key is the immediate successor of the last dumped key.
+                next_key = last_key;
+                next_key.push(0);
+            }
+            if !has_more {
+                let end_key = sekas_schema::shard::end_key(&shard);
+                if end_key.is_empty() {
+                    break;
+                }
+                next_key = end_key;
+            }
+        }
+        writer.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+        Ok(exported)
+    }
+
+    /// Create the table with the properties of the exported one, it is marked
+    /// by [`PROVISIONING`] until all tables are imported.
+    async fn create_imported_table(
+        &self,
+        db: &DatabaseDesc,
+        desc: &TableDesc,
+    ) -> Result<TableDesc> {
+        let mut properties = desc
+            .properties
+            .iter()
+            .filter(|(key, _)| !matches!(key.as_str(), TABLE_TYPE | CLONE_SOURCE))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<HashMap<_, _>>();
+        properties.insert(PROVISIONING.to_owned(), String::default());
+        self.create_table(desc.name.clone(), db.name.clone(), properties, String::default()).await
+    }
+
+    /// Ingest the dump into the new table, which has only one shard.
+    async fn ingest_dump(&self, schema: &Schema, table: &TableDesc, dump: &Path) -> Result<()> {
+        let shards = schema.get_table_shards(table.id).await?;
+        let [(group_id, shard)] = shards.as_slice() else {
+            return Err(Error::InvalidData(format!("shards of table {}", table.id)));
+        };
+
+        let mut client = self.shared.transport_manager.build_move_shard_client(*group_id);
+        let mut reader = BufReader::new(File::open(dump)?);
+        let mut num_keys = 0;
+        loop {
+            let mut value_sets = Vec::with_capacity(INGEST_BATCH_KEYS);
+            while value_sets.len() < INGEST_BATCH_KEYS {
+                let Some(value_set) = read_value_set(&mut reader)? else {
+                    break;
+                };
+                value_sets.push(value_set);
+            }
+            if value_sets.is_empty() {
+                break;
+            }
+            num_keys += value_sets.len();
+            let req = IngestRequest { group_id: *group_id, shard_id: shard.id, value_sets };
+            client.ingest(&req).await?;
+        }
+        info!("import table {} from {} with {num_keys} keys", table.id, dump.display());
+        Ok(())
+    }
+
+    /// Resolve the path relative to the export directory. The absolute paths
+    /// and the paths escaping it are rejected, so the requests never touch
+    /// the files outside of it.
+    fn export_path(&self, path: &str) -> Result<PathBuf> {
+        let Some(export_dir) = self.cfg.export_dir.as_ref() else {
+            return Err(Error::InvalidArgument("the export directory is not configured".into()));
+        };
+        let relative = Path::new(path);
+        if path.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(Error::InvalidArgument(format!(
+                "the export path {path} should be relative to the export directory"
+            )));
+        }
+        Ok(Path::new(export_dir).join(relative))
+    }
+
+    /// Pin the GC watermark of all nodes at the version of the export, see
+    /// [`pin_gc`].
+    async fn pin_gc(
+        &self,
+        schema: &Schema,
+        pin: &ExportPin,
+        lease: Duration,
+        group_ids: Vec<u64>,
+    ) -> Result<()> {
+        let transport_manager = &self.shared.transport_manager;
+        pin_gc(transport_manager, schema, &pin.id, pin.version, lease, group_ids).await
+    }
+}
+
+/// The groups serving the shards of the tables, they are verified by the GC
+/// pins.
+pub(super) async fn pinned_groups(schema: &Schema, table_ids: &[u64]) -> Result<Vec<u64>> {
+    let mut group_ids = vec![];
+    for table_id in table_ids {
+        group_ids.extend(schema.get_table_shards(*table_id).await?.into_iter().map(|(id, _)| id));
+    }
+    group_ids.sort_unstable();
+    group_ids.dedup();
+    Ok(group_ids)
+}
+
+/// Pin the GC watermark of all nodes at the version, it is removed if the
+/// lease is zero. The groups are verified by the nodes serving them, the pins
+/// of the other nodes are left to the caller if any group is rejected.
+///
+/// The nodes failed to reach are skipped, the reads served by them are still
+/// rejected if the versions have been collected.
+pub(super) async fn pin_gc(
+    transport_manager: &TransportManager,
+    schema: &Schema,
+    pin_id: &str,
+    version: u64,
+    lease: Duration,
+    group_ids: Vec<u64>,
+) -> Result<()> {
+    let req = PinGcRequest {
+        pin_id: pin_id.to_owned(),
+        version,
+        lease_ms: lease.as_millis() as u64,
+        group_ids,
+    };
+    let nodes = schema.list_node().await?;
+    let results = futures::future::join_all(nodes.iter().map(|node| {
+        let req = req.clone();
+        async move {
+            let client = transport_manager.get_node_client(node.addr.clone())?;
+            Ok::<_, Error>(client.pin_gc(req).await?)
+        }
+    }))
+    .await;
+    let mut too_old = None;
+    for (node, result) in nodes.iter().zip(results) {
+        match result {
+            Ok(_) => {}
+            Err(err @ Error::VersionTooOld(..)) => too_old = Some(err),
+            Err(err) => warn!("pin gc {pin_id} on node {}: {err}", node.id),
+        }
+    }
+    too_old.map_or(Ok(()), Err)
+}
+
+/// Read a length-delimited value set of the dump, `None` if the dump is
+/// exhausted.
+fn read_value_set(reader: &mut impl Read) -> Result<Option<ValueSet>> {
+    let mut len = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8; 1];
+        if reader.read(&mut byte)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(Error::InvalidData("the dump is truncated".into()));
+        }
+        len |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            let mut buf = vec![0; len as usize];
+            reader.read_exact(&mut buf)?;
+            return Ok(Some(ValueSet::decode(buf.as_slice())?));
+        }
+    }
+    Err(Error::InvalidData("the length of value set overflows".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_dumped_value_sets() {
+        let value_sets = (0..200u64)
+            .map(|i| ValueSet {
+                user_key: i.to_be_bytes().to_vec(),
+                values: vec![Value {
+                    content: Some(vec![0xab; i as usize]),
+                    version: i + 1,
+                    ..Default::default()
+                }],
+            })
+            .collect::<Vec<_>>();
+        let mut dump = vec![];
+        for value_set in &value_sets {
+            dump.extend(value_set.encode_length_delimited_to_vec());
+        }
+
+        let mut reader = dump.as_slice();
+        let mut read = vec![];
+        while let Some(value_set) = read_value_set(&mut reader).unwrap() {
+            read.push(value_set);
+        }
+        assert_eq!(read, value_sets);
+
+        dump.pop();
+        let mut reader = dump.as_slice();
+        let mut result = Ok(None);
+        for _ in 0..value_sets.len() {
+            result = read_value_set(&mut reader);
+        }
+        assert!(result.is_err(), "{result:?}");
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The health alerts of cluster.
+//!
+//! An alert is raised when root finds a state it refuses to handle by itself,
+//! such as two groups claim the same key range, or a shard is dominated by a
+//! single key so splitting it won't spread the load. The alerts are kept in
+//! memory of the root leader until the conflicts are gone, they are shown by
+//! the statement `SHOW alerts`. The violations of the shard coverage of tables
+//! are found by the periodic verification, see `super::coverage`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use log::{error, info};
+use sekas_runtime::time::timestamp_millis;
+
+use super::metrics::CLUSTER_HEALTH_ALERTS;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum HealthAlert {
+    /// The range of shard claimed by the group overlaps with the shard of
+    /// another group in catalog, the descriptor of the group is refused.
+    ShardConflict { group_id: u64, shard_id: u64, conflict_group_id: u64, conflict_shard_id: u64 },
+    /// The requests of shard are dominated by a single key, the shard is not
+    /// split since it won't spread the load. The key is escaped.
+    HotKey { group_id: u64, shard_id: u64, key: String },
+    /// No shard of the table claims the range. The keys are escaped, the empty
+    /// end means the end of key space.
+    ShardGap {
+        table_id: u64,
+        start: String,
+        end: String,
+        prev_shard_id: Option<u64>,
+        next_shard_id: Option<u64>,
+    },
+    /// The range is claimed by two shards of the table.
+    ShardOverlap {
+        table_id: u64,
+        start: String,
+        end: String,
+        shard_id: u64,
+        group_id: u64,
+        overlap_shard_id: u64,
+        overlap_group_id: u64,
+    },
+    /// The shard is routed to the group, but the descriptor of the group with
+    /// the same epoch doesn't list it.
+    ShardNotInGroup { table_id: u64, shard_id: u64, group_id: u64 },
+    /// The shard is listed by the descriptors of several groups, and it is not
+    /// being moved between them.
+    ShardInMultipleGroups { table_id: u64, shard_id: u64, group_ids: Vec<u64> },
+    /// Applying the entry panics the replica, it stops applying entries until
+    /// the quarantine is resolved by the node admin request. The replica with
+    /// corrupted storage is rebuilt on another node instead.
+    ReplicaQuarantined { group_id: u64, replica_id: u64, node_id: u64, index: u64, corrupted: bool },
+}
+
+#[derive(Default)]
+pub(crate) struct ClusterHealth {
+    /// The alerts and the timestamp (in millis) they are raised.
+    alerts: Mutex<HashMap<HealthAlert, u64>>,
+}
+
+impl HealthAlert {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            HealthAlert::ShardConflict { .. } => "shard_conflict",
+            HealthAlert::HotKey { .. } => "hot_key",
+            HealthAlert::ShardGap { .. } => "shard_gap",
+            HealthAlert::ShardOverlap { .. } => "shard_overlap",
+            HealthAlert::ShardNotInGroup { .. } => "shard_not_in_group",
+            HealthAlert::ShardInMultipleGroups { .. } => "shard_in_multiple_groups",
+            HealthAlert::ReplicaQuarantined { .. } => "replica_quarantined",
+        }
+    }
+
+    /// Whether the alert is raised by the verification of shard coverage.
+    pub(crate) fn is_coverage(&self) -> bool {
+        matches!(
+            self,
+            HealthAlert::ShardGap { .. }
+                | HealthAlert::ShardOverlap { .. }
+                | HealthAlert::ShardNotInGroup { .. }
+                | HealthAlert::ShardInMultipleGroups { .. }
+        )
+    }
+}
+
+fn display_start(key: &str) -> &str {
+    if key.is_empty() {
+        "MIN"
+    } else {
+        key
+    }
+}
+
+fn display_end(key: &str) -> &str {
+    if key.is_empty() {
+        "MAX"
+    } else {
+        key
+    }
+}
+
+impl std::fmt::Display for HealthAlert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthAlert::ShardConflict {
+                group_id,
+                shard_id,
+                conflict_group_id,
+                conflict_shard_id,
+            } => write!(
+                f,
+                "the shard {shard_id} of group {group_id} overlaps with the shard {conflict_shard_id} of group {conflict_group_id}"
+            ),
+            HealthAlert::HotKey { group_id, shard_id, key } => write!(
+                f,
+                "the key {key} dominates the requests of shard {shard_id} of group {group_id}, the shard is not split"
+            ),
+            HealthAlert::ShardGap { table_id, start, end, prev_shard_id, next_shard_id } => write!(
+                f,
+                "the range [{}, {}) of table {table_id} is not claimed by any shard, the previous shard {prev_shard_id:?}, the next shard {next_shard_id:?}",
+                display_start(start),
+                display_end(end),
+            ),
+            HealthAlert::ShardOverlap {
+                table_id,
+                start,
+                end,
+                shard_id,
+                group_id,
+                overlap_shard_id,
+                overlap_group_id,
+            } => write!(
+                f,
+                "the range [{}, {}) of table {table_id} is claimed by the shard {shard_id} of group {group_id} and the shard {overlap_shard_id} of group {overlap_group_id}",
+                display_start(start),
+                display_end(end),
+            ),
+            HealthAlert::ShardNotInGroup { table_id, shard_id, group_id } => write!(
+                f,
+                "the shard {shard_id} of table {table_id} is routed to group {group_id}, but the group doesn't list it"
+            ),
+            HealthAlert::ShardInMultipleGroups { table_id, shard_id, group_ids } => write!(
+                f,
+                "the shard {shard_id} of table {table_id} is listed by groups {group_ids:?}"
+            ),
+            HealthAlert::ReplicaQuarantined { group_id, replica_id, node_id, index, corrupted } => {
+                write!(
+                    f,
+                    "the replica {replica_id} of group {group_id} on node {node_id} is quarantined since {}",
+                    if *corrupted {
+                        format!("the local storage is corrupted before applying the entry {index}")
+                    } else {
+                        format!("applying the entry {index} panics")
+                    }
+                )
+            }
+        }
+    }
+}
+
+impl ClusterHealth {
+    /// Raise an alert, it is logged only once until it is resolved.
+    pub(crate) fn raise(&self, alert: HealthAlert) {
+        let mut alerts = self.alerts.lock().unwrap();
+        if alerts.contains_key(&alert) {
+            return;
+        }
+        error!("cluster health alert: {alert}");
+        alerts.insert(alert, timestamp_millis());
+        CLUSTER_HEALTH_ALERTS.set(alerts.len() as i64);
+    }
+
+    /// Resolve the alerts raised by the descriptor of the group, since a
+    /// descriptor without conflicts is accepted.
+    pub(crate) fn resolve_group(&self, group_id: u64) {
+        let mut alerts = self.alerts.lock().unwrap();
+        alerts.retain(|alert, _| {
+            let retain = !matches!(alert, HealthAlert::ShardConflict { group_id: id, .. } if *id == group_id);
+            if !retain {
+                info!("cluster health alert is resolved: {alert}");
+            }
+            retain
+        });
+        CLUSTER_HEALTH_ALERTS.set(alerts.len() as i64);
+    }
+
+    /// Resolve the quarantine alerts of the replica, since it reports a state
+    /// without quarantine.
+    pub(crate) fn resolve_quarantine(&self, replica_id: u64) {
+        let mut alerts = self.alerts.lock().unwrap();
+        alerts.retain(|alert, _| {
+            let retain = !matches!(alert, HealthAlert::ReplicaQuarantined { replica_id: id, .. } if *id == replica_id);
+            if !retain {
+                info!("cluster health alert is resolved: {alert}");
+            }
+            retain
+        });
+        CLUSTER_HEALTH_ALERTS.set(alerts.len() as i64);
+    }
+
+    /// Replace the hot key alerts with the shards dominated by a single key
+    /// now, the alerts of the cooled shards are resolved.
+    pub(crate) fn refresh_hot_keys(&self, hot_keys: Vec<HealthAlert>) {
+        {
+            let mut alerts = self.alerts.lock().unwrap();
+            alerts.retain(|alert, _| {
+                let retain =
+                    !matches!(alert, HealthAlert::HotKey { .. }) || hot_keys.contains(alert);
+                if !retain {
+                    info!("cluster health alert is resolved: {alert}");
+                }
+                retain
+            });
+            CLUSTER_HEALTH_ALERTS.set(alerts.len() as i64);
+        }
+        for alert in hot_keys {
+            self.raise(alert);
+        }
+    }
+
+    /// Replace the coverage alerts with the violations found by the latest
+    /// verification.
+    pub(crate) fn refresh_coverage(&self, violations: Vec<HealthAlert>) {
+        {
+            let mut alerts = self.alerts.lock().unwrap();
+            alerts.retain(|alert, _| {
+                let retain = !alert.is_coverage() || violations.contains(alert);
+                if !retain {
+                    info!("cluster health alert is resolved: {alert}");
+                }
+                retain
+            });
+            CLUSTER_HEALTH_ALERTS.set(alerts.len() as i64);
+        }
+        for alert in violations {
+            self.raise(alert);
+        }
+    }
+
+    /// The alerts ordered by the time they are raised.
+    pub(crate) fn alerts(&self) -> Vec<(HealthAlert, u64)> {
+        let alerts = self.alerts.lock().unwrap();
+        let mut alerts = alerts.iter().map(|(k, v)| (k.clone(), *v)).collect::<Vec<_>>();
+        alerts.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raise_and_resolve_alerts() {
+        let health = ClusterHealth::default();
+        let alert = HealthAlert::ShardConflict {
+            group_id: 1,
+            shard_id: 2,
+            conflict_group_id: 3,
+            conflict_shard_id: 4,
+        };
+        health.raise(alert.clone());
+        health.raise(alert.clone());
+        assert_eq!(health.alerts().len(), 1);
+
+        health.resolve_group(3);
+        assert_eq!(health.alerts().len(), 1);
+        health.resolve_group(1);
+        assert!(health.alerts().is_empty());
+    }
+
+    #[test]
+    fn resolve_quarantine_alerts() {
+        let health = ClusterHealth::default();
+        let alert = |replica_id| HealthAlert::ReplicaQuarantined {
+            group_id: 1,
+            replica_id,
+            node_id: 1,
+            index: 10,
+            corrupted: false,
+        };
+        health.raise(alert(2));
+        health.raise(alert(3));
+        assert_eq!(health.alerts()[0].0.kind(), "replica_quarantined");
+
+        health.resolve_quarantine(2);
+        let alerts = health.alerts().into_iter().map(|(a, _)| a).collect::<Vec<_>>();
+        assert_eq!(alerts, vec![alert(3)]);
+    }
+
+    #[test]
+    fn refresh_hot_key_alerts() {
+        let health = ClusterHealth::default();
+        let hot_key = |shard_id: u64| HealthAlert::HotKey {
+            group_id: 1,
+            shard_id,
+            key: format!("key-{shard_id}"),
+        };
+        health.refresh_hot_keys(vec![hot_key(1), hot_key(2)]);
+        assert_eq!(health.alerts().len(), 2);
+
+        // The hot key alerts are not resolved by the group descriptor.
+        health.resolve_group(1);
+        assert_eq!(health.alerts().len(), 2);
+
+        health.refresh_hot_keys(vec![hot_key(2)]);
+        let alerts = health.alerts().into_iter().map(|(alert, _)| alert).collect::<Vec<_>>();
+        assert_eq!(alerts, vec![hot_key(2)]);
+        health.refresh_hot_keys(vec![]);
+        assert!(health.alerts().is_empty());
+    }
+
+    #[test]
+    fn refresh_coverage_alerts() {
+        let health = ClusterHealth::default();
+        let hot_key = HealthAlert::HotKey { group_id: 1, shard_id: 1, key: "key".to_owned() };
+        let not_in_group = HealthAlert::ShardNotInGroup { table_id: 1, shard_id: 2, group_id: 3 };
+        health.raise(hot_key.clone());
+        health.refresh_coverage(vec![not_in_group.clone()]);
+        assert_eq!(health.alerts().len(), 2);
+
+        health.refresh_coverage(vec![]);
+        let alerts = health.alerts().into_iter().map(|(alert, _)| alert).collect::<Vec<_>>();
+        assert_eq!(alerts, vec![hot_key]);
+    }
+}
@@ -21,7 +21,7 @@ use std::vec;
 use log::{debug, error, info, trace, warn};
 use sekas_api::server::v1::watch_response::{update_event, UpdateEvent};
 use sekas_api::server::v1::*;
-use tokio::time::Instant;
+use sekas_runtime::time::Instant;
 
 use super::{HeartbeatTask, Root, Schema};
 use crate::constants::ROOT_GROUP_ID;
@@ -69,7 +69,24 @@ impl Root {
                 info: Some(piggyback_request::Info::CollectScheduleState(
                     CollectScheduleStateRequest {},
                 )),
-            })
+            });
+            piggybacks.push(PiggybackRequest {
+                info: Some(piggyback_request::Info::CollectNodeStatus(CollectNodeStatusRequest {})),
+            });
+        }
+
+        // The limits changed by the `CONFIG` statement override the node configs.
+        let transfer_limits = schema.get_transfer_limits().await?;
+        if transfer_limits != ConfigureTransferRequest::default() {
+            piggybacks.push(PiggybackRequest {
+                info: Some(piggyback_request::Info::ConfigureTransfer(transfer_limits)),
+            });
+        }
+        let raft_election = schema.get_raft_election().await?;
+        if raft_election != ConfigureRaftRequest::default() {
+            piggybacks.push(PiggybackRequest {
+                info: Some(piggyback_request::Info::ConfigureRaft(raft_election)),
+            });
         }
 
         let resps = {
@@ -83,13 +100,18 @@ impl Root {
                 );
                 let piggybacks = piggybacks.to_owned();
                 let client = self.shared.transport_manager.get_node_client(node.addr.to_owned())?;
+                let clock_skew = self.clock_skew.clone();
+                let node_id = node.id;
                 let handle = sekas_runtime::spawn(async move {
-                    client
-                        .root_heartbeat(HeartbeatRequest {
-                            piggybacks,
-                            timestamp: 0, // TODO: use hlc
-                        })
-                        .await
+                    let send_nanos = clock_skew.clock().now_nanos();
+                    let timestamp = clock_skew.cluster_now_nanos();
+                    let resp =
+                        client.root_heartbeat(HeartbeatRequest { piggybacks, timestamp }).await;
+                    if let Ok(resp) = &resp {
+                        let recv_nanos = clock_skew.clock().now_nanos();
+                        clock_skew.observe(node_id, send_nanos, resp.timestamp, recv_nanos);
+                    }
+                    resp
                 });
                 handles.push(handle);
             }
@@ -103,7 +125,6 @@ impl Root {
 
         let last_heartbeat = Instant::now();
         let mut heartbeat_tasks = Vec::new();
-        let groups = schema.list_group().await?;
         for (i, resp) in resps.iter().enumerate() {
             let n = nodes.get(i).unwrap();
             match resp {
@@ -112,16 +133,23 @@ impl Root {
                     for resp in &res.piggybacks {
                         match resp.info.as_ref().unwrap() {
                             piggyback_response::Info::SyncRoot(_)
-                            | piggyback_response::Info::CollectMovingShardState(_) => {}
+                            | piggyback_response::Info::CollectMovingShardState(_)
+                            | piggyback_response::Info::ConfigureTransfer(_)
+                            | piggyback_response::Info::ConfigureRaft(_) => {}
                             piggyback_response::Info::CollectStats(ref resp) => {
                                 self.handle_collect_stats(&schema, resp, n.to_owned()).await?
                             }
                             piggyback_response::Info::CollectGroupDetail(ref resp) => {
-                                self.handle_group_detail(&schema, resp, &groups).await?
+                                self.handle_group_detail(&schema, resp).await?
                             }
                             piggyback_response::Info::CollectScheduleState(ref resp) => {
                                 self.handle_schedule_state(resp).await?
                             }
+                            piggyback_response::Info::CollectNodeStatus(ref resp) => {
+                                if let Some(status) = &resp.status {
+                                    self.node_statuses.update(n.id, status.clone());
+                                }
+                            }
                         }
                     }
                 }
@@ -130,6 +158,7 @@ impl Root {
                         .with_label_values(&[&n.id.to_string()])
                         .inc();
                     self.liveness.init_node_if_first_seen(n.id);
+                    self.clock_skew.forget(n.id);
                     warn!("send heartbeat error: {err:?}. node={}, target={}", n.id, n.addr);
                 }
             }
@@ -182,20 +211,25 @@ impl Root {
         &self,
         schema: &Schema,
         resp: &CollectGroupDetailResponse,
-        groups: &[GroupDesc],
     ) -> Result<()> {
         let _timer = super::metrics::HEARTBEAT_HANDLE_GROUP_DETAIL_DURATION_SECONDS.start_timer();
         let mut update_events = Vec::new();
         for desc in &resp.group_descs {
             info!("handle group desc {}", desc.id);
-            if let Some(ex) = groups.iter().find(|g| g.id == desc.id) {
+            // Only the reported groups are read, instead of listing all groups on every
+            // heartbeat.
+            if let Some(ex) = schema.get_group(desc.id).await? {
                 if desc.epoch == ex.epoch {
-                    Self::check_group_desc_consistency(ex, desc);
+                    Self::check_group_desc_consistency(&ex, desc);
+                    self.check_ghost_replicas(&ex, desc).await;
                 }
                 if desc.epoch <= ex.epoch {
                     continue;
                 }
             }
+            if self.check_shard_conflicts(schema, desc).await? {
+                continue;
+            }
             schema.update_group_replica(Some(desc.to_owned()), None).await?;
             metrics::ROOT_UPDATE_GROUP_DESC_TOTAL.heartbeat.inc();
             info!(
@@ -257,6 +291,24 @@ impl Root {
         Ok(())
     }
 
+    /// The replicas in catalog but not in the descriptor reported by the leader
+    /// at the same epoch are ghosts, eg. injected by the unsafe admin requests.
+    /// The scheduler removes them by ChangeReplicas, which bumps the epoch,
+    /// then the catalog is updated by the heartbeat as usual.
+    async fn check_ghost_replicas(&self, exists: &GroupDesc, report: &GroupDesc) {
+        for replica in &exists.replicas {
+            if report.replicas.iter().all(|r| r.id != replica.id) {
+                warn!(
+                    "replica {} of group {} is not in the descriptor of leader at epoch {}",
+                    replica.id,
+                    exists.id,
+                    sekas_api::Epoch(exists.epoch)
+                );
+                self.scheduler.sched_remove_ghost_replica(exists.id, replica).await;
+            }
+        }
+    }
+
     fn check_group_desc_consistency(exists: &GroupDesc, report: &GroupDesc) {
         if exists.shards.len() != report.shards.len() {
             error!(
@@ -86,8 +86,5 @@ impl Liveness {
 }
 
 fn current_timestamp() -> u128 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let start = SystemTime::now();
-    let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap();
-    since_the_epoch.as_millis()
+    sekas_runtime::time::unix_time().as_millis()
 }
@@ -0,0 +1,95 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The admin requests to change the log levels of the nodes at runtime.
+//!
+//! The overrides are applied by the nodes themselves and expire after the
+//! ttl, root only fans out the requests and records them into the topology
+//! event log.
+
+use std::time::Duration;
+
+use log::{info, warn};
+use sekas_api::server::v1::*;
+use sekas_client::DEFAULT_LOG_FILTER_TTL;
+
+use super::Root;
+use crate::{Error, Result};
+
+impl Root {
+    /// Override the log level of a target on the node, or all nodes of the
+    /// cluster if the node is not specified, the effective filters of the
+    /// nodes are returned. Only the effective filters are returned if the
+    /// target is empty.
+    ///
+    /// The nodes failed to override are returned with the reason, instead of
+    /// failing the whole request.
+    pub async fn config_log_filter(
+        &self,
+        node_id: Option<u64>,
+        req: SetLogFilterRequest,
+    ) -> Result<ConfigLogFilterResponse> {
+        let schema = self.schema()?;
+        let nodes = match node_id {
+            Some(node_id) => match schema.get_node(node_id).await? {
+                Some(node) => vec![node],
+                None => return Err(Error::InvalidArgument(format!("node {node_id} not exists"))),
+            },
+            None => schema.list_node().await?,
+        };
+
+        let results = futures::future::join_all(nodes.iter().map(|node| {
+            let req = req.clone();
+            async move {
+                let client = self.shared.transport_manager.get_node_client(node.addr.clone())?;
+                Ok::<_, Error>(client.set_log_filter(req).await?)
+            }
+        }))
+        .await;
+        let mut resp = ConfigLogFilterResponse::default();
+        for (node, result) in nodes.iter().zip(results) {
+            let filter = match result {
+                Ok(filter) => NodeLogFilter { node_id: node.id, filter, ..Default::default() },
+                Err(err) => {
+                    warn!("set log filter of node {}: {err}", node.id);
+                    NodeLogFilter { node_id: node.id, error: err.to_string(), ..Default::default() }
+                }
+            };
+            resp.filters.push(filter);
+        }
+        let node_ids = resp.filters.iter().filter(|f| f.error.is_empty()).map(|f| f.node_id);
+        let node_ids = node_ids.collect::<Vec<_>>();
+        if req.target.is_empty() || node_ids.is_empty() {
+            return Ok(resp);
+        }
+
+        let target = format!("log/{}", req.target);
+        let detail = if req.level.is_empty() {
+            format!("log level of `{}` is reverted on nodes {node_ids:?}", req.target)
+        } else {
+            let ttl = match req.ttl_ms {
+                0 => DEFAULT_LOG_FILTER_TTL,
+                ttl_ms => Duration::from_millis(ttl_ms),
+            };
+            format!(
+                "log level of `{}` is set to {} for {ttl:?} on nodes {node_ids:?}",
+                req.target, req.level
+            )
+        };
+        info!("{detail}");
+        self.record_topology_event(&schema, topology_event::Kind::LogFilter, target, detail)
+            .await?;
+        Ok(resp)
+    }
+}
@@ -21,6 +21,15 @@ lazy_static! {
     pub static ref LEADER_STATE_INFO: IntGauge =
         register_int_gauge!("root_service_node_as_leader_info", "the node as root leader count")
             .unwrap();
+    pub static ref CLUSTER_HEALTH_ALERTS: IntGauge =
+        register_int_gauge!("root_cluster_health_alerts", "the number of cluster health alerts")
+            .unwrap();
+    pub static ref ID_SPACE_REMAINING: GaugeVec = register_gauge_vec!(
+        "root_id_space_remaining",
+        "the number of ids could be allocated for each id type",
+        &["type"]
+    )
+    .unwrap();
 }
 
 // bootstrap root.
@@ -49,6 +58,9 @@ make_static_metric! {
             shed_root_leader,
             create_group,
             split_shard,
+            add_read_replica,
+            remove_read_replica,
+            remove_ghost_replica,
         }
     }
     pub struct ReconcileScheduleHandleTaskDuration: Histogram {
@@ -61,6 +73,9 @@ make_static_metric! {
             shed_group_leaders,
             shed_root_leader,
             split_shard,
+            add_read_replica,
+            remove_read_replica,
+            remove_ghost_replica,
         }
     }
     pub struct ReconcileScheduleCreateGroupStepDuration: Histogram {
@@ -260,6 +275,11 @@ lazy_static! {
     .unwrap();
     pub static ref ROOT_UPDATE_GROUP_DESC_TOTAL: UpdateGroupDesc =
         UpdateGroupDesc::from(&ROOT_UPDATE_GROUP_DESC_TOTAL_VEC);
+    pub static ref ROOT_GROUP_DESC_RESYNC_TOTAL: IntCounter = register_int_counter!(
+        "root_group_desc_resync_total",
+        "The count of the reported group_desc deltas which require a full resync"
+    )
+    .unwrap();
     pub static ref ROOT_UPDATE_REPLICA_STATE_TOTAL_VEC: IntCounterVec = register_int_counter_vec!(
         "root_update_replica_state_total",
         "The count of update replica_state",
@@ -281,3 +301,41 @@ lazy_static! {
     )
     .unwrap();
 }
+
+// catalog mirror
+lazy_static! {
+    pub static ref CATALOG_MIRROR_LAG_EVENTS: IntGauge = register_int_gauge!(
+        "root_catalog_mirror_lag_events",
+        "the number of catalog events the mirror lags behind the primary"
+    )
+    .unwrap();
+    pub static ref CATALOG_MIRROR_LAG_SECONDS: Gauge = register_gauge!(
+        "root_catalog_mirror_lag_seconds",
+        "the seconds since the mirror was in sync with the primary last time"
+    )
+    .unwrap();
+}
+
+// quota
+lazy_static! {
+    pub static ref QUOTA_EXCEEDED_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "root_quota_exceeded_total",
+        "the number of DDL and auto splits exceed the quotas of databases",
+        &["limit"]
+    )
+    .unwrap();
+    pub static ref QUOTA_SKIPPED_SPLIT_SHARD_TOTAL: IntCounter = register_int_counter!(
+        "root_quota_skipped_split_shard_total",
+        "the number of auto splits skipped since the shard quotas are exceeded"
+    )
+    .unwrap();
+}
+
+// provisioning
+lazy_static! {
+    pub static ref PROVISIONING_TABLE_DELETED_TOTAL: IntCounter = register_int_counter!(
+        "root_provisioning_table_deleted_total",
+        "the number of tables deleted since they are still being provisioned after the timeout"
+    )
+    .unwrap();
+}
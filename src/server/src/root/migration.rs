@@ -0,0 +1,109 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use log::warn;
+use sekas_api::server::v1::collect_moving_shard_state_response::State;
+use sekas_api::server::v1::migration_status::Phase;
+use sekas_api::server::v1::*;
+
+use super::Root;
+use crate::{Error, Result};
+
+impl Root {
+    /// List the shard migrations in progress. The progress is collected from
+    /// the leaders of dest groups.
+    pub async fn list_migrations(&self) -> Result<Vec<MigrationStatus>> {
+        let schema = self.schema()?;
+        let mut migrations = Vec::new();
+        for group_state in schema.list_group_state().await? {
+            let group_id = group_state.group_id;
+            let Some(leader) = group_state
+                .leader_id
+                .and_then(|id| group_state.replicas.iter().find(|r| r.replica_id == id))
+            else {
+                continue;
+            };
+            let resp = match self.collect_moving_shard_state(leader.node_id, group_id).await {
+                Ok(resp) => resp,
+                Err(err) => {
+                    warn!("collect moving shard state of group {group_id}: {err:?}");
+                    continue;
+                }
+            };
+            let Some(desc) = resp.desc.filter(|desc| desc.dest_group_id == group_id) else {
+                continue;
+            };
+            let phase = match State::from_i32(resp.state) {
+                Some(State::Prepare) => Phase::Preparing,
+                Some(State::Moving) => Phase::Copying,
+                Some(State::Moved) => Phase::Committing,
+                Some(State::Aborting) => Phase::Cancelling,
+                Some(State::None) | None => continue,
+            };
+            migrations.push(MigrationStatus {
+                desc: Some(desc),
+                phase: phase as i32,
+                copied_keys: resp.moved_keys,
+                copied_bytes: resp.moved_bytes,
+            });
+        }
+        Ok(migrations)
+    }
+
+    /// Get the migration status of the shard, `None` if the shard is not in
+    /// migration.
+    pub async fn migration_status(&self, shard_id: u64) -> Result<Option<MigrationStatus>> {
+        let migrations = self.list_migrations().await?;
+        Ok(migrations.into_iter().find(|m| m.desc.as_ref().unwrap().get_shard_id() == shard_id))
+    }
+
+    /// Cancel the migration of the shard. The migration is rolled back, and it
+    /// is rejected once the migration is committed.
+    pub async fn cancel_migration(&self, shard_id: u64) -> Result<()> {
+        let Some(status) = self.migration_status(shard_id).await? else {
+            return Err(Error::InvalidArgument(format!("shard {shard_id} is not in migration")));
+        };
+        if status.phase == Phase::Committing as i32 {
+            return Err(Error::InvalidArgument(format!(
+                "the migration of shard {shard_id} has been committed"
+            )));
+        }
+
+        let desc = status.desc.unwrap();
+        let mut client = self.shared.transport_manager.build_move_shard_client(desc.dest_group_id);
+        client.cancel_move(&desc).await?;
+        Ok(())
+    }
+
+    async fn collect_moving_shard_state(
+        &self,
+        node_id: u64,
+        group_id: u64,
+    ) -> Result<CollectMovingShardStateResponse> {
+        let client = self.shared.transport_manager.find_node_client(node_id)?;
+        let piggyback = PiggybackRequest {
+            info: Some(piggyback_request::Info::CollectMovingShardState(
+                CollectMovingShardStateRequest { group: group_id },
+            )),
+        };
+        let req = HeartbeatRequest { piggybacks: vec![piggyback], timestamp: 0 };
+        let resp = client.root_heartbeat(req).await?;
+        for piggyback in resp.piggybacks {
+            if let Some(piggyback_response::Info::CollectMovingShardState(resp)) = piggyback.info {
+                return Ok(resp);
+            }
+        }
+        Err(Error::InvalidData("CollectMovingShardStateResponse is required".to_owned()))
+    }
+}
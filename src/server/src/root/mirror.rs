@@ -0,0 +1,475 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The catalog mirror, a standby root which mirrors the databases and tables
+//! of a primary cluster.
+//!
+//! The primary root journals the catalog events notified to the watchers. The
+//! journal is in memory, it has a new incarnation once the root leader is
+//! changed, so the mirror resumes from the seq it has applied if the journal
+//! still retains it, otherwise the stream starts with a sealed checkpoint which
+//! lists the whole catalog. The events following a checkpoint might be covered
+//! by it already, they are idempotent.
+//!
+//! The standby root refuses the mutating requests until it is promoted, which
+//! sets a cluster epoch above the primary, so the mirrors have seen the
+//! promoted root reject the stream of the stale primary. Only the catalog is
+//! mirrored, the shards of the mirrored tables are not created in the standby
+//! cluster.
+
+use std::collections::{HashSet, VecDeque};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_stream::try_stream;
+use futures::{Stream, StreamExt};
+use log::{info, warn};
+use sekas_api::server::v1::watch_response::{delete_event, update_event, DeleteEvent, UpdateEvent};
+use sekas_api::server::v1::*;
+use sekas_client::{ConnManager, RootClient, StaticServiceDiscovery};
+use sekas_runtime::time::Instant;
+use tokio::sync::Notify;
+use tonic::Status;
+
+use super::{metrics, Root, Schema};
+use crate::serverpb::v1::CatalogMirrorState;
+use crate::{Error, Result};
+
+/// The max number of events retained by the catalog journal.
+const MAX_JOURNAL_EVENTS: usize = 4096;
+
+/// The intervals to send a sealed checkpoint to the mirror.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The intervals to reconnect the primary once the stream is broken.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The journal of the catalog events notified to the watchers.
+pub struct CatalogJournal {
+    inner: Mutex<JournalInner>,
+    notify: Notify,
+}
+
+struct JournalInner {
+    incarnation: u64,
+    head_seq: u64,
+    events: VecDeque<JournalEvent>,
+}
+
+#[derive(Clone)]
+struct JournalEvent {
+    seq: u64,
+    updates: Vec<UpdateEvent>,
+    deletes: Vec<DeleteEvent>,
+}
+
+/// Whether the catalog is mirrored from a primary.
+#[derive(Default)]
+pub(super) struct MirrorMode {
+    mirroring: AtomicBool,
+    /// Serializes the applying of the stream and the promotion.
+    apply_lock: futures::lock::Mutex<()>,
+}
+
+pub struct CatalogStream {
+    inner: Pin<Box<dyn Stream<Item = Result<ReplicateCatalogResponse, Status>> + Send>>,
+}
+
+impl Stream for CatalogStream {
+    type Item = Result<ReplicateCatalogResponse, Status>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().inner.poll_next_unpin(cx)
+    }
+}
+
+impl Default for CatalogJournal {
+    fn default() -> Self {
+        CatalogJournal { inner: Mutex::new(JournalInner::new()), notify: Notify::new() }
+    }
+}
+
+impl CatalogJournal {
+    /// Start a new incarnation, because the events notified by the other root
+    /// leaders are not journaled.
+    pub fn reset(&self) {
+        *self.inner.lock().unwrap() = JournalInner::new();
+        self.notify.notify_waiters();
+    }
+
+    /// Journal the events of databases and tables, the others are skipped.
+    pub fn append(&self, updates: &[UpdateEvent], deletes: &[DeleteEvent]) {
+        let updates = updates
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.event,
+                    Some(update_event::Event::Database(_) | update_event::Event::Table(_))
+                )
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let deletes = deletes
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.event,
+                    Some(delete_event::Event::Database(_) | delete_event::Event::Table(_))
+                )
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if updates.is_empty() && deletes.is_empty() {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.head_seq += 1;
+        let seq = inner.head_seq;
+        inner.events.push_back(JournalEvent { seq, updates, deletes });
+        while inner.events.len() > MAX_JOURNAL_EVENTS {
+            inner.events.pop_front();
+        }
+        drop(inner);
+        self.notify.notify_waiters();
+    }
+
+    /// The incarnation and the seq of the last event.
+    fn position(&self) -> (u64, u64) {
+        let inner = self.inner.lock().unwrap();
+        (inner.incarnation, inner.head_seq)
+    }
+
+    /// The events after `from_seq`, `None` if the incarnation is changed or the
+    /// events are not retained.
+    fn read_since(&self, incarnation: u64, from_seq: u64) -> Option<Vec<JournalEvent>> {
+        let inner = self.inner.lock().unwrap();
+        if inner.incarnation != incarnation || from_seq > inner.head_seq {
+            return None;
+        }
+        let first_seq = inner.events.front().map_or(inner.head_seq + 1, |e| e.seq);
+        if from_seq + 1 < first_seq {
+            return None;
+        }
+        Some(inner.events.iter().filter(|e| e.seq > from_seq).cloned().collect())
+    }
+}
+
+impl JournalInner {
+    fn new() -> Self {
+        JournalInner {
+            incarnation: sekas_runtime::sim::rng::random::<u64>().max(1),
+            head_seq: 0,
+            events: VecDeque::new(),
+        }
+    }
+}
+
+impl MirrorMode {
+    #[inline]
+    pub(super) fn is_mirroring(&self) -> bool {
+        self.mirroring.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(super) fn set_mirroring(&self, mirroring: bool) {
+        self.mirroring.store(mirroring, Ordering::Release);
+    }
+}
+
+impl Root {
+    /// Replicate the catalog from the event after `from_seq` of the journal
+    /// incarnation, see [`ReplicateCatalogRequest`]. The stream is ended once
+    /// the root leader is dropped.
+    pub async fn replicate_catalog(
+        &self,
+        incarnation: u64,
+        from_seq: u64,
+    ) -> Result<CatalogStream> {
+        self.schema()?;
+        let root = self.clone();
+        let inner = try_stream! {
+            let journal = root.watcher_hub().journal();
+            let (mut incarnation, mut seq) = (incarnation, from_seq);
+            let mut cluster_epoch = root.schema()?.cluster_epoch().await?;
+            let mut need_checkpoint = journal.read_since(incarnation, seq).is_none();
+            let mut next_checkpoint = Instant::now() + CHECKPOINT_INTERVAL;
+            loop {
+                let notified = journal.notify.notified();
+                if need_checkpoint || next_checkpoint <= Instant::now() {
+                    let resp = root.catalog_checkpoint().await?;
+                    (incarnation, seq, cluster_epoch) = (resp.incarnation, resp.seq, resp.cluster_epoch);
+                    need_checkpoint = false;
+                    next_checkpoint = Instant::now() + CHECKPOINT_INTERVAL;
+                    yield resp;
+                }
+                let Some(events) = journal.read_since(incarnation, seq) else {
+                    need_checkpoint = true;
+                    continue;
+                };
+                let head_seq = journal.position().1;
+                for event in events {
+                    seq = event.seq;
+                    yield ReplicateCatalogResponse {
+                        incarnation,
+                        seq,
+                        head_seq,
+                        cluster_epoch,
+                        checkpoint: false,
+                        updates: event.updates,
+                        deletes: event.deletes,
+                    };
+                }
+                root.schema()?;
+                let timeout = next_checkpoint.saturating_duration_since(Instant::now());
+                let _ = sekas_runtime::time::timeout(timeout, notified).await;
+            }
+        };
+        Ok(CatalogStream { inner: Box::pin(inner) })
+    }
+
+    /// Build a sealed checkpoint of the catalog. The position is taken before
+    /// listing, so the checkpoint covers all events up to it.
+    async fn catalog_checkpoint(&self) -> Result<ReplicateCatalogResponse> {
+        let schema = self.schema()?;
+        let (incarnation, seq) = self.watcher_hub().journal().position();
+        let cluster_epoch = schema.cluster_epoch().await?;
+        let mut updates = schema
+            .list_database()
+            .await?
+            .into_iter()
+            .map(|desc| UpdateEvent { event: Some(update_event::Event::Database(desc)) })
+            .collect::<Vec<_>>();
+        updates.extend(
+            schema
+                .list_table()
+                .await?
+                .into_iter()
+                .map(|desc| UpdateEvent { event: Some(update_event::Event::Table(desc)) }),
+        );
+        Ok(ReplicateCatalogResponse {
+            incarnation,
+            seq,
+            head_seq: seq,
+            cluster_epoch,
+            checkpoint: true,
+            updates,
+            deletes: vec![],
+        })
+    }
+
+    /// Promote the mirrored catalog to an authoritative one. The mirroring is
+    /// stopped, the new databases and tables are allocated the ids above the
+    /// mirrored ones, and the cluster epoch is set above the primary. Returns
+    /// the new cluster epoch.
+    pub async fn promote_catalog(&self) -> Result<u64> {
+        let _guard = self.mirror.apply_lock.lock().await;
+        let schema = self.schema()?;
+        if !self.mirror.is_mirroring() {
+            return Err(Error::InvalidArgument(
+                "the catalog is not mirrored from a primary".into(),
+            ));
+        }
+        let mut state = schema.get_catalog_mirror_state().await?.unwrap_or_default();
+        let cluster_epoch = std::cmp::max(state.primary_epoch, schema.cluster_epoch().await?) + 1;
+        state.promoted = true;
+        schema.promote_catalog(cluster_epoch, &state).await?;
+        self.mirror.set_mirroring(false);
+        metrics::CATALOG_MIRROR_LAG_EVENTS.set(0);
+        metrics::CATALOG_MIRROR_LAG_SECONDS.set(0.0);
+        info!("the mirrored catalog is promoted, cluster epoch {cluster_epoch}");
+        Ok(cluster_epoch)
+    }
+
+    /// Refuse the mutating requests while the catalog is mirrored from a
+    /// primary.
+    pub(super) fn check_catalog_writable(&self) -> Result<()> {
+        if self.mirror.is_mirroring() {
+            let primary = self.cfg.catalog_mirror_of.as_deref().unwrap_or_default();
+            return Err(Error::PermissionDenied(format!(
+                "the catalog is mirrored from {primary}, promote it before mutating"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Mirror the catalog of the primary, until the root leader is dropped or
+    /// the catalog is promoted.
+    pub(super) async fn run_catalog_mirror(&self, primary: String) {
+        let discovery = Arc::new(StaticServiceDiscovery::new(vec![primary.clone()]));
+        let client = RootClient::new(discovery, ConnManager::new());
+        let mut synced_at = Instant::now();
+        while self.mirror.is_mirroring() {
+            if let Err(err) = self.mirror_catalog(&client, &mut synced_at).await {
+                warn!("mirror catalog from {primary}: {err:?}");
+            }
+            if self.mirror.is_mirroring() {
+                metrics::CATALOG_MIRROR_LAG_SECONDS.set(synced_at.elapsed().as_secs_f64());
+            }
+            sekas_runtime::time::sleep(RECONNECT_INTERVAL).await;
+        }
+    }
+
+    async fn mirror_catalog(&self, client: &RootClient, synced_at: &mut Instant) -> Result<()> {
+        let schema = self.schema()?;
+        let mut state = schema.get_catalog_mirror_state().await?.unwrap_or_default();
+        let mut stream = client.replicate_catalog(state.incarnation, state.seq).await?;
+        while let Some(resp) = stream.next().await {
+            let resp = resp?;
+            let _guard = self.mirror.apply_lock.lock().await;
+            if !self.mirror.is_mirroring() {
+                return Ok(());
+            }
+            if resp.cluster_epoch < state.primary_epoch {
+                return Err(Error::PermissionDenied(format!(
+                    "the primary is stale, its cluster epoch {} is less than {}",
+                    resp.cluster_epoch, state.primary_epoch
+                )));
+            }
+            apply_catalog_events(&schema, &resp).await?;
+            state.incarnation = resp.incarnation;
+            state.seq = resp.seq;
+            state.primary_epoch = resp.cluster_epoch;
+            schema.set_catalog_mirror_state(&state).await?;
+
+            let lag_events = resp.head_seq.saturating_sub(resp.seq);
+            if lag_events == 0 {
+                *synced_at = Instant::now();
+            }
+            metrics::CATALOG_MIRROR_LAG_EVENTS.set(lag_events as i64);
+            metrics::CATALOG_MIRROR_LAG_SECONDS.set(synced_at.elapsed().as_secs_f64());
+        }
+        Ok(())
+    }
+}
+
+/// Apply the catalog events into the mirrored catalog. The system databases
+/// and tables are skipped, they are same in all clusters.
+async fn apply_catalog_events(schema: &Schema, resp: &ReplicateCatalogResponse) -> Result<()> {
+    let databases = schema.list_database().await?;
+    let tables = schema.list_table().await?;
+    if resp.checkpoint {
+        // Drop the databases and tables not listed by the checkpoint, before the
+        // listed ones are put, since they might share the names.
+        let mut listed = HashSet::new();
+        for event in &resp.updates {
+            match &event.event {
+                Some(update_event::Event::Database(desc)) => listed.insert((true, desc.id)),
+                Some(update_event::Event::Table(desc)) => listed.insert((false, desc.id)),
+                _ => false,
+            };
+        }
+        for db in &databases {
+            if is_user_database(db.id) && !listed.contains(&(true, db.id)) {
+                schema.delete_database(db).await?;
+            }
+        }
+        for table in &tables {
+            if is_user_table(table.id) && !listed.contains(&(false, table.id)) {
+                schema.delete_table(table.clone()).await?;
+            }
+        }
+    }
+
+    for event in &resp.updates {
+        match &event.event {
+            Some(update_event::Event::Database(desc)) if is_user_database(desc.id) => {
+                schema.put_mirrored_database(desc.clone()).await?;
+            }
+            Some(update_event::Event::Table(desc)) if is_user_table(desc.id) => {
+                schema.put_mirrored_table(desc.clone()).await?;
+            }
+            _ => {}
+        }
+    }
+    for event in &resp.deletes {
+        match event.event {
+            Some(delete_event::Event::Database(id)) if is_user_database(id) => {
+                if let Some(db) = databases.iter().find(|db| db.id == id) {
+                    schema.delete_database(db).await?;
+                }
+                // The tables are purged by the primary in background, they are dropped
+                // with the database here.
+                for table in tables.iter().filter(|table| table.db == id) {
+                    schema.delete_table(table.clone()).await?;
+                }
+            }
+            Some(delete_event::Event::Table(id)) if is_user_table(id) => {
+                if let Some(table) = tables.iter().find(|table| table.id == id) {
+                    schema.delete_table(table.clone()).await?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn is_user_database(id: u64) -> bool {
+    id >= sekas_schema::FIRST_USER_DATABASE_ID
+}
+
+#[inline]
+fn is_user_table(id: u64) -> bool {
+    id >= sekas_schema::FIRST_USER_TABLE_ID
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_event(id: u64) -> UpdateEvent {
+        let desc = TableDesc { id, ..Default::default() };
+        UpdateEvent { event: Some(update_event::Event::Table(desc)) }
+    }
+
+    #[test]
+    fn catalog_journal_resume() {
+        let journal = CatalogJournal::default();
+        let (incarnation, seq) = journal.position();
+        assert_eq!(seq, 0);
+        assert!(journal.read_since(incarnation, 0).unwrap().is_empty());
+
+        // The events of nodes and groups are not journaled.
+        let node = UpdateEvent { event: Some(update_event::Event::Node(NodeDesc::default())) };
+        journal.append(&[node], &[]);
+        assert_eq!(journal.position().1, 0);
+
+        journal.append(&[table_event(1)], &[]);
+        let delete = DeleteEvent { event: Some(delete_event::Event::Table(1)) };
+        journal.append(&[], &[delete]);
+        let events = journal.read_since(incarnation, 0).unwrap();
+        assert_eq!(events.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(journal.read_since(incarnation, 1).unwrap().len(), 1);
+        assert!(journal.read_since(incarnation, 2).unwrap().is_empty());
+        assert!(journal.read_since(incarnation, 3).is_none());
+        assert!(journal.read_since(incarnation + 1, 0).is_none());
+
+        // The truncated events could not be resumed.
+        for id in 0..MAX_JOURNAL_EVENTS as u64 {
+            journal.append(&[table_event(id)], &[]);
+        }
+        assert!(journal.read_since(incarnation, 0).is_none());
+        assert!(journal.read_since(incarnation, 1).is_none());
+        assert_eq!(journal.read_since(incarnation, 2).unwrap().len(), MAX_JOURNAL_EVENTS);
+
+        journal.reset();
+        assert_ne!(journal.position().0, incarnation);
+        assert!(journal.read_since(incarnation, 2).is_none());
+    }
+}
@@ -15,15 +15,32 @@
 
 mod allocator;
 mod bg_job;
+mod clock;
+mod clone;
 mod collector;
+mod compact;
+mod coverage;
+mod export;
+mod health;
 mod heartbeat;
 mod liveness;
+mod log_filter;
 mod metrics;
+mod migration;
+mod mirror;
+mod node_status;
+mod page;
+mod provision;
+mod quota;
+mod recommend;
 mod schedule;
 mod schema;
+mod split;
 mod stats;
 mod stmt_executor;
 mod store;
+mod txn_admin;
+mod unsafe_admin;
 mod watch;
 
 use std::collections::*;
@@ -32,21 +49,25 @@ use std::sync::*;
 use std::task::Poll;
 use std::time::Duration;
 
-use log::{error, info, trace, warn};
+use log::{debug, error, info, trace, warn};
 use schedule::BackgroundJob;
 use sekas_api::server::v1::report_request::GroupUpdates;
 use sekas_api::server::v1::watch_response::*;
 use sekas_api::server::v1::*;
-use sekas_rock::time::timestamp_nanos;
+use sekas_runtime::time::{delay_queue, timestamp_millis, timestamp_nanos, Instant};
 use sekas_runtime::TaskGroup;
-use sekas_schema::shard::{SHARD_MAX, SHARD_MIN};
-use tokio::time::Instant;
-use tokio_util::time::delay_queue;
+use sekas_schema::shard::ShardDescBuilder;
 
 use self::allocator::SysAllocSource;
 use self::bg_job::Jobs;
+use self::clock::ClockSkewTracker;
 pub use self::collector::RootCollector;
 use self::diagnosis::Metadata;
+use self::health::{ClusterHealth, HealthAlert};
+pub use self::mirror::CatalogStream;
+use self::mirror::MirrorMode;
+use self::node_status::NodeStatusCache;
+pub use self::page::{group_page_token, node_page_token, replica_state_page_token, Page};
 use self::schedule::ReconcileScheduler;
 pub(crate) use self::schema::*;
 use self::stats::ClusterStats;
@@ -56,7 +77,11 @@ use crate::constants::ROOT_GROUP_ID;
 use crate::node::{Node, Replica, ReplicaRouteTable};
 use crate::serverpb::v1::*;
 use crate::transport::TransportManager;
-use crate::{Config, Error, Result, RootConfig};
+use crate::{compat, Config, Error, Result, RootConfig};
+
+/// The max times to retry an update of table desc which conflicts with the
+/// concurrent updates.
+const MAX_UPDATE_TABLE_RETRIES: usize = 8;
 
 #[derive(Clone)]
 pub struct Root {
@@ -67,7 +92,15 @@ pub struct Root {
     scheduler: Arc<ReconcileScheduler>,
     heartbeat_queue: Arc<HeartbeatQueue>,
     cluster_stats: Arc<ClusterStats>,
+    clock_skew: Arc<ClockSkewTracker>,
+    /// The last status reported by the heartbeat of each node.
+    node_statuses: Arc<NodeStatusCache>,
+    health: Arc<ClusterHealth>,
     jobs: Arc<Jobs>,
+    mirror: Arc<MirrorMode>,
+    /// Serializes the unsafe admin requests, and holds the confirmation
+    /// tokens issued to them, see `unsafe_admin`.
+    unsafe_admin: Arc<futures::lock::Mutex<unsafe_admin::ConfirmTokens>>,
     task_group: TaskGroup,
 }
 
@@ -76,6 +109,7 @@ pub struct RootShared {
     node_ident: NodeIdent,
     local_addr: String,
     cfg_cpu_nums: u32,
+    cfg_labels: Vec<String>,
     core: Mutex<Option<RootCore>>,
     watcher_hub: Arc<WatchHub>,
 }
@@ -123,32 +157,50 @@ impl Root {
     ) -> Self {
         let local_addr = cfg.addr.clone();
         let cfg_cpu_nums = cfg.cpu_nums;
-        let cluster_stats = Arc::new(ClusterStats::default());
+        let cfg_labels = cfg.node.labels.clone();
+        let cluster_stats = ClusterStats::default();
+        #[cfg(any(test, feature = "testing"))]
+        let cluster_stats =
+            cluster_stats.with_split_threshold(cfg.root.testing_knobs.split_threshold);
+        let cluster_stats = Arc::new(cluster_stats);
         let shared = Arc::new(RootShared {
             transport_manager,
             local_addr,
             cfg_cpu_nums,
+            cfg_labels,
             core: Mutex::new(None),
             node_ident: node_ident.to_owned(),
             watcher_hub: Default::default(),
         });
         let liveness =
             Arc::new(liveness::Liveness::new(Duration::from_secs(cfg.root.liveness_threshold_sec)));
-        let info = Arc::new(SysAllocSource::new(shared.clone(), liveness.to_owned()));
+        let node_statuses = Arc::new(NodeStatusCache::default());
+        let info = Arc::new(SysAllocSource::new(
+            shared.clone(),
+            liveness.to_owned(),
+            node_statuses.clone(),
+        ));
         let alloc =
             Arc::new(allocator::Allocator::new(info, cluster_stats.clone(), cfg.root.to_owned()));
         let heartbeat_queue = Arc::new(HeartbeatQueue::default());
-        let jobs =
-            Arc::new(Jobs::new(shared.to_owned(), alloc.to_owned(), heartbeat_queue.to_owned()));
+        let jobs = Arc::new(Jobs::new(
+            shared.to_owned(),
+            alloc.to_owned(),
+            heartbeat_queue.to_owned(),
+            cfg.root.testing_knobs.to_owned(),
+        ));
+        let health = Arc::new(ClusterHealth::default());
         let sched_ctx = schedule::ScheduleContext::new(
             shared.clone(),
             alloc.clone(),
             heartbeat_queue.clone(),
             cluster_stats.clone(),
+            health.clone(),
             jobs.to_owned(),
             cfg.root.to_owned(),
         );
         let scheduler = Arc::new(schedule::ReconcileScheduler::new(sched_ctx));
+        let clock_skew = Arc::new(ClockSkewTracker::new(&cfg.node.clock));
         Root {
             cfg: cfg.root,
             alloc,
@@ -157,7 +209,12 @@ impl Root {
             scheduler,
             heartbeat_queue,
             cluster_stats,
+            clock_skew,
+            node_statuses,
+            health,
             jobs,
+            mirror: Arc::default(),
+            unsafe_admin: Arc::default(),
             task_group: TaskGroup::default(),
         }
     }
@@ -216,6 +273,7 @@ impl Root {
                     .step_leader(
                         &self.shared.local_addr,
                         self.shared.cfg_cpu_nums,
+                        &self.shared.cfg_labels,
                         root_replica,
                         &mut bootstrapped,
                     )
@@ -270,6 +328,7 @@ impl Root {
         &self,
         local_addr: &str,
         cfg_cpu_nums: u32,
+        cfg_labels: &[String],
         root_replica: Arc<Replica>,
         bootstrapped: &mut bool,
     ) -> Result<()> {
@@ -281,7 +340,8 @@ impl Root {
         // not.
         if !*bootstrapped {
             let cluster_id = self.shared.node_ident.cluster_id.clone();
-            if let Err(err) = schema.try_bootstrap_root(local_addr, cfg_cpu_nums, cluster_id).await
+            if let Err(err) =
+                schema.try_bootstrap_root(local_addr, cfg_cpu_nums, cfg_labels, cluster_id).await
             {
                 metrics::BOOTSTRAP_FAIL_TOTAL.inc();
                 error!("boostrap: {err:?}");
@@ -290,6 +350,26 @@ impl Root {
             *bootstrapped = true;
         }
 
+        #[cfg(any(test, feature = "testing"))]
+        if self.cfg.testing_knobs.rollback_id_counters.swap(false, Ordering::AcqRel) {
+            warn!("roll back the id counters for testing");
+            schema.rollback_id_counters().await?;
+        }
+        // The ids are not allocated until the counters are repaired.
+        for (id_type, next_id, repaired_id) in schema.repair_id_counters().await? {
+            warn!(
+                "the {id_type} counter {next_id} falls behind the ids in use, bump it to {repaired_id}"
+            );
+            let detail = format!("bump the {id_type} counter from {next_id} to {repaired_id}");
+            let kind = topology_event::Kind::Repair;
+            self.record_topology_event(&schema, kind, format!("counter/{id_type}"), detail).await?;
+        }
+
+        let mirroring = self.cfg.catalog_mirror_of.is_some()
+            && !schema.get_catalog_mirror_state().await?.is_some_and(|state| state.promoted);
+        self.mirror.set_mirroring(mirroring);
+        self.watcher_hub().journal().reset();
+
         let max_txn_id = schema.max_txn_id().await?;
         let root_core = RootCore {
             schema: Arc::new(schema.to_owned()),
@@ -300,7 +380,9 @@ impl Root {
 
         let cloned_root_core = root_core.clone();
         let txn_bumper_handle = sekas_runtime::spawn(async move {
-            const INTERVAL: Duration = Duration::from_secs(30);
+            // Less than the window reserved by `bump_txn_id`, so the txn ids keep
+            // up with the wall clock.
+            const INTERVAL: Duration = Duration::from_secs(2);
             loop {
                 sekas_runtime::time::sleep(INTERVAL).await;
                 if let Err(err) = cloned_root_core.bump_txn_id().await {
@@ -320,6 +402,22 @@ impl Root {
         self.heartbeat_queue.enable(true).await;
         self.jobs.on_step_leader().await?;
 
+        let root = self.clone();
+        let coverage_verifier_handle = sekas_runtime::spawn(async move {
+            root.run_coverage_verifier().await;
+        });
+        let root = self.clone();
+        let provisioning_janitor_handle = sekas_runtime::spawn(async move {
+            root.run_provisioning_janitor().await;
+        });
+        let catalog_mirror_handle =
+            self.cfg.catalog_mirror_of.clone().filter(|_| mirroring).map(|primary| {
+                let root = self.clone();
+                sekas_runtime::spawn(async move {
+                    root.run_catalog_mirror(primary).await;
+                })
+            });
+
         let node_id = self.shared.node_ident.node_id;
         info!(
             "node {node_id} step root service leader, heartbeat_interval: {:?}, liveness_threshold: {:?}",
@@ -346,11 +444,16 @@ impl Root {
 
         // After that, RootCore needs to be set to None before returning.
         drop(txn_bumper_handle);
+        drop(coverage_verifier_handle);
+        drop(provisioning_janitor_handle);
+        drop(catalog_mirror_handle);
+        self.watcher_hub().journal().reset();
         // Notify txn allocators to exit.
         root_core.max_txn_id.store(0, Ordering::Release);
         self.heartbeat_queue.enable(false).await;
         self.jobs.on_drop_leader();
         self.cluster_stats.reset();
+        self.node_statuses.reset();
         {
             self.liveness.reset();
 
@@ -463,70 +566,40 @@ impl Root {
     }
 
     pub async fn info(&self) -> Result<Metadata> {
+        use diagnosis::*;
+
         let schema = self.schema()?;
         let nodes = schema.list_node().await?;
-        let groups = schema.list_group().await?;
-        let replicas = groups
-            .iter()
-            .filter(|g| g.id != ROOT_GROUP_ID)
-            .flat_map(|g| g.replicas.iter().map(|r| (r, g.id)).collect::<Vec<_>>())
-            .collect::<Vec<_>>();
-        let states = schema.list_replica_state().await?;
         let dbs = schema.list_database().await?;
         let tables = schema.list_table().await?;
 
-        let balanced = !self.scheduler.need_reconcile().await?;
-
-        use diagnosis::*;
-
-        Ok(Metadata {
-            nodes: nodes
-                .iter()
-                .map(|n| {
-                    let replicas = replicas
-                        .iter()
-                        .filter(|(r, _)| r.node_id == n.id)
-                        .map(|(r, g)| NodeReplica {
+        // The groups are read page by page, with the replica states of each group.
+        let mut groups = Vec::new();
+        let mut node_replicas: HashMap<u64, Vec<NodeReplica>> = HashMap::new();
+        let mut filter = GroupFilter::default();
+        loop {
+            let page = schema.list_group_page(&filter).await?;
+            for g in page.items {
+                let states = schema.group_replica_states(g.id).await?;
+                let state_of = |replica_id| states.iter().find(|s| s.replica_id == replica_id);
+                if g.id != ROOT_GROUP_ID {
+                    for r in &g.replicas {
+                        node_replicas.entry(r.node_id).or_default().push(NodeReplica {
                             id: r.id,
-                            group: g.to_owned(),
+                            group: g.id,
                             replica_role: r.role,
-                            raft_role: states
-                                .iter()
-                                .find(|s| s.replica_id == r.id)
-                                .map(|s| s.role)
-                                .unwrap_or(-1),
-                        })
-                        .collect::<Vec<_>>();
-                    let leaders = replicas
-                        .iter()
-                        .filter(|r| r.raft_role == RaftRole::Leader as i32)
-                        .cloned()
-                        .collect::<Vec<_>>();
-                    Node { id: n.id, addr: n.addr.to_owned(), replicas, leaders, status: n.status }
-                })
-                .collect::<Vec<_>>(),
-            databases: dbs
-                .iter()
-                .map(|d| Database {
-                    id: d.id,
-                    name: d.name.to_owned(),
-                    tables: tables
-                        .iter()
-                        .filter(|c| c.db == d.id)
-                        .map(|c| Table { id: c.id, name: c.name.to_owned() })
-                        .collect::<Vec<_>>(),
-                })
-                .collect::<Vec<_>>(),
-            groups: groups
-                .iter()
-                .map(|g| Group {
+                            raft_role: state_of(r.id).map(|s| s.role).unwrap_or(-1),
+                        });
+                    }
+                }
+                groups.push(Group {
                     id: g.id,
                     epoch: g.epoch,
                     replicas: g
                         .replicas
                         .iter()
                         .map(|r| {
-                            let s = states.iter().find(|s| s.replica_id == r.id);
+                            let s = state_of(r.id);
                             GroupReplica {
                                 id: r.id,
                                 node: r.node_id,
@@ -545,18 +618,68 @@ impl Root {
                             GroupShard { id: s.id, table: s.table_id, range }
                         })
                         .collect::<Vec<_>>(),
+                });
+            }
+            if !page.has_more() {
+                break;
+            }
+            filter.page_token = page.next_page_token;
+        }
+
+        let balanced = !self.scheduler.need_reconcile().await?;
+
+        Ok(Metadata {
+            nodes: nodes
+                .iter()
+                .map(|n| {
+                    let replicas = node_replicas.remove(&n.id).unwrap_or_default();
+                    let leaders = replicas
+                        .iter()
+                        .filter(|r| r.raft_role == RaftRole::Leader as i32)
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    let clock_skew = self.clock_skew.node_skew(n.id).unwrap_or_default();
+                    Node {
+                        id: n.id,
+                        addr: n.addr.to_owned(),
+                        replicas,
+                        leaders,
+                        status: n.status,
+                        clock_skew_ms: clock_skew.as_millis() as u64,
+                        clock_skewed: self.clock_skew.is_node_skewed(n.id),
+                    }
                 })
                 .collect::<Vec<_>>(),
+            databases: dbs
+                .iter()
+                .map(|d| Database {
+                    id: d.id,
+                    name: d.name.to_owned(),
+                    tables: tables
+                        .iter()
+                        .filter(|c| c.db == d.id)
+                        .map(|c| Table { id: c.id, name: c.name.to_owned() })
+                        .collect::<Vec<_>>(),
+                })
+                .collect::<Vec<_>>(),
+            groups,
             balanced,
         })
     }
 }
 
 impl Root {
-    pub async fn create_database(&self, name: String) -> Result<DatabaseDesc> {
+    /// Create a database, the database created by the request with the same
+    /// `request_id` is returned if it exists.
+    pub async fn create_database(&self, name: String, request_id: String) -> Result<DatabaseDesc> {
+        self.check_catalog_writable()?;
         let desc = self
             .schema()?
-            .create_database(DatabaseDesc { name: name.to_owned(), ..Default::default() })
+            .create_database(DatabaseDesc {
+                name: name.to_owned(),
+                request_id,
+                ..Default::default()
+            })
             .await?;
         self.watcher_hub()
             .notify_updates(vec![UpdateEvent {
@@ -568,6 +691,7 @@ impl Root {
     }
 
     pub async fn delete_database(&self, name: &str) -> Result<()> {
+        self.check_catalog_writable()?;
         let db = self.get_database(name).await?;
         if db.is_none() {
             return Err(Error::DatabaseNotFound(name.to_owned()));
@@ -576,34 +700,83 @@ impl Root {
         if db.id == sekas_schema::system::db::ID {
             return Err(Error::InvalidArgument("not support delete system database".into()));
         }
-        self.jobs.submit_purge_database_job(db.id, db.name.to_owned()).await?;
         let schema = self.schema()?;
+        // The tables are purged in background without notifying, so the watchers are
+        // notified with the database.
+        let tables = schema.list_database_tables(db.id).await?;
+        self.jobs.submit_purge_database_job(db.id, db.name.to_owned()).await?;
         let id = schema.delete_database(&db).await?;
-        self.watcher_hub()
-            .notify_deletes(vec![DeleteEvent { event: Some(delete_event::Event::Database(id)) }])
-            .await;
+        schema.quota_usages().remove_database(db.id);
+        let mut deletes = vec![DeleteEvent { event: Some(delete_event::Event::Database(id)) }];
+        deletes.extend(
+            tables
+                .into_iter()
+                .map(|table| DeleteEvent { event: Some(delete_event::Event::Table(table.id)) }),
+        );
+        self.watcher_hub().notify_deletes(deletes).await;
         info!("delete database. database={name}");
         Ok(())
     }
 
-    pub async fn create_table(&self, name: String, database: String) -> Result<TableDesc> {
+    /// Create a table, the `properties` override the default properties of
+    /// user tables. `Error::QuotaExceeded` is returned if the database has
+    /// reached its quota of tables.
+    ///
+    /// The table created by the request with the same `request_id` is returned
+    /// if it exists, or it is waited if the create table job is running.
+    pub async fn create_table(
+        &self,
+        name: String,
+        database: String,
+        properties: HashMap<String, String>,
+        request_id: String,
+    ) -> Result<TableDesc> {
+        self.check_catalog_writable()?;
         let schema = self.schema()?;
         let db = schema
             .get_database(&database)
             .await?
             .ok_or_else(|| Error::DatabaseNotFound(database.to_owned()))?;
 
+        if self.jobs.wait_create_table_job(db.id, &name, &request_id).await? {
+            info!("the running create table job of request {request_id} is finished");
+        }
+        if let Some(table) = schema.get_table(db.id, &name).await? {
+            if request_id.is_empty() || table.request_id != request_id {
+                return Err(Error::AlreadyExists(format!("table {name}")));
+            }
+            // The table might be created by the job resumed by this root leader, which has
+            // not notified the watchers yet.
+            info!("table {name} is created by the request {request_id} before");
+            self.watcher_hub()
+                .notify_updates(vec![UpdateEvent {
+                    event: Some(update_event::Event::Table(table.to_owned())),
+                }])
+                .await;
+            return Ok(table);
+        }
+
+        let permit = quota::check_create_table(&schema, &db).await?;
+        validate_table_properties(&properties)?;
+        self.ensure_user_group().await?;
+        let mut table_properties = sekas_schema::system::table::default_user_properties();
+        table_properties.extend(properties);
+        if let Some(created_at) = table_properties.get_mut(sekas_schema::property::PROVISIONING) {
+            *created_at = timestamp_millis().to_string();
+        }
         let table = schema
             .prepare_create_table(TableDesc {
                 name: name.to_owned(),
                 db: db.id,
-                properties: sekas_schema::system::table::default_user_properties(),
+                properties: table_properties,
+                request_id,
                 ..Default::default()
             })
             .await?;
         info!("prepare create table. database={database}, table={table:?}, table_id={}", table.id);
 
         self.do_create_table(schema.to_owned(), table.to_owned()).await?;
+        permit.created(&table);
 
         self.watcher_hub()
             .notify_updates(vec![UpdateEvent {
@@ -614,17 +787,85 @@ impl Root {
         Ok(table)
     }
 
+    /// Make sure there is a group to place the shards of user tables, since the
+    /// user shards never share the root group with the catalog.
+    async fn ensure_user_group(&self) -> Result<()> {
+        if !self.alloc.place_group_for_shard(1).await?.is_empty() {
+            return Ok(());
+        }
+        if !self.alloc.can_allocate_group().await? {
+            return Err(Error::ResourceExhausted(
+                "cluster not ready for user tables, no user group".into(),
+            ));
+        }
+        info!("no user group exists, create one for user tables");
+        self.jobs.submit_create_group_job().await
+    }
+
     async fn do_create_table(&self, schema: Arc<Schema>, table: TableDesc) -> Result<()> {
         let wait_create = {
-            let range = RangePartition { start: SHARD_MIN.to_owned(), end: SHARD_MAX.to_owned() };
             let id = schema.next_shard_id().await?;
-            vec![ShardDesc { id, table_id: table.id.to_owned(), range: Some(range) }]
+            vec![ShardDesc::whole_range(id, table.id)]
         };
 
         self.jobs.submit_create_table_job(table, wait_create).await
     }
 
+    /// Update the properties of a table, the version of the table desc is
+    /// increased and the new desc is notified to the watchers. The properties
+    /// with empty values are removed.
+    pub async fn update_table(
+        &self,
+        name: &str,
+        database: &DatabaseDesc,
+        properties: HashMap<String, String>,
+    ) -> Result<TableDesc> {
+        self.check_catalog_writable()?;
+        let schema = self.schema()?;
+        let db = schema
+            .get_database(&database.name)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        validate_table_properties(&properties)?;
+        let mut retries = 0;
+        let table = loop {
+            let (mut table, prev_value) = schema
+                .get_table_with_value(db.id, name)
+                .await?
+                .ok_or_else(|| Error::TableNotFound(name.to_owned()))?;
+            if table.id < sekas_schema::FIRST_USER_TABLE_ID {
+                return Err(Error::InvalidArgument("unsupported update system table".into()));
+            }
+            for (key, value) in &properties {
+                if value.is_empty() {
+                    table.properties.remove(key);
+                } else {
+                    table.properties.insert(key.clone(), value.clone());
+                }
+            }
+            table.version += 1;
+            // The desc might be updated concurrently since it is read, apply the properties
+            // to the latest desc again.
+            match schema.update_table_if(table.clone(), prev_value).await {
+                Ok(()) => break table,
+                Err(Error::CasFailed(..)) if retries < MAX_UPDATE_TABLE_RETRIES => {
+                    retries += 1;
+                    debug!("update table {name} conflicts with a concurrent update, retry it");
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Table(table.to_owned())),
+            }])
+            .await;
+        info!("update table, database {}, table={name}, version={}", database.name, table.version);
+        Ok(table)
+    }
+
     pub async fn delete_table(&self, name: &str, database: &DatabaseDesc) -> Result<()> {
+        self.check_catalog_writable()?;
         let schema = self.schema()?;
         let db = self
             .get_database(&database.name)
@@ -638,6 +879,7 @@ impl Root {
             }
             self.jobs.submit_purge_table_job(&db, &table).await?;
             schema.delete_table(table).await?;
+            schema.quota_usages().remove_table(table_id).await;
             self.watcher_hub()
                 .notify_deletes(vec![DeleteEvent {
                     event: Some(delete_event::Event::Table(table_id)),
@@ -648,16 +890,26 @@ impl Root {
         Ok(())
     }
 
+    /// List the databases.
+    ///
+    /// The read is linearizable, the databases created before this call are
+    /// returned even if the root leader has been transferred since then.
     pub async fn list_database(&self) -> Result<Vec<DatabaseDesc>> {
-        self.schema()?.list_database().await
+        self.linearizable_schema().await?.list_database().await
     }
 
+    /// Get the database by name, the read is linearizable like
+    /// [`Root::list_database`].
     pub async fn get_database(&self, name: &str) -> Result<Option<DatabaseDesc>> {
-        self.schema()?.get_database(name).await
+        self.linearizable_schema().await?.get_database(name).await
     }
 
+    /// List the tables of the database.
+    ///
+    /// The read is linearizable, the tables created before this call are
+    /// returned even if the root leader has been transferred since then.
     pub async fn list_table(&self, database: &DatabaseDesc) -> Result<Vec<TableDesc>> {
-        let schema = self.schema()?;
+        let schema = self.linearizable_schema().await?;
         let db = schema
             .get_database(&database.name)
             .await?
@@ -665,16 +917,58 @@ impl Root {
         Ok(schema.list_table().await?.iter().filter(|c| c.db == db.id).cloned().collect::<Vec<_>>())
     }
 
+    /// List a page of the groups matching the filter.
+    pub async fn list_groups(&self, filter: &GroupFilter) -> Result<Page<GroupDesc>> {
+        self.schema()?.list_group_page(filter).await
+    }
+
+    /// List a page of the nodes matching the filter.
+    pub async fn list_nodes(&self, filter: &NodeDescFilter) -> Result<Page<NodeDesc>> {
+        self.schema()?.list_node_page(filter).await
+    }
+
+    /// List a page of the replica states matching the filter.
+    pub async fn list_replica_states(
+        &self,
+        filter: &ReplicaStateFilter,
+    ) -> Result<Page<ReplicaState>> {
+        self.schema()?.list_replica_state_page(filter).await
+    }
+
+    /// Get the stats of the tables of the database, which are aggregated from
+    /// the stats reported by the groups.
+    pub async fn table_stats(&self, database: &DatabaseDesc) -> Result<Vec<TableStats>> {
+        let tables = self.list_table(database).await?;
+        Ok(tables
+            .into_iter()
+            .map(|table| TableStats {
+                name: table.name,
+                ..self.cluster_stats.get_table_stats(table.id)
+            })
+            .collect())
+    }
+
+    /// Get the table by name, the read is linearizable like
+    /// [`Root::list_table`].
     pub async fn get_table(
         &self,
         name: &str,
         database: &DatabaseDesc,
     ) -> Result<Option<TableDesc>> {
-        let db = self
+        let schema = self.linearizable_schema().await?;
+        let db = schema
             .get_database(&database.name)
             .await?
             .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
-        self.schema()?.get_table(db.id, name).await
+        schema.get_table(db.id, name).await
+    }
+
+    /// Returns the schema once the local root replica has applied all entries
+    /// committed before this call, so the reads of it are linearizable.
+    pub(super) async fn linearizable_schema(&self) -> Result<Arc<Schema>> {
+        let schema = self.schema()?;
+        schema.read_index().await?;
+        Ok(schema)
     }
 
     pub async fn watch(&self, cur_groups: HashMap<u64, u64>) -> Result<Watcher> {
@@ -683,21 +977,38 @@ impl Root {
         let watcher = {
             let hub = self.watcher_hub();
             let (watcher, mut initializer) = hub.create_watcher().await;
-            let (updates, deletes) = schema.list_all_events(cur_groups).await?;
-            initializer.set_init_resp(updates, deletes);
+            let (updates, deletes) = schema.list_all_events(cur_groups.clone()).await?;
+            initializer.set_init_resp(cur_groups, updates, deletes);
             watcher
         };
         Ok(watcher)
     }
 
+    /// Validate the compatibility of the joining node, the rejection is
+    /// returned if the node could not be admitted.
+    pub async fn check_join(&self, req: &JoinNodeRequest) -> Result<Option<JoinRejection>> {
+        let cluster_id = self.schema()?.cluster_id().await?.unwrap_or_default();
+        let rejection = compat::check_join(&cluster_id, req);
+        if let Some(rejection) = &rejection {
+            warn!(
+                "reject node {} version {} to join cluster: {}",
+                req.addr,
+                req.version,
+                compat::describe_rejection(rejection)
+            );
+        }
+        Ok(rejection)
+    }
+
     pub async fn join(
         &self,
         addr: String,
         capacity: NodeCapacity,
+        labels: Vec<String>,
     ) -> Result<(Vec<u8>, NodeDesc, RootDesc)> {
         let schema = self.schema()?;
         let node = schema
-            .add_node(NodeDesc { addr, capacity: Some(capacity), ..Default::default() })
+            .add_node(NodeDesc { addr, capacity: Some(capacity), labels, ..Default::default() })
             .await?;
         self.watcher_hub()
             .notify_updates(vec![UpdateEvent {
@@ -719,7 +1030,10 @@ impl Root {
         Ok((cluster_id, node, root))
     }
 
-    pub async fn report(&self, updates: Vec<GroupUpdates>) -> Result<()> {
+    /// Apply the updates reported by nodes, returns the descriptors in catalog
+    /// which are newer than the reported ones, and the groups whose reported
+    /// deltas are not applicable to the catalog.
+    pub async fn report(&self, updates: Vec<GroupUpdates>) -> Result<ReportResponse> {
         // mock report doesn't work.
         // return Ok(());
 
@@ -727,31 +1041,78 @@ impl Root {
         let schema = self.schema()?;
         let mut update_events = Vec::new();
         let mut changed_group_states = Vec::new();
-        for u in updates {
+        let mut newer_group_descs = Vec::new();
+        let mut resync_group_ids = Vec::new();
+        for mut u in updates {
+            let pre_group = if u.group_desc.is_some() || u.group_desc_delta.is_some() {
+                schema.get_group(u.group_id).await?
+            } else {
+                None
+            };
+            if let Some(delta) = u.group_desc_delta.take() {
+                match &pre_group {
+                    Some(pre_group) if pre_group.epoch == delta.base_epoch => {
+                        let mut desc = pre_group.clone();
+                        desc.apply_delta(&delta);
+                        u.group_desc = Some(desc);
+                    }
+                    Some(pre_group) if pre_group.epoch > delta.epoch => {
+                        newer_group_descs.push(pre_group.clone());
+                    }
+                    Some(pre_group) if pre_group.epoch == delta.epoch => {}
+                    _ => {
+                        // There is a gap between the catalog and the delta.
+                        metrics::ROOT_GROUP_DESC_RESYNC_TOTAL.inc();
+                        resync_group_ids.push(u.group_id);
+                    }
+                }
+            }
             let group_desc = if let Some(update_group) = &u.group_desc {
-                match schema.get_group(u.group_id).await? {
-                    Some(pre_group) if pre_group.epoch >= update_group.epoch => None,
+                match pre_group {
+                    Some(pre_group) if pre_group.epoch > update_group.epoch => {
+                        newer_group_descs.push(pre_group);
+                        None
+                    }
+                    Some(pre_group) if pre_group.epoch == update_group.epoch => None,
+                    _ if self.check_shard_conflicts(&schema, update_group).await? => None,
                     _ => u.group_desc,
                 }
             } else {
                 None
             };
 
+            let mut quarantine_changed = false;
             let replica_state = if let Some(update_replica_state) = &u.replica_state {
                 match schema.get_replica_state(u.group_id, update_replica_state.replica_id).await? {
-                    Some(pre_rs)
+                    Some(mut pre_rs)
                         if pre_rs.term > update_replica_state.term
                             || (pre_rs.term == update_replica_state.term
                                 && pre_rs.role == update_replica_state.role) =>
                     {
-                        None
+                        // The quarantine is accepted even if the raft state is staled.
+                        if pre_rs.quarantine == update_replica_state.quarantine {
+                            None
+                        } else {
+                            quarantine_changed = true;
+                            pre_rs.quarantine = update_replica_state.quarantine.clone();
+                            Some(pre_rs)
+                        }
+                    }
+                    pre_rs => {
+                        quarantine_changed =
+                            pre_rs.and_then(|rs| rs.quarantine) != update_replica_state.quarantine;
+                        u.replica_state
                     }
-                    _ => u.replica_state,
                 }
             } else {
                 None
             };
             schema.update_group_replica(group_desc.to_owned(), replica_state.to_owned()).await?;
+            if quarantine_changed {
+                if let Some(state) = &replica_state {
+                    self.handle_replica_quarantine(&schema, state).await?;
+                }
+            }
 
             if let Some(sched_state) = u.schedule_state {
                 cluster_stats.handle_schedule_update(&[sched_state], None);
@@ -788,7 +1149,85 @@ impl Root {
 
         self.watcher_hub().notify_updates(update_events).await;
 
-        Ok(())
+        Ok(ReportResponse { newer_group_descs, resync_group_ids })
+    }
+
+    /// Raise or resolve the alert of the quarantined replica, the changes of
+    /// the quarantine are recorded into the topology event log. The replica
+    /// with corrupted storage is rebuilt on another node.
+    async fn handle_replica_quarantine(&self, schema: &Schema, state: &ReplicaState) -> Result<()> {
+        let (group_id, replica_id, node_id) = (state.group_id, state.replica_id, state.node_id);
+        let detail = match &state.quarantine {
+            Some(quarantine) => {
+                self.health.raise(HealthAlert::ReplicaQuarantined {
+                    group_id,
+                    replica_id,
+                    node_id,
+                    index: quarantine.index,
+                    corrupted: quarantine.corrupted,
+                });
+                if quarantine.corrupted {
+                    // The corrupted replica never recovers in place.
+                    self.scheduler
+                        .sched_rebuild_replica(schema, group_id, replica_id, node_id)
+                        .await?;
+                }
+                format!(
+                    "group {group_id} node {node_id} quarantined{} at entry {} term {}: {}",
+                    if quarantine.corrupted { " for corruption" } else { "" },
+                    quarantine.index,
+                    quarantine.term,
+                    quarantine.message
+                )
+            }
+            None => {
+                self.health.resolve_quarantine(replica_id);
+                format!("group {group_id} node {node_id} lifted the quarantine")
+            }
+        };
+        let target = format!("replica/{replica_id}");
+        self.record_topology_event(schema, topology_event::Kind::Quarantine, target, detail).await
+    }
+
+    /// Whether the shards of the group overlap with the shards of other groups
+    /// in catalog. The descriptor with conflicts is refused and raised as a
+    /// health alert, rather than routing the traffic to either of them.
+    async fn check_shard_conflicts(&self, schema: &Schema, desc: &GroupDesc) -> Result<bool> {
+        let mut conflicted = false;
+        let mut filter = GroupFilter::default();
+        loop {
+            let page = schema.list_group_page(&filter).await?;
+            for group in &page.items {
+                if group.id == desc.id {
+                    continue;
+                }
+                for shard in &desc.shards {
+                    // The moving shard is claimed by both the source and dest groups.
+                    let conflicts = group.shards.iter().filter(|other| {
+                        other.id != shard.id
+                            && other.table_id == shard.table_id
+                            && sekas_schema::shard::is_overlapped(shard, other)
+                    });
+                    for other in conflicts {
+                        self.health.raise(HealthAlert::ShardConflict {
+                            group_id: desc.id,
+                            shard_id: shard.id,
+                            conflict_group_id: group.id,
+                            conflict_shard_id: other.id,
+                        });
+                        conflicted = true;
+                    }
+                }
+            }
+            if !page.has_more() {
+                break;
+            }
+            filter.page_token = page.next_page_token;
+        }
+        if !conflicted {
+            self.health.resolve_group(desc.id);
+        }
+        Ok(conflicted)
     }
 
     pub async fn alloc_replica(
@@ -796,12 +1235,37 @@ impl Root {
         group_id: u64,
         epoch: u64,
         requested_cnt: u64,
+        purpose: alloc_replica_request::Purpose,
     ) -> Result<Vec<ReplicaDesc>> {
         let schema = self.schema()?;
         let group_desc = schema.get_group(group_id).await?.ok_or(Error::GroupNotFound(group_id))?;
         if group_desc.epoch != epoch {
             return Err(Error::InvalidArgument("epoch not match".to_owned()));
         }
+        // The group applies for the replicas until it is cured, the decisions are
+        // reported by the passes of the scheduler.
+        let kind = match purpose {
+            alloc_replica_request::Purpose::Cure => "cure",
+            alloc_replica_request::Purpose::Promote => "promote",
+        };
+        let action = format!("{kind} group {group_id} by adding {requested_cnt} replicas");
+        let approved_cure = match purpose {
+            alloc_replica_request::Purpose::Cure => {
+                match self.check_cure_group(&schema, group_id, requested_cnt).await {
+                    Ok(approved_cure) => approved_cure,
+                    Err(Error::ResourceExhausted(reason)) => {
+                        self.scheduler.record_decision(
+                            &action,
+                            schedule_decision::Outcome::Recommended,
+                            reason.clone(),
+                        );
+                        return Err(Error::ResourceExhausted(reason));
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            alloc_replica_request::Purpose::Promote => None,
+        };
         let mut existing_replicas =
             group_desc.replicas.into_iter().map(|r| r.node_id).collect::<HashSet<u64>>();
         let replica_states = schema.group_replica_states(group_id).await?;
@@ -816,6 +1280,14 @@ impl Root {
             .await?;
         if nodes.len() != requested_cnt as usize {
             warn!("non enough nodes to allocate replicas, exist nodes: {}, requested: {requested_cnt}", nodes.len());
+            self.scheduler.record_decision(
+                action,
+                schedule_decision::Outcome::Skipped,
+                format!(
+                    "{kind} skipped: not enough healthy nodes, {} of {requested_cnt} available",
+                    nodes.len()
+                ),
+            );
             return Err(Error::ResourceExhausted("no enough nodes".to_owned()));
         }
 
@@ -832,11 +1304,24 @@ impl Root {
             "advise allocate new group {group_id} replicas in nodes: {:?}",
             replicas.iter().map(|r| r.node_id).collect::<Vec<_>>()
         );
+        let reason = match &approved_cure {
+            Some(desc) => format!("recommendation {} is approved", desc.id),
+            None => format!("the group applied for {kind}"),
+        };
+        if let Some(desc) = approved_cure {
+            recommend::finish_recommendation(&schema, desc).await?;
+        }
+        self.scheduler.record_decision(action, schedule_decision::Outcome::Taken, reason);
         Ok(replicas)
     }
 
     pub async fn alloc_txn_id(&self, num_required: u64) -> Result<u64> {
         let root_core = self.shared.root_core()?;
+        if self.clock_skew.is_skewed() {
+            // The clock of root is skewed, refuse to serve as the timestamp
+            // source until the leadership is moved or the clock recovers.
+            return Err(Error::NotLeader(0, 0, None));
+        }
         loop {
             let next_txn_id = root_core.next_txn_id.load(Ordering::Relaxed);
             let max_txn_id = root_core.max_txn_id.load(Ordering::Acquire);
@@ -844,7 +1329,11 @@ impl Root {
                 return Err(Error::NotLeader(0, 0, None));
             }
 
-            if next_txn_id + num_required > max_txn_id {
+            // The txn ids follow the wall clock of root, so the versions could be
+            // used as the timestamps, eg. the expiry of the values.
+            let now = std::cmp::min(timestamp_nanos(), max_txn_id.saturating_sub(num_required));
+            let txn_id = std::cmp::max(next_txn_id, now);
+            if txn_id + num_required > max_txn_id {
                 sekas_runtime::yield_now().await;
                 continue;
             }
@@ -852,33 +1341,18 @@ impl Root {
                 .next_txn_id
                 .compare_exchange(
                     next_txn_id,
-                    next_txn_id + num_required,
+                    txn_id + num_required,
                     Ordering::AcqRel,
                     Ordering::Relaxed,
                 )
                 .is_ok()
             {
                 // TODO(walter) ensure leadership before return.
-                return Ok(next_txn_id);
+                return Ok(txn_id);
             }
         }
     }
 
-    /// List the descripton of groups.
-    pub async fn list_group(&self) -> Result<Vec<GroupDesc>> {
-        self.schema()?.list_group().await
-    }
-
-    /// Get the description of the specified group.
-    pub async fn get_group(&self, group_id: u64) -> Result<Option<GroupDesc>> {
-        self.schema()?.get_group(group_id).await
-    }
-
-    /// List nodes.
-    pub async fn list_node(&self) -> Result<Vec<NodeDesc>> {
-        self.schema()?.list_node().await
-    }
-
     /// Get the cluster stats.
     #[inline]
     pub fn get_cluster_stats(&self) -> &ClusterStats {
@@ -1004,11 +1478,39 @@ impl HeartbeatQueue {
     }
 }
 
+/// Validate the properties specified by users, the unknown properties are
+/// kept as they are.
+fn validate_table_properties(properties: &HashMap<String, String>) -> Result<()> {
+    use sekas_schema::property::*;
+
+    for (key, value) in properties {
+        let valid = match key.as_str() {
+            TABLE_TYPE | CLONE_SOURCE => false,
+            REPLICATION => matches!(value.as_str(), REPLICATION_MAJORITY | REPLICATION_ASYNC),
+            REPLICAS_PER_GROUP => value.parse::<u64>().map(|v| v > 0).unwrap_or_default(),
+            READ_REPLICAS => value.parse::<u64>().is_ok(),
+            MAX_VERSIONS | TTL => value.parse::<u64>().map(|v| v > 0).unwrap_or_default(),
+            LEADER_PREFERENCE => !value.contains(|c: char| c.is_whitespace() || c == ','),
+            // The value is filled by root, the empty value marks the table on creating, and
+            // clears the mark on updating.
+            PROVISIONING => value.is_empty(),
+            _ => true,
+        };
+        if !valid {
+            return Err(Error::InvalidArgument(format!("table property {key}={value}")));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod root_test {
+    use std::collections::HashMap;
+
     use futures::StreamExt;
+    use prost::Message;
     use sekas_api::server::v1::watch_response::{update_event, UpdateEvent};
-    use sekas_api::server::v1::{DatabaseDesc, GroupDesc};
+    use sekas_api::server::v1::*;
     use sekas_rock::fn_name;
     use tempdir::TempDir;
 
@@ -1017,7 +1519,7 @@ mod root_test {
     use crate::constants::{INITIAL_EPOCH, ROOT_GROUP_ID};
     use crate::engine::Engines;
     use crate::node::Node;
-    use crate::root::Root;
+    use crate::root::{Root, WatchHub};
     use crate::serverpb::v1::NodeIdent;
     use crate::transport::TransportManager;
 
@@ -1025,7 +1527,8 @@ mod root_test {
         let engines = Engines::open(&config.root_dir, &config.db).unwrap();
         let root_list =
             if config.init { vec![config.addr.clone()] } else { config.join_list.clone() };
-        let transport_manager = TransportManager::new(root_list, engines.state()).await;
+        let transport_manager =
+            TransportManager::new(root_list, engines.state(), None).await.unwrap();
         let root = Root::new(transport_manager.clone(), node_ident, config.clone());
         let node = Node::new(config.clone(), engines, transport_manager).await.unwrap();
         (root, node)
@@ -1038,7 +1541,7 @@ mod root_test {
         let ident = NodeIdent { cluster_id: vec![], node_id: 1 };
 
         let (root, node) = create_root_and_node(&config, &ident).await;
-        bootstrap_cluster(&node, "0.0.0.0:8888").await.unwrap();
+        bootstrap_cluster(&node, "0.0.0.0:8888", None).await.unwrap();
         node.bootstrap(&ident).await.unwrap();
         root.bootstrap(&node).await.unwrap();
         // TODO: test on leader logic later.
@@ -1068,11 +1571,18 @@ mod root_test {
         let config = Config { root_dir: tmp_dir.path().to_owned(), ..Default::default() };
         let (root, _node) = create_root_and_node(&config, &ident).await;
         let hub = root.watcher_hub();
-        let _create_db1_event =
-            Some(update_event::Event::Database(DatabaseDesc { id: 1, name: "db1".into() }));
+        let _create_db1_event = Some(update_event::Event::Database(DatabaseDesc {
+            id: 1,
+            name: "db1".into(),
+            ..Default::default()
+        }));
         let mut w = {
             let (w, mut initializer) = hub.create_watcher().await;
-            initializer.set_init_resp(vec![UpdateEvent { event: _create_db1_event }], vec![]);
+            initializer.set_init_resp(
+                HashMap::default(),
+                vec![UpdateEvent { event: _create_db1_event }],
+                vec![],
+            );
             w
         };
         let resp1 = w.next().await.unwrap().unwrap();
@@ -1083,8 +1593,11 @@ mod root_test {
             w
         };
 
-        let _create_db2_event =
-            Some(update_event::Event::Database(DatabaseDesc { id: 2, name: "db2".into() }));
+        let _create_db2_event = Some(update_event::Event::Database(DatabaseDesc {
+            id: 2,
+            name: "db2".into(),
+            ..Default::default()
+        }));
         hub.notify_updates(vec![UpdateEvent { event: _create_db2_event }]).await;
         let resp2 = w.next().await.unwrap().unwrap();
         assert!(matches!(&resp2.updates[0].event, _create_db2_event));
@@ -1092,6 +1605,133 @@ mod root_test {
         assert!(matches!(&resp22.updates[0].event, _create_db2_event));
         // hub.notify_error(Error::NotRootLeader(vec![])).await;
     }
+
+    fn group_event(desc: &GroupDesc) -> UpdateEvent {
+        UpdateEvent { event: Some(update_event::Event::Group(desc.clone())) }
+    }
+
+    fn sorted(mut desc: GroupDesc) -> GroupDesc {
+        desc.shards.sort_by_key(|s| s.id);
+        desc.replicas.sort_by_key(|r| r.id);
+        desc
+    }
+
+    async fn create_group_watcher(hub: &WatchHub, desc: Option<&GroupDesc>) -> super::Watcher {
+        let (w, mut initializer) = hub.create_watcher().await;
+        initializer.set_init_resp(
+            HashMap::default(),
+            desc.into_iter().map(group_event).collect(),
+            vec![],
+        );
+        w
+    }
+
+    #[sekas_macro::test]
+    async fn watch_group_desc_deltas() {
+        const NUM_SHARDS: u64 = 500;
+        const NUM_EPOCH_BUMPS: u64 = 20;
+
+        let shard_key = |id: u64| id.to_be_bytes().to_vec();
+        let mut desc = GroupDesc {
+            id: 1,
+            epoch: INITIAL_EPOCH,
+            shards: (0..NUM_SHARDS)
+                .map(|id| ShardDesc::with_range(id, 1, shard_key(id), shard_key(id + 1)))
+                .collect(),
+            replicas: (1..=3)
+                .map(|id| ReplicaDesc { id, node_id: id, role: ReplicaRole::Voter as i32 })
+                .collect(),
+        };
+        let hub = WatchHub::default();
+        let mut w = create_group_watcher(&hub, Some(&desc)).await;
+        let resp = w.next().await.unwrap().unwrap();
+        assert!(matches!(&resp.updates[0].event, Some(update_event::Event::Group(_))));
+
+        let mut applied = desc.clone();
+        let (mut full_bytes, mut delta_bytes) = (0, 0);
+        for i in 0..NUM_EPOCH_BUMPS {
+            if i % 2 == 0 {
+                // Split a shard.
+                let (parent_id, child_id) = (i, NUM_SHARDS + i);
+                let mut split_key = shard_key(parent_id);
+                split_key.push(0x80);
+                let parent = desc.shard_mut(parent_id).unwrap();
+                parent.range.as_mut().unwrap().end = split_key.clone();
+                desc.shards.push(ShardDesc::with_range(child_id, 1, split_key, shard_key(i + 1)));
+                desc.epoch = sekas_api::apply_shard_delta(desc.epoch);
+            } else {
+                // Move a replica to another node.
+                let replica = &mut desc.replicas[0];
+                replica.id += 10;
+                replica.node_id += 10;
+                desc.epoch = sekas_api::apply_config_delta(desc.epoch);
+            }
+            full_bytes +=
+                WatchResponse { updates: vec![group_event(&desc)], deletes: vec![] }.encoded_len();
+            hub.notify_updates(vec![group_event(&desc)]).await;
+            let resp = w.next().await.unwrap().unwrap();
+            delta_bytes += resp.encoded_len();
+            let Some(update_event::Event::GroupDelta(delta)) = &resp.updates[0].event else {
+                panic!("the group desc delta is expected, but got {:?}", resp.updates[0]);
+            };
+            assert!(applied.apply_delta(delta));
+            assert_eq!(sorted(applied.clone()), sorted(desc.clone()));
+        }
+        assert!(
+            delta_bytes * 20 < full_bytes,
+            "full {full_bytes} bytes, delta {delta_bytes} bytes"
+        );
+
+        // The delta is not applicable to a staled descriptor.
+        let mut staled = desc.clone();
+        staled.epoch -= 1;
+        let delta = desc.delta_since(&staled);
+        assert!(!GroupDesc { epoch: INITIAL_EPOCH, ..desc.clone() }.apply_delta(&delta));
+
+        // The watcher without the base descriptor receives the full descriptor.
+        let mut w2 = create_group_watcher(&hub, None).await;
+        desc.epoch = sekas_api::apply_config_delta(desc.epoch);
+        hub.notify_updates(vec![group_event(&desc)]).await;
+        let resp = w2.next().await.unwrap().unwrap();
+        assert!(matches!(&resp.updates[0].event, Some(update_event::Event::Group(_))));
+        let resp = w.next().await.unwrap().unwrap();
+        assert!(matches!(&resp.updates[0].event, Some(update_event::Event::GroupDelta(_))));
+    }
+
+    #[test]
+    fn validate_table_properties() {
+        use sekas_schema::property::*;
+
+        let properties = |kvs: &[(&str, &str)]| {
+            kvs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        assert!(super::validate_table_properties(&properties(&[
+            (REPLICATION, REPLICATION_ASYNC),
+            (REPLICAS_PER_GROUP, "3"),
+            (READ_REPLICAS, "1"),
+            (TTL, "3600"),
+        ]))
+        .is_ok());
+        assert!(super::validate_table_properties(&properties(&[(TABLE_TYPE, TABLE_TYPE_SYSTEM)]))
+            .is_err());
+        assert!(super::validate_table_properties(&properties(&[(CLONE_SOURCE, "1024")])).is_err());
+        assert!(super::validate_table_properties(&properties(&[(REPLICATION, "all")])).is_err());
+        assert!(
+            super::validate_table_properties(&properties(&[(REPLICAS_PER_GROUP, "0")])).is_err()
+        );
+        assert!(super::validate_table_properties(&properties(&[(READ_REPLICAS, "-1")])).is_err());
+        assert!(super::validate_table_properties(&properties(&[(MAX_VERSIONS, "5")])).is_ok());
+        assert!(super::validate_table_properties(&properties(&[(PROVISIONING, "")])).is_ok());
+        assert!(super::validate_table_properties(&properties(&[(PROVISIONING, "1")])).is_err());
+        assert!(super::validate_table_properties(&properties(&[(MAX_VERSIONS, "0")])).is_err());
+        assert!(super::validate_table_properties(&properties(&[(TTL, "0")])).is_err());
+        assert!(super::validate_table_properties(&properties(&[(TTL, "1h")])).is_err());
+        assert!(super::validate_table_properties(&properties(&[(LEADER_PREFERENCE, "us-east")]))
+            .is_ok());
+        assert!(
+            super::validate_table_properties(&properties(&[(LEADER_PREFERENCE, "a b")])).is_err()
+        );
+    }
 }
 
 pub mod diagnosis {
@@ -1125,6 +1765,12 @@ pub mod diagnosis {
         pub replicas: Vec<NodeReplica>,
         pub leaders: Vec<NodeReplica>,
         pub status: i32,
+        /// The estimated clock skew between node and the cluster.
+        #[serde(default)]
+        pub clock_skew_ms: u64,
+        /// Whether the clock skew of node exceeds the limit.
+        #[serde(default)]
+        pub clock_skewed: bool,
     }
 
     #[derive(Serialize, Deserialize, Clone)]
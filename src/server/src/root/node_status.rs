@@ -0,0 +1,76 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use sekas_api::server::v1::{NodeHealth, NodeRuntimeStatus};
+use sekas_runtime::time::Instant;
+
+/// The last status reported by the heartbeat of each node, so that showing the
+/// nodes doesn't fan out to them.
+#[derive(Default)]
+pub(crate) struct NodeStatusCache {
+    statuses: Mutex<HashMap<u64, (NodeRuntimeStatus, Instant)>>,
+}
+
+impl NodeStatusCache {
+    /// Record the status reported by the node.
+    pub(crate) fn update(&self, node_id: u64, status: NodeRuntimeStatus) {
+        self.statuses.lock().unwrap().insert(node_id, (status, Instant::now()));
+    }
+
+    /// The last status of the node, and the elapsed time since it is reported.
+    pub(crate) fn get(&self, node_id: u64) -> Option<(NodeRuntimeStatus, Duration)> {
+        let statuses = self.statuses.lock().unwrap();
+        statuses.get(&node_id).map(|(status, reported_at)| (status.clone(), reported_at.elapsed()))
+    }
+
+    /// The health in the last status of the node.
+    pub(crate) fn health(&self, node_id: u64) -> Option<NodeHealth> {
+        let statuses = self.statuses.lock().unwrap();
+        statuses.get(&node_id).and_then(|(status, _)| status.health.clone())
+    }
+
+    /// Forget the statuses, since they might be reported to a former root.
+    pub(crate) fn reset(&self) {
+        self.statuses.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_last_reported_status() {
+        let cache = NodeStatusCache::default();
+        assert!(cache.get(1).is_none());
+
+        cache.update(1, NodeRuntimeStatus { node_id: 1, uptime_secs: 10, ..Default::default() });
+        cache.update(1, NodeRuntimeStatus { node_id: 1, uptime_secs: 20, ..Default::default() });
+        let (status, staleness) = cache.get(1).unwrap();
+        assert_eq!(status.uptime_secs, 20);
+        assert!(staleness < Duration::from_secs(60));
+        assert!(cache.health(1).is_none());
+
+        let health = NodeHealth { cpu_util: 0.5, ..Default::default() };
+        cache.update(1, NodeRuntimeStatus { health: Some(health.clone()), ..Default::default() });
+        assert_eq!(cache.health(1), Some(health));
+
+        cache.reset();
+        assert!(cache.get(1).is_none());
+    }
+}
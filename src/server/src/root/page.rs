@@ -0,0 +1,275 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The paged listing of the system tables.
+//!
+//! A page is read by range reads from the key after the page token, at most
+//! a page of rows are read and decoded at a time, so the memory of a listing
+//! is bounded by the page size no matter how many rows are stored. The page
+//! token is the key of the last row read, it is opaque to the callers.
+
+use sekas_api::server::v1::*;
+
+use crate::Result;
+
+/// The page size if the limit isn't specified.
+pub const DEFAULT_PAGE_SIZE: usize = 256;
+
+/// The max page size, the larger limits are capped to it.
+pub const MAX_PAGE_SIZE: usize = 4096;
+
+/// A page of the listing.
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// The token to read the next page, empty if there are no more items.
+    pub next_page_token: Vec<u8>,
+}
+
+/// The rows read by a range read.
+#[derive(Debug, Default)]
+pub struct ScannedRows {
+    pub rows: Vec<(Vec<u8>, Vec<u8>)>,
+    /// The key of the last row read, if there are more rows after it.
+    pub resume_key: Option<Vec<u8>>,
+}
+
+/// The range reads of the system tables.
+#[crate::async_trait]
+pub trait RowScanner: Send + Sync {
+    /// Read at most `limit` rows of the shard with the prefix, after the key
+    /// `start_after` if it is specified.
+    async fn scan_rows(
+        &self,
+        shard_id: u64,
+        prefix: &[u8],
+        start_after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<ScannedRows>;
+}
+
+impl<T> Page<T> {
+    #[inline]
+    pub fn has_more(&self) -> bool {
+        !self.next_page_token.is_empty()
+    }
+}
+
+/// Read a page of the items of the shard with the prefix. The rows are decoded
+/// by `decode`, which returns `None` for the unmatched items.
+pub async fn read_page<S, T, F>(
+    scanner: &S,
+    shard_id: u64,
+    prefix: &[u8],
+    page_token: &[u8],
+    limit: u64,
+    mut decode: F,
+) -> Result<Page<T>>
+where
+    S: RowScanner + ?Sized,
+    F: FnMut(&[u8]) -> Result<Option<T>>,
+{
+    let limit = page_size(limit);
+    let mut start_after = (!page_token.is_empty()).then(|| page_token.to_vec());
+    let mut items = Vec::new();
+    loop {
+        let scanned = scanner.scan_rows(shard_id, prefix, start_after.as_deref(), limit).await?;
+        let num_rows = scanned.rows.len();
+        for (index, (key, value)) in scanned.rows.into_iter().enumerate() {
+            if let Some(item) = decode(&value)? {
+                items.push(item);
+                if items.len() == limit {
+                    // The next page is resumed after this row, the rest rows are read again.
+                    let is_last = index + 1 == num_rows && scanned.resume_key.is_none();
+                    let next_page_token = if is_last { vec![] } else { key };
+                    return Ok(Page { items, next_page_token });
+                }
+            }
+        }
+        if scanned.resume_key.is_none() {
+            return Ok(Page { items, next_page_token: vec![] });
+        }
+        start_after = scanned.resume_key;
+        sekas_runtime::yield_now().await;
+    }
+}
+
+#[inline]
+fn page_size(limit: u64) -> usize {
+    if limit == 0 {
+        DEFAULT_PAGE_SIZE
+    } else {
+        (limit as usize).min(MAX_PAGE_SIZE)
+    }
+}
+
+pub fn match_group(filter: &GroupFilter, desc: &GroupDesc) -> bool {
+    desc.epoch >= filter.min_epoch
+        && filter.node_id.map_or(true, |id| desc.replicas.iter().any(|r| r.node_id == id))
+        && filter.table_id.map_or(true, |id| desc.shards.iter().any(|s| s.table_id == id))
+}
+
+pub fn match_node(filter: &NodeDescFilter, desc: &NodeDesc) -> bool {
+    filter.status.map_or(true, |status| desc.status == status)
+}
+
+pub fn match_replica_state(filter: &ReplicaStateFilter, state: &ReplicaState) -> bool {
+    filter.group_id.map_or(true, |id| state.group_id == id)
+        && filter.node_id.map_or(true, |id| state.node_id == id)
+}
+
+/// The page token to resume the listing after the group, it is the key of the
+/// group desc.
+pub fn group_page_token(desc: &GroupDesc) -> Vec<u8> {
+    desc.id.to_le_bytes().to_vec()
+}
+
+/// The page token to resume the listing after the node.
+pub fn node_page_token(desc: &NodeDesc) -> Vec<u8> {
+    desc.id.to_le_bytes().to_vec()
+}
+
+/// The page token to resume the listing after the replica state.
+pub fn replica_state_page_token(state: &ReplicaState) -> Vec<u8> {
+    let mut token = state.group_id.to_le_bytes().to_vec();
+    token.extend_from_slice(&state.replica_id.to_le_bytes());
+    token
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashSet};
+    use std::ops::Bound;
+
+    use prost::Message;
+
+    use super::*;
+
+    /// The rows of a shard, at most `batch` rows are read by a range read.
+    struct MemScanner {
+        rows: BTreeMap<Vec<u8>, Vec<u8>>,
+        batch: usize,
+    }
+
+    #[crate::async_trait]
+    impl RowScanner for MemScanner {
+        async fn scan_rows(
+            &self,
+            _shard_id: u64,
+            prefix: &[u8],
+            start_after: Option<&[u8]>,
+            limit: usize,
+        ) -> Result<ScannedRows> {
+            let lower = match start_after {
+                Some(key) => Bound::Excluded(key.to_vec()),
+                None => Bound::Unbounded,
+            };
+            let limit = limit.min(self.batch);
+            let mut rows = self
+                .rows
+                .range((lower, Bound::Unbounded))
+                .filter(|(key, _)| key.starts_with(prefix))
+                .take(limit + 1)
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect::<Vec<_>>();
+            let mut resume_key = None;
+            if rows.len() > limit {
+                rows.truncate(limit);
+                resume_key = rows.last().map(|(key, _)| key.clone());
+            }
+            Ok(ScannedRows { rows, resume_key })
+        }
+    }
+
+    fn group_scanner(num_groups: u64, batch: usize) -> MemScanner {
+        let rows = (1..=num_groups)
+            .map(|id| {
+                let desc = GroupDesc {
+                    id,
+                    epoch: id,
+                    shards: vec![ShardDesc { id, table_id: id % 3, ..Default::default() }],
+                    replicas: vec![ReplicaDesc { id, node_id: id % 5, ..Default::default() }],
+                    ..Default::default()
+                };
+                (group_page_token(&desc), desc.encode_to_vec())
+            })
+            .collect();
+        MemScanner { rows, batch }
+    }
+
+    async fn list_all_groups(scanner: &MemScanner, mut filter: GroupFilter) -> Vec<GroupDesc> {
+        let mut groups = vec![];
+        loop {
+            let page = read_page(scanner, 0, &[], &filter.page_token, filter.limit, |val| {
+                let desc = GroupDesc::decode(val).unwrap();
+                Ok(match_group(&filter, &desc).then_some(desc))
+            })
+            .await
+            .unwrap();
+            assert!(page.items.len() <= page_size(filter.limit));
+            groups.extend(page.items);
+            if !page.has_more() {
+                return groups;
+            }
+            filter.page_token = page.next_page_token;
+        }
+    }
+
+    #[sekas_macro::test]
+    async fn list_groups_page_by_page() {
+        let scanner = group_scanner(5000, 100);
+        for limit in [0, 1, 7, 256, 5000, 10000] {
+            let groups =
+                list_all_groups(&scanner, GroupFilter { limit, ..Default::default() }).await;
+            let ids = groups.iter().map(|g| g.id).collect::<HashSet<_>>();
+            assert_eq!(groups.len(), 5000, "limit {limit}");
+            assert_eq!(ids.len(), 5000, "limit {limit}");
+        }
+    }
+
+    #[sekas_macro::test]
+    async fn list_groups_by_filter() {
+        let scanner = group_scanner(3000, 64);
+        let filter = GroupFilter { table_id: Some(1), limit: 10, ..Default::default() };
+        let groups = list_all_groups(&scanner, filter).await;
+        assert_eq!(groups.len(), 1000);
+        assert!(groups.iter().all(|g| g.id % 3 == 1));
+
+        let filter = GroupFilter { node_id: Some(2), min_epoch: 1001, ..Default::default() };
+        let groups = list_all_groups(&scanner, filter).await;
+        assert_eq!(groups.len(), 400);
+        assert!(groups.iter().all(|g| g.id % 5 == 2 && g.epoch >= 1001));
+
+        let filter = GroupFilter { min_epoch: 3001, ..Default::default() };
+        assert!(list_all_groups(&scanner, filter).await.is_empty());
+    }
+
+    #[sekas_macro::test]
+    async fn last_full_page_has_no_token() {
+        let scanner = group_scanner(20, 100);
+        let page = read_page(&scanner, 0, &[], &[], 20, |val| Ok(Some(GroupDesc::decode(val)?)))
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 20);
+        assert!(!page.has_more());
+    }
+
+    #[test]
+    fn replica_state_token_is_prefixed_by_group() {
+        let state = ReplicaState { group_id: 7, replica_id: 9, ..Default::default() };
+        let token = replica_state_page_token(&state);
+        assert!(token.starts_with(&7u64.to_le_bytes()));
+        assert_eq!(token.len(), 16);
+    }
+}
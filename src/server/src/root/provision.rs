@@ -0,0 +1,131 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The janitor of the tables being provisioned.
+//!
+//! A table created by `Database::create_table_with_seed` is marked by the
+//! [`PROVISIONING`] property until its seed rows are committed. The client
+//! might die before that, so the tables still marked after
+//! `root.provisioning_timeout_sec` are deleted by the root leader, the retry of
+//! the client with the same request id creates the table again.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use log::{info, warn};
+use sekas_api::server::v1::TableDesc;
+use sekas_runtime::time::timestamp_millis;
+use sekas_schema::property::PROVISIONING;
+
+use super::metrics::PROVISIONING_TABLE_DELETED_TOTAL;
+use super::Root;
+use crate::Result;
+
+/// The max intervals between two collections.
+const MAX_COLLECT_INTERVAL: Duration = Duration::from_secs(60);
+
+impl Root {
+    /// Delete the tables still being provisioned after the timeout, returns
+    /// the ids of the deleted tables.
+    pub async fn collect_provisioning_tables(&self, timeout: Duration) -> Result<Vec<u64>> {
+        let schema = self.schema()?;
+        let now = timestamp_millis();
+        let expired = schema
+            .list_table()
+            .await?
+            .into_iter()
+            .filter(|table| is_provisioning_expired(table, now, timeout))
+            .collect::<Vec<_>>();
+        if expired.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let databases = schema
+            .list_database()
+            .await?
+            .into_iter()
+            .map(|db| (db.id, db))
+            .collect::<HashMap<_, _>>();
+        let mut deleted = vec![];
+        for table in expired {
+            let Some(db) = databases.get(&table.db) else {
+                continue;
+            };
+            // The seed might be committed since the listing.
+            match schema.get_table(db.id, &table.name).await? {
+                Some(latest)
+                    if latest.id == table.id && is_provisioning_expired(&latest, now, timeout) => {}
+                _ => continue,
+            }
+            info!(
+                "delete table {} of database {}, it is still being provisioned after {timeout:?}",
+                table.name, db.name
+            );
+            self.delete_table(&table.name, db).await?;
+            PROVISIONING_TABLE_DELETED_TOTAL.inc();
+            deleted.push(table.id);
+        }
+        Ok(deleted)
+    }
+
+    /// Collect the tables being provisioned periodically, until the root
+    /// leader is dropped.
+    pub(super) async fn run_provisioning_janitor(&self) {
+        let timeout = Duration::from_secs(self.cfg.provisioning_timeout_sec);
+        if timeout.is_zero() {
+            return;
+        }
+        let interval = timeout.min(MAX_COLLECT_INTERVAL);
+        loop {
+            sekas_runtime::time::sleep(interval).await;
+            if self.check_catalog_writable().is_err() {
+                continue;
+            }
+            if let Err(err) = self.collect_provisioning_tables(timeout).await {
+                warn!("collect provisioning tables: {err:?}");
+            }
+        }
+    }
+}
+
+/// Whether the table is still being provisioned after `timeout`.
+fn is_provisioning_expired(table: &TableDesc, now: u64, timeout: Duration) -> bool {
+    table
+        .properties
+        .get(PROVISIONING)
+        .and_then(|created_at| created_at.parse::<u64>().ok())
+        .is_some_and(|created_at| now.saturating_sub(created_at) >= timeout.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provisioning_expired() {
+        let table = |created_at: Option<&str>| TableDesc {
+            properties: created_at
+                .map(|v| HashMap::from([(PROVISIONING.to_owned(), v.to_owned())]))
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        let timeout = Duration::from_secs(10);
+        assert!(!is_provisioning_expired(&table(None), 20_000, timeout));
+        assert!(!is_provisioning_expired(&table(Some("15000")), 20_000, timeout));
+        assert!(is_provisioning_expired(&table(Some("10000")), 20_000, timeout));
+        // The clock of the former root leader might be ahead.
+        assert!(!is_provisioning_expired(&table(Some("30000")), 20_000, timeout));
+        assert!(!is_provisioning_expired(&table(Some("")), 20_000, timeout));
+    }
+}
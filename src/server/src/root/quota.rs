@@ -0,0 +1,414 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The quotas of the DDL of the databases.
+//!
+//! The cluster defaults are configured by `CONFIG`, and overridden by the
+//! properties of a database with `ALTER DATABASE`. The quotas are checked by
+//! the per-database counters of the tables and shards, see [`QuotaUsages`],
+//! the quota of a deleted table is freed immediately.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::info;
+use sekas_api::server::v1::*;
+use sekas_runtime::time::{timestamp_millis, Instant};
+use sekas_schema::property::{
+    MAX_SHARDS_PER_DATABASE, MAX_SHARDS_PER_TABLE, MAX_TABLES_PER_DATABASE, QUOTA_LIMITS,
+};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use super::metrics::QUOTA_EXCEEDED_TOTAL;
+use super::schema::Schema;
+use super::Root;
+use crate::{Error, Result};
+
+/// The effective quotas of a database, `0` means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    pub max_tables_per_database: u64,
+    pub max_shards_per_table: u64,
+    pub max_shards_per_database: u64,
+}
+
+impl Quota {
+    /// The cluster defaults overridden by the properties of the database.
+    pub fn resolve(defaults: &HashMap<String, u64>, db: &DatabaseDesc) -> Quota {
+        let limit = |name: &str| -> u64 {
+            db.properties
+                .get(name)
+                .and_then(|v| v.parse().ok())
+                .or_else(|| defaults.get(name).cloned())
+                .unwrap_or_default()
+        };
+        Quota {
+            max_tables_per_database: limit(MAX_TABLES_PER_DATABASE),
+            max_shards_per_table: limit(MAX_SHARDS_PER_TABLE),
+            max_shards_per_database: limit(MAX_SHARDS_PER_DATABASE),
+        }
+    }
+
+    /// Check whether a table could be created in the database which owns
+    /// `num_tables` tables.
+    pub fn check_create_table(&self, num_tables: usize) -> Result<()> {
+        check_limit(MAX_TABLES_PER_DATABASE, self.max_tables_per_database, num_tables)
+    }
+
+    /// Check whether a shard of the table could be split, the table owns
+    /// `table_shards` shards and the database owns `db_shards` shards.
+    pub fn check_split_shard(&self, table_shards: usize, db_shards: usize) -> Result<()> {
+        check_limit(MAX_SHARDS_PER_TABLE, self.max_shards_per_table, table_shards)?;
+        check_limit(MAX_SHARDS_PER_DATABASE, self.max_shards_per_database, db_shards)
+    }
+}
+
+impl std::fmt::Display for Quota {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display = |v: u64| if v == 0 { "unlimited".to_owned() } else { v.to_string() };
+        write!(
+            f,
+            "tables {}, shards per table {}, shards {}",
+            display(self.max_tables_per_database),
+            display(self.max_shards_per_table),
+            display(self.max_shards_per_database)
+        )
+    }
+}
+
+/// A new object is rejected if the number of the existing ones reaches the
+/// limit.
+fn check_limit(name: &str, limit: u64, used: usize) -> Result<()> {
+    if limit != 0 && used as u64 >= limit {
+        QUOTA_EXCEEDED_TOTAL.with_label_values(&[name]).inc();
+        return Err(Error::QuotaExceeded { limit: name.to_owned(), value: limit });
+    }
+    Ok(())
+}
+
+/// Parse the value of a quota, the empty value is returned as `None`, which
+/// removes the override of a database.
+pub fn parse_quota(name: &str, value: &str) -> Result<Option<u64>> {
+    if !QUOTA_LIMITS.contains(&name) {
+        return Err(Error::InvalidArgument(format!("unknown quota: {name}")));
+    }
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let Ok(value) = value.parse::<u64>() else {
+        return Err(Error::InvalidArgument(format!(
+            "the value of `{name}` should be a valid u64 numeric"
+        )));
+    };
+    Ok(Some(value))
+}
+
+/// The quota of the database.
+pub async fn database_quota(schema: &Schema, db: &DatabaseDesc) -> Result<Quota> {
+    let defaults = schema.get_quota_limits().await?;
+    Ok(Quota::resolve(&defaults.limits, db))
+}
+
+/// The usages of the quotas are recounted from the catalog after this
+/// interval, it bounds the drifts of the counters, eg. by the merged shards.
+const USAGE_RECOUNT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The usages of the quotas of the databases, so the checks don't scan the
+/// catalog. A database is counted from the catalog when it is checked first,
+/// then the counters are maintained by the DDL and the splits of the root, and
+/// recounted after [`USAGE_RECOUNT_INTERVAL`]. They are owned by the schema of
+/// a root leader, so a new root leader counts them again.
+#[derive(Default)]
+pub struct QuotaUsages {
+    databases: Mutex<HashMap<u64, Arc<AsyncMutex<DatabaseUsage>>>>,
+    /// The id and the name of the database of the tables.
+    table_dbs: Mutex<HashMap<u64, (u64, String)>>,
+}
+
+#[derive(Default)]
+struct DatabaseUsage {
+    counted_at: Option<Instant>,
+    /// The number of the shards of the tables of the database.
+    table_shards: HashMap<u64, usize>,
+}
+
+/// The permit to create a table in the database, the creates of a database are
+/// serialized by it, so the concurrent creates never exceed the quota.
+pub struct CreateTablePermit {
+    usages: Arc<QuotaUsages>,
+    usage: OwnedMutexGuard<DatabaseUsage>,
+    db: DatabaseDesc,
+}
+
+impl QuotaUsages {
+    /// Lock the usage of the database, it is counted from the catalog if it is
+    /// not counted or it is stale.
+    async fn lock(
+        &self,
+        schema: &Schema,
+        db: &DatabaseDesc,
+    ) -> Result<OwnedMutexGuard<DatabaseUsage>> {
+        let usage = self.databases.lock().unwrap().entry(db.id).or_default().clone();
+        let mut usage = usage.lock_owned().await;
+        if usage.counted_at.map_or(true, |at| at.elapsed() >= USAGE_RECOUNT_INTERVAL) {
+            let tables = schema
+                .list_database_tables(db.id)
+                .await?
+                .into_iter()
+                .map(|table| table.id)
+                .collect::<HashSet<_>>();
+            let shards = count_shards(schema, &tables).await?;
+            let mut table_dbs = self.table_dbs.lock().unwrap();
+            usage.table_shards = tables
+                .into_iter()
+                .map(|id| {
+                    table_dbs.insert(id, (db.id, db.name.clone()));
+                    (id, shards.get(&id).cloned().unwrap_or_default())
+                })
+                .collect();
+            usage.counted_at = Some(Instant::now());
+        }
+        Ok(usage)
+    }
+
+    /// The database of the table, `None` if the table is not exists.
+    async fn table_db(&self, schema: &Schema, table_id: u64) -> Result<Option<DatabaseDesc>> {
+        let cached = self.table_dbs.lock().unwrap().get(&table_id).cloned();
+        if let Some((db_id, db_name)) = cached {
+            return Ok(schema.get_database(&db_name).await?.filter(|db| db.id == db_id));
+        }
+        // The tables unknown to the counters are created by the former root leaders,
+        // they are learned all at once.
+        let dbs = schema.list_database().await?;
+        let db_names = dbs.iter().map(|db| (db.id, db.name.clone())).collect::<HashMap<_, _>>();
+        let tables = schema.list_table().await?;
+        let mut table_db = None;
+        let mut table_dbs = self.table_dbs.lock().unwrap();
+        for table in tables {
+            let Some(db_name) = db_names.get(&table.db) else {
+                continue;
+            };
+            if table.id == table_id {
+                table_db = Some(table.db);
+            }
+            table_dbs.insert(table.id, (table.db, db_name.clone()));
+        }
+        Ok(table_db.and_then(|id| dbs.into_iter().find(|db| db.id == id)))
+    }
+
+    /// Count the shard split from the shard of the table.
+    pub async fn add_split_shard(&self, table_id: u64) {
+        let Some((db_id, _)) = self.table_dbs.lock().unwrap().get(&table_id).cloned() else {
+            return;
+        };
+        let usage = self.databases.lock().unwrap().get(&db_id).cloned();
+        if let Some(usage) = usage {
+            if let Some(shards) = usage.lock().await.table_shards.get_mut(&table_id) {
+                *shards += 1;
+            }
+        }
+    }
+
+    /// Forget the deleted table, its quota is freed immediately.
+    pub async fn remove_table(&self, table_id: u64) {
+        let Some((db_id, _)) = self.table_dbs.lock().unwrap().remove(&table_id) else {
+            return;
+        };
+        let usage = self.databases.lock().unwrap().get(&db_id).cloned();
+        if let Some(usage) = usage {
+            usage.lock().await.table_shards.remove(&table_id);
+        }
+    }
+
+    /// Forget the deleted database.
+    pub fn remove_database(&self, db_id: u64) {
+        self.databases.lock().unwrap().remove(&db_id);
+        self.table_dbs.lock().unwrap().retain(|_, (id, _)| *id != db_id);
+    }
+}
+
+impl CreateTablePermit {
+    /// Count the table created with the permit.
+    pub fn created(mut self, table: &TableDesc) {
+        self.usage.table_shards.insert(table.id, 1);
+        let db = (self.db.id, self.db.name.clone());
+        self.usages.table_dbs.lock().unwrap().insert(table.id, db);
+    }
+}
+
+/// Check whether a table could be created in the database. The creates of the
+/// database are serialized until the returned permit is dropped.
+pub async fn check_create_table(schema: &Schema, db: &DatabaseDesc) -> Result<CreateTablePermit> {
+    let quota = database_quota(schema, db).await?;
+    let usages = schema.quota_usages().clone();
+    let usage = usages.lock(schema, db).await?;
+    quota.check_create_table(usage.table_shards.len())?;
+    Ok(CreateTablePermit { usages, usage, db: db.clone() })
+}
+
+/// Check whether the shard of the table could be split. The system tables are
+/// not limited.
+pub async fn check_split_shard(schema: &Schema, table_id: u64) -> Result<()> {
+    if table_id < sekas_schema::FIRST_USER_TABLE_ID {
+        return Ok(());
+    }
+    let usages = schema.quota_usages();
+    let Some(db) = usages.table_db(schema, table_id).await? else {
+        return Ok(());
+    };
+    let quota = database_quota(schema, &db).await?;
+    if quota.max_shards_per_table == 0 && quota.max_shards_per_database == 0 {
+        return Ok(());
+    }
+    let usage = usages.lock(schema, &db).await?;
+    let Some(table_shards) = usage.table_shards.get(&table_id) else {
+        // The table is deleted.
+        return Ok(());
+    };
+    quota.check_split_shard(*table_shards, usage.table_shards.values().sum())
+}
+
+/// Count the shards of the tables.
+async fn count_shards(schema: &Schema, tables: &HashSet<u64>) -> Result<HashMap<u64, usize>> {
+    let mut filter = GroupFilter::default();
+    let mut shards = HashMap::new();
+    loop {
+        let page = schema.list_group_page(&filter).await?;
+        for shard in page.items.iter().flat_map(|g| g.shards.iter()) {
+            if tables.contains(&shard.table_id) {
+                *shards.entry(shard.table_id).or_default() += 1;
+            }
+        }
+        if !page.has_more() {
+            return Ok(shards);
+        }
+        filter.page_token = page.next_page_token;
+    }
+}
+
+/// Record the auto split skipped by the quota in the topology event log.
+pub async fn record_skipped_split(schema: &Schema, shard: u64, err: &Error) -> Result<()> {
+    let event = TopologyEvent {
+        timestamp: timestamp_millis(),
+        kind: topology_event::Kind::Quota.into(),
+        target: format!("shard/{shard}"),
+        detail: format!("skip auto split: {err}"),
+        ..Default::default()
+    };
+    schema.append_topology_event(event).await?;
+    Ok(())
+}
+
+impl Root {
+    /// Update the properties of a database, only the quotas are supported.
+    /// The properties with empty values are removed.
+    pub async fn update_database(
+        &self,
+        name: &str,
+        properties: HashMap<String, String>,
+    ) -> Result<DatabaseDesc> {
+        self.check_catalog_writable()?;
+        let schema = self.schema()?;
+        let mut db = schema
+            .get_database(name)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(name.to_owned()))?;
+        if db.id == sekas_schema::system::db::ID {
+            return Err(Error::InvalidArgument("unsupported update system database".into()));
+        }
+        for (key, value) in properties {
+            match parse_quota(&key, &value)? {
+                Some(limit) => db.properties.insert(key, limit.to_string()),
+                None => db.properties.remove(&key),
+            };
+        }
+        schema.update_database(db.clone()).await?;
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Database(db.to_owned())),
+            }])
+            .await;
+        info!("update database {name}, properties {:?}", db.properties);
+        Ok(db)
+    }
+
+    /// Change the cluster default of a quota, `0` means unlimited.
+    pub async fn set_default_quota(&self, name: &str, value: u64) -> Result<()> {
+        parse_quota(name, "")?;
+        let schema = self.schema()?;
+        let mut limits = schema.get_quota_limits().await?;
+        limits.limits.insert(name.to_owned(), value);
+        info!("change default quotas to {:?}", limits.limits);
+        schema.set_quota_limits(&limits).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db_with(properties: &[(&str, &str)]) -> DatabaseDesc {
+        DatabaseDesc {
+            properties: properties.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resolve_quota_overrides_defaults() {
+        let defaults = HashMap::from([
+            (MAX_TABLES_PER_DATABASE.to_owned(), 10),
+            (MAX_SHARDS_PER_TABLE.to_owned(), 4),
+        ]);
+        let quota = Quota::resolve(&defaults, &db_with(&[]));
+        assert_eq!(quota.max_tables_per_database, 10);
+        assert_eq!(quota.max_shards_per_table, 4);
+        assert_eq!(quota.max_shards_per_database, 0);
+
+        let db = db_with(&[(MAX_TABLES_PER_DATABASE, "0"), (MAX_SHARDS_PER_DATABASE, "8")]);
+        let quota = Quota::resolve(&defaults, &db);
+        assert_eq!(quota.max_tables_per_database, 0);
+        assert_eq!(quota.max_shards_per_table, 4);
+        assert_eq!(quota.max_shards_per_database, 8);
+    }
+
+    #[test]
+    fn check_quota_limits() {
+        let quota = Quota {
+            max_tables_per_database: 2,
+            max_shards_per_table: 3,
+            max_shards_per_database: 0,
+        };
+        assert!(quota.check_create_table(1).is_ok());
+        assert!(matches!(
+            quota.check_create_table(2),
+            Err(Error::QuotaExceeded { limit, value: 2 }) if limit == MAX_TABLES_PER_DATABASE
+        ));
+        assert!(quota.check_split_shard(2, 1000).is_ok());
+        assert!(matches!(
+            quota.check_split_shard(3, 3),
+            Err(Error::QuotaExceeded { limit, value: 3 }) if limit == MAX_SHARDS_PER_TABLE
+        ));
+        assert!(Quota::default().check_create_table(usize::MAX).is_ok());
+    }
+
+    #[test]
+    fn parse_quota_value() {
+        assert_eq!(parse_quota(MAX_SHARDS_PER_TABLE, "12").unwrap(), Some(12));
+        assert_eq!(parse_quota(MAX_SHARDS_PER_TABLE, "").unwrap(), None);
+        assert!(matches!(parse_quota(MAX_SHARDS_PER_TABLE, "-1"), Err(Error::InvalidArgument(_))));
+        assert!(matches!(parse_quota("max_rows", "1"), Err(Error::InvalidArgument(_))));
+    }
+}
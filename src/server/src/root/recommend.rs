@@ -0,0 +1,252 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The recommendations of root scheduler.
+//!
+//! Unless the scheduler is in auto mode, the reconcile tasks and the cures of
+//! groups are recorded as recommendations instead of being executed. In the
+//! manual-approve mode, a recommendation is executed once an operator approves
+//! it. A recommendation expires if the epoch of any involved group is changed,
+//! since the cluster state it was computed against is gone.
+
+use std::collections::HashMap;
+
+use log::info;
+use sekas_runtime::time::timestamp_millis;
+
+use super::schedule::recommendation::Action;
+use super::schedule::reconcile_task::Task;
+use super::schedule::{CureGroupAction, Recommendation, RecommendationStatus, ReconcileTask};
+use super::schema::Schema;
+use super::Root;
+use crate::{Error, Result, RootConfig, ScheduleMode};
+
+/// The schedule policy in effect.
+#[derive(Clone, Copy, Debug)]
+pub struct SchedulePolicy {
+    pub mode: ScheduleMode,
+    pub auto_cure: bool,
+}
+
+impl SchedulePolicy {
+    /// Load the schedule policy, the values changed at runtime take precedence
+    /// over the config.
+    pub async fn load(schema: &Schema, cfg: &RootConfig) -> Result<Self> {
+        let mode = schema.get_schedule_mode().await?.unwrap_or(cfg.schedule_mode);
+        let auto_cure = schema.get_schedule_auto_cure().await?.unwrap_or(cfg.schedule_auto_cure);
+        Ok(SchedulePolicy { mode, auto_cure })
+    }
+
+    /// Whether the groups which lost replicas are cured without approvals.
+    #[inline]
+    pub fn is_auto_cure(&self) -> bool {
+        self.mode == ScheduleMode::Auto || self.auto_cure
+    }
+}
+
+impl Root {
+    pub async fn list_recommendations(&self) -> Result<Vec<Recommendation>> {
+        self.schema()?.list_recommendation().await
+    }
+
+    /// Approve the pending recommendation, it is executed by the scheduler
+    /// later. Only the manual-approve mode accepts approvals.
+    pub async fn approve_action(&self, id: u64) -> Result<()> {
+        let schema = self.schema()?;
+        let policy = SchedulePolicy::load(&schema, &self.cfg).await?;
+        if policy.mode != ScheduleMode::ManualApprove {
+            return Err(Error::InvalidArgument(format!(
+                "the schedule mode is {}, approvals are only accepted in manual-approve mode",
+                policy.mode.as_str()
+            )));
+        }
+
+        let Some(mut desc) = schema.get_recommendation(id).await? else {
+            return Err(Error::InvalidArgument(format!("recommendation {id} not found")));
+        };
+        if desc.status != RecommendationStatus::Pending as i32 {
+            return Err(Error::InvalidArgument(format!(
+                "recommendation {id} is {}, only the pending one could be approved",
+                status_name(&desc)
+            )));
+        }
+        if !is_up_to_date(&schema, &desc).await? {
+            desc.status = RecommendationStatus::Expired as i32;
+            schema.update_recommendation(desc).await?;
+            return Err(Error::InvalidArgument(format!(
+                "recommendation {id} is expired, the cluster state it was computed against has changed"
+            )));
+        }
+
+        info!("recommendation {id} is approved: {}", desc.describe());
+        desc.status = RecommendationStatus::Approved as i32;
+        schema.update_recommendation(desc).await
+    }
+
+    pub async fn set_schedule_mode(&self, mode: ScheduleMode) -> Result<()> {
+        info!("change schedule mode to {}", mode.as_str());
+        self.schema()?.set_schedule_mode(mode).await
+    }
+
+    pub async fn set_schedule_auto_cure(&self, auto_cure: bool) -> Result<()> {
+        info!("change schedule auto cure to {auto_cure}");
+        self.schema()?.set_schedule_auto_cure(auto_cure).await
+    }
+
+    /// Check whether the group could be cured now. If the cure requires an
+    /// approval, it is recorded as a recommendation and `ResourceExhausted` is
+    /// returned until it is approved. The approved recommendation is returned,
+    /// it should be marked as executed once the replicas are allocated.
+    pub(super) async fn check_cure_group(
+        &self,
+        schema: &Schema,
+        group_id: u64,
+        num_required: u64,
+    ) -> Result<Option<Recommendation>> {
+        let policy = SchedulePolicy::load(schema, &self.cfg).await?;
+        if policy.is_auto_cure() {
+            return Ok(None);
+        }
+
+        // The epoch of the group has been checked by the caller, so the expired
+        // recommendations are filtered out.
+        for desc in refresh_recommendations(schema).await? {
+            if matches!(&desc.action, Some(Action::CureGroup(c)) if c.group_id == group_id) {
+                return Ok(Some(desc));
+            }
+        }
+        let action = Action::CureGroup(CureGroupAction { group_id, num_required });
+        recommend_actions(schema, vec![action]).await?;
+        Err(Error::ResourceExhausted(format!(
+            "the cure of group {group_id} is waiting for approval"
+        )))
+    }
+}
+
+/// Record the actions as pending recommendations, unless the equivalent ones
+/// are already pending or approved.
+pub(super) async fn recommend_actions(schema: &Schema, actions: Vec<Action>) -> Result<()> {
+    let mut recommendations =
+        schema.list_recommendation().await?.into_iter().filter(is_active).collect::<Vec<_>>();
+    'NEXT_ACTION: for action in actions {
+        if recommendations.iter().any(|r| is_same_action(r.action.as_ref().unwrap(), &action)) {
+            continue;
+        }
+
+        let mut group_epochs = HashMap::default();
+        for group_id in involved_groups(&action) {
+            let Some(group) = schema.get_group(group_id).await? else {
+                // The group has been removed, the action is meaningless.
+                continue 'NEXT_ACTION;
+            };
+            group_epochs.insert(group_id, group.epoch);
+        }
+        let desc = Recommendation {
+            id: 0,
+            status: RecommendationStatus::Pending as i32,
+            group_epochs,
+            created_at: timestamp_millis(),
+            action: Some(action),
+        };
+        let desc = schema.append_recommendation(desc).await?;
+        info!("record recommendation {}: {}", desc.id, desc.describe());
+        recommendations.push(desc);
+    }
+    Ok(())
+}
+
+/// Expire the pending and approved recommendations whose cluster state has
+/// changed, and return the approved ones which are still valid.
+pub(super) async fn refresh_recommendations(schema: &Schema) -> Result<Vec<Recommendation>> {
+    let mut approved = vec![];
+    for mut desc in schema.list_recommendation().await? {
+        if !is_active(&desc) {
+            continue;
+        }
+        if !is_up_to_date(schema, &desc).await? {
+            info!("recommendation {} is expired: {}", desc.id, desc.describe());
+            desc.status = RecommendationStatus::Expired as i32;
+            schema.update_recommendation(desc).await?;
+            continue;
+        }
+        if desc.status == RecommendationStatus::Approved as i32 {
+            approved.push(desc);
+        }
+    }
+    Ok(approved)
+}
+
+/// Mark the approved recommendation as executed.
+pub(super) async fn finish_recommendation(schema: &Schema, mut desc: Recommendation) -> Result<()> {
+    info!("recommendation {} is executed: {}", desc.id, desc.describe());
+    desc.status = RecommendationStatus::Executed as i32;
+    schema.update_recommendation(desc).await
+}
+
+fn is_active(desc: &Recommendation) -> bool {
+    desc.status == RecommendationStatus::Pending as i32
+        || desc.status == RecommendationStatus::Approved as i32
+}
+
+async fn is_up_to_date(schema: &Schema, desc: &Recommendation) -> Result<bool> {
+    for (group_id, epoch) in &desc.group_epochs {
+        match schema.get_group(*group_id).await? {
+            Some(group) if group.epoch == *epoch => {}
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+pub(super) fn status_name(desc: &Recommendation) -> &'static str {
+    RecommendationStatus::from_i32(desc.status).unwrap_or_default().as_str_name()
+}
+
+fn involved_groups(action: &Action) -> Vec<u64> {
+    match action {
+        Action::CureGroup(c) => vec![c.group_id],
+        Action::Reconcile(ReconcileTask { task: Some(task), .. }) => match task {
+            Task::ReallocateReplica(t) => vec![t.group],
+            Task::MigrateShard(t) => vec![t.src_group, t.dest_group],
+            Task::TransferGroupLeader(t) => vec![t.group],
+            Task::SplitShard(t) => vec![t.group_id],
+            Task::AddReadReplica(t) => vec![t.group],
+            Task::RemoveReadReplica(t) => vec![t.group],
+            Task::RemoveGhostReplica(t) => vec![t.group],
+            Task::ShedLeader(_) | Task::ShedRoot(_) => vec![],
+        },
+        Action::Reconcile(_) => vec![],
+    }
+}
+
+/// Whether the two actions target the same object, the details are ignored
+/// since they are computed against the same cluster state.
+fn is_same_action(a: &Action, b: &Action) -> bool {
+    match (a, b) {
+        (Action::CureGroup(a), Action::CureGroup(b)) => a.group_id == b.group_id,
+        (Action::Reconcile(a), Action::Reconcile(b)) => match (&a.task, &b.task) {
+            (Some(Task::ReallocateReplica(a)), Some(Task::ReallocateReplica(b))) => {
+                a.group == b.group && a.src_replica == b.src_replica
+            }
+            (Some(Task::MigrateShard(a)), Some(Task::MigrateShard(b))) => a.shard == b.shard,
+            (Some(Task::TransferGroupLeader(a)), Some(Task::TransferGroupLeader(b))) => {
+                a.group == b.group
+            }
+            (Some(Task::SplitShard(a)), Some(Task::SplitShard(b))) => a.shard_id == b.shard_id,
+            (Some(Task::AddReadReplica(a)), Some(Task::AddReadReplica(b))) => a.group == b.group,
+            (a, b) => a == b,
+        },
+        _ => false,
+    }
+}
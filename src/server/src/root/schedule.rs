@@ -13,45 +13,96 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod pass;
 mod task;
 
 use log::{debug, error, info, warn};
 use prometheus::HistogramTimer;
 use sekas_api::server::v1::*;
-use sekas_rock::time::timestamp_millis;
+use sekas_rock::ascii::escape_bytes;
+use sekas_runtime::time::timestamp_millis;
 use tokio::sync::Mutex;
 
+pub use self::pass::outcome_name;
+use self::pass::{PassHistory, PassRecorder};
+use self::task::recommendation::Action;
 use self::task::reconcile_task::Task;
 pub use self::task::*;
 use super::allocator::*;
-use super::*;
+use super::health::{ClusterHealth, HealthAlert};
+use super::recommend::{self, SchedulePolicy};
+use super::schema::Schema;
+use super::unsafe_admin::new_topology_event;
+use super::{quota, *};
+use crate::ScheduleMode;
 
 pub struct ReconcileScheduler {
     ctx: ScheduleContext,
     tasks: Mutex<LinkedList<ReconcileTask>>,
+    passes: PassHistory,
+    /// The passes triggered by the timer and operators are serialized.
+    pass_lock: Mutex<()>,
 }
 
+/// The interval to check again the shards skipped splitting by the quotas.
+const QUOTA_SKIP_SPLIT_INTERVAL: Duration = Duration::from_secs(60);
+
 pub struct ScheduleContext {
     shared: Arc<RootShared>,
     alloc: Arc<Allocator<SysAllocSource>>,
     heartbeat_queue: Arc<HeartbeatQueue>,
     cluster_stats: Arc<ClusterStats>,
+    health: Arc<ClusterHealth>,
     bg_jobs: Arc<Jobs>,
     cfg: RootConfig,
+    /// The large shards skipped splitting since the shard quotas are exceeded.
+    quota_capped_shards: Mutex<HashSet<u64>>,
 }
 
 impl ReconcileScheduler {
     pub fn new(ctx: ScheduleContext) -> Self {
-        Self { ctx, tasks: Default::default() }
+        Self {
+            ctx,
+            tasks: Default::default(),
+            passes: PassHistory::default(),
+            pass_lock: Mutex::default(),
+        }
     }
 
     pub async fn poll_and_schedule(&self) -> Duration {
-        let cr = self.generate_schedule_task().await; // TODO: take care self.tasks then can give more > 1 value here.
+        self.run_pass(false).await;
+        Duration::from_secs(self.ctx.cfg.schedule_interval_sec)
+    }
+
+    /// Run a reconciliation pass and returns its summary, which is also
+    /// retained in the history.
+    pub async fn run_pass(&self, manual: bool) -> SchedulePass {
+        let _guard = self.pass_lock.lock().await;
+        let mut pass = self.passes.begin(manual);
+        let cr = self.generate_schedule_task(&mut pass).await; // TODO: take care self.tasks then can give more > 1 value here.
         if matches!(cr, Ok(true)) {
             let _step_timer = metrics::RECONCILE_STEP_DURATION_SECONDS.start_timer();
-            while self.advance_tasks().await {}
+            while self.advance_tasks(&mut pass).await {}
         }
-        Duration::from_secs(self.ctx.cfg.schedule_interval_sec)
+        let pass = pass.finish(cr.as_ref().err());
+        self.passes.record(pass.clone());
+        pass
+    }
+
+    /// The summaries of the recent passes, in descending order of seq.
+    pub fn schedule_passes(&self) -> Vec<SchedulePass> {
+        self.passes.passes()
+    }
+
+    /// Record the decision made out of the passes, eg. the cures, it is
+    /// reported by the next pass.
+    pub fn record_decision(
+        &self,
+        action: impl Into<String>,
+        outcome: schedule_decision::Outcome,
+        reason: impl Into<String>,
+    ) {
+        self.passes.record_decision(action.into(), outcome, reason.into());
     }
 
     pub async fn wait_one_heartbeat_tick(&self) {
@@ -88,61 +139,112 @@ impl ReconcileScheduler {
         .await;
     }
 
-    /// Schedule transfering leader task.
-    pub async fn sched_transfer_leader_task(&self, transfer_leader: TransferLeader) {
-        self.setup_task(ReconcileTask {
-            task: Some(reconcile_task::Task::TransferGroupLeader(TransferGroupLeaderTask {
-                group: transfer_leader.group,
-                target_replica: transfer_leader.target_replica,
-                src_node: transfer_leader.src_node,
-                dest_node: transfer_leader.target_node,
+    /// Schedule removing the ghost replica of the group, which is in the
+    /// descriptor of catalog but not in the descriptor reported by the leader
+    /// at the same epoch, eg. it is injected by the unsafe admin requests.
+    pub async fn sched_remove_ghost_replica(&self, group_id: u64, replica: &ReplicaDesc) {
+        let replica_id = replica.id;
+        let is_removing = |task: &ReconcileTask| matches!(&task.task, Some(Task::RemoveGhostReplica(t)) if t.replica == replica_id);
+        if self.tasks.lock().await.iter().any(is_removing) {
+            return;
+        }
+        let task = ReconcileTask {
+            task: Some(Task::RemoveGhostReplica(RemoveGhostReplicaTask {
+                group: group_id,
+                replica: replica_id,
+                node: replica.node_id,
             })),
             created_at: timestamp_millis(),
             fire_at: 0,
-        })
-        .await;
+        };
+        let action = task.describe();
+        self.setup_task(task).await;
+        let reason = format!("replica {replica_id} is not in the descriptor of the group leader");
+        self.record_decision(action, schedule_decision::Outcome::Taken, reason);
     }
 
-    /// Schedule migrate replica task.
-    pub async fn sched_migrate_replica_task(&self, action: ReallocateReplica) {
-        self.setup_task(ReconcileTask {
-            task: Some(reconcile_task::Task::ReallocateReplica(ReallocateReplicaTask {
-                group: action.group,
-                src_node: action.source_node,
-                src_replica: action.source_replica,
-                dest_node: Some(action.target_node),
-                dest_replica: None,
-            })),
-            created_at: timestamp_millis(),
-            fire_at: 0,
-        })
-        .await;
+    /// Schedule rebuilding the corrupted replica on another node. Like curing
+    /// the group which lost replicas, it requires an approval unless the groups
+    /// are cured automatically.
+    pub async fn sched_rebuild_replica(
+        &self,
+        schema: &Schema,
+        group_id: u64,
+        replica_id: u64,
+        node_id: u64,
+    ) -> Result<()> {
+        let Some(group) = schema.get_group(group_id).await? else {
+            return Ok(());
+        };
+        if !group.replicas.iter().any(|r| r.id == replica_id) {
+            // The replica has been moved out already.
+            return Ok(());
+        }
+        let is_rebuilding = |task: &ReconcileTask| matches!(&task.task, Some(Task::ReallocateReplica(t)) if t.src_replica == replica_id);
+        if self.tasks.lock().await.iter().any(is_rebuilding) {
+            return Ok(());
+        }
+
+        let existing_nodes = group.replicas.iter().map(|r| r.node_id).collect();
+        let Some(target_node) =
+            self.ctx.alloc.allocate_group_replica(existing_nodes, 1).await?.pop()
+        else {
+            warn!("no node to rebuild the corrupted replica {replica_id} of group {group_id}");
+            self.record_decision(
+                format!("rebuild replica {replica_id} of group {group_id}"),
+                schedule_decision::Outcome::Skipped,
+                "cure skipped: not enough healthy nodes",
+            );
+            return Ok(());
+        };
+        info!(
+            "rebuild the corrupted replica {replica_id} of group {group_id} from node {node_id} to node {}",
+            target_node.id
+        );
+        let task = migrate_replica_task(ReallocateReplica {
+            group: group_id,
+            source_node: node_id,
+            source_replica: replica_id,
+            target_node,
+        });
+        let action = task.describe();
+        let reason = format!("replica {replica_id} is corrupted");
+        if SchedulePolicy::load(schema, &self.ctx.cfg).await?.is_auto_cure() {
+            self.setup_task(task).await;
+            self.record_decision(action, schedule_decision::Outcome::Taken, reason);
+        } else {
+            recommend::recommend_actions(schema, vec![Action::Reconcile(task)]).await?;
+            self.record_decision(
+                action,
+                schedule_decision::Outcome::Recommended,
+                format!("{reason}, waiting for approval"),
+            );
+        }
+        Ok(())
     }
 
-    /// Schedule migrate shard task.
-    pub async fn sched_migrate_shard_task(&self, action: ReallocateShard) {
-        self.setup_task(ReconcileTask {
-            task: Some(reconcile_task::Task::MigrateShard(MigrateShardTask {
-                shard: action.shard,
-                src_group: action.source_group,
-                dest_group: action.target_group,
-            })),
-            created_at: timestamp_millis(),
-            fire_at: 0,
-        })
-        .await;
+    /// Schedule the reconcile task generated by the scheduler.
+    async fn sched_task(&self, task: ReconcileTask) {
+        if let Some(Task::SplitShard(split_shard)) = &task.task {
+            let (group_id, shard_id) = (split_shard.group_id, split_shard.shard_id);
+            debug!("sched split shard task, group_id {group_id}, shard_id {shard_id}");
+            self.ctx.cluster_stats.handle_split_shard(shard_id);
+        }
+        self.setup_task(task).await;
     }
 
-    /// Schedule split shard task.
-    pub async fn sched_split_shard_task(&self, group_id: u64, shard_id: u64) {
-        debug!("sched split shard task, group_id {group_id}, shard_id {shard_id}");
-        self.setup_task(ReconcileTask {
-            task: Some(reconcile_task::Task::SplitShard(SplitShardTask { group_id, shard_id })),
-            created_at: timestamp_millis(),
-            fire_at: 0,
-        })
-        .await;
-        self.ctx.cluster_stats.handle_split_shard(shard_id);
+    /// Schedule the approved recommendations, and expire the out-of-date ones.
+    async fn sched_approved_tasks(&self, schema: &Schema, pass: &mut PassRecorder) -> Result<()> {
+        for desc in recommend::refresh_recommendations(schema).await? {
+            // The approved cure is executed once the group applies for new
+            // replicas.
+            if let Some(Action::Reconcile(task)) = desc.action.clone() {
+                pass.taken(task.describe(), format!("recommendation {} is approved", desc.id));
+                self.sched_task(task).await;
+                recommend::finish_recommendation(schema, desc).await?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -165,40 +267,112 @@ impl ReconcileScheduler {
         Ok(false)
     }
 
-    pub async fn generate_schedule_task(&self) -> Result<bool> {
+    pub async fn generate_schedule_task(&self, pass: &mut PassRecorder) -> Result<bool> {
         let _timer = super::metrics::RECONCILE_CHECK_DURATION_SECONDS.start_timer();
+        let schema = self.ctx.shared.schema()?;
+        let policy = SchedulePolicy::load(&schema, &self.ctx.cfg).await?;
+        self.sched_approved_tasks(&schema, pass).await?;
+
+        let cfg = &self.ctx.cfg;
         let group_action = self.ctx.alloc.compute_group_action().await?;
+        let (num_nodes, num_groups) = self.ctx.alloc.num_nodes_and_groups();
+        pass.set_groups_examined(num_groups);
         if let GroupAction::Add(cnt) = group_action {
             metrics::RECONCILE_ALREADY_BALANCED_INFO.cluster_groups.set(0);
             for _ in 0..cnt {
                 self.ctx.bg_jobs.submit_create_group_job().await?;
             }
+            pass.taken(format!("create {cnt} groups"), "the groups are fewer than desired");
             return Ok(true);
         }
         metrics::RECONCILE_ALREADY_BALANCED_INFO.cluster_groups.set(1);
+        if !cfg.enable_group_balance {
+            pass.skipped("create groups", "create group skipped: disabled by config");
+        } else if num_nodes < cfg.replicas_per_group {
+            let reason = format!(
+                "create group skipped: not enough nodes, {num_nodes} of {} required",
+                cfg.replicas_per_group
+            );
+            pass.skipped("create groups", reason);
+        }
+        for (enabled, action) in [
+            (cfg.enable_replica_balance, "replica balance"),
+            (cfg.enable_leader_balance, "leader balance"),
+            (cfg.enable_shard_balance, "shard balance"),
+        ] {
+            if !enabled {
+                pass.skipped(action, format!("{action} skipped: disabled by config"));
+            }
+        }
 
         let ractions = self.comput_replica_role_action().await?;
         let sactions = self.ctx.alloc.compute_shard_action().await?;
+        // The tasks with the reasons why they are generated.
+        let mut tasks = Vec::new();
         for action in ractions {
             match action {
-                ReplicaRoleAction::Replica(ReplicaAction::Migrate(action)) => {
-                    self.sched_migrate_replica_task(action).await
-                }
-                ReplicaRoleAction::Leader(LeaderAction::Shed(action)) => {
-                    self.sched_transfer_leader_task(action).await
-                }
+                ReplicaRoleAction::Replica(ReplicaAction::Migrate(action)) => tasks.push((
+                    migrate_replica_task(action),
+                    "the replicas are unbalanced across nodes",
+                )),
+                ReplicaRoleAction::Leader(LeaderAction::Shed(action)) => tasks.push((
+                    transfer_leader_task(action),
+                    "the leaders are unbalanced or out of the preferred zone",
+                )),
                 _ => {}
             }
         }
 
         for action in sactions {
             match action {
-                ShardAction::Migrate(action) => self.sched_migrate_shard_task(action).await,
+                ShardAction::Migrate(action) => tasks
+                    .push((migrate_shard_task(action), "the shards are unbalanced across groups")),
             }
         }
 
+        let mut migrating_shards =
+            self.tasks.lock().await.iter().filter_map(migrating_shard).collect::<HashSet<_>>();
+        migrating_shards.extend(tasks.iter().filter_map(|(task, _)| migrating_shard(task)));
         for (group_id, shard_id) in self.ctx.cluster_stats.get_large_shards(5) {
-            self.sched_split_shard_task(group_id, shard_id).await;
+            if migrating_shards.contains(&shard_id) {
+                let action = format!("split shard {shard_id} of group {group_id}");
+                pass.skipped(action, "split skipped: migration in flight");
+                continue;
+            }
+            tasks.push((split_shard_task(group_id, shard_id), "the shard exceeds the split size"));
+        }
+        for (shard_id, reason) in self.ctx.cluster_stats.get_skipped_large_shards() {
+            pass.skipped(format!("split shard {shard_id}"), format!("split skipped: {reason}"));
+        }
+        let hot_keys = self.ctx.cluster_stats.get_hot_key_shards();
+        self.ctx.health.refresh_hot_keys(
+            hot_keys
+                .into_iter()
+                .map(|(group_id, shard_id, key)| HealthAlert::HotKey {
+                    group_id,
+                    shard_id,
+                    key: escape_bytes(&key),
+                })
+                .collect(),
+        );
+
+        let table_read_replicas = table_read_replicas(&schema).await?;
+        for action in self.ctx.alloc.compute_read_replica_action(&table_read_replicas).await? {
+            tasks.push((read_replica_task(action), "the read replicas differ from the tables"));
+        }
+
+        if policy.mode == ScheduleMode::Auto {
+            for (task, reason) in tasks {
+                pass.taken(task.describe(), reason);
+                self.sched_task(task).await;
+            }
+        } else {
+            let mode = policy.mode.as_str();
+            for (task, reason) in &tasks {
+                pass.recommended(task.describe(), format!("{reason}, waiting in {mode} mode"));
+            }
+            let actions = tasks.into_iter().map(|(task, _)| Action::Reconcile(task)).collect();
+            recommend::recommend_actions(&schema, actions).await?;
         }
 
         Ok(!self.is_empty().await)
@@ -215,7 +389,9 @@ impl ReconcileScheduler {
         actions.extend_from_slice(
             &replica_actions.iter().cloned().map(ReplicaRoleAction::Replica).collect::<Vec<_>>(),
         );
-        let leader_actions = self.ctx.alloc.compute_leader_action().await?;
+        let schema = self.ctx.shared.schema()?;
+        let leader_zones = group_leader_zones(&schema).await?;
+        let leader_actions = self.ctx.alloc.compute_leader_action(&leader_zones).await?;
         if leader_actions.is_empty() {
             metrics::RECONCILE_ALREADY_BALANCED_INFO.node_leader_count.set(1);
         } else {
@@ -229,7 +405,7 @@ impl ReconcileScheduler {
 }
 
 impl ReconcileScheduler {
-    async fn advance_tasks(&self) -> bool {
+    async fn advance_tasks(&self, pass: &mut PassRecorder) -> bool {
         let mut task = self.tasks.lock().await;
         let mut nowait_next = !task.is_empty();
         metrics::RECONCILE_SCHEDULER_TASK_QUEUE_SIZE.set(task.len() as i64);
@@ -243,12 +419,24 @@ impl ReconcileScheduler {
             }
 
             let _timer = Self::record_exec(task);
-            let Ok(sched_result) = self.ctx.handle_task(task).await else {
-                Self::record_retry(task);
-                // ack == false or meet error, skip current task and retry later.
-                cursor.move_next();
-                continue;
+            let action = task.describe();
+            let mut sched_result = match self.ctx.handle_task(task).await {
+                Ok(sched_result) => sched_result,
+                Err(err) => {
+                    pass.deferred(action, format!("retry later: {err}"));
+                    Self::record_retry(task);
+                    // ack == false or meet error, skip current task and retry later.
+                    cursor.move_next();
+                    continue;
+                }
             };
+            if let Some(reason) = sched_result.skipped.take() {
+                pass.skipped(action, reason);
+            } else if sched_result.ack {
+                pass.taken(action, "executed");
+            } else {
+                pass.deferred(action, "retry later");
+            }
 
             if sched_result.ack {
                 cursor.remove_current();
@@ -294,6 +482,18 @@ impl ReconcileScheduler {
                 metrics::RECONCILE_HANDLE_TASK_TOTAL.split_shard.inc();
                 metrics::RECONCILE_HANDLE_TASK_DURATION_SECONDS.split_shard.start_timer()
             }
+            Task::AddReadReplica(_) => {
+                metrics::RECONCILE_HANDLE_TASK_TOTAL.add_read_replica.inc();
+                metrics::RECONCILE_HANDLE_TASK_DURATION_SECONDS.add_read_replica.start_timer()
+            }
+            Task::RemoveReadReplica(_) => {
+                metrics::RECONCILE_HANDLE_TASK_TOTAL.remove_read_replica.inc();
+                metrics::RECONCILE_HANDLE_TASK_DURATION_SECONDS.remove_read_replica.start_timer()
+            }
+            Task::RemoveGhostReplica(_) => {
+                metrics::RECONCILE_HANDLE_TASK_TOTAL.remove_ghost_replica.inc();
+                metrics::RECONCILE_HANDLE_TASK_DURATION_SECONDS.remove_ghost_replica.start_timer()
+            }
         }
     }
 
@@ -309,10 +509,145 @@ impl ReconcileScheduler {
             Task::ShedLeader(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.shed_group_leaders.inc(),
             Task::ShedRoot(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.shed_root_leader.inc(),
             Task::SplitShard(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.split_shard.inc(),
+            Task::AddReadReplica(_) => metrics::RECONCILE_RETRY_TASK_TOTAL.add_read_replica.inc(),
+            Task::RemoveReadReplica(_) => {
+                metrics::RECONCILE_RETRY_TASK_TOTAL.remove_read_replica.inc()
+            }
+            Task::RemoveGhostReplica(_) => {
+                metrics::RECONCILE_RETRY_TASK_TOTAL.remove_ghost_replica.inc()
+            }
         }
     }
 }
 
+/// The shard migrated by the task.
+fn migrating_shard(task: &ReconcileTask) -> Option<u64> {
+    match &task.task {
+        Some(Task::MigrateShard(t)) => Some(t.shard),
+        _ => None,
+    }
+}
+
+fn transfer_leader_task(transfer_leader: TransferLeader) -> ReconcileTask {
+    ReconcileTask {
+        task: Some(reconcile_task::Task::TransferGroupLeader(TransferGroupLeaderTask {
+            group: transfer_leader.group,
+            target_replica: transfer_leader.target_replica,
+            src_node: transfer_leader.src_node,
+            dest_node: transfer_leader.target_node,
+        })),
+        created_at: timestamp_millis(),
+        fire_at: 0,
+    }
+}
+
+fn migrate_replica_task(action: ReallocateReplica) -> ReconcileTask {
+    ReconcileTask {
+        task: Some(reconcile_task::Task::ReallocateReplica(ReallocateReplicaTask {
+            group: action.group,
+            src_node: action.source_node,
+            src_replica: action.source_replica,
+            dest_node: Some(action.target_node),
+            dest_replica: None,
+        })),
+        created_at: timestamp_millis(),
+        fire_at: 0,
+    }
+}
+
+fn migrate_shard_task(action: ReallocateShard) -> ReconcileTask {
+    ReconcileTask {
+        task: Some(reconcile_task::Task::MigrateShard(MigrateShardTask {
+            shard: action.shard,
+            src_group: action.source_group,
+            dest_group: action.target_group,
+        })),
+        created_at: timestamp_millis(),
+        fire_at: 0,
+    }
+}
+
+fn split_shard_task(group_id: u64, shard_id: u64) -> ReconcileTask {
+    ReconcileTask {
+        task: Some(reconcile_task::Task::SplitShard(SplitShardTask { group_id, shard_id })),
+        created_at: timestamp_millis(),
+        fire_at: 0,
+    }
+}
+
+fn read_replica_task(action: ReadReplicaAction) -> ReconcileTask {
+    let task = match action {
+        ReadReplicaAction::Add { group, target_node } => {
+            reconcile_task::Task::AddReadReplica(AddReadReplicaTask {
+                group,
+                dest_node: target_node,
+            })
+        }
+        ReadReplicaAction::Remove { group, replica, node } => {
+            reconcile_task::Task::RemoveReadReplica(RemoveReadReplicaTask { group, replica, node })
+        }
+    };
+    ReconcileTask { task: Some(task), created_at: timestamp_millis(), fire_at: 0 }
+}
+
+/// The number of read replicas required by each table.
+async fn table_read_replicas(schema: &Schema) -> Result<HashMap<u64, usize>> {
+    let mut table_read_replicas = HashMap::default();
+    for table in schema.list_table().await? {
+        let num_read_replicas = table
+            .properties
+            .get(sekas_schema::property::READ_REPLICAS)
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or_default();
+        if num_read_replicas > 0 {
+            table_read_replicas.insert(table.id, num_read_replicas);
+        }
+    }
+    Ok(table_read_replicas)
+}
+
+/// The zone preferred to place the leader of each group, by the
+/// `leader_preference` of the tables served by the group. The group serving the
+/// tables with different preferences follows the preference of the most shards,
+/// and it is left alone if there is a tie.
+pub(super) async fn group_leader_zones(schema: &Schema) -> Result<HashMap<u64, String>> {
+    let mut table_zones = HashMap::default();
+    for table in schema.list_table().await? {
+        match table.properties.get(sekas_schema::property::LEADER_PREFERENCE) {
+            Some(zone) if !zone.is_empty() => {
+                table_zones.insert(table.id, zone.to_owned());
+            }
+            _ => {}
+        }
+    }
+    if table_zones.is_empty() {
+        return Ok(HashMap::default());
+    }
+
+    let mut leader_zones = HashMap::default();
+    let mut filter = GroupFilter::default();
+    loop {
+        let page = schema.list_group_page(&filter).await?;
+        for group in &page.items {
+            let mut num_shards = HashMap::<&str, usize>::default();
+            for shard in &group.shards {
+                if let Some(zone) = table_zones.get(&shard.table_id) {
+                    *num_shards.entry(zone.as_str()).or_default() += 1;
+                }
+            }
+            let Some(max) = num_shards.values().max().cloned() else { continue };
+            let mut majority = num_shards.into_iter().filter(|(_, n)| *n == max);
+            if let (Some((zone, _)), None) = (majority.next(), majority.next()) {
+                leader_zones.insert(group.id, zone.to_owned());
+            }
+        }
+        if !page.has_more() {
+            return Ok(leader_zones);
+        }
+        filter.page_token = page.next_page_token;
+    }
+}
+
 #[derive(Debug, Default)]
 struct SchedResult {
     /// Ack current task.
@@ -321,6 +656,8 @@ struct SchedResult {
     immediately_next: bool,
     /// Delay task by duration.
     delay: Option<Duration>,
+    /// The reason why the task is skipped.
+    skipped: Option<String>,
 }
 
 impl SchedResult {
@@ -336,7 +673,13 @@ impl SchedResult {
 
     /// Immediately step next and save the delay intervals.
     fn delay(duration: Duration) -> Self {
-        SchedResult { ack: true, immediately_next: true, delay: Some(duration) }
+        SchedResult { ack: true, immediately_next: true, delay: Some(duration), skipped: None }
+    }
+
+    /// Record the reason why the task is skipped.
+    fn skip(mut self, reason: impl Into<String>) -> Self {
+        self.skipped = Some(reason.into());
+        self
     }
 }
 
@@ -346,10 +689,20 @@ impl ScheduleContext {
         alloc: Arc<Allocator<SysAllocSource>>,
         heartbeat_queue: Arc<HeartbeatQueue>,
         cluster_stats: Arc<ClusterStats>,
+        health: Arc<ClusterHealth>,
         bg_jobs: Arc<Jobs>,
         cfg: RootConfig,
     ) -> Self {
-        ScheduleContext { shared, alloc, heartbeat_queue, cluster_stats, bg_jobs, cfg }
+        ScheduleContext {
+            shared,
+            alloc,
+            heartbeat_queue,
+            cluster_stats,
+            health,
+            bg_jobs,
+            cfg,
+            quota_capped_shards: Mutex::default(),
+        }
     }
 
     async fn handle_task(&self, task: &mut ReconcileTask) -> Result<SchedResult> {
@@ -365,6 +718,15 @@ impl ScheduleContext {
             Task::ShedLeader(shed_leader) => self.handle_shed_leader(shed_leader).await,
             Task::ShedRoot(shed_root) => self.handle_shed_root(shed_root).await,
             Task::SplitShard(split_shard) => self.handle_split_shard(split_shard).await,
+            Task::AddReadReplica(add_read_replica) => {
+                self.handle_add_read_replica(add_read_replica).await
+            }
+            Task::RemoveReadReplica(remove_read_replica) => {
+                self.handle_remove_read_replica(remove_read_replica).await
+            }
+            Task::RemoveGhostReplica(remove_ghost_replica) => {
+                self.handle_remove_ghost_replica(remove_ghost_replica).await
+            }
         }
     }
 
@@ -465,7 +827,14 @@ impl ScheduleContext {
                     "abort migrate shard. shard={}, src={}, dest={}, reason={reason}",
                     task.shard, task.src_group, task.dest_group
                 );
-                Ok(SchedResult::ack())
+                Ok(SchedResult::ack().skip(format!("migration skipped: {reason}")))
+            }
+            Err(err @ crate::Error::TxnShardFenced(_)) => {
+                warn!(
+                    "abort migrate shard: {err}. shard={}, src={}, dest={}",
+                    task.shard, task.src_group, task.dest_group
+                );
+                Ok(SchedResult::ack().skip(format!("migration skipped: {err}")))
             }
             Err(err) => {
                 warn!(
@@ -521,11 +890,12 @@ impl ScheduleContext {
                 }
             }
 
+            let filter = ReplicaStateFilter { node_id: Some(node), ..Default::default() };
             let leader_replicas = schema
-                .list_replica_state()
+                .list_replica_state_with(&filter)
                 .await?
                 .into_iter()
-                .filter(|r| r.node_id == node && r.role == RaftRole::Leader as i32)
+                .filter(|r| r.role == RaftRole::Leader as i32)
                 .collect::<Vec<_>>();
 
             // exit when all leader move-out
@@ -540,9 +910,14 @@ impl ScheduleContext {
                 break;
             }
 
+            let leader_zones = group_leader_zones(&schema).await?;
             for replica in &leader_replicas {
                 let group_id = replica.group_id;
                 if let Some(group) = schema.get_group(group_id).await? {
+                    // The replica in the preferred zone takes over the leadership
+                    // if it exists.
+                    let zone = leader_zones.get(&group_id);
+                    let mut in_zone = false;
                     let mut target_replica = None;
                     for r in &group.replicas {
                         if r.id == replica.replica_id {
@@ -552,9 +927,16 @@ impl ScheduleContext {
                         if target_node.is_none() {
                             continue;
                         }
-                        if target_node.as_ref().unwrap().status != NodeStatus::Active as i32 {
+                        let target_node = target_node.unwrap();
+                        if target_node.status != NodeStatus::Active as i32 {
                             continue;
                         }
+                        let is_preferred =
+                            zone.map(|z| target_node.labels.contains(z)).unwrap_or_default();
+                        if in_zone && !is_preferred {
+                            continue;
+                        }
+                        in_zone = is_preferred;
                         target_replica = Some(r.to_owned())
                     }
                     if let Some(target_replica) = target_replica {
@@ -609,15 +991,40 @@ impl ScheduleContext {
     /// Handle the spliting shard stask.
     async fn handle_split_shard_inner(&self, task: &mut SplitShardTask) -> Result<SchedResult> {
         let schema = self.shared.schema()?;
-        if schema.get_group(task.group_id).await?.is_none() {
+        let Some(group) = schema.get_group(task.group_id).await? else {
             warn!("split shard {} but group {} is not exists", task.shard_id, task.group_id);
-            return Ok(SchedResult::next());
+            return Ok(SchedResult::next().skip("split skipped: the group is not exists"));
+        };
+        let Some(shard) = group.shards.iter().find(|s| s.id == task.shard_id) else {
+            warn!("split shard {} but it is not in group {}", task.shard_id, task.group_id);
+            return Ok(SchedResult::next().skip("split skipped: the shard is moved out"));
         };
 
+        // The shard at the quota is skipped rather than failed, it is scheduled again
+        // once it is still large later, the event is recorded only once.
         let old_shard_id = task.shard_id;
+        match quota::check_split_shard(&schema, shard.table_id).await {
+            Ok(()) => {
+                self.quota_capped_shards.lock().await.remove(&old_shard_id);
+            }
+            Err(err @ crate::Error::QuotaExceeded { .. }) => {
+                info!("skip split shard {old_shard_id} of group {}: {err}", task.group_id);
+                metrics::QUOTA_SKIPPED_SPLIT_SHARD_TOTAL.inc();
+                if self.quota_capped_shards.lock().await.insert(old_shard_id) {
+                    quota::record_skipped_split(&schema, old_shard_id, &err).await?;
+                }
+                let reason = format!("split skipped: {err}");
+                return Ok(SchedResult::delay(QUOTA_SKIP_SPLIT_INTERVAL).skip(reason));
+            }
+            Err(err) => return Err(err),
+        }
+
         let new_shard_id = schema.next_shard_id().await?;
         match self.try_split_shard(task.group_id, old_shard_id, new_shard_id).await {
-            Ok(_) => Ok(SchedResult::next()),
+            Ok(_) => {
+                schema.quota_usages().add_split_shard(shard.table_id).await;
+                Ok(SchedResult::next())
+            }
             Err(crate::Error::EpochNotMatch(_)) => {
                 warn!(
                     "split shard meet epoch not match, abort split shard task. group={}, shard={}, new_shard={}",
@@ -627,7 +1034,15 @@ impl ScheduleContext {
             Err(crate::Error::InvalidArgument(msg))
                 if msg.contains("shard estimated split keys is empty") =>
             {
-                Ok(SchedResult::delay(Duration::from_secs(30)))
+                Ok(SchedResult::delay(Duration::from_secs(30))
+                    .skip("split skipped: the split keys are not estimated yet"))
+            }
+            Err(err @ crate::Error::TxnShardFenced(_)) => {
+                warn!(
+                    "abort split shard task: {err}. group={}, shard={old_shard_id}",
+                    task.group_id
+                );
+                Ok(SchedResult::next().skip(format!("split skipped: {err}")))
             }
             Err(err) => {
                 error!(
@@ -641,6 +1056,101 @@ impl ScheduleContext {
 }
 
 impl ScheduleContext {
+    async fn handle_add_read_replica(&self, task: &mut AddReadReplicaTask) -> Result<SchedResult> {
+        let schema = self.shared.schema()?;
+        let (group, node) = (task.group, task.dest_node);
+        let Some(group_desc) = schema.get_group(group).await? else {
+            warn!("group not found abort add read replica task. group={group}");
+            return Ok(SchedResult::ack());
+        };
+        if group_desc.replicas.iter().any(|r| r.node_id == node) {
+            warn!(
+                "node already has replica, abort add read replica task. group={group}, node={node}"
+            );
+            return Ok(SchedResult::ack().skip("add read replica skipped: the node has a replica"));
+        }
+
+        let replica = schema.next_replica_id().await?;
+        info!("start add read replica. group={group}, replica={replica}, node={node}");
+        let client = self.shared.transport_manager.find_node_client(node)?;
+        client.create_replica(replica, GroupDesc { id: group, ..Default::default() }).await?;
+        let mut group_client = self.shared.transport_manager.lazy_group_client(group);
+        match group_client.add_read_replica(replica, node).await {
+            Ok(()) => Ok(SchedResult::ack()),
+            Err(err) => {
+                // Don't retry with the created replica, the allocator will plan it again in the
+                // next round.
+                warn!(
+                    "add read replica meet error, abort task: {err:?}. group={group}, replica={replica}, node={node}"
+                );
+                Ok(SchedResult::ack())
+            }
+        }
+    }
+
+    async fn handle_remove_read_replica(
+        &self,
+        task: &mut RemoveReadReplicaTask,
+    ) -> Result<SchedResult> {
+        let (group, replica) = (task.group, task.replica);
+        info!("start remove read replica. group={group}, replica={replica}, node={}", task.node);
+        let mut group_client = self.shared.transport_manager.lazy_group_client(group);
+        match group_client.remove_group_replica(replica).await {
+            Ok(()) => Ok(SchedResult::ack()),
+            Err(sekas_client::Error::EpochNotMatch(_)) => {
+                warn!("remove read replica meet epoch not match, abort task. group={group}, replica={replica}");
+                Ok(SchedResult::ack())
+            }
+            Err(err) => {
+                warn!("remove read replica meet error and retry later: {err:?}. group={group}, replica={replica}");
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Remove the ghost replica by ChangeReplicas. The replica is not a member
+    /// of the raft group, so the change only bumps the epoch of the group, then
+    /// the descriptor of the leader replaces the one in catalog by heartbeat.
+    async fn handle_remove_ghost_replica(
+        &self,
+        task: &mut RemoveGhostReplicaTask,
+    ) -> Result<SchedResult> {
+        let (group, replica, node) = (task.group, task.replica, task.node);
+        let schema = self.shared.schema()?;
+        let Some(group_desc) = schema.get_group(group).await? else {
+            return Ok(SchedResult::ack());
+        };
+        if group_desc.replicas.iter().all(|r| r.id != replica) {
+            return Ok(SchedResult::ack().skip("remove ghost replica skipped: the replica is gone"));
+        }
+
+        info!("start remove ghost replica. group={group}, replica={replica}, node={node}");
+        let mut group_client = self.shared.transport_manager.lazy_group_client(group);
+        match group_client.remove_group_replica(replica).await {
+            Ok(()) => {}
+            Err(sekas_client::Error::EpochNotMatch(_)) => {
+                warn!("remove ghost replica meet epoch not match, abort task. group={group}, replica={replica}");
+                return Ok(SchedResult::ack());
+            }
+            Err(err) => {
+                warn!("remove ghost replica meet error and retry later: {err:?}. group={group}, replica={replica}");
+                return Err(err.into());
+            }
+        }
+        let detail = format!("remove ghost replica {replica} on node {node} by ChangeReplicas");
+        let target = format!("group/{group}");
+        let event = new_topology_event(topology_event::Kind::Repair, target, detail);
+        schema.append_topology_event(event).await?;
+        let tasks = group_desc
+            .replicas
+            .iter()
+            .filter(|r| r.id != replica)
+            .map(|r| HeartbeatTask { node_id: r.node_id })
+            .collect::<Vec<_>>();
+        self.heartbeat_queue.try_schedule(tasks, Instant::now()).await;
+        Ok(SchedResult::ack())
+    }
+
     async fn get_group_leader(&self, group_id: u64) -> Result<Option<GroupDesc>> {
         let schema = self.shared.schema()?;
         let group = schema.get_group(group_id).await?;
@@ -698,6 +1208,10 @@ impl ScheduleContext {
     }
 
     async fn try_migrate_shard(&self, src_group: u64, target_group: u64, shard: u64) -> Result<()> {
+        if sekas_schema::shard::is_txn_shard(shard) {
+            return Err(crate::Error::TxnShardFenced(shard));
+        }
+
         let src_group = self
             .get_group_leader(src_group)
             .await?
@@ -737,6 +1251,10 @@ impl ScheduleContext {
         old_shard_id: u64,
         new_shard_id: u64,
     ) -> Result<()> {
+        if sekas_schema::shard::is_txn_shard(old_shard_id) {
+            return Err(crate::Error::TxnShardFenced(old_shard_id));
+        }
+
         let mut group_client = self.shared.transport_manager.lazy_group_client(group_id);
         group_client.split_shard(old_shard_id, new_shard_id, None).await?;
         Ok(())
@@ -0,0 +1,200 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The summaries of the reconciliation passes of the scheduler.
+//!
+//! Each pass records the decisions on the actions it considered, with the
+//! reasons why they are taken or skipped, so an operator could tell why the
+//! scheduler isn't doing anything. The summaries of the recent passes are
+//! retained in memory, see `SHOW scheduler`.
+//!
+//! The cures are driven by the groups applying for replicas and the corrupted
+//! replicas reported by the heartbeats rather than by the passes, their
+//! decisions are recorded by the next pass.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use log::debug;
+use sekas_api::server::v1::schedule_decision::Outcome;
+use sekas_api::server::v1::{ScheduleDecision, SchedulePass};
+use sekas_runtime::time::{timestamp_millis, Instant};
+
+use super::super::Root;
+use crate::{Error, Result};
+
+/// The number of the recent passes retained.
+const MAX_RETAINED_PASSES: usize = 32;
+
+/// The number of the decisions retained between the passes, the groups keep
+/// applying for replicas until they are cured.
+const MAX_PENDING_DECISIONS: usize = 256;
+
+/// The summaries of the recent passes.
+#[derive(Default)]
+pub struct PassHistory {
+    next_seq: AtomicU64,
+    passes: Mutex<VecDeque<SchedulePass>>,
+    /// The decisions made between the passes, they are taken by the next pass.
+    pending: Mutex<Vec<ScheduleDecision>>,
+}
+
+/// Collect the decisions of a running pass.
+pub struct PassRecorder {
+    pass: SchedulePass,
+    start: Instant,
+}
+
+impl PassHistory {
+    pub fn begin(&self, manual: bool) -> PassRecorder {
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed) + 1;
+        let decisions = std::mem::take(&mut *self.pending.lock().expect("poisoned"));
+        let pass = SchedulePass {
+            seq,
+            started_at: timestamp_millis(),
+            manual,
+            decisions,
+            ..Default::default()
+        };
+        PassRecorder { pass, start: Instant::now() }
+    }
+
+    /// Record the decision made between the passes, eg. the cures, it is
+    /// reported by the next pass.
+    pub fn record_decision(&self, action: String, outcome: Outcome, reason: String) {
+        debug!("schedule decision: {action}, {}: {reason}", outcome_name(outcome));
+        let mut pending = self.pending.lock().expect("poisoned");
+        if pending.len() >= MAX_PENDING_DECISIONS {
+            pending.remove(0);
+        }
+        pending.push(ScheduleDecision { action, outcome: outcome.into(), reason });
+    }
+
+    /// Retain the finished pass, the oldest one is evicted if the history is
+    /// full.
+    pub fn record(&self, pass: SchedulePass) {
+        let mut passes = self.passes.lock().expect("poisoned");
+        if passes.len() >= MAX_RETAINED_PASSES {
+            passes.pop_back();
+        }
+        passes.push_front(pass);
+    }
+
+    /// The retained passes in descending order of seq.
+    pub fn passes(&self) -> Vec<SchedulePass> {
+        self.passes.lock().expect("poisoned").iter().cloned().collect()
+    }
+}
+
+impl PassRecorder {
+    pub fn set_groups_examined(&mut self, groups: usize) {
+        self.pass.groups_examined = groups as u64;
+    }
+
+    pub fn taken(&mut self, action: impl Into<String>, reason: impl Into<String>) {
+        self.decide(action.into(), Outcome::Taken, reason.into());
+    }
+
+    pub fn skipped(&mut self, action: impl Into<String>, reason: impl Into<String>) {
+        self.decide(action.into(), Outcome::Skipped, reason.into());
+    }
+
+    pub fn recommended(&mut self, action: impl Into<String>, reason: impl Into<String>) {
+        self.decide(action.into(), Outcome::Recommended, reason.into());
+    }
+
+    pub fn deferred(&mut self, action: impl Into<String>, reason: impl Into<String>) {
+        self.decide(action.into(), Outcome::Deferred, reason.into());
+    }
+
+    fn decide(&mut self, action: String, outcome: Outcome, reason: String) {
+        debug!("schedule pass {}: {action}, {}: {reason}", self.pass.seq, outcome_name(outcome));
+        self.pass.decisions.push(ScheduleDecision { action, outcome: outcome.into(), reason });
+    }
+
+    /// Finish the pass, the error aborted the pass is recorded if exists.
+    pub fn finish(mut self, err: Option<&Error>) -> SchedulePass {
+        self.pass.duration_ms = self.start.elapsed().as_millis() as u64;
+        if let Some(err) = err {
+            self.pass.error = err.to_string();
+        }
+        self.pass
+    }
+}
+
+impl Root {
+    /// The summaries of the recent passes of the scheduler, in descending
+    /// order of seq.
+    pub fn list_schedule_passes(&self) -> Result<Vec<SchedulePass>> {
+        self.schema()?;
+        Ok(self.scheduler.schedule_passes())
+    }
+
+    /// Run a pass of the scheduler immediately, it waits for the running pass
+    /// if exists.
+    pub async fn trigger_schedule_pass(&self) -> Result<SchedulePass> {
+        self.schema()?;
+        Ok(self.scheduler.run_pass(true).await)
+    }
+}
+
+/// The name of the outcome of a decision.
+pub fn outcome_name(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Taken => "taken",
+        Outcome::Skipped => "skipped",
+        Outcome::Recommended => "recommended",
+        Outcome::Deferred => "deferred",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retain_recent_passes() {
+        let history = PassHistory::default();
+        for i in 0..(MAX_RETAINED_PASSES + 2) {
+            let mut pass = history.begin(i % 2 == 0);
+            pass.skipped(format!("split shard {i}"), "split skipped: migration in flight");
+            history.record(pass.finish(None));
+        }
+        let passes = history.passes();
+        assert_eq!(passes.len(), MAX_RETAINED_PASSES);
+        assert_eq!(passes[0].seq, MAX_RETAINED_PASSES as u64 + 2);
+        assert_eq!(passes.last().unwrap().seq, 3);
+        assert_eq!(passes[0].decisions[0].outcome, Outcome::Skipped as i32);
+        assert_eq!(passes[0].decisions[0].reason, "split skipped: migration in flight");
+    }
+
+    #[test]
+    fn decisions_between_passes() {
+        let history = PassHistory::default();
+        history.record_decision(
+            "cure group 1 by adding 1 replicas".into(),
+            Outcome::Skipped,
+            "cure skipped: not enough healthy nodes".into(),
+        );
+        let mut pass = history.begin(false);
+        pass.skipped("create groups", "create group skipped: disabled by config");
+        let pass = pass.finish(None);
+        assert_eq!(pass.decisions.len(), 2);
+        assert_eq!(pass.decisions[0].reason, "cure skipped: not enough healthy nodes");
+
+        // The decisions are reported by only one pass.
+        assert!(history.begin(false).finish(None).decisions.is_empty());
+    }
+}
@@ -18,7 +18,7 @@ pub struct ReconcileTask {
     pub created_at: u64,
     #[prost(uint64, tag = "129")]
     pub fire_at: u64,
-    #[prost(oneof = "reconcile_task::Task", tags = "1, 2, 3, 4, 5")]
+    #[prost(oneof = "reconcile_task::Task", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9")]
     pub task: ::core::option::Option<reconcile_task::Task>,
 }
 
@@ -38,6 +38,12 @@ pub mod reconcile_task {
         ShedRoot(super::ShedRootLeaderTask),
         #[prost(message, tag = "6")]
         SplitShard(super::SplitShardTask),
+        #[prost(message, tag = "7")]
+        AddReadReplica(super::AddReadReplicaTask),
+        #[prost(message, tag = "8")]
+        RemoveReadReplica(super::RemoveReadReplicaTask),
+        #[prost(message, tag = "9")]
+        RemoveGhostReplica(super::RemoveGhostReplicaTask),
     }
 }
 
@@ -97,11 +103,133 @@ pub struct SplitShardTask {
     pub group_id: u64,
 }
 
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddReadReplicaTask {
+    #[prost(uint64, tag = "1")]
+    pub group: u64,
+    #[prost(uint64, tag = "2")]
+    pub dest_node: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoveReadReplicaTask {
+    #[prost(uint64, tag = "1")]
+    pub group: u64,
+    #[prost(uint64, tag = "2")]
+    pub replica: u64,
+    #[prost(uint64, tag = "3")]
+    pub node: u64,
+}
+
+/// Remove the replica which is in the descriptor of catalog, but not in the
+/// descriptor of the group leader at the same epoch.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoveGhostReplicaTask {
+    #[prost(uint64, tag = "1")]
+    pub group: u64,
+    #[prost(uint64, tag = "2")]
+    pub replica: u64,
+    #[prost(uint64, tag = "3")]
+    pub node: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Recommendation {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(enumeration = "RecommendationStatus", tag = "2")]
+    pub status: i32,
+    /// The epochs of the involved groups when the recommendation is computed.
+    #[prost(map = "uint64, uint64", tag = "3")]
+    pub group_epochs: ::std::collections::HashMap<u64, u64>,
+    #[prost(uint64, tag = "4")]
+    pub created_at: u64,
+    #[prost(oneof = "recommendation::Action", tags = "5, 6")]
+    pub action: ::core::option::Option<recommendation::Action>,
+}
+
+impl Recommendation {
+    /// Describe the recommended action.
+    pub fn describe(&self) -> String {
+        use recommendation::Action;
+
+        match self.action.as_ref().unwrap() {
+            Action::Reconcile(task) => task.describe(),
+            Action::CureGroup(c) => {
+                format!("cure group {} by adding {} replicas", c.group_id, c.num_required)
+            }
+        }
+    }
+}
+
+impl ReconcileTask {
+    /// Describe the task.
+    pub fn describe(&self) -> String {
+        use reconcile_task::Task;
+
+        match &self.task {
+            Some(task) => match task {
+                Task::ReallocateReplica(t) => format!(
+                    "move replica {} of group {} from node {} to node {}",
+                    t.src_replica,
+                    t.group,
+                    t.src_node,
+                    t.dest_node.as_ref().map(|n| n.id).unwrap_or_default()
+                ),
+                Task::MigrateShard(t) => format!(
+                    "migrate shard {} from group {} to group {}",
+                    t.shard, t.src_group, t.dest_group
+                ),
+                Task::TransferGroupLeader(t) => format!(
+                    "transfer leader of group {} to replica {} on node {}",
+                    t.group, t.target_replica, t.dest_node
+                ),
+                Task::ShedLeader(t) => format!("shed leaders from node {}", t.node_id),
+                Task::ShedRoot(t) => format!("shed root leader from node {}", t.node_id),
+                Task::SplitShard(t) => {
+                    format!("split shard {} of group {}", t.shard_id, t.group_id)
+                }
+                Task::AddReadReplica(t) => {
+                    format!("add read replica of group {} on node {}", t.group, t.dest_node)
+                }
+                Task::RemoveReadReplica(t) => format!(
+                    "remove read replica {} of group {} from node {}",
+                    t.replica, t.group, t.node
+                ),
+                Task::RemoveGhostReplica(t) => format!(
+                    "remove ghost replica {} of group {} on node {}",
+                    t.replica, t.group, t.node
+                ),
+            },
+            None => "unknown".to_owned(),
+        }
+    }
+}
+
+/// Nested message and enum types in `Recommendation`.
+pub mod recommendation {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Action {
+        #[prost(message, tag = "5")]
+        Reconcile(super::ReconcileTask),
+        #[prost(message, tag = "6")]
+        CureGroup(super::CureGroupAction),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CureGroupAction {
+    #[prost(uint64, tag = "1")]
+    pub group_id: u64,
+    #[prost(uint64, tag = "2")]
+    pub num_required: u64,
+}
+
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BackgroundJob {
     #[prost(uint64, tag = "1")]
     pub id: u64,
-    #[prost(oneof = "background_job::Job", tags = "2, 3, 4, 5")]
+    #[prost(oneof = "background_job::Job", tags = "2, 3, 4, 5, 6")]
     pub job: ::core::option::Option<background_job::Job>,
 }
 
@@ -153,6 +281,20 @@ impl BackgroundJob {
                     "database": p.database_id,
                 })
             }
+            Job::CloneTable(c) => {
+                let status = format!("{:?}", CloneTableJobStatus::from_i32(c.status).unwrap());
+                let copied_shards = c.shards.iter().filter(|s| s.finished).count();
+                json!({
+                    "type": "clone table",
+                    "source": c.src_table,
+                    "table": c.desc.as_ref().map(|d| d.id).unwrap_or_default(),
+                    "name": c.desc.as_ref().map(|d| d.name.clone()).unwrap_or_default(),
+                    "read_version": c.read_version,
+                    "status": status,
+                    "copied_shards": copied_shards,
+                    "total_shards": c.shards.len(),
+                })
+            }
         }
     }
 }
@@ -169,6 +311,8 @@ pub mod background_job {
         PurgeTable(super::PurgeTableJob),
         #[prost(message, tag = "5")]
         PurgeDatabase(super::PurgeDatabaseJob),
+        #[prost(message, tag = "6")]
+        CloneTable(super::CloneTableJob),
     }
 }
 
@@ -188,6 +332,9 @@ pub struct CreateTableJob {
     pub remark: ::prost::alloc::string::String,
     #[prost(message, optional, tag = "7")]
     pub desc: ::core::option::Option<::sekas_api::server::v1::TableDesc>,
+    /// The group of each shard, which is recorded before creating the shard.
+    #[prost(map = "uint64, uint64", tag = "8")]
+    pub shard_groups: ::std::collections::HashMap<u64, u64>,
     #[prost(string, tag = "89")]
     pub created_time: ::prost::alloc::string::String,
 }
@@ -236,6 +383,42 @@ pub struct PurgeDatabaseJob {
     pub created_time: ::prost::alloc::string::String,
 }
 
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CloneTableJob {
+    #[prost(uint64, tag = "1")]
+    pub src_table: u64,
+    /// The desc of the dest table.
+    #[prost(message, optional, tag = "2")]
+    pub desc: ::core::option::Option<::sekas_api::server::v1::TableDesc>,
+    /// The version of the snapshot which is cloned from the source table.
+    #[prost(uint64, tag = "3")]
+    pub read_version: u64,
+    #[prost(message, repeated, tag = "4")]
+    pub shards: ::prost::alloc::vec::Vec<CloneShardProgress>,
+    #[prost(enumeration = "CloneTableJobStatus", tag = "5")]
+    pub status: i32,
+    #[prost(string, tag = "6")]
+    pub remark: ::prost::alloc::string::String,
+    #[prost(string, tag = "7")]
+    pub created_time: ::prost::alloc::string::String,
+}
+
+/// The copy progress of a shard of the dest table.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CloneShardProgress {
+    #[prost(message, optional, tag = "1")]
+    pub shard: ::core::option::Option<::sekas_api::server::v1::ShardDesc>,
+    /// The next key to copy, the keys before it are copied.
+    #[prost(bytes = "vec", tag = "2")]
+    pub next_key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bool, tag = "3")]
+    pub finished: bool,
+    #[prost(uint64, tag = "4")]
+    pub copied_keys: u64,
+    #[prost(uint64, tag = "5")]
+    pub copied_bytes: u64,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum TaskStep {
@@ -314,6 +497,42 @@ impl CreateTableJobStatus {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum CloneTableJobStatus {
+    Copying = 0,
+    Rollbacking = 1,
+    Finish = 2,
+    Abort = 3,
+}
+
+impl CloneTableJobStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic
+    /// use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            CloneTableJobStatus::Copying => "CLONE_TABLE_COPYING",
+            CloneTableJobStatus::Rollbacking => "CLONE_TABLE_ROLLBACKING",
+            CloneTableJobStatus::Finish => "CLONE_TABLE_FINISH",
+            CloneTableJobStatus::Abort => "CLONE_TABLE_ABORT",
+        }
+    }
+
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CLONE_TABLE_COPYING" => Some(Self::Copying),
+            "CLONE_TABLE_ROLLBACKING" => Some(Self::Rollbacking),
+            "CLONE_TABLE_FINISH" => Some(Self::Finish),
+            "CLONE_TABLE_ABORT" => Some(Self::Abort),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum CreateOneGroupStatus {
@@ -352,3 +571,39 @@ impl CreateOneGroupStatus {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum RecommendationStatus {
+    Pending = 0,
+    Approved = 1,
+    Executed = 2,
+    Expired = 3,
+}
+
+impl RecommendationStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic
+    /// use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            RecommendationStatus::Pending => "PENDING",
+            RecommendationStatus::Approved => "APPROVED",
+            RecommendationStatus::Executed => "EXECUTED",
+            RecommendationStatus::Expired => "EXPIRED",
+        }
+    }
+
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "PENDING" => Some(Self::Pending),
+            "APPROVED" => Some(Self::Approved),
+            "EXECUTED" => Some(Self::Executed),
+            "EXPIRED" => Some(Self::Expired),
+            _ => None,
+        }
+    }
+}
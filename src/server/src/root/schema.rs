@@ -23,15 +23,18 @@ use log::{debug, info, warn};
 use prost::Message;
 use sekas_api::server::v1::watch_response::{delete_event, update_event, DeleteEvent, UpdateEvent};
 use sekas_api::server::v1::*;
-use sekas_rock::time::timestamp_nanos;
+use sekas_runtime::time::timestamp_nanos;
 use sekas_schema::system::table;
 
-use super::schedule::BackgroundJob;
+use super::page::{self, read_page, Page, MAX_PAGE_SIZE};
+use super::quota::QuotaUsages;
+use super::schedule::{BackgroundJob, Recommendation};
 use super::store::RootStore;
 use crate::constants::*;
 use crate::engine::{GroupEngine, SnapshotMode};
+use crate::serverpb::v1::{CatalogMirrorState, QuotaLimits};
 use crate::transport::TransportManager;
-use crate::{Error, Result};
+use crate::{Error, Result, ScheduleMode};
 
 const META_CLUSTER_ID_KEY: &str = "cluster_id";
 const META_TABLE_ID_KEY: &str = "table_id";
@@ -42,6 +45,21 @@ const META_REPLICA_ID_KEY: &str = "replica_id";
 const META_SHARD_ID_KEY: &str = "shard_id";
 const META_JOB_ID_KEY: &str = "job_id";
 const META_TXN_ID_KEY: &str = "txn_id";
+const META_RECOMMENDATION_ID_KEY: &str = "recommendation_id";
+const META_SCHEDULE_MODE_KEY: &str = "schedule_mode";
+const META_SCHEDULE_AUTO_CURE_KEY: &str = "schedule_auto_cure";
+const META_CLUSTER_EPOCH_KEY: &str = "cluster_epoch";
+const META_CATALOG_MIRROR_KEY: &str = "catalog_mirror";
+const META_TRANSFER_LIMITS_KEY: &str = "transfer_limits";
+const META_RAFT_ELECTION_KEY: &str = "raft_election";
+const META_QUOTA_LIMITS_KEY: &str = "quota_limits";
+const META_TOPOLOGY_EVENT_ID_KEY: &str = "topology_event_id";
+
+const INITIAL_RECOMMENDATION_ID: u64 = 1;
+const INITIAL_TOPOLOGY_EVENT_ID: u64 = 1;
+
+/// The max number of topology events retained, the oldest ones are dropped.
+const MAX_TOPOLOGY_EVENTS: u64 = 4096;
 
 lazy_static! {
     pub static ref ID_GEN_LOCKS: HashMap<String, Mutex<()>> = HashMap::from([
@@ -53,18 +71,33 @@ lazy_static! {
         (META_REPLICA_ID_KEY.to_owned(), Mutex::new(())),
         (META_SHARD_ID_KEY.to_owned(), Mutex::new(())),
         (META_JOB_ID_KEY.to_owned(), Mutex::new(())),
+        (META_RECOMMENDATION_ID_KEY.to_owned(), Mutex::new(())),
+        (META_TOPOLOGY_EVENT_ID_KEY.to_owned(), Mutex::new(())),
     ]);
 }
 
 #[derive(Clone)]
 pub struct Schema {
     store: Arc<RootStore>,
+    quota_usages: Arc<QuotaUsages>,
 }
 
 // public interface.
 impl Schema {
     pub fn new(store: Arc<RootStore>) -> Self {
-        Self { store }
+        Self { store, quota_usages: Arc::default() }
+    }
+
+    /// The usages of the quotas of the databases, counted since this schema is
+    /// created.
+    pub fn quota_usages(&self) -> &Arc<QuotaUsages> {
+        &self.quota_usages
+    }
+
+    /// Wait until the following reads observe all changes committed before this
+    /// call, even if they are committed by a former root leader.
+    pub async fn read_index(&self) -> Result<()> {
+        self.store.read_index().await
     }
 
     pub async fn cluster_id(&self) -> Result<Option<Vec<u8>>> {
@@ -72,13 +105,20 @@ impl Schema {
     }
 
     pub async fn create_database(&self, desc: DatabaseDesc) -> Result<DatabaseDesc> {
-        if self.get_database(&desc.name).await?.is_some() {
+        if let Some(db) = self.get_database(&desc.name).await? {
+            if !desc.request_id.is_empty() && db.request_id == desc.request_id {
+                info!("database {} is created by the request {} before", db.name, db.request_id);
+                return Ok(db);
+            }
             warn!("create database but it already exists. database={}", desc.name);
             return Err(Error::AlreadyExists(format!("database {}", desc.name.to_owned())));
         }
 
         let mut desc = desc.to_owned();
         desc.id = self.next_id(META_DATABASE_ID_KEY).await?;
+        if let Some(owner) = self.list_database().await?.into_iter().find(|db| db.id == desc.id) {
+            return Err(id_owned_error("database", desc.id, &owner.name));
+        }
         self.put_database(desc.clone()).await?;
         Ok(desc)
     }
@@ -93,8 +133,8 @@ impl Schema {
         Ok(Some(desc))
     }
 
-    pub async fn update_database(&self, _desc: DatabaseDesc) -> Result<()> {
-        todo!()
+    pub async fn update_database(&self, desc: DatabaseDesc) -> Result<()> {
+        self.put_database(desc).await
     }
 
     pub async fn delete_database(&self, db: &DatabaseDesc) -> Result<u64> {
@@ -125,33 +165,64 @@ impl Schema {
 
     pub async fn create_table(&self, desc: TableDesc) -> Result<TableDesc> {
         assert!(self.get_table(desc.db, &desc.name).await?.is_none());
+        let floor = sekas_schema::FIRST_USER_TABLE_ID;
+        self.check_allocated(META_TABLE_ID_KEY, floor, desc.id).await?;
+        if let Some(owner) = self.list_table().await?.into_iter().find(|t| t.id == desc.id) {
+            return Err(id_owned_error("table", desc.id, &owner.name));
+        }
         self.put_table(desc.clone()).await?;
         Ok(desc)
     }
 
     pub async fn get_table(&self, database: u64, table: &str) -> Result<Option<TableDesc>> {
-        let val = self.get(table::TABLE_ID, &table_key(database, table)).await?;
-        if val.is_none() {
+        let desc = self.get_table_with_value(database, table).await?;
+        Ok(desc.map(|(desc, _)| desc))
+    }
+
+    /// Read the table desc and the persisted value of it, the value is the
+    /// condition of [`Schema::update_table_if`].
+    pub async fn get_table_with_value(
+        &self,
+        database: u64,
+        table: &str,
+    ) -> Result<Option<(TableDesc, Vec<u8>)>> {
+        let Some(val) = self.get(table::TABLE_ID, &table_key(database, table)).await? else {
             return Ok(None);
-        }
-        let desc = TableDesc::decode(&*val.unwrap())
+        };
+        let desc = TableDesc::decode(&*val)
             .map_err(|_| Error::InvalidData(format!("table desc: {}, {}", database, table)))?;
-        Ok(Some(desc))
+        Ok(Some((desc, val)))
     }
 
     pub async fn get_table_shards(&self, table_id: u64) -> Result<Vec<(u64, ShardDesc)>> {
-        Ok(self
-            .list_group()
-            .await?
-            .iter()
-            .flat_map(|g| {
-                g.shards.iter().filter(|s| s.table_id == table_id).map(|s| (g.id, s.to_owned()))
-            })
-            .collect::<Vec<_>>())
+        let mut filter = GroupFilter { table_id: Some(table_id), ..Default::default() };
+        let mut shards = Vec::new();
+        loop {
+            let page = self.list_group_page(&filter).await?;
+            for g in &page.items {
+                shards.extend(
+                    g.shards
+                        .iter()
+                        .filter(|s| s.table_id == table_id)
+                        .map(|s| (g.id, s.to_owned())),
+                );
+            }
+            if !page.has_more() {
+                return Ok(shards);
+            }
+            filter.page_token = page.next_page_token;
+        }
+    }
+
+    pub async fn update_table(&self, desc: TableDesc) -> Result<()> {
+        self.put_table(desc).await
     }
 
-    pub async fn update_table(&self, _desc: TableDesc) -> Result<()> {
-        todo!()
+    /// Update the table desc only if the persisted value is still
+    /// `expect_value`, otherwise it fails with `Error::CasFailed`.
+    pub async fn update_table_if(&self, desc: TableDesc, expect_value: Vec<u8>) -> Result<()> {
+        let key = table_key(desc.db, &desc.name);
+        self.batch_write(cas_write(table::TABLE_ID, key, desc.encode_to_vec(), expect_value)).await
     }
 
     pub async fn delete_table(&self, table: TableDesc) -> Result<()> {
@@ -177,6 +248,9 @@ impl Schema {
     pub async fn add_node(&self, desc: NodeDesc) -> Result<NodeDesc> {
         let mut desc = desc.to_owned();
         desc.id = self.next_id(META_NODE_ID_KEY).await?;
+        if let Some(owner) = self.get_node(desc.id).await? {
+            return Err(id_owned_error("node", desc.id, &owner.addr));
+        }
         self.put_node(desc.clone()).await?;
         Ok(desc)
     }
@@ -191,6 +265,11 @@ impl Schema {
         Ok(Some(desc))
     }
 
+    /// The bytes of the node descriptor stored in catalog.
+    pub async fn get_node_raw(&self, id: u64) -> Result<Option<Vec<u8>>> {
+        self.get(table::NODE_ID, &id.to_le_bytes()).await
+    }
+
     pub async fn delete_node(&self, id: u64) -> Result<()> {
         self.delete(table::NODE_ID, &id.to_le_bytes()).await
     }
@@ -201,13 +280,17 @@ impl Schema {
     }
 
     pub async fn list_node(&self) -> Result<Vec<NodeDesc>> {
-        let values = self.list(table::NODE_ID).await?;
-        let mut nodes = Vec::new();
-        for val in values {
-            nodes
-                .push(NodeDesc::decode(&*val).map_err(|_| Error::InvalidData("node desc".into()))?);
-        }
-        Ok(nodes)
+        self.list_all(table::NODE_ID, &[], |val| Ok(Some(decode_node(val)?))).await
+    }
+
+    /// List a page of the nodes matching the filter, in the order of key.
+    pub async fn list_node_page(&self, filter: &NodeDescFilter) -> Result<Page<NodeDesc>> {
+        let shard_id = table::shard_id(table::NODE_ID);
+        read_page(&*self.store, shard_id, &[], &filter.page_token, filter.limit, |val| {
+            let desc = decode_node(val)?;
+            Ok(page::match_node(filter, &desc).then_some(desc))
+        })
+        .await
     }
 
     pub(crate) async fn list_node_raw(engine: GroupEngine) -> Result<Vec<NodeDesc>> {
@@ -244,6 +327,9 @@ impl Schema {
         group: Option<GroupDesc>,
         replica: Option<ReplicaState>,
     ) -> Result<()> {
+        if let Some(group) = group.as_ref() {
+            self.check_group_ids(group).await?;
+        }
         if let Some(replica) = replica {
             self.put_replica_state(replica).await?;
         }
@@ -268,20 +354,40 @@ impl Schema {
         Ok(Some(desc))
     }
 
+    /// The bytes of the group descriptor stored in catalog.
+    pub async fn get_group_raw(&self, id: u64) -> Result<Option<Vec<u8>>> {
+        self.get(table::GROUP_ID, &id.to_le_bytes()).await
+    }
+
     pub async fn delete_group(&self, id: u64) -> Result<()> {
         // TODO: prefix delete replica_state
         self.delete(table::GROUP_ID, &id.to_le_bytes()).await
     }
 
     pub async fn list_group(&self) -> Result<Vec<GroupDesc>> {
-        let values = self.list(table::GROUP_ID).await?;
-        let mut groups = Vec::new();
-        for val in values {
-            groups.push(
-                GroupDesc::decode(&*val).map_err(|_| Error::InvalidData("group desc".into()))?,
-            );
-        }
-        Ok(groups)
+        self.list_all(table::GROUP_ID, &[], |val| Ok(Some(decode_group(val)?))).await
+    }
+
+    /// List all groups matching the filter, the limit and page token of the
+    /// filter are ignored.
+    pub async fn list_group_with(&self, filter: &GroupFilter) -> Result<Vec<GroupDesc>> {
+        self.list_all(table::GROUP_ID, &[], |val| {
+            let desc = decode_group(val)?;
+            Ok(page::match_group(filter, &desc).then_some(desc))
+        })
+        .await
+    }
+
+    /// List a page of the groups matching the filter, in the order of key.
+    /// The consumers of all groups should iterate the pages, to bound the
+    /// memory on a cluster with many groups.
+    pub async fn list_group_page(&self, filter: &GroupFilter) -> Result<Page<GroupDesc>> {
+        let shard_id = table::shard_id(table::GROUP_ID);
+        read_page(&*self.store, shard_id, &[], &filter.page_token, filter.limit, |val| {
+            let desc = decode_group(val)?;
+            Ok(page::match_group(filter, &desc).then_some(desc))
+        })
+        .await
     }
 
     pub async fn get_replica_state(
@@ -304,14 +410,37 @@ impl Schema {
     }
 
     pub async fn list_replica_state(&self) -> Result<Vec<ReplicaState>> {
-        let values = self.list(table::REPLICA_STATE_ID).await?;
-        let mut states = Vec::with_capacity(values.len());
-        for val in values {
-            let state = ReplicaState::decode(&*val)
-                .map_err(|_| Error::InvalidData("replica state desc".into()))?;
-            states.push(state.to_owned());
-        }
-        Ok(states)
+        self.list_all(table::REPLICA_STATE_ID, &[], |val| Ok(Some(decode_replica_state(val)?)))
+            .await
+    }
+
+    /// List all replica states matching the filter, the limit and page token of
+    /// the filter are ignored.
+    pub async fn list_replica_state_with(
+        &self,
+        filter: &ReplicaStateFilter,
+    ) -> Result<Vec<ReplicaState>> {
+        let prefix = filter.group_id.map(|id| id.to_le_bytes().to_vec()).unwrap_or_default();
+        self.list_all(table::REPLICA_STATE_ID, &prefix, |val| {
+            let state = decode_replica_state(val)?;
+            Ok(page::match_replica_state(filter, &state).then_some(state))
+        })
+        .await
+    }
+
+    /// List a page of the replica states matching the filter, in the order of
+    /// key. The replicas of a group are read by the prefix of the group.
+    pub async fn list_replica_state_page(
+        &self,
+        filter: &ReplicaStateFilter,
+    ) -> Result<Page<ReplicaState>> {
+        let shard_id = table::shard_id(table::REPLICA_STATE_ID);
+        let prefix = filter.group_id.map(|id| id.to_le_bytes().to_vec()).unwrap_or_default();
+        read_page(&*self.store, shard_id, &prefix, &filter.page_token, filter.limit, |val| {
+            let state = decode_replica_state(val)?;
+            Ok(page::match_replica_state(filter, &state).then_some(state))
+        })
+        .await
     }
 
     pub async fn group_replica_states(&self, group_id: u64) -> Result<Vec<ReplicaState>> {
@@ -526,6 +655,123 @@ impl Schema {
         Ok(Some(job))
     }
 
+    pub async fn append_recommendation(&self, desc: Recommendation) -> Result<Recommendation> {
+        let mut desc = desc.to_owned();
+        desc.id = self.next_id(META_RECOMMENDATION_ID_KEY).await?;
+        self.put_recommendation(desc.to_owned()).await?;
+        Ok(desc)
+    }
+
+    #[inline]
+    pub async fn update_recommendation(&self, desc: Recommendation) -> Result<()> {
+        self.put_recommendation(desc).await
+    }
+
+    pub async fn get_recommendation(&self, id: u64) -> Result<Option<Recommendation>> {
+        let Some(val) = self.get(table::RECOMMENDATION_ID, &id.to_le_bytes()).await? else {
+            return Ok(None);
+        };
+        let desc = Recommendation::decode(&*val)
+            .map_err(|_| Error::InvalidData("recommendation".into()))?;
+        Ok(Some(desc))
+    }
+
+    /// List the recommendations, in the order of id.
+    pub async fn list_recommendation(&self) -> Result<Vec<Recommendation>> {
+        let values = self.list(table::RECOMMENDATION_ID).await?;
+        let mut recommendations = Vec::with_capacity(values.len());
+        for val in values {
+            let desc = Recommendation::decode(&*val)
+                .map_err(|_| Error::InvalidData("recommendation".into()))?;
+            recommendations.push(desc);
+        }
+        recommendations.sort_unstable_by_key(|r| r.id);
+        Ok(recommendations)
+    }
+
+    /// Get the schedule mode changed at runtime, `None` if it has never been
+    /// changed.
+    pub async fn get_schedule_mode(&self) -> Result<Option<ScheduleMode>> {
+        let Some(val) = self.get_meta(META_SCHEDULE_MODE_KEY.as_bytes()).await? else {
+            return Ok(None);
+        };
+        let mode = String::from_utf8(val)
+            .ok()
+            .and_then(|mode| mode.parse().ok())
+            .ok_or_else(|| Error::InvalidData("schedule mode".into()))?;
+        Ok(Some(mode))
+    }
+
+    pub async fn set_schedule_mode(&self, mode: ScheduleMode) -> Result<()> {
+        self.put_meta(META_SCHEDULE_MODE_KEY.as_bytes(), mode.as_str().as_bytes().to_vec()).await
+    }
+
+    /// Get the auto cure option changed at runtime, `None` if it has never
+    /// been changed.
+    pub async fn get_schedule_auto_cure(&self) -> Result<Option<bool>> {
+        let val = self.get_meta(META_SCHEDULE_AUTO_CURE_KEY.as_bytes()).await?;
+        Ok(val.map(|v| v.first().cloned().unwrap_or_default() != 0))
+    }
+
+    pub async fn set_schedule_auto_cure(&self, auto_cure: bool) -> Result<()> {
+        self.put_meta(META_SCHEDULE_AUTO_CURE_KEY.as_bytes(), vec![auto_cure as u8]).await
+    }
+
+    /// Append an event to the topology event log, the oldest events beyond
+    /// [`MAX_TOPOLOGY_EVENTS`] are dropped.
+    pub async fn append_topology_event(&self, event: TopologyEvent) -> Result<TopologyEvent> {
+        let (event, write) = self.topology_event_write(event).await?;
+        self.batch_write(write).await?;
+        Ok(event)
+    }
+
+    /// Update the group desc only if the persisted value is still
+    /// `expect_value`, and append the topology event of the update in a batch,
+    /// so the update is never missed in the event log. It fails with
+    /// `Error::CasFailed` if the desc is changed.
+    pub async fn update_group_with_event_if(
+        &self,
+        desc: GroupDesc,
+        expect_value: Vec<u8>,
+        event: TopologyEvent,
+    ) -> Result<TopologyEvent> {
+        self.check_group_ids(&desc).await?;
+        let key = desc.id.to_le_bytes().to_vec();
+        let write = cas_write(table::GROUP_ID, key, desc.encode_to_vec(), expect_value);
+        let (event, event_write) = self.topology_event_write(event).await?;
+        self.store.batch_write_shards(vec![write, event_write]).await?;
+        Ok(event)
+    }
+
+    /// Update the node desc only if the persisted value is still
+    /// `expect_value`, and append the topology event of the update in a batch,
+    /// see [`Schema::update_group_with_event_if`].
+    pub async fn update_node_with_event_if(
+        &self,
+        desc: NodeDesc,
+        expect_value: Vec<u8>,
+        event: TopologyEvent,
+    ) -> Result<TopologyEvent> {
+        let key = desc.id.to_le_bytes().to_vec();
+        let write = cas_write(table::NODE_ID, key, desc.encode_to_vec(), expect_value);
+        let (event, event_write) = self.topology_event_write(event).await?;
+        self.store.batch_write_shards(vec![write, event_write]).await?;
+        Ok(event)
+    }
+
+    /// List the topology events, in the order of id.
+    pub async fn list_topology_event(&self) -> Result<Vec<TopologyEvent>> {
+        let values = self.list(table::TOPOLOGY_EVENT_ID).await?;
+        let mut events = Vec::with_capacity(values.len());
+        for val in values {
+            let event = TopologyEvent::decode(&*val)
+                .map_err(|_| Error::InvalidData("topology event".into()))?;
+            events.push(event);
+        }
+        events.sort_unstable_by_key(|e| e.id);
+        Ok(events)
+    }
+
     pub async fn max_txn_id(&self) -> Result<u64> {
         let txn_id = self
             .get_meta(META_TXN_ID_KEY.as_bytes())
@@ -541,6 +787,131 @@ impl Schema {
         self.put_meta(META_TXN_ID_KEY.as_bytes(), next_txn_id.to_le_bytes().to_vec()).await?;
         Ok(())
     }
+
+    /// The cluster epoch, it is increased once a mirrored catalog is promoted,
+    /// `0` if the catalog has never been promoted.
+    pub async fn cluster_epoch(&self) -> Result<u64> {
+        let Some(val) = self.get_meta(META_CLUSTER_EPOCH_KEY.as_bytes()).await? else {
+            return Ok(0);
+        };
+        Ok(u64::from_le_bytes(
+            val.try_into().map_err(|_| Error::InvalidData("cluster epoch".to_owned()))?,
+        ))
+    }
+
+    pub async fn get_catalog_mirror_state(&self) -> Result<Option<CatalogMirrorState>> {
+        let Some(val) = self.get_meta(META_CATALOG_MIRROR_KEY.as_bytes()).await? else {
+            return Ok(None);
+        };
+        let state = CatalogMirrorState::decode(&*val)
+            .map_err(|_| Error::InvalidData("catalog mirror state".to_owned()))?;
+        Ok(Some(state))
+    }
+
+    pub async fn set_catalog_mirror_state(&self, state: &CatalogMirrorState) -> Result<()> {
+        self.put_meta(META_CATALOG_MIRROR_KEY.as_bytes(), state.encode_to_vec()).await
+    }
+
+    /// Get the limits of the bulk transfers changed at runtime, the fields
+    /// which have never been changed are absent.
+    pub async fn get_transfer_limits(&self) -> Result<ConfigureTransferRequest> {
+        let Some(val) = self.get_meta(META_TRANSFER_LIMITS_KEY.as_bytes()).await? else {
+            return Ok(ConfigureTransferRequest::default());
+        };
+        ConfigureTransferRequest::decode(&*val)
+            .map_err(|_| Error::InvalidData("transfer limits".to_owned()))
+    }
+
+    pub async fn set_transfer_limits(&self, limits: &ConfigureTransferRequest) -> Result<()> {
+        self.put_meta(META_TRANSFER_LIMITS_KEY.as_bytes(), limits.encode_to_vec()).await
+    }
+
+    /// Get the election options of the raft groups changed at runtime, the
+    /// options which have never been changed are absent.
+    pub async fn get_raft_election(&self) -> Result<ConfigureRaftRequest> {
+        let Some(val) = self.get_meta(META_RAFT_ELECTION_KEY.as_bytes()).await? else {
+            return Ok(ConfigureRaftRequest::default());
+        };
+        ConfigureRaftRequest::decode(&*val)
+            .map_err(|_| Error::InvalidData("raft election options".to_owned()))
+    }
+
+    pub async fn set_raft_election(&self, options: &ConfigureRaftRequest) -> Result<()> {
+        self.put_meta(META_RAFT_ELECTION_KEY.as_bytes(), options.encode_to_vec()).await
+    }
+
+    /// Get the cluster default quotas of the databases, the quotas which have
+    /// never been configured are absent.
+    pub async fn get_quota_limits(&self) -> Result<QuotaLimits> {
+        let Some(val) = self.get_meta(META_QUOTA_LIMITS_KEY.as_bytes()).await? else {
+            return Ok(QuotaLimits::default());
+        };
+        QuotaLimits::decode(&*val).map_err(|_| Error::InvalidData("quota limits".to_owned()))
+    }
+
+    pub async fn set_quota_limits(&self, limits: &QuotaLimits) -> Result<()> {
+        self.put_meta(META_QUOTA_LIMITS_KEY.as_bytes(), limits.encode_to_vec()).await
+    }
+
+    /// Put the database mirrored from the primary, the id is preserved.
+    pub async fn put_mirrored_database(&self, desc: DatabaseDesc) -> Result<()> {
+        self.put_database(desc).await
+    }
+
+    /// Put the table mirrored from the primary, the id is preserved.
+    pub async fn put_mirrored_table(&self, desc: TableDesc) -> Result<()> {
+        self.put_table(desc).await
+    }
+
+    /// Promote the mirrored catalog. The next ids of databases and tables are
+    /// bumped above the mirrored ones, they are written with the cluster epoch
+    /// and the mirror state in a batch.
+    pub async fn promote_catalog(
+        &self,
+        cluster_epoch: u64,
+        state: &CatalogMirrorState,
+    ) -> Result<()> {
+        let _database_id_guard = ID_GEN_LOCKS[META_DATABASE_ID_KEY].lock().await;
+        let _table_id_guard = ID_GEN_LOCKS[META_TABLE_ID_KEY].lock().await;
+        let max_database_id = self.list_database().await?.iter().map(|db| db.id).max();
+        let max_table_id = self.list_table().await?.iter().map(|table| table.id).max();
+        let next_database_id = std::cmp::max(
+            self.peek_next_id(META_DATABASE_ID_KEY).await?,
+            max_database_id.map_or(0, |id| id + 1),
+        );
+        let next_table_id = std::cmp::max(
+            self.peek_next_id(META_TABLE_ID_KEY).await?,
+            max_table_id.map_or(0, |id| id + 1),
+        );
+
+        let mut batch =
+            ShardWriteRequest { shard_id: table::shard_id(table::META_ID), ..Default::default() };
+        let mut put_meta = |key: &str, value| {
+            batch.puts.push(PutRequest { key: key.into(), value, ..Default::default() })
+        };
+        put_meta(META_DATABASE_ID_KEY, next_database_id.to_le_bytes().to_vec());
+        put_meta(META_TABLE_ID_KEY, next_table_id.to_le_bytes().to_vec());
+        put_meta(META_CLUSTER_EPOCH_KEY, cluster_epoch.to_le_bytes().to_vec());
+        put_meta(META_CATALOG_MIRROR_KEY, state.encode_to_vec());
+        self.batch_write(batch).await
+    }
+}
+
+/// The write of `key` in the table, only if the persisted value is still
+/// `expect_value`.
+fn cas_write(
+    table_id: u64,
+    key: Vec<u8>,
+    value: Vec<u8>,
+    expect_value: Vec<u8>,
+) -> ShardWriteRequest {
+    let condition = WriteCondition {
+        r#type: WriteConditionType::ExpectValue.into(),
+        value: expect_value,
+        ..Default::default()
+    };
+    let put = PutRequest { key, value, conditions: vec![condition], ..Default::default() };
+    ShardWriteRequest { shard_id: table::shard_id(table_id), puts: vec![put], ..Default::default() }
 }
 
 pub struct ReplicaNodes(pub Vec<NodeDesc>);
@@ -567,6 +938,7 @@ impl Schema {
         &mut self,
         addr: &str,
         cfg_cpu_nums: u32,
+        cfg_labels: &[String],
         cluster_id: Vec<u8>,
     ) -> Result<()> {
         debug_assert_ne!(cfg_cpu_nums, 0);
@@ -600,6 +972,7 @@ impl Schema {
                 leader_count: 0,
             }),
             status: NodeStatus::Active as i32,
+            labels: cfg_labels.to_owned(),
         };
         self.put_node(node_desc).await?;
 
@@ -613,6 +986,7 @@ impl Schema {
             voted_for: FIRST_REPLICA_ID,
             role: RaftRole::Leader.into(),
             node_id: FIRST_NODE_ID,
+            quarantine: None,
         };
         self.put_replica_state(replica_state).await?;
 
@@ -626,6 +1000,7 @@ impl Schema {
             voted_for: INIT_USER_REPLICA_ID,
             role: RaftRole::Leader.into(),
             node_id: FIRST_NODE_ID,
+            quarantine: None,
         };
         self.put_replica_state(replica_state).await?;
 
@@ -667,26 +1042,172 @@ impl Schema {
         let mut put_meta =
             |key, value| batch.puts.push(PutRequest { key, value, ..Default::default() });
         put_meta(META_CLUSTER_ID_KEY.into(), cluster_id);
-        put_meta(
-            META_DATABASE_ID_KEY.into(),
-            sekas_schema::FIRST_USER_DATABASE_ID.to_le_bytes().to_vec(),
-        );
-        put_meta(
-            META_TABLE_ID_KEY.into(),
-            sekas_schema::FIRST_USER_TABLE_ID.to_le_bytes().to_vec(),
-        );
-        put_meta(META_GROUP_ID_KEY.into(), (FIRST_GROUP_ID + 1).to_le_bytes().to_vec());
-        put_meta(META_NODE_ID_KEY.into(), (FIRST_NODE_ID + 1).to_le_bytes().to_vec());
-        put_meta(META_REPLICA_ID_KEY.into(), (INIT_USER_REPLICA_ID + 1).to_le_bytes().to_vec());
-        put_meta(
-            META_SHARD_ID_KEY.into(),
-            sekas_schema::FIRST_USER_SHARD_ID.to_le_bytes().to_vec(),
-        );
-        put_meta(META_JOB_ID_KEY.into(), INITIAL_JOB_ID.to_le_bytes().to_vec());
+        for (id_type, initial_id) in initial_id_counters() {
+            put_meta(id_type.into(), initial_id.to_le_bytes().to_vec());
+        }
         put_meta(META_TXN_ID_KEY.into(), timestamp_nanos().to_le_bytes().to_vec());
         self.batch_write(batch).await?;
         Ok(())
     }
+
+    /// Roll back the id counters to the initial values, as if the catalog is
+    /// restored from a backup taken right after bootstrapping.
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn rollback_id_counters(&self) -> Result<()> {
+        let mut batch =
+            ShardWriteRequest { shard_id: table::shard_id(table::META_ID), ..Default::default() };
+        for (id_type, initial_id) in initial_id_counters() {
+            batch.puts.push(PutRequest {
+                key: id_type.into(),
+                value: initial_id.to_le_bytes().to_vec(),
+                ..Default::default()
+            });
+        }
+        self.batch_write(batch).await
+    }
+}
+
+// id space safety.
+impl Schema {
+    /// Bump the id counters which fall behind the max ids in use, e.g. the
+    /// catalog is restored from an old backup, so the ids allocated since then
+    /// never collide with the live ones. The repaired counters are returned in
+    /// form of `(id type, next id, repaired next id)`.
+    pub async fn repair_id_counters(&self) -> Result<Vec<(&'static str, u64, u64)>> {
+        let group_max_ids = self.max_group_ids().await?;
+        let mut jobs = self.list_job().await?;
+        jobs.extend(self.list_history_job().await?);
+        let max_ids = [
+            (META_DATABASE_ID_KEY, self.list_database().await?.iter().map(|db| db.id).max()),
+            (META_TABLE_ID_KEY, self.list_table().await?.iter().map(|table| table.id).max()),
+            (META_GROUP_ID_KEY, group_max_ids.group_id),
+            (META_NODE_ID_KEY, self.list_node().await?.iter().map(|node| node.id).max()),
+            (META_REPLICA_ID_KEY, group_max_ids.replica_id),
+            (META_SHARD_ID_KEY, group_max_ids.shard_id),
+            (META_JOB_ID_KEY, jobs.iter().map(|job| job.id).max()),
+            (
+                META_RECOMMENDATION_ID_KEY,
+                self.list_recommendation().await?.iter().map(|r| r.id).max(),
+            ),
+            (
+                META_TOPOLOGY_EVENT_ID_KEY,
+                self.list_topology_event().await?.iter().map(|e| e.id).max(),
+            ),
+        ];
+
+        let mut repaired = Vec::new();
+        for (id_type, max_id) in max_ids {
+            let _mutex = ID_GEN_LOCKS[id_type].lock().await;
+            let next_id = self.peek_next_id(id_type).await?;
+            let floor = max_id.map_or(0, |id| id.saturating_add(1));
+            if next_id < floor {
+                self.put_meta(id_type.as_bytes(), floor.to_le_bytes().to_vec()).await?;
+                repaired.push((id_type, next_id, floor));
+            }
+            record_id_space(id_type, std::cmp::max(next_id, floor));
+        }
+        Ok(repaired)
+    }
+
+    /// The max ids of the groups, their replicas and shards in use, the groups
+    /// and the replica states are read page by page.
+    async fn max_group_ids(&self) -> Result<GroupMaxIds> {
+        let mut max_ids = GroupMaxIds::default();
+        let mut filter = GroupFilter { limit: MAX_PAGE_SIZE as u64, ..Default::default() };
+        loop {
+            let page = self.list_group_page(&filter).await?;
+            for group in &page.items {
+                max_ids.group_id = max_ids.group_id.max(Some(group.id));
+                let replica_ids = group.replicas.iter().map(|replica| replica.id);
+                max_ids.replica_id = max_ids.replica_id.max(replica_ids.max());
+                max_ids.shard_id = max_ids.shard_id.max(group.shards.iter().map(|s| s.id).max());
+            }
+            if !page.has_more() {
+                break;
+            }
+            filter.page_token = page.next_page_token;
+        }
+        let mut filter = ReplicaStateFilter { limit: MAX_PAGE_SIZE as u64, ..Default::default() };
+        loop {
+            let page = self.list_replica_state_page(&filter).await?;
+            let replica_ids = page.items.iter().map(|state| state.replica_id);
+            max_ids.replica_id = max_ids.replica_id.max(replica_ids.max());
+            if !page.has_more() {
+                break;
+            }
+            filter.page_token = page.next_page_token;
+        }
+        Ok(max_ids)
+    }
+
+    /// Check the ids introduced by the group descriptor, they must be allocated
+    /// by root and not owned by the other groups.
+    async fn check_group_ids(&self, desc: &GroupDesc) -> Result<()> {
+        let prev = self.get_group(desc.id).await?;
+        if prev.is_none() {
+            self.check_allocated(META_GROUP_ID_KEY, FIRST_GROUP_ID + 1, desc.id).await?;
+        }
+        let prev = prev.unwrap_or_default();
+        let new_shards = desc
+            .shards
+            .iter()
+            .filter(|shard| prev.shards.iter().all(|s| s.id != shard.id))
+            .collect::<Vec<_>>();
+        let new_replicas = desc
+            .replicas
+            .iter()
+            .filter(|replica| prev.replicas.iter().all(|r| r.id != replica.id))
+            .collect::<Vec<_>>();
+        if new_shards.is_empty() && new_replicas.is_empty() {
+            return Ok(());
+        }
+
+        for shard in &new_shards {
+            let floor = sekas_schema::FIRST_USER_SHARD_ID;
+            self.check_allocated(META_SHARD_ID_KEY, floor, shard.id).await?;
+        }
+        for replica in &new_replicas {
+            self.check_allocated(META_REPLICA_ID_KEY, INIT_USER_REPLICA_ID + 1, replica.id).await?;
+        }
+        let mut filter = GroupFilter { limit: MAX_PAGE_SIZE as u64, ..Default::default() };
+        loop {
+            let page = self.list_group_page(&filter).await?;
+            for group in page.items.iter().filter(|group| group.id != desc.id) {
+                // The moving shard is claimed by both the source and dest groups, so only the
+                // shards of other tables are collisions.
+                for shard in &new_shards {
+                    if group.shards.iter().any(|s| s.id == shard.id && s.table_id != shard.table_id)
+                    {
+                        let owner = format!("group {}", group.id);
+                        return Err(id_owned_error("shard", shard.id, &owner));
+                    }
+                }
+                for replica in &new_replicas {
+                    if group.replicas.iter().any(|r| r.id == replica.id) {
+                        let owner = format!("group {}", group.id);
+                        return Err(id_owned_error("replica", replica.id, &owner));
+                    }
+                }
+            }
+            if !page.has_more() {
+                return Ok(());
+            }
+            filter.page_token = page.next_page_token;
+        }
+    }
+
+    /// Check the id was allocated from the counter, it is in the range of
+    /// `[floor, next id)`.
+    async fn check_allocated(&self, id_type: &str, floor: u64, id: u64) -> Result<()> {
+        let next_id = self.peek_next_id(id_type).await?;
+        if id < floor || id >= next_id {
+            warn!("{id_type} {id} is not allocated, the allocated range is [{floor}, {next_id})");
+            return Err(Error::InvalidData(format!(
+                "{id_type} {id} is not allocated, the allocated range is [{floor}, {next_id})"
+            )));
+        }
+        Ok(())
+    }
 }
 
 // internal methods.
@@ -716,6 +1237,27 @@ impl Schema {
         self.store.put(table::shard_id(table_id), key.to_owned(), value).await
     }
 
+    /// Read all items of the table page by page, the rows of a page are
+    /// decoded before the next page is read.
+    async fn list_all<T, F>(&self, table_id: u64, prefix: &[u8], mut decode: F) -> Result<Vec<T>>
+    where
+        F: FnMut(&[u8]) -> Result<Option<T>>,
+    {
+        let shard_id = table::shard_id(table_id);
+        let mut items = Vec::new();
+        let mut page_token = vec![];
+        loop {
+            let limit = MAX_PAGE_SIZE as u64;
+            let page =
+                read_page(&*self.store, shard_id, prefix, &page_token, limit, &mut decode).await?;
+            items.extend(page.items);
+            if !page.has_more() {
+                return Ok(items);
+            }
+            page_token = page.next_page_token;
+        }
+    }
+
     async fn list(&self, table_id: u64) -> Result<Vec<Vec<u8>>> {
         let rs = self.list_prefix(table_id, &[]).await;
         sekas_runtime::yield_now().await;
@@ -728,15 +1270,51 @@ impl Schema {
 
     async fn next_id(&self, id_type: &str) -> Result<u64> {
         let _mutex = ID_GEN_LOCKS.get(id_type).expect("id gen lock not found").lock().await;
+        let id = self.peek_next_id(id_type).await?;
+        let next_id = id
+            .checked_add(1)
+            .ok_or_else(|| Error::ResourceExhausted(format!("the {id_type} space is exhausted")))?;
+        self.put_meta(id_type.as_bytes(), next_id.to_le_bytes().to_vec()).await?;
+        record_id_space(id_type, next_id);
+        Ok(id)
+    }
+
+    /// Allocate the id of the topology event, and build the write of it. The
+    /// oldest event beyond [`MAX_TOPOLOGY_EVENTS`] is deleted by the write.
+    async fn topology_event_write(
+        &self,
+        event: TopologyEvent,
+    ) -> Result<(TopologyEvent, ShardWriteRequest)> {
+        let mut event = event;
+        event.id = self.next_id(META_TOPOLOGY_EVENT_ID_KEY).await?;
+        let mut write = ShardWriteRequest {
+            shard_id: table::shard_id(table::TOPOLOGY_EVENT_ID),
+            ..Default::default()
+        };
+        write.puts.push(PutRequest {
+            key: event.id.to_le_bytes().to_vec(),
+            value: event.encode_to_vec(),
+            ..Default::default()
+        });
+        if event.id > MAX_TOPOLOGY_EVENTS {
+            let expired_id = event.id - MAX_TOPOLOGY_EVENTS;
+            write.deletes.push(DeleteRequest {
+                key: expired_id.to_le_bytes().to_vec(),
+                ..Default::default()
+            });
+        }
+        Ok((event, write))
+    }
+
+    /// The next id to allocate, the caller should hold the id gen lock.
+    async fn peek_next_id(&self, id_type: &str) -> Result<u64> {
         let id = self
             .get_meta(id_type.as_bytes())
             .await?
             .ok_or_else(|| Error::InvalidData(format!("{} id", id_type)))?;
-        let id = u64::from_le_bytes(
+        Ok(u64::from_le_bytes(
             id.try_into().map_err(|_| Error::InvalidData(format!("{} id", id_type)))?,
-        );
-        self.put_meta(id_type.as_bytes(), (id + 1).to_le_bytes().to_vec()).await?;
-        Ok(id)
+        ))
     }
 }
 
@@ -782,6 +1360,11 @@ impl Schema {
         self.put(table::JOB_HISTORY_ID, &desc.id.to_le_bytes(), desc.encode_to_vec()).await
     }
 
+    #[inline]
+    async fn put_recommendation(&self, desc: Recommendation) -> Result<()> {
+        self.put(table::RECOMMENDATION_ID, &desc.id.to_le_bytes(), desc.encode_to_vec()).await
+    }
+
     #[inline]
     async fn put_table(&self, table: TableDesc) -> Result<()> {
         self.put(table::TABLE_ID, &table_key(table.db, &table.name), table.encode_to_vec()).await
@@ -822,6 +1405,40 @@ impl RemoteStore {
     }
 }
 
+/// The initial values of the id counters, the ids below them are allocated
+/// during bootstrapping.
+fn initial_id_counters() -> [(&'static str, u64); 9] {
+    [
+        (META_DATABASE_ID_KEY, sekas_schema::FIRST_USER_DATABASE_ID),
+        (META_TABLE_ID_KEY, sekas_schema::FIRST_USER_TABLE_ID),
+        (META_GROUP_ID_KEY, FIRST_GROUP_ID + 1),
+        (META_NODE_ID_KEY, FIRST_NODE_ID + 1),
+        (META_REPLICA_ID_KEY, INIT_USER_REPLICA_ID + 1),
+        (META_SHARD_ID_KEY, sekas_schema::FIRST_USER_SHARD_ID),
+        (META_JOB_ID_KEY, INITIAL_JOB_ID),
+        (META_RECOMMENDATION_ID_KEY, INITIAL_RECOMMENDATION_ID),
+        (META_TOPOLOGY_EVENT_ID_KEY, INITIAL_TOPOLOGY_EVENT_ID),
+    ]
+}
+
+/// The max ids in use of the groups, see [`Schema::repair_id_counters`].
+#[derive(Default)]
+struct GroupMaxIds {
+    group_id: Option<u64>,
+    replica_id: Option<u64>,
+    shard_id: Option<u64>,
+}
+
+fn id_owned_error(id_type: &str, id: u64, owner: &str) -> Error {
+    warn!("{id_type} id {id} is already owned by {owner}, the id counter might be rolled back");
+    Error::InvalidData(format!("{id_type} id {id} is already owned by {owner}"))
+}
+
+fn record_id_space(id_type: &str, next_id: u64) {
+    let remaining = u64::MAX - next_id;
+    super::metrics::ID_SPACE_REMAINING.with_label_values(&[id_type]).set(remaining as f64);
+}
+
 #[inline]
 fn table_key(database_id: u64, table_name: &str) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + table_name.len());
@@ -837,6 +1454,18 @@ fn group_key(group_id: u64) -> Vec<u8> {
     buf
 }
 
+fn decode_group(val: &[u8]) -> Result<GroupDesc> {
+    GroupDesc::decode(val).map_err(|_| Error::InvalidData("group desc".into()))
+}
+
+fn decode_node(val: &[u8]) -> Result<NodeDesc> {
+    NodeDesc::decode(val).map_err(|_| Error::InvalidData("node desc".into()))
+}
+
+fn decode_replica_state(val: &[u8]) -> Result<ReplicaState> {
+    ReplicaState::decode(val).map_err(|_| Error::InvalidData("replica state desc".into()))
+}
+
 #[inline]
 fn replica_key(group_id: u64, replica_id: u64) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() * 2);
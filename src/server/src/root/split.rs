@@ -0,0 +1,109 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use log::info;
+use sekas_api::server::v1::*;
+use sekas_runtime::time::Instant;
+
+use super::{quota, Root, Schema};
+use crate::{Error, Result};
+
+/// The max duration to wait for the group desc of the split shards being
+/// reported to root.
+const WAIT_SPLIT_REPORTED_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl Root {
+    /// Split the shard at the key, or at the estimated split key if it is not
+    /// specified. Returns the two shards split from the shard.
+    ///
+    /// `Error::QuotaExceeded` is returned if the table or the database has
+    /// reached its quota of shards.
+    pub async fn split_shard(
+        &self,
+        shard_id: u64,
+        split_key: Option<Vec<u8>>,
+    ) -> Result<Vec<ShardDesc>> {
+        self.check_catalog_writable()?;
+        if sekas_schema::shard::is_txn_shard(shard_id) {
+            return Err(Error::TxnShardFenced(shard_id));
+        }
+
+        let schema = self.schema()?;
+        let Some((group_id, shard)) = find_shard(&schema, shard_id).await? else {
+            return Err(Error::InvalidArgument(format!("shard {shard_id} is not exists")));
+        };
+        if let Some(split_key) = split_key.as_ref() {
+            validate_split_key(&shard, split_key)?;
+        }
+        quota::check_split_shard(&schema, shard.table_id).await?;
+
+        let new_shard_id = schema.next_shard_id().await?;
+        info!("split shard {shard_id} of group {group_id}, new shard {new_shard_id}");
+        let mut group_client = self.shared.transport_manager.lazy_group_client(group_id);
+        group_client.split_shard(shard_id, new_shard_id, split_key).await?;
+        schema.quota_usages().add_split_shard(shard.table_id).await;
+
+        // The group desc is reported to root once the split is applied.
+        let deadline = Instant::now() + WAIT_SPLIT_REPORTED_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Some((_, new_shard)) = find_shard(&schema, new_shard_id).await? {
+                if let Some((_, shard)) = find_shard(&schema, shard_id).await? {
+                    return Ok(vec![shard, new_shard]);
+                }
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+        }
+        Err(Error::DeadlineExceeded(format!(
+            "shard {shard_id} is split, but the new shard {new_shard_id} is not reported"
+        )))
+    }
+}
+
+/// Find the group and the desc of the shard, the groups are read page by page
+/// until the shard is found.
+async fn find_shard(schema: &Schema, shard_id: u64) -> Result<Option<(u64, ShardDesc)>> {
+    let mut filter = GroupFilter::default();
+    loop {
+        let page = schema.list_group_page(&filter).await?;
+        for group in page.items {
+            if let Some(shard) = group.shards.into_iter().find(|shard| shard.id == shard_id) {
+                return Ok(Some((group.id, shard)));
+            }
+        }
+        if !page.has_more() {
+            return Ok(None);
+        }
+        filter.page_token = page.next_page_token;
+    }
+}
+
+/// The split key must belong to the shard, and it must not be the start of the
+/// shard, otherwise the left shard is empty. It is checked again when the
+/// split is evaluated, with the same messages.
+fn validate_split_key(shard: &ShardDesc, split_key: &[u8]) -> Result<()> {
+    let shard_id = shard.id;
+    if !sekas_schema::shard::belong_to(shard, split_key) {
+        return Err(Error::InvalidArgument(format!(
+            "the user provided split key is not belong to the shard {shard_id}"
+        )));
+    }
+    if shard.range.as_ref().is_some_and(|range| range.start == split_key) {
+        return Err(Error::InvalidArgument(format!(
+            "the user provided split key is the start of the shard {shard_id}"
+        )));
+    }
+    Ok(())
+}
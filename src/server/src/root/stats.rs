@@ -17,6 +17,17 @@ use std::sync::{Arc, Mutex};
 
 use sekas_api::server::v1::*;
 
+/// The shard is dominated by a single key if the key serves at least this
+/// ratio of the requests of the shard.
+const HOT_KEY_LOAD_RATIO: f32 = 0.5;
+
+/// The min requests per second of a shard dominated by a single key, the keys
+/// of an idle shard are not hot.
+const HOT_KEY_MIN_REQUESTS_PER_SEC: f32 = 100.0;
+
+/// The shards larger than this size are split.
+const SPLIT_THRESHOLD: u64 = 64 * 1024 * 1024;
+
 struct GroupDelta {
     epoch: u64,
     incoming: Vec<ReplicaDesc>,
@@ -35,6 +46,9 @@ pub struct ClusterStats {
     job_stats: Arc<Mutex<JobStats>>,
     table_set_stats: Arc<Mutex<TableSetStats>>,
     group_set_stats: Arc<Mutex<HashMap<u64, GroupStats>>>,
+    /// Overrides [`SPLIT_THRESHOLD`] if it is not zero.
+    #[cfg(any(test, feature = "testing"))]
+    split_threshold: u64,
 }
 
 #[derive(Default)]
@@ -51,24 +65,66 @@ struct JobStats {
 
 #[derive(Default)]
 pub struct TableSetStats {
-    tables: HashMap<u64, TableStats>,
+    tables: HashMap<u64, TableShards>,
+    /// The shards owned by the group, in the last stats of the group.
+    group_shards: HashMap<u64 /* group */, Vec<(u64 /* table */, u64 /* shard */)>>,
 }
 
 #[derive(Default)]
-pub struct TableStats {
+pub struct TableShards {
     shards: HashMap<u64, ShardStats>,
-    shard_indexes: HashMap<u64, /* group */ u64>,
+    shard_indexes: HashMap<u64, ShardOwner>,
+}
+
+/// The group whose stats of the shard are taken. A shard is reported by both
+/// the source and the dest group during migration, the stats from the group of
+/// the higher epoch are taken, so the shard is counted once.
+#[derive(Clone, Copy)]
+struct ShardOwner {
+    group_id: u64,
+    epoch: u64,
 }
 
 impl ClusterStats {
     pub fn handle_group_stats(&self, group_stats: GroupStats) {
         {
             let mut table_set = self.table_set_stats.lock().expect("poisoned");
+            let owner = ShardOwner { group_id: group_stats.group_id, epoch: group_stats.epoch };
+            let mut owned_shards = Vec::with_capacity(group_stats.shard_stats.len());
             for shard in &group_stats.shard_stats {
                 let shard_id = shard.shard_id;
                 let table_stats = table_set.tables.entry(shard.table_id).or_default();
+                let current = table_stats.shard_indexes.get(&shard_id).cloned();
+                if current.is_some_and(|current| {
+                    current.group_id != owner.group_id && current.epoch > owner.epoch
+                }) {
+                    // The shard is owned by another group.
+                    continue;
+                }
+                if shard.removed {
+                    // The data of the removed shard is being purged, it only
+                    // stays in the group stats.
+                    table_stats.remove_shard(shard_id, owner.group_id);
+                    continue;
+                }
                 table_stats.shards.insert(shard_id, shard.clone());
-                table_stats.shard_indexes.insert(shard_id, group_stats.group_id);
+                table_stats.shard_indexes.insert(shard_id, owner);
+                owned_shards.push((shard.table_id, shard_id));
+            }
+
+            // The shards no longer reported by the group are moved out or deleted.
+            let last_owned_shards =
+                table_set.group_shards.insert(owner.group_id, owned_shards.clone());
+            for (table_id, shard_id) in last_owned_shards.unwrap_or_default() {
+                if owned_shards.contains(&(table_id, shard_id)) {
+                    continue;
+                }
+                if let hash_map::Entry::Occupied(mut ent) = table_set.tables.entry(table_id) {
+                    ent.get_mut().remove_shard(shard_id, owner.group_id);
+                    if ent.get().shards.is_empty() {
+                        ent.remove();
+                    }
+                }
             }
         }
         {
@@ -123,20 +179,23 @@ impl ClusterStats {
         rs
     }
 
-    /// Get the large shards, return the group_id and shard_id.
+    /// Get the large shards, return the group_id and shard_id. The shards
+    /// dominated by a single key are skipped, since splitting them won't spread
+    /// the load, see [`ClusterStats::get_hot_key_shards`].
     pub fn get_large_shards(&self, limit: usize) -> Vec<(u64, u64)> {
-        const SPLIT_THRESHOLD: u64 = 64 * 1024 * 1024;
         let in_spliting = { self.sched_stats.lock().expect("poisoned").split_shards.clone() };
         let table_set = self.table_set_stats.lock().expect("poisoned");
         let mut target_shards = Vec::with_capacity(limit);
         for table_stats in table_set.tables.values() {
             for shard_stats in table_stats.shards.values() {
-                if shard_stats.shard_size < SPLIT_THRESHOLD
+                if shard_stats.shard_size < self.split_threshold()
                     || in_spliting.contains(&shard_stats.shard_id)
+                    || dominant_hot_key(shard_stats).is_some()
                 {
                     continue;
                 }
-                if let Some(&group_id) = table_stats.shard_indexes.get(&shard_stats.shard_id) {
+                if let Some(owner) = table_stats.shard_indexes.get(&shard_stats.shard_id) {
+                    let group_id = owner.group_id;
                     if group_id == sekas_schema::ROOT_GROUP_ID {
                         // Don't split the root groups, such as txn shard.
                         continue;
@@ -151,12 +210,86 @@ impl ClusterStats {
         target_shards
     }
 
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_split_threshold(mut self, split_threshold: u64) -> Self {
+        self.split_threshold = split_threshold;
+        self
+    }
+
+    fn split_threshold(&self) -> u64 {
+        #[cfg(any(test, feature = "testing"))]
+        if self.split_threshold != 0 {
+            return self.split_threshold;
+        }
+        SPLIT_THRESHOLD
+    }
+
+    /// Get the large shards skipped by [`ClusterStats::get_large_shards`],
+    /// return the shard_id and the reason.
+    pub fn get_skipped_large_shards(&self) -> Vec<(u64, &'static str)> {
+        let in_spliting = { self.sched_stats.lock().expect("poisoned").split_shards.clone() };
+        let table_set = self.table_set_stats.lock().expect("poisoned");
+        let mut skipped = Vec::default();
+        for table_stats in table_set.tables.values() {
+            for shard_stats in table_stats.shards.values() {
+                if shard_stats.shard_size < self.split_threshold() {
+                    continue;
+                }
+                let shard_id = shard_stats.shard_id;
+                if in_spliting.contains(&shard_id) {
+                    skipped.push((shard_id, "split in flight"));
+                } else if dominant_hot_key(shard_stats).is_some() {
+                    skipped.push((shard_id, "dominated by a hot key"));
+                }
+            }
+        }
+        skipped
+    }
+
+    /// Get the shards dominated by a single key, return the group_id, shard_id
+    /// and the key prefix.
+    pub fn get_hot_key_shards(&self) -> Vec<(u64, u64, Vec<u8>)> {
+        let table_set = self.table_set_stats.lock().expect("poisoned");
+        let mut target_shards = Vec::default();
+        for table_stats in table_set.tables.values() {
+            for shard_stats in table_stats.shards.values() {
+                let Some(hot_key) = dominant_hot_key(shard_stats) else {
+                    continue;
+                };
+                if let Some(owner) = table_stats.shard_indexes.get(&shard_stats.shard_id) {
+                    target_shards.push((
+                        owner.group_id,
+                        shard_stats.shard_id,
+                        hot_key.key_prefix.clone(),
+                    ));
+                }
+            }
+        }
+        target_shards
+    }
+
     /// Get the stats of a shard.
     pub fn get_shard_stats(&self, shard_id: u64) -> Option<ShardStats> {
         let table_set = self.table_set_stats.lock().expect("poisoned");
         table_set.tables.values().filter_map(|v| v.shards.get(&shard_id)).next().cloned()
     }
 
+    /// Get the stats of a table, summed over the shards of the table. The size
+    /// and the number of keys are approximate.
+    pub fn get_table_stats(&self, table_id: u64) -> TableStats {
+        let mut stats = TableStats { table_id, ..Default::default() };
+        let table_set = self.table_set_stats.lock().expect("poisoned");
+        if let Some(table_shards) = table_set.tables.get(&table_id) {
+            for shard_stats in table_shards.shards.values() {
+                stats.size += shard_stats.shard_size;
+                stats.num_keys += shard_stats.num_keys;
+                stats.read_qps += shard_stats.read_qps;
+                stats.write_qps += shard_stats.write_qps;
+            }
+        }
+        stats
+    }
+
     /// Get the stats of a group.
     pub fn get_group_stats(&self, group_id: u64) -> Option<GroupStats> {
         let group_set = self.group_set_stats.lock().expect("poisoned");
@@ -177,10 +310,33 @@ impl ClusterStats {
         {
             let mut inner = self.table_set_stats.lock().expect("poisoned");
             inner.tables.clear();
+            inner.group_shards.clear();
         }
     }
 }
 
+impl TableShards {
+    /// Remove the shard if it is owned by the group.
+    fn remove_shard(&mut self, shard_id: u64, group_id: u64) {
+        if self.shard_indexes.get(&shard_id).is_some_and(|owner| owner.group_id == group_id) {
+            self.shards.remove(&shard_id);
+            self.shard_indexes.remove(&shard_id);
+        }
+    }
+}
+
+/// Returns the key which serves most of the requests of the shard.
+fn dominant_hot_key(shard_stats: &ShardStats) -> Option<&HotKeyStats> {
+    let hot_key = shard_stats.hot_keys.first()?;
+    if shard_stats.requests_per_sec >= HOT_KEY_MIN_REQUESTS_PER_SEC
+        && hot_key.requests_per_sec >= shard_stats.requests_per_sec * HOT_KEY_LOAD_RATIO
+    {
+        Some(hot_key)
+    } else {
+        None
+    }
+}
+
 impl SchedStats {
     fn replace_state(&mut self, updates: &[ScheduleState]) -> bool {
         let mut updated = false;
@@ -233,3 +389,87 @@ impl SchedStats {
         self.node_view = new_node_view;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shard_stats(shard_id: u64, requests_per_sec: f32, hot_key: f32) -> ShardStats {
+        ShardStats {
+            shard_id,
+            table_id: 1,
+            shard_size: 128 * 1024 * 1024,
+            requests_per_sec,
+            hot_keys: vec![HotKeyStats { key_prefix: b"key".to_vec(), requests_per_sec: hot_key }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn large_shard_dominated_by_hot_key_is_not_split() {
+        let stats = ClusterStats::default();
+        stats.handle_group_stats(GroupStats {
+            group_id: 100,
+            shard_stats: vec![
+                shard_stats(1, 1000.0, 900.0),
+                shard_stats(2, 1000.0, 100.0),
+                // The shard is idle.
+                shard_stats(3, 10.0, 9.0),
+            ],
+            ..Default::default()
+        });
+
+        let mut large_shards = stats.get_large_shards(5);
+        large_shards.sort_unstable();
+        assert_eq!(large_shards, vec![(100, 2), (100, 3)]);
+        assert_eq!(stats.get_skipped_large_shards(), vec![(1, "dominated by a hot key")]);
+        assert_eq!(stats.get_hot_key_shards(), vec![(100, 1, b"key".to_vec())]);
+    }
+
+    fn group_stats(group_id: u64, epoch: u64, shards: &[(u64, f32)]) -> GroupStats {
+        let shard_stats = shards
+            .iter()
+            .map(|&(shard_id, write_qps)| ShardStats {
+                shard_id,
+                table_id: 1,
+                shard_size: 1024,
+                num_keys: 10,
+                write_qps,
+                ..Default::default()
+            })
+            .collect();
+        GroupStats { group_id, epoch, shard_stats, ..Default::default() }
+    }
+
+    #[test]
+    fn migrating_shard_is_counted_once() {
+        let stats = ClusterStats::default();
+        stats.handle_group_stats(group_stats(100, 5, &[(1, 10.0), (2, 20.0)]));
+        let table_stats = stats.get_table_stats(1);
+        assert_eq!((table_stats.size, table_stats.num_keys), (2048, 20));
+        assert_eq!(table_stats.write_qps, 30.0);
+
+        // Shard 2 is being moved to group 101, both groups report it.
+        stats.handle_group_stats(group_stats(101, 3, &[(2, 1.0)]));
+        assert_eq!(stats.get_table_stats(1).write_qps, 30.0);
+        stats.handle_group_stats(group_stats(100, 6, &[(1, 10.0), (2, 20.0)]));
+        stats.handle_group_stats(group_stats(101, 7, &[(2, 2.0)]));
+        let table_stats = stats.get_table_stats(1);
+        assert_eq!((table_stats.size, table_stats.num_keys), (2048, 20));
+        assert_eq!(table_stats.write_qps, 12.0);
+
+        // The source group still reports the shard with a lower epoch.
+        stats.handle_group_stats(group_stats(100, 6, &[(1, 10.0), (2, 20.0)]));
+        assert_eq!(stats.get_table_stats(1).write_qps, 12.0);
+
+        // The shard is moved out of the source group.
+        stats.handle_group_stats(group_stats(100, 8, &[(1, 10.0)]));
+        assert_eq!(stats.get_table_stats(1).write_qps, 12.0);
+
+        // The shard is deleted.
+        stats.handle_group_stats(group_stats(101, 9, &[]));
+        let table_stats = stats.get_table_stats(1);
+        assert_eq!((table_stats.size, table_stats.write_qps), (1024, 10.0));
+        assert_eq!(stats.get_table_stats(2), TableStats { table_id: 2, ..Default::default() });
+    }
+}
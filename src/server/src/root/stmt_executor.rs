@@ -12,13 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use log::warn;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use log::{info, warn};
 use sekas_api::server::v1::*;
-use sekas_parser::{ColumnResult, ConfigStatement, ExecuteResult, Row, ShowStatement};
+use sekas_parser::{
+    AlterDatabaseStatement, AlterTableStatement, ApproveStatement, ColumnResult,
+    CompactTableStatement, ConfigStatement, DebugScheduleStatement, DebugSearchStatement,
+    DebugVerifyStatement, ExecuteResult, KillTxnStatement, Row, ShowStatement, SplitStatement,
+};
 use sekas_rock::ascii::escape_bytes;
+use sekas_runtime::time::timestamp_millis;
+use sekas_schema::property::QUOTA_LIMITS;
 
-use super::Root;
-use crate::{Error, Result};
+use super::health::HealthAlert;
+use super::quota::{database_quota, Quota};
+use super::schedule::{group_leader_zones, outcome_name, Recommendation};
+use super::schema::Schema;
+use super::{recommend, Root};
+use crate::{Error, Result, ScheduleMode};
 
 impl Root {
     /// Handle statement and return with json.
@@ -40,62 +53,464 @@ impl Root {
             return Ok(ExecuteResult::None);
         };
         match stmt {
+            AlterDatabase(alter) => self.handle_alter_database_stmt(alter).await,
+            AlterTable(alter) => self.handle_alter_table_stmt(alter).await,
+            Approve(approve) => self.handle_approve_stmt(approve).await,
+            CompactTable(compact) => self.handle_compact_table_stmt(compact).await,
             Config(config) => self.handle_config_stmt(config).await,
             Show(show) => self.handle_show_stmt(show).await,
-            CreateDb(_) | CreateTable(_) | Debug(_) | Echo(_) | Help(_) | Get(_) | Put(_)
-            | Delete(_) => {
+            DebugSchedule(schedule) => self.handle_debug_schedule_stmt(schedule).await,
+            DebugSearch(search) => self.handle_debug_search_stmt(search).await,
+            DebugVerify(verify) => self.handle_debug_verify_stmt(verify).await,
+            Split(split) => self.handle_split_stmt(split).await,
+            KillTxn(kill) => self.handle_kill_txn_stmt(kill).await,
+            CreateDb(_) | CreateTable(_) | Debug(_) | Echo(_) | Format(_) | Help(_) | Get(_)
+            | Put(_) | Delete(_) | Scan(_) => {
                 Err(Error::InvalidArgument(", local stmt is sent to root server".to_owned()))
             }
         }
     }
 
+    async fn handle_alter_table_stmt(&self, alter: AlterTableStatement) -> Result<ExecuteResult> {
+        let Some(db) = self.get_database(&alter.db_name).await? else {
+            return Ok(ExecuteResult::Msg(format!("database {} not exists", alter.db_name)));
+        };
+        let properties = [(alter.key.clone(), alter.value.clone())].into_iter().collect();
+        match self.update_table(&alter.table_name, &db, properties).await {
+            Ok(table) => Ok(ExecuteResult::Msg(format!(
+                "table {}.{} is altered, version {}",
+                alter.db_name, alter.table_name, table.version
+            ))),
+            Err(Error::InvalidArgument(msg)) => Ok(ExecuteResult::Msg(msg)),
+            Err(Error::TableNotFound(name)) => {
+                Ok(ExecuteResult::Msg(format!("table {}.{name} not exists", alter.db_name)))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn handle_alter_database_stmt(
+        &self,
+        alter: AlterDatabaseStatement,
+    ) -> Result<ExecuteResult> {
+        let properties = [(alter.key.clone(), alter.value.clone())].into_iter().collect();
+        match self.update_database(&alter.db_name, properties).await {
+            Ok(db) => {
+                let quota = database_quota(&self.schema()?, &db).await?;
+                Ok(ExecuteResult::Msg(format!("database {} is altered, quota: {quota}", db.name)))
+            }
+            Err(Error::InvalidArgument(msg)) => Ok(ExecuteResult::Msg(msg)),
+            Err(Error::DatabaseNotFound(name)) => {
+                Ok(ExecuteResult::Msg(format!("database {name} not exists")))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn handle_compact_table_stmt(
+        &self,
+        compact: CompactTableStatement,
+    ) -> Result<ExecuteResult> {
+        let resp = match self.compact_table(&compact.db_name, &compact.table_name).await {
+            Ok(resp) => resp,
+            Err(Error::DatabaseNotFound(name)) => {
+                return Ok(ExecuteResult::Msg(format!("database {name} not exists")))
+            }
+            Err(Error::TableNotFound(name)) => {
+                return Ok(ExecuteResult::Msg(format!(
+                    "table {}.{name} not exists",
+                    compact.db_name
+                )))
+            }
+            Err(err) => return Err(err),
+        };
+
+        let columns = ["group", "replica", "node", "size_before", "size_after", "result"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect();
+        let compaction_to_row = |compaction: ReplicaCompaction| -> Row {
+            let mut values = vec![
+                compaction.group_id.into(),
+                compaction.replica_id.into(),
+                compaction.node_id.into(),
+            ];
+            match compaction.result {
+                Some(result) if compaction.error.is_empty() => {
+                    values.push(result.size_before.into());
+                    values.push(result.size_after.into());
+                    let status = if result.coalesced { "coalesced" } else { "finished" };
+                    values.push(status.to_owned().into());
+                }
+                _ => {
+                    values.push("-".to_owned().into());
+                    values.push("-".to_owned().into());
+                    values.push(format!("ERROR: {}", compaction.error).into());
+                }
+            }
+            Row { values }
+        };
+        let rows = resp.replicas.into_iter().map(compaction_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_approve_stmt(&self, approve_stmt: ApproveStatement) -> Result<ExecuteResult> {
+        let Ok(id) = approve_stmt.id.parse::<u64>() else {
+            return Ok(ExecuteResult::Msg(
+                "The id of recommendation is not a valid u64 numeric".to_owned(),
+            ));
+        };
+        match self.approve_action(id).await {
+            Ok(()) => Ok(ExecuteResult::Msg(format!("recommendation {id} is approved"))),
+            Err(Error::InvalidArgument(msg)) => Ok(ExecuteResult::Msg(msg)),
+            Err(err) => Err(err),
+        }
+    }
+
     async fn handle_config_stmt(&self, config_stmt: ConfigStatement) -> Result<ExecuteResult> {
-        let _ = config_stmt;
-        Ok(ExecuteResult::Msg("the CONFIG statement is not supported yet".to_owned()))
+        let key = String::from_utf8_lossy(&config_stmt.key);
+        let value = String::from_utf8_lossy(&config_stmt.value);
+        if let Some(target) = key.strip_prefix("log.") {
+            return self.handle_config_log_filter(target, &value, &config_stmt).await;
+        }
+        if config_stmt.node.is_some() || config_stmt.ttl.is_some() {
+            return Ok(ExecuteResult::Msg(format!(
+                "ON and TTL clauses are not accepted by config `{key}`"
+            )));
+        }
+        match key.as_ref() {
+            "schedule_mode" => {
+                let mode = match value.parse::<ScheduleMode>() {
+                    Ok(mode) => mode,
+                    Err(msg) => return Ok(ExecuteResult::Msg(msg)),
+                };
+                self.set_schedule_mode(mode).await?;
+            }
+            "schedule_auto_cure" => {
+                let Ok(auto_cure) = value.parse::<bool>() else {
+                    return Ok(ExecuteResult::Msg(format!(
+                        "the value of `{key}` should be true or false"
+                    )));
+                };
+                self.set_schedule_auto_cure(auto_cure).await?;
+            }
+            "snapshot_send_concurrency" | "transfer_bytes_per_sec" => {
+                let Ok(value) = value.parse::<u64>() else {
+                    return Ok(ExecuteResult::Msg(format!(
+                        "the value of `{key}` should be a valid u64 numeric"
+                    )));
+                };
+                let schema = self.schema()?;
+                let mut limits = schema.get_transfer_limits().await?;
+                if key == "snapshot_send_concurrency" {
+                    if value == 0 {
+                        return Ok(ExecuteResult::Msg(format!(
+                            "the value of `{key}` should be positive"
+                        )));
+                    }
+                    limits.snapshot_send_concurrency = Some(value);
+                } else {
+                    limits.bytes_per_sec = Some(value);
+                }
+                info!("change transfer limits to {limits:?}");
+                schema.set_transfer_limits(&limits).await?;
+            }
+            "raft.pre_vote" | "raft.check_quorum" => {
+                let Ok(enabled) = value.parse::<bool>() else {
+                    return Ok(ExecuteResult::Msg(format!(
+                        "the value of `{key}` should be true or false"
+                    )));
+                };
+                let schema = self.schema()?;
+                let mut options = schema.get_raft_election().await?;
+                if key == "raft.pre_vote" {
+                    options.pre_vote = Some(enabled);
+                } else {
+                    options.check_quorum = Some(enabled);
+                }
+                info!("change raft election options to {options:?}");
+                schema.set_raft_election(&options).await?;
+            }
+            limit if QUOTA_LIMITS.contains(&limit) => {
+                let Ok(value) = value.parse::<u64>() else {
+                    return Ok(ExecuteResult::Msg(format!(
+                        "the value of `{key}` should be a valid u64 numeric"
+                    )));
+                };
+                self.set_default_quota(limit, value).await?;
+            }
+            others => return Ok(ExecuteResult::Msg(format!("unknown config: {others}"))),
+        }
+        Ok(ExecuteResult::Msg(format!("config `{key}` is set to `{value}`")))
+    }
+
+    async fn handle_config_log_filter(
+        &self,
+        target: &str,
+        level: &str,
+        config_stmt: &ConfigStatement,
+    ) -> Result<ExecuteResult> {
+        let node_id = match config_stmt.node.as_ref().map(|v| v.parse::<u64>()) {
+            None => None,
+            Some(Ok(node_id)) => Some(node_id),
+            Some(Err(_)) => {
+                return Ok(ExecuteResult::Msg(
+                    "The value of ON clause is not a valid u64 numeric".to_owned(),
+                ))
+            }
+        };
+        if target.is_empty() {
+            return Ok(ExecuteResult::Msg("the target of log is not specified".to_owned()));
+        }
+        let req = SetLogFilterRequest {
+            target: target.to_owned(),
+            level: level.to_owned(),
+            ttl_ms: config_stmt.ttl.unwrap_or_default().saturating_mul(1000),
+        };
+        match self.config_log_filter(node_id, req).await {
+            Ok(resp) => Ok(log_filters_to_result(resp.filters)),
+            Err(Error::InvalidArgument(msg)) => Ok(ExecuteResult::Msg(msg)),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn handle_split_stmt(&self, split_stmt: SplitStatement) -> Result<ExecuteResult> {
+        let Ok(shard_id) = split_stmt.shard.parse::<u64>() else {
+            return Ok(ExecuteResult::Msg("The id of shard is not a valid u64 numeric".to_owned()));
+        };
+        let shards = match self.split_shard(shard_id, Some(split_stmt.key)).await {
+            Ok(shards) => shards,
+            Err(Error::InvalidArgument(msg) | Error::PermissionDenied(msg)) => {
+                return Ok(ExecuteResult::Msg(msg))
+            }
+            Err(err @ (Error::TxnShardFenced(_) | Error::QuotaExceeded { .. })) => {
+                return Ok(ExecuteResult::Msg(err.to_string()))
+            }
+            Err(err) => return Err(err),
+        };
+
+        let columns = ["id", "start", "end"].into_iter().map(ToString::to_string).collect();
+        let shard_to_row = |shard: ShardDesc| -> Row {
+            let range = shard.range.unwrap_or_default();
+            let values = vec![
+                shard.id.into(),
+                escape_bytes(&range.start).into(),
+                escape_bytes(&range.end).into(),
+            ];
+            Row { values }
+        };
+        let rows = shards.into_iter().map(shard_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_kill_txn_stmt(&self, kill_stmt: KillTxnStatement) -> Result<ExecuteResult> {
+        let Ok(txn_id) = kill_stmt.txn_id.parse::<u64>() else {
+            return Ok(ExecuteResult::Msg("The id of txn is not a valid u64 numeric".to_owned()));
+        };
+        match self.kill_txn(txn_id).await {
+            Ok(()) => Ok(ExecuteResult::Msg(format!("txn {txn_id} is killed"))),
+            Err(Error::InvalidArgument(msg)) => Ok(ExecuteResult::Msg(msg)),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn handle_debug_search_stmt(
+        &self,
+        search_stmt: DebugSearchStatement,
+    ) -> Result<ExecuteResult> {
+        let Ok(group_id) = search_stmt.group.parse::<u64>() else {
+            return Ok(ExecuteResult::Msg(
+                "The value of FROM clause is not a valid u64 numeric".to_owned(),
+            ));
+        };
+
+        let schema = self.schema()?;
+        if schema.get_group(group_id).await?.is_none() {
+            return Ok(ExecuteResult::Msg("No such group exists".to_owned()));
+        }
+
+        // The raft log of the leader is searched, since it contains all
+        // committed entries.
+        let leader = schema
+            .group_replica_states(group_id)
+            .await?
+            .into_iter()
+            .filter(|state| state.role == RaftRole::Leader as i32)
+            .max_by_key(|state| state.term);
+        let Some(leader) = leader else {
+            return Ok(ExecuteResult::Msg(format!("The leader of group {group_id} is unknown")));
+        };
+        let Some(node) = schema.get_node(leader.node_id).await? else {
+            return Ok(ExecuteResult::Msg(format!("No such node {} exists", leader.node_id)));
+        };
+
+        let client = self.shared.transport_manager.get_node_client(node.addr)?;
+        let req = SearchRaftLogRequest {
+            group_id,
+            key_prefix: search_stmt.key_prefix,
+            ..Default::default()
+        };
+        let entries = client.search_raft_log(req).await?;
+
+        let columns = ["index", "term", "request_id", "summary"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let entry_to_row = |entry: RaftLogEntry| -> Row {
+            Row {
+                values: vec![
+                    entry.index.into(),
+                    entry.term.into(),
+                    entry.request_id.into(),
+                    entry.summary.into(),
+                ],
+            }
+        };
+        let rows = entries.into_iter().map(entry_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_debug_schedule_stmt(
+        &self,
+        _schedule_stmt: DebugScheduleStatement,
+    ) -> Result<ExecuteResult> {
+        let pass = self.trigger_schedule_pass().await?;
+        Ok(schedule_passes_to_result(vec![pass]))
+    }
+
+    async fn handle_debug_verify_stmt(
+        &self,
+        verify_stmt: DebugVerifyStatement,
+    ) -> Result<ExecuteResult> {
+        if !verify_stmt.property.eq_ignore_ascii_case("coverage") {
+            return Ok(ExecuteResult::Msg(format!("unknown property: {}", verify_stmt.property)));
+        }
+
+        // The violations found by an explicit verification are raised at once, they
+        // are resolved by the following periodic verifications if they are transient.
+        let violations = self.verify_coverage().await?;
+        self.health.refresh_coverage(violations.clone());
+        if violations.is_empty() {
+            return Ok(ExecuteResult::Msg("no violations are found".to_owned()));
+        }
+
+        let columns = ["kind", "table_id", "message"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let violation_to_row = |alert: HealthAlert| -> Row {
+            let table_id = match &alert {
+                HealthAlert::ShardGap { table_id, .. }
+                | HealthAlert::ShardOverlap { table_id, .. }
+                | HealthAlert::ShardNotInGroup { table_id, .. }
+                | HealthAlert::ShardInMultipleGroups { table_id, .. } => *table_id,
+                _ => 0,
+            };
+            Row {
+                values: vec![
+                    alert.kind().to_owned().into(),
+                    table_id.into(),
+                    alert.to_string().into(),
+                ],
+            }
+        };
+        let rows = violations.into_iter().map(violation_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
     }
 
     async fn handle_show_stmt(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+        // The catalog is read after a read index of the root group, so the changes
+        // committed by a former root leader are not missed. The read index is skipped
+        // by the `STALE` modifier, the staleness of the local states is bounded by
+        // the leader lease of the root group.
+        let schema =
+            if show_stmt.stale { self.schema()? } else { self.linearizable_schema().await? };
         match show_stmt.property.as_str() {
-            "databases" => self.handle_show_databases(show_stmt).await,
-            "tables" => self.handle_show_tables(show_stmt).await,
-            "groups" => self.handle_show_groups(show_stmt).await,
-            "replicas" => self.handle_show_replicas(show_stmt).await,
-            "shards" => self.handle_show_shards(show_stmt).await,
-            "nodes" => self.handle_show_nodes(show_stmt).await,
+            "databases" => self.handle_show_databases(&schema, show_stmt).await,
+            "tables" => self.handle_show_tables(&schema, show_stmt).await,
+            "groups" => self.handle_show_groups(&schema, show_stmt).await,
+            "replicas" => self.handle_show_replicas(&schema, show_stmt).await,
+            "shards" => self.handle_show_shards(&schema, show_stmt).await,
+            "intents" => self.handle_show_intents(&schema, show_stmt).await,
+            "hotkeys" => self.handle_show_hotkeys(&schema, show_stmt).await,
+            "nodes" => self.handle_show_nodes(&schema, show_stmt).await,
+            "migrations" => self.handle_show_migrations(show_stmt).await,
+            "clones" => self.handle_show_clones(show_stmt).await,
+            "recommendations" => self.handle_show_recommendations(show_stmt).await,
+            "alerts" => self.handle_show_alerts(show_stmt),
+            "txns" => self.handle_show_txns(show_stmt).await,
+            "log_filters" => self.handle_show_log_filters(show_stmt).await,
+            "scheduler" => self.handle_show_scheduler(show_stmt),
             others => Ok(ExecuteResult::Msg(format!("unknown property: {others}"))),
         }
     }
 
-    async fn handle_show_databases(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+    async fn handle_show_databases(
+        &self,
+        schema: &Schema,
+        show_stmt: ShowStatement,
+    ) -> Result<ExecuteResult> {
         if show_stmt.from.is_some() {
             return Ok(ExecuteResult::Msg(
                 "FROM clause is not required by 'databases' property".to_owned(),
             ));
         }
-        let databases = self.list_database().await?;
-        let columns = ["id", "name"].into_iter().map(ToString::to_string).collect::<Vec<_>>();
+        let databases = schema.list_database().await?;
+        let defaults = schema.get_quota_limits().await?;
+        let columns = ["id", "name"]
+            .into_iter()
+            .chain(QUOTA_LIMITS)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
         let rows = databases
             .into_iter()
-            .map(|db| Row { values: vec![db.id.into(), db.name.into()] })
+            .map(|db| {
+                let quota = Quota::resolve(&defaults.limits, &db);
+                let values = vec![
+                    db.id.into(),
+                    db.name.into(),
+                    quota.max_tables_per_database.into(),
+                    quota.max_shards_per_table.into(),
+                    quota.max_shards_per_database.into(),
+                ];
+                Row { values }
+            })
             .collect::<Vec<_>>();
         Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
     }
 
-    async fn handle_show_tables(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+    async fn handle_show_tables(
+        &self,
+        schema: &Schema,
+        show_stmt: ShowStatement,
+    ) -> Result<ExecuteResult> {
         let Some(db) = show_stmt.from.as_ref() else {
             return Ok(ExecuteResult::Msg(
                 "the database is not specified, add it via the FROM clause".to_owned(),
             ));
         };
-        let Some(db_desc) = self.get_database(db).await? else {
+        let Some(db_desc) = schema.get_database(db).await? else {
             return Ok(ExecuteResult::Msg(format!("database '{db}' is not exists")));
         };
 
-        let tables = self.list_table(&db_desc).await?;
-        let columns = ["id", "name", "type", "replication", "replicas_per_group", "properties"]
-            .into_iter()
-            .map(ToString::to_string)
-            .collect::<Vec<_>>();
+        let tables = schema.list_database_tables(db_desc.id).await?;
+        let columns = [
+            "id",
+            "name",
+            "type",
+            "replication",
+            "replicas_per_group",
+            "properties",
+            "size",
+            "keys",
+            "read_qps",
+            "write_qps",
+            "state",
+        ]
+        .into_iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+        let cluster_stats = self.get_cluster_stats();
         let table_to_row = |table: TableDesc| -> Row {
             use sekas_schema::property::*;
             let mut properties = vec![];
@@ -105,6 +520,14 @@ impl Root {
                 }
             }
             properties.sort_unstable();
+            let stats = cluster_stats.get_table_stats(table.id);
+            let state = if table.properties.contains_key(PROVISIONING) {
+                "provisioning"
+            } else if table.properties.contains_key(CLONE_SOURCE) {
+                "cloning"
+            } else {
+                "ready"
+            };
             let values: Vec<serde_json::Value> = vec![
                 table.id.into(),
                 table.name.into(),
@@ -112,6 +535,11 @@ impl Root {
                 table.properties.get(REPLICATION).cloned().unwrap_or_default().into(),
                 table.properties.get(REPLICAS_PER_GROUP).cloned().unwrap_or_default().into(),
                 properties.join(", ").into(),
+                display_size(stats.size).into(),
+                stats.num_keys.into(),
+                (stats.read_qps as f64).into(),
+                (stats.write_qps as f64).into(),
+                state.into(),
             ];
             Row { values }
         };
@@ -119,19 +547,54 @@ impl Root {
         Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
     }
 
-    async fn handle_show_groups(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+    async fn handle_show_groups(
+        &self,
+        schema: &Schema,
+        show_stmt: ShowStatement,
+    ) -> Result<ExecuteResult> {
         if show_stmt.from.is_some() {
             return Ok(ExecuteResult::Msg(
                 "FROM clause is not required by 'groups' property".to_owned(),
             ));
         }
-        let groups = self.list_group().await?;
+        // The groups are listed by the first page if the limit is specified.
+        let groups = match show_stmt.limit.filter(|limit| *limit > 0) {
+            Some(limit) => {
+                schema.list_group_page(&GroupFilter { limit, ..Default::default() }).await?.items
+            }
+            None => schema.list_group().await?,
+        };
+        let leader_zones = group_leader_zones(schema).await?;
+        let node_labels = schema
+            .list_node()
+            .await?
+            .into_iter()
+            .map(|n| (n.id, n.labels))
+            .collect::<HashMap<_, _>>();
+        let mut leader_nodes = HashMap::new();
+        for state in schema.list_replica_state().await? {
+            if state.role == RaftRole::Leader as i32 {
+                let entry = leader_nodes.entry(state.group_id).or_insert((0, 0));
+                if entry.0 <= state.term {
+                    *entry = (state.term, state.node_id);
+                }
+            }
+        }
 
-        let columns =
-            ["id", "shard_epoch", "config_epoch", "num_replicas", "num_shards", "qps(w/r)", "size"]
-                .into_iter()
-                .map(ToString::to_string)
-                .collect::<Vec<_>>();
+        let columns = [
+            "id",
+            "shard_epoch",
+            "config_epoch",
+            "num_replicas",
+            "num_shards",
+            "qps(w/r)",
+            "size",
+            "preferred_zone",
+            "satisfied",
+        ]
+        .into_iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
 
         let cluster_stats = self.get_cluster_stats();
         let group_to_row = |group: GroupDesc| -> Row {
@@ -153,13 +616,29 @@ impl Root {
                 values.push("-/-".to_owned().into());
                 values.push("-".to_owned().into());
             }
+            if let Some(zone) = leader_zones.get(&group.id) {
+                let satisfied = leader_nodes
+                    .get(&group.id)
+                    .and_then(|(_, node_id)| node_labels.get(node_id))
+                    .map(|labels| labels.contains(zone))
+                    .unwrap_or_default();
+                values.push(zone.clone().into());
+                values.push(if satisfied { "yes" } else { "no" }.into());
+            } else {
+                values.push("-".to_owned().into());
+                values.push("-".to_owned().into());
+            }
             Row { values }
         };
         let rows = groups.into_iter().map(group_to_row).collect::<Vec<_>>();
         Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
     }
 
-    async fn handle_show_replicas(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+    async fn handle_show_replicas(
+        &self,
+        schema: &Schema,
+        show_stmt: ShowStatement,
+    ) -> Result<ExecuteResult> {
         let Some(from) = show_stmt.from else {
             return Ok(ExecuteResult::Msg(
                 "FROM clause is required by 'replicas' property".to_owned(),
@@ -175,7 +654,7 @@ impl Root {
             }
         };
 
-        let Some(group) = self.get_group(group_id).await? else {
+        let Some(group) = schema.get_group(group_id).await? else {
             return Ok(ExecuteResult::Msg("No such group exists".to_owned()));
         };
 
@@ -191,7 +670,11 @@ impl Root {
         Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
     }
 
-    async fn handle_show_shards(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+    async fn handle_show_shards(
+        &self,
+        schema: &Schema,
+        show_stmt: ShowStatement,
+    ) -> Result<ExecuteResult> {
         let Some(from) = show_stmt.from else {
             return Ok(ExecuteResult::Msg(
                 "FROM clause is required by 'shards' property".to_owned(),
@@ -207,14 +690,23 @@ impl Root {
             }
         };
 
-        let Some(group) = self.get_group(group_id).await? else {
+        let Some(group) = schema.get_group(group_id).await? else {
             return Ok(ExecuteResult::Msg("No such group exists".to_owned()));
         };
 
-        let columns = ["id", "table_id", "start", "end", "size"]
-            .into_iter()
-            .map(ToString::to_string)
-            .collect::<Vec<_>>();
+        let columns = [
+            "id",
+            "table_id",
+            "start",
+            "end",
+            "size",
+            "intents",
+            "oldest_intent_age",
+            "resolved_intents_per_sec",
+        ]
+        .into_iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
 
         let cluster_stats = self.get_cluster_stats();
         let shard_to_row = |shard: ShardDesc| -> Row {
@@ -223,11 +715,17 @@ impl Root {
                 None => (vec![], vec![]),
             };
             let (start, end) = (escape_bytes(&start), escape_bytes(&end));
-            let size = if let Some(shard_stats) = cluster_stats.get_shard_stats(shard.id) {
-                display_size(shard_stats.shard_size)
-            } else {
-                "-".to_owned()
-            };
+            let (size, intents, oldest_intent_age, resolve_rate) =
+                if let Some(shard_stats) = cluster_stats.get_shard_stats(shard.id) {
+                    (
+                        display_size(shard_stats.shard_size),
+                        shard_stats.num_intents.to_string(),
+                        display_age(shard_stats.oldest_intent_age_ms),
+                        format!("{:.1}", shard_stats.resolved_intents_per_sec),
+                    )
+                } else {
+                    ("-".to_owned(), "-".to_owned(), "-".to_owned(), "-".to_owned())
+                };
             Row {
                 values: vec![
                     shard.id.into(),
@@ -235,6 +733,9 @@ impl Root {
                     start.into(),
                     end.into(),
                     size.into(),
+                    intents.into(),
+                    oldest_intent_age.into(),
+                    resolve_rate.into(),
                 ],
             }
         };
@@ -242,39 +743,467 @@ impl Root {
         Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
     }
 
-    async fn handle_show_nodes(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
-        if show_stmt.from.is_some() {
+    async fn handle_show_hotkeys(
+        &self,
+        schema: &Schema,
+        show_stmt: ShowStatement,
+    ) -> Result<ExecuteResult> {
+        let Some(from) = show_stmt.from else {
             return Ok(ExecuteResult::Msg(
-                "FROM clause is not required by 'nodes' property".to_owned(),
+                "FROM clause is required by 'hotkeys' property".to_owned(),
             ));
-        }
+        };
 
-        let nodes = self.list_node().await?;
+        let group_id: u64 = match from.parse() {
+            Ok(group_id) => group_id,
+            Err(_) => {
+                return Ok(ExecuteResult::Msg(
+                    "The value of FROM clause is not a valid u64 numeric".to_owned(),
+                ));
+            }
+        };
 
-        let columns = ["id", "status", "addr", "cpu_nums", "leader_count", "replica_count"]
+        let Some(group) = schema.get_group(group_id).await? else {
+            return Ok(ExecuteResult::Msg("No such group exists".to_owned()));
+        };
+
+        let columns = ["shard_id", "key_prefix", "requests_per_sec", "share"]
             .into_iter()
             .map(ToString::to_string)
             .collect::<Vec<_>>();
 
+        // The hot keys of each shard are sampled by the group leader.
+        let cluster_stats = self.get_cluster_stats();
+        let mut hot_keys = group
+            .shards
+            .iter()
+            .filter_map(|shard| cluster_stats.get_shard_stats(shard.id))
+            .flat_map(|shard_stats| {
+                let shard_id = shard_stats.shard_id;
+                let shard_rate = shard_stats.requests_per_sec;
+                shard_stats.hot_keys.into_iter().map(move |hot_key| (shard_id, shard_rate, hot_key))
+            })
+            .collect::<Vec<_>>();
+        hot_keys.sort_unstable_by(|a, b| b.2.requests_per_sec.total_cmp(&a.2.requests_per_sec));
+        let hot_key_to_row = |(shard_id, shard_rate, hot_key): (u64, f32, HotKeyStats)| -> Row {
+            let share = if shard_rate > 0.0 {
+                format!("{:.1}%", hot_key.requests_per_sec * 100.0 / shard_rate)
+            } else {
+                "-".to_owned()
+            };
+            Row {
+                values: vec![
+                    shard_id.into(),
+                    escape_bytes(&hot_key.key_prefix).into(),
+                    format!("{:.1}", hot_key.requests_per_sec).into(),
+                    share.into(),
+                ],
+            }
+        };
+        let rows = hot_keys.into_iter().map(hot_key_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_show_intents(
+        &self,
+        schema: &Schema,
+        show_stmt: ShowStatement,
+    ) -> Result<ExecuteResult> {
+        const MAX_INTENTS: usize = 10;
+
+        let Some(from) = show_stmt.from else {
+            return Ok(ExecuteResult::Msg(
+                "FROM clause is required by 'intents' property".to_owned(),
+            ));
+        };
+
+        let group_id: u64 = match from.parse() {
+            Ok(group_id) => group_id,
+            Err(_) => {
+                return Ok(ExecuteResult::Msg(
+                    "The value of FROM clause is not a valid u64 numeric".to_owned(),
+                ));
+            }
+        };
+
+        let Some(group) = schema.get_group(group_id).await? else {
+            return Ok(ExecuteResult::Msg("No such group exists".to_owned()));
+        };
+
+        let columns = ["shard_id", "key_prefix", "txn_id", "age"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        // The oldest intents of each shard are reported by the group leader.
+        let cluster_stats = self.get_cluster_stats();
+        let mut intents = group
+            .shards
+            .iter()
+            .filter_map(|shard| cluster_stats.get_shard_stats(shard.id))
+            .flat_map(|shard_stats| {
+                let shard_id = shard_stats.shard_id;
+                shard_stats.oldest_intents.into_iter().map(move |intent| (shard_id, intent))
+            })
+            .collect::<Vec<_>>();
+        intents.sort_unstable_by_key(|(_, intent)| Reverse(intent.age_ms));
+        let intent_to_row = |(shard_id, intent): (u64, IntentStats)| -> Row {
+            Row {
+                values: vec![
+                    shard_id.into(),
+                    escape_bytes(&intent.key_prefix).into(),
+                    intent.start_version.into(),
+                    display_age(intent.age_ms).into(),
+                ],
+            }
+        };
+        let rows = intents.into_iter().take(MAX_INTENTS).map(intent_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_show_nodes(
+        &self,
+        schema: &Schema,
+        show_stmt: ShowStatement,
+    ) -> Result<ExecuteResult> {
+        if show_stmt.from.is_some() {
+            return Ok(ExecuteResult::Msg(
+                "FROM clause is not required by 'nodes' property".to_owned(),
+            ));
+        }
+
+        let nodes = match show_stmt.limit.filter(|limit| *limit > 0) {
+            Some(limit) => {
+                schema.list_node_page(&NodeDescFilter { limit, ..Default::default() }).await?.items
+            }
+            None => schema.list_node().await?,
+        };
+
+        let columns = [
+            "id",
+            "status",
+            "addr",
+            "cpu_nums",
+            "leader_count",
+            "replica_count",
+            "version",
+            "git_hash",
+            "uptime",
+            "features",
+            "data_dir",
+            "data_free",
+            "wal_dir",
+            "wal_free",
+            "voters",
+            "learners",
+            "read_replicas",
+            "cpu_util",
+            "load1",
+            "mem_used",
+            "mem_total",
+            "io_util",
+            "open_proposals",
+            "status_age",
+        ]
+        .into_iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+        // The status is reported by the last heartbeat, it is null if the node
+        // hasn't reported to this root yet.
         let node_to_row = |node: NodeDesc| -> Row {
             let capacity = node.capacity.unwrap_or_default();
             let status = NodeStatus::from_i32(node.status).unwrap_or_default();
+            let mut values: Vec<serde_json::Value> = vec![
+                node.id.into(),
+                status.as_str_name().to_owned().into(),
+                node.addr.into(),
+                (capacity.cpu_nums as u32).into(),
+                capacity.leader_count.into(),
+                capacity.replica_count.into(),
+            ];
+            match self.node_statuses.get(node.id) {
+                Some((runtime, age)) => {
+                    let dir = |kind: &str| runtime.dirs.iter().find(|d| d.kind == kind).cloned();
+                    let data_dir = dir("data").unwrap_or_default();
+                    let wal_dir = dir("wal").unwrap_or_default();
+                    let counts = runtime.replica_counts.unwrap_or_default();
+                    let health = runtime.health.unwrap_or_default();
+                    values.extend([
+                        runtime.version.into(),
+                        runtime.git_hash.into(),
+                        display_age(runtime.uptime_secs.saturating_mul(1000)).into(),
+                        runtime.feature_names.join(",").into(),
+                        data_dir.path.into(),
+                        display_size(data_dir.free_bytes).into(),
+                        wal_dir.path.into(),
+                        display_size(wal_dir.free_bytes).into(),
+                        counts.voters.into(),
+                        counts.learners.into(),
+                        counts.read_replicas.into(),
+                        format!("{:.1}%", health.cpu_util * 100.0).into(),
+                        format!("{:.2}", health.load1).into(),
+                        display_size(health.mem_used).into(),
+                        display_size(health.mem_total).into(),
+                        format!("{:.1}%", health.io_util * 100.0).into(),
+                        health.open_proposals.into(),
+                        display_age(age.as_millis() as u64).into(),
+                    ]);
+                }
+                None => values.resize(columns.len(), serde_json::Value::Null),
+            }
+            Row { values }
+        };
+        let rows = nodes.into_iter().map(node_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_show_migrations(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+        use migration_status::Phase;
+
+        if show_stmt.from.is_some() {
+            return Ok(ExecuteResult::Msg(
+                "FROM clause is not required by 'migrations' property".to_owned(),
+            ));
+        }
+
+        let migrations = self.list_migrations().await?;
+        let columns =
+            ["shard_id", "src_group", "dest_group", "phase", "copied_keys", "copied_size"]
+                .into_iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+        let migration_to_row = |migration: MigrationStatus| -> Row {
+            let desc = migration.desc.unwrap_or_default();
+            let phase = Phase::from_i32(migration.phase).unwrap_or(Phase::Preparing);
             Row {
                 values: vec![
-                    node.id.into(),
-                    status.as_str_name().to_owned().into(),
-                    node.addr.into(),
-                    (capacity.cpu_nums as u32).into(),
-                    capacity.leader_count.into(),
-                    capacity.replica_count.into(),
+                    desc.shard_desc.map(|shard| shard.id).unwrap_or_default().into(),
+                    desc.src_group_id.into(),
+                    desc.dest_group_id.into(),
+                    phase.as_str_name().to_owned().into(),
+                    migration.copied_keys.into(),
+                    display_size(migration.copied_bytes).into(),
                 ],
             }
         };
-        let rows = nodes.into_iter().map(node_to_row).collect::<Vec<_>>();
+        let rows = migrations.into_iter().map(migration_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_show_clones(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+        use clone_status::Phase;
+
+        if show_stmt.from.is_some() {
+            return Ok(ExecuteResult::Msg(
+                "FROM clause is not required by 'clones' property".to_owned(),
+            ));
+        }
+
+        let clones = self.list_clones().await?;
+        let columns = [
+            "src_table",
+            "dest_table",
+            "read_version",
+            "phase",
+            "copied_shards",
+            "copied_keys",
+            "copied_size",
+        ]
+        .into_iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+        let clone_to_row = |clone: CloneStatus| -> Row {
+            let phase = Phase::from_i32(clone.phase).unwrap_or(Phase::Copying);
+            Row {
+                values: vec![
+                    clone.src_table.into(),
+                    clone.dest_table.into(),
+                    clone.read_version.into(),
+                    phase.as_str_name().to_owned().into(),
+                    format!("{}/{}", clone.copied_shards, clone.total_shards).into(),
+                    clone.copied_keys.into(),
+                    display_size(clone.copied_bytes).into(),
+                ],
+            }
+        };
+        let rows = clones.into_iter().map(clone_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_show_recommendations(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+        if show_stmt.from.is_some() {
+            return Ok(ExecuteResult::Msg(
+                "FROM clause is not required by 'recommendations' property".to_owned(),
+            ));
+        }
+
+        let recommendations = self.list_recommendations().await?;
+        let columns = ["id", "status", "action", "created_at"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let recommendation_to_row = |desc: Recommendation| -> Row {
+            Row {
+                values: vec![
+                    desc.id.into(),
+                    recommend::status_name(&desc).to_owned().into(),
+                    desc.describe().into(),
+                    desc.created_at.into(),
+                ],
+            }
+        };
+        let rows = recommendations.into_iter().map(recommendation_to_row).collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_show_txns(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+        if show_stmt.from.is_some() {
+            return Ok(ExecuteResult::Msg(
+                "FROM clause is not required by 'txns' property".to_owned(),
+            ));
+        }
+
+        let resp = self.list_active_txns(show_stmt.limit.unwrap_or_default()).await?;
+        let columns = ["txn_id", "state", "age", "heartbeat_age", "intents"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let txn_to_row = |txn: ActiveTxn| -> Row {
+            let state = TxnState::from_i32(txn.state).unwrap_or_default();
+            Row {
+                values: vec![
+                    txn.txn_id.into(),
+                    state.as_str_name().to_owned().into(),
+                    display_age(txn.age_ms).into(),
+                    display_age(txn.heartbeat_age_ms).into(),
+                    txn.num_intents.into(),
+                ],
+            }
+        };
+        // The txns of the unavailable txn shards are missed, they are warned in the
+        // trailing rows instead of failing the statement.
+        let warning_to_row = |warning: String| -> Row {
+            Row {
+                values: vec![
+                    "-".to_owned().into(),
+                    "WARNING".to_owned().into(),
+                    warning.into(),
+                    "-".to_owned().into(),
+                    "-".to_owned().into(),
+                ],
+            }
+        };
+        let rows = resp
+            .txns
+            .into_iter()
+            .map(txn_to_row)
+            .chain(resp.warnings.into_iter().map(warning_to_row))
+            .collect::<Vec<_>>();
+        Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
+    }
+
+    async fn handle_show_log_filters(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+        let node_id = match show_stmt.from.as_ref().map(|v| v.parse::<u64>()) {
+            None => None,
+            Some(Ok(node_id)) => Some(node_id),
+            Some(Err(_)) => {
+                return Ok(ExecuteResult::Msg(
+                    "The value of FROM clause is not a valid u64 numeric".to_owned(),
+                ))
+            }
+        };
+        match self.config_log_filter(node_id, SetLogFilterRequest::default()).await {
+            Ok(resp) => Ok(log_filters_to_result(resp.filters)),
+            Err(Error::InvalidArgument(msg)) => Ok(ExecuteResult::Msg(msg)),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn handle_show_scheduler(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+        if show_stmt.from.is_some() {
+            return Ok(ExecuteResult::Msg(
+                "FROM clause is not required by 'scheduler' property".to_owned(),
+            ));
+        }
+
+        let mut passes = self.list_schedule_passes()?;
+        if let Some(limit) = show_stmt.limit.filter(|limit| *limit != 0) {
+            passes.truncate(limit as usize);
+        }
+        Ok(schedule_passes_to_result(passes))
+    }
+
+    fn handle_show_alerts(&self, show_stmt: ShowStatement) -> Result<ExecuteResult> {
+        if show_stmt.from.is_some() {
+            return Ok(ExecuteResult::Msg(
+                "FROM clause is not required by 'alerts' property".to_owned(),
+            ));
+        }
+
+        let now = timestamp_millis();
+        let columns =
+            ["kind", "age", "message"].into_iter().map(ToString::to_string).collect::<Vec<_>>();
+        let alert_to_row = |(alert, raised_at): (HealthAlert, u64)| -> Row {
+            Row {
+                values: vec![
+                    alert.kind().to_owned().into(),
+                    display_age(now.saturating_sub(raised_at)).into(),
+                    alert.to_string().into(),
+                ],
+            }
+        };
+        let rows = self.health.alerts().into_iter().map(alert_to_row).collect::<Vec<_>>();
         Ok(ExecuteResult::Data(ColumnResult { columns, rows }))
     }
 }
 
+/// Show a row for each decision of the passes, the passes without decisions
+/// are shown as well.
+fn schedule_passes_to_result(passes: Vec<SchedulePass>) -> ExecuteResult {
+    let now = timestamp_millis();
+    let columns =
+        ["pass", "trigger", "age", "duration_ms", "groups", "action", "outcome", "reason"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+    let mut rows = Vec::new();
+    for pass in passes {
+        let mut decisions = pass
+            .decisions
+            .into_iter()
+            .map(|d| {
+                let outcome = schedule_decision::Outcome::from_i32(d.outcome)
+                    .map(outcome_name)
+                    .unwrap_or("unknown");
+                (d.action, outcome, d.reason)
+            })
+            .collect::<Vec<_>>();
+        if !pass.error.is_empty() {
+            decisions.push(("-".to_owned(), "failed", pass.error));
+        } else if decisions.is_empty() {
+            decisions.push(("-".to_owned(), "idle", "nothing to do".to_owned()));
+        }
+        let trigger = if pass.manual { "manual" } else { "timer" };
+        let age = display_age(now.saturating_sub(pass.started_at));
+        for (action, outcome, reason) in decisions {
+            rows.push(Row {
+                values: vec![
+                    pass.seq.into(),
+                    trigger.to_owned().into(),
+                    age.clone().into(),
+                    pass.duration_ms.into(),
+                    pass.groups_examined.into(),
+                    action.into(),
+                    outcome.to_owned().into(),
+                    reason.into(),
+                ],
+            });
+        }
+    }
+    ExecuteResult::Data(ColumnResult { columns, rows })
+}
+
 /// Convert bytes size into readable unit.
 fn display_size(size: u64) -> String {
     const KB: u64 = 1024;
@@ -287,3 +1216,32 @@ fn display_size(size: u64) -> String {
         _ => format!("{}GB", size / GB),
     }
 }
+
+/// The effective log filters of the nodes, the nodes failed are shown with the
+/// reason instead.
+fn log_filters_to_result(filters: Vec<NodeLogFilter>) -> ExecuteResult {
+    let columns = ["node", "filter"].into_iter().map(ToString::to_string).collect();
+    let filter_to_row = |filter: NodeLogFilter| -> Row {
+        let value = if filter.error.is_empty() {
+            filter.filter
+        } else {
+            format!("ERROR: {}", filter.error)
+        };
+        Row { values: vec![filter.node_id.into(), value.into()] }
+    };
+    let rows = filters.into_iter().map(filter_to_row).collect::<Vec<_>>();
+    ExecuteResult::Data(ColumnResult { columns, rows })
+}
+
+/// Convert milliseconds into readable unit.
+fn display_age(age_ms: u64) -> String {
+    const SECOND: u64 = 1000;
+    const MINUTE: u64 = 60 * SECOND;
+    const HOUR: u64 = 60 * MINUTE;
+    match age_ms {
+        0..SECOND => format!("{age_ms}ms"),
+        SECOND..MINUTE => format!("{}s", age_ms / SECOND),
+        MINUTE..HOUR => format!("{}m", age_ms / MINUTE),
+        _ => format!("{}h", age_ms / HOUR),
+    }
+}
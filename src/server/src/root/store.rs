@@ -18,6 +18,7 @@ use std::sync::Arc;
 use sekas_api::server::v1::group_request_union::Request::{self, *};
 use sekas_api::server::v1::*;
 
+use super::page::{RowScanner, ScannedRows};
 use crate::constants::ROOT_GROUP_ID;
 use crate::replica::Replica;
 use crate::{Error, Result};
@@ -36,6 +37,11 @@ impl RootStore {
         Ok(())
     }
 
+    /// Write the shards of the root group atomically.
+    pub async fn batch_write_shards(&self, writes: Vec<ShardWriteRequest>) -> Result<()> {
+        self.replica.batch_write_shards(&writes).await
+    }
+
     pub async fn put(&self, shard_id: u64, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
         let write = ShardWriteRequest {
             shard_id,
@@ -56,6 +62,9 @@ impl RootStore {
             shard_id,
             start_version: sekas_schema::system::txn::TXN_MAX_VERSION,
             user_key: user_key.to_owned(),
+            causal_token: 0,
+            txn_id: 0,
+            max_staleness_ms: 0,
         };
         let resp = self.submit_request(Request::Get(get)).await?;
         let resp = resp
@@ -106,6 +115,62 @@ impl RootStore {
         }
     }
 
+    /// Read at most `limit` rows of the shard with the prefix, after the key
+    /// `start_after` if it is specified.
+    pub async fn scan(
+        &self,
+        shard_id: u64,
+        prefix: &[u8],
+        start_after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<ScannedRows> {
+        let mut req = ShardScanRequest {
+            shard_id,
+            start_version: sekas_schema::system::txn::TXN_MAX_VERSION,
+            limit: limit as u64,
+            ..Default::default()
+        };
+        match start_after {
+            None => req.prefix = Some(prefix.to_owned()),
+            Some(start_after) => {
+                // The prefix scan can't be resumed, scan the range of the prefix instead.
+                req.start_key = Some(start_after.to_owned());
+                req.exclude_start_key = true;
+                let end_key = sekas_rock::lexical::lexical_next_boundary(prefix);
+                if !end_key.is_empty() {
+                    req.end_key = Some(end_key);
+                    req.exclude_end_key = true;
+                }
+            }
+        }
+        let resp = self.submit_request(Scan(req)).await?;
+        let resp = resp
+            .response
+            .ok_or_else(|| Error::InvalidArgument("ScanResponse".into()))?
+            .response
+            .ok_or_else(|| Error::InvalidArgument("ScanUnionResponse".into()))?;
+        let group_response_union::Response::Scan(resp) = resp else {
+            return Err(Error::InvalidArgument("ScanResponse".into()));
+        };
+        let resume_key =
+            if resp.has_more { resp.data.last().map(|v| v.user_key.clone()) } else { None };
+        let rows = resp
+            .data
+            .into_iter()
+            .filter_map(|v| {
+                let value = v.values.last().and_then(|v| v.content.clone())?;
+                Some((v.user_key, value))
+            })
+            .collect();
+        Ok(ScannedRows { rows, resume_key })
+    }
+
+    /// Wait until the local root replica has applied all entries committed
+    /// before this call, by a read index of the root group.
+    pub async fn read_index(&self) -> Result<()> {
+        self.replica.check_lease().await
+    }
+
     async fn submit_request(&self, req: Request) -> Result<GroupResponse> {
         use crate::replica::retry::execute;
         use crate::replica::ExecCtx;
@@ -114,8 +179,130 @@ impl RootStore {
             group_id: ROOT_GROUP_ID,
             epoch: self.replica.epoch(),
             request: Some(GroupRequestUnion { request: Some(req) }),
+            ..Default::default()
         };
 
         execute(&self.replica, &ExecCtx::default(), &request).await
     }
 }
+
+#[crate::async_trait]
+impl RowScanner for RootStore {
+    async fn scan_rows(
+        &self,
+        shard_id: u64,
+        prefix: &[u8],
+        start_after: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<ScannedRows> {
+        self.scan(shard_id, prefix, start_after, limit).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use sekas_rock::fn_name;
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::constants::INITIAL_EPOCH;
+    use crate::engine::Engines;
+    use crate::node::Node;
+    use crate::root::page::read_page;
+    use crate::serverpb::v1::NodeIdent;
+    use crate::transport::TransportManager;
+    use crate::{Config, NodeConfig, RaftConfig, ReplicaConfig};
+
+    const SHARD_ID: u64 = 1;
+
+    async fn create_store(root_dir: &std::path::Path) -> (Node, RootStore) {
+        // There is no root to verify the descriptors with.
+        let replica = ReplicaConfig { verify_descriptor_timeout_ms: 10, ..Default::default() };
+        let config = Config {
+            root_dir: root_dir.to_owned(),
+            node: NodeConfig { replica, ..Default::default() },
+            raft: RaftConfig { tick_interval_ms: 10, ..Default::default() },
+            ..Default::default()
+        };
+        let engines = Engines::open(&config.root_dir, &config.db).unwrap();
+        let transport_manager = TransportManager::new(vec![], engines.state(), None).await.unwrap();
+        let node = Node::new(config, engines, transport_manager).await.unwrap();
+        node.bootstrap(&NodeIdent { cluster_id: vec![], node_id: 1 }).await.unwrap();
+
+        let group = GroupDesc {
+            id: ROOT_GROUP_ID,
+            epoch: INITIAL_EPOCH,
+            shards: vec![ShardDesc::whole(SHARD_ID, 1)],
+            replicas: vec![ReplicaDesc { id: 1, node_id: 1, role: ReplicaRole::Voter.into() }],
+        };
+        node.create_replica(1, group).await.unwrap();
+        let replica = node.replica_table().find(ROOT_GROUP_ID).unwrap();
+        assert!(replica.on_leader(fn_name!(), false).await.unwrap().is_some());
+        (node, RootStore::new(replica))
+    }
+
+    fn keys(scanned: &ScannedRows) -> Vec<Vec<u8>> {
+        scanned.rows.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    #[sekas_macro::test]
+    async fn scan_rows_of_prefix() {
+        let dir = TempDir::new(fn_name!()).unwrap();
+        let (_node, store) = create_store(dir.path()).await;
+        let mut batch = ShardWriteRequest { shard_id: SHARD_ID, ..Default::default() };
+        let mut put = |key: Vec<u8>| {
+            batch.puts.push(PutRequest { key: key.clone(), value: key, ..Default::default() })
+        };
+        for i in 1..=9 {
+            put(format!("a-{i}").into_bytes());
+        }
+        for i in 1..=3 {
+            put(format!("b-{i}").into_bytes());
+        }
+        put(vec![0xFF, 1]);
+        put(vec![0xFF, 2]);
+        store.batch_write(batch).await.unwrap();
+        store.delete(SHARD_ID, b"a-5").await.unwrap();
+
+        let scanned = store.scan(SHARD_ID, b"a-", None, 100).await.unwrap();
+        assert_eq!(scanned.rows.len(), 8);
+        assert!(scanned.resume_key.is_none());
+        assert!(scanned.rows.iter().all(|(key, value)| key == value && key != b"a-5"));
+
+        // The start key is excluded, and the rows of the next prefix are not read.
+        let scanned = store.scan(SHARD_ID, b"a-", None, 3).await.unwrap();
+        assert_eq!(keys(&scanned), vec![b"a-1".to_vec(), b"a-2".to_vec(), b"a-3".to_vec()]);
+        assert_eq!(scanned.resume_key.as_deref(), Some(b"a-3".as_slice()));
+        let scanned = store.scan(SHARD_ID, b"a-", Some(b"a-3".as_slice()), 3).await.unwrap();
+        assert_eq!(keys(&scanned), vec![b"a-4".to_vec(), b"a-6".to_vec(), b"a-7".to_vec()]);
+        let scanned = store.scan(SHARD_ID, b"a-", Some(b"a-7".as_slice()), 3).await.unwrap();
+        assert_eq!(keys(&scanned), vec![b"a-8".to_vec(), b"a-9".to_vec()]);
+        assert!(scanned.resume_key.is_none());
+
+        // The prefix has no upper boundary.
+        let scanned = store.scan(SHARD_ID, &[0xFF], Some([0xFF, 1].as_slice()), 3).await.unwrap();
+        assert_eq!(keys(&scanned), vec![vec![0xFF, 2]]);
+        assert!(scanned.resume_key.is_none());
+
+        // The pages cover every row exactly once.
+        let mut page_token = vec![];
+        let mut seen = HashSet::new();
+        loop {
+            let page =
+                read_page(&store, SHARD_ID, &[], &page_token, 2, |val| Ok(Some(val.to_vec())))
+                    .await
+                    .unwrap();
+            assert!(page.items.len() <= 2);
+            for item in page.items {
+                assert!(seen.insert(item));
+            }
+            if !page.has_more() {
+                break;
+            }
+            page_token = page.next_page_token;
+        }
+        assert_eq!(seen.len(), 13);
+    }
+}
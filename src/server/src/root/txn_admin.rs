@@ -0,0 +1,136 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The admin requests to inspect and abort the txns.
+//!
+//! The txn records are read from the leaders of the txn shards, and the
+//! intents of them are approximated by the oldest intents reported by the
+//! group leaders, so the listing is cheap but not exact.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use log::{info, warn};
+use sekas_api::server::v1::*;
+use sekas_client::{ClientOptions, TxnStateTable};
+use sekas_runtime::time::timestamp_millis;
+use sekas_schema::system::table;
+
+use super::Root;
+use crate::{Error, Result};
+
+/// The timeout of listing the txn records of a txn shard.
+const LIST_TXN_TIMEOUT: Duration = Duration::from_secs(3);
+
+impl Root {
+    /// List the running txns in descending order of age, at most `limit` txns
+    /// are returned if it is not zero.
+    ///
+    /// The txn shards failed to list are returned as warnings, instead of
+    /// failing the whole listing.
+    pub async fn list_active_txns(&self, limit: u64) -> Result<ListActiveTxnsResponse> {
+        let schema = self.schema()?;
+        let filter = GroupFilter { table_id: Some(table::txn_table_id()), ..Default::default() };
+        let groups = schema.list_group_with(&filter).await?;
+
+        let txn_table = TxnStateTable::new(
+            self.shared.transport_manager.build_client(ClientOptions::default()),
+            Some(LIST_TXN_TIMEOUT),
+        );
+        let txn_shards = groups.iter().flat_map(|group| {
+            group
+                .shards
+                .iter()
+                .filter(|shard| shard.table_id == table::txn_table_id())
+                .map(move |shard| (group.id, shard.id))
+        });
+        let listings =
+            futures::future::join_all(
+                txn_shards.map(|(group_id, shard_id)| {
+                    let txn_table = &txn_table;
+                    async move {
+                        (group_id, shard_id, txn_table.list_txn_records(group_id, shard_id).await)
+                    }
+                }),
+            )
+            .await;
+
+        // The intents are counted by the owner txn.
+        let cluster_stats = self.get_cluster_stats();
+        let mut intents: HashMap<u64, (u64, u64)> = HashMap::default();
+        for shard in groups.iter().flat_map(|group| group.shards.iter()) {
+            let Some(shard_stats) = cluster_stats.get_shard_stats(shard.id) else { continue };
+            for intent in shard_stats.oldest_intents {
+                let entry = intents.entry(intent.start_version).or_default();
+                entry.0 += 1;
+                entry.1 = entry.1.max(intent.age_ms);
+            }
+        }
+
+        let now = timestamp_millis();
+        let mut resp = ListActiveTxnsResponse::default();
+        for (group_id, shard_id, listing) in listings {
+            let records = match listing {
+                Ok(records) => records,
+                Err(err) => {
+                    warn!("list txn records of group {group_id} shard {shard_id}: {err}");
+                    resp.warnings.push(format!(
+                        "txn shard {shard_id} of group {group_id} is unavailable: {err}"
+                    ));
+                    continue;
+                }
+            };
+            for record in records.into_iter().filter(|r| r.state == TxnState::Running) {
+                let heartbeat_age_ms = now.saturating_sub(record.heartbeat);
+                let (num_intents, intent_age_ms) =
+                    intents.get(&record.start_version).cloned().unwrap_or_default();
+                resp.txns.push(ActiveTxn {
+                    txn_id: record.start_version,
+                    age_ms: intent_age_ms.max(heartbeat_age_ms),
+                    heartbeat_age_ms,
+                    state: record.state.into(),
+                    num_intents,
+                });
+            }
+        }
+        resp.txns.sort_unstable_by_key(|txn| (Reverse(txn.age_ms), txn.txn_id));
+        if limit > 0 {
+            resp.txns.truncate(limit as usize);
+        }
+        Ok(resp)
+    }
+
+    /// Abort the running txn, the intents of it are resolved by the readers
+    /// and the background resolver. The abortion is recorded into the topology
+    /// event log.
+    pub async fn kill_txn(&self, txn_id: u64) -> Result<()> {
+        let schema = self.schema()?;
+        let txn_table = TxnStateTable::new(
+            self.shared.transport_manager.build_client(ClientOptions::default()),
+            Some(LIST_TXN_TIMEOUT),
+        );
+        match txn_table.abort_txn(txn_id).await {
+            Ok(()) => {}
+            Err(sekas_client::Error::NotFound(_)) => {
+                return Err(Error::InvalidArgument(format!("txn {txn_id} is not exists")));
+            }
+            Err(err) => return Err(err.into()),
+        }
+        info!("txn {txn_id} is killed");
+        let target = format!("txn/{txn_id}");
+        let detail = format!("txn {txn_id} is aborted by the administrator");
+        self.record_topology_event(&schema, topology_event::Kind::KillTxn, target, detail).await
+    }
+}
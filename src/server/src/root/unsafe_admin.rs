@@ -0,0 +1,361 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The unsafe admin requests to read and edit the descriptors in catalog.
+//!
+//! They are the last resort to recover a cluster from the states the
+//! scheduler can't handle, e.g. an epoch which is out of order. The edits are
+//! refused unless the root is started with `--enable-unsafe-admin` and the
+//! request carries the confirmation token issued by the get of the target.
+//! The token is bound to the bytes and epoch of the stored descriptor, so the
+//! edit is refused if the descriptor is changed since it is read. Every edit
+//! is recorded into the topology event log in the same batch.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use log::warn;
+use prost::Message;
+use sekas_api::server::v1::watch_response::*;
+use sekas_api::server::v1::*;
+use sekas_runtime::time::{timestamp_millis, Instant};
+
+use super::{HeartbeatTask, Root, Schema};
+use crate::{Error, Result};
+
+/// The confirmation tokens expire after it, the target should be read again.
+const CONFIRM_TOKEN_TTL: Duration = Duration::from_secs(300);
+
+/// The confirmation tokens issued by the gets of the raw descriptors, by
+/// target. Only the latest token of a target is valid, and it is consumed by
+/// the put.
+#[derive(Default)]
+pub(super) struct ConfirmTokens {
+    tokens: HashMap<String, ConfirmToken>,
+}
+
+struct ConfirmToken {
+    nonce: String,
+    /// The bytes of the stored descriptor when the token is issued.
+    raw: Vec<u8>,
+    /// The epoch of the stored group descriptor, `0` for the nodes.
+    epoch: u64,
+    issued_at: Instant,
+}
+
+impl ConfirmTokens {
+    fn issue(&mut self, target: String, raw: Vec<u8>, epoch: u64) -> String {
+        let now = Instant::now();
+        self.tokens
+            .retain(|_, token| now.saturating_duration_since(token.issued_at) < CONFIRM_TOKEN_TTL);
+        let nonce = uuid::Uuid::new_v4().simple().to_string();
+        self.tokens
+            .insert(target, ConfirmToken { nonce: nonce.clone(), raw, epoch, issued_at: now });
+        nonce
+    }
+
+    /// Consume the token of the target, returns the bytes and epoch of the
+    /// stored descriptor it is bound to.
+    fn consume(&mut self, target: &str, nonce: &str) -> Result<(Vec<u8>, u64)> {
+        match self.tokens.get(target) {
+            Some(token)
+                if !nonce.is_empty()
+                    && token.nonce == nonce
+                    && token.issued_at.elapsed() < CONFIRM_TOKEN_TTL =>
+            {
+                let token = self.tokens.remove(target).unwrap();
+                Ok((token.raw, token.epoch))
+            }
+            _ => Err(Error::PermissionDenied(format!(
+                "the confirmation token of {target} is invalid or expired, get the descriptor again"
+            ))),
+        }
+    }
+}
+
+impl Root {
+    /// Get the group descriptor stored in catalog, the bytes of it and the
+    /// confirmation token to put it.
+    pub async fn get_raw_group_desc(
+        &self,
+        group_id: u64,
+    ) -> Result<(Vec<u8>, Option<GroupDesc>, String)> {
+        let Some(raw) = self.schema()?.get_group_raw(group_id).await? else {
+            return Ok((Vec::default(), None, String::default()));
+        };
+        let desc = decode_group(group_id, &raw)?;
+        let target = format!("group/{group_id}");
+        let token = self.issue_confirm_token(target, raw.clone(), desc.epoch).await;
+        Ok((raw, Some(desc), token))
+    }
+
+    /// Replace the group descriptor stored in catalog if the stored epoch is
+    /// `expected_epoch`. The epoch of the descriptor must not be lower than it.
+    pub async fn put_raw_group_desc(
+        &self,
+        desc: GroupDesc,
+        expected_epoch: u64,
+        confirm_token: &str,
+    ) -> Result<()> {
+        let target = format!("group/{}", desc.id);
+        let schema = self.schema()?;
+        let mut tokens = self.check_unsafe_admin().await?;
+        let (raw, _) = consume_group_token(&mut tokens, &target, confirm_token, expected_epoch)?;
+        let stored = check_stored_group(&schema, desc.id, &raw).await?;
+        if desc.epoch < stored.epoch {
+            return Err(Error::InvalidArgument(format!(
+                "the epoch {} of group {} is lower than the stored {}",
+                desc.epoch, desc.id, stored.epoch
+            )));
+        }
+
+        let detail = format!("replace {stored:?} with {desc:?}");
+        self.apply_raw_group_desc(&schema, &stored, raw, desc, target, detail).await
+    }
+
+    /// Get the shard of the group descriptor stored in catalog, the bytes of
+    /// it, the epoch of the group and the confirmation token to put it.
+    pub async fn get_raw_shard_desc(
+        &self,
+        group_id: u64,
+        shard_id: u64,
+    ) -> Result<(Vec<u8>, Option<ShardDesc>, u64, String)> {
+        let Some(group_raw) = self.schema()?.get_group_raw(group_id).await? else {
+            return Ok((Vec::default(), None, 0, String::default()));
+        };
+        let group = decode_group(group_id, &group_raw)?;
+        let shard = group.shards.into_iter().find(|shard| shard.id == shard_id);
+        let raw = shard.as_ref().map(|shard| shard.encode_to_vec()).unwrap_or_default();
+        // The shard is put by replacing the group descriptor, so the token is bound to
+        // the group descriptor.
+        let target = format!("shard/{group_id}/{shard_id}");
+        let token = self.issue_confirm_token(target, group_raw, group.epoch).await;
+        Ok((raw, shard, group.epoch, token))
+    }
+
+    /// Replace the shard of the group descriptor stored in catalog, or add it
+    /// if not exists, if the stored epoch of the group is `expected_epoch`.
+    pub async fn put_raw_shard_desc(
+        &self,
+        group_id: u64,
+        shard: ShardDesc,
+        expected_epoch: u64,
+        confirm_token: &str,
+    ) -> Result<()> {
+        let target = format!("shard/{group_id}/{}", shard.id);
+        let schema = self.schema()?;
+        let mut tokens = self.check_unsafe_admin().await?;
+        let (raw, _) = consume_group_token(&mut tokens, &target, confirm_token, expected_epoch)?;
+        let stored = check_stored_group(&schema, group_id, &raw).await?;
+
+        let mut desc = stored.clone();
+        let detail = match desc.shards.iter_mut().find(|s| s.id == shard.id) {
+            Some(exist) => {
+                let detail = format!("replace {exist:?} with {shard:?}");
+                *exist = shard;
+                detail
+            }
+            None => {
+                let detail = format!("add {shard:?}");
+                desc.shards.push(shard);
+                detail
+            }
+        };
+        self.apply_raw_group_desc(&schema, &stored, raw, desc, target, detail).await
+    }
+
+    /// Get the node descriptor stored in catalog, the bytes of it and the
+    /// confirmation token to put it.
+    pub async fn get_raw_node_desc(
+        &self,
+        node_id: u64,
+    ) -> Result<(Vec<u8>, Option<NodeDesc>, String)> {
+        let Some(raw) = self.schema()?.get_node_raw(node_id).await? else {
+            return Ok((Vec::default(), None, String::default()));
+        };
+        let desc = NodeDesc::decode(&*raw)
+            .map_err(|_| Error::InvalidData(format!("node desc: {node_id}")))?;
+        let token = self.issue_confirm_token(format!("node/{node_id}"), raw.clone(), 0).await;
+        Ok((raw, Some(desc), token))
+    }
+
+    /// Replace the node descriptor stored in catalog if the stored bytes are
+    /// `expected_raw`.
+    pub async fn put_raw_node_desc(
+        &self,
+        desc: NodeDesc,
+        expected_raw: &[u8],
+        confirm_token: &str,
+    ) -> Result<()> {
+        let target = format!("node/{}", desc.id);
+        let schema = self.schema()?;
+        let mut tokens = self.check_unsafe_admin().await?;
+        let (raw, _) = tokens.consume(&target, confirm_token)?;
+        if raw != expected_raw {
+            return Err(Error::InvalidArgument(format!(
+                "the confirmation token of {target} is not issued for the expected bytes"
+            )));
+        }
+        let Some(stored) = schema.get_node(desc.id).await? else {
+            return Err(Error::InvalidArgument(format!("node {} is not exists", desc.id)));
+        };
+
+        warn!("unsafe admin replace the descriptor of node {} with {desc:?}", desc.id);
+        let detail = format!("replace {stored:?} with {desc:?}");
+        let event = new_topology_event(topology_event::Kind::UnsafeEdit, target, detail);
+        match schema.update_node_with_event_if(desc.clone(), raw, event).await {
+            Ok(_) => {}
+            Err(Error::CasFailed(..)) => {
+                return Err(Error::InvalidArgument(format!(
+                    "node {} is changed, the stored descriptor is {stored:?}",
+                    desc.id
+                )));
+            }
+            Err(err) => return Err(err),
+        }
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent { event: Some(update_event::Event::Node(desc)) }])
+            .await;
+        Ok(())
+    }
+
+    /// List the topology events, in the order of id.
+    pub async fn list_topology_events(&self) -> Result<Vec<TopologyEvent>> {
+        self.schema()?.list_topology_event().await
+    }
+
+    pub(super) async fn record_topology_event(
+        &self,
+        schema: &Schema,
+        kind: topology_event::Kind,
+        target: String,
+        detail: String,
+    ) -> Result<()> {
+        schema.append_topology_event(new_topology_event(kind, target, detail)).await?;
+        Ok(())
+    }
+
+    /// Issue the confirmation token of the target, it is only issued if the
+    /// unsafe admin requests are enabled.
+    async fn issue_confirm_token(&self, target: String, raw: Vec<u8>, epoch: u64) -> String {
+        if !self.cfg.enable_unsafe_admin {
+            return String::default();
+        }
+        self.unsafe_admin.lock().await.issue(target, raw, epoch)
+    }
+
+    /// Check the unsafe admin requests are allowed, the returned guard
+    /// serializes them.
+    async fn check_unsafe_admin(&self) -> Result<futures::lock::MutexGuard<'_, ConfirmTokens>> {
+        if !self.cfg.enable_unsafe_admin {
+            return Err(Error::PermissionDenied(
+                "the unsafe admin requests are disabled, start the root with --enable-unsafe-admin"
+                    .to_owned(),
+            ));
+        }
+        self.check_catalog_writable()?;
+        Ok(self.unsafe_admin.lock().await)
+    }
+
+    /// Save the group descriptor with the topology event of the edit, if the
+    /// stored bytes are still `raw`. The nodes of the replicas are asked to
+    /// heartbeat, so the scheduler reacts to the edit soon.
+    async fn apply_raw_group_desc(
+        &self,
+        schema: &Schema,
+        stored: &GroupDesc,
+        raw: Vec<u8>,
+        desc: GroupDesc,
+        target: String,
+        detail: String,
+    ) -> Result<()> {
+        warn!("unsafe admin replace the descriptor of group {} with {desc:?}", desc.id);
+        let event = new_topology_event(topology_event::Kind::UnsafeEdit, target, detail);
+        match schema.update_group_with_event_if(desc.clone(), raw, event).await {
+            Ok(_) => {}
+            Err(Error::CasFailed(..)) => {
+                return Err(Error::InvalidArgument(format!(
+                    "group {} is changed since the confirmation token is issued",
+                    desc.id
+                )));
+            }
+            Err(err) => return Err(err),
+        }
+        let tasks = stored
+            .replicas
+            .iter()
+            .chain(desc.replicas.iter())
+            .map(|replica| HeartbeatTask { node_id: replica.node_id })
+            .collect::<Vec<_>>();
+        self.heartbeat_queue.try_schedule(tasks, Instant::now()).await;
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent { event: Some(update_event::Event::Group(desc)) }])
+            .await;
+        Ok(())
+    }
+}
+
+/// Build the topology event happened now, its id is allocated once it is
+/// appended.
+pub(super) fn new_topology_event(
+    kind: topology_event::Kind,
+    target: String,
+    detail: String,
+) -> TopologyEvent {
+    TopologyEvent {
+        timestamp: timestamp_millis(),
+        kind: kind.into(),
+        target,
+        detail,
+        ..Default::default()
+    }
+}
+
+/// Consume the confirmation token of the group target, and check the epoch it
+/// is bound to.
+fn consume_group_token(
+    tokens: &mut ConfirmTokens,
+    target: &str,
+    confirm_token: &str,
+    expected_epoch: u64,
+) -> Result<(Vec<u8>, u64)> {
+    let (raw, epoch) = tokens.consume(target, confirm_token)?;
+    if epoch != expected_epoch {
+        return Err(Error::InvalidArgument(format!(
+            "the epoch of {target} is {epoch} when the confirmation token is issued, but \
+             {expected_epoch} is expected"
+        )));
+    }
+    Ok((raw, epoch))
+}
+
+/// Get the stored descriptor of the group, and check it is not changed since
+/// the bytes `raw` are read.
+async fn check_stored_group(schema: &Schema, group_id: u64, raw: &[u8]) -> Result<GroupDesc> {
+    let Some(stored_raw) = schema.get_group_raw(group_id).await? else {
+        return Err(Error::InvalidArgument(format!("group {group_id} is not exists")));
+    };
+    let stored = decode_group(group_id, &stored_raw)?;
+    if stored_raw != raw {
+        return Err(Error::InvalidArgument(format!(
+            "group {group_id} is changed since the confirmation token is issued, the stored \
+             descriptor is {stored:?}"
+        )));
+    }
+    Ok(stored)
+}
+
+fn decode_group(group_id: u64, raw: &[u8]) -> Result<GroupDesc> {
+    GroupDesc::decode(raw).map_err(|_| Error::InvalidData(format!("group desc: {group_id}")))
+}
@@ -18,31 +18,54 @@ use std::task::{Poll, Waker};
 use std::vec;
 
 use futures::Stream;
-use sekas_api::server::v1::watch_response::{DeleteEvent, UpdateEvent};
-use sekas_api::server::v1::WatchResponse;
+use sekas_api::server::v1::watch_response::{delete_event, update_event, DeleteEvent, UpdateEvent};
+use sekas_api::server::v1::{GroupDesc, GroupDescDelta, WatchResponse};
 use tokio::sync::{RwLock, RwLockWriteGuard};
 
+use super::mirror::CatalogJournal;
 use crate::{Error, Result};
 
 #[derive(Default)]
 pub struct WatchHub {
     inner: Arc<RwLock<WatchHubInner>>,
+    journal: Arc<CatalogJournal>,
 }
 
 #[derive(Default)]
 pub struct WatchHubInner {
     next_watcher_id: u64,
     watchers: HashMap<u64, Watcher>,
+    /// The last group descriptors notified, the following descriptors are sent
+    /// as the deltas based on them.
+    group_descs: HashMap<u64, GroupDesc>,
 }
 
 pub struct WatcherInitializer<'a> {
-    _guard: RwLockWriteGuard<'a, WatchHubInner>,
+    guard: RwLockWriteGuard<'a, WatchHubInner>,
     watcher_inner: Arc<Mutex<WatcherInner>>,
 }
 
 impl<'a> WatcherInitializer<'a> {
-    pub fn set_init_resp(&mut self, updates: Vec<UpdateEvent>, deletes: Vec<DeleteEvent>) {
+    /// Set the initial response of the watcher, `cur_groups` are the epochs of
+    /// the group descriptors held by the watcher already.
+    pub fn set_init_resp(
+        &mut self,
+        cur_groups: HashMap<u64, u64>,
+        updates: Vec<UpdateEvent>,
+        deletes: Vec<DeleteEvent>,
+    ) {
         let mut inner = self.watcher_inner.lock().unwrap();
+        inner.group_epochs = cur_groups;
+        for update in &updates {
+            if let Some(update_event::Event::Group(desc)) = &update.event {
+                inner.group_epochs.insert(desc.id, desc.epoch);
+                let group_descs = &mut self.guard.group_descs;
+                if group_descs.get(&desc.id).map_or(true, |d| d.epoch < desc.epoch) {
+                    group_descs.insert(desc.id, desc.clone());
+                }
+            }
+        }
+        forget_deleted_groups(&mut inner.group_epochs, &deletes);
         inner.updates.extend_from_slice(&updates);
         inner.deletes.extend_from_slice(&deletes);
     }
@@ -56,7 +79,13 @@ impl WatchHub {
         let watcher = Watcher { id: inner.next_watcher_id, inner: watcher_inner.to_owned() };
         inner.watchers.insert(watcher.id, watcher.to_owned());
         super::metrics::WATCH_TABLE_SIZE.set(inner.watchers.len() as i64);
-        (watcher, WatcherInitializer { _guard: inner, watcher_inner })
+        (watcher, WatcherInitializer { guard: inner, watcher_inner })
+    }
+
+    /// The journal of the catalog events, which are replicated to the catalog
+    /// mirrors.
+    pub fn journal(&self) -> Arc<CatalogJournal> {
+        self.journal.clone()
     }
 
     pub async fn remove_watcher(&self, id: u64) {
@@ -83,9 +112,12 @@ impl WatchHub {
         deletes: Vec<DeleteEvent>,
         _err: Option<Error>,
     ) {
-        let inner = self.inner.read().await;
+        self.journal.append(&updates, &deletes);
+        let mut inner = self.inner.write().await;
+        let deltas = inner.encode_group_deltas(&updates);
+        forget_deleted_groups(&mut inner.group_descs, &deletes);
         for w in inner.watchers.values() {
-            w.notify(&updates, &deletes, None) // TODO: clonable error
+            w.notify(&updates, &deltas, &deletes, None) // TODO: clonable error
         }
     }
 
@@ -96,6 +128,36 @@ impl WatchHub {
     }
 }
 
+impl WatchHubInner {
+    /// Encode the deltas of the group descriptors based on the last notified
+    /// ones, in the order of the updates.
+    fn encode_group_deltas(&mut self, updates: &[UpdateEvent]) -> Vec<Option<GroupDescDelta>> {
+        let mut deltas = Vec::with_capacity(updates.len());
+        for update in updates {
+            let Some(update_event::Event::Group(desc)) = &update.event else {
+                deltas.push(None);
+                continue;
+            };
+            match self.group_descs.get(&desc.id) {
+                Some(base) if base.epoch >= desc.epoch => deltas.push(None),
+                base => {
+                    deltas.push(base.map(|base| desc.delta_since(base)));
+                    self.group_descs.insert(desc.id, desc.clone());
+                }
+            }
+        }
+        deltas
+    }
+}
+
+fn forget_deleted_groups<T>(groups: &mut HashMap<u64, T>, deletes: &[DeleteEvent]) {
+    for delete in deletes {
+        if let Some(delete_event::Event::Group(group_id)) = &delete.event {
+            groups.remove(group_id);
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Watcher {
     #[allow(dead_code)]
@@ -110,16 +172,40 @@ struct WatcherInner {
     deletes: Vec<DeleteEvent>,
     err: Option<Error>,
     dropped: bool,
+    /// The epochs of the group descriptors sent to the watcher.
+    group_epochs: HashMap<u64, u64>,
 }
 
 impl Watcher {
-    fn notify(&self, updates: &[UpdateEvent], deletes: &[DeleteEvent], err: Option<Error>) {
+    fn notify(
+        &self,
+        updates: &[UpdateEvent],
+        deltas: &[Option<GroupDescDelta>],
+        deletes: &[DeleteEvent],
+        err: Option<Error>,
+    ) {
         let _timer = super::metrics::WATCH_NOTIFY_DURATION_SECONDS.start_timer();
         let mut inner = self.inner.lock().unwrap();
         if inner.dropped {
             return;
         }
-        inner.updates.extend_from_slice(updates); // TODO: set capcity limit
+        // TODO: set capcity limit
+        for (update, delta) in updates.iter().zip(deltas) {
+            let Some(update_event::Event::Group(desc)) = &update.event else {
+                inner.updates.push(update.clone());
+                continue;
+            };
+            // The delta is sent only if the watcher holds the base descriptor.
+            let known_epoch = inner.group_epochs.insert(desc.id, desc.epoch);
+            match delta {
+                Some(delta) if known_epoch == Some(delta.base_epoch) => {
+                    let event = update_event::Event::GroupDelta(delta.clone());
+                    inner.updates.push(UpdateEvent { event: Some(event) });
+                }
+                _ => inner.updates.push(update.clone()),
+            }
+        }
+        forget_deleted_groups(&mut inner.group_epochs, deletes);
         inner.deletes.extend_from_slice(deletes);
         if err.is_some() && inner.err.is_none() {
             inner.err = err
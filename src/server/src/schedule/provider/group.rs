@@ -14,11 +14,11 @@
 
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
 
 use futures::channel::oneshot;
 use sekas_api::server::v1::*;
 use sekas_client::Router;
+use sekas_runtime::time::Instant;
 
 use crate::node::Replica;
 use crate::raftgroup::RaftGroupState;
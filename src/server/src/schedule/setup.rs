@@ -73,7 +73,7 @@ async fn scheduler_main(
             providers,
             schedule_state_observer.clone(),
         );
-        allocate_group_tasks(&mut scheduler, group_providers.clone()).await;
+        allocate_group_tasks(&mut scheduler, group_id, group_providers.clone()).await;
 
         // After the schedule is initialized, the root needs to be notified to clear the
         // expired state in memory.
@@ -88,7 +88,11 @@ async fn scheduler_main(
     debug!("group {group_id} replica {replica_id} scheduler is stopped");
 }
 
-async fn allocate_group_tasks(scheduler: &mut Scheduler, providers: Arc<GroupProviders>) {
+async fn allocate_group_tasks(
+    scheduler: &mut Scheduler,
+    group_id: u64,
+    providers: Arc<GroupProviders>,
+) {
     use super::tasks::*;
 
     let tasks: Vec<Box<dyn Task>> = vec![
@@ -99,6 +103,8 @@ async fn allocate_group_tasks(scheduler: &mut Scheduler, providers: Arc<GroupPro
         Box::new(DurableGroup::new(providers.clone())),
         Box::new(RemoveOrphanReplica::new(providers.clone())),
         Box::new(ReplicaMigration::new(providers)),
+        Box::new(ResolveIntents::new(group_id)),
+        Box::new(AdvanceGcWatermark::new(group_id)),
     ];
     scheduler.install_tasks(tasks);
 }
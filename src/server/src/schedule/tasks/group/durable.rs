@@ -170,6 +170,7 @@ impl DurableGroup {
             current_term: ctx.current_term,
             leader_id: replica_id,
             num_required: num_required as u64,
+            purpose: alloc_replica_request::Purpose::Cure as i32,
         };
         match ctx.transport_manager.root_client().alloc_replica(req).await {
             Ok(resp) => Some(resp.replicas),
@@ -301,7 +302,14 @@ impl Task for DurableGroup {
             return TaskState::Pending(None);
         }
 
-        let replicas = self.providers.descriptor.replicas();
+        // The read replicas are never cured nor counted toward the quorum health.
+        let replicas = self
+            .providers
+            .descriptor
+            .replicas()
+            .into_iter()
+            .filter(|r| r.role != ReplicaRole::ReadReplica as i32)
+            .collect::<Vec<_>>();
         if replicas.len() <= 1 || ctx.group_lock_table.has_config_change() {
             return TaskState::Pending(Some(Duration::from_secs(1)));
         }
@@ -333,6 +341,7 @@ impl Task for DurableGroup {
                         stats.online_learners.insert(r.id, r.clone());
                     }
                 }
+                ReplicaRole::ReadReplica => unreachable!(),
             }
         }
 
@@ -0,0 +1,101 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, warn};
+use sekas_client::RootClient;
+use sekas_runtime::JoinHandle;
+
+use crate::node::Replica;
+use crate::schedule::scheduler::ScheduleContext;
+use crate::schedule::task::{Task, TaskState};
+use crate::schedule::tasks::ADVANCE_GC_WATERMARK_TASK_ID;
+use crate::Result;
+
+/// The max interval to advance the GC watermark of the group, it is advanced
+/// at least once per retention.
+const ADVANCE_GC_WATERMARK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The max duration to allocate a version from root.
+const ALLOC_VERSION_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Advance the GC watermark of the group to the versions older than
+/// `ReplicaConfig::gc_retention_ms` in the background.
+///
+/// The watermark is derived from a version allocated by root instead of the
+/// local clock, since the versions might lag behind the wall time after root
+/// is restarted. It never exceeds the versions pinned on this node, and it is
+/// replicated through raft, so every replica drops the same versions.
+pub struct AdvanceGcWatermark {
+    group_id: u64,
+    advancing: Option<JoinHandle<()>>,
+}
+
+impl AdvanceGcWatermark {
+    pub fn new(group_id: u64) -> Self {
+        AdvanceGcWatermark { group_id, advancing: None }
+    }
+}
+
+#[crate::async_trait]
+impl Task for AdvanceGcWatermark {
+    fn id(&self) -> u64 {
+        ADVANCE_GC_WATERMARK_TASK_ID
+    }
+
+    async fn poll(&mut self, ctx: &mut ScheduleContext<'_>) -> TaskState {
+        let retention = Duration::from_millis(ctx.cfg.gc_retention_ms);
+        let is_advancing = self.advancing.as_ref().is_some_and(|handle| !handle.is_finished());
+        if !retention.is_zero() && !is_advancing {
+            let group_id = self.group_id;
+            let replica = ctx.replica.clone();
+            let root_client = ctx.transport_manager.root_client().clone();
+            self.advancing = Some(sekas_runtime::spawn(async move {
+                if let Err(err) = advance_gc_watermark(replica, root_client, retention).await {
+                    warn!("group {group_id} advance gc watermark: {err:?}");
+                }
+            }));
+        }
+        let interval = if retention.is_zero() {
+            ADVANCE_GC_WATERMARK_INTERVAL
+        } else {
+            ADVANCE_GC_WATERMARK_INTERVAL.min(retention)
+        };
+        TaskState::Pending(Some(interval))
+    }
+}
+
+async fn advance_gc_watermark(
+    replica: Arc<Replica>,
+    root_client: RootClient,
+    retention: Duration,
+) -> Result<()> {
+    let version = root_client.alloc_txn_id(1, Some(ALLOC_VERSION_TIMEOUT)).await?;
+    let gc_state = replica.group_engine().gc_state();
+    let mut watermark = version.saturating_sub(retention.as_nanos() as u64);
+    if let Some(pinned) = gc_state.oldest_pinned() {
+        watermark = watermark.min(pinned);
+    }
+    let current = gc_state.unpinned_watermark();
+    if watermark <= current {
+        return Ok(());
+    }
+    debug!(
+        "group {} advance gc watermark from {current} to {watermark}",
+        replica.replica_info().group_id
+    );
+    replica.update_gc_watermark(watermark).await
+}
@@ -13,9 +13,11 @@
 // limitations under the License.
 
 mod durable;
+mod gc_watermark;
 mod migration;
 mod orphan_replica;
 mod promote;
+mod resolve_intent;
 mod watch_descriptor;
 mod watch_raft_state;
 mod watch_replica_states;
@@ -25,9 +27,11 @@ use std::collections::HashMap;
 use sekas_api::server::v1::{ReplicaDesc, ScheduleState};
 
 pub use self::durable::DurableGroup;
+pub use self::gc_watermark::AdvanceGcWatermark;
 pub use self::migration::ReplicaMigration;
 pub use self::orphan_replica::RemoveOrphanReplica;
 pub use self::promote::PromoteGroup;
+pub use self::resolve_intent::ResolveIntents;
 pub use self::watch_descriptor::WatchGroupDescriptor;
 pub use self::watch_raft_state::WatchRaftState;
 pub use self::watch_replica_states::WatchReplicaStates;
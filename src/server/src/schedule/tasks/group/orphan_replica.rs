@@ -14,10 +14,11 @@
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use log::info;
 use sekas_api::server::v1::*;
+use sekas_runtime::time::Instant;
 
 use super::ActionTaskWithLocks;
 use crate::schedule::actions::{ClearReplicaState, RemoveReplica};
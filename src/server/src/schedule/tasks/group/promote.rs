@@ -89,6 +89,7 @@ impl PromoteGroup {
             current_term: ctx.current_term,
             leader_id: ctx.replica_id,
             num_required: num_required as u64,
+            purpose: alloc_replica_request::Purpose::Promote as i32,
         };
         match ctx.transport_manager.root_client().alloc_replica(req).await {
             Ok(resp) => Some(resp.replicas),
@@ -122,7 +123,13 @@ impl Task for PromoteGroup {
             return TaskState::Pending(Some(Duration::from_secs(1)));
         }
 
-        let replicas = self.providers.descriptor.replicas();
+        let replicas = self
+            .providers
+            .descriptor
+            .replicas()
+            .into_iter()
+            .filter(|r| r.role != ReplicaRole::ReadReplica as i32)
+            .collect::<Vec<_>>();
         if replicas.len() > 1 {
             return TaskState::Terminated;
         } else if replicas.is_empty() {
@@ -0,0 +1,168 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, warn};
+use sekas_runtime::JoinHandle;
+
+use crate::node::Replica;
+use crate::replica::metrics::*;
+use crate::schedule::scheduler::ScheduleContext;
+use crate::schedule::task::{Task, TaskState};
+use crate::schedule::tasks::RESOLVE_INTENT_TASK_ID;
+
+/// The interval to refresh the intent metrics and resolve the old intents.
+const RESOLVE_INTENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The max number of intents resolved in each round.
+const MAX_RESOLVE_INTENTS_PER_ROUND: usize = 64;
+
+/// The max duration to wait for a running txn, the intent is resolved again in
+/// the next rounds.
+const RESOLVE_INTENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Export the intent stats of the shards, and resolve the intents older than
+/// `ReplicaConfig::resolve_intent_age_ms` in the background. The shards with
+/// the oldest intents are resolved first.
+pub struct ResolveIntents {
+    group_id: u64,
+    exported_shards: HashSet<u64>,
+    resolving: Option<JoinHandle<()>>,
+}
+
+impl ResolveIntents {
+    pub fn new(group_id: u64) -> Self {
+        ResolveIntents { group_id, exported_shards: HashSet::default(), resolving: None }
+    }
+
+    fn export_metrics(&mut self, replica: &Replica) -> Vec<(u64, Duration)> {
+        let group_engine = replica.group_engine();
+        let group = self.group_id.to_string();
+        let mut shard_ages = vec![];
+        let mut shards = HashSet::new();
+        for shard in replica.descriptor().shards {
+            let stats = group_engine.shard_intent_stats(shard.id);
+            let oldest_age = stats.oldest_intent.map(|intent| intent.age).unwrap_or_default();
+            let labels = [group.as_str(), &shard.id.to_string()];
+            REPLICA_SHARD_INTENTS.with_label_values(&labels).set(stats.num_intents as i64);
+            REPLICA_SHARD_OLDEST_INTENT_AGE_SECONDS
+                .with_label_values(&labels)
+                .set(oldest_age.as_secs_f64());
+            REPLICA_SHARD_RESOLVED_INTENTS_PER_SEC
+                .with_label_values(&labels)
+                .set(stats.resolve_rate);
+            if stats.num_intents > 0 {
+                shard_ages.push((shard.id, oldest_age));
+            }
+            shards.insert(shard.id);
+        }
+        for shard_id in self.exported_shards.difference(&shards) {
+            remove_shard_metrics(self.group_id, *shard_id);
+        }
+        self.exported_shards = shards;
+        shard_ages
+    }
+
+    fn resolve_old_intents(
+        &mut self,
+        replica: Arc<Replica>,
+        mut shard_ages: Vec<(u64, Duration)>,
+        min_age: Duration,
+    ) {
+        if self.resolving.as_ref().is_some_and(|handle| !handle.is_finished()) {
+            return;
+        }
+
+        shard_ages.retain(|(_, age)| *age >= min_age);
+        shard_ages.sort_unstable_by_key(|(_, age)| Reverse(*age));
+        let group_engine = replica.group_engine();
+        let mut intents = vec![];
+        for (shard_id, _) in shard_ages {
+            let limit = MAX_RESOLVE_INTENTS_PER_ROUND - intents.len();
+            for intent in group_engine.oldest_intents(shard_id, limit) {
+                if intent.age < min_age {
+                    break;
+                }
+                intents.push((shard_id, intent));
+            }
+            if intents.len() >= MAX_RESOLVE_INTENTS_PER_ROUND {
+                break;
+            }
+        }
+        if intents.is_empty() {
+            self.resolving = None;
+            return;
+        }
+
+        let group_id = self.group_id;
+        debug!("group {group_id} try resolve {} intents in background", intents.len());
+        self.resolving = Some(sekas_runtime::spawn(async move {
+            for (shard_id, intent) in intents {
+                let resolve =
+                    replica.resolve_intent(shard_id, &intent.user_key, intent.start_version);
+                match sekas_runtime::time::timeout(RESOLVE_INTENT_TIMEOUT, resolve).await {
+                    Ok(Ok(())) => {
+                        REPLICA_RESOLVE_INTENT_TOTAL.with_label_values(&["ok"]).inc();
+                    }
+                    Ok(Err(err)) => {
+                        REPLICA_RESOLVE_INTENT_TOTAL.with_label_values(&["error"]).inc();
+                        warn!(
+                            "group {group_id} shard {shard_id} resolve intent of txn {}: {err:?}",
+                            intent.start_version
+                        );
+                        return;
+                    }
+                    Err(_) => {
+                        REPLICA_RESOLVE_INTENT_TOTAL.with_label_values(&["timeout"]).inc();
+                    }
+                }
+            }
+        }));
+    }
+}
+
+#[crate::async_trait]
+impl Task for ResolveIntents {
+    fn id(&self) -> u64 {
+        RESOLVE_INTENT_TASK_ID
+    }
+
+    async fn poll(&mut self, ctx: &mut ScheduleContext<'_>) -> TaskState {
+        let shard_ages = self.export_metrics(&ctx.replica);
+        let min_age = Duration::from_millis(ctx.cfg.resolve_intent_age_ms);
+        self.resolve_old_intents(ctx.replica.clone(), shard_ages, min_age);
+        TaskState::Pending(Some(RESOLVE_INTENT_INTERVAL))
+    }
+}
+
+impl Drop for ResolveIntents {
+    fn drop(&mut self) {
+        // The stats are only exported by the leader.
+        for shard_id in &self.exported_shards {
+            remove_shard_metrics(self.group_id, *shard_id);
+        }
+    }
+}
+
+fn remove_shard_metrics(group_id: u64, shard_id: u64) {
+    let labels = [&group_id.to_string(), &shard_id.to_string()];
+    let labels = [labels[0].as_str(), labels[1].as_str()];
+    let _ = REPLICA_SHARD_INTENTS.remove_label_values(&labels);
+    let _ = REPLICA_SHARD_OLDEST_INTENT_AGE_SECONDS.remove_label_values(&labels);
+    let _ = REPLICA_SHARD_RESOLVED_INTENTS_PER_SEC.remove_label_values(&labels);
+}
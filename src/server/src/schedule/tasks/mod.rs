@@ -17,8 +17,8 @@ mod group;
 
 pub use self::action::ActionTask;
 pub use self::group::{
-    DurableGroup, GroupLockTable, PromoteGroup, RemoveOrphanReplica, ReplicaMigration,
-    WatchGroupDescriptor, WatchRaftState, WatchReplicaStates,
+    AdvanceGcWatermark, DurableGroup, GroupLockTable, PromoteGroup, RemoveOrphanReplica,
+    ReplicaMigration, ResolveIntents, WatchGroupDescriptor, WatchRaftState, WatchReplicaStates,
 };
 
 pub const PROMOTE_GROUP_TASK_ID: u64 = 1;
@@ -28,5 +28,7 @@ pub const REPLICA_MIGRATION_TASK_ID: u64 = 4;
 pub const WATCH_REPLICA_STATES_TASK_ID: u64 = 5;
 pub const WATCH_RAFT_STATE_TASK_ID: u64 = 6;
 pub const WATCH_GROUP_DESCRIPTOR_TASK_ID: u64 = 7;
+pub const RESOLVE_INTENT_TASK_ID: u64 = 8;
+pub const ADVANCE_GC_WATERMARK_TASK_ID: u64 = 9;
 
 pub const GENERATED_TASK_ID: u64 = 10;
@@ -15,7 +15,7 @@
 #![allow(clippy::all)]
 
 pub mod v1 {
-    use sekas_api::server::v1::{MoveShardDesc, ShardDesc};
+    use sekas_api::server::v1::{GroupDesc, MoveShardDesc, ShardDesc};
 
     tonic::include_proto!("serverpb.v1");
 
@@ -39,6 +39,21 @@ pub mod v1 {
             })
         }
 
+        #[inline]
+        pub fn sync_epoch(catalog_desc: GroupDesc) -> Box<Self> {
+            let epoch = catalog_desc.epoch;
+            let sync_epoch = SyncEpoch { epoch, catalog_desc: Some(catalog_desc) };
+            Box::new(SyncOp { sync_epoch: Some(sync_epoch), ..Default::default() })
+        }
+
+        #[inline]
+        pub fn update_gc_watermark(watermark: u64) -> Box<Self> {
+            Box::new(SyncOp {
+                update_gc_watermark: Some(UpdateGcWatermark { watermark }),
+                ..Default::default()
+            })
+        }
+
         #[inline]
         pub fn move_shard(event: MoveShardEvent, desc: MoveShardDesc) -> Box<Self> {
             Box::new(SyncOp {
@@ -50,12 +65,15 @@ pub mod v1 {
                 ..Default::default()
             })
         }
+
         #[inline]
-        pub fn ingest(key: Vec<u8>) -> Box<Self> {
+        pub fn ingest(key: Vec<u8>, ingested_keys: u64, ingested_bytes: u64) -> Box<Self> {
             Box::new(SyncOp {
                 move_shard: Some(MoveShard {
                     event: MoveShardEvent::Ingest as i32,
                     last_ingested_key: key,
+                    ingested_keys,
+                    ingested_bytes,
                     ..Default::default()
                 }),
                 ..Default::default()
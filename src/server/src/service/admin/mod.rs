@@ -19,6 +19,7 @@ mod metadata;
 mod metrics;
 mod monitor;
 mod service;
+mod snapshot;
 
 pub use self::service::AdminService;
 use self::service::Router;
@@ -34,6 +35,7 @@ pub fn make_admin_service(server: Server) -> AdminService {
         .route("/uncordon", self::cluster::UncordonHandle::new(server.to_owned()))
         .route("/drain", self::cluster::DrainHandle::new(server.to_owned()))
         .route("/node_status", self::cluster::StatusHandle::new(server.to_owned()))
+        .route("/snapshot_sends", self::snapshot::SnapshotSendsHandle::new(server.to_owned()))
         .route("/monitor", self::monitor::MonitorHandle::new(server));
     let api = Router::nest("/admin", router);
     AdminService::new(api)
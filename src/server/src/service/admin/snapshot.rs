@@ -0,0 +1,52 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use tonic::codegen::*;
+
+use crate::raftgroup::snap::SnapSendRecord;
+use crate::{Result, Server};
+
+#[derive(Debug, Serialize)]
+struct SnapshotSends {
+    num_queued: usize,
+    /// The recently finished transfers, in the order of finishing.
+    records: Vec<SnapSendRecord>,
+}
+
+/// Show the outgoing snapshot transfers of this node.
+pub(super) struct SnapshotSendsHandle {
+    server: Server,
+}
+
+impl SnapshotSendsHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[crate::async_trait]
+impl super::service::HttpHandle for SnapshotSendsHandle {
+    async fn call(&self, _: &str, _: &HashMap<String, String>) -> Result<http::Response<String>> {
+        let scheduler = self.server.node.raft_manager().snapshot_manager().send_scheduler();
+        let sends =
+            SnapshotSends { num_queued: scheduler.num_queued(), records: scheduler.records() };
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&sends).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
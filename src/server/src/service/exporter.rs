@@ -0,0 +1,153 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The HTTP listener which exposes the metrics to the scrapers.
+//!
+//! The node-level, per-group and root-level metrics live in the default
+//! registry of prometheus, the metrics of the embedded client included. The
+//! exporter listens on a dedicated address, so it is reachable before the node
+//! joins the cluster, and `/readyz` tells whether the node serves requests.
+
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use log::{info, warn};
+use prometheus::core::Collector;
+use prometheus::proto::MetricFamily;
+use prometheus::TextEncoder;
+use sekas_runtime::TcpIncoming;
+use tokio::net::TcpListener;
+
+use super::metrics::*;
+use crate::root::RootCollector;
+use crate::{MetricsConfig, Result, Server};
+
+#[derive(Clone)]
+pub struct MetricsExporter {
+    shared: Arc<ExporterShared>,
+}
+
+struct ExporterShared {
+    max_series_per_metric: usize,
+    ready: AtomicBool,
+    collector: Mutex<Option<RootCollector>>,
+}
+
+impl MetricsExporter {
+    /// Bind the listener and serve the scrapes in the background, `None` is
+    /// returned if the exporter is disabled.
+    pub async fn bind(cfg: &MetricsConfig) -> Result<Option<MetricsExporter>> {
+        let Some(addr) = cfg.addr.as_ref() else {
+            return Ok(None);
+        };
+        let listener = TcpListener::bind(addr).await?;
+        let incoming = TcpIncoming::from_listener(listener, true);
+        let exporter = MetricsExporter {
+            shared: Arc::new(ExporterShared {
+                max_series_per_metric: cfg.max_series_per_metric,
+                ready: AtomicBool::new(false),
+                collector: Mutex::default(),
+            }),
+        };
+
+        let cloned_exporter = exporter.clone();
+        let make_service = make_service_fn(move |_| {
+            let exporter = cloned_exporter.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let exporter = exporter.clone();
+                    async move { Ok::<_, Infallible>(exporter.handle(req).await) }
+                }))
+            }
+        });
+        let server = hyper::Server::builder(hyper::server::accept::from_stream(incoming))
+            .serve(make_service);
+        info!("metrics exporter listens on {addr}");
+        sekas_runtime::spawn(async move {
+            if let Err(err) = server.await {
+                warn!("metrics exporter is stopped: {err}");
+            }
+        });
+        Ok(Some(exporter))
+    }
+
+    /// Mark the node ready once it has joined the cluster and opened its
+    /// replicas, the root-level metrics are exposed since then.
+    pub fn set_ready(&self, server: Server) {
+        *self.shared.collector.lock().unwrap() = Some(RootCollector::new("", server));
+        self.shared.ready.store(true, Ordering::Release);
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if req.method() != Method::GET {
+            return response(StatusCode::METHOD_NOT_ALLOWED, String::default());
+        }
+        match req.uri().path() {
+            "/metrics" => {
+                let families = self.gather().await;
+                match TextEncoder::new().encode_to_string(&families) {
+                    Ok(content) => response(StatusCode::OK, content),
+                    Err(err) => response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+                }
+            }
+            "/healthz" => response(StatusCode::OK, "Ok\n".to_owned()),
+            "/readyz" if self.shared.ready.load(Ordering::Acquire) => {
+                response(StatusCode::OK, "Ok\n".to_owned())
+            }
+            "/readyz" => response(StatusCode::SERVICE_UNAVAILABLE, "Not ready\n".to_owned()),
+            _ => response(StatusCode::NOT_FOUND, String::default()),
+        }
+    }
+
+    async fn gather(&self) -> Vec<MetricFamily> {
+        METRICS_EXPORTER_SCRAPE_TOTAL.inc();
+        let mut families = prometheus::gather();
+        let collector = self.shared.collector.lock().unwrap().clone();
+        if let Some(collector) = collector {
+            // The root collector registered by the admin service might belong to
+            // another server of the process, so the families of this server win.
+            collector.try_refresh().await;
+            let root_families = collector.collect();
+            families.retain(|f| root_families.iter().all(|r| r.get_name() != f.get_name()));
+            for mut family in root_families {
+                match families.iter_mut().find(|f| f.get_name() == family.get_name()) {
+                    Some(existing) => {
+                        for metric in family.take_metric() {
+                            existing.mut_metric().push(metric);
+                        }
+                    }
+                    None => families.push(family),
+                }
+            }
+            families.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+        }
+
+        let limit = self.shared.max_series_per_metric;
+        for family in &mut families {
+            let metrics = family.mut_metric();
+            if metrics.len() > limit {
+                METRICS_EXPORTER_DROPPED_SERIES_TOTAL.inc_by((metrics.len() - limit) as u64);
+                metrics.truncate(limit);
+            }
+        }
+        families
+    }
+}
+
+fn response(status: StatusCode, body: String) -> Response<Body> {
+    Response::builder().status(status).body(Body::from(body)).unwrap()
+}
@@ -0,0 +1,220 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Limit the bytes of the group requests before they are buffered.
+//!
+//! The codecs of tonic buffer the whole message before decoding it, so the
+//! limit is enforced on the length prefix of the grpc frames as the body is
+//! received. The request is failed with `MessageTooLarge` once a frame
+//! announces a message larger than `max_message_bytes`.
+
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use hyper::body::Bytes;
+use pin_project::pin_project;
+use tonic::codegen::{http, Body, Service, StdError};
+use tonic::transport::NamedService;
+use tonic::Status;
+
+use crate::Error;
+
+/// The path of the group rpc, see `node.proto`.
+const GROUP_PATH: &str = "/sekas.server.v1.Node/Group";
+
+/// The bytes of the grpc frame header: the compressed flag and the length of
+/// the message in big endian.
+const FRAME_HEADER_BYTES: usize = 5;
+
+/// Wrap the node service, the group requests are limited by
+/// `max_message_bytes`, `0` means unlimited.
+#[derive(Clone)]
+pub struct MessageLimitService<S> {
+    inner: S,
+    max_message_bytes: usize,
+}
+
+/// The request body which fails once the length of a frame exceeds the limit.
+#[pin_project]
+pub struct LimitedBody<B> {
+    #[pin]
+    inner: B,
+    frames: FrameLimit,
+}
+
+/// Track the frame headers of the received bytes.
+#[derive(Debug, Default)]
+struct FrameLimit {
+    max_message_bytes: usize,
+    header: [u8; FRAME_HEADER_BYTES],
+    header_len: usize,
+    /// The bytes of the payload of the current frame to receive.
+    remaining: usize,
+}
+
+impl<S> MessageLimitService<S> {
+    pub fn new(inner: S, max_message_bytes: usize) -> Self {
+        MessageLimitService { inner, max_message_bytes }
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for MessageLimitService<S>
+where
+    S: Service<http::Request<LimitedBody<B>>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let max_message_bytes =
+            if req.uri().path() == GROUP_PATH { self.max_message_bytes } else { 0 };
+        self.inner.call(req.map(|inner| LimitedBody {
+            inner,
+            frames: FrameLimit { max_message_bytes, ..Default::default() },
+        }))
+    }
+}
+
+impl<S: NamedService> NamedService for MessageLimitService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<B> Body for LimitedBody<B>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<StdError>,
+{
+    type Data = Bytes;
+    type Error = StdError;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let data = match ready!(this.inner.poll_data(cx)) {
+            Some(Ok(data)) => data,
+            Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+            None => return Poll::Ready(None),
+        };
+        if let Err(err) = this.frames.advance(&data) {
+            // The status is taken from the error by the tonic decoder.
+            return Poll::Ready(Some(Err(Box::new(Status::from(err)))));
+        }
+        Poll::Ready(Some(Ok(data)))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx).map_err(Into::into)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl FrameLimit {
+    /// Advance the frames by the received bytes, `Error::MessageTooLarge` is
+    /// returned once the header of a frame exceeding the limit is received.
+    fn advance(&mut self, mut data: &[u8]) -> crate::Result<()> {
+        if self.max_message_bytes == 0 {
+            return Ok(());
+        }
+        while !data.is_empty() {
+            if self.remaining > 0 {
+                let n = self.remaining.min(data.len());
+                self.remaining -= n;
+                data = &data[n..];
+                continue;
+            }
+
+            let n = (FRAME_HEADER_BYTES - self.header_len).min(data.len());
+            self.header[self.header_len..self.header_len + n].copy_from_slice(&data[..n]);
+            self.header_len += n;
+            data = &data[n..];
+            if self.header_len < FRAME_HEADER_BYTES {
+                break;
+            }
+            self.header_len = 0;
+            let mut len = [0u8; 4];
+            len.copy_from_slice(&self.header[1..]);
+            let size = u32::from_be_bytes(len) as usize;
+            if size > self.max_message_bytes {
+                return Err(Error::MessageTooLarge {
+                    size: size as u64,
+                    limit: self.max_message_bytes as u64,
+                });
+            }
+            self.remaining = size;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(size: usize) -> Vec<u8> {
+        let mut buf = vec![0u8];
+        buf.extend_from_slice(&(size as u32).to_be_bytes());
+        buf.resize(FRAME_HEADER_BYTES + size, b'v');
+        buf
+    }
+
+    #[test]
+    fn frame_limit_checks_the_length_prefix() {
+        let mut frames = FrameLimit { max_message_bytes: 16, ..Default::default() };
+        let mut data = frame(16);
+        data.extend(frame(0));
+        data.extend(frame(8));
+        frames.advance(&data).unwrap();
+
+        // Only the header is required to reject the frame.
+        let data = frame(17);
+        assert!(matches!(
+            frames.advance(&data[..FRAME_HEADER_BYTES]),
+            Err(Error::MessageTooLarge { size: 17, limit: 16 })
+        ));
+    }
+
+    #[test]
+    fn frame_limit_across_chunks() {
+        let mut data = frame(10);
+        data.extend(frame(32));
+        // The header of the second frame is split across the chunks.
+        let (first, second) = data.split_at(FRAME_HEADER_BYTES + 10 + 2);
+
+        let mut frames = FrameLimit { max_message_bytes: 16, ..Default::default() };
+        frames.advance(first).unwrap();
+        assert!(matches!(frames.advance(second), Err(Error::MessageTooLarge { size: 32, .. })));
+
+        // Unlimited.
+        let mut frames = FrameLimit::default();
+        frames.advance(first).unwrap();
+        frames.advance(second).unwrap();
+    }
+}
@@ -21,14 +21,18 @@ make_static_metric! {
     pub struct GroupRequestTotal: IntCounter {
         "type" => {
             get,
+            get_raw_key,
             scan,
             write,
+            delete_prefix,
             write_intent,
             commit_intent,
             clear_intent,
+            check_prefix_empty,
             transfer,
             split_shard,
             merge_shard,
+            remove_shard,
             accept_shard,
             create_shard,
             move_replicas,
@@ -39,14 +43,18 @@ make_static_metric! {
     pub struct GroupRequestDuration: Histogram {
         "type" => {
             get,
+            get_raw_key,
             scan,
             write,
+            delete_prefix,
             write_intent,
             commit_intent,
             clear_intent,
+            check_prefix_empty,
             transfer,
             split_shard,
             merge_shard,
+            remove_shard,
             accept_shard,
             create_shard,
             move_replicas,
@@ -85,6 +93,10 @@ pub fn take_group_request_metrics(request: &GroupRequest) -> Option<&'static His
             NODE_SERVICE_GROUP_REQUEST_TOTAL.get.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.get)
         }
+        Some(Request::GetRawKey(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.get_raw_key.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.get_raw_key)
+        }
         Some(Request::Scan(_)) => {
             NODE_SERVICE_GROUP_REQUEST_TOTAL.scan.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.scan)
@@ -93,6 +105,10 @@ pub fn take_group_request_metrics(request: &GroupRequest) -> Option<&'static His
             NODE_SERVICE_GROUP_REQUEST_TOTAL.write.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.write)
         }
+        Some(Request::DeletePrefix(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.delete_prefix.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.delete_prefix)
+        }
         Some(Request::AcceptShard(_)) => {
             NODE_SERVICE_GROUP_REQUEST_TOTAL.accept_shard.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.accept_shard)
@@ -125,6 +141,10 @@ pub fn take_group_request_metrics(request: &GroupRequest) -> Option<&'static His
             NODE_SERVICE_GROUP_REQUEST_TOTAL.clear_intent.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.clear_intent)
         }
+        Some(Request::CheckPrefixEmpty(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.check_prefix_empty.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.check_prefix_empty)
+        }
         Some(Request::WatchKey(_)) => {
             NODE_SERVICE_GROUP_REQUEST_TOTAL.watch_key.inc();
             None
@@ -137,6 +157,10 @@ pub fn take_group_request_metrics(request: &GroupRequest) -> Option<&'static His
             NODE_SERVICE_GROUP_REQUEST_TOTAL.merge_shard.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.merge_shard)
         }
+        Some(Request::RemoveShard(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.remove_shard.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.remove_shard)
+        }
         None => None,
     }
 }
@@ -205,6 +229,7 @@ simple_root_method!(watch);
 simple_root_method!(admin);
 simple_root_method!(join);
 simple_root_method!(alloc_replica);
+simple_root_method!(replicate_catalog);
 
 lazy_static! {
     pub static ref RAFT_SERVICE_MSG_REQUEST_TOTAL: IntCounter = register_int_counter!(
@@ -225,6 +250,19 @@ lazy_static! {
     .unwrap();
 }
 
+lazy_static! {
+    pub static ref METRICS_EXPORTER_SCRAPE_TOTAL: IntCounter = register_int_counter!(
+        "metrics_exporter_scrape_total",
+        "The total scrapes of metrics exporter",
+    )
+    .unwrap();
+    pub static ref METRICS_EXPORTER_DROPPED_SERIES_TOTAL: IntCounter = register_int_counter!(
+        "metrics_exporter_dropped_series_total",
+        "The total series dropped by metrics exporter to bound the label cardinality",
+    )
+    .unwrap();
+}
+
 make_static_metric! {
     pub struct DatabaseRequestTotal: IntCounter {
         "type" => {
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 pub mod admin;
+pub mod exporter;
+pub mod message_limit;
 mod metrics;
 pub mod node;
 pub mod raft;
@@ -22,6 +24,7 @@ use std::time::Duration;
 
 use sekas_client::{ClientOptions, SekasClient};
 
+use crate::node::observer::ReplicaObserver;
 use crate::node::Node;
 use crate::root::Root;
 use crate::transport::{AddressResolver, TransportManager};
@@ -33,6 +36,15 @@ pub struct Server {
     pub address_resolver: Arc<AddressResolver>,
 }
 
+impl Server {
+    /// Register an observer of the replica lifecycle, it only sees the events
+    /// emitted after it, use `NodeConfig::replica_observers` to observe the
+    /// replicas recovered by the bootstrap too.
+    pub fn register_replica_observer(&self, observer: Box<dyn ReplicaObserver>) {
+        self.node.replica_observers().register(observer);
+    }
+}
+
 #[derive(Clone)]
 pub struct ProxyServer {
     #[allow(dead_code)]
@@ -42,8 +54,10 @@ pub struct ProxyServer {
 impl ProxyServer {
     #[allow(dead_code)]
     pub(crate) fn new(transport_manager: &TransportManager) -> Self {
-        let opts =
-            ClientOptions { connect_timeout: Some(Duration::from_millis(250)), timeout: None };
-        ProxyServer { client: transport_manager.build_client(opts) }
+        let opts = ClientOptions {
+            connect_timeout: Some(Duration::from_millis(250)),
+            ..Default::default()
+        };
+        ProxyServer { client: transport_manager.build_proxy_client(opts) }
     }
 }
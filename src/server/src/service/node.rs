@@ -13,21 +13,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
-use async_stream::try_stream;
-use futures::channel::mpsc;
+use async_stream::{stream, try_stream};
 use futures::StreamExt;
 use log::trace;
+use prost::Message;
 use sekas_api::server::v1::group_request_union::Request as ShardRequest;
 use sekas_api::server::v1::group_response_union::Response as ShardResponse;
 use sekas_api::server::v1::watch_key_response::WatchResult;
 use sekas_api::server::v1::*;
+use sekas_client::{INTERNAL_ORIGIN_HEADER, MAX_MESSAGE_BYTES_HEADER};
+use sekas_runtime::time::Instant;
 use sekas_schema::system::txn::{TXN_INTENT_VERSION, TXN_MAX_VERSION};
 use tonic::{Request, Response, Status};
 
 use super::metrics::*;
+use crate::cipher::DataCipher;
 use crate::replica::ExecCtx;
 use crate::serverpb::v1::MoveShardEvent;
 use crate::{record_latency, record_latency_opt, Error, Server};
@@ -44,21 +50,47 @@ impl futures::Stream for GroupStream {
     }
 }
 
+/// Handle the group request, the responses are limited by
+/// `max_message_bytes`, which is negotiated with the client, `0` means
+/// unlimited. The requests are limited by the node before they are decoded,
+/// see [`crate::service::message_limit`].
 fn handle_group_request(
     server: Server,
     request: GroupRequest,
+    remote_addr: Option<SocketAddr>,
+    deadline: Option<Instant>,
+    internal_origin: bool,
+    max_message_bytes: usize,
 ) -> impl futures::Stream<Item = Result<GroupResponse, Status>> {
     try_stream! {
         record_latency_opt!(take_group_request_metrics(&request));
-        let mut exec_ctx = ExecCtx::default();
+        let mut exec_ctx = ExecCtx { deadline, internal_origin, ..Default::default() };
         let inner_request = request
             .request
             .as_ref()
             .and_then(|request| request.request.as_ref())
             .ok_or_else(|| Error::InvalidArgument("GroupRequest::request is None".into()))?;
+        if let ShardRequest::Scan(scan_req) = inner_request {
+            let frames = handle_scan_request(
+                server,
+                request.clone(),
+                scan_req.clone(),
+                deadline,
+                max_message_bytes,
+            );
+            for await frame in frames {
+                yield frame;
+            }
+            return;
+        }
         if !matches!(inner_request, ShardRequest::WatchKey(_)) {
-            let response =
-                server.node.execute_request(&exec_ctx, &request).await.unwrap_or_else(error_to_response);
+            let response = match server.node.execute_request(&exec_ctx, &request).await {
+                Ok(resp) => match check_message_size(resp.encoded_len(), max_message_bytes) {
+                    Ok(()) => resp,
+                    Err(err) => error_to_response(err),
+                },
+                Err(err) => error_to_response(err),
+            };
             yield response;
             return;
         }
@@ -70,7 +102,15 @@ fn handle_group_request(
             watch_key_req.version
         );
 
-        let (sender, mut receiver) = mpsc::unbounded();
+        let watch_registry = server.node.watch_registry();
+        let permit = match watch_registry.register(remote_addr) {
+            Ok(permit) => permit,
+            Err(err) => {
+                yield error_to_response(err);
+                return;
+            }
+        };
+        let (sender, mut receiver) = watch_registry.channel(permit);
         exec_ctx.watch_event_sender = Some(sender);
         if let Err(err) = server.node.execute_request(&exec_ctx, &request).await {
             yield error_to_response(err);
@@ -80,13 +120,13 @@ fn handle_group_request(
         // Clear the ownership of sender.
         exec_ctx.watch_event_sender = None;
 
-        // scan the key to obtain an version.
+        // scan the key to obtain an version, the following keys of shard are excluded.
         let scan_req = ShardScanRequest {
             shard_id: watch_key_req.shard_id,
             start_version: TXN_MAX_VERSION,
             limit: 0,
             limit_bytes: 0,
-            end_key: None,
+            end_key: Some(sekas_rock::lexical::lexical_next(&watch_key_req.key)),
             exclude_end_key: true,
             exclude_start_key: false,
             prefix: None,
@@ -94,6 +134,11 @@ fn handle_group_request(
             include_raw_data: true,
             ignore_txn_intent: true,
             allow_scan_moving_shard: true,
+            reverse: false,
+            causal_token: 0,
+            txn_id: 0,
+            max_staleness_ms: 0,
+            seal_data: false,
         };
         let group_scan_req = GroupRequest {
             group_id: request.group_id,
@@ -101,6 +146,7 @@ fn handle_group_request(
             request: Some(GroupRequestUnion {
                 request: Some(ShardRequest::Scan(scan_req)),
             }),
+            ..Default::default()
         };
         let resp = match server.node.execute_request(&exec_ctx, &group_scan_req).await {
             Ok(resp) => resp,
@@ -150,6 +196,7 @@ fn handle_group_request(
             let value = Value {
                 content: event.value.map(Vec::from),
                 version: event.version,
+                ..Default::default()
             };
             let watch_key_resp = WatchKeyResponse {
                 result: WatchResult::ValueUpdated as i32,
@@ -163,10 +210,16 @@ fn handle_group_request(
             };
         }
 
-        let watch_key_resp = WatchKeyResponse {
-            result: WatchResult::ShardMoved as i32,
-            ..Default::default()
+        let result = if receiver.is_lagging() {
+            trace!("watch key is lagging, shard {} key {}",
+                watch_key_req.shard_id,
+                sekas_rock::ascii::escape_bytes(&watch_key_req.key),
+            );
+            WatchResult::Lagging
+        } else {
+            WatchResult::ShardMoved
         };
+        let watch_key_resp = WatchKeyResponse { result: result as i32, ..Default::default() };
         yield GroupResponse {
             response: Some(GroupResponseUnion {
                 response: Some(ShardResponse::WatchKey(watch_key_resp))
@@ -176,6 +229,153 @@ fn handle_group_request(
     }
 }
 
+/// Serve the scan request in frames, so that the value sets of a large scan are
+/// never built into one response.
+///
+/// Each frame is scanned by a separate execution at the same version, no
+/// engine iterator is held between frames, so the scan is released promptly
+/// once the client cancels the stream. All frames except the last one set
+/// `has_more`.
+///
+/// The frames are kept within `max_message_bytes`. A frame exceeding it is
+/// scanned again with half of the keys, until it holds a single key.
+fn handle_scan_request(
+    server: Server,
+    request: GroupRequest,
+    mut scan_req: ShardScanRequest,
+    deadline: Option<Instant>,
+    max_message_bytes: usize,
+) -> impl futures::Stream<Item = GroupResponse> {
+    stream! {
+        let scan_registry = server.node.scan_registry();
+        let frame_bytes = frame_limit_bytes(scan_registry.frame_bytes(), max_message_bytes);
+        let quota = scan_registry.quota();
+        let mut exec_ctx = ExecCtx::default();
+        exec_ctx.scan_quota = Some(quota.clone());
+        // The max keys of each frame, it is set once a frame is split.
+        let mut frame_keys = 0;
+        loop {
+            let mut frame_req = scan_req.clone();
+            if frame_req.limit_bytes == 0 || frame_req.limit_bytes > frame_bytes {
+                frame_req.limit_bytes = frame_bytes;
+            }
+            if frame_keys != 0 && (frame_req.limit == 0 || frame_req.limit > frame_keys) {
+                frame_req.limit = frame_keys;
+            }
+            let group_req = GroupRequest {
+                request: Some(GroupRequestUnion { request: Some(ShardRequest::Scan(frame_req)) }),
+                ..request.clone()
+            };
+            let mut resp = match server.node.execute_request(&exec_ctx, &group_req).await {
+                Ok(resp) => resp,
+                Err(err) => {
+                    yield error_to_response(err);
+                    return;
+                }
+            };
+            let frame_size = resp.encoded_len();
+            let Some(ShardResponse::Scan(frame)) =
+                resp.response.as_mut().and_then(|resp| resp.response.as_mut())
+            else {
+                yield error_to_response(Error::InvalidData("ShardScanResponse is required".into()));
+                return;
+            };
+            if let Err(err) = check_message_size(frame_size, max_message_bytes) {
+                quota.release();
+                if frame.data.len() <= 1 {
+                    yield error_to_response(err);
+                    return;
+                }
+                frame_keys = frame.data.len() as u64 / 2;
+                continue;
+            }
+            let finished = !frame.has_more || !scan_req.advance(frame);
+            if scan_req.seal_data {
+                if let Err(err) = seal_scan_frame(server.node.data_cipher(), frame) {
+                    quota.release();
+                    yield error_to_response(err);
+                    return;
+                }
+            }
+            yield resp;
+            quota.release();
+            if finished {
+                return;
+            }
+            if deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+                yield error_to_response(Error::DeadlineExceeded("scan the next frame".into()));
+                return;
+            }
+        }
+    }
+}
+
+/// Seal the value sets of the frame if the encryption is enabled, see
+/// `ShardScanRequest::seal_data`.
+fn seal_scan_frame(
+    cipher: Option<&Arc<dyn DataCipher>>,
+    frame: &mut ShardScanResponse,
+) -> crate::Result<()> {
+    let Some(cipher) = cipher else { return Ok(()) };
+    if frame.data.is_empty() {
+        return Ok(());
+    }
+    let data = std::mem::take(&mut frame.data);
+    frame.sealed.push(crate::cipher::seal_value_sets(cipher.as_ref(), data)?);
+    Ok(())
+}
+
+/// The value sets of a frame are limited to half of the max message bytes,
+/// since the frame might exceed the limit bytes by the last value set.
+fn frame_limit_bytes(frame_bytes: usize, max_message_bytes: usize) -> u64 {
+    if max_message_bytes == 0 {
+        frame_bytes as u64
+    } else {
+        frame_bytes.min(max_message_bytes / 2).max(1) as u64
+    }
+}
+
+/// `Error::MessageTooLarge` is returned if the size exceeds the limit, `0`
+/// means unlimited.
+fn check_message_size(size: usize, limit: usize) -> crate::Result<()> {
+    if limit != 0 && size > limit {
+        return Err(Error::MessageTooLarge { size: size as u64, limit: limit as u64 });
+    }
+    Ok(())
+}
+
+/// The max bytes of the responses, it is the smaller one of the limits of the
+/// node and the client, see [`MAX_MESSAGE_BYTES_HEADER`].
+fn negotiated_message_bytes<T>(request: &Request<T>, node_limit: usize) -> usize {
+    let client_limit = request
+        .metadata()
+        .get(MAX_MESSAGE_BYTES_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or_default();
+    match (client_limit, node_limit) {
+        (0, limit) | (limit, 0) => limit,
+        (client_limit, node_limit) => client_limit.min(node_limit),
+    }
+}
+
+/// The deadline of the request, parsed from the `grpc-timeout` header.
+fn request_deadline<T>(request: &Request<T>) -> Option<Instant> {
+    let timeout = request.metadata().get("grpc-timeout")?.to_str().ok()?;
+    let (value, unit) = timeout.split_at(timeout.len().checked_sub(1)?);
+    let value = value.parse::<u64>().ok()?;
+    let timeout = match unit {
+        "H" => Duration::from_secs(value * 3600),
+        "M" => Duration::from_secs(value * 60),
+        "S" => Duration::from_secs(value),
+        "m" => Duration::from_millis(value),
+        "u" => Duration::from_micros(value),
+        "n" => Duration::from_nanos(value),
+        _ => return None,
+    };
+    Some(Instant::now() + timeout)
+}
+
 #[crate::async_trait]
 impl node_server::Node for Server {
     type GroupStream = GroupStream;
@@ -184,8 +384,19 @@ impl node_server::Node for Server {
         &self,
         request: Request<GroupRequest>,
     ) -> Result<Response<Self::GroupStream>, Status> {
-        let group_response_stream =
-            Box::pin(handle_group_request(self.clone(), request.into_inner()));
+        let remote_addr = request.remote_addr();
+        let deadline = request_deadline(&request);
+        let origin = request.metadata().get(INTERNAL_ORIGIN_HEADER).map(|v| v.as_bytes());
+        let internal_origin = self.node.is_internal_origin(origin);
+        let max_message_bytes = negotiated_message_bytes(&request, self.node.max_message_bytes());
+        let group_response_stream = Box::pin(handle_group_request(
+            self.clone(),
+            request.into_inner(),
+            remote_addr,
+            deadline,
+            internal_origin,
+            max_message_bytes,
+        ));
         Ok(Response::new(GroupStream { inner: group_response_stream }))
     }
 
@@ -210,6 +421,35 @@ impl node_server::Node for Server {
             node_admin_request::Request::Heartbeat(req) => {
                 node_admin_response::Response::Heartbeat(self.root_heartbeat(req).await?)
             }
+            node_admin_request::Request::SearchRaftLog(req) => {
+                node_admin_response::Response::SearchRaftLog(self.node.search_raft_log(&req)?)
+            }
+            node_admin_request::Request::CompactGroup(req) => {
+                node_admin_response::Response::CompactGroup(self.node.compact_group(&req).await?)
+            }
+            node_admin_request::Request::GetCapabilities(_) => {
+                node_admin_response::Response::GetCapabilities(self.node.get_capabilities())
+            }
+            node_admin_request::Request::GetNodeStatus(_) => {
+                let status = self.node.node_status().await;
+                node_admin_response::Response::GetNodeStatus(GetNodeStatusResponse {
+                    status: Some(status),
+                })
+            }
+            node_admin_request::Request::CompactReplica(req) => {
+                node_admin_response::Response::CompactReplica(self.node.compact_replica(req).await?)
+            }
+            node_admin_request::Request::PinGc(req) => {
+                node_admin_response::Response::PinGc(self.node.pin_gc(&req).await?)
+            }
+            node_admin_request::Request::SetLogFilter(req) => {
+                node_admin_response::Response::SetLogFilter(self.node.set_log_filter(&req)?)
+            }
+            node_admin_request::Request::ResolveQuarantine(req) => {
+                node_admin_response::Response::ResolveQuarantine(
+                    self.node.resolve_quarantine(&req).await?,
+                )
+            }
         };
         Ok(Response::new(NodeAdminResponse { response: Some(resp) }))
     }
@@ -246,6 +486,30 @@ impl node_server::Node for Server {
                 self.node.move_shard(MoveShardEvent::Commit, desc).await?;
                 move_shard_response::Response::MoveOut(MoveOutResponse::default())
             }
+            move_shard_request::Request::CancelMove(req) => {
+                let Some(desc) = req.desc else {
+                    return Err(Status::invalid_argument(
+                        "CancelMoveRequest::desc is empty".to_owned(),
+                    ));
+                };
+                record_latency!(take_migrate_request_metrics());
+                self.node.cancel_move(desc).await?;
+                move_shard_response::Response::CancelMove(CancelMoveResponse::default())
+            }
+            move_shard_request::Request::AbortMove(req) => {
+                let Some(desc) = req.desc else {
+                    return Err(Status::invalid_argument(
+                        "AbortMoveRequest::desc is empty".to_owned(),
+                    ));
+                };
+                record_latency!(take_migrate_request_metrics());
+                self.node.abort_move(desc).await?;
+                move_shard_response::Response::AbortMove(AbortMoveResponse::default())
+            }
+            move_shard_request::Request::Ingest(req) => {
+                self.node.ingest(req).await?;
+                move_shard_response::Response::Ingest(IngestResponse::default())
+            }
         };
         Ok(Response::new(MoveShardResponse { response: Some(resp) }))
     }
@@ -291,6 +555,8 @@ impl Server {
         use piggyback_request::Info as Request;
         use piggyback_response::Info as Response;
         record_latency!(take_root_heartbeat_request_metrics());
+        self.node.clock_skew().observe_cluster_clock(request.timestamp);
+        self.node.refresh_all_version_retention().await;
         let mut piggybacks_resps = Vec::with_capacity(request.piggybacks.len());
 
         for piggyback in request.piggybacks {
@@ -309,13 +575,23 @@ impl Server {
                 Request::CollectScheduleState(req) => {
                     Response::CollectScheduleState(self.node.collect_schedule_state(&req).await)
                 }
+                Request::ConfigureTransfer(req) => {
+                    Response::ConfigureTransfer(self.node.configure_transfer(&req))
+                }
+                Request::ConfigureRaft(req) => {
+                    Response::ConfigureRaft(self.node.configure_raft(&req))
+                }
+                Request::CollectNodeStatus(_) => {
+                    let status = self.node.node_status().await;
+                    Response::CollectNodeStatus(CollectNodeStatusResponse { status: Some(status) })
+                }
             };
             piggybacks_resps.push(PiggybackResponse { info: Some(resp) });
         }
 
         let root = self.node.get_root().await;
         Ok(HeartbeatResponse {
-            timestamp: request.timestamp,
+            timestamp: self.node.clock_skew().clock().now_nanos(),
             root_epoch: root.epoch,
             piggybacks: piggybacks_resps,
         })
@@ -13,16 +13,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use prost::Message;
 use sekas_api::server::v1::*;
 use tonic::{Request, Response, Status};
 
 use super::metrics::*;
-use crate::root::Watcher;
+use crate::root::{
+    group_page_token, node_page_token, replica_state_page_token, CatalogStream, Page, Watcher,
+};
 use crate::{record_latency, Error, Result, Server};
 
 #[tonic::async_trait]
 impl root_server::Root for Server {
     type WatchStream = Watcher;
+    type ReplicateCatalogStream = CatalogStream;
 
     async fn admin(&self, req: Request<AdminRequest>) -> Result<Response<AdminResponse>, Status> {
         record_latency!(take_admin_request_metrics());
@@ -47,15 +51,22 @@ impl root_server::Root for Server {
     ) -> Result<Response<JoinNodeResponse>, Status> {
         record_latency!(take_join_request_metrics());
         let request = request.into_inner();
+        if let Some(rejection) = self.wrap(self.root.check_join(&request).await).await? {
+            return Ok(Response::new(JoinNodeResponse {
+                rejection: Some(rejection),
+                ..Default::default()
+            }));
+        }
         let capacity = request
             .capacity
             .ok_or_else(|| Error::InvalidArgument("capacity is required".into()))?;
         let (cluster_id, node, root) =
-            self.wrap(self.root.join(request.addr, capacity).await).await?;
+            self.wrap(self.root.join(request.addr, capacity, request.labels).await).await?;
         Ok::<Response<JoinNodeResponse>, Status>(Response::new(JoinNodeResponse {
             cluster_id,
             node_id: node.id,
             root: Some(root),
+            rejection: None,
         }))
     }
 
@@ -65,8 +76,8 @@ impl root_server::Root for Server {
     ) -> Result<Response<ReportResponse>, Status> {
         record_latency!(take_report_request_metrics());
         let request = request.into_inner();
-        self.wrap(self.root.report(request.updates).await).await?;
-        Ok(Response::new(ReportResponse {}))
+        let resp = self.wrap(self.root.report(request.updates).await).await?;
+        Ok(Response::new(resp))
     }
 
     async fn alloc_replica(
@@ -75,8 +86,9 @@ impl root_server::Root for Server {
     ) -> Result<Response<AllocReplicaResponse>, Status> {
         record_latency!(take_alloc_replica_request_metrics());
         let req = request.into_inner();
+        let purpose = req.purpose();
         let replicas = self
-            .wrap(self.root.alloc_replica(req.group_id, req.epoch, req.num_required).await)
+            .wrap(self.root.alloc_replica(req.group_id, req.epoch, req.num_required, purpose).await)
             .await?;
         Ok(Response::new(AllocReplicaResponse { replicas }))
     }
@@ -90,6 +102,17 @@ impl root_server::Root for Server {
         let base_txn_id = self.wrap(self.root.alloc_txn_id(req.num_required).await).await?;
         Ok(Response::new(AllocTxnIdResponse { base_txn_id, num: req.num_required }))
     }
+
+    async fn replicate_catalog(
+        &self,
+        request: Request<ReplicateCatalogRequest>,
+    ) -> Result<Response<Self::ReplicateCatalogStream>, Status> {
+        record_latency!(take_replicate_catalog_request_metrics());
+        let req = request.into_inner();
+        let stream =
+            self.wrap(self.root.replicate_catalog(req.incarnation, req.from_seq).await).await?;
+        Ok(Response::new(stream))
+    }
 }
 
 impl Server {
@@ -131,8 +154,9 @@ impl Server {
                 let res = self.handle_create_table(req).await?;
                 Response::CreateTable(res)
             }
-            Request::UpdateTable(_req) => {
-                todo!()
+            Request::UpdateTable(req) => {
+                let res = self.handle_update_table(req).await?;
+                Response::UpdateTable(res)
             }
             Request::DeleteTable(req) => {
                 let res = self.handle_delete_table(req).await?;
@@ -150,6 +174,121 @@ impl Server {
                 let res = self.handle_statement(req).await?;
                 Response::Statement(res)
             }
+            Request::MigrationStatus(req) => {
+                let res = self.handle_migration_status(req).await?;
+                Response::MigrationStatus(res)
+            }
+            Request::CancelMigration(req) => {
+                let res = self.handle_cancel_migration(req).await?;
+                Response::CancelMigration(res)
+            }
+            Request::ApproveAction(req) => {
+                let res = self.handle_approve_action(req).await?;
+                Response::ApproveAction(res)
+            }
+            Request::TableStats(req) => {
+                let res = self.handle_table_stats(req).await?;
+                Response::TableStats(res)
+            }
+            Request::SplitShard(req) => {
+                let res = self.handle_split_shard(req).await?;
+                Response::SplitShard(res)
+            }
+            Request::PromoteCatalog(_req) => {
+                let cluster_epoch = self.root.promote_catalog().await?;
+                Response::PromoteCatalog(PromoteCatalogResponse { cluster_epoch })
+            }
+            Request::GetRawGroupDesc(req) => {
+                let (raw, desc, confirm_token) = self.root.get_raw_group_desc(req.group_id).await?;
+                Response::GetRawGroupDesc(GetRawGroupDescResponse { raw, desc, confirm_token })
+            }
+            Request::PutRawGroupDesc(req) => {
+                let res = self.handle_put_raw_group_desc(req).await?;
+                Response::PutRawGroupDesc(res)
+            }
+            Request::GetRawShardDesc(req) => {
+                let (raw, desc, group_epoch, confirm_token) =
+                    self.root.get_raw_shard_desc(req.group_id, req.shard_id).await?;
+                Response::GetRawShardDesc(GetRawShardDescResponse {
+                    raw,
+                    desc,
+                    group_epoch,
+                    confirm_token,
+                })
+            }
+            Request::PutRawShardDesc(req) => {
+                let res = self.handle_put_raw_shard_desc(req).await?;
+                Response::PutRawShardDesc(res)
+            }
+            Request::GetRawNodeDesc(req) => {
+                let (raw, desc, confirm_token) = self.root.get_raw_node_desc(req.node_id).await?;
+                Response::GetRawNodeDesc(GetRawNodeDescResponse { raw, desc, confirm_token })
+            }
+            Request::PutRawNodeDesc(req) => {
+                let res = self.handle_put_raw_node_desc(req).await?;
+                Response::PutRawNodeDesc(res)
+            }
+            Request::ListTopologyEvents(_req) => {
+                let events = self.root.list_topology_events().await?;
+                Response::ListTopologyEvents(ListTopologyEventsResponse { events })
+            }
+            Request::ListActiveTxns(req) => {
+                let res = self.root.list_active_txns(req.limit).await?;
+                Response::ListActiveTxns(res)
+            }
+            Request::CloneTable(req) => {
+                let res = self
+                    .root
+                    .clone_table(
+                        &req.src_database,
+                        &req.src_table,
+                        &req.dest_database,
+                        &req.dest_table,
+                    )
+                    .await?;
+                Response::CloneTable(res)
+            }
+            Request::CloneStatus(req) => {
+                let status = self.root.clone_status(req.table_id).await?;
+                Response::CloneStatus(CloneStatusResponse { status })
+            }
+            Request::ExportTables(req) => {
+                let manifest = self.root.export_tables(&req.tables, &req.dest).await?;
+                Response::ExportTables(ExportTablesResponse { manifest: Some(manifest) })
+            }
+            Request::ImportTables(req) => {
+                Response::ImportTables(self.root.import_tables(&req.src).await?)
+            }
+            Request::CompactTable(req) => {
+                Response::CompactTable(self.root.compact_table(&req.database, &req.table).await?)
+            }
+            Request::ListGroups(req) => {
+                let res = self.handle_list_groups(req).await?;
+                Response::ListGroups(res)
+            }
+            Request::ListNodes(req) => {
+                let res = self.handle_list_nodes(req).await?;
+                Response::ListNodes(res)
+            }
+            Request::ListReplicaStates(req) => {
+                let res = self.handle_list_replica_states(req).await?;
+                Response::ListReplicaStates(res)
+            }
+            Request::ListSchedulePasses(_req) => {
+                let passes = self.root.list_schedule_passes()?;
+                Response::ListSchedulePasses(ListSchedulePassesResponse { passes })
+            }
+            Request::TriggerSchedulePass(_req) => {
+                let pass = self.root.trigger_schedule_pass().await?;
+                Response::TriggerSchedulePass(TriggerSchedulePassResponse { pass: Some(pass) })
+            }
+            Request::ConfigLogFilter(req) => {
+                let res = self
+                    .root
+                    .config_log_filter(req.node_id, req.request.unwrap_or_default())
+                    .await?;
+                Response::ConfigLogFilter(res)
+            }
         };
         Ok(res)
     }
@@ -158,7 +297,7 @@ impl Server {
         &self,
         req: CreateDatabaseRequest,
     ) -> Result<CreateDatabaseResponse> {
-        let desc = self.root.create_database(req.name).await?;
+        let desc = self.root.create_database(req.name, req.request_id).await?;
         Ok(CreateDatabaseResponse { database: Some(desc) })
     }
 
@@ -177,20 +316,32 @@ impl Server {
 
     async fn handle_list_database(
         &self,
-        _req: ListDatabasesRequest,
+        req: ListDatabasesRequest,
     ) -> Result<ListDatabasesResponse> {
         let databases = self.root.list_database().await?;
-        Ok(ListDatabasesResponse { databases })
+        let (mut databases, has_more) =
+            paginate_by_name(databases, |db| &db.name, &req.start_after, req.limit);
+        let truncated = truncate_by_bytes(&mut databases, self.node.max_message_bytes());
+        Ok(ListDatabasesResponse { databases, has_more: has_more || truncated })
     }
 
     async fn handle_create_table(&self, req: CreateTableRequest) -> Result<CreateTableResponse> {
         let database = req
             .database
             .ok_or_else(|| Error::InvalidArgument("CreateTableRequest::database".to_owned()))?;
-        let desc = self.root.create_table(req.name, database.name).await?;
+        let desc =
+            self.root.create_table(req.name, database.name, req.properties, req.request_id).await?;
         Ok(CreateTableResponse { table: Some(desc) })
     }
 
+    async fn handle_update_table(&self, req: UpdateTableRequest) -> Result<UpdateTableResponse> {
+        let database = req.database.ok_or_else(|| {
+            Error::InvalidArgument("UpdateTableRequest::database is required".to_owned())
+        })?;
+        let table = self.root.update_table(&req.name, &database, req.properties).await?;
+        Ok(UpdateTableResponse { table: Some(table) })
+    }
+
     async fn handle_delete_table(&self, req: DeleteTableRequest) -> Result<DeleteTableResponse> {
         let database = req.database.ok_or_else(|| {
             Error::InvalidArgument("DeleteTableRequest::database is required".to_owned())
@@ -212,7 +363,85 @@ impl Server {
             Error::InvalidArgument("ListTableRequest::database is required".to_owned())
         })?;
         let tables = self.root.list_table(&database).await?;
-        Ok(ListTablesResponse { tables })
+        let (mut tables, has_more) =
+            paginate_by_name(tables, |table| &table.name, &req.start_after, req.limit);
+        let truncated = truncate_by_bytes(&mut tables, self.node.max_message_bytes());
+        Ok(ListTablesResponse { tables, has_more: has_more || truncated })
+    }
+
+    async fn handle_list_groups(&self, req: ListGroupsRequest) -> Result<ListGroupsResponse> {
+        let page = self.root.list_groups(&req.filter.unwrap_or_default()).await?;
+        let (groups, next_page_token) =
+            truncate_page(page, self.node.max_message_bytes(), group_page_token);
+        Ok(ListGroupsResponse { groups, next_page_token })
+    }
+
+    async fn handle_list_nodes(&self, req: ListNodesRequest) -> Result<ListNodesResponse> {
+        let page = self.root.list_nodes(&req.filter.unwrap_or_default()).await?;
+        let (nodes, next_page_token) =
+            truncate_page(page, self.node.max_message_bytes(), node_page_token);
+        Ok(ListNodesResponse { nodes, next_page_token })
+    }
+
+    async fn handle_list_replica_states(
+        &self,
+        req: ListReplicaStatesRequest,
+    ) -> Result<ListReplicaStatesResponse> {
+        let page = self.root.list_replica_states(&req.filter.unwrap_or_default()).await?;
+        let (states, next_page_token) =
+            truncate_page(page, self.node.max_message_bytes(), replica_state_page_token);
+        Ok(ListReplicaStatesResponse { states, next_page_token })
+    }
+
+    async fn handle_table_stats(&self, req: TableStatsRequest) -> Result<TableStatsResponse> {
+        let database = req.database.ok_or_else(|| {
+            Error::InvalidArgument("TableStatsRequest::database is required".to_owned())
+        })?;
+        let tables = self.root.table_stats(&database).await?;
+        Ok(TableStatsResponse { tables })
+    }
+
+    async fn handle_split_shard(
+        &self,
+        req: ManualSplitShardRequest,
+    ) -> Result<ManualSplitShardResponse> {
+        let shards = self.root.split_shard(req.shard_id, req.split_key).await?;
+        Ok(ManualSplitShardResponse { shards })
+    }
+
+    async fn handle_put_raw_group_desc(
+        &self,
+        req: PutRawGroupDescRequest,
+    ) -> Result<PutRawGroupDescResponse> {
+        let desc = req.desc.ok_or_else(|| {
+            Error::InvalidArgument("PutRawGroupDescRequest::desc is required".to_owned())
+        })?;
+        self.root.put_raw_group_desc(desc, req.expected_epoch, &req.confirm_token).await?;
+        Ok(PutRawGroupDescResponse {})
+    }
+
+    async fn handle_put_raw_shard_desc(
+        &self,
+        req: PutRawShardDescRequest,
+    ) -> Result<PutRawShardDescResponse> {
+        let desc = req.desc.ok_or_else(|| {
+            Error::InvalidArgument("PutRawShardDescRequest::desc is required".to_owned())
+        })?;
+        self.root
+            .put_raw_shard_desc(req.group_id, desc, req.expected_epoch, &req.confirm_token)
+            .await?;
+        Ok(PutRawShardDescResponse {})
+    }
+
+    async fn handle_put_raw_node_desc(
+        &self,
+        req: PutRawNodeDescRequest,
+    ) -> Result<PutRawNodeDescResponse> {
+        let desc = req.desc.ok_or_else(|| {
+            Error::InvalidArgument("PutRawNodeDescRequest::desc is required".to_owned())
+        })?;
+        self.root.put_raw_node_desc(desc, &req.expected_raw, &req.confirm_token).await?;
+        Ok(PutRawNodeDescResponse {})
     }
 
     async fn handle_statement(&self, req: StatementRequest) -> Result<StatementResponse> {
@@ -220,9 +449,33 @@ impl Server {
         Ok(StatementResponse { json_body })
     }
 
+    async fn handle_migration_status(
+        &self,
+        req: MigrationStatusRequest,
+    ) -> Result<MigrationStatusResponse> {
+        let status = self.root.migration_status(req.shard_id).await?;
+        Ok(MigrationStatusResponse { status })
+    }
+
+    async fn handle_cancel_migration(
+        &self,
+        req: CancelMigrationRequest,
+    ) -> Result<CancelMigrationResponse> {
+        self.root.cancel_migration(req.shard_id).await?;
+        Ok(CancelMigrationResponse {})
+    }
+
+    async fn handle_approve_action(
+        &self,
+        req: ApproveActionRequest,
+    ) -> Result<ApproveActionResponse> {
+        self.root.approve_action(req.id).await?;
+        Ok(ApproveActionResponse {})
+    }
+
     async fn wrap<T>(&self, result: Result<T>) -> Result<T> {
         match result {
-            Err(Error::NotRootLeader(..) | Error::GroupNotFound(_)) => {
+            Err(Error::NotRootLeader(..) | Error::GroupNotFound(_) | Error::GroupRelocated(..)) => {
                 let roots = self.node.get_root().await;
                 Err(Error::NotRootLeader(roots, 0, None))
             }
@@ -242,3 +495,104 @@ impl Server {
         }
     }
 }
+
+/// Sort the descs by name and return at most `limit` of them whose names are
+/// greater than `start_after`, and whether there are more.
+fn paginate_by_name<T, F>(
+    mut descs: Vec<T>,
+    name: F,
+    start_after: &str,
+    limit: u64,
+) -> (Vec<T>, bool)
+where
+    F: Fn(&T) -> &str,
+{
+    descs.retain(|desc| start_after.is_empty() || name(desc) > start_after);
+    descs.sort_unstable_by(|a, b| name(a).cmp(name(b)));
+    let has_more = limit > 0 && descs.len() as u64 > limit;
+    if has_more {
+        descs.truncate(limit as usize);
+    }
+    (descs, has_more)
+}
+
+/// Truncate the descs of a page to keep the encoded page within the max bytes,
+/// the rest are returned by the following pages. At least one desc is kept.
+/// Returns whether any desc is truncated.
+fn truncate_by_bytes<T: Message>(descs: &mut Vec<T>, max_bytes: usize) -> bool {
+    if max_bytes == 0 {
+        return false;
+    }
+    let mut total_bytes = 0;
+    for (index, desc) in descs.iter().enumerate() {
+        // The tag and the length of the repeated field are encoded in front of each
+        // desc.
+        let len = desc.encoded_len();
+        total_bytes += 1 + prost::length_delimiter_len(len) + len;
+        if index > 0 && total_bytes > max_bytes {
+            descs.truncate(index);
+            return true;
+        }
+    }
+    false
+}
+
+/// Truncate the items of a page by the max bytes, the next page is resumed
+/// after the last kept item if any item is truncated.
+fn truncate_page<T, F>(page: Page<T>, max_bytes: usize, page_token: F) -> (Vec<T>, Vec<u8>)
+where
+    T: Message,
+    F: Fn(&T) -> Vec<u8>,
+{
+    let Page { mut items, next_page_token } = page;
+    if truncate_by_bytes(&mut items, max_bytes) {
+        let next_page_token = items.last().map(page_token).unwrap_or_default();
+        return (items, next_page_token);
+    }
+    (items, next_page_token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paginate_descs_by_name() {
+        let names = ["b", "a", "d", "c"].iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let name = |v: &String| -> &str { v };
+        let (page, has_more) = paginate_by_name(names.clone(), name, "", 0);
+        assert_eq!(page, vec!["a", "b", "c", "d"]);
+        assert!(!has_more);
+
+        let (page, has_more) = paginate_by_name(names.clone(), name, "", 2);
+        assert_eq!(page, vec!["a", "b"]);
+        assert!(has_more);
+        let (page, has_more) = paginate_by_name(names.clone(), name, "b", 2);
+        assert_eq!(page, vec!["c", "d"]);
+        assert!(!has_more);
+        let (page, has_more) = paginate_by_name(names, name, "d", 2);
+        assert!(page.is_empty());
+        assert!(!has_more);
+    }
+
+    #[test]
+    fn truncate_descs_by_bytes() {
+        let table = |name: &str| TableDesc { name: name.to_owned(), ..Default::default() };
+        let descs = (0..10).map(|i| table(&format!("table-{i}"))).collect::<Vec<_>>();
+        let desc_bytes = descs[0].encoded_len() + 2;
+
+        let mut page = descs.clone();
+        assert!(!truncate_by_bytes(&mut page, 0));
+        assert_eq!(page.len(), 10);
+        assert!(!truncate_by_bytes(&mut page, desc_bytes * 10));
+        assert_eq!(page.len(), 10);
+
+        assert!(truncate_by_bytes(&mut page, desc_bytes * 3 + 1));
+        assert_eq!(page, descs[..3]);
+
+        // At least one desc is kept, even if it exceeds the limit.
+        let mut page = descs.clone();
+        assert!(truncate_by_bytes(&mut page, 1));
+        assert_eq!(page, descs[..1]);
+    }
+}
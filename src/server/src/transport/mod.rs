@@ -16,6 +16,7 @@
 
 mod discovery;
 mod resolver;
+mod throttle;
 
 use std::sync::Arc;
 
@@ -23,28 +24,52 @@ use sekas_client::*;
 
 pub(crate) use self::discovery::RootDiscovery;
 pub(crate) use self::resolver::AddressResolver;
+pub(crate) use self::throttle::TokenBucket;
 use crate::engine::StateEngine;
 use crate::Result;
 
 #[derive(Clone)]
 pub(crate) struct TransportManager {
     address_resolver: Arc<AddressResolver>,
+    /// The connections of the node-to-node and root traffic, the group
+    /// requests carry the secret of the cluster as the internal origin.
     conn_manager: ConnManager,
     root_client: RootClient,
+    /// The connections of the clients handed to the proxies, which share the
+    /// channels of `conn_manager` without the secret.
+    proxy_conn_manager: ConnManager,
+    proxy_root_client: RootClient,
     router: Router,
 }
 
 impl TransportManager {
-    pub(crate) async fn new(root_list: Vec<String>, state_engine: StateEngine) -> Self {
+    /// The group requests issued by this server are marked as internal if the
+    /// `cluster_secret` is set, the secret is never sent to the clients.
+    pub(crate) async fn new(
+        root_list: Vec<String>,
+        state_engine: StateEngine,
+        cluster_secret: Option<&str>,
+    ) -> Result<Self> {
         let discovery = Arc::new(RootDiscovery::new(root_list, state_engine));
-        let conn_manager = ConnManager::new();
-        let root_client = RootClient::new(discovery, conn_manager.clone());
+        let proxy_conn_manager = ConnManager::new();
+        let conn_manager = match cluster_secret {
+            Some(secret) => proxy_conn_manager.with_internal_origin(secret.as_bytes())?,
+            None => proxy_conn_manager.clone(),
+        };
+        let root_client = RootClient::new(discovery.clone(), conn_manager.clone());
+        let proxy_root_client = RootClient::new(discovery, proxy_conn_manager.clone());
         let router = Router::new(root_client.clone()).await;
         let address_resolver = Arc::new(AddressResolver::new(router.clone()));
-        TransportManager { address_resolver, conn_manager, root_client, router }
+        Ok(TransportManager {
+            address_resolver,
+            conn_manager,
+            root_client,
+            proxy_conn_manager,
+            proxy_root_client,
+            router,
+        })
     }
 
-    #[allow(dead_code)]
     #[inline]
     pub(crate) fn conn_manager(&self) -> &ConnManager {
         &self.conn_manager
@@ -75,6 +100,18 @@ impl TransportManager {
         )
     }
 
+    /// Build a client for the proxies, its requests don't carry the secret of
+    /// the cluster.
+    #[inline]
+    pub(crate) fn build_proxy_client(&self, opts: ClientOptions) -> SekasClient {
+        SekasClient::build(
+            opts,
+            self.router.clone(),
+            self.proxy_root_client.clone(),
+            self.proxy_conn_manager.clone(),
+        )
+    }
+
     #[inline]
     pub(crate) fn lazy_group_client(&self, group_id: u64) -> GroupClient {
         GroupClient::lazy(group_id, self.build_client(ClientOptions::default()))
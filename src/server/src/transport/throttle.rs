@@ -0,0 +1,120 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sekas_runtime::time::Instant;
+
+/// A token bucket limits the bytes per second of the bulk transfers of a node,
+/// such as pulling shard chunks and sending snapshots, so that they share the
+/// bandwidth instead of saturating the network.
+///
+/// The bytes are always granted, the caller should wait the returned delay
+/// before transferring the next bytes.
+#[derive(Clone)]
+pub struct TokenBucket {
+    inner: Arc<Mutex<BucketInner>>,
+}
+
+struct BucketInner {
+    /// The bytes per second, `0` means unlimited.
+    rate: u64,
+    /// The available bytes, it is negative if the bytes are borrowed.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let inner = BucketInner {
+            rate: bytes_per_sec,
+            tokens: bytes_per_sec as f64,
+            last_refill: Instant::now(),
+        };
+        TokenBucket { inner: Arc::new(Mutex::new(inner)) }
+    }
+
+    /// Change the bytes per second, it takes effect since the next acquiring.
+    pub fn set_rate(&self, bytes_per_sec: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.rate != bytes_per_sec {
+            inner.rate = bytes_per_sec;
+            inner.tokens = inner.tokens.min(bytes_per_sec as f64);
+        }
+    }
+
+    /// Take `bytes` from the bucket, returns the delay to wait until the
+    /// borrowed bytes are paid back.
+    pub fn acquire(&self, bytes: u64) -> Duration {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.rate == 0 {
+            return Duration::ZERO;
+        }
+
+        // At most one second of bytes are accumulated.
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(inner.last_refill).as_secs_f64();
+        let rate = inner.rate as f64;
+        inner.tokens = (inner.tokens + elapsed * rate).min(rate);
+        inner.last_refill = now;
+        inner.tokens -= bytes as f64;
+        if inner.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-inner.tokens / rate)
+        }
+    }
+
+    /// Take `bytes` from the bucket and wait until they are paid back.
+    pub async fn consume(&self, bytes: u64) {
+        let delay = self.acquire(bytes);
+        if !delay.is_zero() {
+            sekas_runtime::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket_unlimited() {
+        let bucket = TokenBucket::new(0);
+        assert_eq!(bucket.acquire(u64::MAX), Duration::ZERO);
+    }
+
+    #[test]
+    fn token_bucket_borrow_bytes() {
+        let bucket = TokenBucket::new(1024);
+        assert_eq!(bucket.acquire(1024), Duration::ZERO);
+        let delay = bucket.acquire(1024);
+        assert!(delay > Duration::from_millis(900), "{delay:?}");
+        assert!(delay <= Duration::from_secs(1), "{delay:?}");
+
+        // The borrowed bytes are shared by all acquirings.
+        let delay = bucket.acquire(512);
+        assert!(delay > Duration::from_millis(1400), "{delay:?}");
+    }
+
+    #[test]
+    fn token_bucket_set_rate() {
+        let bucket = TokenBucket::new(1024);
+        bucket.set_rate(0);
+        assert_eq!(bucket.acquire(4096), Duration::ZERO);
+        bucket.set_rate(1024);
+        assert!(bucket.acquire(1024) > Duration::ZERO);
+    }
+}
@@ -63,10 +63,22 @@ impl ClusterClient {
         client.create_replica(replica_id, desc).await.unwrap();
     }
 
+    pub async fn remove_replica(&self, node_id: u64, replica_id: u64, desc: GroupDesc) {
+        let node_addr = self.nodes.get(&node_id).unwrap();
+        let client = node_client_with_retry(node_addr).await;
+        client.remove_replica(replica_id, desc).await.unwrap();
+    }
+
     pub fn group(&self, group_id: u64) -> GroupClient {
         GroupClient::lazy(group_id, self.client.clone())
     }
 
+    pub fn root_client(&self) -> RootClient {
+        let discovery =
+            Arc::new(StaticServiceDiscovery::new(self.nodes.values().cloned().collect()));
+        RootClient::new(discovery, self.conn_manager.clone())
+    }
+
     pub async fn app_client(&self) -> SekasClient {
         self.client.clone()
     }
@@ -215,6 +227,21 @@ impl ClusterClient {
         panic!("group {group_id} does not have a leader");
     }
 
+    /// Wait until the group has a leader, the time is advanced by the virtual
+    /// clock in simulation mode, instead of the wall clock.
+    pub async fn wait_for_leader(&self, group_id: u64) -> u64 {
+        const TIMEOUT: Duration = Duration::from_secs(60);
+
+        let deadline = sekas_runtime::time::Instant::now() + TIMEOUT;
+        while sekas_runtime::time::Instant::now() < deadline {
+            if let Some(leader) = self.get_group_leader(group_id).await {
+                return leader;
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("group {group_id} does not have a leader after {TIMEOUT:?}");
+    }
+
     pub async fn group_remove_node(&self, group_id: u64, node_id: u64) -> Result<()> {
         if let Ok(state) = self.router.find_group(group_id) {
             for (_, replica) in state.replicas {
@@ -297,6 +324,9 @@ impl ClusterClient {
                 piggyback_response::Info::SyncRoot(_)
                 | piggyback_response::Info::CollectStats(_)
                 | piggyback_response::Info::CollectScheduleState(_)
+                | piggyback_response::Info::ConfigureTransfer(_)
+                | piggyback_response::Info::ConfigureRaft(_)
+                | piggyback_response::Info::CollectNodeStatus(_)
                 | piggyback_response::Info::CollectGroupDetail(_) => {}
                 piggyback_response::Info::CollectMovingShardState(resp) => {
                     return Ok(resp.clone());
@@ -329,6 +359,9 @@ impl ClusterClient {
                 piggyback_response::Info::SyncRoot(_)
                 | piggyback_response::Info::CollectStats(_)
                 | piggyback_response::Info::CollectScheduleState(_)
+                | piggyback_response::Info::ConfigureTransfer(_)
+                | piggyback_response::Info::ConfigureRaft(_)
+                | piggyback_response::Info::CollectNodeStatus(_)
                 | piggyback_response::Info::CollectMovingShardState(_) => {}
                 piggyback_response::Info::CollectGroupDetail(resp) => {
                     for state in &resp.replica_states {
@@ -342,10 +375,42 @@ impl ClusterClient {
         Ok(None)
     }
 
+    /// Collect the descriptor of the group from the node, it is only filled by
+    /// the leader replica.
+    pub async fn collect_group_desc(
+        &self,
+        group_id: u64,
+        node_id: u64,
+    ) -> Result<Option<GroupDesc>> {
+        let node_addr = self.nodes.get(&node_id).unwrap();
+        let client = NodeClient::connect(node_addr.to_string()).await?;
+        let resp = client
+            .root_heartbeat(HeartbeatRequest {
+                timestamp: 0,
+                piggybacks: vec![PiggybackRequest {
+                    info: Some(piggyback_request::Info::CollectGroupDetail(
+                        CollectGroupDetailRequest { groups: vec![group_id] },
+                    )),
+                }],
+            })
+            .await?;
+        for resp in &resp.piggybacks {
+            if let Some(piggyback_response::Info::CollectGroupDetail(resp)) = resp.info.as_ref() {
+                return Ok(resp.group_descs.iter().find(|desc| desc.id == group_id).cloned());
+            }
+        }
+        Ok(None)
+    }
+
     pub async fn get_shard_desc(&self, table_id: u64, key: &[u8]) -> Option<ShardDesc> {
         self.router.find_shard(table_id, key).ok().map(|(_, shard)| shard)
     }
 
+    #[inline]
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
     pub async fn get_router_group_state(&self, group_id: u64) -> Option<RouterGroupState> {
         self.router.find_group(group_id).ok()
     }
@@ -359,7 +424,10 @@ impl ClusterClient {
         self.router.find_group_by_shard(shard.id).ok()
     }
 
+    /// Wait until the groups serving the table have the voters of the
+    /// replication factor, which is bounded by the number of nodes.
     pub async fn assert_table_ready(&self, table_id: u64) {
+        let num_voters = self.nodes.len().min(3);
         let mut ready_group: HashSet<u64> = HashSet::default();
         for i in 0..255u8 {
             for _ in 0..1000 {
@@ -371,7 +439,7 @@ impl ClusterClient {
                     }
                 };
                 if ready_group.insert(state.id) {
-                    self.assert_num_group_voters(state.id, 3).await;
+                    self.assert_num_group_voters(state.id, num_voters).await;
                     info!("table {table_id} is ready");
                     break;
                 }
@@ -12,16 +12,23 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
 use log::info;
+use sekas_runtime::sim::net::{self, Fault};
 use sekas_runtime::{ExecutorConfig, ExecutorOwner, ShutdownNotifier};
+use sekas_server::node::move_shard::MoveShardFaults;
+use sekas_server::node::observer::ReplicaObservers;
 use sekas_server::*;
 use tempdir::TempDir;
 
 use super::client::node_client_with_retry;
+use super::simulation::{ProcessGuard, Simulation};
 use super::socket::next_n_avail_port;
 use crate::helper::socket::next_avail_port;
 
@@ -33,29 +40,105 @@ pub struct TestContext {
     root_cfg: RootConfig,
     replica_knobs: ReplicaTestingKnobs,
     raft_knobs: RaftTestingKnobs,
+    engine_knobs: EngineTestingKnobs,
+    watch_cfg: WatchConfig,
+    scan_cfg: ScanConfig,
+    txn_cfg: TxnConfig,
+    forward_cfg: ForwardConfig,
+    hot_key_cfg: HotKeyConfig,
+    proposal_queue_cfg: ProposalQueueConfig,
+    clock_offsets: HashMap<u64, i64>,
+    fake_versions: HashMap<u64, String>,
+    cluster_ids: HashMap<u64, String>,
+    cluster_secret: String,
+    no_secret_nodes: HashSet<u64>,
+    node_labels: HashMap<u64, Vec<String>>,
+    replica_observers: HashMap<u64, ReplicaObservers>,
+    shard_move_bytes_per_sec: u64,
+    snapshot_send_concurrency: usize,
+    shard_chunk_size: usize,
+    max_message_bytes: usize,
+    move_shard_faults: MoveShardFaults,
+    stall_write_intents: Arc<AtomicBool>,
+    apply_checkpoint_entries: u64,
+    resolve_intent_age_ms: u64,
+    gc_retention_ms: u64,
+    enable_get_raw_key: bool,
     disable_group_promoting: bool,
+    encryption_key_file: Option<PathBuf>,
+    enable_metrics_exporter: bool,
 
     tick_interval_ms: u64,
+    max_entries_per_round: usize,
 
+    addrs: HashMap<u64, String>,
+    metrics_addrs: HashMap<u64, String>,
     notifiers: HashMap<u64, ShutdownNotifier>,
     handles: HashMap<u64, std::thread::JoinHandle<()>>,
+
+    simulation: Option<Simulation>,
+    _guard: Option<ProcessGuard>,
 }
 
 #[allow(dead_code)]
 impl TestContext {
     pub fn new(prefix: &str) -> Self {
+        Self::new_with(prefix, None, Some(ProcessGuard::shared()))
+    }
+
+    /// Create a context in simulation mode, the timers and clocks of servers
+    /// and clients are driven by the virtual clock, the random decisions are
+    /// made by the seeded generator, and the network faults could be injected
+    /// between servers. The tasks are still scheduled by tokio, so the seed
+    /// doesn't replay the interleaving of tasks.
+    pub fn new_simulation(prefix: &str) -> Self {
+        Self::new_with(prefix, Some(Simulation::start(prefix)), None)
+    }
+
+    fn new_with(prefix: &str, simulation: Option<Simulation>, guard: Option<ProcessGuard>) -> Self {
         let root_dir = TempDir::new(prefix).unwrap();
         let mut ctx = TestContext {
             name: prefix.to_owned(),
             root_dir,
             num_cpus: 2,
             disable_group_promoting: false,
+            encryption_key_file: None,
+            enable_metrics_exporter: false,
             replica_knobs: ReplicaTestingKnobs::default(),
             raft_knobs: RaftTestingKnobs::default(),
+            engine_knobs: EngineTestingKnobs::default(),
+            watch_cfg: WatchConfig::default(),
+            scan_cfg: ScanConfig::default(),
+            txn_cfg: TxnConfig::default(),
+            forward_cfg: ForwardConfig::default(),
+            hot_key_cfg: HotKeyConfig::default(),
+            proposal_queue_cfg: ProposalQueueConfig::default(),
+            clock_offsets: HashMap::default(),
+            fake_versions: HashMap::default(),
+            cluster_ids: HashMap::default(),
+            cluster_secret: format!("secret-{}", std::process::id()),
+            no_secret_nodes: HashSet::default(),
+            node_labels: HashMap::default(),
+            replica_observers: HashMap::default(),
+            shard_move_bytes_per_sec: 0,
+            snapshot_send_concurrency: NodeConfig::default().snapshot_send_concurrency,
+            shard_chunk_size: NodeConfig::default().shard_chunk_size,
+            max_message_bytes: NodeConfig::default().max_message_bytes,
+            move_shard_faults: MoveShardFaults::default(),
+            stall_write_intents: Arc::default(),
+            apply_checkpoint_entries: ReplicaConfig::default().apply_checkpoint_entries,
+            resolve_intent_age_ms: ReplicaConfig::default().resolve_intent_age_ms,
+            gc_retention_ms: ReplicaConfig::default().gc_retention_ms,
+            enable_get_raw_key: true,
             root_cfg: RootConfig::default(),
             tick_interval_ms: 500,
+            max_entries_per_round: RaftConfig::default().max_entries_per_round,
+            addrs: HashMap::default(),
+            metrics_addrs: HashMap::default(),
             notifiers: HashMap::default(),
             handles: HashMap::default(),
+            simulation,
+            _guard: guard,
         };
         // Disable all balance by default.
         ctx.disable_all_balance();
@@ -78,6 +161,12 @@ impl TestContext {
         info!("{} shutdown cluster success", self.name);
     }
 
+    /// The data directory of the server `idx`.
+    #[allow(dead_code)]
+    pub fn server_dir(&self, idx: usize) -> PathBuf {
+        self.root_dir.path().join(idx.to_string())
+    }
+
     pub fn next_listen_address(&self) -> String {
         format!("127.0.0.1:{}", next_avail_port())
     }
@@ -86,6 +175,16 @@ impl TestContext {
         next_n_avail_port(n).into_iter().map(|port| format!("127.0.0.1:{port}")).collect()
     }
 
+    /// Expose the metrics of the servers started since then over HTTP.
+    pub fn enable_metrics_exporter(&mut self) {
+        self.enable_metrics_exporter = true;
+    }
+
+    /// The address of the metrics exporter of the server `idx`.
+    pub fn metrics_addr(&self, idx: u64) -> Option<String> {
+        self.metrics_addrs.get(&idx).cloned()
+    }
+
     pub fn mut_replica_testing_knobs(&mut self) -> &mut ReplicaTestingKnobs {
         &mut self.replica_knobs
     }
@@ -94,6 +193,176 @@ impl TestContext {
         &mut self.raft_knobs
     }
 
+    pub fn mut_engine_testing_knobs(&mut self) -> &mut EngineTestingKnobs {
+        &mut self.engine_knobs
+    }
+
+    pub fn mut_root_testing_knobs(&mut self) -> &mut RootTestingKnobs {
+        &mut self.root_cfg.testing_knobs
+    }
+
+    pub fn mut_watch_config(&mut self) -> &mut WatchConfig {
+        &mut self.watch_cfg
+    }
+
+    pub fn mut_scan_config(&mut self) -> &mut ScanConfig {
+        &mut self.scan_cfg
+    }
+
+    pub fn mut_txn_config(&mut self) -> &mut TxnConfig {
+        &mut self.txn_cfg
+    }
+
+    pub fn mut_forward_config(&mut self) -> &mut ForwardConfig {
+        &mut self.forward_cfg
+    }
+
+    pub fn mut_hot_key_config(&mut self) -> &mut HotKeyConfig {
+        &mut self.hot_key_cfg
+    }
+
+    pub fn mut_proposal_queue_config(&mut self) -> &mut ProposalQueueConfig {
+        &mut self.proposal_queue_cfg
+    }
+
+    /// Shift the wall clock of the server `idx`, it should be called before the
+    /// server is spawned.
+    pub fn set_clock_offset(&mut self, idx: usize, offset_ms: i64) {
+        self.clock_offsets.insert(idx as u64, offset_ms);
+    }
+
+    /// Fake the build version of the server `idx`, it should be called before
+    /// the server is spawned.
+    pub fn set_fake_version(&mut self, idx: usize, version: &str) {
+        self.fake_versions.insert(idx as u64, version.to_owned());
+    }
+
+    /// Set the cluster id of the server `idx` to bootstrap or join, it should
+    /// be called before the server is spawned.
+    #[allow(dead_code)]
+    pub fn set_cluster_id(&mut self, idx: usize, cluster_id: &str) {
+        self.cluster_ids.insert(idx as u64, cluster_id.to_owned());
+    }
+
+    /// Spawn the server `idx` without the cluster secret, it should be called
+    /// before the server is spawned.
+    #[allow(dead_code)]
+    pub fn clear_cluster_secret(&mut self, idx: usize) {
+        self.no_secret_nodes.insert(idx as u64);
+    }
+
+    /// The secret shared by the servers, see [`Config::cluster_secret`].
+    #[allow(dead_code)]
+    pub fn cluster_secret(&self) -> &str {
+        &self.cluster_secret
+    }
+
+    /// Label the server `idx`, it should be called before the server is
+    /// spawned.
+    pub fn set_node_labels(&mut self, idx: usize, labels: &[&str]) {
+        self.node_labels.insert(idx as u64, labels.iter().map(ToString::to_string).collect());
+    }
+
+    /// Limit the bandwidth shared by pulling shard chunks during moving shard
+    /// and sending snapshots.
+    pub fn set_shard_move_bytes_per_sec(&mut self, bytes_per_sec: u64) {
+        self.shard_move_bytes_per_sec = bytes_per_sec;
+    }
+
+    /// Limit the number of concurrent outgoing snapshot transfers per server.
+    pub fn set_snapshot_send_concurrency(&mut self, concurrency: usize) {
+        self.snapshot_send_concurrency = concurrency;
+    }
+
+    /// Limit the bytes of each shard chunk pulled during moving shard.
+    pub fn set_shard_chunk_size(&mut self, chunk_size: usize) {
+        self.shard_chunk_size = chunk_size;
+    }
+
+    /// Limit the bytes of each group request and response.
+    pub fn set_max_message_bytes(&mut self, max_message_bytes: usize) {
+        self.max_message_bytes = max_message_bytes;
+    }
+
+    /// The replica observers of the server `idx`, the observers could be
+    /// registered before or after the server is spawned.
+    pub fn replica_observers(&mut self, idx: usize) -> ReplicaObservers {
+        self.replica_observers.entry(idx as u64).or_default().clone()
+    }
+
+    /// The faults injected into the moving shard coordinators of all servers,
+    /// they could be changed after the servers are spawned.
+    pub fn move_shard_faults(&self) -> MoveShardFaults {
+        self.move_shard_faults.clone()
+    }
+
+    /// The switch to stall the write intents of all servers, as if the groups
+    /// can't make progress, it could be changed after the servers are spawned.
+    pub fn stall_write_intents(&self) -> Arc<AtomicBool> {
+        self.stall_write_intents.clone()
+    }
+
+    /// Limit the requests consumed and the entries applied by a group in a
+    /// scheduling round.
+    pub fn set_max_entries_per_round(&mut self, max_entries: usize) {
+        self.max_entries_per_round = max_entries;
+    }
+
+    /// Write an apply checkpoint for every `entries` applied entries.
+    pub fn set_apply_checkpoint_entries(&mut self, entries: u64) {
+        self.apply_checkpoint_entries = entries;
+    }
+
+    /// Resolve the intents older than `age_ms` in the background.
+    pub fn set_resolve_intent_age_ms(&mut self, age_ms: u64) {
+        self.resolve_intent_age_ms = age_ms;
+    }
+
+    /// Advance the GC watermark of the groups to the versions older than
+    /// `retention_ms` in the background.
+    pub fn set_gc_retention_ms(&mut self, retention_ms: u64) {
+        self.gc_retention_ms = retention_ms;
+    }
+
+    /// Reject the raw key state requests.
+    pub fn disable_get_raw_key(&mut self) {
+        self.enable_get_raw_key = false;
+    }
+
+    /// Encrypt the snapshot files of all servers with a shared key, it should
+    /// be called before the servers are spawned.
+    pub fn enable_encryption(&mut self) {
+        let key_file = self.root_dir.path().join("keys");
+        std::fs::write(&key_file, format!("test:{}\n", "5a".repeat(32))).unwrap();
+        self.encryption_key_file = Some(key_file);
+    }
+
+    /// Mirror the catalog of the primary cluster, it should be called before
+    /// the servers are spawned.
+    pub fn set_catalog_mirror_of(&mut self, primary: &str) {
+        self.root_cfg.catalog_mirror_of = Some(primary.to_owned());
+    }
+
+    /// Accept the unsafe admin requests, it should be called before the
+    /// servers are spawned.
+    pub fn enable_unsafe_admin(&mut self) {
+        self.root_cfg.enable_unsafe_admin = true;
+    }
+
+    /// Restrict the table exports into a directory shared by the servers, it
+    /// should be called before the servers are spawned. Returns the directory.
+    pub fn enable_export(&mut self) -> PathBuf {
+        let export_dir = self.root_dir.path().join("exports");
+        self.root_cfg.export_dir = Some(export_dir.display().to_string());
+        export_dir
+    }
+
+    /// Delete the tables still being provisioned after the timeout, it should
+    /// be called before the servers are spawned.
+    pub fn set_provisioning_timeout_sec(&mut self, timeout_sec: u64) {
+        self.root_cfg.provisioning_timeout_sec = timeout_sec;
+    }
+
     pub fn disable_replica_balance(&mut self) {
         self.root_cfg.enable_replica_balance = false;
     }
@@ -141,43 +410,116 @@ impl TestContext {
         join_list: Vec<String>,
         root: RootConfig,
     ) {
+        let cfg = self.build_config(idx, addr, init, join_list, root);
+        let notifier = ShutdownNotifier::new();
+        let shutdown = notifier.subscribe();
+        let name = self.name.clone();
+        let handle = thread::spawn(move || {
+            let owner = ExecutorOwner::new(1);
+            sekas_server::run(cfg, owner.executor(), shutdown).unwrap();
+            info!("{name} server {idx} is shutdown");
+        });
+        self.notifiers.insert(idx as u64, notifier);
+        self.handles.insert(idx as u64, handle);
+    }
+
+    /// Spawn a server which is expected to exit, the result of the server is
+    /// returned by the handle.
+    pub fn spawn_server_expect_exit(
+        &mut self,
+        idx: usize,
+        addr: &str,
+        join_list: Vec<String>,
+    ) -> thread::JoinHandle<sekas_server::Result<()>> {
+        let cfg = self.build_config(idx, addr, false, join_list, self.root_cfg.clone());
+        let notifier = ShutdownNotifier::new();
+        let shutdown = notifier.subscribe();
+        self.notifiers.insert(idx as u64, notifier);
+        thread::spawn(move || {
+            let owner = ExecutorOwner::new(1);
+            sekas_server::run(cfg, owner.executor(), shutdown)
+        })
+    }
+
+    fn build_config(
+        &mut self,
+        idx: usize,
+        addr: &str,
+        init: bool,
+        join_list: Vec<String>,
+        root: RootConfig,
+    ) -> Config {
         let addr = addr.to_owned();
+        self.addrs.insert(idx as u64, addr.clone());
         let name = idx.to_string();
         let root_dir = self.root_dir.path().join(name);
         let cpu_nums = self.num_cpus as u32;
-        let cfg = Config {
+        if self.enable_metrics_exporter && !self.metrics_addrs.contains_key(&(idx as u64)) {
+            let metrics_addr = self.next_listen_address();
+            self.metrics_addrs.insert(idx as u64, metrics_addr);
+        }
+        Config {
             root_dir,
             addr,
             cpu_nums,
             init,
             enable_proxy_service: false,
             join_list,
+            cluster_id: self.cluster_ids.get(&(idx as u64)).cloned(),
+            cluster_secret: (!self.no_secret_nodes.contains(&(idx as u64)))
+                .then(|| self.cluster_secret.clone()),
+            log_level: None,
             node: NodeConfig {
                 replica: ReplicaConfig {
+                    apply_checkpoint_entries: self.apply_checkpoint_entries,
+                    resolve_intent_age_ms: self.resolve_intent_age_ms,
+                    gc_retention_ms: self.gc_retention_ms,
+                    enable_get_raw_key: self.enable_get_raw_key,
                     testing_knobs: self.replica_knobs.clone(),
+                    hot_key: self.hot_key_cfg.clone(),
+                    proposal_queue: self.proposal_queue_cfg.clone(),
                     ..Default::default()
                 },
+                watch: self.watch_cfg.clone(),
+                scan: self.scan_cfg.clone(),
+                txn: self.txn_cfg.clone(),
+                forward: self.forward_cfg.clone(),
+                clock: ClockConfig {
+                    testing_offset_ms: self.clock_offsets.get(&(idx as u64)).cloned().unwrap_or(0),
+                    ..Default::default()
+                },
+                shard_move_bytes_per_sec: self.shard_move_bytes_per_sec,
+                snapshot_send_concurrency: self.snapshot_send_concurrency,
+                shard_chunk_size: self.shard_chunk_size,
+                max_message_bytes: self.max_message_bytes,
+                labels: self.node_labels.get(&(idx as u64)).cloned().unwrap_or_default(),
+                replica_observers: self.replica_observers.entry(idx as u64).or_default().clone(),
+                engine: EngineConfig {
+                    testing_knobs: self.engine_knobs.clone(),
+                    ..Default::default()
+                },
+                testing_knobs: NodeTestingKnobs {
+                    fake_version: self.fake_versions.get(&(idx as u64)).cloned(),
+                    move_shard_faults: self.move_shard_faults.clone(),
+                    stall_write_intents: self.stall_write_intents.clone(),
+                },
                 ..Default::default()
             },
             raft: RaftConfig {
                 tick_interval_ms: self.tick_interval_ms,
+                max_entries_per_round: self.max_entries_per_round,
                 testing_knobs: self.raft_knobs.clone(),
                 ..Default::default()
             },
             root,
             executor: ExecutorConfig::default(),
             db: DbConfig { max_background_jobs: 2, max_sub_compactions: 1, ..DbConfig::default() },
-        };
-        let notifier = ShutdownNotifier::new();
-        let shutdown = notifier.subscribe();
-        let name = self.name.clone();
-        let handle = thread::spawn(move || {
-            let owner = ExecutorOwner::new(1);
-            sekas_server::run(cfg, owner.executor(), shutdown).unwrap();
-            info!("{name} server {idx} is shutdown");
-        });
-        self.notifiers.insert(idx as u64, notifier);
-        self.handles.insert(idx as u64, handle);
+            encryption: EncryptionConfig { key_file: self.encryption_key_file.clone() },
+            metrics: MetricsConfig {
+                addr: self.metrics_addrs.get(&(idx as u64)).cloned(),
+                ..Default::default()
+            },
+        }
     }
 
     /// Create a set of servers and bootstrap all of them.
@@ -229,12 +571,41 @@ impl TestContext {
     }
 
     pub async fn wait_election_timeout(&self) {
-        tokio::time::sleep(Duration::from_millis(self.tick_interval_ms * 6)).await;
+        sekas_runtime::time::sleep(Duration::from_millis(self.tick_interval_ms * 6)).await;
+    }
+
+    /// The seed of simulation, `None` if the context is not in simulation mode.
+    pub fn simulation_seed(&self) -> Option<u64> {
+        self.simulation.as_ref().map(Simulation::seed)
+    }
+
+    /// Inject the fault to the raft messages sent from server `from` to server
+    /// `to`.
+    pub fn inject_fault(&self, from: u64, to: u64, fault: Fault) {
+        net::inject(self.server_addr(from), self.server_addr(to), fault);
+    }
+
+    /// Drop all raft messages between server `a` and server `b`.
+    pub fn partition(&self, a: u64, b: u64) {
+        net::partition(self.server_addr(a), self.server_addr(b));
+    }
+
+    /// Remove the faults between server `a` and server `b`.
+    pub fn heal(&self, a: u64, b: u64) {
+        net::heal(self.server_addr(a), self.server_addr(b));
+    }
+
+    fn server_addr(&self, idx: u64) -> &str {
+        assert!(self.simulation.is_some(), "{} is not in simulation mode", self.name);
+        self.addrs.get(&idx).unwrap_or_else(|| panic!("server {idx} is not spawned"))
     }
 }
 
 impl Drop for TestContext {
     fn drop(&mut self) {
+        // The simulation is stopped after the servers are shutdown, since the
+        // servers might wait the virtual timers during shutdown.
         self.shutdown();
+        self.simulation.take();
     }
 }
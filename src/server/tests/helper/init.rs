@@ -21,6 +21,10 @@ pub fn setup_panic_hook() {
     panic::set_hook(Box::new(move |panic_info| {
         // invoke the default handler and exit the process
         orig_hook(panic_info);
+        if sekas_server::raftgroup::is_apply_panic() {
+            // The replica is quarantined, see `test_apply_quarantine.rs`.
+            return;
+        }
         let backtrace = Backtrace::force_capture();
         error!("{:#?} \nbacktrace: \n{}", panic_info, backtrace);
         process::exit(1);
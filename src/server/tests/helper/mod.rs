@@ -15,5 +15,7 @@
 pub mod client;
 pub mod context;
 pub mod init;
+pub mod move_shard;
 pub mod runtime;
+pub mod simulation;
 pub mod socket;
@@ -0,0 +1,425 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The harness of moving shard tests.
+//!
+//! A shard is moved between two groups which have replicas in all nodes, the
+//! expected data of the shard is tracked by the harness, so the invariants
+//! could be asserted after a fault is injected into the moving shard
+//! coordinator, see [`MoveShardFaults`].
+
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::time::Duration;
+
+use log::{info, warn};
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::group_response_union::Response;
+use sekas_api::server::v1::*;
+use sekas_client::RetryState;
+use sekas_server::node::move_shard::{MoveShardFault, MoveShardFaultPoint, MoveShardFaults};
+
+use super::client::ClusterClient;
+use super::context::TestContext;
+
+pub const SRC_GROUP_ID: u64 = 100000;
+pub const DEST_GROUP_ID: u64 = 100001;
+pub const SHARD_ID: u64 = 10000000;
+
+/// The limit of each shard chunk, so that the shard is pulled in many chunks.
+const SHARD_CHUNK_SIZE: usize = 4 * 1024;
+
+const RETRY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+struct ExpectedValue {
+    value: Vec<u8>,
+    num_versions: usize,
+}
+
+/// A cluster with two groups, the shard is served by the source group before
+/// moving.
+pub struct MoveShardCluster {
+    pub c: ClusterClient,
+    pub shard_desc: ShardDesc,
+    faults: MoveShardFaults,
+    expected: BTreeMap<Vec<u8>, ExpectedValue>,
+}
+
+impl MoveShardCluster {
+    /// Bootstrap `num_nodes` servers and create the source and dest groups in
+    /// all of them.
+    pub async fn new(ctx: &mut TestContext, num_nodes: usize) -> Self {
+        ctx.disable_all_node_scheduler();
+        ctx.set_shard_chunk_size(SHARD_CHUNK_SIZE);
+        let nodes = ctx.bootstrap_servers(num_nodes).await;
+        let mut node_ids = nodes.keys().cloned().collect::<Vec<_>>();
+        node_ids.sort_unstable();
+        let c = ClusterClient::new(nodes).await;
+
+        let shard_desc = ShardDesc::whole(SHARD_ID, SHARD_ID);
+        create_group(&c, SRC_GROUP_ID, &node_ids, vec![shard_desc.clone()]).await;
+        create_group(&c, DEST_GROUP_ID, &node_ids, vec![]).await;
+        c.assert_group_leader(SRC_GROUP_ID).await;
+        c.assert_group_leader(DEST_GROUP_ID).await;
+        c.assert_group_contains_shard(SRC_GROUP_ID, SHARD_ID).await;
+
+        let faults = ctx.move_shard_faults();
+        MoveShardCluster { c, shard_desc, faults, expected: BTreeMap::default() }
+    }
+
+    pub fn key(i: u64) -> Vec<u8> {
+        format!("key-{i:06}").into_bytes()
+    }
+
+    /// The value of key `i` written at round `round`, it is padded so that a
+    /// chunk only holds a few keys.
+    pub fn value(i: u64, round: u64) -> Vec<u8> {
+        let mut value = format!("value-{i}-{round}-").into_bytes();
+        value.resize(256, b'x');
+        value
+    }
+
+    /// Write the keys in range at the round, the writes issued during moving
+    /// are forwarded to the dest group.
+    pub async fn load(&mut self, range: Range<u64>, round: u64) {
+        info!("load keys {range:?} at round {round} into shard {SHARD_ID}");
+        for i in range {
+            let key = Self::key(i);
+            let value = Self::value(i, round);
+            self.put(key.clone(), value.clone()).await;
+            let expected = self
+                .expected
+                .entry(key)
+                .or_insert(ExpectedValue { value: vec![], num_versions: 0 });
+            expected.value = value;
+            expected.num_versions += 1;
+        }
+    }
+
+    async fn put(&self, key: Vec<u8>, value: Vec<u8>) {
+        let put = PutRequest { key, value, ..Default::default() };
+        let req = Request::Write(ShardWriteRequest {
+            shard_id: SHARD_ID,
+            puts: vec![put],
+            ..Default::default()
+        });
+        let mut retry_state = RetryState::with_timeout_opt(Some(RETRY_TIMEOUT));
+        loop {
+            let mut group_client = self.c.group(self.owner_group_id());
+            match group_client.request(&req).await {
+                Ok(_) => return,
+                Err(err) => {
+                    warn!("write shard {SHARD_ID}: {err:?}");
+                    retry_state.force_retry().await.unwrap();
+                }
+            }
+        }
+    }
+
+    async fn get(&self, key: &[u8]) -> Option<Value> {
+        let req = Request::Get(ShardGetRequest {
+            shard_id: SHARD_ID,
+            start_version: u64::MAX,
+            user_key: key.to_owned(),
+            ..Default::default()
+        });
+        let mut retry_state = RetryState::with_timeout_opt(Some(RETRY_TIMEOUT));
+        loop {
+            let mut group_client = self.c.group(self.owner_group_id());
+            match group_client.request(&req).await {
+                Ok(Response::Get(resp)) => return resp.value,
+                Ok(resp) => panic!("invalid response type, Get is required: {resp:?}"),
+                Err(err) => {
+                    warn!("get shard {SHARD_ID}: {err:?}");
+                    retry_state.force_retry().await.unwrap();
+                }
+            }
+        }
+    }
+
+    /// The group serving the shard, in the view of root.
+    pub fn owner_group_id(&self) -> u64 {
+        self.c.router().find_group_by_shard(SHARD_ID).map(|s| s.id).unwrap_or(SRC_GROUP_ID)
+    }
+
+    pub fn faults(&self) -> &MoveShardFaults {
+        &self.faults
+    }
+
+    /// Inject the fault, and returns the number of former hits of the point.
+    pub fn inject(&self, point: MoveShardFaultPoint, fault: MoveShardFault) -> usize {
+        info!("inject {fault:?} at {point:?}");
+        self.faults.inject(point, fault);
+        self.faults.hits(point)
+    }
+
+    pub fn clear(&self, point: MoveShardFaultPoint) {
+        info!("clear fault at {point:?}");
+        self.faults.clear(point);
+    }
+
+    /// Wait until the point is hit again since `former_hits`.
+    pub async fn wait_hit(&self, point: MoveShardFaultPoint, former_hits: usize) {
+        for _ in 0..3000 {
+            if self.faults.hits(point) > former_hits {
+                return;
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the fault point {point:?} is not hit");
+    }
+
+    /// Start moving the shard by accepting it in the dest group, which is the
+    /// request issued by the root scheduler.
+    pub async fn start_move(&self) {
+        let mut retry_state = RetryState::with_timeout_opt(Some(RETRY_TIMEOUT));
+        loop {
+            let src_epoch = self.c.must_group_epoch(SRC_GROUP_ID).await;
+            let mut group_client = self.c.group(DEST_GROUP_ID);
+            match group_client.accept_shard(SRC_GROUP_ID, src_epoch, &self.shard_desc).await {
+                Ok(()) => break,
+                Err(err) => {
+                    warn!("accept shard {SHARD_ID} with src epoch {src_epoch}: {err:?}");
+                    retry_state.force_retry().await.unwrap();
+                }
+            }
+        }
+        info!("shard {SHARD_ID} is accepted by group {DEST_GROUP_ID}");
+    }
+
+    /// Acquire the shard in the source group directly, as the coordinator of
+    /// the dest group does.
+    pub async fn acquire_shard(&self) -> MoveShardDesc {
+        loop {
+            let desc = MoveShardDesc {
+                shard_desc: Some(self.shard_desc.clone()),
+                src_group_id: SRC_GROUP_ID,
+                src_group_epoch: self.c.must_group_epoch(SRC_GROUP_ID).await,
+                dest_group_id: DEST_GROUP_ID,
+                dest_group_epoch: self.c.must_group_epoch(DEST_GROUP_ID).await,
+            };
+            let mut group_client = self.c.group(SRC_GROUP_ID);
+            match group_client.acquire_shard(&desc).await {
+                Ok(()) => return desc,
+                Err(err) => {
+                    warn!("acquire shard {SHARD_ID}: {err:?}");
+                    sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        }
+    }
+
+    /// Wait until the shard is moved into the dest group and the moving states
+    /// of both groups are cleaned.
+    pub async fn wait_move_finished(&self) {
+        for _ in 0..3000 {
+            if self.c.group_contains_shard(DEST_GROUP_ID, SHARD_ID)
+                && !self.is_moving(DEST_GROUP_ID).await
+                && !self.is_moving(SRC_GROUP_ID).await
+            {
+                info!("shard {SHARD_ID} is moved to group {DEST_GROUP_ID}");
+                return;
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("moving shard {SHARD_ID} is not finished");
+    }
+
+    async fn is_moving(&self, group_id: u64) -> bool {
+        use collect_moving_shard_state_response::State;
+
+        let Some(node_id) = self.c.get_group_leader_node_id(group_id).await else {
+            return true;
+        };
+        match self.c.collect_moving_shard_state(group_id, node_id).await {
+            Ok(resp) => resp.state != State::None as i32,
+            Err(_) => true,
+        }
+    }
+
+    /// Transfer the leadership of the group, the moving shard state is
+    /// re-driven by the new leader.
+    pub async fn transfer_leader(&self, group_id: u64) {
+        let former_leader = self.c.wait_for_leader(group_id).await;
+        for _ in 0..100 {
+            if self.c.transfer_group_leader_randomly(group_id).await.is_ok() {
+                break;
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+        }
+        for _ in 0..1000 {
+            if self.c.get_group_leader(group_id).await.is_some_and(|id| id != former_leader) {
+                return;
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the leadership of group {group_id} is not transferred");
+    }
+
+    /// No key is lost: all keys are readable with the latest values, from the
+    /// group serving the shard.
+    pub async fn assert_no_key_lost(&self) {
+        for (key, expected) in &self.expected {
+            let value = self.get(key).await;
+            let content = value.and_then(|v| v.content);
+            assert_eq!(
+                content.as_ref(),
+                Some(&expected.value),
+                "the value of key {} is lost",
+                String::from_utf8_lossy(key)
+            );
+        }
+    }
+
+    /// No key is duplicated: each key is served by the dest group exactly once,
+    /// with all its versions, so the forwarded values are not ingested twice
+    /// nor shadow the newer versions. It should be called after the moving is
+    /// finished.
+    pub async fn assert_no_key_duplicated(&self) {
+        let value_sets = self.scan_all_versions(DEST_GROUP_ID).await;
+        let mut actual = BTreeMap::new();
+        for value_set in value_sets {
+            let key = String::from_utf8_lossy(&value_set.user_key).to_string();
+            assert!(
+                actual.insert(value_set.user_key.clone(), value_set).is_none(),
+                "key {key} is duplicated"
+            );
+        }
+        for (key, expected) in &self.expected {
+            let key_str = String::from_utf8_lossy(key);
+            let value_set = actual.remove(key).unwrap_or_else(|| panic!("key {key_str} is lost"));
+            let latest = value_set.values.iter().max_by_key(|v| v.version).unwrap();
+            assert_eq!(latest.content.as_ref(), Some(&expected.value), "key {key_str}");
+            assert_eq!(
+                value_set.values.len(),
+                expected.num_versions,
+                "the versions of key {key_str} are mismatched: {:?}",
+                value_set.values.iter().map(|v| v.version).collect::<Vec<_>>()
+            );
+        }
+        let unexpected =
+            actual.keys().map(|k| String::from_utf8_lossy(k).to_string()).collect::<Vec<_>>();
+        assert!(unexpected.is_empty(), "unexpected keys {unexpected:?}");
+    }
+
+    async fn scan_all_versions(&self, group_id: u64) -> Vec<ValueSet> {
+        let mut value_sets = Vec::new();
+        let mut start_key = None;
+        loop {
+            let req = Request::Scan(ShardScanRequest {
+                shard_id: SHARD_ID,
+                start_version: u64::MAX,
+                limit: 64,
+                start_key: start_key.clone(),
+                exclude_start_key: start_key.is_some(),
+                include_raw_data: true,
+                ..Default::default()
+            });
+            let mut retry_state = RetryState::with_timeout_opt(Some(RETRY_TIMEOUT));
+            let resp = loop {
+                match self.c.group(group_id).request(&req).await {
+                    Ok(Response::Scan(resp)) => break resp,
+                    Ok(resp) => panic!("invalid response type, Scan is required: {resp:?}"),
+                    Err(err) => {
+                        warn!("scan shard {SHARD_ID} of group {group_id}: {err:?}");
+                        retry_state.force_retry().await.unwrap();
+                    }
+                }
+            };
+            start_key = resp.data.last().map(|v| v.user_key.clone());
+            value_sets.extend(resp.data);
+            if !resp.has_more || start_key.is_none() {
+                return value_sets;
+            }
+        }
+    }
+
+    /// The descriptors and epochs of both groups in the view of root are
+    /// consistent with the leaders of the groups, and the shard is served by
+    /// the group which contains it. If the moving is `settled`, the shard is
+    /// contained by exactly one group.
+    pub async fn assert_descriptors_consistent(&self, settled: bool) {
+        let mut last_err = String::new();
+        for _ in 0..1000 {
+            match self.check_descriptors(settled).await {
+                Ok(()) => return,
+                Err(err) => last_err = err,
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the descriptors are inconsistent: {last_err}");
+    }
+
+    async fn check_descriptors(&self, settled: bool) -> Result<(), String> {
+        let mut owners = Vec::new();
+        for group_id in [SRC_GROUP_ID, DEST_GROUP_ID] {
+            let node_id = self
+                .c
+                .get_group_leader_node_id(group_id)
+                .await
+                .ok_or_else(|| format!("group {group_id} has no leader"))?;
+            let desc = self
+                .c
+                .collect_group_desc(group_id, node_id)
+                .await
+                .map_err(|err| format!("collect group {group_id} desc: {err:?}"))?
+                .ok_or_else(|| format!("group {group_id} desc is not found in node {node_id}"))?;
+            let root_epoch = self.c.get_group_epoch(group_id);
+            if root_epoch != Some(desc.epoch) {
+                return Err(format!(
+                    "the epoch of group {group_id} is {}, but root has {root_epoch:?}",
+                    desc.epoch
+                ));
+            }
+            if desc.shards.iter().any(|s| s.id == SHARD_ID) {
+                owners.push(group_id);
+            }
+        }
+        let root_owner = self.owner_group_id();
+        if !owners.contains(&root_owner) {
+            return Err(format!("shard is served by {root_owner} in root, but owners {owners:?}"));
+        }
+        if settled && owners.len() != 1 {
+            return Err(format!("shard is contained by groups {owners:?}"));
+        }
+        Ok(())
+    }
+
+    /// Assert all invariants once the moving is finished.
+    pub async fn assert_moved(&self) {
+        self.wait_move_finished().await;
+        self.assert_descriptors_consistent(true).await;
+        assert_eq!(self.owner_group_id(), DEST_GROUP_ID);
+        self.assert_no_key_lost().await;
+        self.assert_no_key_duplicated().await;
+    }
+}
+
+async fn create_group(c: &ClusterClient, group_id: u64, nodes: &[u64], shards: Vec<ShardDesc>) {
+    let replicas = nodes
+        .iter()
+        .map(|node_id| {
+            let replica_id = group_id * 10 + node_id;
+            ReplicaDesc { id: replica_id, node_id: *node_id, role: ReplicaRole::Voter as i32 }
+        })
+        .collect::<Vec<_>>();
+    let group_desc =
+        GroupDesc { id: group_id, shards, replicas: replicas.clone(), ..Default::default() };
+    for replica in replicas {
+        c.create_replica(replica.node_id, replica.id, group_desc.clone()).await;
+    }
+}
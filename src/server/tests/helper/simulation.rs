@@ -0,0 +1,140 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The simulation mode of [`super::context::TestContext`].
+//!
+//! The virtual clock and the network faults are shared by the whole test
+//! process, so a simulated context holds the process exclusively, and the
+//! normal contexts share it.
+//!
+//! The seed replays the timers, the random decisions and the network faults,
+//! but not the scheduling of tasks, see [`sekas_runtime::sim`] for details.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::info;
+use sekas_runtime::sim;
+
+/// The env used to override the seed of simulation, to replay the timers,
+/// random decisions and network faults of a failure.
+const SEED_ENV: &str = "SEKAS_SIMULATION_SEED";
+
+/// The max virtual time advanced by the driver in each step.
+const MAX_STEP: Duration = Duration::from_millis(5);
+
+/// The wall time between two steps, to give the nodes a chance to handle the
+/// fired timers.
+const STEP_INTERVAL: Duration = Duration::from_millis(1);
+
+struct ProcessState {
+    num_shared: usize,
+    exclusive: bool,
+}
+
+static PROCESS_STATE: Mutex<ProcessState> =
+    Mutex::new(ProcessState { num_shared: 0, exclusive: false });
+static PROCESS_CONDVAR: Condvar = Condvar::new();
+
+/// A guard holds the test process shared or exclusively.
+pub struct ProcessGuard {
+    exclusive: bool,
+}
+
+impl ProcessGuard {
+    pub fn shared() -> Self {
+        let mut state = PROCESS_STATE.lock().unwrap();
+        while state.exclusive {
+            state = PROCESS_CONDVAR.wait(state).unwrap();
+        }
+        state.num_shared += 1;
+        ProcessGuard { exclusive: false }
+    }
+
+    pub fn exclusive() -> Self {
+        let mut state = PROCESS_STATE.lock().unwrap();
+        while state.exclusive || state.num_shared > 0 {
+            state = PROCESS_CONDVAR.wait(state).unwrap();
+        }
+        state.exclusive = true;
+        ProcessGuard { exclusive: true }
+    }
+}
+
+impl Drop for ProcessGuard {
+    fn drop(&mut self) {
+        let mut state = PROCESS_STATE.lock().unwrap();
+        if self.exclusive {
+            state.exclusive = false;
+        } else {
+            state.num_shared -= 1;
+        }
+        PROCESS_CONDVAR.notify_all();
+    }
+}
+
+/// A running simulation, the virtual clock is driven by a background thread
+/// until it is dropped.
+pub struct Simulation {
+    seed: u64,
+    stopped: Arc<AtomicBool>,
+    driver: Option<thread::JoinHandle<()>>,
+    _guard: ProcessGuard,
+}
+
+impl Simulation {
+    /// Start a simulation, the seed is read from `SEKAS_SIMULATION_SEED` or
+    /// derived from the name of test.
+    pub fn start(name: &str) -> Self {
+        let seed = match std::env::var(SEED_ENV) {
+            Ok(seed) => seed.parse().expect("the simulation seed is an u64"),
+            Err(_) => {
+                let mut hasher = DefaultHasher::new();
+                name.hash(&mut hasher);
+                hasher.finish()
+            }
+        };
+        let guard = ProcessGuard::exclusive();
+        info!("{name} start simulation with seed {seed}, set {SEED_ENV} to replay it");
+        sim::enable(seed);
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_clone = stopped.clone();
+        let driver = thread::spawn(move || {
+            while !stopped_clone.load(Ordering::Acquire) {
+                sim::clock::advance_to_next_timer(MAX_STEP);
+                thread::sleep(STEP_INTERVAL);
+            }
+        });
+        Simulation { seed, stopped, driver: Some(driver), _guard: guard }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Release);
+        if let Some(driver) = self.driver.take() {
+            driver.join().unwrap_or_default();
+        }
+        sim::disable();
+    }
+}
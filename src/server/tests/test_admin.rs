@@ -151,6 +151,46 @@ async fn admin_basic() {
     assert!(m.nodes.len() == node_count);
 }
 
+#[sekas_macro::test]
+async fn admin_list_large_catalog() {
+    const NUM_TABLES: usize = 1500;
+
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addrs = nodes.values().cloned().collect::<Vec<_>>();
+    let c = SekasClient::new(ClientOptions::default(), addrs).await.unwrap();
+
+    let db = c.create_database("db".to_owned()).await.unwrap();
+    c.create_database("another-db".to_owned()).await.unwrap();
+    let databases = c.list_databases().await.unwrap();
+    let names = databases.iter().map(|db| db.name.as_str()).collect::<Vec<_>>();
+    let mut expect = names.clone();
+    expect.sort_unstable();
+    assert_eq!(names, expect);
+    assert!(databases.iter().any(|desc| desc.id == db.desc().id));
+
+    // The tables are created in the reversed order of name.
+    let mut ids = std::collections::HashMap::new();
+    for i in (0..NUM_TABLES).rev() {
+        let name = format!("table-{i:04}");
+        let table = db.create_table(name.clone()).await.unwrap();
+        ids.insert(name, table.id);
+    }
+
+    // All the tables are listed, in the ascending order of name.
+    let tables = db.list_tables().await.unwrap();
+    assert_eq!(tables.len(), NUM_TABLES);
+    for (i, table) in tables.iter().enumerate() {
+        assert_eq!(table.name, format!("table-{i:04}"));
+        assert_eq!(Some(&table.id), ids.get(&table.name));
+        assert_eq!(table.db, db.desc().id);
+    }
+
+    let table = db.get_table("table-0042").await.unwrap().unwrap();
+    assert_eq!(Some(&table.id), ids.get("table-0042"));
+    assert!(db.get_table("table-9999").await.unwrap().is_none());
+}
+
 fn table_key(database_id: u64, table_name: &str) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + table_name.len());
     buf.extend_from_slice(database_id.to_le_bytes().as_slice());
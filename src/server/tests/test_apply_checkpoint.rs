@@ -0,0 +1,104 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::path::Path;
+use std::time::Duration;
+
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const APPLY_CHECKPOINT_ENTRIES: u64 = 64;
+
+/// Return the number of recoveries, and the number of recoveries which replay
+/// no more than `limit` entries.
+fn recoveries_within(limit: f64) -> (u64, u64) {
+    let families = prometheus::gather();
+    let Some(family) =
+        families.iter().find(|f| f.get_name() == "raftgroup_recovery_replayed_entries")
+    else {
+        return (0, 0);
+    };
+    let histogram = family.get_metric()[0].get_histogram();
+    let within = histogram
+        .get_bucket()
+        .iter()
+        .find(|b| b.get_upper_bound() >= limit)
+        .map(|b| b.get_cumulative_count())
+        .unwrap();
+    (histogram.get_sample_count(), within)
+}
+
+/// Copy the files of a running node, except the lock files.
+fn copy_dir(from: &Path, to: &Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&path, &to.join(entry.file_name()));
+        } else if entry.file_name() != "LOCK" {
+            std::fs::copy(&path, to.join(entry.file_name())).unwrap();
+        }
+    }
+}
+
+#[sekas_macro::test]
+async fn restart_replay_bounded_by_apply_checkpoint() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.set_apply_checkpoint_entries(APPLY_CHECKPOINT_ENTRIES);
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    for i in 0..1000u32 {
+        let key = format!("key-{i:04}").into_bytes();
+        db.put(table.id, key, i.to_be_bytes().to_vec()).await.unwrap();
+    }
+    sekas_runtime::time::sleep(Duration::from_millis(1000)).await;
+
+    // The unflushed memtables are flushed during shutdown, so take the image of the
+    // data dir before it, which is what the crashed node left.
+    let dir = ctx.server_dir(0);
+    let image = dir.with_extension("image");
+    copy_dir(&dir, &image);
+    drop(app);
+    ctx.shutdown();
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::rename(&image, &dir).unwrap();
+
+    // The replica might apply a batch of entries beyond the checkpoint interval.
+    let limit = (APPLY_CHECKPOINT_ENTRIES * 2) as f64;
+    let (total, within) = recoveries_within(limit);
+    let nodes = ctx.start_servers(nodes).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.open_database("db".into()).await.unwrap();
+    let value = db.get(table.id, b"key-0999".to_vec()).await.unwrap();
+    assert_eq!(value, Some(999u32.to_be_bytes().to_vec()));
+
+    let (new_total, new_within) = recoveries_within(limit);
+    assert!(new_total > total);
+    assert_eq!(new_total - total, new_within - within);
+}
@@ -0,0 +1,159 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::*;
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+async fn show_alerts(c: &ClusterClient) -> Vec<(String, String)> {
+    let json_body = c.root_client().handle_statement("SHOW alerts").await.unwrap();
+    match serde_json::from_slice(&json_body).unwrap() {
+        ExecuteResult::Data(result) => {
+            assert_eq!(result.columns, vec!["kind", "age", "message"]);
+            result
+                .rows
+                .into_iter()
+                .map(|row| {
+                    let kind = row.values[0].as_str().unwrap().to_owned();
+                    let message = row.values[2].as_str().unwrap().to_owned();
+                    (kind, message)
+                })
+                .collect()
+        }
+        result => panic!("show alerts: {result:?}"),
+    }
+}
+
+async fn wait_quarantine_alert(c: &ClusterClient, exists: bool) {
+    for _ in 0..100 {
+        let alerts = show_alerts(c).await;
+        if alerts.iter().any(|(kind, _)| kind == "replica_quarantined") == exists {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the quarantine alert is not {}", if exists { "raised" } else { "resolved" });
+}
+
+#[sekas_macro::test]
+async fn apply_quarantine_retry_after_fault_cleared() {
+    let mut ctx = TestContext::new(fn_name!());
+    let faults = ctx.mut_replica_testing_knobs().apply_faults.clone();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addr = nodes.values().next().unwrap().clone();
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+
+    // The poisoned entry quarantines the replica, instead of crashing the node.
+    faults.inject_panic(b"poison");
+    let put = db.put(table.id, b"poison".to_vec(), b"value".to_vec());
+    let _ = tokio::time::timeout(Duration::from_secs(1), put).await;
+    wait_quarantine_alert(&c, true).await;
+    let events = c.root_client().list_topology_events().await.unwrap();
+    assert!(events.iter().any(|e| e.kind == topology_event::Kind::Quarantine as i32), "{events:?}");
+
+    // The other groups are still serving.
+    app.create_database("db2".into()).await.unwrap();
+
+    let client = node_client_with_retry(&addr).await;
+    let retry = ResolveQuarantineRequest {
+        group_id,
+        action: QuarantineAction::Retry as i32,
+        ..Default::default()
+    };
+    let quarantine = client.resolve_quarantine(retry.clone()).await.unwrap();
+    assert!(quarantine.is_some(), "the entry is still poisoned");
+
+    let skip = ResolveQuarantineRequest {
+        group_id,
+        action: QuarantineAction::Skip as i32,
+        expected_index: quarantine.unwrap().index,
+        confirm_token: "skip".to_owned(),
+    };
+    let status = client.resolve_quarantine(skip).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied, "{status:?}");
+
+    faults.clear(b"poison");
+    assert_eq!(client.resolve_quarantine(retry).await.unwrap(), None);
+    wait_quarantine_alert(&c, false).await;
+    assert_eq!(db.get(table.id, b"poison".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+    db.put(table.id, b"key".to_vec(), b"value2".to_vec()).await.unwrap();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value2".to_vec()));
+}
+
+#[sekas_macro::test]
+async fn apply_quarantine_skip_poisoned_entry() {
+    let mut ctx = TestContext::new(fn_name!());
+    let faults = ctx.mut_replica_testing_knobs().apply_faults.clone();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addr = nodes.values().next().unwrap().clone();
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    let state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    let replica_id = *state.replicas.keys().next().unwrap();
+
+    faults.inject_panic(b"poison");
+    let put = db.put(table.id, b"poison".to_vec(), b"value".to_vec());
+    let _ = tokio::time::timeout(Duration::from_secs(1), put).await;
+    wait_quarantine_alert(&c, true).await;
+
+    let client = node_client_with_retry(&addr).await;
+    let retry = ResolveQuarantineRequest {
+        group_id: state.id,
+        action: QuarantineAction::Retry as i32,
+        ..Default::default()
+    };
+    let quarantine = client.resolve_quarantine(retry).await.unwrap().unwrap();
+
+    // The skipped entry must be the quarantined one.
+    let mut skip = ResolveQuarantineRequest {
+        group_id: state.id,
+        action: QuarantineAction::Skip as i32,
+        expected_index: quarantine.index + 1,
+        confirm_token: format!("skip/{replica_id}/{}", quarantine.index + 1),
+    };
+    let status = client.resolve_quarantine(skip.clone()).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument, "{status:?}");
+
+    skip.expected_index = quarantine.index;
+    skip.confirm_token = format!("skip/{replica_id}/{}", quarantine.index);
+    assert_eq!(client.resolve_quarantine(skip).await.unwrap(), None);
+    wait_quarantine_alert(&c, false).await;
+
+    // The write of the skipped entry is lost, the fault is never hit again.
+    assert_eq!(db.get(table.id, b"poison".to_vec()).await.unwrap(), None);
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+    db.put(table.id, b"key".to_vec(), b"value2".to_vec()).await.unwrap();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value2".to_vec()));
+}
@@ -14,8 +14,15 @@
 // limitations under the License.
 mod helper;
 
+use std::collections::HashMap;
+use std::time::Duration;
+
 use log::info;
+use sekas_api::server::v1::join_rejection::{Reason, VersionTooOld, WrongCluster};
+use sekas_api::server::v1::{JoinNodeRequest, NodeCapacity};
 use sekas_rock::fn_name;
+use sekas_runtime::time::{sleep, Instant};
+use sekas_server::Error;
 
 use crate::helper::client::*;
 use crate::helper::context::*;
@@ -29,7 +36,7 @@ fn init() {
 
 #[sekas_macro::test]
 async fn bootstrap_simple_cluster() {
-    let mut ctx = TestContext::new(fn_name!());
+    let mut ctx = TestContext::new_simulation(fn_name!());
     let node_1_addr = ctx.next_listen_address();
     ctx.spawn_server(1, &node_1_addr, true, vec![]);
 
@@ -39,7 +46,7 @@ async fn bootstrap_simple_cluster() {
 
 #[sekas_macro::test]
 async fn bootstrap_cluster_join_node() {
-    let mut ctx = TestContext::new(fn_name!());
+    let mut ctx = TestContext::new_simulation(fn_name!());
     let node_1_addr = ctx.next_listen_address();
     ctx.spawn_server(1, &node_1_addr, true, vec![]);
 
@@ -55,7 +62,7 @@ async fn bootstrap_cluster_join_node() {
 
 #[sekas_macro::test]
 async fn bootstrap_restart_cluster() {
-    let mut ctx = TestContext::new(fn_name!());
+    let mut ctx = TestContext::new_simulation(fn_name!());
     let nodes = ctx.bootstrap_servers(3).await;
 
     // Shutdown and restart servers.
@@ -63,6 +70,131 @@ async fn bootstrap_restart_cluster() {
 
     let nodes = ctx.start_servers(nodes).await;
     let c = ClusterClient::new(nodes).await;
+    c.wait_for_leader(sekas_schema::ROOT_GROUP_ID).await;
     let app = c.app_client().await;
     app.create_database("db".into()).await.unwrap();
 }
+
+#[sekas_macro::test]
+async fn bootstrap_join_node_with_wrong_cluster() {
+    let mut ctx = TestContext::new(fn_name!());
+    let node_1_addr = ctx.next_listen_address();
+    ctx.spawn_server(1, &node_1_addr, true, vec![]);
+    node_client_with_retry(&node_1_addr).await;
+
+    let c = ClusterClient::new(HashMap::from([(1, node_1_addr)])).await;
+    let req = JoinNodeRequest {
+        addr: ctx.next_listen_address(),
+        capacity: Some(NodeCapacity { cpu_nums: 2.0, ..Default::default() }),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        features: u64::MAX,
+        cluster_id: b"another cluster".to_vec(),
+        labels: vec![],
+    };
+    let resp = c.root_client().join_node(req).await.unwrap();
+    let rejection = resp.rejection.expect("the node belongs to another cluster");
+    assert!(
+        matches!(rejection.reason, Some(Reason::WrongCluster(WrongCluster { cluster_id })) if !cluster_id.is_empty())
+    );
+}
+
+#[sekas_macro::test]
+async fn bootstrap_join_node_with_configured_cluster_id() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.set_cluster_id(1, "cluster-a");
+    let node_1_addr = ctx.next_listen_address();
+    ctx.spawn_server(1, &node_1_addr, true, vec![]);
+    node_client_with_retry(&node_1_addr).await;
+
+    // The node configured with the same cluster id joins.
+    ctx.set_cluster_id(2, "cluster-a");
+    let node_2_addr = ctx.next_listen_address();
+    ctx.spawn_server(2, &node_2_addr, false, vec![node_1_addr.clone()]);
+    node_client_with_retry(&node_2_addr).await;
+
+    // The node configured with another cluster id is rejected and exits.
+    ctx.set_cluster_id(3, "cluster-b");
+    let node_3_addr = ctx.next_listen_address();
+    let handle = ctx.spawn_server_expect_exit(3, &node_3_addr, vec![node_1_addr]);
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while !handle.is_finished() {
+        assert!(Instant::now() < deadline, "the rejected node is still running");
+        sleep(Duration::from_millis(100)).await;
+    }
+    let result = handle.join().unwrap();
+    assert!(matches!(result, Err(Error::JoinRejected(_))), "{result:?}");
+}
+
+#[sekas_macro::test]
+async fn bootstrap_join_node_without_cluster_secret() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.clear_cluster_secret(1);
+    let node_1_addr = ctx.next_listen_address();
+    ctx.spawn_server(1, &node_1_addr, true, vec![]);
+    node_client_with_retry(&node_1_addr).await;
+
+    // Without a shared secret, the requests issued by the joined node would be
+    // rejected by the others, so the node refuses to start.
+    ctx.clear_cluster_secret(2);
+    let node_2_addr = ctx.next_listen_address();
+    let handle = ctx.spawn_server_expect_exit(2, &node_2_addr, vec![node_1_addr]);
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while !handle.is_finished() {
+        assert!(Instant::now() < deadline, "the node without secret is still running");
+        sleep(Duration::from_millis(100)).await;
+    }
+    let result = handle.join().unwrap();
+    assert!(
+        matches!(&result, Err(Error::InvalidArgument(msg)) if msg.contains("cluster_secret")),
+        "{result:?}"
+    );
+}
+
+#[sekas_macro::test]
+async fn bootstrap_join_node_with_stale_version() {
+    let mut ctx = TestContext::new(fn_name!());
+    let node_1_addr = ctx.next_listen_address();
+    ctx.spawn_server(1, &node_1_addr, true, vec![]);
+    node_client_with_retry(&node_1_addr).await;
+
+    let c = ClusterClient::new(HashMap::from([(1, node_1_addr.clone())])).await;
+    let req = JoinNodeRequest {
+        addr: ctx.next_listen_address(),
+        capacity: Some(NodeCapacity { cpu_nums: 2.0, ..Default::default() }),
+        version: "0.0.1".to_owned(),
+        features: u64::MAX,
+        cluster_id: vec![],
+        labels: vec![],
+    };
+    let resp = c.root_client().join_node(req).await.unwrap();
+    let rejection = resp.rejection.expect("the version of node is too old");
+    assert!(
+        matches!(rejection.reason, Some(Reason::VersionTooOld(VersionTooOld { min })) if !min.is_empty())
+    );
+
+    // The rejected node exits rather than retrying forever.
+    ctx.set_fake_version(2, "0.0.1");
+    let node_2_addr = ctx.next_listen_address();
+    let handle = ctx.spawn_server_expect_exit(2, &node_2_addr, vec![node_1_addr]);
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while !handle.is_finished() {
+        assert!(Instant::now() < deadline, "the rejected node is still running");
+        sleep(Duration::from_millis(100)).await;
+    }
+    let result = handle.join().unwrap();
+    assert!(matches!(result, Err(Error::JoinRejected(_))), "{result:?}");
+}
+
+#[sekas_macro::test]
+async fn bootstrap_single_node_user_table_avoids_root_group() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let group_state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    assert_ne!(group_state.id, sekas_schema::ROOT_GROUP_ID);
+}
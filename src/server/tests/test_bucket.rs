@@ -0,0 +1,87 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use futures::TryStreamExt;
+use sekas_client::{Bucket, DeletePrefixOptions, Range, RangeRequest};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+async fn bucket_keys(bucket: &Bucket, range: Range) -> Vec<Vec<u8>> {
+    let req = RangeRequest { range, ..Default::default() };
+    bucket.range(req).await.unwrap().into_key_stream().try_collect().await.unwrap()
+}
+
+#[sekas_macro::test]
+async fn bucket_isolation() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    // The name of a bucket is a prefix of the other, and the rest of it is the
+    // separator of the encoding.
+    let short = db.bucket(table.id, b"user");
+    let long = db.bucket(table.id, b"user\x00\x01");
+    let keys: Vec<Vec<u8>> = vec![b"".to_vec(), b"\x00\x01".to_vec(), b"a".to_vec(), b"b".to_vec()];
+    for key in &keys {
+        short.put(key.clone(), [b"short-".as_slice(), key].concat()).await.unwrap();
+    }
+    long.put(b"a".to_vec(), b"long-a".to_vec()).await.unwrap();
+    db.put(table.id, b"user".to_vec(), b"raw".to_vec()).await.unwrap();
+
+    assert_eq!(short.get(b"a".to_vec()).await.unwrap(), Some(b"short-a".to_vec()));
+    assert_eq!(long.get(b"a".to_vec()).await.unwrap(), Some(b"long-a".to_vec()));
+    assert_eq!(long.get(b"b".to_vec()).await.unwrap(), None);
+    assert_eq!(long.get(b"".to_vec()).await.unwrap(), None);
+
+    // The scans are bounded to the bucket.
+    assert_eq!(bucket_keys(&short, Range::all()).await, keys);
+    assert_eq!(bucket_keys(&long, Range::all()).await, vec![b"a".to_vec()]);
+    assert_eq!(
+        bucket_keys(&short, Range::Prefix(b"\x00".to_vec())).await,
+        vec![b"\x00\x01".to_vec()]
+    );
+    let range = Range::Range { begin: Some(b"a".to_vec()), end: None };
+    assert_eq!(bucket_keys(&short, range).await, vec![b"a".to_vec(), b"b".to_vec()]);
+    let range = Range::Range { begin: None, end: Some(b"b".to_vec()) };
+    assert_eq!(bucket_keys(&short, range).await, keys[..3].to_vec());
+
+    // Deleting a bucket leaves the other buckets and the keys outside.
+    short.delete(b"b".to_vec()).await.unwrap();
+    assert_eq!(short.get(b"b".to_vec()).await.unwrap(), None);
+    let progress = short.delete_bucket(DeletePrefixOptions::default()).await.unwrap();
+    assert_eq!(progress.wait().await.unwrap(), 3);
+    assert!(bucket_keys(&short, Range::all()).await.is_empty());
+    assert_eq!(bucket_keys(&long, Range::all()).await, vec![b"a".to_vec()]);
+    assert_eq!(db.get(table.id, b"user".to_vec()).await.unwrap(), Some(b"raw".to_vec()));
+
+    // The keys of buckets could be decoded for display.
+    let raw_key = sekas_schema::bucket::bucket_key(b"user\x00\x01", b"a");
+    assert_eq!(db.get(table.id, raw_key.clone()).await.unwrap(), Some(b"long-a".to_vec()));
+    let (bucket, key) = sekas_schema::bucket::unescape_bucket(&raw_key).unwrap();
+    assert_eq!((bucket.as_slice(), key), (b"user\x00\x01".as_slice(), b"a".as_slice()));
+}
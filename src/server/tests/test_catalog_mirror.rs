@@ -0,0 +1,118 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_client::RootClient;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The ids of the tables of the database, `None` if the database not exists.
+async fn list_table_ids(root_client: &RootClient, db: &str) -> Option<Vec<(String, u64)>> {
+    let db = root_client.get_database(db.to_owned()).await.unwrap()?;
+    let mut tables = root_client
+        .list_table(db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|table| (table.name, table.id))
+        .collect::<Vec<_>>();
+    tables.sort_unstable();
+    Some(tables)
+}
+
+async fn wait_mirror_converged(primary: &RootClient, mirror: &RootClient, db: &str) {
+    let expect = list_table_ids(primary, db).await;
+    for _ in 0..100 {
+        if list_table_ids(mirror, db).await == expect {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the mirror is not converged, expect {expect:?}");
+}
+
+#[sekas_macro::test]
+async fn mirror_catalog_then_promote() {
+    let mut primary_ctx = TestContext::new(&format!("{}_primary", fn_name!()));
+    let primary_nodes = primary_ctx.bootstrap_servers(1).await;
+    let primary_addr = primary_nodes[&0].clone();
+    let primary = ClusterClient::new(primary_nodes).await;
+    let app = primary.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    db.create_table("table_a".into()).await.unwrap();
+
+    let mut mirror_ctx = TestContext::new(&format!("{}_mirror", fn_name!()));
+    mirror_ctx.set_catalog_mirror_of(&primary_addr);
+    let mirror_nodes = mirror_ctx.bootstrap_servers(1).await;
+    let mirror = ClusterClient::new(mirror_nodes).await;
+
+    // The catalog created before the mirror is started.
+    let (primary_root, mirror_root) = (primary.root_client(), mirror.root_client());
+    wait_mirror_converged(&primary_root, &mirror_root, "db").await;
+
+    // The DDL after the mirror is started.
+    db.create_table("table_b".into()).await.unwrap();
+    db.create_table("table_c".into()).await.unwrap();
+    db.delete_table("table_a".into()).await.unwrap();
+    wait_mirror_converged(&primary_root, &mirror_root, "db").await;
+    app.create_database("db2".into()).await.unwrap();
+    wait_mirror_converged(&primary_root, &mirror_root, "db2").await;
+    app.delete_database("db2".into()).await.unwrap();
+    wait_mirror_converged(&primary_root, &mirror_root, "db2").await;
+
+    // The mutating requests are refused by the mirror.
+    let result = mirror_root.create_database("db3".into(), String::new()).await;
+    assert!(matches!(result, Err(sekas_client::Error::PermissionDenied(_))), "{result:?}");
+    let db_desc = mirror_root.get_database("db".into()).await.unwrap().unwrap();
+    let result = mirror_root.delete_table(db_desc.clone(), "table_b".into()).await;
+    assert!(matches!(result, Err(sekas_client::Error::PermissionDenied(_))), "{result:?}");
+
+    // Promote the mirror, the DDL works on the promoted root.
+    assert_eq!(mirror_root.promote_catalog().await.unwrap(), 1);
+    let result = mirror_root.promote_catalog().await;
+    assert!(matches!(result, Err(sekas_client::Error::InvalidArgument(_))), "{result:?}");
+
+    let mirrored_tables = list_table_ids(&mirror_root, "db").await.unwrap();
+    let max_table_id = mirrored_tables.iter().map(|(_, id)| *id).max().unwrap();
+    let table = mirror_root
+        .create_table(db_desc, "table_d".into(), Default::default(), String::new())
+        .await
+        .unwrap();
+    assert!(table.id > max_table_id, "table id {} <= {max_table_id}", table.id);
+    mirror.assert_table_ready(table.id).await;
+
+    let db3 = mirror_root.create_database("db3".into(), String::new()).await.unwrap();
+    assert!(db3.id > db.desc().id);
+    let mirror_app = mirror.app_client().await;
+    let db = mirror_app.open_database("db".into()).await.unwrap();
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+
+    // The promoted catalog doesn't follow the primary anymore.
+    app.open_database("db".into()).await.unwrap().create_table("table_e".into()).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let tables = list_table_ids(&mirror_root, "db").await.unwrap();
+    assert!(tables.iter().all(|(name, _)| name != "table_e"), "{tables:?}");
+}
@@ -14,10 +14,13 @@
 // limitations under the License.
 mod helper;
 
+use std::collections::BTreeSet;
 use std::time::Duration;
 
 use log::info;
-use sekas_client::{AppError, ClientOptions};
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::ShardGetRequest;
+use sekas_client::{AppError, ClientOptions, RetryState};
 use sekas_rock::fn_name;
 
 use crate::helper::client::*;
@@ -38,6 +41,7 @@ async fn client_to_unreachable_peers() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
     let db = client.create_database("test_db".to_string()).await.unwrap();
@@ -58,22 +62,73 @@ async fn client_to_unreachable_peers() {
     let v = "value-1".as_bytes().to_vec();
     assert!(matches!(
         db.put(co.id, k.clone(), v.clone()).await,
-        Err(AppError::Network(_) | AppError::DeadlineExceeded(_))
+        Err(AppError::Network(_) | AppError::DeadlineExceeded(..))
     ));
     assert!(matches!(
         db.put(co.id, k.clone(), v.clone()).await,
-        Err(AppError::DeadlineExceeded(_))
+        Err(AppError::DeadlineExceeded(..))
     ));
     assert!(matches!(
         db.put(co.id, k.clone(), v.clone()).await,
-        Err(AppError::DeadlineExceeded(_))
+        Err(AppError::DeadlineExceeded(..))
     ));
     assert!(matches!(
         db.put(co.id, k.clone(), v.clone()).await,
-        Err(AppError::DeadlineExceeded(_))
+        Err(AppError::DeadlineExceeded(..))
     ));
 }
 
+#[sekas_macro::test]
+async fn client_error_context_lists_attempted_nodes() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let client = c.app_client().await;
+    let db = client.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_table("test_co".to_string()).await.unwrap();
+    c.assert_table_ready(co.id).await;
+
+    let state = c.find_router_group_state_by_key(co.id, b"key").await.unwrap();
+    let shard = c.get_shard_desc(co.id, b"key").await.unwrap();
+    let expect_nodes = state.replicas.values().map(|r| r.node_id).collect::<BTreeSet<_>>();
+    assert_eq!(expect_nodes.len(), 3);
+
+    info!("shutdown cluster");
+    ctx.shutdown();
+
+    let req = Request::Get(ShardGetRequest {
+        shard_id: shard.id,
+        user_key: b"key".to_vec(),
+        ..Default::default()
+    });
+    let mut group_client = c.group(state.id);
+    let err = group_client.request(&req).await.unwrap_err();
+    let sekas_client::Error::GroupNotAccessable(group_id, err_ctx) = err else {
+        panic!("expect group not accessable, got {err:?}");
+    };
+    assert_eq!(group_id, state.id);
+    assert_eq!(err_ctx.group_id, Some(state.id));
+    let attempted_nodes = err_ctx.attempts.iter().map(|a| a.node_id).collect::<BTreeSet<_>>();
+    assert_eq!(attempted_nodes, expect_nodes, "{err_ctx}");
+    assert_eq!(err_ctx.attempts.len(), 3, "{err_ctx}");
+    assert!(err_ctx.attempts.iter().all(|a| !a.status.is_empty()), "{err_ctx}");
+
+    // The context is kept once the retrying is timeout.
+    let mut retry_state = RetryState::new(Duration::from_millis(200));
+    let err = loop {
+        let err = group_client.request(&req).await.unwrap_err();
+        if let Err(err) = retry_state.retry(err).await {
+            break AppError::from(err);
+        }
+    };
+    assert!(matches!(err, AppError::DeadlineExceeded(..)), "{err:?}");
+    let err_ctx = err.context().expect("the context of the error");
+    let attempted_nodes = err_ctx.attempts.iter().map(|a| a.node_id).collect::<BTreeSet<_>>();
+    assert_eq!(attempted_nodes, expect_nodes, "{err}");
+    assert!(err_ctx.retries > 0, "{err}");
+    assert!(err.to_string().contains(&format!("group {}", state.id)), "{err}");
+}
+
 #[sekas_macro::test]
 async fn client_create_duplicated_database_or_table() {
     let mut ctx = TestContext::new(fn_name!());
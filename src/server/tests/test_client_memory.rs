@@ -0,0 +1,139 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_client::{AppError, ClientOptions, Range, RangeRequest, WriteBuilder};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const MEMORY_BUDGET: u64 = 64 * 1024;
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key-{i}").into_bytes()
+}
+
+fn budget_options() -> ClientOptions {
+    ClientOptions { memory_budget: Some(MEMORY_BUDGET), ..Default::default() }
+}
+
+#[sekas_macro::test]
+async fn txn_writes_bounded_by_memory_budget() {
+    let ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client_with_options(budget_options()).await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let value = vec![0u8; 4096];
+    let mut txn1 = db.begin_txn();
+    for i in 0..8 {
+        txn1.try_put(table.id, WriteBuilder::new(key(i)).ensure_put(value.clone())).unwrap();
+    }
+
+    // The concurrent txns share the budget.
+    let mut txn2 = db.begin_txn();
+    let mut exhausted = None;
+    for i in 8..32 {
+        if let Err(err) =
+            txn2.try_put(table.id, WriteBuilder::new(key(i)).ensure_put(value.clone()))
+        {
+            exhausted = Some(err);
+            break;
+        }
+    }
+    match exhausted {
+        Some(AppError::ClientMemoryExhausted { category, budget, .. }) => {
+            assert_eq!(category, "txn_writes");
+            assert_eq!(budget, MEMORY_BUDGET);
+        }
+        others => panic!("expect client memory exhausted, but got {others:?}"),
+    }
+    let usage = app.memory_usage();
+    assert!(usage.txn_writes > 0 && usage.txn_writes <= MEMORY_BUDGET, "{usage:?}");
+    assert_eq!(usage.scan_buffers, 0);
+
+    // The discarded put fails the commit.
+    txn2.put(table.id, WriteBuilder::new(key(100)).ensure_put(value.clone()));
+    match txn2.commit().await {
+        Err(AppError::ClientMemoryExhausted { .. }) => {}
+        others => panic!("expect client memory exhausted, but got {others:?}"),
+    }
+    assert_eq!(db.get(table.id, key(8)).await.unwrap(), None);
+
+    // The memory is released once the txns are finished.
+    txn1.abort().await;
+    assert_eq!(app.memory_usage().total(), 0);
+
+    let mut txn = db.begin_txn();
+    for i in 0..8 {
+        txn.try_put(table.id, WriteBuilder::new(key(i)).ensure_put(value.clone())).unwrap();
+    }
+    txn.commit().await.unwrap();
+    assert_eq!(db.get(table.id, key(0)).await.unwrap(), Some(value));
+    assert_eq!(app.memory_usage().total(), 0);
+}
+
+#[sekas_macro::test]
+async fn scan_buffers_bounded_by_memory_budget() {
+    let ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    for i in 0..4 {
+        db.put(table.id, key(i), vec![0u8; 1024]).await.unwrap();
+    }
+    db.put(table.id, b"large".to_vec(), vec![0u8; 2 * MEMORY_BUDGET as usize]).await.unwrap();
+
+    let limited = c.app_client_with_options(budget_options()).await;
+    let db = limited.open_database("db".into()).await.unwrap();
+    let range = Range::Range { begin: Some(key(0)), end: Some(key(4)) };
+    let req = RangeRequest { table_id: table.id, range, ..Default::default() };
+    let value_sets = db.range(req).await.unwrap().try_collect_vec(0).await.unwrap();
+    assert_eq!(value_sets.len(), 4);
+    assert_eq!(limited.memory_usage().total(), 0);
+
+    // The page larger than the budget fails the scan instead of being retried.
+    let req = RangeRequest { table_id: table.id, range: Range::all(), ..Default::default() };
+    let result = db.range(req).await.unwrap().try_collect_vec(0).await;
+    match result {
+        Err(sekas_client::Error::ClientMemoryExhausted { category, .. }) => {
+            assert_eq!(category, "scan_buffers");
+        }
+        others => panic!("expect client memory exhausted, but got {others:?}"),
+    }
+    // The reservations are released once the fetching task exits.
+    for _ in 0..50 {
+        if limited.memory_usage().total() == 0 {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(limited.memory_usage().total(), 0);
+}
@@ -0,0 +1,76 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use log::info;
+use sekas_rock::fn_name;
+use sekas_runtime::time::{sleep, Instant};
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const SKEWED_NODE_ID: u64 = 2;
+
+#[sekas_macro::test]
+async fn skewed_node_refuses_to_serve_commits() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    ctx.set_clock_offset(SKEWED_NODE_ID as usize, 10_000);
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    // Move the leadership of the table to the skewed node.
+    let state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    let group_id = state.id;
+    let replica = state
+        .replicas
+        .values()
+        .find(|r| r.node_id == SKEWED_NODE_ID)
+        .cloned()
+        .expect("the table has a replica on each node");
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while c.get_group_leader_node_id(group_id).await != Some(SKEWED_NODE_ID) {
+        assert!(Instant::now() < deadline, "transfer leadership to the skewed node timeout");
+        let _ = c.group(group_id).transfer_leader(replica.id).await;
+        sleep(Duration::from_millis(100)).await;
+    }
+    info!("the leadership of group {group_id} is transferred to the skewed node");
+
+    // The skewed node refuses to serve commits and moves the leadership away,
+    // the client retries the commits on the new leader.
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let mut num_puts = 0u8;
+    while c.get_group_leader_node_id(group_id).await == Some(SKEWED_NODE_ID) {
+        assert!(Instant::now() < deadline, "the skewed node still serves commits");
+        db.put(table.id, b"key".to_vec(), vec![num_puts]).await.unwrap();
+        num_puts += 1;
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    let value = db.get(table.id, b"key".to_vec()).await.unwrap();
+    assert_eq!(value, Some(b"value".to_vec()));
+}
@@ -0,0 +1,175 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use sekas_api::server::v1::clone_status::Phase;
+use sekas_client::{AppError, Database, Range, RangeRequest};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// Read the key values of the table, at the version if it is specified.
+async fn read_table(db: &Database, table_id: u64, version: Option<u64>) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let req = RangeRequest {
+        table_id,
+        version,
+        range: Range::Range { begin: None, end: None },
+        ..Default::default()
+    };
+    let value_sets = db.range(req).await.unwrap().try_collect_vec(0).await.unwrap();
+    value_sets
+        .into_iter()
+        .filter_map(|value_set| {
+            let value = value_set.values.into_iter().next().and_then(|v| v.content)?;
+            Some((value_set.user_key, value))
+        })
+        .collect()
+}
+
+#[sekas_macro::test]
+async fn clone_table_is_consistent_snapshot() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let pause = ctx.mut_root_testing_knobs().pause_clone_table.clone();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    for i in 0..100u32 {
+        let key = format!("key-{i:03}").into_bytes();
+        db.put(table.id, key, i.to_be_bytes().to_vec()).await.unwrap();
+    }
+
+    pause.store(true, Ordering::Release);
+    let resp = c
+        .root_client()
+        .clone_table("db".into(), "table".into(), "db".into(), "clone".into())
+        .await
+        .unwrap();
+    let clone = resp.table.unwrap();
+    let read_version = resp.read_version;
+    c.assert_table_ready(clone.id).await;
+
+    // The writes to the source after the read version are not cloned.
+    for i in 0..50u32 {
+        let key = format!("key-{i:03}").into_bytes();
+        db.put(table.id, key, b"overwritten".to_vec()).await.unwrap();
+    }
+    for i in 50..60u32 {
+        db.delete(table.id, format!("key-{i:03}").into_bytes()).await.unwrap();
+    }
+    db.put(table.id, b"key-new".to_vec(), b"new".to_vec()).await.unwrap();
+
+    // The clone is read-only until the copying finishes.
+    let status = c.root_client().clone_status(clone.id).await.unwrap().unwrap();
+    assert_eq!(status.phase, Phase::Copying as i32);
+    assert_eq!(status.src_table, table.id);
+    let result = db.put(clone.id, b"key-000".to_vec(), b"value".to_vec()).await;
+    assert!(matches!(result, Err(AppError::PermissionDenied(_))), "{result:?}");
+
+    // The source is written while the copy runs.
+    let stop = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let (db, stop, table_id) = (db.clone(), stop.clone(), table.id);
+        sekas_runtime::spawn(async move {
+            let mut num_writes = 0;
+            while !stop.load(Ordering::Acquire) {
+                let key = format!("key-{:03}", num_writes % 100).into_bytes();
+                db.put(table_id, key, format!("concurrent-{num_writes}").into_bytes())
+                    .await
+                    .unwrap();
+                num_writes += 1;
+            }
+            num_writes
+        })
+    };
+    pause.store(false, Ordering::Release);
+    let mut status = None;
+    for _ in 0..1000 {
+        status = c.root_client().clone_status(clone.id).await.unwrap();
+        if status.as_ref().map(|s| s.phase) == Some(Phase::Finished as i32) {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    stop.store(true, Ordering::Release);
+    let num_writes = writer.await.unwrap();
+    assert!(num_writes > 0);
+    let status = status.unwrap();
+    assert_eq!(status.phase, Phase::Finished as i32, "{status:?}");
+    assert_eq!(status.copied_keys, 100);
+
+    let snapshot = read_table(&db, table.id, Some(read_version)).await;
+    assert_eq!(snapshot.len(), 100);
+    assert_eq!(read_table(&db, clone.id, None).await, snapshot);
+
+    // The clone is writable and independent of the source once it finishes.
+    db.invalidate_table("clone");
+    db.put(clone.id, b"key-000".to_vec(), b"value".to_vec()).await.unwrap();
+    assert_eq!(db.get(clone.id, b"key-000".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+    let value = db.get(table.id, b"key-000".to_vec()).await.unwrap().unwrap();
+    assert!(value.starts_with(b"concurrent-"), "{value:?}");
+}
+
+#[sekas_macro::test]
+async fn clone_table_aborts_once_source_deleted() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let pause = ctx.mut_root_testing_knobs().pause_clone_table.clone();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    pause.store(true, Ordering::Release);
+    let resp = c
+        .root_client()
+        .clone_table("db".into(), "table".into(), "db".into(), "clone".into())
+        .await
+        .unwrap();
+    let clone = resp.table.unwrap();
+    db.delete_table("table".into()).await.unwrap();
+    pause.store(false, Ordering::Release);
+
+    // The partial clone is removed.
+    let mut status = None;
+    for _ in 0..1000 {
+        status = c.root_client().clone_status(clone.id).await.unwrap();
+        if status.as_ref().map(|s| s.phase) == Some(Phase::Aborted as i32) {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    let status = status.unwrap();
+    assert_eq!(status.phase, Phase::Aborted as i32, "{status:?}");
+    db.invalidate_table("clone");
+    assert!(db.get_table("clone").await.unwrap().is_none());
+}
@@ -19,7 +19,10 @@ use log::info;
 use rand::prelude::SmallRng;
 use rand::{Rng, SeedableRng};
 use sekas_api::server::v1::ReplicaRole;
-use sekas_client::{AppError, ClientOptions, RangeRequest, SekasClient, WriteBuilder};
+use sekas_client::{
+    AppError, ClientOptions, OpResult, Range, RangeRequest, SekasClient, WriteBatchError,
+    WriteBuilder,
+};
 use sekas_rock::fn_name;
 
 use crate::helper::client::*;
@@ -257,7 +260,7 @@ async fn cluster_rw_put_with_condition() {
     txn.put(co.id, WriteBuilder::new(k.clone()).expect_exists().ensure_put(v.clone()));
     let r = txn.commit().await;
     info!("put if exists failed: {r:?}");
-    assert!(matches!(r, Err(AppError::CasFailed(0, 0, _))));
+    assert!(matches!(r, Err(AppError::WriteBatch(WriteBatchError { failed_index: 0, .. }))));
 
     // 2. Put if not exists success
     let mut txn = db.begin_txn();
@@ -271,7 +274,7 @@ async fn cluster_rw_put_with_condition() {
     let mut txn = db.begin_txn();
     txn.put(co.id, WriteBuilder::new(k.clone()).expect_not_exists().ensure_put(v.clone()));
     let r = txn.commit().await;
-    assert!(matches!(r, Err(AppError::CasFailed(0, 0, _))));
+    assert!(matches!(r, Err(AppError::WriteBatch(WriteBatchError { failed_index: 0, .. }))));
 
     // 4. Put if exists success
     let mut txn = db.begin_txn();
@@ -286,7 +289,7 @@ async fn cluster_rw_put_with_condition() {
         WriteBuilder::new(k.clone()).expect_value(b"rust".to_vec()).ensure_put(v.clone()),
     );
     let r = txn.commit().await;
-    assert!(matches!(r, Err(AppError::CasFailed(0, 0, _))));
+    assert!(matches!(r, Err(AppError::WriteBatch(WriteBatchError { failed_index: 0, .. }))));
 
     // 6.Put with expected value success
     let mut txn = db.begin_txn();
@@ -300,6 +303,47 @@ async fn cluster_rw_put_with_condition() {
     assert!(r.is_ok());
 }
 
+#[sekas_macro::test]
+async fn cluster_rw_write_batch_with_failed_condition() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_table("test_co".to_string()).await.unwrap();
+    c.assert_table_ready(co.id).await;
+
+    let keys = [b"key_1".to_vec(), b"key_2".to_vec(), b"key_3".to_vec()];
+    db.put(co.id, keys[1].clone(), b"value".to_vec()).await.unwrap();
+
+    // The condition of the middle operation is not satisfied.
+    let mut txn = db.begin_txn();
+    for key in &keys {
+        let builder = WriteBuilder::new(key.clone()).expect_not_exists();
+        txn.put(co.id, builder.ensure_put(b"new_value".to_vec()));
+    }
+    let r = txn.commit().await;
+    info!("write batch with failed condition: {r:?}");
+    let Err(AppError::WriteBatch(WriteBatchError { failed_index, per_op })) = r else {
+        panic!("WriteBatch error is required, but got {r:?}");
+    };
+    assert_eq!(failed_index, 1);
+    assert_eq!(per_op.len(), 3);
+    assert_eq!(per_op[0], OpResult::Applied, "{per_op:?}");
+    assert_eq!(per_op[2], OpResult::Applied, "{per_op:?}");
+    let OpResult::CasFailed { cond_index, prev_value: Some(prev_value) } = &per_op[1] else {
+        panic!("CasFailed with the current value is required, but got {per_op:?}");
+    };
+    assert_eq!(*cond_index, 0);
+    assert_eq!(prev_value.content.as_deref(), Some(b"value".as_slice()));
+
+    // None of the operations is applied.
+    assert!(db.get(co.id, keys[0].clone()).await.unwrap().is_none());
+    assert_eq!(db.get(co.id, keys[1].clone()).await.unwrap(), Some(b"value".to_vec()));
+    assert!(db.get(co.id, keys[2].clone()).await.unwrap().is_none());
+}
+
 #[sekas_macro::test]
 async fn cluster_rw_concurrent_inc() {
     let mut ctx = TestContext::new(fn_name!());
@@ -343,6 +387,136 @@ async fn cluster_rw_concurrent_inc() {
     assert_eq!(r, expect);
 }
 
+#[sekas_macro::test]
+async fn cluster_rw_concurrent_append_to_prefix() {
+    const NUM_APPENDERS: u64 = 4;
+    const NUM_APPENDS: u64 = 100;
+
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_table("test_co".to_string()).await.unwrap();
+    c.assert_table_ready(co.id).await;
+
+    let prefix = b"inbox-1-".to_vec();
+    let mut handles = Vec::new();
+    for appender in 0..NUM_APPENDERS {
+        let (db, co, prefix) = (db.clone(), co.clone(), prefix.clone());
+        handles.push(spawn(async move {
+            let mut appended = Vec::new();
+            for i in 0..NUM_APPENDS {
+                let value = format!("{appender}-{i}").into_bytes();
+                let mut txn = db.begin_txn();
+                txn.put(
+                    co.id,
+                    WriteBuilder::new(prefix.clone()).ensure_append_to_prefix(value.clone()),
+                );
+                let resp = txn.commit().await.unwrap();
+                appended.push((resp.sequences[0].unwrap(), value));
+            }
+            appended
+        }));
+    }
+    let mut appended = Vec::new();
+    for handle in handles {
+        appended.extend(handle.await.unwrap());
+    }
+
+    // The sequences never collide, and they are dense.
+    appended.sort();
+    let sequences = appended.iter().map(|(sequence, _)| *sequence).collect::<Vec<_>>();
+    let num_entries = NUM_APPENDERS * NUM_APPENDS;
+    assert_eq!(sequences, (1..=num_entries).collect::<Vec<_>>());
+    for (sequence, value) in &appended {
+        let entry_key = sekas_api::append_entry_key(&prefix, *sequence);
+        assert_eq!(db.get(co.id, entry_key).await.unwrap().as_ref(), Some(value));
+    }
+    let last_sequence = db.get(co.id, prefix.clone()).await.unwrap();
+    assert_eq!(last_sequence, Some(num_entries.to_be_bytes().to_vec()));
+
+    // The latest entries are read by a reverse scan.
+    let req = RangeRequest {
+        table_id: co.id,
+        range: Range::Range {
+            begin: Some(sekas_api::append_entry_key(&prefix, 0)),
+            end: Some(sekas_api::append_entry_key(&prefix, u64::MAX)),
+        },
+        reverse: true,
+        ..Default::default()
+    };
+    let latest = db.range(req).await.unwrap().try_collect_vec(10).await.unwrap();
+    let keys = latest.into_iter().map(|value_set| value_set.user_key).collect::<Vec<_>>();
+    let expect = (num_entries - 9..=num_entries)
+        .rev()
+        .map(|sequence| sekas_api::append_entry_key(&prefix, sequence))
+        .collect::<Vec<_>>();
+    assert_eq!(keys, expect);
+}
+
+#[sekas_macro::test]
+async fn cluster_rw_concurrent_add_returning() {
+    const NUM_ADDS: i64 = 50;
+
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("test_db".to_string()).await.unwrap();
+    let co = db.create_table("test_co".to_string()).await.unwrap();
+    c.assert_table_ready(co.id).await;
+
+    let key = b"counter".to_vec();
+    // The one-shot adder.
+    let one_shot = {
+        let (db, co, key) = (db.clone(), co.clone(), key.clone());
+        spawn(async move {
+            let mut values = Vec::new();
+            for _ in 0..NUM_ADDS {
+                let put = WriteBuilder::new(key.clone()).ensure_add(1);
+                let value = db.put_returning(co.id, put).await.unwrap().unwrap();
+                values.push(i64::from_be_bytes(value.try_into().unwrap()));
+            }
+            values
+        })
+    };
+    // The txn adder, the add is resolved at commit.
+    let txn = {
+        let (db, co, key) = (db.clone(), co.clone(), key.clone());
+        spawn(async move {
+            let mut values = Vec::new();
+            for i in 0..NUM_ADDS {
+                let mut txn = db.begin_txn();
+                txn.put(
+                    co.id,
+                    WriteBuilder::new(format!("key-{i}").into_bytes()).ensure_put(vec![]),
+                );
+                let index = txn.put_returning(co.id, WriteBuilder::new(key.clone()).ensure_add(1));
+                let resp = txn.commit().await.unwrap();
+                let value = resp.new_value(index).unwrap();
+                values.push(i64::from_be_bytes(value.try_into().unwrap()));
+            }
+            values
+        })
+    };
+    let one_shot = one_shot.await.unwrap();
+    let txn = txn.await.unwrap();
+
+    // The values returned by each adder are increasing.
+    assert!(one_shot.windows(2).all(|w| w[0] < w[1]), "{one_shot:?}");
+    assert!(txn.windows(2).all(|w| w[0] < w[1]), "{txn:?}");
+
+    // Each add sees a distinct intermediate value, produced by itself.
+    let mut values = one_shot.into_iter().chain(txn).collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values, (1..=NUM_ADDS * 2).collect::<Vec<_>>());
+    let counter = db.get(co.id, key).await.unwrap();
+    assert_eq!(counter, Some((NUM_ADDS * 2).to_be_bytes().to_vec()));
+}
+
 #[sekas_macro::test]
 async fn cluster_rw_write_two_table_in_batch() {
     let mut ctx = TestContext::new(fn_name!());
@@ -402,6 +576,8 @@ async fn cluster_rw_entire_range() {
         range: sekas_client::Range::all(),
         limit: 10,
         limit_bytes: 0,
+        reverse: false,
+        options: sekas_client::ScanOptions::default(),
         buffered_requests: 1,
     };
     let mut range_stream = db.range(range_request).await.unwrap();
@@ -454,6 +630,8 @@ async fn cluster_rw_range_with_many_shard() {
         range: sekas_client::Range::all(),
         limit: 10,
         limit_bytes: 0,
+        reverse: false,
+        options: sekas_client::ScanOptions::default(),
         buffered_requests: 1,
     };
     let mut range_stream = db.range(range_request).await.unwrap();
@@ -478,6 +656,65 @@ async fn cluster_rw_range_with_many_shard() {
     assert_eq!(index, 100);
 }
 
+#[sekas_macro::test]
+async fn cluster_rw_reverse_range_with_many_shard() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("db".to_string()).await.unwrap();
+    let co = db.create_table("co".to_string()).await.unwrap();
+    c.assert_table_ready(co.id).await;
+
+    // The keys with 0x00 and 0xFF suffixes are adjacent to the boundary of pages.
+    for i in 0..20 {
+        let base = format!("key {i:04}").into_bytes();
+        for suffix in [None, Some(0x00u8), Some(0xFFu8)] {
+            let mut k = base.clone();
+            k.extend(suffix);
+            db.put(co.id, k.clone(), k).await.unwrap();
+        }
+    }
+
+    let old_shard_id = sekas_schema::FIRST_USER_SHARD_ID;
+    let new_shard_id = old_shard_id + 1024;
+    let group_state = c.find_router_group_state_by_key(co.id, &[0]).await.unwrap();
+    let mut group_client = c.group(group_state.id);
+    let split_key = b"key 0010".to_vec();
+    group_client.split_shard(old_shard_id, new_shard_id, Some(split_key)).await.unwrap();
+    c.assert_group_contains_shard(group_state.id, new_shard_id).await;
+
+    async fn collect_keys(
+        db: &sekas_client::Database,
+        table_id: u64,
+        reverse: bool,
+    ) -> Vec<Vec<u8>> {
+        let range_request = RangeRequest {
+            table_id,
+            version: None,
+            range: sekas_client::Range::all(),
+            limit: 7,
+            limit_bytes: 0,
+            reverse,
+            options: sekas_client::ScanOptions::default(),
+            buffered_requests: 1,
+        };
+        let mut range_stream = db.range(range_request).await.unwrap();
+        let mut keys = vec![];
+        while let Some(values) = range_stream.next().await {
+            keys.extend(values.unwrap().into_iter().map(|value_set| value_set.user_key));
+        }
+        keys
+    }
+
+    let forward = collect_keys(&db, co.id, false).await;
+    let mut reverse = collect_keys(&db, co.id, true).await;
+    assert_eq!(forward.len(), 60);
+    reverse.reverse();
+    assert_eq!(forward, reverse);
+}
+
 // Watch the updation of a key
 #[sekas_macro::test]
 async fn cluster_rw_watch_key() {
@@ -638,6 +875,75 @@ async fn cluster_rw_watch_key_history() {
     }
 }
 
+/// Watch a key beyond the limit of watches per connection.
+#[sekas_macro::test]
+async fn cluster_rw_watch_key_beyond_limits() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.mut_watch_config().max_watches_per_connection = 2;
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("db".to_string()).await.unwrap();
+    let co = db.create_table("co".to_string()).await.unwrap();
+    c.assert_table_ready(co.id).await;
+
+    const KEY: &str = "KEY";
+    db.put(co.id, KEY.as_bytes().to_vec(), b"value".to_vec()).await.unwrap();
+
+    // The watches are established once the existing value is received.
+    let mut receivers = vec![];
+    for _ in 0..2 {
+        let mut receiver = db.watch(co.id, KEY.as_bytes()).await.unwrap();
+        let value = receiver.next().await.unwrap().unwrap();
+        assert_eq!(value.content.as_deref(), Some(b"value".as_slice()));
+        receivers.push(receiver);
+    }
+
+    let mut receiver = db.watch(co.id, KEY.as_bytes()).await.unwrap();
+    let r = receiver.next().await.unwrap();
+    info!("watch key beyond limits: {r:?}");
+    assert!(
+        matches!(&r, Err(AppError::ResourceExhausted(msg)) if msg.contains("max_watches_per_connection")),
+        "{r:?}"
+    );
+    assert!(receiver.next().await.is_none());
+}
+
+/// Watch a key whose events exceed the buffered bytes limit of a watch.
+#[sekas_macro::test]
+async fn cluster_rw_watch_key_resume_lagging() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.mut_watch_config().max_buffered_bytes_per_watch = 1024;
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let db = app.create_database("db".to_string()).await.unwrap();
+    let co = db.create_table("co".to_string()).await.unwrap();
+    c.assert_table_ready(co.id).await;
+
+    const KEY: &str = "KEY";
+    let mut receiver = db.watch(co.id, KEY.as_bytes()).await.unwrap();
+
+    // Each event exceeds the limit, so the watch is always cancelled as lagging and
+    // resumed from the last delivered version.
+    let handle = spawn(async move {
+        let mut last_version = 0;
+        for i in 0..10u8 {
+            let value = receiver.next().await.unwrap().unwrap();
+            assert!(value.version > last_version);
+            assert_eq!(value.content, Some(vec![i; 4096]));
+            last_version = value.version;
+        }
+    });
+
+    for i in 0..10u8 {
+        db.put(co.id, KEY.as_bytes().to_vec(), vec![i; 4096]).await.unwrap();
+    }
+    handle.await.unwrap();
+}
+
 /// Watch a key but shard moved.
 #[sekas_macro::test]
 async fn cluster_rw_watch_key_with_moving_shard() {
@@ -0,0 +1,232 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::path::Path;
+use std::time::Duration;
+
+use rand::RngCore;
+use sekas_api::server::v1::CompactGroupRequest;
+use sekas_client::{AppError, CreateTableOptions, ReadOptions, WriteBuilder};
+use sekas_rock::fn_name;
+use sekas_schema::property::MAX_VERSIONS;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The total size of the sst files under the dir.
+fn sst_size(dir: &Path) -> u64 {
+    let mut size = 0;
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        if entry.file_type().unwrap().is_dir() {
+            size += sst_size(&path);
+        } else if path.extension().is_some_and(|ext| ext == "sst") {
+            size += entry.metadata().map(|m| m.len()).unwrap_or_default();
+        }
+    }
+    size
+}
+
+#[sekas_macro::test]
+async fn compaction_drops_versions_beneath_gc_watermark() {
+    const NUM_KEYS: usize = 64;
+    const NUM_VERSIONS: usize = 16;
+    const VALUE_SIZE: usize = 4096;
+
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+
+    let mut value = vec![0; VALUE_SIZE];
+    for _ in 0..NUM_VERSIONS {
+        for i in 0..NUM_KEYS {
+            // The random values are not compressed.
+            rand::thread_rng().fill_bytes(&mut value);
+            let key = format!("key-{i:04}").into_bytes();
+            db.put(table.id, key, value.clone()).await.unwrap();
+        }
+    }
+    let mut latest_values = vec![];
+    for i in 0..NUM_KEYS {
+        let key = format!("key-{i:04}").into_bytes();
+        latest_values.push(db.get(table.id, key).await.unwrap().unwrap());
+    }
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let client = node_client_with_retry(nodes.values().next().unwrap()).await;
+    let dir = ctx.server_dir(0);
+
+    // Nothing is dropped without a GC watermark.
+    let resp =
+        client.compact_group(CompactGroupRequest { group_id, gc_watermark: None }).await.unwrap();
+    assert_eq!(resp.dropped_versions, 0);
+    let size_before = sst_size(&dir);
+    let data_size = (NUM_KEYS * NUM_VERSIONS * VALUE_SIZE) as u64;
+    assert!(size_before >= data_size, "sst size {size_before}");
+
+    // All the versions except the latest ones are beneath the watermark, and the
+    // following reads are above it.
+    let gc_watermark = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    let req = CompactGroupRequest { group_id, gc_watermark: Some(gc_watermark) };
+    let resp = client.compact_group(req).await.unwrap();
+    assert!(resp.dropped_versions >= (NUM_KEYS * (NUM_VERSIONS - 1)) as u64, "{resp:?}");
+    assert!(resp.reclaimed_bytes >= data_size / 2, "{resp:?}");
+    let size_after = sst_size(&dir);
+    assert!(
+        size_after < size_before - data_size / 2,
+        "sst size before {size_before}, after {size_after}"
+    );
+
+    // The latest versions are still readable.
+    for (i, expect) in latest_values.into_iter().enumerate() {
+        let key = format!("key-{i:04}").into_bytes();
+        let value = db.get(table.id, key).await.unwrap();
+        assert_eq!(value, Some(expect));
+    }
+}
+
+#[sekas_macro::test]
+async fn compaction_drops_versions_beyond_max_versions() {
+    const NUM_REWRITES: usize = 1000;
+    const MAX_VERSIONS_PER_KEY: u64 = 5;
+
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let mut opts = CreateTableOptions::new("table");
+    opts.properties.insert(MAX_VERSIONS.to_owned(), MAX_VERSIONS_PER_KEY.to_string());
+    let table = db.create_table_with(opts).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let mut versions = Vec::with_capacity(NUM_REWRITES);
+    for i in 0..NUM_REWRITES {
+        let mut txn = db.begin_txn();
+        let value = format!("value-{i}").into_bytes();
+        txn.put(table.id, WriteBuilder::new(b"key".to_vec()).ensure_put(value));
+        versions.push(txn.commit().await.unwrap().version);
+    }
+    // The intents are resolved by the leader asynchronously.
+    sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+    let state = db.get_raw(table.id, b"key".to_vec()).await.unwrap();
+    assert_eq!(state.versions_retained, NUM_REWRITES as u64);
+
+    // Only the newest versions are retained, the version beneath the watermark
+    // is dropped even though the reads at the watermark are accepted.
+    let gc_watermark = versions[NUM_REWRITES - MAX_VERSIONS_PER_KEY as usize - 1];
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let client = node_client_with_retry(nodes.values().next().unwrap()).await;
+    let mut truncated = false;
+    for _ in 0..100 {
+        let req = CompactGroupRequest { group_id, gc_watermark: Some(gc_watermark) };
+        client.compact_group(req).await.unwrap();
+        let state = db.get_raw(table.id, b"key".to_vec()).await.unwrap();
+        if state.versions_retained == MAX_VERSIONS_PER_KEY {
+            truncated = true;
+            break;
+        }
+        // The table desc might not be delivered to the node yet.
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(truncated, "the versions beyond max versions should be dropped");
+    let latest = db.get(table.id, b"key".to_vec()).await.unwrap();
+    assert_eq!(latest, Some(format!("value-{}", NUM_REWRITES - 1).into_bytes()));
+
+    // The retained versions are readable, the reads of the dropped versions are
+    // rejected, either by the watermark or by the truncated version.
+    let oldest_retained = versions[NUM_REWRITES - MAX_VERSIONS_PER_KEY as usize];
+    let opts = ReadOptions::exact(oldest_retained);
+    let resp = db.get_with(table.id, b"key".to_vec(), &opts).await.unwrap();
+    let expect = format!("value-{}", NUM_REWRITES - MAX_VERSIONS_PER_KEY as usize).into_bytes();
+    assert_eq!(resp.value, Some(expect));
+    let rejected = [
+        (versions[0], gc_watermark),
+        (gc_watermark, oldest_retained),
+        (oldest_retained - 1, oldest_retained),
+    ];
+    for (read_version, expect) in rejected {
+        let opts = ReadOptions::exact(read_version);
+        let r = db.get_with(table.id, b"key".to_vec(), &opts).await;
+        let Err(AppError::VersionTooOld { version, gc_watermark }) = r else {
+            panic!("the read of the dropped versions should be rejected, {r:?}");
+        };
+        assert_eq!((version, gc_watermark), (read_version, expect));
+    }
+}
+
+#[sekas_macro::test]
+async fn gc_watermark_is_advanced_and_replicated() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.set_gc_retention_ms(500);
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    c.assert_num_group_voters(group_id, 3).await;
+
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    let old_version = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    db.put(table.id, b"key".to_vec(), b"new value".to_vec()).await.unwrap();
+
+    // The leader advances the watermark above the old version in background.
+    let mut advanced = false;
+    for _ in 0..100 {
+        let opts = ReadOptions::exact(old_version);
+        match db.get_with(table.id, b"key".to_vec(), &opts).await {
+            Err(AppError::VersionTooOld { .. }) => {
+                advanced = true;
+                break;
+            }
+            Ok(resp) => assert_eq!(resp.value, Some(b"value".to_vec())),
+            Err(err) => panic!("read at the old version: {err:?}"),
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(advanced, "the gc watermark should be advanced by the leader");
+
+    // The watermark is replicated, so the superseded version is dropped by the
+    // compaction of every replica, without specifying a watermark.
+    let state = c.get_router_group_state(group_id).await.unwrap();
+    for replica in state.replicas.values() {
+        let client = node_client_with_retry(&nodes[&replica.node_id]).await;
+        let mut dropped_versions = 0;
+        for _ in 0..50 {
+            let req = CompactGroupRequest { group_id, gc_watermark: None };
+            dropped_versions += client.compact_group(req).await.unwrap().dropped_versions;
+            if dropped_versions > 0 {
+                break;
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(dropped_versions > 0, "replica {} keeps the superseded version", replica.id);
+    }
+    let value = db.get(table.id, b"key".to_vec()).await.unwrap();
+    assert_eq!(value, Some(b"new value".to_vec()));
+}
@@ -0,0 +1,139 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::group_response_union::Response;
+use sekas_api::server::v1::*;
+use sekas_client::{ClientOptions, GroupClient, SekasClient};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn get_request(shard_id: u64, key: &[u8]) -> Request {
+    Request::Get(ShardGetRequest {
+        shard_id,
+        start_version: u64::MAX,
+        user_key: key.to_vec(),
+        ..Default::default()
+    })
+}
+
+/// Wait until the number of the active streams to the nodes satisfies the
+/// condition.
+async fn wait_for_streams(app: &SekasClient, cond: impl Fn(usize) -> bool) {
+    for _ in 0..100 {
+        let streams = app.channel_stats().values().map(|s| s.streams).sum::<usize>();
+        if cond(streams) {
+            return;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the active streams are not expected: {:?}", app.channel_stats());
+}
+
+#[sekas_macro::test]
+async fn group_clients_share_bounded_channels() {
+    const NUM_NODES: usize = 3;
+    const NUM_GROUP_CLIENTS: usize = 500;
+    const MAX_CHANNELS: usize = 2;
+
+    let ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(NUM_NODES).await;
+    let c = ClusterClient::new(nodes).await;
+    let opts = ClientOptions { max_channels_per_node: Some(MAX_CHANNELS), ..Default::default() };
+    let app = c.app_client_with_options(opts).await;
+    let db = app.create_database("db".into()).await.unwrap();
+
+    let mut shards = vec![];
+    for i in 0..NUM_NODES {
+        let table = db.create_table(format!("table_{i}")).await.unwrap();
+        c.assert_table_ready(table.id).await;
+        db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+        let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+        let shard_id = c.get_shard_desc(table.id, b"key").await.unwrap().id;
+        shards.push((group_id, shard_id));
+    }
+
+    let mut handles = Vec::with_capacity(NUM_GROUP_CLIENTS);
+    for i in 0..NUM_GROUP_CLIENTS {
+        let (group_id, shard_id) = shards[i % shards.len()];
+        let app = app.clone();
+        handles.push(sekas_runtime::spawn(async move {
+            let mut group_client = GroupClient::lazy(group_id, app);
+            group_client.request(&get_request(shard_id, b"key")).await
+        }));
+    }
+    for handle in handles {
+        match handle.await.unwrap() {
+            Ok(Response::Get(resp)) => {
+                assert_eq!(resp.value.and_then(|v| v.content), Some(b"value".to_vec()));
+            }
+            others => panic!("unexpected response {others:?}"),
+        }
+    }
+
+    // The group clients share the channels to each node instead of dialing their
+    // own.
+    let stats = app.channel_stats();
+    assert!(!stats.is_empty() && stats.len() <= NUM_NODES, "{stats:?}");
+    for (addr, stats) in &stats {
+        assert!(stats.channels <= MAX_CHANNELS, "node {addr}: {stats:?}");
+        assert_eq!(stats.streams, 0, "node {addr}: {stats:?}");
+    }
+    let total = stats.values().map(|s| s.channels).sum::<usize>();
+    assert!(total <= NUM_NODES * MAX_CHANNELS, "{stats:?}");
+}
+
+#[sekas_macro::test]
+async fn long_lived_streams_are_counted() {
+    const NUM_WATCHES: usize = 8;
+    const MAX_CHANNELS: usize = 2;
+
+    let ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let opts = ClientOptions { max_channels_per_node: Some(MAX_CHANNELS), ..Default::default() };
+    let app = c.app_client_with_options(opts).await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let mut watchers = Vec::with_capacity(NUM_WATCHES);
+    for i in 0..NUM_WATCHES {
+        watchers.push(db.watch(table.id, format!("key_{i}").as_bytes()).await.unwrap());
+    }
+
+    // The watches keep their streams open, they are counted until closed, so
+    // the streams are spread across the channels.
+    wait_for_streams(&app, |streams| streams >= NUM_WATCHES).await;
+    let stats = app.channel_stats();
+    assert_eq!(stats.len(), 1, "{stats:?}");
+    for (addr, stats) in &stats {
+        assert_eq!(stats.channels, MAX_CHANNELS, "node {addr}: {stats:?}");
+    }
+
+    drop(watchers);
+    wait_for_streams(&app, |streams| streams == 0).await;
+}
@@ -0,0 +1,56 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+async fn execute(c: &ClusterClient, stmt: &str) -> ExecuteResult {
+    let json_body = c.root_client().handle_statement(stmt).await.unwrap();
+    serde_json::from_slice(&json_body).unwrap()
+}
+
+#[sekas_macro::test]
+async fn verify_coverage_of_split_shards() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let shard = c.get_shard_desc(table.id, b"a").await.unwrap();
+    c.root_client().split_shard(shard.id, Some(b"m".to_vec())).await.unwrap();
+
+    match execute(&c, "DEBUG VERIFY COVERAGE").await {
+        ExecuteResult::Msg(msg) => assert_eq!(msg, "no violations are found"),
+        others => panic!("verify coverage: {others:?}"),
+    }
+    match execute(&c, "DEBUG VERIFY unknown").await {
+        ExecuteResult::Msg(msg) => assert!(msg.contains("unknown property"), "{msg}"),
+        others => panic!("verify unknown property: {others:?}"),
+    }
+}
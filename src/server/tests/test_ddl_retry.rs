@@ -0,0 +1,142 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use log::info;
+use sekas_client::{AppError, CreateTableOptions, Database};
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// Execute the statement at root, returns the rows of the result.
+async fn show(c: &ClusterClient, stmt: &str) -> Vec<Vec<serde_json::Value>> {
+    let json_body = c.root_client().handle_statement(stmt).await.unwrap();
+    match serde_json::from_slice(&json_body).unwrap() {
+        ExecuteResult::Data(result) => result.rows.into_iter().map(|row| row.values).collect(),
+        result => panic!("execute {stmt}: {result:?}"),
+    }
+}
+
+/// The ids of the shards of the table, in the groups reported to root.
+async fn table_shards(c: &ClusterClient, table_id: u64) -> Vec<u64> {
+    let mut shards = vec![];
+    for group in show(c, "SHOW groups").await {
+        let group_id = group[0].as_u64().unwrap();
+        for shard in show(c, &format!("SHOW shards FROM {group_id}")).await {
+            if shard[1].as_u64() == Some(table_id) {
+                shards.push(shard[0].as_u64().unwrap());
+            }
+        }
+    }
+    shards
+}
+
+async fn create_table(db: &Database, name: &str, request_id: &str) -> sekas_client::AppResult<u64> {
+    let opts = CreateTableOptions {
+        request_id: Some(request_id.to_owned()),
+        ..CreateTableOptions::new(name)
+    };
+    db.create_table_with(opts).await.map(|desc| desc.id)
+}
+
+#[sekas_macro::test]
+async fn retried_ddl_returns_original_desc() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let root_client = c.root_client();
+    let db = root_client.create_database("db".into(), "create-db".into()).await.unwrap();
+    let retried = root_client.create_database("db".into(), "create-db".into()).await.unwrap();
+    assert_eq!(db.id, retried.id);
+    let result = root_client.create_database("db".into(), "other".into()).await;
+    assert!(matches!(result, Err(sekas_client::Error::AlreadyExists(_))), "{result:?}");
+
+    let db = app.open_database("db".into()).await.unwrap();
+    let table_id = create_table(&db, "table", "create-table").await.unwrap();
+    assert_eq!(create_table(&db, "table", "create-table").await.unwrap(), table_id);
+    let result = create_table(&db, "table", "other").await;
+    assert!(matches!(result, Err(AppError::AlreadyExists(_))), "{result:?}");
+    assert_eq!(db.list_table().await.unwrap().len(), 1);
+}
+
+#[sekas_macro::test]
+async fn resume_create_table_after_root_failover() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    let pause = ctx.mut_root_testing_knobs().pause_before_write_table_desc.clone();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    c.assert_root_group_has_promoted().await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let probe = db.create_table("probe".into()).await.unwrap();
+
+    // The create table job is paused once the shard is created.
+    pause.store(true, Ordering::Release);
+    let table_id = probe.id + 1;
+    let cloned_db = db.clone();
+    let handle =
+        sekas_runtime::spawn(
+            async move { create_table(&cloned_db, "table", "create-table").await },
+        );
+    for _ in 0..1000 {
+        if c.get_shard_desc(table_id, b"key").await.is_some() {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(c.get_shard_desc(table_id, b"key").await.is_some());
+    assert!(db.open_table("table".into()).await.is_err());
+
+    // Kill the root leader before the table desc is written.
+    let leader = c.get_group_leader_node_id(0).await.unwrap();
+    info!("stop the root leader {leader}");
+    ctx.stop_server(leader).await;
+    pause.store(false, Ordering::Release);
+
+    let mut result = create_table(&db, "table", "create-table").await;
+    for _ in 0..100 {
+        match &result {
+            Ok(_) | Err(AppError::AlreadyExists(_)) => break,
+            Err(err) => info!("retry create table: {err:?}"),
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+        result = create_table(&db, "table", "create-table").await;
+    }
+    assert_eq!(result.unwrap(), table_id);
+    match handle.await.unwrap() {
+        Ok(id) => assert_eq!(id, table_id),
+        Err(err) => assert!(!matches!(err, AppError::AlreadyExists(_)), "{err:?}"),
+    }
+
+    let tables = db.list_table().await.unwrap();
+    let tables = tables.iter().filter(|t| t.name == "table").collect::<Vec<_>>();
+    assert_eq!(tables.len(), 1, "{tables:?}");
+    assert_eq!(tables[0].id, table_id);
+    sekas_runtime::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(table_shards(&c, table_id).await.len(), 1);
+}
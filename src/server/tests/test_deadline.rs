@@ -0,0 +1,117 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use sekas_api::server::v1::*;
+use sekas_client::{AppError, CallOptions, TxnOptions, TxnStateTable, WriteBuilder};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+#[sekas_macro::test]
+async fn commit_against_stalled_group_times_out() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let stall = ctx.stall_write_intents();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"v1".to_vec()).await.unwrap();
+
+    stall.store(true, Ordering::Release);
+    let mut txn = db.begin_txn();
+    txn.put(table.id, WriteBuilder::new(b"key".to_vec()).ensure_put(b"v2".to_vec()));
+    let start = Instant::now();
+    let result = txn.commit_with(CallOptions::with_timeout(Duration::from_millis(100))).await;
+    let elapsed = start.elapsed();
+    let txn_id = match result {
+        Err(AppError::CommitTimedOut { txn_id, outcome_unknown: false }) => txn_id,
+        other => panic!("expect commit timed out with known outcome, but got {other:?}"),
+    };
+    assert!(elapsed < Duration::from_secs(1), "{elapsed:?}");
+    assert_ne!(txn_id, 0);
+
+    // The reads are not stalled, and the txn is never committed.
+    let txn = db.begin_txn();
+    let value = txn.get_with(table.id, b"key".to_vec(), &CallOptions::default()).await.unwrap();
+    assert_eq!(value, Some(b"v1".to_vec()));
+    let ts_table = TxnStateTable::new(app.clone(), Some(Duration::from_secs(5)));
+    let mut aborted = false;
+    for _ in 0..100 {
+        let record = ts_table.get_txn_record(txn_id).await.unwrap().unwrap();
+        if record.state == TxnState::Aborted {
+            aborted = true;
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(aborted, "the timed out txn {txn_id} should be aborted");
+
+    stall.store(false, Ordering::Release);
+    db.put(table.id, b"key".to_vec(), b"v3".to_vec()).await.unwrap();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"v3".to_vec()));
+}
+
+#[sekas_macro::test]
+async fn default_op_timeout_bounds_database_calls() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let stall = ctx.stall_write_intents();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    stall.store(true, Ordering::Release);
+    let bounded_db = db.with_call_options(CallOptions::with_timeout(Duration::from_millis(100)));
+    let result = bounded_db.put(table.id, b"key".to_vec(), b"value".to_vec()).await;
+    assert!(
+        matches!(result, Err(AppError::CommitTimedOut { outcome_unknown: false, .. })),
+        "{result:?}"
+    );
+
+    // The timeout of the call overrides the default op timeout of the txn.
+    let mut txn = db.begin_txn();
+    txn.set_options(TxnOptions {
+        default_op_timeout: Some(Duration::from_secs(60)),
+        ..Default::default()
+    });
+    txn.put(table.id, WriteBuilder::new(b"key".to_vec()).ensure_put(b"value".to_vec()));
+    let result = txn.commit_with(CallOptions::with_timeout(Duration::from_millis(100))).await;
+    assert!(matches!(result, Err(AppError::CommitTimedOut { .. })), "{result:?}");
+
+    // The DDLs are issued with the timeout as well.
+    stall.store(false, Ordering::Release);
+    let bounded_db = db.with_call_options(CallOptions::with_timeout(Duration::from_secs(10)));
+    let table = bounded_db.create_table("table-1".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    bounded_db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert_eq!(bounded_db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+}
@@ -0,0 +1,154 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use sekas_client::{Database, DeletePrefixOptions, Range, RangeRequest};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+use crate::helper::runtime::spawn;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn key(prefix: &str, index: usize) -> Vec<u8> {
+    format!("{prefix}-{index:03}").into_bytes()
+}
+
+async fn prefix_keys(db: &Database, table_id: u64, prefix: &str) -> Vec<Vec<u8>> {
+    let req = RangeRequest {
+        table_id,
+        range: Range::Prefix(format!("{prefix}-").into_bytes()),
+        limit: 17,
+        ..Default::default()
+    };
+    db.range(req).await.unwrap().into_key_stream().try_collect().await.unwrap()
+}
+
+/// Split the shard covering the first key at the split key.
+async fn split_shard(c: &ClusterClient, table_id: u64, split_key: Vec<u8>, new_shard_id: u64) {
+    let group_state = c.find_router_group_state_by_key(table_id, &split_key).await.unwrap();
+    let shard_desc = c.get_shard_desc(table_id, &split_key).await.unwrap();
+    c.group(group_state.id)
+        .split_shard(shard_desc.id, new_shard_id, Some(split_key))
+        .await
+        .unwrap();
+    c.assert_group_contains_shard(group_state.id, new_shard_id).await;
+}
+
+#[sekas_macro::test]
+async fn delete_prefix_across_shards() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    for prefix in ["a", "b", "c"] {
+        for i in 0..100 {
+            db.put(table.id, key(prefix, i), b"value".to_vec()).await.unwrap();
+        }
+    }
+
+    // The prefix `b` is covered by three shards.
+    let first_shard_id = sekas_schema::FIRST_USER_SHARD_ID;
+    split_shard(&c, table.id, key("b", 33), first_shard_id + 1024).await;
+    split_shard(&c, table.id, key("b", 66), first_shard_id + 1025).await;
+
+    // Keep writing the neighbouring prefix during the deletion.
+    let stopped = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let db = db.clone();
+        let stopped = stopped.clone();
+        spawn(async move {
+            let mut i = 100;
+            while !stopped.load(Ordering::Relaxed) {
+                db.put(table.id, key("c", i), b"value".to_vec()).await.unwrap();
+                i += 1;
+            }
+            i
+        })
+    };
+
+    let mut watcher = db.watch(table.id, &key("b", 50)).await.unwrap();
+    let value = watcher.next().await.unwrap().unwrap();
+    assert_eq!(value.content, Some(b"value".to_vec()));
+
+    // The dry run only counts the keys.
+    let opts = DeletePrefixOptions { dry_run: true, ..Default::default() };
+    let progress = db.delete_prefix(table.id, b"b-".to_vec(), opts).await.unwrap();
+    assert_eq!(progress.wait().await.unwrap(), 100);
+    assert_eq!(prefix_keys(&db, table.id, "b").await.len(), 100);
+
+    let opts = DeletePrefixOptions { rate_limit: 1000, ..Default::default() };
+    let progress = db.delete_prefix(table.id, b"b-".to_vec(), opts).await.unwrap();
+    let shards = progress.try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(shards.len(), 3, "{shards:?}");
+    assert_eq!(shards.iter().map(|shard| shard.num_deleted).sum::<u64>(), 100);
+
+    stopped.store(true, Ordering::Relaxed);
+    let num_written = writer.await.unwrap();
+
+    // The watcher receives the delete event.
+    let value = watcher.next().await.unwrap().unwrap();
+    assert!(value.content.is_none());
+
+    assert!(prefix_keys(&db, table.id, "b").await.is_empty());
+    assert_eq!(
+        prefix_keys(&db, table.id, "a").await,
+        (0..100).map(|i| key("a", i)).collect::<Vec<_>>()
+    );
+    let mut expect_keys = (0..num_written).map(|i| key("c", i)).collect::<Vec<_>>();
+    expect_keys.sort();
+    assert_eq!(prefix_keys(&db, table.id, "c").await, expect_keys);
+}
+
+#[sekas_macro::test]
+async fn delete_prefix_keeps_newer_writes() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    for i in 0..10 {
+        db.put(table.id, key("b", i), b"value".to_vec()).await.unwrap();
+    }
+
+    // The write above the fence version survives, no matter whether the shard
+    // is deleted before it or not.
+    let progress =
+        db.delete_prefix(table.id, b"b-".to_vec(), DeletePrefixOptions::default()).await.unwrap();
+    db.put(table.id, key("b", 5), b"newer".to_vec()).await.unwrap();
+    let num_deleted = progress.wait().await.unwrap();
+    assert!(num_deleted == 9 || num_deleted == 10, "{num_deleted}");
+    assert_eq!(prefix_keys(&db, table.id, "b").await, vec![key("b", 5)]);
+    assert_eq!(db.get(table.id, key("b", 5)).await.unwrap(), Some(b"newer".to_vec()));
+
+    // The empty prefix is rejected.
+    assert!(db.delete_prefix(table.id, vec![], DeletePrefixOptions::default()).await.is_err());
+}
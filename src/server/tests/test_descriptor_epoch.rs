@@ -0,0 +1,263 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use log::info;
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::report_request::GroupUpdates;
+use sekas_api::server::v1::*;
+use sekas_api::Epoch;
+use sekas_client::WriteBuilder;
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// Send a write intent to the replica on the node directly, with the specified
+/// epoch. Returns the descriptor which the write is served under, if the epoch
+/// is not matched.
+async fn write_with_epoch(
+    addr: &str,
+    group_id: u64,
+    epoch: u64,
+    shard_id: u64,
+    start_version: u64,
+    key: &[u8],
+) -> sekas_client::Result<Option<GroupDesc>> {
+    let client = node_client_with_retry(addr).await;
+    let write = WriteBuilder::new(key.to_vec()).ensure_put(b"value".to_vec());
+    let req = GroupRequest {
+        group_id,
+        epoch,
+        request: Some(GroupRequestUnion {
+            request: Some(Request::WriteIntent(WriteIntentRequest {
+                shard_id,
+                start_version,
+                write: Some(write_intent_request::Write::Put(write)),
+                ..Default::default()
+            })),
+        }),
+        ..Default::default()
+    };
+    let resp = client.unary_group_request(req).await?;
+    match (resp.response, resp.error.map(sekas_client::Error::from)) {
+        (Some(_), None) => Ok(None),
+        (Some(_), Some(sekas_client::Error::EpochNotMatch(desc))) => Ok(Some(desc)),
+        (_, Some(err)) => Err(err),
+        (None, None) => unreachable!("the response of write intent is empty"),
+    }
+}
+
+/// Read the descriptor of group from the leader, by sending a request with an
+/// expired epoch.
+async fn read_group_desc(
+    nodes: &HashMap<u64, String>,
+    c: &ClusterClient,
+    group_id: u64,
+    shard_id: u64,
+) -> GroupDesc {
+    for i in 0..1000 {
+        if let Some(node_id) = c.get_group_leader_node_id(group_id).await {
+            let start_version = c.root_client().alloc_txn_id(1, None).await.unwrap();
+            let key = format!("read-desc-{i}");
+            let addr = &nodes[&node_id];
+            match write_with_epoch(addr, group_id, 0, shard_id, start_version, key.as_bytes()).await
+            {
+                Ok(Some(desc)) => return desc,
+                result => info!("read descriptor of group {group_id}: {result:?}"),
+            }
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("read descriptor of group {group_id} timeout");
+}
+
+async fn show_alerts(c: &ClusterClient) -> Vec<(String, String)> {
+    let json_body = c.root_client().handle_statement("SHOW alerts").await.unwrap();
+    match serde_json::from_slice(&json_body).unwrap() {
+        ExecuteResult::Data(result) => {
+            assert_eq!(result.columns, vec!["kind", "age", "message"]);
+            result
+                .rows
+                .into_iter()
+                .map(|row| {
+                    let kind = row.values[0].as_str().unwrap().to_owned();
+                    let message = row.values[2].as_str().unwrap().to_owned();
+                    (kind, message)
+                })
+                .collect()
+        }
+        result => panic!("show alerts: {result:?}"),
+    }
+}
+
+#[sekas_macro::test]
+async fn stale_leader_sync_epoch_before_serving() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let shard_id = c.get_shard_desc(table.id, b"key").await.unwrap().id;
+    let desc = read_group_desc(&nodes, &c, group_id, shard_id).await;
+    let stale_epoch = desc.epoch;
+
+    // 1. The catalog is advanced with a shard unknown by the replicas, just like
+    // the replicas are restored from an old disk image.
+    let extra_shard = ShardDesc::with_range(1 << 40, table.id + 1000, vec![], vec![]);
+    let mut forged_shards = desc.shards.clone();
+    forged_shards.push(extra_shard.clone());
+    let forged_desc = GroupDesc {
+        epoch: Epoch(stale_epoch).apply_shard_delta().0,
+        shards: forged_shards,
+        ..desc.clone()
+    };
+    let forged_epoch = forged_desc.epoch;
+    let report = |desc: GroupDesc| ReportRequest {
+        updates: vec![GroupUpdates { group_id, group_desc: Some(desc), ..Default::default() }],
+    };
+    let resp = c.root_client().report(&report(forged_desc.clone())).await.unwrap();
+    assert!(resp.newer_group_descs.is_empty());
+    let resp = c.root_client().report(&report(desc.clone())).await.unwrap();
+    assert_eq!(resp.newer_group_descs, vec![forged_desc.clone()]);
+
+    // 2. Elect a new leader with the stale descriptor.
+    let leader_id = c.assert_group_leader(group_id).await;
+    let leader_node_id = c.get_group_leader_node_id(group_id).await.unwrap();
+    let transferee = desc.replicas.iter().find(|r| r.id != leader_id).unwrap().id;
+    let client = node_client_with_retry(&nodes[&leader_node_id]).await;
+    let req = GroupRequest::transfer_leader(group_id, stale_epoch, transferee);
+    let resp = client.unary_group_request(req).await.unwrap();
+    assert!(resp.error.is_none(), "transfer leader: {:?}", resp.error);
+    for _ in 0..1000 {
+        if c.get_group_leader(group_id).await.is_some_and(|id| id != leader_id) {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_ne!(c.get_group_leader(group_id).await, Some(leader_id));
+
+    // 3. No write is acknowledged under the stale epoch.
+    let mut lifted_epoch = None;
+    for i in 0..1000 {
+        let start_version = c.root_client().alloc_txn_id(1, None).await.unwrap();
+        let key = format!("stale-key-{i}");
+        for addr in nodes.values() {
+            let write = write_with_epoch(
+                addr,
+                group_id,
+                stale_epoch,
+                shard_id,
+                start_version,
+                key.as_bytes(),
+            );
+            match write.await {
+                Ok(Some(desc)) => {
+                    assert!(
+                        desc.epoch > forged_epoch,
+                        "write is served under epoch {}",
+                        Epoch(desc.epoch)
+                    );
+                    assert!(
+                        desc.shards.iter().any(|s| s.id == extra_shard.id),
+                        "the shards of catalog are not taken: {desc:?}"
+                    );
+                    lifted_epoch = Some(desc.epoch);
+                }
+                Ok(None) => {
+                    panic!("write with the stale epoch {} is acknowledged", Epoch(stale_epoch))
+                }
+                Err(err) => info!("write with the stale epoch: {err:?}"),
+            }
+        }
+        if lifted_epoch.is_some() {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    let lifted_epoch = lifted_epoch.expect("the epoch of the new leader is not lifted");
+    info!("the epoch is lifted from {} to {}", Epoch(stale_epoch), Epoch(lifted_epoch));
+
+    // 4. The writes are served under the lifted epoch, and the catalog isn't
+    // overwritten by the stale shards.
+    c.assert_large_group_epoch(group_id, forged_epoch).await;
+    assert!(c.group_contains_shard(group_id, extra_shard.id));
+    db.put(table.id, b"key".to_vec(), b"new-value".to_vec()).await.unwrap();
+    let value = db.get(table.id, b"key".to_vec()).await.unwrap();
+    assert_eq!(value, Some(b"new-value".to_vec()));
+}
+
+#[sekas_macro::test]
+async fn conflict_shard_raise_alert() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let shard = c.get_shard_desc(table.id, b"key").await.unwrap();
+    assert!(show_alerts(&c).await.is_empty());
+
+    // Another group claims the range of the shard.
+    let conflict_group_id = 10000;
+    let conflict_desc = GroupDesc {
+        id: conflict_group_id,
+        epoch: 1,
+        shards: vec![ShardDesc { id: 10000, ..shard.clone() }],
+        ..Default::default()
+    };
+    let req = ReportRequest {
+        updates: vec![GroupUpdates {
+            group_id: conflict_group_id,
+            group_desc: Some(conflict_desc),
+            ..Default::default()
+        }],
+    };
+    c.root_client().report(&req).await.unwrap();
+    let alerts = show_alerts(&c).await;
+    assert_eq!(alerts.len(), 1, "{alerts:?}");
+    assert_eq!(alerts[0].0, "shard_conflict");
+    assert!(alerts[0].1.ends_with(&format!("of group {group_id}")), "{alerts:?}");
+
+    // The conflict descriptor is refused, the shard is still routed to the former
+    // group.
+    sekas_runtime::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id, group_id);
+    assert!(c.get_router_group_state(conflict_group_id).await.is_none());
+    db.put(table.id, b"key".to_vec(), b"new-value".to_vec()).await.unwrap();
+    let value = db.get(table.id, b"key".to_vec()).await.unwrap();
+    assert_eq!(value, Some(b"new-value".to_vec()));
+}
@@ -0,0 +1,199 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::{RaftRole, ReplicaState};
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+use sekas_runtime::time::Instant;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The sum of the counter over the labels, it is shared by all servers of the
+/// test process.
+fn counter(name: &str, label: (&str, &str)) -> u64 {
+    let families = prometheus::gather();
+    let Some(family) = families.iter().find(|f| f.get_name() == name) else {
+        return 0;
+    };
+    family
+        .get_metric()
+        .iter()
+        .filter(|m| {
+            m.get_label().iter().any(|l| l.get_name() == label.0 && l.get_value() == label.1)
+        })
+        .map(|m| m.get_counter().get_value() as u64)
+        .sum()
+}
+
+fn votes_ignored_in_lease() -> u64 {
+    counter("raftgroup_election_prevented_total", ("type", "in_lease"))
+}
+
+fn not_leader_proposals() -> u64 {
+    counter("raftgroup_propose_total", ("type", "not_leader"))
+}
+
+async fn execute_msg(c: &ClusterClient, stmt: &str) -> String {
+    let json_body = c.root_client().handle_statement(stmt).await.unwrap();
+    match serde_json::from_slice(&json_body).unwrap() {
+        ExecuteResult::Msg(msg) => msg,
+        others => panic!("execute {stmt}: {others:?}"),
+    }
+}
+
+async fn replica_states(c: &ClusterClient, group_id: u64) -> Vec<ReplicaState> {
+    let mut states = vec![];
+    for node_id in 0..3 {
+        if let Some(state) = c.collect_replica_state(group_id, node_id).await.unwrap() {
+            states.push(state);
+        }
+    }
+    states
+}
+
+#[sekas_macro::test]
+async fn brief_follower_partition_keeps_leader() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let leader_node_id = c.get_group_leader_node_id(group_id).await.unwrap();
+    let follower_node_id = (0..3).find(|id| *id != leader_node_id).unwrap();
+    let former_states = replica_states(&c, group_id).await;
+    assert_eq!(former_states.len(), 3, "{former_states:?}");
+    let former_ignored = votes_ignored_in_lease();
+    let former_not_leader = not_leader_proposals();
+
+    // The follower couldn't hear from the leader for several election timeouts,
+    // but the other follower still could.
+    ctx.partition(leader_node_id, follower_node_id);
+    for i in 0..10 {
+        let value = format!("value-{i}").into_bytes();
+        db.put(table.id, b"key".to_vec(), value).await.unwrap();
+        ctx.wait_election_timeout().await;
+    }
+    ctx.heal(leader_node_id, follower_node_id);
+    ctx.wait_election_timeout().await;
+    db.put(table.id, b"key".to_vec(), b"healed".to_vec()).await.unwrap();
+
+    // Neither the term nor the leader is changed, the pre-votes of the follower
+    // are ignored by the other follower which has heard from the leader.
+    let states = replica_states(&c, group_id).await;
+    for (former, state) in former_states.iter().zip(&states) {
+        assert_eq!(former.term, state.term, "former {former_states:?}, now {states:?}");
+    }
+    assert_eq!(c.get_group_leader_node_id(group_id).await, Some(leader_node_id));
+    assert!(votes_ignored_in_lease() > former_ignored);
+    assert_eq!(not_leader_proposals(), former_not_leader);
+}
+
+#[sekas_macro::test]
+async fn partitioned_leader_fails_over() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let leader_node_id = c.get_group_leader_node_id(group_id).await.unwrap();
+    let former_term =
+        c.collect_replica_state(group_id, leader_node_id).await.unwrap().unwrap().term;
+
+    let start = Instant::now();
+    for node_id in (0..3).filter(|id| *id != leader_node_id) {
+        ctx.partition(leader_node_id, node_id);
+    }
+    let mut new_leader = None;
+    while new_leader.is_none() && start.elapsed() < Duration::from_secs(30) {
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+        new_leader = replica_states(&c, group_id).await.into_iter().find(|s| {
+            s.node_id != leader_node_id && s.role == RaftRole::Leader as i32 && s.term > former_term
+        });
+    }
+    let elapsed = start.elapsed();
+    let new_leader = new_leader.expect("a new leader should be elected");
+    // The followers campaign after a randomized election timeout, which is less
+    // than two election timeouts, the pre-vote takes another round trip.
+    assert!(elapsed < Duration::from_secs(5), "failover takes {elapsed:?}");
+
+    // The partitioned leader steps down since it loses the quorum.
+    ctx.wait_election_timeout().await;
+    let state = c.collect_replica_state(group_id, leader_node_id).await.unwrap().unwrap();
+    assert_ne!(state.role, RaftRole::Leader as i32, "{state:?}");
+
+    db.put(table.id, b"key".to_vec(), b"failover".to_vec()).await.unwrap();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"failover".to_vec()));
+    assert_eq!(c.get_group_leader_node_id(group_id).await, Some(new_leader.node_id));
+}
+
+#[sekas_macro::test]
+async fn config_election_options() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    c.assert_root_group_has_promoted().await;
+    let mut clients = vec![];
+    for addr in nodes.values() {
+        clients.push(node_client_with_retry(addr).await);
+    }
+    for client in &clients {
+        let status = client.get_node_status().await.unwrap();
+        assert!(status.pre_vote && status.check_quorum, "{status:?}");
+    }
+
+    let msg = execute_msg(&c, r#"CONFIG "raft.pre_vote" "false""#).await;
+    assert_eq!(msg, "config `raft.pre_vote` is set to `false`");
+    let msg = execute_msg(&c, r#"CONFIG "raft.check_quorum" "true""#).await;
+    assert_eq!(msg, "config `raft.check_quorum` is set to `true`");
+    let msg = execute_msg(&c, r#"CONFIG "raft.check_quorum" "maybe""#).await;
+    assert_eq!(msg, "the value of `raft.check_quorum` should be true or false");
+
+    // The options are delivered to all nodes by the heartbeats.
+    for client in &clients {
+        let mut applied = false;
+        for _ in 0..100 {
+            let status = client.get_node_status().await.unwrap();
+            assert!(status.check_quorum, "{status:?}");
+            if !status.pre_vote {
+                applied = true;
+                break;
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(applied, "the node should apply `raft.pre_vote`");
+    }
+}
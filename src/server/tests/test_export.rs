@@ -0,0 +1,274 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use prost::Message;
+use sekas_api::server::v1::{CompactGroupRequest, PinGcRequest, ValueSet};
+use sekas_client::{AppError, Database, Range, RangeRequest, WriteBuilder};
+use sekas_rock::fn_name;
+use sekas_runtime::time::sleep;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// Read the key values of the table, at the version if it is specified.
+async fn read_table(
+    db: &Database,
+    table_id: u64,
+    version: Option<u64>,
+) -> Result<BTreeMap<Vec<u8>, Vec<u8>>, AppError> {
+    let req = RangeRequest {
+        table_id,
+        version,
+        range: Range::Range { begin: None, end: None },
+        ..Default::default()
+    };
+    let value_sets = db.range(req).await?.try_collect_vec(0).await?;
+    Ok(value_sets
+        .into_iter()
+        .filter_map(|value_set| {
+            let value = value_set.values.into_iter().next().and_then(|v| v.content)?;
+            Some((value_set.user_key, value))
+        })
+        .collect())
+}
+
+/// Read the key values of the dump.
+fn read_dump(path: &Path) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    let content = std::fs::read(path).unwrap();
+    let mut buf = content.as_slice();
+    let mut values = BTreeMap::new();
+    while !buf.is_empty() {
+        let value_set = ValueSet::decode_length_delimited(&mut buf).unwrap();
+        if let Some(value) = value_set.values.into_iter().next().and_then(|v| v.content) {
+            values.insert(value_set.user_key, value);
+        }
+    }
+    values
+}
+
+/// Move the shard of the table to the group, until the router observes it.
+async fn move_table_to_group(c: &ClusterClient, table_id: u64, target_group_id: u64) {
+    c.assert_num_group_voters(target_group_id, 3).await;
+    for _ in 0..100 {
+        let state = c.find_router_group_state_by_key(table_id, &[]).await.unwrap();
+        if state.id == target_group_id {
+            return;
+        }
+        let shard_desc = c.get_shard_desc(table_id, &[]).await.unwrap();
+        let mut client = c.group(target_group_id);
+        if let Err(err) = client.accept_shard(state.id, state.epoch, &shard_desc).await {
+            tracing::warn!("move table {table_id} to group {target_group_id}: {err:?}");
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the table {table_id} should be moved to group {target_group_id}");
+}
+
+#[sekas_macro::test]
+async fn export_tables_at_single_version() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.set_num_cpus(3); // Add another group to serve one of the tables.
+    ctx.enable_group_balance();
+    ctx.disable_shard_balance();
+    let export_dir = ctx.enable_export();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let orders = db.create_table("orders".into()).await.unwrap();
+    let items = db.create_table("items".into()).await.unwrap();
+    c.assert_table_ready(orders.id).await;
+    c.assert_table_ready(items.id).await;
+
+    // The tables are served by different groups, so the export crosses them.
+    let orders_group_id = c.find_router_group_state_by_key(orders.id, &[]).await.unwrap().id;
+    let target_group_id = if orders_group_id == 1 { 2 } else { 1 };
+    move_table_to_group(&c, items.id, target_group_id).await;
+
+    // Each txn writes a pair of rows into both tables.
+    let stopped = Arc::new(AtomicBool::new(false));
+    let committed = Arc::new(AtomicU64::new(0));
+    let workload = {
+        let (db, stopped, committed) = (db.clone(), stopped.clone(), committed.clone());
+        let (orders_id, items_id) = (orders.id, items.id);
+        sekas_runtime::spawn(async move {
+            let mut i = 0u64;
+            while !stopped.load(Ordering::Acquire) {
+                let key = format!("order-{i:06}").into_bytes();
+                let mut txn = db.begin_txn();
+                txn.put(orders_id, WriteBuilder::new(key.clone()).ensure_put(b"order".to_vec()));
+                txn.put(items_id, WriteBuilder::new(key).ensure_put(b"item".to_vec()));
+                txn.commit().await.unwrap();
+                committed.fetch_add(1, Ordering::AcqRel);
+                i += 1;
+            }
+        })
+    };
+    while committed.load(Ordering::Acquire) < 20 {
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    // The paths outside of the export directory are rejected.
+    let specs = vec![("db".into(), "orders".into()), ("db".into(), "items".into())];
+    let outside = ctx.server_dir(0).join("export").display().to_string();
+    for path in [outside.as_str(), "../export", "a/../../export", ""] {
+        let result = c.root_client().export_tables(specs.clone(), path.to_owned()).await;
+        assert!(result.is_err(), "{path}: {result:?}");
+    }
+
+    let dest = export_dir.join("export");
+    let manifest = c.root_client().export_tables(specs.clone(), "export".into()).await.unwrap();
+    let exported_at = committed.load(Ordering::Acquire);
+    while committed.load(Ordering::Acquire) < exported_at + 20 {
+        sleep(Duration::from_millis(10)).await;
+    }
+    stopped.store(true, Ordering::Release);
+    workload.await.unwrap();
+
+    // Every pair is either fully present or fully absent in the export.
+    assert_eq!(manifest.tables.len(), 2, "{manifest:?}");
+    assert_eq!(manifest.tables[0].table.as_ref().unwrap().id, orders.id);
+    assert_eq!(manifest.tables[1].table.as_ref().unwrap().id, items.id);
+    let exported_orders = read_dump(&dest.join(&manifest.tables[0].file));
+    let exported_items = read_dump(&dest.join(&manifest.tables[1].file));
+    assert!(exported_orders.len() >= 20, "{} orders", exported_orders.len());
+    assert!(exported_orders.keys().eq(exported_items.keys()));
+    assert_eq!(manifest.tables[0].num_keys, exported_orders.len() as u64);
+    let read_version = manifest.read_version;
+    assert_eq!(read_table(&db, orders.id, Some(read_version)).await.unwrap(), exported_orders);
+    assert_eq!(read_table(&db, items.id, Some(read_version)).await.unwrap(), exported_items);
+    assert!(read_table(&db, orders.id, None).await.unwrap().len() > exported_orders.len());
+
+    // The same directory couldn't be exported twice.
+    let result = c.root_client().export_tables(specs, "export".into()).await;
+    assert!(result.is_err(), "{result:?}");
+
+    // The tables are restored together.
+    db.delete_table("orders".into()).await.unwrap();
+    db.delete_table("items".into()).await.unwrap();
+    let result = c.root_client().import_tables("../exports/export".into()).await;
+    assert!(result.is_err(), "{result:?}");
+    let resp = c.root_client().import_tables("export".into()).await.unwrap();
+    assert_eq!(resp.read_version, read_version);
+    assert_eq!(resp.tables.len(), 2);
+    for table in &resp.tables {
+        assert!(!table.properties.contains_key(sekas_schema::property::PROVISIONING));
+        c.assert_table_ready(table.id).await;
+    }
+    db.invalidate_table("orders");
+    db.invalidate_table("items");
+    assert_eq!(read_table(&db, resp.tables[0].id, None).await.unwrap(), exported_orders);
+    assert_eq!(read_table(&db, resp.tables[1].id, None).await.unwrap(), exported_items);
+
+    // Nothing is imported if any table exists.
+    let result = c.root_client().import_tables("export".into()).await;
+    assert!(result.is_err(), "{result:?}");
+}
+
+#[sekas_macro::test]
+async fn gc_pin_retains_versions_until_lease_expires() {
+    let ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let client = node_client_with_retry(nodes.values().next().unwrap()).await;
+    let version = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    let pin_req =
+        PinGcRequest { pin_id: "pin".into(), version, lease_ms: 500, group_ids: vec![group_id] };
+    client.pin_gc(pin_req.clone()).await.unwrap();
+    db.put(table.id, b"key".to_vec(), b"new value".to_vec()).await.unwrap();
+
+    // The versions visible at the pinned version are retained by compaction.
+    let gc_watermark = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    let compact_req = CompactGroupRequest { group_id, gc_watermark: Some(gc_watermark) };
+    client.compact_group(compact_req.clone()).await.unwrap();
+    let values = read_table(&db, table.id, Some(version)).await.unwrap();
+    assert_eq!(values.get(b"key".as_slice()), Some(&b"value".to_vec()));
+
+    // The pin is rejected once the versions might be collected.
+    let result = client.pin_gc(PinGcRequest { pin_id: "stale".into(), ..pin_req }).await;
+    assert!(result.is_err(), "{result:?}");
+
+    // The pin expires by itself.
+    sleep(Duration::from_millis(600)).await;
+    client.compact_group(compact_req).await.unwrap();
+    let result = read_table(&db, table.id, Some(version)).await;
+    assert!(matches!(result, Err(AppError::VersionTooOld { .. })), "{result:?}");
+}
+
+#[sekas_macro::test]
+async fn gc_pin_survives_restart() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let addr = nodes.values().next().unwrap().clone();
+    let client = node_client_with_retry(&addr).await;
+    let version = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    let pin_req =
+        PinGcRequest { pin_id: "pin".into(), version, lease_ms: 60_000, group_ids: vec![group_id] };
+    client.pin_gc(pin_req.clone()).await.unwrap();
+    db.put(table.id, b"key".to_vec(), b"new value".to_vec()).await.unwrap();
+    drop((client, db, app, c));
+    ctx.shutdown();
+
+    // The pin is restored by the restarted node.
+    let nodes = ctx.start_servers(nodes).await;
+    let c = ClusterClient::new(nodes).await;
+    let db = c.app_client().await.open_database("db".into()).await.unwrap();
+    let client = node_client_with_retry(&addr).await;
+    let gc_watermark = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    let compact_req = CompactGroupRequest { group_id, gc_watermark: Some(gc_watermark) };
+    client.compact_group(compact_req.clone()).await.unwrap();
+    let values = read_table(&db, table.id, Some(version)).await.unwrap();
+    assert_eq!(values.get(b"key".as_slice()), Some(&b"value".to_vec()));
+
+    // The renewal of the held pin is accepted, although the watermark of the
+    // group exceeds the version.
+    client.pin_gc(pin_req.clone()).await.unwrap();
+    client.pin_gc(PinGcRequest { lease_ms: 0, ..pin_req.clone() }).await.unwrap();
+    client.compact_group(compact_req).await.unwrap();
+    let result = read_table(&db, table.id, Some(version)).await;
+    assert!(matches!(result, Err(AppError::VersionTooOld { .. })), "{result:?}");
+
+    // The groups are verified again once the pin is lost.
+    let result = client.pin_gc(pin_req).await;
+    assert!(result.is_err(), "{result:?}");
+}
@@ -0,0 +1,190 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::*;
+use sekas_client::{GroupClient, RetryState};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const HEAVY_GROUP_ID: u64 = 100000;
+const LIGHT_GROUP_IDS: [u64; 3] = [100001, 100002, 100003];
+
+/// The bound of the apply delay of the light groups.
+const MAX_LIGHT_APPLY_DELAY: Duration = Duration::from_millis(500);
+
+/// The budget of a scheduling round.
+const MAX_ENTRIES_PER_ROUND: usize = 8;
+
+fn shard_id_of(group_id: u64) -> u64 {
+    group_id * 10
+}
+
+async fn create_group(c: &ClusterClient, group_id: u64, nodes: Vec<u64>) {
+    let replicas = nodes
+        .iter()
+        .cloned()
+        .map(|node_id| {
+            let replica_id = group_id * 10 + node_id;
+            ReplicaDesc { id: replica_id, node_id, role: ReplicaRole::Voter as i32 }
+        })
+        .collect::<Vec<_>>();
+    let group_desc = GroupDesc {
+        id: group_id,
+        shards: vec![ShardDesc::whole(shard_id_of(group_id), shard_id_of(group_id))],
+        replicas: replicas.clone(),
+        ..Default::default()
+    };
+    for replica in replicas {
+        c.create_replica(replica.node_id, replica.id, group_desc.clone()).await;
+    }
+    c.assert_group_leader(group_id).await;
+}
+
+/// Put a key into the group, returns the duration until it is applied.
+async fn put(
+    group_client: &mut GroupClient,
+    group_id: u64,
+    key: Vec<u8>,
+    value: Vec<u8>,
+) -> Duration {
+    let put = PutRequest { key, value, ..Default::default() };
+    let req = Request::Write(ShardWriteRequest {
+        shard_id: shard_id_of(group_id),
+        puts: vec![put],
+        ..Default::default()
+    });
+    let mut retry_state = RetryState::default();
+    let start = Instant::now();
+    loop {
+        match group_client.request(&req).await {
+            Ok(_) => return start.elapsed(),
+            Err(err) => retry_state.retry(err).await.unwrap(),
+        }
+    }
+}
+
+/// The max apply delay of the light groups, each of them puts `num_keys` keys.
+async fn max_light_apply_delay(c: &ClusterClient, round: &str, num_keys: usize) -> Duration {
+    let mut max_delay = Duration::ZERO;
+    for i in 0..num_keys {
+        for group_id in LIGHT_GROUP_IDS {
+            let key = format!("{round}-key-{i}").into_bytes();
+            let delay = put(&mut c.group(group_id), group_id, key, b"value".to_vec()).await;
+            max_delay = max_delay.max(delay);
+        }
+    }
+    max_delay
+}
+
+fn histogram(name: &str, label: Option<(&str, &str)>) -> Option<prometheus::proto::Histogram> {
+    let families = prometheus::gather();
+    let family = families.iter().find(|f| f.get_name() == name)?;
+    family
+        .get_metric()
+        .iter()
+        .find(|m| {
+            label.is_none()
+                || m.get_label().iter().any(|l| Some((l.get_name(), l.get_value())) == label)
+        })
+        .map(|m| m.get_histogram().clone())
+}
+
+fn schedule_delay_count(kind: &str) -> u64 {
+    histogram("raftgroup_worker_schedule_delay_seconds", Some(("type", kind)))
+        .map(|h| h.get_sample_count())
+        .unwrap_or_default()
+}
+
+/// The number of rounds applying more entries than the budget.
+fn num_rounds_exceeding_apply_budget() -> u64 {
+    let Some(h) = histogram("raftgroup_worker_apply_entries_size", None) else {
+        return 0;
+    };
+    let within_budget = h
+        .get_bucket()
+        .iter()
+        .find(|b| b.get_upper_bound() >= MAX_ENTRIES_PER_ROUND as f64)
+        .map(|b| b.get_cumulative_count())
+        .unwrap_or_default();
+    h.get_sample_count() - within_budget
+}
+
+#[sekas_macro::test]
+async fn light_groups_are_not_starved_by_heavy_group() {
+    const NUM_FLOODERS: usize = 16;
+    const VALUE_SIZE: usize = 64;
+
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    ctx.disable_all_node_scheduler();
+    ctx.set_max_entries_per_round(MAX_ENTRIES_PER_ROUND);
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    // The followers acknowledge the entries appended by many rounds of the
+    // leader at once, so the entries committed in a round of the heavy group
+    // exceed the budget.
+    create_group(&c, HEAVY_GROUP_ID, vec![0, 1, 2]).await;
+    for group_id in LIGHT_GROUP_IDS {
+        create_group(&c, group_id, vec![0]).await;
+    }
+
+    // 1. Before the writes flood.
+    let delay = max_light_apply_delay(&c, "before", 16).await;
+    assert!(delay < MAX_LIGHT_APPLY_DELAY, "the apply delay before flooding is {delay:?}");
+
+    // 2. During the writes flood the heavy group.
+    let stopped = Arc::new(AtomicBool::new(false));
+    let mut flooders = Vec::with_capacity(NUM_FLOODERS);
+    for flooder in 0..NUM_FLOODERS {
+        let mut group_client = c.group(HEAVY_GROUP_ID);
+        let stopped = stopped.clone();
+        flooders.push(sekas_runtime::spawn(async move {
+            let value = vec![b'x'; VALUE_SIZE];
+            let mut i = 0;
+            while !stopped.load(Ordering::Acquire) {
+                let key = format!("flood-{flooder}-{i}").into_bytes();
+                put(&mut group_client, HEAVY_GROUP_ID, key, value.clone()).await;
+                i += 1;
+            }
+        }));
+    }
+    // Wait until the flood saturates the heavy group.
+    sekas_runtime::time::sleep(Duration::from_secs(1)).await;
+    let delay = max_light_apply_delay(&c, "during", 16).await;
+    stopped.store(true, Ordering::Release);
+    for flooder in flooders {
+        flooder.await.unwrap();
+    }
+    assert!(delay < MAX_LIGHT_APPLY_DELAY, "the apply delay during flooding is {delay:?}");
+
+    // The entries applied in a round are bounded by the budget, and the heavy
+    // group is requeued behind the light groups.
+    assert_eq!(num_rounds_exceeding_apply_budget(), 0);
+    assert!(schedule_delay_count("heavy") > 0);
+    assert!(schedule_delay_count("light") > 0);
+}
@@ -0,0 +1,212 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::{Duration, Instant};
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::group_response_union::Response;
+use sekas_api::server::v1::*;
+use sekas_client::NodeClient;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn put_request(shard_id: u64, key: &[u8], value: &[u8]) -> Request {
+    let put = PutRequest { key: key.to_vec(), value: value.to_vec(), ..Default::default() };
+    Request::Write(ShardWriteRequest { shard_id, puts: vec![put], ..Default::default() })
+}
+
+fn get_request(shard_id: u64, key: &[u8]) -> Request {
+    Request::Get(ShardGetRequest {
+        shard_id,
+        start_version: u64::MAX,
+        user_key: key.to_vec(),
+        ..Default::default()
+    })
+}
+
+/// Issue the request to the node directly, the response or the error is
+/// returned without retrying.
+async fn issue(
+    client: &NodeClient,
+    group_id: u64,
+    epoch: u64,
+    request: Request,
+    allow_forward: bool,
+) -> Result<Response, sekas_client::Error> {
+    let req = GroupRequest {
+        group_id,
+        epoch,
+        request: Some(GroupRequestUnion { request: Some(request) }),
+        allow_forward,
+        ..Default::default()
+    };
+    let resp = client.unary_group_request(req).await?;
+    if let Some(err) = resp.error {
+        return Err(err.into());
+    }
+    Ok(resp.response.unwrap().response.unwrap())
+}
+
+#[sekas_macro::test]
+async fn follower_forwards_request_to_leader() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let shard = c.get_shard_desc(table.id, b"key").await.unwrap();
+    let state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    c.assert_group_leader(state.id).await;
+    let follower = c.must_group_any_follower(state.id).await;
+    let client = node_client_with_retry(&nodes[&follower.node_id]).await;
+
+    // The follower relays the response of the leader in a single round trip.
+    let put = put_request(shard.id, b"key", b"forwarded");
+    let resp = issue(&client, state.id, state.epoch, put, true).await.unwrap();
+    assert!(matches!(resp, Response::Write(_)), "{resp:?}");
+    let get = get_request(shard.id, b"key");
+    match issue(&client, state.id, state.epoch, get, true).await.unwrap() {
+        Response::Get(resp) => {
+            assert_eq!(resp.value.and_then(|v| v.content), Some(b"forwarded".to_vec()));
+        }
+        others => panic!("unexpected response {others:?}"),
+    }
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"forwarded".to_vec()));
+
+    // The request which doesn't allow forwarding is redirected.
+    let put = put_request(shard.id, b"key", b"redirected");
+    let err = issue(&client, state.id, state.epoch, put, false).await.unwrap_err();
+    assert!(matches!(err, sekas_client::Error::NotLeader(_, _, Some(_))), "{err:?}");
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"forwarded".to_vec()));
+}
+
+#[sekas_macro::test]
+async fn forward_exceeds_concurrency_limit() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.mut_forward_config().max_concurrent_forwards = 0;
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let shard = c.get_shard_desc(table.id, b"key").await.unwrap();
+    let state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    c.assert_group_leader(state.id).await;
+    let follower = c.must_group_any_follower(state.id).await;
+    let client = node_client_with_retry(&nodes[&follower.node_id]).await;
+
+    // The follower returns `NotLeader` instead of forwarding, and the client
+    // still succeeds by retrying the leader.
+    let put = put_request(shard.id, b"key", b"value");
+    let err = issue(&client, state.id, state.epoch, put, true).await.unwrap_err();
+    assert!(matches!(err, sekas_client::Error::NotLeader(_, _, Some(_))), "{err:?}");
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+}
+
+/// The round trip between the client and the nodes of the remote region.
+const CROSS_REGION_RTT: Duration = Duration::from_millis(50);
+
+/// Issue the request to a node in the remote region.
+async fn issue_cross_region(
+    client: &NodeClient,
+    group_id: u64,
+    epoch: u64,
+    request: Request,
+    allow_forward: bool,
+) -> Result<Response, sekas_client::Error> {
+    sekas_runtime::time::sleep(CROSS_REGION_RTT).await;
+    issue(client, group_id, epoch, request, allow_forward).await
+}
+
+fn percentile(latencies: &mut [Duration], percent: usize) -> Duration {
+    latencies.sort();
+    latencies[(latencies.len() * percent / 100).min(latencies.len() - 1)]
+}
+
+#[sekas_macro::test]
+async fn forward_improves_tail_latency_of_misrouted_writes() {
+    const NUM_WRITES: usize = 20;
+
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let shard = c.get_shard_desc(table.id, b"key").await.unwrap();
+    let state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    c.assert_group_leader(state.id).await;
+    let leader_node_id = c.get_group_leader_node_id(state.id).await.unwrap();
+    let follower = c.must_group_any_follower(state.id).await;
+    let follower_client = node_client_with_retry(&nodes[&follower.node_id]).await;
+    let leader_client = node_client_with_retry(&nodes[&leader_node_id]).await;
+
+    // The nodes are in the same region, and every write of the client in the
+    // remote region is routed to the follower first.
+    let mut redirected = Vec::with_capacity(NUM_WRITES);
+    for i in 0..NUM_WRITES {
+        let value = format!("redirected-{i}").into_bytes();
+        let start = Instant::now();
+        let put = put_request(shard.id, b"key", &value);
+        let err = issue_cross_region(&follower_client, state.id, state.epoch, put, false)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, sekas_client::Error::NotLeader(_, _, Some(_))), "{err:?}");
+        let put = put_request(shard.id, b"key", &value);
+        issue_cross_region(&leader_client, state.id, state.epoch, put, false).await.unwrap();
+        redirected.push(start.elapsed());
+    }
+
+    let mut forwarded = Vec::with_capacity(NUM_WRITES);
+    for i in 0..NUM_WRITES {
+        let value = format!("forwarded-{i}").into_bytes();
+        let start = Instant::now();
+        let put = put_request(shard.id, b"key", &value);
+        issue_cross_region(&follower_client, state.id, state.epoch, put, true).await.unwrap();
+        forwarded.push(start.elapsed());
+    }
+
+    // The forwarded writes save a cross region round trip.
+    let redirected_p99 = percentile(&mut redirected, 99);
+    let forwarded_p99 = percentile(&mut forwarded, 99);
+    assert!(redirected_p99 >= CROSS_REGION_RTT * 2, "{redirected_p99:?}");
+    assert!(
+        forwarded_p99 < redirected_p99,
+        "forwarded p99 {forwarded_p99:?}, redirected p99 {redirected_p99:?}"
+    );
+    let value = format!("forwarded-{}", NUM_WRITES - 1).into_bytes();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(value));
+}
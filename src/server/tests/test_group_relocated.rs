@@ -0,0 +1,112 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::group_response_union::Response;
+use sekas_api::server::v1::*;
+use sekas_client::{GroupClient, RouterGroupState};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn get_request(shard_id: u64, key: &[u8]) -> Request {
+    Request::Get(ShardGetRequest {
+        shard_id,
+        start_version: u64::MAX,
+        user_key: key.to_vec(),
+        ..Default::default()
+    })
+}
+
+#[sekas_macro::test]
+async fn stale_request_is_redirected_by_relocated_hint() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(4).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let shard = c.get_shard_desc(table.id, b"key").await.unwrap();
+    let stale_state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    let group_id = stale_state.id;
+    c.assert_group_leader(group_id).await;
+    let follower = c.must_group_any_follower(group_id).await;
+    let target_node_id =
+        *nodes.keys().find(|id| !stale_state.replicas.values().any(|r| r.node_id == **id)).unwrap();
+
+    // Move the replica away from the node of the follower.
+    let new_replica =
+        ReplicaDesc { id: 123123, node_id: target_node_id, role: ReplicaRole::Voter as i32 };
+    c.group(group_id).move_replicas(vec![new_replica], vec![follower.clone()]).await.unwrap();
+    c.assert_group_not_contains_node(group_id, follower.node_id).await;
+
+    // The node tells where the group went, once the replica is removed.
+    let client = node_client_with_retry(&nodes[&follower.node_id]).await;
+    let mut relocated = None;
+    for _ in 0..1000 {
+        let req = GroupRequest {
+            group_id,
+            epoch: stale_state.epoch,
+            request: Some(GroupRequestUnion { request: Some(get_request(shard.id, b"key")) }),
+            ..Default::default()
+        };
+        let err = match client.unary_group_request(req).await {
+            Ok(resp) => resp.error.map(sekas_client::Error::from),
+            Err(status) => Some(status.into()),
+        };
+        if let Some(sekas_client::Error::GroupRelocated(_, hint)) = err {
+            if hint.replicas.iter().any(|r| r.id == new_replica.id) {
+                relocated = Some(hint);
+                break;
+            }
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    let hint = relocated.expect("the node should return the relocated hint");
+    assert!(hint.epoch > stale_state.epoch);
+    assert!(hint.replicas.iter().all(|r| r.node_id != follower.node_id));
+
+    // The stale client only knows the removed replica. Without the hint it would
+    // retry the node and give up, so the request is served after exactly one
+    // misdirected attempt.
+    let stale_state = RouterGroupState {
+        id: group_id,
+        epoch: stale_state.epoch,
+        leader_state: None,
+        replicas: HashMap::from([(follower.id, follower)]),
+    };
+    let mut group_client = GroupClient::new(stale_state, app.clone());
+    match group_client.request(&get_request(shard.id, b"key")).await.unwrap() {
+        Response::Get(resp) => {
+            assert_eq!(resp.value.and_then(|v| v.content), Some(b"value".to_vec()));
+        }
+        others => panic!("unexpected response {others:?}"),
+    }
+}
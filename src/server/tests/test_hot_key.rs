@@ -0,0 +1,65 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+#[sekas_macro::test]
+async fn show_hot_keys_of_group() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.mut_hot_key_config().sample_rate = 1.0;
+    ctx.mut_hot_key_config().window_ms = 1000;
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"hot-key").await.unwrap().id;
+    let root_client = c.root_client();
+    let stmt = format!("SHOW hotkeys FROM {group_id}");
+    let mut reported = false;
+    for i in 0..600 {
+        db.put(table.id, b"hot-key".to_vec(), b"value".to_vec()).await.unwrap();
+        db.get(table.id, b"hot-key".to_vec()).await.unwrap();
+        if i % 4 == 0 {
+            db.put(table.id, b"warm-key".to_vec(), b"value".to_vec()).await.unwrap();
+        }
+
+        let resp = root_client.handle_statement(&stmt).await.unwrap();
+        let resp = String::from_utf8(resp).unwrap();
+        // The hot keys are in descending order of request rate.
+        if let (Some(hot), Some(warm)) = (resp.find("hot-key"), resp.find("warm-key")) {
+            assert!(hot < warm, "{resp}");
+            reported = true;
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(reported, "the hot keys of group {group_id} are not reported");
+}
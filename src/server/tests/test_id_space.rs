@@ -0,0 +1,95 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use log::info;
+use sekas_api::server::v1::*;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const ROOT_GROUP_ID: u64 = 0;
+
+#[sekas_macro::test]
+async fn repair_rolled_back_id_counters() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let rollback = ctx.mut_root_testing_knobs().rollback_id_counters.clone();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    c.assert_root_group_has_promoted().await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let mut table_ids = HashSet::new();
+    let mut shard_ids = HashSet::new();
+    for i in 0..3 {
+        let table = db.create_table(format!("table-{i}")).await.unwrap();
+        c.assert_table_ready(table.id).await;
+        table_ids.insert(table.id);
+        shard_ids.insert(c.get_shard_desc(table.id, b"key").await.unwrap().id);
+    }
+
+    // The new root leader finds the counters rolled back, as if the catalog is
+    // restored from an old backup.
+    rollback.store(true, Ordering::Release);
+    c.transfer_group_leader_randomly(ROOT_GROUP_ID).await.unwrap();
+    let mut repaired = false;
+    for _ in 0..100 {
+        if !rollback.load(Ordering::Acquire) {
+            let events = c.root_client().list_topology_events().await.unwrap_or_default();
+            repaired = events.iter().any(|e| {
+                e.kind == topology_event::Kind::Repair as i32 && e.target == "counter/table_id"
+            });
+            if repaired {
+                break;
+            }
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(repaired);
+
+    // The ids allocated since then never collide with the live ones.
+    let mut result = db.create_table("table-new".into()).await;
+    for _ in 0..100 {
+        match &result {
+            Ok(_) => break,
+            Err(err) => info!("retry create table: {err:?}"),
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+        result = db.create_table("table-new".into()).await;
+    }
+    let table = result.unwrap();
+    c.assert_table_ready(table.id).await;
+    assert!(table_ids.iter().all(|id| *id < table.id), "{table_ids:?} {}", table.id);
+    let shard = c.get_shard_desc(table.id, b"key").await.unwrap();
+    assert!(!shard_ids.contains(&shard.id), "{shard_ids:?} {}", shard.id);
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+
+    let tables = db.list_table().await.unwrap();
+    let unique_ids = tables.iter().map(|t| t.id).collect::<HashSet<_>>();
+    assert_eq!(unique_ids.len(), tables.len(), "{tables:?}");
+}
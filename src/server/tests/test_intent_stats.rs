@@ -0,0 +1,151 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::*;
+use sekas_client::{TxnStateTable, WriteBuilder};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn shard_gauge(name: &str, group_id: u64, shard_id: u64) -> Option<f64> {
+    let families = prometheus::gather();
+    let family = families.iter().find(|f| f.get_name() == name)?;
+    let (group_id, shard_id) = (group_id.to_string(), shard_id.to_string());
+    family
+        .get_metric()
+        .iter()
+        .find(|m| {
+            let labels = m.get_label();
+            labels.iter().any(|l| l.get_name() == "group" && l.get_value() == group_id)
+                && labels.iter().any(|l| l.get_name() == "shard" && l.get_value() == shard_id)
+        })
+        .map(|m| m.get_gauge().get_value())
+}
+
+fn shard_intents(group_id: u64, shard_id: u64) -> Option<f64> {
+    shard_gauge("replica_shard_intents", group_id, shard_id)
+}
+
+fn oldest_intent_age(group_id: u64, shard_id: u64) -> Option<f64> {
+    shard_gauge("replica_shard_oldest_intent_age_seconds", group_id, shard_id)
+}
+
+async fn wait_shard_intents(group_id: u64, shard_id: u64, expect: f64) {
+    for _ in 0..1000 {
+        if shard_intents(group_id, shard_id) == Some(expect) {
+            return;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("group {group_id} shard {shard_id} doesn't have {expect} intents");
+}
+
+#[sekas_macro::test]
+async fn intent_age_reset_after_resolved() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.set_resolve_intent_age_ms(3000);
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let shard_id = c.get_shard_desc(table.id, b"key").await.unwrap().id;
+    let ts_table = TxnStateTable::new(app.clone(), Some(Duration::from_secs(5)));
+    let root_client = c.root_client();
+    let mut group_client = c.group(group_id);
+
+    // 1. The intent of a running txn is left untouched, its age grows.
+    let start_version = root_client.alloc_txn_id(1, None).await.unwrap();
+    ts_table.begin_txn(start_version).await.unwrap();
+    let heartbeat_app = app.clone();
+    let heartbeat = sekas_runtime::spawn(async move {
+        let ts_table = TxnStateTable::new(heartbeat_app, Some(Duration::from_secs(5)));
+        loop {
+            sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+            ts_table.heartbeat(start_version).await.unwrap();
+        }
+    });
+    let write = WriteBuilder::new(b"key-1".to_vec()).ensure_put(b"value".to_vec());
+    let req = Request::WriteIntent(WriteIntentRequest {
+        shard_id,
+        start_version,
+        write: Some(write_intent_request::Write::Put(write)),
+        ..Default::default()
+    });
+    group_client.request(&req).await.unwrap();
+    wait_shard_intents(group_id, shard_id, 1.0).await;
+
+    let former_age = oldest_intent_age(group_id, shard_id).unwrap();
+    sekas_runtime::time::sleep(Duration::from_secs(4)).await;
+    let age = oldest_intent_age(group_id, shard_id).unwrap();
+    assert!(age >= former_age + 2.0, "former age {former_age}, age {age}");
+
+    // The intent stats are reported to root by heartbeats.
+    let stmt = format!("SHOW intents FROM {group_id}");
+    let mut reported = false;
+    for _ in 0..600 {
+        let resp = root_client.handle_statement(&stmt).await.unwrap();
+        if String::from_utf8(resp).unwrap().contains(&start_version.to_string()) {
+            reported = true;
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(reported, "the intent of txn {start_version} should be shown");
+
+    // 2. The intent is cleared once the txn is aborted explicitly.
+    drop(heartbeat);
+    ts_table.abort_txn(start_version).await.unwrap();
+    let req = Request::ClearIntent(ClearIntentRequest {
+        shard_id,
+        start_version,
+        user_key: b"key-1".to_vec(),
+    });
+    group_client.request(&req).await.unwrap();
+    wait_shard_intents(group_id, shard_id, 0.0).await;
+    assert_eq!(oldest_intent_age(group_id, shard_id), Some(0.0));
+
+    // 3. The intent of an expired txn is resolved by the background resolver.
+    let start_version = root_client.alloc_txn_id(1, None).await.unwrap();
+    ts_table.begin_txn(start_version).await.unwrap();
+    let write = WriteBuilder::new(b"key-2".to_vec()).ensure_put(b"value".to_vec());
+    let req = Request::WriteIntent(WriteIntentRequest {
+        shard_id,
+        start_version,
+        write: Some(write_intent_request::Write::Put(write)),
+        ..Default::default()
+    });
+    group_client.request(&req).await.unwrap();
+    wait_shard_intents(group_id, shard_id, 1.0).await;
+    wait_shard_intents(group_id, shard_id, 0.0).await;
+    assert_eq!(oldest_intent_age(group_id, shard_id), Some(0.0));
+    let txn_record = ts_table.get_txn_record(start_version).await.unwrap().unwrap();
+    assert_eq!(txn_record.state, TxnState::Aborted);
+}
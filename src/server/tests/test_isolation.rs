@@ -22,8 +22,14 @@ use helper::context::TestContext;
 use helper::init::setup_panic_hook;
 use helper::runtime::spawn;
 use log::info;
-use sekas_client::{AppError, Database, TableDesc, Txn, WriteBuilder};
+use rand::Rng;
+use sekas_api::server::v1::ShardScanRequest;
+use sekas_client::{
+    AppError, CreateTableOptions, Database, TableDesc, TransferOptions, Txn, TxnRetryOptions,
+    WriteBuilder,
+};
 use sekas_rock::fn_name;
+use serde_json::json;
 
 const DB: &str = "DB";
 const TABLE_A: &str = "TABLE_A";
@@ -37,18 +43,19 @@ fn init() {
 
 /// build a cluster and create a DB and two table.
 async fn bootstrap_servers_and_tables(
-    name: &str,
+    mut ctx: TestContext,
 ) -> (TestContext, ClusterClient, Database, TableDesc, TableDesc) {
-    let mut ctx = TestContext::new(name);
     let nodes = ctx.bootstrap_servers(3).await;
     let c = ClusterClient::new(nodes).await;
     let app = c.app_client().await;
 
     let db = app.create_database(DB.to_string()).await.unwrap();
-    let table_a = db.create_table(TABLE_A.to_string()).await.unwrap();
-    let table_b = db.create_table(TABLE_B.to_string()).await.unwrap();
-    c.assert_table_ready(table_a.id).await;
-    c.assert_table_ready(table_b.id).await;
+    let create_table = |name: &str| {
+        let opts = CreateTableOptions { wait_ready: true, ..CreateTableOptions::new(name) };
+        db.create_table_with(opts)
+    };
+    let table_a = create_table(TABLE_A).await.unwrap();
+    let table_b = create_table(TABLE_B).await.unwrap();
 
     // ATTN: here is an assumption, two table would not be optimized in one txn
     // batch write.
@@ -60,7 +67,8 @@ async fn bootstrap_servers_and_tables(
 async fn test_atomic_operation() {
     // The atomic operation should not count in conflict ranges, since it does not
     // depend on the previous value.
-    let (ctx, c, db, table_a, _table_b) = bootstrap_servers_and_tables(fn_name!()).await;
+    let (ctx, c, db, table_a, _table_b) =
+        bootstrap_servers_and_tables(TestContext::new_simulation(fn_name!())).await;
 
     let table_a = table_a.id;
     let loop_times = 100;
@@ -96,68 +104,208 @@ async fn test_atomic_operation() {
     drop(ctx);
 }
 
+#[sekas_macro::test]
+async fn test_merge_json_disjoint_fields() {
+    // The merge json operation does not depend on the previous value read by the
+    // client, so the patches of disjoint fields both survive.
+    let (ctx, c, db, table_a, _table_b) =
+        bootstrap_servers_and_tables(TestContext::new_simulation(fn_name!())).await;
+
+    let table_a = table_a.id;
+    let key = b"document".to_vec();
+    let loop_times = 100;
+
+    let mut txn = db.begin_txn();
+    txn.put(table_a, WriteBuilder::new(key.clone()).ensure_merge_json(json!({"kind": "doc"})));
+    txn.commit().await.unwrap();
+
+    let mut handles = vec![];
+    for field in ["a", "b"] {
+        let db_clone = db.clone();
+        let key_clone = key.clone();
+        handles.push(spawn(async move {
+            for i in 0..loop_times {
+                let mut txn = db_clone.begin_txn();
+                let patch = json!({ field: { "value": i }, "last": field });
+                txn.put(table_a, WriteBuilder::new(key_clone.clone()).ensure_merge_json(patch));
+                txn.commit().await.unwrap();
+            }
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    let txn = db.begin_txn();
+    let document = txn.get_json(table_a, key.clone()).await.unwrap().unwrap();
+    assert_eq!(document["kind"], json!("doc"));
+    assert_eq!(document["a"], json!({ "value": loop_times - 1 }));
+    assert_eq!(document["b"], json!({ "value": loop_times - 1 }));
+
+    // Remove a field by the null member.
+    let mut txn = db.begin_txn();
+    txn.put(table_a, WriteBuilder::new(key.clone()).ensure_merge_json(json!({ "a": null })));
+    txn.commit().await.unwrap();
+    let txn = db.begin_txn();
+    let document = txn.get_json(table_a, key.clone()).await.unwrap().unwrap();
+    assert!(document.get("a").is_none());
+    assert_eq!(document["b"], json!({ "value": loop_times - 1 }));
+
+    // Merge into a non-JSON value is rejected.
+    let mut txn = db.begin_txn();
+    txn.put(table_a, WriteBuilder::new(key.clone()).ensure_put(b"not json".to_vec()));
+    txn.commit().await.unwrap();
+    let mut txn = db.begin_txn();
+    txn.put(table_a, WriteBuilder::new(key.clone()).ensure_merge_json(json!({ "a": 1 })));
+    assert!(matches!(txn.commit().await, Err(AppError::InvalidJson(_))));
+    let txn = db.begin_txn();
+    assert!(matches!(txn.get_json(table_a, key).await, Err(AppError::InvalidJson(_))));
+
+    drop(c);
+    drop(ctx);
+}
+
+#[sekas_macro::test]
+async fn test_add_onto_non_numeric_value() {
+    let (ctx, c, db, table_a, _table_b) =
+        bootstrap_servers_and_tables(TestContext::new(fn_name!())).await;
+
+    let table_id = table_a.id;
+    let key = b"counter".to_vec();
+
+    // Blob then add, the txn is aborted without writing anything.
+    db.put(table_id, key.clone(), b"blob".to_vec()).await.unwrap();
+    let mut txn = db.begin_txn();
+    txn.put(table_id, WriteBuilder::new(key.clone()).ensure_add(1));
+    let result = txn.commit().await;
+    assert!(matches!(result, Err(AppError::ValueTypeMismatch { actual_len: 4, .. })), "{result:?}");
+    assert_eq!(db.get(table_id, key.clone()).await.unwrap(), Some(b"blob".to_vec()));
+
+    // Add then blob then add.
+    db.delete(table_id, key.clone()).await.unwrap();
+    let mut txn = db.begin_txn();
+    txn.put(table_id, WriteBuilder::new(key.clone()).ensure_add(2));
+    txn.commit().await.unwrap();
+    assert_eq!(read_i64(&db.begin_txn(), table_id, key.clone()).await, 2);
+    db.put(table_id, key.clone(), b"0123456789".to_vec()).await.unwrap();
+    let mut txn = db.begin_txn();
+    txn.put(table_id, WriteBuilder::new(key.clone()).ensure_add(3));
+    let result = txn.commit().await;
+    assert!(
+        matches!(result, Err(AppError::ValueTypeMismatch { actual_len: 10, .. })),
+        "{result:?}"
+    );
+    assert_eq!(db.get(table_id, key.clone()).await.unwrap(), Some(b"0123456789".to_vec()));
+
+    // The force add reinitializes the value from the delta.
+    let mut txn = db.begin_txn();
+    txn.put(table_id, WriteBuilder::new(key.clone()).ensure_force_add(5));
+    txn.commit().await.unwrap();
+    assert_eq!(read_i64(&db.begin_txn(), table_id, key.clone()).await, 5);
+    let mut txn = db.begin_txn();
+    txn.put(table_id, WriteBuilder::new(key.clone()).ensure_force_add(5));
+    txn.commit().await.unwrap();
+    assert_eq!(read_i64(&db.begin_txn(), table_id, key.clone()).await, 10);
+
+    drop(c);
+    drop(ctx);
+}
+
+#[sekas_macro::test]
+async fn test_put_conflicts_with_running_add() {
+    let (ctx, c, db, table_a, _table_b) =
+        bootstrap_servers_and_tables(TestContext::new(fn_name!())).await;
+
+    let table_id = table_a.id;
+    let key = b"counter".to_vec();
+    let mut txn = db.begin_txn();
+    txn.put(table_id, WriteBuilder::new(key.clone()).ensure_add(1));
+    txn.commit().await.unwrap();
+
+    // The intent of the add is flushed but not committed.
+    let mut adder = db.begin_txn();
+    adder.put(table_id, WriteBuilder::new(key.clone()).ensure_add(1));
+    adder.flush().await.unwrap();
+
+    let mut putter = db.begin_txn();
+    putter.put(table_id, WriteBuilder::new(key.clone()).ensure_put(b"blob".to_vec()));
+    let result = putter.commit().await;
+    assert!(matches!(result, Err(AppError::TxnConflict)), "{result:?}");
+
+    adder.commit().await.unwrap();
+    assert_eq!(read_i64(&db.begin_txn(), table_id, key).await, 2);
+
+    drop(c);
+    drop(ctx);
+}
+
 #[sekas_macro::test]
 async fn test_lost_update_anomaly() {
     // The constraint:
     //      r1[x]...w2[x]...w1[x]...c1
 
-    let (ctx, c, db, table_a, _table_b) = bootstrap_servers_and_tables(fn_name!()).await;
+    let (ctx, c, db, table_a, _table_b) =
+        bootstrap_servers_and_tables(TestContext::new(fn_name!())).await;
 
     let table_a = table_a.id;
     let loop_times = 100;
 
     let db_clone = db.clone();
     let bumper_a = spawn(async move {
+        let options = TxnRetryOptions { max_attempts: usize::MAX, ..Default::default() };
         for i in 0..loop_times {
-            loop {
-                let mut txn = db_clone.begin_txn();
-                let value = read_i64(&txn, table_a, table_a.to_string().into_bytes()).await;
-                let a = value & 0x0000FFFF;
-                let b = value & 0xFFFF0000;
-                if a != i {
-                    panic!("a = {}, i = {}, b = {}, the lost update anomaly is exists", a, i, b);
-                }
-                let value = b | (a + 1);
-
-                let put = WriteBuilder::new(table_a.to_string().into_bytes())
-                    .ensure_put(value.to_be_bytes().to_vec());
-                txn.put(table_a, put);
-                match txn.commit().await {
-                    Ok(_) => break,
-                    Err(AppError::TxnConflict) => {
-                        info!("bumper a txn is conflict, retry later ...");
-                    }
-                    Err(err) => panic!("commit txn: {err:?}"),
-                }
-            }
+            db_clone
+                .run_txn(options.clone(), move |txn| {
+                    Box::pin(async move {
+                        let value = read_i64(txn, table_a, table_a.to_string().into_bytes()).await;
+                        let a = value & 0x0000FFFF;
+                        let b = value & 0xFFFF0000;
+                        if a != i {
+                            panic!(
+                                "a = {}, i = {}, b = {}, the lost update anomaly is exists",
+                                a, i, b
+                            );
+                        }
+                        let value = b | (a + 1);
+
+                        let put = WriteBuilder::new(table_a.to_string().into_bytes())
+                            .ensure_put(value.to_be_bytes().to_vec());
+                        txn.put(table_a, put);
+                        Ok(())
+                    })
+                })
+                .await
+                .unwrap();
             sekas_runtime::time::sleep(Duration::from_millis(5)).await;
         }
     });
 
     let db_clone = db.clone();
     let bumper_b = spawn(async move {
+        let options = TxnRetryOptions { max_attempts: usize::MAX, ..Default::default() };
         for i in 0..loop_times {
-            loop {
-                let mut txn = db_clone.begin_txn();
-                let value = read_i64(&txn, table_a, table_a.to_string().into_bytes()).await;
-                let a = value & 0x0000FFFF;
-                let b = (value & 0xFFFF0000) >> 16;
-                if b != i {
-                    panic!("b = {}, i = {}, a = {}, the lost update anomaly is exists", b, i, a);
-                }
-                let value = a | ((b + 1) << 16);
-
-                let put = WriteBuilder::new(table_a.to_string().into_bytes())
-                    .ensure_put(value.to_be_bytes().to_vec());
-                txn.put(table_a, put);
-                match txn.commit().await {
-                    Ok(_) => break,
-                    Err(AppError::TxnConflict) => {
-                        info!("bumper b txn is conflict, retry later ...");
-                    }
-                    Err(err) => panic!("commit txn: {err:?}"),
-                }
-            }
+            db_clone
+                .run_txn(options.clone(), move |txn| {
+                    Box::pin(async move {
+                        let value = read_i64(txn, table_a, table_a.to_string().into_bytes()).await;
+                        let a = value & 0x0000FFFF;
+                        let b = (value & 0xFFFF0000) >> 16;
+                        if b != i {
+                            panic!(
+                                "b = {}, i = {}, a = {}, the lost update anomaly is exists",
+                                b, i, a
+                            );
+                        }
+                        let value = a | ((b + 1) << 16);
+
+                        let put = WriteBuilder::new(table_a.to_string().into_bytes())
+                            .ensure_put(value.to_be_bytes().to_vec());
+                        txn.put(table_a, put);
+                        Ok(())
+                    })
+                })
+                .await
+                .unwrap();
             sekas_runtime::time::sleep(Duration::from_millis(3)).await;
         }
     });
@@ -173,6 +321,120 @@ async fn test_lost_update_anomaly() {
     drop(ctx);
 }
 
+#[sekas_macro::test]
+async fn test_transfer_conflict() {
+    let (ctx, c, db, table_a, table_b) =
+        bootstrap_servers_and_tables(TestContext::new(fn_name!())).await;
+
+    let from = (table_a.id, b"from".to_vec());
+    let to = (table_b.id, b"to".to_vec());
+    let mut txn = db.begin_txn();
+    txn.put(from.0, WriteBuilder::new(from.1.clone()).ensure_add(10));
+    txn.commit().await.unwrap();
+
+    // The floor of source is enforced.
+    let mut txn = db.begin_txn();
+    let result = txn.transfer(from.clone(), to.clone(), 11, TransferOptions::default()).await;
+    assert!(matches!(
+        result,
+        Err(AppError::InsufficientBalance { balance: 10, amount: 11, floor: 0 })
+    ));
+    let options = TransferOptions { floor: -1 };
+    let balances = txn.transfer(from.clone(), to.clone(), 11, options).await.unwrap();
+    assert_eq!(balances, (-1, 11));
+    drop(txn);
+
+    // Both transfers read the balance 10, only the first one is committed.
+    let mut txn_1 = db.begin_txn();
+    let mut txn_2 = db.begin_txn();
+    let options = TransferOptions::default();
+    let balances = txn_1.transfer(from.clone(), to.clone(), 6, options.clone()).await.unwrap();
+    assert_eq!(balances, (4, 6));
+    let balances = txn_2.transfer(from.clone(), to.clone(), 7, options).await.unwrap();
+    assert_eq!(balances, (3, 7));
+    txn_1.commit().await.unwrap();
+    assert!(matches!(txn_2.commit().await, Err(AppError::TxnConflict)));
+
+    let txn = db.begin_txn();
+    assert_eq!(read_i64(&txn, from.0, from.1).await, 4);
+    assert_eq!(read_i64(&txn, to.0, to.1).await, 6);
+
+    drop(c);
+    drop(ctx);
+}
+
+#[sekas_macro::test]
+async fn test_concurrent_transfers() {
+    // The constraint: the sum of balances is conserved, and no balance is below
+    // the floor.
+    let (ctx, c, db, table_a, table_b) =
+        bootstrap_servers_and_tables(TestContext::new(fn_name!())).await;
+
+    const NUM_ACCOUNTS: usize = 6;
+    const NUM_WORKERS: usize = 4;
+    const INITIAL_BALANCE: i64 = 100;
+    let loop_times = 25;
+    let accounts: Vec<(u64, Vec<u8>)> = (0..NUM_ACCOUNTS)
+        .map(|i| {
+            let table_id = if i % 2 == 0 { table_a.id } else { table_b.id };
+            (table_id, format!("account-{i}").into_bytes())
+        })
+        .collect();
+
+    let mut txn = db.begin_txn();
+    for (table_id, key) in &accounts {
+        txn.put(*table_id, WriteBuilder::new(key.clone()).ensure_add(INITIAL_BALANCE));
+    }
+    txn.commit().await.unwrap();
+
+    let mut workers = vec![];
+    for _ in 0..NUM_WORKERS {
+        let db = db.clone();
+        let accounts = accounts.clone();
+        workers.push(spawn(async move {
+            let options = TxnRetryOptions { max_attempts: usize::MAX, ..Default::default() };
+            for _ in 0..loop_times {
+                let (from, to, amount) = {
+                    let mut rng = rand::thread_rng();
+                    let from = rng.gen_range(0..NUM_ACCOUNTS);
+                    let to = (from + rng.gen_range(1..NUM_ACCOUNTS)) % NUM_ACCOUNTS;
+                    (accounts[from].clone(), accounts[to].clone(), rng.gen_range(1..=60))
+                };
+                let result = db
+                    .run_txn(options.clone(), |txn| {
+                        let (from, to) = (from.clone(), to.clone());
+                        Box::pin(async move {
+                            txn.transfer(from, to, amount, TransferOptions::default()).await
+                        })
+                    })
+                    .await;
+                match result {
+                    Ok((from_balance, _)) => assert!(from_balance >= 0),
+                    Err(AppError::InsufficientBalance { balance, .. }) => {
+                        assert!(balance < amount, "balance {balance}, amount {amount}");
+                    }
+                    Err(err) => panic!("transfer {amount}: {err:?}"),
+                }
+            }
+        }));
+    }
+    for worker in workers {
+        worker.await.unwrap();
+    }
+
+    let txn = db.begin_txn();
+    let mut sum = 0;
+    for (table_id, key) in accounts {
+        let balance = read_i64(&txn, table_id, key).await;
+        assert!(balance >= 0, "balance {balance}");
+        sum += balance;
+    }
+    assert_eq!(sum, INITIAL_BALANCE * NUM_ACCOUNTS as i64);
+
+    drop(c);
+    drop(ctx);
+}
+
 // TODO(walter) support serializable snapshot isolation.
 #[ignore]
 #[sekas_macro::test]
@@ -180,7 +442,8 @@ async fn test_write_skew_anomaly() {
     // The constraint: account balances are allowed to go negative as long as the
     // sum of commonly held balances remains non-negative
 
-    let (ctx, c, db, table_a, table_b) = bootstrap_servers_and_tables(fn_name!()).await;
+    let (ctx, c, db, table_a, table_b) =
+        bootstrap_servers_and_tables(TestContext::new(fn_name!())).await;
 
     let table_a = table_a.id;
     let table_b = table_b.id;
@@ -266,6 +529,182 @@ async fn test_write_skew_anomaly() {
     drop(ctx);
 }
 
+#[sekas_macro::test]
+async fn test_put_if_prefix_empty_race() {
+    let (ctx, c, db, table_a, _) = bootstrap_servers_and_tables(TestContext::new(fn_name!())).await;
+    let table_id = table_a.id;
+
+    // The unrelated writes under the other prefix are never rejected.
+    let exit_flag = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let db = db.clone();
+        let exit_flag = exit_flag.clone();
+        spawn(async move {
+            let mut num_writes = 0;
+            while !exit_flag.load(Ordering::Acquire) {
+                let key = format!("other/{num_writes}").into_bytes();
+                db.put(table_id, key, b"value".to_vec()).await.unwrap();
+                num_writes += 1;
+            }
+            num_writes
+        })
+    };
+
+    const NUM_CREATORS: usize = 2;
+    for round in 0..3 {
+        // Split the prefix into two shards, the markers of creators are located in
+        // both.
+        let prefix = format!("dir-{round}/").into_bytes();
+        let split_key = [prefix.as_slice(), b"m"].concat();
+        let shard = c.get_shard_desc(table_id, &split_key).await.unwrap();
+        let group_state = c.find_router_group_state_by_key(table_id, &split_key).await.unwrap();
+        let new_shard_id = (1 << 20) + round;
+        c.group(group_state.id).split_shard(shard.id, new_shard_id, Some(split_key)).await.unwrap();
+        c.assert_group_contains_shard(group_state.id, new_shard_id).await;
+
+        let mut creators = vec![];
+        for i in 0..NUM_CREATORS {
+            let db = db.clone();
+            let prefix = prefix.clone();
+            let marker_key = [prefix.as_slice(), if i % 2 == 0 { b"a" } else { b"z" }].concat();
+            creators.push(spawn(async move {
+                let options = TxnRetryOptions { max_attempts: usize::MAX, ..Default::default() };
+                let result = db
+                    .run_txn(options, |txn| {
+                        let (prefix, marker_key) = (prefix.clone(), marker_key.clone());
+                        Box::pin(async move {
+                            txn.put_if_prefix_empty(table_id, prefix, marker_key, b"dir".to_vec());
+                            Ok(())
+                        })
+                    })
+                    .await;
+                (marker_key, result)
+            }));
+        }
+
+        let mut winners = vec![];
+        for creator in creators {
+            match creator.await.unwrap() {
+                (marker_key, Ok(())) => winners.push(marker_key),
+                (_, Err(AppError::PrefixNotEmpty { key, .. })) => {
+                    assert!(key.starts_with(&prefix), "key {key:?}");
+                }
+                (_, Err(err)) => panic!("create prefix {prefix:?}: {err:?}"),
+            }
+        }
+        assert_eq!(winners.len(), 1, "round {round}");
+        info!("round {round} the marker {:?} wins", winners[0]);
+        let value = db.get(table_id, winners[0].clone()).await.unwrap();
+        assert_eq!(value, Some(b"dir".to_vec()));
+    }
+
+    // The prefix of other dirs are not affected.
+    let mut txn = db.begin_txn();
+    txn.put_if_prefix_empty(table_id, b"dir-".to_vec(), b"dir-".to_vec(), b"dir".to_vec());
+    assert!(matches!(txn.commit().await, Err(AppError::PrefixNotEmpty { .. })));
+    let mut txn = db.begin_txn();
+    txn.put_if_prefix_empty(table_id, b"dir-x/".to_vec(), b"dir-x/".to_vec(), b"dir".to_vec());
+    txn.commit().await.unwrap();
+
+    exit_flag.store(true, Ordering::Release);
+    let num_writes = writer.await.unwrap();
+    info!("{num_writes} unrelated writes are committed");
+    assert!(num_writes > 0);
+
+    drop(c);
+    drop(ctx);
+}
+
+#[sekas_macro::test]
+async fn test_read_own_flushed_writes() {
+    let (ctx, c, db, table_a, _table_b) =
+        bootstrap_servers_and_tables(TestContext::new(fn_name!())).await;
+
+    let table_id = table_a.id;
+    db.put(table_id, b"a".to_vec(), b"old-a".to_vec()).await.unwrap();
+    db.put(table_id, b"b".to_vec(), b"old-b".to_vec()).await.unwrap();
+
+    // The txn started before the flush.
+    let older = db.begin_txn();
+    assert_eq!(older.get(table_id, b"a".to_vec()).await.unwrap(), Some(b"old-a".to_vec()));
+
+    let mut txn = db.begin_txn();
+    txn.put(table_id, WriteBuilder::new(b"a".to_vec()).ensure_put(b"new-a".to_vec()));
+    txn.delete(table_id, WriteBuilder::new(b"b".to_vec()).ensure_delete());
+    txn.flush().await.unwrap();
+    txn.put(table_id, WriteBuilder::new(b"c".to_vec()).ensure_put(b"new-c".to_vec()));
+
+    // The flushed writes are visible to the txn itself, the buffered one is not.
+    assert_eq!(txn.get(table_id, b"a".to_vec()).await.unwrap(), Some(b"new-a".to_vec()));
+    assert_eq!(txn.get(table_id, b"b".to_vec()).await.unwrap(), None);
+    assert_eq!(txn.get(table_id, b"c".to_vec()).await.unwrap(), None);
+    let shard_id = c.get_shard_desc(table_id, b"a").await.unwrap().id;
+    let resp = txn.scan(ShardScanRequest { shard_id, ..Default::default() }).await.unwrap();
+    let values = resp
+        .data
+        .into_iter()
+        .map(|v| (v.user_key, v.values[0].content.clone().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec![(b"a".to_vec(), b"new-a".to_vec())]);
+
+    // The flushed keys can't be written again.
+    let mut rewrite = db.begin_txn();
+    rewrite.put(table_id, WriteBuilder::new(b"d".to_vec()).ensure_put(b"x".to_vec()));
+    rewrite.flush().await.unwrap();
+    rewrite.put(table_id, WriteBuilder::new(b"d".to_vec()).ensure_put(b"y".to_vec()));
+    assert!(matches!(rewrite.flush().await, Err(AppError::InvalidArgument(_))));
+    drop(rewrite);
+
+    // The provisional values are invisible to other txns: the older txn ignores
+    // them, and the younger txn waits until the txn is committed, at a version
+    // newer than its snapshot.
+    assert_eq!(older.get(table_id, b"a".to_vec()).await.unwrap(), Some(b"old-a".to_vec()));
+    assert_eq!(older.get(table_id, b"b".to_vec()).await.unwrap(), Some(b"old-b".to_vec()));
+    let younger = db.begin_txn();
+    let handle = spawn(async move { younger.get(table_id, b"a".to_vec()).await.unwrap() });
+    sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+
+    txn.commit().await.unwrap();
+    assert_eq!(handle.await.unwrap(), Some(b"old-a".to_vec()));
+    assert_eq!(db.get(table_id, b"a".to_vec()).await.unwrap(), Some(b"new-a".to_vec()));
+    assert_eq!(db.get(table_id, b"b".to_vec()).await.unwrap(), None);
+    assert_eq!(db.get(table_id, b"c".to_vec()).await.unwrap(), Some(b"new-c".to_vec()));
+
+    drop(c);
+    drop(ctx);
+}
+
+#[sekas_macro::test]
+async fn test_read_behind_heartbeating_intent_is_bounded() {
+    let (ctx, c, db, table_a, _table_b) =
+        bootstrap_servers_and_tables(TestContext::new(fn_name!())).await;
+
+    let table_id = table_a.id;
+    db.put(table_id, b"a".to_vec(), b"old-a".to_vec()).await.unwrap();
+
+    // The intent is flushed, and the lease of the txn is kept by heartbeats.
+    let mut blocker = db.begin_txn();
+    blocker.put(table_id, WriteBuilder::new(b"a".to_vec()).ensure_put(b"new-a".to_vec()));
+    blocker.flush().await.unwrap();
+
+    // The reader gives up once the intent is held past the bounded wait.
+    let reader = db.begin_txn();
+    let start = sekas_runtime::time::Instant::now();
+    let result = reader.get(table_id, b"a".to_vec()).await;
+    assert!(matches!(result, Err(AppError::TxnConflict)), "{result:?}");
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_secs(1), "elapsed {elapsed:?}");
+    assert!(elapsed < Duration::from_secs(5), "elapsed {elapsed:?}");
+
+    // The blocker is still alive, and the reader retried later observes it.
+    blocker.commit().await.unwrap();
+    let reader = db.begin_txn();
+    assert_eq!(reader.get(table_id, b"a".to_vec()).await.unwrap(), Some(b"new-a".to_vec()));
+
+    drop(c);
+    drop(ctx);
+}
+
 async fn read_i64(txn: &Txn, table_id: u64, key: Vec<u8>) -> i64 {
     match txn.get(table_id, key).await.unwrap() {
         Some(bytes) => sekas_rock::num::decode_i64(&bytes).unwrap(),
@@ -0,0 +1,211 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::StreamExt;
+use sekas_client::{AppError, Database, LargeValueOptions, Range, RangeRequest};
+use sekas_rock::fn_name;
+use sekas_runtime::time::timeout;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// A reader yields the data, then fails or pends forever.
+struct BrokenReader {
+    data: Vec<u8>,
+    offset: usize,
+    pending: bool,
+}
+
+impl AsyncRead for BrokenReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if this.offset < this.data.len() {
+            let len = buf.remaining().min(this.data.len() - this.offset);
+            buf.put_slice(&this.data[this.offset..this.offset + len]);
+            this.offset += len;
+            Poll::Ready(Ok(()))
+        } else if this.pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "broken reader")))
+        }
+    }
+}
+
+fn small_chunk_options() -> LargeValueOptions {
+    LargeValueOptions { chunk_size: 1024, max_txn_bytes: 4096, ..Default::default() }
+}
+
+fn gen_value(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed)).collect()
+}
+
+async fn count_chunks(db: &Database, table_id: u64, key: &[u8]) -> usize {
+    let mut prefix = key.to_vec();
+    prefix.extend_from_slice(b"\0chunk\0");
+    let request = RangeRequest { table_id, range: Range::Prefix(prefix), ..Default::default() };
+    let mut stream = db.range(request).await.unwrap();
+    let mut num_chunks = 0;
+    while let Some(value_sets) = stream.next().await {
+        num_chunks += value_sets
+            .unwrap()
+            .into_iter()
+            .filter(|v| v.values.iter().any(|v| v.content.is_some()))
+            .count();
+    }
+    num_chunks
+}
+
+#[sekas_macro::test]
+async fn large_value_put_and_get() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let opts = small_chunk_options();
+    for (key, len) in [("empty", 0), ("single", 3000), ("staged", 20 * 1024 + 17)] {
+        let key = key.as_bytes().to_vec();
+        let value = gen_value(len, 1);
+        db.put_large(table.id, key.clone(), value.as_slice(), &opts).await.unwrap();
+        let got = db.get_large(table.id, key.clone()).await.unwrap();
+        assert_eq!(got, Some(value), "key {key:?}");
+        assert_eq!(count_chunks(&db, table.id, &key).await, len.div_ceil(opts.chunk_size));
+    }
+
+    // Overwrite removes the chunks of the previous value.
+    let value = gen_value(5000, 2);
+    db.put_large(table.id, b"staged".to_vec(), value.as_slice(), &opts).await.unwrap();
+    assert_eq!(db.get_large(table.id, b"staged".to_vec()).await.unwrap(), Some(value));
+    assert_eq!(count_chunks(&db, table.id, b"staged").await, 5);
+
+    // Delete removes all chunks.
+    db.delete_large(table.id, b"staged".to_vec()).await.unwrap();
+    assert_eq!(db.get_large(table.id, b"staged".to_vec()).await.unwrap(), None);
+    assert_eq!(count_chunks(&db, table.id, b"staged").await, 0);
+
+    db.put(table.id, b"plain".to_vec(), b"value".to_vec()).await.unwrap();
+    assert!(matches!(
+        db.get_large(table.id, b"plain".to_vec()).await,
+        Err(AppError::InvalidArgument(_))
+    ));
+}
+
+#[sekas_macro::test]
+async fn large_value_partial_upload_failure() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let opts = small_chunk_options();
+    let key = b"key".to_vec();
+    let value = gen_value(3000, 1);
+    db.put_large(table.id, key.clone(), value.as_slice(), &opts).await.unwrap();
+
+    // The staged chunks are removed if the reader fails.
+    let reader = BrokenReader { data: gen_value(10 * 1024, 2), offset: 0, pending: false };
+    let result = db.put_large(table.id, key.clone(), reader, &opts).await;
+    assert!(matches!(result, Err(AppError::Internal(_))));
+    assert_eq!(db.get_large(table.id, key.clone()).await.unwrap(), Some(value.clone()));
+    assert_eq!(count_chunks(&db, table.id, &key).await, 3);
+
+    // The staged chunks of a cancelled upload are garbage collected.
+    let reader = BrokenReader { data: gen_value(10 * 1024, 3), offset: 0, pending: true };
+    let result =
+        timeout(Duration::from_secs(5), db.put_large(table.id, key.clone(), reader, &opts)).await;
+    assert!(result.is_err(), "the upload is cancelled");
+    assert_eq!(db.get_large(table.id, key.clone()).await.unwrap(), Some(value.clone()));
+    assert!(count_chunks(&db, table.id, &key).await > 3);
+
+    assert_eq!(db.gc_large(table.id, key.clone(), &opts).await.unwrap(), 0);
+    let gc_opts = LargeValueOptions { stale_staging_timeout: Duration::ZERO, ..opts };
+    assert_eq!(db.gc_large(table.id, key.clone(), &gc_opts).await.unwrap(), 8);
+    assert_eq!(count_chunks(&db, table.id, &key).await, 3);
+    assert_eq!(db.get_large(table.id, key.clone()).await.unwrap(), Some(value));
+}
+
+#[sekas_macro::test]
+async fn large_value_read_during_overwrite() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let opts = small_chunk_options();
+    let key = b"key".to_vec();
+    let values =
+        (0..8u8).map(|seed| gen_value(16 * 1024 + seed as usize, seed)).collect::<Vec<_>>();
+    db.put_large(table.id, key.clone(), values[0].as_slice(), &opts).await.unwrap();
+
+    let mut writers = vec![];
+    for writer in 0..2 {
+        let db = db.clone();
+        let key = key.clone();
+        let opts = opts.clone();
+        let values = values.clone();
+        writers.push(sekas_runtime::spawn(async move {
+            for value in values.iter().skip(1 + writer).step_by(2) {
+                loop {
+                    match db.put_large(table.id, key.clone(), value.as_slice(), &opts).await {
+                        Ok(()) => break,
+                        Err(AppError::TxnConflict) => continue,
+                        Err(err) => panic!("put large value: {err:?}"),
+                    }
+                }
+            }
+        }));
+    }
+
+    // The reader sees either the old or the new value, never a mix.
+    while writers.iter().any(|w| !w.is_finished()) {
+        let value = db.get_large(table.id, key.clone()).await.unwrap().unwrap();
+        assert!(values.contains(&value), "read a mixed value");
+    }
+    for writer in writers {
+        writer.await.unwrap();
+    }
+
+    let value = db.get_large(table.id, key.clone()).await.unwrap().unwrap();
+    assert!(values.contains(&value));
+    let gc_opts = LargeValueOptions { stale_staging_timeout: Duration::ZERO, ..opts.clone() };
+    db.gc_large(table.id, key.clone(), &gc_opts).await.unwrap();
+    assert_eq!(count_chunks(&db, table.id, &key).await, value.len().div_ceil(opts.chunk_size));
+}
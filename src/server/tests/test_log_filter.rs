@@ -0,0 +1,116 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use sekas_api::server::v1::*;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+/// The logs emitted by the process, they are printed to stderr as well.
+static CAPTURED: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// The trace line emitted by each txn of the client.
+const KNOWN_LINE: &str = "txn get start version";
+
+struct CapturedWriter;
+
+impl Write for CapturedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        CAPTURED.lock().unwrap().extend_from_slice(buf);
+        std::io::stderr().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter("info")
+        .with_filter_reloading()
+        .with_writer(|| CapturedWriter);
+    let handle = builder.reload_handle();
+    builder.init();
+    sekas_client::install_log_filter("info", handle);
+}
+
+fn take_captured() -> String {
+    String::from_utf8_lossy(&std::mem::take(&mut *CAPTURED.lock().unwrap())).into_owned()
+}
+
+#[sekas_macro::test]
+async fn override_log_level_until_expired() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    take_captured();
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert!(!take_captured().contains(KNOWN_LINE));
+
+    // The override is applied to all nodes and recorded into the event log.
+    let result = app.handle_statement("CONFIG log.sekas_client::txn trace TTL 30").await.unwrap();
+    let result = String::from_utf8(result).unwrap();
+    assert!(result.contains("sekas_client::txn=trace"), "{result}");
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert!(take_captured().contains(KNOWN_LINE));
+    let events = c.root_client().list_topology_events().await.unwrap();
+    assert!(
+        events.iter().any(|e| e.kind == topology_event::Kind::LogFilter as i32
+            && e.target == "log/sekas_client::txn"),
+        "{events:?}"
+    );
+
+    // The effective filters are queryable by node.
+    let filters = c.root_client().config_log_filter(None, SetLogFilterRequest::default()).await;
+    let filters = filters.unwrap();
+    assert_eq!(filters.len(), 3, "{filters:?}");
+    let node_id = filters[0].node_id;
+    let filters = c
+        .root_client()
+        .config_log_filter(Some(node_id), SetLogFilterRequest::default())
+        .await
+        .unwrap();
+    assert_eq!(filters.len(), 1, "{filters:?}");
+    assert_eq!(filters[0].filter, "info,sekas_client::txn=trace");
+
+    // The embedded client shares the same mechanism.
+    let filter = app.set_log_filter("sekas_client::txn_table", "debug", Duration::ZERO).unwrap();
+    assert_eq!(filter, "info,sekas_client::txn=trace,sekas_client::txn_table=debug");
+    let filter = app.set_log_filter("sekas_client::txn_table", "", Duration::ZERO).unwrap();
+    assert_eq!(filter, "info,sekas_client::txn=trace");
+    assert!(app.set_log_filter("sekas_client::txn", "verbose", Duration::ZERO).is_err());
+
+    // The level reverts to the default after the ttl.
+    sekas_runtime::time::sleep(Duration::from_secs(31)).await;
+    assert_eq!(sekas_client::effective_log_filter().as_deref(), Some("info"));
+    take_captured();
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert!(!take_captured().contains(KNOWN_LINE));
+}
@@ -0,0 +1,121 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::path::Path;
+
+use rand::RngCore;
+use sekas_api::server::v1::{CompactRange, CompactReplicaRequest};
+use sekas_client::CreateTableOptions;
+use sekas_rock::fn_name;
+use sekas_schema::property::MAX_VERSIONS;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The total size of the sst files under the dir.
+fn sst_size(dir: &Path) -> u64 {
+    let mut size = 0;
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        if entry.file_type().unwrap().is_dir() {
+            size += sst_size(&path);
+        } else if path.extension().is_some_and(|ext| ext == "sst") {
+            size += entry.metadata().map(|m| m.len()).unwrap_or_default();
+        }
+    }
+    size
+}
+
+#[sekas_macro::test]
+async fn compact_table_reclaims_deleted_data() {
+    const NUM_KEYS: usize = 256;
+    const NUM_RETAINED: usize = 16;
+    const VALUE_SIZE: usize = 16 * 1024;
+
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    // The deleted values are shadowed by the tombstones, so they are dropped by
+    // the compactions without any GC watermark.
+    let mut opts = CreateTableOptions::new("table");
+    opts.properties.insert(MAX_VERSIONS.to_owned(), "1".to_owned());
+    let table = db.create_table_with(opts).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let mut value = vec![0; VALUE_SIZE];
+    for i in 0..NUM_KEYS {
+        // The random values are not compressed.
+        rand::thread_rng().fill_bytes(&mut value);
+        let key = format!("key-{i:04}").into_bytes();
+        db.put(table.id, key, value.clone()).await.unwrap();
+    }
+    for i in NUM_RETAINED..NUM_KEYS {
+        let key = format!("key-{i:04}").into_bytes();
+        db.delete(table.id, key).await.unwrap();
+    }
+    let data_size = (NUM_KEYS * VALUE_SIZE) as u64;
+
+    // All replicas of the table are compacted, the leaders and the followers.
+    let replicas = c.root_client().compact_table("db".into(), "table".into()).await.unwrap();
+    assert_eq!(replicas.len(), 3, "{replicas:?}");
+    for replica in &replicas {
+        assert!(replica.error.is_empty(), "{replica:?}");
+        let result = replica.result.as_ref().unwrap();
+        assert!(result.size_before >= data_size / 2, "{replica:?}");
+        assert!(result.size_after < result.size_before / 4, "{replica:?}");
+    }
+    let size = (0..3).map(|i| sst_size(&ctx.server_dir(i))).sum::<u64>();
+    assert!(size < data_size, "sst size {size}, data size {data_size}");
+
+    // The retained keys are still readable.
+    for i in 0..NUM_KEYS {
+        let key = format!("key-{i:04}").into_bytes();
+        let value = db.get(table.id, key).await.unwrap();
+        assert_eq!(value.is_some(), i < NUM_RETAINED, "key {i}");
+    }
+
+    // The concurrent requests of a replica are queued or coalesced.
+    let replica = &replicas[0];
+    let client = node_client_with_retry(&nodes[&replica.node_id]).await;
+    let range = CompactRange { table_id: table.id, ..Default::default() };
+    let requests = (0..4).map(|_| {
+        let req =
+            CompactReplicaRequest { replica_id: replica.replica_id, range: Some(range.clone()) };
+        client.compact_replica(req)
+    });
+    for resp in futures::future::join_all(requests).await {
+        let resp = resp.unwrap();
+        assert!(resp.size_after < data_size / 4, "{resp:?}");
+    }
+
+    // The statement reports the results of the replicas.
+    let result = app.handle_statement("COMPACT TABLE db.table").await.unwrap();
+    let result = String::from_utf8(result).unwrap();
+    assert!(result.contains("finished") || result.contains("coalesced"), "{result}");
+    let result = app.handle_statement("COMPACT TABLE db.not_exists").await.unwrap();
+    let result = String::from_utf8(result).unwrap();
+    assert!(result.contains("not exists"), "{result}");
+}
@@ -0,0 +1,119 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+async fn execute(c: &ClusterClient, stmt: &str) -> ExecuteResult {
+    let json_body = c.root_client().handle_statement(stmt).await.unwrap();
+    serde_json::from_slice(&json_body).unwrap()
+}
+
+fn expect_invalid_argument<T: std::fmt::Debug>(result: sekas_client::Result<T>, msg: &str) {
+    match result {
+        Err(sekas_client::Error::InvalidArgument(err)) => assert!(err.contains(msg), "{err}"),
+        others => panic!("expect invalid argument, but got {others:?}"),
+    }
+}
+
+#[sekas_macro::test]
+async fn split_shard_at_user_provided_key() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    for key in [b"a", b"m", b"z"] {
+        db.put(table.id, key.to_vec(), b"value".to_vec()).await.unwrap();
+    }
+
+    let shard = c.get_shard_desc(table.id, b"a").await.unwrap();
+    let rows = match execute(&c, &format!("SPLIT SHARD {} AT m", shard.id)).await {
+        ExecuteResult::Data(result) => {
+            assert_eq!(result.columns, vec!["id", "start", "end"]);
+            result.rows.into_iter().map(|row| row.values).collect::<Vec<_>>()
+        }
+        others => panic!("split shard {}: {others:?}", shard.id),
+    };
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0][0].as_u64(), Some(shard.id));
+    assert_eq!(rows[0][2].as_str(), Some("m"));
+    assert_eq!(rows[1][1].as_str(), Some("m"));
+    let new_shard_id = rows[1][0].as_u64().unwrap();
+    assert_ne!(new_shard_id, shard.id);
+
+    assert_eq!(c.get_shard_desc(table.id, b"a").await.unwrap().id, shard.id);
+    assert_eq!(c.get_shard_desc(table.id, b"m").await.unwrap().id, new_shard_id);
+    for key in [b"a", b"m", b"z"] {
+        assert_eq!(db.get(table.id, key.to_vec()).await.unwrap(), Some(b"value".to_vec()));
+    }
+
+    // The shard split by the client api.
+    let shards = c.root_client().split_shard(new_shard_id, Some(b"t".to_vec())).await.unwrap();
+    assert_eq!(shards.len(), 2);
+    assert_eq!(shards[0].id, new_shard_id);
+    assert_eq!(shards[1].range.as_ref().unwrap().start, b"t");
+}
+
+#[sekas_macro::test]
+async fn split_shard_at_invalid_key() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let shard = c.get_shard_desc(table.id, b"a").await.unwrap();
+    let shards = c.root_client().split_shard(shard.id, Some(b"m".to_vec())).await.unwrap();
+    let new_shard_id = shards[1].id;
+
+    // The key outside of the shard.
+    let result = c.root_client().split_shard(shard.id, Some(b"z".to_vec())).await;
+    expect_invalid_argument(result, "not belong to the shard");
+    match execute(&c, &format!("SPLIT SHARD {} AT z", shard.id)).await {
+        ExecuteResult::Msg(msg) => assert!(msg.contains("not belong to the shard"), "{msg}"),
+        others => panic!("split shard at a key outside of the shard: {others:?}"),
+    }
+
+    // The key equal to the start of the shard.
+    let result = c.root_client().split_shard(new_shard_id, Some(b"m".to_vec())).await;
+    expect_invalid_argument(result, "start of the shard");
+    match execute(&c, &format!("SPLIT SHARD {new_shard_id} AT m")).await {
+        ExecuteResult::Msg(msg) => assert!(msg.contains("start of the shard"), "{msg}"),
+        others => panic!("split shard at the start of the shard: {others:?}"),
+    }
+
+    // The split at the start is rejected by the group as well.
+    let group_id = c.find_router_group_state_by_key(table.id, b"m").await.unwrap().id;
+    let result =
+        c.group(group_id).split_shard(new_shard_id, new_shard_id + 100, Some(b"m".to_vec())).await;
+    expect_invalid_argument(result, "start of the shard");
+}
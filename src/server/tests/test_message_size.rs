@@ -0,0 +1,120 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_client::{
+    AppError, ClientOptions, Database, Range, RangeRequest, TxnOptions, WriteBuilder,
+};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+const MB: usize = 1024 * 1024;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key-{i:02}").into_bytes()
+}
+
+async fn scan_all(db: &Database, table_id: u64) -> Vec<(Vec<u8>, usize)> {
+    let request = RangeRequest {
+        table_id,
+        range: Range::Range { begin: None, end: None },
+        ..Default::default()
+    };
+    let value_sets = db.range(request).await.unwrap().try_collect_vec(0).await.unwrap();
+    value_sets
+        .into_iter()
+        .filter_map(|value_set| {
+            let value = value_set.values.into_iter().next().and_then(|v| v.content)?;
+            Some((value_set.user_key, value.len()))
+        })
+        .collect()
+}
+
+#[sekas_macro::test]
+async fn large_scan_is_split_within_message_limit() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.set_max_message_bytes(4 * MB);
+    // The frames are only limited by the max message bytes.
+    ctx.mut_scan_config().frame_bytes = 64 * MB;
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    // The natural response of the scan takes 10MB.
+    for i in 0..10 {
+        db.put(table.id, key(i), vec![b'v'; MB]).await.unwrap();
+    }
+    let expect = (0..10).map(|i| (key(i), MB)).collect::<Vec<_>>();
+    assert_eq!(scan_all(&db, table.id).await, expect);
+
+    // The responses are limited by the client as well.
+    let opts = ClientOptions { max_message_bytes: Some(3 * MB / 2), ..Default::default() };
+    let app = c.app_client_with_options(opts).await;
+    let db = app.open_database("db".into()).await.unwrap();
+    assert_eq!(scan_all(&db, table.id).await, expect);
+}
+
+#[sekas_macro::test]
+async fn oversized_value_is_rejected_without_retry() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.set_max_message_bytes(4 * MB);
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    // The limits of the txn are specified, so the node capabilities are not
+    // fetched, the request is rejected by the leader.
+    let mut txn = db.begin_txn();
+    txn.set_options(TxnOptions {
+        max_write_bytes: Some(0),
+        max_write_count: Some(0),
+        ..Default::default()
+    });
+    txn.put(table.id, WriteBuilder::new(key(0)).ensure_put(vec![b'v'; 5 * MB]));
+    match txn.commit().await {
+        Err(AppError::MessageTooLarge { size, limit }) => {
+            assert_eq!(limit, 4 * MB as u64);
+            assert!(size > limit, "size {size}");
+        }
+        others => panic!("expect message too large, but got {others:?}"),
+    }
+
+    // The limit is negotiated by the node capabilities, the request is rejected
+    // before it is sent.
+    match db.put(table.id, key(1), vec![b'v'; 5 * MB]).await {
+        Err(AppError::MessageTooLarge { limit, .. }) => assert_eq!(limit, 4 * MB as u64),
+        others => panic!("expect message too large, but got {others:?}"),
+    }
+
+    // The values within the limit are served as usual.
+    db.put(table.id, key(2), vec![b'v'; MB]).await.unwrap();
+    assert_eq!(db.get(table.id, key(0)).await.unwrap(), None);
+    assert_eq!(db.get(table.id, key(1)).await.unwrap(), None);
+    assert_eq!(db.get(table.id, key(2)).await.unwrap(), Some(vec![b'v'; MB]));
+}
@@ -0,0 +1,98 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The sum of the series of the metric in the prometheus text format.
+fn metric_value(content: &str, name: &str) -> Option<f64> {
+    let values = content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (series, value) = line.rsplit_once(' ')?;
+            let metric = series.split('{').next()?;
+            if metric != name {
+                return None;
+            }
+            value.parse::<f64>().ok()
+        })
+        .collect::<Vec<_>>();
+    (!values.is_empty()).then(|| values.into_iter().sum())
+}
+
+async fn scrape(addr: &str) -> String {
+    let resp = reqwest::get(format!("http://{addr}/metrics")).await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    resp.text().await.unwrap()
+}
+
+#[sekas_macro::test]
+async fn metrics_exporter_exposes_counters() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.enable_metrics_exporter();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addr = ctx.metrics_addr(0).unwrap();
+
+    let resp = reqwest::get(format!("http://{addr}/healthz")).await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let mut ready = false;
+    for _ in 0..100 {
+        let resp = reqwest::get(format!("http://{addr}/readyz")).await.unwrap();
+        if resp.status() == reqwest::StatusCode::OK {
+            ready = true;
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(ready);
+    let resp = reqwest::get(format!("http://{addr}/unknown")).await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let content = scrape(&addr).await;
+    let before = metric_value(&content, "node_service_group_request_total").unwrap();
+    for i in 0..10u32 {
+        db.put(table.id, i.to_be_bytes().to_vec(), b"value".to_vec()).await.unwrap();
+    }
+    let content = scrape(&addr).await;
+    let after = metric_value(&content, "node_service_group_request_total").unwrap();
+    assert!(after >= before + 10.0, "before {before}, after {after}");
+    assert!(metric_value(&content, "metrics_exporter_scrape_total").unwrap() >= 2.0);
+
+    // The root-level metrics are exposed by the root leader.
+    assert_eq!(metric_value(&content, "cluster_node_total"), Some(3.0), "{content}");
+
+    // The client metrics could be merged into the exporters of applications.
+    let families = sekas_client::metrics_registry().gather();
+    assert!(families.iter().any(|f| f.get_name() == "group_client_group_request_total"));
+}
@@ -14,13 +14,13 @@
 // limitations under the License.
 mod helper;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use log::{debug, error, info, warn};
 use sekas_api::server::v1::group_request_union::Request;
 use sekas_api::server::v1::group_response_union::Response;
 use sekas_api::server::v1::*;
-use sekas_client::RetryState;
+use sekas_client::{RetryState, ShardClient};
 use sekas_rock::fn_name;
 
 use crate::helper::client::*;
@@ -99,6 +99,9 @@ async fn validate(c: &ClusterClient, group_id: u64, shard_id: u64, range: std::o
             shard_id,
             start_version: u64::MAX,
             user_key: key.as_bytes().to_vec(),
+            causal_token: 0,
+            txn_id: 0,
+            max_staleness_ms: 0,
         });
 
         let mut retry_state = RetryState::default();
@@ -106,7 +109,7 @@ async fn validate(c: &ClusterClient, group_id: u64, shard_id: u64, range: std::o
             match c.request(&req).await {
                 Ok(resp) => {
                     let Response::Get(resp) = resp else { panic!("Invalid response type") };
-                    assert!(matches!(resp.value, Some(Value { content: Some(content), version: _})
+                    assert!(matches!(resp.value, Some(Value { content: Some(content), .. })
                             if content == expected_value));
                     break;
                 }
@@ -302,6 +305,27 @@ async fn move_shard_basic() {
     move_shard(&c, &shard_desc, group_id_2, group_id_1).await;
 }
 
+#[sekas_macro::test]
+async fn move_shard_with_encryption() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.enable_encryption();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let node_ids = nodes.keys().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes).await;
+    let (group_id_1, group_id_2, shard_desc) = create_two_groups(&c, node_ids, 1000).await;
+
+    move_shard(&c, &shard_desc, group_id_2, group_id_1).await;
+    validate(&c, group_id_2, shard_desc.id, 0..1000).await;
+
+    // The pulled shard chunks are sealed in transit.
+    let app = c.app_client().await;
+    let client = ShardClient::new(group_id_2, shard_desc.id, app);
+    let resp = client.pull(None, true).await.unwrap();
+    assert!(resp.data.is_empty(), "{} plain value sets", resp.data.len());
+    assert!(!resp.sealed.is_empty());
+    assert!(resp.sealed.iter().all(|sealed| sealed.key_id == "test"));
+}
+
 #[sekas_macro::test]
 async fn move_shard_abort() {
     let mut ctx = TestContext::new(fn_name!());
@@ -480,7 +504,7 @@ async fn move_shard_receive_forward_request_after_shard_migrated() {
         shard_id,
         forward_data: vec![ValueSet {
             user_key: b"a".to_vec(),
-            values: vec![Value { content: Some(b"b".to_vec()), version: 1 }],
+            values: vec![Value::with_value(b"b".to_vec(), 1)],
         }],
         request: Some(GroupRequestUnion {
             request: Some(Request::Write(ShardWriteRequest {
@@ -500,6 +524,9 @@ async fn move_shard_receive_forward_request_after_shard_migrated() {
             shard_id,
             start_version: u64::MAX,
             user_key: b"a".to_vec(),
+            causal_token: 0,
+            txn_id: 0,
+            max_staleness_ms: 0,
         }))
         .await
         .unwrap();
@@ -515,6 +542,9 @@ async fn move_shard_receive_forward_request_after_shard_migrated() {
             shard_id,
             start_version: u64::MAX,
             user_key: b"b".to_vec(),
+            causal_token: 0,
+            txn_id: 0,
+            max_staleness_ms: 0,
         }))
         .await
         .unwrap();
@@ -522,7 +552,83 @@ async fn move_shard_receive_forward_request_after_shard_migrated() {
         Response::Get(ShardGetResponse { value }) => value,
         _ => panic!("invalid response type, Get is required"),
     };
-    assert!(
-        matches!(value, Some(Value { content: Some(v), version: _ }) if v == b"value".to_vec())
-    );
+    assert!(matches!(value, Some(Value { content: Some(v), .. }) if v == b"value".to_vec()));
+}
+
+async fn insert_large_values(c: &ClusterClient, group_id: u64, shard_id: u64, num_keys: u64) {
+    let mut c = c.group(group_id);
+    for i in 0..num_keys {
+        let put = PutRequest {
+            key: format!("large-{i}").into_bytes(),
+            value: vec![b'x'; 1024],
+            ..Default::default()
+        };
+        let req =
+            Request::Write(ShardWriteRequest { shard_id, puts: vec![put], ..Default::default() });
+
+        let mut retry_state = RetryState::default();
+        while let Err(err) = c.request(&req).await {
+            retry_state.retry(err).await.unwrap();
+        }
+    }
+}
+
+/// Cancel a slow moving shard, both groups should return to the descriptors
+/// before moving, and the writes during moving should be kept.
+#[sekas_macro::test]
+async fn move_shard_cancel() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.set_shard_move_bytes_per_sec(32 * 1024);
+    let nodes = ctx.bootstrap_servers(3).await;
+    let node_ids = nodes.keys().cloned().collect::<Vec<_>>();
+    let c = ClusterClient::new(nodes).await;
+    let (group_id_1, group_id_2, shard_desc) = create_two_groups(&c, node_ids, 100).await;
+    let shard_id = shard_desc.id;
+    insert_large_values(&c, group_id_1, shard_id, 512).await;
+
+    let src_epoch = c.must_group_epoch(group_id_1).await;
+    let dest_epoch = c.must_group_epoch(group_id_2).await;
+
+    info!("issue accept shard {} request to group {}", shard_id, group_id_2);
+    let mut group_client = c.group(group_id_2);
+    group_client.accept_shard(group_id_1, src_epoch, &shard_desc).await.unwrap();
+
+    // The progress of copying advances.
+    let root_client = c.root_client();
+    let mut copied_keys = 0;
+    for _ in 0..2 {
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            assert!(Instant::now() < deadline, "the copying progress of shard doesn't advance");
+            let status = root_client.migration_status(shard_id).await.unwrap();
+            if let Some(status) = status.filter(|s| s.copied_keys > copied_keys) {
+                assert_eq!(status.phase, migration_status::Phase::Copying as i32);
+                assert!(status.copied_bytes > 0);
+                copied_keys = status.copied_keys;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+    info!("shard {shard_id} copied keys {copied_keys}");
+
+    // The writes during moving are forwarded to the dest group.
+    insert(&c, group_id_1, shard_id, 100..110).await;
+
+    root_client.cancel_migration(shard_id).await.unwrap();
+    for _ in 0..1000 {
+        if is_not_in_shard_moving(&c, group_id_2).await
+            && root_client.migration_status(shard_id).await.unwrap().is_none()
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(root_client.migration_status(shard_id).await.unwrap().is_none());
+    assert!(root_client.cancel_migration(shard_id).await.is_err());
+
+    assert_eq!(c.must_group_epoch(group_id_1).await, src_epoch);
+    assert_eq!(c.must_group_epoch(group_id_2).await, dest_epoch);
+    assert!(c.group_contains_shard(group_id_1, shard_id));
+    validate(&c, group_id_1, shard_id, 0..110).await;
 }
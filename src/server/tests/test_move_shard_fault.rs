@@ -0,0 +1,176 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_rock::fn_name;
+use sekas_server::node::move_shard::{MoveShardFault, MoveShardFaultPoint};
+
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+use crate::helper::move_shard::*;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const NUM_KEYS: u64 = 64;
+
+async fn setup(ctx: &mut TestContext) -> MoveShardCluster {
+    let mut cluster = MoveShardCluster::new(ctx, 3).await;
+    cluster.load(0..NUM_KEYS, 0).await;
+    cluster.assert_descriptors_consistent(true).await;
+    cluster
+}
+
+/// The dest leader crashes once the shard is acquired, the new leader acquires
+/// the shard again and finishes the moving.
+#[sekas_macro::test]
+async fn move_shard_fail_after_acquire_shard() {
+    let mut ctx = TestContext::new(fn_name!());
+    let mut cluster = setup(&mut ctx).await;
+
+    let point = MoveShardFaultPoint::AfterAcquireShard;
+    let hits = cluster.inject(point, MoveShardFault::Fail);
+    cluster.start_move().await;
+    cluster.wait_hit(point, hits).await;
+    cluster.assert_descriptors_consistent(false).await;
+
+    cluster.transfer_leader(DEST_GROUP_ID).await;
+    cluster.assert_moved().await;
+    assert!(cluster.faults().hits(point) > hits + 1);
+
+    cluster.load(0..NUM_KEYS, 1).await;
+    cluster.assert_no_key_lost().await;
+    cluster.assert_no_key_duplicated().await;
+}
+
+/// The writes during pulling are forwarded to the dest group, the forwarded
+/// values are not ingested again by the following chunks.
+#[sekas_macro::test]
+async fn move_shard_pause_mid_pull_with_forwarded_writes() {
+    let mut ctx = TestContext::new(fn_name!());
+    let mut cluster = setup(&mut ctx).await;
+
+    let point = MoveShardFaultPoint::MidPull;
+    let hits = cluster.inject(point, MoveShardFault::Pause);
+    cluster.start_move().await;
+    cluster.wait_hit(point, hits).await;
+
+    // Both the pulled and the pending keys are rewritten.
+    cluster.load(0..NUM_KEYS, 1).await;
+    cluster.load(NUM_KEYS..NUM_KEYS + 8, 1).await;
+    cluster.assert_no_key_lost().await;
+    cluster.assert_descriptors_consistent(false).await;
+
+    cluster.clear(point);
+    cluster.assert_moved().await;
+}
+
+/// The dest leader crashes after some chunks are pulled, the new leader resumes
+/// pulling since the saved progress.
+#[sekas_macro::test]
+async fn move_shard_fail_mid_pull() {
+    let mut ctx = TestContext::new(fn_name!());
+    let mut cluster = setup(&mut ctx).await;
+
+    let point = MoveShardFaultPoint::MidPull;
+    let hits = cluster.inject(point, MoveShardFault::Fail);
+    cluster.start_move().await;
+    cluster.wait_hit(point, hits).await;
+    cluster.load(0..NUM_KEYS / 2, 1).await;
+
+    cluster.transfer_leader(DEST_GROUP_ID).await;
+    cluster.load(NUM_KEYS / 2..NUM_KEYS, 1).await;
+    cluster.assert_moved().await;
+}
+
+/// The source leader changes after all chunks are pulled, the moving is
+/// committed and the source group moves out the shard with the new leader.
+#[sekas_macro::test]
+async fn move_shard_pause_before_commit_moving() {
+    let mut ctx = TestContext::new(fn_name!());
+    let mut cluster = setup(&mut ctx).await;
+
+    let point = MoveShardFaultPoint::BeforeCommitMoving;
+    let hits = cluster.inject(point, MoveShardFault::Pause);
+    cluster.start_move().await;
+    cluster.wait_hit(point, hits).await;
+
+    cluster.transfer_leader(SRC_GROUP_ID).await;
+    cluster.load(0..NUM_KEYS, 1).await;
+    cluster.assert_no_key_lost().await;
+    cluster.assert_descriptors_consistent(false).await;
+
+    cluster.clear(point);
+    cluster.assert_moved().await;
+}
+
+/// The dest leader crashes before the moving is committed, the new leader
+/// pulls the rest chunks again and commits the moving.
+#[sekas_macro::test]
+async fn move_shard_fail_before_commit_moving() {
+    let mut ctx = TestContext::new(fn_name!());
+    let mut cluster = setup(&mut ctx).await;
+
+    let point = MoveShardFaultPoint::BeforeCommitMoving;
+    let hits = cluster.inject(point, MoveShardFault::Fail);
+    cluster.start_move().await;
+    cluster.wait_hit(point, hits).await;
+    cluster.load(NUM_KEYS / 2..NUM_KEYS, 1).await;
+
+    cluster.transfer_leader(DEST_GROUP_ID).await;
+    cluster.assert_moved().await;
+}
+
+/// The moving is committed in the dest group, the writes are still forwarded
+/// by the source group until it moves out the shard.
+#[sekas_macro::test]
+async fn move_shard_pause_before_cleanup_source() {
+    let mut ctx = TestContext::new(fn_name!());
+    let mut cluster = setup(&mut ctx).await;
+
+    let point = MoveShardFaultPoint::BeforeCleanupSource;
+    let hits = cluster.inject(point, MoveShardFault::Pause);
+    cluster.start_move().await;
+    cluster.wait_hit(point, hits).await;
+
+    assert_eq!(cluster.owner_group_id(), SRC_GROUP_ID);
+    cluster.load(0..NUM_KEYS, 1).await;
+    cluster.assert_no_key_lost().await;
+    cluster.assert_descriptors_consistent(true).await;
+
+    cluster.clear(point);
+    cluster.assert_moved().await;
+}
+
+/// The dest leader crashes after the moving is committed, the new leader asks
+/// the source group to move out the shard.
+#[sekas_macro::test]
+async fn move_shard_fail_before_cleanup_source() {
+    let mut ctx = TestContext::new(fn_name!());
+    let mut cluster = setup(&mut ctx).await;
+
+    let point = MoveShardFaultPoint::BeforeCleanupSource;
+    let hits = cluster.inject(point, MoveShardFault::Fail);
+    cluster.start_move().await;
+    cluster.wait_hit(point, hits).await;
+    cluster.load(0..NUM_KEYS / 2, 1).await;
+
+    cluster.transfer_leader(DEST_GROUP_ID).await;
+    cluster.assert_moved().await;
+    cluster.load(NUM_KEYS / 2..NUM_KEYS, 1).await;
+    cluster.assert_no_key_duplicated().await;
+}
@@ -15,11 +15,14 @@
 mod helper;
 
 use std::collections::HashSet;
+use std::time::Duration;
 
 use helper::context::TestContext;
 use log::info;
 use sekas_api::server::v1::*;
+use sekas_parser::ExecuteResult;
 use sekas_rock::fn_name;
+use sekas_runtime::time::{sleep, Instant};
 
 use crate::helper::client::*;
 use crate::helper::init::setup_panic_hook;
@@ -257,3 +260,76 @@ async fn node_schedule_cure_group() {
     ctx.wait_election_timeout().await;
     c.assert_group_not_contains_node(group_id, offline_node_id).await;
 }
+
+async fn execute_root_statement(c: &ClusterClient, stmt: &str) -> ExecuteResult {
+    let json_body = c.root_client().handle_statement(stmt).await.unwrap();
+    serde_json::from_slice(&json_body).unwrap()
+}
+
+/// Find the recommendation by the described action, returns the id and status.
+async fn find_recommendation(c: &ClusterClient, action: &str) -> Option<(u64, String)> {
+    let ExecuteResult::Data(result) = execute_root_statement(c, "SHOW recommendations").await
+    else {
+        panic!("SHOW recommendations should return data");
+    };
+    result.rows.into_iter().rev().find_map(|row| {
+        if row.values[2].as_str() != Some(action) {
+            return None;
+        }
+        Some((row.values[0].as_u64().unwrap(), row.values[1].as_str().unwrap().to_owned()))
+    })
+}
+
+#[sekas_macro::test]
+async fn node_schedule_cure_group_after_approved() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(4).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+
+    execute_root_statement(&c, "CONFIG schedule_mode advise").await;
+    execute_root_statement(&c, "CONFIG schedule_auto_cure false").await;
+
+    let group_id = 10;
+    let mut node_id_list = nodes.keys().cloned().collect::<Vec<_>>();
+    node_id_list.sort_unstable();
+    node_id_list.pop();
+    let offline_node_id = node_id_list.last().cloned().unwrap();
+
+    info!("create new group {group_id}");
+    create_group(&c, group_id, node_id_list, vec![]).await;
+    c.assert_group_leader(group_id).await;
+    c.assert_root_group_has_promoted().await;
+
+    info!("stop server {offline_node_id}");
+    ctx.stop_server(offline_node_id).await;
+    ctx.wait_election_timeout().await;
+    c.assert_group_leader(group_id).await;
+
+    info!("the cure of group is recorded as a pending recommendation");
+    let action = format!("cure group {group_id} by adding 1 replicas");
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let id = loop {
+        assert!(Instant::now() < deadline, "the cure of group {group_id} isn't recommended");
+        if let Some((id, status)) = find_recommendation(&c, &action).await {
+            assert_eq!(status, "PENDING");
+            break id;
+        }
+        sleep(Duration::from_millis(100)).await;
+    };
+    ctx.wait_election_timeout().await;
+    let state = c.get_router_group_state(group_id).await.unwrap();
+    assert!(state.replicas.values().any(|r| r.node_id == offline_node_id));
+
+    info!("approvals are rejected in advise mode");
+    assert!(c.root_client().approve_action(id).await.is_err());
+
+    info!("approve the cure of group in manual-approve mode");
+    execute_root_statement(&c, "CONFIG schedule_mode manual-approve").await;
+    c.root_client().approve_action(id).await.unwrap();
+    c.assert_group_not_contains_node(group_id, offline_node_id).await;
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while find_recommendation(&c, &action).await != Some((id, "EXECUTED".to_owned())) {
+        assert!(Instant::now() < deadline, "recommendation {id} isn't executed");
+        sleep(Duration::from_millis(100)).await;
+    }
+}
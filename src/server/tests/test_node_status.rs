@@ -0,0 +1,98 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The index of the `version` column of `SHOW nodes`.
+const VERSION_COLUMN: usize = 6;
+
+/// Show the nodes until the status of all of them are reported.
+async fn show_reported_nodes(c: &ClusterClient) -> Vec<Vec<serde_json::Value>> {
+    for _ in 0..100 {
+        let json_body = c.root_client().handle_statement("SHOW nodes").await.unwrap();
+        let ExecuteResult::Data(result) = serde_json::from_slice(&json_body).unwrap() else {
+            panic!("SHOW nodes returns no data");
+        };
+        let rows = result.rows.into_iter().map(|row| row.values).collect::<Vec<_>>();
+        if rows.iter().all(|row| !row[VERSION_COLUMN].is_null()) {
+            return rows;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+    }
+    panic!("the status of nodes are not reported");
+}
+
+#[sekas_macro::test]
+async fn node_status_reports_build_and_uptime() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let addr = nodes.get(&0).unwrap().clone();
+    let c = ClusterClient::new(nodes.clone()).await;
+    c.wait_for_leader(sekas_schema::ROOT_GROUP_ID).await;
+
+    let client = node_client_with_retry(&addr).await;
+    let status = client.get_node_status().await.unwrap();
+    assert_eq!(status.version, env!("CARGO_PKG_VERSION"));
+    assert!(!status.git_hash.is_empty());
+    assert_ne!(status.features, 0);
+    assert!(!status.feature_names.is_empty());
+    for kind in ["data", "wal"] {
+        let dir = status.dirs.iter().find(|d| d.kind == kind).expect(kind);
+        assert!(dir.total_bytes > 0, "{kind} dir {dir:?}");
+    }
+    assert!(status.replica_counts.unwrap_or_default().voters > 0);
+    let health = status.health.unwrap_or_default();
+    assert!((0.0..=1.0).contains(&health.cpu_util));
+    assert!(health.mem_used <= health.mem_total);
+    if cfg!(target_os = "linux") {
+        assert!(health.mem_total > 0);
+    }
+
+    // The root caches the status reported by the heartbeats.
+    let rows = show_reported_nodes(&c).await;
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0][VERSION_COLUMN].as_str(), Some(env!("CARGO_PKG_VERSION")));
+
+    // The uptime is reset by restarting, but the node id is persisted.
+    sekas_runtime::time::sleep(Duration::from_secs(3)).await;
+    let before = client.get_node_status().await.unwrap();
+    assert!(before.uptime_secs >= 2, "uptime {}", before.uptime_secs);
+    drop(client);
+    drop(c);
+    ctx.shutdown();
+
+    let nodes = ctx.start_servers(nodes).await;
+    let client = node_client_with_retry(&addr).await;
+    let after = client.get_node_status().await.unwrap();
+    assert_eq!(after.node_id, before.node_id);
+    assert!(after.uptime_secs < before.uptime_secs, "uptime {}", after.uptime_secs);
+
+    let c = ClusterClient::new(nodes).await;
+    let rows = show_reported_nodes(&c).await;
+    assert_eq!(rows[0][0].as_u64(), Some(after.node_id));
+}
@@ -0,0 +1,154 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use sekas_rock::fn_name;
+use sekas_server::offline::{self, Corruption, ReplayOptions};
+use sekas_server::serverpb::v1::ReplicaLocalState;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+#[sekas_macro::test]
+async fn offline_inspect_data_dir() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    for i in 0..100u32 {
+        let key = format!("key-{i:03}").into_bytes();
+        db.put(table.id, key, i.to_be_bytes().to_vec()).await.unwrap();
+    }
+    db.put(table.id, b"key-000".to_vec(), b"overwrite".to_vec()).await.unwrap();
+    // The intents are committed asynchronously, and the dump skips intents.
+    sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+
+    let table_group = c.find_router_group_state_by_key(table.id, b"key-000").await.unwrap();
+    let root_group = c.get_router_group_state(sekas_schema::ROOT_GROUP_ID).await.unwrap();
+    let shard = c.get_shard_desc(table.id, b"key-000").await.unwrap();
+    drop(app);
+    ctx.shutdown();
+
+    let dir = ctx.server_dir(0);
+    let inspection = offline::inspect(&dir).await.unwrap();
+    let node_id = inspection.node_id.unwrap();
+    assert!(inspection.cluster_id.as_ref().is_some_and(|id| !id.is_empty()));
+    assert!(!inspection.is_corrupted(), "{inspection:?}");
+    let mut expected_replicas = HashSet::new();
+    for group in [&root_group, &table_group] {
+        for replica in group.replicas.values().filter(|r| r.node_id == node_id) {
+            expected_replicas.insert((group.id, replica.id));
+        }
+    }
+    let replicas =
+        inspection.replicas.iter().map(|r| (r.group_id, r.replica_id)).collect::<HashSet<_>>();
+    assert_eq!(replicas, expected_replicas);
+    for replica in &inspection.replicas {
+        assert_eq!(replica.state, ReplicaLocalState::Normal);
+        assert!(replica.applied_index > 0, "{replica:?}");
+        assert!(replica.term > 0, "{replica:?}");
+        assert!(replica.log_range.is_some(), "{replica:?}");
+    }
+
+    let dump = dir.join("shard.dump");
+    let num_keys = offline::dump_shard(&dir, shard.id, &dump).await.unwrap();
+    assert_eq!(num_keys, 100);
+    let (shard_desc, value_sets) = offline::load_shard_dump(&dump).unwrap();
+    assert_eq!(shard_desc.id, shard.id);
+    assert_eq!(value_sets.len(), 100);
+    assert_eq!(value_sets[0].user_key, b"key-000");
+    assert_eq!(value_sets[0].values.len(), 2);
+    assert_eq!(value_sets[0].values[0].content.as_deref(), Some(b"overwrite".as_slice()));
+
+    // A snapshot without meta is reported.
+    let replica_id = inspection.replicas[0].replica_id;
+    let snap_dir = dir.join("log").join("snap").join(replica_id.to_string()).join("1000");
+    std::fs::create_dir_all(&snap_dir).unwrap();
+    let inspection = offline::inspect(&dir).await.unwrap();
+    let replica = inspection.replicas.iter().find(|r| r.replica_id == replica_id).unwrap();
+    assert_eq!(replica.corruptions, vec![Corruption::MissingSnapMeta { dir: snap_dir }]);
+
+    // The node could be started after inspection.
+    let nodes = ctx.start_servers(nodes).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.open_database("db".into()).await.unwrap();
+    let value = db.get(table.id, b"key-099".to_vec()).await.unwrap();
+    assert_eq!(value, Some(99u32.to_be_bytes().to_vec()));
+}
+
+#[sekas_macro::test]
+async fn offline_replay_group_finds_divergent_entry() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    for i in 0..20u32 {
+        let key = format!("key-{i:03}").into_bytes();
+        db.put(table.id, key, i.to_be_bytes().to_vec()).await.unwrap();
+    }
+    sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+    let group = c.find_router_group_state_by_key(table.id, b"key-000").await.unwrap();
+    drop(app);
+    ctx.shutdown();
+
+    let dir = ctx.server_dir(0);
+    let inspection = offline::inspect(&dir).await.unwrap();
+    let replica = inspection.replicas.iter().find(|r| r.group_id == group.id).unwrap();
+    let (first_index, last_index) = replica.log_range.unwrap();
+    let digests = offline::replay_group(&dir, group.id, first_index, last_index).await.unwrap();
+    assert_eq!(digests.len() as u64, last_index - first_index + 1);
+    assert_eq!(digests.first().map(|d| d.index), Some(first_index));
+
+    // The replay is deterministic.
+    let replayed = offline::replay_group(&dir, group.id, first_index, last_index).await.unwrap();
+    assert_eq!(offline::first_divergence(&digests, &replayed), None);
+    assert_eq!(digests, replayed);
+
+    // Apply the writes of an entry differently.
+    let diverged_index = digests.iter().filter(|d| d.write_bytes > 0).nth(10).unwrap().index;
+    let opts = ReplayOptions::default();
+    opts.replica.testing_knobs.apply_faults.inject_divergence(diverged_index);
+    let diverged =
+        offline::replay_group_with(&dir, group.id, first_index, last_index, opts).await.unwrap();
+    assert_eq!(offline::first_divergence(&digests, &diverged), Some(diverged_index));
+
+    // A part of the range is replayed from the same base state.
+    let tail = offline::replay_group(&dir, group.id, diverged_index, last_index).await.unwrap();
+    assert_eq!(tail.as_slice(), &digests[(diverged_index - first_index) as usize..]);
+
+    // The entries not retained by the raft logs are refused.
+    assert!(offline::replay_group(&dir, group.id, first_index, last_index + 1).await.is_err());
+    assert!(offline::replay_group(&dir, group.id, 0, last_index).await.is_err());
+    let opts = ReplayOptions { base: Some(dir.join("no-such-snapshot")), ..Default::default() };
+    assert!(offline::replay_group_with(&dir, group.id, first_index, last_index, opts)
+        .await
+        .is_err());
+}
@@ -0,0 +1,115 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::HashSet;
+
+use sekas_client::{ParallelScanOptions, Range, RangeRequest, ShardScanHandle};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+use crate::helper::runtime::spawn;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const NUM_KEYS: usize = 200;
+
+fn key(index: usize) -> Vec<u8> {
+    format!("key-{index:03}").into_bytes()
+}
+
+/// Split the shard containing the key at the key.
+async fn split_at(c: &ClusterClient, table_id: u64, split_key: Vec<u8>, new_shard_id: u64) {
+    let shard = c.get_shard_desc(table_id, &split_key).await.unwrap();
+    let group = c.find_router_group_state_by_key(table_id, &split_key).await.unwrap();
+    c.group(group.id).split_shard(shard.id, new_shard_id, Some(split_key.clone())).await.unwrap();
+    // Wait until the router observes the new shard.
+    while c.get_shard_desc(table_id, &split_key).await.map(|s| s.id) != Some(new_shard_id) {
+        sekas_runtime::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+}
+
+#[sekas_macro::test]
+async fn parallel_scan_by_shard_handles() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    for i in 0..NUM_KEYS {
+        db.put(table.id, key(i), b"value-0".to_vec()).await.unwrap();
+    }
+    let first_shard_id = sekas_schema::FIRST_USER_SHARD_ID + 1024;
+    split_at(&c, table.id, key(50), first_shard_id).await;
+    split_at(&c, table.id, key(120), first_shard_id + 1).await;
+
+    let (version, handles) =
+        db.parallel_scan(table.id, ParallelScanOptions::default()).await.unwrap();
+    assert_eq!(handles.len(), 3, "{handles:?}");
+    assert!(handles.iter().all(|h| h.read_version() == version.0));
+    assert!(handles.iter().all(|h| !h.node_addrs().is_empty()), "{handles:?}");
+    // The handles are serialized, as they are sent to other processes.
+    let handles = handles.iter().map(ShardScanHandle::to_bytes).collect::<Vec<_>>();
+
+    // The writes and splits after the handles are created are not observed.
+    for i in (0..NUM_KEYS).step_by(3) {
+        db.put(table.id, key(i), b"value-1".to_vec()).await.unwrap();
+    }
+    db.put(table.id, b"key-new".to_vec(), b"value-1".to_vec()).await.unwrap();
+    split_at(&c, table.id, key(80), first_shard_id + 2).await;
+
+    let mut tasks = vec![];
+    for handle in handles {
+        let app = c.app_client().await;
+        tasks.push(spawn(async move {
+            let handle = ShardScanHandle::from_bytes(&handle).unwrap();
+            let stream = app.resume_shard_scan(&handle).await.unwrap();
+            stream.try_collect_vec(0).await.unwrap()
+        }));
+    }
+    let mut union = vec![];
+    for task in tasks {
+        union.extend(task.await.unwrap());
+    }
+
+    let snapshot = RangeRequest {
+        table_id: table.id,
+        version: Some(version.0),
+        range: Range::all(),
+        ..Default::default()
+    };
+    let expect = db.range(snapshot).await.unwrap().try_collect_vec(0).await.unwrap();
+    assert_eq!(expect.len(), NUM_KEYS);
+    let keys = union.iter().map(|v| v.user_key.clone()).collect::<HashSet<_>>();
+    assert_eq!(keys.len(), union.len(), "the ranges of handles are overlapped");
+    assert_eq!(union, expect);
+
+    // The range of the parallel scan is limited.
+    let opts =
+        ParallelScanOptions { range: Range::Prefix(b"key-1".to_vec()), ..Default::default() };
+    let (_, handles) = db.parallel_scan(table.id, opts).await.unwrap();
+    assert_eq!(handles.len(), 1, "{handles:?}");
+    let value_sets =
+        app.resume_shard_scan(&handles[0]).await.unwrap().try_collect_vec(0).await.unwrap();
+    assert_eq!(value_sets.len(), 100);
+}
@@ -0,0 +1,120 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sekas_client::Database;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const NUM_WRITES: usize = 50;
+
+fn table_proposals(group_id: u64, table_id: u64) -> u64 {
+    let families = prometheus::gather();
+    let Some(family) = families.iter().find(|f| f.get_name() == "replica_table_proposal_total")
+    else {
+        return 0;
+    };
+    let (group_id, table_id) = (group_id.to_string(), table_id.to_string());
+    family
+        .get_metric()
+        .iter()
+        .find(|m| {
+            let labels = m.get_label();
+            labels.iter().any(|l| l.get_name() == "group" && l.get_value() == group_id)
+                && labels.iter().any(|l| l.get_name() == "table" && l.get_value() == table_id)
+        })
+        .map(|m| m.get_counter().get_value() as u64)
+        .unwrap_or_default()
+}
+
+/// The p99 latency of the low-rate writes.
+async fn low_rate_writes_p99(db: &Database, table_id: u64, round: usize) -> Duration {
+    let mut latencies = Vec::with_capacity(NUM_WRITES);
+    for i in 0..NUM_WRITES {
+        let key = format!("key-{i}").into_bytes();
+        let value = format!("value-{round}-{i}").into_bytes();
+        let start_at = Instant::now();
+        db.put(table_id, key, value).await.unwrap();
+        latencies.push(start_at.elapsed());
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    latencies.sort_unstable();
+    latencies[(NUM_WRITES * 99 / 100).min(NUM_WRITES - 1)]
+}
+
+#[sekas_macro::test]
+async fn bulk_writer_does_not_starve_low_rate_writer() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.mut_proposal_queue_config().max_inflight_proposals = 2;
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let bulk_table = db.create_table("bulk".into()).await.unwrap();
+    let table = db.create_table("interactive".into()).await.unwrap();
+    c.assert_table_ready(bulk_table.id).await;
+    c.assert_table_ready(table.id).await;
+
+    // Both tables share the same group.
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let bulk_group_id = c.find_router_group_state_by_key(bulk_table.id, b"key").await.unwrap().id;
+    assert_eq!(group_id, bulk_group_id);
+
+    let solo_p99 = low_rate_writes_p99(&db, table.id, 0).await;
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    let mut handles = Vec::new();
+    for writer in 0..8 {
+        let (db, stopped) = (db.clone(), stopped.clone());
+        let table_id = bulk_table.id;
+        handles.push(sekas_runtime::spawn(async move {
+            let value = vec![b'x'; 256 * 1024];
+            let mut i = 0;
+            while !stopped.load(Ordering::Relaxed) {
+                let key = format!("bulk-{writer}-{i}").into_bytes();
+                db.put(table_id, key, value.clone()).await.unwrap();
+                i += 1;
+            }
+        }));
+    }
+    // Wait until the bulk writes saturate the in-flight proposals.
+    sekas_runtime::time::sleep(Duration::from_millis(500)).await;
+
+    let former_proposals = table_proposals(group_id, table.id);
+    let contended_p99 = low_rate_writes_p99(&db, table.id, 1).await;
+    stopped.store(true, Ordering::Relaxed);
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    // Each write proposes an intent and commits it.
+    assert!(table_proposals(group_id, table.id) >= former_proposals + NUM_WRITES as u64);
+    assert!(
+        contended_p99 <= solo_p99 * 4 + Duration::from_millis(100),
+        "solo p99 {solo_p99:?}, contended p99 {contended_p99:?}"
+    );
+}
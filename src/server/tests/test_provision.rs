@@ -0,0 +1,146 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use sekas_api::server::v1::TableDesc;
+use sekas_client::{AppError, CreateTableOptions, Database};
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+use sekas_schema::property::PROVISIONING;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn seed_rows(value: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    (0..10).map(|i| (format!("key-{i}").into_bytes(), value.to_vec())).collect()
+}
+
+fn create_options(name: &str, request_id: &str) -> CreateTableOptions {
+    CreateTableOptions { request_id: Some(request_id.to_owned()), ..CreateTableOptions::new(name) }
+}
+
+/// Create the table as `create_table_with_seed` does, and the client dies
+/// before the seed is written.
+async fn create_table_then_die(db: &Database, name: &str, request_id: &str) -> TableDesc {
+    let mut opts = create_options(name, request_id);
+    opts.wait_ready = true;
+    opts.properties = HashMap::from([(PROVISIONING.to_owned(), String::new())]);
+    db.create_table_with(opts).await.unwrap()
+}
+
+/// The state of the table reported by `SHOW tables`.
+async fn show_table_state(c: &ClusterClient, name: &str) -> Option<String> {
+    let json_body = c.root_client().handle_statement("SHOW tables FROM db").await.unwrap();
+    let ExecuteResult::Data(result) = serde_json::from_slice(&json_body).unwrap() else {
+        panic!("show tables");
+    };
+    let state = result.columns.iter().position(|c| c == "state").unwrap();
+    let row = result.rows.into_iter().find(|row| row.values[1] == name)?;
+    row.values[state].as_str().map(ToOwned::to_owned)
+}
+
+async fn assert_seeded(db: &Database, table_id: u64, value: &[u8]) {
+    for (key, _) in seed_rows(value) {
+        assert_eq!(db.get(table_id, key).await.unwrap(), Some(value.to_vec()));
+    }
+}
+
+#[sekas_macro::test]
+async fn retry_completes_provisioning_table() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+
+    let table = create_table_then_die(&db, "table", "provision-table").await;
+    let created_at = table.properties.get(PROVISIONING).unwrap();
+    assert!(created_at.parse::<u64>().unwrap() > 0, "{created_at}");
+    assert_eq!(show_table_state(&c, "table").await.as_deref(), Some("provisioning"));
+    assert_eq!(db.get(table.id, b"key-0".to_vec()).await.unwrap(), None);
+
+    // The retry with the same request id reuses the table.
+    let opts = create_options("table", "provision-table");
+    let desc = db.create_table_with_seed(opts.clone(), seed_rows(b"seed")).await.unwrap();
+    assert_eq!(desc.id, table.id);
+    assert!(!desc.properties.contains_key(PROVISIONING));
+    assert_eq!(show_table_state(&c, "table").await.as_deref(), Some("ready"));
+    assert_seeded(&db, table.id, b"seed").await;
+
+    // The seed isn't written again once the table is ready.
+    db.put(table.id, b"key-0".to_vec(), b"updated".to_vec()).await.unwrap();
+    let desc = db.create_table_with_seed(opts, seed_rows(b"other")).await.unwrap();
+    assert_eq!(desc.id, table.id);
+    assert_eq!(db.get(table.id, b"key-0".to_vec()).await.unwrap(), Some(b"updated".to_vec()));
+    assert_eq!(db.get(table.id, b"key-1".to_vec()).await.unwrap(), Some(b"seed".to_vec()));
+
+    let result = db
+        .create_table_with_seed(create_options("table", "other-request"), seed_rows(b"seed"))
+        .await;
+    assert!(matches!(result, Err(AppError::AlreadyExists(_))), "{result:?}");
+
+    // The mark could not be set by the clients.
+    let properties = HashMap::from([(PROVISIONING.to_owned(), "1".to_owned())]);
+    let result = db.alter_table("table".into(), properties).await;
+    assert!(matches!(result, Err(AppError::InvalidArgument(_))), "{result:?}");
+}
+
+#[sekas_macro::test]
+async fn janitor_deletes_stale_provisioning_table() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.set_provisioning_timeout_sec(5);
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+
+    let seeded = db
+        .create_table_with_seed(create_options("seeded", "provision-seeded"), seed_rows(b"seed"))
+        .await
+        .unwrap();
+    let stale = create_table_then_die(&db, "stale", "provision-stale").await;
+
+    let mut deleted = false;
+    for _ in 0..150 {
+        if db.get_table("stale").await.unwrap().is_none() {
+            deleted = true;
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+    }
+    assert!(deleted, "the stale provisioning table should be deleted");
+    assert_eq!(show_table_state(&c, "stale").await, None);
+
+    // The table completed in time is kept.
+    assert_eq!(db.get_table("seeded").await.unwrap().map(|t| t.id), Some(seeded.id));
+    assert_eq!(show_table_state(&c, "seeded").await.as_deref(), Some("ready"));
+    assert_seeded(&db, seeded.id, b"seed").await;
+
+    // The retry after the deletion creates the table again.
+    let opts = create_options("stale", "provision-stale");
+    let desc = db.create_table_with_seed(opts, seed_rows(b"retry")).await.unwrap();
+    assert_ne!(desc.id, stale.id);
+    assert!(!desc.properties.contains_key(PROVISIONING));
+    assert_seeded(&db, desc.id, b"retry").await;
+}
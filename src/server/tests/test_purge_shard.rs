@@ -0,0 +1,117 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::path::Path;
+use std::time::Duration;
+
+use rand::RngCore;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The total size of the sst files under the dir.
+fn sst_size(dir: &Path) -> u64 {
+    let mut size = 0;
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+        if entry.file_type().unwrap().is_dir() {
+            size += sst_size(&path);
+        } else if path.extension().is_some_and(|ext| ext == "sst") {
+            // The file might be removed by compaction concurrently.
+            size += entry.metadata().map(|m| m.len()).unwrap_or_default();
+        }
+    }
+    size
+}
+
+#[sekas_macro::test]
+async fn purge_shards_of_dropped_table() {
+    const NUM_KEYS: usize = 1024;
+    const VALUE_SIZE: usize = 4096;
+
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.set_apply_checkpoint_entries(64);
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    let other_table = db.create_table("other".into()).await.unwrap();
+
+    let mut value = vec![0; VALUE_SIZE];
+    for i in 0..NUM_KEYS {
+        // The random values are not compressed.
+        rand::thread_rng().fill_bytes(&mut value);
+        let key = format!("key-{i:04}").into_bytes();
+        db.put(table.id, key, value.clone()).await.unwrap();
+    }
+    for i in 0..16 {
+        let key = format!("key-{i:04}").into_bytes();
+        db.put(other_table.id, key, b"value".to_vec()).await.unwrap();
+    }
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let shard_id = c.get_shard_desc(table.id, b"key").await.unwrap().id;
+    let dir = ctx.server_dir(0);
+    let data_size = (NUM_KEYS * VALUE_SIZE) as u64;
+    let mut size_before = 0;
+    for _ in 0..100 {
+        size_before = sst_size(&dir);
+        if size_before >= data_size {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(size_before >= data_size, "sst size {size_before}");
+
+    db.delete_table("table".into()).await.unwrap();
+    for _ in 0..1000 {
+        if !c.group_contains_shard(group_id, shard_id) {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(!c.group_contains_shard(group_id, shard_id));
+
+    // The space is reclaimed once the range of the shard is compacted.
+    let mut size_after = size_before;
+    for _ in 0..300 {
+        size_after = sst_size(&dir);
+        if size_after < size_before - data_size / 2 {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(
+        size_after < size_before - data_size / 2,
+        "sst size before {size_before}, after {size_after}"
+    );
+
+    // The shards of the other tables are untouched.
+    for i in 0..16 {
+        let key = format!("key-{i:04}").into_bytes();
+        let value = db.get(other_table.id, key).await.unwrap();
+        assert_eq!(value, Some(b"value".to_vec()));
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::topology_event;
+use sekas_client::{AppError, Database};
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+async fn execute(c: &ClusterClient, stmt: &str) -> ExecuteResult {
+    let json_body = c.root_client().handle_statement(stmt).await.unwrap();
+    serde_json::from_slice(&json_body).unwrap()
+}
+
+async fn execute_msg(c: &ClusterClient, stmt: &str) -> String {
+    match execute(c, stmt).await {
+        ExecuteResult::Msg(msg) => msg,
+        others => panic!("execute {stmt}: {others:?}"),
+    }
+}
+
+/// The effective quotas of the database shown by `SHOW databases`.
+async fn show_database_quota(c: &ClusterClient, name: &str) -> Vec<u64> {
+    let ExecuteResult::Data(result) = execute(c, "SHOW databases").await else {
+        panic!("show databases");
+    };
+    let quotas = ["max_tables_per_database", "max_shards_per_table", "max_shards_per_database"];
+    assert_eq!(result.columns[2..], quotas);
+    let row = result.rows.into_iter().find(|row| row.values[1] == name).unwrap();
+    row.values[2..].iter().map(|v| v.as_u64().unwrap()).collect()
+}
+
+async fn assert_table_healthy(c: &ClusterClient, db: &Database, table_id: u64) {
+    c.assert_table_ready(table_id).await;
+    db.put(table_id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert_eq!(db.get(table_id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+}
+
+#[sekas_macro::test]
+async fn create_tables_up_to_quota() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+
+    let msg = execute_msg(&c, "CONFIG max_tables_per_database 2").await;
+    assert!(msg.contains("is set"), "{msg}");
+    let db = app.create_database("db".into()).await.unwrap();
+    let other_db = app.create_database("other_db".into()).await.unwrap();
+    let mut tables = vec![];
+    for i in 0..2 {
+        tables.push(db.create_table(format!("table_{i}")).await.unwrap());
+    }
+    match db.create_table("table_2".into()).await {
+        Err(AppError::QuotaExceeded { limit, value }) => {
+            assert_eq!(limit, "max_tables_per_database");
+            assert_eq!(value, 2);
+        }
+        others => panic!("expect quota exceeded, but got {others:?}"),
+    }
+    for table in &tables {
+        assert_table_healthy(&c, &db, table.id).await;
+    }
+
+    // The override of a database doesn't change the others.
+    let msg = execute_msg(&c, "ALTER DATABASE db SET max_tables_per_database 3").await;
+    assert!(msg.contains("is altered"), "{msg}");
+    assert_eq!(show_database_quota(&c, "db").await, vec![3, 0, 0]);
+    assert_eq!(show_database_quota(&c, "other_db").await, vec![2, 0, 0]);
+    tables.push(db.create_table("table_2".into()).await.unwrap());
+    assert!(matches!(
+        db.create_table("table_3".into()).await,
+        Err(AppError::QuotaExceeded { value: 3, .. })
+    ));
+    for i in 0..2 {
+        other_db.create_table(format!("table_{i}")).await.unwrap();
+    }
+    assert!(matches!(
+        other_db.create_table("table_2".into()).await,
+        Err(AppError::QuotaExceeded { value: 2, .. })
+    ));
+
+    // The quota is freed once the table is deleted.
+    db.delete_table("table_0".into()).await.unwrap();
+    let table = db.create_table("table_3".into()).await.unwrap();
+    assert_table_healthy(&c, &db, table.id).await;
+    assert_table_healthy(&c, &db, tables[2].id).await;
+
+    // The override is removed by an empty value, and the quota is unlimited by 0.
+    execute_msg(&c, "ALTER DATABASE db SET max_tables_per_database \"\"").await;
+    assert!(matches!(
+        db.create_table("table_4".into()).await,
+        Err(AppError::QuotaExceeded { value: 2, .. })
+    ));
+    execute_msg(&c, "CONFIG max_tables_per_database 0").await;
+    db.create_table("table_4".into()).await.unwrap();
+
+    let msg = execute_msg(&c, "ALTER DATABASE db SET max_rows 1").await;
+    assert!(msg.contains("unknown quota"), "{msg}");
+}
+
+#[sekas_macro::test]
+async fn split_shards_up_to_quota() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table_a = db.create_table("table_a".into()).await.unwrap();
+    let table_b = db.create_table("table_b".into()).await.unwrap();
+    c.assert_table_ready(table_a.id).await;
+    c.assert_table_ready(table_b.id).await;
+
+    execute_msg(&c, "CONFIG max_shards_per_table 2").await;
+    let root = c.root_client();
+    let shard = c.get_shard_desc(table_a.id, b"a").await.unwrap();
+    let shards = root.split_shard(shard.id, Some(b"m".to_vec())).await.unwrap();
+    match root.split_shard(shards[1].id, Some(b"t".to_vec())).await {
+        Err(sekas_client::Error::QuotaExceeded { limit, value }) => {
+            assert_eq!(limit, "max_shards_per_table");
+            assert_eq!(value, 2);
+        }
+        others => panic!("expect quota exceeded, but got {others:?}"),
+    }
+    let msg = execute_msg(&c, &format!("SPLIT SHARD {} AT \"t\"", shards[1].id)).await;
+    assert!(msg.contains("max_shards_per_table"), "{msg}");
+    assert_table_healthy(&c, &db, table_a.id).await;
+
+    // The table b has 1 shard, the database has 3 shards.
+    execute_msg(&c, "ALTER DATABASE db SET max_shards_per_database 3").await;
+    assert_eq!(show_database_quota(&c, "db").await, vec![0, 2, 3]);
+    let shard = c.get_shard_desc(table_b.id, b"a").await.unwrap();
+    match root.split_shard(shard.id, Some(b"m".to_vec())).await {
+        Err(sekas_client::Error::QuotaExceeded { limit, value }) => {
+            assert_eq!(limit, "max_shards_per_database");
+            assert_eq!(value, 3);
+        }
+        others => panic!("expect quota exceeded, but got {others:?}"),
+    }
+    assert_table_healthy(&c, &db, table_b.id).await;
+
+    // The shards of the deleted table are freed immediately.
+    db.delete_table("table_a".into()).await.unwrap();
+    root.split_shard(shard.id, Some(b"m".to_vec())).await.unwrap();
+    assert_table_healthy(&c, &db, table_b.id).await;
+}
+
+#[sekas_macro::test]
+async fn auto_split_is_skipped_at_quota() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.enable_metrics_exporter();
+    // Any shard with data exceeds the split threshold.
+    ctx.mut_root_testing_knobs().split_threshold = 1;
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    execute_msg(&c, "CONFIG max_shards_per_table 1").await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    for i in 0..100 {
+        db.put(table.id, format!("key-{i:03}").into_bytes(), vec![b'v'; 1024]).await.unwrap();
+    }
+    // The shard size is estimated by the flushed data.
+    c.root_client().compact_table("db".into(), "table".into()).await.unwrap();
+
+    let shard = c.get_shard_desc(table.id, b"key").await.unwrap();
+    let target = format!("shard/{}", shard.id);
+    let mut recorded = false;
+    for _ in 0..300 {
+        let events = c.root_client().list_topology_events().await.unwrap_or_default();
+        if events.iter().any(|e| e.kind == topology_event::Kind::Quota as i32 && e.target == target)
+        {
+            recorded = true;
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(recorded, "the skipped auto split of shard {} is not recorded", shard.id);
+
+    let addr = ctx.metrics_addr(0).unwrap();
+    let content =
+        reqwest::get(format!("http://{addr}/metrics")).await.unwrap().text().await.unwrap();
+    let skipped = content
+        .lines()
+        .find_map(|line| line.strip_prefix("root_quota_skipped_split_shard_total "))
+        .and_then(|value| value.parse::<f64>().ok());
+    assert!(skipped.is_some_and(|v| v >= 1.0), "{skipped:?}");
+
+    // The shard is not split, and it is served as usual.
+    assert_eq!(c.get_shard_desc(table.id, b"key-099").await.unwrap().id, shard.id);
+    assert_table_healthy(&c, &db, table.id).await;
+}
@@ -0,0 +1,112 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::*;
+use sekas_client::{AppError, TxnStateTable, WriteBuilder};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+#[sekas_macro::test]
+async fn get_raw_key_with_parked_intent() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let key = b"key".to_vec();
+    let state = db.get_raw(table.id, key.clone()).await.unwrap();
+    assert_eq!(state, RawKeyState::default());
+
+    db.put(table.id, key.clone(), b"value".to_vec()).await.unwrap();
+    db.delete(table.id, key.clone()).await.unwrap();
+    let state = db.get_raw(table.id, key.clone()).await.unwrap();
+    let latest = state.latest.unwrap();
+    assert_eq!(latest.size, 5);
+    assert!(state.tombstone.unwrap() > latest.version);
+    assert_eq!(state.versions_retained, 2);
+    assert!(state.intent.is_none());
+
+    // The intent of the parked txn is reported without waiting for it.
+    let group_id = c.find_router_group_state_by_key(table.id, &key).await.unwrap().id;
+    let shard_id = c.get_shard_desc(table.id, &key).await.unwrap().id;
+    let ts_table = TxnStateTable::new(app.clone(), Some(Duration::from_secs(5)));
+    let root_client = c.root_client();
+    let mut group_client = c.group(group_id);
+    let start_version = root_client.alloc_txn_id(1, None).await.unwrap();
+    ts_table.begin_txn(start_version).await.unwrap();
+    let write = WriteBuilder::new(key.clone()).ensure_put(b"new-value".to_vec());
+    let req = Request::WriteIntent(WriteIntentRequest {
+        shard_id,
+        start_version,
+        write: Some(write_intent_request::Write::Put(write)),
+        ..Default::default()
+    });
+    group_client.request(&req).await.unwrap();
+    let state = db.get_raw(table.id, key.clone()).await.unwrap();
+    let expect = IntentInfo { txn_id: start_version, start_version, is_delete: false };
+    assert_eq!(state.intent, Some(expect));
+    assert_eq!(state.versions_retained, 2);
+
+    // The intent is gone once it is committed.
+    let commit_version = root_client.alloc_txn_id(1, None).await.unwrap();
+    ts_table.commit_txn(start_version, commit_version).await.unwrap();
+    let req = Request::CommitIntent(CommitIntentRequest {
+        shard_id,
+        start_version,
+        commit_version,
+        user_key: key.clone(),
+    });
+    group_client.request(&req).await.unwrap();
+    let state = db.get_raw(table.id, key.clone()).await.unwrap();
+    assert!(state.intent.is_none());
+    assert_eq!(state.latest, Some(ValueMeta { version: commit_version, size: 9 }));
+    assert_eq!(state.tombstone, None);
+    assert_eq!(state.versions_retained, 3);
+}
+
+#[sekas_macro::test]
+async fn get_raw_key_disabled() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_get_raw_key();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    // The readiness of shards is probed without the raw key state requests.
+    db.wait_table_ready(table.id, Duration::from_secs(10)).await.unwrap();
+
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    let r = db.get_raw(table.id, b"key".to_vec()).await;
+    assert!(matches!(r, Err(AppError::PermissionDenied(_))), "{r:?}");
+}
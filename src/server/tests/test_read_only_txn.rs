@@ -0,0 +1,131 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_client::{CommitPhase, WriteBuilder};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The requests issued to the txn state table, by type.
+fn txn_table_requests(kind: &str) -> u64 {
+    let families = prometheus::gather();
+    let Some(family) = families.iter().find(|f| f.get_name() == "client_txn_table_request_total")
+    else {
+        return 0;
+    };
+    family
+        .get_metric()
+        .iter()
+        .find(|m| m.get_label().iter().any(|l| l.get_name() == "type" && l.get_value() == kind))
+        .map(|m| m.get_counter().get_value() as u64)
+        .unwrap_or_default()
+}
+
+fn all_txn_table_requests() -> u64 {
+    ["begin", "heartbeat", "commit", "abort", "get"].into_iter().map(txn_table_requests).sum()
+}
+
+#[sekas_macro::test]
+async fn read_only_txn_skips_txn_record() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    db.put(table.id, b"key-1".to_vec(), b"v1".to_vec()).await.unwrap();
+    db.put(table.id, b"key-2".to_vec(), b"v1".to_vec()).await.unwrap();
+    // The intents are resolved by the leader asynchronously.
+    sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+
+    // All reads share the version acquired by the first read.
+    let txn = db.begin_txn();
+    assert_eq!(txn.get(table.id, b"key-1".to_vec()).await.unwrap(), Some(b"v1".to_vec()));
+    db.put(table.id, b"key-2".to_vec(), b"v2".to_vec()).await.unwrap();
+    assert_eq!(txn.get(table.id, b"key-2".to_vec()).await.unwrap(), Some(b"v1".to_vec()));
+    let former_requests = all_txn_table_requests();
+    let resp = txn.commit().await.unwrap();
+    assert_eq!(resp.stats.phase, CommitPhase::ReadOnly);
+    assert!(resp.version > 0);
+    assert_eq!(all_txn_table_requests(), former_requests);
+
+    // No request hits the txn state table for a read only txn.
+    sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+    let former_requests = all_txn_table_requests();
+    let txn = db.begin_txn();
+    assert_eq!(txn.get(table.id, b"key-2".to_vec()).await.unwrap(), Some(b"v2".to_vec()));
+    assert_eq!(txn.get(table.id, b"key-3".to_vec()).await.unwrap(), None);
+    let resp = txn.commit().await.unwrap();
+    assert_eq!(resp.stats.phase, CommitPhase::ReadOnly);
+    let txn = db.begin_txn();
+    assert_eq!(txn.get(table.id, b"key-1".to_vec()).await.unwrap(), Some(b"v1".to_vec()));
+    txn.abort().await;
+    assert_eq!(db.get(table.id, b"key-1".to_vec()).await.unwrap(), Some(b"v1".to_vec()));
+    assert_eq!(all_txn_table_requests(), former_requests);
+
+    // The txn never reads doesn't allocate a version.
+    let resp = db.begin_txn().commit().await.unwrap();
+    assert_eq!(resp.version, 0);
+    assert_eq!(resp.stats.phase, CommitPhase::ReadOnly);
+    assert_eq!(all_txn_table_requests(), former_requests);
+}
+
+#[sekas_macro::test]
+async fn mixed_txn_writes_txn_record() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    db.put(table.id, b"key".to_vec(), b"v1".to_vec()).await.unwrap();
+
+    // The txn record is created by the first write.
+    let former_begins = txn_table_requests("begin");
+    let former_commits = txn_table_requests("commit");
+    let mut txn = db.begin_txn();
+    let value = txn.get(table.id, b"key".to_vec()).await.unwrap();
+    assert_eq!(value, Some(b"v1".to_vec()));
+    txn.put(table.id, WriteBuilder::new(b"key".to_vec()).ensure_put(b"v2".to_vec()));
+    let resp = txn.commit().await.unwrap();
+    assert_eq!(resp.stats.phase, CommitPhase::Committed);
+    assert_eq!(txn_table_requests("begin"), former_begins + 1);
+    assert_eq!(txn_table_requests("commit"), former_commits + 1);
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"v2".to_vec()));
+
+    // The flushed intents are cleared once the txn is aborted.
+    let former_aborts = txn_table_requests("abort");
+    let mut txn = db.begin_txn();
+    txn.put(table.id, WriteBuilder::new(b"key".to_vec()).ensure_put(b"v3".to_vec()));
+    txn.flush().await.unwrap();
+    assert_eq!(txn.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"v3".to_vec()));
+    txn.abort().await;
+    assert_eq!(txn_table_requests("abort"), former_aborts + 1);
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"v2".to_vec()));
+}
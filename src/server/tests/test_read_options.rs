@@ -0,0 +1,243 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::{
+    CompactGroupRequest, GroupRequest, GroupRequestUnion, ReplicaDesc, ReplicaRole, ShardGetRequest,
+};
+use sekas_client::{AppError, CreateTableOptions, Database, ReadOptions, WriteBuilder};
+use sekas_rock::fn_name;
+use sekas_schema::property::{NODE_LABEL_ANALYTICS, READ_REPLICAS};
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn node_counter(name: &str, node_id: u64, label: (&str, &str)) -> u64 {
+    let families = prometheus::gather();
+    let Some(family) = families.iter().find(|f| f.get_name() == name) else {
+        return 0;
+    };
+    let node_id = node_id.to_string();
+    family
+        .get_metric()
+        .iter()
+        .find(|m| {
+            let labels = m.get_label();
+            labels.iter().any(|l| l.get_name() == "node" && l.get_value() == node_id)
+                && labels.iter().any(|l| l.get_name() == label.0 && l.get_value() == label.1)
+        })
+        .map(|m| m.get_counter().get_value() as u64)
+        .unwrap_or_default()
+}
+
+fn read_replica_gets(node_id: u64) -> u64 {
+    node_counter("node_read_replica_request_total", node_id, ("type", "get"))
+}
+
+fn read_replica_stale_rejects(node_id: u64) -> u64 {
+    node_counter("node_read_replica_rejected_total", node_id, ("reason", "stale"))
+}
+
+async fn wait_read_replica(c: &ClusterClient, group_id: u64) -> ReplicaDesc {
+    for _ in 0..1000 {
+        let state = c.get_router_group_state(group_id).await.unwrap();
+        let read_replica =
+            state.replicas.into_values().find(|r| r.role == ReplicaRole::ReadReplica as i32);
+        if let Some(read_replica) = read_replica {
+            return read_replica;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("a read replica should be added to the group {group_id}");
+}
+
+async fn commit_put(db: &Database, table_id: u64, key: &[u8], value: &[u8]) -> u64 {
+    let mut txn = db.begin_txn();
+    txn.put(table_id, WriteBuilder::new(key.to_vec()).ensure_put(value.to_vec()));
+    let version = txn.commit().await.unwrap().version;
+    // The intents are resolved by the leader asynchronously.
+    sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+    version
+}
+
+#[sekas_macro::test]
+async fn bounded_staleness_read_on_lagging_read_replica() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    ctx.disable_all_balance();
+    ctx.set_node_labels(3, &[NODE_LABEL_ANALYTICS]);
+    let nodes = ctx.bootstrap_servers(4).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let mut opts = CreateTableOptions::new("table");
+    opts.properties.insert(READ_REPLICAS.to_owned(), "1".to_owned());
+    let table = db.create_table_with(opts).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let group_state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    let node_id = wait_read_replica(&c, group_state.id).await.node_id;
+
+    // The version of the recent commit is reused, the read replica serves the read
+    // once it has applied past the version.
+    let v1 = commit_put(&db, table.id, b"key", b"v1").await;
+    let opts = ReadOptions::bounded_staleness(Duration::from_secs(60));
+    let former_gets = read_replica_gets(node_id);
+    let resp = db.get_with(table.id, b"key".to_vec(), &opts).await.unwrap();
+    assert_eq!(resp.value, Some(b"v1".to_vec()));
+    assert_eq!(resp.read_version, v1);
+    assert_eq!(read_replica_gets(node_id), former_gets + 1);
+
+    // The lagging read replica still serves the reads within the bound.
+    for idx in (0..4).filter(|idx| *idx != node_id) {
+        ctx.partition(idx, node_id);
+    }
+    let resp = db.get_with(table.id, b"key".to_vec(), &opts).await.unwrap();
+    assert_eq!(resp.value, Some(b"v1".to_vec()));
+    assert_eq!(resp.read_version, v1);
+    assert_eq!(read_replica_gets(node_id), former_gets + 2);
+
+    // The read replica hasn't applied past the newer version, the read is failed
+    // over to the leader.
+    let v2 = commit_put(&db, table.id, b"key", b"v2").await;
+    let resp = db.get_with(table.id, b"key".to_vec(), &opts).await.unwrap();
+    assert_eq!(resp.value, Some(b"v2".to_vec()));
+    assert_eq!(resp.read_version, v2);
+    assert_eq!(read_replica_gets(node_id), former_gets + 2);
+
+    // The stale version is not reused beyond the bound.
+    let opts = ReadOptions::bounded_staleness(Duration::ZERO);
+    let resp = db.get_batch_with(table.id, vec![b"key".to_vec()], &opts).await.unwrap();
+    assert_eq!(resp.value, vec![Some(b"v2".to_vec())]);
+    assert!(resp.read_version > v2);
+    assert_eq!(read_replica_gets(node_id), former_gets + 2);
+}
+
+#[sekas_macro::test]
+async fn partitioned_read_replica_rejects_stale_reads() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    ctx.disable_all_balance();
+    ctx.set_node_labels(3, &[NODE_LABEL_ANALYTICS]);
+    let nodes = ctx.bootstrap_servers(4).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let mut opts = CreateTableOptions::new("table");
+    opts.properties.insert(READ_REPLICAS.to_owned(), "1".to_owned());
+    let table = db.create_table_with(opts).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let group_state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    let read_replica = wait_read_replica(&c, group_state.id).await;
+    let node_id = read_replica.node_id;
+    let v1 = commit_put(&db, table.id, b"key", b"v1").await;
+    let bound = Duration::from_secs(2);
+    let opts = ReadOptions::bounded_staleness(bound);
+    let resp = db.get_with(table.id, b"key".to_vec(), &opts).await.unwrap();
+    assert_eq!(resp.value, Some(b"v1".to_vec()));
+
+    // The read replica is partitioned from the leader, the writes keep going.
+    for idx in (0..4).filter(|idx| *idx != node_id) {
+        ctx.partition(idx, node_id);
+    }
+    for i in 0..4 {
+        let value = format!("v{}", i + 2);
+        commit_put(&db, table.id, b"key", value.as_bytes()).await;
+    }
+    sekas_runtime::time::sleep(bound + Duration::from_secs(1)).await;
+
+    // The reads pinned to the read replica are rejected, even if it has applied
+    // past the read version.
+    let former_rejects = read_replica_stale_rejects(node_id);
+    let shard = c.get_shard_desc(table.id, b"key").await.unwrap();
+    let state = c.get_router_group_state(group_state.id).await.unwrap();
+    let req = GroupRequest {
+        group_id: group_state.id,
+        epoch: state.epoch,
+        request: Some(GroupRequestUnion {
+            request: Some(Request::Get(ShardGetRequest {
+                shard_id: shard.id,
+                start_version: v1,
+                user_key: b"key".to_vec(),
+                max_staleness_ms: bound.as_millis() as u64,
+                ..Default::default()
+            })),
+        }),
+        ..Default::default()
+    };
+    let client = node_client_with_retry(&nodes[&node_id]).await;
+    let resp = client.unary_group_request(req).await.unwrap();
+    let err = resp.error.map(sekas_client::Error::from);
+    assert!(matches!(err, Some(sekas_client::Error::ReplicaNotReady(..))), "{err:?}");
+    assert_eq!(read_replica_stale_rejects(node_id), former_rejects + 1);
+
+    // The bounded staleness reads are served by the leader instead.
+    let former_gets = read_replica_gets(node_id);
+    let resp = db.get_with(table.id, b"key".to_vec(), &opts).await.unwrap();
+    assert_eq!(resp.value, Some(b"v5".to_vec()));
+    assert_eq!(read_replica_gets(node_id), former_gets);
+    assert!(read_replica_stale_rejects(node_id) > former_rejects + 1);
+}
+
+#[sekas_macro::test]
+async fn exact_read_beneath_gc_watermark() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let v1 = commit_put(&db, table.id, b"key", b"v1").await;
+    let v2 = commit_put(&db, table.id, b"key", b"v2").await;
+    let resp = db.get_with(table.id, b"key".to_vec(), &ReadOptions::exact(v1)).await.unwrap();
+    assert_eq!(resp.value, Some(b"v1".to_vec()));
+    assert_eq!(resp.read_version, v1);
+    let resp = db.get_with(table.id, b"key".to_vec(), &ReadOptions::exact(v1 - 1)).await.unwrap();
+    assert_eq!(resp.value, None);
+    let resp = db.get_with(table.id, b"key".to_vec(), &ReadOptions::linearizable()).await.unwrap();
+    assert_eq!(resp.value, Some(b"v2".to_vec()));
+    assert!(resp.read_version > v2);
+
+    let keys = vec![b"key".to_vec(), b"other-key".to_vec()];
+    let resp = db.get_batch_with(table.id, keys, &ReadOptions::exact(v1)).await.unwrap();
+    assert_eq!(resp.value, vec![Some(b"v1".to_vec()), None]);
+    assert_eq!(resp.read_version, v1);
+
+    let r = db.get_with(table.id, b"key".to_vec(), &ReadOptions::exact(0)).await;
+    assert!(matches!(r, Err(AppError::InvalidArgument(_))), "{r:?}");
+
+    // The reads beneath the GC watermark are rejected.
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let client = node_client_with_retry(nodes.values().next().unwrap()).await;
+    let req = CompactGroupRequest { group_id, gc_watermark: Some(v2) };
+    client.compact_group(req).await.unwrap();
+    let r = db.get_with(table.id, b"key".to_vec(), &ReadOptions::exact(v1)).await;
+    let Err(AppError::VersionTooOld { version, gc_watermark }) = r else {
+        panic!("the read beneath the gc watermark should be rejected, {r:?}");
+    };
+    assert_eq!((version, gc_watermark), (v1, v2));
+    let resp = db.get_with(table.id, b"key".to_vec(), &ReadOptions::exact(v2)).await.unwrap();
+    assert_eq!(resp.value, Some(b"v2".to_vec()));
+}
@@ -0,0 +1,99 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::{ReplicaRole, ShardScanRequest};
+use sekas_client::{CreateTableOptions, ReadPreference, WriteBuilder};
+use sekas_rock::fn_name;
+use sekas_schema::property::{NODE_LABEL_ANALYTICS, READ_REPLICAS};
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn read_replica_requests(node_id: u64, request_type: &str) -> u64 {
+    let families = prometheus::gather();
+    let Some(family) = families.iter().find(|f| f.get_name() == "node_read_replica_request_total")
+    else {
+        return 0;
+    };
+    let node_id = node_id.to_string();
+    family
+        .get_metric()
+        .iter()
+        .find(|m| {
+            let labels = m.get_label();
+            labels.iter().any(|l| l.get_name() == "node" && l.get_value() == node_id)
+                && labels.iter().any(|l| l.get_name() == "type" && l.get_value() == request_type)
+        })
+        .map(|m| m.get_counter().get_value() as u64)
+        .unwrap_or_default()
+}
+
+#[sekas_macro::test]
+async fn scan_served_by_read_replica() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.set_node_labels(3, &[NODE_LABEL_ANALYTICS]);
+    let nodes = ctx.bootstrap_servers(4).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let mut opts = CreateTableOptions::new("table");
+    opts.properties.insert(READ_REPLICAS.to_owned(), "1".to_owned());
+    let table = db.create_table_with(opts).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let group_state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    let mut read_replica = None;
+    for _ in 0..1000 {
+        let state = c.get_router_group_state(group_state.id).await.unwrap();
+        read_replica =
+            state.replicas.into_values().find(|r| r.role == ReplicaRole::ReadReplica as i32);
+        if read_replica.is_some() {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    let read_replica = read_replica.expect("a read replica should be added to the table group");
+
+    let mut txn = db.begin_txn();
+    for i in 0..10u32 {
+        let key = format!("key-{i:03}").into_bytes();
+        txn.put(table.id, WriteBuilder::new(key).ensure_put(i.to_be_bytes().to_vec()));
+    }
+    let token = txn.commit().await.unwrap().version;
+    // The intents are resolved by the leader asynchronously.
+    sekas_runtime::time::sleep(Duration::from_millis(200)).await;
+
+    let shard = c.get_shard_desc(table.id, b"key").await.unwrap();
+    let former_scans = read_replica_requests(read_replica.node_id, "scan");
+    for _ in 0..5 {
+        let mut txn = db.begin_txn();
+        txn.set_read_preference(ReadPreference::PreferReadReplica);
+        txn.set_causal_token(token);
+        let resp =
+            txn.scan(ShardScanRequest { shard_id: shard.id, ..Default::default() }).await.unwrap();
+        assert_eq!(resp.data.len(), 10);
+    }
+    assert_eq!(read_replica_requests(read_replica.node_id, "scan"), former_scans + 5);
+}
@@ -0,0 +1,177 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::info;
+use sekas_api::server::v1::*;
+use sekas_rock::fn_name;
+use sekas_runtime::time::sleep;
+use sekas_server::node::observer::{ReplicaEvent, ReplicaObserver};
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// Record the events of a group.
+#[derive(Clone)]
+struct RecordObserver {
+    group_id: u64,
+    events: Arc<Mutex<Vec<ReplicaEvent>>>,
+}
+
+impl RecordObserver {
+    fn new(group_id: u64) -> Self {
+        RecordObserver { group_id, events: Arc::default() }
+    }
+
+    fn record(&self, group_id: u64, event: ReplicaEvent) {
+        if group_id == self.group_id {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    fn events(&self) -> Vec<ReplicaEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    async fn wait_for<F>(&self, pred: F)
+    where
+        F: Fn(&ReplicaEvent) -> bool,
+    {
+        for _ in 0..1000 {
+            if self.events().iter().any(&pred) {
+                return;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the expected event is not observed, recorded {:?}", self.events());
+    }
+}
+
+impl ReplicaObserver for RecordObserver {
+    fn on_replica_created(&mut self, group_id: u64, replica_id: u64) {
+        self.record(group_id, ReplicaEvent::Created { group_id, replica_id });
+    }
+
+    fn on_leader_acquired(&mut self, group_id: u64, term: u64) {
+        self.record(group_id, ReplicaEvent::LeaderAcquired { group_id, term });
+    }
+
+    fn on_leader_lost(&mut self, group_id: u64) {
+        self.record(group_id, ReplicaEvent::LeaderLost { group_id });
+    }
+
+    fn on_replica_destroyed(&mut self, group_id: u64, replica_id: u64) {
+        self.record(group_id, ReplicaEvent::Destroyed { group_id, replica_id });
+    }
+}
+
+/// Assert the events of a replica are in order: created first, then the
+/// alternating leader acquired and lost in increasing terms. Returns the
+/// number of terms led by the replica.
+fn assert_replica_events(events: &[ReplicaEvent], group_id: u64, replica_id: u64) -> usize {
+    assert_eq!(events.first(), Some(&ReplicaEvent::Created { group_id, replica_id }), "{events:?}");
+    let mut terms = vec![];
+    let mut is_leader = false;
+    for event in &events[1..] {
+        match *event {
+            ReplicaEvent::LeaderAcquired { term, .. } => {
+                assert!(!is_leader, "{events:?}");
+                assert!(terms.last().map(|t| *t < term).unwrap_or(true), "{events:?}");
+                terms.push(term);
+                is_leader = true;
+            }
+            ReplicaEvent::LeaderLost { .. } => {
+                assert!(is_leader, "{events:?}");
+                is_leader = false;
+            }
+            ReplicaEvent::Destroyed { .. } => {
+                assert_eq!(event, events.last().unwrap(), "{events:?}");
+                assert!(!is_leader, "{events:?}");
+            }
+            ReplicaEvent::Created { .. } => panic!("replica is created twice {events:?}"),
+        }
+    }
+    terms.len()
+}
+
+async fn transfer_leader_to(c: &ClusterClient, group_id: u64, replica_id: u64) {
+    for _ in 0..600 {
+        if c.get_group_leader(group_id).await == Some(replica_id) {
+            return;
+        }
+        let _ = c.group(group_id).transfer_leader(replica_id).await;
+        sleep(Duration::from_millis(100)).await;
+    }
+    panic!("transfer leader of group {group_id} to replica {replica_id} timeout");
+}
+
+#[sekas_macro::test]
+async fn replica_events_of_add_transfer_and_remove() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_node_scheduler();
+    let group_id = 100000000;
+    let observers = (0..3).map(|_| RecordObserver::new(group_id)).collect::<Vec<_>>();
+    for (idx, observer) in observers.iter().enumerate() {
+        ctx.replica_observers(idx).register(Box::new(observer.clone()));
+    }
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+
+    let group_desc = GroupDesc {
+        id: group_id,
+        replicas: vec![
+            ReplicaDesc { id: 100, node_id: 0, role: ReplicaRole::Voter as i32 },
+            ReplicaDesc { id: 101, node_id: 1, role: ReplicaRole::Voter as i32 },
+        ],
+        ..Default::default()
+    };
+    c.create_replica(0, 100, group_desc.clone()).await;
+    c.create_replica(1, 101, group_desc).await;
+    c.assert_group_leader(group_id).await;
+
+    info!("add replica 102 into group {group_id}");
+    c.create_replica(2, 102, GroupDesc { id: group_id, ..Default::default() }).await;
+    c.group(group_id).add_replica(102, 2).await.unwrap();
+    c.assert_group_contains_member(group_id, 102).await;
+
+    info!("transfer leader to replica 102 and back to replica 100");
+    transfer_leader_to(&c, group_id, 102).await;
+    observers[2].wait_for(|e| matches!(e, ReplicaEvent::LeaderAcquired { .. })).await;
+    transfer_leader_to(&c, group_id, 100).await;
+    observers[2].wait_for(|e| matches!(e, ReplicaEvent::LeaderLost { .. })).await;
+
+    info!("remove replica 102 from group {group_id}");
+    c.group(group_id).remove_group_replica(102).await.unwrap();
+    c.assert_group_not_contains_member(group_id, 102).await;
+    let desc = c.collect_group_desc(group_id, 0).await.unwrap().unwrap();
+    c.remove_replica(2, 102, desc).await;
+    observers[2].wait_for(|e| matches!(e, ReplicaEvent::Destroyed { .. })).await;
+
+    let events = observers[2].events();
+    assert_eq!(assert_replica_events(&events, group_id, 102), 1, "{events:?}");
+    assert_eq!(events.len(), 4, "{events:?}");
+    assert!(assert_replica_events(&observers[0].events(), group_id, 100) >= 1);
+    assert_replica_events(&observers[1].events(), group_id, 101);
+    assert_eq!(ctx.replica_observers(2).dropped_events(), 0);
+}
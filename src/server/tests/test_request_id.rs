@@ -0,0 +1,127 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use log::info;
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::*;
+use sekas_client::{ClientOptions, GroupClient, WriteBuilder};
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// Search the raft log of the group leader, returns the request id and summary
+/// of the entries.
+async fn search_raft_log(c: &ClusterClient, group_id: u64, prefix: &str) -> Vec<(String, String)> {
+    let stmt = format!("DEBUG SEARCH \"{prefix}\" FROM {group_id}");
+    for _ in 0..100 {
+        let json_body = c.root_client().handle_statement(&stmt).await.unwrap();
+        match serde_json::from_slice(&json_body).unwrap() {
+            ExecuteResult::Data(result) => {
+                assert_eq!(result.columns, vec!["index", "term", "request_id", "summary"]);
+                return result
+                    .rows
+                    .into_iter()
+                    .map(|row| {
+                        let request_id = row.values[2].as_str().unwrap().to_owned();
+                        let summary = row.values[3].as_str().unwrap().to_owned();
+                        (request_id, summary)
+                    })
+                    .collect();
+            }
+            // The leader of group might not be reported to root yet.
+            result => {
+                info!("search raft log of group {group_id}: {result:?}");
+                sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+    panic!("search raft log of group {group_id} timeout");
+}
+
+async fn write_intent(
+    group_client: &mut GroupClient,
+    shard_id: u64,
+    start_version: u64,
+    key: &[u8],
+    request_id: &str,
+) {
+    let write = WriteBuilder::new(key.to_vec()).ensure_put(b"value".to_vec());
+    let req = Request::WriteIntent(WriteIntentRequest {
+        shard_id,
+        start_version,
+        write: Some(write_intent_request::Write::Put(write)),
+        ..Default::default()
+    });
+    for _ in 0..100 {
+        match group_client.request_with_id(&req, request_id).await {
+            Ok(_) => return,
+            // The router of a new client might not know the group yet.
+            Err(sekas_client::Error::GroupNotAccessable(..)) => {
+                sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+            }
+            Err(err) => panic!("write intent with request {request_id}: {err:?}"),
+        }
+    }
+    panic!("write intent with request {request_id} timeout");
+}
+
+#[sekas_macro::test]
+async fn request_id_of_put_is_searchable() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let shard_id = c.get_shard_desc(table.id, b"key").await.unwrap().id;
+
+    // 1. The id generated by the client is recorded.
+    db.put(table.id, b"put-key".to_vec(), b"value".to_vec()).await.unwrap();
+    let entries = search_raft_log(&c, group_id, "put-key").await;
+    assert!(!entries.is_empty());
+    assert!(entries.iter().all(|(request_id, _)| !request_id.is_empty()), "{entries:?}");
+
+    // 2. The id specified by the client is recorded.
+    let mut group_client = c.group(group_id);
+    let start_version = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    write_intent(&mut group_client, shard_id, start_version, b"traced-key", "traced-request").await;
+    let entries = search_raft_log(&c, group_id, "traced-key").await;
+    assert_eq!(entries, vec![("traced-request".to_owned(), "write intent 1".to_owned())]);
+
+    // 3. The id is not recorded if the client disables it.
+    let opts = ClientOptions { record_request_id: Some(false), ..Default::default() };
+    let client = c.app_client_with_options(opts).await;
+    let mut group_client = GroupClient::lazy(group_id, client);
+    let start_version = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    write_intent(&mut group_client, shard_id, start_version, b"untraced-key", "untraced-request")
+        .await;
+    let entries = search_raft_log(&c, group_id, "untraced-key").await;
+    assert_eq!(entries, vec![(String::new(), "write intent 1".to_owned())]);
+}
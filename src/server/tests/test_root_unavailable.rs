@@ -0,0 +1,144 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::group_response_union::Response;
+use sekas_api::server::v1::*;
+use sekas_client::{AppError, ClientOptions, GroupClient, RetryState, RootStatus, SekasClient};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const GROUP_ID: u64 = 100000;
+const SHARD_ID: u64 = 10000000;
+const ROOT_UNAVAILABLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+async fn put(client: &SekasClient, key: &[u8], value: &[u8]) {
+    let mut group_client = GroupClient::lazy(GROUP_ID, client.clone());
+    let put = PutRequest { key: key.to_vec(), value: value.to_vec(), ..Default::default() };
+    let req = Request::Write(ShardWriteRequest {
+        shard_id: SHARD_ID,
+        puts: vec![put],
+        ..Default::default()
+    });
+    let mut retry_state = RetryState::new(Duration::from_secs(10));
+    while let Err(err) = group_client.request(&req).await {
+        retry_state.retry(err).await.unwrap();
+    }
+}
+
+async fn get(client: &SekasClient, key: &[u8]) -> Option<Vec<u8>> {
+    let mut group_client = GroupClient::lazy(GROUP_ID, client.clone());
+    let req = Request::Get(ShardGetRequest {
+        shard_id: SHARD_ID,
+        start_version: u64::MAX,
+        user_key: key.to_vec(),
+        ..Default::default()
+    });
+    let mut retry_state = RetryState::new(Duration::from_secs(10));
+    loop {
+        match group_client.request(&req).await {
+            Ok(Response::Get(resp)) => return resp.value.and_then(|v| v.content),
+            Ok(resp) => panic!("invalid response {resp:?}"),
+            Err(err) => retry_state.retry(err).await.unwrap(),
+        }
+    }
+}
+
+#[sekas_macro::test]
+async fn data_plane_survives_root_unavailable() {
+    let mut ctx = TestContext::new(fn_name!());
+    // The root group isn't promoted with two nodes, so it is only served by node 0.
+    let nodes = ctx.bootstrap_servers(2).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let opts = ClientOptions {
+        root_unavailable_timeout: Some(ROOT_UNAVAILABLE_TIMEOUT),
+        ..Default::default()
+    };
+    let app = c.app_client_with_options(opts).await;
+    app.create_database("db".into()).await.unwrap();
+    assert_eq!(app.root_status(), RootStatus::Available);
+
+    // The group on node 1 is healthy while root is unavailable.
+    let group_desc = GroupDesc {
+        id: GROUP_ID,
+        shards: vec![ShardDesc::whole(SHARD_ID, SHARD_ID)],
+        replicas: vec![ReplicaDesc {
+            id: GROUP_ID * 10 + 1,
+            node_id: 1,
+            role: ReplicaRole::Voter as i32,
+        }],
+        ..Default::default()
+    };
+    c.create_replica(1, GROUP_ID * 10 + 1, group_desc).await;
+    c.assert_group_leader(GROUP_ID).await;
+    for _ in 0..600 {
+        let state = app.router().find_group(GROUP_ID);
+        if state.map(|s| s.leader_state.is_some()).unwrap_or_default() {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    put(&app, b"key-0", b"value-0").await;
+
+    ctx.stop_server(0).await;
+
+    // The first root-dependent operation fails once the timeout elapsed, the
+    // following ones fail fast.
+    let r = app.create_database("db-1".into()).await;
+    assert!(matches!(r, Err(AppError::RootUnavailable { .. })), "{r:?}");
+    let RootStatus::Unavailable { since } = app.root_status() else {
+        panic!("root is still available");
+    };
+    let db = app.open_database("db".into()).await;
+    assert!(matches!(db, Err(AppError::RootUnavailable { .. })), "{db:?}");
+    let start = Instant::now();
+    let r = app.create_database("db-1".into()).await;
+    assert!(matches!(r, Err(AppError::RootUnavailable { since: s }) if s == since), "{r:?}");
+    assert!(start.elapsed() < ROOT_UNAVAILABLE_TIMEOUT, "{:?}", start.elapsed());
+
+    // The data operations are served by the cached routing.
+    for i in 1..10 {
+        let key = format!("key-{i}").into_bytes();
+        put(&app, &key, b"value").await;
+        assert_eq!(get(&app, &key).await, Some(b"value".to_vec()));
+    }
+    assert_eq!(get(&app, b"key-0").await, Some(b"value-0".to_vec()));
+
+    // The client is recovered once root is reachable again.
+    let root_addr = nodes.get(&0).cloned().unwrap();
+    ctx.start_servers(HashMap::from([(0, root_addr)])).await;
+    for _ in 0..600 {
+        if app.root_status() == RootStatus::Available {
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(app.root_status(), RootStatus::Available);
+    app.create_database("db-1".into()).await.unwrap();
+    app.open_database("db".into()).await.unwrap();
+    assert_eq!(get(&app, b"key-1").await, Some(b"value".to_vec()));
+}
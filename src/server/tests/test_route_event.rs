@@ -0,0 +1,92 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use log::info;
+use sekas_client::{RouteEvent, RouteEventFilter};
+use sekas_rock::fn_name;
+use sekas_runtime::time::{sleep, timeout};
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// Wait for the first event matching the predicate, the other events are
+/// skipped.
+async fn wait_event<S, F>(events: &mut S, pred: F) -> RouteEvent
+where
+    S: Stream<Item = RouteEvent> + Unpin,
+    F: Fn(&RouteEvent) -> bool,
+{
+    loop {
+        let event = timeout(Duration::from_secs(30), events.next())
+            .await
+            .expect("wait route event timeout")
+            .expect("the stream is not terminated");
+        info!("receive route event {event:?}");
+        if pred(&event) {
+            return event;
+        }
+    }
+}
+
+#[sekas_macro::test]
+async fn route_events_of_split_and_leader_transfer() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let shard_id = c.get_shard_desc(table.id, b"key").await.unwrap().id;
+    let filter = RouteEventFilter { groups: HashSet::from([group_id]), ..Default::default() };
+    let mut events = Box::pin(app.router().subscribe(filter));
+
+    let new_shard_id = shard_id + 1024;
+    c.group(group_id).split_shard(shard_id, new_shard_id, Some(b"key".to_vec())).await.unwrap();
+    let event = wait_event(&mut events, |e| matches!(e, RouteEvent::ShardSplit { .. })).await;
+    assert_eq!(
+        event,
+        RouteEvent::ShardSplit { group: group_id, parent: shard_id, children: vec![new_shard_id] }
+    );
+
+    let follower = c.must_group_any_follower(group_id).await;
+    for _ in 0..600 {
+        if c.get_group_leader_node_id(group_id).await == Some(follower.node_id) {
+            break;
+        }
+        let _ = c.group(group_id).transfer_leader(follower.id).await;
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(c.get_group_leader_node_id(group_id).await, Some(follower.node_id));
+    let event = wait_event(
+        &mut events,
+        |e| matches!(e, RouteEvent::LeaderChanged { node, .. } if *node == follower.node_id),
+    )
+    .await;
+    assert_eq!(event, RouteEvent::LeaderChanged { group: group_id, node: follower.node_id });
+}
@@ -0,0 +1,130 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_api::server::v1::ShardScanRequest;
+use sekas_client::{AppError, Range, RangeRequest};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const FRAME_BYTES: usize = 16 * 1024;
+const MAX_BYTES_PER_SCAN: usize = 64 * 1024;
+const VALUE_BYTES: usize = 4 * 1024;
+
+fn key(index: usize) -> Vec<u8> {
+    format!("key-{index:03}").into_bytes()
+}
+
+/// Read the peak bytes held by the in-flight scans of nodes.
+fn peak_scan_buffered_bytes() -> usize {
+    prometheus::gather()
+        .iter()
+        .filter(|family| family.get_name() == "node_scan_peak_buffered_bytes")
+        .flat_map(|family| family.get_metric())
+        .map(|metric| metric.get_gauge().get_value() as usize)
+        .max()
+        .unwrap_or_default()
+}
+
+#[sekas_macro::test]
+async fn scan_table_larger_than_scan_memory_limit() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.mut_scan_config().frame_bytes = FRAME_BYTES;
+    ctx.mut_scan_config().max_bytes_per_scan = MAX_BYTES_PER_SCAN;
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    // The table is 4 times larger than the memory limit of a scan.
+    let num_keys = 4 * MAX_BYTES_PER_SCAN / VALUE_BYTES;
+    for i in 0..num_keys {
+        db.put(table.id, key(i), vec![i as u8; VALUE_BYTES]).await.unwrap();
+    }
+
+    // The frames are reassembled for the paginated api.
+    let shard_id = c.get_shard_desc(table.id, &key(0)).await.unwrap().id;
+    let resp = db.scan(ShardScanRequest { shard_id, ..Default::default() }).await.unwrap();
+    assert!(!resp.has_more);
+    let keys = resp.data.iter().map(|v| v.user_key.clone()).collect::<Vec<_>>();
+    assert_eq!(keys, (0..num_keys).map(key).collect::<Vec<_>>());
+
+    // The limits of the request are applied across frames.
+    let req = ShardScanRequest { shard_id, limit: 10, reverse: true, ..Default::default() };
+    let resp = db.scan(req).await.unwrap();
+    assert!(resp.has_more);
+    let keys = resp.data.iter().map(|v| v.user_key.clone()).collect::<Vec<_>>();
+    assert_eq!(keys, (num_keys - 10..num_keys).rev().map(key).collect::<Vec<_>>());
+
+    // The range stream consumes the frames directly.
+    let req = RangeRequest {
+        table_id: table.id,
+        range: Range::Prefix(b"key-".to_vec()),
+        ..Default::default()
+    };
+    let stream = db.range(req).await.unwrap();
+    assert_eq!(stream.count().await.unwrap(), num_keys);
+
+    // A frame holds at most one value set beyond the frame bytes.
+    let peak_bytes = peak_scan_buffered_bytes();
+    assert!(peak_bytes > 0);
+    assert!(peak_bytes < FRAME_BYTES + 2 * VALUE_BYTES, "peak bytes {peak_bytes}");
+
+    // A value set exceeds the memory limit of a scan is rejected.
+    let large_key = b"large-key".to_vec();
+    db.put(table.id, large_key.clone(), vec![0; 2 * MAX_BYTES_PER_SCAN]).await.unwrap();
+    let shard_id = c.get_shard_desc(table.id, &large_key).await.unwrap().id;
+    let req = ShardScanRequest { shard_id, prefix: Some(large_key), ..Default::default() };
+    let r = db.scan(req).await;
+    assert!(
+        matches!(&r, Err(AppError::ResourceExhausted(msg)) if msg.contains("max_bytes_per_scan")),
+        "{r:?}"
+    );
+}
+
+#[sekas_macro::test]
+async fn scan_pages_resume_from_keys_extending_last_key() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let keys: Vec<Vec<u8>> =
+        vec![b"a".to_vec(), b"a\x00".to_vec(), b"aa".to_vec(), b"ab".to_vec(), b"b".to_vec()];
+    for key in &keys {
+        db.put(table.id, key.clone(), b"value".to_vec()).await.unwrap();
+    }
+
+    // Each page holds a single key, so every key is read after resuming from the
+    // previous one.
+    let req = RangeRequest { table_id: table.id, limit: 1, ..Default::default() };
+    let value_sets = db.range(req).await.unwrap().try_collect_vec(0).await.unwrap();
+    let scanned_keys = value_sets.into_iter().map(|v| v.user_key).collect::<Vec<_>>();
+    assert_eq!(scanned_keys, keys);
+}
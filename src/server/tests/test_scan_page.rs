@@ -0,0 +1,136 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use sekas_api::server::v1::ValueSet;
+use sekas_client::{Database, Range, RangeRequest, ScanToken};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+use crate::helper::runtime::spawn;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const NUM_KEYS: usize = 200;
+const PAGE_SIZE: u64 = 7;
+
+fn key(index: usize) -> Vec<u8> {
+    format!("key-{index:03}").into_bytes()
+}
+
+fn range_request(table_id: u64, reverse: bool) -> RangeRequest {
+    RangeRequest {
+        table_id,
+        range: Range::Prefix(b"key-".to_vec()),
+        limit: PAGE_SIZE,
+        reverse,
+        ..Default::default()
+    }
+}
+
+/// Scan all pages, the token is persisted and restored between pages.
+async fn scan_all_pages(db: &Database, request: RangeRequest) -> (u64, Vec<ValueSet>) {
+    let mut page = db.scan_page(request).await.unwrap();
+    let read_version = page.read_version;
+    let mut value_sets = std::mem::take(&mut page.value_sets);
+    while let Some(token) = page.next.take() {
+        let token = ScanToken::from_bytes(&token.to_bytes()).unwrap();
+        assert_eq!(token.read_version(), read_version);
+        page = db.resume_scan(&token, PAGE_SIZE, 0).await.unwrap();
+        assert!(page.value_sets.len() as u64 <= PAGE_SIZE);
+        assert_eq!(page.read_version, read_version);
+        value_sets.append(&mut page.value_sets);
+        sekas_runtime::time::sleep(Duration::from_millis(5)).await;
+    }
+    (read_version, value_sets)
+}
+
+/// Split and merge the shards of the table repeatedly, until it is stopped.
+fn reshuffle_shards(c: ClusterClient, table_id: u64, stopped: Arc<AtomicBool>) {
+    spawn(async move {
+        let mut i = 0;
+        while !stopped.load(Ordering::Relaxed) {
+            i += 1;
+            let split_key = key((i * 37) % NUM_KEYS);
+            let Some(shard) = c.get_shard_desc(table_id, &split_key).await else { continue };
+            let Some(group) = c.find_router_group_state_by_key(table_id, &split_key).await else {
+                continue;
+            };
+            let new_shard_id = sekas_schema::FIRST_USER_SHARD_ID + 1024 + i as u64;
+            let mut group_client = c.group(group.id);
+            if group_client.split_shard(shard.id, new_shard_id, Some(split_key)).await.is_ok() {
+                sekas_runtime::time::sleep(Duration::from_millis(20)).await;
+                group_client.merge_shard(shard.id, new_shard_id).await.ok();
+            }
+            sekas_runtime::time::sleep(Duration::from_millis(20)).await;
+        }
+    });
+}
+
+#[sekas_macro::test]
+async fn scan_pages_during_split_and_merge() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    for i in 0..NUM_KEYS {
+        db.put(table.id, key(i), b"value-0".to_vec()).await.unwrap();
+    }
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    reshuffle_shards(ClusterClient::new(nodes).await, table.id, stopped.clone());
+    let writer = {
+        let db = db.clone();
+        let stopped = stopped.clone();
+        spawn(async move {
+            let mut round = 1;
+            while !stopped.load(Ordering::Relaxed) {
+                for i in (0..NUM_KEYS).step_by(3) {
+                    let value = format!("value-{round}").into_bytes();
+                    db.put(table.id, key(i), value).await.unwrap();
+                }
+                round += 1;
+            }
+        })
+    };
+
+    for reverse in [false, true] {
+        let (read_version, pages) = scan_all_pages(&db, range_request(table.id, reverse)).await;
+        let snapshot = RangeRequest {
+            version: Some(read_version),
+            limit: 0,
+            ..range_request(table.id, reverse)
+        };
+        let expect = db.range(snapshot).await.unwrap().try_collect_vec(0).await.unwrap();
+        assert_eq!(pages.len(), NUM_KEYS);
+        assert_eq!(pages, expect);
+    }
+
+    stopped.store(true, Ordering::Relaxed);
+    writer.await.unwrap();
+}
@@ -0,0 +1,149 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::schedule_decision::Outcome;
+use sekas_api::server::v1::*;
+use sekas_parser::{ColumnResult, ExecuteResult};
+use sekas_rock::fn_name;
+use sekas_runtime::time::{sleep, Instant};
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const NOT_ENOUGH_NODES: &str = "create group skipped: not enough nodes, 1 of 3 required";
+const SHARD_BALANCE_DISABLED: &str = "shard balance skipped: disabled by config";
+const CURE_NOT_ENOUGH_NODES: &str = "cure skipped: not enough healthy nodes";
+
+async fn execute_data(c: &ClusterClient, stmt: &str) -> ColumnResult {
+    let json_body = c.root_client().handle_statement(stmt).await.unwrap();
+    match serde_json::from_slice(&json_body).unwrap() {
+        ExecuteResult::Data(result) => result,
+        others => panic!("execute {stmt}: {others:?}"),
+    }
+}
+
+/// The values of the column in the rows.
+fn column(result: &ColumnResult, name: &str) -> Vec<serde_json::Value> {
+    let index = result.columns.iter().position(|c| c == name).unwrap();
+    result.rows.iter().map(|row| row.values[index].clone()).collect()
+}
+
+async fn create_group(c: &ClusterClient, group_id: u64, nodes: Vec<u64>) {
+    let replicas = nodes
+        .iter()
+        .map(|&node_id| {
+            let replica_id = group_id * 10 + node_id;
+            ReplicaDesc { id: replica_id, node_id, role: ReplicaRole::Voter as i32 }
+        })
+        .collect::<Vec<_>>();
+    let group_desc = GroupDesc { id: group_id, replicas: replicas.clone(), ..Default::default() };
+    for replica in replicas {
+        c.create_replica(replica.node_id, replica.id, group_desc.clone()).await;
+    }
+    c.assert_group_leader(group_id).await;
+}
+
+fn skipped_reasons(pass: &SchedulePass) -> Vec<&str> {
+    pass.decisions
+        .iter()
+        .filter(|d| d.outcome == Outcome::Skipped as i32)
+        .map(|d| d.reason.as_str())
+        .collect()
+}
+
+#[sekas_macro::test]
+async fn schedule_now_reports_skipped_actions() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_shard_balance();
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    c.assert_root_group_has_promoted().await;
+
+    // The groups could not be created with a single node.
+    let result = execute_data(&c, "DEBUG SCHEDULE NOW").await;
+    let reasons = column(&result, "reason");
+    assert!(reasons.iter().any(|r| r == NOT_ENOUGH_NODES), "{result:?}");
+    assert!(reasons.iter().any(|r| r == SHARD_BALANCE_DISABLED), "{result:?}");
+    assert!(column(&result, "trigger").iter().all(|t| t == "manual"), "{result:?}");
+    let seq = column(&result, "pass")[0].as_u64().unwrap();
+
+    // The manual pass is retained.
+    let result = execute_data(&c, "SHOW scheduler LIMIT 1").await;
+    assert!(column(&result, "pass").iter().all(|p| p.as_u64() == Some(seq)), "{result:?}");
+    assert!(column(&result, "reason").iter().any(|r| r == NOT_ENOUGH_NODES), "{result:?}");
+
+    let root_client = c.root_client();
+    let pass = root_client.trigger_schedule_pass().await.unwrap();
+    assert!(pass.manual);
+    assert!(pass.seq > seq);
+    assert!(pass.error.is_empty(), "{pass:?}");
+    assert!(pass.groups_examined >= 1, "{pass:?}");
+    let reasons = skipped_reasons(&pass);
+    assert!(reasons.contains(&NOT_ENOUGH_NODES), "{pass:?}");
+    assert!(reasons.contains(&SHARD_BALANCE_DISABLED), "{pass:?}");
+
+    // The passes are listed in descending order of seq, including the ones
+    // triggered by the timer.
+    let passes = root_client.list_schedule_passes().await.unwrap();
+    assert_eq!(passes.first().map(|p| p.seq), Some(pass.seq));
+    assert!(passes.windows(2).all(|w| w[0].seq > w[1].seq), "{passes:?}");
+    assert!(passes.iter().any(|p| p.seq == seq && p.manual), "{passes:?}");
+}
+
+#[sekas_macro::test]
+async fn schedule_pass_reports_skipped_cure() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    c.assert_root_group_has_promoted().await;
+
+    let group_id = 10;
+    let mut node_ids = nodes.keys().cloned().collect::<Vec<_>>();
+    node_ids.sort_unstable();
+    create_group(&c, group_id, node_ids.clone()).await;
+
+    // There is no node left to replace the replica of the offline node.
+    let offline_node_id = *node_ids.last().unwrap();
+    ctx.stop_server(offline_node_id).await;
+    ctx.wait_election_timeout().await;
+    c.assert_group_leader(group_id).await;
+
+    let action = format!("cure group {group_id} by adding 1 replicas");
+    let deadline = Instant::now() + Duration::from_secs(60);
+    loop {
+        assert!(Instant::now() < deadline, "the skipped cure of group {group_id} isn't reported");
+        if let Ok(pass) = c.root_client().trigger_schedule_pass().await {
+            let skipped = pass.decisions.iter().any(|d| {
+                d.action == action
+                    && d.outcome == Outcome::Skipped as i32
+                    && d.reason.starts_with(CURE_NOT_ENOUGH_NODES)
+            });
+            if skipped {
+                break;
+            }
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use sekas_client::{AppError, ClientOptions, Database, TableDesc};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn ttl_property(ttl: &str) -> HashMap<String, String> {
+    HashMap::from([("ttl".to_owned(), ttl.to_owned())])
+}
+
+/// Open the table until the desc with the `ttl` property is observed.
+async fn wait_table_ttl(db: &Database, ttl: &str, timeout: Duration) -> TableDesc {
+    let deadline = sekas_runtime::time::Instant::now() + timeout;
+    loop {
+        let desc = db.open_table("table".into()).await.unwrap();
+        if desc.properties.get("ttl").map(String::as_str) == Some(ttl) {
+            return desc;
+        }
+        if sekas_runtime::time::Instant::now() > deadline {
+            panic!("the altered table desc is not observed in {timeout:?}, desc {desc:?}");
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[sekas_macro::test]
+async fn alter_table_observed_by_watch_event() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    assert_eq!(table.version, 0);
+
+    // The cached desc never expires, it is refreshed by the table changed event.
+    let opts =
+        ClientOptions { schema_cache_ttl: Some(Duration::from_secs(3600)), ..Default::default() };
+    let other = c.app_client_with_options(opts).await;
+    let other_db = other.open_database("db".into()).await.unwrap();
+    let desc = other_db.open_table("table".into()).await.unwrap();
+    assert_eq!(desc.version, 0);
+
+    let altered = db.alter_table("table".into(), ttl_property("3600")).await.unwrap();
+    assert_eq!(altered.id, table.id);
+    assert_eq!(altered.version, 1);
+    let desc = wait_table_ttl(&other_db, "3600", Duration::from_secs(3)).await;
+    assert_eq!(desc.version, 1);
+
+    // The desc is read from root once it is invalidated.
+    other_db.invalidate_table("table");
+    let desc = other_db.open_table("table".into()).await.unwrap();
+    assert_eq!(desc, altered);
+
+    let r = db.alter_table("not-exists".into(), ttl_property("60")).await;
+    assert!(matches!(r, Err(AppError::NotFound(_))), "{r:?}");
+}
+
+#[sekas_macro::test]
+async fn alter_table_observed_after_ttl() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    db.create_table("table".into()).await.unwrap();
+
+    let ttl = Duration::from_millis(500);
+    let opts = ClientOptions { schema_cache_ttl: Some(ttl), ..Default::default() };
+    let other = c.app_client_with_options(opts).await;
+    let other_db = other.open_database("db".into()).await.unwrap();
+    other_db.open_table("table".into()).await.unwrap();
+
+    db.alter_table("table".into(), ttl_property("60")).await.unwrap();
+    let desc = wait_table_ttl(&other_db, "60", ttl * 2).await;
+    assert_eq!(desc.version, 1);
+}
+
+#[sekas_macro::test]
+async fn concurrent_alter_table_keeps_all_properties() {
+    const NUM_ALTERS: usize = 8;
+
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    db.create_table("table".into()).await.unwrap();
+
+    // Each alter sets a different property, none of them is overwritten by the
+    // others.
+    let handles = (0..NUM_ALTERS)
+        .map(|i| {
+            let db = db.clone();
+            sekas_runtime::spawn(async move {
+                let properties = HashMap::from([(format!("prop-{i}"), i.to_string())]);
+                db.alter_table("table".into(), properties).await.unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    db.invalidate_table("table");
+    let desc = db.open_table("table".into()).await.unwrap();
+    assert_eq!(desc.version, NUM_ALTERS as u64);
+    for i in 0..NUM_ALTERS {
+        let value = desc.properties.get(&format!("prop-{i}"));
+        assert_eq!(value, Some(&i.to_string()), "{desc:?}");
+    }
+}
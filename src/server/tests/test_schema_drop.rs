@@ -0,0 +1,119 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_client::{AppError, Database, WriteBuilder};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The deletion is observed by the watch stream of root in this duration.
+const WATCH_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Get the key until it fails, the error is returned.
+async fn wait_get_failed(db: &Database, table_id: u64) -> AppError {
+    let deadline = sekas_runtime::time::Instant::now() + WATCH_INTERVAL;
+    loop {
+        if let Err(err) = db.get(table_id, b"key".to_vec()).await {
+            return err;
+        }
+        if sekas_runtime::time::Instant::now() > deadline {
+            panic!("the deletion of table {table_id} is not observed in {WATCH_INTERVAL:?}");
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[sekas_macro::test]
+async fn dropped_database_handles_fail_fast() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    db.on_dropped(move || sender.send(()).unwrap());
+
+    // The database is dropped by another client.
+    let other = c.app_client().await;
+    other.delete_database("db".into()).await.unwrap();
+    let err = wait_get_failed(&db, table.id).await;
+    assert!(
+        matches!(err, AppError::DatabaseDropped { db_id, .. } if db_id == db.desc().id),
+        "{err:?}"
+    );
+    tokio::time::timeout(WATCH_INTERVAL, receiver).await.unwrap().unwrap();
+    let r = db.put(table.id, b"key".to_vec(), b"value".to_vec()).await;
+    assert!(matches!(r, Err(AppError::DatabaseDropped { .. })), "{r:?}");
+    let r = db.create_table("other".into()).await;
+    assert!(matches!(r, Err(AppError::DatabaseDropped { .. })), "{r:?}");
+
+    // The database recreated under the same name doesn't resurrect the old handle.
+    let new_db = other.create_database("db".into()).await.unwrap();
+    assert_ne!(new_db.desc().id, db.desc().id);
+    let r = db.get(table.id, b"key".to_vec()).await;
+    assert!(matches!(r, Err(AppError::DatabaseDropped { .. })), "{r:?}");
+    let reopened = app.open_database("db".into()).await.unwrap();
+    let new_table = reopened.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(new_table.id).await;
+    reopened.put(new_table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    // The handler registered after the database is dropped is called immediately.
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    db.on_dropped(move || sender.send(()).unwrap());
+    tokio::time::timeout(WATCH_INTERVAL, receiver).await.unwrap().unwrap();
+}
+
+#[sekas_macro::test]
+async fn dropped_table_handles_fail_fast() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let other = c.app_client().await;
+    let other_db = other.open_database("db".into()).await.unwrap();
+    other_db.delete_table("table".into()).await.unwrap();
+    let err = wait_get_failed(&db, table.id).await;
+    assert!(matches!(err, AppError::TableDropped { table_id } if table_id == table.id), "{err:?}");
+
+    // The table recreated under the same name has a new id, the old id keeps
+    // failing.
+    let new_table = other_db.create_table("table".into()).await.unwrap();
+    assert_ne!(new_table.id, table.id);
+    c.assert_table_ready(new_table.id).await;
+    let mut txn = db.begin_txn();
+    txn.put(table.id, WriteBuilder::new(b"key".to_vec()).ensure_put(b"v".to_vec()));
+    let r = txn.commit().await;
+    assert!(matches!(r, Err(AppError::TableDropped { .. })), "{r:?}");
+    db.put(new_table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert_eq!(db.get(new_table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+}
@@ -0,0 +1,119 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::{Duration, Instant};
+
+use sekas_client::{ClientOptions, SekasClient, ShardLeaseOptions};
+use sekas_rock::fn_name;
+use sekas_runtime::time::{sleep, timeout};
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// The epoch of the group serving the key, observed by the router of the app.
+fn routed_epoch(app: &SekasClient, table_id: u64, key: &[u8]) -> u64 {
+    app.router().find_shard(table_id, key).unwrap().0.epoch
+}
+
+async fn wait_routed_epoch_changed(app: &SekasClient, table_id: u64, key: &[u8], old: u64) {
+    for _ in 0..300 {
+        if routed_epoch(app, table_id, key) != old {
+            return;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the routing of key {key:?} is not changed");
+}
+
+#[sekas_macro::test]
+async fn shard_lease_notified_before_split_is_routed() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let window = Duration::from_secs(30);
+    let opts = ClientOptions {
+        shard_lease: Some(ShardLeaseOptions { window, ttl: Duration::from_secs(60) }),
+        ..Default::default()
+    };
+    let app = c.app_client_with_options(opts).await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let (group_state, shard) = app.router().find_shard(table.id, b"key").unwrap();
+    let old_epoch = group_state.epoch;
+    let mut lease = app.router().lease_shard(shard.id);
+    c.group(group_state.id)
+        .split_shard(shard.id, shard.id + 1024, Some(b"key".to_vec()))
+        .await
+        .unwrap();
+
+    let notice = timeout(Duration::from_secs(30), lease.notified())
+        .await
+        .expect("wait shard lease notice timeout")
+        .expect("the router is not dropped");
+    assert_eq!(notice.shard_id, shard.id);
+    assert_eq!(notice.group_id, group_state.id);
+    assert!(notice.epoch > old_epoch);
+
+    // The new routing is not served until the notice is acked.
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(routed_epoch(&app, table.id, b"key"), old_epoch);
+    assert_eq!(routed_epoch(&app, table.id, b"a"), old_epoch);
+    let new_epoch = notice.epoch;
+    notice.ack();
+
+    wait_routed_epoch_changed(&app, table.id, b"key", old_epoch).await;
+    assert_eq!(routed_epoch(&app, table.id, b"key"), new_epoch);
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+    db.put(table.id, b"a".to_vec(), b"value".to_vec()).await.unwrap();
+}
+
+#[sekas_macro::test]
+async fn shard_lease_unresponsive_holder_not_stall_routing() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let window = Duration::from_millis(200);
+    let opts = ClientOptions {
+        shard_lease: Some(ShardLeaseOptions { window, ttl: Duration::from_secs(60) }),
+        ..Default::default()
+    };
+    let app = c.app_client_with_options(opts).await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    // The holder never reads the notices.
+    let (group_state, shard) = app.router().find_shard(table.id, b"key").unwrap();
+    let _lease = app.router().lease_shard(shard.id);
+    let start_at = Instant::now();
+    c.group(group_state.id)
+        .split_shard(shard.id, shard.id + 1024, Some(b"key".to_vec()))
+        .await
+        .unwrap();
+    wait_routed_epoch_changed(&app, table.id, b"key", group_state.epoch).await;
+    assert!(start_at.elapsed() >= window);
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+}
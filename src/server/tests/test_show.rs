@@ -0,0 +1,68 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const ROOT_GROUP_ID: u64 = 0;
+
+/// Execute the statement at root, returns the rows of the result.
+async fn show(c: &ClusterClient, stmt: &str) -> Vec<Vec<serde_json::Value>> {
+    let json_body = c.root_client().handle_statement(stmt).await.unwrap();
+    match serde_json::from_slice(&json_body).unwrap() {
+        ExecuteResult::Data(result) => result.rows.into_iter().map(|row| row.values).collect(),
+        result => panic!("execute {stmt}: {result:?}"),
+    }
+}
+
+fn contains_table(rows: &[Vec<serde_json::Value>], name: &str) -> bool {
+    rows.iter().any(|row| row[1].as_str() == Some(name))
+}
+
+#[sekas_macro::test]
+async fn show_tables_across_root_leader_transfers() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    c.assert_root_group_has_promoted().await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+
+    for i in 0..16 {
+        let name = format!("table-{i}");
+        db.create_table(name.clone()).await.unwrap();
+        // The show is served by the new root leader, which might not apply the table
+        // desc yet.
+        c.transfer_group_leader_randomly(ROOT_GROUP_ID).await.ok();
+        let tables = show(&c, "SHOW tables FROM db").await;
+        assert!(contains_table(&tables, &name), "table {name} is missed: {tables:?}");
+    }
+
+    // The stale show is served by the local states of root.
+    let tables = show(&c, "SHOW tables FROM db STALE").await;
+    assert!(!tables.is_empty());
+    let databases = show(&c, "SHOW databases STALE").await;
+    assert!(databases.iter().any(|row| row[1].as_str() == Some("db")), "{databases:?}");
+}
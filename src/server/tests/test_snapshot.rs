@@ -74,6 +74,18 @@ async fn insert(c: &ClusterClient, group_id: u64, shard_id: u64, range: std::ops
 async fn snapshot_send() {
     let mut ctx = TestContext::new(fn_name!());
     ctx.mut_raft_testing_knobs().force_new_peer_receiving_snapshot = true;
+    send_snapshot_to_new_replica(ctx).await;
+}
+
+#[sekas_macro::test]
+async fn snapshot_send_with_encryption() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.mut_raft_testing_knobs().force_new_peer_receiving_snapshot = true;
+    ctx.enable_encryption();
+    send_snapshot_to_new_replica(ctx).await;
+}
+
+async fn send_snapshot_to_new_replica(mut ctx: TestContext) {
     let nodes = ctx.bootstrap_servers(4).await;
     let c = ClusterClient::new(nodes.clone()).await;
 
@@ -105,3 +117,94 @@ async fn snapshot_send() {
     ctx.wait_election_timeout().await;
     insert(&c, group_id, shard_id, 100..110).await;
 }
+
+/// The outgoing snapshot transfers of a node, in the order of finishing.
+async fn snapshot_sends(addr: &str) -> Vec<serde_json::Value> {
+    let resp = reqwest::get(format!("http://{addr}/admin/snapshot_sends")).await.unwrap();
+    let sends: serde_json::Value = resp.json().await.unwrap();
+    sends["records"].as_array().cloned().unwrap_or_default()
+}
+
+#[sekas_macro::test]
+async fn snapshot_send_prefers_groups_lacking_quorum() {
+    let mut ctx = TestContext::new_simulation(fn_name!());
+    ctx.mut_raft_testing_knobs().force_new_peer_receiving_snapshot = true;
+    ctx.set_snapshot_send_concurrency(1);
+    // Slow down the transfers, so that the snapshots are queued.
+    ctx.set_shard_move_bytes_per_sec(4 * 1024);
+    let nodes = ctx.bootstrap_servers(5).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+
+    // The groups 9 and 10 lose quorum once the node 3 joins, since their
+    // replicas on node 4 are down.
+    let healthy_groups = (1..=8).collect::<Vec<u64>>();
+    let lacking_groups = vec![9, 10];
+    for group_id in 1..=10 {
+        let shard_id = group_id * 100;
+        let shard_desc = ShardDesc {
+            id: shard_id,
+            table_id: shard_id,
+            range: Some(RangePartition { start: vec![], end: vec![] }),
+        };
+        let third_node_id = if group_id > 8 { 4 } else { 2 };
+        create_group(&c, group_id, vec![0, 1, third_node_id], vec![shard_desc]).await;
+        insert(&c, group_id, shard_id, 1..200).await;
+
+        // All snapshots are sent by node 0.
+        let mut group_client = c.group(group_id);
+        while c.get_group_leader_node_id(group_id).await != Some(0) {
+            group_client.transfer_leader(group_id * 10).await.unwrap_or_default();
+            ctx.wait_election_timeout().await;
+        }
+    }
+    ctx.stop_server(4).await;
+    ctx.wait_election_timeout().await;
+
+    // Node 3 rejoins with an empty disk, all of its replicas require snapshots.
+    for group_id in 1..=10 {
+        let replica_id = group_id * 10 + 3;
+        let empty_desc = GroupDesc { id: group_id, ..Default::default() };
+        c.create_replica(3, replica_id, empty_desc).await;
+        c.group(group_id).add_replica(replica_id, 3).await.unwrap();
+    }
+
+    let leader_addr = nodes.get(&0).unwrap();
+    let is_leader_replica = |record: &serde_json::Value| {
+        let replica_id = record["replica_id"].as_u64().unwrap();
+        replica_id % 10 == 0 && (1..=10).contains(&(replica_id / 10))
+    };
+    let records = loop {
+        let records = snapshot_sends(leader_addr)
+            .await
+            .into_iter()
+            .filter(is_leader_replica)
+            .collect::<Vec<_>>();
+        if records.len() >= 10 {
+            break records;
+        }
+        ctx.wait_election_timeout().await;
+    };
+
+    // The transfers are serialized.
+    for window in records.windows(2) {
+        let finished_at = window[0]["finished_at_ms"].as_u64().unwrap();
+        let started_at = window[1]["started_at_ms"].as_u64().unwrap();
+        assert!(finished_at <= started_at, "{window:?}");
+    }
+
+    // The groups lacking quorum jump the queue of the healthy groups.
+    let position = |group_id: u64| {
+        records.iter().position(|r| r["replica_id"].as_u64() == Some(group_id * 10)).unwrap()
+    };
+    let last_healthy = healthy_groups.iter().map(|id| position(*id)).max().unwrap();
+    for group_id in &lacking_groups {
+        let record = &records[position(*group_id)];
+        assert!(position(*group_id) < last_healthy, "{records:?}");
+        assert_eq!(record["quorum_lacking"].as_bool(), Some(true), "{record:?}");
+    }
+
+    // The groups lacking quorum are available again.
+    for group_id in lacking_groups {
+        insert(&c, group_id, group_id * 100, 200..210).await;
+    }
+}
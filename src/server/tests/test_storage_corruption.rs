@@ -0,0 +1,65 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_api::server::v1::*;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+#[sekas_macro::test]
+async fn corrupted_replica_is_rebuilt_on_another_node() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let faults = ctx.mut_engine_testing_knobs().storage_faults.clone();
+    let nodes = ctx.bootstrap_servers(4).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+
+    let state = c.find_router_group_state_by_key(table.id, b"key").await.unwrap();
+    let group_id = state.id;
+    let leader_id = c.assert_group_leader(group_id).await;
+    let leader_node_id = state.replicas[&leader_id].node_id;
+
+    // Reading the corrupted key quarantines the leader, the request is redirected
+    // to the other replicas instead of crashing the node.
+    faults.inject_corruption(leader_id, b"key", b"kez");
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+    db.put(table.id, b"key".to_vec(), b"value2".to_vec()).await.unwrap();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value2".to_vec()));
+
+    // The node still serves the other groups.
+    let client = node_client_with_retry(&nodes[&leader_node_id]).await;
+    client.get_root().await.unwrap();
+    app.create_database("db2".into()).await.unwrap();
+
+    // The root rebuilds the corrupted replica on the spare node.
+    c.assert_group_not_contains_member(group_id, leader_id).await;
+    c.assert_num_group_voters(group_id, 3).await;
+    let events = c.root_client().list_topology_events().await.unwrap();
+    assert!(events.iter().any(|e| e.kind == topology_event::Kind::Quarantine as i32), "{events:?}");
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value2".to_vec()));
+}
@@ -0,0 +1,174 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::{FusedStream, Stream};
+use futures::{StreamExt, TryStreamExt};
+use sekas_client::{Database, MoveShardClient, RangeRequest};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+use crate::helper::runtime::spawn;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn key(index: usize) -> Vec<u8> {
+    format!("key-{index:03}").into_bytes()
+}
+
+async fn put_keys(db: &Database, table_id: u64, num_keys: usize) {
+    for i in 0..num_keys {
+        db.put(table_id, key(i), b"value".to_vec()).await.unwrap();
+    }
+}
+
+fn range_request(table_id: u64, limit: u64) -> RangeRequest {
+    RangeRequest { table_id, limit, ..Default::default() }
+}
+
+/// Read the alive background tasks of client streams.
+fn alive_stream_tasks(kind: &str) -> i64 {
+    prometheus::gather()
+        .iter()
+        .filter(|family| family.get_name() == "client_stream_tasks")
+        .flat_map(|family| family.get_metric())
+        .filter(|metric| {
+            metric.get_label().iter().any(|l| l.get_name() == "type" && l.get_value() == kind)
+        })
+        .map(|metric| metric.get_gauge().get_value() as i64)
+        .sum()
+}
+
+#[sekas_macro::test]
+async fn range_stream_collectors() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    put_keys(&db, table.id, 100).await;
+
+    let stream = db.range(range_request(table.id, 7)).await.unwrap();
+    let value_sets = stream.try_collect_vec(25).await.unwrap();
+    let keys = value_sets.into_iter().map(|value_set| value_set.user_key).collect::<Vec<_>>();
+    assert_eq!(keys, (0..25).map(key).collect::<Vec<_>>());
+
+    let stream = db.range(range_request(table.id, 7)).await.unwrap();
+    assert_eq!(stream.count().await.unwrap(), 100);
+
+    let stream = db.range(range_request(table.id, 7)).await.unwrap();
+    let keys = stream.into_key_stream().try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(keys, (0..100).map(key).collect::<Vec<_>>());
+
+    // The chunks of shard are pulled until the end of shard.
+    let group_id = c.find_router_group_state_by_key(table.id, &key(0)).await.unwrap().id;
+    let shard_id = c.get_shard_desc(table.id, &key(0)).await.unwrap().id;
+    let client = MoveShardClient::new(group_id, app.clone());
+    let chunks = client.pull_shard(shard_id, None).try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), 100);
+}
+
+#[sekas_macro::test]
+async fn range_stream_fused_after_error() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    // No shard serves the unknown table.
+    let mut stream = db.range(range_request(table.id + 1024, 0)).await.unwrap();
+    assert!(matches!(stream.next().await, Some(Err(_))));
+    assert!(stream.is_terminated());
+    assert_eq!(stream.size_hint(), (0, Some(0)));
+    assert!(stream.next().await.is_none());
+    assert!(stream.next().await.is_none());
+}
+
+#[sekas_macro::test]
+async fn drop_streams_mid_flight() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    put_keys(&db, table.id, 200).await;
+
+    // Keep writing during the streams are dropped.
+    let stopped = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let db = db.clone();
+        let stopped = stopped.clone();
+        spawn(async move {
+            let mut i = 0;
+            while !stopped.load(Ordering::Relaxed) {
+                db.put(table.id, key(i % 200), format!("value-{i}").into_bytes()).await.unwrap();
+                i += 1;
+            }
+        })
+    };
+
+    let mut handles = vec![];
+    for i in 0..32 {
+        let db = db.clone();
+        handles.push(spawn(async move {
+            // Drop the range stream after the first page is received, the rest
+            // pages are still being fetched.
+            let mut stream = db.range(range_request(table.id, 1)).await.unwrap();
+            assert!(matches!(stream.next().await, Some(Ok(_))));
+            drop(stream);
+
+            // Drop the watch stream while it is watching.
+            let mut stream = db.watch(table.id, &key(i)).await.unwrap();
+            if i % 2 == 0 {
+                assert!(matches!(stream.next().await, Some(Ok(_))));
+            }
+            drop(stream);
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    stopped.store(true, Ordering::Relaxed);
+    writer.await.unwrap();
+
+    // The background tasks are aborted.
+    for _ in 0..100 {
+        if alive_stream_tasks("range") == 0 && alive_stream_tasks("watch") == 0 {
+            return;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!(
+        "the background tasks of streams are leaked, range {}, watch {}",
+        alive_stream_tasks("range"),
+        alive_stream_tasks("watch")
+    );
+}
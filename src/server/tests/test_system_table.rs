@@ -0,0 +1,109 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::*;
+use sekas_client::{AppError, DeletePrefixOptions, Error};
+use sekas_rock::fn_name;
+use sekas_schema::system::table::{TABLE_ID, TABLE_SHARD_ID};
+use sekas_schema::ROOT_GROUP_ID;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+#[sekas_macro::test]
+async fn reject_writes_to_system_tables() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.set_cluster_id(0, "cluster");
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+
+    // The client rejects the writes before sending them.
+    let result = db.put(TABLE_ID, b"key".to_vec(), b"value".to_vec()).await;
+    assert!(matches!(result, Err(AppError::PermissionDenied(_))), "{result:?}");
+    let result = db.delete(TABLE_ID, b"key".to_vec()).await;
+    assert!(matches!(result, Err(AppError::PermissionDenied(_))), "{result:?}");
+    let result = db.delete_prefix(TABLE_ID, b"key".to_vec(), DeletePrefixOptions::default()).await;
+    assert!(matches!(result, Err(AppError::PermissionDenied(_))), "{result:?}");
+
+    // The servers reject the writes not issued by the servers.
+    let put = PutRequest { key: b"key".to_vec(), value: b"value".to_vec(), ..Default::default() };
+    let req = Request::Write(ShardWriteRequest {
+        shard_id: TABLE_SHARD_ID,
+        puts: vec![put],
+        ..Default::default()
+    });
+    let result = c.group(ROOT_GROUP_ID).request(&req).await;
+    assert!(matches!(result, Err(Error::PermissionDenied(_))), "{result:?}");
+    let commit = Request::CommitIntent(CommitIntentRequest {
+        shard_id: TABLE_SHARD_ID,
+        user_key: b"key".to_vec(),
+        ..Default::default()
+    });
+    let result = c.group(ROOT_GROUP_ID).request(&commit).await;
+    assert!(matches!(result, Err(Error::PermissionDenied(_))), "{result:?}");
+    let clear = Request::ClearIntent(ClearIntentRequest {
+        shard_id: TABLE_SHARD_ID,
+        user_key: b"key".to_vec(),
+        ..Default::default()
+    });
+    let result = c.group(ROOT_GROUP_ID).request(&clear).await;
+    assert!(matches!(result, Err(Error::PermissionDenied(_))), "{result:?}");
+
+    // The cluster id known by the clients isn't the origin of the servers.
+    c.assert_group_leader(ROOT_GROUP_ID).await;
+    let leader = c.get_group_leader_node_id(ROOT_GROUP_ID).await.unwrap();
+    let epoch = c.must_group_epoch(ROOT_GROUP_ID).await;
+    let client = node_client_with_retry(&nodes[&leader]).await;
+    let issue = |origin: &str, request: Request| {
+        let client = client.clone().with_internal_origin(Some(origin.parse().unwrap()));
+        let req = GroupRequest {
+            group_id: ROOT_GROUP_ID,
+            epoch,
+            request: Some(GroupRequestUnion { request: Some(request) }),
+            ..Default::default()
+        };
+        async move {
+            match client.unary_group_request(req).await {
+                Ok(resp) => resp.error.map_or(Ok(()), |err| Err(Error::from(err))),
+                Err(status) => Err(Error::from(status)),
+            }
+        }
+    };
+    let result = issue("cluster", req.clone()).await;
+    assert!(matches!(result, Err(Error::PermissionDenied(_))), "{result:?}");
+    let result = issue("cluster", clear.clone()).await;
+    assert!(matches!(result, Err(Error::PermissionDenied(_))), "{result:?}");
+    let result = issue(ctx.cluster_secret(), clear).await;
+    assert!(!matches!(result, Err(Error::PermissionDenied(_))), "{result:?}");
+
+    // The catalog maintained by root and the user tables are not affected.
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+    let tables = db.list_table().await.unwrap();
+    assert!(tables.iter().any(|t| t.id == table.id), "{tables:?}");
+}
@@ -0,0 +1,101 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_parser::ExecuteResult;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+/// Execute the statement at root, returns the columns and the rows of the
+/// result.
+async fn show(c: &ClusterClient, stmt: &str) -> (Vec<String>, Vec<Vec<serde_json::Value>>) {
+    let json_body = c.root_client().handle_statement(stmt).await.unwrap();
+    match serde_json::from_slice(&json_body).unwrap() {
+        ExecuteResult::Data(result) => {
+            (result.columns, result.rows.into_iter().map(|row| row.values).collect())
+        }
+        result => panic!("execute {stmt}: {result:?}"),
+    }
+}
+
+/// The write qps of the tables, in the `SHOW tables` result.
+async fn show_write_qps(c: &ClusterClient, hot: &str, cold: &str) -> (f64, f64) {
+    let (columns, rows) = show(c, "SHOW tables FROM db").await;
+    let name = columns.iter().position(|c| c == "name").unwrap();
+    let write_qps = columns.iter().position(|c| c == "write_qps").unwrap();
+    let find = |table: &str| {
+        let row = rows.iter().find(|row| row[name].as_str() == Some(table)).unwrap();
+        row[write_qps].as_f64().unwrap()
+    };
+    (find(hot), find(cold))
+}
+
+fn is_ratio_within(hot: f64, cold: f64, ratio: f64) -> bool {
+    cold > 0.0 && (hot / cold - ratio).abs() <= ratio * 0.4
+}
+
+#[sekas_macro::test]
+async fn table_stats_reflect_write_rates() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let hot = db.create_table("hot".into()).await.unwrap();
+    let cold = db.create_table("cold".into()).await.unwrap();
+    c.assert_table_ready(hot.id).await;
+    c.assert_table_ready(cold.id).await;
+
+    const RATIO: f64 = 4.0;
+    let root_client = c.root_client();
+    let mut reported = false;
+    for i in 0..6000u64 {
+        for j in 0..RATIO as u64 {
+            let key = format!("key-{}", i * RATIO as u64 + j).into_bytes();
+            db.put(hot.id, key, b"value".to_vec()).await.unwrap();
+        }
+        db.put(cold.id, format!("key-{i}").into_bytes(), b"value".to_vec()).await.unwrap();
+        if i % 50 != 0 {
+            continue;
+        }
+
+        let stats = root_client.table_stats(db.desc()).await.unwrap();
+        let find = |id: u64| stats.iter().find(|s| s.table_id == id).cloned().unwrap();
+        let (hot_stats, cold_stats) = (find(hot.id), find(cold.id));
+        assert_eq!(hot_stats.name, "hot");
+        if !is_ratio_within(hot_stats.write_qps as f64, cold_stats.write_qps as f64, RATIO) {
+            continue;
+        }
+        let (hot_qps, cold_qps) = show_write_qps(&c, "hot", "cold").await;
+        if is_ratio_within(hot_qps, cold_qps, RATIO) {
+            assert_eq!(hot_stats.read_qps, 0.0);
+            reported = true;
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(reported, "the write rates of the tables are not reported");
+}
@@ -0,0 +1,138 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use futures::StreamExt;
+use sekas_api::server::v1::CompactGroupRequest;
+use sekas_client::{CreateTableOptions, WriteBuilder};
+use sekas_rock::fn_name;
+use sekas_runtime::time::sleep;
+use sekas_schema::property::TTL;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+#[sekas_macro::test]
+async fn expired_values_are_absent() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes.clone()).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let mut receiver = db.watch(table.id, b"key").await.unwrap();
+    let mut txn = db.begin_txn();
+    let put = WriteBuilder::new(b"key".to_vec()).with_ttl(Duration::from_secs(1));
+    txn.put(table.id, put.ensure_put(b"value".to_vec()));
+    txn.commit().await.unwrap();
+    let value = receiver.next().await.unwrap().unwrap();
+    assert_eq!(value.content, Some(b"value".to_vec()));
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+
+    // The expiry is derived from the commit version and compared with the
+    // version of the read.
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), None);
+
+    // The expired value is absent for the atomic operations.
+    let mut txn = db.begin_txn();
+    txn.put(
+        table.id,
+        WriteBuilder::new(b"counter".to_vec()).with_ttl(Duration::from_secs(1)).ensure_add(5),
+    );
+    txn.commit().await.unwrap();
+    sleep(Duration::from_millis(1500)).await;
+    let mut txn = db.begin_txn();
+    txn.put(table.id, WriteBuilder::new(b"counter".to_vec()).ensure_add(1));
+    txn.commit().await.unwrap();
+    let value = db.get(table.id, b"counter".to_vec()).await.unwrap().unwrap();
+    assert_eq!(i64::from_be_bytes(value.try_into().unwrap()), 1);
+
+    // The expiry is cleared by the later write without TTL, and the watchers
+    // never observe the expiry.
+    db.put(table.id, b"key".to_vec(), b"forever".to_vec()).await.unwrap();
+    let value = receiver.next().await.unwrap().unwrap();
+    assert_eq!(value.content, Some(b"forever".to_vec()));
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"forever".to_vec()));
+
+    // The values expired before the gc watermark are removed by compaction.
+    let group_id = c.find_router_group_state_by_key(table.id, b"counter").await.unwrap().id;
+    let client = node_client_with_retry(nodes.values().next().unwrap()).await;
+    let mut txn = db.begin_txn();
+    txn.put(
+        table.id,
+        WriteBuilder::new(b"counter".to_vec()).with_ttl(Duration::from_secs(1)).ensure_add(1),
+    );
+    txn.commit().await.unwrap();
+    // The intents are resolved by the leader asynchronously.
+    sleep(Duration::from_millis(1500)).await;
+    let gc_watermark = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    let req = CompactGroupRequest { group_id, gc_watermark: Some(gc_watermark) };
+    client.compact_group(req).await.unwrap();
+    let state = db.get_raw(table.id, b"counter".to_vec()).await.unwrap();
+    assert_eq!(state.versions_retained, 0, "{state:?}");
+    assert_eq!(db.get(table.id, b"counter".to_vec()).await.unwrap(), None);
+}
+
+#[sekas_macro::test]
+async fn table_ttl_applies_to_new_writes() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let mut opts = CreateTableOptions::new("table");
+    opts.properties.insert(TTL.to_owned(), "1".to_owned());
+    let table = db.create_table_with(opts).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let mut expired = false;
+    for _ in 0..20 {
+        // The table desc might not be delivered to the node yet.
+        db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+        sleep(Duration::from_millis(1500)).await;
+        if db.get(table.id, b"key".to_vec()).await.unwrap().is_none() {
+            expired = true;
+            break;
+        }
+    }
+    assert!(expired, "the values should be expired by the table TTL");
+
+    // The shorter TTL of the write wins.
+    let put = WriteBuilder::new(b"key".to_vec()).with_ttl(Duration::from_millis(100));
+    let mut txn = db.begin_txn();
+    txn.put(table.id, put.ensure_put(b"value".to_vec()));
+    txn.commit().await.unwrap();
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), None);
+
+    let put = WriteBuilder::new(b"key".to_vec()).with_ttl(Duration::from_secs(3600));
+    let mut txn = db.begin_txn();
+    txn.put(table.id, put.ensure_put(b"value".to_vec()));
+    txn.commit().await.unwrap();
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), None);
+}
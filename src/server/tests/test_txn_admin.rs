@@ -0,0 +1,156 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use sekas_api::server::v1::group_request_union::Request;
+use sekas_api::server::v1::*;
+use sekas_client::{SekasClient, TxnStateTable, WriteBuilder};
+use sekas_rock::fn_name;
+use sekas_runtime::JoinHandle;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn shard_intents(group_id: u64, shard_id: u64) -> Option<f64> {
+    let families = prometheus::gather();
+    let family = families.iter().find(|f| f.get_name() == "replica_shard_intents")?;
+    let (group_id, shard_id) = (group_id.to_string(), shard_id.to_string());
+    family
+        .get_metric()
+        .iter()
+        .find(|m| {
+            let labels = m.get_label();
+            labels.iter().any(|l| l.get_name() == "group" && l.get_value() == group_id)
+                && labels.iter().any(|l| l.get_name() == "shard" && l.get_value() == shard_id)
+        })
+        .map(|m| m.get_gauge().get_value())
+}
+
+/// Begin a txn and write an intent of it, the txn is kept alive by heartbeats
+/// until the returned handle is dropped.
+async fn park_txn(
+    c: &ClusterClient,
+    app: &SekasClient,
+    shard_id: u64,
+    group_id: u64,
+    key: &[u8],
+) -> (u64, JoinHandle<()>) {
+    let ts_table = TxnStateTable::new(app.clone(), Some(Duration::from_secs(5)));
+    let start_version = c.root_client().alloc_txn_id(1, None).await.unwrap();
+    ts_table.begin_txn(start_version).await.unwrap();
+    let write = WriteBuilder::new(key.to_vec()).ensure_put(b"value".to_vec());
+    let req = Request::WriteIntent(WriteIntentRequest {
+        shard_id,
+        start_version,
+        write: Some(write_intent_request::Write::Put(write)),
+        ..Default::default()
+    });
+    c.group(group_id).request(&req).await.unwrap();
+    let heartbeat = sekas_runtime::spawn(async move {
+        loop {
+            sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+            ts_table.heartbeat(start_version).await.unwrap();
+        }
+    });
+    (start_version, heartbeat)
+}
+
+#[sekas_macro::test]
+async fn show_and_kill_txns() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.disable_all_balance();
+    ctx.set_resolve_intent_age_ms(1000);
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let shard_id = c.get_shard_desc(table.id, b"key").await.unwrap().id;
+    let root_client = c.root_client();
+
+    // 1. Park two txns, both of them are listed with their intents.
+    let (killed_txn, killed_heartbeat) = park_txn(&c, &app, shard_id, group_id, b"key-1").await;
+    let (alive_txn, _alive_heartbeat) = park_txn(&c, &app, shard_id, group_id, b"key-2").await;
+    let mut listed = false;
+    for _ in 0..600 {
+        let resp = root_client.list_active_txns(0).await.unwrap();
+        assert!(resp.warnings.is_empty(), "{:?}", resp.warnings);
+        let num_intents = |txn_id: u64| {
+            resp.txns.iter().find(|txn| txn.txn_id == txn_id).map(|txn| txn.num_intents)
+        };
+        if num_intents(killed_txn) == Some(1) && num_intents(alive_txn) == Some(1) {
+            assert!(resp.txns.iter().all(|txn| txn.state == TxnState::Running as i32));
+            listed = true;
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(listed, "txn {killed_txn} and {alive_txn} should be listed");
+
+    let resp = root_client.list_active_txns(1).await.unwrap();
+    assert_eq!(resp.txns.len(), 1);
+    let resp = root_client.handle_statement("SHOW txns LIMIT 5").await.unwrap();
+    let output = String::from_utf8(resp).unwrap();
+    assert!(output.contains(&killed_txn.to_string()), "{output}");
+    assert!(output.contains(&alive_txn.to_string()), "{output}");
+
+    // 2. Kill a txn, its intent is resolved but the other is left untouched.
+    drop(killed_heartbeat);
+    let resp = root_client.handle_statement(&format!("KILL TXN {killed_txn}")).await.unwrap();
+    let output = String::from_utf8(resp).unwrap();
+    assert!(output.contains("killed"), "{output}");
+    let mut cleared = false;
+    for _ in 0..600 {
+        if shard_intents(group_id, shard_id) == Some(1.0) {
+            cleared = true;
+            break;
+        }
+        sekas_runtime::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(cleared, "the intent of txn {killed_txn} should be cleared");
+
+    let ts_table = TxnStateTable::new(app.clone(), Some(Duration::from_secs(5)));
+    let record = ts_table.get_txn_record(killed_txn).await.unwrap().unwrap();
+    assert_eq!(record.state, TxnState::Aborted);
+    let record = ts_table.get_txn_record(alive_txn).await.unwrap().unwrap();
+    assert_eq!(record.state, TxnState::Running);
+    let resp = root_client.list_active_txns(0).await.unwrap();
+    assert!(resp.txns.iter().all(|txn| txn.txn_id != killed_txn), "{:?}", resp.txns);
+    assert!(resp.txns.iter().any(|txn| txn.txn_id == alive_txn), "{:?}", resp.txns);
+
+    // The kill is recorded into the topology event log.
+    let events = root_client.list_topology_events().await.unwrap();
+    assert!(
+        events.iter().any(|e| e.kind == topology_event::Kind::KillTxn as i32
+            && e.target == format!("txn/{killed_txn}")),
+        "{events:?}"
+    );
+
+    // Kill an unknown txn.
+    let resp = root_client.handle_statement("KILL TXN 1").await.unwrap();
+    let output = String::from_utf8(resp).unwrap();
+    assert!(output.contains("not exists"), "{output}");
+}
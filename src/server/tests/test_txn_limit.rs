@@ -0,0 +1,121 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_client::{AppError, CommitPhase, TxnOptions, TxnOverflow, WriteBuilder};
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn key(i: usize) -> Vec<u8> {
+    format!("key-{i}").into_bytes()
+}
+
+#[sekas_macro::test]
+async fn txn_too_large_by_node_capabilities() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.mut_txn_config().max_write_count = 4;
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let mut txn = db.begin_txn();
+    for i in 0..5 {
+        txn.put(table.id, WriteBuilder::new(key(i)).ensure_put(b"value".to_vec()));
+    }
+    assert_eq!(txn.staged_write_count(), 5);
+    assert!(txn.staged_write_bytes() > 0);
+    match txn.commit().await {
+        Err(AppError::TxnTooLarge { write_count: 5, max_write_count: 4, .. }) => {}
+        others => panic!("expect txn too large, but got {others:?}"),
+    }
+    for i in 0..5 {
+        assert_eq!(db.get(table.id, key(i)).await.unwrap(), None);
+    }
+
+    // The txn within the limits is committed as usual.
+    let mut txn = db.begin_txn();
+    for i in 0..4 {
+        txn.put(table.id, WriteBuilder::new(key(i)).ensure_put(b"value".to_vec()));
+    }
+    let resp = txn.commit().await.unwrap();
+    assert_eq!(resp.stats.phase, CommitPhase::Committed);
+}
+
+#[sekas_macro::test]
+async fn txn_auto_chunk_commit() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let options = TxnOptions {
+        max_write_count: Some(2),
+        on_overflow: TxnOverflow::AutoChunk,
+        ..Default::default()
+    };
+    let mut txn = db.begin_txn();
+    txn.set_options(options.clone());
+    for i in 0..5 {
+        txn.put(table.id, WriteBuilder::new(key(i)).ensure_put(b"value".to_vec()));
+    }
+    let resp = txn.commit().await.unwrap();
+    assert_eq!(resp.stats.phase, CommitPhase::Chunked { num_chunks: 3 });
+    assert_eq!(resp.puts.len(), 5);
+    for i in 0..5 {
+        assert_eq!(db.get(table.id, key(i)).await.unwrap(), Some(b"value".to_vec()));
+    }
+
+    // The second chunk is failed by the condition of its last write, the writes
+    // of the first chunk are committed, and the rest are not.
+    let mut txn = db.begin_txn();
+    txn.set_options(options);
+    for i in 10..15 {
+        let builder = WriteBuilder::new(key(i));
+        let builder = if i == 13 { builder.expect_value(b"other".to_vec()) } else { builder };
+        txn.put(table.id, builder.ensure_put(b"value".to_vec()));
+    }
+    match txn.commit().await {
+        Err(AppError::TxnChunkFailed {
+            failed_chunk: 1,
+            committed_writes: 2,
+            committed_version,
+            source,
+        }) => {
+            assert_ne!(committed_version, 0);
+            assert!(matches!(*source, AppError::WriteBatch(_)), "{source:?}");
+        }
+        others => panic!("expect txn chunk failed, but got {others:?}"),
+    }
+    for i in 10..12 {
+        assert_eq!(db.get(table.id, key(i)).await.unwrap(), Some(b"value".to_vec()));
+    }
+    for i in 12..15 {
+        assert_eq!(db.get(table.id, key(i)).await.unwrap(), None);
+    }
+}
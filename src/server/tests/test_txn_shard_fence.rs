@@ -0,0 +1,97 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use sekas_client::{Database, WriteBuilder};
+use sekas_rock::fn_name;
+use sekas_schema::system::table::txn_table_id;
+use sekas_schema::FIRST_TXN_SHARD_ID;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+const NUM_WRITERS: u64 = 4;
+const NUM_TXNS: u64 = 32;
+
+fn key(writer: u64, index: u64) -> Vec<u8> {
+    format!("writer-{writer}-key-{index}").into_bytes()
+}
+
+async fn write_txns(db: Database, table_id: u64, writer: u64) {
+    for i in 0..NUM_TXNS {
+        let mut txn = db.begin_txn();
+        txn.put(table_id, WriteBuilder::new(key(writer, i)).ensure_put(i.to_le_bytes().to_vec()));
+        txn.put(table_id, WriteBuilder::new(key(writer, NUM_TXNS)).ensure_put(vec![]));
+        txn.commit().await.unwrap();
+    }
+}
+
+fn is_txn_shard_fenced<T>(result: &sekas_client::Result<T>) -> bool {
+    use sekas_client::Error;
+
+    matches!(result, Err(Error::TxnShardFenced(shard_id)) if *shard_id == FIRST_TXN_SHARD_ID)
+}
+
+/// The split, merge and move requests targeting the txn shard are rejected,
+/// and the concurrent txns are not affected.
+#[sekas_macro::test]
+async fn txn_shard_fenced_under_txn_load() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(3).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    c.assert_table_ready(table.id).await;
+
+    let writers = (0..NUM_WRITERS)
+        .map(|writer| sekas_runtime::spawn(write_txns(db.clone(), table.id, writer)))
+        .collect::<Vec<_>>();
+
+    let txn_group = c.router().find_group_by_shard(FIRST_TXN_SHARD_ID).unwrap();
+    let txn_shard = c.get_shard_desc(txn_table_id(), b"").await.unwrap();
+    assert_eq!(txn_shard.id, FIRST_TXN_SHARD_ID);
+    let user_group = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    for _ in 0..4 {
+        let result = c.group(txn_group.id).split_shard(txn_shard.id, txn_shard.id + 1, None).await;
+        assert!(is_txn_shard_fenced(&result), "split txn shard: {result:?}");
+        let result = c.group(txn_group.id).merge_shard(txn_shard.id, txn_shard.id + 1).await;
+        assert!(is_txn_shard_fenced(&result), "merge txn shard: {result:?}");
+        let result =
+            c.group(user_group).accept_shard(txn_group.id, txn_group.epoch, &txn_shard).await;
+        assert!(is_txn_shard_fenced(&result), "move txn shard: {result:?}");
+    }
+
+    for writer in writers {
+        writer.await.unwrap();
+    }
+    for writer in 0..NUM_WRITERS {
+        for i in 0..NUM_TXNS {
+            let value = db.get(table.id, key(writer, i)).await.unwrap();
+            assert_eq!(value, Some(i.to_le_bytes().to_vec()));
+        }
+    }
+
+    // The txn shard is kept as it is.
+    let group = c.router().find_group_by_shard(FIRST_TXN_SHARD_ID).unwrap();
+    assert_eq!(group.id, txn_group.id);
+    assert_eq!(c.get_shard_desc(txn_table_id(), b"").await, Some(txn_shard));
+}
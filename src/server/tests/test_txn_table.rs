@@ -38,6 +38,7 @@ async fn txn_table_begin_txn_idempotent() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
 
@@ -64,6 +65,7 @@ async fn txn_table_commit_txn_idempotent() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
 
@@ -96,6 +98,7 @@ async fn txn_table_abort_txn_idempotent() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
 
@@ -127,6 +130,7 @@ async fn txn_table_normal_case() {
     let opts = ClientOptions {
         connect_timeout: Some(Duration::from_millis(50)),
         timeout: Some(Duration::from_millis(200)),
+        ..Default::default()
     };
     let client = c.app_client_with_options(opts).await;
 
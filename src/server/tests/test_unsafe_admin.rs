@@ -0,0 +1,173 @@
+// Copyright 2024-present The Sekas Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use prost::Message;
+use sekas_api::server::v1::*;
+use sekas_rock::fn_name;
+
+use crate::helper::client::*;
+use crate::helper::context::*;
+use crate::helper::init::setup_panic_hook;
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+#[sekas_macro::test]
+async fn unsafe_admin_disabled_by_default() {
+    let mut ctx = TestContext::new(fn_name!());
+    let nodes = ctx.bootstrap_servers(1).await;
+    let c = ClusterClient::new(nodes).await;
+    let root_client = c.root_client();
+
+    let resp = root_client.get_raw_node_desc(0).await.unwrap();
+    assert!(resp.raw.is_empty() && resp.desc.is_none());
+    let resp = root_client.get_raw_node_desc(1).await.unwrap();
+    let desc = resp.desc.unwrap();
+    assert_eq!(NodeDesc::decode(resp.raw.as_slice()).unwrap(), desc);
+    assert!(resp.confirm_token.is_empty());
+
+    let result = root_client.put_raw_node_desc(desc, resp.raw, "node/1".to_owned()).await;
+    assert!(matches!(result, Err(sekas_client::Error::PermissionDenied(_))), "{result:?}");
+    assert!(root_client.list_topology_events().await.unwrap().is_empty());
+}
+
+/// Allocate a replica of the group without creating it, it becomes a ghost
+/// replica once it is injected into the group descriptor.
+async fn alloc_ghost_replica(c: &ClusterClient, group_id: u64, epoch: u64) -> ReplicaDesc {
+    let root_client = c.root_client();
+    for _ in 0..100 {
+        let req = AllocReplicaRequest {
+            group_id,
+            epoch,
+            num_required: 1,
+            purpose: alloc_replica_request::Purpose::Promote as i32,
+            ..Default::default()
+        };
+        match root_client.alloc_replica(req).await {
+            Ok(resp) if !resp.replicas.is_empty() => return resp.replicas[0].clone(),
+            _ => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+    panic!("alloc ghost replica of group {group_id} timeout");
+}
+
+#[sekas_macro::test]
+async fn unsafe_admin_inject_ghost_replica() {
+    let mut ctx = TestContext::new(fn_name!());
+    ctx.enable_unsafe_admin();
+    ctx.disable_all_balance();
+    ctx.disable_all_node_scheduler();
+    let nodes = ctx.bootstrap_servers(2).await;
+    let c = ClusterClient::new(nodes).await;
+    let app = c.app_client().await;
+    let db = app.create_database("db".into()).await.unwrap();
+    let table = db.create_table("table".into()).await.unwrap();
+    db.put(table.id, b"key".to_vec(), b"value".to_vec()).await.unwrap();
+    let group_id = c.find_router_group_state_by_key(table.id, b"key").await.unwrap().id;
+    let target = format!("group/{group_id}");
+
+    let root_client = c.root_client();
+    let resp = root_client.get_raw_group_desc(group_id).await.unwrap();
+    let desc = resp.desc.unwrap();
+    assert_eq!(GroupDesc::decode(resp.raw.as_slice()).unwrap(), desc);
+    assert!(!resp.confirm_token.is_empty());
+    let ghost_replica = alloc_ghost_replica(&c, group_id, desc.epoch).await;
+    let mut ghost = desc.clone();
+    ghost.replicas.push(ghost_replica.clone());
+
+    // The guards of the unsafe put, the token is consumed by each put.
+    let result = root_client.put_raw_group_desc(ghost.clone(), desc.epoch, target.clone()).await;
+    assert!(matches!(result, Err(sekas_client::Error::PermissionDenied(_))), "{result:?}");
+    let token = root_client.get_raw_group_desc(group_id).await.unwrap().confirm_token;
+    let result = root_client.put_raw_group_desc(ghost.clone(), desc.epoch + 1, token.clone()).await;
+    assert!(matches!(result, Err(sekas_client::Error::InvalidArgument(_))), "{result:?}");
+    let result = root_client.put_raw_group_desc(ghost.clone(), desc.epoch, token).await;
+    assert!(matches!(result, Err(sekas_client::Error::PermissionDenied(_))), "{result:?}");
+    let mut lower = ghost.clone();
+    lower.epoch -= 1;
+    let token = root_client.get_raw_group_desc(group_id).await.unwrap().confirm_token;
+    let result = root_client.put_raw_group_desc(lower, desc.epoch, token).await;
+    assert!(matches!(result, Err(sekas_client::Error::InvalidArgument(_))), "{result:?}");
+    let staled = root_client.get_raw_group_desc(group_id).await.unwrap().confirm_token;
+    let token = root_client.get_raw_group_desc(group_id).await.unwrap().confirm_token;
+    let result = root_client.put_raw_group_desc(ghost.clone(), desc.epoch, staled).await;
+    assert!(matches!(result, Err(sekas_client::Error::PermissionDenied(_))), "{result:?}");
+
+    let shard = desc.shards.iter().find(|s| s.table_id == table.id).unwrap().clone();
+    let resp = root_client.get_raw_shard_desc(group_id, shard.id).await.unwrap();
+    assert_eq!(resp.desc, Some(shard.clone()));
+    assert_eq!(resp.group_epoch, desc.epoch);
+    let result =
+        root_client.put_raw_shard_desc(group_id, shard, desc.epoch + 1, resp.confirm_token).await;
+    assert!(matches!(result, Err(sekas_client::Error::InvalidArgument(_))), "{result:?}");
+
+    // Inject a ghost replica, the scheduler removes it from the group.
+    root_client.put_raw_group_desc(ghost, desc.epoch, token).await.unwrap();
+    let mut removed = None;
+    for _ in 0..100 {
+        let resp = root_client.get_raw_group_desc(group_id).await.unwrap();
+        let current = resp.desc.unwrap();
+        if current.epoch > desc.epoch && current.replicas.iter().all(|r| r.id != ghost_replica.id) {
+            removed = Some(current);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let removed = removed.expect("the ghost replica is not removed");
+    assert_eq!(removed.replicas, desc.replicas);
+
+    let mut events = Vec::default();
+    for _ in 0..100 {
+        events = root_client
+            .list_topology_events()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| (topology_event::Kind::from_i32(e.kind).unwrap(), e.target))
+            .collect::<Vec<_>>();
+        if events.len() >= 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        events,
+        vec![
+            (topology_event::Kind::UnsafeEdit, target.clone()),
+            (topology_event::Kind::Repair, target.clone())
+        ]
+    );
+
+    // The node descriptor is guarded by the stored bytes.
+    let resp = root_client.get_raw_node_desc(1).await.unwrap();
+    let mut node = resp.desc.unwrap();
+    node.labels.push("zone=z1".to_owned());
+    let result =
+        root_client.put_raw_node_desc(node.clone(), b"staled".to_vec(), resp.confirm_token).await;
+    assert!(matches!(result, Err(sekas_client::Error::InvalidArgument(_))), "{result:?}");
+    let resp = root_client.get_raw_node_desc(1).await.unwrap();
+    root_client.put_raw_node_desc(node.clone(), resp.raw, resp.confirm_token).await.unwrap();
+    assert_eq!(root_client.get_raw_node_desc(1).await.unwrap().desc, Some(node));
+    let events = root_client.list_topology_events().await.unwrap();
+    assert_eq!(events.last().unwrap().target, "node/1");
+
+    // The data is still accessible.
+    assert_eq!(db.get(table.id, b"key".to_vec()).await.unwrap(), Some(b"value".to_vec()));
+}